@@ -0,0 +1,338 @@
+//! CDD XML deserialization model.
+//!
+//! Serde-deserializable types for the subset of the CANdelaStudio document
+//! format the importer maps: data types, DIDs, DTCs, state groups and the
+//! ECU/variant tree with its diagnostic instances. Elements outside that
+//! subset are skipped.
+//!
+//! Children that CDD interleaves (e.g. `DIAGCLASS` and `DIAGINST` inside an
+//! `ECU`) are collected with the `$value` + enum pattern, which keeps their
+//! document order.
+
+use serde::Deserialize;
+
+// --- Root ---
+
+#[derive(Debug, Deserialize)]
+#[serde(rename = "CANDELA")]
+pub struct Candela {
+    #[serde(rename = "@dtdvers")]
+    pub dtd_version: Option<String>,
+    #[serde(rename = "ECUDOC")]
+    pub ecudoc: Option<EcuDoc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EcuDoc {
+    #[serde(rename = "$value", default)]
+    pub children: Vec<EcuDocChild>,
+}
+
+#[derive(Debug, Deserialize)]
+pub enum EcuDocChild {
+    #[serde(rename = "DATATYPES")]
+    DataTypes(DataTypes),
+    #[serde(rename = "DIDS")]
+    Dids(Dids),
+    #[serde(rename = "DTCS")]
+    Dtcs(Dtcs),
+    #[serde(rename = "STATEGROUP")]
+    StateGroup(StateGroup),
+    #[serde(rename = "ECU")]
+    Ecu(Layer),
+    #[serde(other)]
+    Other,
+}
+
+// --- Texts ---
+
+/// A multilingual text: one `TUV` per language.
+#[derive(Debug, Default, Deserialize)]
+pub struct Text {
+    #[serde(rename = "TUV", default)]
+    pub tuvs: Vec<Tuv>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Tuv {
+    #[serde(rename = "@lang")]
+    pub lang: Option<String>,
+    #[serde(rename = "$text", default)]
+    pub value: String,
+}
+
+impl Text {
+    /// The English text, or the first one when there is none.
+    pub fn value(&self) -> Option<&str> {
+        self.tuvs
+            .iter()
+            .find(|t| t.lang.as_deref() == Some("en-US"))
+            .or_else(|| self.tuvs.first())
+            .map(|t| t.value.as_str())
+            .filter(|v| !v.is_empty())
+    }
+}
+
+// --- Data types ---
+
+#[derive(Debug, Deserialize)]
+pub struct DataTypes {
+    #[serde(rename = "$value", default)]
+    pub children: Vec<DataTypeChild>,
+}
+
+#[derive(Debug, Deserialize)]
+pub enum DataTypeChild {
+    /// Raw value, no conversion.
+    #[serde(rename = "IDENT")]
+    Ident(DataType),
+    /// Linear conversion `phys = f * raw + o`.
+    #[serde(rename = "LINCOMP")]
+    LinComp(DataType),
+    /// Raw value ranges mapped to texts.
+    #[serde(rename = "TEXTTBL")]
+    TextTbl(DataType),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DataType {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "QUAL")]
+    pub qual: Option<String>,
+    #[serde(rename = "NAME")]
+    pub name: Option<Text>,
+    #[serde(rename = "CVALUETYPE")]
+    pub coded: Option<CodedValueType>,
+    #[serde(rename = "PVALUETYPE")]
+    pub physical: Option<PhysicalValueType>,
+    #[serde(rename = "COMP")]
+    pub comp: Option<Comp>,
+    #[serde(rename = "TEXTMAP", default)]
+    pub text_maps: Vec<TextMap>,
+}
+
+/// Coded (on the wire) representation of a data type.
+#[derive(Debug, Deserialize)]
+pub struct CodedValueType {
+    /// Bit length.
+    #[serde(rename = "@bl")]
+    pub bit_length: Option<String>,
+    /// Byte order: `21` is Motorola (high-low), `12` Intel (low-high).
+    #[serde(rename = "@bo")]
+    pub byte_order: Option<String>,
+    /// Encoding: `uns`, `sgn`, `flt`, `asc`, `utf8` or `bytes`.
+    #[serde(rename = "@enc")]
+    pub encoding: Option<String>,
+    /// Minimum and maximum size in units of `bl` for variable-length data.
+    #[serde(rename = "@minsz")]
+    pub min_size: Option<String>,
+    #[serde(rename = "@maxsz")]
+    pub max_size: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PhysicalValueType {
+    #[serde(rename = "@unit")]
+    pub unit: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Comp {
+    /// Factor.
+    #[serde(rename = "@f")]
+    pub factor: Option<String>,
+    /// Offset.
+    #[serde(rename = "@o")]
+    pub offset: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TextMap {
+    /// First raw value of the range.
+    #[serde(rename = "@s")]
+    pub start: String,
+    /// Last raw value of the range.
+    #[serde(rename = "@e")]
+    pub end: String,
+    #[serde(rename = "TEXT")]
+    pub text: Option<Text>,
+}
+
+// --- DIDs ---
+
+#[derive(Debug, Deserialize)]
+pub struct Dids {
+    #[serde(rename = "DID", default)]
+    pub items: Vec<Did>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Did {
+    /// Data identifier.
+    #[serde(rename = "@n")]
+    pub number: String,
+    #[serde(rename = "QUAL")]
+    pub qual: Option<String>,
+    #[serde(rename = "NAME")]
+    pub name: Option<Text>,
+    #[serde(rename = "STRUCTURE")]
+    pub structure: Option<Structure>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Structure {
+    #[serde(rename = "DATAOBJ", default)]
+    pub data_objects: Vec<DataObj>,
+}
+
+/// A data item of a DID or service, typed by a data type id.
+#[derive(Debug, Deserialize)]
+pub struct DataObj {
+    #[serde(rename = "@dtref")]
+    pub dtref: String,
+    #[serde(rename = "QUAL")]
+    pub qual: Option<String>,
+}
+
+// --- DTCs ---
+
+#[derive(Debug, Deserialize)]
+pub struct Dtcs {
+    #[serde(rename = "DTC", default)]
+    pub items: Vec<Dtc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Dtc {
+    /// Trouble code.
+    #[serde(rename = "@n")]
+    pub number: String,
+    #[serde(rename = "QUAL")]
+    pub qual: Option<String>,
+    #[serde(rename = "NAME")]
+    pub name: Option<Text>,
+}
+
+// --- State groups ---
+
+#[derive(Debug, Deserialize)]
+pub struct StateGroup {
+    #[serde(rename = "QUAL")]
+    pub qual: Option<String>,
+    #[serde(rename = "STATE", default)]
+    pub states: Vec<State>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct State {
+    #[serde(rename = "QUAL")]
+    pub qual: Option<String>,
+    #[serde(rename = "NAME")]
+    pub name: Option<Text>,
+}
+
+// --- ECU and variants ---
+
+/// An `ECU`, `VAR` or `DIAGCLASS`: a named container of diagnostic
+/// instances and further containers.
+#[derive(Debug, Deserialize)]
+pub struct Layer {
+    #[serde(rename = "$value", default)]
+    pub children: Vec<LayerChild>,
+}
+
+#[derive(Debug, Deserialize)]
+pub enum LayerChild {
+    #[serde(rename = "QUAL")]
+    Qual(String),
+    #[serde(rename = "NAME")]
+    Name(Text),
+    #[serde(rename = "DIAGCLASS")]
+    DiagClass(Layer),
+    #[serde(rename = "DIAGINST")]
+    DiagInst(DiagInst),
+    #[serde(rename = "VAR")]
+    Var(Layer),
+    #[serde(other)]
+    Other,
+}
+
+impl Layer {
+    pub fn qual(&self) -> Option<&str> {
+        self.children.iter().find_map(|c| match c {
+            LayerChild::Qual(q) => Some(q.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn name(&self) -> Option<&Text> {
+        self.children.iter().find_map(|c| match c {
+            LayerChild::Name(n) => Some(n),
+            _ => None,
+        })
+    }
+
+    /// The diagnostic instances of this layer, including those grouped in
+    /// (nested) diagnostic classes, in document order.
+    pub fn diag_insts(&self) -> Vec<&DiagInst> {
+        let mut out = Vec::new();
+        for child in &self.children {
+            match child {
+                LayerChild::DiagInst(inst) => out.push(inst),
+                LayerChild::DiagClass(class) => out.extend(class.diag_insts()),
+                _ => {}
+            }
+        }
+        out
+    }
+
+    pub fn variants(&self) -> impl Iterator<Item = &Layer> {
+        self.children.iter().filter_map(|c| match c {
+            LayerChild::Var(var) => Some(var),
+            _ => None,
+        })
+    }
+}
+
+/// A diagnostic service instance with its request and positive response.
+#[derive(Debug, Deserialize)]
+pub struct DiagInst {
+    #[serde(rename = "QUAL")]
+    pub qual: Option<String>,
+    #[serde(rename = "NAME")]
+    pub name: Option<Text>,
+    #[serde(rename = "REQ")]
+    pub request: Option<Message>,
+    #[serde(rename = "POS")]
+    pub pos_response: Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Message {
+    #[serde(rename = "$value", default)]
+    pub children: Vec<MessageChild>,
+}
+
+#[derive(Debug, Deserialize)]
+pub enum MessageChild {
+    /// A constant, e.g. the service id.
+    #[serde(rename = "CONSTCOMP")]
+    Const(ConstComp),
+    #[serde(rename = "DATAOBJ")]
+    Data(DataObj),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConstComp {
+    #[serde(rename = "@bl")]
+    pub bit_length: String,
+    #[serde(rename = "@v")]
+    pub value: String,
+    #[serde(rename = "QUAL")]
+    pub qual: Option<String>,
+}
@@ -0,0 +1,9 @@
+//! CANdelaStudio (CDD) import.
+//!
+//! CDD is read-only: it is parsed into the IR and converted to the other
+//! formats from there. See [`cdd_model`] for the subset of CDD that is mapped.
+
+pub mod cdd_model;
+pub mod parser;
+
+pub use parser::{CddParseError, parse_cdd};
@@ -0,0 +1,665 @@
+//! CDD parser: XML string -> DiagDatabase IR.
+//!
+//! The ECU becomes the base variant and every `VAR` a variant inheriting
+//! from it. DIDs become ReadDataByIdentifier services on the base variant,
+//! laid out like the ones the YAML parser generates, so DIDs imported from
+//! CDD export to YAML as `dids:` entries. State groups become state charts
+//! of the base variant; DTCs go to the database.
+
+use std::collections::HashMap;
+
+use diag_ir::*;
+use thiserror::Error;
+
+use crate::cdd_model::{self, Candela, DataTypeChild, EcuDocChild, MessageChild};
+
+#[derive(Debug, Error)]
+pub enum CddParseError {
+    #[error("XML deserialization failed: {0}")]
+    XmlError(#[from] quick_xml::DeError),
+    #[error("Missing required element: {0}")]
+    MissingElement(String),
+    #[error("Invalid value '{value}' for {attribute} in {element}")]
+    InvalidAttribute {
+        element: String,
+        attribute: String,
+        value: String,
+    },
+    #[error("Unknown data type '{0}'")]
+    UnknownDataType(String),
+}
+
+const READ_DATA_BY_IDENTIFIER: u32 = 0x22;
+
+/// Parse a CDD XML string into a DiagDatabase IR.
+pub fn parse_cdd(xml: &str) -> Result<DiagDatabase, CddParseError> {
+    let cdd: Candela = quick_xml::de::from_str(xml)?;
+    Interner::default().enter(|| cdd_to_ir(cdd))
+}
+
+fn cdd_to_ir(cdd: Candela) -> Result<DiagDatabase, CddParseError> {
+    let ecudoc = cdd
+        .ecudoc
+        .ok_or_else(|| CddParseError::MissingElement("ECUDOC".into()))?;
+
+    let mut data_types = HashMap::new();
+    let mut dids = Vec::new();
+    let mut dtcs = Vec::new();
+    let mut state_groups = Vec::new();
+    let mut ecu = None;
+    for child in &ecudoc.children {
+        match child {
+            EcuDocChild::DataTypes(types) => {
+                for dt in &types.children {
+                    let (kind, dt) = match dt {
+                        DataTypeChild::Ident(dt) => (CompuKind::Identical, dt),
+                        DataTypeChild::LinComp(dt) => (CompuKind::Linear, dt),
+                        DataTypeChild::TextTbl(dt) => (CompuKind::TextTable, dt),
+                        DataTypeChild::Other => continue,
+                    };
+                    data_types.insert(dt.id.as_str(), (kind, dt));
+                }
+            }
+            EcuDocChild::Dids(d) => dids.extend(&d.items),
+            EcuDocChild::Dtcs(d) => dtcs.extend(&d.items),
+            EcuDocChild::StateGroup(group) => state_groups.push(group),
+            EcuDocChild::Ecu(layer) => ecu = Some(layer),
+            EcuDocChild::Other => {}
+        }
+    }
+    let ecu = ecu.ok_or_else(|| CddParseError::MissingElement("ECU".into()))?;
+    let ecu_name = ecu
+        .qual()
+        .ok_or_else(|| CddParseError::MissingElement("ECU/QUAL".into()))?
+        .to_string();
+    let types = DataTypes(data_types);
+
+    let mut services = Vec::new();
+    for did in dids {
+        services.push(did_to_read_service(did, &types)?);
+    }
+    for inst in ecu.diag_insts() {
+        services.push(diag_inst_to_service(inst, &types)?);
+    }
+    let base = Variant {
+        diag_layer: DiagLayer {
+            short_name: ecu_name.as_str().into(),
+            long_name: ecu.name().and_then(long_name),
+            diag_services: services,
+            state_charts: state_groups.into_iter().map(state_chart).collect(),
+            ..Default::default()
+        },
+        is_base_variant: true,
+        variant_patterns: vec![],
+        parent_refs: vec![],
+    };
+
+    let mut variants = vec![base];
+    for var in ecu.variants() {
+        variants.push(variant(var, &ecu_name, &types)?);
+    }
+
+    Ok(DiagDatabase {
+        ecu_name,
+        variants,
+        dtcs: dtcs.into_iter().map(dtc).collect::<Result<_, _>>()?,
+        ..Default::default()
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CompuKind {
+    Identical,
+    Linear,
+    TextTable,
+}
+
+/// Data types by id.
+struct DataTypes<'a>(HashMap<&'a str, (CompuKind, &'a cdd_model::DataType)>);
+
+impl DataTypes<'_> {
+    /// The DOP of the data type `dtref`, named `name`.
+    fn dop(&self, dtref: &str, name: &str) -> Result<Dop, CddParseError> {
+        let (kind, dt) = self
+            .0
+            .get(dtref)
+            .ok_or_else(|| CddParseError::UnknownDataType(dtref.into()))?;
+        data_type_to_dop(*kind, dt, name)
+    }
+}
+
+fn data_type_to_dop(
+    kind: CompuKind,
+    dt: &cdd_model::DataType,
+    name: &str,
+) -> Result<Dop, CddParseError> {
+    let coded = dt
+        .coded
+        .as_ref()
+        .ok_or_else(|| CddParseError::MissingElement(format!("CVALUETYPE of '{}'", dt.id)))?;
+    let attr = |attribute: &str, value: Option<&String>| -> Result<Option<u32>, CddParseError> {
+        value
+            .map(|v| {
+                parse_number(v).ok_or_else(|| CddParseError::InvalidAttribute {
+                    element: format!("CVALUETYPE of '{}'", dt.id),
+                    attribute: attribute.into(),
+                    value: v.clone(),
+                })
+            })
+            .transpose()
+    };
+    let bit_length = attr("bl", coded.bit_length.as_ref())?.unwrap_or(8);
+    let min_size = attr("minsz", coded.min_size.as_ref())?;
+    let max_size = attr("maxsz", coded.max_size.as_ref())?;
+
+    let (base_data_type, phys_data_type) = match coded.encoding.as_deref() {
+        Some("sgn") => (DataType::AInt32, PhysicalTypeDataType::AInt32),
+        Some("flt") if bit_length == 64 => (DataType::AFloat64, PhysicalTypeDataType::AFloat64),
+        Some("flt") => (DataType::AFloat32, PhysicalTypeDataType::AFloat32),
+        Some("asc") => (DataType::AAsciiString, PhysicalTypeDataType::AAsciiString),
+        Some("utf8") => (DataType::AUtf8String, PhysicalTypeDataType::AUtf8String),
+        Some("uns") | None => (DataType::AUint32, PhysicalTypeDataType::AUint32),
+        Some(_) => (DataType::ABytefield, PhysicalTypeDataType::ABytefield),
+    };
+    let encoding = if coded.encoding.as_deref() == Some("sgn") {
+        "signed"
+    } else {
+        "unsigned"
+    };
+
+    // Fixed-size strings and byte fields are standard-length; others run to
+    // the end of the PDU.
+    let (type_name, specific_data) = match (min_size, max_size) {
+        (Some(min), Some(max)) if min == max => (
+            DiagCodedTypeName::StandardLengthType,
+            standard_length(bit_length * min),
+        ),
+        (None, None) => (
+            DiagCodedTypeName::StandardLengthType,
+            standard_length(bit_length),
+        ),
+        (min, max) => (
+            DiagCodedTypeName::MinMaxLengthType,
+            DiagCodedTypeData::MinMax {
+                min_length: min.unwrap_or(0) * bit_length / 8,
+                max_length: max.map(|max| max * bit_length / 8),
+                termination: Termination::EndOfPdu,
+            },
+        ),
+    };
+
+    Ok(Dop {
+        dop_type: DopType::Regular,
+        short_name: name.into(),
+        sdgs: None,
+        specific_data: Some(DopData::NormalDop {
+            compu_method: Some(compu_method(kind, dt)?),
+            diag_coded_type: Some(DiagCodedType {
+                type_name,
+                base_type_encoding: encoding.into(),
+                base_data_type,
+                is_high_low_byte_order: coded.byte_order.as_deref() != Some("12"),
+                specific_data: Some(specific_data),
+            }),
+            physical_type: Some(PhysicalType {
+                precision: None,
+                base_data_type: phys_data_type,
+                display_radix: Radix::Dec,
+            }),
+            internal_constr: None,
+            unit_ref: dt
+                .physical
+                .as_ref()
+                .and_then(|p| p.unit.as_ref())
+                .filter(|u| !u.is_empty())
+                .map(|u| Unit {
+                    short_name: u.as_str().into(),
+                    display_name: u.clone(),
+                    factor_si_to_unit: None,
+                    offset_si_to_unit: None,
+                    physical_dimension: None,
+                }),
+            phys_constr: None,
+        }),
+    })
+}
+
+fn standard_length(bit_length: u32) -> DiagCodedTypeData {
+    DiagCodedTypeData::StandardLength {
+        bit_length,
+        bit_mask: vec![],
+        condensed: false,
+    }
+}
+
+fn compu_method(kind: CompuKind, dt: &cdd_model::DataType) -> Result<CompuMethod, CddParseError> {
+    let float = |attribute: &str, value: Option<&String>, default: f64| {
+        value.map_or(Ok(default), |v| {
+            v.trim()
+                .parse::<f64>()
+                .map_err(|_| CddParseError::InvalidAttribute {
+                    element: format!("COMP of '{}'", dt.id),
+                    attribute: attribute.into(),
+                    value: v.clone(),
+                })
+        })
+    };
+    let internal_to_phys = |compu_scales| {
+        Some(CompuInternalToPhys {
+            compu_scales,
+            prog_code: None,
+            compu_default_value: None,
+        })
+    };
+    Ok(match kind {
+        CompuKind::Identical => CompuMethod {
+            category: CompuCategory::Identical,
+            internal_to_phys: None,
+            phys_to_internal: None,
+        },
+        CompuKind::Linear => {
+            let comp = dt.comp.as_ref();
+            let factor = float("f", comp.and_then(|c| c.factor.as_ref()), 1.0)?;
+            let offset = float("o", comp.and_then(|c| c.offset.as_ref()), 0.0)?;
+            CompuMethod {
+                category: CompuCategory::Linear,
+                internal_to_phys: internal_to_phys(vec![CompuScale {
+                    short_label: None,
+                    lower_limit: None,
+                    upper_limit: None,
+                    inverse_values: None,
+                    consts: None,
+                    rational_co_effs: Some(CompuRationalCoEffs {
+                        numerator: vec![offset, factor],
+                        denominator: vec![1.0],
+                    }),
+                }]),
+                phys_to_internal: None,
+            }
+        }
+        CompuKind::TextTable => {
+            let limit = |value: &str| Limit {
+                value: value.trim().into(),
+                interval_type: IntervalType::Closed,
+            };
+            let scales = dt
+                .text_maps
+                .iter()
+                .map(|map| {
+                    let text = map
+                        .text
+                        .as_ref()
+                        .and_then(cdd_model::Text::value)
+                        .unwrap_or_default()
+                        .to_string();
+                    CompuScale {
+                        short_label: Some(Text {
+                            value: text.clone(),
+                            ti: String::new(),
+                        }),
+                        lower_limit: Some(limit(&map.start)),
+                        upper_limit: Some(limit(&map.end)),
+                        inverse_values: None,
+                        consts: Some(CompuValues {
+                            v: None,
+                            vt: text,
+                            vt_ti: String::new(),
+                        }),
+                        rational_co_effs: None,
+                    }
+                })
+                .collect();
+            CompuMethod {
+                category: CompuCategory::TextTable,
+                internal_to_phys: internal_to_phys(scales),
+                phys_to_internal: None,
+            }
+        }
+    })
+}
+
+/// Byte length of a DOP's coded value, when fixed.
+fn byte_length(dop: &Dop) -> Option<u32> {
+    match &dop.specific_data {
+        Some(DopData::NormalDop {
+            diag_coded_type:
+                Some(DiagCodedType {
+                    specific_data: Some(DiagCodedTypeData::StandardLength { bit_length, .. }),
+                    ..
+                }),
+            ..
+        }) => Some(bit_length.div_ceil(8)),
+        _ => None,
+    }
+}
+
+fn coded_param(id: u32, name: &str, semantic: &str, byte: u32, value: u32, bits: u32) -> Param {
+    Param {
+        id,
+        param_type: ParamType::CodedConst,
+        short_name: name.into(),
+        semantic: semantic.into(),
+        byte_position: Some(byte),
+        bit_position: Some(0),
+        specific_data: Some(ParamData::CodedConst {
+            coded_value: value.to_string(),
+            diag_coded_type: DiagCodedType {
+                type_name: DiagCodedTypeName::StandardLengthType,
+                base_type_encoding: "unsigned".into(),
+                base_data_type: DataType::AUint32,
+                is_high_low_byte_order: true,
+                specific_data: Some(standard_length(bits)),
+            },
+        }),
+        ..Default::default()
+    }
+}
+
+fn value_param(id: u32, name: &str, byte: Option<u32>, dop: Dop) -> Param {
+    Param {
+        id,
+        param_type: ParamType::Value,
+        short_name: name.into(),
+        semantic: "DATA".into(),
+        byte_position: byte,
+        specific_data: Some(ParamData::Value {
+            physical_default_value: String::new(),
+            dop: DopRef::new(dop),
+        }),
+        ..Default::default()
+    }
+}
+
+/// Value params for `data` starting at byte `start`. Params after one of
+/// variable length get no byte position.
+fn data_params(
+    data: &[&cdd_model::DataObj],
+    first_id: u32,
+    start: u32,
+    types: &DataTypes,
+) -> Result<Vec<Param>, CddParseError> {
+    let mut byte = Some(start);
+    let mut params = Vec::new();
+    for (id, obj) in (first_id..).zip(data) {
+        let name = obj.qual.as_deref().unwrap_or(&obj.dtref);
+        let dop = types.dop(&obj.dtref, name)?;
+        let len = byte_length(&dop);
+        params.push(value_param(id, name, byte, dop));
+        byte = byte.zip(len).map(|(b, l)| b + l);
+    }
+    Ok(params)
+}
+
+/// A ReadDataByIdentifier service laid out like the ones YAML generates.
+fn did_to_read_service(
+    did: &cdd_model::Did,
+    types: &DataTypes,
+) -> Result<DiagService, CddParseError> {
+    let name = did
+        .qual
+        .as_deref()
+        .ok_or_else(|| CddParseError::MissingElement(format!("QUAL of DID {}", did.number)))?;
+    let id = parse_number(&did.number).ok_or_else(|| CddParseError::InvalidAttribute {
+        element: format!("DID '{name}'"),
+        attribute: "n".into(),
+        value: did.number.clone(),
+    })?;
+    let data: Vec<_> = did.structure.iter().flat_map(|s| &s.data_objects).collect();
+    let sid = READ_DATA_BY_IDENTIFIER;
+
+    let mut response = vec![
+        coded_param(0, "SID_PR", "SERVICE-ID", 0, sid + 0x40, 8),
+        Param {
+            id: 1,
+            param_type: ParamType::MatchingRequestParam,
+            short_name: "DID_PR".into(),
+            semantic: "DID".into(),
+            byte_position: Some(1),
+            bit_position: Some(0),
+            specific_data: Some(ParamData::MatchingRequestParam {
+                request_byte_pos: 1,
+                byte_length: 2,
+            }),
+            ..Default::default()
+        },
+    ];
+    // A DID with a single data object names its value after the DID.
+    if let [obj] = data.as_slice() {
+        response.push(value_param(2, name, Some(3), types.dop(&obj.dtref, name)?));
+    } else {
+        response.extend(data_params(&data, 2, 3, types)?);
+    }
+
+    Ok(DiagService {
+        diag_comm: DiagComm {
+            short_name: format!("{name}_Read").into(),
+            long_name: did.name.as_ref().and_then(long_name),
+            diag_class_type: DiagClassType::StartComm,
+            is_executable: true,
+            ..Default::default()
+        },
+        request: Some(Request {
+            params: vec![
+                coded_param(0, "SID_RQ", "SERVICE-ID", 0, sid, 8),
+                coded_param(1, "DID_RQ", "DID", 1, id, 16),
+            ],
+            sdgs: None,
+        }),
+        pos_responses: vec![Response {
+            response_type: ResponseType::PosResponse,
+            params: response,
+            sdgs: None,
+            pos_response_suffix: None,
+            audience: None,
+        }],
+        addressing: Addressing::Physical,
+        transmission_mode: TransmissionMode::SendAndReceive,
+        ..Default::default()
+    })
+}
+
+/// Params of a `REQ` or `POS`, in document order.
+fn message_params(
+    message: &cdd_model::Message,
+    service: &str,
+    types: &DataTypes,
+) -> Result<Vec<Param>, CddParseError> {
+    let mut byte = Some(0);
+    let mut params = Vec::new();
+    for child in &message.children {
+        let id = u32::try_from(params.len()).unwrap_or(u32::MAX);
+        let (param, len) = match child {
+            MessageChild::Const(c) => {
+                let number = |attribute: &str, value: &String| {
+                    parse_number(value).ok_or_else(|| CddParseError::InvalidAttribute {
+                        element: format!("CONSTCOMP of '{service}'"),
+                        attribute: attribute.into(),
+                        value: value.clone(),
+                    })
+                };
+                let bits = number("bl", &c.bit_length)?;
+                let value = number("v", &c.value)?;
+                // The first constant is the service id.
+                let (name, semantic) = match (c.qual.as_deref(), id) {
+                    (Some(qual), _) => (qual, ""),
+                    (None, 0) => ("SID", "SERVICE-ID"),
+                    (None, _) => ("CONST", ""),
+                };
+                let mut param = coded_param(id, name, semantic, 0, value, bits);
+                param.byte_position = byte;
+                (param, Some(bits.div_ceil(8)))
+            }
+            MessageChild::Data(obj) => {
+                let name = obj.qual.as_deref().unwrap_or(&obj.dtref);
+                let dop = types.dop(&obj.dtref, name)?;
+                let len = byte_length(&dop);
+                (value_param(id, name, byte, dop), len)
+            }
+            MessageChild::Other => continue,
+        };
+        params.push(param);
+        byte = byte.zip(len).map(|(b, l)| b + l);
+    }
+    Ok(params)
+}
+
+fn diag_inst_to_service(
+    inst: &cdd_model::DiagInst,
+    types: &DataTypes,
+) -> Result<DiagService, CddParseError> {
+    let name = inst
+        .qual
+        .as_deref()
+        .ok_or_else(|| CddParseError::MissingElement("QUAL of DIAGINST".into()))?;
+    let request = inst
+        .request
+        .as_ref()
+        .map(|req| message_params(req, name, types))
+        .transpose()?
+        .map(|params| Request { params, sdgs: None });
+    let pos_responses = inst
+        .pos_response
+        .as_ref()
+        .map(|pos| message_params(pos, name, types))
+        .transpose()?
+        .map(|params| Response {
+            response_type: ResponseType::PosResponse,
+            params,
+            sdgs: None,
+            pos_response_suffix: None,
+            audience: None,
+        })
+        .into_iter()
+        .collect();
+    Ok(DiagService {
+        diag_comm: DiagComm {
+            short_name: name.into(),
+            long_name: inst.name.as_ref().and_then(long_name),
+            diag_class_type: DiagClassType::StartComm,
+            is_executable: true,
+            ..Default::default()
+        },
+        request,
+        pos_responses,
+        addressing: Addressing::Physical,
+        transmission_mode: TransmissionMode::SendAndReceive,
+        ..Default::default()
+    })
+}
+
+fn variant(
+    var: &cdd_model::Layer,
+    base_name: &str,
+    types: &DataTypes,
+) -> Result<Variant, CddParseError> {
+    let name = var
+        .qual()
+        .ok_or_else(|| CddParseError::MissingElement("QUAL of VAR".into()))?;
+    let mut services = Vec::new();
+    for inst in var.diag_insts() {
+        services.push(diag_inst_to_service(inst, types)?);
+    }
+    Ok(Variant {
+        diag_layer: DiagLayer {
+            short_name: name.into(),
+            long_name: var.name().and_then(long_name),
+            diag_services: services,
+            ..Default::default()
+        },
+        is_base_variant: false,
+        variant_patterns: vec![],
+        parent_refs: vec![ParentRef {
+            ref_type: ParentRefType::Variant(Box::new(Variant {
+                diag_layer: DiagLayer {
+                    short_name: base_name.into(),
+                    ..Default::default()
+                },
+                is_base_variant: true,
+                variant_patterns: vec![],
+                parent_refs: vec![],
+            })),
+            not_inherited_diag_comm_short_names: vec![],
+            not_inherited_variables_short_names: vec![],
+            not_inherited_dops_short_names: vec![],
+            not_inherited_tables_short_names: vec![],
+            not_inherited_global_neg_responses_short_names: vec![],
+        }],
+    })
+}
+
+/// A state group as a state chart starting in its first state.
+fn state_chart(group: &cdd_model::StateGroup) -> StateChart {
+    let states: Vec<State> = group
+        .states
+        .iter()
+        .filter_map(|s| {
+            Some(State {
+                short_name: s.qual.as_deref()?.into(),
+                long_name: s.name.as_ref().and_then(long_name),
+            })
+        })
+        .collect();
+    StateChart {
+        short_name: group.qual.as_deref().unwrap_or_default().into(),
+        semantic: Name::default(),
+        state_transitions: vec![],
+        start_state_short_name_ref: states
+            .first()
+            .map(|s| s.short_name.to_string())
+            .unwrap_or_default(),
+        states,
+    }
+}
+
+fn dtc(dtc: &cdd_model::Dtc) -> Result<Dtc, CddParseError> {
+    let trouble_code =
+        parse_number(&dtc.number).ok_or_else(|| CddParseError::InvalidAttribute {
+            element: "DTC".into(),
+            attribute: "n".into(),
+            value: dtc.number.clone(),
+        })?;
+    Ok(Dtc {
+        short_name: dtc
+            .qual
+            .clone()
+            .unwrap_or_else(|| format!("DTC_{trouble_code:06X}"))
+            .into(),
+        trouble_code,
+        display_trouble_code: String::new(),
+        text: dtc
+            .name
+            .as_ref()
+            .and_then(cdd_model::Text::value)
+            .map(|value| Text {
+                value: value.into(),
+                ti: String::new(),
+            }),
+        level: None,
+        sdgs: None,
+        is_temporary: false,
+    })
+}
+
+fn long_name(text: &cdd_model::Text) -> Option<LongName> {
+    text.value().map(|value| LongName {
+        value: value.into(),
+        ti: String::new(),
+    })
+}
+
+/// Parse a decimal or `0x`-prefixed hexadecimal number.
+fn parse_number(s: &str) -> Option<u32> {
+    diag_ir::parse_uint(s).and_then(|v| u32::try_from(v).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("0xF190"), Some(0xF190));
+        assert_eq!(parse_number(" 42 "), Some(42));
+        assert_eq!(parse_number("F190"), None);
+    }
+}
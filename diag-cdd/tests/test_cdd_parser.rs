@@ -0,0 +1,176 @@
+use diag_cdd::{CddParseError, parse_cdd};
+use diag_ir::*;
+
+fn parse_minimal() -> DiagDatabase {
+    let xml = include_str!("../../test-fixtures/cdd/minimal.cdd");
+    parse_cdd(xml).expect("Failed to parse minimal CDD to IR")
+}
+
+fn service<'a>(variant: &'a Variant, name: &str) -> &'a DiagService {
+    variant
+        .diag_layer
+        .diag_services
+        .iter()
+        .find(|s| s.diag_comm.short_name == name)
+        .unwrap_or_else(|| panic!("missing service {name}"))
+}
+
+fn value_dop(param: &Param) -> &Dop {
+    match &param.specific_data {
+        Some(ParamData::Value { dop, .. }) => dop,
+        other => panic!("expected a value param, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_parse_cdd_layers() {
+    let db = parse_minimal();
+    assert_eq!(db.ecu_name, "ECM");
+    assert_eq!(db.variants.len(), 2);
+
+    let base = &db.variants[0];
+    assert!(base.is_base_variant);
+    assert_eq!(base.diag_layer.short_name, "ECM");
+    assert_eq!(
+        base.diag_layer.long_name.as_ref().unwrap().value,
+        "Engine Control Module"
+    );
+
+    let app = &db.variants[1];
+    assert!(!app.is_base_variant);
+    assert_eq!(app.diag_layer.short_name, "ECM_App");
+    match &app.parent_refs[0].ref_type {
+        ParentRefType::Variant(parent) => assert_eq!(parent.diag_layer.short_name, "ECM"),
+        other => panic!("expected a variant parent, got {other:?}"),
+    }
+    assert_eq!(app.diag_layer.diag_services.len(), 1);
+}
+
+#[test]
+fn test_parse_cdd_did_read_service() {
+    let db = parse_minimal();
+    let vin = service(&db.variants[0], "VIN_Read");
+    assert_eq!(
+        vin.diag_comm.long_name.as_ref().unwrap().value,
+        "Vehicle Identification Number"
+    );
+
+    let request = &vin.request.as_ref().unwrap().params;
+    assert_eq!(request.len(), 2);
+    assert!(matches!(
+        &request[1].specific_data,
+        Some(ParamData::CodedConst { coded_value, .. }) if coded_value == "61840"
+    ));
+
+    let response = &vin.pos_responses[0].params;
+    assert_eq!(response.len(), 3);
+    assert_eq!(response[2].short_name, "VIN");
+    assert_eq!(response[2].byte_position, Some(3));
+    let Some(DopData::NormalDop {
+        diag_coded_type: Some(coded),
+        ..
+    }) = &value_dop(&response[2]).specific_data
+    else {
+        panic!("expected a normal DOP");
+    };
+    assert_eq!(coded.base_data_type, DataType::AAsciiString);
+    assert!(matches!(
+        coded.specific_data,
+        Some(DiagCodedTypeData::StandardLength {
+            bit_length: 136,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_parse_cdd_data_types() {
+    let db = parse_minimal();
+    let status = service(&db.variants[0], "EngineStatus_Read");
+    let response = &status.pos_responses[0].params;
+    assert_eq!(response.len(), 4);
+    assert_eq!(response[2].short_name, "VehicleSpeed");
+    assert_eq!(response[3].short_name, "State");
+    assert_eq!(response[3].byte_position, Some(5));
+
+    let Some(DopData::NormalDop {
+        compu_method: Some(speed),
+        unit_ref: Some(unit),
+        ..
+    }) = &value_dop(&response[2]).specific_data
+    else {
+        panic!("expected a normal DOP with a unit");
+    };
+    assert_eq!(speed.category, CompuCategory::Linear);
+    let coeffs = speed.internal_to_phys.as_ref().unwrap().compu_scales[0]
+        .rational_co_effs
+        .as_ref()
+        .unwrap();
+    assert_eq!(coeffs.numerator, [0.0, 0.01]);
+    assert_eq!(unit.short_name, "km/h");
+
+    let Some(DopData::NormalDop {
+        compu_method: Some(state),
+        diag_coded_type: Some(coded),
+        ..
+    }) = &value_dop(&response[3]).specific_data
+    else {
+        panic!("expected a normal DOP");
+    };
+    assert!(!coded.is_high_low_byte_order);
+    assert_eq!(state.category, CompuCategory::TextTable);
+    let scales = &state.internal_to_phys.as_ref().unwrap().compu_scales;
+    assert_eq!(scales.len(), 2);
+    assert_eq!(scales[0].consts.as_ref().unwrap().vt, "Off");
+    assert_eq!(scales[1].upper_limit.as_ref().unwrap().value, "2");
+}
+
+#[test]
+fn test_parse_cdd_diag_inst() {
+    let db = parse_minimal();
+    let session = service(&db.variants[0], "ExtendedSession_Start");
+    let request = &session.request.as_ref().unwrap().params;
+    assert_eq!(request[0].semantic, "SERVICE-ID");
+    assert_eq!(request[1].short_name, "SessionType");
+    assert_eq!(request[1].byte_position, Some(1));
+
+    let response = &session.pos_responses[0].params;
+    assert_eq!(response.len(), 3);
+    assert_eq!(response[2].short_name, "Timing");
+    assert_eq!(response[2].byte_position, Some(2));
+}
+
+#[test]
+fn test_parse_cdd_dtcs_and_state_groups() {
+    let db = parse_minimal();
+    assert_eq!(db.dtcs.len(), 2);
+    assert_eq!(db.dtcs[0].short_name, "P0101");
+    assert_eq!(db.dtcs[0].trouble_code, 0x01_0100);
+    assert_eq!(
+        db.dtcs[0].text.as_ref().unwrap().value,
+        "Mass air flow sensor range"
+    );
+    assert_eq!(db.dtcs[1].trouble_code, 0x01_0201);
+    assert_eq!(db.dtcs[1].text, None);
+
+    let chart = &db.variants[0].diag_layer.state_charts[0];
+    assert_eq!(chart.short_name, "Session");
+    assert_eq!(chart.start_state_short_name_ref, "Default");
+    assert_eq!(chart.states.len(), 2);
+}
+
+#[test]
+fn test_parse_cdd_unknown_data_type() {
+    let xml = r#"<CANDELA><ECUDOC>
+        <DIDS><DID n="1"><QUAL>X</QUAL><STRUCTURE><DATAOBJ dtref="missing"/></STRUCTURE></DID></DIDS>
+        <ECU><QUAL>ECM</QUAL></ECU>
+    </ECUDOC></CANDELA>"#;
+    let err = parse_cdd(xml).unwrap_err();
+    assert!(matches!(err, CddParseError::UnknownDataType(ref id) if id == "missing"));
+}
+
+#[test]
+fn test_parse_cdd_requires_ecu() {
+    let err = parse_cdd("<CANDELA><ECUDOC/></CANDELA>").unwrap_err();
+    assert!(matches!(err, CddParseError::MissingElement(ref e) if e == "ECU"));
+}
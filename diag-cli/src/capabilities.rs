@@ -0,0 +1,76 @@
+use std::fmt::Write as _;
+
+use crate::formats::{FormatProvider, providers};
+
+/// Print which IR features each format keeps when written.
+pub fn run_capabilities() {
+    print!("{}", matrix(&providers().collect::<Vec<_>>()));
+}
+
+/// The capability matrix of `providers`: one row per IR feature, one column
+/// per format. Built from the descriptors the writers use for loss reports,
+/// so it shows what a conversion actually drops.
+fn matrix(providers: &[&dyn FormatProvider]) -> String {
+    let features = diag_ir::FormatCapabilities::FEATURE_NAMES;
+    let width = features.map(str::len).into_iter().max().unwrap_or(0);
+
+    let mut out = format!("{:<width$}", "Feature");
+    for provider in providers {
+        let _ = write!(out, "  {:<4}", provider.name());
+    }
+    out = out.trim_end().to_string();
+    out.push('\n');
+    for (row, feature) in features.iter().enumerate() {
+        let mut line = format!("{feature:<width$}");
+        for provider in providers {
+            let cell = match provider.capabilities() {
+                Some(caps) if caps.features()[row].1 => "yes",
+                Some(_) => "no",
+                None => "-",
+            };
+            let _ = write!(line, "  {cell:<4}");
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    if providers.iter().any(|p| p.capabilities().is_none()) {
+        out.push_str("\n-: input-only format\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_writable_format_declares_capabilities() {
+        for provider in providers() {
+            assert_eq!(
+                provider.writable(),
+                provider.capabilities().is_some(),
+                "{}",
+                provider.name()
+            );
+        }
+    }
+
+    #[test]
+    fn matrix_has_a_row_per_feature_and_a_column_per_format() {
+        let matrix = matrix(&providers().collect::<Vec<_>>());
+        let lines: Vec<&str> = matrix.lines().collect();
+        assert_eq!(
+            lines[0].split_whitespace().collect::<Vec<_>>(),
+            ["Feature", "ODX", "PDX", "CDD", "YAML", "MDD", "JSON"]
+        );
+        let memory = lines
+            .iter()
+            .find(|l| l.starts_with("memory configuration"))
+            .unwrap();
+        assert_eq!(
+            memory.split_whitespace().skip(2).collect::<Vec<_>>(),
+            ["no", "no", "-", "yes", "yes", "yes"]
+        );
+        assert!(matrix.ends_with("-: input-only format\n"));
+    }
+}
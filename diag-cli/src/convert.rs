@@ -1,8 +1,12 @@
 use anyhow::{Context, Result, bail};
+use serde::Serialize;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 use crate::Format;
+use crate::provenance::Provenance;
+use crate::sidecar_log::{ConversionLog, LogFormat, LogWarning, Timings};
+use crate::size_budget::SizePolicy;
 
 pub fn parse_compression(s: &str) -> Result<mdd_format::compression::Compression> {
     match s {
@@ -14,36 +18,206 @@ pub fn parse_compression(s: &str) -> Result<mdd_format::compression::Compression
     }
 }
 
+/// Encryption of MDD output under the key in `path` (see
+/// [`mdd_format::encryption::Key::from_file_contents`]).
+pub fn load_encryption_key(path: &Path) -> Result<mdd_format::encryption::ChunkEncryption> {
+    Ok(mdd_format::encryption::ChunkEncryption {
+        keys: load_decryption_key(path)?,
+        key_identifier: None,
+    })
+}
+
+/// The key in `path`, for reading encrypted MDD input.
+pub fn load_decryption_key(path: &Path) -> Result<mdd_format::encryption::KeySource> {
+    let contents =
+        std::fs::read(path).with_context(|| format!("reading key file {}", path.display()))?;
+    let key = mdd_format::encryption::Key::from_file_contents(&contents)
+        .with_context(|| format!("key file {}", path.display()))?;
+    Ok(key.into())
+}
+
+/// Read the metadata and FlatBuffers payload of an MDD file, decrypting it
+/// with `keys` when given.
+pub(crate) fn read_mdd_file(
+    path: &Path,
+    keys: Option<&mdd_format::encryption::KeySource>,
+) -> Result<(mdd_format::reader::MddMetadata, Vec<u8>)> {
+    let read = match keys {
+        Some(keys) => mdd_format::reader::read_mdd_file_with_key(path, keys),
+        None => mdd_format::reader::read_mdd_file(path),
+    };
+    read.with_context(|| format!("reading MDD from {}", path.display()))
+}
+
+/// Ed25519 signing of MDD output with the secret key in `path` (see
+/// [`mdd_format::signing::signing_key_from_file_contents`]).
+pub fn load_signing_key(path: &Path) -> Result<mdd_format::signing::ContainerSigning> {
+    let contents =
+        std::fs::read(path).with_context(|| format!("reading key file {}", path.display()))?;
+    let key = mdd_format::signing::signing_key_from_file_contents(&contents)
+        .with_context(|| format!("key file {}", path.display()))?;
+    Ok(mdd_format::signing::ContainerSigning {
+        key,
+        key_identifier: None,
+    })
+}
+
 pub fn parse_input(input: &Path, lenient: bool) -> Result<diag_ir::types::DiagDatabase> {
-    let in_fmt = crate::detect_format(input).context("input file")?;
-
-    let db = match in_fmt {
-        Format::Yaml => {
-            let text = std::fs::read_to_string(input)
-                .with_context(|| format!("reading {}", input.display()))?;
-            diag_yaml::parse_yaml(&text)
-                .with_context(|| format!("parsing YAML from {}", input.display()))?
-        }
-        Format::Odx => {
-            let text = std::fs::read_to_string(input)
-                .with_context(|| format!("reading {}", input.display()))?;
-            if lenient {
-                diag_odx::parse_odx_lenient(&text)
-            } else {
-                diag_odx::parse_odx(&text)
-            }
-            .with_context(|| format!("parsing ODX from {}", input.display()))?
+    parse_input_with(
+        input,
+        None,
+        &diag_odx::ParseOptions {
+            lenient,
+            ..Default::default()
+        },
+    )
+}
+
+/// Like [`parse_input`], with an explicit input format (`None` detects it)
+/// and the full set of ODX parser options.
+pub fn parse_input_with(
+    input: &Path,
+    from: Option<Format>,
+    odx_options: &diag_odx::ParseOptions,
+) -> Result<diag_ir::types::DiagDatabase> {
+    let in_fmt = crate::detect_input_format(input, from).context("input file")?;
+    in_fmt.provider().parse(input, odx_options)
+}
+
+/// Merge the databases of `others` into `db`, parsed from `input`. Conflicts
+/// are reported with the file that disagrees.
+fn merge_inputs(
+    db: diag_ir::types::DiagDatabase,
+    input: &Path,
+    others: &[PathBuf],
+    odx_options: &diag_odx::ParseOptions,
+) -> Result<diag_ir::types::DiagDatabase> {
+    let mut databases = vec![db];
+    for other in others {
+        databases.push(parse_input_with(other, None, odx_options)?);
+    }
+    match diag_ir::merge(&databases) {
+        Ok(db) => {
+            log::info!("Merged {} inputs", databases.len());
+            Ok(db)
         }
-        Format::Pdx => diag_odx::read_pdx_file(input)
-            .with_context(|| format!("reading PDX from {}", input.display()))?,
-        Format::Mdd => {
-            let (_meta, fbs_data) = mdd_format::reader::read_mdd_file(input)
-                .with_context(|| format!("reading MDD from {}", input.display()))?;
-            diag_ir::flatbuffers_to_ir(&fbs_data).with_context(|| "converting FlatBuffers to IR")?
+        Err(diag_ir::MergeConflict(conflicts)) => {
+            let files: Vec<&Path> = std::iter::once(input)
+                .chain(others.iter().map(PathBuf::as_path))
+                .collect();
+            let report: Vec<String> = conflicts
+                .iter()
+                .map(|c| {
+                    format!(
+                        "  {}: {} differs from an earlier input",
+                        c.path,
+                        files[c.input].display()
+                    )
+                })
+                .collect();
+            bail!(
+                "{} item(s) are defined differently by the merged inputs:\n{}",
+                conflicts.len(),
+                report.join("\n")
+            );
         }
+    }
+}
+
+/// Decode an MDD file, decrypting it with `keys` when given and converting
+/// only the named variants (all when `variants` is empty). A damaged file
+/// fails with a report of what is damaged; with `force`, what is readable is
+/// converted and the damage comes back as warnings.
+pub(crate) fn read_mdd_input(
+    input: &Path,
+    keys: Option<&mdd_format::encryption::KeySource>,
+    variants: &[String],
+    force: bool,
+) -> Result<(diag_ir::types::DiagDatabase, Vec<String>)> {
+    let read = || -> Result<diag_ir::types::DiagDatabase> {
+        let source = mdd_format::reader::MddSource::open(input)?;
+        let stream = source.stream()?;
+        let fbs_data = match keys {
+            Some(keys) => stream.flatbuffers_with_key(keys)?,
+            None => stream.flatbuffers()?,
+        };
+        Ok(if variants.is_empty() {
+            diag_ir::flatbuffers_to_ir(&fbs_data)?
+        } else {
+            let names: Vec<&str> = variants.iter().map(String::as_str).collect();
+            diag_ir::flatbuffers_to_ir_variants(&fbs_data, &names)?
+        })
     };
+    let err = match read() {
+        Ok(db) => return Ok((db, vec![])),
+        Err(err) => err,
+    };
+    let context = || format!("reading MDD from {}", input.display());
+    if matches!(
+        err.downcast_ref(),
+        Some(mdd_format::reader::MddReadError::Encrypted)
+    ) {
+        bail!(
+            "{}: the description is encrypted; pass --decrypt-key-file",
+            context()
+        );
+    }
+    let Ok(bytes) = std::fs::read(input) else {
+        return Err(err.context(context()));
+    };
+    let Ok(recovery) = mdd_format::reader::recover_mdd_bytes(&bytes) else {
+        return Err(err.context(context()));
+    };
+    let mut damage: Vec<String> = recovery.container_error.iter().cloned().collect();
+    damage.extend(
+        recovery
+            .chunks
+            .iter()
+            .filter(|c| c.error.is_some())
+            .map(ToString::to_string),
+    );
+    if !force {
+        let details: String = damage
+            .iter()
+            .flat_map(|line| ["\n  ", line.as_str()])
+            .collect();
+        bail!(
+            "{}: {err}{details}\nrerun with --force to convert what is readable",
+            context()
+        );
+    }
 
-    Ok(db)
+    let mut db = if let Some(payload) = &recovery.description {
+        let salvage = diag_ir::salvage_flatbuffers(payload);
+        damage.extend(salvage.warnings);
+        salvage.database
+    } else {
+        damage.push("no diagnostic description survived; only the header is kept".into());
+        diag_ir::types::DiagDatabase::default()
+    };
+    let header = &recovery.metadata;
+    for (field, value) in [
+        (&mut db.ecu_name, &header.ecu_name),
+        (&mut db.version, &header.version),
+        (&mut db.revision, &header.revision),
+    ] {
+        if field.is_empty() {
+            field.clone_from(value);
+        }
+    }
+    Ok((db, damage))
+}
+
+/// Keep only the named variants, failing on names the database lacks.
+fn select_variants(db: &mut diag_ir::types::DiagDatabase, names: &[String]) -> Result<()> {
+    for name in names {
+        if !db.variants.iter().any(|v| &v.diag_layer.short_name == name) {
+            bail!("Unknown variant '{name}'");
+        }
+    }
+    db.variants
+        .retain(|v| names.iter().any(|n| *n == v.diag_layer.short_name));
+    Ok(())
 }
 
 /// Collect unique code_file names from all SingleEcuJob ProgCode entries.
@@ -66,15 +240,16 @@ fn collect_code_file_refs(db: &diag_ir::types::DiagDatabase) -> Vec<String> {
     refs.into_iter().collect()
 }
 
-/// Build ExtraChunk entries by reading referenced job files from a directory.
-fn build_job_file_chunks(
+/// Read the job files referenced by SingleEcuJob ProgCode entries from a
+/// directory, as (name, contents). Missing files are skipped with a warning.
+pub(crate) fn read_job_files(
     db: &diag_ir::types::DiagDatabase,
     job_files_dir: &Path,
-) -> Result<Vec<mdd_format::writer::ExtraChunk>> {
+) -> Result<Vec<(String, Vec<u8>)>> {
     let refs = collect_code_file_refs(db);
-    let mut chunks = Vec::new();
-    for name in &refs {
-        let file_path = job_files_dir.join(name);
+    let mut files = Vec::new();
+    for name in refs {
+        let file_path = job_files_dir.join(&name);
         if !file_path.exists() {
             log::warn!("Job file not found: {}", file_path.display());
             continue;
@@ -82,51 +257,452 @@ fn build_job_file_chunks(
         let data = std::fs::read(&file_path)
             .with_context(|| format!("reading job file {}", file_path.display()))?;
         log::info!("Including job file: {} ({} bytes)", name, data.len());
-        chunks.push(mdd_format::writer::ExtraChunk {
+        files.push((name, data));
+    }
+    Ok(files)
+}
+
+/// Build ExtraChunk entries by reading referenced job files from a directory.
+pub(crate) fn build_job_file_chunks(
+    db: &diag_ir::types::DiagDatabase,
+    job_files_dir: &Path,
+) -> Result<Vec<mdd_format::writer::ExtraChunk>> {
+    Ok(read_job_files(db, job_files_dir)?
+        .into_iter()
+        .map(|(name, data)| mdd_format::writer::ExtraChunk {
             chunk_type: mdd_format::writer::ExtraChunkType::JarFile,
-            name: name.clone(),
+            name,
             data,
-        });
+        })
+        .collect())
+}
+
+pub fn format_extension(fmt: &str) -> Result<&'static str> {
+    match crate::formats::by_name(fmt) {
+        Some(format) if format.provider().writable() => Ok(format.extension()),
+        _ => bail!(
+            "Unknown output format: {fmt}. Use {}",
+            crate::formats::writable_names()
+        ),
     }
-    Ok(chunks)
 }
 
-pub fn format_extension(fmt: &str) -> Result<&str> {
-    match fmt {
-        "odx" => Ok("odx"),
-        "yaml" | "yml" => Ok("yml"),
-        "mdd" => Ok("mdd"),
-        other => bail!("Unknown output format: {other}. Use odx, yaml, or mdd"),
+/// Options shared by single-file and batch conversion.
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    /// Input format, overriding detection by extension and content.
+    pub from: Option<Format>,
+    pub compression: String,
+    pub dry_run: bool,
+    /// Keep only what matches this audience expression (`development|oem`,
+    /// `!aftermarket`).
+    pub audience: Option<diag_ir::AudienceFilter>,
+    /// Write one output per audience from a single parse, each filtered like
+    /// `audience` and named `<stem>_<audience>.<ext>`.
+    pub split_audiences: Vec<String>,
+    /// Keep only services reachable from this functional group.
+    pub functional_group: Option<String>,
+    /// Keep only untagged items and items tagged with one of these features.
+    pub features: Option<Vec<String>>,
+    /// Keep only these variants. MDD input skips decoding the others.
+    pub variants: Vec<String>,
+    /// Drop services, jobs and DTCs with one of these lifecycle statuses.
+    pub exclude_lifecycle: Vec<diag_ir::LifecycleStatus>,
+    /// Keep only what these selectors (e.g. `services[semantic=DATA-READ]`)
+    /// select, by the rules of [`diag_ir::extract`].
+    pub select: Vec<diag_ir::Selector>,
+    /// Keep only the services, or DTCs, matching this filter expression
+    /// (e.g. `service.semantic == ROUTINE && service.access.security`).
+    pub filter: Option<diag_ir::FilterExpr>,
+    pub include_job_files: Option<PathBuf>,
+    pub lenient: bool,
+    /// Reject YAML input with keys the format does not know instead of
+    /// ignoring them.
+    pub strict: bool,
+    /// Convert what is readable of a damaged MDD input instead of failing.
+    pub force: bool,
+    /// Keep ODX elements and attributes the converter does not model and
+    /// re-emit them when writing ODX.
+    pub preserve_unknown_xml: bool,
+    /// Carry the comments of a YAML input (the first one when merging) over
+    /// to YAML output.
+    pub preserve_comments: bool,
+    /// Spelling of DID, RID and DTC keys in YAML output.
+    pub yaml_numerals: diag_yaml::NumeralPolicy,
+    /// Write only these top-level sections of YAML output.
+    pub yaml_sections: Vec<String>,
+    /// ODX version of ODX and PDX output.
+    pub odx_version: Option<diag_odx::OdxVersion>,
+    pub log_level: String,
+    /// Layout of the `.log` sidecar.
+    pub log_format: LogFormat,
+    /// Omit provenance (converter version, input hash, command line,
+    /// timestamp) so identical inputs produce byte-identical outputs.
+    pub deterministic: bool,
+    /// Sort the database into canonical order and pretty-print ODX output.
+    pub normalize: bool,
+    /// Make service, job and DTC short names valid ODX short names and
+    /// unique, logging every rename.
+    pub fix_short_names: bool,
+    /// Fail instead of writing when the output format cannot represent
+    /// everything in the input.
+    pub strict_roundtrip: bool,
+    /// Write `<output>.loss.json` listing what the output format dropped.
+    pub loss_report: bool,
+    /// Fail instead of writing MDD output over these size limits.
+    pub size_policy: SizePolicy,
+    /// Merge the translated DTC texts of a CSV (language, file).
+    pub dtc_texts: Option<(String, PathBuf)>,
+    /// Further inputs merged into the input, in order, before the filters.
+    pub merge: Vec<PathBuf>,
+    /// Rhai script run on the database after the filters.
+    pub script: Option<PathBuf>,
+    /// CSV of `old,new` short names applied after the script.
+    pub rename_map: Option<PathBuf>,
+    /// Output profile; `minimal` strips long names, descriptions, SDGs and
+    /// admin data.
+    pub profile: diag_ir::Profile,
+    /// Write `<output>.profile.json` with what the profile removed.
+    pub profile_mapping: bool,
+    /// Skip the input unless its validity window covers this date.
+    pub valid_on: Option<diag_ir::Date>,
+    /// Skip the input unless it applies to this vehicle model year.
+    pub model_year: Option<u16>,
+    /// Embed a name search index in MDD output.
+    pub search_index: bool,
+    /// Decrypt encrypted MDD input.
+    pub decryption: Option<mdd_format::encryption::KeySource>,
+    /// Encrypt the description and search index of MDD output.
+    pub encryption: Option<mdd_format::encryption::ChunkEncryption>,
+    /// Sign MDD output.
+    pub signing: Option<mdd_format::signing::ContainerSigning>,
+    /// Which definition of a repeated trouble code or DID to keep.
+    pub duplicates: diag_ir::DuplicatePolicy,
+    /// What to do with characters XML cannot represent.
+    pub invalid_chars: diag_ir::InvalidCharPolicy,
+}
+
+impl ConvertOptions {
+    /// Whether the conversion changes content, which makes a same-format
+    /// conversion (YAML -> YAML, ODX -> ODX, MDD -> MDD) meaningful: any
+    /// transform option differs from its default.
+    pub fn has_transforms(&self) -> bool {
+        let default = Self::default();
+        self.audience != default.audience
+            || self.split_audiences != default.split_audiences
+            || self.functional_group != default.functional_group
+            || self.features != default.features
+            || self.variants != default.variants
+            || self.exclude_lifecycle != default.exclude_lifecycle
+            || self.select != default.select
+            || self.filter != default.filter
+            || self.yaml_sections != default.yaml_sections
+            || self.yaml_numerals != default.yaml_numerals
+            || self.odx_version != default.odx_version
+            || self.normalize != default.normalize
+            || self.fix_short_names != default.fix_short_names
+            || self.dtc_texts != default.dtc_texts
+            || self.script != default.script
+            || self.rename_map != default.rename_map
+            || self.merge != default.merge
+            || self.profile != default.profile
+            || self.search_index != default.search_index
+            || self.valid_on != default.valid_on
+            || self.model_year != default.model_year
+            || self.duplicates != default.duplicates
+            || self.invalid_chars != default.invalid_chars
+            // Keys do not compare; any key re-encrypts or re-signs.
+            || self.encryption.is_some()
+            || self.signing.is_some()
     }
 }
 
-pub fn run_convert(
-    input: &Path,
-    output: &Path,
-    compression: &str,
-    dry_run: bool,
-    audience: Option<&str>,
-    include_job_files: Option<&Path>,
-    lenient: bool,
-    log_level: &str,
-) -> Result<()> {
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            from: None,
+            compression: "lzma".into(),
+            dry_run: false,
+            audience: None,
+            split_audiences: vec![],
+            functional_group: None,
+            features: None,
+            variants: vec![],
+            exclude_lifecycle: vec![],
+            select: vec![],
+            filter: None,
+            include_job_files: None,
+            lenient: false,
+            strict: false,
+            force: false,
+            preserve_unknown_xml: false,
+            preserve_comments: false,
+            yaml_numerals: diag_yaml::NumeralPolicy::default(),
+            yaml_sections: vec![],
+            odx_version: None,
+            log_level: "off".into(),
+            log_format: LogFormat::Text,
+            deterministic: false,
+            normalize: false,
+            fix_short_names: false,
+            strict_roundtrip: false,
+            loss_report: false,
+            size_policy: SizePolicy::default(),
+            dtc_texts: None,
+            merge: vec![],
+            script: None,
+            rename_map: None,
+            profile: diag_ir::Profile::Full,
+            profile_mapping: false,
+            valid_on: None,
+            model_year: None,
+            search_index: false,
+            decryption: None,
+            encryption: None,
+            signing: None,
+            duplicates: diag_ir::DuplicatePolicy::First,
+            invalid_chars: diag_ir::InvalidCharPolicy::Strip,
+        }
+    }
+}
+
+/// Contents of `<output>.loss.json`.
+#[derive(Debug, Serialize)]
+struct LossReport {
+    input: String,
+    output: String,
+    format: &'static str,
+    losses: Vec<diag_ir::Loss>,
+}
+
+/// `out.mdd` -> `out.mdd.<suffix>`.
+fn sidecar_path(output: &Path, suffix: &str) -> PathBuf {
+    output.with_extension(format!(
+        "{}.{suffix}",
+        output.extension().and_then(|e| e.to_str()).unwrap_or("out")
+    ))
+}
+
+/// A database left out of a conversion because its validity does not cover
+/// the requested date or model year (`--valid-on`, `--model-year`).
+#[derive(Debug)]
+pub struct NotApplicable(String);
+
+impl std::fmt::Display for NotApplicable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NotApplicable {}
+
+/// Fail with [`NotApplicable`] unless `validity` covers the date and model
+/// year of `opts`. Databases without validity apply everywhere.
+fn check_applicable(validity: Option<&diag_ir::Validity>, opts: &ConvertOptions) -> Result<()> {
+    let Some(validity) = validity else {
+        return Ok(());
+    };
+    if let Some(date) = opts.valid_on {
+        let status = validity.status_on(date);
+        if status != diag_ir::ValidityStatus::Valid {
+            return Err(NotApplicable(format!("{status} (--valid-on {date})")).into());
+        }
+    }
+    if let Some(year) = opts.model_year
+        && !validity.applies_to_model_year(year)
+    {
+        return Err(NotApplicable(format!("does not apply to model year {year}")).into());
+    }
+    Ok(())
+}
+
+/// Parse a `--valid-on` value: `YYYY-MM-DD` or `today`.
+pub fn parse_valid_on(s: &str) -> Result<diag_ir::Date, String> {
+    if s.eq_ignore_ascii_case("today") {
+        Ok(diag_ir::Date::today())
+    } else {
+        s.parse()
+    }
+}
+
+pub fn run_convert(input: &Path, output: &Path, opts: &ConvertOptions) -> Result<()> {
     let total_start = Instant::now();
     let out_fmt = crate::detect_format(output).context("output file")?;
-    let in_fmt = crate::detect_format(input).context("input file")?;
+    let in_fmt = crate::detect_input_format(input, opts.from).context("input file")?;
+
+    if in_fmt == out_fmt && !opts.has_transforms() {
+        bail!(
+            "Input and output formats are the same ({in_fmt:?}). Nothing to convert; \
+             pass a transform option (see `convert --help`) or use `normalize` / `repack`."
+        );
+    }
+
+    if out_fmt != Format::MDD && !opts.size_policy.is_empty() {
+        bail!("Size budgets (--max-output-size, --size-policy) apply to MDD output only");
+    }
+
+    if !opts.yaml_sections.is_empty() {
+        if out_fmt != Format::YAML {
+            bail!("--sections applies to YAML output only");
+        }
+        diag_yaml::check_sections(&opts.yaml_sections)?;
+    }
+
+    if opts.odx_version.is_some() && out_fmt != Format::ODX && out_fmt != Format::PDX {
+        bail!("--odx-version applies to ODX and PDX output only");
+    }
 
-    if in_fmt == out_fmt {
-        bail!("Input and output formats are the same ({in_fmt:?}). Nothing to convert.");
+    if opts.encryption.is_some() && out_fmt != Format::MDD {
+        bail!("--encrypt-key-file applies to MDD output only");
+    }
+
+    if opts.signing.is_some() && out_fmt != Format::MDD {
+        bail!("--sign-key-file applies to MDD output only");
+    }
+
+    if opts.audience.is_some() && !opts.split_audiences.is_empty() {
+        bail!("--audience and --split-by-audience cannot be combined");
     }
 
     log::info!("Converting {:?} -> {:?}", in_fmt, out_fmt);
 
+    let parsed = parse_for_convert(input, in_fmt, opts)?;
+    if opts.split_audiences.is_empty() {
+        return write_converted(input, output, out_fmt, parsed, opts, total_start);
+    }
+    for audience in &opts.split_audiences {
+        let target_opts = ConvertOptions {
+            audience: Some(diag_ir::AudienceFilter::single(audience.as_str())),
+            split_audiences: vec![],
+            ..opts.clone()
+        };
+        write_converted(
+            input,
+            &audience_output(output, audience),
+            out_fmt,
+            parsed.clone(),
+            &target_opts,
+            total_start,
+        )?;
+    }
+    Ok(())
+}
+
+/// `output` with `_<audience>` appended to the file stem.
+fn audience_output(output: &Path, audience: &str) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    match output.extension() {
+        Some(ext) => output.with_file_name(format!("{stem}_{audience}.{}", ext.to_string_lossy())),
+        None => output.with_file_name(format!("{stem}_{audience}")),
+    }
+}
+
+/// The input database after parsing and the fixes every output shares,
+/// before any per-output filter.
+#[derive(Clone)]
+struct Parsed {
+    in_fmt: Format,
+    input_size: u64,
+    db: diag_ir::types::DiagDatabase,
+    warnings: Vec<LogWarning>,
+    parse_ms: f64,
+}
+
+fn parse_for_convert(input: &Path, in_fmt: Format, opts: &ConvertOptions) -> Result<Parsed> {
     let input_size = std::fs::metadata(input).map_or(0, |m| m.len());
 
     let parse_start = Instant::now();
-    let mut db = parse_input(input, lenient)?;
+    let odx_options = diag_odx::ParseOptions {
+        lenient: opts.lenient,
+        preserve_unknown_xml: opts.preserve_unknown_xml,
+        duplicates: opts.duplicates,
+        ..Default::default()
+    };
+    let mut warnings: Vec<LogWarning> = Vec::new();
+    let check_validity = opts.valid_on.is_some() || opts.model_year.is_some();
+    if check_validity && in_fmt == Format::MDD {
+        // The header answers this without decoding the description.
+        let meta = mdd_format::reader::read_metadata_file(input)
+            .with_context(|| format!("reading MDD header from {}", input.display()))?;
+        check_applicable(meta.validity.as_ref().map(Into::into).as_ref(), opts)?;
+    }
+    let mut db = if in_fmt == Format::MDD {
+        let (db, damage) =
+            read_mdd_input(input, opts.decryption.as_ref(), &opts.variants, opts.force)?;
+        for line in damage {
+            log::warn!("{line}");
+            warnings.push(LogWarning::new("mdd_recovery", line));
+        }
+        db
+    } else if in_fmt == Format::YAML && opts.strict {
+        let text = std::fs::read_to_string(input)
+            .with_context(|| format!("reading {}", input.display()))?;
+        diag_yaml::parse_yaml_strict(&text)
+            .map_err(|e| crate::snippet::located(input, &text, e.offset(), e))
+            .with_context(|| format!("parsing YAML from {}", input.display()))?
+    } else {
+        parse_input_with(input, opts.from, &odx_options)?
+    };
+    if !opts.merge.is_empty() {
+        db = merge_inputs(db, input, &opts.merge, &odx_options)?;
+    }
+    if check_validity && in_fmt != Format::MDD {
+        check_applicable(diag_ir::validity(&db).as_ref(), opts)?;
+    }
+    if !opts.variants.is_empty() {
+        select_variants(&mut db, &opts.variants)?;
+    }
+    for field in diag_ir::sanitize(&mut db, opts.invalid_chars)
+        .with_context(|| format!("checking the characters of {}", input.display()))?
+    {
+        let message = format!("{field}; {}", opts.invalid_chars.resolution());
+        log::warn!("{message}");
+        warnings.push(LogWarning::new("invalid_chars", message));
+    }
     let parse_ms = parse_start.elapsed().as_secs_f64() * 1000.0;
 
-    if let Some(aud) = audience {
+    for duplicate in diag_ir::resolve_duplicates(&mut db, opts.duplicates)? {
+        let resolution = opts.duplicates.resolution(&duplicate);
+        log::warn!("{duplicate}; {resolution}");
+        warnings.push(LogWarning::new(
+            "duplicate",
+            format!("{duplicate}; {resolution}"),
+        ));
+    }
+
+    for unresolved in diag_ir::resolve_comparam_defaults(&mut db) {
+        log::warn!("{unresolved}");
+        warnings.push(LogWarning::new("unresolved_comparam", unresolved));
+    }
+
+    Ok(Parsed {
+        in_fmt,
+        input_size,
+        db,
+        warnings,
+        parse_ms,
+    })
+}
+
+/// Filter, transform and write one output from `parsed`.
+fn write_converted(
+    input: &Path,
+    output: &Path,
+    out_fmt: Format,
+    parsed: Parsed,
+    opts: &ConvertOptions,
+    total_start: Instant,
+) -> Result<()> {
+    let log_level = opts.log_level.as_str();
+    let Parsed {
+        in_fmt,
+        input_size,
+        mut db,
+        mut warnings,
+        parse_ms,
+    } = parsed;
+
+    if let Some(aud) = &opts.audience {
         let before = db
             .variants
             .iter()
@@ -143,15 +719,121 @@ pub fn run_convert(
         }
     }
 
+    if let Some(group) = opts.functional_group.as_deref() {
+        diag_ir::filter_by_functional_group(&mut db, group)?;
+        log::info!(
+            "Functional group filter '{group}': {} services kept",
+            db.variants
+                .iter()
+                .map(|v| v.diag_layer.diag_services.len())
+                .sum::<usize>()
+        );
+    }
+
+    if let Some(features) = &opts.features {
+        diag_ir::filter_by_features(&mut db, features);
+        log::info!(
+            "Feature selection '{}': {} services kept",
+            features.join(","),
+            db.variants
+                .iter()
+                .map(|v| v.diag_layer.diag_services.len())
+                .sum::<usize>()
+        );
+    }
+
+    if !opts.exclude_lifecycle.is_empty() {
+        let removed = diag_ir::filter_by_lifecycle(&mut db, &opts.exclude_lifecycle);
+        log::info!("Lifecycle filter: {removed} services/jobs/DTCs removed");
+    }
+
+    if !opts.select.is_empty() {
+        db = diag_ir::extract(&db, &opts.select);
+        let selectors: Vec<String> = opts.select.iter().map(ToString::to_string).collect();
+        log::info!(
+            "Selection {}: {} services kept",
+            selectors.join(" "),
+            db.variants
+                .iter()
+                .map(|v| v.diag_layer.diag_services.len())
+                .sum::<usize>()
+        );
+    }
+
+    if let Some(filter) = &opts.filter {
+        let removed = diag_ir::filter_by_expr(&mut db, filter);
+        log::info!("Filter '{filter}': {removed} services/DTCs removed");
+    }
+
+    if let Some((lang, path)) = &opts.dtc_texts {
+        let texts = crate::dtc_texts::read_csv(path)?;
+        match diag_ir::merge_dtc_texts(&mut db, lang, &texts) {
+            Ok(n) => log::info!("DTC texts: {n} '{lang}' translation(s) merged"),
+            Err(errors) => {
+                let report: Vec<String> = errors.iter().map(|e| format!("  {e}")).collect();
+                bail!(
+                    "{} row(s) of {} match no DTC:\n{}",
+                    errors.len(),
+                    path.display(),
+                    report.join("\n")
+                );
+            }
+        }
+    }
+
+    if let Some(script) = &opts.script {
+        crate::script::run_script(&mut db, script)?;
+    }
+
+    if let Some(path) = &opts.rename_map {
+        let names = crate::renames::read_rename_map(path)?;
+        let report = diag_ir::apply_renames(&mut db, &names);
+        for rename in &report.renamed {
+            log::info!("Rename: {rename}");
+        }
+        for old in &report.unmatched {
+            let message = format!("'{old}' of {} matches nothing", path.display());
+            log::warn!("Rename map: {message}");
+            warnings.push(LogWarning::new("rename_unmatched", message));
+        }
+        if opts.dry_run {
+            for rename in &report.renamed {
+                println!("{rename}");
+            }
+            for old in &report.unmatched {
+                println!("unmatched '{old}'");
+            }
+        }
+    }
+
+    if opts.fix_short_names {
+        for fix in diag_ir::fix_short_names(&mut db) {
+            log::warn!("Short name: {fix}");
+            warnings.push(LogWarning::new("short_name", fix));
+        }
+    }
+
+    if opts.normalize {
+        diag_ir::normalize(&mut db);
+    }
+
+    let profile_mapping =
+        diag_ir::apply_profile(&mut db, opts.profile).context("applying output profile")?;
+    if opts.profile != diag_ir::Profile::Full {
+        log::info!(
+            "Profile {}: stripped {} object(s)",
+            opts.profile,
+            profile_mapping.removed.len()
+        );
+    }
+
     let validate_start = Instant::now();
-    let validation_warnings: Vec<String> = if let Err(errors) = diag_ir::validate_database(&db) {
+    if let Err(errors) = diag_ir::validate_database(&db) {
         for e in &errors {
             log::warn!("Validation: {e}");
+            warnings.push(LogWarning::new("validation", e));
         }
-        errors.into_iter().map(|e| e.to_string()).collect()
-    } else {
-        Vec::new()
-    };
+    }
     let validate_ms = validate_start.elapsed().as_secs_f64() * 1000.0;
 
     log::debug!("Parse time: {parse_ms:.1}ms");
@@ -164,7 +846,41 @@ pub fn run_convert(
         db.dtcs.len()
     );
 
-    if dry_run {
+    let mut loss_report = None;
+    if let Some(caps) = out_fmt.provider().capabilities() {
+        let losses = diag_ir::find_losses(&db, caps);
+        if !losses.is_empty() && opts.strict_roundtrip {
+            let report: Vec<String> = losses.iter().map(|l| format!("  {l}")).collect();
+            bail!(
+                "{} element(s) cannot be represented in {} output:\n{}",
+                losses.len(),
+                caps.format,
+                report.join("\n")
+            );
+        }
+        if !losses.is_empty() {
+            log::warn!(
+                "{} element(s) cannot be represented in {} output and will be dropped \
+                 (use --strict-roundtrip to fail instead)",
+                losses.len(),
+                caps.format
+            );
+        }
+        for loss in &losses {
+            log::info!("Dropped {loss}");
+            warnings.push(LogWarning::new("format_loss", loss));
+        }
+        if opts.loss_report {
+            loss_report = Some(LossReport {
+                input: input.display().to_string(),
+                output: output.display().to_string(),
+                format: caps.format,
+                losses,
+            });
+        }
+    }
+
+    if opts.dry_run {
         let fbs_data = diag_ir::ir_to_flatbuffers(&db);
         println!(
             "dry run: would write {} bytes to {}",
@@ -174,42 +890,36 @@ pub fn run_convert(
         return Ok(());
     }
 
+    let provenance = if opts.deterministic {
+        None
+    } else {
+        Some(Provenance::capture(input)?)
+    };
+
     let write_start = Instant::now();
-    let mut fbs_size: Option<usize> = None;
+    let ctx = crate::formats::WriteContext {
+        input,
+        in_fmt,
+        opts,
+        provenance: provenance.as_ref(),
+    };
+    let fbs_size = out_fmt.provider().write(&mut db, output, &ctx)?;
 
-    match out_fmt {
-        Format::Yaml => {
-            let yaml = diag_yaml::write_yaml(&db).context("writing YAML")?;
-            std::fs::write(output, &yaml)
-                .with_context(|| format!("writing {}", output.display()))?;
-        }
-        Format::Odx => {
-            let xml = diag_odx::write_odx(&db).context("writing ODX")?;
-            std::fs::write(output, &xml)
-                .with_context(|| format!("writing {}", output.display()))?;
-        }
-        Format::Mdd => {
-            let fbs_data = diag_ir::ir_to_flatbuffers(&db);
-            fbs_size = Some(fbs_data.len());
-            let extra_chunks = if let Some(dir) = include_job_files {
-                build_job_file_chunks(&db, dir)?
-            } else {
-                vec![]
-            };
-            let options = mdd_format::writer::WriteOptions {
-                version: db.version.clone(),
-                ecu_name: db.ecu_name.clone(),
-                revision: db.revision.clone(),
-                compression: parse_compression(compression)?,
-                extra_chunks,
-                ..Default::default()
-            };
-            mdd_format::writer::write_mdd_file(&fbs_data, &options, output)
-                .with_context(|| format!("writing MDD to {}", output.display()))?;
-        }
-        Format::Pdx => {
-            bail!("PDX is an input-only format (ZIP archive). Use .odx for ODX output.");
-        }
+    if let Some(report) = &loss_report {
+        let path = sidecar_path(output, "loss.json");
+        let json = serde_json::to_string_pretty(report).context("serializing loss report")?;
+        std::fs::write(&path, json + "\n")
+            .with_context(|| format!("writing {}", path.display()))?;
+        log::info!("Loss report written to {}", path.display());
+    }
+
+    if opts.profile_mapping {
+        let path = sidecar_path(output, "profile.json");
+        let json = serde_json::to_string_pretty(&profile_mapping)
+            .context("serializing profile mapping")?;
+        std::fs::write(&path, json + "\n")
+            .with_context(|| format!("writing {}", path.display()))?;
+        log::info!("Profile mapping written to {}", path.display());
     }
 
     let write_ms = write_start.elapsed().as_secs_f64() * 1000.0;
@@ -221,71 +931,26 @@ pub fn run_convert(
 
     // Write .log file if requested
     if log_level != "off" {
-        let log_path = output.with_extension(format!(
-            "{}.log",
-            output.extension().and_then(|e| e.to_str()).unwrap_or("out")
-        ));
-        let output_size = std::fs::metadata(output).map_or(0, |m| m.len());
-        let mut log_lines = Vec::new();
-        log_lines.push(format!("input: {}", input.display()));
-        log_lines.push(format!("input_size: {} bytes", input_size));
-        log_lines.push(format!("output: {}", output.display()));
-        log_lines.push(format!("output_size: {} bytes", output_size));
-        log_lines.push(format!("input_format: {:?}", in_fmt));
-        log_lines.push(format!("output_format: {:?}", out_fmt));
-        log_lines.push(format!("parse_time: {parse_ms:.1}ms"));
-        log_lines.push(format!("validate_time: {validate_ms:.1}ms"));
-        log_lines.push(format!("write_time: {write_ms:.1}ms"));
-        log_lines.push(format!("total_time: {total_ms:.1}ms"));
-        log_lines.push(format!("ecu: {}", db.ecu_name));
-        log_lines.push(format!("variants: {}", db.variants.len()));
-        log_lines.push(format!("dtcs: {}", db.dtcs.len()));
-
-        if let Some(fbs) = fbs_size {
-            log_lines.push(format!("fbs_size: {} bytes", fbs));
-            if output_size > 0 {
-                let ratio = fbs as f64 / output_size as f64;
-                log_lines.push(format!("compression_ratio: {ratio:.2}x"));
-            }
-        }
-
-        if !validation_warnings.is_empty() {
-            log_lines.push(format!(
-                "validation_warnings: {}",
-                validation_warnings.len()
-            ));
-            if log_level == "debug" {
-                for w in &validation_warnings {
-                    log_lines.push(format!("  - {w}"));
-                }
-            }
-        }
-
-        if log_level == "debug" {
-            let services: usize = db
-                .variants
-                .iter()
-                .map(|v| v.diag_layer.diag_services.len())
-                .sum();
-            let jobs: usize = db
-                .variants
-                .iter()
-                .map(|v| v.diag_layer.single_ecu_jobs.len())
-                .sum();
-            log_lines.push(format!("total_services: {services}"));
-            log_lines.push(format!("total_single_ecu_jobs: {jobs}"));
-            for v in &db.variants {
-                log_lines.push(format!(
-                    "  variant '{}': {} services, {} jobs",
-                    v.diag_layer.short_name,
-                    v.diag_layer.diag_services.len(),
-                    v.diag_layer.single_ecu_jobs.len(),
-                ));
-            }
-        }
-
-        let log_content = log_lines.join("\n") + "\n";
-        std::fs::write(&log_path, &log_content)
+        let log_path = sidecar_path(output, "log");
+        let log = ConversionLog {
+            input,
+            output,
+            input_format: in_fmt,
+            output_format: out_fmt,
+            input_size,
+            output_size: std::fs::metadata(output).map_or(0, |m| m.len()),
+            fbs_size,
+            timings: Timings {
+                parse: parse_ms,
+                validate: validate_ms,
+                write: write_ms,
+                total: total_ms,
+            },
+            db: &db,
+            warnings: &warnings,
+            debug: log_level == "debug",
+        };
+        std::fs::write(&log_path, log.render(opts.log_format))
             .with_context(|| format!("writing log to {}", log_path.display()))?;
     }
 
@@ -296,12 +961,7 @@ pub fn run_batch_convert(
     inputs: &[PathBuf],
     output_dir: &Path,
     out_ext: &str,
-    compression: &str,
-    dry_run: bool,
-    audience: Option<&str>,
-    include_job_files: Option<&Path>,
-    lenient: bool,
-    log_level: &str,
+    opts: &ConvertOptions,
 ) -> Result<()> {
     use rayon::prelude::*;
 
@@ -315,25 +975,23 @@ pub fn run_batch_convert(
         .map(|input| {
             let stem = input.file_stem().unwrap_or_default();
             let out_path = output_dir.join(format!("{}.{}", stem.to_string_lossy(), out_ext));
-            let result = run_convert(
-                input,
-                &out_path,
-                compression,
-                dry_run,
-                audience,
-                include_job_files,
-                lenient,
-                log_level,
-            );
+            let result = run_convert(input, &out_path, opts);
             (input.clone(), result)
         })
         .collect();
 
-    let mut failed = 0;
+    let (mut failed, mut skipped) = (0, 0);
     for (input, result) in &results {
-        if let Err(e) = result {
-            eprintln!("FAILED {}: {e:#}", input.display());
-            failed += 1;
+        match result {
+            Err(e) if e.downcast_ref::<NotApplicable>().is_some() => {
+                eprintln!("SKIPPED {}: {e}", input.display());
+                skipped += 1;
+            }
+            Err(e) => {
+                eprintln!("FAILED {}: {e:#}", input.display());
+                failed += 1;
+            }
+            Ok(()) => {}
         }
     }
 
@@ -341,17 +999,26 @@ pub fn run_batch_convert(
         bail!("{failed} of {} files failed to convert", inputs.len());
     }
 
-    println!(
-        "Batch complete: {} files converted to {}",
-        inputs.len(),
-        output_dir.display()
-    );
+    if skipped > 0 {
+        println!(
+            "Batch complete: {} files converted to {}, {skipped} skipped as not applicable",
+            inputs.len() - skipped,
+            output_dir.display()
+        );
+    } else {
+        println!(
+            "Batch complete: {} files converted to {}",
+            inputs.len(),
+            output_dir.display()
+        );
+    }
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::fixture;
 
     #[test]
     fn parse_compression_lzma() {
@@ -411,9 +1078,1138 @@ mod tests {
         assert_eq!(format_extension("mdd").unwrap(), "mdd");
     }
 
+    #[test]
+    fn format_extension_json() {
+        assert_eq!(format_extension("json").unwrap(), "json");
+    }
+
+    #[test]
+    fn convert_records_provenance_in_mdd_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.mdd");
+        run_convert(
+            &fixture("yaml/example-ecm.yml"),
+            &out,
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+        let (meta, _) = mdd_format::reader::read_mdd_file(&out).unwrap();
+        assert_eq!(meta.metadata["provenance_source"], "example-ecm.yml");
+        assert_eq!(meta.metadata["provenance_source_sha256"].len(), 64);
+        assert!(meta.metadata.contains_key("provenance_timestamp"));
+    }
+
+    #[test]
+    fn convert_records_provenance_in_odx_admin_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.odx");
+        run_convert(
+            &fixture("yaml/example-ecm.yml"),
+            &out,
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+        let xml = std::fs::read_to_string(&out).unwrap();
+        assert!(xml.contains("<TOOL>diag-converter "));
+        assert!(xml.contains("Converted from example-ecm.yml (sha256 "));
+    }
+
+    #[test]
+    fn convert_yaml_to_pdx() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.pdx");
+        run_convert(
+            &fixture("yaml/example-ecm.yml"),
+            &out,
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+        let db = diag_odx::read_pdx_file(&out).unwrap();
+        assert!(!db.variants.is_empty());
+    }
+
+    #[test]
+    fn convert_odx_project_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.json");
+        run_convert(&fixture("odx/project"), &out, &ConvertOptions::default()).unwrap();
+        let db = diag_ir::parse_json(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        let names: Vec<&str> = db
+            .variants
+            .iter()
+            .map(|v| v.diag_layer.short_name.as_str())
+            .collect();
+        assert_eq!(names, ["SplitECU_Base", "SplitECU_HW1"]);
+    }
+
+    #[test]
+    fn select_keeps_only_the_selected_services() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("reads.json");
+        let opts = ConvertOptions {
+            select: vec!["services[semantic=DATA-READ]".parse().unwrap()],
+            ..Default::default()
+        };
+        run_convert(&fixture("yaml/example-ecm.yml"), &out, &opts).unwrap();
+        let db = diag_ir::parse_json(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        let services: Vec<_> = db
+            .variants
+            .iter()
+            .flat_map(|v| &v.diag_layer.diag_services)
+            .collect();
+        assert!(!services.is_empty());
+        for svc in services {
+            assert_eq!(
+                diag_ir::Semantic::of(svc),
+                Some(diag_ir::Semantic::DataRead),
+                "{}",
+                svc.diag_comm.short_name
+            );
+        }
+        assert!(db.dtcs.is_empty());
+    }
+
+    #[test]
+    fn encrypted_mdd_converts_back_with_its_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_file = dir.path().join("ecu.key");
+        std::fs::write(&key_file, "42".repeat(32)).unwrap();
+        let input = fixture("yaml/example-ecm.yml");
+        let mdd = dir.path().join("ecm.mdd");
+        let opts = ConvertOptions {
+            encryption: Some(load_encryption_key(&key_file).unwrap()),
+            ..Default::default()
+        };
+        run_convert(&input, &mdd, &opts).unwrap();
+
+        let yaml = dir.path().join("ecm.yml");
+        let err = run_convert(&mdd, &yaml, &ConvertOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("--decrypt-key-file"), "{err}");
+        let opts = ConvertOptions {
+            decryption: Some(load_decryption_key(&key_file).unwrap()),
+            ..Default::default()
+        };
+        run_convert(&mdd, &yaml, &opts).unwrap();
+
+        // Sorted: the YAML writer may list a variant's services in another
+        // order than the input did.
+        let service_names = |db: &diag_ir::types::DiagDatabase| -> Vec<String> {
+            let mut names: Vec<_> = db
+                .variants
+                .iter()
+                .flat_map(|v| &v.diag_layer.diag_services)
+                .map(|svc| svc.diag_comm.short_name.to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        let original = parse_input(&input, false).unwrap();
+        let converted = parse_input(&yaml, false).unwrap();
+        assert_eq!(converted.ecu_name, original.ecu_name);
+        assert_eq!(service_names(&converted), service_names(&original));
+    }
+
+    #[test]
+    fn extract_keeps_only_the_functional_group_services() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("diagnostics.json");
+        let opts = ConvertOptions {
+            functional_group: Some("Diagnostics".into()),
+            ..Default::default()
+        };
+        run_convert(&fixture("odx/minimal.odx"), &out, &opts).unwrap();
+        let db = diag_ir::parse_json(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        let services: Vec<&str> = db
+            .variants
+            .iter()
+            .map(|v| &v.diag_layer)
+            .chain(db.functional_groups.iter().map(|fg| &fg.diag_layer))
+            .chain(db.protocols.iter().map(|p| &p.diag_layer))
+            .flat_map(|layer| &layer.diag_services)
+            .map(|svc| svc.diag_comm.short_name.as_str())
+            .collect();
+        assert!(services.contains(&"TesterPresent"), "{services:?}");
+        assert!(!services.contains(&"Read_VehicleSpeed"), "{services:?}");
+    }
+
+    #[test]
+    fn where_keeps_only_the_matching_services() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("reads.json");
+        let opts = ConvertOptions {
+            filter: Some("service.semantic == DATA-READ".parse().unwrap()),
+            ..Default::default()
+        };
+        let input = fixture("yaml/example-ecm.yml");
+        run_convert(&input, &out, &opts).unwrap();
+        let db = diag_ir::parse_json(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        let services: Vec<_> = db
+            .variants
+            .iter()
+            .flat_map(|v| &v.diag_layer.diag_services)
+            .collect();
+        assert!(!services.is_empty());
+        for svc in services {
+            assert_eq!(
+                diag_ir::Semantic::of(svc),
+                Some(diag_ir::Semantic::DataRead),
+                "{}",
+                svc.diag_comm.short_name
+            );
+        }
+        // DTCs are left alone by a service expression.
+        assert!(!db.dtcs.is_empty());
+    }
+
+    #[test]
+    fn odx_version_selects_the_output_dialect() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = ConvertOptions {
+            odx_version: Some(diag_odx::OdxVersion::V2_0),
+            ..Default::default()
+        };
+        let input = fixture("odx/minimal.odx");
+        let err = run_convert(&input, &dir.path().join("out.yml"), &opts).unwrap_err();
+        assert!(err.to_string().contains("--odx-version"), "{err}");
+
+        let out = dir.path().join("out.odx");
+        run_convert(&input, &out, &opts).unwrap();
+        let xml = std::fs::read_to_string(&out).unwrap();
+        assert_eq!(
+            diag_odx::OdxVersion::of_document(&xml),
+            Some(diag_odx::OdxVersion::V2_0)
+        );
+        assert_eq!(diag_odx::parse_odx(&xml).unwrap().version, "2.0.1");
+    }
+
+    #[test]
+    fn sign_key_file_signs_mdd_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let secret = dir.path().join("release.key");
+        std::fs::write(&secret, format!("{}\n", "07".repeat(32))).unwrap();
+        let signing = load_signing_key(&secret).unwrap();
+        let public = signing.key.verifying_key();
+        let opts = ConvertOptions {
+            signing: Some(signing),
+            ..Default::default()
+        };
+
+        let input = fixture("yaml/example-ecm.yml");
+        let err = run_convert(&input, &dir.path().join("out.json"), &opts).unwrap_err();
+        assert!(err.to_string().contains("--sign-key-file"), "{err}");
+
+        let signed = dir.path().join("signed.mdd");
+        run_convert(&input, &signed, &opts).unwrap();
+        let verify = |path: &Path| {
+            mdd_format::reader::verify_mdd_signature(&std::fs::read(path).unwrap(), &public)
+        };
+        verify(&signed).unwrap();
+
+        // An MDD rewrite keeps the original container and signs it anew.
+        let resigned = dir.path().join("resigned.mdd");
+        run_convert(&signed, &resigned, &opts).unwrap();
+        verify(&resigned).unwrap();
+
+        let unsigned = dir.path().join("unsigned.mdd");
+        run_convert(&input, &unsigned, &ConvertOptions::default()).unwrap();
+        assert!(matches!(
+            verify(&unsigned),
+            Err(mdd_format::reader::MddReadError::ChunksSignature(
+                mdd_format::signing::SigningError::Unsigned
+            ))
+        ));
+    }
+
+    #[test]
+    fn sections_apply_to_yaml_output_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = ConvertOptions {
+            yaml_sections: vec!["dtcs".into()],
+            ..Default::default()
+        };
+        let input = fixture("yaml/example-ecm.yml");
+        let err = run_convert(&input, &dir.path().join("out.mdd"), &opts).unwrap_err();
+        assert!(err.to_string().contains("--sections"), "{err}");
+
+        let out = dir.path().join("dtcs.yml");
+        run_convert(&input, &out, &opts).unwrap();
+        let doc: serde_yaml::Value =
+            serde_yaml::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        assert!(doc.get("dtcs").is_some());
+        assert!(doc.get("dids").is_none());
+    }
+
+    #[test]
+    fn split_by_audience_matches_separate_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = fixture("yaml/example-ecm.yml");
+        let split = ConvertOptions {
+            split_audiences: vec!["development".into(), "manufacturing".into()],
+            ..Default::default()
+        };
+        run_convert(&input, &dir.path().join("ecu.json"), &split).unwrap();
+        assert!(!dir.path().join("ecu.json").exists());
+
+        for audience in ["development", "manufacturing"] {
+            let single = dir.path().join(format!("single_{audience}.json"));
+            let opts = ConvertOptions {
+                audience: Some(audience.parse().unwrap()),
+                ..Default::default()
+            };
+            run_convert(&input, &single, &opts).unwrap();
+            assert_eq!(
+                std::fs::read_to_string(dir.path().join(format!("ecu_{audience}.json"))).unwrap(),
+                std::fs::read_to_string(&single).unwrap(),
+                "{audience}"
+            );
+        }
+    }
+
+    #[test]
+    fn split_by_audience_rejects_audience() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = ConvertOptions {
+            audience: Some("development".parse().unwrap()),
+            split_audiences: vec!["aftersales".into()],
+            ..Default::default()
+        };
+        let err = run_convert(
+            &fixture("yaml/example-ecm.yml"),
+            &dir.path().join("ecu.json"),
+            &opts,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("--split-by-audience"));
+    }
+
+    #[test]
+    fn audience_output_suffixes_the_stem() {
+        assert_eq!(
+            audience_output(Path::new("out/ecu.mdd"), "aftersales"),
+            Path::new("out/ecu_aftersales.mdd")
+        );
+        assert_eq!(
+            audience_output(Path::new("ecu"), "development"),
+            Path::new("ecu_development")
+        );
+    }
+
+    #[test]
+    fn convert_cdd_to_yaml() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.yml");
+        run_convert(
+            &fixture("cdd/minimal.cdd"),
+            &out,
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+        let db = diag_yaml::parse_yaml(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        assert_eq!(db.ecu_name, "ECM");
+        let base = db.variants.iter().find(|v| v.is_base_variant).unwrap();
+        assert!(
+            base.diag_layer
+                .diag_services
+                .iter()
+                .any(|s| s.diag_comm.short_name == "VIN_Read")
+        );
+        assert_eq!(db.dtcs.len(), 2);
+
+        let err = run_convert(
+            &out,
+            &dir.path().join("back.cdd"),
+            &ConvertOptions::default(),
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("input-only"));
+    }
+
+    #[test]
+    fn deterministic_convert_is_reproducible() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = ConvertOptions {
+            deterministic: true,
+            ..Default::default()
+        };
+        let a = dir.path().join("a.mdd");
+        let b = dir.path().join("b.mdd");
+        run_convert(&fixture("yaml/example-ecm.yml"), &a, &opts).unwrap();
+        run_convert(&fixture("yaml/example-ecm.yml"), &b, &opts).unwrap();
+        assert_eq!(std::fs::read(&a).unwrap(), std::fs::read(&b).unwrap());
+        let (meta, _) = mdd_format::reader::read_mdd_file(&a).unwrap();
+        assert!(!meta.metadata.keys().any(|k| k.starts_with("provenance_")));
+    }
+
+    #[test]
+    fn same_format_without_transform_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = run_convert(
+            &fixture("yaml/minimal-ecu.yml"),
+            &dir.path().join("out.yml"),
+            &ConvertOptions::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Nothing to convert"), "{err}");
+
+        // Skipping inputs outside a validity window is a transform too.
+        let opts = ConvertOptions {
+            model_year: Some(2024),
+            ..Default::default()
+        };
+        run_convert(
+            &fixture("yaml/minimal-ecu.yml"),
+            &dir.path().join("out.yml"),
+            &opts,
+        )
+        .unwrap();
+        // So is respelling the identifier keys.
+        let opts = ConvertOptions {
+            yaml_numerals: diag_yaml::NumeralPolicy::Decimal,
+            ..Default::default()
+        };
+        run_convert(
+            &fixture("yaml/minimal-ecu.yml"),
+            &dir.path().join("out.yml"),
+            &opts,
+        )
+        .unwrap();
+        // And resolving duplicates other than by keeping the first (`--duplicates`).
+        let opts = ConvertOptions {
+            duplicates: diag_ir::DuplicatePolicy::Last,
+            ..Default::default()
+        };
+        run_convert(
+            &fixture("yaml/minimal-ecu.yml"),
+            &dir.path().join("out.yml"),
+            &opts,
+        )
+        .unwrap();
+        // And escaping or rejecting characters XML cannot represent
+        // (`--invalid-chars escape`, `--fail-on-invalid-chars`).
+        for invalid_chars in [
+            diag_ir::InvalidCharPolicy::Escape,
+            diag_ir::InvalidCharPolicy::Error,
+        ] {
+            let opts = ConvertOptions {
+                invalid_chars,
+                ..Default::default()
+            };
+            run_convert(
+                &fixture("yaml/minimal-ecu.yml"),
+                &dir.path().join("out.yml"),
+                &opts,
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn same_format_normalize_is_idempotent() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = ConvertOptions {
+            normalize: true,
+            deterministic: true,
+            ..Default::default()
+        };
+        let once = dir.path().join("once.yml");
+        let twice = dir.path().join("twice.yml");
+        run_convert(&fixture("yaml/minimal-ecu.yml"), &once, &opts).unwrap();
+        run_convert(&once, &twice, &opts).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&once).unwrap(),
+            std::fs::read_to_string(&twice).unwrap()
+        );
+    }
+
+    #[test]
+    fn selected_variants_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let mdd = dir.path().join("all.mdd");
+        let one = dir.path().join("one.mdd");
+        run_convert(
+            &fixture("odx/minimal.odx"),
+            &mdd,
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+        let opts = ConvertOptions {
+            variants: vec!["TestECU_HW1".into()],
+            ..Default::default()
+        };
+        run_convert(&mdd, &one, &opts).unwrap();
+        let db = parse_input(&one, false).unwrap();
+        let names: Vec<_> = db
+            .variants
+            .iter()
+            .map(|v| v.diag_layer.short_name.as_str())
+            .collect();
+        assert_eq!(names, ["TestECU_HW1"]);
+
+        let opts = ConvertOptions {
+            variants: vec!["Nope".into()],
+            ..Default::default()
+        };
+        let err = run_convert(&fixture("odx/minimal.odx"), &one, &opts).unwrap_err();
+        assert!(err.to_string().contains("Unknown variant 'Nope'"), "{err}");
+    }
+
+    #[test]
+    fn truncated_mdd_needs_force() {
+        let dir = tempfile::tempdir().unwrap();
+        let mdd = dir.path().join("ecu.mdd");
+        let out = dir.path().join("ecu.yml");
+        run_convert(
+            &fixture("odx/minimal.odx"),
+            &mdd,
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+        let bytes = std::fs::read(&mdd).unwrap();
+        std::fs::write(&mdd, &bytes[..bytes.len() * 3 / 4]).unwrap();
+
+        let err = run_convert(&mdd, &out, &ConvertOptions::default()).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("file ends in chunk 0"), "{msg}");
+        assert!(msg.contains("--force"), "{msg}");
+        assert!(!out.exists());
+
+        let opts = ConvertOptions {
+            force: true,
+            ..Default::default()
+        };
+        run_convert(&mdd, &out, &opts).unwrap();
+        let db = parse_input(&out, false).unwrap();
+        assert_eq!(db.ecu_name, "TestECU");
+    }
+
+    #[test]
+    fn mdd_over_size_budget_is_not_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.mdd");
+        let opts = ConvertOptions {
+            size_policy: SizePolicy {
+                max_output_size: Some(crate::size_budget::ByteSize(256)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let err = run_convert(&fixture("yaml/example-ecm.yml"), &out, &opts).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("over the limit of 256 B"), "{msg}");
+        assert!(msg.contains("Largest contributors"), "{msg}");
+        assert!(msg.contains("\n  variant "), "{msg}");
+        assert!(!out.exists());
+
+        let opts = ConvertOptions {
+            size_policy: SizePolicy {
+                max_output_size: Some(crate::size_budget::ByteSize(25_000_000)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        run_convert(&fixture("yaml/example-ecm.yml"), &out, &opts).unwrap();
+        assert!(out.exists());
+    }
+
+    #[test]
+    fn json_log_sidecar_has_one_event_per_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.mdd");
+        let opts = ConvertOptions {
+            log_level: "debug".into(),
+            log_format: LogFormat::Json,
+            deterministic: true,
+            ..Default::default()
+        };
+        run_convert(&fixture("yaml/example-ecm.yml"), &out, &opts).unwrap();
+        let log = std::fs::read_to_string(dir.path().join("out.mdd.log")).unwrap();
+        let events: Vec<serde_json::Value> = log
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(events[0]["event"], "start");
+        assert_eq!(events[0]["input_format"], "yaml");
+        let phases: Vec<&str> = events
+            .iter()
+            .filter(|e| e["event"] == "phase")
+            .map(|e| e["phase"].as_str().unwrap())
+            .collect();
+        assert_eq!(phases, ["parse", "validate", "write"]);
+        let counts = events.iter().find(|e| e["event"] == "counts").unwrap();
+        assert_eq!(counts["ecu"], "Engine Control Module");
+        assert!(events.iter().any(|e| e["event"] == "variant"));
+        let sizes = events.iter().find(|e| e["event"] == "sizes").unwrap();
+        assert!(sizes["fbs_size"].as_u64().unwrap() > 0);
+        assert_eq!(events.last().unwrap()["event"], "done");
+    }
+
+    #[test]
+    fn normalize_can_preserve_yaml_comments() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.yml");
+        let opts = ConvertOptions {
+            normalize: true,
+            deterministic: true,
+            preserve_comments: true,
+            ..Default::default()
+        };
+        run_convert(&fixture("yaml/minimal-ecu.yml"), &out, &opts).unwrap();
+        let yaml = std::fs::read_to_string(&out).unwrap();
+        assert!(yaml.starts_with("# Minimal ECU diagnostic description\n"));
+        assert!(yaml.contains("# Types using deterministic (fixed-length) definitions\ntypes:"));
+
+        let plain = dir.path().join("plain.yml");
+        let opts = ConvertOptions {
+            preserve_comments: false,
+            ..opts
+        };
+        run_convert(&fixture("yaml/minimal-ecu.yml"), &plain, &opts).unwrap();
+        assert!(!std::fs::read_to_string(&plain).unwrap().contains('#'));
+    }
+
+    #[test]
+    fn merge_can_preserve_the_comments_of_the_first_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let dids = dir.path().join("dids.yml");
+        let dtcs = dir.path().join("dtcs.yml");
+        std::fs::write(&dids, format!("# Identification DIDs\n{DID_FRAGMENT}")).unwrap();
+        std::fs::write(&dtcs, DTC_FRAGMENT).unwrap();
+
+        let out = dir.path().join("merged.yml");
+        let opts = ConvertOptions {
+            merge: vec![dtcs],
+            preserve_comments: true,
+            ..Default::default()
+        };
+        run_convert(&dids, &out, &opts).unwrap();
+        let yaml = std::fs::read_to_string(&out).unwrap();
+        assert!(yaml.starts_with("# Identification DIDs\n"), "{yaml}");
+        assert!(yaml.contains("AirflowFault"));
+    }
+
+    #[test]
+    fn colliding_short_names_are_fixed_and_logged() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("ecu.yml");
+        std::fs::write(
+            &input,
+            r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0xF190:
+    name: VIN
+    type: ascii
+  0xF191:
+    name: VIN
+    type: ascii
+"#,
+        )
+        .unwrap();
+        let out = dir.path().join("out.mdd");
+        let opts = ConvertOptions {
+            log_level: "info".into(),
+            fix_short_names: true,
+            ..Default::default()
+        };
+        run_convert(&input, &out, &opts).unwrap();
+
+        let log = std::fs::read_to_string(dir.path().join("out.mdd.log")).unwrap();
+        assert!(log.contains("short_name_fixes: 1"), "{log}");
+        assert!(log.contains("renamed 'VIN_Read' to 'VIN_Read_2'"), "{log}");
+        let db = parse_input(&out, false).unwrap();
+        assert!(diag_ir::check_short_names(&db).is_empty());
+    }
+
+    #[test]
+    fn rename_map_renames_services() {
+        let dir = tempfile::tempdir().unwrap();
+        let map = dir.path().join("renames.csv");
+        std::fs::write(
+            &map,
+            "old,new\nVIN_Read,VIN_Get\nNo_Such_Service,Anything\n",
+        )
+        .unwrap();
+        let input = dir.path().join("ecu.yml");
+        std::fs::write(
+            &input,
+            r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0xF190:
+    name: VIN
+    type: ascii
+"#,
+        )
+        .unwrap();
+        let out = dir.path().join("out.mdd");
+        let opts = ConvertOptions {
+            rename_map: Some(map),
+            ..Default::default()
+        };
+        run_convert(&input, &out, &opts).unwrap();
+
+        let db = parse_input(&out, false).unwrap();
+        let names: Vec<&str> = db.variants[0]
+            .diag_layer
+            .diag_services
+            .iter()
+            .map(|s| s.diag_comm.short_name.as_str())
+            .collect();
+        assert!(names.contains(&"VIN_Get"), "{names:?}");
+        assert!(!names.contains(&"VIN_Read"), "{names:?}");
+    }
+
+    #[test]
+    fn mdd_to_mdd_keeps_container_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = fixture("mdd/FLXC1000.mdd");
+        let out = dir.path().join("out.mdd");
+        // Deterministic, so no provenance entries are added.
+        let opts = ConvertOptions {
+            normalize: true,
+            deterministic: true,
+            ..Default::default()
+        };
+        run_convert(&input, &out, &opts).unwrap();
+
+        let (old_meta, _) = mdd_format::reader::read_mdd_file(&input).unwrap();
+        let (new_meta, fbs) = mdd_format::reader::read_mdd_file(&out).unwrap();
+        assert_eq!(new_meta.ecu_name, old_meta.ecu_name);
+        assert_eq!(new_meta.metadata, old_meta.metadata);
+        assert!(diag_ir::flatbuffers_to_ir(&fbs).is_ok());
+    }
+
+    #[test]
+    fn features_drop_items_tagged_for_other_builds() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("ecu.yml");
+        std::fs::write(
+            &input,
+            r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0xF190:
+    name: VIN
+    type: ascii
+    access: public
+  0xFD00:
+    name: DebugCounters
+    type: u32
+    access: public
+    features: [debug]
+services:
+  testerPresent:
+    enabled: true
+    features: [base]
+"#,
+        )
+        .unwrap();
+        let out = dir.path().join("out.mdd");
+        let opts = ConvertOptions {
+            features: Some(vec!["base".into()]),
+            ..Default::default()
+        };
+        run_convert(&input, &out, &opts).unwrap();
+
+        let (_, fbs) = mdd_format::reader::read_mdd_file(&out).unwrap();
+        let db = diag_ir::flatbuffers_to_ir(&fbs).unwrap();
+        let names: Vec<&str> = db.variants[0]
+            .diag_layer
+            .diag_services
+            .iter()
+            .map(|s| s.diag_comm.short_name.as_str())
+            .collect();
+        assert!(names.contains(&"VIN_Read"), "{names:?}");
+        assert!(names.contains(&"TesterPresent"), "{names:?}");
+        assert!(!names.contains(&"DebugCounters_Read"), "{names:?}");
+        assert_eq!(db.metadata["features"], "base");
+    }
+
+    const LIFECYCLE_YAML: &str = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0xF190:
+    name: VIN
+    type: ascii
+    access: public
+  0xF1A0:
+    name: LegacyId
+    type: ascii
+    access: public
+    lifecycle:
+      status: deprecated
+      since: "2.1"
+dtcs:
+  0x012300:
+    name: OldFault
+    sae: P0123
+    lifecycle:
+      status: obsolete
+"#;
+
+    #[test]
+    fn lifecycle_survives_mdd_and_odx() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("ecu.yml");
+        std::fs::write(&input, LIFECYCLE_YAML).unwrap();
+
+        let mdd = dir.path().join("out.mdd");
+        run_convert(&input, &mdd, &ConvertOptions::default()).unwrap();
+        let (_, fbs) = mdd_format::reader::read_mdd_file(&mdd).unwrap();
+        let db = diag_ir::flatbuffers_to_ir(&fbs).unwrap();
+        let legacy = db.variants[0]
+            .diag_layer
+            .diag_services
+            .iter()
+            .find(|s| s.diag_comm.short_name == "LegacyId_Read")
+            .unwrap();
+        let lc = diag_ir::lifecycle(legacy.diag_comm.sdgs.as_ref()).unwrap();
+        assert_eq!(lc.to_string(), "deprecated since 2.1");
+
+        let odx = dir.path().join("out.odx");
+        run_convert(&mdd, &odx, &ConvertOptions::default()).unwrap();
+        let db = diag_odx::parse_odx(&std::fs::read_to_string(&odx).unwrap()).unwrap();
+        let lc = diag_ir::lifecycle(db.dtcs[0].sdgs.as_ref()).unwrap();
+        assert_eq!(lc.status, diag_ir::LifecycleStatus::Obsolete);
+    }
+
+    #[test]
+    fn exclude_lifecycle_drops_matching_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("ecu.yml");
+        std::fs::write(&input, LIFECYCLE_YAML).unwrap();
+        let out = dir.path().join("out.odx");
+        let opts = ConvertOptions {
+            exclude_lifecycle: vec![
+                diag_ir::LifecycleStatus::Deprecated,
+                diag_ir::LifecycleStatus::Obsolete,
+            ],
+            ..Default::default()
+        };
+        run_convert(&input, &out, &opts).unwrap();
+
+        let db = diag_odx::parse_odx(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        let names: Vec<&str> = db.variants[0]
+            .diag_layer
+            .diag_services
+            .iter()
+            .map(|s| s.diag_comm.short_name.as_str())
+            .collect();
+        assert!(names.contains(&"VIN_Read"), "{names:?}");
+        assert!(!names.contains(&"LegacyId_Read"), "{names:?}");
+        assert!(db.dtcs.is_empty());
+    }
+
+    #[test]
+    fn functional_group_filter_rejects_unknown_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = ConvertOptions {
+            functional_group: Some("FG_Missing".into()),
+            ..Default::default()
+        };
+        let err = run_convert(
+            &fixture("yaml/minimal-ecu.yml"),
+            &dir.path().join("out.mdd"),
+            &opts,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("FG_Missing"), "{err}");
+    }
+
+    /// `example-ecm.yml` with a memory configuration, which ODX cannot carry.
+    fn ecm_with_memory(dir: &Path) -> PathBuf {
+        let mut yaml = std::fs::read_to_string(fixture("yaml/example-ecm.yml")).unwrap();
+        yaml.push_str(
+            "\nmemory:\n  regions:\n    flash:\n      name: Flash\n      start: 0x08000000\n      \
+             end: 0x080FFFFF\n      access: read_write\n",
+        );
+        let path = dir.join("ecm.yml");
+        std::fs::write(&path, yaml).unwrap();
+        path
+    }
+
+    #[test]
+    fn strict_roundtrip_rejects_lossy_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.odx");
+        let opts = ConvertOptions {
+            strict_roundtrip: true,
+            ..Default::default()
+        };
+        let err = run_convert(&ecm_with_memory(dir.path()), &out, &opts).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("cannot be represented in ODX output"), "{msg}");
+        assert!(
+            msg.contains("memory: memory configuration cannot be represented in ODX"),
+            "{msg}"
+        );
+        assert!(!out.exists());
+
+        // Type definitions travel in an SDG.
+        run_convert(&fixture("yaml/example-ecm.yml"), &out, &opts).unwrap();
+    }
+
+    #[test]
+    fn strict_roundtrip_accepts_lossless_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = ConvertOptions {
+            strict_roundtrip: true,
+            ..Default::default()
+        };
+        run_convert(
+            &fixture("mdd/FLXC1000.mdd"),
+            &dir.path().join("out.odx"),
+            &opts,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn loss_report_is_written_next_to_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.odx");
+        let opts = ConvertOptions {
+            loss_report: true,
+            ..Default::default()
+        };
+        run_convert(&ecm_with_memory(dir.path()), &out, &opts).unwrap();
+
+        let report: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(dir.path().join("out.odx.loss.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(report["format"], "ODX");
+        let losses = report["losses"].as_array().unwrap();
+        assert!(losses.iter().any(|l| l["path"] == "memory"
+            && l["reason"] == "memory configuration cannot be represented in ODX"));
+    }
+
+    #[test]
+    fn minimal_profile_strips_descriptions_and_writes_mapping() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.mdd");
+        let opts = ConvertOptions {
+            profile: diag_ir::Profile::Minimal,
+            profile_mapping: true,
+            ..Default::default()
+        };
+        run_convert(&fixture("yaml/example-ecm.yml"), &out, &opts).unwrap();
+
+        let (_, fbs) = mdd_format::reader::read_mdd_file(&out).unwrap();
+        let db = diag_ir::flatbuffers_to_ir(&fbs).unwrap();
+        assert!(!db.metadata.contains_key("author"));
+        for variant in &db.variants {
+            assert_eq!(variant.diag_layer.long_name, None);
+            for svc in &variant.diag_layer.diag_services {
+                assert_eq!(svc.diag_comm.long_name, None);
+                assert_eq!(svc.diag_comm.sdgs, None);
+            }
+        }
+
+        let mapping: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(dir.path().join("out.mdd.profile.json")).unwrap(),
+        )
+        .unwrap();
+        assert!(mapping["metadata"]["author"].is_string());
+        assert!(
+            mapping
+                .as_object()
+                .unwrap()
+                .values()
+                .any(|removed| removed.get("long_name").is_some())
+        );
+    }
+
+    #[test]
+    fn compatibility_is_written_to_the_mdd_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("ecu.yml");
+        std::fs::write(
+            &input,
+            r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+identification:
+  compatibility:
+    hardware: ["HW-0815"]
+    software: ["SW-4711", "SW-4712"]
+"#,
+        )
+        .unwrap();
+        let mdd = dir.path().join("ecu.mdd");
+        run_convert(&input, &mdd, &ConvertOptions::default()).unwrap();
+
+        let meta = mdd_format::reader::read_metadata_file(&mdd).unwrap();
+        let header = meta.compatibility.unwrap();
+        assert_eq!(header.hardware_part_numbers, ["HW-0815"]);
+        assert_eq!(header.software_part_numbers, ["SW-4711", "SW-4712"]);
+
+        let yaml = dir.path().join("back.yml");
+        run_convert(&mdd, &yaml, &ConvertOptions::default()).unwrap();
+        let doc = diag_yaml::parse_yaml_document(&std::fs::read_to_string(&yaml).unwrap()).unwrap();
+        let compatibility = doc.identification.unwrap().compatibility.unwrap();
+        assert_eq!(compatibility.software, ["SW-4711", "SW-4712"]);
+    }
+
+    #[test]
+    fn validity_is_written_to_the_mdd_header_and_filters_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("ecu.yml");
+        std::fs::write(
+            &input,
+            r#"
+schema: "opensovd.cda.diagdesc/v1"
+meta:
+  validity:
+    valid_from: "2025-01-01"
+    valid_until: "2026-12-31"
+    model_years: [2025, 2026]
+ecu:
+  name: "TEST"
+"#,
+        )
+        .unwrap();
+        let mdd = dir.path().join("ecu.mdd");
+        run_convert(&input, &mdd, &ConvertOptions::default()).unwrap();
+
+        let meta = mdd_format::reader::read_metadata_file(&mdd).unwrap();
+        let header = meta.validity.unwrap();
+        assert_eq!(header.valid_until.as_deref(), Some("2026-12-31"));
+        assert_eq!(header.model_years, [2025, 2026]);
+
+        let yaml = dir.path().join("back.yml");
+        run_convert(&mdd, &yaml, &ConvertOptions::default()).unwrap();
+        let doc = diag_yaml::parse_yaml_document(&std::fs::read_to_string(&yaml).unwrap()).unwrap();
+        let validity = doc.meta.unwrap().validity.unwrap();
+        assert_eq!(validity.valid_from, Some("2025-01-01".parse().unwrap()));
+
+        let expired = ConvertOptions {
+            valid_on: Some("2027-01-01".parse().unwrap()),
+            ..Default::default()
+        };
+        let err = run_convert(&mdd, &dir.path().join("x.yml"), &expired).unwrap_err();
+        assert!(err.downcast_ref::<NotApplicable>().is_some(), "{err:#}");
+        assert!(err.to_string().contains("expired on 2026-12-31"), "{err:#}");
+
+        let out_dir = dir.path().join("out");
+        let other_year = ConvertOptions {
+            model_year: Some(2024),
+            ..Default::default()
+        };
+        run_batch_convert(&[mdd.clone(), input], &out_dir, "odx", &other_year).unwrap();
+        assert!(!out_dir.join("ecu.odx").exists());
+
+        let current = ConvertOptions {
+            valid_on: Some("2026-06-01".parse().unwrap()),
+            model_year: Some(2026),
+            ..Default::default()
+        };
+        run_batch_convert(&[mdd], &out_dir, "odx", &current).unwrap();
+        assert!(out_dir.join("ecu.odx").exists());
+    }
+
+    const DID_FRAGMENT: &str = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0xF190:
+    name: VIN
+    type:
+      base: ascii
+      length: 17
+    access: public
+"#;
+
+    const DTC_FRAGMENT: &str = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dtcs:
+  0x012300:
+    name: AirflowFault
+    sae: P0123
+"#;
+
+    #[test]
+    fn merge_combines_fragments() {
+        let dir = tempfile::tempdir().unwrap();
+        let dids = dir.path().join("dids.yml");
+        let dtcs = dir.path().join("dtcs.yml");
+        std::fs::write(&dids, DID_FRAGMENT).unwrap();
+        std::fs::write(&dtcs, DTC_FRAGMENT).unwrap();
+
+        let out = dir.path().join("out.odx");
+        let opts = ConvertOptions {
+            merge: vec![dtcs.clone()],
+            ..Default::default()
+        };
+        run_convert(&dids, &out, &opts).unwrap();
+        let db = diag_odx::parse_odx(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        assert!(
+            db.variants[0]
+                .diag_layer
+                .diag_services
+                .iter()
+                .any(|s| s.diag_comm.short_name == "VIN_Read")
+        );
+        assert_eq!(db.dtcs.len(), 1);
+
+        // The same DID with another length in a second fragment is a conflict.
+        let other = dir.path().join("other.yml");
+        std::fs::write(&other, DID_FRAGMENT.replace("17", "8")).unwrap();
+        let opts = ConvertOptions {
+            merge: vec![dtcs, other],
+            ..Default::default()
+        };
+        let err = run_convert(&dids, &dir.path().join("x.mdd"), &opts).unwrap_err();
+        let msg = err.to_string();
+        assert!(
+            msg.contains("/services/VIN_Read: ") && msg.contains("other.yml differs"),
+            "{msg}"
+        );
+    }
+
+    #[test]
+    fn json_roundtrips_yaml() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = fixture("yaml/example-ecm.yml");
+        // Normalizing makes the direct yaml -> yaml conversion a transform.
+        let opts = ConvertOptions {
+            normalize: true,
+            deterministic: true,
+            ..Default::default()
+        };
+        let direct = dir.path().join("direct.yml");
+        run_convert(&input, &direct, &opts).unwrap();
+        let json = dir.path().join("ecm.json");
+        run_convert(&input, &json, &opts).unwrap();
+        let via_json = dir.path().join("via-json.yml");
+        run_convert(&json, &via_json, &opts).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&via_json).unwrap(),
+            std::fs::read_to_string(&direct).unwrap()
+        );
+        let text = std::fs::read_to_string(&json).unwrap();
+        assert_eq!(
+            diag_ir::write_json(&diag_ir::parse_json(&text).unwrap()).unwrap(),
+            text
+        );
+    }
+
     #[test]
     fn format_extension_invalid() {
-        let err = format_extension("json").unwrap_err();
+        let err = format_extension("xml").unwrap_err();
         assert!(err.to_string().contains("Unknown output format"));
     }
 }
@@ -0,0 +1,79 @@
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+
+use crate::Format;
+
+/// Write a patch turning the MDD file `old` into `new`, for shipping only
+/// the difference over the air.
+pub fn run_delta(old: &Path, new: &Path, output: &Path) -> Result<()> {
+    for path in [old, new] {
+        if crate::detect_format(path)? != Format::MDD {
+            bail!("delta works on .mdd files only: {}", path.display());
+        }
+    }
+    let old_bytes = std::fs::read(old).with_context(|| format!("reading {}", old.display()))?;
+    let new_bytes = std::fs::read(new).with_context(|| format!("reading {}", new.display()))?;
+    let patch = mdd_format::delta::make_delta(&old_bytes, &new_bytes).context("computing delta")?;
+    std::fs::write(output, &patch).with_context(|| format!("writing {}", output.display()))?;
+
+    println!(
+        "Delta {} -> {}: {} bytes ({} byte target)",
+        old.display(),
+        new.display(),
+        patch.len(),
+        new_bytes.len()
+    );
+    Ok(())
+}
+
+/// Rebuild the new MDD file from `old` and a patch written by `delta`. The
+/// result is checked against the digest in the patch before it is written.
+pub fn run_apply_delta(old: &Path, patch: &Path, output: &Path) -> Result<()> {
+    let old_bytes = std::fs::read(old).with_context(|| format!("reading {}", old.display()))?;
+    let patch_bytes =
+        std::fs::read(patch).with_context(|| format!("reading {}", patch.display()))?;
+    let new_bytes = mdd_format::delta::apply_delta(&old_bytes, &patch_bytes)
+        .with_context(|| format!("applying {} to {}", patch.display(), old.display()))?;
+    std::fs::write(output, &new_bytes).with_context(|| format!("writing {}", output.display()))?;
+
+    println!(
+        "Patched {} -> {} ({} bytes, SHA-512 verified)",
+        old.display(),
+        output.display(),
+        new_bytes.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{ConvertOptions, run_convert};
+    use crate::test_support::fixture;
+
+    #[test]
+    fn apply_delta_rebuilds_the_new_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = fixture("yaml/example-ecm.yml");
+        let old = dir.path().join("old.mdd");
+        let new = dir.path().join("new.mdd");
+        run_convert(&input, &old, &ConvertOptions::default()).unwrap();
+        let opts = ConvertOptions {
+            audience: Some("development".parse().unwrap()),
+            ..Default::default()
+        };
+        run_convert(&input, &new, &opts).unwrap();
+
+        let patch = dir.path().join("update.mddp");
+        run_delta(&old, &new, &patch).unwrap();
+        let patched = dir.path().join("patched.mdd");
+        run_apply_delta(&old, &patch, &patched).unwrap();
+        assert_eq!(
+            std::fs::read(&patched).unwrap(),
+            std::fs::read(&new).unwrap()
+        );
+
+        let err = run_apply_delta(&new, &patch, &patched).unwrap_err();
+        assert!(format!("{err:#}").contains("another file"), "{err:#}");
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::Result;
+use std::fmt::{self, Write as _};
+use std::path::Path;
+use std::str::FromStr;
+
+use diag_ir::{Change, DatabaseDiff};
+
+use crate::convert::parse_input;
+
+/// Layout of the `diff` report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffFormat {
+    /// One `+`/`-`/`~` line per change, grouped by section and variant.
+    #[default]
+    Text,
+    /// The [`DatabaseDiff`] as a JSON object, for CI pipelines.
+    Json,
+}
+
+impl FromStr for DiffFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown diff format '{s}' (expected text or json)")),
+        }
+    }
+}
+
+impl fmt::Display for DiffFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        })
+    }
+}
+
+/// Print the semantic differences between `old` and `new`, which may be in
+/// different formats.
+pub fn run_diff(old: &Path, new: &Path, format: DiffFormat) -> Result<()> {
+    let diff = diag_ir::diff_databases(&parse_input(old, false)?, &parse_input(new, false)?);
+    match format {
+        DiffFormat::Text => print!("{}", report(&diff)),
+        DiffFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+    }
+    Ok(())
+}
+
+fn report(diff: &DatabaseDiff) -> String {
+    let mut out = String::new();
+    section(&mut out, "Variants", &diff.variants);
+    let by_variant = [
+        ("Services", &diff.services),
+        ("DIDs", &diff.dids),
+        ("State charts", &diff.state_charts),
+        ("ComParams", &diff.comparams),
+    ];
+    for (title, changes) in by_variant {
+        for (variant, changes) in changes {
+            section(&mut out, &format!("{title} of {variant}"), changes);
+        }
+    }
+    section(&mut out, "DTCs", &diff.dtcs);
+    section(&mut out, "ECU identification", &diff.identification);
+    if diff.is_empty() {
+        out.push_str("No differences\n");
+    } else {
+        let _ = writeln!(
+            out,
+            "{} change(s), {} breaking",
+            diff.changes().count(),
+            diff.breaking_count()
+        );
+    }
+    out
+}
+
+fn section(out: &mut String, title: &str, changes: &[Change]) {
+    if changes.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "{title}:");
+    for change in changes {
+        let breaking = if change.is_breaking() {
+            "  (breaking)"
+        } else {
+            ""
+        };
+        let _ = writeln!(out, "  {change}{breaking}");
+    }
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    #[test]
+    fn report_groups_changes_by_section() {
+        let old = fixture("yaml/FLXC1000.yml");
+        let a = parse_input(&old, false).unwrap();
+        let mut b = a.clone();
+        let layer = &mut b.variants[0].diag_layer;
+        let variant = layer.short_name.clone();
+        let removed = layer.diag_services.remove(0).diag_comm.short_name;
+        b.dtcs.clear();
+
+        assert_eq!(report(&diag_ir::diff_databases(&a, &a)), "No differences\n");
+        let text = report(&diag_ir::diff_databases(&a, &b));
+        assert!(
+            text.contains(&format!(
+                "Services of {variant}:\n  - {removed}  (breaking)\n"
+            )),
+            "{text}"
+        );
+        assert!(text.contains("DTCs:\n  - "), "{text}");
+        assert!(text.ends_with(" breaking\n"), "{text}");
+    }
+}
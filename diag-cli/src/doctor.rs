@@ -0,0 +1,422 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::path::Path;
+
+use crate::Format;
+use crate::formats::FormatProvider;
+
+/// Databases larger than this (FlatBuffers bytes) load noticeably faster from
+/// zstd- than from LZMA-compressed MDD files.
+const LARGE_IR_BYTES: usize = 16 * 1024 * 1024;
+
+/// Print a triage report for `input`: what the file is, whether it parses,
+/// and which flags are likely to help. Encrypted MDD input is read with
+/// `keys` when given.
+pub fn run_doctor(input: &Path, keys: Option<mdd_format::encryption::KeySource>) -> Result<()> {
+    let bytes = std::fs::read(input).with_context(|| format!("reading {}", input.display()))?;
+    print!("{}", diagnose(input, &bytes, keys));
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Report {
+    file: String,
+    size: usize,
+    by_extension: Option<Format>,
+    by_content: Option<Format>,
+    encoding: Option<String>,
+    odx_version: Option<String>,
+    /// YAML schema violations; `None` for formats without a schema check.
+    schema_violations: Option<usize>,
+    /// MDD description chunk: compression, stored and uncompressed bytes.
+    mdd_chunk: Option<(String, usize, Option<u64>)>,
+    parse: Option<Result<Summary, String>>,
+    suggestions: Vec<String>,
+    /// Key encrypted MDD input is read with (`--decrypt-key-file`).
+    decryption: Option<mdd_format::encryption::KeySource>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Summary {
+    variants: usize,
+    services: usize,
+    dtcs: usize,
+    /// Size of the FlatBuffers encoding, i.e. the uncompressed MDD payload.
+    ir_bytes: usize,
+    short_name_issues: usize,
+}
+
+impl Summary {
+    fn of(db: &diag_ir::DiagDatabase) -> Self {
+        Self {
+            variants: db.variants.len(),
+            services: db
+                .variants
+                .iter()
+                .map(|v| v.diag_layer.diag_services.len())
+                .sum(),
+            dtcs: db.dtcs.len(),
+            ir_bytes: diag_ir::ir_to_flatbuffers(db).len(),
+            short_name_issues: diag_ir::check_short_names(db).len(),
+        }
+    }
+}
+
+fn diagnose(
+    input: &Path,
+    bytes: &[u8],
+    decryption: Option<mdd_format::encryption::KeySource>,
+) -> Report {
+    let mut report = Report {
+        file: input.display().to_string(),
+        size: bytes.len(),
+        by_extension: crate::detect_format(input).ok(),
+        by_content: crate::sniff_format(bytes),
+        decryption,
+        ..Default::default()
+    };
+    let Some(format) = report.by_content.or(report.by_extension) else {
+        report.suggestions.push(format!(
+            "not an {} file; check that it is not truncated",
+            crate::formats::names()
+        ));
+        return report;
+    };
+    if let (Some(content), Some(ext)) = (report.by_content, report.by_extension) {
+        if content != ext {
+            report.suggestions.push(format!(
+                "--from {}: the content is {}, the extension says {} (or rename the file to .{})",
+                content.extension(),
+                content.name(),
+                ext.name(),
+                content.extension()
+            ));
+        }
+    }
+
+    format.provider().diagnose(input, bytes, &mut report);
+
+    if let Some(Ok(summary)) = &report.parse {
+        if summary.short_name_issues > 0 {
+            report.suggestions.push(format!(
+                "--fix-short-names: {} short name(s) break the ODX rules",
+                summary.short_name_issues
+            ));
+        }
+        if summary.ir_bytes > LARGE_IR_BYTES {
+            report.suggestions.push(
+                "--compression zstd: LZMA (the default) decompresses slowly for a database \
+                 this large"
+                    .into(),
+            );
+        }
+    }
+    report
+}
+
+/// Parse with the format's own reader and record the outcome.
+pub(crate) fn diagnose_parse<P: FormatProvider + ?Sized>(
+    provider: &P,
+    input: &Path,
+    report: &mut Report,
+) {
+    report.parse = Some(
+        provider
+            .parse(input, &diag_odx::ParseOptions::default())
+            .map(|db| Summary::of(&db))
+            .map_err(|e| format!("{e:#}")),
+    );
+}
+
+pub(crate) fn diagnose_odx(bytes: &[u8], report: &mut Report) {
+    report.encoding = Some(diag_odx::detect_encoding(bytes));
+    let text = match diag_odx::decode_odx(bytes) {
+        Ok(text) => text,
+        Err(e) => {
+            report.parse = Some(Err(e.to_string()));
+            return;
+        }
+    };
+    report.odx_version = diag_odx::model_version(&text);
+    report.parse = Some(match diag_odx::parse_odx(&text) {
+        Ok(db) => Ok(Summary::of(&db)),
+        Err(e) => {
+            if let Ok(db) = diag_odx::parse_odx_lenient(&text) {
+                report.suggestions.push(format!(
+                    "--lenient: the file loads ({} variant(s)) when malformed entries are \
+                     skipped",
+                    db.variants.len()
+                ));
+            }
+            Err(e.to_string())
+        }
+    });
+}
+
+pub(crate) fn diagnose_pdx(input: &Path, report: &mut Report) {
+    match diag_odx::read_pdx_entries(input) {
+        Ok(entries) => {
+            if let Some((_, bytes)) = entries.first() {
+                report.encoding = Some(diag_odx::detect_encoding(bytes));
+            }
+            report.odx_version = entries
+                .iter()
+                .find_map(|(_, bytes)| diag_odx::model_version(&diag_odx::decode_odx(bytes).ok()?));
+        }
+        Err(e) => {
+            report.parse = Some(Err(e.to_string()));
+            return;
+        }
+    }
+    report.parse = Some(
+        diag_odx::read_pdx_file(input)
+            .map(|db| Summary::of(&db))
+            .map_err(|e| e.to_string()),
+    );
+}
+
+pub(crate) fn diagnose_yaml(bytes: &[u8], report: &mut Report) {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => {
+            report.encoding = Some("not UTF-8".into());
+            report.parse = Some(Err(format!("YAML input must be UTF-8: {e}")));
+            return;
+        }
+    };
+    report.encoding = Some(if text.starts_with('\u{feff}') {
+        "UTF-8 (BOM)".into()
+    } else {
+        "UTF-8".into()
+    });
+    report.schema_violations = Some(
+        diag_yaml::validate_yaml_schema(text)
+            .err()
+            .map_or(0, |errors| errors.len()),
+    );
+    if report.schema_violations > Some(0) {
+        report
+            .suggestions
+            .push("run `validate` to list the schema violations".into());
+    }
+    report.parse = Some(
+        diag_yaml::parse_yaml(text)
+            .map(|db| Summary::of(&db))
+            .map_err(|e| e.to_string()),
+    );
+}
+
+pub(crate) fn diagnose_cdd(bytes: &[u8], report: &mut Report) {
+    report.encoding = Some(diag_odx::detect_encoding(bytes));
+    report.parse = Some(match diag_odx::decode_odx(bytes) {
+        Ok(text) => diag_cdd::parse_cdd(&text)
+            .map(|db| Summary::of(&db))
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    });
+}
+
+pub(crate) fn diagnose_json(bytes: &[u8], report: &mut Report) {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => {
+            report.encoding = Some("not UTF-8".into());
+            report.parse = Some(Err(format!("JSON input must be UTF-8: {e}")));
+            return;
+        }
+    };
+    report.encoding = Some("UTF-8".into());
+    report.parse = Some(
+        diag_ir::parse_json(text)
+            .map(|db| Summary::of(&db))
+            .map_err(|e| e.to_string()),
+    );
+}
+
+pub(crate) fn diagnose_mdd(bytes: &[u8], report: &mut Report) {
+    use mdd_format::fileformat::chunk::DataType;
+
+    if let Ok(container) = mdd_format::reader::read_mdd_container(bytes) {
+        if let Some(chunk) = container
+            .chunks
+            .iter()
+            .find(|c| c.r#type == DataType::DiagnosticDescription as i32)
+        {
+            let compression = chunk
+                .compression_algorithm
+                .clone()
+                .filter(|c| !c.is_empty())
+                .unwrap_or_else(|| "none".into());
+            if compression == "none" {
+                report
+                    .suggestions
+                    .push("`repack --compression lzma` to shrink the file".into());
+            }
+            let stored = chunk.data.as_ref().map_or(0, Vec::len);
+            report.mdd_chunk = Some((compression, stored, chunk.uncompressed_size));
+        }
+    }
+    let read = match &report.decryption {
+        Some(keys) => mdd_format::reader::read_mdd_bytes_with_key(bytes, keys),
+        None => mdd_format::reader::read_mdd_bytes(bytes),
+    };
+    if matches!(read, Err(mdd_format::reader::MddReadError::Encrypted)) {
+        report
+            .suggestions
+            .push("--decrypt-key-file: the description is encrypted".into());
+    } else if let Ok(recovery) = mdd_format::reader::recover_mdd_bytes(bytes) {
+        // Recovery cannot decrypt, so encrypted chunks read as damaged.
+        if recovery.is_damaged() && report.decryption.is_none() {
+            report.suggestions.push(
+                "--force: the file is damaged; convert what is readable, with a warning per \
+                 damaged part"
+                    .into(),
+            );
+        }
+    }
+    report.parse = Some(
+        read.map_err(|e| e.to_string())
+            .and_then(|(_, fbs)| diag_ir::flatbuffers_to_ir(&fbs).map_err(|e| e.to_string()))
+            .map(|db| Summary::of(&db)),
+    );
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |format: Option<Format>| format.map_or("unknown", Format::name);
+        writeln!(f, "File:        {} ({} bytes)", self.file, self.size)?;
+        writeln!(
+            f,
+            "Format:      {} (by content; by extension: {})",
+            name(self.by_content),
+            name(self.by_extension)
+        )?;
+        if let Some(encoding) = &self.encoding {
+            writeln!(f, "Encoding:    {encoding}")?;
+        }
+        if let Some(version) = &self.odx_version {
+            writeln!(f, "ODX version: {version}")?;
+        }
+        if let Some(count) = self.schema_violations {
+            writeln!(f, "Schema:      {count} violation(s)")?;
+        }
+        if let Some((compression, stored, uncompressed)) = &self.mdd_chunk {
+            let uncompressed = uncompressed.map_or("unknown".into(), |n| n.to_string());
+            writeln!(
+                f,
+                "Payload:     {compression}, {stored} bytes stored, {uncompressed} uncompressed"
+            )?;
+        }
+        match &self.parse {
+            Some(Ok(s)) => {
+                writeln!(
+                    f,
+                    "Parse:       ok ({} variant(s), {} service(s), {} DTC(s))",
+                    s.variants, s.services, s.dtcs
+                )?;
+                writeln!(
+                    f,
+                    "IR size:     {} bytes (FlatBuffers, uncompressed)",
+                    s.ir_bytes
+                )?;
+            }
+            Some(Err(e)) => writeln!(f, "Parse:       failed: {e}")?,
+            None => {}
+        }
+        if self.suggestions.is_empty() {
+            writeln!(f, "Suggestions: none")?;
+        } else {
+            writeln!(f, "Suggestions:")?;
+            for s in &self.suggestions {
+                writeln!(f, "  - {s}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    #[test]
+    fn odx_with_wrong_extension_is_recognized() {
+        let report = diagnose(
+            Path::new("ecu.yml"),
+            &std::fs::read(fixture("odx/minimal.odx")).unwrap(),
+            None,
+        );
+        assert_eq!(report.by_content, Some(Format::ODX));
+        assert_eq!(report.by_extension, Some(Format::YAML));
+        assert_eq!(report.odx_version.as_deref(), Some("2.2.0"));
+        assert!(matches!(report.parse, Some(Ok(_))));
+        let text = report.to_string();
+        assert!(
+            text.contains("Format:      ODX (by content; by extension: YAML)"),
+            "{text}"
+        );
+        assert!(text.contains("--from odx"), "{text}");
+    }
+
+    #[test]
+    fn yaml_schema_violations_are_counted() {
+        let yaml = b"schema: \"opensovd.cda.diagdesc/v1\"\necu:\n  name: \"TEST\"\n  bogus: 1\n";
+        let report = diagnose(Path::new("ecu.yml"), yaml, None);
+        assert_eq!(report.by_content, Some(Format::YAML));
+        assert_eq!(report.encoding.as_deref(), Some("UTF-8"));
+        assert!(report.schema_violations > Some(0));
+        assert!(report.suggestions.iter().any(|s| s.contains("validate")));
+    }
+
+    #[test]
+    fn mdd_payload_is_described() {
+        let report = diagnose(
+            Path::new("blob"),
+            &std::fs::read(fixture("mdd/FLXCNG1000.mdd")).unwrap(),
+            None,
+        );
+        assert_eq!(report.by_content, Some(Format::MDD));
+        let (compression, stored, _) = report.mdd_chunk.as_ref().unwrap();
+        assert_eq!(compression, "lzma");
+        assert!(*stored > 0);
+        assert!(report.suggestions.is_empty());
+    }
+
+    /// A format without a doctor of its own, as a format from another crate
+    /// would register it.
+    struct Plain;
+
+    impl FormatProvider for Plain {
+        fn name(&self) -> &'static str {
+            "PLAIN"
+        }
+
+        fn extensions(&self) -> &'static [&'static str] {
+            &["plain"]
+        }
+
+        fn parse(&self, input: &Path, _: &diag_odx::ParseOptions) -> Result<diag_ir::DiagDatabase> {
+            let text = std::fs::read_to_string(input)?;
+            Ok(diag_yaml::parse_yaml(&text)?)
+        }
+    }
+
+    #[test]
+    fn formats_without_a_doctor_report_whether_they_parse() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("ecu.plain");
+        std::fs::write(
+            &input,
+            std::fs::read(fixture("yaml/minimal-ecu.yml")).unwrap(),
+        )
+        .unwrap();
+        let mut report = Report::default();
+        Format(&Plain).provider().diagnose(&input, &[], &mut report);
+        assert!(matches!(report.parse, Some(Ok(_))), "{report:?}");
+
+        std::fs::write(&input, "ecu: [").unwrap();
+        Format(&Plain).provider().diagnose(&input, &[], &mut report);
+        assert!(matches!(report.parse, Some(Err(_))), "{report:?}");
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{Context, Result, bail};
+use std::fmt::Write as _;
+use std::path::Path;
+
+use diag_ir::DtcText;
+
+use crate::convert::parse_input;
+use crate::export::{csv_field, write_report};
+
+const HEADER: &str = "trouble_code,display_trouble_code,ti,source,text";
+
+/// Write the DTC texts of `input` with their `lang` translations as CSV to
+/// `output`, or to stdout when no output is given.
+pub fn run_export(input: &Path, lang: &str, output: Option<&Path>) -> Result<()> {
+    let db = parse_input(input, false)?;
+    let texts = diag_ir::dtc_texts(&db, lang);
+    let translated = texts.iter().filter(|t| !t.text.is_empty()).count();
+    write_report(&to_csv(&texts), output, || {
+        format!(
+            "{} DTC text(s), {translated} translated to '{lang}', from {}",
+            texts.len(),
+            input.display()
+        )
+    })
+}
+
+/// Read a CSV written by [`run_export`]. Only the `trouble_code`, `ti` and
+/// `text` columns are used; columns may be reordered and others added.
+pub fn read_csv(path: &Path) -> Result<Vec<DtcText>> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let mut records = parse_csv(content.trim_start_matches('\u{feff}'))
+        .with_context(|| format!("parsing {}", path.display()))?
+        .into_iter();
+    let header = records.next().unwrap_or_default();
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim() == name)
+            .with_context(|| format!("{}: missing column '{name}'", path.display()))
+    };
+    let (code, ti, text) = (column("trouble_code")?, column("ti")?, column("text")?);
+    let display = column("display_trouble_code").ok();
+    let source = column("source").ok();
+
+    records
+        .enumerate()
+        .filter(|(_, record)| record.iter().any(|f| !f.is_empty()))
+        .map(|(i, record)| {
+            let field = |i: usize| record.get(i).cloned().unwrap_or_default();
+            let field_opt = |i: Option<usize>| i.map(field).unwrap_or_default();
+            Ok(DtcText {
+                trouble_code: parse_trouble_code(&field(code))
+                    .with_context(|| format!("{}: record {}", path.display(), i + 1))?,
+                display_trouble_code: field_opt(display),
+                ti: field(ti),
+                source: field_opt(source),
+                text: field(text),
+            })
+        })
+        .collect()
+}
+
+fn to_csv(texts: &[DtcText]) -> String {
+    let mut csv = format!("{HEADER}\n");
+    for t in texts {
+        let _ = writeln!(
+            csv,
+            "0x{:06X},{},{},{},{}",
+            t.trouble_code,
+            csv_field(&t.display_trouble_code),
+            csv_field(&t.ti),
+            csv_field(&t.source),
+            csv_field(&t.text)
+        );
+    }
+    csv
+}
+
+fn parse_trouble_code(s: &str) -> Result<u32> {
+    diag_ir::parse_uint(s)
+        .and_then(|v| u32::try_from(v).ok())
+        .with_context(|| format!("invalid trouble code '{}'", s.trim()))
+}
+
+/// Split RFC 4180 CSV into records. Quoted fields may contain separators,
+/// doubled quotes and line breaks.
+pub fn parse_csv(text: &str) -> Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        bail!("unterminated quoted field");
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{ConvertOptions, run_convert};
+
+    #[test]
+    fn parse_quoted_fields() {
+        let records = parse_csv("a,\"b,\"\"c\"\"\"\r\n\"multi\nline\",\n").unwrap();
+        assert_eq!(records, [vec!["a", "b,\"c\""], vec!["multi\nline", ""]]);
+        assert!(parse_csv("\"open").is_err());
+    }
+
+    #[test]
+    fn translated_texts_are_merged_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("ecu.yml");
+        std::fs::write(
+            &input,
+            r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dtcs:
+  0x012300:
+    name: ThrottleHigh
+    sae: P0123
+    description: "Throttle Position Sensor, Circuit High"
+    text_id: TI_P0123
+  0x012400:
+    name: ThrottleLow
+    sae: P0124
+    description: "Throttle Position Sensor, Circuit Low"
+"#,
+        )
+        .unwrap();
+
+        let csv = dir.path().join("texts_de.csv");
+        run_export(&input, "de", Some(&csv)).unwrap();
+        let exported = std::fs::read_to_string(&csv).unwrap();
+        assert_eq!(
+            exported,
+            "trouble_code,display_trouble_code,ti,source,text\n\
+             0x012300,P0123,TI_P0123,\"Throttle Position Sensor, Circuit High\",\n\
+             0x012400,P0124,,\"Throttle Position Sensor, Circuit Low\",\n"
+        );
+
+        std::fs::write(
+            &csv,
+            exported.replacen(
+                "High\",\n",
+                "High\",\"Drosselklappensensor, Stromkreis hoch\"\n",
+                1,
+            ),
+        )
+        .unwrap();
+        let output = dir.path().join("ecu_de.yml");
+        let opts = ConvertOptions {
+            dtc_texts: Some(("de".into(), csv.clone())),
+            ..Default::default()
+        };
+        run_convert(&input, &output, &opts).unwrap();
+
+        let yaml = std::fs::read_to_string(&output).unwrap();
+        assert!(
+            yaml.contains("de: Drosselklappensensor, Stromkreis hoch"),
+            "{yaml}"
+        );
+        let mdd = dir.path().join("ecu_de.mdd");
+        run_convert(&output, &mdd, &ConvertOptions::default()).unwrap();
+        let texts = read_csv(&csv).unwrap();
+        let db = parse_input(&mdd, false).unwrap();
+        assert_eq!(diag_ir::dtc_texts(&db, "de")[0].text, texts[0].text);
+
+        std::fs::write(&csv, "trouble_code,ti,text\n0x999999,,Unbekannt\n").unwrap();
+        let err = run_convert(&input, &output, &opts).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("No DTC 0x999999 with text ID ''"),
+            "{err:#}"
+        );
+    }
+}
@@ -0,0 +1,780 @@
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use serde::ser;
+use std::fmt::{self, Write as _};
+use std::path::Path;
+
+use crate::Format;
+use crate::convert::parse_input_with;
+
+/// How much of the IR `dump-ir` prints.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Nesting levels to expand below the printed node; deeper structs and
+    /// lists are collapsed to their name and length.
+    pub depth: Option<usize>,
+    /// List and map entries to print before eliding the rest; 0 prints all.
+    pub items: usize,
+    /// Characters of a string to print before truncating it; 0 prints all.
+    pub string: usize,
+}
+
+/// Print the IR of `input`, or the part `path` selects, indented and with
+/// the names of structs and enum variants.
+pub fn run_dump_ir(
+    input: &Path,
+    from: Option<Format>,
+    path: Option<&str>,
+    limits: &Limits,
+) -> Result<()> {
+    let db = parse_input_with(input, from, &diag_odx::ParseOptions::default())?;
+    let root = db.serialize(NodeSerializer)?;
+    let node = match path {
+        Some(path) => select(&root, path)?,
+        None => &root,
+    };
+    println!("{}", render(node, limits));
+    Ok(())
+}
+
+/// The IR as serde sees it, keeping the type names `serde_json` drops.
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    /// `()`, a unit struct or a unit enum variant.
+    Unit(&'static str),
+    None,
+    Bool(bool),
+    Number(String),
+    Str(String),
+    Seq(Vec<Node>),
+    Map(Vec<(Node, Node)>),
+    /// A tuple struct or a newtype or tuple enum variant.
+    Tuple(&'static str, Vec<Node>),
+    /// A struct or a struct enum variant.
+    Struct(&'static str, Vec<(&'static str, Node)>),
+}
+
+impl Node {
+    fn is_scalar(&self) -> bool {
+        matches!(
+            self,
+            Node::Unit(_) | Node::None | Node::Bool(_) | Node::Number(_)
+        )
+    }
+
+    fn kind(&self) -> String {
+        match self {
+            Node::Unit(name) | Node::Tuple(name, _) | Node::Struct(name, _) => (*name).to_string(),
+            Node::None => "None".into(),
+            Node::Bool(_) => "a bool".into(),
+            Node::Number(_) => "a number".into(),
+            Node::Str(_) => "a string".into(),
+            Node::Seq(items) => format!("a list of {} items", items.len()),
+            Node::Map(entries) => format!("a map of {} entries", entries.len()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DumpError(String);
+
+impl fmt::Display for DumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for DumpError {}
+
+impl ser::Error for DumpError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+struct NodeSerializer;
+
+impl ser::Serializer for NodeSerializer {
+    type Ok = Node;
+    type Error = DumpError;
+    type SerializeSeq = SeqBuilder;
+    type SerializeTuple = SeqBuilder;
+    type SerializeTupleStruct = SeqBuilder;
+    type SerializeTupleVariant = SeqBuilder;
+    type SerializeMap = MapBuilder;
+    type SerializeStruct = StructBuilder;
+    type SerializeStructVariant = StructBuilder;
+
+    fn serialize_bool(self, v: bool) -> Result<Node, DumpError> {
+        Ok(Node::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Node, DumpError> {
+        Ok(Node::Number(v.to_string()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Node, DumpError> {
+        Ok(Node::Number(v.to_string()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Node, DumpError> {
+        Ok(Node::Number(v.to_string()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Node, DumpError> {
+        Ok(Node::Number(v.to_string()))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Node, DumpError> {
+        Ok(Node::Number(v.to_string()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Node, DumpError> {
+        Ok(Node::Number(v.to_string()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Node, DumpError> {
+        Ok(Node::Number(v.to_string()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Node, DumpError> {
+        Ok(Node::Number(v.to_string()))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Node, DumpError> {
+        Ok(Node::Number(v.to_string()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Node, DumpError> {
+        Ok(Node::Number(v.to_string()))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Node, DumpError> {
+        Ok(Node::Str(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Node, DumpError> {
+        Ok(Node::Str(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Node, DumpError> {
+        Ok(Node::Seq(
+            v.iter().map(|b| Node::Number(b.to_string())).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Node, DumpError> {
+        Ok(Node::None)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Node, DumpError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Node, DumpError> {
+        Ok(Node::Unit("()"))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Node, DumpError> {
+        Ok(Node::Unit(name))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Node, DumpError> {
+        Ok(Node::Unit(variant))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Node, DumpError> {
+        Ok(Node::Tuple(name, vec![value.serialize(self)?]))
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Node, DumpError> {
+        Ok(Node::Tuple(variant, vec![value.serialize(self)?]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqBuilder, DumpError> {
+        Ok(SeqBuilder::new(None, len.unwrap_or(0)))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqBuilder, DumpError> {
+        Ok(SeqBuilder::new(None, len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<SeqBuilder, DumpError> {
+        Ok(SeqBuilder::new(Some(name), len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqBuilder, DumpError> {
+        Ok(SeqBuilder::new(Some(variant), len))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapBuilder, DumpError> {
+        Ok(MapBuilder {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<StructBuilder, DumpError> {
+        Ok(StructBuilder {
+            name,
+            fields: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<StructBuilder, DumpError> {
+        Ok(StructBuilder {
+            name: variant,
+            fields: Vec::with_capacity(len),
+        })
+    }
+}
+
+struct SeqBuilder {
+    name: Option<&'static str>,
+    items: Vec<Node>,
+}
+
+impl SeqBuilder {
+    fn new(name: Option<&'static str>, len: usize) -> Self {
+        Self {
+            name,
+            items: Vec::with_capacity(len),
+        }
+    }
+
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), DumpError> {
+        self.items.push(value.serialize(NodeSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Node {
+        match self.name {
+            Some(name) => Node::Tuple(name, self.items),
+            None => Node::Seq(self.items),
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqBuilder {
+    type Ok = Node;
+    type Error = DumpError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), DumpError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, DumpError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for SeqBuilder {
+    type Ok = Node;
+    type Error = DumpError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), DumpError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, DumpError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqBuilder {
+    type Ok = Node;
+    type Error = DumpError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), DumpError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, DumpError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqBuilder {
+    type Ok = Node;
+    type Error = DumpError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), DumpError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, DumpError> {
+        Ok(self.finish())
+    }
+}
+
+struct MapBuilder {
+    entries: Vec<(Node, Node)>,
+    key: Option<Node>,
+}
+
+impl ser::SerializeMap for MapBuilder {
+    type Ok = Node;
+    type Error = DumpError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), DumpError> {
+        self.key = Some(key.serialize(NodeSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), DumpError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| DumpError("map value without a key".into()))?;
+        self.entries.push((key, value.serialize(NodeSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, DumpError> {
+        Ok(Node::Map(self.entries))
+    }
+}
+
+struct StructBuilder {
+    name: &'static str,
+    fields: Vec<(&'static str, Node)>,
+}
+
+impl StructBuilder {
+    fn push<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), DumpError> {
+        self.fields.push((key, value.serialize(NodeSerializer)?));
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for StructBuilder {
+    type Ok = Node;
+    type Error = DumpError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), DumpError> {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Node, DumpError> {
+        Ok(Node::Struct(self.name, self.fields))
+    }
+}
+
+impl ser::SerializeStructVariant for StructBuilder {
+    type Ok = Node;
+    type Error = DumpError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), DumpError> {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Node, DumpError> {
+        Ok(Node::Struct(self.name, self.fields))
+    }
+}
+
+// --- Paths ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Field(String),
+    Index(usize),
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Segment::Field(name) => f.write_str(name),
+            Segment::Index(index) => write!(f, "[{index}]"),
+        }
+    }
+}
+
+/// Parse a path like `variants[0].diag_services[3].request`.
+fn parse_path(path: &str) -> Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    for part in path.split('.') {
+        let (name, mut indices) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if !name.is_empty() {
+            segments.push(Segment::Field(name.to_string()));
+        } else if indices.is_empty() {
+            bail!("empty segment in path '{path}'");
+        }
+        while !indices.is_empty() {
+            let Some((index, rest)) = indices.strip_prefix('[').and_then(|r| r.split_once(']'))
+            else {
+                bail!("malformed index '{indices}' in path '{path}'");
+            };
+            let index = index
+                .trim()
+                .parse()
+                .with_context(|| format!("index '{index}' in path '{path}' is not a number"))?;
+            segments.push(Segment::Index(index));
+            indices = rest;
+        }
+    }
+    Ok(segments)
+}
+
+/// The node `path` selects below `root`. Fields of a layer's `diag_layer`
+/// can be named directly on the variant, functional group or ECU shared
+/// data, and newtype variants are looked through.
+fn select<'a>(root: &'a Node, path: &str) -> Result<&'a Node> {
+    let mut node = root;
+    let mut walked = String::new();
+    for segment in parse_path(path)? {
+        node = step(node, &segment).map_err(|reason| {
+            let at = if walked.is_empty() {
+                "the database".to_string()
+            } else {
+                format!("'{walked}'")
+            };
+            anyhow::anyhow!("path '{path}': {reason} in {at}")
+        })?;
+        if !walked.is_empty() && matches!(segment, Segment::Field(_)) {
+            walked.push('.');
+        }
+        let _ = write!(walked, "{segment}");
+    }
+    Ok(node)
+}
+
+fn step<'a>(node: &'a Node, segment: &Segment) -> Result<&'a Node, String> {
+    match (node, segment) {
+        (Node::Tuple(_, items), Segment::Index(index)) if items.len() > 1 => items
+            .get(*index)
+            .ok_or_else(|| format!("index {index} out of range ({} items)", items.len())),
+        (Node::Tuple(_, items), _) if items.len() == 1 => step(&items[0], segment),
+        (Node::Seq(items), Segment::Index(index)) => items
+            .get(*index)
+            .ok_or_else(|| format!("index {index} out of range ({} items)", items.len())),
+        (Node::Struct(name, fields), Segment::Field(field)) => {
+            if let Some((_, value)) = fields.iter().find(|(key, _)| key == field) {
+                return Ok(value);
+            }
+            let in_layer = fields
+                .iter()
+                .find(|(key, _)| *key == "diag_layer")
+                .and_then(|(_, layer)| step(layer, segment).ok());
+            if let Some(value) = in_layer {
+                return Ok(value);
+            }
+            let names: Vec<&str> = fields.iter().map(|(key, _)| *key).collect();
+            Err(format!(
+                "no field '{field}' in {name} (fields: {})",
+                names.join(", ")
+            ))
+        }
+        (Node::Map(entries), Segment::Field(key)) => entries
+            .iter()
+            .find(|(k, _)| match k {
+                Node::Str(k) | Node::Number(k) => k == key,
+                _ => false,
+            })
+            .map(|(_, value)| value)
+            .ok_or_else(|| format!("no key '{key}'")),
+        (node, segment) => Err(format!("cannot select '{segment}' from {}", node.kind())),
+    }
+}
+
+// --- Rendering ---
+
+fn render(node: &Node, limits: &Limits) -> String {
+    let mut out = String::new();
+    write_node(&mut out, node, 0, limits);
+    out
+}
+
+fn indent(out: &mut String, level: usize) {
+    out.extend(std::iter::repeat_n("    ", level));
+}
+
+fn write_node(out: &mut String, node: &Node, level: usize, limits: &Limits) {
+    match node {
+        Node::Unit(name) => out.push_str(name),
+        Node::None => out.push_str("None"),
+        Node::Bool(v) => {
+            let _ = write!(out, "{v}");
+        }
+        Node::Number(v) => out.push_str(v),
+        Node::Str(v) => write_string(out, v, limits.string),
+        Node::Seq(items) => write_items(out, ('[', ']'), items, level, limits),
+        Node::Tuple(name, items) => {
+            out.push_str(name);
+            if let [item] = items.as_slice() {
+                out.push('(');
+                write_node(out, item, level, limits);
+                out.push(')');
+            } else {
+                write_items(out, ('(', ')'), items, level, limits);
+            }
+        }
+        Node::Struct(name, fields) => {
+            out.push_str(name);
+            out.push(' ');
+            let fields: Vec<(String, &Node)> = fields
+                .iter()
+                .map(|(key, value)| ((*key).to_string(), value))
+                .collect();
+            write_entries(out, &fields, false, level, limits);
+        }
+        Node::Map(entries) => {
+            let entries: Vec<(String, &Node)> = entries
+                .iter()
+                .map(|(key, value)| (render(key, limits), value))
+                .collect();
+            write_entries(out, &entries, true, level, limits);
+        }
+    }
+}
+
+fn write_string(out: &mut String, value: &str, limit: usize) {
+    let chars = value.chars().count();
+    if limit == 0 || chars <= limit {
+        let _ = write!(out, "{value:?}");
+    } else {
+        let head: String = value.chars().take(limit).collect();
+        let _ = write!(out, "{head:?}.. ({chars} chars)");
+    }
+}
+
+/// Entries to print of `len` and how many are elided.
+fn shown(len: usize, limit: usize) -> (usize, usize) {
+    if limit == 0 || len <= limit {
+        (len, 0)
+    } else {
+        (limit, len - limit)
+    }
+}
+
+fn collapsed(level: usize, limits: &Limits) -> bool {
+    limits.depth.is_some_and(|depth| level >= depth)
+}
+
+fn write_items(
+    out: &mut String,
+    (open, close): (char, char),
+    items: &[Node],
+    level: usize,
+    limits: &Limits,
+) {
+    out.push(open);
+    if items.is_empty() {
+        out.push(close);
+        return;
+    }
+    if collapsed(level, limits) {
+        let _ = write!(out, ".. {} items{close}", items.len());
+        return;
+    }
+    let (count, elided) = shown(items.len(), limits.items);
+    if items.iter().all(Node::is_scalar) {
+        for (i, item) in items[..count].iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            write_node(out, item, level, limits);
+        }
+        if elided > 0 {
+            let _ = write!(out, ", .. {elided} more");
+        }
+        out.push(close);
+        return;
+    }
+    out.push('\n');
+    for item in &items[..count] {
+        indent(out, level + 1);
+        write_node(out, item, level + 1, limits);
+        out.push_str(",\n");
+    }
+    if elided > 0 {
+        indent(out, level + 1);
+        let _ = writeln!(out, ".. {elided} more");
+    }
+    indent(out, level);
+    out.push(close);
+}
+
+/// Struct fields (never elided) or map entries (elided past `limits.items`).
+fn write_entries(
+    out: &mut String,
+    entries: &[(String, &Node)],
+    elide: bool,
+    level: usize,
+    limits: &Limits,
+) {
+    if entries.is_empty() {
+        out.push_str("{}");
+        return;
+    }
+    if collapsed(level, limits) {
+        out.push_str("{ .. }");
+        return;
+    }
+    let (count, elided) = if elide {
+        shown(entries.len(), limits.items)
+    } else {
+        (entries.len(), 0)
+    };
+    out.push_str("{\n");
+    for (key, value) in &entries[..count] {
+        indent(out, level + 1);
+        let _ = write!(out, "{key}: ");
+        write_node(out, value, level + 1, limits);
+        out.push_str(",\n");
+    }
+    if elided > 0 {
+        indent(out, level + 1);
+        let _ = writeln!(out, ".. {elided} more");
+    }
+    indent(out, level);
+    out.push('}');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: Limits = Limits {
+        depth: None,
+        items: 0,
+        string: 0,
+    };
+
+    fn fixture() -> Node {
+        let input = crate::test_support::fixture("mdd/FLXCNG1000.mdd");
+        let db = crate::convert::parse_input(&input, false).unwrap();
+        db.serialize(NodeSerializer).unwrap()
+    }
+
+    #[test]
+    fn parse_path_splits_fields_and_indices() {
+        assert_eq!(
+            parse_path("variants[0].diag_services[3].request").unwrap(),
+            vec![
+                Segment::Field("variants".into()),
+                Segment::Index(0),
+                Segment::Field("diag_services".into()),
+                Segment::Index(3),
+                Segment::Field("request".into()),
+            ]
+        );
+        assert!(parse_path("variants[x]").is_err());
+        assert!(parse_path("variants..dtcs").is_err());
+        assert!(parse_path("variants[0").is_err());
+    }
+
+    #[test]
+    fn select_looks_through_diag_layer() {
+        let root = fixture();
+        let service = select(&root, "variants[0].diag_services[0]").unwrap();
+        assert!(matches!(service, Node::Struct("DiagService", _)));
+        let text = render(service, &ALL);
+        assert!(
+            text.starts_with("DiagService {\n    diag_comm: DiagComm {\n"),
+            "{text}"
+        );
+        assert!(text.ends_with("\n}"), "{text}");
+
+        let err = select(&root, "variants[0].no_such_field")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("no field 'no_such_field' in Variant"), "{err}");
+        assert!(err.contains("in 'variants[0]'"), "{err}");
+        let err = select(&root, "variants[9999]").unwrap_err().to_string();
+        assert!(err.contains("index 9999 out of range"), "{err}");
+    }
+
+    #[test]
+    fn render_shows_enum_names_and_truncates() {
+        let node = Node::Struct(
+            "Param",
+            vec![
+                (
+                    "short_name",
+                    Node::Str("VehicleIdentificationNumber".into()),
+                ),
+                ("byte_position", Node::None),
+                ("kind", Node::Unit("CodedConst")),
+                (
+                    "values",
+                    Node::Seq((1..=5).map(|i| Node::Number(i.to_string())).collect()),
+                ),
+                (
+                    "data",
+                    Node::Tuple("Value", vec![Node::Struct("ValueData", vec![])]),
+                ),
+            ],
+        );
+        let limits = Limits {
+            depth: None,
+            items: 3,
+            string: 7,
+        };
+        assert_eq!(
+            render(&node, &limits),
+            "Param {\n    short_name: \"Vehicle\".. (27 chars),\n    byte_position: None,\n    \
+             kind: CodedConst,\n    values: [1, 2, 3, .. 2 more],\n    \
+             data: Value(ValueData {}),\n}"
+        );
+
+        let shallow = Limits {
+            depth: Some(1),
+            ..ALL
+        };
+        let nested = Node::Struct(
+            "Outer",
+            vec![(
+                "inner",
+                Node::Struct("Inner", vec![("a", Node::Bool(true))]),
+            )],
+        );
+        assert_eq!(
+            render(&nested, &shallow),
+            "Outer {\n    inner: Inner { .. },\n}"
+        );
+    }
+}
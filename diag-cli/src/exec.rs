@@ -0,0 +1,422 @@
+//! Run one database service against a live ECU.
+
+use anyhow::{Context, Result, bail};
+use diag_ir::{DiagDatabase, DiagService, ParamType, PreConditionStateRef, find_by_name};
+use diag_runtime::variant_detect::request_bytes;
+use diag_runtime::{NamedValue, Value, encode_request};
+use diag_transport::{Transport, TransportOptions, TransportSpec};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::convert::parse_input;
+
+/// Computes the security key for `(level, seed)`.
+pub type KeySource<'a> = dyn FnMut(u32, &[u8]) -> Result<Vec<u8>> + 'a;
+
+/// Settings of one `exec` invocation.
+pub struct ExecOptions {
+    pub service: String,
+    /// `doip:` or `isotp:` spec; without one the DoIP entity named by the
+    /// database's transport section is used.
+    pub transport: Option<String>,
+    /// Tester and ECU logical addresses, overriding the database's.
+    pub tester_addr: Option<u16>,
+    pub ecu_addr: Option<u16>,
+    /// `NAME=VALUE` request parameters.
+    pub params: Vec<String>,
+    /// Program computing security keys, called as `<program> <level> <seed hex>`;
+    /// without one the key is prompted for on stdin.
+    pub key_command: Option<PathBuf>,
+    pub skip_preconditions: bool,
+}
+
+pub fn run_exec(db_path: &Path, opts: &ExecOptions) -> Result<()> {
+    let mut db = parse_input(db_path, false)?;
+    diag_ir::complete_transport(&mut db);
+    let (spec, options) =
+        connection(&db, opts).with_context(|| format!("no transport for {}", db_path.display()))?;
+    let mut transport =
+        diag_transport::open(&spec, &options).with_context(|| format!("connecting to {spec}"))?;
+    let mut key_source = |level: u32, seed: &[u8]| match &opts.key_command {
+        Some(cmd) => key_from_command(cmd, level, seed),
+        None => key_from_prompt(level, seed),
+    };
+    let lines = execute(&db, opts, transport.as_mut(), &mut key_source)?;
+    let mut stdout = std::io::stdout().lock();
+    for line in lines {
+        writeln!(stdout, "{line}")?;
+    }
+    Ok(())
+}
+
+/// Where and how to connect: the command-line options where given, the
+/// database's transport section otherwise.
+fn connection(db: &DiagDatabase, opts: &ExecOptions) -> Result<(TransportSpec, TransportOptions)> {
+    let config = db.transport.clone().unwrap_or_default();
+    let spec = match &opts.transport {
+        Some(transport) => transport.parse()?,
+        None => TransportSpec::from_config(&config)
+            .context("the database names no DoIP IP address; pass --transport")?,
+    };
+    let mut options = TransportOptions::from_config(&config);
+    if let Some(tester) = opts.tester_addr {
+        options.tester_address = tester;
+    }
+    if opts.ecu_addr.is_some() {
+        options.ecu_address = opts.ecu_addr;
+    }
+    Ok((spec, options))
+}
+
+/// Establish the service's preconditions, send it and return the decoded
+/// positive response as `NAME = value` lines.
+pub fn execute(
+    db: &DiagDatabase,
+    opts: &ExecOptions,
+    transport: &mut dyn Transport,
+    key_source: &mut KeySource,
+) -> Result<Vec<String>> {
+    let service = find_service(db, &opts.service)
+        .with_context(|| format!("Unknown service '{}'", opts.service))?;
+    let values = parse_params(&opts.params)?;
+    let request = encode_request(service, &values)
+        .with_context(|| format!("encoding request of {}", opts.service))?;
+
+    if !opts.skip_preconditions {
+        let refs = &service.diag_comm.pre_condition_state_refs;
+        if let Some(session) = refs.iter().find(|r| r.value == "Session") {
+            enter_session(db, session, transport)?;
+        }
+        if let Some(security) = refs.iter().find(|r| r.value == "SecurityAccess") {
+            unlock(db, security, transport, key_source)?;
+        }
+        if refs.iter().any(|r| r.value == "Authentication") {
+            log::warn!(
+                "{} requires authentication, which exec does not perform",
+                opts.service
+            );
+        }
+    }
+
+    let response = transport.request(&request)?;
+    if let [0x7F, _, nrc, ..] = response.as_slice() {
+        bail!("{}: negative response 0x{nrc:02X}", opts.service);
+    }
+    let params: Vec<_> = service
+        .pos_responses
+        .iter()
+        .flat_map(|r| &r.params)
+        .filter(|p| p.param_type == ParamType::Value)
+        .collect();
+    if params.is_empty() {
+        return Ok(vec![format!("response = {}", hex(&response))]);
+    }
+    let decoded = diag_runtime::decode_response(service, &response)
+        .with_context(|| format!("decoding response of {}", opts.service))?;
+    Ok(params
+        .into_iter()
+        .map(
+            |p| match diag_runtime::value::find(&decoded.params, &p.short_name) {
+                Some(v) => format!("{} = {v}", p.short_name),
+                None => format!("{} = <missing>", p.short_name),
+            },
+        )
+        .collect())
+}
+
+/// The service by short name or alias in any case, preferring the base
+/// variant's definition.
+fn find_service<'a>(db: &'a DiagDatabase, name: &str) -> Option<&'a DiagService> {
+    let mut variants: Vec<_> = db.variants.iter().collect();
+    variants.sort_by_key(|v| !v.is_base_variant);
+    let services = variants
+        .into_iter()
+        .flat_map(|v| &v.diag_layer.diag_services)
+        .filter(|s| s.request.is_some());
+    find_by_name(services, name, |s| &s.diag_comm)
+}
+
+/// `NAME=VALUE` params; values read as numbers where they are ones, see
+/// [`Value::parse`].
+fn parse_params(params: &[String]) -> Result<Vec<NamedValue>> {
+    params
+        .iter()
+        .map(|p| match p.split_once('=') {
+            Some((name, value)) => Ok(NamedValue::new(name.trim(), Value::parse(value.trim()))),
+            None => bail!("Invalid --param '{p}' (expected NAME=VALUE)"),
+        })
+        .collect()
+}
+
+/// Switch to the session via its `<state>_Start` service, falling back to a
+/// bare 0x10 request with the session ID.
+fn enter_session(
+    db: &DiagDatabase,
+    session: &PreConditionStateRef,
+    transport: &mut dyn Transport,
+) -> Result<()> {
+    let name = &session.in_param_path_short_name;
+    let request = find_service(db, &format!("{name}_Start"))
+        .and_then(request_bytes)
+        .or_else(|| {
+            let id = session
+                .state
+                .as_ref()?
+                .long_name
+                .as_ref()?
+                .value
+                .parse()
+                .ok()?;
+            Some(vec![0x10, id])
+        })
+        .with_context(|| format!("No way to enter session {name}"))?;
+    expect_positive(&format!("session {name}"), &transport.request(&request)?)
+}
+
+/// Unlock the security level with seed/key (0x27), sending the seed request
+/// of `RequestSeed_<state>` if the database has one; the key subfunction is
+/// the seed subfunction + 1. A zero seed means the level is already unlocked.
+fn unlock(
+    db: &DiagDatabase,
+    security: &PreConditionStateRef,
+    transport: &mut dyn Transport,
+    key_source: &mut KeySource,
+) -> Result<()> {
+    let name = &security.in_param_path_short_name;
+    let level: u32 = security
+        .state
+        .as_ref()
+        .and_then(|s| s.long_name.as_ref())
+        .and_then(|l| l.value.parse().ok())
+        .or_else(|| name.strip_prefix("Level_")?.parse().ok())
+        .with_context(|| format!("Unknown security level {name}"))?;
+    let seed_request = find_service(db, &format!("RequestSeed_{name}"))
+        .and_then(request_bytes)
+        .or_else(|| {
+            Some(vec![
+                0x27,
+                u8::try_from(level.checked_mul(2)?.checked_sub(1)?).ok()?,
+            ])
+        })
+        .with_context(|| format!("Security level {level} out of range"))?;
+    let Some(&seed_sub) = seed_request.get(1) else {
+        bail!("Seed request of level {level} has no subfunction");
+    };
+
+    let response = transport.request(&seed_request)?;
+    expect_positive(&format!("seed request for level {level}"), &response)?;
+    let seed = response.get(2..).unwrap_or_default();
+    if seed.iter().all(|&b| b == 0) {
+        return Ok(());
+    }
+    let mut request = vec![0x27, seed_sub.wrapping_add(1)];
+    request.extend(key_source(level, seed)?);
+    expect_positive(
+        &format!("key for level {level}"),
+        &transport.request(&request)?,
+    )
+}
+
+fn expect_positive(what: &str, response: &[u8]) -> Result<()> {
+    match response {
+        [0x7F, _, nrc, ..] => bail!("{what}: negative response 0x{nrc:02X}"),
+        [] => bail!("{what}: empty response"),
+        _ => Ok(()),
+    }
+}
+
+fn key_from_command(cmd: &Path, level: u32, seed: &[u8]) -> Result<Vec<u8>> {
+    let output = std::process::Command::new(cmd)
+        .arg(level.to_string())
+        .arg(hex(seed).replace(' ', ""))
+        .output()
+        .with_context(|| format!("running {}", cmd.display()))?;
+    if !output.status.success() {
+        bail!("{} failed: {}", cmd.display(), output.status);
+    }
+    parse_key(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn key_from_prompt(level: u32, seed: &[u8]) -> Result<Vec<u8>> {
+    eprint!(
+        "Seed for security level {level}: {}\nKey (hex): ",
+        hex(seed)
+    );
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    parse_key(&line)
+}
+
+fn parse_key(s: &str) -> Result<Vec<u8>> {
+    match diag_ir::parse_hex_bytes(s) {
+        Some(key) if !key.is_empty() => Ok(key),
+        _ => bail!("Invalid key '{}'", s.trim()),
+    }
+}
+
+/// Parse a DoIP logical address (`0x0E00` or decimal) for clap.
+pub fn parse_address(s: &str) -> Result<u16, String> {
+    diag_ir::transport::parse_address(s).ok_or_else(|| format!("invalid logical address '{s}'"))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diag_transport::{DoipClient, DoipServer};
+    use std::net::TcpListener;
+
+    const ECU_YAML: &str = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+sessions:
+  default:
+    id: 0x01
+  extended:
+    id: 0x03
+security:
+  level_01:
+    level: 1
+    seed_request: 0x01
+    key_send: 0x02
+    seed_size: 2
+    key_size: 2
+services:
+  diagnosticSessionControl:
+    enabled: true
+  securityAccess:
+    enabled: true
+  readDataByIdentifier:
+    enabled: true
+access_patterns:
+  locked:
+    sessions: [extended]
+    security: [level_01]
+    authentication: none
+dids:
+  0xF190:
+    name: VIN
+    type:
+      base: ascii
+      length: 3
+    access: locked
+"#;
+
+    /// An ECU that only reveals the VIN in the extended session after the
+    /// key `seed ^ 0xFFFF` was sent.
+    fn spawn_ecu() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut server = DoipServer::new(stream, 0x1000);
+            let (mut session, mut unlocked) = (1, false);
+            while let Ok(request) = server.receive() {
+                let response = match request.as_slice() {
+                    [0x10, id] => {
+                        session = *id;
+                        vec![0x50, *id]
+                    }
+                    [0x27, 0x01] => vec![0x67, 0x01, 0x12, 0x34],
+                    [0x27, 0x02, 0xED, 0xCB] => {
+                        unlocked = true;
+                        vec![0x67, 0x02]
+                    }
+                    [0x27, ..] => vec![0x7F, 0x27, 0x35],
+                    [0x22, 0xF1, 0x90] if session == 3 && unlocked => b"\x62\xF1\x90WVW".to_vec(),
+                    [0x22, ..] => vec![0x7F, 0x22, 0x33],
+                    _ => vec![0x7F, request[0], 0x11],
+                };
+                server.send(&response).unwrap();
+            }
+        });
+        addr
+    }
+
+    fn opts(service: &str) -> ExecOptions {
+        ExecOptions {
+            service: service.into(),
+            transport: None,
+            tester_addr: None,
+            ecu_addr: None,
+            params: vec![],
+            key_command: None,
+            skip_preconditions: false,
+        }
+    }
+
+    #[test]
+    fn exec_establishes_session_and_security() {
+        let db = diag_yaml::parse_yaml(ECU_YAML).unwrap();
+        let mut client = DoipClient::connect(&spawn_ecu(), &TransportOptions::default()).unwrap();
+        let mut seeds = Vec::new();
+        let mut key_source = |level: u32, seed: &[u8]| {
+            seeds.push((level, seed.to_vec()));
+            Ok(seed.iter().map(|b| b ^ 0xFF).collect())
+        };
+        let lines = execute(&db, &opts("VIN_Read"), &mut client, &mut key_source).unwrap();
+        assert_eq!(lines, ["VIN = WVW"]);
+        assert_eq!(seeds, [(1, vec![0x12, 0x34])]);
+    }
+
+    #[test]
+    fn exec_reports_negative_responses() {
+        let db = diag_yaml::parse_yaml(ECU_YAML).unwrap();
+        let mut client = DoipClient::connect(&spawn_ecu(), &TransportOptions::default()).unwrap();
+        let mut key_source = |_: u32, _: &[u8]| Ok(vec![0, 0]);
+        let unchecked = ExecOptions {
+            skip_preconditions: true,
+            ..opts("VIN_Read")
+        };
+        let err = execute(&db, &unchecked, &mut client, &mut key_source).unwrap_err();
+        assert_eq!(err.to_string(), "VIN_Read: negative response 0x33");
+
+        let err = execute(&db, &opts("Nope"), &mut client, &mut key_source).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown service 'Nope'");
+    }
+
+    #[test]
+    fn connection_falls_back_to_the_database() {
+        let mut db = DiagDatabase::default();
+        assert!(connection(&db, &opts("VIN_Read")).is_err());
+
+        db.transport = Some(diag_ir::TransportConfig {
+            doip: Some(diag_ir::DoipConfig {
+                ip: Some("10.0.0.5".into()),
+                logical_address: Some(0x1000),
+                tester_address: Some(0x0E80),
+                ..Default::default()
+            }),
+        });
+        let (spec, options) = connection(&db, &opts("VIN_Read")).unwrap();
+        assert_eq!(spec.to_string(), "doip:10.0.0.5:13400");
+        assert_eq!(options.tester_address, 0x0E80);
+        assert_eq!(options.ecu_address, Some(0x1000));
+
+        let explicit = ExecOptions {
+            transport: Some("doip:127.0.0.1".into()),
+            tester_addr: Some(0x0E00),
+            ..opts("VIN_Read")
+        };
+        let (spec, options) = connection(&db, &explicit).unwrap();
+        assert_eq!(spec.to_string(), "doip:127.0.0.1:13400");
+        assert_eq!(options.tester_address, 0x0E00);
+        assert_eq!(options.ecu_address, Some(0x1000));
+    }
+
+    #[test]
+    fn parse_key_accepts_spaced_and_prefixed_hex() {
+        assert_eq!(parse_key("ED CB\n").unwrap(), [0xED, 0xCB]);
+        assert_eq!(parse_key("0xedcb").unwrap(), [0xED, 0xCB]);
+        assert!(parse_key("EDC").is_err());
+        assert!(parse_key("").is_err());
+    }
+}
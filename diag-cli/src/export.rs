@@ -0,0 +1,383 @@
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::path::Path;
+
+use diag_ir::SearchKind;
+
+use crate::Format;
+use crate::convert::parse_input;
+
+/// Write the requirement trace matrix of `input` as CSV to `output`, or to
+/// stdout when no output is given.
+pub fn run_trace_matrix(input: &Path, output: Option<&Path>) -> Result<()> {
+    let db = parse_input(input, false)?;
+    let links = diag_ir::trace_links(&db);
+    let csv = trace_matrix_csv(&links);
+    write_report(&csv, output, || {
+        format!("{} trace link(s) from {}", links.len(), input.display())
+    })
+}
+
+/// Write the flash sequence of `input` resolved to services, as Markdown
+/// documentation or a JSON plan.
+pub fn run_flash_plan(input: &Path, format: &str, output: Option<&Path>) -> Result<()> {
+    let db = parse_input(input, false)?;
+    let plan = match diag_ir::flash_plan(&db) {
+        Ok(plan) => plan,
+        Err(errors) => {
+            for e in &errors {
+                eprintln!("  {e}");
+            }
+            bail!(
+                "{} unresolved flash sequence step(s) in {}",
+                errors.len(),
+                input.display()
+            );
+        }
+    };
+    if plan.is_empty() {
+        bail!("{} has no flash_sequence", input.display());
+    }
+    let text = match format {
+        "markdown" | "md" => flash_plan_markdown(&db.ecu_name, &plan),
+        "json" => serde_json::to_string_pretty(&plan)? + "\n",
+        other => bail!("Unsupported flash plan format: {other} (expected markdown or json)"),
+    };
+    write_report(&text, output, || {
+        format!("{}-step flash plan from {}", plan.len(), input.display())
+    })
+}
+
+/// One object of `export idmap`: its ODX ID (ODX and PDX input only) and
+/// its path in the FlatBuffers description of MDD output.
+#[derive(Debug, Serialize)]
+struct IdMapRow {
+    kind: &'static str,
+    layer: String,
+    short_name: String,
+    odx_id: String,
+    mdd_index: String,
+}
+
+/// Write the mapping between the ODX IDs, short names and MDD object indices
+/// of `input` as CSV or JSON.
+pub fn run_id_map(input: &Path, format: &str, output: Option<&Path>) -> Result<()> {
+    let in_fmt = crate::detect_input_format(input, None).context("input file")?;
+    let db = parse_input(input, false)?;
+    let odx_ids = if in_fmt == Format::ODX || in_fmt == Format::PDX {
+        diag_odx::load_odx_ids(&[input.to_path_buf()])?
+    } else {
+        vec![]
+    };
+    let rows = id_map(&db, &odx_ids);
+    let text = match format {
+        "csv" => id_map_csv(&rows),
+        "json" => serde_json::to_string_pretty(&rows)? + "\n",
+        other => bail!("Unsupported ID map format: {other} (expected csv or json)"),
+    };
+    write_report(&text, output, || {
+        format!("{} ID mapping(s) from {}", rows.len(), input.display())
+    })
+}
+
+/// The objects of `db` in database order with their ODX IDs, followed by
+/// the ODX elements that have no place in the MDD (e.g. protocol layers).
+///
+/// An object is matched by kind, layer and short name. Objects a layer
+/// inherits are not defined in it; they, and the DTCs the IR keeps outside
+/// the layers, fall back to the one ODX element of their kind and short
+/// name, if it is unique.
+fn id_map(db: &diag_ir::DiagDatabase, odx_ids: &[diag_odx::OdxId]) -> Vec<IdMapRow> {
+    let mut by_layer: HashMap<(SearchKind, &str, &str), &str> = HashMap::new();
+    let mut by_name: HashMap<(SearchKind, &str), Option<&str>> = HashMap::new();
+    for odx in odx_ids {
+        by_layer
+            .entry((odx.kind, &odx.layer, &odx.short_name))
+            .or_insert(&odx.id);
+        by_name
+            .entry((odx.kind, &odx.short_name))
+            .and_modify(|id| *id = None)
+            .or_insert(Some(&odx.id));
+    }
+
+    let mut mapped = HashSet::new();
+    let mut rows: Vec<IdMapRow> = diag_ir::object_locations(db)
+        .into_iter()
+        .map(|location| {
+            let kind = location.kind;
+            let odx_id = by_layer
+                .get(&(kind, location.layer.as_str(), location.short_name.as_str()))
+                .copied()
+                .or_else(|| {
+                    by_name
+                        .get(&(kind, location.short_name.as_str()))
+                        .copied()
+                        .flatten()
+                })
+                .unwrap_or_default();
+            if !odx_id.is_empty() {
+                mapped.insert(odx_id);
+            }
+            IdMapRow {
+                kind: kind.as_str(),
+                layer: location.layer,
+                short_name: location.short_name,
+                odx_id: odx_id.to_string(),
+                mdd_index: location.path,
+            }
+        })
+        .collect();
+    rows.extend(
+        odx_ids
+            .iter()
+            .filter(|odx| !mapped.contains(odx.id.as_str()))
+            .map(|odx| IdMapRow {
+                kind: odx.kind.as_str(),
+                layer: odx.layer.clone(),
+                short_name: odx.short_name.clone(),
+                odx_id: odx.id.clone(),
+                mdd_index: String::new(),
+            }),
+    );
+    rows
+}
+
+fn id_map_csv(rows: &[IdMapRow]) -> String {
+    let mut csv = String::from("kind,layer,short_name,odx_id,mdd_index\n");
+    for row in rows {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{}",
+            row.kind,
+            csv_field(&row.layer),
+            csv_field(&row.short_name),
+            csv_field(&row.odx_id),
+            row.mdd_index
+        );
+    }
+    csv
+}
+
+/// Write a report to `output`, or to stdout when no output is given.
+pub(crate) fn write_report(
+    text: &str,
+    output: Option<&Path>,
+    summary: impl FnOnce() -> String,
+) -> Result<()> {
+    match output {
+        Some(path) => {
+            std::fs::write(path, text).with_context(|| format!("writing {}", path.display()))?;
+            eprintln!("Wrote {} to {}", summary(), path.display());
+        }
+        None => print!("{text}"),
+    }
+    Ok(())
+}
+
+fn flash_plan_markdown(ecu_name: &str, plan: &[diag_ir::FlashPlanStep]) -> String {
+    let mut md = format!(
+        "# Flash sequence: {ecu_name}\n\n| # | Step | Services |\n|---|------|----------|\n"
+    );
+    for (i, step) in plan.iter().enumerate() {
+        let services: Vec<String> = step.services.iter().map(|s| format!("`{s}`")).collect();
+        let _ = writeln!(
+            md,
+            "| {} | {} | {} |",
+            i + 1,
+            step.step,
+            services.join(", ")
+        );
+    }
+    md
+}
+
+fn trace_matrix_csv(links: &[diag_ir::TraceLink]) -> String {
+    let mut csv = String::from("requirement,layer,kind,object\n");
+    for link in links {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{}",
+            csv_field(&link.requirement),
+            csv_field(&link.layer),
+            link.kind,
+            csv_field(&link.object)
+        );
+    }
+    csv
+}
+
+/// Quote a field if it contains a separator, quote or line break (RFC 4180).
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    #[test]
+    fn flash_plan_as_markdown_and_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("ecu.yml");
+        std::fs::write(
+            &input,
+            r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+sessions:
+  programming:
+    id: 0x02
+services:
+  diagnosticSessionControl:
+    enabled: true
+  requestDownload:
+    enabled: true
+  ecuReset:
+    enabled: true
+memory:
+  data_blocks:
+    app:
+      name: App
+      memory_address: 0x08000000
+      memory_size: 0x1000
+      checksum_type: crc32
+flash_sequence:
+  - session: programming
+  - transfer: App
+  - check: App
+  - reset: hardReset
+"#,
+        )
+        .unwrap();
+
+        let md = dir.path().join("plan.md");
+        run_flash_plan(&input, "markdown", Some(&md)).unwrap();
+        let md = std::fs::read_to_string(&md).unwrap();
+        assert!(md.starts_with("# Flash sequence: TEST\n"), "{md}");
+        assert!(
+            md.contains(
+                "| 2 | transfer block App | `RequestDownload`, `TransferData`, `TransferExit` |"
+            ),
+            "{md}"
+        );
+        assert!(
+            md.contains("| 3 | check block App | `CheckMemory_App` |"),
+            "{md}"
+        );
+
+        // The plan survives conversion to MDD, which drops the memory config.
+        let mdd = dir.path().join("ecu.mdd");
+        let opts = crate::convert::ConvertOptions::default();
+        crate::convert::run_convert(&input, &mdd, &opts).unwrap();
+        let json = dir.path().join("plan.json");
+        run_flash_plan(&mdd, "json", Some(&json)).unwrap();
+        let plan: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+        assert_eq!(
+            plan[0]["step"],
+            serde_json::json!({"session": "programming"})
+        );
+        assert_eq!(plan[3]["services"], serde_json::json!(["HardReset"]));
+
+        let broken = std::fs::read_to_string(&input)
+            .unwrap()
+            .replace("reset: hardReset", "reset: warmReset");
+        std::fs::write(&input, broken).unwrap();
+        let err = run_flash_plan(&input, "json", None).unwrap_err();
+        assert!(err.to_string().starts_with("1 unresolved"), "{err}");
+    }
+
+    #[test]
+    fn trace_matrix_from_yaml() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("ecu.yml");
+        std::fs::write(
+            &input,
+            r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0xF190:
+    name: VIN
+    type: ascii
+    access: public
+    requirements: [SWR-1234, "SWR-9,legacy"]
+services:
+  testerPresent:
+    enabled: true
+    requirements: [SWR-1234]
+"#,
+        )
+        .unwrap();
+        let out = dir.path().join("trace.csv");
+        run_trace_matrix(&input, Some(&out)).unwrap();
+
+        let csv = std::fs::read_to_string(&out).unwrap();
+        assert_eq!(
+            csv,
+            "requirement,layer,kind,object\n\
+             SWR-1234,TEST,service,TesterPresent\n\
+             SWR-1234,TEST,service,VIN_Read\n\
+             \"SWR-9,legacy\",TEST,service,VIN_Read\n"
+        );
+
+        // The links survive conversion to MDD and ODX.
+        for ext in ["mdd", "odx"] {
+            let converted = dir.path().join(format!("ecu.{ext}"));
+            let opts = crate::convert::ConvertOptions::default();
+            crate::convert::run_convert(&input, &converted, &opts).unwrap();
+            let out = dir.path().join(format!("trace_{ext}.csv"));
+            run_trace_matrix(&converted, Some(&out)).unwrap();
+            assert_eq!(std::fs::read_to_string(&out).unwrap(), csv, "{ext}");
+        }
+    }
+
+    #[test]
+    fn id_map_of_an_odx_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = fixture("odx/project");
+        let out = dir.path().join("ids.json");
+        run_id_map(&input, "json", Some(&out)).unwrap();
+        let rows: Vec<serde_json::Value> =
+            serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        let row = |kind: &str, layer: &str, name: &str| {
+            rows.iter()
+                .find(|r| r["kind"] == kind && r["layer"] == layer && r["short_name"] == name)
+                .unwrap_or_else(|| panic!("no row for {kind} {layer}/{name}"))
+        };
+
+        assert_eq!(
+            row("layer", "SplitECU_HW1", "SplitECU_HW1")["odx_id"],
+            "EV_Split_HW1"
+        );
+        assert_eq!(
+            row("layer", "SplitECU_HW1", "SplitECU_HW1")["mdd_index"],
+            "variants[1]"
+        );
+        let read = row("service", "SplitECU_HW1", "Read_Voltage");
+        assert_eq!(read["odx_id"], "DS_ReadVoltage");
+        assert!(
+            read["mdd_index"]
+                .as_str()
+                .unwrap()
+                .starts_with("variants[1].diag_layer.diag_services[")
+        );
+        // Inherited from the protocol, which has no place in the MDD.
+        let inherited = row("service", "SplitECU_HW1", "TesterPresent");
+        assert_eq!(inherited["odx_id"], "DS_TesterPresent");
+        let protocol = rows
+            .iter()
+            .find(|r| r["odx_id"] == "PR_UDS")
+            .expect("protocol layer");
+        assert_eq!(protocol["mdd_index"], "");
+    }
+}
@@ -0,0 +1,570 @@
+//! Registry of the file formats the CLI reads and writes.
+//!
+//! Every format is a [`FormatProvider`] in [`FORMATS`]: its names and
+//! extensions, how to recognize its content, how to parse it into the IR and
+//! how to write the IR back. Format detection, `--from`, `-f`, `convert`,
+//! `info` and `doctor` all go through the table, so a new format is one
+//! provider plus its entry here; [`Format`] is a handle to a provider, not a
+//! closed list. Formats from other crates are registered the same way behind
+//! a cargo feature (`#[cfg(feature = "...")]` on the table entry).
+
+use anyhow::{Context, Result, bail};
+use diag_ir::types::DiagDatabase;
+use std::path::Path;
+
+use crate::Format;
+use crate::convert::{
+    ConvertOptions, build_job_file_chunks, parse_compression, read_job_files, read_mdd_input,
+};
+use crate::doctor::{self, Report};
+use crate::provenance::Provenance;
+
+/// What a writer may need besides the database and the output path.
+pub(crate) struct WriteContext<'a> {
+    pub input: &'a Path,
+    pub in_fmt: Format,
+    pub opts: &'a ConvertOptions,
+    /// `None` with `--deterministic`.
+    pub provenance: Option<&'a Provenance>,
+}
+
+pub(crate) trait FormatProvider: Sync {
+    /// Display name, e.g. `ODX`.
+    fn name(&self) -> &'static str;
+
+    /// File extensions without the dot; the first one names output files.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Whether the start of a file is in this format. `text` is `bytes`
+    /// decoded (UTF-8 or UTF-16) with leading whitespace and BOM removed.
+    fn sniff(&self, _bytes: &[u8], _text: &str) -> bool {
+        false
+    }
+
+    fn parse(&self, input: &Path, odx_options: &diag_odx::ParseOptions) -> Result<DiagDatabase>;
+
+    /// Whether [`write`](Self::write) is supported.
+    fn writable(&self) -> bool {
+        true
+    }
+
+    /// Write `db` to `output`. Returns the size of the FlatBuffers
+    /// description when the format embeds one.
+    fn write(
+        &self,
+        _db: &mut DiagDatabase,
+        _output: &Path,
+        _ctx: &WriteContext<'_>,
+    ) -> Result<Option<usize>> {
+        bail!(
+            "{} is an input-only format. Use {} for output.",
+            self.name(),
+            writable_extensions()
+        )
+    }
+
+    /// What the writer can represent, for loss reports.
+    fn capabilities(&self) -> Option<&'static diag_ir::FormatCapabilities> {
+        None
+    }
+
+    /// Fill in the format-specific part of a `doctor` report. By default
+    /// this only records whether the file parses.
+    fn diagnose(&self, input: &Path, _bytes: &[u8], report: &mut Report) {
+        doctor::diagnose_parse(self, input, report);
+    }
+}
+
+/// All formats, in the order they are listed in messages.
+pub(crate) static FORMATS: &[Format] = &[
+    Format::ODX,
+    Format::PDX,
+    Format::CDD,
+    Format::YAML,
+    Format::MDD,
+    Format::JSON,
+];
+
+impl Format {
+    pub(crate) const ODX: Self = Self(&OdxFormat);
+    pub(crate) const PDX: Self = Self(&PdxFormat);
+    pub(crate) const CDD: Self = Self(&CddFormat);
+    pub(crate) const YAML: Self = Self(&YamlFormat);
+    pub(crate) const MDD: Self = Self(&MddFormat);
+    pub(crate) const JSON: Self = Self(&JsonFormat);
+
+    pub(crate) fn provider(self) -> &'static dyn FormatProvider {
+        self.0
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        self.provider().name()
+    }
+
+    /// The extension output files of this format get.
+    pub(crate) fn extension(self) -> &'static str {
+        self.provider().extensions()[0]
+    }
+}
+
+impl PartialEq for Format {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+impl std::fmt::Debug for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The format with `extension` (case-sensitive, as on disk).
+pub(crate) fn by_extension(extension: &str) -> Option<Format> {
+    FORMATS
+        .iter()
+        .copied()
+        .find(|f| f.provider().extensions().contains(&extension))
+}
+
+/// The format named `name` (a format name or extension, any case).
+pub(crate) fn by_name(name: &str) -> Option<Format> {
+    let name = name.trim().to_ascii_lowercase();
+    FORMATS.iter().copied().find(|f| {
+        f.name().eq_ignore_ascii_case(&name) || f.provider().extensions().contains(&name.as_str())
+    })
+}
+
+/// The first format whose [`FormatProvider::sniff`] accepts the content.
+pub(crate) fn sniff(bytes: &[u8], text: &str) -> Option<Format> {
+    FORMATS
+        .iter()
+        .copied()
+        .find(|f| f.provider().sniff(bytes, text))
+}
+
+/// Lowercase names of all formats, e.g. `odx, pdx, cdd, yaml, mdd or json`.
+pub(crate) fn names() -> String {
+    list(FORMATS.iter().map(|f| f.name().to_ascii_lowercase()))
+}
+
+/// Lowercase names of the writable formats, e.g. `odx, pdx, yaml, mdd or json`.
+pub(crate) fn writable_names() -> String {
+    list(
+        providers()
+            .filter(|p| p.writable())
+            .map(|p| p.name().to_ascii_lowercase()),
+    )
+}
+
+/// Extensions of the writable formats, e.g. `.odx, .pdx, .yml, .mdd or .json`.
+fn writable_extensions() -> String {
+    list(
+        providers()
+            .filter(|p| p.writable())
+            .map(|p| format!(".{}", p.extensions()[0])),
+    )
+}
+
+/// The providers of [`FORMATS`], in order.
+pub(crate) fn providers() -> impl Iterator<Item = &'static dyn FormatProvider> {
+    FORMATS.iter().map(|f| f.provider())
+}
+
+fn list(items: impl Iterator<Item = String>) -> String {
+    let items: Vec<String> = items.collect();
+    match items.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} or {last}", rest.join(", ")),
+        _ => items.concat(),
+    }
+}
+
+fn read_text(input: &Path) -> Result<String> {
+    std::fs::read_to_string(input).with_context(|| format!("reading {}", input.display()))
+}
+
+fn write_file(output: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    std::fs::write(output, contents).with_context(|| format!("writing {}", output.display()))
+}
+
+struct OdxFormat;
+
+impl FormatProvider for OdxFormat {
+    fn name(&self) -> &'static str {
+        "ODX"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["odx"]
+    }
+
+    fn sniff(&self, _bytes: &[u8], text: &str) -> bool {
+        text.starts_with('<') && text.contains("<ODX") && !text.contains("<CANDELA")
+    }
+
+    fn parse(&self, input: &Path, odx_options: &diag_odx::ParseOptions) -> Result<DiagDatabase> {
+        if input.is_dir() {
+            return diag_odx::load_odx_project_with(&[input.to_path_buf()], odx_options)
+                .with_context(|| format!("loading ODX project {}", input.display()));
+        }
+        let bytes = std::fs::read(input).with_context(|| format!("reading {}", input.display()))?;
+        let text =
+            diag_odx::decode_odx(&bytes).with_context(|| format!("reading {}", input.display()))?;
+        diag_odx::parse_odx_with(&text, odx_options)
+            .map_err(|e| crate::snippet::located(input, &text, e.offset(), e))
+            .with_context(|| format!("parsing ODX from {}", input.display()))
+    }
+
+    fn write(
+        &self,
+        db: &mut DiagDatabase,
+        output: &Path,
+        ctx: &WriteContext<'_>,
+    ) -> Result<Option<usize>> {
+        if let Some(p) = ctx.provenance {
+            p.apply_to_odx_metadata(db);
+        }
+        let odx_opts = diag_odx::OdxWriteOptions {
+            pretty: ctx.opts.normalize,
+            version: ctx.opts.odx_version,
+        };
+        let xml = diag_odx::write_odx_with(db, &odx_opts).context("writing ODX")?;
+        write_file(output, xml)?;
+        Ok(None)
+    }
+
+    fn capabilities(&self) -> Option<&'static diag_ir::FormatCapabilities> {
+        Some(&diag_odx::CAPABILITIES)
+    }
+
+    fn diagnose(&self, _input: &Path, bytes: &[u8], report: &mut Report) {
+        doctor::diagnose_odx(bytes, report);
+    }
+}
+
+struct PdxFormat;
+
+impl FormatProvider for PdxFormat {
+    fn name(&self) -> &'static str {
+        "PDX"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["pdx"]
+    }
+
+    fn sniff(&self, bytes: &[u8], _text: &str) -> bool {
+        bytes.starts_with(b"PK\x03\x04")
+    }
+
+    fn parse(&self, input: &Path, _odx_options: &diag_odx::ParseOptions) -> Result<DiagDatabase> {
+        diag_odx::read_pdx_file(input)
+            .with_context(|| format!("reading PDX from {}", input.display()))
+    }
+
+    fn write(
+        &self,
+        db: &mut DiagDatabase,
+        output: &Path,
+        ctx: &WriteContext<'_>,
+    ) -> Result<Option<usize>> {
+        if let Some(p) = ctx.provenance {
+            p.apply_to_odx_metadata(db);
+        }
+        let extra_files = match ctx.opts.include_job_files.as_deref() {
+            Some(dir) => read_job_files(db, dir)?
+                .into_iter()
+                .map(|(name, data)| diag_odx::PdxFile { name, data })
+                .collect(),
+            None => vec![],
+        };
+        let pdx_opts = diag_odx::PdxWriteOptions {
+            pretty: ctx.opts.normalize,
+            version: ctx.opts.odx_version,
+            extra_files,
+        };
+        diag_odx::write_pdx_file(db, output, &pdx_opts)
+            .with_context(|| format!("writing PDX to {}", output.display()))?;
+        Ok(None)
+    }
+
+    fn capabilities(&self) -> Option<&'static diag_ir::FormatCapabilities> {
+        Some(&diag_odx::CAPABILITIES)
+    }
+
+    fn diagnose(&self, input: &Path, _bytes: &[u8], report: &mut Report) {
+        doctor::diagnose_pdx(input, report);
+    }
+}
+
+struct CddFormat;
+
+impl FormatProvider for CddFormat {
+    fn name(&self) -> &'static str {
+        "CDD"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["cdd"]
+    }
+
+    fn sniff(&self, _bytes: &[u8], text: &str) -> bool {
+        text.starts_with('<') && text.contains("<CANDELA")
+    }
+
+    fn parse(&self, input: &Path, _odx_options: &diag_odx::ParseOptions) -> Result<DiagDatabase> {
+        let bytes = std::fs::read(input).with_context(|| format!("reading {}", input.display()))?;
+        let text =
+            diag_odx::decode_odx(&bytes).with_context(|| format!("reading {}", input.display()))?;
+        diag_cdd::parse_cdd(&text).with_context(|| format!("parsing CDD from {}", input.display()))
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn diagnose(&self, _input: &Path, bytes: &[u8], report: &mut Report) {
+        doctor::diagnose_cdd(bytes, report);
+    }
+}
+
+struct YamlFormat;
+
+impl FormatProvider for YamlFormat {
+    fn name(&self) -> &'static str {
+        "YAML"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["yml", "yaml"]
+    }
+
+    fn sniff(&self, _bytes: &[u8], text: &str) -> bool {
+        // Binary content decodes with replacement characters; one at the end
+        // is a multi-byte character cut off by the prefix length.
+        !text.starts_with(['<', '{'])
+            && !text
+                .trim_end_matches(char::REPLACEMENT_CHARACTER)
+                .contains(char::REPLACEMENT_CHARACTER)
+            && text
+                .lines()
+                .any(|line| line.starts_with("schema:") || line.starts_with("ecu:"))
+    }
+
+    fn parse(&self, input: &Path, _odx_options: &diag_odx::ParseOptions) -> Result<DiagDatabase> {
+        let text = read_text(input)?;
+        diag_yaml::parse_yaml(&text)
+            .map_err(|e| crate::snippet::located(input, &text, e.offset(), e))
+            .with_context(|| format!("parsing YAML from {}", input.display()))
+    }
+
+    fn write(
+        &self,
+        db: &mut DiagDatabase,
+        output: &Path,
+        ctx: &WriteContext<'_>,
+    ) -> Result<Option<usize>> {
+        let yaml_opts = diag_yaml::YamlWriteOptions {
+            numerals: ctx.opts.yaml_numerals,
+            sections: ctx.opts.yaml_sections.clone(),
+        };
+        let mut yaml = diag_yaml::write_yaml_with(db, &yaml_opts).context("writing YAML")?;
+        if ctx.opts.preserve_comments && ctx.in_fmt == Format::YAML {
+            let original = read_text(ctx.input)?;
+            yaml = diag_yaml::comments::carry_comments(&original, &yaml);
+        }
+        write_file(output, yaml)?;
+        Ok(None)
+    }
+
+    fn capabilities(&self) -> Option<&'static diag_ir::FormatCapabilities> {
+        Some(&diag_yaml::CAPABILITIES)
+    }
+
+    fn diagnose(&self, _input: &Path, bytes: &[u8], report: &mut Report) {
+        doctor::diagnose_yaml(bytes, report);
+    }
+}
+
+struct MddFormat;
+
+impl FormatProvider for MddFormat {
+    fn name(&self) -> &'static str {
+        "MDD"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["mdd"]
+    }
+
+    fn sniff(&self, bytes: &[u8], _text: &str) -> bool {
+        bytes.starts_with(mdd_format::reader::FILE_MAGIC)
+    }
+
+    fn parse(&self, input: &Path, _odx_options: &diag_odx::ParseOptions) -> Result<DiagDatabase> {
+        Ok(read_mdd_input(input, None, &[], false)?.0)
+    }
+
+    fn write(
+        &self,
+        db: &mut DiagDatabase,
+        output: &Path,
+        ctx: &WriteContext<'_>,
+    ) -> Result<Option<usize>> {
+        let opts = ctx.opts;
+        let compression = parse_compression(&opts.compression)?;
+        let fbs_data = diag_ir::ir_to_flatbuffers(db);
+        let extra_chunks = if let Some(dir) = opts.include_job_files.as_deref() {
+            build_job_file_chunks(db, dir)?
+        } else {
+            vec![]
+        };
+        let search_index = opts
+            .search_index
+            .then(|| diag_ir::SearchIndex::build(db).to_bytes());
+        let bytes = if ctx.in_fmt == Format::MDD
+            && opts.include_job_files.is_none()
+            && opts.encryption.is_none()
+            && opts.decryption.is_none()
+        {
+            // Same-format rewrite: keep the original container metadata
+            // and embedded chunks, only the description changes.
+            let original = std::fs::read(ctx.input)
+                .with_context(|| format!("reading {}", ctx.input.display()))?;
+            let bytes =
+                mdd_format::writer::repack_mdd_bytes(&original, &fbs_data, compression, None)
+                    .context("rewriting MDD")?;
+            let bytes = match &search_index {
+                Some(index) => {
+                    mdd_format::writer::add_search_index(&bytes, index, compression, None)
+                        .context("rewriting MDD")?
+                }
+                None => bytes,
+            };
+            match &opts.signing {
+                Some(signing) => {
+                    mdd_format::writer::sign_mdd_bytes(&bytes, signing).context("signing MDD")?
+                }
+                None => bytes,
+            }
+        } else {
+            let options = mdd_format::writer::WriteOptions {
+                version: db.version.clone(),
+                ecu_name: db.ecu_name.clone(),
+                revision: db.revision.clone(),
+                compression,
+                compression_level: None,
+                metadata: ctx
+                    .provenance
+                    .map(Provenance::mdd_metadata)
+                    .unwrap_or_default(),
+                extra_chunks,
+                compatibility: diag_ir::compatibility(db).map(|c| {
+                    mdd_format::fileformat::Compatibility {
+                        hardware_part_numbers: c.hardware,
+                        software_part_numbers: c.software,
+                    }
+                }),
+                validity: diag_ir::validity(db).as_ref().map(Into::into),
+                search_index,
+                encryption: opts.encryption.clone(),
+                signing: opts.signing.clone(),
+            };
+            mdd_format::writer::write_mdd_bytes(&fbs_data, &options)
+                .with_context(|| format!("writing MDD to {}", output.display()))?
+        };
+        opts.size_policy.check(db, bytes.len())?;
+        std::fs::write(output, bytes)
+            .with_context(|| format!("writing MDD to {}", output.display()))?;
+        Ok(Some(fbs_data.len()))
+    }
+
+    fn capabilities(&self) -> Option<&'static diag_ir::FormatCapabilities> {
+        Some(&diag_ir::FBS_CAPABILITIES)
+    }
+
+    fn diagnose(&self, _input: &Path, bytes: &[u8], report: &mut Report) {
+        doctor::diagnose_mdd(bytes, report);
+    }
+}
+
+struct JsonFormat;
+
+impl FormatProvider for JsonFormat {
+    fn name(&self) -> &'static str {
+        "JSON"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json"]
+    }
+
+    fn sniff(&self, _bytes: &[u8], text: &str) -> bool {
+        text.starts_with('{') && text.contains(diag_ir::JSON_SCHEMA)
+    }
+
+    fn parse(&self, input: &Path, _odx_options: &diag_odx::ParseOptions) -> Result<DiagDatabase> {
+        let text = read_text(input)?;
+        diag_ir::parse_json(&text).with_context(|| format!("parsing JSON from {}", input.display()))
+    }
+
+    fn write(
+        &self,
+        db: &mut DiagDatabase,
+        output: &Path,
+        _ctx: &WriteContext<'_>,
+    ) -> Result<Option<usize>> {
+        let json = diag_ir::write_json(db).context("writing JSON")?;
+        write_file(output, json)?;
+        Ok(None)
+    }
+
+    fn capabilities(&self) -> Option<&'static diag_ir::FormatCapabilities> {
+        Some(&diag_ir::JSON_CAPABILITIES)
+    }
+
+    fn diagnose(&self, _input: &Path, bytes: &[u8], report: &mut Report) {
+        doctor::diagnose_json(bytes, report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_unique() {
+        let mut seen = std::collections::HashSet::new();
+        for format in FORMATS {
+            assert!(
+                seen.insert(format.name()),
+                "format {format:?} registered twice"
+            );
+        }
+    }
+
+    #[test]
+    fn extensions_are_unique() {
+        let mut seen = std::collections::HashSet::new();
+        for ext in providers().flat_map(FormatProvider::extensions) {
+            assert!(seen.insert(ext), "extension {ext} claimed twice");
+        }
+    }
+
+    #[test]
+    fn lookup_by_name_and_extension() {
+        assert_eq!(by_name(" YAML "), Some(Format::YAML));
+        assert_eq!(by_name("yml"), Some(Format::YAML));
+        assert_eq!(by_extension("yaml"), Some(Format::YAML));
+        assert_eq!(by_extension("xml"), None);
+        assert_eq!(Format::YAML.extension(), "yml");
+        assert_eq!(names(), "odx, pdx, cdd, yaml, mdd or json");
+    }
+
+    #[test]
+    fn input_only_formats_refuse_to_write() {
+        assert!(!Format::CDD.provider().writable());
+        assert_eq!(writable_names(), "odx, pdx, yaml, mdd or json");
+        assert_eq!(writable_extensions(), ".odx, .pdx, .yml, .mdd or .json");
+    }
+}
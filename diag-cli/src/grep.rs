@@ -0,0 +1,99 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use diag_ir::{FilterExpr, SearchIndex, SearchKind};
+
+use crate::Format;
+use crate::convert::parse_input;
+
+/// Print the objects of `input` whose name contains `pattern`, limited to
+/// `kinds` when any are given. MDD files with an embedded search index are
+/// answered from the index alone; other inputs, and every input with a
+/// `filter`, are indexed after parsing, keeping only the services or DTCs
+/// the filter matches.
+pub fn run_grep(
+    input: &Path,
+    pattern: &str,
+    kinds: &[SearchKind],
+    filter: Option<&FilterExpr>,
+) -> Result<()> {
+    let (index, embedded) = match filter {
+        Some(filter) => {
+            let mut db = parse_input(input, false)?;
+            diag_ir::filter_by_expr(&mut db, filter);
+            (SearchIndex::build(&db), false)
+        }
+        None => load_index(input)?,
+    };
+    let matches: Vec<_> = index
+        .find(pattern)
+        .into_iter()
+        .filter(|e| kinds.is_empty() || kinds.contains(&e.kind))
+        .collect();
+    for entry in &matches {
+        println!("{:<7}  {}", entry.kind.as_str(), entry.path);
+    }
+    eprintln!(
+        "{} match(es) in {}{}",
+        matches.len(),
+        input.display(),
+        if embedded { " (embedded index)" } else { "" }
+    );
+    Ok(())
+}
+
+/// The search index of `input`, and whether it was read from the file.
+fn load_index(input: &Path) -> Result<(SearchIndex, bool)> {
+    if crate::detect_input_format(input, None)? == Format::MDD {
+        let source = mdd_format::reader::MddSource::open(input)
+            .with_context(|| format!("reading {}", input.display()))?;
+        if let Some(bytes) = source.stream()?.search_index()? {
+            let index = SearchIndex::from_bytes(&bytes)
+                .with_context(|| format!("reading the search index of {}", input.display()))?;
+            return Ok((index, true));
+        }
+    }
+    Ok((SearchIndex::build(&parse_input(input, false)?), false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    #[test]
+    fn embedded_index_is_used_and_matches_a_parsed_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = fixture("yaml/FLXC1000.yml");
+        let output = dir.path().join("ecu.mdd");
+        let opts = crate::convert::ConvertOptions {
+            search_index: true,
+            ..Default::default()
+        };
+        crate::convert::run_convert(&input, &output, &opts).unwrap();
+
+        let (embedded, from_file) = load_index(&output).unwrap();
+        assert!(from_file);
+        let (parsed, from_file) = load_index(&input).unwrap();
+        assert!(!from_file);
+        assert_eq!(embedded, parsed);
+        assert!(
+            embedded
+                .find("vin")
+                .iter()
+                .any(|e| e.kind == SearchKind::Service),
+            "{:?}",
+            embedded.find("vin")
+        );
+
+        // Rewriting the description without a new index leaves the old one
+        // unused.
+        let stale = dir.path().join("stale.mdd");
+        let opts = crate::convert::ConvertOptions {
+            normalize: true,
+            ..Default::default()
+        };
+        crate::convert::run_convert(&output, &stale, &opts).unwrap();
+        assert!(!load_index(&stale).unwrap().1);
+    }
+}
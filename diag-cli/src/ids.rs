@@ -0,0 +1,105 @@
+use anyhow::{Context, Result, bail};
+use diag_ir::{IdKind, IdPolicy, IdRange};
+use std::path::Path;
+
+use crate::convert::parse_input;
+
+/// Check that every DID, routine ID and DTC number of `input` lies inside the
+/// ranges reserved by the policy file.
+pub fn run_check_ids(input: &Path, policy_path: &Path) -> Result<()> {
+    let policy = load_policy(policy_path)?;
+    let db = parse_input(input, false)?;
+    let violations = diag_ir::check_ids(&db, &policy);
+    for violation in &violations {
+        eprintln!("  {violation}");
+    }
+    if !violations.is_empty() {
+        bail!(
+            "{} identifier(s) in {} outside the ranges of {}",
+            violations.len(),
+            input.display(),
+            policy_path.display()
+        );
+    }
+    eprintln!("All identifiers in {} are within policy", input.display());
+    Ok(())
+}
+
+/// Print the next `count` DIDs in `range` that `input` does not use yet.
+pub fn run_allocate_did(input: &Path, range: &str, count: usize) -> Result<()> {
+    let range: IdRange = range
+        .parse()
+        .map_err(|e: String| anyhow::anyhow!(e))
+        .context("invalid --range")?;
+    let db = parse_input(input, false)?;
+    let free = diag_ir::next_free_ids(&db, IdKind::Did, range, count);
+    if free.is_empty() {
+        bail!(
+            "No free DID in {}-{}",
+            IdKind::Did.format(range.start),
+            IdKind::Did.format(range.end)
+        );
+    }
+    for id in free {
+        println!("{}", IdKind::Did.format(id));
+    }
+    Ok(())
+}
+
+fn load_policy(path: &Path) -> Result<IdPolicy> {
+    let text =
+        std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    serde_yaml::from_str(&text).with_context(|| format!("parsing ID policy {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ECU_YAML: &str = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0x0200:
+    name: Calibration
+    type: uint8
+    access: public
+  0xF190:
+    name: VIN
+    type: ascii
+    access: public
+"#;
+
+    #[test]
+    fn check_ids_against_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("ecu.yml");
+        std::fs::write(&input, ECU_YAML).unwrap();
+
+        let strict = dir.path().join("strict.yml");
+        std::fs::write(&strict, "dids: [\"0x0200-0x02FF\"]\n").unwrap();
+        let err = run_check_ids(&input, &strict).unwrap_err();
+        assert!(err.to_string().starts_with("1 identifier(s)"), "{err}");
+
+        let lenient = dir.path().join("lenient.yml");
+        std::fs::write(&lenient, "dids: [\"0x0200-0x02FF\", \"0xF180-0xF19F\"]\n").unwrap();
+        run_check_ids(&input, &lenient).unwrap();
+
+        let bad = dir.path().join("bad.yml");
+        std::fs::write(&bad, "dids: [\"0x02FF-0x0200\"]\n").unwrap();
+        assert!(run_check_ids(&input, &bad).is_err());
+    }
+
+    #[test]
+    fn allocate_did_fails_when_range_is_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("ecu.yml");
+        std::fs::write(&input, ECU_YAML).unwrap();
+
+        run_allocate_did(&input, "0x0200-0x02FF", 1).unwrap();
+        let err = run_allocate_did(&input, "0x0200-0x0200", 1).unwrap_err();
+        assert_eq!(err.to_string(), "No free DID in 0x0200-0x0200");
+        assert!(run_allocate_did(&input, "0x0200-0x01FF", 1).is_err());
+    }
+}
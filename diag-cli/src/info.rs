@@ -1,20 +1,43 @@
 use anyhow::{Context, Result};
+use std::fmt::Write as _;
 use std::path::Path;
 
 use crate::Format;
-use crate::convert::parse_input;
+use crate::convert::{parse_input_with, read_mdd_file};
 
-pub fn run_info(input: &Path) -> Result<()> {
-    let in_fmt = crate::detect_format(input).context("input file")?;
-    let db = parse_input(input, false)?;
-
-    let format_str = match in_fmt {
-        Format::Odx => "ODX",
-        Format::Pdx => "PDX",
-        Format::Yaml => "YAML",
-        Format::Mdd => "MDD",
+pub fn run_info(
+    input: &Path,
+    from: Option<Format>,
+    detailed: bool,
+    keys: Option<&mdd_format::encryption::KeySource>,
+) -> Result<()> {
+    let in_fmt = crate::detect_input_format(input, from).context("input file")?;
+    let (db, features, compatibility, validity) = if in_fmt == Format::MDD {
+        let (meta, fbs_data) = read_mdd_file(input, keys)?;
+        let mut db = diag_ir::flatbuffers_to_ir(&fbs_data)
+            .with_context(|| "converting FlatBuffers to IR")?;
+        diag_ir::complete_transport(&mut db);
+        let compatibility = meta.compatibility.map(|c| diag_ir::Compatibility {
+            hardware: c.hardware_part_numbers,
+            software: c.software_part_numbers,
+        });
+        let validity = meta.validity.as_ref().map(diag_ir::Validity::from);
+        (
+            db,
+            diag_ir::read_feature_flags(&fbs_data)?,
+            compatibility,
+            validity,
+        )
+    } else {
+        let db = parse_input_with(input, Some(in_fmt), &diag_odx::ParseOptions::default())?;
+        let features = diag_ir::FeatureFlags::detect(&db);
+        let compatibility = diag_ir::compatibility(&db);
+        let validity = diag_ir::validity(&db);
+        (db, Some(features), compatibility, validity)
     };
 
+    let format_str = in_fmt.name();
+
     println!("File:        {}", input.display());
     println!("Format:      {format_str}");
     println!("ECU:         {}", db.ecu_name);
@@ -42,6 +65,27 @@ pub fn run_info(input: &Path) -> Result<()> {
 
     println!("DTCs:        {}", db.dtcs.len());
 
+    if let Some(doip) = db.transport.as_ref().and_then(|t| t.doip.as_ref()) {
+        println!("DoIP:        {}", doip_summary(doip));
+    }
+
+    if let Some(c) = compatibility {
+        if !c.hardware.is_empty() {
+            println!("Hardware:    {}", c.hardware.join(", "));
+        }
+        if !c.software.is_empty() {
+            println!("Software:    {}", c.software.join(", "));
+        }
+    }
+
+    if let Some(v) = validity {
+        println!("Validity:    {v}");
+        let status = v.status_on(diag_ir::Date::today());
+        if status != diag_ir::ValidityStatus::Valid {
+            eprintln!("warning: database {status}");
+        }
+    }
+
     let state_charts: usize = db
         .variants
         .iter()
@@ -51,5 +95,218 @@ pub fn run_info(input: &Path) -> Result<()> {
         println!("StateCharts: {state_charts}");
     }
 
+    match features {
+        Some(f) if f.is_empty() => println!("Features:    none"),
+        Some(f) => println!("Features:    {f}"),
+        None => println!("Features:    not recorded"),
+    }
+
+    if detailed {
+        let chunks = if in_fmt == Format::MDD {
+            Some(mdd_chunks(input)?)
+        } else {
+            None
+        };
+        print!(
+            "{}",
+            detailed_report(&diag_ir::DatabaseStats::of(&db), chunks.as_deref())
+        );
+    }
+
     Ok(())
 }
+
+/// `IP:PORT, ECU 0x..., functional 0x..., tester 0x..., EID ..., GID ...`,
+/// leaving out what the database does not state.
+fn doip_summary(doip: &diag_ir::DoipConfig) -> String {
+    let mut parts = Vec::new();
+    if let Some(ip) = &doip.ip {
+        parts.push(match doip.port {
+            Some(port) => format!("{ip}:{port}"),
+            None => ip.clone(),
+        });
+    }
+    let addresses = [
+        ("ECU", doip.logical_address),
+        ("functional", doip.functional_address),
+        ("tester", doip.tester_address),
+    ];
+    for (name, address) in addresses {
+        if let Some(address) = address {
+            parts.push(format!("{name} 0x{address:04X}"));
+        }
+    }
+    for (name, id) in [("EID", doip.eid), ("GID", doip.gid)] {
+        if let Some(id) = id {
+            parts.push(format!(
+                "{name} {}",
+                diag_ir::transport::format_entity_id(&id)
+            ));
+        }
+    }
+    parts.join(", ")
+}
+
+/// One chunk of an MDD file as listed by `info --detailed`.
+struct ChunkLine {
+    kind: String,
+    name: Option<String>,
+    compression: Option<String>,
+    stored: usize,
+    uncompressed: Option<u64>,
+}
+
+/// The chunks of an MDD file, read from their headers without
+/// decompressing anything.
+fn mdd_chunks(input: &Path) -> Result<Vec<ChunkLine>> {
+    let source = mdd_format::reader::MddSource::open(input)
+        .with_context(|| format!("reading MDD from {}", input.display()))?;
+    let stream = source.stream()?;
+    let mut lines = Vec::with_capacity(stream.chunk_count());
+    for chunk in stream.chunks() {
+        let chunk = chunk?;
+        let header = chunk.header();
+        lines.push(ChunkLine {
+            kind: mdd_format::fileformat::chunk::DataType::try_from(header.r#type)
+                .map_or_else(|_| header.r#type.to_string(), |t| t.as_str_name().into()),
+            name: header.name.clone(),
+            compression: chunk.compression().map(str::to_string),
+            stored: chunk.data().map_or(0, <[u8]>::len),
+            uncompressed: header.uncompressed_size,
+        });
+    }
+    Ok(lines)
+}
+
+fn detailed_report(stats: &diag_ir::DatabaseStats, chunks: Option<&[ChunkLine]>) -> String {
+    let mut out = String::new();
+    for variant in &stats.variants {
+        let _ = writeln!(
+            out,
+            "\nVariant {}{}: {} services, {} jobs, {} SDGs",
+            variant.name,
+            if variant.is_base_variant {
+                " (base)"
+            } else {
+                ""
+            },
+            variant.services.len(),
+            variant.single_ecu_jobs,
+            variant.sdgs
+        );
+        if variant.services.is_empty() {
+            continue;
+        }
+        let _ = writeln!(
+            out,
+            "  {:<4}  {:>3}  {:>4}  {:<16}  Service",
+            "SID", "Req", "Resp", "Semantic"
+        );
+        for svc in &variant.services {
+            let sid = svc
+                .sid
+                .map(|sid| format!("0x{sid:02X}"))
+                .unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "  {sid:<4}  {:>3}  {:>4}  {:<16}  {}",
+                svc.request_params, svc.response_params, svc.semantic, svc.name
+            );
+        }
+    }
+
+    if !stats.dop_kinds.is_empty() {
+        out.push_str("\nParams by DOP kind:\n");
+        for (kind, count) in &stats.dop_kinds {
+            let _ = writeln!(out, "  {kind:<22}  {count:>6}");
+        }
+    }
+    let _ = writeln!(out, "\nSDGs:        {}", stats.sdgs);
+
+    if !stats.memory_regions.is_empty() {
+        out.push_str("\nMemory map:\n");
+        for region in &stats.memory_regions {
+            let access = format!("{:?}", region.access);
+            let end = region
+                .start_address
+                .saturating_add(region.size.saturating_sub(1));
+            let _ = writeln!(
+                out,
+                "  0x{:08X}-0x{end:08X}  {:<9}  {}",
+                region.start_address, access, region.name
+            );
+        }
+    }
+
+    if let Some(chunks) = chunks {
+        out.push_str("\nMDD chunks:\n");
+        for (index, chunk) in chunks.iter().enumerate() {
+            let _ = write!(out, "  {index}  {}", chunk.kind);
+            if let Some(name) = &chunk.name {
+                let _ = write!(out, " '{name}'");
+            }
+            let _ = write!(
+                out,
+                ": {} bytes {}",
+                chunk.stored,
+                chunk.compression.as_deref().unwrap_or("uncompressed")
+            );
+            if let Some(size) = chunk.uncompressed {
+                let _ = write!(out, ", {size} bytes uncompressed");
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    #[test]
+    fn detailed_report_lists_services_and_chunks() {
+        let db = diag_yaml::parse_yaml(
+            &std::fs::read_to_string(fixture("yaml/example-ecm.yml")).unwrap(),
+        )
+        .unwrap();
+        let stats = diag_ir::DatabaseStats::of(&db);
+        let chunks = [ChunkLine {
+            kind: "DIAGNOSTIC_DESCRIPTION".into(),
+            name: None,
+            compression: Some("lzma".into()),
+            stored: 1000,
+            uncompressed: Some(4000),
+        }];
+        let report = detailed_report(&stats, Some(&chunks));
+        assert!(report.contains("  SID   Req  Resp"), "{report}");
+        assert!(report.contains("0x22"), "{report}");
+        assert!(report.contains("Params by DOP kind:\n  normal"), "{report}");
+        assert!(
+            report.ends_with(
+                "MDD chunks:\n  0  DIAGNOSTIC_DESCRIPTION: 1000 bytes lzma, 4000 bytes uncompressed\n"
+            ),
+            "{report}"
+        );
+    }
+
+    #[test]
+    fn doip_summary_lists_the_stated_addresses() {
+        let db = diag_yaml::parse_yaml(
+            &std::fs::read_to_string(fixture("yaml/example-ecm.yml")).unwrap(),
+        )
+        .unwrap();
+        let doip = db.transport.unwrap().doip.unwrap();
+        assert_eq!(
+            doip_summary(&doip),
+            "192.168.0.50:13400, ECU 0x0E00, functional 0xE400, tester 0x0E80"
+        );
+
+        let doip = diag_ir::DoipConfig {
+            eid: Some([0, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E]),
+            ..Default::default()
+        };
+        assert_eq!(doip_summary(&doip), "EID 00:1A:2B:3C:4D:5E");
+    }
+}
@@ -0,0 +1,495 @@
+use anyhow::{Result, bail};
+use std::collections::BTreeSet;
+use std::fmt::{self, Write as _};
+use std::path::Path;
+use std::str::FromStr;
+
+use diag_ir::{
+    AccessRequirements, DiagDatabase, ExprSubject, FilterExpr, ParamConstraints, Semantic,
+};
+
+use crate::convert::parse_input;
+use crate::export::{csv_field, write_report};
+
+/// What `list` prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ListKind {
+    /// Services with their layer and semantic.
+    #[default]
+    Services,
+    /// Services with the sessions, security levels and authentication
+    /// roles they require.
+    AccessMatrix,
+    /// Service parameters with the internal and physical constraints of
+    /// their DOPs.
+    Params,
+}
+
+impl FromStr for ListKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "services" => Ok(Self::Services),
+            "access-matrix" => Ok(Self::AccessMatrix),
+            "params" => Ok(Self::Params),
+            _ => Err(format!(
+                "unknown list kind '{s}' (expected services, access-matrix or params)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ListKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Services => "services",
+            Self::AccessMatrix => "access-matrix",
+            Self::Params => "params",
+        })
+    }
+}
+
+/// Print the access matrix of `input` as a table, or as CSV with `csv`,
+/// to stdout or `output`. With `semantics`, only services with one of them
+/// are listed; with `filter`, only services matching it; with
+/// `functional_group`, only services reachable from that group.
+pub fn run_access_matrix(
+    input: &Path,
+    semantics: &[String],
+    filter: Option<&FilterExpr>,
+    functional_group: Option<&str>,
+    csv: bool,
+    output: Option<&Path>,
+) -> Result<()> {
+    let db = parse_filtered(input, filter, functional_group)?;
+    let rows = access_rows(&db, semantics)?;
+    let text = if csv {
+        access_matrix_csv(&rows)
+    } else {
+        access_matrix_table(&rows)
+    };
+    write_report(&text, output, || {
+        format!("access matrix of {} service(s)", rows.len())
+    })
+}
+
+/// Print the value parameters of the services of `input` with their
+/// INTERNAL-CONSTR and PHYS-CONSTR, as a table or as CSV with `csv`, to
+/// stdout or `output`. Services are selected as for [`run_access_matrix`].
+pub fn run_params(
+    input: &Path,
+    semantics: &[String],
+    filter: Option<&FilterExpr>,
+    functional_group: Option<&str>,
+    csv: bool,
+    output: Option<&Path>,
+) -> Result<()> {
+    let db = parse_filtered(input, filter, functional_group)?;
+    let rows = param_rows(&db, semantics)?;
+    let text = if csv {
+        params_csv(&rows)
+    } else {
+        params_table(&rows)
+    };
+    write_report(&text, output, || {
+        format!("constraints of {} parameter(s)", rows.len())
+    })
+}
+
+/// Print the services of `input` with their layer and semantic, keeping
+/// only those with one of `semantics` when any are given, those matching
+/// `filter` and those reachable from `functional_group`.
+pub fn run_list(
+    input: &Path,
+    semantics: &[String],
+    filter: Option<&FilterExpr>,
+    functional_group: Option<&str>,
+) -> Result<()> {
+    let db = parse_filtered(input, filter, functional_group)?;
+    let rows: Vec<(&str, &str, Option<Semantic>)> = if semantics.is_empty() {
+        db.variants
+            .iter()
+            .map(|v| &v.diag_layer)
+            .chain(db.functional_groups.iter().map(|fg| &fg.diag_layer))
+            .chain(db.protocols.iter().map(|p| &p.diag_layer))
+            .chain(db.ecu_shared_datas.iter().map(|e| &e.diag_layer))
+            .flat_map(|layer| {
+                layer.diag_services.iter().map(|svc| {
+                    (
+                        layer.short_name.as_str(),
+                        svc.diag_comm.short_name.as_str(),
+                        Semantic::of(svc),
+                    )
+                })
+            })
+            .collect()
+    } else {
+        let semantics: Vec<&str> = semantics.iter().map(String::as_str).collect();
+        diag_ir::by_semantic(&db, &semantics)?
+            .into_iter()
+            .map(|m| {
+                (
+                    m.layer,
+                    m.service.diag_comm.short_name.as_str(),
+                    Some(m.semantic),
+                )
+            })
+            .collect()
+    };
+
+    let width = rows.iter().map(|(l, s, _)| l.len() + s.len() + 1).max();
+    for (layer, service, semantic) in &rows {
+        let name = format!("{layer}/{service}");
+        println!(
+            "{name:<width$}  {}",
+            semantic.map_or("-", Semantic::as_str),
+            width = width.unwrap_or(0)
+        );
+    }
+    eprintln!("{} service(s) in {}", rows.len(), input.display());
+    Ok(())
+}
+
+/// Parse `input` and keep the services reachable from `functional_group`
+/// and matching `filter`.
+fn parse_filtered(
+    input: &Path,
+    filter: Option<&FilterExpr>,
+    functional_group: Option<&str>,
+) -> Result<DiagDatabase> {
+    let mut db = parse_input(input, false)?;
+    if let Some(group) = functional_group {
+        diag_ir::filter_by_functional_group(&mut db, group)?;
+    }
+    if let Some(filter) = filter {
+        if filter.subject() != ExprSubject::Service {
+            bail!("--where on list takes service fields, not '{filter}'");
+        }
+        diag_ir::filter_by_expr(&mut db, filter);
+    }
+    Ok(db)
+}
+
+/// The `(layer, service)` pairs with one of `semantics`, or `None` when
+/// no semantics are given.
+fn wanted_services<'a>(
+    db: &'a DiagDatabase,
+    semantics: &[String],
+) -> Result<Option<BTreeSet<(&'a str, &'a str)>>> {
+    if semantics.is_empty() {
+        return Ok(None);
+    }
+    let semantics: Vec<&str> = semantics.iter().map(String::as_str).collect();
+    Ok(Some(
+        diag_ir::by_semantic(db, &semantics)?
+            .into_iter()
+            .map(|m| (m.layer, m.service.diag_comm.short_name.as_str()))
+            .collect(),
+    ))
+}
+
+fn access_rows(db: &DiagDatabase, semantics: &[String]) -> Result<Vec<AccessRequirements>> {
+    let mut rows = diag_ir::access_matrix(db);
+    if let Some(wanted) = wanted_services(db, semantics)? {
+        rows.retain(|r| wanted.contains(&(r.layer.as_str(), r.service.as_str())));
+    }
+    Ok(rows)
+}
+
+fn param_rows(db: &DiagDatabase, semantics: &[String]) -> Result<Vec<ParamConstraints>> {
+    let mut rows = diag_ir::param_constraints(db);
+    if let Some(wanted) = wanted_services(db, semantics)? {
+        rows.retain(|r| wanted.contains(&(r.layer.as_str(), r.service.as_str())));
+    }
+    Ok(rows)
+}
+
+const ACCESS_COLUMNS: [&str; 6] = [
+    "layer",
+    "service",
+    "sessions",
+    "security_levels",
+    "authentication_roles",
+    "other_preconditions",
+];
+
+/// The requirement cells of a row; several states are joined with `;`.
+fn access_cells(row: &AccessRequirements) -> [String; 4] {
+    [
+        row.sessions.join(";"),
+        row.security_levels.join(";"),
+        row.authentication_roles.join(";"),
+        row.other.join(";"),
+    ]
+}
+
+/// One line per service. An empty cell means the service does not restrict
+/// that kind of state.
+fn access_matrix_csv(rows: &[AccessRequirements]) -> String {
+    let mut csv = ACCESS_COLUMNS.join(",") + "\n";
+    for row in rows {
+        let cells = access_cells(row);
+        let _ = writeln!(
+            csv,
+            "{},{},{}",
+            csv_field(&row.layer),
+            csv_field(&row.service),
+            cells.map(|c| csv_field(&c)).join(",")
+        );
+    }
+    csv
+}
+
+fn access_matrix_table(rows: &[AccessRequirements]) -> String {
+    let cells: Vec<[String; 6]> = rows
+        .iter()
+        .map(|row| {
+            let [sessions, security, authentication, other] =
+                access_cells(row).map(|c| if c.is_empty() { "-".into() } else { c });
+            [
+                row.layer.clone(),
+                row.service.clone(),
+                sessions,
+                security,
+                authentication,
+                other,
+            ]
+        })
+        .collect();
+    table(
+        [
+            "Layer",
+            "Service",
+            "Sessions",
+            "Security",
+            "Authentication",
+            "Other",
+        ],
+        cells,
+    )
+}
+
+const PARAM_COLUMNS: [&str; 6] = [
+    "layer",
+    "service",
+    "param",
+    "dop",
+    "internal_constr",
+    "phys_constr",
+];
+
+/// The constraint cells of a row in interval notation; empty when the DOP
+/// has no such constraint.
+fn param_cells(row: &ParamConstraints) -> [String; 2] {
+    [&row.internal, &row.physical].map(|c| c.as_ref().map(ToString::to_string).unwrap_or_default())
+}
+
+fn params_csv(rows: &[ParamConstraints]) -> String {
+    let mut csv = PARAM_COLUMNS.join(",") + "\n";
+    for row in rows {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{}",
+            csv_field(&row.layer),
+            csv_field(&row.service),
+            csv_field(&row.param),
+            csv_field(&row.dop),
+            param_cells(row).map(|c| csv_field(&c)).join(",")
+        );
+    }
+    csv
+}
+
+fn params_table(rows: &[ParamConstraints]) -> String {
+    let cells: Vec<[String; 6]> = rows
+        .iter()
+        .map(|row| {
+            let [internal, physical] =
+                param_cells(row).map(|c| if c.is_empty() { "-".into() } else { c });
+            [
+                row.layer.clone(),
+                row.service.clone(),
+                row.param.clone(),
+                row.dop.clone(),
+                internal,
+                physical,
+            ]
+        })
+        .collect();
+    table(
+        [
+            "Layer",
+            "Service",
+            "Param",
+            "DOP",
+            "Internal-Constr",
+            "Phys-Constr",
+        ],
+        cells,
+    )
+}
+
+/// Left-aligned columns, each as wide as its widest cell.
+fn table<const N: usize>(header: [&str; N], cells: Vec<[String; N]>) -> String {
+    let widths: Vec<usize> = (0..N)
+        .map(|i| {
+            cells
+                .iter()
+                .map(|row| row[i].len())
+                .chain([header[i].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut out = String::new();
+    for row in std::iter::once(header.map(String::from)).chain(cells) {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(&widths) {
+            let _ = write!(line, "{cell:<width$}  ");
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    #[test]
+    fn unknown_semantic_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("ecu.yml");
+        std::fs::write(
+            &input,
+            "schema: \"opensovd.cda.diagdesc/v1\"\necu:\n  name: \"TEST\"\n",
+        )
+        .unwrap();
+        run_list(&input, &["ROUTINE".into()], None, None).unwrap();
+        let err = run_list(&input, &["READ-ALL".into()], None, None).unwrap_err();
+        assert!(
+            err.to_string().starts_with("unknown semantic 'READ-ALL'"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn where_takes_service_fields() {
+        let input = fixture("yaml/example-ecm.yml");
+        let all = parse_filtered(&input, None, None).unwrap();
+        let filter = "service.semantic == DATA-READ".parse().unwrap();
+        let reads = parse_filtered(&input, Some(&filter), None).unwrap();
+        let rows = access_rows(&reads, &[]).unwrap();
+        assert!(!rows.is_empty());
+        assert!(rows.len() < access_rows(&all, &[]).unwrap().len());
+
+        let filter = "dtc.level > 1".parse().unwrap();
+        let err = parse_filtered(&input, Some(&filter), None).unwrap_err();
+        assert!(err.to_string().contains("service fields"), "{err}");
+    }
+
+    #[test]
+    fn functional_group_keeps_reachable_services() {
+        let input = fixture("odx/minimal.odx");
+        let db = parse_filtered(&input, None, Some("Diagnostics")).unwrap();
+        let services: BTreeSet<String> = access_rows(&db, &[])
+            .unwrap()
+            .into_iter()
+            .map(|r| r.service)
+            .collect();
+        assert!(services.contains("TesterPresent"), "{services:?}");
+        assert!(!services.contains("Read_VehicleSpeed"), "{services:?}");
+
+        let err = parse_filtered(&input, None, Some("NoSuchGroup")).unwrap_err();
+        assert!(err.to_string().contains("NoSuchGroup"), "{err}");
+    }
+
+    #[test]
+    fn access_matrix_csv_has_a_line_per_service() {
+        let rows = [
+            AccessRequirements {
+                layer: "Base".into(),
+                service: "Write_VIN".into(),
+                sessions: vec!["Extended".into(), "Programming".into()],
+                security_levels: vec!["Level_01".into()],
+                ..Default::default()
+            },
+            AccessRequirements {
+                layer: "Base".into(),
+                service: "Read, all".into(),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            access_matrix_csv(&rows),
+            "layer,service,sessions,security_levels,authentication_roles,other_preconditions\n\
+             Base,Write_VIN,Extended;Programming,Level_01,,\n\
+             Base,\"Read, all\",,,,\n"
+        );
+        let table = access_matrix_table(&rows);
+        assert!(
+            table.starts_with("Layer  Service    Sessions              Security"),
+            "{table}"
+        );
+        assert!(
+            table
+                .ends_with("Base   Read, all  -                     -         -               -\n"),
+            "{table}"
+        );
+    }
+
+    #[test]
+    fn params_show_internal_and_physical_constraints() {
+        let closed = |lo: &str, hi: &str| diag_ir::InternalConstr {
+            lower_limit: Some(diag_ir::Limit {
+                value: lo.into(),
+                interval_type: diag_ir::IntervalType::Closed,
+            }),
+            upper_limit: Some(diag_ir::Limit {
+                value: hi.into(),
+                interval_type: diag_ir::IntervalType::Closed,
+            }),
+            scale_constrs: vec![],
+        };
+        let rows = [
+            ParamConstraints {
+                layer: "Base".into(),
+                service: "Read_Temperature".into(),
+                param: "Coolant".into(),
+                dop: "Temperature".into(),
+                internal: Some(closed("0", "255")),
+                physical: Some(closed("-40", "215")),
+            },
+            ParamConstraints {
+                layer: "Base".into(),
+                service: "Read_Temperature".into(),
+                param: "Oil".into(),
+                dop: "Temperature_Raw".into(),
+                internal: None,
+                physical: None,
+            },
+        ];
+        assert_eq!(
+            params_csv(&rows),
+            "layer,service,param,dop,internal_constr,phys_constr\n\
+             Base,Read_Temperature,Coolant,Temperature,\"[0, 255]\",\"[-40, 215]\"\n\
+             Base,Read_Temperature,Oil,Temperature_Raw,,\n"
+        );
+        let table = params_table(&rows);
+        assert!(
+            table.contains("Param    DOP              Internal-Constr  Phys-Constr\n"),
+            "{table}"
+        );
+        assert!(
+            table.contains("Coolant  Temperature      [0, 255]         [-40, 215]\n"),
+            "{table}"
+        );
+
+        let input = fixture("odx/minimal.odx");
+        let db = parse_filtered(&input, None, None).unwrap();
+        assert!(!param_rows(&db, &[]).unwrap().is_empty());
+    }
+}
@@ -1,10 +1,37 @@
+mod capabilities;
 mod convert;
+mod delta;
+mod diff;
+mod doctor;
+mod dtc_texts;
+mod dump_ir;
+mod exec;
+mod export;
+mod formats;
+mod grep;
+mod ids;
 mod info;
+mod list;
+mod provenance;
+mod record;
+mod renames;
+mod repack;
+mod roundtrip;
+mod script;
+mod sidecar_log;
+mod simulate;
+mod size_budget;
+mod snippet;
+mod stats;
+#[cfg(test)]
+mod test_support;
 mod validate;
 
 use anyhow::{Result, bail};
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 #[derive(Parser)]
 #[command(
@@ -24,7 +51,8 @@ struct Cli {
 enum Command {
     /// Convert between diagnostic formats (ODX, YAML, MDD)
     Convert {
-        /// Input file(s) (.odx, .pdx, .yml/.yaml, .mdd)
+        /// Input file(s) (.odx, .pdx, .yml/.yaml, .mdd), or a directory of
+        /// ODX files to load as one project
         #[arg(required = true)]
         input: Vec<PathBuf>,
 
@@ -36,7 +64,12 @@ enum Command {
         #[arg(short = 'O', long, conflicts_with = "output")]
         output_dir: Option<PathBuf>,
 
-        /// Output format when using -O (odx, yaml, mdd)
+        /// Input format (odx, pdx, cdd, yaml, mdd, json); by default detected from the
+        /// extension, then from the content
+        #[arg(long, value_name = "FORMAT")]
+        from: Option<Format>,
+
+        /// Output format when using -O (odx, pdx, yaml, mdd, json)
         #[arg(short, long, default_value = "mdd")]
         format: String,
 
@@ -48,9 +81,41 @@ enum Command {
         #[arg(long)]
         dry_run: bool,
 
-        /// Filter output by audience (e.g. development, aftermarket, oem)
-        #[arg(long)]
-        audience: Option<String>,
+        /// Filter output by audience: a name (development, aftermarket, oem),
+        /// alternatives (development|oem) or a negation (!aftermarket)
+        #[arg(long, value_name = "EXPR")]
+        audience: Option<diag_ir::AudienceFilter>,
+
+        /// Write one output per audience from a single parse (e.g.
+        /// development,manufacturing,aftersales), named <stem>_<audience>.<ext>
+        #[arg(
+            long,
+            value_name = "AUDIENCES",
+            value_delimiter = ',',
+            conflicts_with = "audience"
+        )]
+        split_by_audience: Vec<String>,
+
+        /// Keep only services reachable from this functional group (after inheritance)
+        #[arg(long, value_name = "NAME")]
+        functional_group: Option<String>,
+
+        /// Comma-separated feature tags to build for (e.g. base,debug); untagged items are always kept
+        #[arg(long, value_name = "TAGS", value_delimiter = ',')]
+        features: Option<Vec<String>>,
+
+        /// Comma-separated variants to keep; MDD input skips decoding the others
+        #[arg(long = "variant", value_name = "NAMES", value_delimiter = ',')]
+        variants: Vec<String>,
+
+        /// Comma-separated lifecycle statuses to drop (draft, released, deprecated, obsolete)
+        #[arg(long, value_name = "STATUS", value_delimiter = ',')]
+        exclude_lifecycle: Vec<diag_ir::LifecycleStatus>,
+
+        /// Keep only the services, or DTCs, matching a filter expression such as
+        /// 'service.semantic == ROUTINE && service.access.security >= level1'
+        #[arg(long = "where", value_name = "EXPR")]
+        filter: Option<diag_ir::FilterExpr>,
 
         /// Directory containing job files (JARs) referenced by SingleEcuJob ProgCode entries
         #[arg(long)]
@@ -60,9 +125,282 @@ enum Command {
         #[arg(short = 'L', long)]
         lenient: bool,
 
+        /// Fail on YAML keys the format does not know (e.g. a misspelled `writeable:`),
+        /// listing each with the nearest known key
+        #[arg(long)]
+        strict: bool,
+
+        /// Convert what is readable of a truncated or corrupted MDD input, with a warning for
+        /// each damaged part, instead of failing
+        #[arg(long)]
+        force: bool,
+
+        /// Keep ODX elements/attributes the converter does not model and re-emit them in ODX output
+        #[arg(long)]
+        preserve_unknown_xml: bool,
+
         /// Write .log file alongside output (off, info, debug)
         #[arg(long, default_value = "off")]
         log_level: String,
+
+        /// Layout of the .log file: key-value text or JSON lines (text, json)
+        #[arg(long, value_name = "FORMAT", default_value = "text")]
+        log_format: sidecar_log::LogFormat,
+
+        /// Omit provenance metadata (converter version, input hash, command line, timestamp)
+        #[arg(long)]
+        deterministic: bool,
+
+        /// Sort into canonical order (and pretty-print ODX); allows same-format conversion
+        #[arg(long)]
+        normalize: bool,
+
+        /// Replace invalid characters in service, job and DTC short names and add _2, _3, ...
+        /// suffixes to repeated ones; every rename is logged
+        #[arg(long)]
+        fix_short_names: bool,
+
+        /// Fail with a loss report if the output format cannot represent the whole input
+        #[arg(long)]
+        strict_roundtrip: bool,
+
+        /// Write <output>.loss.json listing what the output format could not represent
+        #[arg(long)]
+        loss_report: bool,
+
+        /// Spelling of DID/RID/DTC keys in YAML output (decimal, hex, padded-hex)
+        #[arg(long, value_name = "POLICY", default_value = "padded-hex")]
+        yaml_numerals: diag_yaml::NumeralPolicy,
+
+        /// Write only these top-level YAML sections (e.g. dids,dtcs), plus schema and ecu
+        #[arg(long, value_name = "SECTIONS", value_delimiter = ',')]
+        sections: Vec<String>,
+
+        /// ODX version of ODX/PDX output (2.0.1, 2.2.0, 2.3.0); by default the element
+        /// names of 2.2.0 with the input's model version
+        #[arg(long, value_name = "VERSION")]
+        odx_version: Option<diag_odx::OdxVersion>,
+
+        /// Fail if the MDD output is larger than SIZE (e.g. 25MB, 512KiB)
+        #[arg(long, value_name = "SIZE")]
+        max_output_size: Option<size_budget::ByteSize>,
+
+        /// YAML file with max_output_size and per-variant size budgets
+        #[arg(long, value_name = "FILE")]
+        size_policy: Option<PathBuf>,
+
+        /// Output profile: full, or minimal to drop long names, descriptions, SDGs and admin data
+        #[arg(long, value_name = "PROFILE", default_value = "full")]
+        profile: diag_ir::Profile,
+
+        /// Write <output>.profile.json with what the profile removed
+        #[arg(long)]
+        profile_mapping: bool,
+
+        /// Skip inputs whose validity window does not cover DATE (YYYY-MM-DD or today)
+        #[arg(long, value_name = "DATE", value_parser = convert::parse_valid_on)]
+        valid_on: Option<diag_ir::Date>,
+
+        /// Skip inputs that do not apply to this vehicle model year
+        #[arg(long, value_name = "YEAR")]
+        model_year: Option<u16>,
+
+        /// Embed a name search index in MDD output, so `grep` does not have to decode the
+        /// diagnostic description
+        #[arg(long)]
+        search_index: bool,
+
+        /// Encrypt the description (and search index) of MDD output with AES-256-GCM under
+        /// the key in this file: 32 raw bytes or 64 hex digits
+        #[arg(long, value_name = "FILE")]
+        encrypt_key_file: Option<PathBuf>,
+
+        /// Decrypt encrypted MDD input with the key in this file: 32 raw bytes or 64 hex digits
+        #[arg(long, value_name = "FILE")]
+        decrypt_key_file: Option<PathBuf>,
+
+        /// Sign MDD output with Ed25519 under the secret key (32-byte seed, raw or as 64 hex
+        /// digits) in this file; `validate --public-key` checks the signature
+        #[arg(long, value_name = "FILE")]
+        sign_key_file: Option<PathBuf>,
+
+        /// Which definition of a trouble code or DID defined more than once to keep: first,
+        /// last, error (fail listing them) or merge-texts (first DTC with all distinct texts)
+        #[arg(long, value_name = "POLICY", default_value = "first")]
+        duplicates: diag_ir::DuplicatePolicy,
+
+        /// What to do with characters XML cannot represent (control characters) in names and
+        /// texts: strip or escape (as \x1B); every changed field is reported
+        #[arg(long, value_name = "POLICY", default_value = "strip")]
+        invalid_chars: diag_ir::InvalidCharPolicy,
+
+        /// Fail, listing the fields, if names or texts hold characters XML cannot represent
+        #[arg(long)]
+        fail_on_invalid_chars: bool,
+    },
+
+    /// Merge files of one ECU (e.g. YAML fragments for DTCs, DIDs and routines) into one,
+    /// failing on items they define differently
+    Merge {
+        /// Input files (.odx, .pdx, .yml/.yaml, .mdd), merged in order
+        #[arg(required = true, num_args = 2..)]
+        input: Vec<PathBuf>,
+
+        /// Output file (.odx, .yml/.yaml, .mdd)
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Compression for MDD output (lzma, gzip, zstd, none)
+        #[arg(long, default_value = "lzma")]
+        compression: String,
+
+        /// Keep the comments of the first input when it and the output are YAML
+        #[arg(long)]
+        preserve_comments: bool,
+    },
+
+    /// Rewrite a file in canonical order without changing its format
+    Normalize {
+        /// Input file (.odx, .yml/.yaml, .mdd)
+        input: PathBuf,
+
+        /// Output file (default: rewrite the input in place)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Keep the comments of a YAML input (anchors are still expanded)
+        #[arg(long)]
+        preserve_comments: bool,
+
+        /// Spelling of DID/RID/DTC keys in YAML output (decimal, hex, padded-hex)
+        #[arg(long, value_name = "POLICY", default_value = "padded-hex")]
+        yaml_numerals: diag_yaml::NumeralPolicy,
+    },
+
+    /// Edit a file with a Rhai script (rename services, set audiences, drop params) or a
+    /// rename map
+    Transform {
+        /// Input file (.odx, .pdx, .yml/.yaml, .mdd)
+        input: PathBuf,
+
+        /// Rhai script to run on the database
+        #[arg(long, value_name = "FILE")]
+        script: Option<PathBuf>,
+
+        /// CSV with `old` and `new` columns; renames services, jobs, DOPs and states and
+        /// the references to them. Entries that match nothing are warnings
+        #[arg(long, value_name = "FILE")]
+        rename_map: Option<PathBuf>,
+
+        /// Report the renames and unmatched rename-map entries without writing
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Output file (default: rewrite the input in place)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Keep the comments of a YAML input (anchors are still expanded)
+        #[arg(long)]
+        preserve_comments: bool,
+    },
+
+    /// Write the part of a file a query selects (e.g. only the DATA-READ services of one
+    /// variant) as a new file
+    Extract {
+        /// Input file (.odx, .pdx, .yml/.yaml, .mdd, .json)
+        input: PathBuf,
+
+        /// Output file; its extension selects the format
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Selector such as services[semantic=DATA-READ], jobs[name=Flash*] or
+        /// dtcs[code=0x123456]; repeat to combine
+        #[arg(long, value_name = "SELECTOR")]
+        select: Vec<diag_ir::Selector>,
+
+        /// Comma-separated variants to keep; MDD input skips decoding the others
+        #[arg(long = "variant", value_name = "NAMES", value_delimiter = ',')]
+        variants: Vec<String>,
+
+        /// Keep only services reachable from this functional group (after inheritance)
+        #[arg(long, value_name = "NAME")]
+        functional_group: Option<String>,
+
+        /// Input format (odx, pdx, cdd, yaml, mdd, json); by default detected from the
+        /// extension, then from the content
+        #[arg(long, value_name = "FORMAT")]
+        from: Option<Format>,
+
+        /// Compression for MDD output (lzma, gzip, zstd, none)
+        #[arg(long, default_value = "lzma")]
+        compression: String,
+    },
+
+    /// Recompress an MDD file and migrate it to the current schema
+    Repack {
+        /// Input MDD file
+        input: PathBuf,
+
+        /// Output MDD file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Compression for the diagnostic description (lzma, gzip, zstd, none)
+        #[arg(long, default_value = "lzma")]
+        compression: String,
+
+        /// Compression level (lzma/gzip: 0-9, zstd: 1-22; default: codec default)
+        #[arg(long)]
+        level: Option<i32>,
+    },
+
+    /// Write a binary patch turning one MDD file into another, e.g. for over-the-air updates
+    Delta {
+        /// MDD file the patch applies to
+        old: PathBuf,
+
+        /// MDD file the patch produces
+        new: PathBuf,
+
+        /// Patch file (.mddp)
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Rebuild an MDD file from the old one and a patch written by `delta`, verifying both
+    /// against the SHA-512 digests in the patch
+    ApplyDelta {
+        /// MDD file the patch was made for
+        old: PathBuf,
+
+        /// Patch file (.mddp)
+        patch: PathBuf,
+
+        /// Patched MDD file
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Convert a file to another format and back, and report every IR field the round trip
+    /// changes
+    Roundtrip {
+        /// Input file (.odx, .pdx, .yml/.yaml, .mdd, .json)
+        input: PathBuf,
+
+        /// Intermediate format (odx, pdx, yaml, mdd, json)
+        #[arg(long, value_name = "FORMAT")]
+        via: Format,
+
+        /// Input format (odx, pdx, cdd, yaml, mdd, json); by default detected from the
+        /// extension, then from the content
+        #[arg(long, value_name = "FORMAT")]
+        from: Option<Format>,
+
+        /// Keep the intermediate file at this path instead of a temporary file
+        #[arg(long, value_name = "PATH")]
+        keep: Option<PathBuf>,
     },
 
     /// Validate a diagnostic input file
@@ -70,6 +408,11 @@ enum Command {
         /// Input file to validate (.odx, .yml/.yaml, .mdd)
         input: PathBuf,
 
+        /// Input format (odx, pdx, cdd, yaml, mdd, json); by default detected from the
+        /// extension, then from the content
+        #[arg(long, value_name = "FORMAT")]
+        from: Option<Format>,
+
         /// Suppress individual error output
         #[arg(short, long)]
         quiet: bool,
@@ -77,34 +420,438 @@ enum Command {
         /// Print summary count only
         #[arg(short, long)]
         summary: bool,
+
+        /// YAML file with complexity budgets (max_services_per_variant, max_params_per_response,
+        /// max_structure_depth, max_end_of_pdu_items); violations fail validation
+        #[arg(long, value_name = "FILE")]
+        policy: Option<PathBuf>,
+
+        /// Check the Ed25519 signature of MDD input against the public key (32 bytes, raw or
+        /// as 64 hex digits) in this file; an unsigned or mismatching file fails validation
+        #[arg(long, value_name = "FILE")]
+        public_key: Option<PathBuf>,
+    },
+
+    /// Triage a file: detected format, encoding, ODX version, schema violations, IR size and
+    /// suggested flags
+    Doctor {
+        /// File to inspect
+        input: PathBuf,
+
+        /// Decrypt encrypted MDD input with the key in this file: 32 raw bytes or 64 hex digits
+        #[arg(long, value_name = "FILE")]
+        decrypt_key_file: Option<PathBuf>,
     },
 
+    /// Show which IR features (tables, memory, audiences, ...) each output format keeps
+    Capabilities,
+
     /// Display information about a diagnostic file
     Info {
         /// Input file (.odx, .yml/.yaml, .mdd)
         input: PathBuf,
+
+        /// Input format (odx, pdx, cdd, yaml, mdd, json); by default detected from the
+        /// extension, then from the content
+        #[arg(long, value_name = "FORMAT")]
+        from: Option<Format>,
+
+        /// Also list the services of every variant, params by DOP kind, SDG counts, the
+        /// memory map and, for MDD input, the container chunks
+        #[arg(long)]
+        detailed: bool,
+
+        /// Decrypt encrypted MDD input with the key in this file: 32 raw bytes or 64 hex digits
+        #[arg(long, value_name = "FILE")]
+        decrypt_key_file: Option<PathBuf>,
+    },
+
+    /// Show the serialized size and object counts per variant and the largest services
+    Stats {
+        /// Input file (.odx, .pdx, .yml/.yaml, .mdd)
+        input: PathBuf,
+
+        /// Input format (odx, pdx, cdd, yaml, mdd, json); by default detected from the
+        /// extension, then from the content
+        #[arg(long, value_name = "FORMAT")]
+        from: Option<Format>,
+
+        /// Number of services in the largest-services table
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+
+    /// Print the IR of a file (or the part a path selects) indented, with struct and enum
+    /// names
+    DumpIr {
+        /// Input file (.odx, .pdx, .yml/.yaml, .mdd)
+        input: PathBuf,
+
+        /// Input format (odx, pdx, cdd, yaml, mdd, json); by default detected from the
+        /// extension, then from the content
+        #[arg(long, value_name = "FORMAT")]
+        from: Option<Format>,
+
+        /// Part of the IR to print, e.g. `variants[0].diag_services[3]`; fields of a layer
+        /// can be named directly on its variant
+        #[arg(long)]
+        path: Option<String>,
+
+        /// Nesting levels to expand; deeper structs and lists are collapsed
+        #[arg(long)]
+        depth: Option<usize>,
+
+        /// List and map entries to print before eliding the rest (0 prints all)
+        #[arg(long, default_value_t = 20)]
+        max_items: usize,
+
+        /// Characters of a string to print before truncating it (0 prints all)
+        #[arg(long, default_value_t = 120)]
+        max_string: usize,
+    },
+
+    /// List the services of a diagnostic file with their semantic
+    List {
+        /// Input file (.odx, .pdx, .yml/.yaml, .mdd)
+        input: PathBuf,
+
+        /// Only list services with these semantics, e.g. DATA-READ,ROUTINE (any spelling,
+        /// e.g. DATAREAD; services without one are classified by their service ID)
+        #[arg(long, value_delimiter = ',')]
+        semantic: Vec<String>,
+
+        /// Only list services matching a filter expression, e.g.
+        /// 'service.access.session == Extended && !service.access.security'
+        #[arg(long = "where", value_name = "EXPR")]
+        filter: Option<diag_ir::FilterExpr>,
+
+        /// Only list services reachable from this functional group (after inheritance)
+        #[arg(long, value_name = "NAME")]
+        functional_group: Option<String>,
+
+        /// What to list: services, access-matrix for the sessions, security levels and
+        /// authentication roles each service requires, or params for the INTERNAL-CONSTR and
+        /// PHYS-CONSTR of each service parameter
+        #[arg(long, default_value = "services")]
+        kind: list::ListKind,
+
+        /// Print the access matrix or parameter constraints as CSV
+        #[arg(long)]
+        csv: bool,
+
+        /// Write the access matrix or parameter constraints to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Compare two diagnostic files (in any formats): added, removed and changed variants,
+    /// services, DIDs, state charts, ComParams and DTCs
+    Diff {
+        /// Old file (.odx, .pdx, .yml/.yaml, .mdd)
+        old: PathBuf,
+
+        /// New file (.odx, .pdx, .yml/.yaml, .mdd)
+        new: PathBuf,
+
+        /// Report layout (text, json)
+        #[arg(long, value_name = "FORMAT", default_value = "text")]
+        format: diff::DiffFormat,
+    },
+
+    /// Find layers, services, jobs, parameters and DTCs by name
+    Grep {
+        /// Input file (.odx, .pdx, .yml/.yaml, .mdd)
+        input: PathBuf,
+
+        /// Text the name contains, ignoring case
+        pattern: String,
+
+        /// Only show these kinds (layer, service, job, param, dtc)
+        #[arg(long, value_delimiter = ',')]
+        kind: Vec<diag_ir::SearchKind>,
+
+        /// Only search the services, or DTCs, matching a filter expression, e.g.
+        /// 'service.sid == 0x22'; the file is then parsed instead of using an
+        /// embedded search index
+        #[arg(long = "where", value_name = "EXPR")]
+        filter: Option<diag_ir::FilterExpr>,
+    },
+
+    /// Export reports derived from a diagnostic file
+    Export {
+        #[command(subcommand)]
+        report: ExportCommand,
+    },
+
+    /// Merge data maintained outside the database back into it
+    Import {
+        #[command(subcommand)]
+        data: ImportCommand,
+    },
+
+    /// Check that DID, routine ID and DTC numbers lie in the reserved ranges
+    CheckIds {
+        /// Input file (.odx, .pdx, .yml/.yaml, .mdd)
+        input: PathBuf,
+
+        /// YAML policy file listing reserved `dids`, `rids` and `dtcs` ranges
+        #[arg(long)]
+        policy: PathBuf,
+    },
+
+    /// Suggest the next free DID in a range
+    AllocateDid {
+        /// Input file (.odx, .pdx, .yml/.yaml, .mdd)
+        input: PathBuf,
+
+        /// Range to allocate from, e.g. 0x0200-0x02FF
+        #[arg(long)]
+        range: String,
+
+        /// Number of identifiers to suggest
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+    },
+
+    /// Proxy a tester session to an ECU and record the annotated exchanges
+    Record {
+        /// ECU to forward to, e.g. doip:192.168.1.10[:13400]
+        #[arg(long)]
+        transport: String,
+
+        /// Database used to annotate requests with service names
+        #[arg(long)]
+        db: PathBuf,
+
+        /// Output session file (.json)
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Address the tester connects to
+        #[arg(long, default_value = "0.0.0.0:13400")]
+        listen: String,
+    },
+
+    /// Run a service against a live ECU and print the decoded response
+    Exec {
+        /// Database file (.odx, .pdx, .yml/.yaml, .mdd)
+        db: PathBuf,
+
+        /// Short name or alias of the service to run, in any case
+        #[arg(long)]
+        service: String,
+
+        /// ECU to send to, e.g. doip:192.168.1.10[:13400] or isotp:can0:0x7E0:0x7E8
+        /// (default: the DoIP entity named by the database)
+        #[arg(long)]
+        transport: Option<String>,
+
+        /// DoIP logical address of the tester (default: from the database, else 0x0E00)
+        #[arg(long, value_parser = exec::parse_address)]
+        tester_addr: Option<u16>,
+
+        /// DoIP logical address of the ECU (default: from the database, else reported
+        /// by routing activation)
+        #[arg(long, value_parser = exec::parse_address)]
+        ecu_addr: Option<u16>,
+
+        /// Request parameter value, NAME=VALUE (repeatable)
+        #[arg(long = "param", value_name = "NAME=VALUE")]
+        params: Vec<String>,
+
+        /// Program computing security keys, called as `<program> <level> <seed-hex>`
+        /// (default: prompt for the key)
+        #[arg(long)]
+        key_command: Option<PathBuf>,
+
+        /// Send the request without entering its session or security level first
+        #[arg(long)]
+        skip_preconditions: bool,
+    },
+
+    /// Re-send a recorded session and compare the ECU's responses
+    Replay {
+        /// Session file written by `record`
+        session: PathBuf,
+
+        /// ECU to send to, e.g. doip:192.168.1.10[:13400] or isotp:can0:0x7E0:0x7E8
+        #[arg(long)]
+        transport: String,
+    },
+
+    /// Run a mock ECU over DoIP whose fault memory is seeded from the database
+    Simulate {
+        /// Database file (.odx, .pdx, .yml/.yaml, .mdd)
+        db: PathBuf,
+
+        /// Address testers connect to
+        #[arg(long, default_value = "0.0.0.0:13400")]
+        listen: String,
+
+        /// Address of the fault injection control socket (inject, clear, list)
+        #[arg(long, value_name = "ADDR")]
+        control: Option<String>,
+
+        /// DoIP logical address of the ECU (default: from the database, else 0x1000)
+        #[arg(long, value_parser = exec::parse_address)]
+        ecu_addr: Option<u16>,
+
+        /// Simulator profile (YAML) with per-service latency, NRC 0x78 sequences and
+        /// injected negative responses
+        #[arg(long, value_name = "FILE")]
+        profile: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ExportCommand {
+    /// CSV mapping requirement IDs to the services and jobs implementing them
+    TraceMatrix {
+        /// Input file (.odx, .pdx, .yml/.yaml, .mdd)
+        input: PathBuf,
+
+        /// Output CSV file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// The flash_sequence resolved to the services it sends
+    FlashPlan {
+        /// Input file (.odx, .pdx, .yml/.yaml, .mdd)
+        input: PathBuf,
+
+        /// Output format (markdown, json)
+        #[arg(short, long, default_value = "markdown")]
+        format: String,
+
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// CSV of DTC texts keyed by trouble code and text ID, with their translations
+    DtcTexts {
+        /// Input file (.odx, .pdx, .yml/.yaml, .mdd)
+        input: PathBuf,
+
+        /// Language of the translation column (e.g. de)
+        #[arg(long)]
+        lang: String,
+
+        /// Output CSV file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Mapping of the ODX IDs and short names of layers, services, jobs and DTCs to their
+    /// object indices in MDD output
+    #[command(name = "idmap", alias = "id-map")]
+    IdMap {
+        /// Input file (.odx, .pdx, ODX project directory, .yml/.yaml, .mdd)
+        input: PathBuf,
+
+        /// Output format (csv, json)
+        #[arg(short, long, default_value = "csv")]
+        format: String,
+
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImportCommand {
+    /// Translated DTC texts from a CSV written by `export dtc-texts`
+    DtcTexts {
+        /// Database file (.odx, .yml/.yaml, .mdd)
+        input: PathBuf,
+
+        /// CSV with the translations
+        texts: PathBuf,
+
+        /// Language of the translations (e.g. de)
+        #[arg(long)]
+        lang: String,
+
+        /// Output file (default: rewrite the input in place)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub(crate) enum Format {
-    Odx,
-    Pdx,
-    Yaml,
-    Mdd,
+/// A file format: a handle to the [`formats::FormatProvider`] that reads and
+/// writes it. The built-in formats are associated constants such as
+/// [`Format::ODX`]; formats compare by name.
+#[derive(Clone, Copy)]
+pub(crate) struct Format(&'static dyn formats::FormatProvider);
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        formats::by_name(s)
+            .ok_or_else(|| format!("unknown format '{s}' (expected {})", formats::names()))
+    }
 }
 
 pub(crate) fn detect_format(path: &Path) -> Result<Format> {
     match path.extension().and_then(|e| e.to_str()) {
-        Some("odx") => Ok(Format::Odx),
-        Some("pdx") => Ok(Format::Pdx),
-        Some("yml" | "yaml") => Ok(Format::Yaml),
-        Some("mdd") => Ok(Format::Mdd),
-        Some(ext) => bail!("Unknown file extension: .{ext}"),
+        Some(ext) => formats::by_extension(ext)
+            .ok_or_else(|| anyhow::anyhow!("Unknown file extension: .{ext}")),
         None => bail!("Cannot detect format: file has no extension"),
     }
 }
 
+/// Format of an input file: `from` when given, else the extension, else the
+/// content (for `.xml` files and extensionless blobs). A directory is an ODX
+/// project.
+pub(crate) fn detect_input_format(path: &Path, from: Option<Format>) -> Result<Format> {
+    if let Some(format) = from {
+        return Ok(format);
+    }
+    if path.is_dir() {
+        return Ok(Format::ODX);
+    }
+    let err = match detect_format(path) {
+        Ok(format) => return Ok(format),
+        Err(err) => err,
+    };
+    let mut prefix = Vec::new();
+    if let Ok(file) = std::fs::File::open(path) {
+        // A read error leaves the prefix short; the caller reports it on open.
+        let _ = file.take(SNIFF_PREFIX as u64).read_to_end(&mut prefix);
+    }
+    sniff_format(&prefix).ok_or_else(|| {
+        anyhow::anyhow!("{err}, and the content is not recognized either (pass --from)")
+    })
+}
+
+/// How much of a file [`sniff_format`] looks at.
+const SNIFF_PREFIX: usize = 4096;
+
+/// Detect the format of file content: the MDD magic header, a ZIP archive
+/// (PDX), an `<ODX` or `<CANDELA` (CDD) root element, a JSON object naming
+/// the IR schema, or YAML with a top-level `schema:` or `ecu:` key. Only the
+/// first few KiB are looked at.
+pub(crate) fn sniff_format(bytes: &[u8]) -> Option<Format> {
+    let prefix = &bytes[..bytes.len().min(SNIFF_PREFIX)];
+    let utf16 = |unit: fn([u8; 2]) -> u16, data: &[u8]| {
+        char::decode_utf16(data.chunks_exact(2).map(|c| unit([c[0], c[1]])))
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect::<String>()
+    };
+    let text = match prefix {
+        [0xFF, 0xFE, rest @ ..] => utf16(u16::from_le_bytes, rest),
+        [0xFE, 0xFF, rest @ ..] => utf16(u16::from_be_bytes, rest),
+        [b'<', 0, ..] => utf16(u16::from_le_bytes, prefix),
+        [0, b'<', ..] => utf16(u16::from_be_bytes, prefix),
+        _ => String::from_utf8_lossy(prefix).into_owned(),
+    };
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    formats::sniff(bytes, text)
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
@@ -113,13 +860,45 @@ fn main() -> Result<()> {
             input,
             output,
             output_dir,
+            from,
             format,
             compression,
             dry_run,
             audience,
+            split_by_audience,
+            functional_group,
+            features,
+            variants,
+            exclude_lifecycle,
+            filter,
             include_job_files,
             lenient,
+            strict,
+            force,
+            preserve_unknown_xml,
             log_level,
+            log_format,
+            deterministic,
+            normalize,
+            fix_short_names,
+            strict_roundtrip,
+            loss_report,
+            yaml_numerals,
+            sections,
+            odx_version,
+            max_output_size,
+            size_policy,
+            profile,
+            profile_mapping,
+            valid_on,
+            model_year,
+            search_index,
+            encrypt_key_file,
+            decrypt_key_file,
+            sign_key_file,
+            duplicates,
+            invalid_chars,
+            fail_on_invalid_chars,
         }) => {
             let env_level = match log_level.as_str() {
                 "debug" => "debug",
@@ -129,30 +908,77 @@ fn main() -> Result<()> {
             env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(env_level))
                 .init();
 
+            let mut size_policy = match &size_policy {
+                Some(path) => size_budget::SizePolicy::load(path)?,
+                None => size_budget::SizePolicy::default(),
+            };
+            if max_output_size.is_some() {
+                size_policy.max_output_size = max_output_size;
+            }
+
+            let opts = convert::ConvertOptions {
+                from,
+                compression,
+                dry_run,
+                audience,
+                split_audiences: split_by_audience,
+                functional_group,
+                features,
+                variants,
+                exclude_lifecycle,
+                select: vec![],
+                filter,
+                include_job_files,
+                lenient,
+                strict,
+                force,
+                preserve_unknown_xml,
+                preserve_comments: false,
+                yaml_numerals,
+                yaml_sections: sections,
+                odx_version,
+                log_level,
+                log_format,
+                deterministic,
+                normalize,
+                fix_short_names,
+                strict_roundtrip,
+                loss_report,
+                size_policy,
+                dtc_texts: None,
+                merge: vec![],
+                script: None,
+                rename_map: None,
+                profile,
+                profile_mapping,
+                valid_on,
+                model_year,
+                search_index,
+                decryption: decrypt_key_file
+                    .as_deref()
+                    .map(convert::load_decryption_key)
+                    .transpose()?,
+                encryption: encrypt_key_file
+                    .as_deref()
+                    .map(convert::load_encryption_key)
+                    .transpose()?,
+                signing: sign_key_file
+                    .as_deref()
+                    .map(convert::load_signing_key)
+                    .transpose()?,
+                duplicates,
+                invalid_chars: if fail_on_invalid_chars {
+                    diag_ir::InvalidCharPolicy::Error
+                } else {
+                    invalid_chars
+                },
+            };
+
             if let (1, Some(out)) = (input.len(), &output) {
-                convert::run_convert(
-                    &input[0],
-                    out,
-                    &compression,
-                    dry_run,
-                    audience.as_deref(),
-                    include_job_files.as_deref(),
-                    lenient,
-                    &log_level,
-                )
+                convert::run_convert(&input[0], out, &opts)
             } else if let Some(dir) = &output_dir {
                 let ext = convert::format_extension(&format)?;
-                convert::run_batch_convert(
-                    &input,
-                    dir,
-                    ext,
-                    &compression,
-                    dry_run,
-                    audience.as_deref(),
-                    include_job_files.as_deref(),
-                    lenient,
-                    &log_level,
-                )
+                convert::run_batch_convert(&input, dir, ext, &opts)
             } else if input.len() > 1 {
                 bail!("Multiple input files require -O/--output-dir instead of -o/--output")
             } else {
@@ -160,13 +986,333 @@ fn main() -> Result<()> {
             }
         }
 
+        Some(Command::Merge {
+            input,
+            output,
+            compression,
+            preserve_comments,
+        }) => {
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+                .init();
+            let opts = convert::ConvertOptions {
+                merge: input[1..].to_vec(),
+                compression,
+                preserve_comments,
+                ..Default::default()
+            };
+            convert::run_convert(&input[0], &output, &opts)
+        }
+
+        Some(Command::Normalize {
+            input,
+            output,
+            preserve_comments,
+            yaml_numerals,
+        }) => {
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+                .init();
+            let opts = convert::ConvertOptions {
+                normalize: true,
+                deterministic: true,
+                preserve_comments,
+                yaml_numerals,
+                ..Default::default()
+            };
+            convert::run_convert(&input, output.as_deref().unwrap_or(&input), &opts)
+        }
+
+        Some(Command::Transform {
+            input,
+            script,
+            rename_map,
+            dry_run,
+            output,
+            preserve_comments,
+        }) => {
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+                .init();
+            if script.is_none() && rename_map.is_none() {
+                bail!("Nothing to transform; pass --script or --rename-map");
+            }
+            let opts = convert::ConvertOptions {
+                script,
+                rename_map,
+                dry_run,
+                deterministic: true,
+                preserve_comments,
+                ..Default::default()
+            };
+            convert::run_convert(&input, output.as_deref().unwrap_or(&input), &opts)
+        }
+
+        Some(Command::Extract {
+            input,
+            output,
+            select,
+            variants,
+            functional_group,
+            from,
+            compression,
+        }) => {
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+                .init();
+            if select.is_empty() && variants.is_empty() && functional_group.is_none() {
+                bail!("Nothing to extract; pass --select, --variant or --functional-group");
+            }
+            let opts = convert::ConvertOptions {
+                from,
+                compression,
+                functional_group,
+                variants,
+                select,
+                ..Default::default()
+            };
+            convert::run_convert(&input, &output, &opts)
+        }
+
+        Some(Command::Repack {
+            input,
+            output,
+            compression,
+            level,
+        }) => {
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+                .init();
+            repack::run_repack(&input, &output, &compression, level)
+        }
+
+        Some(Command::Delta { old, new, output }) => delta::run_delta(&old, &new, &output),
+
+        Some(Command::ApplyDelta { old, patch, output }) => {
+            delta::run_apply_delta(&old, &patch, &output)
+        }
+
         Some(Command::Validate {
             input,
+            from,
+            quiet,
+            summary,
+            policy,
+            public_key,
+        }) => validate::run_validate(
+            &input,
+            from,
             quiet,
             summary,
-        }) => validate::run_validate(&input, quiet, summary),
+            policy.as_deref(),
+            public_key.as_deref(),
+        ),
+
+        Some(Command::Roundtrip {
+            input,
+            via,
+            from,
+            keep,
+        }) => roundtrip::run_roundtrip(&input, from, via, keep.as_deref()),
+
+        Some(Command::Capabilities) => {
+            capabilities::run_capabilities();
+            Ok(())
+        }
+
+        Some(Command::Info {
+            input,
+            from,
+            detailed,
+            decrypt_key_file,
+        }) => {
+            let keys = decrypt_key_file
+                .as_deref()
+                .map(convert::load_decryption_key)
+                .transpose()?;
+            info::run_info(&input, from, detailed, keys.as_ref())
+        }
+
+        Some(Command::Doctor {
+            input,
+            decrypt_key_file,
+        }) => doctor::run_doctor(
+            &input,
+            decrypt_key_file
+                .as_deref()
+                .map(convert::load_decryption_key)
+                .transpose()?,
+        ),
+
+        Some(Command::Stats { input, from, top }) => stats::run_stats(&input, from, top),
+
+        Some(Command::DumpIr {
+            input,
+            from,
+            path,
+            depth,
+            max_items,
+            max_string,
+        }) => dump_ir::run_dump_ir(
+            &input,
+            from,
+            path.as_deref(),
+            &dump_ir::Limits {
+                depth,
+                items: max_items,
+                string: max_string,
+            },
+        ),
+
+        Some(Command::List {
+            input,
+            semantic,
+            filter,
+            functional_group,
+            kind,
+            csv,
+            output,
+        }) => {
+            let functional_group = functional_group.as_deref();
+            match kind {
+                list::ListKind::Services if csv || output.is_some() => {
+                    bail!("--csv and --output apply to --kind access-matrix and params")
+                }
+                list::ListKind::Services => {
+                    list::run_list(&input, &semantic, filter.as_ref(), functional_group)
+                }
+                list::ListKind::AccessMatrix => list::run_access_matrix(
+                    &input,
+                    &semantic,
+                    filter.as_ref(),
+                    functional_group,
+                    csv,
+                    output.as_deref(),
+                ),
+                list::ListKind::Params => list::run_params(
+                    &input,
+                    &semantic,
+                    filter.as_ref(),
+                    functional_group,
+                    csv,
+                    output.as_deref(),
+                ),
+            }
+        }
+
+        Some(Command::Diff { old, new, format }) => diff::run_diff(&old, &new, format),
+
+        Some(Command::Grep {
+            input,
+            pattern,
+            kind,
+            filter,
+        }) => grep::run_grep(&input, &pattern, &kind, filter.as_ref()),
+
+        Some(Command::Export { report }) => match report {
+            ExportCommand::TraceMatrix { input, output } => {
+                export::run_trace_matrix(&input, output.as_deref())
+            }
+            ExportCommand::FlashPlan {
+                input,
+                format,
+                output,
+            } => export::run_flash_plan(&input, &format, output.as_deref()),
+            ExportCommand::DtcTexts {
+                input,
+                lang,
+                output,
+            } => dtc_texts::run_export(&input, &lang, output.as_deref()),
+            ExportCommand::IdMap {
+                input,
+                format,
+                output,
+            } => export::run_id_map(&input, &format, output.as_deref()),
+        },
+
+        Some(Command::Import { data }) => match data {
+            ImportCommand::DtcTexts {
+                input,
+                texts,
+                lang,
+                output,
+            } => {
+                env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+                    .init();
+                // Only the translations change: keep comments and provenance.
+                let opts = convert::ConvertOptions {
+                    dtc_texts: Some((lang, texts)),
+                    deterministic: true,
+                    preserve_comments: true,
+                    ..Default::default()
+                };
+                convert::run_convert(&input, output.as_deref().unwrap_or(&input), &opts)
+            }
+        },
+
+        Some(Command::CheckIds { input, policy }) => ids::run_check_ids(&input, &policy),
+
+        Some(Command::AllocateDid {
+            input,
+            range,
+            count,
+        }) => ids::run_allocate_did(&input, &range, count),
+
+        Some(Command::Record {
+            transport,
+            db,
+            output,
+            listen,
+        }) => {
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+                .init();
+            record::run_record(&transport, &db, &output, &listen)
+        }
+
+        Some(Command::Replay { session, transport }) => {
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+                .init();
+            record::run_replay(&session, &transport)
+        }
+
+        Some(Command::Simulate {
+            db,
+            listen,
+            control,
+            ecu_addr,
+            profile,
+        }) => {
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+                .init();
+            simulate::run_simulate(
+                &db,
+                &listen,
+                control.as_deref(),
+                ecu_addr,
+                profile.as_deref(),
+            )
+        }
 
-        Some(Command::Info { input }) => info::run_info(&input),
+        Some(Command::Exec {
+            db,
+            service,
+            transport,
+            tester_addr,
+            ecu_addr,
+            params,
+            key_command,
+            skip_preconditions,
+        }) => {
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+                .init();
+            exec::run_exec(
+                &db,
+                &exec::ExecOptions {
+                    service,
+                    transport,
+                    tester_addr,
+                    ecu_addr,
+                    params,
+                    key_command,
+                    skip_preconditions,
+                },
+            )
+        }
 
         None => {
             if let Some(bare) = cli.bare_input {
@@ -176,40 +1322,81 @@ fn main() -> Result<()> {
                 );
             }
             bail!(
-                "No command specified. Use: diag-converter convert|validate|info. Run with --help for details."
+                "No command specified. Use: diag-converter {}. Run with --help for details.",
+                command_names().join("|")
             );
         }
     }
 }
 
+/// Names of the visible subcommands, read from the parser so new ones are
+/// listed without touching the usage hint.
+fn command_names() -> Vec<String> {
+    Cli::command()
+        .get_subcommands()
+        .filter(|c| !c.is_hide_set())
+        .map(|c| c.get_name().to_string())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::fixture;
     use std::path::Path;
 
+    #[test]
+    fn command_names_lists_every_subcommand() {
+        let names = command_names();
+        for name in [
+            "convert",
+            "diff",
+            "merge",
+            "extract",
+            "roundtrip",
+            "capabilities",
+            "grep",
+        ] {
+            assert!(
+                names.iter().any(|n| n == name),
+                "{name} missing from {names:?}"
+            );
+        }
+    }
+
     #[test]
     fn detect_format_odx() {
-        assert_eq!(detect_format(Path::new("file.odx")).unwrap(), Format::Odx);
+        assert_eq!(detect_format(Path::new("file.odx")).unwrap(), Format::ODX);
     }
 
     #[test]
     fn detect_format_pdx() {
-        assert_eq!(detect_format(Path::new("file.pdx")).unwrap(), Format::Pdx);
+        assert_eq!(detect_format(Path::new("file.pdx")).unwrap(), Format::PDX);
+    }
+
+    #[test]
+    fn detect_format_cdd() {
+        assert_eq!(detect_format(Path::new("file.cdd")).unwrap(), Format::CDD);
     }
 
     #[test]
     fn detect_format_yml() {
-        assert_eq!(detect_format(Path::new("file.yml")).unwrap(), Format::Yaml);
+        assert_eq!(detect_format(Path::new("file.yml")).unwrap(), Format::YAML);
     }
 
     #[test]
     fn detect_format_yaml() {
-        assert_eq!(detect_format(Path::new("file.yaml")).unwrap(), Format::Yaml);
+        assert_eq!(detect_format(Path::new("file.yaml")).unwrap(), Format::YAML);
     }
 
     #[test]
     fn detect_format_mdd() {
-        assert_eq!(detect_format(Path::new("file.mdd")).unwrap(), Format::Mdd);
+        assert_eq!(detect_format(Path::new("file.mdd")).unwrap(), Format::MDD);
+    }
+
+    #[test]
+    fn detect_format_json() {
+        assert_eq!(detect_format(Path::new("file.json")).unwrap(), Format::JSON);
     }
 
     #[test]
@@ -223,4 +1410,59 @@ mod tests {
         let err = detect_format(Path::new("noext")).unwrap_err();
         assert!(err.to_string().contains("no extension"));
     }
+
+    #[test]
+    fn sniff_format_by_content() {
+        assert_eq!(
+            sniff_format(b"MDD version 0      \0rest"),
+            Some(Format::MDD)
+        );
+        assert_eq!(sniff_format(b"PK\x03\x04zip"), Some(Format::PDX));
+        assert_eq!(
+            sniff_format(
+                b"\xEF\xBB\xBF<?xml version=\"1.0\"?>\n<!-- x -->\n<ODX MODEL-VERSION=\"2.2.0\">"
+            ),
+            Some(Format::ODX)
+        );
+        let utf16: Vec<u8> = "<ODX/>".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(sniff_format(&utf16), Some(Format::ODX));
+        assert_eq!(
+            sniff_format(b"<?xml version=\"1.0\"?>\n<CANDELA dtdvers=\"1.9\">"),
+            Some(Format::CDD)
+        );
+        assert_eq!(
+            sniff_format(b"# ECU\nschema: \"opensovd.cda.diagdesc/v1\"\n"),
+            Some(Format::YAML)
+        );
+        assert_eq!(
+            sniff_format(b"{\n  \"schema\": \"diag-converter/ir/v1\",\n  \"database\": {"),
+            Some(Format::JSON)
+        );
+        assert_eq!(sniff_format(b"{\"plan\": []}"), None);
+        assert_eq!(sniff_format(b"<html/>"), None);
+        assert_eq!(sniff_format(b"hello"), None);
+    }
+
+    #[test]
+    fn input_format_falls_back_to_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let odx = std::fs::read(fixture("odx/minimal.odx")).unwrap();
+        let xml = dir.path().join("export.xml");
+        let blob = dir.path().join("blob");
+        std::fs::write(&xml, &odx).unwrap();
+        std::fs::write(&blob, &odx).unwrap();
+        assert_eq!(detect_input_format(&xml, None).unwrap(), Format::ODX);
+        assert_eq!(detect_input_format(&blob, None).unwrap(), Format::ODX);
+        assert_eq!(
+            detect_input_format(&blob, Some(Format::YAML)).unwrap(),
+            Format::YAML
+        );
+
+        let text = dir.path().join("notes.txt");
+        std::fs::write(&text, "hello").unwrap();
+        let err = detect_input_format(&text, None).unwrap_err().to_string();
+        assert!(err.contains(".txt") && err.contains("--from"), "{err}");
+        assert_eq!("YAML".parse::<Format>(), Ok(Format::YAML));
+        assert!("xml".parse::<Format>().is_err());
+    }
 }
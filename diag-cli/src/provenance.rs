@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where an output artifact came from: converter build, source file and
+/// invocation. Recorded in MDD metadata and as an ODX DOC-REVISION.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub converter_version: String,
+    pub source: String,
+    pub source_sha256: String,
+    pub command_line: String,
+    pub timestamp: String,
+}
+
+impl Provenance {
+    /// Capture provenance for converting `input` in the current process.
+    /// A directory (an ODX project) is hashed as the names and contents of
+    /// its files, in name order.
+    pub fn capture(input: &Path) -> Result<Self> {
+        let mut hasher = Sha256::new();
+        hash_path(input, input, &mut hasher)
+            .with_context(|| format!("hashing {}", input.display()))?;
+        let source = input.file_name().map_or_else(
+            || input.display().to_string(),
+            |n| n.to_string_lossy().into_owned(),
+        );
+        Ok(Self {
+            converter_version: env!("CARGO_PKG_VERSION").to_string(),
+            source,
+            source_sha256: to_hex(&hasher.finalize()),
+            command_line: std::env::args().collect::<Vec<_>>().join(" "),
+            timestamp: format_rfc3339(SystemTime::now()),
+        })
+    }
+
+    /// Entries for the MDD protobuf `metadata` map.
+    pub fn mdd_metadata(&self) -> HashMap<String, String> {
+        HashMap::from([
+            (
+                "provenance_converter_version".into(),
+                self.converter_version.clone(),
+            ),
+            ("provenance_source".into(), self.source.clone()),
+            (
+                "provenance_source_sha256".into(),
+                self.source_sha256.clone(),
+            ),
+            ("provenance_command_line".into(), self.command_line.clone()),
+            ("provenance_timestamp".into(), self.timestamp.clone()),
+        ])
+    }
+
+    /// Record as IR metadata so the ODX writer emits a DOC-REVISION for it.
+    pub fn apply_to_odx_metadata(&self, db: &mut diag_ir::DiagDatabase) {
+        use diag_odx::parser::{
+            META_PROVENANCE_CHANGE, META_PROVENANCE_DATE, META_PROVENANCE_REASON,
+            META_PROVENANCE_TOOL,
+        };
+        let entries = [
+            (
+                META_PROVENANCE_TOOL,
+                format!("diag-converter {}", self.converter_version),
+            ),
+            (META_PROVENANCE_DATE, self.timestamp.clone()),
+            (
+                META_PROVENANCE_CHANGE,
+                format!(
+                    "Converted from {} (sha256 {})",
+                    self.source, self.source_sha256
+                ),
+            ),
+            (META_PROVENANCE_REASON, self.command_line.clone()),
+        ];
+        for (key, value) in entries {
+            db.metadata.insert(key.to_string(), value);
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+/// Format a system time as an RFC 3339 UTC timestamp (second precision).
+fn format_rfc3339(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let rem = secs % 86_400;
+    let (year, month, day) = civil_from_days(secs / 86_400);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+fn hash_path(root: &Path, path: &Path, hasher: &mut Sha256) -> std::io::Result<()> {
+    if !path.is_dir() {
+        hasher.update(std::fs::read(path)?);
+        return Ok(());
+    }
+    let mut children = std::fs::read_dir(path)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    children.sort();
+    for child in children {
+        if !child.is_dir() {
+            let name = child.strip_prefix(root).unwrap_or(&child);
+            hasher.update(name.to_string_lossy().as_bytes());
+            hasher.update([0]);
+        }
+        hash_path(root, &child, hasher)?;
+    }
+    Ok(())
+}
+
+/// Days since 1970-01-01 to (year, month, day), proleptic Gregorian calendar.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn rfc3339_epoch() {
+        assert_eq!(format_rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn rfc3339_leap_day() {
+        let t = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(format_rfc3339(t), "2024-02-29T12:34:56Z");
+    }
+
+    #[test]
+    fn capture_hashes_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ecu.yml");
+        std::fs::write(&path, b"abc").unwrap();
+        let p = Provenance::capture(&path).unwrap();
+        assert_eq!(p.source, "ecu.yml");
+        assert_eq!(
+            p.source_sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(p.mdd_metadata()["provenance_source"], "ecu.yml");
+    }
+
+    #[test]
+    fn capture_hashes_directory_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+        std::fs::write(project.join("a.odx-d"), b"a").unwrap();
+        let first = Provenance::capture(&project).unwrap();
+        assert_eq!(first.source, "project");
+
+        std::fs::write(project.join("b.odx-d"), b"b").unwrap();
+        let second = Provenance::capture(&project).unwrap();
+        assert_ne!(first.source_sha256, second.source_sha256);
+    }
+}
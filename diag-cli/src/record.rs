@@ -0,0 +1,484 @@
+//! Record a tester/ECU DoIP session through a proxy and replay it later, for
+//! regression-testing ECU software drops against a known-good session.
+
+use anyhow::{Context, Result, bail};
+use diag_ir::DiagService;
+use diag_runtime::{NamedValue, RuntimeError};
+use serde::{Deserialize, Serialize};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+
+use diag_transport::doip::{self, Message};
+use diag_transport::{Transport, TransportOptions, TransportSpec};
+
+use crate::convert::parse_input;
+
+/// A recorded session, stored as JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// Transport spec of the ECU the session was recorded against.
+    pub transport: String,
+    /// Database used to name and decode the exchanges.
+    pub database: String,
+    pub exchanges: Vec<Exchange>,
+}
+
+/// One request and the ECU's final response to it. For a request sent to a
+/// functional address, the first ECU to answer gives the response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    /// Time of the request since the tester connected.
+    pub time_ms: u64,
+    pub source: u16,
+    pub target: u16,
+    /// Database service the request matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    #[serde(with = "hex_bytes")]
+    pub request: Vec<u8>,
+    /// Request params decoded with the database, as `name = value`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub request_params: Vec<String>,
+    /// Final response; `None` if the ECU never answered.
+    #[serde(
+        default,
+        with = "hex_bytes_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub response: Option<Vec<u8>>,
+    /// Final response params decoded with the database, as `name = value`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub response_params: Vec<String>,
+    /// Number of NRC 0x78 frames before the final response.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub pending: u32,
+    /// `positive`, `negative 0xNN` or `no response`.
+    pub outcome: String,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+/// Proxy one tester connection on `listen` to the ECU at `transport` and save
+/// the annotated session to `output`.
+pub fn run_record(transport: &str, db_path: &Path, output: &Path, listen: &str) -> Result<()> {
+    let TransportSpec::Doip { addr: ecu_addr } = transport.parse()? else {
+        bail!("record only supports doip: transports");
+    };
+    let db = parse_input(db_path, false)?;
+    let services: Vec<DiagService> = db
+        .variants
+        .iter()
+        .flat_map(|v| v.diag_layer.diag_services.iter().cloned())
+        .collect();
+
+    let listener = TcpListener::bind(listen).with_context(|| format!("binding {listen}"))?;
+    eprintln!("Waiting for a tester on {listen}...");
+    let (tester, peer) = listener.accept().context("accepting tester connection")?;
+    let ecu = TcpStream::connect(&ecu_addr).with_context(|| format!("connecting to {ecu_addr}"))?;
+    eprintln!("Recording {peer} <-> {ecu_addr}");
+
+    let session = Session {
+        transport: transport.to_string(),
+        database: db_path.display().to_string(),
+        exchanges: record_session(&tester, &ecu, &services),
+    };
+    std::fs::write(output, serde_json::to_string_pretty(&session)? + "\n")
+        .with_context(|| format!("writing {}", output.display()))?;
+    eprintln!(
+        "Recorded {} exchange(s) to {}",
+        session.exchanges.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// Forward DoIP messages between `tester` and `ecu` until either side closes,
+/// pairing each diagnostic request with the ECU's final response and decoding
+/// both with the matching service of `services`.
+pub fn record_session(
+    tester: &TcpStream,
+    ecu: &TcpStream,
+    services: &[DiagService],
+) -> Vec<Exchange> {
+    let start = Instant::now();
+    let exchanges = Mutex::new(Vec::<Exchange>::new());
+    let lock = || exchanges.lock().unwrap_or_else(PoisonError::into_inner);
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            pump(tester, ecu, |msg| {
+                let Some((source, target, data)) = msg.as_diagnostic() else {
+                    return;
+                };
+                let service = diag_sim::match_service(services, data);
+                lock().push(Exchange {
+                    time_ms: start.elapsed().as_millis() as u64,
+                    source,
+                    target,
+                    service: service.map(|svc| svc.diag_comm.short_name.to_string()),
+                    request: data.to_vec(),
+                    request_params: service
+                        .map(|svc| param_lines(diag_runtime::decode_request(svc, data)))
+                        .unwrap_or_default(),
+                    response: None,
+                    response_params: vec![],
+                    pending: 0,
+                    outcome: "no response".into(),
+                });
+            });
+        });
+        scope.spawn(|| {
+            pump(ecu, tester, |msg| {
+                let Some((source, target, data)) = msg.as_diagnostic() else {
+                    return;
+                };
+                let mut exchanges = lock();
+                // A functional request is answered from the physical address
+                // of the ECU, not the address it was sent to.
+                let Some(exchange) = exchanges.iter_mut().find(|e| {
+                    e.response.is_none()
+                        && e.source == target
+                        && (e.target == source || doip::is_functional_address(e.target))
+                }) else {
+                    log::warn!("unsolicited response {}", hex(data));
+                    return;
+                };
+                match data {
+                    _ if diag_transport::is_response_pending(data) => {
+                        exchange.pending += 1;
+                        return;
+                    }
+                    [0x7F, _, nrc] => exchange.outcome = format!("negative 0x{nrc:02X}"),
+                    _ => exchange.outcome = "positive".into(),
+                }
+                exchange.response = Some(data.to_vec());
+                exchange.response_params = services
+                    .iter()
+                    .find(|svc| {
+                        exchange.service.as_deref() == Some(svc.diag_comm.short_name.as_str())
+                    })
+                    .map(|svc| {
+                        param_lines(diag_runtime::decode_response(svc, data).map(|r| r.params))
+                    })
+                    .unwrap_or_default();
+            });
+        });
+    });
+    exchanges
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// `name = value` for each decoded param; nothing if the message did not
+/// decode, which is logged.
+fn param_lines(decoded: Result<Vec<NamedValue>, RuntimeError>) -> Vec<String> {
+    match decoded {
+        Ok(params) => params
+            .iter()
+            .map(|p| format!("{} = {}", p.name, p.value))
+            .collect(),
+        Err(e) => {
+            log::debug!("not decoded: {e}");
+            vec![]
+        }
+    }
+}
+
+/// Copy messages from `from` to `to`, observing each one, until `from` closes;
+/// then shut both sides down so the opposite direction ends too.
+fn pump(from: &TcpStream, to: &TcpStream, mut observe: impl FnMut(&Message)) {
+    let (mut reader, mut writer) = (from, to);
+    let result = (|| -> Result<()> {
+        while let Some(message) = doip::read_message(&mut reader)? {
+            observe(&message);
+            doip::write_message(&mut writer, &message)?;
+        }
+        Ok(())
+    })();
+    // Reads fail once the other direction shut the socket down; that is the
+    // normal end of a session, not an error.
+    if let Err(e) = result {
+        log::debug!("proxy stopped: {e:#}");
+    }
+    let _ = from.shutdown(Shutdown::Both);
+    let _ = to.shutdown(Shutdown::Both);
+}
+
+/// Re-send the requests of a recorded session to the ECU at `transport` and
+/// report every response that differs from the recording.
+pub fn run_replay(session_path: &Path, transport: &str) -> Result<()> {
+    let text = std::fs::read_to_string(session_path)
+        .with_context(|| format!("reading {}", session_path.display()))?;
+    let session: Session = serde_json::from_str(&text)
+        .with_context(|| format!("parsing session {}", session_path.display()))?;
+    let spec: TransportSpec = transport.parse()?;
+    let Some(first) = session.exchanges.first() else {
+        eprintln!("{} has no exchanges", session_path.display());
+        return Ok(());
+    };
+    let options = TransportOptions {
+        tester_address: first.source,
+        ecu_address: Some(first.target),
+        ..Default::default()
+    };
+    let mut transport = diag_transport::open(&spec, &options)
+        .with_context(|| format!("connecting to {transport}"))?;
+
+    let mismatches = replay_session(&session, transport.as_mut())?;
+    for mismatch in &mismatches {
+        eprintln!("  {mismatch}");
+    }
+    if !mismatches.is_empty() {
+        bail!(
+            "{} of {} response(s) differ from {}",
+            mismatches.len(),
+            session.exchanges.len(),
+            session_path.display()
+        );
+    }
+    eprintln!(
+        "All {} response(s) match {}",
+        session.exchanges.len(),
+        session_path.display()
+    );
+    Ok(())
+}
+
+/// Replay `session` over `transport` and describe each differing response.
+pub fn replay_session(session: &Session, transport: &mut dyn Transport) -> Result<Vec<String>> {
+    let mut mismatches = Vec::new();
+    for (i, exchange) in session.exchanges.iter().enumerate() {
+        let actual = transport
+            .request(&exchange.request)
+            .with_context(|| format!("replaying exchange #{}", i + 1))?;
+        let Some(expected) = &exchange.response else {
+            continue;
+        };
+        if *expected != actual {
+            mismatches.push(format!(
+                "#{} {}: expected {}, got {}",
+                i + 1,
+                exchange.service.as_deref().unwrap_or("?"),
+                hex(expected),
+                hex(&actual)
+            ));
+        }
+    }
+    Ok(mismatches)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Bytes as space-separated hex (`"22 F1 90"`).
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&super::hex(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(d)?;
+        diag_ir::parse_hex_bytes(&s)
+            .ok_or_else(|| D::Error::custom(format!("invalid hex bytes '{s}'")))
+    }
+}
+
+mod hex_bytes_opt {
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => super::hex_bytes::serialize(bytes, s),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+        super::hex_bytes::deserialize(d).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diag_transport::{DoipClient, DoipServer};
+
+    const TESTER: u16 = 0x0E00;
+    const ECU: u16 = 0x1000;
+
+    /// A DoIP ECU answering the VIN DID with `vin` and everything else with
+    /// NRC 0x11, after one response-pending frame.
+    fn vin_ecu(vin: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut server = DoipServer::new(stream.unwrap(), ECU);
+                std::thread::spawn(move || {
+                    while let Ok(request) = server.receive() {
+                        server.send(&[0x7F, request[0], 0x78]).unwrap();
+                        let response = match request.as_slice() {
+                            [0x22, 0xF1, 0x90] => [&[0x62, 0xF1, 0x90], vin].concat(),
+                            _ => vec![0x7F, request[0], 0x11],
+                        };
+                        server.send(&response).unwrap();
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    fn connect(addr: &str) -> DoipClient {
+        connect_to(addr, None)
+    }
+
+    /// Connect with requests sent to `target` instead of the ECU's address.
+    fn connect_to(addr: &str, target: Option<u16>) -> DoipClient {
+        let options = TransportOptions {
+            tester_address: TESTER,
+            ecu_address: target,
+            ..Default::default()
+        };
+        DoipClient::connect(addr, &options).unwrap()
+    }
+
+    fn services() -> Vec<DiagService> {
+        let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0xF190:
+    name: VIN
+    type:
+      base: ascii
+      length: 3
+    access: public
+"#;
+        let db = diag_yaml::parse_yaml(yaml).unwrap();
+        db.variants
+            .into_iter()
+            .flat_map(|v| v.diag_layer.diag_services)
+            .collect()
+    }
+
+    fn record(ecu_addr: &str) -> Vec<Exchange> {
+        record_to(ecu_addr, None)
+    }
+
+    fn record_to(ecu_addr: &str, target: Option<u16>) -> Vec<Exchange> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap().to_string();
+        let ecu_addr = ecu_addr.to_string();
+        let proxy = std::thread::spawn(move || {
+            let (tester, _) = listener.accept().unwrap();
+            let ecu = TcpStream::connect(ecu_addr).unwrap();
+            record_session(&tester, &ecu, &services())
+        });
+        let mut client = connect_to(&proxy_addr, target);
+        client.request(&[0x22, 0xF1, 0x90]).unwrap();
+        client.request(&[0x31, 0x01, 0xFF, 0x00]).unwrap();
+        drop(client);
+        proxy.join().unwrap()
+    }
+
+    #[test]
+    fn test_record_annotates_exchanges() {
+        let exchanges = record(&vin_ecu(b"ABC"));
+        assert_eq!(exchanges.len(), 2);
+        assert_eq!(exchanges[0].service.as_deref(), Some("VIN_Read"));
+        assert_eq!(
+            exchanges[0].response.as_deref(),
+            Some(&b"\x62\xF1\x90ABC"[..])
+        );
+        assert_eq!(exchanges[0].pending, 1);
+        assert_eq!(exchanges[0].outcome, "positive");
+        assert_eq!(exchanges[1].service, None);
+        assert_eq!(exchanges[1].outcome, "negative 0x11");
+        assert_eq!((exchanges[1].source, exchanges[1].target), (TESTER, ECU));
+    }
+
+    #[test]
+    fn test_record_decodes_exchanges() {
+        let exchanges = record(&vin_ecu(b"ABC"));
+        assert_eq!(
+            exchanges[0].request_params,
+            ["SID_RQ = 34", "DID_RQ = 61840"]
+        );
+        assert!(
+            exchanges[0]
+                .response_params
+                .iter()
+                .any(|p| p == "VIN = ABC"),
+            "{:?}",
+            exchanges[0].response_params
+        );
+        // Without a matching service there is nothing to decode with.
+        assert!(exchanges[1].request_params.is_empty());
+        assert!(exchanges[1].response_params.is_empty());
+    }
+
+    #[test]
+    fn test_record_pairs_functional_requests() {
+        let exchanges = record_to(&vin_ecu(b"ABC"), Some(0xE400));
+        assert_eq!(exchanges.len(), 2);
+        assert_eq!((exchanges[0].source, exchanges[0].target), (TESTER, 0xE400));
+        assert_eq!(exchanges[0].outcome, "positive");
+        assert_eq!(exchanges[0].pending, 1);
+        assert_eq!(exchanges[1].outcome, "negative 0x11");
+    }
+
+    #[test]
+    fn test_replay_reports_changed_responses() {
+        let session = Session {
+            transport: "doip:127.0.0.1".into(),
+            database: "ecu.yml".into(),
+            exchanges: record(&vin_ecu(b"ABC")),
+        };
+        let json = serde_json::to_string(&session).unwrap();
+        assert!(json.contains(r#""request":"22 F1 90""#), "{json}");
+        let session: Session = serde_json::from_str(&json).unwrap();
+
+        assert!(
+            replay_session(&session, &mut connect(&vin_ecu(b"ABC")))
+                .unwrap()
+                .is_empty()
+        );
+        let mismatches = replay_session(&session, &mut connect(&vin_ecu(b"XYZ"))).unwrap();
+        assert_eq!(
+            mismatches,
+            ["#1 VIN_Read: expected 62 F1 90 41 42 43, got 62 F1 90 58 59 5A"]
+        );
+    }
+
+    #[test]
+    fn test_session_without_response_roundtrips() {
+        let exchange = Exchange {
+            time_ms: 0,
+            source: TESTER,
+            target: ECU,
+            service: Some("Reset".into()),
+            request: vec![0x11, 0x01],
+            request_params: vec![],
+            response: None,
+            response_params: vec![],
+            pending: 0,
+            outcome: "no response".into(),
+        };
+        let json = serde_json::to_string(&exchange).unwrap();
+        assert!(!json.contains("\"response\""), "{json}");
+        assert_eq!(serde_json::from_str::<Exchange>(&json).unwrap(), exchange);
+    }
+}
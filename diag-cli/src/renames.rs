@@ -0,0 +1,80 @@
+//! Rename maps for `transform --rename-map`.
+//!
+//! A rename map is a CSV with an `old` and a `new` column, one short name
+//! per row:
+//!
+//! ```text
+//! old,new
+//! Read_VIN,VIN_Read
+//! VehicleSpeed_DOP,VehicleSpeed
+//! ```
+
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::dtc_texts::parse_csv;
+
+/// Read the rename map at `path`. Other columns are ignored; blank rows are
+/// skipped. An old name listed twice with different new names is an error.
+pub fn read_rename_map(path: &Path) -> Result<BTreeMap<String, String>> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    parse_rename_map(content.trim_start_matches('\u{feff}'))
+        .with_context(|| format!("parsing {}", path.display()))
+}
+
+fn parse_rename_map(text: &str) -> Result<BTreeMap<String, String>> {
+    let mut records = parse_csv(text)?.into_iter();
+    let header = records.next().unwrap_or_default();
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim() == name)
+            .with_context(|| format!("missing column '{name}'"))
+    };
+    let (old, new) = (column("old")?, column("new")?);
+
+    let mut names = BTreeMap::new();
+    for (i, record) in records.enumerate() {
+        if record.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+        let field = |index: usize| record.get(index).map_or("", |f| f.trim()).to_string();
+        let (from, to) = (field(old), field(new));
+        if from.is_empty() || to.is_empty() {
+            bail!("record {}: old and new name are both required", i + 1);
+        }
+        if let Some(previous) = names.get(&from).filter(|previous| **previous != to) {
+            bail!(
+                "record {}: '{from}' is renamed to both '{previous}' and '{to}'",
+                i + 1
+            );
+        }
+        names.insert(from, to);
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_map() {
+        let names =
+            parse_rename_map("note,old,new\nservice,Read_VIN,VIN_Read\n\n,\"Speed\" , Speed_2\n")
+                .unwrap();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names["Read_VIN"], "VIN_Read");
+        assert_eq!(names["Speed"], "Speed_2");
+
+        assert!(parse_rename_map("from,to\nA,B\n").is_err());
+        assert!(parse_rename_map("old,new\nA,\n").is_err());
+        let error = parse_rename_map("old,new\nA,B\nA,C\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "record 2: 'A' is renamed to both 'B' and 'C'"
+        );
+    }
+}
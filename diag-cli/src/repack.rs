@@ -0,0 +1,102 @@
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+
+use crate::Format;
+use crate::convert::parse_compression;
+
+/// Rewrite an MDD file in place of a YAML/ODX round trip: the description is
+/// migrated to the current schema with shared DOPs and recompressed, while
+/// container metadata and extra chunks (job files etc.) are kept as they are.
+pub fn run_repack(
+    input: &Path,
+    output: &Path,
+    compression: &str,
+    level: Option<i32>,
+) -> Result<()> {
+    for path in [input, output] {
+        if crate::detect_format(path)? != Format::MDD {
+            bail!("repack works on .mdd files only: {}", path.display());
+        }
+    }
+    let compression = parse_compression(compression)?;
+
+    let original = std::fs::read(input).with_context(|| format!("reading {}", input.display()))?;
+    let (_meta, fbs_data) = mdd_format::reader::read_mdd_bytes(&original)
+        .with_context(|| format!("reading MDD from {}", input.display()))?;
+    let db = diag_ir::flatbuffers_to_ir(&fbs_data).context("converting FlatBuffers to IR")?;
+
+    let fbs_options = diag_ir::FbsWriteOptions { dedup_dops: true };
+    let migrated = diag_ir::ir_to_flatbuffers_with_options(&db, &fbs_options);
+    let repacked = mdd_format::writer::repack_mdd_bytes(&original, &migrated, compression, level)
+        .context("repacking MDD")?;
+    std::fs::write(output, &repacked).with_context(|| format!("writing {}", output.display()))?;
+
+    println!(
+        "Repacked {} -> {} ({} -> {} bytes, description {} -> {} bytes uncompressed)",
+        input.display(),
+        output.display(),
+        original.len(),
+        repacked.len(),
+        fbs_data.len(),
+        migrated.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    #[test]
+    fn repack_recompresses_and_preserves_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.mdd");
+        let input = fixture("mdd/FLXC1000.mdd");
+        run_repack(&input, &out, "zstd", Some(19)).unwrap();
+
+        let (old_meta, old_fbs) = mdd_format::reader::read_mdd_file(&input).unwrap();
+        let (new_meta, new_fbs) = mdd_format::reader::read_mdd_file(&out).unwrap();
+        assert_eq!(new_meta.ecu_name, old_meta.ecu_name);
+        assert_eq!(new_meta.metadata, old_meta.metadata);
+        assert_eq!(
+            diag_ir::flatbuffers_to_ir(&new_fbs).unwrap(),
+            diag_ir::flatbuffers_to_ir(&old_fbs).unwrap()
+        );
+        let flags = diag_ir::read_feature_flags(&new_fbs).unwrap().unwrap();
+        assert!(flags.contains(diag_ir::Feature::DedupedDops));
+
+        let container =
+            mdd_format::reader::read_mdd_container(&std::fs::read(&out).unwrap()).unwrap();
+        assert_eq!(
+            container.chunks[0].compression_algorithm.as_deref(),
+            Some("zstd")
+        );
+    }
+
+    #[test]
+    fn repack_rejects_non_mdd() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = run_repack(
+            &fixture("yaml/minimal-ecu.yml"),
+            &dir.path().join("out.mdd"),
+            "lzma",
+            None,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains(".mdd files only"));
+    }
+
+    #[test]
+    fn repack_rejects_bad_level() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = run_repack(
+            &fixture("mdd/FLXC1000.mdd"),
+            &dir.path().join("out.mdd"),
+            "gzip",
+            Some(42),
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("out of range"), "{err:#}");
+    }
+}
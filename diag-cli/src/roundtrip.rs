@@ -0,0 +1,134 @@
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+
+use diag_ir::FieldDifference;
+
+use crate::Format;
+use crate::convert::ConvertOptions;
+use crate::formats::WriteContext;
+
+/// Convert `input` to `via` and back, and report every field of the IR the
+/// round trip changed. Fails when there is one. The intermediate file is
+/// written to `keep`, or to a temporary file that is removed afterwards.
+pub fn run_roundtrip(
+    input: &Path,
+    from: Option<Format>,
+    via: Format,
+    keep: Option<&Path>,
+) -> Result<()> {
+    let in_fmt = crate::detect_input_format(input, from).context("input file")?;
+    let differences = roundtrip(input, in_fmt, via, keep)?;
+
+    println!(
+        "{} -> {} -> IR: {} difference(s)",
+        input.display(),
+        via.name(),
+        differences.len()
+    );
+    for difference in &differences {
+        println!("  {difference}");
+    }
+    if differences.is_empty() {
+        return Ok(());
+    }
+    bail!(
+        "the round trip through {} is lossy ({} field(s) changed)",
+        via.name(),
+        differences.len()
+    )
+}
+
+fn roundtrip(
+    input: &Path,
+    in_fmt: Format,
+    via: Format,
+    keep: Option<&Path>,
+) -> Result<Vec<FieldDifference>> {
+    let odx_options = diag_odx::ParseOptions::default();
+    let original = in_fmt.provider().parse(input, &odx_options)?;
+
+    let provider = via.provider();
+    let intermediate = keep.map_or_else(
+        || {
+            std::env::temp_dir().join(format!(
+                "diag-converter-roundtrip-{}.{}",
+                std::process::id(),
+                provider.extensions()[0]
+            ))
+        },
+        Path::to_path_buf,
+    );
+    let opts = ConvertOptions::default();
+    let ctx = WriteContext {
+        input,
+        in_fmt,
+        opts: &opts,
+        provenance: None,
+    };
+    let back = write_and_parse(&original, &intermediate, &ctx, via, &odx_options);
+    if keep.is_none() {
+        let _ = std::fs::remove_file(&intermediate);
+    }
+    let back = back?;
+
+    if let Some(caps) = provider.capabilities() {
+        let losses = diag_ir::find_losses(&original, caps);
+        if !losses.is_empty() {
+            log::warn!(
+                "{} element(s) cannot be represented in {} (see convert --loss-report)",
+                losses.len(),
+                caps.format
+            );
+        }
+    }
+    Ok(diag_ir::compare_databases(&original, &back))
+}
+
+fn write_and_parse(
+    db: &diag_ir::DiagDatabase,
+    intermediate: &Path,
+    ctx: &WriteContext<'_>,
+    via: Format,
+    odx_options: &diag_odx::ParseOptions,
+) -> Result<diag_ir::DiagDatabase> {
+    let provider = via.provider();
+    provider
+        .write(&mut db.clone(), intermediate, ctx)
+        .with_context(|| format!("writing {}", via.name()))?;
+    provider
+        .parse(intermediate, odx_options)
+        .with_context(|| format!("parsing back {}", intermediate.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    #[test]
+    fn roundtrip_through_json_is_lossless() {
+        let dir = tempfile::tempdir().unwrap();
+        let keep = dir.path().join("via.json");
+        let differences = roundtrip(
+            &fixture("yaml/example-ecm.yml"),
+            Format::YAML,
+            Format::JSON,
+            Some(&keep),
+        )
+        .unwrap();
+        assert!(differences.is_empty(), "{differences:?}");
+        assert!(keep.exists());
+    }
+
+    #[test]
+    fn roundtrip_through_an_input_only_format_fails() {
+        let err = roundtrip(
+            &fixture("yaml/minimal-ecu.yml"),
+            Format::YAML,
+            Format::CDD,
+            None,
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("input-only"), "{err:#}");
+    }
+}
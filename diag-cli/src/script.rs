@@ -0,0 +1,345 @@
+//! Custom transforms written in Rhai (`transform --script`).
+//!
+//! A script sees the services of every layer as `Service` values:
+//!
+//! ```text
+//! for svc in services() {
+//!     if svc.name.starts_with("Old_") { svc.name = "New_" + svc.name.sub_string(4); }
+//!     if svc.sid == 0x34 { set_audiences(svc, ["Development"]); }
+//!     drop_param(svc, "Reserved_1");
+//! }
+//! ```
+//!
+//! The engine has no file or module access and no `eval`, and a script stops
+//! after a fixed number of operations, so a rules file cannot do more than
+//! edit the database it is given.
+
+use anyhow::{Context, Result, anyhow};
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use diag_ir::types::{AdditionalAudience, Audience, DiagDatabase, DiagLayer, DiagService};
+use rhai::{Array, Dynamic, Engine, INT};
+
+/// Operations after which a script is stopped as a runaway loop.
+const MAX_OPERATIONS: u64 = 100_000_000;
+
+/// A service of the database, by layer and position.
+#[derive(Debug, Clone, Copy)]
+struct ServiceRef {
+    layer: LayerRef,
+    index: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum LayerRef {
+    Variant(usize),
+    FunctionalGroup(usize),
+    Protocol(usize),
+    EcuSharedData(usize),
+}
+
+fn layer(db: &DiagDatabase, layer: LayerRef) -> &DiagLayer {
+    match layer {
+        LayerRef::Variant(i) => &db.variants[i].diag_layer,
+        LayerRef::FunctionalGroup(i) => &db.functional_groups[i].diag_layer,
+        LayerRef::Protocol(i) => &db.protocols[i].diag_layer,
+        LayerRef::EcuSharedData(i) => &db.ecu_shared_datas[i].diag_layer,
+    }
+}
+
+fn layer_mut(db: &mut DiagDatabase, layer: LayerRef) -> &mut DiagLayer {
+    match layer {
+        LayerRef::Variant(i) => &mut db.variants[i].diag_layer,
+        LayerRef::FunctionalGroup(i) => &mut db.functional_groups[i].diag_layer,
+        LayerRef::Protocol(i) => &mut db.protocols[i].diag_layer,
+        LayerRef::EcuSharedData(i) => &mut db.ecu_shared_datas[i].diag_layer,
+    }
+}
+
+/// Every service of the database, layer by layer.
+fn service_refs(db: &DiagDatabase) -> Vec<ServiceRef> {
+    let layers = (0..db.variants.len())
+        .map(LayerRef::Variant)
+        .chain((0..db.functional_groups.len()).map(LayerRef::FunctionalGroup))
+        .chain((0..db.protocols.len()).map(LayerRef::Protocol))
+        .chain((0..db.ecu_shared_datas.len()).map(LayerRef::EcuSharedData));
+    layers
+        .flat_map(|l| {
+            (0..layer(db, l).diag_services.len()).map(move |index| ServiceRef { layer: l, index })
+        })
+        .collect()
+}
+
+type Shared = Rc<RefCell<DiagDatabase>>;
+
+fn with_service<T>(db: &Shared, svc: &ServiceRef, f: impl FnOnce(&DiagService) -> T) -> T {
+    f(&layer(&db.borrow(), svc.layer).diag_services[svc.index])
+}
+
+/// Run the Rhai script at `path` on `db`. The database is left as the
+/// script left it, also when the script fails part way.
+pub fn run_script(db: &mut DiagDatabase, path: &Path) -> Result<()> {
+    let source =
+        std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    run_script_source(db, &source).with_context(|| format!("running {}", path.display()))
+}
+
+fn run_script_source(db: &mut DiagDatabase, source: &str) -> Result<()> {
+    let shared: Shared = Rc::new(RefCell::new(std::mem::take(db)));
+    let result = {
+        let engine = engine(&shared);
+        engine
+            .compile(source)
+            .map_err(|e| anyhow!("{e}"))
+            .and_then(|ast| engine.run_ast(&ast).map_err(|e| anyhow!("{e}")))
+    };
+    *db = Rc::try_unwrap(shared)
+        .map_err(|_| anyhow!("script engine still holds the database"))?
+        .into_inner();
+    result
+}
+
+fn engine(db: &Shared) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.on_print(|text| log::info!("script: {text}"));
+    engine.on_debug(|text, _, pos| log::debug!("script ({pos}): {text}"));
+
+    engine.register_type_with_name::<ServiceRef>("Service");
+    let shared = db.clone();
+    engine.register_fn("services", move || -> Array {
+        service_refs(&shared.borrow())
+            .into_iter()
+            .map(Dynamic::from)
+            .collect()
+    });
+
+    let shared = db.clone();
+    engine.register_get("layer", move |svc: &mut ServiceRef| -> String {
+        layer(&shared.borrow(), svc.layer).short_name.to_string()
+    });
+    let shared = db.clone();
+    engine.register_get("name", move |svc: &mut ServiceRef| -> String {
+        with_service(&shared, svc, |s| s.diag_comm.short_name.to_string())
+    });
+    let shared = db.clone();
+    engine.register_set("name", move |svc: &mut ServiceRef, name: String| {
+        let mut db = shared.borrow_mut();
+        let service = &mut layer_mut(&mut db, svc.layer).diag_services[svc.index];
+        log::info!(
+            "script: renamed '{}' to '{name}'",
+            service.diag_comm.short_name
+        );
+        service.diag_comm.short_name = name.into();
+    });
+    let shared = db.clone();
+    engine.register_get("semantic", move |svc: &mut ServiceRef| -> String {
+        with_service(&shared, svc, |s| s.diag_comm.semantic.to_string())
+    });
+    let shared = db.clone();
+    engine.register_get("sid", move |svc: &mut ServiceRef| -> Dynamic {
+        with_service(&shared, svc, |s| {
+            diag_ir::service_sid(s).map_or(Dynamic::UNIT, |sid| Dynamic::from(INT::from(sid)))
+        })
+    });
+    let shared = db.clone();
+    engine.register_get("audiences", move |svc: &mut ServiceRef| -> Array {
+        with_service(&shared, svc, |s| {
+            s.diag_comm
+                .audience
+                .iter()
+                .flat_map(|a| &a.enabled_audiences)
+                .map(|a| Dynamic::from(a.short_name.to_string()))
+                .collect()
+        })
+    });
+    let shared = db.clone();
+    engine.register_get("params", move |svc: &mut ServiceRef| -> Array {
+        with_service(&shared, svc, |s| {
+            let mut names: Vec<String> = Vec::new();
+            for name in params(s) {
+                if !names.iter().any(|n| n == name) {
+                    names.push(name.to_string());
+                }
+            }
+            names.into_iter().map(Dynamic::from).collect()
+        })
+    });
+
+    let shared = db.clone();
+    engine.register_fn(
+        "set_audiences",
+        move |svc: ServiceRef, names: Array| -> Result<(), Box<rhai::EvalAltResult>> {
+            let names = names
+                .into_iter()
+                .map(|n| {
+                    n.into_string()
+                        .map_err(|t| format!("audience must be a string, not {t}"))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            set_audiences(&mut shared.borrow_mut(), svc, &names);
+            Ok(())
+        },
+    );
+    let shared = db.clone();
+    engine.register_fn("drop_param", move |svc: ServiceRef, name: &str| -> INT {
+        let mut db = shared.borrow_mut();
+        let service = &mut layer_mut(&mut db, svc.layer).diag_services[svc.index];
+        let mut dropped = 0;
+        let lists = service
+            .request
+            .iter_mut()
+            .map(|r| &mut r.params)
+            .chain(service.pos_responses.iter_mut().map(|r| &mut r.params))
+            .chain(service.neg_responses.iter_mut().map(|r| &mut r.params));
+        for params in lists {
+            let before = params.len();
+            params.retain(|p| p.short_name != name);
+            dropped += before - params.len();
+        }
+        if dropped > 0 {
+            log::info!(
+                "script: dropped {dropped} '{name}' param(s) of '{}'",
+                service.diag_comm.short_name
+            );
+        }
+        INT::try_from(dropped).unwrap_or(INT::MAX)
+    });
+    engine
+}
+
+/// Names of the request and response params of a service.
+fn params(svc: &DiagService) -> impl Iterator<Item = &str> {
+    svc.request
+        .iter()
+        .flat_map(|r| &r.params)
+        .chain(svc.pos_responses.iter().flat_map(|r| &r.params))
+        .chain(svc.neg_responses.iter().flat_map(|r| &r.params))
+        .map(|p| p.short_name.as_str())
+}
+
+/// Make the service visible to exactly `names` (to everyone when empty),
+/// declaring audiences the layer does not know yet.
+fn set_audiences(db: &mut DiagDatabase, svc: ServiceRef, names: &[String]) {
+    let layer = layer_mut(db, svc.layer);
+    for name in names {
+        if !layer
+            .additional_audiences
+            .iter()
+            .any(|a| &a.short_name == name)
+        {
+            layer.additional_audiences.push(AdditionalAudience {
+                short_name: name.as_str().into(),
+                long_name: None,
+            });
+        }
+    }
+    let comm = &mut layer.diag_services[svc.index].diag_comm;
+    let audience = comm.audience.get_or_insert_with(Audience::default);
+    audience.enabled_audiences = names
+        .iter()
+        .map(|name| AdditionalAudience {
+            short_name: name.as_str().into(),
+            long_name: None,
+        })
+        .collect();
+    if *audience == Audience::default() {
+        comm.audience = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diag_ir::types::{DiagComm, DiagLayer, Param, Request, Variant};
+
+    fn database() -> DiagDatabase {
+        let service = |name: &str, params: &[&str]| DiagService {
+            diag_comm: DiagComm {
+                short_name: name.into(),
+                ..Default::default()
+            },
+            request: Some(Request {
+                params: params
+                    .iter()
+                    .map(|p| Param {
+                        short_name: (*p).into(),
+                        ..Default::default()
+                    })
+                    .collect(),
+                sdgs: None,
+            }),
+            ..Default::default()
+        };
+        DiagDatabase {
+            ecu_name: "ECU".into(),
+            variants: vec![Variant {
+                diag_layer: DiagLayer {
+                    short_name: "Base".into(),
+                    diag_services: vec![
+                        service("Old_Read", &["SID_RQ", "Reserved"]),
+                        service("Flash", &["SID_RQ"]),
+                    ],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn script_renames_sets_audiences_and_drops_params() {
+        let mut db = database();
+        let script = r#"
+            let dropped = 0;
+            for svc in services() {
+                if svc.name.starts_with("Old_") {
+                    svc.name = "New_" + svc.name.sub_string(4);
+                }
+                if svc.name == "Flash" {
+                    set_audiences(svc, ["Development"]);
+                }
+                dropped += drop_param(svc, "Reserved");
+            }
+            if dropped != 1 { throw "dropped " + dropped; }
+            if services()[1].audiences != ["Development"] { throw "audiences not set"; }
+        "#;
+        run_script_source(&mut db, script).unwrap();
+
+        let layer = &db.variants[0].diag_layer;
+        assert_eq!(layer.diag_services[0].diag_comm.short_name, "New_Read");
+        let params: Vec<_> = params(&layer.diag_services[0]).collect();
+        assert_eq!(params, ["SID_RQ"]);
+        assert_eq!(layer.additional_audiences[0].short_name, "Development");
+        let mut aftermarket = db.clone();
+        diag_ir::filter_by_audience(&mut aftermarket, &"Aftermarket".parse().unwrap());
+        assert_eq!(aftermarket.variants[0].diag_layer.diag_services.len(), 1);
+    }
+
+    #[test]
+    fn script_errors_name_the_position_and_keep_the_database() {
+        let mut db = database();
+        let err = run_script_source(
+            &mut db,
+            "let s = services()[0];\ns.name = \"X\";\nnot_a_function();",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("not_a_function"), "{err}");
+        assert!(err.to_string().contains("line 3"), "{err}");
+        // Edits made before the error stay; the database is handed back.
+        assert_eq!(
+            db.variants[0].diag_layer.diag_services[0]
+                .diag_comm
+                .short_name,
+            "X"
+        );
+
+        assert!(run_script_source(&mut db, "eval(\"1\")").is_err());
+        assert!(run_script_source(&mut db, "import \"other\" as o;").is_err());
+    }
+}
@@ -0,0 +1,279 @@
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::Format;
+
+/// Layout of the `.log` sidecar written by `convert --log-level`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// `key: value` lines.
+    #[default]
+    Text,
+    /// One JSON object per line, each tagged with an `event`.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" | "jsonl" => Ok(Self::Json),
+            _ => Err(format!("unknown log format '{s}' (expected text or json)")),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        })
+    }
+}
+
+/// A warning raised during a conversion, with a stable code for tooling.
+#[derive(Debug, Clone)]
+pub struct LogWarning {
+    /// `validation`, `format_loss`, `unresolved_comparam` or `short_name`.
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl LogWarning {
+    pub fn new(code: &'static str, message: impl fmt::Display) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Phase timings in milliseconds.
+#[derive(Debug, Clone, Copy)]
+pub struct Timings {
+    pub parse: f64,
+    pub validate: f64,
+    pub write: f64,
+    pub total: f64,
+}
+
+/// Everything the sidecar reports about one conversion.
+pub struct ConversionLog<'a> {
+    pub input: &'a Path,
+    pub output: &'a Path,
+    pub input_format: Format,
+    pub output_format: Format,
+    pub input_size: u64,
+    pub output_size: u64,
+    /// Size of the uncompressed FlatBuffers payload, for MDD output.
+    pub fbs_size: Option<usize>,
+    pub timings: Timings,
+    pub db: &'a diag_ir::DiagDatabase,
+    pub warnings: &'a [LogWarning],
+    /// `--log-level debug`: list validation warnings and per-variant counts.
+    pub debug: bool,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    Start {
+        input: String,
+        input_format: String,
+        input_size: u64,
+        output: String,
+        output_format: String,
+    },
+    Phase {
+        phase: &'static str,
+        ms: f64,
+    },
+    Warning {
+        code: &'static str,
+        message: &'a str,
+    },
+    Counts {
+        ecu: &'a str,
+        variants: usize,
+        dtcs: usize,
+        services: usize,
+        single_ecu_jobs: usize,
+    },
+    Variant {
+        name: &'a str,
+        services: usize,
+        single_ecu_jobs: usize,
+    },
+    Sizes {
+        input_size: u64,
+        output_size: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fbs_size: Option<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        compression_ratio: Option<f64>,
+    },
+    Done {
+        total_ms: f64,
+        warnings: usize,
+    },
+}
+
+impl ConversionLog<'_> {
+    pub fn render(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Text => self.to_text(),
+            LogFormat::Json => self.to_jsonl(),
+        }
+    }
+
+    fn compression_ratio(&self) -> Option<f64> {
+        self.fbs_size
+            .filter(|_| self.output_size > 0)
+            .map(|fbs| fbs as f64 / self.output_size as f64)
+    }
+
+    fn service_counts(&self) -> (usize, usize) {
+        let layers = self.db.variants.iter().map(|v| &v.diag_layer);
+        let services = layers.clone().map(|l| l.diag_services.len()).sum();
+        let jobs = layers.map(|l| l.single_ecu_jobs.len()).sum();
+        (services, jobs)
+    }
+
+    fn to_text(&self) -> String {
+        let t = &self.timings;
+        let mut lines = vec![
+            format!("input: {}", self.input.display()),
+            format!("input_size: {} bytes", self.input_size),
+            format!("output: {}", self.output.display()),
+            format!("output_size: {} bytes", self.output_size),
+            format!("input_format: {:?}", self.input_format),
+            format!("output_format: {:?}", self.output_format),
+            format!("parse_time: {:.1}ms", t.parse),
+            format!("validate_time: {:.1}ms", t.validate),
+            format!("write_time: {:.1}ms", t.write),
+            format!("total_time: {:.1}ms", t.total),
+            format!("ecu: {}", self.db.ecu_name),
+            format!("variants: {}", self.db.variants.len()),
+            format!("dtcs: {}", self.db.dtcs.len()),
+        ];
+
+        if let Some(fbs) = self.fbs_size {
+            lines.push(format!("fbs_size: {fbs} bytes"));
+            if let Some(ratio) = self.compression_ratio() {
+                lines.push(format!("compression_ratio: {ratio:.2}x"));
+            }
+        }
+
+        let validation: Vec<&LogWarning> = self
+            .warnings
+            .iter()
+            .filter(|w| w.code == "validation")
+            .collect();
+        if !validation.is_empty() {
+            lines.push(format!("validation_warnings: {}", validation.len()));
+            if self.debug {
+                for w in &validation {
+                    lines.push(format!("  - {}", w.message));
+                }
+            }
+        }
+
+        let renames: Vec<&LogWarning> = self
+            .warnings
+            .iter()
+            .filter(|w| w.code == "short_name")
+            .collect();
+        if !renames.is_empty() {
+            lines.push(format!("short_name_fixes: {}", renames.len()));
+            for w in &renames {
+                lines.push(format!("  - {}", w.message));
+            }
+        }
+
+        if self.debug {
+            let (services, jobs) = self.service_counts();
+            lines.push(format!("total_services: {services}"));
+            lines.push(format!("total_single_ecu_jobs: {jobs}"));
+            for v in &self.db.variants {
+                lines.push(format!(
+                    "  variant '{}': {} services, {} jobs",
+                    v.diag_layer.short_name,
+                    v.diag_layer.diag_services.len(),
+                    v.diag_layer.single_ecu_jobs.len(),
+                ));
+            }
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    fn to_jsonl(&self) -> String {
+        let t = &self.timings;
+        let (services, single_ecu_jobs) = self.service_counts();
+        let mut events = vec![Event::Start {
+            input: self.input.display().to_string(),
+            input_format: format_name(self.input_format),
+            input_size: self.input_size,
+            output: self.output.display().to_string(),
+            output_format: format_name(self.output_format),
+        }];
+        for (phase, ms) in [
+            ("parse", t.parse),
+            ("validate", t.validate),
+            ("write", t.write),
+        ] {
+            events.push(Event::Phase {
+                phase,
+                ms: round_ms(ms),
+            });
+        }
+        events.extend(self.warnings.iter().map(|w| Event::Warning {
+            code: w.code,
+            message: &w.message,
+        }));
+        events.push(Event::Counts {
+            ecu: &self.db.ecu_name,
+            variants: self.db.variants.len(),
+            dtcs: self.db.dtcs.len(),
+            services,
+            single_ecu_jobs,
+        });
+        if self.debug {
+            events.extend(self.db.variants.iter().map(|v| Event::Variant {
+                name: &v.diag_layer.short_name,
+                services: v.diag_layer.diag_services.len(),
+                single_ecu_jobs: v.diag_layer.single_ecu_jobs.len(),
+            }));
+        }
+        events.push(Event::Sizes {
+            input_size: self.input_size,
+            output_size: self.output_size,
+            fbs_size: self.fbs_size,
+            compression_ratio: self
+                .compression_ratio()
+                .map(|r| (r * 100.0).round() / 100.0),
+        });
+        events.push(Event::Done {
+            total_ms: round_ms(t.total),
+            warnings: self.warnings.len(),
+        });
+
+        events
+            .iter()
+            .map(|e| serde_json::to_string(e).expect("log events serialize") + "\n")
+            .collect()
+    }
+}
+
+fn format_name(format: Format) -> String {
+    format!("{format:?}").to_ascii_lowercase()
+}
+
+fn round_ms(ms: f64) -> f64 {
+    (ms * 10.0).round() / 10.0
+}
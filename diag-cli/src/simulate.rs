@@ -0,0 +1,260 @@
+//! Run a mock ECU over DoIP. The fault memory of the database answers
+//! ReadDTCInformation (0x19) and ClearDiagnosticInformation (0x14), other
+//! services get serviceNotSupported, and faults are injected at runtime
+//! through the control socket (see [`diag_sim::control`]). A simulator
+//! profile (see [`diag_sim::profile`]) adds latency, NRC 0x78 sequences and
+//! injected negative responses per service.
+//!
+//! The database is loaded through a [`DatabaseCache`], so an edited file is
+//! picked up by the next tester connection without restarting; the fault
+//! memory and the ECU address stay those of the first load.
+
+use anyhow::{Context, Result};
+use diag_ir::{DatabaseCache, DiagDatabase, DiagService};
+use diag_sim::{FaultMemory, SimProfile};
+use diag_transport::DoipServer;
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::convert::parse_input;
+
+/// ECU logical address answered on when neither `--ecu-addr` nor the
+/// database's transport section names one.
+const DEFAULT_ECU_ADDRESS: u16 = 0x1000;
+
+/// Simulate the ECU of `db_path` for testers connecting to `listen`, with
+/// the fault injection control socket on `control` and the responses shaped
+/// by the simulator profile in `profile`.
+pub fn run_simulate(
+    db_path: &Path,
+    listen: &str,
+    control: Option<&str>,
+    ecu_addr: Option<u16>,
+    profile: Option<&Path>,
+) -> Result<()> {
+    let cache = DatabaseCache::new(1);
+    let db = cache.get_or_load(db_path, load_database)?;
+    let profile = profile.map(load_profile).transpose()?.unwrap_or_default();
+    let ecu_address = ecu_addr
+        .or_else(|| db.transport.as_ref()?.doip.as_ref()?.logical_address)
+        .unwrap_or(DEFAULT_ECU_ADDRESS);
+    let store = Arc::new(Mutex::new(FaultMemory::from_database(&db)));
+
+    if let Some(control) = control {
+        let listener = TcpListener::bind(control).with_context(|| format!("binding {control}"))?;
+        eprintln!("Fault injection control socket on {control}");
+        diag_sim::spawn_control_server(listener, Arc::clone(&store));
+    }
+    let listener = TcpListener::bind(listen).with_context(|| format!("binding {listen}"))?;
+    eprintln!(
+        "Simulating {} (logical address 0x{ecu_address:04X}, {} DTC(s)) on {listen}",
+        db.ecu_name,
+        db.dtcs.len()
+    );
+    let mut current = db;
+    let services = || {
+        match cache.get_or_load(db_path, load_database) {
+            Ok(db) => current = db,
+            Err(e) => log::warn!(
+                "reloading {}, keeping the loaded database: {e:#}",
+                db_path.display()
+            ),
+        }
+        database_services(&current)
+    };
+    serve_testers(&listener, services, &profile, &store, ecu_address)
+}
+
+fn load_database(path: &Path) -> Result<DiagDatabase> {
+    parse_input(path, false)
+}
+
+fn database_services(db: &DiagDatabase) -> Vec<DiagService> {
+    db.variants
+        .iter()
+        .flat_map(|v| v.diag_layer.diag_services.iter().cloned())
+        .collect()
+}
+
+fn load_profile(path: &Path) -> Result<SimProfile> {
+    let text =
+        std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    SimProfile::from_yaml(&text).with_context(|| format!("simulator profile {}", path.display()))
+}
+
+/// Serve one tester connection after the other until accepting fails,
+/// asking `services` for the services of each new connection.
+fn serve_testers(
+    listener: &TcpListener,
+    mut services: impl FnMut() -> Vec<DiagService>,
+    profile: &SimProfile,
+    store: &Mutex<FaultMemory>,
+    ecu_address: u16,
+) -> Result<()> {
+    let mut rng = profile.rng();
+    for stream in listener.incoming() {
+        let stream = stream.context("accepting tester connection")?;
+        let peer = stream
+            .peer_addr()
+            .map_or_else(|_| "tester".into(), |addr| addr.to_string());
+        eprintln!("{peer} connected");
+        let services = services();
+        let mut server = DoipServer::new(stream, ecu_address);
+        let served = diag_sim::serve(&mut server, &services, profile, &mut rng, |request| {
+            store
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .handle(request)
+        });
+        match served {
+            Ok(()) => eprintln!("{peer} disconnected"),
+            Err(e) => log::warn!("{peer}: {e}"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diag_transport::{DoipClient, Transport, TransportOptions};
+
+    const ECU_YAML: &str = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dtcs:
+  0x012300:
+    name: ThrottleHigh
+    sae: P0123
+"#;
+
+    #[test]
+    fn injected_faults_are_read_and_cleared_over_doip() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("ecu.yml");
+        std::fs::write(&db_path, ECU_YAML).unwrap();
+        let db = parse_input(&db_path, false).unwrap();
+        let store = Arc::new(Mutex::new(FaultMemory::from_database(&db)));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let ecu_store = Arc::clone(&store);
+        std::thread::spawn(move || {
+            serve_testers(
+                &listener,
+                Vec::new,
+                &SimProfile::default(),
+                &ecu_store,
+                0x1000,
+            )
+        });
+
+        let mut client = DoipClient::connect(&addr, &TransportOptions::default()).unwrap();
+        assert_eq!(
+            client.request(&[0x19, 0x02, 0xFF]).unwrap(),
+            [0x59, 0x02, 0xFF]
+        );
+        assert_eq!(
+            diag_sim::execute_command(&store, "inject ThrottleHigh"),
+            "ok"
+        );
+        assert_eq!(
+            client.request(&[0x19, 0x02, 0xFF]).unwrap(),
+            [0x59, 0x02, 0xFF, 0x01, 0x23, 0x00, 0x09]
+        );
+        assert_eq!(client.request(&[0x14, 0xFF, 0xFF, 0xFF]).unwrap(), [0x54]);
+        assert_eq!(
+            client.request(&[0x19, 0x02, 0xFF]).unwrap(),
+            [0x59, 0x02, 0xFF]
+        );
+        assert_eq!(
+            client.request(&[0x22, 0xF1, 0x90]).unwrap(),
+            [0x7F, 0x22, 0x11]
+        );
+    }
+
+    #[test]
+    fn profile_file_injects_pending_frames_and_nrcs_over_doip() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("ecu.yml");
+        std::fs::write(
+            &db_path,
+            format!("{ECU_YAML}services:\n  testerPresent:\n    enabled: true\n"),
+        )
+        .unwrap();
+        let profile_path = dir.path().join("profile.yml");
+        std::fs::write(
+            &profile_path,
+            "seed: 1\n\
+             default: { response_pending: 2, pending_interval_ms: 1 }\n\
+             services:\n  \
+               TesterPresent:\n    \
+                 negative_responses: [{ nrc: 0x22, probability: 1.0 }]\n",
+        )
+        .unwrap();
+        let db = parse_input(&db_path, false).unwrap();
+        let services = database_services(&db);
+        let profile = load_profile(&profile_path).unwrap();
+        let store = Mutex::new(FaultMemory::from_database(&db));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            serve_testers(&listener, || services.clone(), &profile, &store, 0x1000)
+        });
+
+        let mut client = DoipClient::connect(&addr, &TransportOptions::default()).unwrap();
+        client.send(&[0x19, 0x02, 0xFF]).unwrap();
+        assert_eq!(client.receive().unwrap(), [0x7F, 0x19, 0x78]);
+        assert_eq!(client.receive().unwrap(), [0x7F, 0x19, 0x78]);
+        assert_eq!(client.receive().unwrap(), [0x59, 0x02, 0xFF]);
+        client.send(&[0x3E, 0x00]).unwrap();
+        assert_eq!(client.receive().unwrap(), [0x7F, 0x3E, 0x22]);
+
+        let err = load_profile(&db_path).unwrap_err();
+        assert!(format!("{err:#}").contains("simulator profile"), "{err:#}");
+    }
+
+    #[test]
+    fn edited_database_is_served_to_the_next_tester() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("ecu.yml");
+        std::fs::write(&db_path, ECU_YAML).unwrap();
+        let profile = SimProfile::from_yaml(
+            "services:\n  TesterPresent:\n    negative_responses: [{ nrc: 0x22, probability: 1.0 }]\n",
+        )
+        .unwrap();
+        let cache = DatabaseCache::new(1);
+        let first = cache.get_or_load(&db_path, load_database).unwrap();
+        let store = Mutex::new(FaultMemory::from_database(&first));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let path = db_path.clone();
+        std::thread::spawn(move || {
+            let services = || database_services(&cache.get_or_load(&path, load_database).unwrap());
+            serve_testers(&listener, services, &profile, &store, 0x1000)
+        });
+
+        let mut client = DoipClient::connect(&addr, &TransportOptions::default()).unwrap();
+        assert_eq!(client.request(&[0x3E, 0x00]).unwrap(), [0x7F, 0x3E, 0x11]);
+        drop(client);
+
+        std::fs::write(
+            &db_path,
+            format!("{ECU_YAML}services:\n  testerPresent:\n    enabled: true\n"),
+        )
+        .unwrap();
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+        std::fs::File::options()
+            .write(true)
+            .open(&db_path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        let mut client = DoipClient::connect(&addr, &TransportOptions::default()).unwrap();
+        assert_eq!(client.request(&[0x3E, 0x00]).unwrap(), [0x7F, 0x3E, 0x22]);
+    }
+}
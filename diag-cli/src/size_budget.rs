@@ -0,0 +1,162 @@
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use diag_ir::{DiagDatabase, SizeBreakdown};
+
+/// Contributors listed when a budget is exceeded.
+const TOP_CONTRIBUTORS: usize = 10;
+
+/// A byte count written as `25MB`, `512KiB` or `1048576`. Decimal units
+/// (KB, MB, GB) are powers of 1000, binary units (KiB, MiB, GiB) of 1024.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text = s.trim();
+        let split = text
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(text.len());
+        let (number, unit) = text.split_at(split);
+        let factor: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+            "" | "b" => 1,
+            "k" | "kb" => 1_000,
+            "m" | "mb" => 1_000_000,
+            "g" | "gb" => 1_000_000_000,
+            "kib" => 1 << 10,
+            "mib" => 1 << 20,
+            "gib" => 1 << 30,
+            other => return Err(format!("unknown size unit '{other}' in '{s}'")),
+        };
+        let value: f64 = number
+            .parse()
+            .map_err(|_| format!("invalid size '{s}' (expected e.g. 25MB, 512KiB)"))?;
+        #[allow(clippy::cast_sign_loss)]
+        Ok(Self((value * factor as f64).round() as u64))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            n if n >= 1_000_000 => write!(f, "{:.1} MB", n as f64 / 1e6),
+            n if n >= 1_000 => write!(f, "{:.1} KB", n as f64 / 1e3),
+            n => write!(f, "{n} B"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Bytes(u64),
+            Text(String),
+        }
+        match Raw::deserialize(d)? {
+            Raw::Bytes(n) => Ok(Self(n)),
+            Raw::Text(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Size limits for MDD output, from `--max-output-size` and
+/// `--size-policy`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SizePolicy {
+    /// Limit for the written MDD file.
+    #[serde(default)]
+    pub max_output_size: Option<ByteSize>,
+    /// Limits per variant short name, on the variant's uncompressed
+    /// FlatBuffers size.
+    #[serde(default)]
+    pub variants: BTreeMap<String, ByteSize>,
+}
+
+impl SizePolicy {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        serde_yaml::from_str(&text)
+            .with_context(|| format!("parsing size policy {}", path.display()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max_output_size.is_none() && self.variants.is_empty()
+    }
+
+    /// Fail when `output_size` or a variant of `db` is over budget, listing
+    /// the largest contributors.
+    pub fn check(&self, db: &DiagDatabase, output_size: usize) -> Result<()> {
+        let mut violations = Vec::new();
+        if let Some(max) = self.max_output_size {
+            if output_size as u64 > max.0 {
+                violations.push(format!(
+                    "MDD output is {} ({output_size} bytes), over the limit of {max}",
+                    ByteSize(output_size as u64)
+                ));
+            }
+        }
+        let sizes =
+            (!self.variants.is_empty() || !violations.is_empty()).then(|| SizeBreakdown::of(db));
+        if let Some(sizes) = &sizes {
+            for (name, max) in &self.variants {
+                match sizes.variant(name) {
+                    Some(bytes) if bytes as u64 > max.0 => violations.push(format!(
+                        "variant {name} is {} ({bytes} bytes uncompressed), over its budget of {max}",
+                        ByteSize(bytes as u64)
+                    )),
+                    Some(_) => {}
+                    None => log::warn!("Size policy names unknown variant '{name}'"),
+                }
+            }
+        }
+        let Some(sizes) = sizes.filter(|_| !violations.is_empty()) else {
+            return Ok(());
+        };
+
+        let mut report = violations;
+        report.push(format!(
+            "Largest contributors (uncompressed, {} total):",
+            ByteSize(sizes.total as u64)
+        ));
+        report.extend(sizes.top(TOP_CONTRIBUTORS).into_iter().map(|c| {
+            format!(
+                "  {:<8} {:<48} {:>10}",
+                c.kind.as_str(),
+                c.name,
+                ByteSize(c.bytes as u64).to_string()
+            )
+        }));
+        bail!(report.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sizes() {
+        assert_eq!("25MB".parse(), Ok(ByteSize(25_000_000)));
+        assert_eq!("1.5 KiB".parse(), Ok(ByteSize(1536)));
+        assert_eq!("4096".parse(), Ok(ByteSize(4096)));
+        assert!("12 parsecs".parse::<ByteSize>().is_err());
+    }
+
+    #[test]
+    fn policy_file_accepts_numbers_and_units() {
+        let policy: SizePolicy =
+            serde_yaml::from_str("max_output_size: 2MB\nvariants:\n  Base: 65536\n").unwrap();
+        assert_eq!(policy.max_output_size, Some(ByteSize(2_000_000)));
+        assert_eq!(policy.variants["Base"], ByteSize(65536));
+    }
+}
@@ -0,0 +1,141 @@
+//! Source excerpts for parse errors.
+//!
+//! ```text
+//! did not find expected key at line 5 column 3
+//!   --> ecu.yml:5:3
+//!    |
+//!  4 |     name: VIN
+//!  5 |   writable: true
+//!    |   ^
+//!    = hint: keys of one mapping must be indented alike
+//! ```
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Known causes of common parser messages, by message fragment.
+const HINTS: &[(&str, &str)] = &[
+    (
+        "did not find expected key",
+        "keys of one mapping must be indented alike",
+    ),
+    (
+        "mapping values are not allowed",
+        "quote values that contain ': '",
+    ),
+    ("tab character", "indent YAML with spaces, not tabs"),
+    (
+        "cannot start any token",
+        "quote values that start with '@', '`' or '%'",
+    ),
+    (
+        "ill-formed document",
+        "every start tag needs a matching end tag",
+    ),
+    (
+        "missing field",
+        "the key or element is required here; check its spelling",
+    ),
+    (
+        "duplicate field",
+        "the key or element may only appear once here",
+    ),
+];
+
+/// `message` followed by the line of `text` holding byte `offset`, the line
+/// before it, a caret under the offending column and a hint when the
+/// message has a known cause.
+pub fn render(file: &Path, text: &str, offset: usize, message: &str) -> String {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[offset..].find('\n').map_or(text.len(), |i| offset + i);
+    let line_no = text[..line_start].matches('\n').count() + 1;
+    let column = text[line_start..offset].chars().count() + 1;
+    let previous = (line_no > 1).then(|| {
+        let before = &text[..line_start - 1];
+        &before[before.rfind('\n').map_or(0, |i| i + 1)..]
+    });
+
+    let width = line_no.to_string().len();
+    let gutter = " ".repeat(width);
+    let mut out = String::new();
+    let _ = writeln!(out, "{message}");
+    let _ = writeln!(out, "{gutter}--> {}:{line_no}:{column}", file.display());
+    let _ = writeln!(out, "{gutter} |");
+    if let Some(previous) = previous.filter(|l| !l.trim().is_empty()) {
+        let _ = writeln!(out, "{:>width$} | {}", line_no - 1, previous.trim_end());
+    }
+    let _ = writeln!(
+        out,
+        "{line_no:>width$} | {}",
+        text[line_start..line_end].trim_end()
+    );
+    // Keep tabs in the padding so the caret lines up with the text.
+    let padding: String = text[line_start..offset]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let _ = write!(out, "{gutter} | {padding}^");
+    if let Some((_, hint)) = HINTS.iter().find(|(cause, _)| message.contains(cause)) {
+        let _ = write!(out, "\n{gutter} = hint: {hint}");
+    }
+    out
+}
+
+/// A parse error of `file` as an excerpt of `text` when the parser knows
+/// where it failed, as is otherwise.
+pub fn located<E>(file: &Path, text: &str, offset: Option<usize>, error: E) -> anyhow::Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    match offset {
+        Some(offset) => anyhow::anyhow!(render(file, text, offset, &error.to_string())),
+        None => error.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_points_at_the_offending_column() {
+        let text = "ecu:\n  name: ECU\n dids: {}\n";
+        let offset = text.find("dids").unwrap();
+        let out = render(
+            Path::new("ecu.yml"),
+            text,
+            offset,
+            "did not find expected key",
+        );
+        assert_eq!(
+            out,
+            "did not find expected key\n \
+             --> ecu.yml:3:2\n  \
+             |\n\
+             2 |   name: ECU\n\
+             3 |  dids: {}\n  \
+             |  ^\n  \
+             = hint: keys of one mapping must be indented alike"
+        );
+    }
+
+    #[test]
+    fn yaml_and_odx_errors_carry_their_offset() {
+        let yaml = "schema: \"opensovd.cda.diagdesc/v1\"\necu:\n  name: [unclosed\n";
+        let err = diag_yaml::parse_yaml(yaml).unwrap_err();
+        let out = render(Path::new("ecu.yml"), yaml, err.offset().unwrap(), "");
+        assert!(out.contains("--> ecu.yml:"), "{out}");
+
+        let xml = "<ODX MODEL-VERSION=\"2.2.0\">\n  <DIAG-LAYER-CONTAINER ID=\"DLC\">\n    \
+                   <SHORT-NAME>ECU</SHORT-NAME>\n    <BASE-VARIANTS>\n      \
+                   <BASE-VARIANT ID=\"BV\"><SHORT-NAME>A</SHORT-NAME></BASE-VARIANT>\n    \
+                   </DIAG-LAYER-CONTAINER>\n</ODX>\n";
+        let err = diag_odx::parse_odx(xml).unwrap_err();
+        let out = render(Path::new("ecu.odx"), xml, err.offset().unwrap(), "");
+        assert!(out.contains("--> ecu.odx:6:5"), "{out}");
+    }
+}
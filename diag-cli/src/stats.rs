@@ -0,0 +1,119 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use diag_ir::{DiagDatabase, SizeBreakdown};
+
+use crate::Format;
+use crate::convert::parse_input_with;
+
+/// Print the size and object counts of every variant of `input`, its `top`
+/// largest services and the size of its other sections.
+pub fn run_stats(input: &Path, from: Option<Format>, top: usize) -> Result<()> {
+    let db = parse_input_with(input, from, &diag_odx::ParseOptions::default())?;
+    print!("{}", report(&db, top));
+    Ok(())
+}
+
+fn report(db: &DiagDatabase, top: usize) -> String {
+    let sizes = SizeBreakdown::of(db);
+    let variants = diag_ir::variant_stats(db, &sizes);
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "Total: {} bytes (FlatBuffers, uncompressed)",
+        sizes.total
+    );
+
+    if !variants.is_empty() {
+        let width = variants
+            .iter()
+            .map(|v| v.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("Variant".len());
+        let _ = writeln!(
+            out,
+            "\n{:<width$}  {:>8}  {:>4}  {:>6}  {:>9}",
+            "Variant", "Services", "Jobs", "Params", "Bytes"
+        );
+        for v in &variants {
+            let _ = writeln!(
+                out,
+                "{:<width$}  {:>8}  {:>4}  {:>6}  {:>9}",
+                v.name, v.services, v.single_ecu_jobs, v.params, v.bytes
+            );
+        }
+    }
+
+    if !sizes.services.is_empty() && top > 0 {
+        let params: HashMap<String, usize> = db
+            .variants
+            .iter()
+            .flat_map(|v| {
+                v.diag_layer.diag_services.iter().map(|svc| {
+                    (
+                        format!("{}/{}", v.diag_layer.short_name, svc.diag_comm.short_name),
+                        diag_ir::param_count(svc),
+                    )
+                })
+            })
+            .collect();
+        let shown = top.min(sizes.services.len());
+        let _ = writeln!(
+            out,
+            "\nLargest services ({shown} of {}):\n{:>9}  {:>6}  Service",
+            sizes.services.len(),
+            "Bytes",
+            "Params"
+        );
+        for svc in &sizes.services[..shown] {
+            let _ = writeln!(
+                out,
+                "{:>9}  {:>6}  {}",
+                svc.bytes,
+                params.get(&svc.name).copied().unwrap_or(0),
+                svc.name
+            );
+        }
+    }
+
+    if !sizes.sections.is_empty() {
+        let _ = writeln!(out, "\nSections:\n{:>9}  Section", "Bytes");
+        for section in &sizes.sections {
+            let _ = writeln!(out, "{:>9}  {}", section.bytes, section.name);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    #[test]
+    fn report_lists_variants_and_largest_services() {
+        let input = fixture("mdd/FLXCNG1000.mdd");
+        let db = crate::convert::parse_input(&input, false).unwrap();
+        let text = report(&db, 3);
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("Total: "), "{text}");
+        assert!(
+            lines
+                .iter()
+                .any(|l| l.starts_with("FLXCNG1000") && l.split_whitespace().count() == 5),
+            "{text}"
+        );
+        let largest = lines
+            .iter()
+            .position(|l| l.starts_with("Largest services (3 of "))
+            .unwrap();
+        let bytes: Vec<usize> = lines[largest + 2..largest + 5]
+            .iter()
+            .map(|l| l.split_whitespace().next().unwrap().parse().unwrap())
+            .collect();
+        assert!(bytes.windows(2).all(|w| w[0] >= w[1]), "{text}");
+    }
+}
@@ -0,0 +1,11 @@
+//! Helpers shared by the CLI tests. Integration tests include this file with
+//! `#[path]`, since they cannot reach `crate::test_support`.
+
+use std::path::{Path, PathBuf};
+
+/// The path of `rel` inside the workspace's `test-fixtures` directory.
+pub(crate) fn fixture(rel: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../test-fixtures")
+        .join(rel)
+}
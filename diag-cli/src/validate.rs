@@ -2,38 +2,98 @@ use anyhow::{Context, Result, bail};
 use std::path::Path;
 
 use crate::Format;
-use crate::convert::parse_input;
+use crate::convert::parse_input_with;
 
-pub fn run_validate(input: &Path, quiet: bool, summary: bool) -> Result<()> {
+pub fn run_validate(
+    input: &Path,
+    from: Option<Format>,
+    quiet: bool,
+    summary: bool,
+    policy: Option<&Path>,
+    public_key: Option<&Path>,
+) -> Result<()> {
     let mut all_errors: Vec<String> = Vec::new();
 
     // Schema + semantic validation for YAML files
-    let in_fmt = crate::detect_format(input).context("input file")?;
-    if in_fmt == Format::Yaml {
+    let in_fmt = crate::detect_input_format(input, from).context("input file")?;
+    if public_key.is_some() && in_fmt != Format::MDD {
+        bail!("--public-key applies to MDD input only");
+    }
+    if in_fmt == Format::YAML {
         let text = std::fs::read_to_string(input)
             .with_context(|| format!("reading {}", input.display()))?;
+        // Strict mode: unknown keys, reported with the nearest known key
+        // instead of the schema's bare "additional properties" error.
+        let unknown = diag_yaml::unknown_keys(&text).unwrap_or_default();
         if let Err(schema_errors) = diag_yaml::validate_yaml_schema(&text) {
             for e in &schema_errors {
+                if e.message
+                    .starts_with("Additional properties are not allowed")
+                    && unknown.iter().any(|k| k.pointer() == e.path)
+                {
+                    continue;
+                }
                 all_errors.push(format!("schema: {e}"));
             }
         }
-        // Semantic validation on YAML model
-        if let Ok(doc) = serde_yaml::from_str::<diag_yaml::yaml_model::YamlDocument>(&text) {
+        for key in &unknown {
+            all_errors.push(format!("strict: {key}"));
+        }
+        // Semantic validation on YAML model; issues inside aliased nodes
+        // name the anchor they were copied from.
+        let anchors = diag_yaml::anchors::analyze_anchors(&text)?;
+        if let Ok(doc) = diag_yaml::parse_yaml_document(&text) {
             let semantic_issues = diag_yaml::validate_semantics(&doc);
             for issue in &semantic_issues {
-                all_errors.push(issue.to_string());
+                match anchors.origin(&issue.path) {
+                    Some(origin) => all_errors.push(format!("{issue} ({origin})")),
+                    None => all_errors.push(issue.to_string()),
+                }
             }
         }
     }
 
+    // Container signature, checked on the stored chunks
+    if let Some(path) = public_key {
+        let contents =
+            std::fs::read(path).with_context(|| format!("reading key file {}", path.display()))?;
+        let key = mdd_format::signing::verifying_key_from_file_contents(&contents)
+            .with_context(|| format!("key file {}", path.display()))?;
+        let data = std::fs::read(input).with_context(|| format!("reading {}", input.display()))?;
+        if let Err(e) = mdd_format::reader::verify_mdd_signature(&data, &key) {
+            all_errors.push(format!("signature: {e}"));
+        }
+    }
+
     // IR-level validation (parse first)
-    let db = parse_input(input, false)?;
+    let db = parse_input_with(input, Some(in_fmt), &diag_odx::ParseOptions::default())?;
     if let Err(ir_errors) = diag_ir::validate_database(&db) {
         for e in &ir_errors {
             all_errors.push(e.to_string());
         }
     }
 
+    // Complexity budgets of the target tester
+    if let Some(path) = policy {
+        let policy = load_policy(path)?;
+        for v in diag_ir::check_complexity(&db, &policy) {
+            all_errors.push(format!("policy: {v}"));
+        }
+    }
+
+    // Lints: reported, but never fail validation
+    if !quiet {
+        for w in diag_ir::check_phys_constraints(&db) {
+            eprintln!("{}: warning: {w}", input.display());
+        }
+        if let Some(validity) = diag_ir::validity(&db) {
+            let status = validity.status_on(diag_ir::Date::today());
+            if status != diag_ir::ValidityStatus::Valid {
+                eprintln!("{}: warning: database {status}", input.display());
+            }
+        }
+    }
+
     if all_errors.is_empty() {
         if !quiet {
             println!("{}: valid", input.display());
@@ -63,3 +123,10 @@ pub fn run_validate(input: &Path, quiet: bool, summary: bool) -> Result<()> {
         input.display()
     );
 }
+
+fn load_policy(path: &Path) -> Result<diag_ir::ComplexityPolicy> {
+    let text =
+        std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    serde_yaml::from_str(&text)
+        .with_context(|| format!("parsing complexity policy {}", path.display()))
+}
@@ -0,0 +1,188 @@
+//! Golden-file regression suite.
+//!
+//! Every sample in [`SAMPLES`] is converted into each other format and the
+//! result compared with the checked-in golden file
+//! `test-fixtures/goldens/<sample>.<from>-to-<to>.<ext>`, so any change in
+//! converter output shows up in review as a golden diff. ODX output is
+//! pretty-printed; MDD output is decoded again and stored as the JSON of the
+//! header and IR, since the container itself is binary.
+//!
+//! Run `cargo xtask goldens --bless` (or set `UPDATE_GOLDENS=1`) to rewrite
+//! the goldens after an intended change.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use diag_ir::{DiagDatabase, flatbuffers_to_ir, ir_to_flatbuffers};
+use mdd_format::reader::read_mdd_bytes;
+use mdd_format::writer::{WriteOptions, write_mdd_bytes};
+
+#[path = "../src/test_support.rs"]
+mod test_support;
+
+use test_support::fixture;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Yaml,
+    Odx,
+    Mdd,
+}
+
+const FORMATS: [Format; 3] = [Format::Yaml, Format::Odx, Format::Mdd];
+
+/// Public sample databases, relative to `test-fixtures`. Their file stems
+/// name the goldens, so they must be unique.
+const SAMPLES: &[&str] = &[
+    "yaml/minimal-ecu.yml",
+    "odx/minimal.odx",
+    "mdd/FLXCNG1000.mdd",
+];
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Format::Yaml => "yaml",
+            Format::Odx => "odx",
+            Format::Mdd => "mdd",
+        }
+    }
+
+    fn of(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "yml" | "yaml" => Some(Format::Yaml),
+            "odx" => Some(Format::Odx),
+            "mdd" => Some(Format::Mdd),
+            _ => None,
+        }
+    }
+
+    /// Extension of the golden file for output in this format.
+    fn golden_extension(self) -> &'static str {
+        match self {
+            Format::Yaml => "yml",
+            Format::Odx => "odx",
+            Format::Mdd => "json",
+        }
+    }
+}
+
+fn blessing() -> bool {
+    std::env::var_os("UPDATE_GOLDENS").is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+fn parse(path: &Path, format: Format) -> DiagDatabase {
+    let bytes = std::fs::read(path).unwrap();
+    match format {
+        Format::Yaml => diag_yaml::parse_yaml(std::str::from_utf8(&bytes).unwrap()).unwrap(),
+        Format::Odx => diag_odx::parse_odx(std::str::from_utf8(&bytes).unwrap()).unwrap(),
+        Format::Mdd => {
+            let (_, fbs) = read_mdd_bytes(&bytes).unwrap();
+            flatbuffers_to_ir(&fbs).unwrap()
+        }
+    }
+}
+
+fn render(db: &DiagDatabase, format: Format) -> String {
+    match format {
+        Format::Yaml => diag_yaml::write_yaml(db).unwrap(),
+        Format::Odx => diag_odx::write_odx_pretty(db).unwrap(),
+        Format::Mdd => {
+            let options = WriteOptions {
+                version: db.version.clone(),
+                ecu_name: db.ecu_name.clone(),
+                revision: db.revision.clone(),
+                ..Default::default()
+            };
+            let mdd = write_mdd_bytes(&ir_to_flatbuffers(db), &options).unwrap();
+            let (meta, fbs) = read_mdd_bytes(&mdd).unwrap();
+            let decoded = flatbuffers_to_ir(&fbs).unwrap();
+            let doc = serde_json::json!({
+                "header": {
+                    "version": meta.version,
+                    "ecu_name": meta.ecu_name,
+                    "revision": meta.revision,
+                },
+                "database": decoded,
+            });
+            serde_json::to_string_pretty(&doc).unwrap() + "\n"
+        }
+    }
+}
+
+/// Line number and both versions of the first line that differs.
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    for line in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (None, None) => break,
+            (e, a) if e == a => {}
+            (e, a) => {
+                return format!(
+                    "line {line}:\n    golden: {}\n    actual: {}",
+                    e.unwrap_or("<end of file>"),
+                    a.unwrap_or("<end of file>")
+                );
+            }
+        }
+    }
+    "line endings differ".into()
+}
+
+#[test]
+fn test_goldens() {
+    let dir = fixture("goldens");
+    let bless = blessing();
+    let mut expected_files = BTreeSet::new();
+    let mut failures = Vec::new();
+    for sample in SAMPLES {
+        let sample = fixture(sample);
+        let from = Format::of(&sample).unwrap();
+        let stem = sample.file_stem().unwrap().to_str().unwrap();
+        let db = parse(&sample, from);
+        for to in FORMATS.into_iter().filter(|to| *to != from) {
+            let name = format!(
+                "{stem}.{}-to-{}.{}",
+                from.name(),
+                to.name(),
+                to.golden_extension()
+            );
+            let path = dir.join(&name);
+            let actual = render(&db, to);
+            expected_files.insert(name.clone());
+            if bless {
+                std::fs::write(&path, &actual).unwrap();
+                continue;
+            }
+            match std::fs::read_to_string(&path) {
+                Ok(golden) if golden == actual => {}
+                Ok(golden) => failures.push(format!(
+                    "{name} differs, {}",
+                    first_difference(&golden, &actual)
+                )),
+                Err(_) => failures.push(format!("{name} is missing")),
+            }
+        }
+    }
+
+    for entry in std::fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        if !expected_files.contains(&name) {
+            if bless {
+                std::fs::remove_file(&path).unwrap();
+            } else {
+                failures.push(format!("{name} belongs to no sample"));
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} golden file(s) out of date; run `cargo xtask goldens --bless` if the change is \
+         intended:\n  {}",
+        failures.len(),
+        failures.join("\n  ")
+    );
+}
@@ -477,3 +477,29 @@ fn lossless_odx_to_yaml_protocols_minimal() {
         );
     }
 }
+
+// -- Session server timing through ODX ---------------------------------
+
+#[test]
+fn session_server_timing_survives_yaml_odx_yaml() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  id: "TIMING_ECU"
+  name: "TimingECU"
+sessions:
+  default:
+    id: 1
+    timing:
+      p2_server_max: 25
+      p2_star_server_max: 2000
+services:
+  diagnosticSessionControl:
+    enabled: true
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let odx = write_odx(&db).unwrap();
+    let yaml_out = write_yaml(&parse_odx(&odx).unwrap()).unwrap();
+    assert!(yaml_out.contains("p2_server_max: 25"), "{yaml_out}");
+    assert!(yaml_out.contains("p2_star_server_max: 2000"), "{yaml_out}");
+}
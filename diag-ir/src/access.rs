@@ -0,0 +1,228 @@
+//! Access requirements of services: the sessions, security levels and
+//! authentication roles a service may be sent in, as its
+//! PRE-CONDITION-STATE-REFs define them.
+//!
+//! YAML input names the state chart of each reference (`Session`,
+//! `SecurityAccess`, `Authentication`). ODX references only name the state;
+//! its chart is found among the state charts of the database and classified
+//! by its short name and semantic.
+
+use serde::Serialize;
+
+use crate::types::{DiagDatabase, DiagLayer, PreConditionStateRef, StateChart};
+
+/// What kind of state a pre-condition refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum AccessKind {
+    Session,
+    Security,
+    Authentication,
+    /// A state of another chart, or one no chart defines.
+    Other,
+}
+
+impl AccessKind {
+    /// The kind of the states of `chart`, from its short name and semantic.
+    pub fn of_chart(chart: &StateChart) -> Self {
+        let name = format!("{} {}", chart.short_name, chart.semantic).to_ascii_uppercase();
+        if name.contains("SESSION") {
+            Self::Session
+        } else if name.contains("SECUR") {
+            Self::Security
+        } else if name.contains("AUTH") {
+            Self::Authentication
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// The states a service requires, by kind. An empty list means the service
+/// does not restrict that kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AccessRequirements {
+    pub layer: String,
+    pub service: String,
+    pub sessions: Vec<String>,
+    pub security_levels: Vec<String>,
+    pub authentication_roles: Vec<String>,
+    /// Pre-conditions on other states, as `chart/state` where the chart is
+    /// known.
+    pub other: Vec<String>,
+}
+
+/// The access requirements of every service, layer by layer in database
+/// order: variants, functional groups, protocols, ECU shared data.
+pub fn access_matrix(db: &DiagDatabase) -> Vec<AccessRequirements> {
+    let layers: Vec<&DiagLayer> = db
+        .variants
+        .iter()
+        .map(|v| &v.diag_layer)
+        .chain(db.functional_groups.iter().map(|fg| &fg.diag_layer))
+        .chain(db.protocols.iter().map(|p| &p.diag_layer))
+        .chain(db.ecu_shared_datas.iter().map(|e| &e.diag_layer))
+        .collect();
+    let mut matrix = Vec::new();
+    for layer in &layers {
+        for svc in &layer.diag_services {
+            let mut row = AccessRequirements {
+                layer: layer.short_name.to_string(),
+                service: svc.diag_comm.short_name.to_string(),
+                ..Default::default()
+            };
+            for pre_condition in &svc.diag_comm.pre_condition_state_refs {
+                let state = state_name(pre_condition);
+                let (kind, chart) = classify(pre_condition, state, layer, &layers);
+                let list = match kind {
+                    AccessKind::Session => &mut row.sessions,
+                    AccessKind::Security => &mut row.security_levels,
+                    AccessKind::Authentication => &mut row.authentication_roles,
+                    AccessKind::Other => {
+                        row.other.push(match chart {
+                            Some(chart) => format!("{chart}/{state}"),
+                            None => state.to_string(),
+                        });
+                        continue;
+                    }
+                };
+                if !list.iter().any(|s| s == state) {
+                    list.push(state.to_string());
+                }
+            }
+            matrix.push(row);
+        }
+    }
+    matrix
+}
+
+fn state_name(pre_condition: &PreConditionStateRef) -> &str {
+    pre_condition
+        .state
+        .as_ref()
+        .map(|s| s.short_name.as_str())
+        .filter(|s| !s.is_empty())
+        .or_else(|| Some(pre_condition.in_param_path_short_name.as_str()).filter(|s| !s.is_empty()))
+        .unwrap_or(&pre_condition.value)
+}
+
+/// The kind of the state a pre-condition refers to and the name of its
+/// chart. Charts of the service's own layer are searched first.
+fn classify<'a>(
+    pre_condition: &PreConditionStateRef,
+    state: &str,
+    layer: &'a DiagLayer,
+    layers: &[&'a DiagLayer],
+) -> (AccessKind, Option<&'a str>) {
+    match pre_condition.value.as_str() {
+        "Session" => return (AccessKind::Session, None),
+        "SecurityAccess" => return (AccessKind::Security, None),
+        "Authentication" => return (AccessKind::Authentication, None),
+        _ => {}
+    }
+    std::iter::once(layer)
+        .chain(layers.iter().copied())
+        .flat_map(|l| &l.state_charts)
+        .find(|chart| chart.states.iter().any(|s| s.short_name == state))
+        .map_or((AccessKind::Other, None), |chart| {
+            (AccessKind::of_chart(chart), Some(chart.short_name.as_str()))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::*;
+
+    fn pre_condition(value: &str, state: &str) -> PreConditionStateRef {
+        PreConditionStateRef {
+            value: value.into(),
+            in_param_if_short_name: String::new(),
+            in_param_path_short_name: String::new(),
+            state: Some(State {
+                short_name: state.into(),
+                long_name: None,
+            }),
+        }
+    }
+
+    fn chart(name: &str, states: &[&str]) -> StateChart {
+        StateChart {
+            short_name: name.into(),
+            semantic: Name::default(),
+            state_transitions: vec![],
+            start_state_short_name_ref: String::new(),
+            states: states
+                .iter()
+                .map(|s| State {
+                    short_name: (*s).into(),
+                    long_name: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_access_matrix_classifies_pre_conditions() {
+        let service = |name: &str, refs: Vec<PreConditionStateRef>| DiagService {
+            diag_comm: DiagComm {
+                short_name: name.into(),
+                pre_condition_state_refs: refs,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let db = DiagDatabase {
+            variants: vec![Variant {
+                diag_layer: DiagLayer {
+                    short_name: "Base".into(),
+                    state_charts: vec![
+                        chart("SC_DiagnosticSession", &["Default", "Extended"]),
+                        chart("SC_SecurityAccess", &["Locked", "Level_01"]),
+                        chart("SC_Voltage", &["Normal"]),
+                    ],
+                    diag_services: vec![
+                        // As YAML writes them: the chart in `value`.
+                        service(
+                            "Write_VIN",
+                            vec![
+                                pre_condition("Session", "Extended"),
+                                pre_condition("SecurityAccess", "Level_01"),
+                                pre_condition("Authentication", "Workshop"),
+                            ],
+                        ),
+                        // As ODX maps them: only the state.
+                        service(
+                            "Reset",
+                            vec![
+                                pre_condition("S_Default", "Default"),
+                                pre_condition("S_Extended", "Extended"),
+                                pre_condition("S_Normal", "Normal"),
+                                pre_condition("S_Unknown", "Unknown"),
+                            ],
+                        ),
+                        service("Read_VIN", vec![]),
+                    ],
+                    ..Default::default()
+                },
+                is_base_variant: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let matrix = access_matrix(&db);
+        assert_eq!(matrix.len(), 3);
+        assert_eq!(matrix[0].sessions, ["Extended"]);
+        assert_eq!(matrix[0].security_levels, ["Level_01"]);
+        assert_eq!(matrix[0].authentication_roles, ["Workshop"]);
+        assert_eq!(matrix[1].sessions, ["Default", "Extended"]);
+        assert_eq!(matrix[1].other, ["SC_Voltage/Normal", "Unknown"]);
+        assert_eq!(
+            matrix[2],
+            AccessRequirements {
+                layer: "Base".into(),
+                service: "Read_VIN".into(),
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Historical names of services and jobs.
+//!
+//! Tester scripts keep using the names services had before they were
+//! renamed. Those names are stored as an `aliases` SDG with one SD per name,
+//! so they survive ODX and MDD, and [`find_by_name`] resolves a name given by
+//! a user against short names and aliases alike, ignoring case.
+
+use crate::types::{DiagComm, Sd, SdOrSdg, Sdg, Sdgs};
+
+/// SDG caption under which aliases are stored.
+pub const ALIASES_CAPTION: &str = "aliases";
+
+/// How a name refers to a service or job, best match first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NameMatch {
+    /// The short name, spelled exactly.
+    ShortName,
+    /// The short name in another case.
+    IgnoringCase,
+    /// One of the aliases, in any case.
+    Alias,
+}
+
+/// Former names of a service or job.
+pub fn aliases(diag_comm: &DiagComm) -> Vec<&str> {
+    diag_comm
+        .sdgs
+        .iter()
+        .flat_map(|sdgs| &sdgs.sdgs)
+        .filter(|sdg| sdg.caption_sn == ALIASES_CAPTION)
+        .flat_map(|sdg| &sdg.sds)
+        .filter_map(|sd| match sd {
+            SdOrSdg::Sd(sd) => Some(sd.value.as_str()),
+            SdOrSdg::Sdg(_) => None,
+        })
+        .collect()
+}
+
+/// Replace the aliases of a service or job (removes them when `names` is
+/// empty).
+pub fn set_aliases(diag_comm: &mut DiagComm, names: &[String]) {
+    if let Some(sdgs) = &mut diag_comm.sdgs {
+        sdgs.sdgs.retain(|sdg| sdg.caption_sn != ALIASES_CAPTION);
+    }
+    if names.is_empty() {
+        return;
+    }
+    let sdg = Sdg {
+        caption_sn: ALIASES_CAPTION.into(),
+        sds: names
+            .iter()
+            .map(|name| {
+                SdOrSdg::Sd(Sd {
+                    value: name.clone(),
+                    si: "alias".into(),
+                    ti: String::new(),
+                })
+            })
+            .collect(),
+        si: String::new(),
+    };
+    diag_comm
+        .sdgs
+        .get_or_insert_with(|| Sdgs { sdgs: vec![] })
+        .sdgs
+        .push(sdg);
+}
+
+/// Whether `name` refers to the service or job, and how.
+pub fn name_match(diag_comm: &DiagComm, name: &str) -> Option<NameMatch> {
+    if diag_comm.short_name == name {
+        Some(NameMatch::ShortName)
+    } else if diag_comm.short_name.eq_ignore_ascii_case(name) {
+        Some(NameMatch::IgnoringCase)
+    } else if aliases(diag_comm)
+        .iter()
+        .any(|alias| alias.eq_ignore_ascii_case(name))
+    {
+        Some(NameMatch::Alias)
+    } else {
+        None
+    }
+}
+
+/// The item `name` refers to: the first one with that exact short name,
+/// else the first with that short name in another case, else the first
+/// with that alias.
+pub fn find_by_name<'a, T: 'a>(
+    items: impl IntoIterator<Item = &'a T>,
+    name: &str,
+    diag_comm: impl Fn(&T) -> &DiagComm,
+) -> Option<&'a T> {
+    let mut best: Option<(NameMatch, &'a T)> = None;
+    for item in items {
+        match name_match(diag_comm(item), name) {
+            Some(NameMatch::ShortName) => return Some(item),
+            Some(found) if best.is_none_or(|(m, _)| found < m) => best = Some((found, item)),
+            _ => {}
+        }
+    }
+    best.map(|(_, item)| item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comm(short_name: &str, aliases: &[&str]) -> DiagComm {
+        let mut comm = DiagComm {
+            short_name: short_name.into(),
+            ..Default::default()
+        };
+        let names: Vec<String> = aliases.iter().map(|a| (*a).to_string()).collect();
+        set_aliases(&mut comm, &names);
+        comm
+    }
+
+    #[test]
+    fn test_aliases_roundtrip_through_sdgs() {
+        let mut c = comm("VIN_Read", &["VehicleIdent_Read", "VIN_Get"]);
+        assert_eq!(aliases(&c), ["VehicleIdent_Read", "VIN_Get"]);
+        set_aliases(&mut c, &["Old_Read".into()]);
+        assert_eq!(aliases(&c), ["Old_Read"]);
+        set_aliases(&mut c, &[]);
+        assert!(aliases(&c).is_empty());
+    }
+
+    #[test]
+    fn test_find_by_name_prefers_short_names_over_aliases() {
+        let items = [
+            comm("Reset", &["vin_read"]),
+            comm("VIN_READ", &[]),
+            comm("VIN_Read", &[]),
+            comm("Ident_Read", &["VehicleIdent_Read"]),
+        ];
+        let find = |name| find_by_name(&items, name, |c| c).map(|c| c.short_name.as_str());
+        assert_eq!(find("VIN_Read"), Some("VIN_Read"));
+        assert_eq!(find("vin_READ"), Some("VIN_READ"));
+        assert_eq!(find("vehicleident_read"), Some("Ident_Read"));
+        assert_eq!(find("Unknown"), None);
+        assert_eq!(
+            name_match(&items[3], "VEHICLEIDENT_READ"),
+            Some(NameMatch::Alias)
+        );
+    }
+}
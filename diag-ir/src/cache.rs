@@ -0,0 +1,269 @@
+//! Shared cache of loaded databases.
+//!
+//! Long-running consumers (a simulator serving several testers, a watcher
+//! re-running checks) often open the same file many times. [`DatabaseCache`]
+//! loads each file once and hands out `Arc`s to the one copy. An entry is
+//! reloaded when the file's modification time changes, and the least
+//! recently used entry is dropped once the cache is full; consumers still
+//! holding its `Arc` keep their copy.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use crate::types::DiagDatabase;
+
+struct Entry {
+    db: Arc<DiagDatabase>,
+    /// Modification time of the file when it was loaded.
+    modified: Option<SystemTime>,
+    /// Value of [`Entries::clock`] at the last lookup.
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_path: HashMap<PathBuf, Entry>,
+    clock: u64,
+}
+
+/// Thread-safe LRU cache of databases, keyed by file path.
+pub struct DatabaseCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+impl DatabaseCache {
+    /// A cache holding at most `capacity` databases (at least one).
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: Mutex::default(),
+        }
+    }
+
+    /// The database of `path`, calling `load` unless a copy loaded from the
+    /// file's current version is cached.
+    ///
+    /// The lock is not held while loading, so other paths stay available;
+    /// two threads missing the same path at once may both load it.
+    pub fn get_or_load<E>(
+        &self,
+        path: &Path,
+        load: impl FnOnce(&Path) -> Result<DiagDatabase, E>,
+    ) -> Result<Arc<DiagDatabase>, E> {
+        let modified = modified(path);
+        {
+            let mut entries = self.lock();
+            entries.clock += 1;
+            let now = entries.clock;
+            match entries.by_path.get_mut(path) {
+                Some(entry) if modified.is_some() && entry.modified == modified => {
+                    entry.last_used = now;
+                    return Ok(Arc::clone(&entry.db));
+                }
+                Some(_) => {
+                    log::debug!("{} changed, reloading", path.display());
+                    entries.by_path.remove(path);
+                }
+                None => {}
+            }
+        }
+
+        let db = Arc::new(load(path)?);
+        let mut entries = self.lock();
+        entries.clock += 1;
+        let last_used = entries.clock;
+        entries.by_path.insert(
+            path.to_path_buf(),
+            Entry {
+                db: Arc::clone(&db),
+                modified,
+                last_used,
+            },
+        );
+        while entries.by_path.len() > self.capacity {
+            let Some(oldest) = entries
+                .by_path
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(p, _)| p.clone())
+            else {
+                break;
+            };
+            log::debug!("Evicting {} from the database cache", oldest.display());
+            entries.by_path.remove(&oldest);
+        }
+        Ok(db)
+    }
+
+    /// Drop the entry of `path`, e.g. after a watcher reported a change the
+    /// modification time does not show.
+    pub fn invalidate(&self, path: &Path) {
+        self.lock().by_path.remove(path);
+    }
+
+    pub fn clear(&self) {
+        self.lock().by_path.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().by_path.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        // An entry map is consistent after every statement, so a panic in
+        // another thread leaves nothing half-updated.
+        self.entries
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl Default for DatabaseCache {
+    /// A cache of 8 databases.
+    fn default() -> Self {
+        Self::new(8)
+    }
+}
+
+impl std::fmt::Debug for DatabaseCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let entries = self.lock();
+        let mut paths: Vec<_> = entries.by_path.keys().collect();
+        paths.sort();
+        f.debug_struct("DatabaseCache")
+            .field("capacity", &self.capacity)
+            .field("paths", &paths)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Modification time of `path`; `None` when it cannot be read, which makes
+/// every lookup a reload.
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use std::fs::File;
+    use std::time::Duration;
+
+    /// A file in the temp directory, removed on drop.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("diag-ir-cache-{}-{name}", std::process::id()));
+            std::fs::write(&path, name).unwrap();
+            Self(path)
+        }
+
+        fn touch(&self, age: Duration) {
+            let file = File::options().write(true).open(&self.0).unwrap();
+            file.set_modified(SystemTime::now() - age).unwrap();
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[allow(clippy::unnecessary_wraps)] // the signature `get_or_load` takes
+    fn load(path: &Path) -> Result<DiagDatabase, Infallible> {
+        Ok(DiagDatabase {
+            ecu_name: std::fs::read_to_string(path).unwrap(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_cache_shares_one_copy() {
+        let file = TempFile::new("shared");
+        let cache = DatabaseCache::new(2);
+        let a = cache.get_or_load(&file.0, load).unwrap();
+        let b = cache
+            .get_or_load(&file.0, |_| -> Result<_, Infallible> {
+                panic!("cached database loaded again")
+            })
+            .unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(a.ecu_name, "shared");
+    }
+
+    #[test]
+    fn test_cache_reloads_changed_file() {
+        let file = TempFile::new("changed");
+        file.touch(Duration::from_secs(30));
+        let cache = DatabaseCache::default();
+        let old = cache.get_or_load(&file.0, load).unwrap();
+
+        std::fs::write(&file.0, "changed_again").unwrap();
+        file.touch(Duration::ZERO);
+        let new = cache.get_or_load(&file.0, load).unwrap();
+        assert_eq!(new.ecu_name, "changed_again");
+        assert_eq!(old.ecu_name, "changed");
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let (a, b, c) = (TempFile::new("a"), TempFile::new("b"), TempFile::new("c"));
+        let cache = DatabaseCache::new(2);
+        cache.get_or_load(&a.0, load).unwrap();
+        cache.get_or_load(&b.0, load).unwrap();
+        cache.get_or_load(&a.0, load).unwrap();
+        cache.get_or_load(&c.0, load).unwrap();
+        assert_eq!(cache.len(), 2);
+
+        let mut loaded = false;
+        cache
+            .get_or_load(&b.0, |p| {
+                loaded = true;
+                load(p)
+            })
+            .unwrap();
+        assert!(loaded, "b should have been evicted");
+    }
+
+    #[test]
+    fn test_cache_is_shared_between_threads() {
+        let file = TempFile::new("threads");
+        let cache = Arc::new(DatabaseCache::new(1));
+        let first = cache.get_or_load(&file.0, load).unwrap();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let cache = Arc::clone(&cache);
+                let path = file.0.clone();
+                std::thread::spawn(move || cache.get_or_load(&path, load).unwrap())
+            })
+            .collect();
+        for handle in handles {
+            assert!(Arc::ptr_eq(&first, &handle.join().unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_cache_invalidate() {
+        let file = TempFile::new("invalidate");
+        let cache = DatabaseCache::new(1);
+        cache.get_or_load(&file.0, load).unwrap();
+        cache.invalidate(&file.0);
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,518 @@
+//! Reading and writing coded (internal) values of a DIAG-CODED-TYPE at a
+//! byte and bit position of a message.
+
+use crate::numbers::parse_hex_bytes;
+use crate::types::{DataType, DiagCodedType, DiagCodedTypeData, Termination};
+
+use super::Problem;
+use super::value::Value;
+
+/// Read a value of `coded_type` at `byte`/`bit` of `pdu`. `param_length` is
+/// the length in bits a PARAM-LENGTH-INFO type's length key gave. Returns
+/// the internal value and the position after it.
+pub(crate) fn read(
+    coded_type: &DiagCodedType,
+    pdu: &[u8],
+    byte: usize,
+    bit: u32,
+    param_length: Option<u64>,
+) -> Result<(Value, usize), Problem> {
+    let big_endian = coded_type.is_high_low_byte_order;
+    match &coded_type.specific_data {
+        Some(DiagCodedTypeData::StandardLength {
+            bit_length,
+            bit_mask,
+            condensed,
+        }) => {
+            let end = byte + (bit + bit_length).div_ceil(8) as usize;
+            let bytes = pdu.get(byte..end).ok_or(Problem::Truncated(end))?;
+            if !is_numeric(coded_type.base_data_type) {
+                return Ok((bytes_value(bytes, coded_type), end));
+            }
+            if bit + bit_length > 64 {
+                return Err(Problem::Unsupported(format!(
+                    "{bit_length}-bit numeric value"
+                )));
+            }
+            let mut raw = to_uint(bytes, big_endian) >> bit;
+            if *bit_length < 64 {
+                raw &= (1u64 << bit_length) - 1;
+            }
+            if !bit_mask.is_empty() {
+                let mask = to_uint(bit_mask, true);
+                raw = if *condensed {
+                    condense(raw, mask)
+                } else {
+                    raw & mask
+                };
+            }
+            Ok((numeric(raw, *bit_length, coded_type.base_data_type), end))
+        }
+        Some(DiagCodedTypeData::MinMax {
+            min_length,
+            max_length,
+            termination,
+        }) => {
+            let data = pdu.get(byte..).ok_or(Problem::Truncated(byte))?;
+            let max = max_length.map_or(data.len(), |m| data.len().min(m as usize));
+            let (len, consumed) = match terminator(*termination) {
+                Some(t) => match data[..max].iter().position(|&b| b == t) {
+                    Some(i) => (i, i + 1),
+                    None => (max, max),
+                },
+                None => (max, max),
+            };
+            if len < *min_length as usize {
+                return Err(Problem::Truncated(byte + *min_length as usize));
+            }
+            Ok((bytes_value(&data[..len], coded_type), byte + consumed))
+        }
+        Some(DiagCodedTypeData::LeadingLength { bit_length }) => {
+            let start = byte + bit_length.div_ceil(8) as usize;
+            let prefix = pdu.get(byte..start).ok_or(Problem::Truncated(start))?;
+            let end = start + to_uint(prefix, big_endian) as usize;
+            let bytes = pdu.get(start..end).ok_or(Problem::Truncated(end))?;
+            Ok((bytes_value(bytes, coded_type), end))
+        }
+        Some(DiagCodedTypeData::ParamLength { length_key }) => {
+            let bits = param_length.ok_or_else(|| {
+                Problem::Unsupported(format!(
+                    "length key {} was not decoded before",
+                    length_key.short_name
+                ))
+            })?;
+            let end = byte + bits.div_ceil(8) as usize;
+            let bytes = pdu.get(byte..end).ok_or(Problem::Truncated(end))?;
+            if is_numeric(coded_type.base_data_type) && bits <= 64 {
+                let raw = to_uint(bytes, big_endian);
+                return Ok((numeric(raw, bits as u32, coded_type.base_data_type), end));
+            }
+            Ok((bytes_value(bytes, coded_type), end))
+        }
+        None => Err(Problem::Unsupported("coded type without length".into())),
+    }
+}
+
+/// Write the internal `value` as `coded_type` at `byte`/`bit` of `pdu`,
+/// growing it as needed. Returns the position after the value.
+pub(crate) fn write(
+    coded_type: &DiagCodedType,
+    value: &Value,
+    pdu: &mut Vec<u8>,
+    byte: usize,
+    bit: u32,
+) -> Result<usize, Problem> {
+    let big_endian = coded_type.is_high_low_byte_order;
+    match &coded_type.specific_data {
+        Some(DiagCodedTypeData::StandardLength {
+            bit_length,
+            bit_mask,
+            condensed,
+        }) => {
+            let len = (bit + bit_length).div_ceil(8) as usize;
+            reserve(pdu, byte + len);
+            if !is_numeric(coded_type.base_data_type) {
+                let bytes = value_bytes(value, coded_type)?;
+                if bytes.len() > len {
+                    return Err(Problem::Invalid(format!(
+                        "{} bytes do not fit in {len}",
+                        bytes.len()
+                    )));
+                }
+                pdu[byte..byte + bytes.len()].copy_from_slice(&bytes);
+                return Ok(byte + len);
+            }
+            if bit + bit_length > 64 {
+                return Err(Problem::Unsupported(format!(
+                    "{bit_length}-bit numeric value"
+                )));
+            }
+            let mut raw = raw_bits(value, *bit_length, coded_type.base_data_type)?;
+            if !bit_mask.is_empty() {
+                let mask = to_uint(bit_mask, true);
+                raw = if *condensed {
+                    expand(raw, mask)
+                } else {
+                    raw & mask
+                };
+            }
+            let shifted = raw << bit;
+            for i in 0..len {
+                let b = (shifted >> (8 * i)) as u8;
+                let idx = if big_endian {
+                    byte + len - 1 - i
+                } else {
+                    byte + i
+                };
+                pdu[idx] |= b;
+            }
+            Ok(byte + len)
+        }
+        Some(DiagCodedTypeData::MinMax {
+            min_length,
+            max_length,
+            termination,
+        }) => {
+            let mut bytes = value_bytes(value, coded_type)?;
+            if let Some(max) = max_length {
+                if bytes.len() > *max as usize {
+                    return Err(Problem::Invalid(format!(
+                        "{} bytes exceed the maximum of {max}",
+                        bytes.len()
+                    )));
+                }
+            }
+            if bytes.len() < *min_length as usize {
+                bytes.resize(*min_length as usize, 0);
+            }
+            if let Some(t) = terminator(*termination) {
+                if max_length.is_none_or(|max| bytes.len() < max as usize) {
+                    bytes.push(t);
+                }
+            }
+            put(pdu, byte, &bytes);
+            Ok(byte + bytes.len())
+        }
+        Some(DiagCodedTypeData::LeadingLength { bit_length }) => {
+            let bytes = value_bytes(value, coded_type)?;
+            let width = bit_length.div_ceil(8) as usize;
+            if width > 8 || (*bit_length < 64 && (bytes.len() as u64) >> bit_length != 0) {
+                return Err(Problem::Invalid(format!(
+                    "{} bytes do not fit a {bit_length}-bit length prefix",
+                    bytes.len()
+                )));
+            }
+            let mut prefix = (bytes.len() as u64).to_be_bytes()[8 - width..].to_vec();
+            if !big_endian {
+                prefix.reverse();
+            }
+            put(pdu, byte, &prefix);
+            put(pdu, byte + width, &bytes);
+            Ok(byte + width + bytes.len())
+        }
+        Some(DiagCodedTypeData::ParamLength { .. }) => {
+            let bytes = value_bytes(value, coded_type)?;
+            put(pdu, byte, &bytes);
+            Ok(byte + bytes.len())
+        }
+        None => Err(Problem::Unsupported("coded type without length".into())),
+    }
+}
+
+/// The length in bits `value` takes as a PARAM-LENGTH-INFO value, which its
+/// length key has to carry.
+pub(crate) fn param_length(value: &Value, coded_type: &DiagCodedType) -> Result<u64, Problem> {
+    Ok(value_bytes(value, coded_type)?.len() as u64 * 8)
+}
+
+/// The internal value a coded value of the IR (e.g. `0x22`, `34`) denotes.
+pub(crate) fn parse_coded(text: &str, coded_type: &DiagCodedType) -> Value {
+    match coded_type.base_data_type {
+        DataType::AAsciiString | DataType::AUtf8String | DataType::AUnicode2String => {
+            Value::Text(text.to_string())
+        }
+        _ => Value::parse(text),
+    }
+}
+
+pub(crate) fn is_numeric(data_type: DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::AInt32 | DataType::AUint32 | DataType::AFloat32 | DataType::AFloat64
+    )
+}
+
+fn terminator(termination: Termination) -> Option<u8> {
+    match termination {
+        Termination::Zero => Some(0x00),
+        Termination::HexFf => Some(0xFF),
+        Termination::EndOfPdu => None,
+    }
+}
+
+fn reserve(pdu: &mut Vec<u8>, len: usize) {
+    if pdu.len() < len {
+        pdu.resize(len, 0);
+    }
+}
+
+fn put(pdu: &mut Vec<u8>, at: usize, bytes: &[u8]) {
+    reserve(pdu, at + bytes.len());
+    pdu[at..at + bytes.len()].copy_from_slice(bytes);
+}
+
+fn to_uint(bytes: &[u8], big_endian: bool) -> u64 {
+    let fold = |acc: u64, b: &u8| (acc << 8) | u64::from(*b);
+    if big_endian {
+        bytes.iter().fold(0, fold)
+    } else {
+        bytes.iter().rev().fold(0, fold)
+    }
+}
+
+/// Gather the bits of `raw` selected by `mask` into the low bits of the
+/// result (IS-CONDENSED), keeping their order.
+fn condense(raw: u64, mask: u64) -> u64 {
+    let (mut out, mut bit) = (0, 0);
+    for i in (0..64).filter(|i| mask >> i & 1 == 1) {
+        out |= (raw >> i & 1) << bit;
+        bit += 1;
+    }
+    out
+}
+
+/// Inverse of [`condense`].
+fn expand(value: u64, mask: u64) -> u64 {
+    let (mut out, mut bit) = (0, 0);
+    for i in (0..64).filter(|i| mask >> i & 1 == 1) {
+        out |= (value >> bit & 1) << i;
+        bit += 1;
+    }
+    out
+}
+
+fn numeric(raw: u64, bit_length: u32, data_type: DataType) -> Value {
+    match data_type {
+        DataType::AInt32 => {
+            let shift = 64 - bit_length.clamp(1, 64);
+            Value::Int(i64::from_ne_bytes((raw << shift).to_ne_bytes()) >> shift)
+        }
+        DataType::AFloat32 => Value::Float(f64::from(f32::from_bits(raw as u32))),
+        DataType::AFloat64 => Value::Float(f64::from_bits(raw)),
+        _ => i64::try_from(raw).map_or(Value::Float(raw as f64), Value::Int),
+    }
+}
+
+/// The `bit_length` bits a numeric internal value is coded as.
+fn raw_bits(value: &Value, bit_length: u32, data_type: DataType) -> Result<u64, Problem> {
+    let x = value
+        .as_f64()
+        .ok_or_else(|| Problem::Invalid(format!("'{value}' is not a number")))?;
+    let limit = if bit_length < 64 {
+        (1u64 << bit_length) - 1
+    } else {
+        u64::MAX
+    };
+    let raw = match data_type {
+        DataType::AFloat32 => u64::from((x as f32).to_bits()),
+        DataType::AFloat64 => x.to_bits(),
+        DataType::AInt32 => {
+            let v = match value {
+                Value::Int(v) => *v,
+                _ => x.round() as i64,
+            };
+            let half = i64::try_from(limit >> 1).unwrap_or(i64::MAX);
+            if v > half || v < -half - 1 {
+                return Err(Problem::Invalid(format!(
+                    "{v} does not fit in {bit_length} signed bits"
+                )));
+            }
+            u64::from_ne_bytes(v.to_ne_bytes()) & limit
+        }
+        _ => {
+            let v = match value {
+                Value::Int(v) => u64::try_from(*v),
+                _ => u64::try_from(x.round() as i64),
+            }
+            .map_err(|_| Problem::Invalid(format!("{value} is negative")))?;
+            if v > limit {
+                return Err(Problem::Invalid(format!(
+                    "{v} does not fit in {bit_length} bits"
+                )));
+            }
+            v
+        }
+    };
+    Ok(raw)
+}
+
+fn bytes_value(bytes: &[u8], coded_type: &DiagCodedType) -> Value {
+    match coded_type.base_data_type {
+        DataType::AAsciiString | DataType::AUtf8String => Value::Text(
+            String::from_utf8_lossy(bytes)
+                .trim_end_matches('\0')
+                .to_string(),
+        ),
+        DataType::AUnicode2String => {
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|c| {
+                    if coded_type.is_high_low_byte_order {
+                        u16::from_be_bytes([c[0], c[1]])
+                    } else {
+                        u16::from_le_bytes([c[0], c[1]])
+                    }
+                })
+                .collect();
+            Value::Text(
+                String::from_utf16_lossy(&units)
+                    .trim_end_matches('\0')
+                    .to_string(),
+            )
+        }
+        _ => Value::Bytes(bytes.to_vec()),
+    }
+}
+
+fn value_bytes(value: &Value, coded_type: &DiagCodedType) -> Result<Vec<u8>, Problem> {
+    match (coded_type.base_data_type, value) {
+        (DataType::AAsciiString | DataType::AUtf8String, Value::Text(text)) => {
+            Ok(text.as_bytes().to_vec())
+        }
+        // Physical defaults like "123" read as numbers.
+        (DataType::AAsciiString | DataType::AUtf8String, Value::Int(_) | Value::Float(_)) => {
+            Ok(value.to_string().into_bytes())
+        }
+        (DataType::AUnicode2String, Value::Text(text)) => Ok(text
+            .encode_utf16()
+            .flat_map(|unit| {
+                if coded_type.is_high_low_byte_order {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                }
+            })
+            .collect()),
+        (_, Value::Bytes(bytes)) => Ok(bytes.clone()),
+        (DataType::ABytefield, Value::Text(text)) => parse_hex_bytes(text)
+            .ok_or_else(|| Problem::Invalid(format!("'{text}' is not hex bytes"))),
+        (DataType::ABytefield, Value::Int(v)) => {
+            let bytes = v.to_be_bytes();
+            let first = bytes.iter().position(|&b| b != 0).unwrap_or(7);
+            Ok(bytes[first..].to_vec())
+        }
+        (data_type, _) => Err(Problem::Invalid(format!(
+            "'{value}' cannot be coded as {data_type:?}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DiagCodedTypeName;
+
+    fn standard(data_type: DataType, bit_length: u32, big_endian: bool) -> DiagCodedType {
+        DiagCodedType {
+            type_name: DiagCodedTypeName::StandardLengthType,
+            base_data_type: data_type,
+            is_high_low_byte_order: big_endian,
+            specific_data: Some(DiagCodedTypeData::StandardLength {
+                bit_length,
+                bit_mask: vec![],
+                condensed: false,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_standard_length_roundtrip() {
+        let cases = [
+            (standard(DataType::AUint32, 16, true), Value::Int(0x1234), 0),
+            (
+                standard(DataType::AUint32, 16, false),
+                Value::Int(0x1234),
+                0,
+            ),
+            (standard(DataType::AInt32, 12, true), Value::Int(-5), 4),
+            (standard(DataType::AFloat32, 32, true), Value::Float(1.5), 0),
+            (
+                standard(DataType::AAsciiString, 32, true),
+                Value::from("AB"),
+                0,
+            ),
+        ];
+        for (coded_type, value, bit) in cases {
+            let mut pdu = vec![0x62];
+            let end = write(&coded_type, &value, &mut pdu, 1, bit).unwrap();
+            assert_eq!(end, pdu.len());
+            let (read_back, read_end) = read(&coded_type, &pdu, 1, bit, None).unwrap();
+            assert_eq!(read_back, value, "{coded_type:?}");
+            assert_eq!(read_end, end);
+        }
+        let mut pdu = vec![];
+        write(
+            &standard(DataType::AUint32, 16, false),
+            &Value::Int(0x1234),
+            &mut pdu,
+            0,
+            0,
+        )
+        .unwrap();
+        assert_eq!(pdu, [0x34, 0x12]);
+    }
+
+    #[test]
+    fn test_condensed_bit_mask_roundtrip() {
+        let mut coded_type = standard(DataType::AUint32, 8, true);
+        coded_type.specific_data = Some(DiagCodedTypeData::StandardLength {
+            bit_length: 8,
+            bit_mask: vec![0b1010_0101],
+            condensed: true,
+        });
+        let mut pdu = vec![];
+        write(&coded_type, &Value::Int(11), &mut pdu, 0, 0).unwrap();
+        assert_eq!(pdu, [0b1000_0101]);
+        // Unmasked bits set by the ECU are ignored.
+        assert_eq!(
+            read(&coded_type, &[0xDF], 0, 0, None).unwrap().0,
+            Value::Int(11)
+        );
+
+        // Without condensing, the masked value keeps its bit positions.
+        coded_type.specific_data = Some(DiagCodedTypeData::StandardLength {
+            bit_length: 8,
+            bit_mask: vec![0b1010_0101],
+            condensed: false,
+        });
+        assert_eq!(
+            read(&coded_type, &[0xDF], 0, 0, None).unwrap().0,
+            Value::Int(0b1000_0101)
+        );
+    }
+
+    #[test]
+    fn test_value_out_of_range() {
+        let coded_type = standard(DataType::AUint32, 8, true);
+        let err = write(&coded_type, &Value::Int(256), &mut vec![], 0, 0).unwrap_err();
+        assert!(matches!(err, Problem::Invalid(_)));
+        let err = read(&coded_type, &[0x22], 1, 0, None).unwrap_err();
+        assert!(matches!(err, Problem::Truncated(2)));
+    }
+
+    #[test]
+    fn test_terminated_and_prefixed_strings() {
+        let zero_terminated = DiagCodedType {
+            base_data_type: DataType::AAsciiString,
+            specific_data: Some(DiagCodedTypeData::MinMax {
+                min_length: 1,
+                max_length: Some(8),
+                termination: Termination::Zero,
+            }),
+            ..Default::default()
+        };
+        let mut pdu = vec![];
+        let end = write(&zero_terminated, &Value::from("VIN"), &mut pdu, 0, 0).unwrap();
+        assert_eq!(pdu, b"VIN\0");
+        pdu.push(0x42);
+        assert_eq!(
+            read(&zero_terminated, &pdu, 0, 0, None).unwrap(),
+            (Value::from("VIN"), end)
+        );
+
+        let prefixed = DiagCodedType {
+            base_data_type: DataType::ABytefield,
+            is_high_low_byte_order: true,
+            specific_data: Some(DiagCodedTypeData::LeadingLength { bit_length: 8 }),
+            ..Default::default()
+        };
+        let mut pdu = vec![];
+        write(&prefixed, &Value::Bytes(vec![1, 2, 3]), &mut pdu, 0, 0).unwrap();
+        assert_eq!(pdu, [3, 1, 2, 3]);
+        assert_eq!(
+            read(&prefixed, &pdu, 0, 0, None).unwrap(),
+            (Value::Bytes(vec![1, 2, 3]), 4)
+        );
+    }
+}
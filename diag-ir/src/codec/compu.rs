@@ -0,0 +1,282 @@
+//! Compu methods: conversion between internal and physical values.
+
+use crate::types::{
+    CompuCategory, CompuMethod, CompuRationalCoEffs, CompuScale, IntervalType, Limit, PhysicalType,
+    PhysicalTypeDataType,
+};
+
+use super::Problem;
+use super::value::Value;
+
+/// The physical value of an internal one. Integral results of a scaling
+/// method stay integers when the physical type is an integer type.
+pub(crate) fn to_physical(
+    compu: Option<&CompuMethod>,
+    physical_type: Option<&PhysicalType>,
+    internal: Value,
+) -> Result<Value, Problem> {
+    let Some(cm) = compu.filter(|cm| cm.category != CompuCategory::Identical) else {
+        return Ok(internal);
+    };
+    let scales = internal_scales(cm);
+    let x = match &internal {
+        Value::Int(v) => *v as f64,
+        Value::Float(v) => *v,
+        _ => {
+            return Err(Problem::Unsupported(format!(
+                "{:?} compu method on a non-numeric value",
+                cm.category
+            )));
+        }
+    };
+    let y = match cm.category {
+        CompuCategory::Linear | CompuCategory::RatFunc => scales
+            .first()
+            .and_then(|s| s.rational_co_effs.as_ref())
+            .and_then(|c| rational(c, x)),
+        CompuCategory::ScaleLinear | CompuCategory::ScaleRatFunc => scales
+            .iter()
+            .find(|s| in_range(s, x))
+            .and_then(|s| s.rational_co_effs.as_ref())
+            .and_then(|c| rational(c, x)),
+        CompuCategory::TabIntp => interpolate(&points(scales, false), x),
+        CompuCategory::TextTable => {
+            let text = scales
+                .iter()
+                .find(|s| in_range(s, x))
+                .and_then(|s| s.consts.as_ref())
+                .or_else(|| {
+                    cm.internal_to_phys
+                        .as_ref()
+                        .and_then(|itp| itp.compu_default_value.as_ref())
+                        .and_then(|d| d.values.as_ref())
+                });
+            return text
+                .map(|c| Value::Text(c.vt.clone()))
+                .ok_or_else(|| Problem::Invalid(format!("no TEXTTABLE entry for {internal}")));
+        }
+        CompuCategory::CompuCode => {
+            return Err(Problem::Unsupported("COMPUCODE compu methods".into()));
+        }
+        CompuCategory::Identical => Some(x),
+    };
+    let y = y.ok_or_else(|| {
+        Problem::Invalid(format!(
+            "{:?} compu method does not cover {internal}",
+            cm.category
+        ))
+    })?;
+    Ok(number(y, physical_type))
+}
+
+/// The internal value of a physical one.
+pub(crate) fn to_internal(compu: Option<&CompuMethod>, physical: &Value) -> Result<Value, Problem> {
+    let Some(cm) = compu.filter(|cm| cm.category != CompuCategory::Identical) else {
+        return Ok(physical.clone());
+    };
+    let scales = internal_scales(cm);
+    if cm.category == CompuCategory::TextTable {
+        return match physical {
+            Value::Text(text) => scales
+                .iter()
+                .find(|s| s.consts.as_ref().is_some_and(|c| c.vt == *text))
+                .and_then(|s| s.lower_limit.as_ref())
+                .map(|limit| Value::parse(&limit.value))
+                .ok_or_else(|| Problem::Invalid(format!("no TEXTTABLE entry '{text}'"))),
+            // A number is taken as the internal value itself.
+            other => Ok(other.clone()),
+        };
+    }
+    let y = physical
+        .as_f64()
+        .ok_or_else(|| Problem::Invalid(format!("'{physical}' is not a number")))?;
+    let x = match cm.category {
+        CompuCategory::Linear => scales
+            .first()
+            .and_then(|s| s.rational_co_effs.as_ref())
+            .and_then(|c| inverse_linear(c, y)),
+        CompuCategory::ScaleLinear => scales.iter().find_map(|s| {
+            let x = inverse_linear(s.rational_co_effs.as_ref()?, y)?;
+            in_range(s, x).then_some(x)
+        }),
+        CompuCategory::TabIntp => interpolate(&points(scales, true), y),
+        category => {
+            return Err(Problem::Unsupported(format!(
+                "{category:?} compu methods cannot be inverted"
+            )));
+        }
+    };
+    x.map(Value::Float).ok_or_else(|| {
+        Problem::Invalid(format!(
+            "{physical} is outside the {:?} compu method",
+            cm.category
+        ))
+    })
+}
+
+fn internal_scales(cm: &CompuMethod) -> &[CompuScale] {
+    cm.internal_to_phys
+        .as_ref()
+        .map_or(&[][..], |itp| itp.compu_scales.as_slice())
+}
+
+fn number(y: f64, physical_type: Option<&PhysicalType>) -> Value {
+    let integral = physical_type.is_some_and(|pt| {
+        matches!(
+            pt.base_data_type,
+            PhysicalTypeDataType::AInt32 | PhysicalTypeDataType::AUint32
+        )
+    });
+    if integral && y.fract() == 0.0 && y.abs() < 9.0e15 {
+        Value::Int(y as i64)
+    } else {
+        Value::Float(y)
+    }
+}
+
+/// `(n0 + n1 x + n2 x^2 ...) / (d0 + d1 x + ...)`; `None` for a zero
+/// denominator.
+fn rational(coeffs: &CompuRationalCoEffs, x: f64) -> Option<f64> {
+    let poly = |c: &[f64]| c.iter().rev().fold(0.0, |acc, k| acc * x + k);
+    let denominator = if coeffs.denominator.is_empty() {
+        1.0
+    } else {
+        poly(&coeffs.denominator)
+    };
+    (denominator != 0.0).then(|| poly(&coeffs.numerator) / denominator)
+}
+
+/// Inverse of a linear [`rational`] function with a constant denominator.
+fn inverse_linear(coeffs: &CompuRationalCoEffs, y: f64) -> Option<f64> {
+    if coeffs.numerator.len() > 2 || coeffs.denominator.len() > 1 {
+        return None;
+    }
+    let offset = coeffs.numerator.first().copied().unwrap_or(0.0);
+    let factor = coeffs.numerator.get(1).copied().unwrap_or(0.0);
+    let denominator = coeffs.denominator.first().copied().unwrap_or(1.0);
+    (factor != 0.0).then(|| (y * denominator - offset) / factor)
+}
+
+/// The `(internal, physical)` points of a TAB-INTP method, or the
+/// `(physical, internal)` points when `inverse`.
+fn points(scales: &[CompuScale], inverse: bool) -> Vec<(f64, f64)> {
+    scales
+        .iter()
+        .filter_map(|s| {
+            let x = s.lower_limit.as_ref()?.value.trim().parse().ok()?;
+            let y = s.consts.as_ref()?.v?;
+            Some(if inverse { (y, x) } else { (x, y) })
+        })
+        .collect()
+}
+
+fn interpolate(points: &[(f64, f64)], x: f64) -> Option<f64> {
+    points.windows(2).find_map(|w| {
+        let ((x0, y0), (x1, y1)) = (w[0], w[1]);
+        let (lo, hi) = if x0 <= x1 { (x0, x1) } else { (x1, x0) };
+        if x < lo || x > hi {
+            return None;
+        }
+        let dx = x1 - x0;
+        if dx == 0.0 {
+            return Some(y0);
+        }
+        Some(y0 + (x - x0) * (y1 - y0) / dx)
+    })
+}
+
+fn in_range(scale: &CompuScale, x: f64) -> bool {
+    let check = |limit: Option<&Limit>, lower: bool| {
+        let Some(limit) = limit else { return true };
+        if limit.interval_type == IntervalType::Infinite {
+            return true;
+        }
+        let Some(bound) = Value::parse(&limit.value).as_f64() else {
+            return true;
+        };
+        match (lower, limit.interval_type) {
+            (true, IntervalType::Open) => x > bound,
+            (true, _) => x >= bound,
+            (false, IntervalType::Open) => x < bound,
+            (false, _) => x <= bound,
+        }
+    };
+    // A scale with only a lower limit matches that single value.
+    let upper = scale.upper_limit.as_ref().or(scale.lower_limit.as_ref());
+    check(scale.lower_limit.as_ref(), true) && check(upper, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{CompuInternalToPhys, CompuValues, Radix};
+
+    fn method(category: CompuCategory, compu_scales: Vec<CompuScale>) -> CompuMethod {
+        CompuMethod {
+            category,
+            internal_to_phys: Some(CompuInternalToPhys {
+                compu_scales,
+                prog_code: None,
+                compu_default_value: None,
+            }),
+            phys_to_internal: None,
+        }
+    }
+
+    fn scale(lower: Option<&str>, vt: &str, numerator: Vec<f64>) -> CompuScale {
+        CompuScale {
+            short_label: None,
+            lower_limit: lower.map(|value| Limit {
+                value: value.into(),
+                interval_type: IntervalType::Closed,
+            }),
+            upper_limit: None,
+            inverse_values: None,
+            consts: (!vt.is_empty()).then(|| CompuValues {
+                v: None,
+                vt: vt.into(),
+                vt_ti: String::new(),
+            }),
+            rational_co_effs: (!numerator.is_empty()).then(|| CompuRationalCoEffs {
+                numerator,
+                denominator: vec![10.0],
+            }),
+        }
+    }
+
+    #[test]
+    fn test_linear_roundtrip() {
+        // phys = (-400 + x) / 10
+        let cm = method(
+            CompuCategory::Linear,
+            vec![scale(None, "", vec![-400.0, 1.0])],
+        );
+        let phys = to_physical(Some(&cm), None, Value::Int(650)).unwrap();
+        assert_eq!(phys, Value::Float(25.0));
+        assert_eq!(to_internal(Some(&cm), &phys).unwrap(), Value::Float(650.0));
+
+        let integer = PhysicalType {
+            precision: None,
+            base_data_type: PhysicalTypeDataType::AInt32,
+            display_radix: Radix::Dec,
+        };
+        let phys = to_physical(Some(&cm), Some(&integer), Value::Int(650)).unwrap();
+        assert_eq!(phys, Value::Int(25));
+    }
+
+    #[test]
+    fn test_text_table_roundtrip() {
+        let cm = method(
+            CompuCategory::TextTable,
+            vec![
+                scale(Some("0"), "Off", vec![]),
+                scale(Some("1"), "On", vec![]),
+            ],
+        );
+        let phys = to_physical(Some(&cm), None, Value::Int(1)).unwrap();
+        assert_eq!(phys, Value::from("On"));
+        assert_eq!(to_internal(Some(&cm), &phys).unwrap(), Value::Int(1));
+        assert!(to_physical(Some(&cm), None, Value::Int(2)).is_err());
+        assert!(to_internal(Some(&cm), &Value::from("Standby")).is_err());
+    }
+}
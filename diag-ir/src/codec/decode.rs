@@ -0,0 +1,766 @@
+//! Decoding messages into named physical values.
+
+use std::collections::HashMap;
+
+use crate::flash::service_sid;
+use crate::types::{DiagDatabase, DiagService, Dop, DopData, Field, Param, ParamData};
+
+use super::coding;
+use super::compu::to_physical;
+use super::value::{NamedValue, Value};
+use super::{Problem, RuntimeError, hex};
+
+/// Which message of a service a payload is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Request,
+    PositiveResponse,
+    NegativeResponse,
+}
+
+/// A decoded positive or negative response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub positive: bool,
+    pub params: Vec<NamedValue>,
+}
+
+/// A payload decoded as a message of a database service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message<'a> {
+    pub service: &'a DiagService,
+    pub kind: MessageKind,
+    pub params: Vec<NamedValue>,
+}
+
+/// Decode `pdu` as a request of `service`.
+pub fn decode_request(service: &DiagService, pdu: &[u8]) -> Result<Vec<NamedValue>, RuntimeError> {
+    let request = service
+        .request
+        .as_ref()
+        .ok_or_else(|| RuntimeError::NoRequest(service.diag_comm.short_name.to_string()))?;
+    decode_params(&request.params, pdu)
+}
+
+/// Decode `pdu` as a response of `service`: a negative response if it starts
+/// with 0x7F, else a positive one. Of several responses, the first whose
+/// coded constants match `pdu` is used.
+pub fn decode_response(service: &DiagService, pdu: &[u8]) -> Result<Response, RuntimeError> {
+    let positive = pdu.first() != Some(&0x7F);
+    let responses = if positive {
+        &service.pos_responses
+    } else {
+        &service.neg_responses
+    };
+    let response = responses
+        .iter()
+        .find(|r| const_bytes_matched(&r.params, pdu).is_some())
+        .or(responses.first())
+        .ok_or_else(|| RuntimeError::UnknownMessage(hex(pdu)))?;
+    Ok(Response {
+        positive,
+        params: decode_params(&response.params, pdu)?,
+    })
+}
+
+/// Decode `pdu` as a request or positive response of a service of `db`:
+/// the one whose byte-aligned coded constants match the most bytes of it,
+/// preferring base variants on a tie. A negative response names only the
+/// rejected SID, so it is decoded with the first service of that SID.
+pub fn decode_message<'a>(db: &'a DiagDatabase, pdu: &[u8]) -> Result<Message<'a>, RuntimeError> {
+    let mut variants: Vec<_> = db.variants.iter().collect();
+    variants.sort_by_key(|v| !v.is_base_variant);
+    let services: Vec<&DiagService> = variants
+        .into_iter()
+        .map(|v| &v.diag_layer)
+        .chain(db.functional_groups.iter().map(|fg| &fg.diag_layer))
+        .flat_map(|layer| &layer.diag_services)
+        .collect();
+    let unknown = || RuntimeError::UnknownMessage(hex(pdu));
+
+    if let [0x7F, sid, ..] = pdu {
+        let service = services
+            .iter()
+            .find(|s| service_sid(s) == Some(*sid))
+            .ok_or_else(unknown)?;
+        let response = decode_response(service, pdu)?;
+        return Ok(Message {
+            service,
+            kind: MessageKind::NegativeResponse,
+            params: response.params,
+        });
+    }
+
+    let mut best: Option<(usize, &DiagService, MessageKind, &[Param])> = None;
+    for service in services {
+        let request = service
+            .request
+            .iter()
+            .map(|r| (MessageKind::Request, r.params.as_slice()));
+        let responses = service
+            .pos_responses
+            .iter()
+            .map(|r| (MessageKind::PositiveResponse, r.params.as_slice()));
+        for (kind, params) in request.chain(responses) {
+            if let Some(matched) = const_bytes_matched(params, pdu) {
+                if matched > 0 && best.is_none_or(|(most, ..)| matched > most) {
+                    best = Some((matched, service, kind, params));
+                }
+            }
+        }
+    }
+    let (_, service, kind, params) = best.ok_or_else(unknown)?;
+    Ok(Message {
+        service,
+        kind,
+        params: decode_params(params, pdu)?,
+    })
+}
+
+/// Decode the params of a request or response from `pdu`. Coded constants
+/// are included with their coded value; reserved params are skipped.
+pub fn decode_params(params: &[Param], pdu: &[u8]) -> Result<Vec<NamedValue>, RuntimeError> {
+    let decoder = Decoder { pdu };
+    decoder.structure(params, 0, "").map(|(values, _)| values)
+}
+
+/// How many bytes the byte-aligned CODED-CONST params of `params` cover,
+/// or `None` if one of them does not match `pdu`.
+fn const_bytes_matched(params: &[Param], pdu: &[u8]) -> Option<usize> {
+    let mut matched = 0;
+    for param in params {
+        let Some(ParamData::CodedConst {
+            coded_value,
+            diag_coded_type,
+        }) = &param.specific_data
+        else {
+            continue;
+        };
+        let Some(byte) = param.byte_position else {
+            continue;
+        };
+        let (value, end) = coding::read(
+            diag_coded_type,
+            pdu,
+            byte as usize,
+            param.bit_position.unwrap_or(0),
+            None,
+        )
+        .ok()?;
+        if value != coding::parse_coded(coded_value, diag_coded_type) {
+            return None;
+        }
+        matched += end - byte as usize;
+    }
+    Some(matched)
+}
+
+struct Decoder<'p> {
+    pdu: &'p [u8],
+}
+
+impl Decoder<'_> {
+    fn error(&self, path: &str) -> impl Fn(Problem) -> RuntimeError + use<> {
+        let path = path.to_string();
+        let len = self.pdu.len();
+        move |problem| problem.at(&path, len)
+    }
+
+    /// Decode the params of a structure starting at `base`. Returns their
+    /// values and the position after the last one.
+    fn structure(
+        &self,
+        params: &[Param],
+        base: usize,
+        path: &str,
+    ) -> Result<(Vec<NamedValue>, usize), RuntimeError> {
+        let mut values = Vec::new();
+        let mut lengths: HashMap<&str, u64> = HashMap::new();
+        let (mut cursor, mut end) = (base, base);
+        for param in params {
+            let path = join(path, &param.short_name);
+            let byte = param.byte_position.map_or(cursor, |p| base + p as usize);
+            let bit = param.bit_position.unwrap_or(0);
+            let err = self.error(&path);
+            let (value, next) = match &param.specific_data {
+                Some(
+                    ParamData::CodedConst {
+                        diag_coded_type, ..
+                    }
+                    | ParamData::NrcConst {
+                        diag_coded_type, ..
+                    },
+                ) => {
+                    let (value, next) =
+                        coding::read(diag_coded_type, self.pdu, byte, bit, None).map_err(err)?;
+                    (Some(value), next)
+                }
+                Some(ParamData::Reserved { bit_length }) => {
+                    (None, byte + (bit + bit_length).div_ceil(8) as usize)
+                }
+                Some(ParamData::MatchingRequestParam { byte_length, .. }) => {
+                    let next = byte + *byte_length as usize;
+                    let bytes = self
+                        .pdu
+                        .get(byte..next)
+                        .ok_or(Problem::Truncated(next))
+                        .map_err(err)?;
+                    (Some(Value::Bytes(bytes.to_vec())), next)
+                }
+                Some(
+                    ParamData::Value { dop, .. }
+                    | ParamData::PhysConst { dop, .. }
+                    | ParamData::System { dop, .. }
+                    | ParamData::LengthKeyRef { dop },
+                ) => {
+                    let (value, next) = self.dop(dop, byte, bit, &path, &lengths)?;
+                    (Some(value), next)
+                }
+                _ => {
+                    return Err(RuntimeError::Unsupported {
+                        param: path,
+                        reason: "table, dynamic and empty params cannot be decoded".into(),
+                    });
+                }
+            };
+            if let (Some(ParamData::LengthKeyRef { .. }), Some(Value::Int(bits))) =
+                (&param.specific_data, &value)
+            {
+                if let Ok(bits) = u64::try_from(*bits) {
+                    lengths.insert(&param.short_name, bits);
+                }
+            }
+            if let Some(value) = value {
+                values.push(NamedValue {
+                    name: param.short_name.to_string(),
+                    value,
+                });
+            }
+            cursor = next;
+            end = end.max(next);
+        }
+        Ok((values, end))
+    }
+
+    /// Decode a value of `dop` at `byte`/`bit`. Returns it and the position
+    /// after it.
+    fn dop(
+        &self,
+        dop: &Dop,
+        byte: usize,
+        bit: u32,
+        path: &str,
+        lengths: &HashMap<&str, u64>,
+    ) -> Result<(Value, usize), RuntimeError> {
+        let err = self.error(path);
+        let unsupported = |reason: &str| RuntimeError::Unsupported {
+            param: path.to_string(),
+            reason: format!("DOP {}: {reason}", dop.short_name),
+        };
+        match &dop.specific_data {
+            Some(DopData::NormalDop {
+                diag_coded_type: Some(coded_type),
+                compu_method,
+                physical_type,
+                ..
+            }) => {
+                let param_length = match &coded_type.specific_data {
+                    Some(crate::types::DiagCodedTypeData::ParamLength { length_key }) => {
+                        lengths.get(length_key.short_name.as_str()).copied()
+                    }
+                    _ => None,
+                };
+                let (internal, next) =
+                    coding::read(coded_type, self.pdu, byte, bit, param_length).map_err(&err)?;
+                let value = to_physical(compu_method.as_ref(), physical_type.as_ref(), internal)
+                    .map_err(&err)?;
+                Ok((value, next))
+            }
+            Some(DopData::DtcDop {
+                diag_coded_type: Some(coded_type),
+                compu_method,
+                physical_type,
+                ..
+            }) => {
+                let (internal, next) =
+                    coding::read(coded_type, self.pdu, byte, bit, None).map_err(&err)?;
+                let value = to_physical(compu_method.as_ref(), physical_type.as_ref(), internal)
+                    .map_err(&err)?;
+                Ok((value, next))
+            }
+            Some(DopData::Structure {
+                params, byte_size, ..
+            }) => {
+                let (values, end) = self.structure(params, byte, path)?;
+                let end = byte_size.map_or(end, |size| byte + size as usize);
+                Ok((Value::Struct(values), end))
+            }
+            Some(DopData::StaticField {
+                fixed_number_of_items,
+                item_byte_size,
+                field,
+            }) => {
+                let item = item_structure(field.as_ref()).ok_or_else(|| unsupported("no item"))?;
+                let mut items = Vec::new();
+                for i in 0..*fixed_number_of_items as usize {
+                    let at = byte + i * *item_byte_size as usize;
+                    items.push(self.dop(item, at, 0, &format!("{path}[{i}]"), lengths)?.0);
+                }
+                let end = byte + (*fixed_number_of_items * *item_byte_size) as usize;
+                Ok((Value::Array(items), end))
+            }
+            Some(DopData::EndOfPduField {
+                max_number_of_items,
+                min_number_of_items,
+                field,
+            }) => {
+                let item = item_structure(field.as_ref()).ok_or_else(|| unsupported("no item"))?;
+                let mut items = Vec::new();
+                let mut at = byte;
+                while at < self.pdu.len()
+                    && max_number_of_items.is_none_or(|max| items.len() < max as usize)
+                {
+                    let path = format!("{path}[{}]", items.len());
+                    let (value, next) = self.dop(item, at, 0, &path, lengths)?;
+                    items.push(value);
+                    if next <= at {
+                        break;
+                    }
+                    at = next;
+                }
+                if let Some(min) = min_number_of_items {
+                    if items.len() < *min as usize {
+                        return Err(RuntimeError::InvalidValue {
+                            param: path.to_string(),
+                            reason: format!("{} items, at least {min} expected", items.len()),
+                        });
+                    }
+                }
+                Ok((Value::Array(items), at))
+            }
+            Some(DopData::DynamicLengthField {
+                offset,
+                field,
+                determine_number_of_items: Some(count),
+            }) => {
+                let item = item_structure(field.as_ref()).ok_or_else(|| unsupported("no item"))?;
+                let (n, _) = self.dop(
+                    &count.dop,
+                    byte + count.byte_position as usize,
+                    count.bit_position,
+                    path,
+                    lengths,
+                )?;
+                let n = match n {
+                    Value::Int(n) => usize::try_from(n).ok(),
+                    _ => None,
+                }
+                .ok_or_else(|| unsupported("the number of items is not a count"))?;
+                let mut items = Vec::new();
+                let mut at = byte + *offset as usize;
+                for i in 0..n {
+                    let (value, next) = self.dop(item, at, 0, &format!("{path}[{i}]"), lengths)?;
+                    items.push(value);
+                    at = next;
+                }
+                Ok((Value::Array(items), at))
+            }
+            Some(DopData::MuxDop {
+                byte_position,
+                switch_key: Some(key),
+                default_case,
+                cases,
+                ..
+            }) => {
+                let (key_value, key_end) = self.dop(
+                    &key.dop,
+                    byte + key.byte_position as usize,
+                    key.bit_position.unwrap_or(0),
+                    path,
+                    lengths,
+                )?;
+                let selected = cases
+                    .iter()
+                    .find(|case| case_matches(case, &key_value))
+                    .map(|case| (&case.short_name, case.structure.as_deref()))
+                    .or_else(|| {
+                        default_case
+                            .as_ref()
+                            .map(|d| (&d.short_name, d.structure.as_deref()))
+                    });
+                let Some((case, structure)) = selected else {
+                    return Err(RuntimeError::InvalidValue {
+                        param: path.to_string(),
+                        reason: format!("no case of {} for switch key {key_value}", dop.short_name),
+                    });
+                };
+                let at = byte + *byte_position as usize;
+                let (params, end) = match structure {
+                    Some(structure) => {
+                        let (value, end) =
+                            self.dop(structure, at, 0, &join(path, case), lengths)?;
+                        match value {
+                            Value::Struct(params) => (params, end),
+                            other => (vec![NamedValue::new(case.as_str(), other)], end),
+                        }
+                    }
+                    None => (vec![], at),
+                };
+                Ok((
+                    Value::Mux {
+                        case: case.to_string(),
+                        params,
+                    },
+                    end.max(key_end),
+                ))
+            }
+            Some(DopData::EnvDataDesc { .. } | DopData::EnvData { .. }) => {
+                Err(unsupported("environment data cannot be decoded"))
+            }
+            _ => Err(unsupported("incomplete DOP")),
+        }
+    }
+}
+
+/// The structure of one item of a field.
+pub(crate) fn item_structure(field: Option<&Field>) -> Option<&Dop> {
+    field?.basic_structure.as_deref()
+}
+
+/// Whether the switch key value falls in the limits of `case`.
+fn case_matches(case: &crate::types::Case, key: &Value) -> bool {
+    let bound = |limit: Option<&crate::types::Limit>| limit.map(|l| Value::parse(&l.value));
+    let lower = bound(case.lower_limit.as_ref());
+    let upper = bound(case.upper_limit.as_ref()).or_else(|| lower.clone());
+    match key.as_f64() {
+        Some(x) => {
+            lower
+                .as_ref()
+                .and_then(Value::as_f64)
+                .is_none_or(|l| x >= l)
+                && upper
+                    .as_ref()
+                    .and_then(Value::as_f64)
+                    .is_none_or(|u| x <= u)
+        }
+        None => lower.is_some_and(|l| l.to_string().trim() == key.to_string()),
+    }
+}
+
+pub(crate) fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::encode::encode_params;
+    use crate::test_support;
+    use crate::types::{
+        Case, CompuCategory, CompuInternalToPhys, CompuMethod, CompuRationalCoEffs, CompuScale,
+        DataType, DiagCodedType, DiagCodedTypeData, DiagCodedTypeName, DiagComm, DiagLayer, DopRef,
+        DopType, IntervalType, Limit, ParamType, Request, ResponseType, SwitchKey, Variant,
+    };
+
+    fn uint(bit_length: u32) -> DiagCodedType {
+        DiagCodedType {
+            type_name: DiagCodedTypeName::StandardLengthType,
+            base_data_type: DataType::AUint32,
+            is_high_low_byte_order: true,
+            specific_data: Some(DiagCodedTypeData::StandardLength {
+                bit_length,
+                bit_mask: vec![],
+                condensed: false,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn coded_const(name: &str, byte: u32, value: &str, bits: u32) -> Param {
+        Param {
+            short_name: name.into(),
+            byte_position: Some(byte),
+            specific_data: Some(ParamData::CodedConst {
+                coded_value: value.into(),
+                diag_coded_type: uint(bits),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn dop(name: &str, dop_type: DopType, data: DopData) -> Dop {
+        Dop {
+            dop_type,
+            specific_data: Some(data),
+            ..test_support::dop(name)
+        }
+    }
+
+    fn normal(name: &str, bits: u32, compu_method: Option<CompuMethod>) -> Dop {
+        dop(
+            name,
+            DopType::Regular,
+            DopData::NormalDop {
+                compu_method,
+                diag_coded_type: Some(uint(bits)),
+                physical_type: None,
+                internal_constr: None,
+                unit_ref: None,
+                phys_constr: None,
+            },
+        )
+    }
+
+    fn structure(name: &str, params: Vec<Param>) -> Dop {
+        dop(
+            name,
+            DopType::Structure,
+            DopData::Structure {
+                params,
+                byte_size: None,
+                is_visible: true,
+            },
+        )
+    }
+
+    fn value(name: &str, byte: Option<u32>, dop: Dop) -> Param {
+        Param {
+            param_type: ParamType::Value,
+            short_name: name.into(),
+            byte_position: byte,
+            specific_data: Some(ParamData::Value {
+                physical_default_value: String::new(),
+                dop: DopRef::new(dop),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// phys = x - 40
+    fn celsius() -> CompuMethod {
+        CompuMethod {
+            category: CompuCategory::Linear,
+            internal_to_phys: Some(CompuInternalToPhys {
+                compu_scales: vec![CompuScale {
+                    short_label: None,
+                    lower_limit: None,
+                    upper_limit: None,
+                    inverse_values: None,
+                    consts: None,
+                    rational_co_effs: Some(CompuRationalCoEffs {
+                        numerator: vec![-40.0, 1.0],
+                        denominator: vec![1.0],
+                    }),
+                }],
+                prog_code: None,
+                compu_default_value: None,
+            }),
+            phys_to_internal: None,
+        }
+    }
+
+    fn temperature_response() -> Vec<Param> {
+        vec![
+            coded_const("SID", 0, "0x62", 8),
+            coded_const("DID", 1, "0x0100", 16),
+            value("Temp", Some(3), normal("Temp", 8, Some(celsius()))),
+        ]
+    }
+
+    fn roundtrip(params: &[Param], pdu: &[u8]) -> Vec<NamedValue> {
+        let values = decode_params(params, pdu).unwrap();
+        assert_eq!(encode_params(params, &values).unwrap(), pdu);
+        values
+    }
+
+    #[test]
+    fn test_scaled_value() {
+        let values = roundtrip(&temperature_response(), &[0x62, 0x01, 0x00, 0x41]);
+        assert_eq!(
+            values,
+            [
+                NamedValue::new("SID", 0x62_i64),
+                NamedValue::new("DID", 0x100_i64),
+                NamedValue::new("Temp", 25.0),
+            ]
+        );
+        let encoded =
+            encode_params(&temperature_response(), &[NamedValue::new("Temp", -40.0)]).unwrap();
+        assert_eq!(encoded, [0x62, 0x01, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_truncated_message() {
+        let err = decode_params(&temperature_response(), &[0x62, 0x01, 0x00]).unwrap_err();
+        match err {
+            RuntimeError::Truncated { param, needed, len } => {
+                assert_eq!((param.as_str(), needed, len), ("Temp", 4, 3));
+            }
+            other => panic!("expected a truncated message, got {other}"),
+        }
+    }
+
+    #[test]
+    fn test_end_of_pdu_field_of_structures() {
+        let record = structure(
+            "DtcRecord",
+            vec![
+                value("Dtc", Some(0), normal("Dtc", 24, None)),
+                value("Status", Some(3), normal("Status", 8, None)),
+            ],
+        );
+        let field = dop(
+            "Dtcs",
+            DopType::EndOfPduField,
+            DopData::EndOfPduField {
+                max_number_of_items: None,
+                min_number_of_items: None,
+                field: Some(Field {
+                    basic_structure: Some(DopRef::new(record)),
+                    env_data_desc: None,
+                    is_visible: true,
+                }),
+            },
+        );
+        let params = vec![
+            coded_const("SID", 0, "0x59", 8),
+            coded_const("ReportType", 1, "0x02", 8),
+            value("Mask", Some(2), normal("Mask", 8, None)),
+            value("Records", Some(3), field),
+        ];
+        let pdu = [
+            0x59, 0x02, 0xFF, 0x12, 0x34, 0x56, 0x08, 0xAB, 0xCD, 0xEF, 0x09,
+        ];
+        let values = roundtrip(&params, &pdu);
+        let Some(Value::Array(records)) = crate::codec::value::find(&values, "Records") else {
+            panic!("expected records, got {values:?}");
+        };
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].get("Dtc"), Some(&Value::Int(0xABCDEF)));
+        assert_eq!(records[1].get("Status"), Some(&Value::Int(9)));
+    }
+
+    #[test]
+    fn test_multiplexer() {
+        let case = |name: &str, key: &str, params| Case {
+            short_name: name.into(),
+            long_name: None,
+            structure: Some(DopRef::new(structure(name, params))),
+            lower_limit: Some(Limit {
+                value: key.into(),
+                interval_type: IntervalType::Closed,
+            }),
+            upper_limit: None,
+        };
+        let mux = dop(
+            "Result",
+            DopType::Mux,
+            DopData::MuxDop {
+                byte_position: 1,
+                switch_key: Some(SwitchKey {
+                    byte_position: 0,
+                    bit_position: None,
+                    dop: DopRef::new(normal("Kind", 8, None)),
+                }),
+                default_case: None,
+                cases: vec![
+                    case(
+                        "Narrow",
+                        "1",
+                        vec![value("A", Some(0), normal("A", 8, None))],
+                    ),
+                    case(
+                        "Wide",
+                        "2",
+                        vec![value("B", Some(0), normal("B", 16, None))],
+                    ),
+                ],
+                is_visible: true,
+            },
+        );
+        let params = vec![
+            coded_const("SID", 0, "0x71", 8),
+            value("Result", Some(1), mux),
+        ];
+        let values = roundtrip(&params, &[0x71, 0x02, 0x12, 0x34]);
+        assert_eq!(
+            values[1].value,
+            Value::Mux {
+                case: "Wide".into(),
+                params: vec![NamedValue::new("B", 0x1234_i64)],
+            }
+        );
+        let narrow = Value::Mux {
+            case: "Narrow".into(),
+            params: vec![NamedValue::new("A", 7_i64)],
+        };
+        let encoded = encode_params(&params, &[NamedValue::new("Result", narrow)]).unwrap();
+        assert_eq!(encoded, [0x71, 0x01, 0x07]);
+    }
+
+    #[test]
+    fn test_decode_message_finds_the_service() {
+        let service = |name: &str, request: Vec<Param>, response: Vec<Param>| DiagService {
+            diag_comm: DiagComm {
+                short_name: name.into(),
+                ..Default::default()
+            },
+            request: Some(Request {
+                params: request,
+                sdgs: None,
+            }),
+            pos_responses: vec![crate::types::Response {
+                response_type: ResponseType::PosResponse,
+                params: response,
+                sdgs: None,
+                pos_response_suffix: None,
+                audience: None,
+            }],
+            ..Default::default()
+        };
+        let db = DiagDatabase {
+            variants: vec![Variant {
+                diag_layer: DiagLayer {
+                    short_name: "Base".into(),
+                    diag_services: vec![
+                        service(
+                            "ReadDataByIdentifier",
+                            vec![coded_const("SID", 0, "0x22", 8)],
+                            vec![coded_const("SID", 0, "0x62", 8)],
+                        ),
+                        service(
+                            "Temperature_Read",
+                            vec![
+                                coded_const("SID", 0, "0x22", 8),
+                                coded_const("DID", 1, "0x0100", 16),
+                            ],
+                            temperature_response(),
+                        ),
+                    ],
+                    ..Default::default()
+                },
+                is_base_variant: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let message = decode_message(&db, &[0x62, 0x01, 0x00, 0x41]).unwrap();
+        assert_eq!(message.service.diag_comm.short_name, "Temperature_Read");
+        assert_eq!(message.kind, MessageKind::PositiveResponse);
+        assert_eq!(message.params[2], NamedValue::new("Temp", 25.0));
+
+        let message = decode_message(&db, &[0x22, 0x01, 0x00]).unwrap();
+        assert_eq!(message.service.diag_comm.short_name, "Temperature_Read");
+        assert_eq!(message.kind, MessageKind::Request);
+
+        assert!(matches!(
+            decode_message(&db, &[0x31, 0x01]),
+            Err(RuntimeError::UnknownMessage(_))
+        ));
+    }
+}
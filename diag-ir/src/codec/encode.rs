@@ -0,0 +1,372 @@
+//! Encoding physical values into messages.
+
+use std::collections::HashMap;
+
+use crate::types::{DiagCodedTypeData, DiagService, Dop, DopData, Param, ParamData};
+
+use super::coding;
+use super::compu::to_internal;
+use super::decode::{item_structure, join};
+use super::value::{NamedValue, Value, find};
+use super::{Problem, RuntimeError};
+
+/// Encode a request of `service` from physical values given by param short
+/// name, see [`encode_params`].
+pub fn encode_request(
+    service: &DiagService,
+    values: &[NamedValue],
+) -> Result<Vec<u8>, RuntimeError> {
+    let request = service
+        .request
+        .as_ref()
+        .ok_or_else(|| RuntimeError::NoRequest(service.diag_comm.short_name.to_string()))?;
+    encode_params(&request.params, values)
+}
+
+/// Encode params from physical values given by short name. Coded and
+/// physical constants take their constant, VALUE params without a value
+/// their physical default, and length keys without a value the length of
+/// the param they give the length of. Structures, fields and multiplexers
+/// take a [`Value::Struct`], [`Value::Array`] and [`Value::Mux`].
+pub fn encode_params(params: &[Param], values: &[NamedValue]) -> Result<Vec<u8>, RuntimeError> {
+    let mut encoder = Encoder { pdu: Vec::new() };
+    encoder.structure(params, values, 0, "")?;
+    Ok(encoder.pdu)
+}
+
+struct Encoder {
+    pdu: Vec<u8>,
+}
+
+impl Encoder {
+    fn error(&self, path: &str) -> impl Fn(Problem) -> RuntimeError + use<> {
+        let path = path.to_string();
+        let len = self.pdu.len();
+        move |problem| problem.at(&path, len)
+    }
+
+    fn reserve(&mut self, len: usize) {
+        if self.pdu.len() < len {
+            self.pdu.resize(len, 0);
+        }
+    }
+
+    /// Encode the params of a structure starting at `base`. Returns the
+    /// position after the last one.
+    fn structure(
+        &mut self,
+        params: &[Param],
+        values: &[NamedValue],
+        base: usize,
+        path: &str,
+    ) -> Result<usize, RuntimeError> {
+        let lengths = param_lengths(params, values, path)?;
+        let (mut cursor, mut end) = (base, base);
+        for param in params {
+            let path = join(path, &param.short_name);
+            let byte = param.byte_position.map_or(cursor, |p| base + p as usize);
+            let bit = param.bit_position.unwrap_or(0);
+            let given = find(values, &param.short_name);
+            let missing = || RuntimeError::MissingValue {
+                param: path.clone(),
+            };
+            let err = self.error(&path);
+            let next = match &param.specific_data {
+                Some(ParamData::CodedConst {
+                    coded_value,
+                    diag_coded_type,
+                }) => {
+                    let value = coding::parse_coded(coded_value, diag_coded_type);
+                    coding::write(diag_coded_type, &value, &mut self.pdu, byte, bit).map_err(err)?
+                }
+                Some(ParamData::NrcConst {
+                    coded_values,
+                    diag_coded_type,
+                }) => {
+                    let value = match given {
+                        Some(value) => value.clone(),
+                        None => coded_values
+                            .first()
+                            .map(|v| coding::parse_coded(v, diag_coded_type))
+                            .ok_or_else(missing)?,
+                    };
+                    coding::write(diag_coded_type, &value, &mut self.pdu, byte, bit).map_err(err)?
+                }
+                Some(ParamData::Reserved { bit_length }) => {
+                    let next = byte + (bit + bit_length).div_ceil(8) as usize;
+                    self.reserve(next);
+                    next
+                }
+                Some(ParamData::MatchingRequestParam { byte_length, .. }) => {
+                    let next = byte + *byte_length as usize;
+                    self.reserve(next);
+                    if let Some(value) = given {
+                        let Value::Bytes(bytes) = value else {
+                            return Err(Problem::Invalid(format!("'{value}' is not bytes"))
+                                .at(&path, self.pdu.len()));
+                        };
+                        let n = bytes.len().min(*byte_length as usize);
+                        self.pdu[byte..byte + n].copy_from_slice(&bytes[..n]);
+                    }
+                    next
+                }
+                Some(ParamData::Value {
+                    physical_default_value,
+                    dop,
+                }) => {
+                    let default = (!physical_default_value.is_empty())
+                        .then(|| Value::parse(physical_default_value));
+                    let value = given.cloned().or(default).ok_or_else(missing)?;
+                    self.dop(dop, &value, byte, bit, &path)?
+                }
+                Some(ParamData::PhysConst {
+                    phys_constant_value,
+                    dop,
+                }) => self.dop(dop, &Value::parse(phys_constant_value), byte, bit, &path)?,
+                Some(ParamData::System { dop, .. }) => {
+                    let value = given.ok_or_else(missing)?;
+                    self.dop(dop, value, byte, bit, &path)?
+                }
+                Some(ParamData::LengthKeyRef { dop }) => {
+                    let value = given
+                        .cloned()
+                        .or_else(|| {
+                            let bits = lengths.get(param.short_name.as_str())?;
+                            i64::try_from(*bits).ok().map(Value::Int)
+                        })
+                        .ok_or_else(missing)?;
+                    self.dop(dop, &value, byte, bit, &path)?
+                }
+                _ => {
+                    return Err(RuntimeError::Unsupported {
+                        param: path,
+                        reason: "table, dynamic and empty params cannot be encoded".into(),
+                    });
+                }
+            };
+            cursor = next;
+            end = end.max(next);
+        }
+        Ok(end)
+    }
+
+    /// Encode `value` as `dop` at `byte`/`bit`. Returns the position after
+    /// it.
+    fn dop(
+        &mut self,
+        dop: &Dop,
+        value: &Value,
+        byte: usize,
+        bit: u32,
+        path: &str,
+    ) -> Result<usize, RuntimeError> {
+        let err = self.error(path);
+        let unsupported = |reason: &str| RuntimeError::Unsupported {
+            param: path.to_string(),
+            reason: format!("DOP {}: {reason}", dop.short_name),
+        };
+        let invalid = |reason: String| RuntimeError::InvalidValue {
+            param: path.to_string(),
+            reason,
+        };
+        match &dop.specific_data {
+            Some(
+                DopData::NormalDop {
+                    diag_coded_type: Some(coded_type),
+                    compu_method,
+                    ..
+                }
+                | DopData::DtcDop {
+                    diag_coded_type: Some(coded_type),
+                    compu_method,
+                    ..
+                },
+            ) => {
+                let internal = to_internal(compu_method.as_ref(), value).map_err(&err)?;
+                coding::write(coded_type, &internal, &mut self.pdu, byte, bit).map_err(&err)
+            }
+            Some(DopData::Structure { .. }) => {
+                let Value::Struct(values) = value else {
+                    return Err(invalid(format!("'{value}' is not a structure")));
+                };
+                self.structure_dop(dop, values, byte, path)
+            }
+            Some(DopData::StaticField {
+                fixed_number_of_items,
+                item_byte_size,
+                field,
+            }) => {
+                let item = item_structure(field.as_ref()).ok_or_else(|| unsupported("no item"))?;
+                let items = as_items(value).map_err(&invalid)?;
+                if items.len() != *fixed_number_of_items as usize {
+                    return Err(invalid(format!(
+                        "{} items, {fixed_number_of_items} expected",
+                        items.len()
+                    )));
+                }
+                for (i, value) in items.iter().enumerate() {
+                    let at = byte + i * *item_byte_size as usize;
+                    self.dop(item, value, at, 0, &format!("{path}[{i}]"))?;
+                }
+                let end = byte + (*fixed_number_of_items * *item_byte_size) as usize;
+                self.reserve(end);
+                Ok(end)
+            }
+            Some(DopData::EndOfPduField {
+                max_number_of_items,
+                min_number_of_items,
+                field,
+            }) => {
+                let item = item_structure(field.as_ref()).ok_or_else(|| unsupported("no item"))?;
+                let items = as_items(value).map_err(&invalid)?;
+                let n = items.len();
+                if min_number_of_items.is_some_and(|min| n < min as usize)
+                    || max_number_of_items.is_some_and(|max| n > max as usize)
+                {
+                    return Err(invalid(format!("{n} items are outside the allowed count")));
+                }
+                self.items(item, items, byte, path)
+            }
+            Some(DopData::DynamicLengthField {
+                offset,
+                field,
+                determine_number_of_items: Some(count),
+            }) => {
+                let item = item_structure(field.as_ref()).ok_or_else(|| unsupported("no item"))?;
+                let items = as_items(value).map_err(&invalid)?;
+                let n = i64::try_from(items.len()).map_err(|e| invalid(e.to_string()))?;
+                self.dop(
+                    &count.dop,
+                    &Value::Int(n),
+                    byte + count.byte_position as usize,
+                    count.bit_position,
+                    path,
+                )?;
+                self.items(item, items, byte + *offset as usize, path)
+            }
+            Some(DopData::MuxDop {
+                byte_position,
+                switch_key: Some(key),
+                default_case,
+                cases,
+                ..
+            }) => {
+                let Value::Mux { case, params } = value else {
+                    return Err(invalid(format!("'{value}' is not a multiplexer case")));
+                };
+                let structure = match cases.iter().find(|c| c.short_name == *case) {
+                    Some(selected) => {
+                        let key_value = selected
+                            .lower_limit
+                            .as_ref()
+                            .map(|l| Value::parse(&l.value))
+                            .ok_or_else(|| invalid(format!("case {case} has no limit")))?;
+                        self.dop(
+                            &key.dop,
+                            &key_value,
+                            byte + key.byte_position as usize,
+                            key.bit_position.unwrap_or(0),
+                            path,
+                        )?;
+                        selected.structure.as_deref()
+                    }
+                    None if default_case.as_ref().is_some_and(|d| d.short_name == *case) => {
+                        return Err(unsupported(
+                            "the default case has no switch key value to encode",
+                        ));
+                    }
+                    None => return Err(invalid(format!("{} has no case {case}", dop.short_name))),
+                };
+                let at = byte + *byte_position as usize;
+                match structure {
+                    Some(structure) => self.structure_dop(structure, params, at, &join(path, case)),
+                    None => Ok(at),
+                }
+            }
+            Some(DopData::EnvDataDesc { .. } | DopData::EnvData { .. }) => {
+                Err(unsupported("environment data cannot be encoded"))
+            }
+            _ => Err(unsupported("incomplete DOP")),
+        }
+    }
+
+    /// Encode the params of a STRUCTURE DOP at `byte`.
+    fn structure_dop(
+        &mut self,
+        dop: &Dop,
+        values: &[NamedValue],
+        byte: usize,
+        path: &str,
+    ) -> Result<usize, RuntimeError> {
+        let Some(DopData::Structure {
+            params, byte_size, ..
+        }) = &dop.specific_data
+        else {
+            return Err(RuntimeError::Unsupported {
+                param: path.to_string(),
+                reason: format!("DOP {} is not a structure", dop.short_name),
+            });
+        };
+        let end = self.structure(params, values, byte, path)?;
+        let end = byte_size.map_or(end, |size| byte + size as usize);
+        self.reserve(end);
+        Ok(end)
+    }
+
+    /// Encode field items one after the other from `byte`.
+    fn items(
+        &mut self,
+        item: &Dop,
+        items: &[Value],
+        byte: usize,
+        path: &str,
+    ) -> Result<usize, RuntimeError> {
+        let mut at = byte;
+        for (i, value) in items.iter().enumerate() {
+            at = self.dop(item, value, at, 0, &format!("{path}[{i}]"))?;
+        }
+        self.reserve(at);
+        Ok(at)
+    }
+}
+
+fn as_items(value: &Value) -> Result<&[Value], String> {
+    match value {
+        Value::Array(items) => Ok(items),
+        other => Err(format!("'{other}' is not a list of items")),
+    }
+}
+
+/// The bit lengths of the PARAM-LENGTH-INFO values given among `values`, by
+/// the short name of their length key.
+fn param_lengths<'p>(
+    params: &'p [Param],
+    values: &[NamedValue],
+    path: &str,
+) -> Result<HashMap<&'p str, u64>, RuntimeError> {
+    let mut lengths = HashMap::new();
+    for param in params {
+        let Some(ParamData::Value { dop, .. }) = &param.specific_data else {
+            continue;
+        };
+        let Some(DopData::NormalDop {
+            diag_coded_type: Some(coded_type),
+            compu_method,
+            ..
+        }) = &dop.specific_data
+        else {
+            continue;
+        };
+        let Some(DiagCodedTypeData::ParamLength { length_key }) = &coded_type.specific_data else {
+            continue;
+        };
+        let Some(value) = find(values, &param.short_name) else {
+            continue;
+        };
+        let path = join(path, &param.short_name);
+        let internal = to_internal(compu_method.as_ref(), value).map_err(|p| p.at(&path, 0))?;
+        let bits = coding::param_length(&internal, coded_type).map_err(|p| p.at(&path, 0))?;
+        lengths.insert(length_key.short_name.as_str(), bits);
+    }
+    Ok(lengths)
+}
@@ -0,0 +1,76 @@
+//! Runtime codec for UDS messages described by the IR.
+//!
+//! [`decode_request`], [`decode_response`] and [`decode_message`] turn a raw
+//! payload into named parameters with physical values, applying the DOPs and
+//! compu methods of the IR; [`encode_request`] and [`encode_params`] go the
+//! other way. Structures, static, dynamic-length and END-OF-PDU fields and
+//! multiplexers nest: their values are [`Value::Struct`], [`Value::Array`]
+//! and [`Value::Mux`]. Table params and environment data are not supported.
+//!
+//! It lives in diag-ir so that [`variant_detect`](crate::variant_detect)
+//! can decode out-params with it; the `diag-runtime` crate re-exports it for
+//! testers.
+
+pub(crate) mod coding;
+mod compu;
+pub mod decode;
+pub mod encode;
+pub mod value;
+
+pub use decode::{
+    Message, MessageKind, Response, decode_message, decode_params, decode_request, decode_response,
+};
+pub use encode::{encode_params, encode_request};
+pub use value::{NamedValue, Value};
+
+/// Errors raised while decoding or encoding a message. `param` is the path of
+/// the parameter, with the short names of enclosing structures joined by
+/// `.`.
+#[derive(Debug, thiserror::Error)]
+pub enum RuntimeError {
+    #[error("{param}: message ends after {len} bytes, needs {needed}")]
+    Truncated {
+        param: String,
+        needed: usize,
+        len: usize,
+    },
+    #[error("{param}: {reason}")]
+    Unsupported { param: String, reason: String },
+    #[error("{param}: {reason}")]
+    InvalidValue { param: String, reason: String },
+    #[error("{param}: no value given")]
+    MissingValue { param: String },
+    #[error("service {0} has no request")]
+    NoRequest(String),
+    #[error("no service matches message {0}")]
+    UnknownMessage(String),
+}
+
+/// A problem with one coded value, before it is tied to a parameter.
+#[derive(Debug)]
+pub(crate) enum Problem {
+    /// The message must be at least this many bytes long.
+    Truncated(usize),
+    Unsupported(String),
+    Invalid(String),
+}
+
+impl Problem {
+    pub(crate) fn at(self, param: &str, len: usize) -> RuntimeError {
+        let param = param.to_string();
+        match self {
+            Problem::Truncated(needed) => RuntimeError::Truncated { param, needed, len },
+            Problem::Unsupported(reason) => RuntimeError::Unsupported { param, reason },
+            Problem::Invalid(reason) => RuntimeError::InvalidValue { param, reason },
+        }
+    }
+}
+
+/// Hex bytes separated by spaces, as messages are usually written.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,139 @@
+//! Physical parameter values.
+
+use std::fmt;
+
+/// The physical value of a parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+    /// The params of a structure, in message order.
+    Struct(Vec<NamedValue>),
+    /// The items of a field, each a [`Value::Struct`].
+    Array(Vec<Value>),
+    /// The case a multiplexer selected and the params of its structure.
+    Mux {
+        case: String,
+        params: Vec<NamedValue>,
+    },
+}
+
+/// A parameter's short name and value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedValue {
+    pub name: String,
+    pub value: Value,
+}
+
+impl NamedValue {
+    pub fn new(name: impl Into<String>, value: impl Into<Value>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
+
+impl Value {
+    /// Read a value written as text: an integer (decimal or `0x` hex), a
+    /// float, or else the text itself. Physical defaults and constants of
+    /// the IR are stored this way.
+    pub fn parse(s: &str) -> Value {
+        let t = s.trim();
+        if let Ok(v) = t.parse::<i64>() {
+            return Value::Int(v);
+        }
+        if let Some(v) = crate::numbers::parse_uint(t).and_then(|v| i64::try_from(v).ok()) {
+            return Value::Int(v);
+        }
+        match t.parse::<f64>() {
+            Ok(v) if !t.is_empty() => Value::Float(v),
+            _ => Value::Text(s.to_string()),
+        }
+    }
+
+    /// The value as a number, if it is one.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int(v) => Some(*v as f64),
+            Value::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// The value of the param `name` of a structure or multiplexer case.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Struct(params) | Value::Mux { params, .. } => find(params, name),
+            _ => None,
+        }
+    }
+}
+
+/// The value of the param `name` among `values`.
+pub fn find<'a>(values: &'a [NamedValue], name: &str) -> Option<&'a Value> {
+    values.iter().find(|v| v.name == name).map(|v| &v.value)
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::Int(v)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Float(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Text(v.to_string())
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(v: Vec<u8>) -> Self {
+        Value::Bytes(v)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(v) => write!(f, "{v}"),
+            Value::Float(v) => write!(f, "{v}"),
+            Value::Text(v) => write!(f, "{v}"),
+            Value::Bytes(v) => f.write_str(&super::hex(v)),
+            Value::Struct(params) => write_params(f, params),
+            Value::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_str("]")
+            }
+            Value::Mux { case, params } => {
+                write!(f, "{case} ")?;
+                write_params(f, params)
+            }
+        }
+    }
+}
+
+fn write_params(f: &mut fmt::Formatter<'_>, params: &[NamedValue]) -> fmt::Result {
+    f.write_str("{")?;
+    for (i, param) in params.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{} = {}", param.name, param.value)?;
+    }
+    f.write_str("}")
+}
@@ -0,0 +1,258 @@
+//! Parameter defaults taken from communication parameters.
+//!
+//! A `physical_default_value` of the form `${comparam:NAME}` is replaced at
+//! convert time with the value the ECU's ComParamRefs configure for `NAME`,
+//! so generated services carry e.g. the actual tester address instead of an
+//! empty default.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::types::*;
+
+const PREFIX: &str = "${comparam:";
+
+/// Build the `${comparam:NAME}` reference stored in a default value.
+pub fn comparam_reference(name: &str) -> String {
+    format!("{PREFIX}{name}}}")
+}
+
+/// The comparam name referenced by a default value, if it is a reference.
+pub fn parse_comparam_reference(value: &str) -> Option<&str> {
+    value.trim().strip_prefix(PREFIX)?.strip_suffix('}')
+}
+
+/// A default that references a comparam no ComParamRef configures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedDefault {
+    /// `<layer>/<service or job>/<param>`
+    pub location: String,
+    pub com_param: String,
+}
+
+impl fmt::Display for UnresolvedDefault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: default references unknown comparam {}",
+            self.location, self.com_param
+        )
+    }
+}
+
+/// Replace every `${comparam:NAME}` default in the database with the
+/// configured comparam value. A layer sees its own ComParamRefs first, then
+/// those of the base variants and protocols. Unresolvable references are left
+/// untouched and returned.
+pub fn resolve_comparam_defaults(db: &mut DiagDatabase) -> Vec<UnresolvedDefault> {
+    let shared = db
+        .variants
+        .iter()
+        .filter(|v| v.is_base_variant)
+        .map(|v| &v.diag_layer)
+        .chain(db.protocols.iter().map(|p| &p.diag_layer))
+        .fold(BTreeMap::new(), |mut values, layer| {
+            collect_values(layer, &mut values);
+            values
+        });
+
+    let mut unresolved = Vec::new();
+    let layers = db
+        .variants
+        .iter_mut()
+        .map(|v| &mut v.diag_layer)
+        .chain(db.functional_groups.iter_mut().map(|fg| &mut fg.diag_layer))
+        .chain(db.protocols.iter_mut().map(|p| &mut p.diag_layer))
+        .chain(db.ecu_shared_datas.iter_mut().map(|e| &mut e.diag_layer));
+    for layer in layers {
+        let mut values = BTreeMap::new();
+        collect_values(layer, &mut values);
+        for (name, value) in &shared {
+            values.entry(name.clone()).or_insert_with(|| value.clone());
+        }
+        resolve_layer(layer, &values, &mut unresolved);
+    }
+    unresolved
+}
+
+/// First configured value per comparam: the ref's own value, else the
+/// comparam's default.
+fn collect_values(layer: &DiagLayer, values: &mut BTreeMap<String, String>) {
+    for cp_ref in &layer.com_param_refs {
+        let Some(cp) = &cp_ref.com_param else {
+            continue;
+        };
+        let value = cp_ref
+            .simple_value
+            .as_ref()
+            .map(|v| v.value.clone())
+            .or_else(|| match &cp.specific_data {
+                Some(ComParamSpecificData::Regular {
+                    physical_default_value,
+                    ..
+                }) => Some(physical_default_value.clone()),
+                _ => None,
+            });
+        if let Some(value) = value {
+            values.entry(cp.short_name.to_string()).or_insert(value);
+        }
+    }
+}
+
+fn resolve_layer(
+    layer: &mut DiagLayer,
+    values: &BTreeMap<String, String>,
+    unresolved: &mut Vec<UnresolvedDefault>,
+) {
+    let mut resolve = |location: String, value: &mut String| {
+        let Some(name) = parse_comparam_reference(value) else {
+            return;
+        };
+        match values.get(name) {
+            Some(v) => *value = v.clone(),
+            None => unresolved.push(UnresolvedDefault {
+                location,
+                com_param: name.to_string(),
+            }),
+        }
+    };
+
+    let layer_name = &layer.short_name;
+    for svc in &mut layer.diag_services {
+        let svc_name = &svc.diag_comm.short_name;
+        let params = svc
+            .request
+            .iter_mut()
+            .flat_map(|r| &mut r.params)
+            .chain(svc.pos_responses.iter_mut().flat_map(|r| &mut r.params))
+            .chain(svc.neg_responses.iter_mut().flat_map(|r| &mut r.params));
+        for param in params {
+            let location = format!("{layer_name}/{svc_name}/{}", param.short_name);
+            resolve(location.clone(), &mut param.physical_default_value);
+            if let Some(ParamData::Value {
+                physical_default_value,
+                ..
+            }) = &mut param.specific_data
+            {
+                resolve(location, physical_default_value);
+            }
+        }
+    }
+    for job in &mut layer.single_ecu_jobs {
+        let job_name = &job.diag_comm.short_name;
+        let params = job
+            .input_params
+            .iter_mut()
+            .chain(&mut job.output_params)
+            .chain(&mut job.neg_output_params);
+        for param in params {
+            let location = format!("{layer_name}/{job_name}/{}", param.short_name);
+            resolve(location, &mut param.physical_default_value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comparam_ref(name: &str, value: &str) -> ComParamRef {
+        ComParamRef {
+            simple_value: Some(SimpleValue {
+                value: value.into(),
+            }),
+            complex_value: None,
+            com_param: Some(Box::new(ComParam {
+                com_param_type: ComParamType::Regular,
+                short_name: name.into(),
+                long_name: None,
+                param_class: String::new(),
+                cp_type: ComParamStandardisationLevel::Standard,
+                display_level: None,
+                cp_usage: ComParamUsage::EcuComm,
+                specific_data: None,
+            })),
+            protocol: None,
+            prot_stack: None,
+        }
+    }
+
+    fn job_param(name: &str, default: &str) -> JobParam {
+        JobParam {
+            short_name: name.into(),
+            long_name: None,
+            physical_default_value: default.into(),
+            dop_base: None,
+            semantic: Name::default(),
+        }
+    }
+
+    #[test]
+    fn test_reference_syntax() {
+        let r = comparam_reference("CP_TesterAddress");
+        assert_eq!(r, "${comparam:CP_TesterAddress}");
+        assert_eq!(parse_comparam_reference(&r), Some("CP_TesterAddress"));
+        assert_eq!(parse_comparam_reference("0x0E80"), None);
+    }
+
+    #[test]
+    fn test_resolves_from_own_layer_then_base_variant() {
+        let job = SingleEcuJob {
+            diag_comm: DiagComm {
+                short_name: "Flash".into(),
+                ..Default::default()
+            },
+            prog_codes: vec![],
+            input_params: vec![
+                job_param("Tester", "${comparam:CP_TesterAddress}"),
+                job_param("Timeout", "${comparam:CP_P2Max}"),
+                job_param("Plain", "7"),
+                job_param("Missing", "${comparam:CP_Unknown}"),
+            ],
+            output_params: vec![],
+            neg_output_params: vec![],
+        };
+        let mut db = DiagDatabase {
+            variants: vec![
+                Variant {
+                    diag_layer: DiagLayer {
+                        short_name: "Base".into(),
+                        com_param_refs: vec![
+                            comparam_ref("CP_TesterAddress", "0x0E80"),
+                            comparam_ref("CP_P2Max", "50"),
+                        ],
+                        ..Default::default()
+                    },
+                    is_base_variant: true,
+                    ..Default::default()
+                },
+                Variant {
+                    diag_layer: DiagLayer {
+                        short_name: "App".into(),
+                        com_param_refs: vec![comparam_ref("CP_P2Max", "100")],
+                        single_ecu_jobs: vec![job],
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let unresolved = resolve_comparam_defaults(&mut db);
+
+        let defaults: Vec<&str> = db.variants[1].diag_layer.single_ecu_jobs[0]
+            .input_params
+            .iter()
+            .map(|p| p.physical_default_value.as_str())
+            .collect();
+        assert_eq!(defaults, ["0x0E80", "100", "7", "${comparam:CP_Unknown}"]);
+        assert_eq!(
+            unresolved,
+            [UnresolvedDefault {
+                location: "App/Flash/Missing".into(),
+                com_param: "CP_Unknown".into(),
+            }]
+        );
+    }
+}
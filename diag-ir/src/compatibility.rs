@@ -0,0 +1,104 @@
+//! ECU hardware and software part numbers a database applies to.
+//!
+//! Stored as a `compatibility` SDG on the base variant with one SD per part
+//! number (SI `hardware` or `software`), so the list travels through YAML,
+//! ODX and MDD. MDD output also records it in the container header.
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{DiagDatabase, Sd, SdOrSdg, Sdg, Sdgs};
+
+/// SDG caption under which the part numbers are stored.
+pub const COMPATIBILITY_CAPTION: &str = "compatibility";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Compatibility {
+    /// Compatible ECU hardware part numbers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hardware: Vec<String>,
+    /// Compatible ECU software part numbers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub software: Vec<String>,
+}
+
+impl Compatibility {
+    pub fn is_empty(&self) -> bool {
+        self.hardware.is_empty() && self.software.is_empty()
+    }
+}
+
+/// Read the part numbers stored in `sdgs`, if any.
+pub fn compatibility_of(sdgs: Option<&Sdgs>) -> Option<Compatibility> {
+    let sdg = sdgs?
+        .sdgs
+        .iter()
+        .find(|sdg| sdg.caption_sn == COMPATIBILITY_CAPTION)?;
+    let mut compatibility = Compatibility::default();
+    for sd in &sdg.sds {
+        match sd {
+            SdOrSdg::Sd(sd) if sd.si == "hardware" => compatibility.hardware.push(sd.value.clone()),
+            SdOrSdg::Sd(sd) if sd.si == "software" => compatibility.software.push(sd.value.clone()),
+            _ => {}
+        }
+    }
+    Some(compatibility)
+}
+
+/// The part numbers of `db`, stored on its base variant.
+pub fn compatibility(db: &DiagDatabase) -> Option<Compatibility> {
+    let base = db.variants.iter().find(|v| v.is_base_variant)?;
+    compatibility_of(base.diag_layer.sdgs.as_ref()).filter(|c| !c.is_empty())
+}
+
+/// Store `compatibility` in `sdgs`, replacing any previous list.
+pub fn set_compatibility(sdgs: &mut Option<Sdgs>, compatibility: &Compatibility) {
+    let sd = |si: &str, value: &String| {
+        SdOrSdg::Sd(Sd {
+            value: value.clone(),
+            si: si.to_string(),
+            ti: String::new(),
+        })
+    };
+    let sds = compatibility
+        .hardware
+        .iter()
+        .map(|hw| sd("hardware", hw))
+        .chain(compatibility.software.iter().map(|sw| sd("software", sw)))
+        .collect();
+    let sdgs = &mut sdgs.get_or_insert_with(|| Sdgs { sdgs: vec![] }).sdgs;
+    sdgs.retain(|sdg| sdg.caption_sn != COMPATIBILITY_CAPTION);
+    sdgs.push(Sdg {
+        caption_sn: COMPATIBILITY_CAPTION.into(),
+        sds,
+        si: String::new(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Variant;
+
+    #[test]
+    fn test_compatibility_roundtrip_through_sdgs() {
+        let mut db = DiagDatabase {
+            variants: vec![Variant {
+                is_base_variant: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert_eq!(compatibility(&db), None);
+
+        let list = Compatibility {
+            hardware: vec!["HW-1".into(), "HW-2".into()],
+            software: vec!["SW-1".into()],
+        };
+        let sdgs = &mut db.variants[0].diag_layer.sdgs;
+        set_compatibility(sdgs, &Compatibility::default());
+        set_compatibility(sdgs, &list);
+        assert_eq!(sdgs.as_ref().unwrap().sdgs.len(), 1);
+        assert_eq!(compatibility(&db), Some(list));
+    }
+}
@@ -0,0 +1,352 @@
+//! Complexity budgets: limits on the shape of a database for testers with
+//! little memory, e.g. RTOS-based ones that allocate decode buffers per
+//! response and recurse per structure level.
+
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::types::{DiagDatabase, DiagLayer, Dop, DopData, Field, Param, ParamData};
+
+/// Limits checked by [`check_complexity`]. A limit left out is not checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ComplexityPolicy {
+    /// Diag services defined by one layer (inherited ones not counted).
+    #[serde(default)]
+    pub max_services_per_variant: Option<usize>,
+    /// Top-level params of one positive or negative response.
+    #[serde(default)]
+    pub max_params_per_response: Option<usize>,
+    /// Structures nested in one another within a request or response.
+    #[serde(default)]
+    pub max_structure_depth: Option<usize>,
+    /// `MAX-NUMBER-OF-ITEMS` of an end-of-PDU field. A field without a
+    /// maximum violates any limit.
+    #[serde(default)]
+    pub max_end_of_pdu_items: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ComplexityRule {
+    ServicesPerVariant,
+    ParamsPerResponse,
+    StructureDepth,
+    EndOfPduItems,
+}
+
+/// A value over one of the policy's limits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComplexityViolation {
+    pub rule: ComplexityRule,
+    /// `Layer`, `Layer/Service request`, `Layer/Service positive response`,
+    /// with a param path for end-of-PDU fields.
+    pub location: String,
+    /// The measured value; `None` for an end-of-PDU field without maximum.
+    pub actual: Option<usize>,
+    pub limit: usize,
+}
+
+impl fmt::Display for ComplexityViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let actual = self
+            .actual
+            .map_or_else(|| "unbounded".to_string(), |n| n.to_string());
+        let what = match self.rule {
+            ComplexityRule::ServicesPerVariant => "services",
+            ComplexityRule::ParamsPerResponse => "params",
+            ComplexityRule::StructureDepth => "levels of nested structures",
+            ComplexityRule::EndOfPduItems => "end-of-PDU items",
+        };
+        write!(
+            f,
+            "{}: {actual} {what}, limit is {}",
+            self.location, self.limit
+        )
+    }
+}
+
+/// Everything in `db` that exceeds a limit of `policy`.
+pub fn check_complexity(db: &DiagDatabase, policy: &ComplexityPolicy) -> Vec<ComplexityViolation> {
+    let layers = db
+        .variants
+        .iter()
+        .map(|v| &v.diag_layer)
+        .chain(db.functional_groups.iter().map(|fg| &fg.diag_layer))
+        .chain(db.protocols.iter().map(|p| &p.diag_layer))
+        .chain(db.ecu_shared_datas.iter().map(|e| &e.diag_layer));
+    let mut violations = Vec::new();
+    for layer in layers {
+        check_layer(layer, policy, &mut violations);
+    }
+    violations
+}
+
+fn check_layer(layer: &DiagLayer, policy: &ComplexityPolicy, out: &mut Vec<ComplexityViolation>) {
+    if let Some(limit) = policy.max_services_per_variant {
+        let count = layer.diag_services.len();
+        if count > limit {
+            out.push(ComplexityViolation {
+                rule: ComplexityRule::ServicesPerVariant,
+                location: layer.short_name.to_string(),
+                actual: Some(count),
+                limit,
+            });
+        }
+    }
+
+    for svc in &layer.diag_services {
+        let service = format!("{}/{}", layer.short_name, svc.diag_comm.short_name);
+        let mut messages: Vec<(String, &[Param], bool)> = Vec::new();
+        if let Some(request) = &svc.request {
+            messages.push((format!("{service} request"), &request.params, false));
+        }
+        for (kind, responses) in [
+            ("positive", &svc.pos_responses),
+            ("negative", &svc.neg_responses),
+        ] {
+            for (i, response) in responses.iter().enumerate() {
+                let label = if responses.len() > 1 {
+                    format!("{service} {kind} response {}", i + 1)
+                } else {
+                    format!("{service} {kind} response")
+                };
+                messages.push((label, &response.params, true));
+            }
+        }
+
+        for (location, params, is_response) in messages {
+            if let Some(limit) = policy.max_params_per_response.filter(|_| is_response) {
+                if params.len() > limit {
+                    out.push(ComplexityViolation {
+                        rule: ComplexityRule::ParamsPerResponse,
+                        location: location.clone(),
+                        actual: Some(params.len()),
+                        limit,
+                    });
+                }
+            }
+            if let Some(limit) = policy.max_structure_depth {
+                let depth = params.iter().map(param_depth).max().unwrap_or(0);
+                if depth > limit {
+                    out.push(ComplexityViolation {
+                        rule: ComplexityRule::StructureDepth,
+                        location: location.clone(),
+                        actual: Some(depth),
+                        limit,
+                    });
+                }
+            }
+            if let Some(limit) = policy.max_end_of_pdu_items {
+                let mut fields = Vec::new();
+                for param in params {
+                    end_of_pdu_fields(param, &param.short_name, &mut fields);
+                }
+                for (path, max) in fields {
+                    let actual = max.and_then(|n| usize::try_from(n).ok());
+                    if actual.is_none_or(|n| n > limit) {
+                        out.push(ComplexityViolation {
+                            rule: ComplexityRule::EndOfPduItems,
+                            location: format!("{location} param {path}"),
+                            actual,
+                            limit,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The DOP a param is decoded with, if it has one.
+fn param_dop(param: &Param) -> Option<&Dop> {
+    match param.specific_data.as_ref()? {
+        ParamData::Value { dop, .. }
+        | ParamData::PhysConst { dop, .. }
+        | ParamData::System { dop, .. }
+        | ParamData::LengthKeyRef { dop } => Some(dop),
+        _ => None,
+    }
+}
+
+/// DOPs nested directly in `dop`, with the short name of the param they
+/// belong to for structures and env data.
+fn children(dop: &Dop) -> Vec<(Option<&str>, &Dop)> {
+    fn field_dops(field: Option<&Field>) -> Vec<(Option<&str>, &Dop)> {
+        field
+            .iter()
+            .flat_map(|f| f.basic_structure.iter().chain(&f.env_data_desc))
+            .map(|d| (None, &**d))
+            .collect()
+    }
+    fn param_dops(params: &[Param]) -> Vec<(Option<&str>, &Dop)> {
+        params
+            .iter()
+            .filter_map(|p| param_dop(p).map(|d| (Some(p.short_name.as_str()), d)))
+            .collect()
+    }
+    match &dop.specific_data {
+        Some(DopData::Structure { params, .. } | DopData::EnvData { params, .. }) => {
+            param_dops(params)
+        }
+        Some(
+            DopData::EndOfPduField { field, .. }
+            | DopData::StaticField { field, .. }
+            | DopData::DynamicLengthField { field, .. },
+        ) => field_dops(field.as_ref()),
+        Some(DopData::MuxDop {
+            default_case,
+            cases,
+            ..
+        }) => default_case
+            .iter()
+            .filter_map(|c| c.structure.as_deref())
+            .chain(cases.iter().filter_map(|c| c.structure.as_deref()))
+            .map(|d| (None, d))
+            .collect(),
+        Some(DopData::EnvDataDesc { env_datas, .. }) => {
+            env_datas.iter().map(|d| (None, d)).collect()
+        }
+        _ => vec![],
+    }
+}
+
+fn param_depth(param: &Param) -> usize {
+    param_dop(param).map_or(0, dop_depth)
+}
+
+/// Structures nested in one another below and including `dop`.
+fn dop_depth(dop: &Dop) -> usize {
+    let own = usize::from(matches!(dop.specific_data, Some(DopData::Structure { .. })));
+    own + children(dop)
+        .into_iter()
+        .map(|(_, d)| dop_depth(d))
+        .max()
+        .unwrap_or(0)
+}
+
+/// End-of-PDU fields reachable from `param`, by param path, with their
+/// `MAX-NUMBER-OF-ITEMS`.
+fn end_of_pdu_fields(param: &Param, path: &str, out: &mut Vec<(String, Option<u32>)>) {
+    if let Some(dop) = param_dop(param) {
+        collect_end_of_pdu(dop, path, out);
+    }
+}
+
+fn collect_end_of_pdu(dop: &Dop, path: &str, out: &mut Vec<(String, Option<u32>)>) {
+    if let Some(DopData::EndOfPduField {
+        max_number_of_items,
+        ..
+    }) = &dop.specific_data
+    {
+        out.push((path.to_string(), *max_number_of_items));
+    }
+    for (name, child) in children(dop) {
+        match name {
+            Some(name) => collect_end_of_pdu(child, &format!("{path}/{name}"), out),
+            None => collect_end_of_pdu(child, path, out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, service, variant};
+    use crate::types::{DiagService, DopRef, Name, ParamType, Response, ResponseType};
+
+    fn param(name: &str, dop: Dop) -> Param {
+        Param {
+            id: 0,
+            param_type: ParamType::Value,
+            short_name: name.into(),
+            semantic: Name::default(),
+            sdgs: None,
+            physical_default_value: String::new(),
+            byte_position: None,
+            bit_position: None,
+            specific_data: Some(ParamData::Value {
+                physical_default_value: String::new(),
+                dop: DopRef::new(dop),
+            }),
+        }
+    }
+
+    fn dop(name: &str, data: DopData) -> Dop {
+        Dop {
+            specific_data: Some(data),
+            ..test_support::dop(name)
+        }
+    }
+
+    fn structure(params: Vec<Param>) -> Dop {
+        dop(
+            "S",
+            DopData::Structure {
+                params,
+                byte_size: None,
+                is_visible: true,
+            },
+        )
+    }
+
+    fn database(response_params: Vec<Param>) -> DiagDatabase {
+        let response = Response {
+            response_type: ResponseType::PosResponse,
+            params: response_params,
+            sdgs: None,
+            pos_response_suffix: None,
+            audience: None,
+        };
+        let services = ["A", "B", "C"].map(|name| DiagService {
+            pos_responses: vec![response.clone()],
+            ..service(name, "")
+        });
+        DiagDatabase {
+            variants: vec![variant("Base", services.to_vec())],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check_complexity() {
+        let items = dop(
+            "Items",
+            DopData::EndOfPduField {
+                max_number_of_items: None,
+                min_number_of_items: None,
+                field: Some(Field {
+                    basic_structure: Some(DopRef::new(structure(vec![]))),
+                    env_data_desc: None,
+                    is_visible: true,
+                }),
+            },
+        );
+        let nested = structure(vec![param("Inner", structure(vec![param("List", items)]))]);
+        let db = database(vec![
+            param("Record", nested),
+            param("Tail", structure(vec![])),
+        ]);
+        let policy = ComplexityPolicy {
+            max_services_per_variant: Some(2),
+            max_params_per_response: Some(1),
+            max_structure_depth: Some(2),
+            max_end_of_pdu_items: Some(100),
+        };
+        let violations: Vec<String> = check_complexity(&db, &policy)
+            .iter()
+            .filter(|v| v.location == "Base" || v.location.starts_with("Base/A "))
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            violations,
+            [
+                "Base: 3 services, limit is 2",
+                "Base/A positive response: 2 params, limit is 1",
+                "Base/A positive response: 3 levels of nested structures, limit is 2",
+                "Base/A positive response param Record/Inner/List: unbounded end-of-PDU items, limit is 100",
+            ]
+        );
+        assert!(check_complexity(&db, &ComplexityPolicy::default()).is_empty());
+    }
+}
@@ -0,0 +1,471 @@
+//! Consistency lint for physical constraints (PHYS-CONSTR).
+//!
+//! A DOP's physical constraint should lie inside the physical range its
+//! values can actually take: the internal constraint, and the range of the
+//! coded type, mapped through the compu method. Constraints that reach beyond
+//! it promise values the ECU can never send or accept.
+//!
+//! [`param_constraints`] lists both constraints per parameter for
+//! documentation output.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::numbers::parse_number;
+use crate::types::*;
+
+/// A physical constraint that conflicts with the rest of its DOP.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintWarning {
+    pub dop: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConstraintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DOP '{}': {}", self.dop, self.message)
+    }
+}
+
+/// Interval notation: `[0, 100]`, `(0, 100]`, `[0, inf)`.
+impl fmt::Display for InternalConstr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bound = |limit: &Option<Limit>, open: char, closed: char, inf: &str| match limit {
+            Some(l) if l.interval_type != IntervalType::Infinite => {
+                let bracket = if l.interval_type == IntervalType::Open {
+                    open
+                } else {
+                    closed
+                };
+                (bracket, l.value.clone())
+            }
+            _ => (open, inf.to_string()),
+        };
+        let (lb, lower) = bound(&self.lower_limit, '(', '[', "-inf");
+        let (ub, upper) = bound(&self.upper_limit, ')', ']', "inf");
+        write!(f, "{lb}{lower}, {upper}{ub}")
+    }
+}
+
+/// Check the physical constraint of every DOP used by a service parameter.
+/// Each DOP is reported once, under its short name.
+pub fn check_phys_constraints(db: &DiagDatabase) -> Vec<ConstraintWarning> {
+    let mut dops = BTreeMap::new();
+    for variant in &db.variants {
+        for service in &variant.diag_layer.diag_services {
+            let params = service
+                .request
+                .iter()
+                .flat_map(|r| &r.params)
+                .chain(service.pos_responses.iter().flat_map(|r| &r.params))
+                .chain(service.neg_responses.iter().flat_map(|r| &r.params));
+            for param in params {
+                collect_dops(param, &mut dops);
+            }
+        }
+    }
+    dops.values().flat_map(|dop| check_dop(dop)).collect()
+}
+
+/// A value parameter of a service with the constraints of its DOP.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamConstraints {
+    pub layer: String,
+    pub service: String,
+    /// Members of a structure are prefixed with the structure param,
+    /// e.g. `Data.Speed`.
+    pub param: String,
+    pub dop: String,
+    pub internal: Option<InternalConstr>,
+    pub physical: Option<InternalConstr>,
+}
+
+/// Every parameter backed by a normal DOP, per service of every layer: the
+/// request first, then the positive and negative responses.
+pub fn param_constraints(db: &DiagDatabase) -> Vec<ParamConstraints> {
+    let layers = db
+        .variants
+        .iter()
+        .map(|v| &v.diag_layer)
+        .chain(db.functional_groups.iter().map(|fg| &fg.diag_layer))
+        .chain(db.protocols.iter().map(|p| &p.diag_layer))
+        .chain(db.ecu_shared_datas.iter().map(|e| &e.diag_layer));
+    let mut rows = Vec::new();
+    for layer in layers {
+        for service in &layer.diag_services {
+            let params = service
+                .request
+                .iter()
+                .flat_map(|r| &r.params)
+                .chain(service.pos_responses.iter().flat_map(|r| &r.params))
+                .chain(service.neg_responses.iter().flat_map(|r| &r.params));
+            for param in params {
+                let mut row = |name: String, dop: &Dop| {
+                    if let Some(DopData::NormalDop {
+                        internal_constr,
+                        phys_constr,
+                        ..
+                    }) = &dop.specific_data
+                    {
+                        rows.push(ParamConstraints {
+                            layer: layer.short_name.to_string(),
+                            service: service.diag_comm.short_name.to_string(),
+                            param: name,
+                            dop: dop.short_name.to_string(),
+                            internal: internal_constr.clone(),
+                            physical: phys_constr.clone(),
+                        });
+                    }
+                };
+                visit_value_params(param, &param.short_name, &mut row);
+            }
+        }
+    }
+    rows
+}
+
+fn param_dop(param: &Param) -> Option<&Dop> {
+    match &param.specific_data {
+        Some(
+            ParamData::Value { dop, .. }
+            | ParamData::PhysConst { dop, .. }
+            | ParamData::System { dop, .. }
+            | ParamData::LengthKeyRef { dop },
+        ) => Some(dop),
+        _ => None,
+    }
+}
+
+fn visit_value_params(param: &Param, name: &str, visit: &mut impl FnMut(String, &Dop)) {
+    let Some(dop) = param_dop(param) else {
+        return;
+    };
+    if let Some(DopData::Structure { params, .. }) = &dop.specific_data {
+        for p in params {
+            visit_value_params(p, &format!("{name}.{}", p.short_name), visit);
+        }
+    }
+    visit(name.to_string(), dop);
+}
+
+fn collect_dops<'a>(param: &'a Param, dops: &mut BTreeMap<&'a str, &'a Dop>) {
+    let Some(dop) = param_dop(param) else {
+        return;
+    };
+    if let Some(DopData::Structure { params, .. }) = &dop.specific_data {
+        for p in params {
+            collect_dops(p, dops);
+        }
+    }
+    dops.entry(dop.short_name.as_str()).or_insert(dop);
+}
+
+fn check_dop(dop: &Dop) -> Vec<ConstraintWarning> {
+    let Some(DopData::NormalDop {
+        compu_method,
+        diag_coded_type,
+        internal_constr,
+        phys_constr: Some(phys),
+        ..
+    }) = &dop.specific_data
+    else {
+        return vec![];
+    };
+    let warn = |message: String| ConstraintWarning {
+        dop: dop.short_name.to_string(),
+        message,
+    };
+    let phys_range = bounds(phys);
+    if phys_range.0 > phys_range.1 {
+        return vec![warn(format!("PHYS-CONSTR {phys} is empty"))];
+    }
+
+    let mut warnings = Vec::new();
+    if let Some(internal) = internal_constr {
+        if let Some(range) = to_phys(compu_method.as_ref(), bounds(internal)) {
+            if !contains(range, phys_range) {
+                warnings.push(warn(format!(
+                    "PHYS-CONSTR {phys} exceeds the physical range [{}, {}] of INTERNAL-CONSTR {internal}",
+                    range.0, range.1
+                )));
+            }
+        }
+    }
+    let coded_range = diag_coded_type.as_ref().and_then(coded_range);
+    let compu_range = match compu_method {
+        Some(cm) if cm.category == CompuCategory::ScaleLinear => scale_linear_range(cm),
+        _ => coded_range.and_then(|r| to_phys(compu_method.as_ref(), r)),
+    };
+    if let Some(range) = compu_range {
+        if !contains(range, phys_range) {
+            warnings.push(warn(format!(
+                "PHYS-CONSTR {phys} exceeds the compu method range [{}, {}]",
+                range.0, range.1
+            )));
+        }
+    }
+    warnings
+}
+
+/// Numeric bounds of a constraint; infinite or unparsable limits are open-ended.
+fn bounds(constr: &InternalConstr) -> (f64, f64) {
+    let limit = |l: &Option<Limit>, inf: f64| {
+        l.as_ref()
+            .filter(|l| l.interval_type != IntervalType::Infinite)
+            .and_then(|l| parse_number(&l.value))
+            .unwrap_or(inf)
+    };
+    (
+        limit(&constr.lower_limit, f64::NEG_INFINITY),
+        limit(&constr.upper_limit, f64::INFINITY),
+    )
+}
+
+fn contains(outer: (f64, f64), inner: (f64, f64)) -> bool {
+    const EPS: f64 = 1e-9;
+    inner.0 >= outer.0 - EPS && inner.1 <= outer.1 + EPS
+}
+
+/// Map an internal range through an IDENTICAL or LINEAR compu method.
+fn to_phys(compu: Option<&CompuMethod>, (lo, hi): (f64, f64)) -> Option<(f64, f64)> {
+    let scale = match compu {
+        None => return Some((lo, hi)),
+        Some(cm) if cm.category == CompuCategory::Identical => return Some((lo, hi)),
+        Some(cm) if cm.category == CompuCategory::Linear => cm
+            .internal_to_phys
+            .as_ref()
+            .and_then(|itp| itp.compu_scales.first())?,
+        Some(_) => return None,
+    };
+    map_scale(scale, lo, hi)
+}
+
+fn linear(scale: &CompuScale, x: f64) -> Option<f64> {
+    let coeffs = scale.rational_co_effs.as_ref()?;
+    let offset = coeffs.numerator.first().copied().unwrap_or(0.0);
+    let factor = coeffs.numerator.get(1).copied().unwrap_or(0.0);
+    let denominator = coeffs.denominator.first().copied().unwrap_or(1.0);
+    Some((offset + factor * x) / denominator)
+}
+
+fn map_scale(scale: &CompuScale, lo: f64, hi: f64) -> Option<(f64, f64)> {
+    let (a, b) = (linear(scale, lo)?, linear(scale, hi)?);
+    if a.is_nan() || b.is_nan() {
+        // e.g. a zero factor applied to an infinite bound
+        return None;
+    }
+    Some((a.min(b), a.max(b)))
+}
+
+/// Union of the physical ranges of all SCALE-LINEAR scales.
+fn scale_linear_range(cm: &CompuMethod) -> Option<(f64, f64)> {
+    let scales = &cm.internal_to_phys.as_ref()?.compu_scales;
+    scales
+        .iter()
+        .map(|s| {
+            let constr = InternalConstr {
+                lower_limit: s.lower_limit.clone(),
+                upper_limit: s.upper_limit.clone(),
+                scale_constrs: vec![],
+            };
+            let (lo, hi) = bounds(&constr);
+            map_scale(s, lo, hi)
+        })
+        .reduce(|a, b| Some((a?.0.min(b?.0), a?.1.max(b?.1))))?
+}
+
+/// Internal values a standard-length integer coded type can hold.
+fn coded_range(dct: &DiagCodedType) -> Option<(f64, f64)> {
+    let Some(DiagCodedTypeData::StandardLength { bit_length, .. }) = &dct.specific_data else {
+        return None;
+    };
+    let bits = i32::try_from((*bit_length).clamp(1, 64)).unwrap_or(64);
+    let max = 2f64.powi(bits);
+    match dct.base_data_type {
+        DataType::AUint32 => Some((0.0, max - 1.0)),
+        DataType::AInt32 => Some((-max / 2.0, max / 2.0 - 1.0)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(value: &str) -> Limit {
+        Limit {
+            value: value.into(),
+            interval_type: IntervalType::Closed,
+        }
+    }
+
+    fn constr(lo: &str, hi: &str) -> InternalConstr {
+        InternalConstr {
+            lower_limit: Some(limit(lo)),
+            upper_limit: Some(limit(hi)),
+            scale_constrs: vec![],
+        }
+    }
+
+    /// An 8-bit unsigned DOP with `phys = x * 0.5 - 40`.
+    fn dop(internal: Option<InternalConstr>, phys: InternalConstr) -> Dop {
+        Dop {
+            dop_type: DopType::Regular,
+            short_name: "Temperature".into(),
+            sdgs: None,
+            specific_data: Some(DopData::NormalDop {
+                compu_method: Some(CompuMethod {
+                    category: CompuCategory::Linear,
+                    internal_to_phys: Some(CompuInternalToPhys {
+                        compu_scales: vec![CompuScale {
+                            short_label: None,
+                            lower_limit: None,
+                            upper_limit: None,
+                            inverse_values: None,
+                            consts: None,
+                            rational_co_effs: Some(CompuRationalCoEffs {
+                                numerator: vec![-40.0, 0.5],
+                                denominator: vec![],
+                            }),
+                        }],
+                        prog_code: None,
+                        compu_default_value: None,
+                    }),
+                    phys_to_internal: None,
+                }),
+                diag_coded_type: Some(DiagCodedType {
+                    specific_data: Some(DiagCodedTypeData::StandardLength {
+                        bit_length: 8,
+                        bit_mask: vec![],
+                        condensed: false,
+                    }),
+                    ..Default::default()
+                }),
+                physical_type: None,
+                internal_constr: internal,
+                unit_ref: None,
+                phys_constr: Some(phys),
+            }),
+        }
+    }
+
+    fn messages(dop: &Dop) -> Vec<String> {
+        check_dop(dop).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_consistent_constraints_pass() {
+        assert!(messages(&dop(Some(constr("0", "200")), constr("-40", "60"))).is_empty());
+    }
+
+    #[test]
+    fn test_phys_constr_beyond_internal_constr() {
+        assert_eq!(
+            messages(&dop(Some(constr("0", "100")), constr("-40", "60"))),
+            [
+                "DOP 'Temperature': PHYS-CONSTR [-40, 60] exceeds the physical range [-40, 10] of INTERNAL-CONSTR [0, 100]"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_phys_constr_beyond_coded_range() {
+        assert_eq!(
+            messages(&dop(None, constr("-40", "100"))),
+            [
+                "DOP 'Temperature': PHYS-CONSTR [-40, 100] exceeds the compu method range [-40, 87.5]"
+            ]
+        );
+        assert_eq!(
+            messages(&dop(None, constr("10", "0"))),
+            ["DOP 'Temperature': PHYS-CONSTR [10, 0] is empty"]
+        );
+    }
+
+    #[test]
+    fn test_param_constraints_lists_both_constraints() {
+        let param = |name: &str, dop: Dop| Param {
+            short_name: name.into(),
+            specific_data: Some(ParamData::Value {
+                physical_default_value: String::new(),
+                dop: dop.into(),
+            }),
+            ..Default::default()
+        };
+        let record = Dop {
+            dop_type: DopType::Structure,
+            short_name: "Record".into(),
+            sdgs: None,
+            specific_data: Some(DopData::Structure {
+                params: vec![param("Coolant", dop(None, constr("-40", "87.5")))],
+                byte_size: None,
+                is_visible: true,
+            }),
+        };
+        let db = DiagDatabase {
+            variants: vec![Variant {
+                diag_layer: DiagLayer {
+                    short_name: "Base".into(),
+                    diag_services: vec![DiagService {
+                        diag_comm: DiagComm {
+                            short_name: "Read_Temperatures".into(),
+                            ..Default::default()
+                        },
+                        pos_responses: vec![Response {
+                            params: vec![
+                                param("Oil", dop(Some(constr("0", "200")), constr("-40", "60"))),
+                                param("Data", record),
+                            ],
+                            response_type: ResponseType::PosResponse,
+                            sdgs: None,
+                            pos_response_suffix: None,
+                            audience: None,
+                        }],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let rows: Vec<(String, Option<String>, Option<String>)> = param_constraints(&db)
+            .into_iter()
+            .map(|r| {
+                assert_eq!(
+                    (r.layer.as_str(), r.service.as_str()),
+                    ("Base", "Read_Temperatures")
+                );
+                (
+                    r.param,
+                    r.internal.map(|c| c.to_string()),
+                    r.physical.map(|c| c.to_string()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (
+                    "Oil".into(),
+                    Some("[0, 200]".into()),
+                    Some("[-40, 60]".into())
+                ),
+                ("Data.Coolant".into(), None, Some("[-40, 87.5]".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_constraint_display() {
+        let c = InternalConstr {
+            lower_limit: Some(Limit {
+                value: "0".into(),
+                interval_type: IntervalType::Open,
+            }),
+            upper_limit: None,
+            scale_constrs: vec![],
+        };
+        assert_eq!(c.to_string(), "(0, inf)");
+    }
+}
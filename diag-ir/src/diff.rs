@@ -0,0 +1,872 @@
+use crate::ids::request_coded_value;
+use crate::lifecycle::{Lifecycle, lifecycle};
+use crate::types::{
+    ComParamRef, DiagCodedType, DiagCodedTypeData, DiagDatabase, DiagLayer, DiagService, Dop,
+    DopData, Dtc, EcuIdentification, Param, ParamData, StateChart,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// A single difference between two databases.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Change {
+    Added {
+        name: String,
+    },
+    Removed {
+        name: String,
+    },
+    /// Same request bytes and parameter layout under a different short name.
+    Renamed {
+        from: String,
+        to: String,
+    },
+    Modified {
+        name: String,
+        fields: Vec<String>,
+    },
+    /// Lifecycle status set, changed or cleared (e.g. a service deprecated).
+    Lifecycle {
+        name: String,
+        from: Option<Lifecycle>,
+        to: Option<Lifecycle>,
+    },
+}
+
+impl Change {
+    /// Removals and request/response layout changes break existing testers.
+    /// Additions and renames do not change what goes over the wire.
+    pub fn is_breaking(&self) -> bool {
+        match self {
+            Change::Removed { .. } => true,
+            Change::Modified { fields, .. } => fields.iter().any(|f| {
+                matches!(
+                    f.as_str(),
+                    "request" | "pos_responses" | "neg_responses" | "data"
+                )
+            }),
+            Change::Added { .. } | Change::Renamed { .. } | Change::Lifecycle { .. } => false,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lifecycle =
+            |l: &Option<Lifecycle>| l.as_ref().map_or("none".into(), ToString::to_string);
+        match self {
+            Change::Added { name } => write!(f, "+ {name}"),
+            Change::Removed { name } => write!(f, "- {name}"),
+            Change::Renamed { from, to } => write!(f, "~ {from} renamed to {to}"),
+            Change::Modified { name, fields } => write!(f, "~ {name}: {}", fields.join(", ")),
+            Change::Lifecycle { name, from, to } => write!(
+                f,
+                "~ {name}: lifecycle {} -> {}",
+                lifecycle(from),
+                lifecycle(to)
+            ),
+        }
+    }
+}
+
+/// Semantic diff of two databases. Items are matched by short name (DIDs
+/// and DTCs by their number), so ordering differences are ignored. Services
+/// sharing a short name are matched in order and reported as `<name>#<n>`
+/// from the second one on.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DatabaseDiff {
+    pub variants: Vec<Change>,
+    /// Service changes keyed by the name of the variant they belong to.
+    pub services: BTreeMap<String, Vec<Change>>,
+    /// DID changes (`0xF190`), by variant: `data` when the read response
+    /// changed, `name` when the read service was renamed, `writable` when a
+    /// write service was added or removed.
+    pub dids: BTreeMap<String, Vec<Change>>,
+    /// State chart changes by variant.
+    pub state_charts: BTreeMap<String, Vec<Change>>,
+    /// ComParam changes (`Protocol/CP_Name`) by variant.
+    pub comparams: BTreeMap<String, Vec<Change>>,
+    /// DTCs, matched by trouble code.
+    pub dtcs: Vec<Change>,
+    /// The ECU identification, as one item named `identification`.
+    pub identification: Vec<Change>,
+}
+
+impl DatabaseDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes().next().is_none()
+    }
+
+    /// Every change, variants first and the identification last.
+    pub fn changes(&self) -> impl Iterator<Item = &Change> {
+        self.variants
+            .iter()
+            .chain(self.services.values().flatten())
+            .chain(self.dids.values().flatten())
+            .chain(self.state_charts.values().flatten())
+            .chain(self.comparams.values().flatten())
+            .chain(&self.dtcs)
+            .chain(&self.identification)
+    }
+
+    /// Breaking changes, without the DID section: it restates service
+    /// changes by identifier.
+    pub fn breaking_count(&self) -> usize {
+        self.variants
+            .iter()
+            .chain(self.services.values().flatten())
+            .chain(self.state_charts.values().flatten())
+            .chain(self.comparams.values().flatten())
+            .chain(&self.dtcs)
+            .chain(&self.identification)
+            .filter(|c| c.is_breaking())
+            .count()
+    }
+}
+
+/// Compare two databases and return their semantic differences.
+pub fn diff_databases(old: &DiagDatabase, new: &DiagDatabase) -> DatabaseDiff {
+    let old_layers: BTreeMap<&str, &DiagLayer> = old
+        .variants
+        .iter()
+        .map(|v| (v.diag_layer.short_name.as_str(), &v.diag_layer))
+        .collect();
+    let new_layers: BTreeMap<&str, &DiagLayer> = new
+        .variants
+        .iter()
+        .map(|v| (v.diag_layer.short_name.as_str(), &v.diag_layer))
+        .collect();
+
+    let mut diff = DatabaseDiff::default();
+    for (name, old_layer) in &old_layers {
+        match new_layers.get(name) {
+            Some(new_layer) => {
+                let sections = [
+                    (
+                        &mut diff.services,
+                        diff_services(&old_layer.diag_services, &new_layer.diag_services),
+                    ),
+                    (&mut diff.dids, diff_dids(old_layer, new_layer)),
+                    (
+                        &mut diff.state_charts,
+                        diff_state_charts(&old_layer.state_charts, &new_layer.state_charts),
+                    ),
+                    (
+                        &mut diff.comparams,
+                        diff_comparams(&old_layer.com_param_refs, &new_layer.com_param_refs),
+                    ),
+                ];
+                for (section, changes) in sections {
+                    if !changes.is_empty() {
+                        section.insert((*name).to_string(), changes);
+                    }
+                }
+            }
+            None => diff.variants.push(Change::Removed {
+                name: (*name).to_string(),
+            }),
+        }
+    }
+    for name in new_layers.keys() {
+        if !old_layers.contains_key(name) {
+            diff.variants.push(Change::Added {
+                name: (*name).to_string(),
+            });
+        }
+    }
+    diff.dtcs = diff_dtcs(&old.dtcs, &new.dtcs);
+    diff.identification =
+        diff_identification(old.identification.as_ref(), new.identification.as_ref());
+    diff
+}
+
+/// Added, removed and modified items of two collections keyed alike.
+fn diff_keyed<K: Ord, T>(
+    old: &BTreeMap<K, T>,
+    new: &BTreeMap<K, T>,
+    name: impl Fn(&K) -> String,
+    modified_fields: impl Fn(&T, &T) -> Vec<String>,
+) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (key, old_item) in old {
+        match new.get(key) {
+            Some(new_item) => {
+                let fields = modified_fields(old_item, new_item);
+                if !fields.is_empty() {
+                    changes.push(Change::Modified {
+                        name: name(key),
+                        fields,
+                    });
+                }
+            }
+            None => changes.push(Change::Removed { name: name(key) }),
+        }
+    }
+    for key in new.keys().filter(|key| !old.contains_key(*key)) {
+        changes.push(Change::Added { name: name(key) });
+    }
+    changes
+}
+
+/// Names of the fields whose `checks` report a change.
+fn changed(checks: &[(&str, bool)]) -> Vec<String> {
+    checks
+        .iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(field, _)| (*field).to_string())
+        .collect()
+}
+
+/// The read (0x22) and write (0x2E) services of a layer by DID.
+#[derive(Default)]
+struct DidServices<'a> {
+    read: Option<&'a DiagService>,
+    write: Option<&'a DiagService>,
+}
+
+fn did_services(layer: &DiagLayer) -> BTreeMap<u32, DidServices<'_>> {
+    let mut dids: BTreeMap<u32, DidServices<'_>> = BTreeMap::new();
+    for svc in &layer.diag_services {
+        let (Some(sid), Some(did)) = (request_coded_value(svc, 0), request_coded_value(svc, 1))
+        else {
+            continue;
+        };
+        match sid {
+            0x22 => dids.entry(did).or_default().read = Some(svc),
+            0x2E => dids.entry(did).or_default().write = Some(svc),
+            _ => {}
+        }
+    }
+    dids
+}
+
+fn diff_dids(old: &DiagLayer, new: &DiagLayer) -> Vec<Change> {
+    diff_keyed(
+        &did_services(old),
+        &did_services(new),
+        |did| format!("0x{did:04X}"),
+        |a, b| {
+            let read_name = |d: &DidServices<'_>| d.read.map(|s| s.diag_comm.short_name.clone());
+            changed(&[
+                ("name", read_name(a) != read_name(b)),
+                (
+                    "data",
+                    a.read.map(|s| &s.pos_responses) != b.read.map(|s| &s.pos_responses),
+                ),
+                ("writable", a.write.is_some() != b.write.is_some()),
+            ])
+        },
+    )
+}
+
+fn diff_state_charts(old: &[StateChart], new: &[StateChart]) -> Vec<Change> {
+    let old: BTreeMap<&str, &StateChart> = old.iter().map(|c| (c.short_name.as_str(), c)).collect();
+    let new: BTreeMap<&str, &StateChart> = new.iter().map(|c| (c.short_name.as_str(), c)).collect();
+    diff_keyed(&old, &new, ToString::to_string, |a, b| {
+        changed(&[
+            ("semantic", a.semantic != b.semantic),
+            (
+                "start_state",
+                a.start_state_short_name_ref != b.start_state_short_name_ref,
+            ),
+            ("states", a.states != b.states),
+            (
+                "state_transitions",
+                a.state_transitions != b.state_transitions,
+            ),
+        ])
+    })
+}
+
+/// `Protocol/CP_Name`, or `CP_Name` for refs without a protocol.
+fn comparam_name(cp: &ComParamRef) -> String {
+    let name = cp.com_param.as_ref().map_or("", |c| c.short_name.as_str());
+    match &cp.protocol {
+        Some(protocol) => format!("{}/{name}", protocol.diag_layer.short_name),
+        None => name.to_string(),
+    }
+}
+
+fn diff_comparams(old: &[ComParamRef], new: &[ComParamRef]) -> Vec<Change> {
+    let old: BTreeMap<String, &ComParamRef> =
+        old.iter().map(|cp| (comparam_name(cp), cp)).collect();
+    let new: BTreeMap<String, &ComParamRef> =
+        new.iter().map(|cp| (comparam_name(cp), cp)).collect();
+    diff_keyed(&old, &new, Clone::clone, |a, b| {
+        changed(&[
+            (
+                "value",
+                a.simple_value != b.simple_value || a.complex_value != b.complex_value,
+            ),
+            ("definition", a.com_param != b.com_param),
+            ("prot_stack", a.prot_stack != b.prot_stack),
+        ])
+    })
+}
+
+fn diff_identification(
+    old: Option<&EcuIdentification>,
+    new: Option<&EcuIdentification>,
+) -> Vec<Change> {
+    fn keyed(ident: Option<&EcuIdentification>) -> BTreeMap<&str, &EcuIdentification> {
+        ident
+            .filter(|i| !i.is_empty())
+            .map(|i| ("identification", i))
+            .into_iter()
+            .collect()
+    }
+    diff_keyed(&keyed(old), &keyed(new), ToString::to_string, |a, b| {
+        changed(&[
+            ("part_number", a.part_number != b.part_number),
+            ("supplier_code", a.supplier_code != b.supplier_code),
+            ("hardware_version", a.hardware_version != b.hardware_version),
+            ("software_version", a.software_version != b.software_version),
+            ("dids", a.dids != b.dids),
+        ])
+    })
+}
+
+fn diff_dtcs(old: &[Dtc], new: &[Dtc]) -> Vec<Change> {
+    let old: BTreeMap<u32, &Dtc> = old.iter().map(|d| (d.trouble_code, d)).collect();
+    let new: BTreeMap<u32, &Dtc> = new.iter().map(|d| (d.trouble_code, d)).collect();
+    // Current name, or the last one for removed DTCs.
+    let name = |code: &u32| {
+        new.get(code)
+            .or_else(|| old.get(code))
+            .map_or_else(|| format!("0x{code:06X}"), |d| d.short_name.to_string())
+    };
+    let mut changes = diff_keyed(&old, &new, name, |a, b| {
+        changed(&[
+            (
+                "display_trouble_code",
+                a.display_trouble_code != b.display_trouble_code,
+            ),
+            ("text", a.text != b.text),
+            ("level", a.level != b.level),
+            ("is_temporary", a.is_temporary != b.is_temporary),
+        ])
+    });
+    for (code, a) in &old {
+        let Some(b) = new.get(code) else { continue };
+        if a.short_name != b.short_name {
+            changes.push(Change::Renamed {
+                from: a.short_name.to_string(),
+                to: b.short_name.to_string(),
+            });
+        }
+        let from = lifecycle(a.sdgs.as_ref());
+        let to = lifecycle(b.sdgs.as_ref());
+        if from != to {
+            changes.push(Change::Lifecycle {
+                name: b.short_name.to_string(),
+                from,
+                to,
+            });
+        }
+    }
+    changes
+}
+
+/// Services keyed by short name and occurrence. Services sharing a short
+/// name are each compared with the one at the same position on the other
+/// side rather than collapsing into the last of them.
+fn services_by_name(services: &[DiagService]) -> BTreeMap<(&str, usize), &DiagService> {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    services
+        .iter()
+        .map(|s| {
+            let name = s.diag_comm.short_name.as_str();
+            let occurrence = seen.entry(name).or_default();
+            *occurrence += 1;
+            ((name, *occurrence), s)
+        })
+        .collect()
+}
+
+/// Name a change reports for the `occurrence`th service named `name`:
+/// repeated short names get a `#<n>` suffix from the second one on.
+fn occurrence_name((name, occurrence): (&str, usize)) -> String {
+    if occurrence == 1 {
+        name.to_string()
+    } else {
+        format!("{name}#{occurrence}")
+    }
+}
+
+fn diff_services(old: &[DiagService], new: &[DiagService]) -> Vec<Change> {
+    let old_by_name = services_by_name(old);
+    let new_by_name = services_by_name(new);
+
+    let mut changes = Vec::new();
+    let mut removed = Vec::new();
+    for (key, old_svc) in &old_by_name {
+        let name = occurrence_name(*key);
+        match new_by_name.get(key) {
+            Some(new_svc) => {
+                let fields = modified_fields(old_svc, new_svc);
+                if !fields.is_empty() {
+                    changes.push(Change::Modified {
+                        name: name.clone(),
+                        fields,
+                    });
+                }
+                let from = lifecycle(old_svc.diag_comm.sdgs.as_ref());
+                let to = lifecycle(new_svc.diag_comm.sdgs.as_ref());
+                if from != to {
+                    changes.push(Change::Lifecycle { name, from, to });
+                }
+            }
+            None => removed.push((name, *old_svc)),
+        }
+    }
+    let added: Vec<(String, &DiagService)> = new_by_name
+        .iter()
+        .filter(|(key, _)| !old_by_name.contains_key(*key))
+        .map(|(key, svc)| (occurrence_name(*key), *svc))
+        .collect();
+
+    let renames = detect_renames(&removed, &added);
+    for (name, _) in &removed {
+        match renames.get(name.as_str()) {
+            Some(to) => changes.push(Change::Renamed {
+                from: name.clone(),
+                to: (*to).to_string(),
+            }),
+            None => changes.push(Change::Removed { name: name.clone() }),
+        }
+    }
+    for (name, _) in &added {
+        if !renames.values().any(|to| to == name) {
+            changes.push(Change::Added { name: name.clone() });
+        }
+    }
+    changes
+}
+
+/// Pair removed and added services whose layout signatures match exactly one
+/// counterpart, by the names [`diff_services`] reports them under. Ambiguous
+/// signatures (e.g. two identical services added) are left as plain
+/// additions and removals.
+fn detect_renames<'a>(
+    removed: &'a [(String, &DiagService)],
+    added: &'a [(String, &DiagService)],
+) -> HashMap<&'a str, &'a str> {
+    let mut by_signature: HashMap<String, (Vec<&str>, Vec<&str>)> = HashMap::new();
+    for (name, svc) in removed {
+        if let Some(sig) = layout_signature(svc) {
+            by_signature.entry(sig).or_default().0.push(name);
+        }
+    }
+    for (name, svc) in added {
+        if let Some(sig) = layout_signature(svc) {
+            by_signature.entry(sig).or_default().1.push(name);
+        }
+    }
+    by_signature
+        .into_values()
+        .filter_map(|(from, to)| match (from.as_slice(), to.as_slice()) {
+            ([from], [to]) => Some((*from, *to)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Request bytes plus the parameter layout of request and responses, with all
+/// short names left out. Services without constant request bytes have nothing
+/// to anchor a rename on and yield `None`.
+fn layout_signature(svc: &DiagService) -> Option<String> {
+    let request = svc.request.as_ref()?;
+    if !request
+        .params
+        .iter()
+        .any(|p| matches!(p.specific_data, Some(ParamData::CodedConst { .. })))
+    {
+        return None;
+    }
+    let mut sig = params_layout(&request.params);
+    for resp in svc.pos_responses.iter().chain(&svc.neg_responses) {
+        sig.push('|');
+        sig.push_str(&params_layout(&resp.params));
+    }
+    Some(sig)
+}
+
+fn params_layout(params: &[Param]) -> String {
+    params
+        .iter()
+        .map(|p| {
+            let detail = match &p.specific_data {
+                Some(ParamData::CodedConst {
+                    coded_value,
+                    diag_coded_type,
+                }) => format!("{coded_value}/{}", coded_width(diag_coded_type)),
+                Some(ParamData::NrcConst {
+                    coded_values,
+                    diag_coded_type,
+                }) => format!(
+                    "{}/{}",
+                    coded_values.join(","),
+                    coded_width(diag_coded_type)
+                ),
+                Some(
+                    ParamData::Value { dop, .. }
+                    | ParamData::PhysConst { dop, .. }
+                    | ParamData::System { dop, .. }
+                    | ParamData::LengthKeyRef { dop },
+                ) => dop_width(dop),
+                Some(ParamData::MatchingRequestParam {
+                    request_byte_pos,
+                    byte_length,
+                }) => format!("{request_byte_pos}+{byte_length}"),
+                Some(ParamData::Reserved { bit_length }) => bit_length.to_string(),
+                _ => String::new(),
+            };
+            format!(
+                "{:?}@{:?}.{:?}:{detail}",
+                p.param_type, p.byte_position, p.bit_position
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+fn coded_width(dct: &DiagCodedType) -> String {
+    match &dct.specific_data {
+        Some(DiagCodedTypeData::StandardLength { bit_length, .. }) => bit_length.to_string(),
+        Some(DiagCodedTypeData::LeadingLength { bit_length }) => format!("L{bit_length}"),
+        Some(DiagCodedTypeData::MinMax {
+            min_length,
+            max_length,
+            ..
+        }) => format!("{min_length}..{max_length:?}"),
+        Some(DiagCodedTypeData::ParamLength { .. }) => "P".into(),
+        None => String::new(),
+    }
+}
+
+fn dop_width(dop: &Dop) -> String {
+    match &dop.specific_data {
+        Some(DopData::NormalDop {
+            diag_coded_type: Some(dct),
+            ..
+        }) => coded_width(dct),
+        _ => format!("{:?}", dop.dop_type),
+    }
+}
+
+fn modified_fields(old: &DiagService, new: &DiagService) -> Vec<String> {
+    let (a, b) = (&old.diag_comm, &new.diag_comm);
+    let checks = [
+        ("long_name", a.long_name != b.long_name),
+        ("semantic", a.semantic != b.semantic),
+        ("funct_classes", a.funct_classes != b.funct_classes),
+        ("audience", a.audience != b.audience),
+        (
+            "pre_condition_state_refs",
+            a.pre_condition_state_refs != b.pre_condition_state_refs,
+        ),
+        (
+            "state_transition_refs",
+            a.state_transition_refs != b.state_transition_refs,
+        ),
+        ("request", old.request != new.request),
+        ("pos_responses", old.pos_responses != new.pos_responses),
+        ("neg_responses", old.neg_responses != new.neg_responses),
+        ("addressing", old.addressing != new.addressing),
+        (
+            "transmission_mode",
+            old.transmission_mode != new.transmission_mode,
+        ),
+        ("com_param_refs", old.com_param_refs != new.com_param_refs),
+    ];
+    checks
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(field, _)| field.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{database, service};
+    use crate::types::*;
+
+    fn coded_const(name: &str, value: &str, byte: u32) -> Param {
+        Param {
+            short_name: name.into(),
+            param_type: ParamType::CodedConst,
+            byte_position: Some(byte),
+            specific_data: Some(ParamData::CodedConst {
+                coded_value: value.into(),
+                diag_coded_type: DiagCodedType {
+                    type_name: DiagCodedTypeName::StandardLengthType,
+                    base_type_encoding: String::new(),
+                    base_data_type: DataType::AUint32,
+                    is_high_low_byte_order: true,
+                    specific_data: Some(DiagCodedTypeData::StandardLength {
+                        bit_length: 8,
+                        bit_mask: vec![],
+                        condensed: false,
+                    }),
+                },
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// A service whose request codes `sid` and `did`.
+    fn coded_service(name: &str, sid: &str, did: &str) -> DiagService {
+        DiagService {
+            request: Some(Request {
+                params: vec![coded_const("SID", sid, 0), coded_const("DID", did, 1)],
+                sdgs: None,
+            }),
+            ..service(name, "")
+        }
+    }
+
+    #[test]
+    fn test_identical_databases_have_empty_diff() {
+        let a = database(vec![coded_service("Read_A", "34", "1")]);
+        assert!(diff_databases(&a, &a.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_service_order_is_ignored() {
+        let a = database(vec![
+            coded_service("A", "34", "1"),
+            coded_service("B", "34", "2"),
+        ]);
+        let b = database(vec![
+            coded_service("B", "34", "2"),
+            coded_service("A", "34", "1"),
+        ]);
+        assert!(diff_databases(&a, &b).is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_short_names_are_compared_by_occurrence() {
+        let a = database(vec![
+            coded_service("Read", "34", "1"),
+            coded_service("Read", "34", "2"),
+        ]);
+        let b = database(vec![
+            coded_service("Read", "34", "1"),
+            coded_service("Read", "34", "3"),
+        ]);
+        assert_eq!(
+            diff_databases(&a, &b).services["Base"],
+            vec![Change::Modified {
+                name: "Read#2".into(),
+                fields: vec!["request".into()],
+            }]
+        );
+
+        let c = database(vec![coded_service("Read", "34", "1")]);
+        assert_eq!(
+            diff_databases(&a, &c).services["Base"],
+            vec![Change::Removed {
+                name: "Read#2".into()
+            }]
+        );
+        assert!(diff_databases(&a, &a.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_rename_detected_by_layout() {
+        let a = database(vec![coded_service("Read_VIN", "34", "61840")]);
+        let b = database(vec![coded_service("Read_VehicleIdent", "34", "61840")]);
+        let diff = diff_databases(&a, &b);
+        assert_eq!(
+            diff.services["Base"],
+            vec![Change::Renamed {
+                from: "Read_VIN".into(),
+                to: "Read_VehicleIdent".into(),
+            }]
+        );
+        assert_eq!(diff.breaking_count(), 0);
+    }
+
+    #[test]
+    fn test_different_request_bytes_is_remove_and_add() {
+        let a = database(vec![coded_service("Read_VIN", "34", "61840")]);
+        let b = database(vec![coded_service("Read_Serial", "34", "61836")]);
+        let diff = diff_databases(&a, &b);
+        assert_eq!(
+            diff.services["Base"],
+            vec![
+                Change::Removed {
+                    name: "Read_VIN".into()
+                },
+                Change::Added {
+                    name: "Read_Serial".into()
+                },
+            ]
+        );
+        assert_eq!(diff.breaking_count(), 1);
+    }
+
+    #[test]
+    fn test_ambiguous_rename_not_paired() {
+        let a = database(vec![coded_service("Old", "34", "1")]);
+        let b = database(vec![
+            coded_service("New1", "34", "1"),
+            coded_service("New2", "34", "1"),
+        ]);
+        let changes = &diff_databases(&a, &b).services["Base"];
+        assert!(!changes.iter().any(|c| matches!(c, Change::Renamed { .. })));
+        assert_eq!(changes.len(), 3);
+    }
+
+    #[test]
+    fn test_modified_request_is_breaking() {
+        let a = database(vec![coded_service("Read_A", "34", "1")]);
+        let b = database(vec![coded_service("Read_A", "34", "2")]);
+        let diff = diff_databases(&a, &b);
+        assert_eq!(
+            diff.services["Base"],
+            vec![Change::Modified {
+                name: "Read_A".into(),
+                fields: vec!["request".into()],
+            }]
+        );
+        assert_eq!(diff.breaking_count(), 1);
+    }
+
+    #[test]
+    fn test_deprecation_is_reported() {
+        let a = database(vec![coded_service("Read_A", "34", "1")]);
+        let mut b = a.clone();
+        let deprecated = crate::lifecycle::Lifecycle {
+            status: crate::lifecycle::LifecycleStatus::Deprecated,
+            since: Some("2.1".into()),
+        };
+        crate::lifecycle::set_lifecycle(
+            &mut b.variants[0].diag_layer.diag_services[0].diag_comm.sdgs,
+            &deprecated,
+        );
+        let diff = diff_databases(&a, &b);
+        assert_eq!(
+            diff.services["Base"],
+            vec![Change::Lifecycle {
+                name: "Read_A".into(),
+                from: None,
+                to: Some(deprecated),
+            }]
+        );
+        assert_eq!(diff.breaking_count(), 0);
+    }
+
+    #[test]
+    fn test_did_and_dtc_changes() {
+        let dtc = |name: &str, code: u32, text: &str| Dtc {
+            short_name: name.into(),
+            trouble_code: code,
+            text: Some(Text {
+                value: text.into(),
+                ti: String::new(),
+            }),
+            ..Default::default()
+        };
+        let mut a = database(vec![
+            coded_service("VIN_Read", "34", "61840"),
+            coded_service("Serial_Read", "34", "61836"),
+        ]);
+        a.dtcs = vec![dtc("P0101", 0x0101, "Airflow"), dtc("P0102", 0x0102, "Low")];
+        let mut b = database(vec![
+            coded_service("VIN_Read", "34", "61840"),
+            coded_service("VIN_Write", "46", "61840"),
+        ]);
+        b.dtcs = vec![
+            dtc("P0101_MAF", 0x0101, "MAF range"),
+            dtc("P0200", 0x0200, "Injector"),
+        ];
+
+        let diff = diff_databases(&a, &b);
+        let lines: Vec<String> = diff.dids["Base"].iter().map(ToString::to_string).collect();
+        assert_eq!(lines, ["- 0xF18C", "~ 0xF190: writable"]);
+        let lines: Vec<String> = diff.dtcs.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "~ P0101_MAF: text",
+                "- P0102",
+                "+ P0200",
+                "~ P0101 renamed to P0101_MAF",
+            ]
+        );
+        // The removed Serial_Read service and DTC; the DID restates the
+        // service.
+        assert_eq!(diff.breaking_count(), 2);
+    }
+
+    #[test]
+    fn test_state_chart_and_comparam_changes() {
+        let chart = |start: &str| StateChart {
+            short_name: "Session".into(),
+            semantic: "SESSION".into(),
+            state_transitions: vec![],
+            start_state_short_name_ref: start.into(),
+            states: vec![],
+        };
+        let comparam = |value: &str| ComParamRef {
+            simple_value: Some(SimpleValue {
+                value: value.into(),
+            }),
+            complex_value: None,
+            com_param: Some(Box::new(ComParam {
+                com_param_type: ComParamType::Regular,
+                short_name: "CP_P2Max".into(),
+                long_name: None,
+                param_class: String::new(),
+                cp_type: ComParamStandardisationLevel::Standard,
+                display_level: None,
+                cp_usage: ComParamUsage::EcuComm,
+                specific_data: None,
+            })),
+            protocol: None,
+            prot_stack: None,
+        };
+        let mut a = database(vec![]);
+        a.variants[0].diag_layer.state_charts = vec![chart("Default")];
+        a.variants[0].diag_layer.com_param_refs = vec![comparam("50")];
+        let mut b = database(vec![]);
+        b.variants[0].diag_layer.state_charts = vec![chart("Extended")];
+        b.variants[0].diag_layer.com_param_refs = vec![comparam("100")];
+
+        let diff = diff_databases(&a, &b);
+        assert_eq!(
+            diff.state_charts["Base"][0].to_string(),
+            "~ Session: start_state"
+        );
+        assert_eq!(diff.comparams["Base"][0].to_string(), "~ CP_P2Max: value");
+        assert_eq!(diff.breaking_count(), 0);
+    }
+
+    #[test]
+    fn test_identification_changes() {
+        let ident = |software_version: &str| EcuIdentification {
+            part_number: Some("8W0907115A".into()),
+            software_version: Some(software_version.into()),
+            ..Default::default()
+        };
+        let mut a = database(vec![]);
+        a.identification = Some(ident("0042"));
+        let mut b = database(vec![]);
+        b.identification = Some(ident("0043"));
+
+        let diff = diff_databases(&a, &b);
+        assert_eq!(
+            diff.identification[0].to_string(),
+            "~ identification: software_version"
+        );
+        assert_eq!(diff.breaking_count(), 0);
+
+        b.identification = None;
+        let diff = diff_databases(&a, &b);
+        assert_eq!(diff.identification[0].to_string(), "- identification");
+    }
+}
@@ -0,0 +1,180 @@
+//! Translated DTC texts.
+//!
+//! A DTC has one text in the language of the database. Translations are
+//! stored as a `dtc_texts` SDG on the DTC with one SD per language (the SI
+//! is the language code), so they travel through YAML, ODX and MDD without
+//! any schema change. [`dtc_texts`] and [`merge_dtc_texts`] exchange them
+//! as rows keyed by trouble code and text ID, so translators work on the
+//! text alone.
+
+use std::collections::BTreeMap;
+
+use crate::types::{DiagDatabase, Sd, SdOrSdg, Sdg, Sdgs};
+
+/// SDG caption under which translations are stored.
+pub const DTC_TEXTS_CAPTION: &str = "dtc_texts";
+
+/// One DTC text and its translation into one language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DtcText {
+    pub trouble_code: u32,
+    pub display_trouble_code: String,
+    /// Text ID (`TI`) of the DTC text; empty when the source has none.
+    pub ti: String,
+    /// The text in the language of the database.
+    pub source: String,
+    /// The translation; empty when not translated yet.
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DtcTextError {
+    #[error("No DTC 0x{trouble_code:06X} with text ID '{ti}'")]
+    UnknownDtc { trouble_code: u32, ti: String },
+}
+
+/// Translations stored in `sdgs`, by language.
+pub fn dtc_translations(sdgs: Option<&Sdgs>) -> BTreeMap<String, String> {
+    sdgs.into_iter()
+        .flat_map(|s| &s.sdgs)
+        .filter(|sdg| sdg.caption_sn == DTC_TEXTS_CAPTION)
+        .flat_map(|sdg| &sdg.sds)
+        .filter_map(|sd| match sd {
+            SdOrSdg::Sd(sd) => Some((sd.si.clone(), sd.value.clone())),
+            SdOrSdg::Sdg(_) => None,
+        })
+        .collect()
+}
+
+/// Store the `lang` translation in `sdgs`, replacing a previous one.
+pub fn set_dtc_translation(sdgs: &mut Option<Sdgs>, lang: &str, text: &str) {
+    let sdgs = &mut sdgs.get_or_insert_with(|| Sdgs { sdgs: vec![] }).sdgs;
+    let index = if let Some(i) = sdgs.iter().position(|s| s.caption_sn == DTC_TEXTS_CAPTION) {
+        i
+    } else {
+        sdgs.push(Sdg {
+            caption_sn: DTC_TEXTS_CAPTION.into(),
+            sds: vec![],
+            si: String::new(),
+        });
+        sdgs.len() - 1
+    };
+    let sds = &mut sdgs[index].sds;
+    sds.retain(|sd| !matches!(sd, SdOrSdg::Sd(sd) if sd.si == lang));
+    sds.push(SdOrSdg::Sd(Sd {
+        value: text.to_string(),
+        si: lang.to_string(),
+        ti: String::new(),
+    }));
+}
+
+/// The text of every DTC with a text, with its `lang` translation.
+pub fn dtc_texts(db: &DiagDatabase, lang: &str) -> Vec<DtcText> {
+    db.dtcs
+        .iter()
+        .filter_map(|dtc| {
+            let text = dtc.text.as_ref()?;
+            Some(DtcText {
+                trouble_code: dtc.trouble_code,
+                display_trouble_code: dtc.display_trouble_code.clone(),
+                ti: text.ti.clone(),
+                source: text.value.clone(),
+                text: dtc_translations(dtc.sdgs.as_ref())
+                    .remove(lang)
+                    .unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Store the `lang` translations of `texts` on the DTCs they belong to.
+/// Rows without a translation are skipped. Nothing is changed when a row
+/// matches no DTC. Returns the number of DTCs updated.
+pub fn merge_dtc_texts(
+    db: &mut DiagDatabase,
+    lang: &str,
+    texts: &[DtcText],
+) -> Result<usize, Vec<DtcTextError>> {
+    let key = |code: u32, ti: &str| (code, ti.to_string());
+    let index: BTreeMap<(u32, String), usize> = db
+        .dtcs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, dtc)| Some((key(dtc.trouble_code, &dtc.text.as_ref()?.ti), i)))
+        .collect();
+
+    let mut updates = Vec::new();
+    let mut errors = Vec::new();
+    for row in texts.iter().filter(|row| !row.text.is_empty()) {
+        match index.get(&key(row.trouble_code, &row.ti)) {
+            Some(&i) => updates.push((i, row.text.as_str())),
+            None => errors.push(DtcTextError::UnknownDtc {
+                trouble_code: row.trouble_code,
+                ti: row.ti.clone(),
+            }),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    for (i, text) in &updates {
+        set_dtc_translation(&mut db.dtcs[*i].sdgs, lang, text);
+    }
+    Ok(updates.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Dtc, Text};
+
+    fn dtc(code: u32, text: &str, ti: &str) -> Dtc {
+        Dtc {
+            short_name: format!("DTC_{code:06X}").into(),
+            trouble_code: code,
+            display_trouble_code: String::new(),
+            text: Some(Text {
+                value: text.into(),
+                ti: ti.into(),
+            }),
+            level: None,
+            sdgs: None,
+            is_temporary: false,
+        }
+    }
+
+    #[test]
+    fn test_merge_dtc_texts() {
+        let mut db = DiagDatabase {
+            dtcs: vec![
+                dtc(0x01_2300, "Throttle high", "TI_1"),
+                dtc(0x01_2400, "Throttle low", ""),
+            ],
+            ..Default::default()
+        };
+        let mut rows = dtc_texts(&db, "de");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].ti, "TI_1");
+        assert!(rows.iter().all(|r| r.text.is_empty()));
+
+        rows[0].text = "Drosselklappe hoch".into();
+        assert_eq!(merge_dtc_texts(&mut db, "de", &rows), Ok(1));
+        rows[0].text = "Drosselklappe zu hoch".into();
+        assert_eq!(merge_dtc_texts(&mut db, "de", &rows), Ok(1));
+        assert_eq!(
+            dtc_translations(db.dtcs[0].sdgs.as_ref()),
+            [("de".to_string(), "Drosselklappe zu hoch".to_string())].into()
+        );
+        assert_eq!(dtc_texts(&db, "de")[0].text, "Drosselklappe zu hoch");
+        assert_eq!(dtc_texts(&db, "fr")[0].text, "");
+
+        rows[1].ti = "TI_2".into();
+        rows[1].text = "Drosselklappe niedrig".into();
+        let before = db.clone();
+        assert_eq!(
+            merge_dtc_texts(&mut db, "de", &rows).unwrap_err()[0].to_string(),
+            "No DTC 0x012400 with text ID 'TI_2'"
+        );
+        assert_eq!(db, before);
+    }
+}
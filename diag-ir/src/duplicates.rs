@@ -0,0 +1,415 @@
+//! Trouble codes and DIDs defined more than once.
+//!
+//! ODX may define one trouble code in several DTC-DOPs with different texts,
+//! and merged or generated inputs may give a layer two services reading the
+//! same DID. Exact copies,
+//! such as the DTCs every variant inherits from its base, are dropped
+//! silently; definitions that differ are reported and resolved by a
+//! [`DuplicatePolicy`].
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::ids::request_coded_value;
+use crate::types::{DiagDatabase, DiagLayer, Dtc, Text};
+
+/// Services addressing a DID: Read/WriteDataByIdentifier and
+/// InputOutputControlByIdentifier.
+const DID_SIDS: [u32; 3] = [0x22, 0x2E, 0x2F];
+
+/// Which of several definitions of one identifier to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Keep the first definition.
+    #[default]
+    First,
+    /// Keep the last definition.
+    Last,
+    /// Fail listing every duplicate.
+    Error,
+    /// Keep the first DTC with the distinct texts of all of them joined by
+    /// ` / `, and missing levels and SDGs taken from the others. DIDs have
+    /// no texts; their first service is kept.
+    MergeTexts,
+}
+
+impl DuplicatePolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::First => "first",
+            Self::Last => "last",
+            Self::Error => "error",
+            Self::MergeTexts => "merge-texts",
+        }
+    }
+
+    /// What the policy does with `duplicate`, for warnings.
+    pub fn resolution(self, duplicate: &Duplicate) -> &'static str {
+        match (self, duplicate) {
+            (Self::MergeTexts, Duplicate::Dtc { .. }) => "merged into the first",
+            (Self::First | Self::MergeTexts, _) => "kept the first",
+            (Self::Last, _) => "kept the last",
+            (Self::Error, _) => "rejected",
+        }
+    }
+}
+
+impl fmt::Display for DuplicatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Self::First, Self::Last, Self::Error, Self::MergeTexts]
+            .into_iter()
+            .find(|p| p.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "unknown duplicate policy '{s}' (expected first, last, error or merge-texts)"
+                )
+            })
+    }
+}
+
+/// An identifier with differing definitions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Duplicate {
+    Dtc {
+        trouble_code: u32,
+        /// Short names of the definitions, in database order.
+        names: Vec<String>,
+        /// Distinct texts of the definitions.
+        texts: Vec<String>,
+    },
+    Did {
+        layer: String,
+        sid: u8,
+        did: u16,
+        /// Services addressing the DID, in layer order.
+        services: Vec<String>,
+    },
+}
+
+impl fmt::Display for Duplicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dtc {
+                trouble_code,
+                names,
+                texts,
+            } => {
+                write!(
+                    f,
+                    "DTC 0x{trouble_code:06X} is defined {} times ({})",
+                    names.len(),
+                    names.join(", ")
+                )?;
+                if texts.len() > 1 {
+                    write!(f, " with different texts: '{}'", texts.join("' / '"))?;
+                }
+                Ok(())
+            }
+            Self::Did {
+                layer,
+                sid,
+                did,
+                services,
+            } => write!(
+                f,
+                "DID 0x{did:04X} has {} services with SID 0x{sid:02X} in layer '{layer}' ({})",
+                services.len(),
+                services.join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{} duplicate identifier(s):{}", .0.len(), list(.0))]
+pub struct DuplicateError(pub Vec<Duplicate>);
+
+fn list(duplicates: &[Duplicate]) -> String {
+    let mut out = String::new();
+    for duplicate in duplicates {
+        out.push_str("\n  ");
+        out.push_str(&duplicate.to_string());
+    }
+    out
+}
+
+/// Resolve duplicate trouble codes in `dtcs` by `policy`, returning the
+/// duplicates found. With [`DuplicatePolicy::Error`], only exact copies are
+/// removed when there are any.
+pub fn resolve_dtc_duplicates(
+    dtcs: &mut Vec<Dtc>,
+    policy: DuplicatePolicy,
+) -> Result<Vec<Duplicate>, DuplicateError> {
+    // Exact copies are not duplicates worth reporting.
+    let mut unique: Vec<Dtc> = Vec::with_capacity(dtcs.len());
+    let mut groups: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+    for dtc in dtcs.drain(..) {
+        let members = groups.entry(dtc.trouble_code).or_default();
+        if !members.iter().any(|&i| unique[i] == dtc) {
+            members.push(unique.len());
+            unique.push(dtc);
+        }
+    }
+    let duplicates: Vec<(u32, Vec<usize>)> = groups
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .collect();
+    let found: Vec<Duplicate> = duplicates
+        .iter()
+        .map(|(code, members)| {
+            let mut texts: Vec<String> = Vec::new();
+            for text in members.iter().filter_map(|&i| unique[i].text.as_ref()) {
+                let text = shown_text(text);
+                if !texts.iter().any(|t| t == text) {
+                    texts.push(text.to_string());
+                }
+            }
+            Duplicate::Dtc {
+                trouble_code: *code,
+                names: members
+                    .iter()
+                    .map(|&i| unique[i].short_name.to_string())
+                    .collect(),
+                texts,
+            }
+        })
+        .collect();
+    if policy == DuplicatePolicy::Error && !found.is_empty() {
+        *dtcs = unique;
+        return Err(DuplicateError(found));
+    }
+
+    let mut keep = vec![true; unique.len()];
+    for (_, members) in &duplicates {
+        let kept = if policy == DuplicatePolicy::Last {
+            members[members.len() - 1]
+        } else {
+            members[0]
+        };
+        if policy == DuplicatePolicy::MergeTexts {
+            let others: Vec<Dtc> = members[1..].iter().map(|&i| unique[i].clone()).collect();
+            merge_into(&mut unique[kept], &others);
+        }
+        for &i in members {
+            keep[i] = i == kept;
+        }
+    }
+    let mut keep = keep.into_iter();
+    unique.retain(|_| keep.next().unwrap_or(true));
+    *dtcs = unique;
+    Ok(found)
+}
+
+/// The text a tester shows: ODX keeps it in `ti` when the `TEXT` element
+/// has no content of its own.
+fn shown_text(text: &Text) -> &str {
+    if text.value.is_empty() {
+        &text.ti
+    } else {
+        &text.value
+    }
+}
+
+/// Give `dtc` the distinct texts of `others` and the level and SDGs it lacks.
+fn merge_into(dtc: &mut Dtc, others: &[Dtc]) {
+    let texts: Vec<&Text> = dtc
+        .text
+        .iter()
+        .chain(others.iter().filter_map(|o| o.text.as_ref()))
+        .collect();
+    let join = |field: fn(&Text) -> &str| {
+        let mut distinct: Vec<&str> = Vec::new();
+        for value in texts.iter().map(|t| field(t)).filter(|v| !v.is_empty()) {
+            if !distinct.contains(&value) {
+                distinct.push(value);
+            }
+        }
+        distinct.join(" / ")
+    };
+    let merged = Text {
+        value: join(|t| &t.value),
+        ti: join(|t| &t.ti),
+    };
+    if !texts.is_empty() {
+        dtc.text = Some(merged);
+    }
+    for other in others {
+        dtc.level = dtc.level.or(other.level);
+        if dtc.sdgs.is_none() {
+            dtc.sdgs.clone_from(&other.sdgs);
+        }
+    }
+}
+
+/// Resolve duplicate DTCs and per-layer duplicate DID services of `db` by
+/// `policy`, returning the duplicates found. With
+/// [`DuplicatePolicy::Error`], nothing changes when there are any.
+pub fn resolve_duplicates(
+    db: &mut DiagDatabase,
+    policy: DuplicatePolicy,
+) -> Result<Vec<Duplicate>, DuplicateError> {
+    let mut found = Vec::new();
+    let mut dtcs = db.dtcs.clone();
+    match resolve_dtc_duplicates(&mut dtcs, policy) {
+        Ok(dtc_duplicates) => found.extend(dtc_duplicates),
+        Err(DuplicateError(dtc_duplicates)) => found.extend(dtc_duplicates),
+    }
+    let mut drops = Vec::new();
+    for layer in layers_mut(db) {
+        let (layer_duplicates, dropped) = did_duplicates(layer, policy);
+        found.extend(layer_duplicates);
+        drops.push(dropped);
+    }
+    if policy == DuplicatePolicy::Error {
+        return if found.is_empty() {
+            Ok(found)
+        } else {
+            Err(DuplicateError(found))
+        };
+    }
+    db.dtcs = dtcs;
+    for (layer, dropped) in layers_mut(db).zip(drops) {
+        let mut index = 0;
+        layer.diag_services.retain(|_| {
+            index += 1;
+            !dropped.contains(&(index - 1))
+        });
+    }
+    Ok(found)
+}
+
+/// Services of `layer` sharing a DID and SID, and the indices of those the
+/// policy drops.
+fn did_duplicates(layer: &DiagLayer, policy: DuplicatePolicy) -> (Vec<Duplicate>, Vec<usize>) {
+    let mut groups: BTreeMap<(u16, u32), Vec<usize>> = BTreeMap::new();
+    for (i, svc) in layer.diag_services.iter().enumerate() {
+        let Some(sid) = request_coded_value(svc, 0).filter(|sid| DID_SIDS.contains(sid)) else {
+            continue;
+        };
+        let Some(did) = request_coded_value(svc, 1).and_then(|d| u16::try_from(d).ok()) else {
+            continue;
+        };
+        groups.entry((did, sid)).or_default().push(i);
+    }
+    let mut found = Vec::new();
+    let mut dropped = Vec::new();
+    for ((did, sid), members) in groups.into_iter().filter(|(_, m)| m.len() > 1) {
+        found.push(Duplicate::Did {
+            layer: layer.short_name.to_string(),
+            sid: u8::try_from(sid).unwrap_or(u8::MAX),
+            did,
+            services: members
+                .iter()
+                .map(|&i| layer.diag_services[i].diag_comm.short_name.to_string())
+                .collect(),
+        });
+        let kept = if policy == DuplicatePolicy::Last {
+            members[members.len() - 1]
+        } else {
+            members[0]
+        };
+        dropped.extend(members.into_iter().filter(|&i| i != kept));
+    }
+    (found, dropped)
+}
+
+fn layers_mut(db: &mut DiagDatabase) -> impl Iterator<Item = &mut DiagLayer> {
+    db.variants
+        .iter_mut()
+        .map(|v| &mut v.diag_layer)
+        .chain(db.functional_groups.iter_mut().map(|fg| &mut fg.diag_layer))
+        .chain(db.protocols.iter_mut().map(|p| &mut p.diag_layer))
+        .chain(db.ecu_shared_datas.iter_mut().map(|e| &mut e.diag_layer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dtc(name: &str, code: u32, text: &str, level: Option<u32>) -> Dtc {
+        Dtc {
+            short_name: name.into(),
+            trouble_code: code,
+            display_trouble_code: String::new(),
+            text: Some(Text {
+                value: text.into(),
+                ti: String::new(),
+            }),
+            level,
+            sdgs: None,
+            is_temporary: false,
+        }
+    }
+
+    fn dtcs() -> Vec<Dtc> {
+        vec![
+            dtc("P0101", 0x0101, "Airflow", None),
+            dtc("P0102", 0x0102, "Airflow low", None),
+            dtc("P0101", 0x0101, "Airflow", None),
+            dtc("P0101_Alt", 0x0101, "MAF range", Some(2)),
+        ]
+    }
+
+    #[test]
+    fn test_dtc_policies() {
+        let mut first = dtcs();
+        let found = resolve_dtc_duplicates(&mut first, DuplicatePolicy::First).unwrap();
+        assert_eq!(
+            found.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "DTC 0x000101 is defined 2 times (P0101, P0101_Alt) with different texts: 'Airflow' / 'MAF range'"
+            ]
+        );
+        let names = |dtcs: &[Dtc]| {
+            dtcs.iter()
+                .map(|d| d.short_name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&first), ["P0101", "P0102"]);
+
+        let mut last = dtcs();
+        resolve_dtc_duplicates(&mut last, DuplicatePolicy::Last).unwrap();
+        assert_eq!(names(&last), ["P0102", "P0101_Alt"]);
+
+        let mut merged = dtcs();
+        resolve_dtc_duplicates(&mut merged, DuplicatePolicy::MergeTexts).unwrap();
+        assert_eq!(names(&merged), ["P0101", "P0102"]);
+        assert_eq!(
+            merged[0].text.as_ref().unwrap().value,
+            "Airflow / MAF range"
+        );
+        assert_eq!(merged[0].level, Some(2));
+
+        let mut strict = dtcs();
+        let err = resolve_dtc_duplicates(&mut strict, DuplicatePolicy::Error).unwrap_err();
+        assert_eq!(err.0, found);
+
+        // Exact copies alone are not reported.
+        let mut copies = vec![dtcs()[0].clone(), dtcs()[0].clone()];
+        assert_eq!(
+            resolve_dtc_duplicates(&mut copies, DuplicatePolicy::Error),
+            Ok(vec![])
+        );
+        assert_eq!(copies.len(), 1);
+    }
+
+    #[test]
+    fn test_policy_parsing() {
+        assert_eq!(
+            "merge-texts".parse::<DuplicatePolicy>(),
+            Ok(DuplicatePolicy::MergeTexts)
+        );
+        assert!("keep".parse::<DuplicatePolicy>().is_err());
+    }
+}
@@ -0,0 +1,718 @@
+//! Incremental, transactional database edits.
+//!
+//! [`validate_database`](crate::validate_database) checks the whole
+//! database, which is too slow to run after every change in an editor or
+//! every line of a batch script. An [`Editor`] indexes the database once and
+//! keeps the index current while it edits. Edits stay pending until
+//! [`Editor::commit`], which re-validates only the layers the edits touched
+//! and rolls every pending edit back when one of them is invalid. Dropping
+//! the editor rolls pending edits back as well.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use thiserror::Error;
+
+use crate::ids::request_coded_value;
+use crate::short_names::{ShortNameIssue, ShortNameRule, short_name_rules};
+use crate::types::{
+    Addressing, DataType, DiagClassType, DiagCodedType, DiagCodedTypeData, DiagCodedTypeName,
+    DiagComm, DiagDatabase, DiagLayer, DiagService, Dop, DopData, DopRef, Dtc, Param, ParamData,
+    ParamType, Request, Response, ResponseType, Text, TransmissionMode,
+};
+use crate::validate::ValidationError;
+
+/// DID services: ReadDataByIdentifier, WriteDataByIdentifier and
+/// InputOutputControlByIdentifier.
+const DID_SIDS: [u8; 3] = [0x22, 0x2E, 0x2F];
+const READ_DATA_BY_IDENTIFIER: u8 = 0x22;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum EditError {
+    #[error("No layer '{0}'")]
+    UnknownLayer(String),
+    #[error("No service '{service}' in layer '{layer}'")]
+    UnknownService { layer: String, service: String },
+    #[error("Layer '{layer}' already has a service '{service}'")]
+    DuplicateService { layer: String, service: String },
+    #[error("DID 0x{did:04X} is already read by '{service}' in layer '{layer}'")]
+    DuplicateDid {
+        layer: String,
+        did: u16,
+        service: String,
+    },
+    #[error("No DTC 0x{0:06X}")]
+    UnknownDtc(u32),
+}
+
+/// Edits a database in place, one transaction at a time.
+#[derive(Debug)]
+pub struct Editor<'a> {
+    db: &'a mut DiagDatabase,
+    index: Index,
+    /// Pending edits, oldest first.
+    undo: Vec<Undo>,
+    touched: BTreeSet<LayerKey>,
+}
+
+impl<'a> Editor<'a> {
+    /// Index `db` for editing.
+    pub fn new(db: &'a mut DiagDatabase) -> Self {
+        let index = Index::build(db);
+        Self {
+            db,
+            index,
+            undo: vec![],
+            touched: BTreeSet::new(),
+        }
+    }
+
+    /// The database, pending edits included.
+    pub fn database(&self) -> &DiagDatabase {
+        self.db
+    }
+
+    /// Whether there are edits to commit or roll back.
+    pub fn has_pending(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Add a ReadDataByIdentifier service `<name>_Read` for `did` to `layer`,
+    /// returning the value in a parameter named `name` and decoded by `dop`.
+    pub fn add_did(
+        &mut self,
+        layer: &str,
+        did: u16,
+        name: &str,
+        dop: Dop,
+    ) -> Result<(), EditError> {
+        let key = self.index.layer(layer)?;
+        let service = format!("{name}_Read");
+        let entry = &self.index.entries[&key];
+        if entry.services.contains_key(&service) {
+            return Err(EditError::DuplicateService {
+                layer: layer.into(),
+                service,
+            });
+        }
+        if let Some(existing) = entry.dids.get(&(u32::from(did), READ_DATA_BY_IDENTIFIER)) {
+            return Err(EditError::DuplicateDid {
+                layer: layer.into(),
+                did,
+                service: existing.clone(),
+            });
+        }
+
+        let services = &mut layer_mut(self.db, key).diag_services;
+        services.push(read_did_service(did, name, dop));
+        let position = services.len() - 1;
+        self.index
+            .entries
+            .get_mut(&key)
+            .expect("indexed layer")
+            .add(&services[position], position);
+        self.undo.push(Undo::Insert {
+            layer: key,
+            position,
+        });
+        self.touched.insert(key);
+        Ok(())
+    }
+
+    /// Remove the service `service` from `layer`.
+    pub fn remove_service(&mut self, layer: &str, service: &str) -> Result<(), EditError> {
+        let key = self.index.layer(layer)?;
+        let Some(&position) = self.index.entries[&key].services.get(service) else {
+            return Err(EditError::UnknownService {
+                layer: layer.into(),
+                service: service.into(),
+            });
+        };
+        let removed = layer_mut(self.db, key).diag_services.remove(position);
+        self.undo.push(Undo::Remove {
+            layer: key,
+            position,
+            service: Box::new(removed),
+        });
+        self.index.reindex(self.db, key);
+        self.touched.insert(key);
+        Ok(())
+    }
+
+    /// Set the text of the DTC `trouble_code`, keeping its text ID. Copies of
+    /// the DTC in the DTC DOPs of services are updated too.
+    pub fn update_dtc_text(&mut self, trouble_code: u32, text: &str) -> Result<(), EditError> {
+        let Some(&position) = self.index.dtcs.get(&trouble_code) else {
+            return Err(EditError::UnknownDtc(trouble_code));
+        };
+        let dtc = &mut self.db.dtcs[position];
+        self.undo.push(Undo::DtcText {
+            position,
+            text: dtc.text.clone(),
+        });
+        set_text(dtc, text);
+
+        for (&key, entry) in &self.index.entries {
+            let Some(services) = entry.dtc_users.get(&trouble_code) else {
+                continue;
+            };
+            for &position in services {
+                let service = &mut layer_mut(self.db, key).diag_services[position];
+                self.undo.push(Undo::Replace {
+                    layer: key,
+                    position,
+                    service: Box::new(service.clone()),
+                });
+                for dtc in dtc_dop_entries_mut(service).filter(|d| d.trouble_code == trouble_code) {
+                    set_text(dtc, text);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the layers the pending edits touched and make the edits
+    /// final. When a touched layer is invalid, including one that was
+    /// invalid before, every pending edit is rolled back and the problems
+    /// are returned.
+    pub fn commit(&mut self) -> Result<(), Vec<ValidationError>> {
+        let errors = self.validate_touched();
+        if !errors.is_empty() {
+            self.rollback();
+            return Err(errors);
+        }
+        self.undo.clear();
+        self.touched.clear();
+        Ok(())
+    }
+
+    /// Undo every pending edit.
+    pub fn rollback(&mut self) {
+        let mut changed = BTreeSet::new();
+        while let Some(undo) = self.undo.pop() {
+            match undo {
+                Undo::Insert { layer, position } => {
+                    layer_mut(self.db, layer).diag_services.remove(position);
+                    changed.insert(layer);
+                }
+                Undo::Remove {
+                    layer,
+                    position,
+                    service,
+                } => {
+                    layer_mut(self.db, layer)
+                        .diag_services
+                        .insert(position, *service);
+                    changed.insert(layer);
+                }
+                Undo::Replace {
+                    layer,
+                    position,
+                    service,
+                } => layer_mut(self.db, layer).diag_services[position] = *service,
+                Undo::DtcText { position, text } => self.db.dtcs[position].text = text,
+            }
+        }
+        for key in changed {
+            self.index.reindex(self.db, key);
+        }
+        self.touched.clear();
+    }
+
+    /// The checks of [`validate_database`](crate::validate_database) that
+    /// the edits can affect, run on the touched layers only.
+    fn validate_touched(&self) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        for &key in &self.touched {
+            let layer = layer_ref(self.db, key);
+            let names = layer
+                .diag_services
+                .iter()
+                .map(|s| &s.diag_comm.short_name)
+                .chain(
+                    layer
+                        .single_ecu_jobs
+                        .iter()
+                        .map(|j| &j.diag_comm.short_name),
+                );
+            let mut seen = HashSet::new();
+            let mut has_empty = false;
+            for name in names {
+                has_empty |= name.is_empty();
+                let issue = |rule| {
+                    ValidationError::ShortName(ShortNameIssue {
+                        rule,
+                        scope: layer.short_name.to_string(),
+                        name: name.to_string(),
+                    })
+                };
+                errors.extend(short_name_rules(name).into_iter().map(issue));
+                if !name.is_empty() && !seen.insert(name) {
+                    errors.push(issue(ShortNameRule::Unique));
+                }
+            }
+            if has_empty {
+                errors.push(ValidationError::EmptyServiceName(
+                    layer.short_name.to_string(),
+                ));
+            }
+        }
+
+        // A removed service may have been the one serving an identification DID.
+        if let Some(ident) = &self.db.identification {
+            for (role, did) in &ident.dids {
+                if !self.index.serves_did(u32::from(*did)) {
+                    errors.push(ValidationError::UnknownIdentificationDid(
+                        role.clone(),
+                        *did,
+                    ));
+                }
+            }
+        }
+        errors
+    }
+}
+
+impl Drop for Editor<'_> {
+    fn drop(&mut self) {
+        if self.has_pending() {
+            self.rollback();
+        }
+    }
+}
+
+/// How to revert one edit.
+#[derive(Debug)]
+enum Undo {
+    /// A service was added at `position`.
+    Insert { layer: LayerKey, position: usize },
+    /// `service` was removed from `position`.
+    Remove {
+        layer: LayerKey,
+        position: usize,
+        service: Box<DiagService>,
+    },
+    /// The service at `position` was `service` before.
+    Replace {
+        layer: LayerKey,
+        position: usize,
+        service: Box<DiagService>,
+    },
+    /// The DTC at `position` had `text` before.
+    DtcText { position: usize, text: Option<Text> },
+}
+
+/// Position of a layer in the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum LayerKey {
+    Variant(usize),
+    FunctionalGroup(usize),
+    Protocol(usize),
+    EcuSharedData(usize),
+}
+
+fn layer_keys(db: &DiagDatabase) -> impl Iterator<Item = LayerKey> + use<> {
+    (0..db.variants.len())
+        .map(LayerKey::Variant)
+        .chain((0..db.functional_groups.len()).map(LayerKey::FunctionalGroup))
+        .chain((0..db.protocols.len()).map(LayerKey::Protocol))
+        .chain((0..db.ecu_shared_datas.len()).map(LayerKey::EcuSharedData))
+}
+
+fn layer_ref(db: &DiagDatabase, key: LayerKey) -> &DiagLayer {
+    match key {
+        LayerKey::Variant(i) => &db.variants[i].diag_layer,
+        LayerKey::FunctionalGroup(i) => &db.functional_groups[i].diag_layer,
+        LayerKey::Protocol(i) => &db.protocols[i].diag_layer,
+        LayerKey::EcuSharedData(i) => &db.ecu_shared_datas[i].diag_layer,
+    }
+}
+
+fn layer_mut(db: &mut DiagDatabase, key: LayerKey) -> &mut DiagLayer {
+    match key {
+        LayerKey::Variant(i) => &mut db.variants[i].diag_layer,
+        LayerKey::FunctionalGroup(i) => &mut db.functional_groups[i].diag_layer,
+        LayerKey::Protocol(i) => &mut db.protocols[i].diag_layer,
+        LayerKey::EcuSharedData(i) => &mut db.ecu_shared_datas[i].diag_layer,
+    }
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    layers: HashMap<String, LayerKey>,
+    entries: BTreeMap<LayerKey, LayerEntry>,
+    /// Position of each DTC by trouble code (the first, if repeated).
+    dtcs: HashMap<u32, usize>,
+}
+
+/// What the editor looks up in one layer.
+#[derive(Debug, Default)]
+struct LayerEntry {
+    /// Service positions by short name.
+    services: HashMap<String, usize>,
+    /// Services addressing a DID, by DID and service ID.
+    dids: BTreeMap<(u32, u8), String>,
+    /// Positions of the services whose DTC DOPs list a trouble code.
+    dtc_users: HashMap<u32, BTreeSet<usize>>,
+}
+
+impl Index {
+    fn build(db: &DiagDatabase) -> Self {
+        let mut index = Self::default();
+        for key in layer_keys(db) {
+            index
+                .layers
+                .entry(layer_ref(db, key).short_name.to_string())
+                .or_insert(key);
+            index.reindex(db, key);
+        }
+        for (position, dtc) in db.dtcs.iter().enumerate() {
+            index.dtcs.entry(dtc.trouble_code).or_insert(position);
+        }
+        index
+    }
+
+    fn layer(&self, name: &str) -> Result<LayerKey, EditError> {
+        self.layers
+            .get(name)
+            .copied()
+            .ok_or_else(|| EditError::UnknownLayer(name.into()))
+    }
+
+    /// Rebuild the entry of one layer after its services moved.
+    fn reindex(&mut self, db: &DiagDatabase, key: LayerKey) {
+        let mut entry = LayerEntry::default();
+        for (position, service) in layer_ref(db, key).diag_services.iter().enumerate() {
+            entry.add(service, position);
+        }
+        self.entries.insert(key, entry);
+    }
+
+    fn serves_did(&self, did: u32) -> bool {
+        self.entries
+            .values()
+            .any(|e| e.dids.range((did, 0)..=(did, u8::MAX)).next().is_some())
+    }
+}
+
+impl LayerEntry {
+    fn add(&mut self, service: &DiagService, position: usize) {
+        let name = &service.diag_comm.short_name;
+        self.services.entry(name.to_string()).or_insert(position);
+        let sid = request_coded_value(service, 0).and_then(|sid| u8::try_from(sid).ok());
+        if let Some(sid) = sid.filter(|sid| DID_SIDS.contains(sid)) {
+            if let Some(did) = request_coded_value(service, 1) {
+                self.dids
+                    .entry((did, sid))
+                    .or_insert_with(|| name.to_string());
+            }
+        }
+        for dtc in dtc_dop_entries(service) {
+            self.dtc_users
+                .entry(dtc.trouble_code)
+                .or_default()
+                .insert(position);
+        }
+    }
+}
+
+fn set_text(dtc: &mut Dtc, text: &str) {
+    dtc.text
+        .get_or_insert_with(|| Text {
+            value: String::new(),
+            ti: String::new(),
+        })
+        .value = text.into();
+}
+
+/// The DOP a parameter is decoded with, if any.
+fn param_dop(param: &Param) -> Option<&Dop> {
+    match param.specific_data.as_ref()? {
+        ParamData::Value { dop, .. }
+        | ParamData::PhysConst { dop, .. }
+        | ParamData::System { dop, .. }
+        | ParamData::LengthKeyRef { dop } => Some(dop),
+        _ => None,
+    }
+}
+
+fn param_dop_mut(param: &mut Param) -> Option<&mut Dop> {
+    match param.specific_data.as_mut()? {
+        ParamData::Value { dop, .. }
+        | ParamData::PhysConst { dop, .. }
+        | ParamData::System { dop, .. }
+        | ParamData::LengthKeyRef { dop } => Some(dop),
+        _ => None,
+    }
+}
+
+/// DTCs listed by the DTC DOPs of the service's request and response params.
+fn dtc_dop_entries(service: &DiagService) -> impl Iterator<Item = &Dtc> {
+    let responses = service.pos_responses.iter().chain(&service.neg_responses);
+    service
+        .request
+        .iter()
+        .flat_map(|r| &r.params)
+        .chain(responses.flat_map(|r| &r.params))
+        .filter_map(param_dop)
+        .filter_map(|dop| match &dop.specific_data {
+            Some(DopData::DtcDop { dtcs, .. }) => Some(dtcs),
+            _ => None,
+        })
+        .flatten()
+}
+
+fn dtc_dop_entries_mut(service: &mut DiagService) -> impl Iterator<Item = &mut Dtc> {
+    let responses = service
+        .pos_responses
+        .iter_mut()
+        .chain(&mut service.neg_responses);
+    service
+        .request
+        .iter_mut()
+        .flat_map(|r| &mut r.params)
+        .chain(responses.flat_map(|r| &mut r.params))
+        .filter_map(param_dop_mut)
+        .filter_map(|dop| match &mut dop.specific_data {
+            Some(DopData::DtcDop { dtcs, .. }) => Some(dtcs),
+            _ => None,
+        })
+        .flatten()
+}
+
+fn coded_type(bit_length: u32) -> DiagCodedType {
+    DiagCodedType {
+        type_name: DiagCodedTypeName::StandardLengthType,
+        base_type_encoding: "unsigned".into(),
+        base_data_type: DataType::AUint32,
+        is_high_low_byte_order: true,
+        specific_data: Some(DiagCodedTypeData::StandardLength {
+            bit_length,
+            bit_mask: vec![],
+            condensed: false,
+        }),
+    }
+}
+
+fn coded_param(id: u32, name: &str, semantic: &str, byte: u32, value: u32, bits: u32) -> Param {
+    Param {
+        id,
+        param_type: ParamType::CodedConst,
+        short_name: name.into(),
+        semantic: semantic.into(),
+        byte_position: Some(byte),
+        bit_position: Some(0),
+        specific_data: Some(ParamData::CodedConst {
+            coded_value: value.to_string(),
+            diag_coded_type: coded_type(bits),
+        }),
+        ..Default::default()
+    }
+}
+
+/// A ReadDataByIdentifier service laid out like the ones YAML generates.
+fn read_did_service(did: u16, name: &str, dop: Dop) -> DiagService {
+    let sid = u32::from(READ_DATA_BY_IDENTIFIER);
+    DiagService {
+        diag_comm: DiagComm {
+            short_name: format!("{name}_Read").into(),
+            diag_class_type: DiagClassType::StartComm,
+            is_executable: true,
+            ..Default::default()
+        },
+        request: Some(Request {
+            params: vec![
+                coded_param(0, "SID_RQ", "SERVICE-ID", 0, sid, 8),
+                coded_param(1, "DID_RQ", "DID", 1, u32::from(did), 16),
+            ],
+            sdgs: None,
+        }),
+        pos_responses: vec![Response {
+            response_type: ResponseType::PosResponse,
+            params: vec![
+                coded_param(0, "SID_PR", "SERVICE-ID", 0, sid + 0x40, 8),
+                Param {
+                    id: 1,
+                    param_type: ParamType::MatchingRequestParam,
+                    short_name: "DID_PR".into(),
+                    semantic: "DID".into(),
+                    byte_position: Some(1),
+                    bit_position: Some(0),
+                    specific_data: Some(ParamData::MatchingRequestParam {
+                        request_byte_pos: 1,
+                        byte_length: 2,
+                    }),
+                    ..Default::default()
+                },
+                Param {
+                    id: 2,
+                    param_type: ParamType::Value,
+                    short_name: name.into(),
+                    semantic: "DATA".into(),
+                    byte_position: Some(3),
+                    specific_data: Some(ParamData::Value {
+                        physical_default_value: String::new(),
+                        dop: DopRef::new(dop),
+                    }),
+                    ..Default::default()
+                },
+            ],
+            sdgs: None,
+            pos_response_suffix: None,
+            audience: None,
+        }],
+        addressing: Addressing::Physical,
+        transmission_mode: TransmissionMode::SendAndReceive,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, dop, service};
+    use crate::types::DopType;
+
+    fn dtc(code: u32, text: &str) -> Dtc {
+        Dtc {
+            short_name: format!("DTC_{code:06X}").into(),
+            trouble_code: code,
+            text: Some(Text {
+                value: text.into(),
+                ti: "TI_1".into(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// A layer `Base` with a fault memory service whose response lists the
+    /// database's DTC.
+    fn database() -> DiagDatabase {
+        let fault_memory = DiagService {
+            pos_responses: vec![Response {
+                response_type: ResponseType::PosResponse,
+                params: vec![Param {
+                    short_name: "Dtc".into(),
+                    specific_data: Some(ParamData::Value {
+                        physical_default_value: String::new(),
+                        dop: DopRef::new(Dop {
+                            dop_type: DopType::Dtc,
+                            short_name: "DtcDop".into(),
+                            sdgs: None,
+                            specific_data: Some(DopData::DtcDop {
+                                diag_coded_type: None,
+                                physical_type: None,
+                                compu_method: None,
+                                dtcs: vec![dtc(0x01_2300, "Throttle high")],
+                                is_visible: true,
+                            }),
+                        }),
+                    }),
+                    ..Default::default()
+                }],
+                sdgs: None,
+                pos_response_suffix: None,
+                audience: None,
+            }],
+            ..service("FaultMemory_Read", "")
+        };
+        let services = vec![fault_memory, read_did_service(0xF190, "Vin", dop("Vin"))];
+        DiagDatabase {
+            ecu_name: "ECU".into(),
+            dtcs: vec![dtc(0x01_2300, "Throttle high")],
+            ..test_support::database(services)
+        }
+    }
+
+    fn service_names(db: &DiagDatabase) -> Vec<&str> {
+        db.variants[0]
+            .diag_layer
+            .diag_services
+            .iter()
+            .map(|s| s.diag_comm.short_name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_edits_keep_the_index_current() {
+        let mut db = database();
+        let mut editor = Editor::new(&mut db);
+        editor
+            .add_did("Base", 0x0200, "Speed", dop("Speed"))
+            .unwrap();
+        assert_eq!(
+            editor.add_did("Base", 0xF190, "Vin2", dop("Vin")),
+            Err(EditError::DuplicateDid {
+                layer: "Base".into(),
+                did: 0xF190,
+                service: "Vin_Read".into(),
+            })
+        );
+        editor.remove_service("Base", "FaultMemory_Read").unwrap();
+        // Positions moved; the index must still find the last service.
+        editor.remove_service("Base", "Speed_Read").unwrap();
+        assert_eq!(
+            editor
+                .remove_service("Base", "Speed_Read")
+                .unwrap_err()
+                .to_string(),
+            "No service 'Speed_Read' in layer 'Base'"
+        );
+        assert_eq!(
+            editor.remove_service("App", "Vin_Read"),
+            Err(EditError::UnknownLayer("App".into()))
+        );
+        editor.commit().unwrap();
+        assert!(!editor.has_pending());
+        drop(editor);
+        assert_eq!(service_names(&db), ["Vin_Read"]);
+        assert!(crate::validate_database(&db).is_ok());
+        assert_eq!(crate::ids::used_ids(&db).len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_commit_rolls_back() {
+        let mut db = database();
+        let before = db.clone();
+        let mut editor = Editor::new(&mut db);
+        editor.remove_service("Base", "Vin_Read").unwrap();
+        editor
+            .add_did("Base", 0x0200, "Engine Speed", dop("Speed"))
+            .unwrap();
+        let errors = editor.commit().unwrap_err();
+        assert_eq!(
+            errors.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "short name 'Engine Speed_Read' in 'Base' has characters other than A-Z, a-z, 0-9 and _"
+            ]
+        );
+        assert_eq!(editor.database(), &before);
+        // The index was restored along with the services.
+        editor.remove_service("Base", "Vin_Read").unwrap();
+        drop(editor);
+        assert_eq!(db, before);
+    }
+
+    #[test]
+    fn test_update_dtc_text_updates_dop_copies() {
+        let mut db = database();
+        let mut editor = Editor::new(&mut db);
+        editor
+            .update_dtc_text(0x01_2300, "Throttle too high")
+            .unwrap();
+        assert_eq!(
+            editor.update_dtc_text(0x01_2400, "x"),
+            Err(EditError::UnknownDtc(0x01_2400))
+        );
+        editor.commit().unwrap();
+        drop(editor);
+        let texts: Vec<_> = dtc_dop_entries(&db.variants[0].diag_layer.diag_services[0])
+            .chain(&db.dtcs)
+            .map(|d| d.text.as_ref().unwrap())
+            .collect();
+        assert_eq!(texts.len(), 2);
+        for text in texts {
+            assert_eq!(text.value, "Throttle too high");
+            assert_eq!(text.ti, "TI_1");
+        }
+    }
+}
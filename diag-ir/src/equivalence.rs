@@ -0,0 +1,205 @@
+//! Canonical comparison of databases, for checking that a conversion kept
+//! everything.
+//!
+//! Both databases are brought into [`normalize`] order and compared field by
+//! field. Absent and empty values are treated alike: `None`, `""`, `[]` and
+//! a struct made only of such values all mean "nothing", since formats
+//! differ in which of them they write for missing data. Everything else,
+//! including `0` and `false`, must match.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::normalize::normalize;
+use crate::types::DiagDatabase;
+
+/// Longest rendering of a value in a [`FieldDifference`].
+const MAX_VALUE_LEN: usize = 80;
+
+/// One field that differs between two databases.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldDifference {
+    /// Slash-separated location, with list items named by their short name
+    /// where all items of the list have distinct ones, e.g.
+    /// `variants/Base/diag_layer/diag_services/Read_VIN/request/params/VIN/byte_position`.
+    pub path: String,
+    /// The value in the first database as JSON, shortened; `(none)` when absent.
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for FieldDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.path, self.expected, self.actual)
+    }
+}
+
+/// `db` in canonical order, see [`normalize`].
+pub fn canonical_form(db: &DiagDatabase) -> DiagDatabase {
+    let mut db = db.clone();
+    normalize(&mut db);
+    db
+}
+
+/// Every field in which `actual` differs from `expected` once both are in
+/// canonical form. Where a whole list item or struct is missing on one side,
+/// it is reported once rather than field by field.
+pub fn compare_databases(expected: &DiagDatabase, actual: &DiagDatabase) -> Vec<FieldDifference> {
+    let to_value = |db: &DiagDatabase| {
+        serde_json::to_value(canonical_form(db)).expect("the IR has string map keys only")
+    };
+    let mut differences = Vec::new();
+    compare_values("", &to_value(expected), &to_value(actual), &mut differences);
+    differences
+}
+
+/// Whether [`compare_databases`] finds no difference.
+pub fn equivalent(a: &DiagDatabase, b: &DiagDatabase) -> bool {
+    compare_databases(a, b).is_empty()
+}
+
+fn compare_values(path: &str, expected: &Value, actual: &Value, out: &mut Vec<FieldDifference>) {
+    match (expected, actual) {
+        (Value::Object(a), Value::Object(b)) => {
+            let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+            for key in keys {
+                compare_values(
+                    &join(path, key),
+                    a.get(key).unwrap_or(&Value::Null),
+                    b.get(key).unwrap_or(&Value::Null),
+                    out,
+                );
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            if let (Some(names_a), Some(names_b)) = (unique_names(a), unique_names(b)) {
+                // Match items by name, so one missing item is one difference.
+                for (name, x) in names_a.iter().zip(a) {
+                    let y = names_b.iter().position(|n| n == name).map(|i| &b[i]);
+                    compare_values(&join(path, name), x, y.unwrap_or(&Value::Null), out);
+                }
+                for (name, y) in names_b.iter().zip(b) {
+                    if !names_a.contains(name) {
+                        compare_values(&join(path, name), &Value::Null, y, out);
+                    }
+                }
+                return;
+            }
+            for i in 0..a.len().max(b.len()) {
+                let (x, y) = (a.get(i), b.get(i));
+                compare_values(
+                    &join(path, &i.to_string()),
+                    x.unwrap_or(&Value::Null),
+                    y.unwrap_or(&Value::Null),
+                    out,
+                );
+            }
+        }
+        _ if is_empty(expected) && is_empty(actual) => {}
+        _ if expected == actual => {}
+        _ => out.push(FieldDifference {
+            path: path.to_string(),
+            expected: render(expected),
+            actual: render(actual),
+        }),
+    }
+}
+
+fn join(path: &str, segment: &str) -> String {
+    if path.is_empty() {
+        segment.to_string()
+    } else {
+        format!("{path}/{segment}")
+    }
+}
+
+/// The names of `items` if every item has one and no two share it.
+fn unique_names(items: &[Value]) -> Option<Vec<&str>> {
+    let names: Vec<&str> = items.iter().map(item_name).collect::<Option<_>>()?;
+    let distinct: BTreeSet<&str> = names.iter().copied().collect();
+    (distinct.len() == names.len()).then_some(names)
+}
+
+/// The short name of a list item: its own, or that of its layer or
+/// diag comm.
+fn item_name(item: &Value) -> Option<&str> {
+    ["short_name", "name"]
+        .iter()
+        .find_map(|key| item.get(key))
+        .or_else(|| {
+            ["diag_layer", "diag_comm"]
+                .iter()
+                .find_map(|key| item.get(key)?.get("short_name"))
+        })
+        .and_then(Value::as_str)
+        .filter(|name| !name.is_empty())
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.is_empty(),
+        Value::Array(items) => items.is_empty(),
+        Value::Object(fields) => fields.values().all(is_empty),
+        Value::Bool(_) | Value::Number(_) => false,
+    }
+}
+
+fn render(value: &Value) -> String {
+    if is_empty(value) {
+        return "(none)".into();
+    }
+    let text = value.to_string();
+    match text.char_indices().nth(MAX_VALUE_LEN) {
+        Some((cut, _)) => format!("{}...", &text[..cut]),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, services};
+    use crate::types::*;
+
+    fn database(names: &[&str]) -> DiagDatabase {
+        DiagDatabase {
+            ecu_name: "ECU".into(),
+            ..test_support::database(services(names))
+        }
+    }
+
+    #[test]
+    fn test_order_and_empty_values_do_not_count() {
+        let a = database(&["Read", "Write"]);
+        let mut b = database(&["Write", "Read"]);
+        b.variants[0].diag_layer.sdgs = Some(Sdgs { sdgs: vec![] });
+        b.variants[0].diag_layer.description = Some(String::new());
+        assert!(equivalent(&a, &b), "{:?}", compare_databases(&a, &b));
+    }
+
+    #[test]
+    fn test_differences_are_located_by_name() {
+        let a = database(&["Read", "Write"]);
+        let mut b = database(&["Read", "Write"]);
+        b.variants[0].diag_layer.diag_services[1].diag_comm.semantic = "STORE".into();
+        b.variants[0].diag_layer.diag_services.remove(0);
+        let differences = compare_databases(&a, &b);
+        let paths: Vec<&str> = differences.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "variants/Base/diag_layer/diag_services/Read",
+                "variants/Base/diag_layer/diag_services/Write/diag_comm/semantic",
+            ]
+        );
+        assert_eq!(differences[0].actual, "(none)");
+        assert_eq!(
+            differences[1].to_string().rsplit_once(": ").unwrap().1,
+            "(none) -> \"STORE\""
+        );
+    }
+}
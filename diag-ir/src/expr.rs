@@ -0,0 +1,742 @@
+//! Filter expressions over services and DTCs.
+//!
+//! ```text
+//! service.semantic == "ROUTINE" && service.access.security >= "level1"
+//! service.sid == 0x22 || service.name == "Read_*"
+//! !(dtc.level > 2)
+//! service.layer == EV_Engine && !service.access.authentication
+//! ```
+//!
+//! A comparison is `field op value` with `==`, `!=`, `<`, `<=`, `>` or
+//! `>=`; values are quoted strings or bare words. `!` negates, `&&` binds
+//! tighter than `||`, and parentheses group. A field on its own holds when
+//! the element has a value for it, so `service.access.security` keeps the
+//! services that require some security level.
+//!
+//! `==` compares like [`query`](crate::query) conditions: `*` matches any
+//! run of characters in names, semantics compare by their [`Semantic`]
+//! classification and numbers in decimal or `0x` hex. Security levels
+//! compare by the number in their name when both have one, so `level1`
+//! equals `Level_01`; the ordering operators apply only to them and to
+//! numbers. The access fields list every state a service accepts: a
+//! comparison holds when one of them meets it, `!=` when none equals the
+//! value.
+//!
+//! An expression tests either services or DTCs, not both.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::access::{AccessRequirements, access_matrix};
+use crate::flash::service_sid;
+use crate::numbers::parse_uint;
+use crate::query::wildcard_match;
+use crate::semantic::Semantic;
+use crate::types::{DiagDatabase, DiagLayer, DiagService, Dtc};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ExprError {
+    #[error("invalid filter expression '{expression}' at offset {offset}: {reason}")]
+    Syntax {
+        expression: String,
+        offset: usize,
+        reason: String,
+    },
+    #[error("unknown field '{field}' (expected {})", expected_fields())]
+    UnknownField { field: String },
+    #[error("{field} values cannot be compared with '{op}'")]
+    Unordered { field: ExprField, op: CompareOp },
+    #[error("'{0}' tests both service and DTC fields")]
+    MixedSubjects(String),
+}
+
+/// What an expression tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExprSubject {
+    /// Diagnostic services of every layer.
+    Service,
+    Dtc,
+}
+
+/// A field an expression tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExprField {
+    ServiceName,
+    ServiceSemantic,
+    /// Service ID of the request.
+    ServiceSid,
+    /// Short name of the layer defining the service.
+    ServiceLayer,
+    /// Sessions the service may be sent in.
+    Session,
+    /// Security levels the service may be sent in.
+    Security,
+    /// Authentication roles the service may be sent in.
+    Authentication,
+    DtcName,
+    /// Trouble code; also matches the display code, e.g. `P0100`.
+    DtcCode,
+    DtcLevel,
+}
+
+const FIELDS: [ExprField; 10] = [
+    ExprField::ServiceName,
+    ExprField::ServiceSemantic,
+    ExprField::ServiceSid,
+    ExprField::ServiceLayer,
+    ExprField::Session,
+    ExprField::Security,
+    ExprField::Authentication,
+    ExprField::DtcName,
+    ExprField::DtcCode,
+    ExprField::DtcLevel,
+];
+
+fn expected_fields() -> String {
+    FIELDS.map(ExprField::as_str).join(", ")
+}
+
+impl ExprField {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ServiceName => "service.name",
+            Self::ServiceSemantic => "service.semantic",
+            Self::ServiceSid => "service.sid",
+            Self::ServiceLayer => "service.layer",
+            Self::Session => "service.access.session",
+            Self::Security => "service.access.security",
+            Self::Authentication => "service.access.authentication",
+            Self::DtcName => "dtc.name",
+            Self::DtcCode => "dtc.code",
+            Self::DtcLevel => "dtc.level",
+        }
+    }
+
+    pub fn subject(self) -> ExprSubject {
+        match self {
+            Self::DtcName | Self::DtcCode | Self::DtcLevel => ExprSubject::Dtc,
+            _ => ExprSubject::Service,
+        }
+    }
+
+    /// Whether `<`, `<=`, `>` and `>=` apply to the field.
+    fn is_ordered(self) -> bool {
+        matches!(
+            self,
+            Self::ServiceSid | Self::Security | Self::DtcCode | Self::DtcLevel
+        )
+    }
+
+    /// The number a value of the field is compared by, if it has one.
+    fn number(self, value: &str) -> Option<u64> {
+        match self {
+            Self::ServiceSid | Self::DtcCode | Self::DtcLevel => parse_uint(value),
+            Self::Security => level_number(value),
+            _ => None,
+        }
+    }
+
+    /// Whether the actual value `actual` equals the expression value
+    /// `expected`.
+    fn equals(self, expected: &str, actual: &str) -> bool {
+        if self == Self::ServiceSemantic {
+            if let (Some(e), Some(a)) = (Semantic::parse(expected), Semantic::parse(actual)) {
+                return e == a;
+            }
+        }
+        match (self.number(expected), self.number(actual)) {
+            (Some(e), Some(a)) => e == a,
+            _ => wildcard_match(expected, actual),
+        }
+    }
+}
+
+impl fmt::Display for ExprField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ExprField {
+    type Err = ExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FIELDS
+            .into_iter()
+            .find(|f| f.as_str() == s)
+            .ok_or_else(|| ExprError::UnknownField {
+                field: s.to_string(),
+            })
+    }
+}
+
+/// The number at the end of a security level name: 1 for `Level_01`.
+fn level_number(name: &str) -> Option<u64> {
+    let digits = name.len() - name.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    name[name.len() - digits..].parse().ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+        }
+    }
+
+    fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            Self::Eq => ordering.is_eq(),
+            Self::Ne => ordering.is_ne(),
+            Self::Lt => ordering.is_lt(),
+            Self::Le => ordering.is_le(),
+            Self::Gt => ordering.is_gt(),
+            Self::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Compare {
+        field: ExprField,
+        op: CompareOp,
+        value: String,
+    },
+    /// A field on its own: the element has a value for it.
+    Present(ExprField),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Evaluate against an element whose values of a field `values` gives.
+    fn eval(&self, values: &dyn Fn(ExprField) -> Vec<String>) -> bool {
+        match self {
+            Self::Compare {
+                field,
+                op: CompareOp::Eq,
+                value,
+            } => values(*field).iter().any(|a| field.equals(value, a)),
+            Self::Compare {
+                field,
+                op: CompareOp::Ne,
+                value,
+            } => !values(*field).iter().any(|a| field.equals(value, a)),
+            Self::Compare { field, op, value } => {
+                let Some(expected) = field.number(value) else {
+                    return false;
+                };
+                values(*field)
+                    .iter()
+                    .filter_map(|a| field.number(a))
+                    .any(|a| op.accepts(a.cmp(&expected)))
+            }
+            Self::Present(field) => !values(*field).is_empty(),
+            Self::Not(e) => !e.eval(values),
+            Self::And(a, b) => a.eval(values) && b.eval(values),
+            Self::Or(a, b) => a.eval(values) || b.eval(values),
+        }
+    }
+
+    fn fields(&self, out: &mut Vec<ExprField>) {
+        match self {
+            Self::Compare { field, .. } | Self::Present(field) => out.push(*field),
+            Self::Not(e) => e.fields(out),
+            Self::And(a, b) | Self::Or(a, b) => {
+                a.fields(out);
+                b.fields(out);
+            }
+        }
+    }
+}
+
+/// A parsed filter expression. See the [module docs](self) for the syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterExpr {
+    source: String,
+    expr: Expr,
+    subject: ExprSubject,
+}
+
+impl FilterExpr {
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    pub fn subject(&self) -> ExprSubject {
+        self.subject
+    }
+
+    /// Whether `svc`, with the access requirements `access` of its row in
+    /// the [`access_matrix`], matches. Always false for a DTC expression.
+    pub fn matches_service(&self, svc: &DiagService, access: &AccessRequirements) -> bool {
+        self.subject == ExprSubject::Service
+            && self.expr.eval(&|field| {
+                let values = match field {
+                    ExprField::ServiceName => vec![svc.diag_comm.short_name.to_string()],
+                    ExprField::ServiceSemantic => vec![
+                        Semantic::of(svc)
+                            .map_or_else(|| svc.diag_comm.semantic.to_string(), |s| s.to_string()),
+                    ],
+                    ExprField::ServiceSid => service_sid(svc)
+                        .map(|s| s.to_string())
+                        .into_iter()
+                        .collect(),
+                    ExprField::ServiceLayer => vec![access.layer.clone()],
+                    ExprField::Session => access.sessions.clone(),
+                    ExprField::Security => access.security_levels.clone(),
+                    ExprField::Authentication => access.authentication_roles.clone(),
+                    ExprField::DtcName | ExprField::DtcCode | ExprField::DtcLevel => vec![],
+                };
+                values.into_iter().filter(|v| !v.is_empty()).collect()
+            })
+    }
+
+    /// Whether `dtc` matches. Always false for a service expression.
+    pub fn matches_dtc(&self, dtc: &Dtc) -> bool {
+        self.subject == ExprSubject::Dtc
+            && self.expr.eval(&|field| {
+                let values = match field {
+                    ExprField::DtcName => vec![dtc.short_name.to_string()],
+                    ExprField::DtcCode => vec![
+                        dtc.trouble_code.to_string(),
+                        dtc.display_trouble_code.clone(),
+                    ],
+                    ExprField::DtcLevel => dtc.level.map(|l| l.to_string()).into_iter().collect(),
+                    _ => vec![],
+                };
+                values.into_iter().filter(|v| !v.is_empty()).collect()
+            })
+    }
+}
+
+impl fmt::Display for FilterExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.source.trim())
+    }
+}
+
+impl FromStr for FilterExpr {
+    type Err = ExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            source: s,
+            tokens: tokenize(s)?,
+            next: 0,
+        };
+        let expr = parser.or()?;
+        if let Some((offset, _)) = parser.tokens.get(parser.next) {
+            return Err(parser.syntax(*offset, "expected '&&', '||' or the end"));
+        }
+        let mut fields = Vec::new();
+        expr.fields(&mut fields);
+        let subject = fields.first().map_or(ExprSubject::Service, |f| f.subject());
+        if fields.iter().any(|f| f.subject() != subject) {
+            return Err(ExprError::MixedSubjects(s.to_string()));
+        }
+        Ok(Self {
+            source: s.to_string(),
+            expr,
+            subject,
+        })
+    }
+}
+
+/// Keep the services (for a service expression) or DTCs (for a DTC
+/// expression) matching `filter`. Returns the number removed.
+pub fn filter_by_expr(db: &mut DiagDatabase, filter: &FilterExpr) -> usize {
+    match filter.subject {
+        ExprSubject::Dtc => {
+            let before = db.dtcs.len();
+            db.dtcs.retain(|dtc| filter.matches_dtc(dtc));
+            before - db.dtcs.len()
+        }
+        ExprSubject::Service => {
+            // The matrix has a row per service, in layer order.
+            let access = access_matrix(db);
+            let services = layers(db).flat_map(|layer| &layer.diag_services);
+            let keep: Vec<bool> = services
+                .zip(&access)
+                .map(|(svc, access)| filter.matches_service(svc, access))
+                .collect();
+            let removed = keep.iter().filter(|k| !**k).count();
+            let mut keep = keep.into_iter();
+            let layers = db
+                .variants
+                .iter_mut()
+                .map(|v| &mut v.diag_layer)
+                .chain(db.functional_groups.iter_mut().map(|fg| &mut fg.diag_layer))
+                .chain(db.protocols.iter_mut().map(|p| &mut p.diag_layer))
+                .chain(db.ecu_shared_datas.iter_mut().map(|e| &mut e.diag_layer));
+            for layer in layers {
+                layer.diag_services.retain(|_| keep.next().unwrap_or(true));
+            }
+            removed
+        }
+    }
+}
+
+fn layers(db: &DiagDatabase) -> impl Iterator<Item = &DiagLayer> {
+    db.variants
+        .iter()
+        .map(|v| &v.diag_layer)
+        .chain(db.functional_groups.iter().map(|fg| &fg.diag_layer))
+        .chain(db.protocols.iter().map(|p| &p.diag_layer))
+        .chain(db.ecu_shared_datas.iter().map(|e| &e.diag_layer))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    Op(CompareOp),
+    /// A field name or unquoted value.
+    Word(String),
+    Quoted(String),
+}
+
+/// Characters that end an unquoted word.
+const DELIMITERS: &str = "()!=<>&|\"";
+
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, ExprError> {
+    let syntax = |offset: usize, reason: &str| ExprError::Syntax {
+        expression: s.to_string(),
+        offset,
+        reason: reason.to_string(),
+    };
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        let mut followed_by = |next: char| chars.next_if(|(_, c)| *c == next).is_some();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' if followed_by('&') => Token::And,
+            '|' if followed_by('|') => Token::Or,
+            '=' if followed_by('=') => Token::Op(CompareOp::Eq),
+            '!' if followed_by('=') => Token::Op(CompareOp::Ne),
+            '!' => Token::Not,
+            '<' if followed_by('=') => Token::Op(CompareOp::Le),
+            '<' => Token::Op(CompareOp::Lt),
+            '>' if followed_by('=') => Token::Op(CompareOp::Ge),
+            '>' => Token::Op(CompareOp::Gt),
+            '&' => return Err(syntax(offset, "expected '&&'")),
+            '|' => return Err(syntax(offset, "expected '||'")),
+            '=' => return Err(syntax(offset, "expected '=='")),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) => text.push(c),
+                            None => return Err(syntax(offset, "unterminated string")),
+                        },
+                        Some((_, c)) => text.push(c),
+                        None => return Err(syntax(offset, "unterminated string")),
+                    }
+                }
+                Token::Quoted(text)
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some((_, c)) =
+                    chars.next_if(|(_, c)| !c.is_whitespace() && !DELIMITERS.contains(*c))
+                {
+                    word.push(c);
+                }
+                Token::Word(word)
+            }
+        };
+        tokens.push((offset, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+}
+
+impl Parser<'_> {
+    fn syntax(&self, offset: usize, reason: &str) -> ExprError {
+        ExprError::Syntax {
+            expression: self.source.to_string(),
+            offset,
+            reason: reason.to_string(),
+        }
+    }
+
+    /// The offset of the next token, or the end of the source.
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map_or(self.source.len(), |(offset, _)| *offset)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.tokens.get(self.next).is_some_and(|(_, t)| t == token);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn take(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).map(|(_, t)| t.clone());
+        self.next += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr, ExprError> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ExprError> {
+        let mut expr = self.unary()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat(&Token::Open) {
+            let expr = self.or()?;
+            let offset = self.offset();
+            if !self.eat(&Token::Close) {
+                return Err(self.syntax(offset, "expected ')'"));
+            }
+            return Ok(expr);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ExprError> {
+        let offset = self.offset();
+        let Some(Token::Word(name)) = self.take() else {
+            return Err(self.syntax(offset, "expected a field"));
+        };
+        let field: ExprField = name.parse()?;
+        let Some((_, Token::Op(op))) = self.tokens.get(self.next) else {
+            return Ok(Expr::Present(field));
+        };
+        let op = *op;
+        self.next += 1;
+        if !matches!(op, CompareOp::Eq | CompareOp::Ne) && !field.is_ordered() {
+            return Err(ExprError::Unordered { field, op });
+        }
+        let offset = self.offset();
+        let Some(Token::Word(value) | Token::Quoted(value)) = self.take() else {
+            return Err(self.syntax(offset, "expected a value"));
+        };
+        Ok(Expr::Compare { field, op, value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{service, variant};
+    use crate::types::PreConditionStateRef;
+
+    /// A service allowed in the `(chart, state)` pairs `states`.
+    fn gated(name: &str, semantic: &str, states: &[(&str, &str)]) -> DiagService {
+        let mut svc = service(name, semantic);
+        svc.diag_comm.pre_condition_state_refs = states
+            .iter()
+            .map(|(chart, state)| PreConditionStateRef {
+                value: (*chart).into(),
+                in_param_if_short_name: String::new(),
+                in_param_path_short_name: (*state).into(),
+                state: None,
+            })
+            .collect();
+        svc
+    }
+
+    fn database() -> DiagDatabase {
+        let services = vec![
+            gated("Read_VIN", "DATA-READ", &[]),
+            gated(
+                "Erase_Memory",
+                "ROUTINE",
+                &[("Session", "Programming"), ("SecurityAccess", "Level_01")],
+            ),
+            gated(
+                "Check_Programming",
+                "ROUTINE",
+                &[("Session", "Extended"), ("SecurityAccess", "Level_03")],
+            ),
+            gated("Self_Test", "ROUTINE", &[("Session", "Extended")]),
+        ];
+        DiagDatabase {
+            variants: vec![variant("EV_Engine", services)],
+            dtcs: vec![
+                Dtc {
+                    short_name: "P0100".into(),
+                    trouble_code: 0x0100,
+                    display_trouble_code: "P0100".into(),
+                    level: Some(1),
+                    ..Default::default()
+                },
+                Dtc {
+                    short_name: "P0200".into(),
+                    trouble_code: 0x0200,
+                    display_trouble_code: "P0200".into(),
+                    level: Some(3),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn kept_services(expression: &str) -> Vec<String> {
+        let mut db = database();
+        filter_by_expr(&mut db, &expression.parse().unwrap());
+        db.variants[0]
+            .diag_layer
+            .diag_services
+            .iter()
+            .map(|s| s.diag_comm.short_name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let expr: FilterExpr = "service.name == a || !service.name == \"b c\" && service.sid"
+            .parse()
+            .unwrap();
+        let compare = |value: &str| Expr::Compare {
+            field: ExprField::ServiceName,
+            op: CompareOp::Eq,
+            value: value.into(),
+        };
+        assert_eq!(
+            *expr.expr(),
+            Expr::Or(
+                Box::new(compare("a")),
+                Box::new(Expr::And(
+                    Box::new(Expr::Not(Box::new(compare("b c")))),
+                    Box::new(Expr::Present(ExprField::ServiceSid)),
+                )),
+            )
+        );
+        assert_eq!(expr.subject(), ExprSubject::Service);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (bad, offset) in [
+            ("service.name ==", 15),
+            ("(service.name == a", 18),
+            ("service.name = a", 13),
+            ("service.name == a service.sid", 18),
+            ("&& service.sid", 0),
+            ("service.name == \"a", 16),
+            ("", 0),
+        ] {
+            match bad.parse::<FilterExpr>() {
+                Err(ExprError::Syntax { offset: at, .. }) => assert_eq!(at, offset, "{bad}"),
+                other => panic!("{bad}: {other:?}"),
+            }
+        }
+        assert!(matches!(
+            "service.kind == x".parse::<FilterExpr>(),
+            Err(ExprError::UnknownField { .. })
+        ));
+        assert!(matches!(
+            "service.name < x".parse::<FilterExpr>(),
+            Err(ExprError::Unordered { .. })
+        ));
+        assert!(matches!(
+            "service.sid == 0x31 && dtc.level > 1".parse::<FilterExpr>(),
+            Err(ExprError::MixedSubjects(_))
+        ));
+    }
+
+    #[test]
+    fn test_filter_services() {
+        assert_eq!(
+            kept_services(
+                r#"service.semantic == "ROUTINE" && service.access.security >= "level1""#
+            ),
+            ["Erase_Memory", "Check_Programming"]
+        );
+        assert_eq!(
+            kept_services("service.access.security > level1"),
+            ["Check_Programming"]
+        );
+        assert_eq!(
+            kept_services("service.access.security == level3"),
+            ["Check_Programming"]
+        );
+        assert_eq!(
+            kept_services("service.semantic == read-data || service.access.session == Prog*"),
+            ["Read_VIN", "Erase_Memory"]
+        );
+        assert_eq!(
+            kept_services("!service.access.security && service.access.session != Programming"),
+            ["Read_VIN", "Self_Test"]
+        );
+        assert_eq!(
+            kept_services("service.layer == EV_* && !(service.name == *_VIN)"),
+            ["Erase_Memory", "Check_Programming", "Self_Test"]
+        );
+    }
+
+    #[test]
+    fn test_filter_dtcs() {
+        let mut db = database();
+        let removed = filter_by_expr(&mut db, &"dtc.level >= 2".parse().unwrap());
+        assert_eq!(removed, 1);
+        assert_eq!(db.dtcs[0].short_name, "P0200");
+        assert_eq!(db.variants[0].diag_layer.diag_services.len(), 4);
+
+        let mut db = database();
+        filter_by_expr(
+            &mut db,
+            &"dtc.code == 0x100 || dtc.code == P02*".parse().unwrap(),
+        );
+        assert_eq!(db.dtcs.len(), 2);
+    }
+}
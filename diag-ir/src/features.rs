@@ -0,0 +1,321 @@
+//! Registry of the feature flags stored in FBS `EcuData.feature_flags`.
+//!
+//! The shared schema only declares `FeatureFlag::RESERVED = 0`; the values
+//! below are assigned by diag-converter and written as plain `FeatureFlag(n)`
+//! bytes, so the schema stays identical to upstream. Readers that do not know
+//! a value ignore it.
+//!
+//! | Value | Flag | Meaning |
+//! |---|---|---|
+//! | 1 | `has_memory_config` | `EcuData.memory` holds a `MemoryConfig` |
+//! | 2 | `has_protocols` | ComParamRefs carry protocol stubs, so protocols can be rebuilt |
+//! | 3 | `has_tables` | Parameters or parent refs use TABLE-KEY/TABLE-ENTRY/TABLE-STRUCT |
+//! | 4 | `flattened_inheritance` | Inherited services are materialized in every child layer |
+//! | 5 | `deduped_dops` | Identical DOPs share one FlatBuffers table |
+
+use crate::types::*;
+use mdd_format::dataformat;
+use std::fmt;
+
+/// A single capability recorded in `EcuData.feature_flags`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(i8)]
+pub enum Feature {
+    HasMemoryConfig = 1,
+    HasProtocols = 2,
+    HasTables = 3,
+    FlattenedInheritance = 4,
+    /// Set by `ir_to_flatbuffers_with_options` when DOP sharing is enabled;
+    /// never derived from the IR itself.
+    DedupedDops = 5,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 5] = [
+        Feature::HasMemoryConfig,
+        Feature::HasProtocols,
+        Feature::HasTables,
+        Feature::FlattenedInheritance,
+        Feature::DedupedDops,
+    ];
+
+    /// Stable snake_case name, as shown by `info`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::HasMemoryConfig => "has_memory_config",
+            Feature::HasProtocols => "has_protocols",
+            Feature::HasTables => "has_tables",
+            Feature::FlattenedInheritance => "flattened_inheritance",
+            Feature::DedupedDops => "deduped_dops",
+        }
+    }
+
+    pub fn from_fbs(flag: dataformat::FeatureFlag) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| *f as i8 == flag.0)
+    }
+
+    pub fn to_fbs(self) -> dataformat::FeatureFlag {
+        dataformat::FeatureFlag(self as i8)
+    }
+
+    fn bit(self) -> u8 {
+        1 << (self as u8)
+    }
+}
+
+/// Set of [`Feature`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FeatureFlags(u8);
+
+impl FeatureFlags {
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(self, feature: Feature) -> bool {
+        self.0 & feature.bit() != 0
+    }
+
+    pub fn insert(&mut self, feature: Feature) {
+        self.0 |= feature.bit();
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Feature> {
+        Feature::ALL.into_iter().filter(move |f| self.contains(*f))
+    }
+
+    /// Derive the flags that hold for `db`, as written by `ir_to_flatbuffers`.
+    pub fn detect(db: &DiagDatabase) -> Self {
+        let mut flags = Self::empty();
+        if db.memory.is_some() {
+            flags.insert(Feature::HasMemoryConfig);
+        }
+        if all_layers(db).any(|l| l.com_param_refs.iter().any(|c| c.protocol.is_some())) {
+            flags.insert(Feature::HasProtocols);
+        }
+        if uses_tables(db) {
+            flags.insert(Feature::HasTables);
+        }
+        if inheritance_is_flattened(db) {
+            flags.insert(Feature::FlattenedInheritance);
+        }
+        flags
+    }
+
+    /// Decode a FlatBuffers flag vector. Returns `None` when the vector is
+    /// absent or empty (e.g. files from writers that do not record flags), in
+    /// which case readers must not assume any capability is missing.
+    pub fn from_fbs(
+        flags: Option<flatbuffers::Vector<'_, dataformat::FeatureFlag>>,
+    ) -> Option<Self> {
+        let flags = flags.filter(|v| !v.is_empty())?;
+        let mut set = Self::empty();
+        for feature in flags.iter().filter_map(Feature::from_fbs) {
+            set.insert(feature);
+        }
+        Some(set)
+    }
+
+    pub fn to_fbs(self) -> Vec<dataformat::FeatureFlag> {
+        self.iter().map(Feature::to_fbs).collect()
+    }
+}
+
+impl FromIterator<Feature> for FeatureFlags {
+    fn from_iter<I: IntoIterator<Item = Feature>>(iter: I) -> Self {
+        let mut set = Self::empty();
+        for feature in iter {
+            set.insert(feature);
+        }
+        set
+    }
+}
+
+impl fmt::Display for FeatureFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.iter().map(Feature::name).collect();
+        f.write_str(&names.join(", "))
+    }
+}
+
+/// Read the feature flags recorded in FlatBuffers `EcuData` bytes.
+/// See [`FeatureFlags::from_fbs`] for when this is `None`.
+pub fn read_feature_flags(
+    fbs_data: &[u8],
+) -> Result<Option<FeatureFlags>, crate::from_fbs::ConversionError> {
+    let ecu_data = dataformat::root_as_ecu_data(fbs_data)
+        .map_err(|e| crate::from_fbs::ConversionError::FlatBuffersParse(e.to_string()))?;
+    Ok(FeatureFlags::from_fbs(ecu_data.feature_flags()))
+}
+
+fn all_layers(db: &DiagDatabase) -> impl Iterator<Item = &DiagLayer> {
+    db.variants
+        .iter()
+        .map(|v| &v.diag_layer)
+        .chain(db.functional_groups.iter().map(|fg| &fg.diag_layer))
+        .chain(db.protocols.iter().map(|p| &p.diag_layer))
+        .chain(db.ecu_shared_datas.iter().map(|e| &e.diag_layer))
+}
+
+fn all_parent_refs(db: &DiagDatabase) -> impl Iterator<Item = (&DiagLayer, &ParentRef)> {
+    let variants = db
+        .variants
+        .iter()
+        .flat_map(|v| v.parent_refs.iter().map(move |p| (&v.diag_layer, p)));
+    let groups = db
+        .functional_groups
+        .iter()
+        .flat_map(|fg| fg.parent_refs.iter().map(move |p| (&fg.diag_layer, p)));
+    variants.chain(groups)
+}
+
+fn uses_tables(db: &DiagDatabase) -> bool {
+    let table_param = |p: &Param| {
+        matches!(
+            p.specific_data,
+            Some(
+                ParamData::TableEntry { .. }
+                    | ParamData::TableKey { .. }
+                    | ParamData::TableStruct { .. }
+            )
+        )
+    };
+    all_parent_refs(db).any(|(_, p)| matches!(p.ref_type, ParentRefType::TableDop(_)))
+        || all_layers(db).flat_map(|l| &l.diag_services).any(|s| {
+            s.request.iter().flat_map(|r| &r.params).any(table_param)
+                || s.pos_responses
+                    .iter()
+                    .chain(&s.neg_responses)
+                    .flat_map(|r| &r.params)
+                    .any(table_param)
+        })
+}
+
+/// True when at least one layer inherits from a parent and every inheritable
+/// service and job of each parent is already present in the child.
+fn inheritance_is_flattened(db: &DiagDatabase) -> bool {
+    let mut inherits = false;
+    for (child, parent_ref) in all_parent_refs(db) {
+        let Some(parent) = parent_layer(db, &parent_ref.ref_type) else {
+            continue;
+        };
+        inherits = true;
+        let excluded = &parent_ref.not_inherited_diag_comm_short_names;
+        let child_has = |name: &str| {
+            child
+                .diag_services
+                .iter()
+                .any(|s| s.diag_comm.short_name == name)
+                || child
+                    .single_ecu_jobs
+                    .iter()
+                    .any(|j| j.diag_comm.short_name == name)
+        };
+        let missing = parent
+            .diag_services
+            .iter()
+            .map(|s| s.diag_comm.short_name.as_str())
+            .chain(
+                parent
+                    .single_ecu_jobs
+                    .iter()
+                    .map(|j| j.diag_comm.short_name.as_str()),
+            )
+            .filter(|name| !excluded.iter().any(|e| e == name))
+            .any(|name| !child_has(name));
+        if missing {
+            return false;
+        }
+    }
+    inherits
+}
+
+/// Resolve a parent ref to its full layer in `db`, falling back to the stub
+/// embedded in the ref.
+fn parent_layer<'a>(db: &'a DiagDatabase, ref_type: &'a ParentRefType) -> Option<&'a DiagLayer> {
+    let stub = match ref_type {
+        ParentRefType::Variant(v) => &v.diag_layer,
+        ParentRefType::Protocol(p) => &p.diag_layer,
+        ParentRefType::FunctionalGroup(fg) => &fg.diag_layer,
+        ParentRefType::EcuSharedData(e) => &e.diag_layer,
+        ParentRefType::TableDop(_) => return None,
+    };
+    Some(
+        all_layers(db)
+            .find(|l| l.short_name == stub.short_name)
+            .unwrap_or(stub),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{base_variant, services, variant};
+
+    /// A base variant without `parent`, otherwise a variant inheriting from it.
+    fn inheriting(name: &str, names: &[&str], parent: Option<&str>) -> Variant {
+        let Some(parent) = parent else {
+            return base_variant(name, services(names));
+        };
+        Variant {
+            parent_refs: vec![ParentRef {
+                ref_type: ParentRefType::Variant(Box::new(base_variant(parent, vec![]))),
+                not_inherited_diag_comm_short_names: vec![],
+                not_inherited_variables_short_names: vec![],
+                not_inherited_dops_short_names: vec![],
+                not_inherited_tables_short_names: vec![],
+                not_inherited_global_neg_responses_short_names: vec![],
+            }],
+            ..variant(name, services(names))
+        }
+    }
+
+    #[test]
+    fn test_flag_set_operations() {
+        let mut flags = FeatureFlags::empty();
+        assert!(flags.is_empty());
+        flags.insert(Feature::HasTables);
+        flags.insert(Feature::HasMemoryConfig);
+        assert!(flags.contains(Feature::HasTables));
+        assert!(!flags.contains(Feature::HasProtocols));
+        assert_eq!(flags.to_string(), "has_memory_config, has_tables");
+        assert_eq!(
+            flags.to_fbs(),
+            vec![dataformat::FeatureFlag(1), dataformat::FeatureFlag(3)]
+        );
+        assert_eq!(Feature::from_fbs(dataformat::FeatureFlag::RESERVED), None);
+    }
+
+    #[test]
+    fn test_detect_flattened_inheritance() {
+        let mut db = DiagDatabase {
+            variants: vec![
+                inheriting("Base", &["A", "B"], None),
+                inheriting("V1", &["A", "B", "C"], Some("Base")),
+            ],
+            ..Default::default()
+        };
+        assert!(FeatureFlags::detect(&db).contains(Feature::FlattenedInheritance));
+
+        db.variants[1].diag_layer.diag_services.remove(1);
+        assert!(!FeatureFlags::detect(&db).contains(Feature::FlattenedInheritance));
+
+        db.variants[1].parent_refs[0]
+            .not_inherited_diag_comm_short_names
+            .push("B".into());
+        assert!(FeatureFlags::detect(&db).contains(Feature::FlattenedInheritance));
+    }
+
+    #[test]
+    fn test_detect_without_inheritance_or_memory() {
+        let db = DiagDatabase {
+            variants: vec![inheriting("Base", &["A"], None)],
+            ..Default::default()
+        };
+        assert!(FeatureFlags::detect(&db).is_empty());
+    }
+}
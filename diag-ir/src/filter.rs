@@ -1,34 +1,411 @@
-use crate::types::{Audience, DiagDatabase};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
 
-/// Filter the database to only include entities visible to the given audience.
+use thiserror::Error;
+
+use crate::semantic::Semantic;
+use crate::types::{
+    Audience, DiagComm, DiagDatabase, DiagLayer, DiagService, Param, ParamData, ParentRef,
+    ParentRefType, Sd, SdOrSdg, Sdg, Sdgs, TableDop, TableKeyReference,
+};
+
+#[derive(Debug, Error)]
+pub enum FilterError {
+    #[error("functional group '{name}' not found (available: {available})")]
+    UnknownFunctionalGroup { name: String, available: String },
+    #[error("{0}")]
+    UnknownSemantic(String),
+    #[error("invalid audience filter '{expression}': {reason}")]
+    InvalidAudienceFilter { expression: String, reason: String },
+}
+
+/// One alternative of an [`AudienceFilter`]: an audience name, optionally
+/// negated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudienceTerm {
+    pub audience: String,
+    /// `!name`: matches entities *not* visible to `audience`.
+    pub negated: bool,
+}
+
+/// Audience selection such as `development`, `development|oem` or
+/// `!aftermarket`.
 ///
-/// For each service/job with an `Audience` field:
-/// - If `enabled_audiences` is non-empty and the target is not in it: remove.
-/// - If `disabled_audiences` contains the target: remove.
-/// - If no audience is set, the entity is kept (visible to all).
-pub fn filter_by_audience(db: &mut DiagDatabase, audience: &str) {
-    for variant in &mut db.variants {
-        variant
-            .diag_layer
+/// The expression is a `|`-separated list of audience names, each optionally
+/// prefixed with `!`. An entity matches when at least one alternative does: a
+/// plain name matches entities visible to that audience, a negated one
+/// entities hidden from it. Entities without an audience restriction are
+/// visible to every audience, so `!aftermarket` drops them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudienceFilter {
+    terms: Vec<AudienceTerm>,
+}
+
+impl AudienceFilter {
+    /// A filter for exactly one audience, without parsing `name`.
+    pub fn single(name: impl Into<String>) -> Self {
+        Self {
+            terms: vec![AudienceTerm {
+                audience: name.into(),
+                negated: false,
+            }],
+        }
+    }
+
+    pub fn terms(&self) -> &[AudienceTerm] {
+        &self.terms
+    }
+
+    /// Whether an entity restricted by `audience` passes the filter.
+    pub fn matches(&self, audience: &Option<Audience>) -> bool {
+        self.terms
+            .iter()
+            .any(|term| is_visible(audience, &term.audience) != term.negated)
+    }
+}
+
+impl FromStr for AudienceFilter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let terms = s
+            .split('|')
+            .map(|term| {
+                let term = term.trim();
+                let (negated, name) = match term.strip_prefix('!') {
+                    Some(name) => (true, name.trim_start()),
+                    None => (false, term),
+                };
+                if name.is_empty() || name.contains('!') {
+                    return Err(FilterError::InvalidAudienceFilter {
+                        expression: s.to_string(),
+                        reason: if name.is_empty() {
+                            "empty audience name".into()
+                        } else {
+                            format!("unexpected '!' in '{name}'")
+                        },
+                    });
+                }
+                Ok(AudienceTerm {
+                    audience: name.to_string(),
+                    negated,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { terms })
+    }
+}
+
+impl fmt::Display for AudienceFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, term) in self.terms.iter().enumerate() {
+            if i > 0 {
+                f.write_str("|")?;
+            }
+            if term.negated {
+                f.write_str("!")?;
+            }
+            f.write_str(&term.audience)?;
+        }
+        Ok(())
+    }
+}
+
+/// Filter the database to only include entities matching the audience filter.
+///
+/// An entity is visible to one audience by these rules:
+/// - If `enabled_audiences` is non-empty and the audience is not in it: hidden.
+/// - If `disabled_audiences` contains the audience: hidden.
+/// - If no audience is set, the entity is visible to all.
+///
+/// Services and single-ECU jobs of variants and functional groups, and the
+/// rows of the tables they reference, are kept when they match `filter`.
+pub fn filter_by_audience(db: &mut DiagDatabase, filter: &AudienceFilter) {
+    let layers = db
+        .variants
+        .iter_mut()
+        .map(|v| (&mut v.diag_layer, &mut v.parent_refs))
+        .chain(
+            db.functional_groups
+                .iter_mut()
+                .map(|fg| (&mut fg.diag_layer, &mut fg.parent_refs)),
+        );
+    for (layer, parent_refs) in layers {
+        layer
             .diag_services
-            .retain(|svc| is_visible(&svc.diag_comm.audience, audience));
-        variant
-            .diag_layer
+            .retain(|svc| filter.matches(&svc.diag_comm.audience));
+        layer
             .single_ecu_jobs
-            .retain(|job| is_visible(&job.diag_comm.audience, audience));
+            .retain(|job| filter.matches(&job.diag_comm.audience));
+
+        for parent_ref in parent_refs {
+            if let ParentRefType::TableDop(table) = &mut parent_ref.ref_type {
+                retain_rows(table, filter);
+            }
+        }
+        let params = layer.diag_services.iter_mut().flat_map(|svc| {
+            svc.request
+                .iter_mut()
+                .flat_map(|r| &mut r.params)
+                .chain(svc.pos_responses.iter_mut().flat_map(|r| &mut r.params))
+                .chain(svc.neg_responses.iter_mut().flat_map(|r| &mut r.params))
+        });
+        for param in params {
+            retain_param_rows(param, filter);
+        }
+    }
+}
+
+fn retain_rows(table: &mut TableDop, filter: &AudienceFilter) {
+    table.rows.retain(|row| filter.matches(&row.audience));
+}
+
+/// Filter the rows of the table a TABLE-KEY parameter references.
+fn retain_param_rows(param: &mut Param, filter: &AudienceFilter) {
+    match &mut param.specific_data {
+        Some(ParamData::TableKey {
+            table_key_reference: TableKeyReference::TableDop(table),
+        }) => retain_rows(table, filter),
+        Some(ParamData::TableStruct { table_key }) => retain_param_rows(table_key, filter),
+        _ => {}
     }
+}
+
+/// Restrict the database to the services and jobs reachable from one
+/// functional group.
+///
+/// Reachable means defined in the group itself or inherited through its parent
+/// references (recursively, honouring NOT-INHERITED lists). Every layer keeps
+/// only those services/jobs, and all other functional groups are dropped.
+pub fn filter_by_functional_group(db: &mut DiagDatabase, name: &str) -> Result<(), FilterError> {
+    let Some(group) = db
+        .functional_groups
+        .iter()
+        .find(|fg| fg.diag_layer.short_name == name)
+    else {
+        return Err(FilterError::UnknownFunctionalGroup {
+            name: name.to_string(),
+            available: db
+                .functional_groups
+                .iter()
+                .map(|fg| fg.diag_layer.short_name.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+        });
+    };
+
+    let mut reachable = BTreeSet::new();
+    let mut visited = BTreeSet::new();
+    collect_reachable(
+        db,
+        &group.diag_layer,
+        &group.parent_refs,
+        &mut visited,
+        &mut reachable,
+    );
 
-    for fg in &mut db.functional_groups {
-        fg.diag_layer
+    db.functional_groups
+        .retain(|fg| fg.diag_layer.short_name == name);
+    let layers = db
+        .variants
+        .iter_mut()
+        .map(|v| &mut v.diag_layer)
+        .chain(db.functional_groups.iter_mut().map(|fg| &mut fg.diag_layer))
+        .chain(db.protocols.iter_mut().map(|p| &mut p.diag_layer))
+        .chain(db.ecu_shared_datas.iter_mut().map(|e| &mut e.diag_layer));
+    for layer in layers {
+        layer
             .diag_services
-            .retain(|svc| is_visible(&svc.diag_comm.audience, audience));
-        fg.diag_layer
+            .retain(|svc| reachable.contains(svc.diag_comm.short_name.as_str()));
+        layer
             .single_ecu_jobs
-            .retain(|job| is_visible(&job.diag_comm.audience, audience));
+            .retain(|job| reachable.contains(job.diag_comm.short_name.as_str()));
     }
+    Ok(())
 }
 
-fn is_visible(audience_field: &Option<Audience>, target: &str) -> bool {
+/// SDG caption under which a service's or job's feature tags are stored.
+pub const FEATURE_TAGS_CAPTION: &str = "features";
+
+/// Metadata key recording the feature selection a database was built with.
+pub const FEATURES_METADATA_KEY: &str = "features";
+
+/// Feature tags attached to a service or job.
+pub fn feature_tags(diag_comm: &DiagComm) -> Vec<&str> {
+    diag_comm
+        .sdgs
+        .iter()
+        .flat_map(|sdgs| &sdgs.sdgs)
+        .filter(|sdg| sdg.caption_sn == FEATURE_TAGS_CAPTION)
+        .flat_map(|sdg| &sdg.sds)
+        .filter_map(|sd| match sd {
+            SdOrSdg::Sd(sd) => Some(sd.value.as_str()),
+            SdOrSdg::Sdg(_) => None,
+        })
+        .collect()
+}
+
+/// Attach feature tags to a service or job as a `features` SDG, one SD per tag.
+pub fn set_feature_tags(diag_comm: &mut DiagComm, tags: &[String]) {
+    if tags.is_empty() {
+        return;
+    }
+    let sdg = Sdg {
+        caption_sn: FEATURE_TAGS_CAPTION.into(),
+        sds: tags
+            .iter()
+            .map(|tag| {
+                SdOrSdg::Sd(Sd {
+                    value: tag.clone(),
+                    si: String::new(),
+                    ti: String::new(),
+                })
+            })
+            .collect(),
+        si: String::new(),
+    };
+    diag_comm
+        .sdgs
+        .get_or_insert_with(|| Sdgs { sdgs: vec![] })
+        .sdgs
+        .push(sdg);
+}
+
+/// Keep only the services and jobs built for the given features.
+///
+/// Untagged services/jobs are always kept; tagged ones are kept when at least
+/// one of their tags is enabled. The selection is recorded in the database
+/// metadata under `features`.
+pub fn filter_by_features(db: &mut DiagDatabase, enabled: &[String]) {
+    let keep = |dc: &DiagComm| {
+        let tags = feature_tags(dc);
+        tags.is_empty() || tags.iter().any(|t| enabled.iter().any(|e| e == t))
+    };
+    let layers = db
+        .variants
+        .iter_mut()
+        .map(|v| &mut v.diag_layer)
+        .chain(db.functional_groups.iter_mut().map(|fg| &mut fg.diag_layer))
+        .chain(db.protocols.iter_mut().map(|p| &mut p.diag_layer))
+        .chain(db.ecu_shared_datas.iter_mut().map(|e| &mut e.diag_layer));
+    for layer in layers {
+        layer.diag_services.retain(|svc| keep(&svc.diag_comm));
+        layer.single_ecu_jobs.retain(|job| keep(&job.diag_comm));
+    }
+    db.metadata
+        .insert(FEATURES_METADATA_KEY.into(), enabled.join(","));
+}
+
+/// A service found by [`by_semantic`].
+#[derive(Debug, Clone, Copy)]
+pub struct SemanticMatch<'a> {
+    /// Short name of the layer defining the service.
+    pub layer: &'a str,
+    pub service: &'a DiagService,
+    pub semantic: Semantic,
+}
+
+/// The services of every layer whose [`Semantic`] is one of `semantics`,
+/// given in any spelling [`Semantic::parse`] accepts. Layers are visited in
+/// database order: variants, functional groups, protocols, ECU shared data.
+pub fn by_semantic<'a>(
+    db: &'a DiagDatabase,
+    semantics: &[&str],
+) -> Result<Vec<SemanticMatch<'a>>, FilterError> {
+    let wanted = semantics
+        .iter()
+        .map(|s| s.parse::<Semantic>().map_err(FilterError::UnknownSemantic))
+        .collect::<Result<BTreeSet<_>, _>>()?;
+    let layers = db
+        .variants
+        .iter()
+        .map(|v| &v.diag_layer)
+        .chain(db.functional_groups.iter().map(|fg| &fg.diag_layer))
+        .chain(db.protocols.iter().map(|p| &p.diag_layer))
+        .chain(db.ecu_shared_datas.iter().map(|e| &e.diag_layer));
+    Ok(layers
+        .flat_map(|layer| {
+            layer.diag_services.iter().filter_map(|service| {
+                let semantic = Semantic::of(service).filter(|s| wanted.contains(s))?;
+                Some(SemanticMatch {
+                    layer: &layer.short_name,
+                    service,
+                    semantic,
+                })
+            })
+        })
+        .collect())
+}
+
+/// Names of the services and jobs visible in `layer`, including what it
+/// inherits through `parent_refs`.
+fn collect_reachable(
+    db: &DiagDatabase,
+    layer: &DiagLayer,
+    parent_refs: &[ParentRef],
+    visited: &mut BTreeSet<String>,
+    out: &mut BTreeSet<String>,
+) {
+    if !visited.insert(layer.short_name.to_string()) {
+        return;
+    }
+    out.extend(
+        layer
+            .diag_services
+            .iter()
+            .map(|s| &s.diag_comm)
+            .chain(layer.single_ecu_jobs.iter().map(|j| &j.diag_comm))
+            .map(|dc| dc.short_name.to_string()),
+    );
+    for parent_ref in parent_refs {
+        let Some((parent, grand_parents)) = resolve_parent(db, &parent_ref.ref_type) else {
+            continue;
+        };
+        let mut inherited = BTreeSet::new();
+        collect_reachable(db, parent, grand_parents, visited, &mut inherited);
+        out.extend(
+            inherited
+                .into_iter()
+                .filter(|n| !parent_ref.not_inherited_diag_comm_short_names.contains(n)),
+        );
+    }
+}
+
+/// Look a parent reference up in the database; references may be stubs that
+/// only carry the short name.
+fn resolve_parent<'a>(
+    db: &'a DiagDatabase,
+    ref_type: &'a ParentRefType,
+) -> Option<(&'a DiagLayer, &'a [ParentRef])> {
+    let (stub, stub_refs): (&DiagLayer, &[ParentRef]) = match ref_type {
+        ParentRefType::Variant(v) => (&v.diag_layer, &v.parent_refs),
+        ParentRefType::Protocol(p) => (&p.diag_layer, &p.parent_refs),
+        ParentRefType::FunctionalGroup(fg) => (&fg.diag_layer, &fg.parent_refs),
+        ParentRefType::EcuSharedData(e) => (&e.diag_layer, &[]),
+        ParentRefType::TableDop(_) => return None,
+    };
+    let name = &stub.short_name;
+    let found = db
+        .variants
+        .iter()
+        .map(|v| (&v.diag_layer, v.parent_refs.as_slice()))
+        .chain(
+            db.functional_groups
+                .iter()
+                .map(|fg| (&fg.diag_layer, fg.parent_refs.as_slice())),
+        )
+        .chain(
+            db.protocols
+                .iter()
+                .map(|p| (&p.diag_layer, p.parent_refs.as_slice())),
+        )
+        .chain(db.ecu_shared_datas.iter().map(|e| (&e.diag_layer, &[][..])))
+        .find(|(l, _)| &l.short_name == name);
+    Some(found.unwrap_or((stub, stub_refs)))
+}
+
+pub(crate) fn is_visible(audience_field: &Option<Audience>, target: &str) -> bool {
     let aud = match audience_field {
         Some(a) => a,
         None => return true, // no audience restriction
@@ -56,22 +433,18 @@ fn is_visible(audience_field: &Option<Audience>, target: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{service, services};
     use crate::types::*;
 
     fn make_service(name: &str, audience: Option<Audience>) -> DiagService {
-        DiagService {
-            diag_comm: DiagComm {
-                short_name: name.to_string(),
-                audience,
-                ..Default::default()
-            },
-            ..Default::default()
-        }
+        let mut svc = service(name, "");
+        svc.diag_comm.audience = audience;
+        svc
     }
 
     fn aa(name: &str) -> AdditionalAudience {
         AdditionalAudience {
-            short_name: name.to_string(),
+            short_name: name.into(),
             long_name: None,
         }
     }
@@ -132,7 +505,7 @@ mod tests {
             ..Default::default()
         };
 
-        filter_by_audience(&mut db, "aftermarket");
+        filter_by_audience(&mut db, &AudienceFilter::single("aftermarket"));
 
         let names: Vec<&str> = db.functional_groups[0]
             .diag_layer
@@ -147,6 +520,85 @@ mod tests {
         );
     }
 
+    fn layer(name: &str, names: &[&str]) -> DiagLayer {
+        DiagLayer {
+            short_name: name.into(),
+            diag_services: services(names),
+            ..Default::default()
+        }
+    }
+
+    fn service_names(layer: &DiagLayer) -> Vec<&str> {
+        layer
+            .diag_services
+            .iter()
+            .map(|s| s.diag_comm.short_name.as_str())
+            .collect()
+    }
+
+    fn obd_database() -> DiagDatabase {
+        let esd_ref = ParentRef {
+            ref_type: ParentRefType::EcuSharedData(Box::new(EcuSharedData {
+                diag_layer: DiagLayer {
+                    short_name: "ESD_Obd".into(),
+                    ..Default::default()
+                },
+            })),
+            not_inherited_diag_comm_short_names: vec!["Obd_Mode09".into()],
+            not_inherited_variables_short_names: vec![],
+            not_inherited_dops_short_names: vec![],
+            not_inherited_tables_short_names: vec![],
+            not_inherited_global_neg_responses_short_names: vec![],
+        };
+        DiagDatabase {
+            variants: vec![Variant {
+                is_base_variant: true,
+                diag_layer: layer("Base", &["Obd_Mode01", "ReadDID", "Obd_Mode09"]),
+                ..Default::default()
+            }],
+            functional_groups: vec![
+                FunctionalGroup {
+                    diag_layer: layer("FG_Obd", &["Obd_Mode03"]),
+                    parent_refs: vec![esd_ref],
+                },
+                FunctionalGroup {
+                    diag_layer: layer("FG_Other", &["Other"]),
+                    parent_refs: vec![],
+                },
+            ],
+            ecu_shared_datas: vec![EcuSharedData {
+                diag_layer: layer("ESD_Obd", &["Obd_Mode01", "Obd_Mode09"]),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_filter_by_functional_group() {
+        let mut db = obd_database();
+        filter_by_functional_group(&mut db, "FG_Obd").unwrap();
+
+        assert_eq!(db.functional_groups.len(), 1);
+        assert_eq!(
+            service_names(&db.functional_groups[0].diag_layer),
+            ["Obd_Mode03"]
+        );
+        // Obd_Mode09 is excluded by the NOT-INHERITED list of the parent ref.
+        assert_eq!(service_names(&db.variants[0].diag_layer), ["Obd_Mode01"]);
+        assert_eq!(
+            service_names(&db.ecu_shared_datas[0].diag_layer),
+            ["Obd_Mode01"]
+        );
+    }
+
+    #[test]
+    fn test_filter_by_unknown_functional_group() {
+        let mut db = obd_database();
+        let err = filter_by_functional_group(&mut db, "FG_Missing").unwrap_err();
+        assert!(err.to_string().contains("FG_Obd, FG_Other"), "{err}");
+        assert_eq!(db, obd_database());
+    }
+
     #[test]
     fn test_filter_database() {
         let mut db = DiagDatabase {
@@ -179,7 +631,7 @@ mod tests {
             ..Default::default()
         };
 
-        filter_by_audience(&mut db, "aftermarket");
+        filter_by_audience(&mut db, &AudienceFilter::single("aftermarket"));
 
         let names: Vec<&str> = db.variants[0]
             .diag_layer
@@ -190,4 +642,213 @@ mod tests {
         // "Public" kept (no audience), "DevOnly" removed (not in enabled), "NoAftermarket" removed (in disabled)
         assert_eq!(names, vec!["Public"]);
     }
+
+    #[test]
+    fn test_parse_audience_filter() {
+        let filter: AudienceFilter = " development | !oem ".parse().unwrap();
+        assert_eq!(
+            filter.terms(),
+            [
+                AudienceTerm {
+                    audience: "development".into(),
+                    negated: false,
+                },
+                AudienceTerm {
+                    audience: "oem".into(),
+                    negated: true,
+                },
+            ]
+        );
+        assert_eq!(filter.to_string(), "development|!oem");
+        for bad in ["", "development|", "!", "!!oem", "dev!elopment"] {
+            let err = bad.parse::<AudienceFilter>().unwrap_err();
+            assert!(
+                matches!(err, FilterError::InvalidAudienceFilter { .. }),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_filter_by_audience_expression() {
+        let dev_only = Some(Audience {
+            enabled_audiences: vec![aa("development")],
+            ..Default::default()
+        });
+        let oem_only = Some(Audience {
+            enabled_audiences: vec![aa("oem")],
+            ..Default::default()
+        });
+        let no_aftermarket = Some(Audience {
+            disabled_audiences: vec![aa("aftermarket")],
+            ..Default::default()
+        });
+        let row = |name: &str, audience: Option<Audience>| TableRow {
+            short_name: name.into(),
+            audience,
+            ..Default::default()
+        };
+        let mut lookup = make_service("Lookup", None);
+        lookup.request = Some(Request {
+            params: vec![Param {
+                short_name: "Key".into(),
+                specific_data: Some(ParamData::TableKey {
+                    table_key_reference: TableKeyReference::TableDop(Box::new(TableDop {
+                        short_name: "Codes".into(),
+                        rows: vec![
+                            row("Any", None),
+                            row("Dev", dev_only.clone()),
+                            row("Oem", oem_only.clone()),
+                            row("NotAftermarket", no_aftermarket.clone()),
+                        ],
+                        ..Default::default()
+                    })),
+                }),
+                ..Default::default()
+            }],
+            sdgs: None,
+        });
+        let db = DiagDatabase {
+            variants: vec![Variant {
+                diag_layer: DiagLayer {
+                    short_name: "Base".into(),
+                    diag_services: vec![
+                        lookup,
+                        make_service("Dev", dev_only.clone()),
+                        make_service("Oem", oem_only.clone()),
+                        make_service("NotAftermarket", no_aftermarket),
+                    ],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let filtered = |expression: &str| {
+            let mut db = db.clone();
+            filter_by_audience(&mut db, &expression.parse().unwrap());
+            let layer = &db.variants[0].diag_layer;
+            let Some(ParamData::TableKey {
+                table_key_reference: TableKeyReference::TableDop(table),
+            }) = &layer.diag_services[0].request.as_ref().unwrap().params[0].specific_data
+            else {
+                panic!("lookup service filtered out");
+            };
+            let rows: Vec<String> = table
+                .rows
+                .iter()
+                .map(|r| r.short_name.to_string())
+                .collect();
+            (
+                service_names(layer)
+                    .into_iter()
+                    .map(String::from)
+                    .collect::<Vec<_>>(),
+                rows,
+            )
+        };
+
+        let (services, rows) = filtered("development|oem");
+        assert_eq!(services, ["Lookup", "Dev", "Oem", "NotAftermarket"]);
+        assert_eq!(rows, ["Any", "Dev", "Oem", "NotAftermarket"]);
+
+        let (services, rows) = filtered("aftermarket|!development");
+        assert_eq!(services, ["Lookup", "Oem"]);
+        assert_eq!(rows, ["Any", "Oem"]);
+    }
+
+    fn tagged_service(name: &str, tags: &[&str]) -> DiagService {
+        let mut svc = make_service(name, None);
+        let tags: Vec<String> = tags.iter().map(|t| (*t).to_string()).collect();
+        set_feature_tags(&mut svc.diag_comm, &tags);
+        svc
+    }
+
+    #[test]
+    fn test_feature_tags_roundtrip_through_sdgs() {
+        let svc = tagged_service("Debug_Dump", &["debug", "eol"]);
+        assert_eq!(feature_tags(&svc.diag_comm), ["debug", "eol"]);
+        assert!(feature_tags(&make_service("Plain", None).diag_comm).is_empty());
+    }
+
+    #[test]
+    fn test_filter_by_features() {
+        let mut base = layer("Base", &["Public"]);
+        base.diag_services.extend([
+            tagged_service("Debug_Dump", &["debug"]),
+            tagged_service("Base_Only", &["base"]),
+            tagged_service("Eol_Or_Debug", &["eol", "debug"]),
+        ]);
+        let mut db = DiagDatabase {
+            variants: vec![Variant {
+                diag_layer: base,
+                is_base_variant: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        filter_by_features(&mut db, &["base".to_string(), "eol".to_string()]);
+
+        assert_eq!(
+            service_names(&db.variants[0].diag_layer),
+            ["Public", "Base_Only", "Eol_Or_Debug"]
+        );
+        assert_eq!(db.metadata.get(FEATURES_METADATA_KEY).unwrap(), "base,eol");
+    }
+
+    #[test]
+    fn test_by_semantic() {
+        let semantic_service = |name: &str, semantic: &str| {
+            let mut svc = make_service(name, None);
+            svc.diag_comm.semantic = semantic.into();
+            svc
+        };
+        let mut generated = make_service("VIN_Read", None);
+        generated.request = Some(Request {
+            params: vec![Param {
+                byte_position: Some(0),
+                specific_data: Some(ParamData::CodedConst {
+                    coded_value: "34".into(),
+                    diag_coded_type: DiagCodedType::default(),
+                }),
+                ..Default::default()
+            }],
+            sdgs: None,
+        });
+        let db = DiagDatabase {
+            variants: vec![Variant {
+                diag_layer: DiagLayer {
+                    short_name: "Base".into(),
+                    diag_services: vec![
+                        semantic_service("ReadSpeed", "DATAREAD"),
+                        semantic_service("SelfTest", "ROUTINE"),
+                        semantic_service("Session", "SESSION"),
+                        generated,
+                    ],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let found = by_semantic(&db, &["DATA-READ", "routine"]).unwrap();
+        let names: Vec<_> = found
+            .iter()
+            .map(|m| (m.layer, m.service.diag_comm.short_name.as_str(), m.semantic))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("Base", "ReadSpeed", Semantic::DataRead),
+                ("Base", "SelfTest", Semantic::Routine),
+                ("Base", "VIN_Read", Semantic::DataRead),
+            ]
+        );
+        assert!(matches!(
+            by_semantic(&db, &["READ-ALL"]),
+            Err(FilterError::UnknownSemantic(_))
+        ));
+    }
 }
@@ -0,0 +1,346 @@
+//! Flash sequence: the ordered steps of a reprogramming run.
+//!
+//! Steps reference services, security levels and data blocks by name. The
+//! sequence is stored as a `flash_sequence` SDG on the layer, one SD per step
+//! with the step kind as SI, so it travels through YAML, ODX and MDD.
+//! [`flash_plan`] resolves it against the database into the concrete services
+//! a tester (or simulator) sends.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{
+    DataBlockType, DiagDatabase, DiagLayer, DiagService, ParamData, Sd, SdOrSdg, Sdg, Sdgs,
+};
+
+/// SDG caption under which the flash sequence is stored.
+pub const FLASH_SEQUENCE_CAPTION: &str = "flash_sequence";
+
+/// One step of a flash sequence.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlashStep {
+    /// Switch to a diagnostic session (`<name>_Start`).
+    Session(String),
+    /// Unlock a security level (`RequestSeed_Level_<n>` + `SendKey_Level_<n>`).
+    Security(u32),
+    /// Start a routine, e.g. an erase routine.
+    Routine(String),
+    /// Download (or upload) a data block.
+    Transfer(String),
+    /// Verify a data block with its CheckMemory routine.
+    Check(String),
+    /// Reset the ECU.
+    Reset(String),
+    /// Send any other service by short name.
+    Service(String),
+}
+
+impl FlashStep {
+    fn kind(&self) -> &'static str {
+        match self {
+            FlashStep::Session(_) => "session",
+            FlashStep::Security(_) => "security",
+            FlashStep::Routine(_) => "routine",
+            FlashStep::Transfer(_) => "transfer",
+            FlashStep::Check(_) => "check",
+            FlashStep::Reset(_) => "reset",
+            FlashStep::Service(_) => "service",
+        }
+    }
+
+    fn target(&self) -> String {
+        match self {
+            FlashStep::Security(level) => level.to_string(),
+            FlashStep::Session(s)
+            | FlashStep::Routine(s)
+            | FlashStep::Transfer(s)
+            | FlashStep::Check(s)
+            | FlashStep::Reset(s)
+            | FlashStep::Service(s) => s.clone(),
+        }
+    }
+
+    fn from_sd(kind: &str, target: &str) -> Option<Self> {
+        let target = target.to_string();
+        Some(match kind {
+            "session" => FlashStep::Session(target),
+            "security" => FlashStep::Security(target.parse().ok()?),
+            "routine" => FlashStep::Routine(target),
+            "transfer" => FlashStep::Transfer(target),
+            "check" => FlashStep::Check(target),
+            "reset" => FlashStep::Reset(target),
+            "service" => FlashStep::Service(target),
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for FlashStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlashStep::Security(level) => write!(f, "security level {level}"),
+            FlashStep::Transfer(block) | FlashStep::Check(block) => {
+                write!(f, "{} block {block}", self.kind())
+            }
+            _ => write!(f, "{} {}", self.kind(), self.target()),
+        }
+    }
+}
+
+/// Short name of the CheckMemory routine generated for a data block.
+pub fn check_memory_service_name(block: &str) -> String {
+    let name: String = block
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("CheckMemory_{name}")
+}
+
+/// Read the flash sequence stored in `sdgs`. Unknown steps are skipped.
+pub fn flash_sequence(sdgs: Option<&Sdgs>) -> Vec<FlashStep> {
+    sdgs.iter()
+        .flat_map(|sdgs| &sdgs.sdgs)
+        .filter(|sdg| sdg.caption_sn == FLASH_SEQUENCE_CAPTION)
+        .flat_map(|sdg| &sdg.sds)
+        .filter_map(|sd| match sd {
+            SdOrSdg::Sd(sd) => FlashStep::from_sd(&sd.si, &sd.value),
+            SdOrSdg::Sdg(_) => None,
+        })
+        .collect()
+}
+
+/// The SDG storing `steps`.
+pub fn flash_sequence_sdg(steps: &[FlashStep]) -> Sdg {
+    Sdg {
+        caption_sn: FLASH_SEQUENCE_CAPTION.into(),
+        sds: steps
+            .iter()
+            .map(|step| {
+                SdOrSdg::Sd(Sd {
+                    value: step.target(),
+                    si: step.kind().into(),
+                    ti: String::new(),
+                })
+            })
+            .collect(),
+        si: String::new(),
+    }
+}
+
+/// A flash step resolved to the services it sends, in order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FlashPlanStep {
+    pub step: FlashStep,
+    pub services: Vec<String>,
+}
+
+/// Resolve the flash sequence of the base variant against its services and
+/// the memory config. Returns every unresolvable reference on failure.
+///
+/// The memory config is not carried by ODX or MDD; without it data block names
+/// are not checked and transfers are assumed to be downloads.
+pub fn flash_plan(db: &DiagDatabase) -> Result<Vec<FlashPlanStep>, Vec<String>> {
+    let Some(layer) = db
+        .variants
+        .iter()
+        .find(|v| v.is_base_variant)
+        .or_else(|| db.variants.first())
+        .map(|v| &v.diag_layer)
+    else {
+        return Ok(vec![]);
+    };
+    let mut plan = Vec::new();
+    let mut errors = Vec::new();
+    for step in flash_sequence(layer.sdgs.as_ref()) {
+        match resolve_step(db, layer, &step) {
+            Ok(services) => plan.push(FlashPlanStep { step, services }),
+            Err(e) => errors.push(format!("{step}: {e}")),
+        }
+    }
+    if errors.is_empty() {
+        Ok(plan)
+    } else {
+        Err(errors)
+    }
+}
+
+fn resolve_step(
+    db: &DiagDatabase,
+    layer: &DiagLayer,
+    step: &FlashStep,
+) -> Result<Vec<String>, String> {
+    let find = |sid: Option<u8>, name: &str| {
+        let services = layer
+            .diag_services
+            .iter()
+            .filter(|svc| sid.is_none_or(|sid| service_sid(svc) == Some(sid)));
+        crate::aliases::find_by_name(services, name, |svc| &svc.diag_comm)
+            .map(|svc| svc.diag_comm.short_name.to_string())
+            .ok_or_else(|| format!("no service {name}"))
+    };
+    let block_type = |block: &str| match &db.memory {
+        Some(mc) => mc
+            .data_blocks
+            .iter()
+            .find(|b| b.name == block)
+            .map(|b| (b.block_type, b.checksum_type.is_some()))
+            .ok_or_else(|| format!("no data block {block}")),
+        None => Ok((DataBlockType::Download, true)),
+    };
+    match step {
+        FlashStep::Session(name) => Ok(vec![find(Some(0x10), &format!("{name}_Start"))?]),
+        FlashStep::Security(level) => Ok(vec![
+            find(Some(0x27), &format!("RequestSeed_Level_{level}"))?,
+            find(Some(0x27), &format!("SendKey_Level_{level}"))?,
+        ]),
+        FlashStep::Routine(name) => Ok(vec![find(Some(0x31), name)?]),
+        FlashStep::Transfer(block) => {
+            let names: &[&str] = match block_type(block)?.0 {
+                DataBlockType::Download => &["RequestDownload", "TransferData", "TransferExit"],
+                DataBlockType::Upload => &[
+                    "RequestUpload",
+                    "TransferData_Upload",
+                    "RequestTransferExit_Upload",
+                ],
+            };
+            names.iter().map(|name| find(None, name)).collect()
+        }
+        FlashStep::Check(block) => {
+            if !block_type(block)?.1 {
+                return Err(format!("data block {block} has no checksum_type"));
+            }
+            Ok(vec![find(Some(0x31), &check_memory_service_name(block))?])
+        }
+        FlashStep::Reset(name) => Ok(vec![find(Some(0x11), name)?]),
+        FlashStep::Service(name) => Ok(vec![find(None, name)?]),
+    }
+}
+
+/// The service ID of a service: the coded constant at byte 0 of its request.
+pub fn service_sid(svc: &DiagService) -> Option<u8> {
+    svc.request
+        .as_ref()?
+        .params
+        .iter()
+        .find_map(|p| match &p.specific_data {
+            Some(ParamData::CodedConst { coded_value, .. }) if p.byte_position == Some(0) => {
+                crate::numbers::parse_uint(coded_value).and_then(|v| u8::try_from(v).ok())
+            }
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{database, service};
+    use crate::types::*;
+
+    /// A service whose request codes `sid`.
+    fn sid_service(name: &str, sid: u8) -> DiagService {
+        DiagService {
+            request: Some(Request {
+                params: vec![Param {
+                    byte_position: Some(0),
+                    specific_data: Some(ParamData::CodedConst {
+                        coded_value: sid.to_string(),
+                        diag_coded_type: DiagCodedType::default(),
+                    }),
+                    ..Default::default()
+                }],
+                sdgs: None,
+            }),
+            ..service(name, "")
+        }
+    }
+
+    /// A database whose base variant records `steps` as its flash sequence.
+    fn flash_database(steps: &[FlashStep]) -> DiagDatabase {
+        let mut db = database(vec![
+            sid_service("Programming_Start", 0x10),
+            sid_service("RequestSeed_Level_1", 0x27),
+            sid_service("SendKey_Level_1", 0x27),
+            sid_service("EraseMemory", 0x31),
+            sid_service("RequestDownload", 0x34),
+            sid_service("TransferData", 0x36),
+            sid_service("TransferExit", 0x37),
+            sid_service("CheckMemory_App_Image", 0x31),
+            sid_service("HardReset", 0x11),
+        ]);
+        db.variants[0].diag_layer.sdgs = Some(Sdgs {
+            sdgs: vec![flash_sequence_sdg(steps)],
+        });
+        db
+    }
+
+    #[test]
+    fn test_flash_sequence_roundtrip_through_sdgs() {
+        let steps = vec![
+            FlashStep::Session("programming".into()),
+            FlashStep::Security(1),
+            FlashStep::Transfer("App Image".into()),
+        ];
+        let sdgs = Sdgs {
+            sdgs: vec![flash_sequence_sdg(&steps)],
+        };
+        assert_eq!(flash_sequence(Some(&sdgs)), steps);
+        assert_eq!(steps[1].to_string(), "security level 1");
+        assert_eq!(steps[2].to_string(), "transfer block App Image");
+    }
+
+    #[test]
+    fn test_flash_plan_resolves_services() {
+        let db = flash_database(&[
+            FlashStep::Session("programming".into()),
+            FlashStep::Security(1),
+            FlashStep::Routine("EraseMemory".into()),
+            FlashStep::Transfer("App Image".into()),
+            FlashStep::Check("App Image".into()),
+            FlashStep::Reset("hardReset".into()),
+        ]);
+        let plan = flash_plan(&db).unwrap();
+        let services: Vec<&str> = plan
+            .iter()
+            .flat_map(|s| &s.services)
+            .map(String::as_str)
+            .collect();
+        assert_eq!(
+            services,
+            [
+                "Programming_Start",
+                "RequestSeed_Level_1",
+                "SendKey_Level_1",
+                "EraseMemory",
+                "RequestDownload",
+                "TransferData",
+                "TransferExit",
+                "CheckMemory_App_Image",
+                "HardReset",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flash_plan_reports_unresolved_references() {
+        let mut db = flash_database(&[
+            FlashStep::Security(3),
+            FlashStep::Reset("Programming_Start".into()),
+            FlashStep::Transfer("Missing".into()),
+        ]);
+        db.memory = Some(MemoryConfig {
+            default_address_format: AddressFormat::default(),
+            regions: vec![],
+            data_blocks: vec![],
+        });
+        assert_eq!(
+            flash_plan(&db).unwrap_err(),
+            [
+                "security level 3: no service RequestSeed_Level_3",
+                "reset Programming_Start: no service Programming_Start",
+                "transfer block Missing: no data block Missing",
+            ]
+        );
+    }
+}
@@ -1,6 +1,11 @@
+use crate::features::{Feature, FeatureFlags};
+use crate::name::Interner;
 use crate::types::*;
 use mdd_format::dataformat;
-use std::collections::BTreeMap;
+use rayon::prelude::*;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, PoisonError};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -11,6 +16,35 @@ pub enum ConversionError {
 
 /// Convert FlatBuffers EcuData bytes to IR DiagDatabase.
 pub fn flatbuffers_to_ir(fbs_data: &[u8]) -> Result<DiagDatabase, ConversionError> {
+    convert_ecu_data(fbs_data, None)
+}
+
+/// Like [`flatbuffers_to_ir`], converting only the variants named in
+/// `variants`. The others are skipped without being decoded, which makes
+/// picking one variant out of a large database cheap. Protocols are rebuilt
+/// from the selected variants only.
+pub fn flatbuffers_to_ir_variants(
+    fbs_data: &[u8],
+    variants: &[&str],
+) -> Result<DiagDatabase, ConversionError> {
+    convert_ecu_data(fbs_data, Some(variants))
+}
+
+/// Names repeat across the whole database, so they are interned into one set
+/// shared with the workers that convert variants in parallel.
+fn convert_ecu_data(
+    fbs_data: &[u8],
+    selected: Option<&[&str]>,
+) -> Result<DiagDatabase, ConversionError> {
+    let interner = Interner::default();
+    interner.enter(|| convert_ecu_data_interned(fbs_data, selected, &interner))
+}
+
+fn convert_ecu_data_interned(
+    fbs_data: &[u8],
+    selected: Option<&[&str]>,
+    interner: &Interner,
+) -> Result<DiagDatabase, ConversionError> {
     let ecu_data = dataformat::root_as_ecu_data(fbs_data)
         .map_err(|e| ConversionError::FlatBuffersParse(e.to_string()))?;
 
@@ -24,16 +58,37 @@ pub fn flatbuffers_to_ir(fbs_data: &[u8]) -> Result<DiagDatabase, ConversionErro
         }
     }
 
+    // Variants are independent of each other and make up most of the data,
+    // so they are converted in parallel. They share one DOP cache, so a DOP
+    // table referenced from several variants is converted once, and
+    // intern into the same set of names.
+    let dops = DopCache::default();
     let variants: Vec<Variant> = ecu_data
         .variants()
-        .map(|v| (0..v.len()).map(|i| convert_variant(&v.get(i))).collect())
+        .map(|v| {
+            let wanted: Vec<_> = (0..v.len())
+                .map(|i| v.get(i))
+                .filter(|variant| {
+                    selected.is_none_or(|names| {
+                        let name = variant.diag_layer().and_then(|dl| dl.short_name());
+                        names.contains(&name.unwrap_or(""))
+                    })
+                })
+                .collect();
+            wanted
+                .par_iter()
+                .map(|variant| {
+                    interner.enter(|| with_dop_cache(&dops, || convert_variant(variant)))
+                })
+                .collect()
+        })
         .unwrap_or_default();
 
     let functional_groups: Vec<FunctionalGroup> = ecu_data
         .functional_groups()
         .map(|v| {
             (0..v.len())
-                .map(|i| convert_functional_group(&v.get(i)))
+                .map(|i| with_dop_cache(&dops, || convert_functional_group(&v.get(i))))
                 .collect()
         })
         .unwrap_or_default();
@@ -46,8 +101,14 @@ pub fn flatbuffers_to_ir(fbs_data: &[u8]) -> Result<DiagDatabase, ConversionErro
     // Reconstruct top-level protocols from variant ComParamRefs.
     // The FBS schema stores protocol stubs on each ComParamRef but does not
     // have a dedicated top-level protocols array. Group ComParamRefs by their
-    // embedded protocol short_name to rebuild the protocol list.
-    let protocols = reconstruct_protocols_from_variants(&variants);
+    // embedded protocol short_name to rebuild the protocol list. Files that
+    // record feature flags tell us up front whether any stubs exist.
+    let features = FeatureFlags::from_fbs(ecu_data.feature_flags());
+    let protocols = if features.is_none_or(|f| f.contains(Feature::HasProtocols)) {
+        reconstruct_protocols_from_variants(&variants)
+    } else {
+        vec![]
+    };
 
     Ok(DiagDatabase {
         version: ecu_data.version().unwrap_or("").to_string(),
@@ -59,11 +120,19 @@ pub fn flatbuffers_to_ir(fbs_data: &[u8]) -> Result<DiagDatabase, ConversionErro
         protocols,
         ecu_shared_datas: vec![],
         dtcs,
-        // MemoryConfig and TypeDefinition are not part of the shared FBS schema
-        // (odx-converter does not serialize them). These IR fields are only
-        // populated by the YAML parser and are lost during MDD serialization.
-        memory: None,
-        type_definitions: vec![],
+        memory: ecu_data.memory().map(|m| convert_memory_config(&m)),
+        type_definitions: ecu_data
+            .type_definitions()
+            .map(|v| {
+                (0..v.len())
+                    .map(|i| convert_type_definition(&v.get(i)))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        transport: ecu_data.transport().map(|t| convert_transport_config(&t)),
+        identification: ecu_data
+            .identification()
+            .map(|i| convert_ecu_identification(&i)),
     })
 }
 
@@ -73,8 +142,8 @@ pub fn flatbuffers_to_ir(fbs_data: &[u8]) -> Result<DiagDatabase, ConversionErro
 /// which protocol it belongs to (short_name, prot_stack, com_param_spec).
 /// We group refs by protocol short_name and rebuild a Protocol with the
 /// collected ComParamRefs on its DiagLayer.
-fn reconstruct_protocols_from_variants(variants: &[Variant]) -> Vec<Protocol> {
-    let mut proto_map: BTreeMap<String, Protocol> = BTreeMap::new();
+pub(crate) fn reconstruct_protocols_from_variants(variants: &[Variant]) -> Vec<Protocol> {
+    let mut proto_map: BTreeMap<Name, Protocol> = BTreeMap::new();
 
     for variant in variants {
         for cpr in &variant.diag_layer.com_param_refs {
@@ -96,6 +165,7 @@ fn reconstruct_protocols_from_variants(variants: &[Variant]) -> Vec<Protocol> {
                     state_charts: vec![],
                     additional_audiences: vec![],
                     sdgs: None,
+                    description: None,
                 },
                 com_param_spec: proto_stub.com_param_spec.clone(),
                 prot_stack: proto_stub.prot_stack.clone(),
@@ -112,7 +182,11 @@ fn s(opt: Option<&str>) -> String {
     opt.unwrap_or("").to_string()
 }
 
-fn convert_variant(v: &dataformat::Variant<'_>) -> Variant {
+fn n(opt: Option<&str>) -> Name {
+    opt.map(Name::from).unwrap_or_default()
+}
+
+pub(crate) fn convert_variant(v: &dataformat::Variant<'_>) -> Variant {
     Variant {
         diag_layer: v
             .diag_layer()
@@ -137,7 +211,7 @@ fn convert_variant(v: &dataformat::Variant<'_>) -> Variant {
     }
 }
 
-fn convert_functional_group(fg: &dataformat::FunctionalGroup<'_>) -> FunctionalGroup {
+pub(crate) fn convert_functional_group(fg: &dataformat::FunctionalGroup<'_>) -> FunctionalGroup {
     FunctionalGroup {
         diag_layer: fg
             .diag_layer()
@@ -155,7 +229,7 @@ fn convert_functional_group(fg: &dataformat::FunctionalGroup<'_>) -> FunctionalG
 
 fn empty_diag_layer() -> DiagLayer {
     DiagLayer {
-        short_name: String::new(),
+        short_name: Name::default(),
         long_name: None,
         funct_classes: vec![],
         com_param_refs: vec![],
@@ -164,19 +238,20 @@ fn empty_diag_layer() -> DiagLayer {
         state_charts: vec![],
         additional_audiences: vec![],
         sdgs: None,
+        description: None,
     }
 }
 
 fn convert_diag_layer(dl: &dataformat::DiagLayer<'_>) -> DiagLayer {
     DiagLayer {
-        short_name: s(dl.short_name()),
+        short_name: n(dl.short_name()),
         long_name: dl.long_name().map(|ln| convert_long_name(&ln)),
         funct_classes: dl
             .funct_classes()
             .map(|v| {
                 (0..v.len())
                     .map(|i| FunctClass {
-                        short_name: s(v.get(i).short_name()),
+                        short_name: n(v.get(i).short_name()),
                     })
                     .collect()
             })
@@ -222,6 +297,7 @@ fn convert_diag_layer(dl: &dataformat::DiagLayer<'_>) -> DiagLayer {
             })
             .unwrap_or_default(),
         sdgs: dl.sdgs().map(|sdgs| convert_sdgs(&sdgs)),
+        description: None,
     }
 }
 
@@ -256,9 +332,9 @@ fn convert_diag_service(ds: &dataformat::DiagService<'_>) -> DiagService {
 
 fn empty_diag_comm() -> DiagComm {
     DiagComm {
-        short_name: String::new(),
+        short_name: Name::default(),
         long_name: None,
-        semantic: String::new(),
+        semantic: Name::default(),
         funct_classes: vec![],
         sdgs: None,
         diag_class_type: DiagClassType::StartComm,
@@ -269,20 +345,21 @@ fn empty_diag_comm() -> DiagComm {
         is_mandatory: false,
         is_executable: true,
         is_final: false,
+        description: None,
     }
 }
 
 fn convert_diag_comm(dc: &dataformat::DiagComm<'_>) -> DiagComm {
     DiagComm {
-        short_name: s(dc.short_name()),
+        short_name: n(dc.short_name()),
         long_name: dc.long_name().map(|ln| convert_long_name(&ln)),
-        semantic: s(dc.semantic()),
+        semantic: n(dc.semantic()),
         funct_classes: dc
             .funct_class()
             .map(|v| {
                 (0..v.len())
                     .map(|i| FunctClass {
-                        short_name: s(v.get(i).short_name()),
+                        short_name: n(v.get(i).short_name()),
                     })
                     .collect()
             })
@@ -313,6 +390,7 @@ fn convert_diag_comm(dc: &dataformat::DiagComm<'_>) -> DiagComm {
         is_mandatory: dc.is_mandatory(),
         is_executable: dc.is_executable(),
         is_final: dc.is_final(),
+        description: None,
     }
 }
 
@@ -334,6 +412,8 @@ fn convert_response(r: &dataformat::Response<'_>) -> Response {
             .map(|v| (0..v.len()).map(|i| convert_param(&v.get(i))).collect())
             .unwrap_or_default(),
         sdgs: r.sdgs().map(|sdgs| convert_sdgs(&sdgs)),
+        pos_response_suffix: None,
+        audience: None,
     }
 }
 
@@ -341,8 +421,8 @@ fn convert_param(p: &dataformat::Param<'_>) -> Param {
     Param {
         id: p.id(),
         param_type: convert_param_type(p.param_type()),
-        short_name: s(p.short_name()),
-        semantic: s(p.semantic()),
+        short_name: n(p.short_name()),
+        semantic: n(p.semantic()),
         sdgs: p.sdgs().map(|sdgs| convert_sdgs(&sdgs)),
         physical_default_value: s(p.physical_default_value()),
         byte_position: p.byte_position(),
@@ -367,7 +447,9 @@ fn convert_param_specific_data(p: &dataformat::Param<'_>) -> Option<ParamData> {
         dataformat::ParamSpecificData::LengthKeyRef => {
             p.specific_data_as_length_key_ref()
                 .map(|lkr| ParamData::LengthKeyRef {
-                    dop: Box::new(lkr.dop().map_or_else(empty_dop, |d| convert_dop(&d))),
+                    dop: lkr
+                        .dop()
+                        .map_or_else(|| DopRef::new(empty_dop()), |d| mapped_dop(&d)),
                 })
         }
         dataformat::ParamSpecificData::MatchingRequestParam => p
@@ -392,7 +474,9 @@ fn convert_param_specific_data(p: &dataformat::Param<'_>) -> Option<ParamData> {
             p.specific_data_as_phys_const()
                 .map(|pc| ParamData::PhysConst {
                     phys_constant_value: s(pc.phys_constant_value()),
-                    dop: Box::new(pc.dop().map_or_else(empty_dop, |d| convert_dop(&d))),
+                    dop: pc
+                        .dop()
+                        .map_or_else(|| DopRef::new(empty_dop()), |d| mapped_dop(&d)),
                 })
         }
         dataformat::ParamSpecificData::Reserved => {
@@ -402,14 +486,18 @@ fn convert_param_specific_data(p: &dataformat::Param<'_>) -> Option<ParamData> {
         }
         dataformat::ParamSpecificData::System => {
             p.specific_data_as_system().map(|sys| ParamData::System {
-                dop: Box::new(sys.dop().map_or_else(empty_dop, |d| convert_dop(&d))),
+                dop: sys
+                    .dop()
+                    .map_or_else(|| DopRef::new(empty_dop()), |d| mapped_dop(&d)),
                 sys_param: s(sys.sys_param()),
             })
         }
         dataformat::ParamSpecificData::Value => {
             p.specific_data_as_value().map(|v| ParamData::Value {
                 physical_default_value: s(v.physical_default_value()),
-                dop: Box::new(v.dop().map_or_else(empty_dop, |d| convert_dop(&d))),
+                dop: v
+                    .dop()
+                    .map_or_else(|| DopRef::new(empty_dop()), |d| mapped_dop(&d)),
             })
         }
         dataformat::ParamSpecificData::TableEntry => {
@@ -456,10 +544,59 @@ fn convert_param_specific_data(p: &dataformat::Param<'_>) -> Option<ParamData> {
     }
 }
 
+/// DOPs converted so far, keyed by the buffer offset of their FlatBuffers
+/// table. Files written with DOP deduplication point every parameter using a
+/// DOP at one table, so the offset identifies the DOP.
+type DopCache = Arc<Mutex<HashMap<usize, DopRef>>>;
+
+thread_local! {
+    /// The cache [`mapped_dop`] uses on this thread, set by [`with_dop_cache`].
+    static DOP_CACHE: RefCell<Option<DopCache>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with `cache` as this thread's DOP cache.
+fn with_dop_cache<T>(cache: &DopCache, f: impl FnOnce() -> T) -> T {
+    // Restores the previous cache on the way out, panics included, so no
+    // offsets into this buffer outlive the conversion.
+    struct Restore(Option<DopCache>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            DOP_CACHE.with(|c| *c.borrow_mut() = self.0.take());
+        }
+    }
+    let _restore = Restore(DOP_CACHE.with(|c| c.replace(Some(Arc::clone(cache)))));
+    f()
+}
+
+/// Convert `d` once per conversion: every parameter referencing the same DOP
+/// table gets the same [`DopRef`]. Outside [`with_dop_cache`] each call
+/// converts a copy of its own.
+fn mapped_dop(d: &dataformat::DOP<'_>) -> DopRef {
+    let Some(cache) = DOP_CACHE.with(|c| c.borrow().clone()) else {
+        return DopRef::new(convert_dop(d));
+    };
+    let loc = d._tab.loc();
+    if let Some(dop) = cache
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&loc)
+    {
+        return dop.clone();
+    }
+    // Converted without holding the lock: DOPs nest through structures.
+    let dop = DopRef::new(convert_dop(d));
+    cache
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(loc)
+        .or_insert(dop)
+        .clone()
+}
+
 fn convert_dop(d: &dataformat::DOP<'_>) -> Dop {
     Dop {
         dop_type: convert_dop_type(d.dop_type()),
-        short_name: s(d.short_name()),
+        short_name: n(d.short_name()),
         sdgs: d.sdgs().map(|sdgs| convert_sdgs(&sdgs)),
         specific_data: convert_dop_specific_data(d),
     }
@@ -468,7 +605,7 @@ fn convert_dop(d: &dataformat::DOP<'_>) -> Dop {
 fn empty_dop() -> Dop {
     Dop {
         dop_type: DopType::Regular,
-        short_name: String::new(),
+        short_name: Name::default(),
         sdgs: None,
         specific_data: None,
     }
@@ -559,12 +696,14 @@ fn convert_dop_specific_data(d: &dataformat::DOP<'_>) -> Option<DopData> {
                 switch_key: mux.switch_key().map(|sk| SwitchKey {
                     byte_position: sk.byte_position(),
                     bit_position: sk.bit_position(),
-                    dop: Box::new(sk.dop().map_or_else(empty_dop, |d| convert_dop(&d))),
+                    dop: sk
+                        .dop()
+                        .map_or_else(|| DopRef::new(empty_dop()), |d| mapped_dop(&d)),
                 }),
                 default_case: mux.default_case().map(|dc| DefaultCase {
-                    short_name: s(dc.short_name()),
+                    short_name: n(dc.short_name()),
                     long_name: dc.long_name().map(|ln| convert_long_name(&ln)),
-                    structure: dc.structure().map(|d| Box::new(convert_dop(&d))),
+                    structure: dc.structure().map(|d| mapped_dop(&d)),
                 }),
                 cases: mux
                     .cases()
@@ -573,9 +712,9 @@ fn convert_dop_specific_data(d: &dataformat::DOP<'_>) -> Option<DopData> {
                             .map(|i| {
                                 let c = v.get(i);
                                 Case {
-                                    short_name: s(c.short_name()),
+                                    short_name: n(c.short_name()),
                                     long_name: c.long_name().map(|ln| convert_long_name(&ln)),
-                                    structure: c.structure().map(|d| Box::new(convert_dop(&d))),
+                                    structure: c.structure().map(|d| mapped_dop(&d)),
                                     lower_limit: c.lower_limit().map(|l| convert_limit(&l)),
                                     upper_limit: c.upper_limit().map(|l| convert_limit(&l)),
                                 }
@@ -595,7 +734,9 @@ fn convert_dop_specific_data(d: &dataformat::DOP<'_>) -> Option<DopData> {
                     DetermineNumberOfItems {
                         byte_position: dni.byte_position(),
                         bit_position: dni.bit_position(),
-                        dop: Box::new(dni.dop().map_or_else(empty_dop, |d| convert_dop(&d))),
+                        dop: dni
+                            .dop()
+                            .map_or_else(|| DopRef::new(empty_dop()), |d| mapped_dop(&d)),
                     }
                 }),
             }),
@@ -605,8 +746,8 @@ fn convert_dop_specific_data(d: &dataformat::DOP<'_>) -> Option<DopData> {
 
 fn convert_field(f: &dataformat::Field<'_>) -> Field {
     Field {
-        basic_structure: f.basic_structure().map(|d| Box::new(convert_dop(&d))),
-        env_data_desc: f.env_data_desc().map(|d| Box::new(convert_dop(&d))),
+        basic_structure: f.basic_structure().map(|d| mapped_dop(&d)),
+        env_data_desc: f.env_data_desc().map(|d| mapped_dop(&d)),
         is_visible: f.is_visible(),
     }
 }
@@ -785,7 +926,7 @@ fn convert_internal_constr(ic: &dataformat::InternalConstr<'_>) -> InternalConst
 
 fn convert_unit(u: &dataformat::Unit<'_>) -> Unit {
     Unit {
-        short_name: s(u.short_name()),
+        short_name: n(u.short_name()),
         display_name: s(u.display_name()),
         factor_si_to_unit: u.factorsitounit(),
         offset_si_to_unit: u.offsetitounit(),
@@ -797,7 +938,7 @@ fn convert_unit(u: &dataformat::Unit<'_>) -> Unit {
 
 fn convert_physical_dimension(pd: &dataformat::PhysicalDimension<'_>) -> PhysicalDimension {
     PhysicalDimension {
-        short_name: s(pd.short_name()),
+        short_name: n(pd.short_name()),
         long_name: pd.long_name().map(|ln| convert_long_name(&ln)),
         length_exp: pd.length_exp(),
         mass_exp: pd.mass_exp(),
@@ -809,9 +950,9 @@ fn convert_physical_dimension(pd: &dataformat::PhysicalDimension<'_>) -> Physica
     }
 }
 
-fn convert_dtc(dtc: &dataformat::DTC<'_>) -> Dtc {
+pub(crate) fn convert_dtc(dtc: &dataformat::DTC<'_>) -> Dtc {
     Dtc {
-        short_name: s(dtc.short_name()),
+        short_name: n(dtc.short_name()),
         trouble_code: dtc.trouble_code(),
         display_trouble_code: s(dtc.display_trouble_code()),
         text: dtc.text().map(|t| convert_text(&t)),
@@ -821,6 +962,175 @@ fn convert_dtc(dtc: &dataformat::DTC<'_>) -> Dtc {
     }
 }
 
+fn convert_address_format(af: &dataformat::AddressFormat<'_>) -> AddressFormat {
+    AddressFormat {
+        address_bytes: af.address_bytes(),
+        length_bytes: af.length_bytes(),
+    }
+}
+
+pub(crate) fn convert_memory_config(mc: &dataformat::MemoryConfig<'_>) -> MemoryConfig {
+    MemoryConfig {
+        default_address_format: mc
+            .default_address_format()
+            .map(|af| convert_address_format(&af))
+            .unwrap_or_default(),
+        regions: mc
+            .regions()
+            .map(|v| {
+                (0..v.len())
+                    .map(|i| convert_memory_region(&v.get(i)))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        data_blocks: mc
+            .data_blocks()
+            .map(|v| {
+                (0..v.len())
+                    .map(|i| convert_data_block(&v.get(i)))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn convert_memory_region(r: &dataformat::MemoryRegion<'_>) -> MemoryRegion {
+    let access = match r.access() {
+        dataformat::MemoryAccess::READ => MemoryAccess::Read,
+        dataformat::MemoryAccess::WRITE => MemoryAccess::Write,
+        dataformat::MemoryAccess::READ_WRITE => MemoryAccess::ReadWrite,
+        dataformat::MemoryAccess::EXECUTE => MemoryAccess::Execute,
+        _ => MemoryAccess::Read,
+    };
+    MemoryRegion {
+        name: s(r.name()),
+        description: r.description().map(str::to_string),
+        start_address: r.start_address(),
+        size: r.size(),
+        access,
+        address_format: r.address_format().map(|af| convert_address_format(&af)),
+        security_level: r.security_level().map(str::to_string),
+        session: r
+            .sessions()
+            .map(|v| (0..v.len()).map(|i| v.get(i).to_string()).collect()),
+    }
+}
+
+fn convert_checksum(c: &dataformat::Checksum<'_>) -> ChecksumType {
+    match c.algorithm() {
+        dataformat::ChecksumAlgorithm::CRC16_CCITT => ChecksumType::Crc16Ccitt,
+        dataformat::ChecksumAlgorithm::CRC32 => ChecksumType::Crc32,
+        dataformat::ChecksumAlgorithm::SHA256 => ChecksumType::Sha256,
+        dataformat::ChecksumAlgorithm::OEM => ChecksumType::Oem {
+            name: s(c.name()),
+            length: c.length(),
+            parameters: c
+                .parameters()
+                .map(|v| {
+                    v.iter()
+                        .filter_map(|kv| Some((kv.key()?.to_string(), kv.value()?.to_string())))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        },
+        _ => ChecksumType::Crc32,
+    }
+}
+
+fn convert_data_block(b: &dataformat::DataBlock<'_>) -> DataBlock {
+    let block_type = match b.block_type() {
+        dataformat::DataBlockType::DOWNLOAD => DataBlockType::Download,
+        dataformat::DataBlockType::UPLOAD => DataBlockType::Upload,
+        _ => DataBlockType::Download,
+    };
+    let format = match b.format() {
+        dataformat::DataBlockFormat::RAW => DataBlockFormat::Raw,
+        dataformat::DataBlockFormat::ENCRYPTED => DataBlockFormat::Encrypted,
+        dataformat::DataBlockFormat::COMPRESSED => DataBlockFormat::Compressed,
+        dataformat::DataBlockFormat::ENCRYPTED_COMPRESSED => DataBlockFormat::EncryptedCompressed,
+        _ => DataBlockFormat::Raw,
+    };
+    DataBlock {
+        name: s(b.name()),
+        description: b.description().map(str::to_string),
+        block_type,
+        memory_address: b.memory_address(),
+        memory_size: b.memory_size(),
+        format,
+        max_block_length: b.max_block_length(),
+        security_level: b.security_level().map(str::to_string),
+        session: b.session().map(str::to_string),
+        checksum_type: b.checksum().map(|c| convert_checksum(&c)),
+    }
+}
+
+pub(crate) fn convert_transport_config(tc: &dataformat::TransportConfig<'_>) -> TransportConfig {
+    let entity_id = |v: Option<flatbuffers::Vector<'_, u8>>| v?.bytes().try_into().ok();
+    TransportConfig {
+        doip: tc.doip().map(|d| DoipConfig {
+            ip: d.ip().map(str::to_string),
+            port: d.port(),
+            logical_address: d.logical_address(),
+            functional_address: d.functional_address(),
+            tester_address: d.tester_address(),
+            eid: entity_id(d.eid()),
+            gid: entity_id(d.gid()),
+        }),
+    }
+}
+
+pub(crate) fn convert_ecu_identification(
+    ident: &dataformat::EcuIdentification<'_>,
+) -> EcuIdentification {
+    EcuIdentification {
+        part_number: ident.part_number().map(str::to_string),
+        supplier_code: ident.supplier_code().map(str::to_string),
+        hardware_version: ident.hardware_version().map(str::to_string),
+        software_version: ident.software_version().map(str::to_string),
+        dids: ident
+            .dids()
+            .map(|v| v.iter().map(|d| (s(d.role()), d.did())).collect())
+            .unwrap_or_default(),
+    }
+}
+
+pub(crate) fn convert_type_definition(td: &dataformat::TypeDefinition<'_>) -> TypeDefinition {
+    TypeDefinition {
+        name: s(td.name()),
+        base: s(td.base()),
+        bit_length: td.bit_length(),
+        bit_mask: td.bit_mask(),
+        condensed: td.condensed(),
+        min_length: td.min_length(),
+        max_length: td.max_length(),
+        length_prefix_bits: td.length_prefix_bits(),
+        enum_values_json: td.enum_values().map(enum_values_json),
+        description: td.description().map(str::to_string),
+    }
+}
+
+/// The JSON object (or, without keys, list) `enum_values` was written from.
+fn enum_values_json(
+    entries: flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<dataformat::EnumValue<'_>>>,
+) -> String {
+    let value = |e: &dataformat::EnumValue<'_>| match e.number() {
+        Some(n) => n.to_string(),
+        None => serde_json::Value::from(e.text().unwrap_or_default()).to_string(),
+    };
+    if !entries.is_empty() && entries.iter().all(|e| e.key().is_none()) {
+        let items: Vec<_> = entries.iter().map(|e| value(&e)).collect();
+        return format!("[{}]", items.join(","));
+    }
+    let members: Vec<_> = entries
+        .iter()
+        .map(|e| {
+            let key = serde_json::Value::from(e.key().unwrap_or_default());
+            format!("{key}:{}", value(&e))
+        })
+        .collect();
+    format!("{{{}}}", members.join(","))
+}
+
 fn convert_variant_pattern(vp: &dataformat::VariantPattern<'_>) -> VariantPattern {
     VariantPattern {
         matching_parameters: vp
@@ -867,8 +1177,8 @@ fn empty_param() -> Param {
     Param {
         id: 0,
         param_type: ParamType::Value,
-        short_name: String::new(),
-        semantic: String::new(),
+        short_name: Name::default(),
+        semantic: Name::default(),
         sdgs: None,
         physical_default_value: String::new(),
         byte_position: None,
@@ -890,11 +1200,11 @@ fn empty_table_dop() -> TableDop {
 
 fn convert_table_row(tr: &dataformat::TableRow<'_>) -> TableRow {
     TableRow {
-        short_name: s(tr.short_name()),
+        short_name: n(tr.short_name()),
         long_name: tr.long_name().map(|ln| convert_long_name(&ln)),
         key: s(tr.key()),
-        dop: tr.dop().map(|d| Box::new(convert_dop(&d))),
-        structure: tr.structure().map(|d| Box::new(convert_dop(&d))),
+        dop: tr.dop().map(|d| mapped_dop(&d)),
+        structure: tr.structure().map(|d| mapped_dop(&d)),
         sdgs: tr.sdgs().map(|sdgs| convert_sdgs(&sdgs)),
         audience: tr.audience().map(|a| convert_audience(&a)),
         funct_class_refs: tr
@@ -902,7 +1212,7 @@ fn convert_table_row(tr: &dataformat::TableRow<'_>) -> TableRow {
             .map(|v| {
                 (0..v.len())
                     .map(|i| FunctClass {
-                        short_name: s(v.get(i).short_name()),
+                        short_name: n(v.get(i).short_name()),
                     })
                     .collect()
             })
@@ -924,7 +1234,7 @@ fn convert_table_row(tr: &dataformat::TableRow<'_>) -> TableRow {
             })
             .unwrap_or_default(),
         is_executable: tr.is_executable(),
-        semantic: s(tr.semantic()),
+        semantic: n(tr.semantic()),
         is_mandatory: tr.is_mandatory(),
         is_final: tr.is_final(),
     }
@@ -932,12 +1242,12 @@ fn convert_table_row(tr: &dataformat::TableRow<'_>) -> TableRow {
 
 fn convert_table_dop(td: &dataformat::TableDop<'_>) -> TableDop {
     TableDop {
-        semantic: s(td.semantic()),
-        short_name: s(td.short_name()),
+        semantic: n(td.semantic()),
+        short_name: n(td.short_name()),
         long_name: td.long_name().map(|ln| convert_long_name(&ln)),
         key_label: s(td.key_label()),
         struct_label: s(td.struct_label()),
-        key_dop: td.key_dop().map(|d| Box::new(convert_dop(&d))),
+        key_dop: td.key_dop().map(|d| mapped_dop(&d)),
         rows: td
             .rows()
             .map(|v| (0..v.len()).map(|i| convert_table_row(&v.get(i))).collect())
@@ -976,7 +1286,7 @@ fn convert_table_dop(td: &dataformat::TableDop<'_>) -> TableDop {
                                 }
                                 _ => DiagServiceOrJob::DiagService(Box::new(empty_diag_service())),
                             },
-                            semantic: s(tdc.semantic()),
+                            semantic: n(tdc.semantic()),
                         }
                     })
                     .collect()
@@ -1091,7 +1401,7 @@ fn empty_protocol() -> Protocol {
 
 fn convert_prot_stack(ps: &dataformat::ProtStack<'_>) -> ProtStack {
     ProtStack {
-        short_name: s(ps.short_name()),
+        short_name: n(ps.short_name()),
         long_name: ps.long_name().map(|ln| convert_long_name(&ln)),
         pdu_protocol_type: s(ps.pdu_protocol_type()),
         physical_link_type: s(ps.physical_link_type()),
@@ -1133,7 +1443,7 @@ fn convert_unit_spec(us: &dataformat::UnitSpec<'_>) -> UnitSpec {
                     .map(|i| {
                         let ug = v.get(i);
                         UnitGroup {
-                            short_name: s(ug.short_name()),
+                            short_name: n(ug.short_name()),
                             long_name: ug.long_name().map(|ln| convert_long_name(&ln)),
                             unit_refs: ug
                                 .unitrefs()
@@ -1178,7 +1488,7 @@ fn convert_com_param(cp: &dataformat::ComParam<'_>) -> ComParam {
             .specific_data_as_regular_com_param()
             .map(|rcp| ComParamSpecificData::Regular {
                 physical_default_value: s(rcp.physical_default_value()),
-                dop: rcp.dop().map(|d| Box::new(convert_dop(&d))),
+                dop: rcp.dop().map(|d| mapped_dop(&d)),
             }),
         dataformat::ComParamSpecificData::ComplexComParam => cp
             .specific_data_as_complex_com_param()
@@ -1202,7 +1512,7 @@ fn convert_com_param(cp: &dataformat::ComParam<'_>) -> ComParam {
 
     ComParam {
         com_param_type: convert_com_param_type(cp.com_param_type()),
-        short_name: s(cp.short_name()),
+        short_name: n(cp.short_name()),
         long_name: cp.long_name().map(|ln| convert_long_name(&ln)),
         param_class: s(cp.param_class()),
         cp_type: convert_com_param_standardisation_level(cp.cp_type()),
@@ -1297,7 +1607,7 @@ fn convert_prog_code(pc: &dataformat::ProgCode<'_>) -> ProgCode {
                     .map(|i| {
                         let lib = v.get(i);
                         Library {
-                            short_name: s(lib.short_name()),
+                            short_name: n(lib.short_name()),
                             long_name: lib.long_name().map(|ln| convert_long_name(&ln)),
                             code_file: s(lib.code_file()),
                             encryption: s(lib.encryption()),
@@ -1313,18 +1623,18 @@ fn convert_prog_code(pc: &dataformat::ProgCode<'_>) -> ProgCode {
 
 fn convert_job_param(jp: &dataformat::JobParam<'_>) -> JobParam {
     JobParam {
-        short_name: s(jp.short_name()),
+        short_name: n(jp.short_name()),
         long_name: jp.long_name().map(|ln| convert_long_name(&ln)),
         physical_default_value: s(jp.physical_default_value()),
-        dop_base: jp.dop_base().map(|d| Box::new(convert_dop(&d))),
-        semantic: s(jp.semantic()),
+        dop_base: jp.dop_base().map(|d| mapped_dop(&d)),
+        semantic: n(jp.semantic()),
     }
 }
 
 fn convert_state_chart(sc: &dataformat::StateChart<'_>) -> StateChart {
     StateChart {
-        short_name: s(sc.short_name()),
-        semantic: s(sc.semantic()),
+        short_name: n(sc.short_name()),
+        semantic: n(sc.semantic()),
         state_transitions: sc
             .state_transitions()
             .map(|v| {
@@ -1332,7 +1642,7 @@ fn convert_state_chart(sc: &dataformat::StateChart<'_>) -> StateChart {
                     .map(|i| {
                         let st = v.get(i);
                         StateTransition {
-                            short_name: s(st.short_name()),
+                            short_name: n(st.short_name()),
                             source_short_name_ref: s(st.source_short_name_ref()),
                             target_short_name_ref: s(st.target_short_name_ref()),
                         }
@@ -1348,7 +1658,7 @@ fn convert_state_chart(sc: &dataformat::StateChart<'_>) -> StateChart {
                     .map(|i| {
                         let state = v.get(i);
                         State {
-                            short_name: s(state.short_name()),
+                            short_name: n(state.short_name()),
                             long_name: state.long_name().map(|ln| convert_long_name(&ln)),
                         }
                     })
@@ -1386,7 +1696,7 @@ fn convert_audience(a: &dataformat::Audience<'_>) -> Audience {
 
 fn convert_additional_audience(aa: &dataformat::AdditionalAudience<'_>) -> AdditionalAudience {
     AdditionalAudience {
-        short_name: s(aa.short_name()),
+        short_name: n(aa.short_name()),
         long_name: aa.long_name().map(|ln| convert_long_name(&ln)),
     }
 }
@@ -1397,7 +1707,7 @@ fn convert_state_transition_ref(
     StateTransitionRef {
         value: s(str_ref.value()),
         state_transition: str_ref.state_transition().map(|st| StateTransition {
-            short_name: s(st.short_name()),
+            short_name: n(st.short_name()),
             source_short_name_ref: s(st.source_short_name_ref()),
             target_short_name_ref: s(st.target_short_name_ref()),
         }),
@@ -1412,7 +1722,7 @@ fn convert_pre_condition_state_ref(
         in_param_if_short_name: s(pcsr.in_param_if_short_name()),
         in_param_path_short_name: s(pcsr.in_param_path_short_name()),
         state: pcsr.state().map(|state| State {
-            short_name: s(state.short_name()),
+            short_name: n(state.short_name()),
             long_name: state.long_name().map(|ln| convert_long_name(&ln)),
         }),
     }
@@ -0,0 +1,116 @@
+//! ECU identification as SDGs, for formats without a place for it.
+//!
+//! YAML states [`DiagDatabase::identification`] in its `identification`
+//! section and MDD in its own table; ODX has no element for it, so the ODX
+//! writer stores it as an `ecu_identification` SDG on the
+//! DIAG-LAYER-CONTAINER with one SD per set field (SI = field name) and a
+//! nested `dids` SDG mapping each DID's role to its number.
+
+use crate::types::{DiagDatabase, EcuIdentification, Sd, SdOrSdg, Sdg, Sdgs};
+
+/// SDG caption under which the ODX writer stores the identification.
+pub const IDENTIFICATION_CAPTION: &str = "ecu_identification";
+
+const DIDS_CAPTION: &str = "dids";
+
+/// The SDG holding `db`'s identification, or `None` when it has none.
+pub fn identification_sdg(db: &DiagDatabase) -> Option<Sdg> {
+    let ident = db.identification.as_ref().filter(|i| !i.is_empty())?;
+    let sd = |si: &str, value: String| {
+        SdOrSdg::Sd(Sd {
+            value,
+            si: si.into(),
+            ti: String::new(),
+        })
+    };
+    let fields = [
+        ("part_number", &ident.part_number),
+        ("supplier_code", &ident.supplier_code),
+        ("hardware_version", &ident.hardware_version),
+        ("software_version", &ident.software_version),
+    ];
+    let mut sds: Vec<SdOrSdg> = fields
+        .into_iter()
+        .filter_map(|(si, value)| Some(sd(si, value.clone()?)))
+        .collect();
+    if !ident.dids.is_empty() {
+        sds.push(SdOrSdg::Sdg(Sdg {
+            caption_sn: DIDS_CAPTION.into(),
+            sds: ident
+                .dids
+                .iter()
+                .map(|(role, did)| sd(role, format!("0x{did:04X}")))
+                .collect(),
+            si: String::new(),
+        }));
+    }
+    Some(Sdg {
+        caption_sn: IDENTIFICATION_CAPTION.into(),
+        sds,
+        si: String::new(),
+    })
+}
+
+/// Read the identification stored in `sdgs`. DIDs that do not parse are
+/// skipped.
+pub fn identification_of(sdgs: Option<&Sdgs>) -> Option<EcuIdentification> {
+    let sdg = sdgs?
+        .sdgs
+        .iter()
+        .find(|sdg| sdg.caption_sn == IDENTIFICATION_CAPTION)?;
+    let mut ident = EcuIdentification::default();
+    for entry in &sdg.sds {
+        match entry {
+            SdOrSdg::Sd(sd) => {
+                let value = Some(sd.value.clone());
+                match sd.si.as_str() {
+                    "part_number" => ident.part_number = value,
+                    "supplier_code" => ident.supplier_code = value,
+                    "hardware_version" => ident.hardware_version = value,
+                    "software_version" => ident.software_version = value,
+                    _ => {}
+                }
+            }
+            SdOrSdg::Sdg(dids) if dids.caption_sn == DIDS_CAPTION => {
+                for did in &dids.sds {
+                    if let SdOrSdg::Sd(sd) = did {
+                        if let Some(n) = crate::transport::parse_address(&sd.value) {
+                            ident.dids.insert(sd.si.clone(), n);
+                        }
+                    }
+                }
+            }
+            SdOrSdg::Sdg(_) => {}
+        }
+    }
+    Some(ident)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identification_roundtrip_through_sdgs() {
+        let ident = EcuIdentification {
+            part_number: Some("8W0907115A".into()),
+            supplier_code: Some("BOSCH".into()),
+            hardware_version: None,
+            software_version: Some("0042".into()),
+            dids: [
+                ("vin".to_string(), 0xF190),
+                ("part_number".to_string(), 0xF187),
+            ]
+            .into(),
+        };
+        let mut db = DiagDatabase::default();
+        assert_eq!(identification_sdg(&db), None);
+
+        db.identification = Some(ident.clone());
+        let sdgs = Sdgs {
+            sdgs: vec![identification_sdg(&db).unwrap()],
+        };
+        assert_eq!(identification_of(Some(&sdgs)), Some(ident));
+        assert_eq!(identification_of(None), None);
+    }
+}
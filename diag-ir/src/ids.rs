@@ -0,0 +1,301 @@
+//! Numeric identifier allocation: which DIDs, routine IDs and DTC numbers a
+//! database uses, whether they fall inside the ranges an OEM reserved for the
+//! ECU, and which identifier is free next.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::numbers::parse_uint;
+use crate::types::{DiagDatabase, DiagService, ParamData};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IdKind {
+    Did,
+    Rid,
+    Dtc,
+}
+
+impl IdKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IdKind::Did => "DID",
+            IdKind::Rid => "RID",
+            IdKind::Dtc => "DTC",
+        }
+    }
+
+    /// Hex digits used when printing an identifier of this kind.
+    fn width(self) -> usize {
+        match self {
+            IdKind::Did | IdKind::Rid => 4,
+            IdKind::Dtc => 6,
+        }
+    }
+
+    pub fn format(self, id: u32) -> String {
+        format!("0x{id:0width$X}", width = self.width())
+    }
+}
+
+impl fmt::Display for IdKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Inclusive identifier range, written `0x0200-0x02FF`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct IdRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl IdRange {
+    pub fn contains(&self, id: u32) -> bool {
+        (self.start..=self.end).contains(&id)
+    }
+}
+
+impl FromStr for IdRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |v: &str| {
+            parse_uint(v)
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(|| format!("invalid identifier '{}' in range '{s}'", v.trim()))
+        };
+        let (start, end) = s.split_once('-').unwrap_or((s, s));
+        let (start, end) = (parse(start)?, parse(end)?);
+        if start > end {
+            return Err(format!("range '{s}' ends before it starts"));
+        }
+        Ok(IdRange { start, end })
+    }
+}
+
+impl TryFrom<String> for IdRange {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Reserved identifier ranges per kind. A kind without ranges is not checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IdPolicy {
+    #[serde(default)]
+    pub dids: Vec<IdRange>,
+    #[serde(default)]
+    pub rids: Vec<IdRange>,
+    #[serde(default)]
+    pub dtcs: Vec<IdRange>,
+}
+
+impl IdPolicy {
+    pub fn ranges(&self, kind: IdKind) -> &[IdRange] {
+        match kind {
+            IdKind::Did => &self.dids,
+            IdKind::Rid => &self.rids,
+            IdKind::Dtc => &self.dtcs,
+        }
+    }
+}
+
+/// An identifier outside every range reserved for its kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdViolation {
+    pub kind: IdKind,
+    pub id: u32,
+    /// Services or DTCs using the identifier.
+    pub users: Vec<String>,
+}
+
+impl fmt::Display for IdViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} is outside the reserved ranges (used by {})",
+            self.kind,
+            self.kind.format(self.id),
+            self.users.join(", ")
+        )
+    }
+}
+
+/// Every identifier in the database with the services/DTCs using it.
+pub fn used_ids(db: &DiagDatabase) -> BTreeMap<(IdKind, u32), BTreeSet<String>> {
+    let mut used: BTreeMap<(IdKind, u32), BTreeSet<String>> = BTreeMap::new();
+    let layers = db
+        .variants
+        .iter()
+        .map(|v| &v.diag_layer)
+        .chain(db.functional_groups.iter().map(|fg| &fg.diag_layer))
+        .chain(db.protocols.iter().map(|p| &p.diag_layer))
+        .chain(db.ecu_shared_datas.iter().map(|e| &e.diag_layer));
+    for layer in layers {
+        for svc in &layer.diag_services {
+            if let Some(key) = service_id(svc) {
+                used.entry(key)
+                    .or_default()
+                    .insert(svc.diag_comm.short_name.to_string());
+            }
+        }
+    }
+    for dtc in &db.dtcs {
+        used.entry((IdKind::Dtc, dtc.trouble_code))
+            .or_default()
+            .insert(dtc.short_name.to_string());
+    }
+    used
+}
+
+/// The DID (0x22/0x2E/0x2F) or routine ID (0x31) a service addresses.
+fn service_id(svc: &DiagService) -> Option<(IdKind, u32)> {
+    let coded_at = |byte| request_coded_value(svc, byte);
+    match coded_at(0)? {
+        0x22 | 0x2E | 0x2F => Some((IdKind::Did, coded_at(1)?)),
+        0x31 => Some((IdKind::Rid, coded_at(2)?)),
+        _ => None,
+    }
+}
+
+/// The coded constant at `byte` of the service's request.
+pub(crate) fn request_coded_value(svc: &DiagService, byte: u32) -> Option<u32> {
+    svc.request
+        .as_ref()?
+        .params
+        .iter()
+        .find_map(|p| match &p.specific_data {
+            Some(ParamData::CodedConst { coded_value, .. }) if p.byte_position == Some(byte) => {
+                parse_uint(coded_value).and_then(|v| u32::try_from(v).ok())
+            }
+            _ => None,
+        })
+}
+
+/// Identifiers that fall outside the policy's ranges for their kind.
+pub fn check_ids(db: &DiagDatabase, policy: &IdPolicy) -> Vec<IdViolation> {
+    used_ids(db)
+        .into_iter()
+        .filter(|((kind, id), _)| {
+            let ranges = policy.ranges(*kind);
+            !ranges.is_empty() && !ranges.iter().any(|r| r.contains(*id))
+        })
+        .map(|((kind, id), users)| IdViolation {
+            kind,
+            id,
+            users: users.into_iter().collect(),
+        })
+        .collect()
+}
+
+/// The first `count` identifiers of `kind` in `range` that the database does
+/// not use yet.
+pub fn next_free_ids(db: &DiagDatabase, kind: IdKind, range: IdRange, count: usize) -> Vec<u32> {
+    let used = used_ids(db);
+    (range.start..=range.end)
+        .filter(|id| !used.contains_key(&(kind, *id)))
+        .take(count)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, service};
+    use crate::types::*;
+
+    fn coded(byte: u32, value: &str) -> Param {
+        Param {
+            byte_position: Some(byte),
+            specific_data: Some(ParamData::CodedConst {
+                coded_value: value.into(),
+                diag_coded_type: DiagCodedType::default(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// A service whose request holds `params`.
+    fn requesting(name: &str, params: Vec<Param>) -> DiagService {
+        DiagService {
+            request: Some(Request { params, sdgs: None }),
+            ..service(name, "")
+        }
+    }
+
+    fn database() -> DiagDatabase {
+        let services = vec![
+            requesting("A_Read", vec![coded(0, "34"), coded(1, "512")]),
+            requesting("A_Write", vec![coded(0, "46"), coded(1, "512")]),
+            requesting("B_Read", vec![coded(0, "34"), coded(1, "0x0201")]),
+            requesting("Vin_Read", vec![coded(0, "34"), coded(1, "61840")]),
+            requesting(
+                "Erase_Start",
+                vec![coded(0, "49"), coded(1, "1"), coded(2, "0xFF00")],
+            ),
+        ];
+        DiagDatabase {
+            dtcs: vec![Dtc {
+                short_name: "P0123".into(),
+                trouble_code: 0x012300,
+                ..Default::default()
+            }],
+            ..test_support::database(services)
+        }
+    }
+
+    #[test]
+    fn test_range_parsing() {
+        assert_eq!(
+            "0x0200-0x02FF".parse(),
+            Ok(IdRange {
+                start: 0x200,
+                end: 0x2FF
+            })
+        );
+        assert_eq!("0xF190".parse::<IdRange>().unwrap().end, 0xF190);
+        assert!("0x02FF-0x0200".parse::<IdRange>().is_err());
+        assert!("0x02XX-0x0300".parse::<IdRange>().is_err());
+    }
+
+    #[test]
+    fn test_check_ids_reports_out_of_range_ids() {
+        let policy = IdPolicy {
+            dids: vec!["0x0200-0x02FF".parse().unwrap()],
+            rids: vec![],
+            dtcs: vec!["0x010000-0x01FFFF".parse().unwrap()],
+        };
+        let violations = check_ids(&database(), &policy);
+        assert_eq!(
+            violations,
+            [IdViolation {
+                kind: IdKind::Did,
+                id: 0xF190,
+                users: vec!["Vin_Read".into()],
+            }]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "DID 0xF190 is outside the reserved ranges (used by Vin_Read)"
+        );
+    }
+
+    #[test]
+    fn test_next_free_ids_skips_used() {
+        let range = "0x0200-0x0204".parse().unwrap();
+        assert_eq!(
+            next_free_ids(&database(), IdKind::Did, range, 2),
+            [0x202, 0x203]
+        );
+        let full = "0x0200-0x0201".parse().unwrap();
+        assert!(next_free_ids(&database(), IdKind::Did, full, 1).is_empty());
+    }
+}
@@ -0,0 +1,123 @@
+//! IR <-> JSON.
+//!
+//! JSON is the IR itself, serialized with serde, so it carries everything the
+//! IR holds. It is meant for web tooling and scripts that have no FlatBuffers
+//! bindings. The database is wrapped in an envelope naming the schema:
+//!
+//! ```json
+//! { "schema": "diag-converter/ir/v1", "database": { "ecu_name": "ECM", ... } }
+//! ```
+//!
+//! Non-finite floats (NaN, infinity) have no JSON form and are rejected.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::loss::FormatCapabilities;
+use crate::types::DiagDatabase;
+
+/// Schema identifier in the `schema` key of every JSON document.
+pub const JSON_SCHEMA: &str = "diag-converter/ir/v1";
+
+/// JSON is a direct serialization of the IR and loses nothing.
+pub const JSON_CAPABILITIES: FormatCapabilities = FormatCapabilities {
+    format: "JSON",
+    functional_groups: true,
+    memory: true,
+    type_definitions: true,
+    descriptions: true,
+    tables: true,
+    complex_dops: true,
+    additional_audiences: true,
+    custom_state_charts: true,
+};
+
+#[derive(Debug, Error)]
+pub enum JsonError {
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("unsupported schema '{0}' (expected {JSON_SCHEMA})")]
+    Schema(String),
+}
+
+#[derive(Serialize)]
+struct Document<'a> {
+    schema: &'a str,
+    database: &'a DiagDatabase,
+}
+
+/// The `schema` key alone, checked before the database is parsed so a
+/// document of another schema is reported as such.
+#[derive(Deserialize)]
+struct Header {
+    schema: String,
+}
+
+#[derive(Deserialize)]
+struct OwnedDocument {
+    database: DiagDatabase,
+}
+
+/// Write a DiagDatabase IR to a pretty-printed JSON string.
+pub fn write_json(db: &DiagDatabase) -> Result<String, JsonError> {
+    let doc = Document {
+        schema: JSON_SCHEMA,
+        database: db,
+    };
+    Ok(serde_json::to_string_pretty(&doc)? + "\n")
+}
+
+/// Parse a JSON string written by [`write_json`].
+pub fn parse_json(text: &str) -> Result<DiagDatabase, JsonError> {
+    let header: Header = serde_json::from_str(text)?;
+    if header.schema != JSON_SCHEMA {
+        return Err(JsonError::Schema(header.schema));
+    }
+    let doc: OwnedDocument = serde_json::from_str(text)?;
+    Ok(doc.database)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::*;
+
+    #[test]
+    fn test_json_roundtrip() {
+        let db = DiagDatabase {
+            ecu_name: "ECM".into(),
+            version: "1.0".into(),
+            variants: vec![Variant {
+                diag_layer: DiagLayer {
+                    short_name: "Base".into(),
+                    diag_services: vec![DiagService {
+                        diag_comm: DiagComm {
+                            short_name: "VIN_Read".into(),
+                            semantic: "DATA-READ".into(),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                is_base_variant: true,
+                ..Default::default()
+            }],
+            dtcs: vec![Dtc {
+                short_name: "P0100".into(),
+                trouble_code: 0x0100,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let json = write_json(&db).unwrap();
+        assert!(json.contains(r#""schema": "diag-converter/ir/v1""#));
+        assert_eq!(parse_json(&json).unwrap(), db);
+    }
+
+    #[test]
+    fn test_json_rejects_unknown_schema() {
+        let err = parse_json(r#"{"schema": "other/v9", "database": {}}"#).unwrap_err();
+        assert!(matches!(err, JsonError::Schema(ref s) if s == "other/v9"));
+    }
+}
@@ -0,0 +1,398 @@
+//! Byte sizes of DOPs, computed from their layout.
+//!
+//! Structures and static fields declare their size (BYTE-SIZE and
+//! ITEM-BYTE-SIZE in ODX, `size` in YAML) next to the parameters that make it
+//! up. Decoders trust the declared value, so one that disagrees with the
+//! layout shifts every following parameter.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::types::*;
+
+/// Size in bytes of the data `dop` describes, or `None` if it is not fixed
+/// (or depends on a coded type of another length kind).
+///
+/// Structures span to the end of their last parameter. A parameter without
+/// BYTE-POSITION starts at the byte after the previous one.
+pub fn compute_byte_size(dop: &Dop) -> Option<u32> {
+    bit_length(dop).map(|bits| bits.div_ceil(8))
+}
+
+/// Size in bytes of a parameter list laid out as in a structure, or `None`
+/// if any parameter has no fixed length.
+pub fn params_byte_size(params: &[Param]) -> Option<u32> {
+    let mut next_byte = 0u32;
+    let mut end_bit = 0u32;
+    for param in params {
+        let start = param
+            .byte_position
+            .unwrap_or(next_byte)
+            .checked_mul(8)?
+            .checked_add(param.bit_position.unwrap_or(0))?;
+        let end = start.checked_add(param_bit_length(param)?)?;
+        end_bit = end_bit.max(end);
+        next_byte = end.div_ceil(8);
+    }
+    Some(end_bit.div_ceil(8))
+}
+
+fn bit_length(dop: &Dop) -> Option<u32> {
+    match dop.specific_data.as_ref()? {
+        DopData::NormalDop {
+            diag_coded_type, ..
+        }
+        | DopData::DtcDop {
+            diag_coded_type, ..
+        } => coded_bit_length(diag_coded_type.as_ref()?),
+        DopData::Structure { params, .. } => params_byte_size(params)?.checked_mul(8),
+        DopData::StaticField {
+            fixed_number_of_items,
+            item_byte_size,
+            ..
+        } => fixed_number_of_items
+            .checked_mul(*item_byte_size)?
+            .checked_mul(8),
+        _ => None,
+    }
+}
+
+fn coded_bit_length(coded: &DiagCodedType) -> Option<u32> {
+    match coded.specific_data.as_ref()? {
+        DiagCodedTypeData::StandardLength { bit_length, .. } => Some(*bit_length),
+        _ => None,
+    }
+}
+
+fn param_bit_length(param: &Param) -> Option<u32> {
+    match param.specific_data.as_ref()? {
+        ParamData::CodedConst {
+            diag_coded_type, ..
+        }
+        | ParamData::NrcConst {
+            diag_coded_type, ..
+        } => coded_bit_length(diag_coded_type),
+        ParamData::Value { dop, .. }
+        | ParamData::PhysConst { dop, .. }
+        | ParamData::System { dop, .. }
+        | ParamData::LengthKeyRef { dop } => bit_length(dop),
+        ParamData::Reserved { bit_length } => Some(*bit_length),
+        ParamData::MatchingRequestParam { byte_length, .. } => byte_length.checked_mul(8),
+        _ => None,
+    }
+}
+
+/// A declared size that disagrees with the computed one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteSizeMismatch {
+    pub dop: String,
+    /// `BYTE-SIZE` of a structure or `ITEM-BYTE-SIZE` of a static field.
+    pub attribute: &'static str,
+    pub declared: u32,
+    pub computed: u32,
+}
+
+impl fmt::Display for ByteSizeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "DOP '{}': {} is {} but its layout takes {} byte(s)",
+            self.dop, self.attribute, self.declared, self.computed
+        )
+    }
+}
+
+/// Check the declared sizes of every structure and static field used by a
+/// service parameter. Each DOP is checked once, under its short name; DOPs
+/// whose size cannot be computed are skipped.
+pub fn check_byte_sizes(db: &DiagDatabase) -> Vec<ByteSizeMismatch> {
+    let mut dops = BTreeMap::new();
+    for variant in &db.variants {
+        for service in &variant.diag_layer.diag_services {
+            let params = service
+                .request
+                .iter()
+                .flat_map(|r| &r.params)
+                .chain(service.pos_responses.iter().flat_map(|r| &r.params))
+                .chain(service.neg_responses.iter().flat_map(|r| &r.params));
+            for param in params {
+                if let Some(dop) = param_dop(param) {
+                    collect_dops(dop, &mut dops);
+                }
+            }
+        }
+    }
+    dops.values().filter_map(|dop| check_dop(dop)).collect()
+}
+
+fn param_dop(param: &Param) -> Option<&Dop> {
+    match param.specific_data.as_ref()? {
+        ParamData::Value { dop, .. }
+        | ParamData::PhysConst { dop, .. }
+        | ParamData::System { dop, .. }
+        | ParamData::LengthKeyRef { dop } => Some(dop),
+        _ => None,
+    }
+}
+
+fn collect_dops<'a>(dop: &'a Dop, dops: &mut BTreeMap<&'a str, &'a Dop>) {
+    if dops.contains_key(dop.short_name.as_str()) {
+        return;
+    }
+    dops.insert(&dop.short_name, dop);
+    let nested: Vec<&Dop> = match &dop.specific_data {
+        Some(DopData::Structure { params, .. }) => params.iter().filter_map(param_dop).collect(),
+        Some(
+            DopData::StaticField { field, .. }
+            | DopData::EndOfPduField { field, .. }
+            | DopData::DynamicLengthField { field, .. },
+        ) => field
+            .iter()
+            .filter_map(|f| f.basic_structure.as_deref())
+            .collect(),
+        Some(DopData::MuxDop {
+            default_case,
+            cases,
+            ..
+        }) => default_case
+            .iter()
+            .filter_map(|c| c.structure.as_deref())
+            .chain(cases.iter().filter_map(|c| c.structure.as_deref()))
+            .collect(),
+        _ => vec![],
+    };
+    for nested in nested {
+        collect_dops(nested, dops);
+    }
+}
+
+fn check_dop(dop: &Dop) -> Option<ByteSizeMismatch> {
+    let (attribute, declared, computed) = match dop.specific_data.as_ref()? {
+        DopData::Structure {
+            params, byte_size, ..
+        } => ("BYTE-SIZE", (*byte_size)?, params_byte_size(params)?),
+        DopData::StaticField {
+            item_byte_size,
+            field,
+            ..
+        } => (
+            "ITEM-BYTE-SIZE",
+            *item_byte_size,
+            compute_byte_size(field.as_ref()?.basic_structure.as_deref()?)?,
+        ),
+        _ => return None,
+    };
+    (declared != computed).then(|| ByteSizeMismatch {
+        dop: dop.short_name.to_string(),
+        attribute,
+        declared,
+        computed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uint(name: &str, bits: u32) -> Dop {
+        Dop {
+            dop_type: DopType::Regular,
+            short_name: name.into(),
+            sdgs: None,
+            specific_data: Some(DopData::NormalDop {
+                compu_method: None,
+                diag_coded_type: Some(DiagCodedType {
+                    type_name: DiagCodedTypeName::StandardLengthType,
+                    base_type_encoding: String::new(),
+                    base_data_type: DataType::AUint32,
+                    is_high_low_byte_order: true,
+                    specific_data: Some(DiagCodedTypeData::StandardLength {
+                        bit_length: bits,
+                        bit_mask: vec![],
+                        condensed: false,
+                    }),
+                }),
+                physical_type: None,
+                internal_constr: None,
+                unit_ref: None,
+                phys_constr: None,
+            }),
+        }
+    }
+
+    fn value(dop: Dop, byte_position: Option<u32>, bit_position: Option<u32>) -> Param {
+        Param {
+            id: 0,
+            param_type: ParamType::Value,
+            short_name: dop.short_name.clone(),
+            semantic: "DATA".into(),
+            sdgs: None,
+            physical_default_value: String::new(),
+            byte_position,
+            bit_position,
+            specific_data: Some(ParamData::Value {
+                physical_default_value: String::new(),
+                dop: DopRef::new(dop),
+            }),
+        }
+    }
+
+    fn structure(name: &str, params: Vec<Param>, byte_size: Option<u32>) -> Dop {
+        Dop {
+            dop_type: DopType::Structure,
+            short_name: name.into(),
+            sdgs: None,
+            specific_data: Some(DopData::Structure {
+                params,
+                byte_size,
+                is_visible: true,
+            }),
+        }
+    }
+
+    fn static_field(name: &str, items: u32, item_byte_size: u32, item: Dop) -> Dop {
+        Dop {
+            dop_type: DopType::StaticField,
+            short_name: name.into(),
+            sdgs: None,
+            specific_data: Some(DopData::StaticField {
+                fixed_number_of_items: items,
+                item_byte_size,
+                field: Some(Field {
+                    basic_structure: Some(DopRef::new(item)),
+                    env_data_desc: None,
+                    is_visible: true,
+                }),
+            }),
+        }
+    }
+
+    fn db(response_params: Vec<Param>) -> DiagDatabase {
+        DiagDatabase {
+            ecu_name: "ECU".into(),
+            variants: vec![Variant {
+                diag_layer: DiagLayer {
+                    short_name: "Base".into(),
+                    diag_services: vec![DiagService {
+                        diag_comm: DiagComm {
+                            short_name: "ReadData".into(),
+                            ..Default::default()
+                        },
+                        pos_responses: vec![Response {
+                            response_type: ResponseType::PosResponse,
+                            params: response_params,
+                            sdgs: None,
+                            pos_response_suffix: None,
+                            audience: None,
+                        }],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                is_base_variant: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn standard_lengths_round_up_to_bytes() {
+        assert_eq!(compute_byte_size(&uint("u8", 8)), Some(1));
+        assert_eq!(compute_byte_size(&uint("u12", 12)), Some(2));
+        assert_eq!(compute_byte_size(&uint("u32", 32)), Some(4));
+    }
+
+    #[test]
+    fn structure_spans_to_its_last_param() {
+        // Implicit positions follow each other; bit fields share a byte.
+        let packed = structure(
+            "Packed",
+            vec![
+                value(uint("a", 16), None, None),
+                value(uint("b", 8), None, None),
+                value(uint("flags_lo", 4), Some(3), Some(0)),
+                value(uint("flags_hi", 4), Some(3), Some(4)),
+            ],
+            None,
+        );
+        assert_eq!(compute_byte_size(&packed), Some(4));
+
+        let gap = structure("Gap", vec![value(uint("a", 8), Some(6), None)], None);
+        assert_eq!(compute_byte_size(&gap), Some(7));
+
+        let nested = structure("Nested", vec![value(packed, None, None)], None);
+        assert_eq!(compute_byte_size(&nested), Some(4));
+
+        let field = static_field("Items", 3, 5, uint("item", 24));
+        assert_eq!(compute_byte_size(&field), Some(15));
+    }
+
+    #[test]
+    fn variable_lengths_have_no_size() {
+        let mut unknown = uint("text", 0);
+        if let Some(DopData::NormalDop {
+            diag_coded_type: Some(coded),
+            ..
+        }) = &mut unknown.specific_data
+        {
+            coded.specific_data = Some(DiagCodedTypeData::LeadingLength { bit_length: 8 });
+        }
+        assert_eq!(compute_byte_size(&unknown), None);
+        let s = structure(
+            "S",
+            vec![value(uint("a", 8), None, None), value(unknown, None, None)],
+            None,
+        );
+        assert_eq!(compute_byte_size(&s), None);
+    }
+
+    #[test]
+    fn declared_sizes_are_checked() {
+        let ok = structure(
+            "Ok",
+            vec![
+                value(uint("a", 16), None, None),
+                value(uint("b", 8), None, None),
+            ],
+            Some(3),
+        );
+        let wrong = structure(
+            "Wrong",
+            vec![
+                value(uint("a", 16), None, None),
+                value(uint("b", 8), None, None),
+            ],
+            Some(8),
+        );
+        let field = static_field("Items", 2, 4, ok.clone());
+        let db = db(vec![
+            value(ok, Some(1), None),
+            value(wrong, None, None),
+            value(field, None, None),
+        ]);
+
+        let mismatches = check_byte_sizes(&db);
+        assert_eq!(
+            mismatches,
+            vec![
+                ByteSizeMismatch {
+                    dop: "Items".into(),
+                    attribute: "ITEM-BYTE-SIZE",
+                    declared: 4,
+                    computed: 3,
+                },
+                ByteSizeMismatch {
+                    dop: "Wrong".into(),
+                    attribute: "BYTE-SIZE",
+                    declared: 8,
+                    computed: 3,
+                },
+            ]
+        );
+        assert_eq!(
+            mismatches[1].to_string(),
+            "DOP 'Wrong': BYTE-SIZE is 8 but its layout takes 3 byte(s)"
+        );
+    }
+}
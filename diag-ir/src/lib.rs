@@ -1,11 +1,125 @@
+pub mod access;
+pub mod aliases;
+pub mod cache;
+pub mod codec;
+pub mod comparam_defaults;
+pub mod compatibility;
+pub mod complexity;
+pub mod constraints;
+pub mod diff;
+pub mod dtc_texts;
+pub mod duplicates;
+pub mod edit;
+pub mod equivalence;
+pub mod expr;
+pub mod features;
 pub mod filter;
+pub mod flash;
 pub mod from_fbs;
+pub mod identification;
+pub mod ids;
+pub mod json;
+pub mod layout;
+pub mod lifecycle;
+pub mod loss;
+pub mod merge;
+pub mod name;
+pub mod normalize;
+mod numbers;
+pub mod object_index;
+pub mod profile;
+pub mod query;
+pub mod rename;
+pub mod salvage;
+pub mod sanitize;
+pub mod search;
+pub mod semantic;
+pub mod short_names;
+pub mod stats;
+#[cfg(test)]
+mod test_support;
 pub mod to_fbs;
+pub mod trace;
+pub mod transport;
+pub mod type_definitions;
 pub mod types;
 pub mod validate;
+pub mod validity;
+pub mod variant_detect;
+pub mod view;
 
-pub use filter::filter_by_audience;
-pub use from_fbs::flatbuffers_to_ir;
-pub use to_fbs::ir_to_flatbuffers;
+pub use access::{AccessKind, AccessRequirements, access_matrix};
+pub use aliases::{ALIASES_CAPTION, NameMatch, aliases, find_by_name, name_match, set_aliases};
+pub use cache::DatabaseCache;
+pub use comparam_defaults::{UnresolvedDefault, resolve_comparam_defaults};
+pub use compatibility::{
+    COMPATIBILITY_CAPTION, Compatibility, compatibility, compatibility_of, set_compatibility,
+};
+pub use complexity::{ComplexityPolicy, ComplexityRule, ComplexityViolation, check_complexity};
+pub use constraints::{
+    ConstraintWarning, ParamConstraints, check_phys_constraints, param_constraints,
+};
+pub use diff::{Change, DatabaseDiff, diff_databases};
+pub use dtc_texts::{
+    DTC_TEXTS_CAPTION, DtcText, DtcTextError, dtc_texts, dtc_translations, merge_dtc_texts,
+    set_dtc_translation,
+};
+pub use duplicates::{
+    Duplicate, DuplicateError, DuplicatePolicy, resolve_dtc_duplicates, resolve_duplicates,
+};
+pub use edit::{EditError, Editor};
+pub use equivalence::{FieldDifference, canonical_form, compare_databases, equivalent};
+pub use expr::{CompareOp, Expr, ExprError, ExprField, ExprSubject, FilterExpr, filter_by_expr};
+pub use features::{Feature, FeatureFlags, read_feature_flags};
+pub use filter::{
+    AudienceFilter, AudienceTerm, FEATURE_TAGS_CAPTION, FEATURES_METADATA_KEY, FilterError,
+    SemanticMatch, by_semantic, feature_tags, filter_by_audience, filter_by_features,
+    filter_by_functional_group, set_feature_tags,
+};
+pub use flash::{
+    FLASH_SEQUENCE_CAPTION, FlashPlanStep, FlashStep, check_memory_service_name, flash_plan,
+    flash_sequence, flash_sequence_sdg, service_sid,
+};
+pub use from_fbs::{flatbuffers_to_ir, flatbuffers_to_ir_variants};
+pub use identification::{IDENTIFICATION_CAPTION, identification_of, identification_sdg};
+pub use ids::{IdKind, IdPolicy, IdRange, IdViolation, check_ids, next_free_ids, used_ids};
+pub use json::{JSON_CAPABILITIES, JSON_SCHEMA, JsonError, parse_json, write_json};
+pub use layout::{ByteSizeMismatch, check_byte_sizes, compute_byte_size, params_byte_size};
+pub use lifecycle::{
+    LIFECYCLE_CAPTION, Lifecycle, LifecycleStatus, filter_by_lifecycle, lifecycle, set_lifecycle,
+};
+pub use loss::{FormatCapabilities, Loss, find_losses};
+pub use merge::{Conflict, MergeConflict, merge};
+pub use name::{Interner, Name};
+pub use normalize::normalize;
+pub use numbers::{parse_hex_bytes, parse_uint};
+pub use object_index::{ObjectLocation, object_locations};
+pub use profile::{Profile, ProfileError, ProfileMapping, apply_profile};
+pub use query::{QueryError, Selector, extract};
+pub use rename::{Rename, RenameKind, RenameReport, apply_renames};
+pub use salvage::{Salvage, salvage_flatbuffers};
+pub use sanitize::{InvalidCharPolicy, InvalidChars, SanitizeError, is_invalid_xml_char, sanitize};
+pub use search::{SearchEntry, SearchIndex, SearchIndexError, SearchKind};
+pub use semantic::Semantic;
+pub use short_names::{
+    MAX_SHORT_NAME_LEN, ShortNameFix, ShortNameIssue, ShortNameRule, check_short_names,
+    fix_short_names,
+};
+pub use stats::{
+    ContributorKind, DatabaseStats, ServiceStats, SizeBreakdown, SizeContribution, VariantDetail,
+    VariantStats, param_count, variant_stats,
+};
+pub use to_fbs::{
+    FBS_CAPABILITIES, FbsWriteOptions, ir_to_flatbuffers, ir_to_flatbuffers_with_options,
+};
+pub use trace::{REQUIREMENTS_CAPTION, TraceLink, requirements, set_requirements, trace_links};
+pub use transport::{
+    TRANSPORT_CAPTION, complete_transport, doip_from_comparams, transport_of, transport_sdg,
+};
+pub use type_definitions::{TYPE_DEFINITIONS_CAPTION, type_definitions_of, type_definitions_sdg};
 pub use types::*;
 pub use validate::validate_database;
+pub use validity::{
+    Date, VALIDITY_CAPTION, Validity, ValidityStatus, set_validity, validity, validity_of,
+};
+pub use view::DatabaseView;
@@ -0,0 +1,240 @@
+//! Lifecycle status of services, DIDs and DTCs.
+//!
+//! The status is stored as a `lifecycle` SDG with a `status` SD and an
+//! optional `since` SD, so it travels through YAML, ODX and MDD without any
+//! schema change.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{DiagDatabase, Sd, SdOrSdg, Sdg, Sdgs};
+
+/// SDG caption under which the lifecycle is stored.
+pub const LIFECYCLE_CAPTION: &str = "lifecycle";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LifecycleStatus {
+    Draft,
+    Released,
+    Deprecated,
+    Obsolete,
+}
+
+impl LifecycleStatus {
+    pub const ALL: [LifecycleStatus; 4] = [
+        LifecycleStatus::Draft,
+        LifecycleStatus::Released,
+        LifecycleStatus::Deprecated,
+        LifecycleStatus::Obsolete,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LifecycleStatus::Draft => "draft",
+            LifecycleStatus::Released => "released",
+            LifecycleStatus::Deprecated => "deprecated",
+            LifecycleStatus::Obsolete => "obsolete",
+        }
+    }
+}
+
+impl fmt::Display for LifecycleStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LifecycleStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|status| status.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "unknown lifecycle status '{s}' \
+                     (expected draft, released, deprecated or obsolete)"
+                )
+            })
+    }
+}
+
+/// Lifecycle of one service, DID or DTC.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lifecycle {
+    pub status: LifecycleStatus,
+    /// Version in which the status took effect, e.g. `"2.1"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+}
+
+impl fmt::Display for Lifecycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.since {
+            Some(since) => write!(f, "{} since {since}", self.status),
+            None => write!(f, "{}", self.status),
+        }
+    }
+}
+
+/// Read the lifecycle stored in `sdgs`, if any.
+pub fn lifecycle(sdgs: Option<&Sdgs>) -> Option<Lifecycle> {
+    let sdg = sdgs?
+        .sdgs
+        .iter()
+        .find(|sdg| sdg.caption_sn == LIFECYCLE_CAPTION)?;
+    let sd = |si: &str| {
+        sdg.sds.iter().find_map(|sd| match sd {
+            SdOrSdg::Sd(sd) if sd.si == si => Some(sd.value.clone()),
+            _ => None,
+        })
+    };
+    Some(Lifecycle {
+        status: sd("status")?.parse().ok()?,
+        since: sd("since"),
+    })
+}
+
+/// Store `lifecycle` in `sdgs`, replacing any previous lifecycle.
+pub fn set_lifecycle(sdgs: &mut Option<Sdgs>, lifecycle: &Lifecycle) {
+    let sd = |si: &str, value: &str| {
+        SdOrSdg::Sd(Sd {
+            value: value.to_string(),
+            si: si.to_string(),
+            ti: String::new(),
+        })
+    };
+    let mut sds = vec![sd("status", lifecycle.status.as_str())];
+    if let Some(since) = &lifecycle.since {
+        sds.push(sd("since", since));
+    }
+    let sdgs = &mut sdgs.get_or_insert_with(|| Sdgs { sdgs: vec![] }).sdgs;
+    sdgs.retain(|sdg| sdg.caption_sn != LIFECYCLE_CAPTION);
+    sdgs.push(Sdg {
+        caption_sn: LIFECYCLE_CAPTION.into(),
+        sds,
+        si: String::new(),
+    });
+}
+
+/// Remove every service, job and DTC whose lifecycle status is in `excluded`.
+/// Returns the number of removed entities.
+pub fn filter_by_lifecycle(db: &mut DiagDatabase, excluded: &[LifecycleStatus]) -> usize {
+    let keep = |sdgs: Option<&Sdgs>| lifecycle(sdgs).is_none_or(|l| !excluded.contains(&l.status));
+    let mut removed = 0;
+    let layers = db
+        .variants
+        .iter_mut()
+        .map(|v| &mut v.diag_layer)
+        .chain(db.functional_groups.iter_mut().map(|fg| &mut fg.diag_layer))
+        .chain(db.protocols.iter_mut().map(|p| &mut p.diag_layer))
+        .chain(db.ecu_shared_datas.iter_mut().map(|e| &mut e.diag_layer));
+    for layer in layers {
+        let before = layer.diag_services.len() + layer.single_ecu_jobs.len();
+        layer
+            .diag_services
+            .retain(|svc| keep(svc.diag_comm.sdgs.as_ref()));
+        layer
+            .single_ecu_jobs
+            .retain(|job| keep(job.diag_comm.sdgs.as_ref()));
+        removed += before - layer.diag_services.len() - layer.single_ecu_jobs.len();
+    }
+    let before = db.dtcs.len();
+    db.dtcs.retain(|dtc| keep(dtc.sdgs.as_ref()));
+    removed + before - db.dtcs.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::*;
+
+    fn deprecated() -> Lifecycle {
+        Lifecycle {
+            status: LifecycleStatus::Deprecated,
+            since: Some("2.1".into()),
+        }
+    }
+
+    #[test]
+    fn test_lifecycle_roundtrip_through_sdgs() {
+        let mut sdgs = None;
+        assert_eq!(lifecycle(sdgs.as_ref()), None);
+        set_lifecycle(
+            &mut sdgs,
+            &Lifecycle {
+                status: LifecycleStatus::Released,
+                since: None,
+            },
+        );
+        set_lifecycle(&mut sdgs, &deprecated());
+        assert_eq!(sdgs.as_ref().unwrap().sdgs.len(), 1);
+        assert_eq!(lifecycle(sdgs.as_ref()), Some(deprecated()));
+        assert_eq!(deprecated().to_string(), "deprecated since 2.1");
+    }
+
+    #[test]
+    fn test_status_from_str() {
+        assert_eq!("Obsolete".parse(), Ok(LifecycleStatus::Obsolete));
+        assert!("retired".parse::<LifecycleStatus>().is_err());
+    }
+
+    #[test]
+    fn test_filter_by_lifecycle() {
+        let service = |name: &str, lc: Option<Lifecycle>| {
+            let mut svc = DiagService {
+                diag_comm: DiagComm {
+                    short_name: name.into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            if let Some(lc) = lc {
+                set_lifecycle(&mut svc.diag_comm.sdgs, &lc);
+            }
+            svc
+        };
+        let mut old_dtc = Dtc {
+            short_name: "P0001".into(),
+            ..Default::default()
+        };
+        set_lifecycle(
+            &mut old_dtc.sdgs,
+            &Lifecycle {
+                status: LifecycleStatus::Obsolete,
+                since: None,
+            },
+        );
+        let mut db = DiagDatabase {
+            variants: vec![Variant {
+                diag_layer: DiagLayer {
+                    short_name: "Base".into(),
+                    diag_services: vec![
+                        service("Current", None),
+                        service("Old", Some(deprecated())),
+                    ],
+                    ..Default::default()
+                },
+                is_base_variant: true,
+                ..Default::default()
+            }],
+            dtcs: vec![old_dtc, Dtc::default()],
+            ..Default::default()
+        };
+
+        let removed = filter_by_lifecycle(
+            &mut db,
+            &[LifecycleStatus::Deprecated, LifecycleStatus::Obsolete],
+        );
+
+        assert_eq!(removed, 2);
+        let layer = &db.variants[0].diag_layer;
+        assert_eq!(layer.diag_services.len(), 1);
+        assert_eq!(layer.diag_services[0].diag_comm.short_name, "Current");
+        assert_eq!(db.dtcs.len(), 1);
+    }
+}
@@ -0,0 +1,373 @@
+//! Information loss analysis for format conversion.
+//!
+//! Not every output format can carry everything the IR holds. Each writer
+//! publishes a [`FormatCapabilities`] descriptor, and [`find_losses`] lists
+//! every IR element the target would silently drop.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::types::*;
+
+/// What a target format can represent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatCapabilities {
+    /// Format name used in loss reasons.
+    pub format: &'static str,
+    pub functional_groups: bool,
+    pub memory: bool,
+    pub type_definitions: bool,
+    /// Rich-text descriptions of layers, services and jobs.
+    pub descriptions: bool,
+    /// TABLE-KEY / TABLE-STRUCT / TABLE-ENTRY params and TABLE-DOP parents.
+    pub tables: bool,
+    /// Fields, multiplexers and environment data DOPs.
+    pub complex_dops: bool,
+    pub additional_audiences: bool,
+    /// State charts other than `Session`, `SecurityAccess` and `Authentication`.
+    pub custom_state_charts: bool,
+}
+
+impl FormatCapabilities {
+    /// Human-readable names of the capabilities, in declaration order.
+    pub const FEATURE_NAMES: [&'static str; 8] = [
+        "functional groups",
+        "memory configuration",
+        "type definitions",
+        "descriptions",
+        "tables",
+        "fields, multiplexers and env data",
+        "additional audiences",
+        "custom state charts",
+    ];
+
+    /// Every capability with its name from [`FEATURE_NAMES`](Self::FEATURE_NAMES).
+    /// The destructuring makes a new field a compile error here until it
+    /// is listed, so tables built from this never miss one.
+    pub fn features(&self) -> [(&'static str, bool); 8] {
+        let Self {
+            format: _,
+            functional_groups,
+            memory,
+            type_definitions,
+            descriptions,
+            tables,
+            complex_dops,
+            additional_audiences,
+            custom_state_charts,
+        } = *self;
+        let kept = [
+            functional_groups,
+            memory,
+            type_definitions,
+            descriptions,
+            tables,
+            complex_dops,
+            additional_audiences,
+            custom_state_charts,
+        ];
+        std::array::from_fn(|i| (Self::FEATURE_NAMES[i], kept[i]))
+    }
+}
+
+/// One IR element the target format cannot represent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Loss {
+    /// Slash-separated location in the IR, e.g.
+    /// `variants/Base/services/Read_VIN/request/params/VIN`.
+    pub path: String,
+    pub reason: String,
+}
+
+impl fmt::Display for Loss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.reason)
+    }
+}
+
+const KNOWN_STATE_CHARTS: [&str; 3] = ["Session", "SecurityAccess", "Authentication"];
+
+/// List everything in `db` that would be dropped when writing it in a format
+/// with the given capabilities.
+pub fn find_losses(db: &DiagDatabase, caps: &FormatCapabilities) -> Vec<Loss> {
+    let mut losses = Vec::new();
+    let mut lose = |path: String, what: &str| {
+        losses.push(Loss {
+            path,
+            reason: format!("{what} cannot be represented in {}", caps.format),
+        });
+    };
+
+    if !caps.memory && db.memory.is_some() {
+        lose("memory".into(), "memory configuration");
+    }
+    if !caps.type_definitions {
+        for td in &db.type_definitions {
+            lose(format!("type_definitions/{}", td.name), "type definition");
+        }
+    }
+    if !caps.functional_groups {
+        for fg in &db.functional_groups {
+            lose(
+                format!("functional_groups/{}", fg.diag_layer.short_name),
+                "functional group",
+            );
+        }
+    }
+
+    let layers = db
+        .variants
+        .iter()
+        .map(|v| ("variants", &v.diag_layer, v.parent_refs.as_slice()))
+        .chain(
+            db.functional_groups
+                .iter()
+                .filter(|_| caps.functional_groups)
+                .map(|fg| {
+                    (
+                        "functional_groups",
+                        &fg.diag_layer,
+                        fg.parent_refs.as_slice(),
+                    )
+                }),
+        )
+        .chain(
+            db.protocols
+                .iter()
+                .map(|p| ("protocols", &p.diag_layer, p.parent_refs.as_slice())),
+        )
+        .chain(
+            db.ecu_shared_datas
+                .iter()
+                .map(|e| ("ecu_shared_datas", &e.diag_layer, &[][..])),
+        );
+    for (kind, layer, parent_refs) in layers {
+        let base = format!("{kind}/{}", layer.short_name);
+        layer_losses(&base, layer, parent_refs, caps, &mut lose);
+    }
+    losses
+}
+
+fn layer_losses(
+    base: &str,
+    layer: &DiagLayer,
+    parent_refs: &[ParentRef],
+    caps: &FormatCapabilities,
+    lose: &mut impl FnMut(String, &str),
+) {
+    if !caps.descriptions && layer.description.is_some() {
+        lose(format!("{base}/description"), "description");
+    }
+    if !caps.additional_audiences && !layer.additional_audiences.is_empty() {
+        lose(
+            format!("{base}/additional_audiences"),
+            "additional audiences",
+        );
+    }
+    if !caps.custom_state_charts {
+        for sc in &layer.state_charts {
+            if !KNOWN_STATE_CHARTS.contains(&sc.short_name.as_str()) {
+                lose(
+                    format!("{base}/state_charts/{}", sc.short_name),
+                    "custom state chart",
+                );
+            }
+        }
+    }
+    if !caps.tables {
+        for parent_ref in parent_refs {
+            if let ParentRefType::TableDop(t) = &parent_ref.ref_type {
+                lose(
+                    format!("{base}/parent_refs/{}", t.short_name),
+                    "TABLE parent reference",
+                );
+            }
+        }
+    }
+
+    for svc in &layer.diag_services {
+        let svc_path = format!("{base}/services/{}", svc.diag_comm.short_name);
+        if !caps.descriptions && svc.diag_comm.description.is_some() {
+            lose(format!("{svc_path}/description"), "description");
+        }
+        if let Some(req) = &svc.request {
+            params_losses(&format!("{svc_path}/request"), &req.params, caps, lose);
+        }
+        for (i, resp) in svc.pos_responses.iter().enumerate() {
+            let path = format!("{svc_path}/pos_responses/{i}");
+            params_losses(&path, &resp.params, caps, lose);
+        }
+        for (i, resp) in svc.neg_responses.iter().enumerate() {
+            let path = format!("{svc_path}/neg_responses/{i}");
+            params_losses(&path, &resp.params, caps, lose);
+        }
+    }
+    if !caps.descriptions {
+        for job in &layer.single_ecu_jobs {
+            if job.diag_comm.description.is_some() {
+                lose(
+                    format!("{base}/jobs/{}/description", job.diag_comm.short_name),
+                    "description",
+                );
+            }
+        }
+    }
+}
+
+fn params_losses(
+    base: &str,
+    params: &[Param],
+    caps: &FormatCapabilities,
+    lose: &mut impl FnMut(String, &str),
+) {
+    for param in params {
+        let path = format!("{base}/params/{}", param.short_name);
+        match &param.specific_data {
+            Some(
+                ParamData::TableKey { .. }
+                | ParamData::TableStruct { .. }
+                | ParamData::TableEntry { .. },
+            ) if !caps.tables => lose(path, "TABLE parameter"),
+            Some(
+                ParamData::Value { dop, .. }
+                | ParamData::PhysConst { dop, .. }
+                | ParamData::System { dop, .. }
+                | ParamData::LengthKeyRef { dop },
+            ) if !caps.complex_dops && is_complex(dop) => {
+                lose(
+                    path,
+                    &format!("DOP {} (field/multiplexer/env data)", dop.short_name),
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_complex(dop: &Dop) -> bool {
+    matches!(
+        dop.specific_data,
+        Some(
+            DopData::EndOfPduField { .. }
+                | DopData::StaticField { .. }
+                | DopData::DynamicLengthField { .. }
+                | DopData::MuxDop { .. }
+                | DopData::EnvDataDesc { .. }
+                | DopData::EnvData { .. }
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    const NOTHING: FormatCapabilities = FormatCapabilities {
+        format: "test",
+        functional_groups: false,
+        memory: false,
+        type_definitions: false,
+        descriptions: false,
+        tables: false,
+        complex_dops: false,
+        additional_audiences: false,
+        custom_state_charts: false,
+    };
+
+    const EVERYTHING: FormatCapabilities = FormatCapabilities {
+        format: "test",
+        functional_groups: true,
+        memory: true,
+        type_definitions: true,
+        descriptions: true,
+        tables: true,
+        complex_dops: true,
+        additional_audiences: true,
+        custom_state_charts: true,
+    };
+
+    fn database() -> DiagDatabase {
+        let table_param = Param {
+            short_name: "Key".into(),
+            param_type: ParamType::TableKey,
+            specific_data: Some(ParamData::TableKey {
+                table_key_reference: TableKeyReference::TableRow(Box::default()),
+            }),
+            ..Default::default()
+        };
+        let lookup = DiagService {
+            request: Some(Request {
+                params: vec![table_param],
+                sdgs: None,
+            }),
+            ..test_support::service("Lookup", "")
+        };
+        let mut db = DiagDatabase {
+            memory: Some(MemoryConfig {
+                default_address_format: AddressFormat::default(),
+                regions: vec![],
+                data_blocks: vec![],
+            }),
+            functional_groups: vec![FunctionalGroup {
+                diag_layer: DiagLayer {
+                    short_name: "FG_Obd".into(),
+                    description: Some("not reported twice".into()),
+                    ..Default::default()
+                },
+                parent_refs: vec![],
+            }],
+            ..test_support::database(vec![lookup])
+        };
+        let base = &mut db.variants[0].diag_layer;
+        base.description = Some("Base layer".into());
+        base.state_charts = vec![StateChart {
+            short_name: "Session".into(),
+            semantic: Name::default(),
+            state_transitions: vec![],
+            start_state_short_name_ref: String::new(),
+            states: vec![],
+        }];
+        db
+    }
+
+    #[test]
+    fn test_features_follow_the_fields() {
+        assert!(EVERYTHING.features().iter().all(|&(_, kept)| kept));
+        assert!(NOTHING.features().iter().all(|&(_, kept)| !kept));
+        let memory_only = FormatCapabilities {
+            memory: true,
+            ..NOTHING
+        };
+        let kept: Vec<&str> = memory_only
+            .features()
+            .iter()
+            .filter(|&&(_, kept)| kept)
+            .map(|&(name, _)| name)
+            .collect();
+        assert_eq!(kept, ["memory configuration"]);
+    }
+
+    #[test]
+    fn test_full_capabilities_lose_nothing() {
+        assert_eq!(find_losses(&database(), &EVERYTHING), vec![]);
+    }
+
+    #[test]
+    fn test_losses_are_listed_with_paths() {
+        let paths: Vec<String> = find_losses(&database(), &NOTHING)
+            .into_iter()
+            .map(|l| l.path)
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "memory",
+                "functional_groups/FG_Obd",
+                "variants/Base/description",
+                "variants/Base/services/Lookup/request/params/Key",
+            ]
+        );
+    }
+}
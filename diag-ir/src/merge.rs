@@ -0,0 +1,370 @@
+//! Combine several databases of one ECU into one.
+//!
+//! Teams often keep a description in fragments, e.g. one YAML file each for
+//! the DTCs, DIDs and routines. [`merge`] joins them: variants and functional
+//! groups are matched by short name and their layers combined, and services,
+//! jobs, state charts, DTCs and type definitions are matched by short name
+//! (DTCs by trouble code). Identical copies, such as the session state chart
+//! every fragment declares, are kept once; items the inputs define
+//! differently are reported as a [`MergeConflict`].
+
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::types::*;
+
+/// An item an input defines differently from an earlier input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// Slash-separated location in the IR, e.g.
+    /// `variants/Base/services/Read_VIN`.
+    pub path: String,
+    /// Index of the input that disagrees with an earlier one.
+    pub input: usize,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: input {} differs from an earlier input",
+            self.path,
+            self.input + 1
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{} merge conflict(s):{}", .0.len(), list(.0))]
+pub struct MergeConflict(pub Vec<Conflict>);
+
+fn list(conflicts: &[Conflict]) -> String {
+    let mut out = String::new();
+    for conflict in conflicts {
+        out.push_str("\n  ");
+        out.push_str(&conflict.to_string());
+    }
+    out
+}
+
+/// Merge `databases` in order. The ECU name must agree; version, revision
+/// and metadata keys take the first non-empty value. Every conflict is
+/// reported, not only the first.
+pub fn merge(databases: &[DiagDatabase]) -> Result<DiagDatabase, MergeConflict> {
+    let Some((first, rest)) = databases.split_first() else {
+        return Ok(DiagDatabase::default());
+    };
+    let mut merged = first.clone();
+    let mut conflicts = Vec::new();
+    for (i, db) in rest.iter().enumerate() {
+        let mut merger = Merger {
+            input: i + 1,
+            conflicts: &mut conflicts,
+        };
+        merger.database(&mut merged, db.clone());
+    }
+    if conflicts.is_empty() {
+        Ok(merged)
+    } else {
+        Err(MergeConflict(conflicts))
+    }
+}
+
+/// Merges one input into the result, collecting conflicts.
+struct Merger<'a> {
+    input: usize,
+    conflicts: &'a mut Vec<Conflict>,
+}
+
+impl Merger<'_> {
+    fn conflict(&mut self, path: String) {
+        self.conflicts.push(Conflict {
+            path,
+            input: self.input,
+        });
+    }
+
+    fn database(&mut self, into: &mut DiagDatabase, db: DiagDatabase) {
+        if into.ecu_name.is_empty() {
+            into.ecu_name = db.ecu_name;
+        } else if !db.ecu_name.is_empty() && into.ecu_name != db.ecu_name {
+            self.conflict("ecu_name".into());
+        }
+        if into.version.is_empty() {
+            into.version = db.version;
+        }
+        if into.revision.is_empty() {
+            into.revision = db.revision;
+        }
+        for (key, value) in db.metadata {
+            into.metadata.entry(key).or_insert(value);
+        }
+
+        for variant in db.variants {
+            let name = variant.diag_layer.short_name.clone();
+            match into
+                .variants
+                .iter_mut()
+                .find(|v| v.diag_layer.short_name == name)
+            {
+                Some(existing) => self.variant(existing, variant, &format!("variants/{name}")),
+                None => into.variants.push(variant),
+            }
+        }
+        for group in db.functional_groups {
+            let name = group.diag_layer.short_name.clone();
+            match into
+                .functional_groups
+                .iter_mut()
+                .find(|g| g.diag_layer.short_name == name)
+            {
+                Some(existing) => {
+                    let base = format!("functional_groups/{name}");
+                    self.layer(&mut existing.diag_layer, group.diag_layer, &base);
+                    append_distinct(&mut existing.parent_refs, group.parent_refs);
+                }
+                None => into.functional_groups.push(group),
+            }
+        }
+        self.keyed(
+            &mut into.protocols,
+            db.protocols,
+            |p| p.diag_layer.short_name.clone(),
+            |name| format!("protocols/{name}"),
+        );
+        self.keyed(
+            &mut into.ecu_shared_datas,
+            db.ecu_shared_datas,
+            |e| e.diag_layer.short_name.clone(),
+            |name| format!("ecu_shared_datas/{name}"),
+        );
+        self.keyed(
+            &mut into.dtcs,
+            db.dtcs,
+            |d| d.trouble_code,
+            |code| format!("dtcs/0x{code:06X}"),
+        );
+        self.option(&mut into.memory, db.memory, "memory".into());
+        self.keyed(
+            &mut into.type_definitions,
+            db.type_definitions,
+            |t| t.name.clone(),
+            |name| format!("type_definitions/{name}"),
+        );
+        self.option(&mut into.transport, db.transport, "transport".into());
+        self.option(
+            &mut into.identification,
+            db.identification,
+            "identification".into(),
+        );
+    }
+
+    fn variant(&mut self, into: &mut Variant, variant: Variant, base: &str) {
+        if into.is_base_variant != variant.is_base_variant {
+            self.conflict(format!("{base}/is_base_variant"));
+        }
+        self.layer(&mut into.diag_layer, variant.diag_layer, base);
+        append_distinct(&mut into.variant_patterns, variant.variant_patterns);
+        append_distinct(&mut into.parent_refs, variant.parent_refs);
+    }
+
+    fn layer(&mut self, into: &mut DiagLayer, layer: DiagLayer, base: &str) {
+        self.option(
+            &mut into.long_name,
+            layer.long_name,
+            format!("{base}/long_name"),
+        );
+        self.option(
+            &mut into.description,
+            layer.description,
+            format!("{base}/description"),
+        );
+        append_distinct(&mut into.funct_classes, layer.funct_classes);
+        self.keyed(
+            &mut into.com_param_refs,
+            layer.com_param_refs,
+            comparam_key,
+            |(protocol, name)| match protocol {
+                Some(protocol) => format!("{base}/com_param_refs/{protocol}/{name}"),
+                None => format!("{base}/com_param_refs/{name}"),
+            },
+        );
+        self.keyed(
+            &mut into.diag_services,
+            layer.diag_services,
+            |s| s.diag_comm.short_name.clone(),
+            |name| format!("{base}/services/{name}"),
+        );
+        self.keyed(
+            &mut into.single_ecu_jobs,
+            layer.single_ecu_jobs,
+            |j| j.diag_comm.short_name.clone(),
+            |name| format!("{base}/jobs/{name}"),
+        );
+        self.keyed(
+            &mut into.state_charts,
+            layer.state_charts,
+            |c| c.short_name.clone(),
+            |name| format!("{base}/state_charts/{name}"),
+        );
+        self.keyed(
+            &mut into.additional_audiences,
+            layer.additional_audiences,
+            |a| a.short_name.clone(),
+            |name| format!("{base}/additional_audiences/{name}"),
+        );
+        if let Some(other) = layer.sdgs {
+            match into.sdgs.as_mut() {
+                Some(sdgs) => append_distinct(&mut sdgs.sdgs, other.sdgs),
+                None => into.sdgs = Some(other),
+            }
+        }
+    }
+
+    /// Append the items of `other` whose key `into` lacks. Identical copies
+    /// are dropped; other items with a known key are conflicts.
+    fn keyed<T: PartialEq, K: PartialEq>(
+        &mut self,
+        into: &mut Vec<T>,
+        other: Vec<T>,
+        key: impl Fn(&T) -> K,
+        path: impl Fn(K) -> String,
+    ) {
+        for item in other {
+            let k = key(&item);
+            match into.iter().find(|&i| key(i) == k) {
+                None => into.push(item),
+                Some(existing) if *existing == item => {}
+                Some(_) => self.conflict(path(k)),
+            }
+        }
+    }
+
+    fn option<T: PartialEq>(&mut self, into: &mut Option<T>, other: Option<T>, path: String) {
+        let Some(other) = other else { return };
+        match into.as_ref() {
+            None => *into = Some(other),
+            Some(existing) if *existing != other => self.conflict(path),
+            Some(_) => {}
+        }
+    }
+}
+
+/// Protocol and ComParam short names of a ComParamRef.
+fn comparam_key(cp: &ComParamRef) -> (Option<String>, String) {
+    (
+        cp.protocol
+            .as_ref()
+            .map(|p| p.diag_layer.short_name.to_string()),
+        cp.com_param
+            .as_ref()
+            .map(|c| c.short_name.to_string())
+            .unwrap_or_default(),
+    )
+}
+
+/// Append the items of `other` that `into` does not contain yet.
+fn append_distinct<T: PartialEq>(into: &mut Vec<T>, other: Vec<T>) {
+    for item in other {
+        if !into.contains(&item) {
+            into.push(item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{database, service};
+
+    fn dtc(name: &str, code: u32) -> Dtc {
+        Dtc {
+            short_name: name.into(),
+            trouble_code: code,
+            ..Default::default()
+        }
+    }
+
+    fn db(services: Vec<DiagService>, dtcs: Vec<Dtc>) -> DiagDatabase {
+        DiagDatabase {
+            ecu_name: "ECM".into(),
+            dtcs,
+            ..database(services)
+        }
+    }
+
+    fn names(db: &DiagDatabase) -> Vec<&str> {
+        db.variants[0]
+            .diag_layer
+            .diag_services
+            .iter()
+            .map(|s| s.diag_comm.short_name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_combines_fragments() {
+        let dids = db(vec![service("VIN_Read", "DATA-READ")], vec![]);
+        let mut dtcs = db(
+            vec![service("VIN_Read", "DATA-READ")],
+            vec![dtc("P0100", 0x0100)],
+        );
+        dtcs.version = "2".into();
+        dtcs.variants.push(Variant {
+            diag_layer: DiagLayer {
+                short_name: "App".into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let routines = db(vec![service("SelfTest_Start", "ROUTINE")], vec![]);
+
+        let merged = merge(&[dids, dtcs, routines]).unwrap();
+        assert_eq!(names(&merged), ["VIN_Read", "SelfTest_Start"]);
+        assert_eq!(merged.variants.len(), 2);
+        assert_eq!(merged.dtcs.len(), 1);
+        assert_eq!(merged.version, "2");
+    }
+
+    #[test]
+    fn test_merge_reports_every_conflict() {
+        let a = db(
+            vec![service("VIN_Read", "DATA-READ")],
+            vec![dtc("P0100", 0x0100)],
+        );
+        let b = db(vec![service("VIN_Read", "IDENTIFICATION")], vec![]);
+        let mut c = db(vec![], vec![dtc("P0100_MAF", 0x0100)]);
+        c.ecu_name = "TCM".into();
+
+        let err = merge(&[a, b, c]).unwrap_err();
+        assert_eq!(
+            err.0,
+            [
+                Conflict {
+                    path: "variants/Base/services/VIN_Read".into(),
+                    input: 1,
+                },
+                Conflict {
+                    path: "ecu_name".into(),
+                    input: 2,
+                },
+                Conflict {
+                    path: "dtcs/0x000100".into(),
+                    input: 2,
+                },
+            ]
+        );
+        assert!(
+            err.to_string()
+                .starts_with("3 merge conflict(s):\n  variants/Base/services/VIN_Read: input 2")
+        );
+    }
+
+    #[test]
+    fn test_merge_of_nothing_is_empty() {
+        assert_eq!(merge(&[]).unwrap(), DiagDatabase::default());
+        let one = db(vec![service("VIN_Read", "DATA-READ")], vec![]);
+        assert_eq!(merge(std::slice::from_ref(&one)).unwrap(), one);
+    }
+}
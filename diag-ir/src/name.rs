@@ -0,0 +1,245 @@
+//! Interned strings for the short names and semantics of the IR.
+//!
+//! A large database repeats the same few thousand names (`SID_RQ`,
+//! `DID_PR`, `DATA-READ`, ...) across hundreds of thousands of objects.
+//! [`Name`] is a shared, immutable string, and parsers convert under
+//! [`Interner::enter`], so every occurrence of a name points at one
+//! allocation.
+
+use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{BuildHasher, BuildHasherDefault, DefaultHasher};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A short name or semantic. Cloning shares the string. Compares, hashes,
+/// prints and serializes exactly like the string itself.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Name(Arc<str>);
+
+impl Name {
+    /// The name, interned if an [`Interner`] is active on this thread.
+    pub fn new(s: &str) -> Self {
+        INTERNER.with(|i| match &*i.borrow() {
+            Some(interner) => interner.intern(s),
+            None => Self(Arc::from(s)),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether both names share one allocation.
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Arc::ptr_eq(&a.0, &b.0)
+    }
+}
+
+impl Deref for Name {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Name {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Name {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Name {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl From<&String> for Name {
+    fn from(s: &String) -> Self {
+        Self::new(s)
+    }
+}
+
+impl From<String> for Name {
+    fn from(s: String) -> Self {
+        Self::new(&s)
+    }
+}
+
+impl From<&Name> for Name {
+    fn from(name: &Name) -> Self {
+        name.clone()
+    }
+}
+
+impl From<Name> for String {
+    fn from(name: Name) -> Self {
+        name.0.to_string()
+    }
+}
+
+impl PartialEq<str> for Name {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Name {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Name {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl PartialEq<Name> for str {
+    fn eq(&self, other: &Name) -> bool {
+        self == &*other.0
+    }
+}
+
+impl PartialEq<Name> for &str {
+    fn eq(&self, other: &Name) -> bool {
+        *self == &*other.0
+    }
+}
+
+impl PartialEq<Name> for String {
+    fn eq(&self, other: &Name) -> bool {
+        **self == *other.0
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Debug for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl Serialize for Name {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Name {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+/// The set of names created so far by one conversion. Clones share the set,
+/// so parallel workers of a conversion intern into the same one. The set is
+/// split by hash into [`SHARDS`] locks, so workers interning different names
+/// rarely wait for each other.
+#[derive(Clone)]
+pub struct Interner(Arc<[Mutex<HashSet<Name>>]>);
+
+const SHARDS: usize = 64;
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self((0..SHARDS).map(|_| Mutex::default()).collect())
+    }
+}
+
+thread_local! {
+    /// The interner [`Name::new`] uses on this thread, set by [`Interner::enter`].
+    static INTERNER: RefCell<Option<Interner>> = const { RefCell::new(None) };
+}
+
+impl Interner {
+    /// The shared copy of `s`.
+    pub fn intern(&self, s: &str) -> Name {
+        let shard = BuildHasherDefault::<DefaultHasher>::default().hash_one(s);
+        let shard = &self.0[shard as usize % SHARDS];
+        let mut names = shard.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(name) = names.get(s) {
+            return name.clone();
+        }
+        let name = Name(Arc::from(s));
+        names.insert(name.clone());
+        name
+    }
+
+    /// Run `f` with this interner active on the current thread: every
+    /// [`Name`] it creates is interned.
+    pub fn enter<T>(&self, f: impl FnOnce() -> T) -> T {
+        // Restores the previous interner on the way out, panics included.
+        struct Restore(Option<Interner>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                INTERNER.with(|i| *i.borrow_mut() = self.0.take());
+            }
+        }
+        let _restore = Restore(INTERNER.with(|i| i.replace(Some(self.clone()))));
+        f()
+    }
+
+    /// How many distinct names were interned.
+    pub fn len(&self) -> usize {
+        self.0
+            .iter()
+            .map(|shard| shard.lock().unwrap_or_else(PoisonError::into_inner).len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_are_shared_inside_an_interner() {
+        let interner = Interner::default();
+        let (a, b) = interner.enter(|| (Name::from("SID_RQ"), Name::from("SID_RQ".to_string())));
+        assert!(Name::ptr_eq(&a, &b));
+        assert_eq!(interner.len(), 1);
+
+        // Names in different shards are counted together.
+        interner.enter(|| (Name::from("DID_PR"), Name::from("DATA-READ")));
+        assert_eq!(interner.len(), 3);
+
+        let outside = Name::from("SID_RQ");
+        assert_eq!(outside, a);
+        assert!(!Name::ptr_eq(&outside, &a));
+    }
+
+    #[test]
+    fn test_name_behaves_like_a_string() {
+        let name = Name::from("DATA-READ");
+        assert_eq!(name, "DATA-READ");
+        assert_eq!(name.to_string(), "DATA-READ");
+        assert_eq!(format!("{name:?}"), "\"DATA-READ\"");
+        assert_eq!(serde_json::to_string(&name).unwrap(), "\"DATA-READ\"");
+        let back: Name = serde_json::from_str("\"DATA-READ\"").unwrap();
+        assert_eq!(back, name);
+        assert!(Name::default().is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::types::{DiagDatabase, DiagLayer};
+
+/// Put the database into a canonical order so that equivalent inputs
+/// serialize identically and diff cleanly.
+///
+/// Only collections whose order carries no meaning are sorted: variants (base
+/// variants first), functional groups, protocols, ECU shared data, services,
+/// jobs, state charts and DTCs. Parameters, states and anything else that is
+/// position-sensitive keep their order.
+pub fn normalize(db: &mut DiagDatabase) {
+    db.variants.sort_by(|a, b| {
+        b.is_base_variant
+            .cmp(&a.is_base_variant)
+            .then_with(|| a.diag_layer.short_name.cmp(&b.diag_layer.short_name))
+    });
+    db.functional_groups
+        .sort_by(|a, b| a.diag_layer.short_name.cmp(&b.diag_layer.short_name));
+    db.protocols
+        .sort_by(|a, b| a.diag_layer.short_name.cmp(&b.diag_layer.short_name));
+    db.ecu_shared_datas
+        .sort_by(|a, b| a.diag_layer.short_name.cmp(&b.diag_layer.short_name));
+    db.dtcs.sort_by(|a, b| {
+        a.trouble_code
+            .cmp(&b.trouble_code)
+            .then_with(|| a.short_name.cmp(&b.short_name))
+    });
+
+    let layers = db
+        .variants
+        .iter_mut()
+        .map(|v| &mut v.diag_layer)
+        .chain(db.functional_groups.iter_mut().map(|fg| &mut fg.diag_layer))
+        .chain(db.protocols.iter_mut().map(|p| &mut p.diag_layer))
+        .chain(db.ecu_shared_datas.iter_mut().map(|e| &mut e.diag_layer));
+    for layer in layers {
+        normalize_layer(layer);
+    }
+}
+
+fn normalize_layer(layer: &mut DiagLayer) {
+    layer
+        .diag_services
+        .sort_by(|a, b| a.diag_comm.short_name.cmp(&b.diag_comm.short_name));
+    layer
+        .single_ecu_jobs
+        .sort_by(|a, b| a.diag_comm.short_name.cmp(&b.diag_comm.short_name));
+    layer
+        .state_charts
+        .sort_by(|a, b| a.short_name.cmp(&b.short_name));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{base_variant, services, variant};
+    use crate::types::*;
+
+    fn names(layer: &DiagLayer) -> Vec<&str> {
+        layer
+            .diag_services
+            .iter()
+            .map(|s| s.diag_comm.short_name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_normalize_sorts_variants_and_services() {
+        let mut db = DiagDatabase {
+            variants: vec![
+                variant("App_B", services(&["Write", "Read"])),
+                base_variant("Base", services(&["Z", "A"])),
+                variant("App_A", vec![]),
+            ],
+            ..Default::default()
+        };
+        normalize(&mut db);
+
+        let order: Vec<&str> = db
+            .variants
+            .iter()
+            .map(|v| v.diag_layer.short_name.as_str())
+            .collect();
+        assert_eq!(order, ["Base", "App_A", "App_B"]);
+        assert_eq!(names(&db.variants[0].diag_layer), ["A", "Z"]);
+        assert_eq!(names(&db.variants[2].diag_layer), ["Read", "Write"]);
+    }
+
+    #[test]
+    fn test_normalize_is_idempotent_and_keeps_params() {
+        let mut db = DiagDatabase {
+            variants: vec![base_variant("Base", services(&["B", "A"]))],
+            dtcs: vec![
+                Dtc {
+                    short_name: "DTC_2".into(),
+                    trouble_code: 2,
+                    ..Default::default()
+                },
+                Dtc {
+                    short_name: "DTC_1".into(),
+                    trouble_code: 1,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let params = vec![
+            Param {
+                short_name: "SID".into(),
+                ..Default::default()
+            },
+            Param {
+                short_name: "DID".into(),
+                ..Default::default()
+            },
+        ];
+        db.variants[0].diag_layer.diag_services[0].request = Some(Request {
+            params: params.clone(),
+            sdgs: None,
+        });
+
+        normalize(&mut db);
+        let once = db.clone();
+        normalize(&mut db);
+        assert_eq!(db, once);
+        assert_eq!(db.dtcs[0].short_name, "DTC_1");
+        let service_b = &db.variants[0].diag_layer.diag_services[1];
+        assert_eq!(service_b.request.as_ref().unwrap().params, params);
+    }
+}
@@ -0,0 +1,57 @@
+//! Numbers as the IR stores them in text: coded values, limits and IDs.
+//! Every crate reading such text parses it with these, so `0x`/`0X` and
+//! whitespace are handled the same everywhere.
+
+/// Parse a decimal or `0x`-prefixed hex unsigned integer, ignoring
+/// surrounding whitespace.
+pub fn parse_uint(s: &str) -> Option<u64> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Parse hex bytes, optionally `0x`-prefixed, with any whitespace between
+/// the digits (`22 F1 90`, `0x22F190`). An odd number of digits is an
+/// error.
+pub fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim();
+    let hex: String = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s)
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).ok().filter(|p| p.len() == 2)?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
+
+/// Parse a decimal, float or `0x`-prefixed hex number.
+pub(crate) fn parse_number(s: &str) -> Option<f64> {
+    parse_uint(s).map_or_else(|| s.trim().parse().ok(), |v| Some(v as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_spellings() {
+        for s in ["0xF190", "0XF190", " 61840 ", "\t0xf190\n"] {
+            assert_eq!(parse_uint(s), Some(0xF190), "{s:?}");
+        }
+        assert_eq!(parse_uint("F190"), None);
+        for s in ["22 F1 90", "0x22F190", " 0X22 f1 90 "] {
+            assert_eq!(parse_hex_bytes(s), Some(vec![0x22, 0xF1, 0x90]), "{s:?}");
+        }
+        assert_eq!(parse_hex_bytes("2 F1 90"), None);
+        assert_eq!(parse_hex_bytes("22 G1"), None);
+    }
+}
@@ -0,0 +1,138 @@
+//! Where the named objects of a database end up in its FlatBuffers
+//! description.
+//!
+//! [`ir_to_flatbuffers`](crate::ir_to_flatbuffers) writes variants,
+//! functional groups and DTCs in database order, and the services and jobs
+//! of each layer in layer order, so an object's position in the database is
+//! its index in the `EcuData` an MDD file embeds. Locations are paths into
+//! `EcuData`, e.g. `variants[1].diag_layer.diag_services[4]` or `dtcs[0]`.
+//! Protocols and ECU shared data are only reachable through parent
+//! references and get no location.
+
+use crate::search::SearchKind;
+use crate::types::{DiagDatabase, DiagLayer};
+
+/// A layer, service, job or DTC and its path in the FlatBuffers `EcuData`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectLocation {
+    pub kind: SearchKind,
+    /// Short name of the layer; empty for DTCs.
+    pub layer: String,
+    pub short_name: String,
+    pub path: String,
+}
+
+/// The locations of the layers, services and jobs of variants and functional
+/// groups, and of the DTCs, in database order.
+pub fn object_locations(db: &DiagDatabase) -> Vec<ObjectLocation> {
+    let mut locations = Vec::new();
+    let layers = db
+        .variants
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (format!("variants[{i}]"), &v.diag_layer))
+        .chain(
+            db.functional_groups
+                .iter()
+                .enumerate()
+                .map(|(i, fg)| (format!("functional_groups[{i}]"), &fg.diag_layer)),
+        );
+    for (path, layer) in layers {
+        layer_locations(&path, layer, &mut locations);
+    }
+    for (i, dtc) in db.dtcs.iter().enumerate() {
+        locations.push(ObjectLocation {
+            kind: SearchKind::Dtc,
+            layer: String::new(),
+            short_name: dtc.short_name.to_string(),
+            path: format!("dtcs[{i}]"),
+        });
+    }
+    locations
+}
+
+fn layer_locations(path: &str, layer: &DiagLayer, locations: &mut Vec<ObjectLocation>) {
+    let location = |kind, short_name: &str, path: String| ObjectLocation {
+        kind,
+        layer: layer.short_name.to_string(),
+        short_name: short_name.to_string(),
+        path,
+    };
+    locations.push(location(
+        SearchKind::Layer,
+        &layer.short_name,
+        path.to_string(),
+    ));
+    for (i, svc) in layer.diag_services.iter().enumerate() {
+        locations.push(location(
+            SearchKind::Service,
+            &svc.diag_comm.short_name,
+            format!("{path}.diag_layer.diag_services[{i}]"),
+        ));
+    }
+    for (i, job) in layer.single_ecu_jobs.iter().enumerate() {
+        locations.push(location(
+            SearchKind::Job,
+            &job.diag_comm.short_name,
+            format!("{path}.diag_layer.single_ecu_jobs[{i}]"),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DiagComm, DiagService, Dtc, FunctionalGroup, Variant};
+
+    #[test]
+    fn test_object_locations() {
+        let service = |name: &str| DiagService {
+            diag_comm: DiagComm {
+                short_name: name.into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let db = DiagDatabase {
+            variants: vec![Variant {
+                diag_layer: DiagLayer {
+                    short_name: "Base".into(),
+                    diag_services: vec![service("Read"), service("Write")],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            functional_groups: vec![FunctionalGroup {
+                diag_layer: DiagLayer {
+                    short_name: "FG_Obd".into(),
+                    diag_services: vec![service("Obd")],
+                    ..Default::default()
+                },
+                parent_refs: vec![],
+            }],
+            dtcs: vec![Dtc {
+                short_name: "P0100".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let locations = object_locations(&db);
+        let paths: Vec<_> = locations
+            .iter()
+            .map(|l| (l.short_name.as_str(), l.path.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("Base", "variants[0]"),
+                ("Read", "variants[0].diag_layer.diag_services[0]"),
+                ("Write", "variants[0].diag_layer.diag_services[1]"),
+                ("FG_Obd", "functional_groups[0]"),
+                ("Obd", "functional_groups[0].diag_layer.diag_services[0]"),
+                ("P0100", "dtcs[0]"),
+            ]
+        );
+        assert_eq!(locations[2].layer, "Base");
+        assert_eq!(locations[2].kind, SearchKind::Service);
+    }
+}
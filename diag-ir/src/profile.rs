@@ -0,0 +1,287 @@
+//! Output profiles: IR transforms that trim a database for a deployment
+//! target, applied before any writer so they work for every output format.
+//!
+//! The `minimal` profile drops what a tester never needs to decode traffic:
+//! long names, descriptions, SDGs and the document metadata taken from ODX
+//! ADMIN-DATA or the YAML `meta` section. What was removed can be kept in a
+//! [`ProfileMapping`] keyed by the path of the object it was removed from.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::compatibility::COMPATIBILITY_CAPTION;
+use crate::types::DiagDatabase;
+
+/// Fields the minimal profile removes from every object that has them.
+const STRIPPED_FIELDS: &[&str] = &["long_name", "description", "sdgs"];
+
+/// SDGs the minimal profile keeps because tools act on them.
+const KEPT_SDG_CAPTIONS: &[&str] = &[COMPATIBILITY_CAPTION];
+
+/// Database metadata the minimal profile removes: ODX ADMIN-DATA and the
+/// documentation fields of the YAML `meta` section.
+const STRIPPED_METADATA: &[&str] = &[
+    "admin_language",
+    "admin_doc_state",
+    "admin_doc_date",
+    "author",
+    "domain",
+    "created",
+    "description",
+];
+
+/// Keys whose objects are state chart states. Their long name is not
+/// documentation: it carries the session ID or security level and the YAML
+/// key the state was written as.
+const STATE_KEYS: &[&str] = &["states", "state"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Profile {
+    /// Keep everything.
+    #[default]
+    Full,
+    /// Drop long names, descriptions, SDGs and admin metadata.
+    Minimal,
+}
+
+impl Profile {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::Minimal => "minimal",
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Self::Full, Self::Minimal]
+            .into_iter()
+            .find(|p| p.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown profile '{s}' (expected full or minimal)"))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProfileError {
+    #[error("Cannot rewrite the database for the profile: {0}")]
+    Rewrite(#[from] serde_json::Error),
+}
+
+/// Everything a profile removed, by object path. Paths name list items by
+/// their short name where they have one (`variants/Base/diag_layer/
+/// diag_services/ReadVIN/diag_comm`), else by index.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct ProfileMapping {
+    pub removed: BTreeMap<String, Map<String, Value>>,
+}
+
+impl ProfileMapping {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty()
+    }
+}
+
+/// Apply `profile` to `db`.
+pub fn apply_profile(
+    db: &mut DiagDatabase,
+    profile: Profile,
+) -> Result<ProfileMapping, ProfileError> {
+    let mut mapping = ProfileMapping::default();
+    if profile == Profile::Full {
+        return Ok(mapping);
+    }
+
+    let mut metadata = Map::new();
+    for key in STRIPPED_METADATA {
+        if let Some(value) = db.metadata.remove(*key) {
+            metadata.insert((*key).to_string(), Value::String(value));
+        }
+    }
+    if !metadata.is_empty() {
+        mapping.removed.insert("metadata".into(), metadata);
+    }
+
+    let mut value = serde_json::to_value(&*db)?;
+    strip(&mut value, "", false, &mut mapping);
+    *db = serde_json::from_value(value)?;
+    Ok(mapping)
+}
+
+fn strip(value: &mut Value, path: &str, in_state: bool, mapping: &mut ProfileMapping) {
+    match value {
+        Value::Object(object) => {
+            let mut removed = Map::new();
+            for field in STRIPPED_FIELDS {
+                if in_state && *field == "long_name" {
+                    continue;
+                }
+                if let Some(v) = object.get_mut(*field).filter(|v| !v.is_null()) {
+                    let kept = if *field == "sdgs" {
+                        take_kept_sdgs(v)
+                    } else {
+                        None
+                    };
+                    let value = std::mem::replace(v, kept.unwrap_or(Value::Null));
+                    if value["sdgs"].as_array().is_none_or(|sdgs| !sdgs.is_empty()) {
+                        removed.insert((*field).to_string(), value);
+                    }
+                }
+            }
+            if !removed.is_empty() {
+                let key = if path.is_empty() { "/" } else { path };
+                mapping.removed.insert(key.to_string(), removed);
+            }
+            // Stripped fields are null now, or kept SDGs with nothing to strip.
+            for (key, child) in object
+                .iter_mut()
+                .filter(|(key, _)| !STRIPPED_FIELDS.contains(&key.as_str()))
+            {
+                let is_state = STATE_KEYS.contains(&key.as_str());
+                strip(child, &join(path, key), is_state, mapping);
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                let segment = item_name(item).map_or_else(|| i.to_string(), str::to_string);
+                strip(item, &join(path, &segment), in_state, mapping);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Move the SDGs with a caption in [`KEPT_SDG_CAPTIONS`] out of `sdgs`
+/// into a value of their own.
+fn take_kept_sdgs(sdgs: &mut Value) -> Option<Value> {
+    let list = sdgs.get_mut("sdgs")?.as_array_mut()?;
+    let is_kept = |sdg: &Value| {
+        sdg.get("caption_sn")
+            .and_then(Value::as_str)
+            .is_some_and(|c| KEPT_SDG_CAPTIONS.contains(&c))
+    };
+    let kept: Vec<Value> = list.iter().filter(|s| is_kept(s)).cloned().collect();
+    list.retain(|s| !is_kept(s));
+    (!kept.is_empty()).then(|| serde_json::json!({ "sdgs": kept }))
+}
+
+/// Short name of a list item, looking into the `diag_layer` of variants
+/// and the `diag_comm` of services as well.
+pub(crate) fn item_name(item: &Value) -> Option<&str> {
+    [item, &item["diag_layer"], &item["diag_comm"]]
+        .into_iter()
+        .find_map(|v| v.get("short_name").and_then(Value::as_str))
+        .filter(|s| !s.is_empty())
+}
+
+pub(crate) fn join(path: &str, segment: &str) -> String {
+    if path.is_empty() {
+        segment.to_string()
+    } else {
+        format!("{path}/{segment}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        DiagComm, DiagLayer, DiagService, LongName, Name, Sdg, Sdgs, State, StateChart, Variant,
+    };
+
+    #[test]
+    fn test_minimal_profile_strips_documentation() {
+        let mut db = DiagDatabase {
+            ecu_name: "ECU".into(),
+            metadata: [
+                ("author".to_string(), "someone".to_string()),
+                ("ecu_id".to_string(), "7".to_string()),
+            ]
+            .into(),
+            variants: vec![Variant {
+                diag_layer: DiagLayer {
+                    short_name: "Base".into(),
+                    description: Some("The base variant".into()),
+                    state_charts: vec![StateChart {
+                        short_name: "Session".into(),
+                        semantic: Name::default(),
+                        state_transitions: vec![],
+                        start_state_short_name_ref: "Default".into(),
+                        states: vec![State {
+                            short_name: "Default".into(),
+                            long_name: Some(LongName {
+                                value: "1".into(),
+                                ti: "default".into(),
+                            }),
+                        }],
+                    }],
+                    diag_services: vec![DiagService {
+                        diag_comm: DiagComm {
+                            short_name: "ReadVIN".into(),
+                            long_name: Some(LongName {
+                                value: "Read VIN".into(),
+                                ti: String::new(),
+                            }),
+                            sdgs: Some(Sdgs {
+                                sdgs: vec![Sdg {
+                                    caption_sn: "notes".into(),
+                                    sds: vec![],
+                                    si: String::new(),
+                                }],
+                            }),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                is_base_variant: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        crate::set_compatibility(
+            &mut db.variants[0].diag_layer.sdgs,
+            &crate::Compatibility {
+                hardware: vec!["HW-1".into()],
+                software: vec![],
+            },
+        );
+        let full = db.clone();
+        assert!(apply_profile(&mut db, Profile::Full).unwrap().is_empty());
+        assert_eq!(db, full);
+
+        let mapping = apply_profile(&mut db, Profile::Minimal).unwrap();
+        let layer = &db.variants[0].diag_layer;
+        assert_eq!(layer.description, None);
+        assert_eq!(layer.diag_services[0].diag_comm.long_name, None);
+        assert_eq!(layer.diag_services[0].diag_comm.sdgs, None);
+        assert_eq!(layer.diag_services[0].diag_comm.short_name, "ReadVIN");
+        assert_eq!(db.metadata.keys().collect::<Vec<_>>(), ["ecu_id"]);
+        assert_eq!(crate::compatibility(&db).unwrap().hardware, ["HW-1"]);
+        let state = &layer.state_charts[0].states[0];
+        assert_eq!(state.long_name.as_ref().unwrap().value, "1");
+
+        let removed = &mapping.removed["variants/Base/diag_layer/diag_services/ReadVIN/diag_comm"];
+        assert_eq!(removed["long_name"]["value"], "Read VIN");
+        assert_eq!(removed["sdgs"]["sdgs"][0]["caption_sn"], "notes");
+        assert_eq!(
+            mapping.removed["variants/Base/diag_layer"]["description"],
+            "The base variant"
+        );
+        assert_eq!(mapping.removed["metadata"]["author"], "someone");
+    }
+}
@@ -0,0 +1,506 @@
+//! Selectors extracting parts of a database.
+//!
+//! A selector names a collection and, in brackets, conditions its elements
+//! must meet:
+//!
+//! ```text
+//! services[semantic=DATA-READ]
+//! services[sid=0x31, layer=EV_Engine*]
+//! jobs
+//! dtcs[code!=0x0]
+//! variants[name=EV_Engine]
+//! ```
+//!
+//! Conditions are `field=value` or `field!=value`, separated by commas, and
+//! must all hold. A `*` in the value matches any run of characters. Names
+//! compare case-sensitively; semantics by their [`Semantic`] classification
+//! when both sides have one (services without a recognizable semantic by
+//! their service ID, see [`Semantic::of`]); numbers (`sid`, `code`,
+//! `level`) in decimal or `0x` hex.
+//!
+//! [`extract`] keeps the elements matching any selector of their
+//! collection. Once a query selects services, jobs or DTCs, the ones it does
+//! not select are dropped, so `services[...]` yields a database without jobs
+//! and DTCs. Variants are kept unless the query selects some.
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::flash::service_sid;
+use crate::numbers::parse_uint;
+use crate::semantic::Semantic;
+use crate::types::{DiagComm, DiagDatabase, DiagLayer, Dtc, Variant};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum QueryError {
+    #[error("invalid selector '{selector}': {reason}")]
+    Syntax { selector: String, reason: String },
+    #[error("unknown collection '{0}' (expected services, jobs, dtcs or variants)")]
+    UnknownCollection(String),
+    #[error("{collection} have no field '{field}' (expected {expected})")]
+    UnknownField {
+        collection: Collection,
+        field: String,
+        expected: String,
+    },
+}
+
+/// What a selector selects from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collection {
+    /// Diagnostic services of every layer.
+    Services,
+    /// Single-ECU jobs of every layer.
+    Jobs,
+    Dtcs,
+    Variants,
+}
+
+impl Collection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Services => "services",
+            Self::Jobs => "jobs",
+            Self::Dtcs => "dtcs",
+            Self::Variants => "variants",
+        }
+    }
+
+    /// The fields conditions on this collection may test.
+    pub fn fields(self) -> &'static [Field] {
+        match self {
+            Self::Services => &[Field::Name, Field::Semantic, Field::Sid, Field::Layer],
+            Self::Jobs => &[Field::Name, Field::Semantic, Field::Layer],
+            Self::Dtcs => &[Field::Name, Field::Code, Field::Level],
+            Self::Variants => &[Field::Name],
+        }
+    }
+}
+
+impl fmt::Display for Collection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Collection {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "services" => Ok(Self::Services),
+            "jobs" => Ok(Self::Jobs),
+            "dtcs" => Ok(Self::Dtcs),
+            "variants" => Ok(Self::Variants),
+            _ => Err(QueryError::UnknownCollection(s.to_string())),
+        }
+    }
+}
+
+/// A field a condition tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// Short name.
+    Name,
+    Semantic,
+    /// Service ID of a service's request.
+    Sid,
+    /// Short name of the layer defining a service or job.
+    Layer,
+    /// Trouble code of a DTC; also matches its display code, e.g. `P0100`.
+    Code,
+    Level,
+}
+
+impl Field {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Semantic => "semantic",
+            Self::Sid => "sid",
+            Self::Layer => "layer",
+            Self::Code => "code",
+            Self::Level => "level",
+        }
+    }
+}
+
+/// `field=value` or `field!=value`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    pub field: Field,
+    pub negated: bool,
+    pub value: String,
+}
+
+impl Condition {
+    pub fn equals(field: Field, value: impl Into<String>) -> Self {
+        Self {
+            field,
+            negated: false,
+            value: value.into(),
+        }
+    }
+
+    /// Whether `actual`, the field's value(s) of an element, meets the
+    /// condition. `None` never equals anything.
+    fn holds(&self, actual: &[Option<String>]) -> bool {
+        let equal = actual
+            .iter()
+            .flatten()
+            .any(|actual| value_matches(self.field, &self.value, actual));
+        equal != self.negated
+    }
+}
+
+/// A collection and the conditions its selected elements meet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    pub collection: Collection,
+    pub conditions: Vec<Condition>,
+}
+
+impl Selector {
+    /// The variant named `name`.
+    pub fn variant(name: impl Into<String>) -> Self {
+        Self {
+            collection: Collection::Variants,
+            conditions: vec![Condition::equals(Field::Name, name)],
+        }
+    }
+
+    fn selects(&self, fields: impl Fn(Field) -> Vec<Option<String>>) -> bool {
+        self.conditions.iter().all(|c| c.holds(&fields(c.field)))
+    }
+}
+
+impl FromStr for Selector {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let syntax = |reason: &str| QueryError::Syntax {
+            selector: s.to_string(),
+            reason: reason.to_string(),
+        };
+        let s_trimmed = s.trim();
+        let (collection, body) = match s_trimmed.split_once('[') {
+            Some((collection, rest)) => {
+                let body = rest
+                    .strip_suffix(']')
+                    .ok_or_else(|| syntax("missing closing ']'"))?;
+                (collection.trim(), Some(body))
+            }
+            None => (s_trimmed, None),
+        };
+        let collection: Collection = collection.parse()?;
+
+        let mut conditions = Vec::new();
+        for condition in body.into_iter().flat_map(|b| b.split(',')) {
+            let condition = condition.trim();
+            if condition.is_empty() {
+                return Err(syntax("empty condition"));
+            }
+            let (field, negated, value) = if let Some((field, value)) = condition.split_once("!=") {
+                (field, true, value)
+            } else if let Some((field, value)) = condition.split_once('=') {
+                (field, false, value)
+            } else {
+                let reason = format!("expected '=' or '!=' in '{condition}'");
+                return Err(syntax(&reason));
+            };
+            let field = field.trim();
+            let Some(&field) = collection.fields().iter().find(|f| f.as_str() == field) else {
+                return Err(QueryError::UnknownField {
+                    collection,
+                    field: field.to_string(),
+                    expected: collection
+                        .fields()
+                        .iter()
+                        .map(|f| f.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                });
+            };
+            conditions.push(Condition {
+                field,
+                negated,
+                value: value.trim().to_string(),
+            });
+        }
+        Ok(Self {
+            collection,
+            conditions,
+        })
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.collection.as_str())?;
+        if self.conditions.is_empty() {
+            return Ok(());
+        }
+        f.write_str("[")?;
+        for (i, c) in self.conditions.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            let op = if c.negated { "!=" } else { "=" };
+            write!(f, "{}{op}{}", c.field.as_str(), c.value)?;
+        }
+        f.write_str("]")
+    }
+}
+
+/// Whether `actual` equals the condition value `expected` for `field`.
+fn value_matches(field: Field, expected: &str, actual: &str) -> bool {
+    match field {
+        Field::Semantic => match (Semantic::parse(expected), Semantic::parse(actual)) {
+            (Some(e), Some(a)) => e == a,
+            _ => wildcard_match(expected, actual),
+        },
+        Field::Sid | Field::Code | Field::Level => match (parse_uint(expected), parse_uint(actual))
+        {
+            (Some(e), Some(a)) => e == a,
+            _ => wildcard_match(expected, actual),
+        },
+        Field::Name | Field::Layer => wildcard_match(expected, actual),
+    }
+}
+
+/// Match `text` against `pattern`, where `*` stands for any run of
+/// characters.
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+fn comm_field(layer: &DiagLayer, comm: &DiagComm, field: Field) -> Option<String> {
+    match field {
+        Field::Name => Some(comm.short_name.to_string()),
+        Field::Semantic => Some(comm.semantic.to_string()),
+        Field::Layer => Some(layer.short_name.to_string()),
+        Field::Sid | Field::Code | Field::Level => None,
+    }
+}
+
+fn dtc_fields(dtc: &Dtc, field: Field) -> Vec<Option<String>> {
+    match field {
+        Field::Name => vec![Some(dtc.short_name.to_string())],
+        Field::Code => vec![
+            Some(dtc.trouble_code.to_string()),
+            Some(dtc.display_trouble_code.clone()),
+        ],
+        Field::Level => vec![dtc.level.map(|l| l.to_string())],
+        Field::Semantic | Field::Sid | Field::Layer => vec![None],
+    }
+}
+
+fn variant_fields(variant: &Variant, field: Field) -> Vec<Option<String>> {
+    match field {
+        Field::Name => vec![Some(variant.diag_layer.short_name.to_string())],
+        _ => vec![None],
+    }
+}
+
+/// The part of `db` the selectors select. See the [module docs](self) for
+/// which collections are kept whole.
+pub fn extract(db: &DiagDatabase, selectors: &[Selector]) -> DiagDatabase {
+    let of = |collection: Collection| -> Vec<&Selector> {
+        selectors
+            .iter()
+            .filter(|s| s.collection == collection)
+            .collect()
+    };
+    let (services, jobs, dtcs, variants) = (
+        of(Collection::Services),
+        of(Collection::Jobs),
+        of(Collection::Dtcs),
+        of(Collection::Variants),
+    );
+    let selects_elements = !(services.is_empty() && jobs.is_empty() && dtcs.is_empty());
+
+    let mut db = db.clone();
+    if !variants.is_empty() {
+        db.variants
+            .retain(|v| variants.iter().any(|s| s.selects(|f| variant_fields(v, f))));
+    }
+    if !selects_elements {
+        return db;
+    }
+    db.dtcs
+        .retain(|dtc| dtcs.iter().any(|s| s.selects(|f| dtc_fields(dtc, f))));
+
+    let layers = db
+        .variants
+        .iter_mut()
+        .map(|v| &mut v.diag_layer)
+        .chain(db.functional_groups.iter_mut().map(|fg| &mut fg.diag_layer))
+        .chain(db.protocols.iter_mut().map(|p| &mut p.diag_layer))
+        .chain(db.ecu_shared_datas.iter_mut().map(|e| &mut e.diag_layer));
+    for layer in layers {
+        let mut diag_services = std::mem::take(&mut layer.diag_services);
+        diag_services.retain(|svc| {
+            services.iter().any(|s| {
+                s.selects(|f| match f {
+                    Field::Sid => vec![service_sid(svc).map(|sid| sid.to_string())],
+                    // Services generated from YAML have no semantic; classify
+                    // them by their service ID.
+                    Field::Semantic if Semantic::parse(&svc.diag_comm.semantic).is_none() => {
+                        vec![Semantic::of(svc).map(|s| s.as_str().to_string())]
+                    }
+                    _ => vec![comm_field(layer, &svc.diag_comm, f)],
+                })
+            })
+        });
+        layer.diag_services = diag_services;
+
+        let mut single_ecu_jobs = std::mem::take(&mut layer.single_ecu_jobs);
+        single_ecu_jobs.retain(|job| {
+            jobs.iter()
+                .any(|s| s.selects(|f| vec![comm_field(layer, &job.diag_comm, f)]))
+        });
+        layer.single_ecu_jobs = single_ecu_jobs;
+    }
+    db
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{service, variant};
+    use crate::types::SingleEcuJob;
+
+    fn database() -> DiagDatabase {
+        let ecu_variant = |name: &str| {
+            let services = vec![
+                service("Read_VIN", "DATA-READ"),
+                service("Write_VIN", "DATA-WRITE"),
+            ];
+            let mut v = variant(name, services);
+            v.diag_layer.single_ecu_jobs = vec![SingleEcuJob {
+                diag_comm: DiagComm {
+                    short_name: "Flash".into(),
+                    ..Default::default()
+                },
+                prog_codes: vec![],
+                input_params: vec![],
+                output_params: vec![],
+                neg_output_params: vec![],
+            }];
+            v
+        };
+        DiagDatabase {
+            variants: vec![ecu_variant("EV_Engine"), ecu_variant("EV_Gearbox")],
+            dtcs: vec![Dtc {
+                short_name: "P0100".into(),
+                trouble_code: 0x0100,
+                display_trouble_code: "P0100".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_selectors() {
+        let selector: Selector = "services[semantic=DATA-READ, name!=Read_*]"
+            .parse()
+            .unwrap();
+        assert_eq!(selector.collection, Collection::Services);
+        assert_eq!(
+            selector.conditions,
+            [
+                Condition::equals(Field::Semantic, "DATA-READ"),
+                Condition {
+                    field: Field::Name,
+                    negated: true,
+                    value: "Read_*".into(),
+                },
+            ]
+        );
+        assert_eq!(
+            selector.to_string(),
+            "services[semantic=DATA-READ, name!=Read_*]"
+        );
+        assert_eq!("jobs".parse::<Selector>().unwrap().conditions, []);
+
+        assert!(matches!(
+            "things[name=x]".parse::<Selector>(),
+            Err(QueryError::UnknownCollection(_))
+        ));
+        assert!(matches!(
+            "dtcs[sid=0x22]".parse::<Selector>(),
+            Err(QueryError::UnknownField { .. })
+        ));
+        for bad in ["services[name=x", "services[name]", "services[,]"] {
+            assert!(
+                matches!(bad.parse::<Selector>(), Err(QueryError::Syntax { .. })),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("Read_*", "Read_VIN"));
+        assert!(wildcard_match("*VIN", "Read_VIN"));
+        assert!(wildcard_match("R*_*N", "Read_VIN"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("Read_", "Read_VIN"));
+        assert!(!wildcard_match("*_*_*", "Read_VIN"));
+    }
+
+    #[test]
+    fn test_extract_services() {
+        let db = database();
+        let selectors = [
+            "services[semantic=read-data]".parse().unwrap(),
+            Selector::variant("EV_Engine"),
+        ];
+        let extracted = extract(&db, &selectors);
+        assert_eq!(extracted.variants.len(), 1);
+        let layer = &extracted.variants[0].diag_layer;
+        assert_eq!(layer.short_name, "EV_Engine");
+        let names: Vec<_> = layer
+            .diag_services
+            .iter()
+            .map(|s| s.diag_comm.short_name.as_str())
+            .collect();
+        assert_eq!(names, ["Read_VIN"]);
+        assert!(layer.single_ecu_jobs.is_empty());
+        assert!(extracted.dtcs.is_empty());
+    }
+
+    #[test]
+    fn test_extract_variants_only_keeps_elements() {
+        let db = database();
+        let extracted = extract(&db, &[Selector::variant("EV_Gearbox")]);
+        assert_eq!(extracted.variants, db.variants[1..]);
+        assert_eq!(extracted.dtcs, db.dtcs);
+
+        let dtcs = extract(&db, &["dtcs[code=0x100]".parse().unwrap()]);
+        assert_eq!(dtcs.dtcs, db.dtcs);
+        assert!(
+            dtcs.variants
+                .iter()
+                .all(|v| v.diag_layer.diag_services.is_empty())
+        );
+    }
+}
@@ -0,0 +1,569 @@
+//! Bulk renames from an old -> new short-name map.
+//!
+//! Adopting new naming rules means renaming hundreds of services, DOPs and
+//! states at once, and every place that refers to them by name has to
+//! follow. [`apply_renames`] renames the services, jobs, DOPs and states
+//! whose short name is a key of the map, and updates the name references
+//! to them: the NOT-INHERITED lists of parent refs, the start state and
+//! transitions of state charts, the state references of services, the
+//! service copies in variant patterns and table connectors, and the
+//! `service` steps of flash sequences. Map entries that matched nothing are
+//! reported, so a typo in the map does not go unnoticed.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+use crate::flash::FLASH_SEQUENCE_CAPTION;
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenameKind {
+    Service,
+    Job,
+    Dop,
+    State,
+}
+
+impl RenameKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Service => "service",
+            Self::Job => "job",
+            Self::Dop => "DOP",
+            Self::State => "state",
+        }
+    }
+}
+
+impl fmt::Display for RenameKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An object renamed by [`apply_renames`]. `scope` is the layer short name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+    pub kind: RenameKind,
+    pub scope: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl fmt::Display for Rename {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "renamed {} '{}' to '{}' in '{}'",
+            self.kind, self.old, self.new, self.scope
+        )
+    }
+}
+
+/// What [`apply_renames`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenameReport {
+    /// Renamed objects in database order. A DOP is listed once per layer,
+    /// however many parameters use it.
+    pub renamed: Vec<Rename>,
+    /// Old names of the map entries that matched nothing, in map order.
+    pub unmatched: Vec<String>,
+}
+
+/// Rename every service, job, DOP and state whose short name is a key of
+/// `names` to its value, and update the references to them. One map covers
+/// all kinds. A DOP shared by several parameters is renamed once and stays
+/// shared.
+pub fn apply_renames(db: &mut DiagDatabase, names: &BTreeMap<String, String>) -> RenameReport {
+    let mut renamer = Renamer {
+        names,
+        matched: BTreeSet::new(),
+        renamed: Vec::new(),
+        reported_dops: HashSet::new(),
+        dops: HashMap::new(),
+    };
+    for variant in &mut db.variants {
+        renamer.layer(&mut variant.diag_layer);
+        let scope = variant.diag_layer.short_name.clone();
+        for parameter in variant
+            .variant_patterns
+            .iter_mut()
+            .flat_map(|p| &mut p.matching_parameters)
+        {
+            renamer.service(&scope, &mut parameter.diag_service);
+            renamer.param(&scope, &mut parameter.out_param);
+        }
+        renamer.parent_refs(&mut variant.parent_refs);
+    }
+    for group in &mut db.functional_groups {
+        renamer.layer(&mut group.diag_layer);
+        renamer.parent_refs(&mut group.parent_refs);
+    }
+    for protocol in &mut db.protocols {
+        renamer.layer(&mut protocol.diag_layer);
+        renamer.parent_refs(&mut protocol.parent_refs);
+    }
+    for shared in &mut db.ecu_shared_datas {
+        renamer.layer(&mut shared.diag_layer);
+    }
+
+    RenameReport {
+        renamed: renamer.renamed,
+        unmatched: names
+            .keys()
+            .filter(|old| !renamer.matched.contains(old.as_str()))
+            .cloned()
+            .collect(),
+    }
+}
+
+struct Renamer<'a> {
+    names: &'a BTreeMap<String, String>,
+    matched: BTreeSet<&'a str>,
+    renamed: Vec<Rename>,
+    /// (layer, old name) of the DOPs already listed in `renamed`.
+    reported_dops: HashSet<(String, String)>,
+    /// Shared DOPs already visited, by address, with the DOP replacing
+    /// them. The original is kept alive so its address is not reused.
+    dops: HashMap<*const Dop, (DopRef, DopRef)>,
+}
+
+impl Renamer<'_> {
+    /// Replace `name` by its new name, if the map has one.
+    fn rename<T: AsRef<str> + for<'s> From<&'s String>>(&mut self, name: &mut T) -> bool {
+        let Some((old, new)) = self.names.get_key_value(name.as_ref()) else {
+            return false;
+        };
+        self.matched.insert(old.as_str());
+        *name = T::from(new);
+        true
+    }
+
+    fn report(&mut self, kind: RenameKind, scope: &str, old: String, new: &str) {
+        if old != new {
+            self.renamed.push(Rename {
+                kind,
+                scope: scope.to_string(),
+                old,
+                new: new.to_string(),
+            });
+        }
+    }
+
+    fn layer(&mut self, layer: &mut DiagLayer) {
+        let scope = layer.short_name.clone();
+        for service in &mut layer.diag_services {
+            let old = service.diag_comm.short_name.to_string();
+            self.service(&scope, service);
+            self.report(
+                RenameKind::Service,
+                &scope,
+                old,
+                &service.diag_comm.short_name,
+            );
+        }
+        for job in &mut layer.single_ecu_jobs {
+            let old = job.diag_comm.short_name.to_string();
+            self.job(&scope, job);
+            self.report(RenameKind::Job, &scope, old, &job.diag_comm.short_name);
+        }
+        for chart in &mut layer.state_charts {
+            for state in &mut chart.states {
+                let old = state.short_name.to_string();
+                self.rename(&mut state.short_name);
+                self.report(RenameKind::State, &scope, old, &state.short_name);
+            }
+            self.rename(&mut chart.start_state_short_name_ref);
+            for transition in &mut chart.state_transitions {
+                self.transition(transition);
+            }
+        }
+        let flash_steps = layer
+            .sdgs
+            .iter_mut()
+            .flat_map(|sdgs| &mut sdgs.sdgs)
+            .filter(|sdg| sdg.caption_sn == FLASH_SEQUENCE_CAPTION)
+            .flat_map(|sdg| &mut sdg.sds);
+        for step in flash_steps {
+            if let SdOrSdg::Sd(sd) = step {
+                if sd.si == "service" {
+                    self.rename(&mut sd.value);
+                }
+            }
+        }
+    }
+
+    fn parent_refs(&mut self, parent_refs: &mut [ParentRef]) {
+        for parent in parent_refs {
+            for name in parent
+                .not_inherited_diag_comm_short_names
+                .iter_mut()
+                .chain(&mut parent.not_inherited_dops_short_names)
+            {
+                self.rename(name);
+            }
+        }
+    }
+
+    fn transition(&mut self, transition: &mut StateTransition) -> bool {
+        let source = self.rename(&mut transition.source_short_name_ref);
+        let target = self.rename(&mut transition.target_short_name_ref);
+        source || target
+    }
+
+    fn diag_comm(&mut self, comm: &mut DiagComm) -> bool {
+        let mut changed = self.rename(&mut comm.short_name);
+        for state_ref in &mut comm.pre_condition_state_refs {
+            if let Some(state) = &mut state_ref.state {
+                changed |= self.rename(&mut state.short_name);
+            }
+        }
+        for transition_ref in &mut comm.state_transition_refs {
+            if let Some(transition) = &mut transition_ref.state_transition {
+                changed |= self.transition(transition);
+            }
+        }
+        changed
+    }
+
+    fn service(&mut self, scope: &str, service: &mut DiagService) -> bool {
+        let mut changed = self.diag_comm(&mut service.diag_comm);
+        if let Some(request) = &mut service.request {
+            changed |= self.params(scope, &mut request.params);
+        }
+        for response in service
+            .pos_responses
+            .iter_mut()
+            .chain(&mut service.neg_responses)
+        {
+            changed |= self.params(scope, &mut response.params);
+        }
+        changed
+    }
+
+    fn job(&mut self, scope: &str, job: &mut SingleEcuJob) -> bool {
+        let mut changed = self.diag_comm(&mut job.diag_comm);
+        let params = job
+            .input_params
+            .iter_mut()
+            .chain(&mut job.output_params)
+            .chain(&mut job.neg_output_params);
+        for param in params {
+            if let Some(dop) = &mut param.dop_base {
+                changed |= self.dop_ref(scope, dop);
+            }
+        }
+        changed
+    }
+
+    fn params(&mut self, scope: &str, params: &mut [Param]) -> bool {
+        let mut changed = false;
+        for param in params {
+            changed |= self.param(scope, param);
+        }
+        changed
+    }
+
+    fn param(&mut self, scope: &str, param: &mut Param) -> bool {
+        match &mut param.specific_data {
+            Some(
+                ParamData::Value { dop, .. }
+                | ParamData::PhysConst { dop, .. }
+                | ParamData::System { dop, .. }
+                | ParamData::LengthKeyRef { dop },
+            ) => self.dop_ref(scope, dop),
+            Some(ParamData::TableEntry {
+                param: entry,
+                table_row,
+                ..
+            }) => {
+                let entry = self.param(scope, entry);
+                let row = self.table_row(scope, table_row);
+                entry || row
+            }
+            Some(ParamData::TableKey {
+                table_key_reference,
+            }) => match table_key_reference {
+                TableKeyReference::TableDop(table) => self.table_dop(scope, table),
+                TableKeyReference::TableRow(row) => self.table_row(scope, row),
+            },
+            Some(ParamData::TableStruct { table_key }) => self.param(scope, table_key),
+            _ => false,
+        }
+    }
+
+    fn table_dop(&mut self, scope: &str, table: &mut TableDop) -> bool {
+        let mut changed = false;
+        if let Some(dop) = &mut table.key_dop {
+            changed |= self.dop_ref(scope, dop);
+        }
+        for row in &mut table.rows {
+            changed |= self.table_row(scope, row);
+        }
+        for connector in &mut table.diag_comm_connectors {
+            changed |= match &mut connector.diag_comm {
+                DiagServiceOrJob::DiagService(service) => self.service(scope, service),
+                DiagServiceOrJob::SingleEcuJob(job) => self.job(scope, job),
+            };
+        }
+        changed
+    }
+
+    fn table_row(&mut self, scope: &str, row: &mut TableRow) -> bool {
+        let mut changed = false;
+        for dop in row.dop.iter_mut().chain(&mut row.structure) {
+            changed |= self.dop_ref(scope, dop);
+        }
+        changed
+    }
+
+    /// Rename a shared DOP. Parameters sharing it before share the renamed
+    /// DOP afterwards; DOPs the map does not touch stay as they are.
+    fn dop_ref(&mut self, scope: &str, dop: &mut DopRef) -> bool {
+        let address = std::ptr::from_ref::<Dop>(dop);
+        if let Some((original, renamed)) = self.dops.get(&address) {
+            let changed = !DopRef::ptr_eq(original, renamed);
+            *dop = renamed.clone();
+            return changed;
+        }
+        let original = dop.clone();
+        let mut copy = Dop::clone(&original);
+        let changed = self.dop(scope, &mut copy);
+        if changed {
+            *dop = DopRef::new(copy);
+        }
+        self.dops.insert(address, (original, dop.clone()));
+        changed
+    }
+
+    fn dop(&mut self, scope: &str, dop: &mut Dop) -> bool {
+        let old = dop.short_name.to_string();
+        let mut changed = self.rename(&mut dop.short_name);
+        if changed && self.reported_dops.insert((scope.to_string(), old.clone())) {
+            self.report(RenameKind::Dop, scope, old, &dop.short_name);
+        }
+        match &mut dop.specific_data {
+            Some(DopData::EndOfPduField { field, .. } | DopData::StaticField { field, .. }) => {
+                if let Some(field) = field {
+                    changed |= self.field(scope, field);
+                }
+            }
+            Some(DopData::DynamicLengthField {
+                field,
+                determine_number_of_items,
+                ..
+            }) => {
+                if let Some(field) = field {
+                    changed |= self.field(scope, field);
+                }
+                if let Some(items) = determine_number_of_items {
+                    changed |= self.dop_ref(scope, &mut items.dop);
+                }
+            }
+            Some(DopData::EnvDataDesc { env_datas, .. }) => {
+                for env_data in env_datas {
+                    changed |= self.dop(scope, env_data);
+                }
+            }
+            Some(DopData::EnvData { params, .. } | DopData::Structure { params, .. }) => {
+                changed |= self.params(scope, params);
+            }
+            Some(DopData::MuxDop {
+                switch_key,
+                default_case,
+                cases,
+                ..
+            }) => {
+                if let Some(key) = switch_key {
+                    changed |= self.dop_ref(scope, &mut key.dop);
+                }
+                let structures = default_case
+                    .iter_mut()
+                    .map(|c| &mut c.structure)
+                    .chain(cases.iter_mut().map(|c| &mut c.structure))
+                    .flatten();
+                for structure in structures {
+                    changed |= self.dop_ref(scope, structure);
+                }
+            }
+            _ => {}
+        }
+        changed
+    }
+
+    fn field(&mut self, scope: &str, field: &mut Field) -> bool {
+        let mut changed = false;
+        for dop in field
+            .basic_structure
+            .iter_mut()
+            .chain(&mut field.env_data_desc)
+        {
+            changed |= self.dop_ref(scope, dop);
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{base_variant, dop, service, variant};
+
+    fn value(name: &str, dop: &DopRef) -> Param {
+        Param {
+            short_name: name.into(),
+            param_type: ParamType::Value,
+            specific_data: Some(ParamData::Value {
+                physical_default_value: String::new(),
+                dop: dop.clone(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// A service allowed in state `Default` answering with `params`.
+    fn reading(name: &str, params: Vec<Param>) -> DiagService {
+        let mut svc = DiagService {
+            pos_responses: vec![Response {
+                response_type: ResponseType::PosResponse,
+                params,
+                sdgs: None,
+                pos_response_suffix: None,
+                audience: None,
+            }],
+            ..service(name, "")
+        };
+        svc.diag_comm.pre_condition_state_refs = vec![PreConditionStateRef {
+            value: String::new(),
+            in_param_if_short_name: String::new(),
+            in_param_path_short_name: String::new(),
+            state: Some(State {
+                short_name: "Default".into(),
+                long_name: None,
+            }),
+        }];
+        svc
+    }
+
+    fn database() -> DiagDatabase {
+        let speed = DopRef::new(dop("Speed"));
+        let mut base = base_variant(
+            "Base",
+            vec![
+                reading("Read_Speed", vec![value("Speed", &speed)]),
+                reading("Read_Limit", vec![value("Limit", &speed)]),
+            ],
+        );
+        base.diag_layer.state_charts = vec![StateChart {
+            short_name: "Session".into(),
+            semantic: Name::default(),
+            state_transitions: vec![StateTransition {
+                short_name: "ToExtended".into(),
+                source_short_name_ref: "Default".into(),
+                target_short_name_ref: "Extended".into(),
+            }],
+            start_state_short_name_ref: "Default".into(),
+            states: vec![
+                State {
+                    short_name: "Default".into(),
+                    long_name: None,
+                },
+                State {
+                    short_name: "Extended".into(),
+                    long_name: None,
+                },
+            ],
+        }];
+        DiagDatabase {
+            variants: vec![
+                base,
+                Variant {
+                    parent_refs: vec![ParentRef {
+                        ref_type: ParentRefType::Variant(Box::default()),
+                        not_inherited_diag_comm_short_names: vec!["Read_Limit".into()],
+                        not_inherited_variables_short_names: vec![],
+                        not_inherited_dops_short_names: vec!["Speed".into()],
+                        not_inherited_tables_short_names: vec![],
+                        not_inherited_global_neg_responses_short_names: vec![],
+                    }],
+                    ..variant("Variant_A", vec![])
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn names(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(old, new)| ((*old).to_string(), (*new).to_string()))
+            .collect()
+    }
+
+    fn dop_of(service: &DiagService) -> &DopRef {
+        match &service.pos_responses[0].params[0].specific_data {
+            Some(ParamData::Value { dop, .. }) => dop,
+            other => panic!("expected a VALUE param, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_renames_objects_and_references() {
+        let mut db = database();
+        let report = apply_renames(
+            &mut db,
+            &names(&[
+                ("Read_Limit", "Limit_Read"),
+                ("Speed", "VehicleSpeed"),
+                ("Default", "DefaultSession"),
+                ("Missing", "Anything"),
+            ]),
+        );
+        let renamed: Vec<String> = report.renamed.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            renamed,
+            [
+                "renamed DOP 'Speed' to 'VehicleSpeed' in 'Base'",
+                "renamed service 'Read_Limit' to 'Limit_Read' in 'Base'",
+                "renamed state 'Default' to 'DefaultSession' in 'Base'",
+            ]
+        );
+        assert_eq!(report.unmatched, ["Missing"]);
+
+        let base = &db.variants[0].diag_layer;
+        assert_eq!(base.diag_services[1].diag_comm.short_name, "Limit_Read");
+        let chart = &base.state_charts[0];
+        assert_eq!(chart.start_state_short_name_ref, "DefaultSession");
+        assert_eq!(
+            chart.state_transitions[0].source_short_name_ref,
+            "DefaultSession"
+        );
+        let state_ref = &base.diag_services[0].diag_comm.pre_condition_state_refs[0];
+        assert_eq!(
+            state_ref.state.as_ref().unwrap().short_name,
+            "DefaultSession"
+        );
+        let parent = &db.variants[1].parent_refs[0];
+        assert_eq!(parent.not_inherited_diag_comm_short_names, ["Limit_Read"]);
+        assert_eq!(parent.not_inherited_dops_short_names, ["VehicleSpeed"]);
+
+        // Both parameters still share the one renamed DOP.
+        let (first, second) = (
+            dop_of(&base.diag_services[0]),
+            dop_of(&base.diag_services[1]),
+        );
+        assert_eq!(first.short_name, "VehicleSpeed");
+        assert!(DopRef::ptr_eq(first, second));
+    }
+
+    #[test]
+    fn test_untouched_dops_stay_shared() {
+        let mut db = database();
+        let before = dop_of(&db.variants[0].diag_layer.diag_services[0]).clone();
+        let report = apply_renames(&mut db, &names(&[("Read_Speed", "Speed_Read")]));
+        assert_eq!(report.renamed.len(), 1);
+        assert!(report.unmatched.is_empty());
+        let after = dop_of(&db.variants[0].diag_layer.diag_services[0]);
+        assert!(DopRef::ptr_eq(&before, after));
+    }
+}
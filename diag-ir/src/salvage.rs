@@ -0,0 +1,343 @@
+//! Partial conversion of damaged FlatBuffers payloads.
+//!
+//! [`flatbuffers_to_ir`](crate::flatbuffers_to_ir) verifies the whole buffer
+//! up front and rejects it on the first bad offset. [`salvage_flatbuffers`]
+//! verifies the header strings, every variant, functional group, DTC and
+//! type definition, and the memory, transport and identification sections
+//! on its own instead, converts those that pass and reports the others, so a
+//! truncated payload still yields what it holds.
+
+use flatbuffers::{
+    Follow, ForwardsUOffset, InvalidFlatbuffer, SIZE_UOFFSET, VOffsetT, Vector, Verifiable,
+    Verifier, VerifierOptions,
+};
+use mdd_format::dataformat::{self, EcuData};
+
+use crate::from_fbs::{
+    convert_dtc, convert_ecu_identification, convert_functional_group, convert_memory_config,
+    convert_transport_config, convert_type_definition, convert_variant,
+    reconstruct_protocols_from_variants,
+};
+use crate::types::DiagDatabase;
+
+/// What [`salvage_flatbuffers`] recovered, and what it had to skip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Salvage {
+    pub database: DiagDatabase,
+    /// One entry per skipped part; empty when the payload was intact.
+    pub warnings: Vec<String>,
+}
+
+/// Convert what is readable of a possibly damaged FlatBuffers payload.
+/// Intact payloads convert exactly as with
+/// [`flatbuffers_to_ir`](crate::flatbuffers_to_ir).
+pub fn salvage_flatbuffers(fbs_data: &[u8]) -> Salvage {
+    if let Ok(database) = crate::flatbuffers_to_ir(fbs_data) {
+        return Salvage {
+            database,
+            warnings: vec![],
+        };
+    }
+    let mut salvage = Salvage {
+        database: DiagDatabase::default(),
+        warnings: vec![],
+    };
+    let opts = VerifierOptions::default();
+    let mut v = Verifier::new(&opts, fbs_data);
+    let fields = match root_fields(&mut v) {
+        Ok(fields) => fields,
+        Err(e) => {
+            salvage
+                .warnings
+                .push(format!("root table unreadable: {}", brief(&e)));
+            return salvage;
+        }
+    };
+    let [
+        version,
+        ecu_name,
+        revision,
+        variants,
+        functional_groups,
+        dtcs,
+        memory,
+        type_definitions,
+        transport,
+        identification,
+    ] = fields;
+
+    let db = &mut salvage.database;
+    let warnings = &mut salvage.warnings;
+    for (pos, name, target) in [
+        (version, "version", &mut db.version),
+        (ecu_name, "ecu_name", &mut db.ecu_name),
+        (revision, "revision", &mut db.revision),
+    ] {
+        let Some(pos) = pos else { continue };
+        match follow_verified::<ForwardsUOffset<&str>>(&mut v, fbs_data, pos) {
+            Ok(s) => *target = s.to_string(),
+            Err(e) => warnings.push(format!("{name} skipped: {}", brief(&e))),
+        }
+    }
+
+    db.variants = tables::<dataformat::Variant>(&mut v, fbs_data, variants, "variant", warnings)
+        .iter()
+        .map(convert_variant)
+        .collect();
+    db.functional_groups = tables::<dataformat::FunctionalGroup>(
+        &mut v,
+        fbs_data,
+        functional_groups,
+        "functional group",
+        warnings,
+    )
+    .iter()
+    .map(convert_functional_group)
+    .collect();
+    db.dtcs = tables::<dataformat::DTC>(&mut v, fbs_data, dtcs, "DTC", warnings)
+        .iter()
+        .map(convert_dtc)
+        .collect();
+    db.type_definitions = tables::<dataformat::TypeDefinition>(
+        &mut v,
+        fbs_data,
+        type_definitions,
+        "type definition",
+        warnings,
+    )
+    .iter()
+    .map(convert_type_definition)
+    .collect();
+    if let Some(pos) = memory {
+        match follow_verified::<ForwardsUOffset<dataformat::MemoryConfig>>(&mut v, fbs_data, pos) {
+            Ok(memory) => db.memory = Some(convert_memory_config(&memory)),
+            Err(e) => warnings.push(format!("memory skipped: {}", brief(&e))),
+        }
+    }
+    if let Some(pos) = transport {
+        match follow_verified::<ForwardsUOffset<dataformat::TransportConfig>>(&mut v, fbs_data, pos)
+        {
+            Ok(transport) => db.transport = Some(convert_transport_config(&transport)),
+            Err(e) => warnings.push(format!("transport skipped: {}", brief(&e))),
+        }
+    }
+    if let Some(pos) = identification {
+        match follow_verified::<ForwardsUOffset<dataformat::EcuIdentification>>(
+            &mut v, fbs_data, pos,
+        ) {
+            Ok(ident) => db.identification = Some(convert_ecu_identification(&ident)),
+            Err(e) => warnings.push(format!("identification skipped: {}", brief(&e))),
+        }
+    }
+    db.protocols = reconstruct_protocols_from_variants(&db.variants);
+    if warnings.is_empty() {
+        // Only parts salvage does not read (metadata, feature flags) are damaged.
+        warnings.push("metadata and feature flags skipped: payload fails verification".into());
+    }
+    salvage
+}
+
+/// Positions of the root table fields salvage reads, in the order version,
+/// ECU name, revision, variants, functional groups, DTCs, memory, type
+/// definitions, transport, identification.
+fn root_fields(v: &mut Verifier) -> Result<[Option<usize>; 10], InvalidFlatbuffer> {
+    let root = v.get_uoffset(0)? as usize;
+    let mut table = v.visit_table(root)?;
+    let mut field = |offset: VOffsetT| table.deref(offset);
+    Ok([
+        field(EcuData::VT_VERSION)?,
+        field(EcuData::VT_ECU_NAME)?,
+        field(EcuData::VT_REVISION)?,
+        field(EcuData::VT_VARIANTS)?,
+        field(EcuData::VT_FUNCTIONAL_GROUPS)?,
+        field(EcuData::VT_DTCS)?,
+        field(EcuData::VT_MEMORY)?,
+        field(EcuData::VT_TYPE_DEFINITIONS)?,
+        field(EcuData::VT_TRANSPORT)?,
+        field(EcuData::VT_IDENTIFICATION)?,
+    ])
+}
+
+/// Verify `T` at `pos` on its own and follow it.
+// The generated accessors are only reachable through `Follow`, which is
+// unsafe; this is the one place tables are followed.
+#[allow(unsafe_code)]
+fn follow_verified<'a, T: Verifiable + Follow<'a>>(
+    v: &mut Verifier,
+    buf: &'a [u8],
+    pos: usize,
+) -> Result<T::Inner, InvalidFlatbuffer> {
+    v.reset();
+    T::run_verifier(v, pos)?;
+    // SAFETY: `T` was verified at `pos` just above.
+    Ok(unsafe { T::follow(buf, pos) })
+}
+
+/// The readable tables of the vector of tables referenced from `field`,
+/// with a warning for each unreadable one.
+fn tables<'a, T>(
+    v: &mut Verifier,
+    buf: &'a [u8],
+    field: Option<usize>,
+    what: &str,
+    warnings: &mut Vec<String>,
+) -> Vec<T::Inner>
+where
+    T: Verifiable + Follow<'a>,
+{
+    let Some(field) = field else {
+        return vec![];
+    };
+    v.reset();
+    let slots = match vector_slots(v, field) {
+        Ok(slots) => slots,
+        Err(e) => {
+            warnings.push(format!("all {what}s skipped: {}", brief(&e)));
+            return vec![];
+        }
+    };
+    let mut found = Vec::new();
+    for (i, slot) in slots.enumerate() {
+        match follow_verified::<ForwardsUOffset<T>>(v, buf, slot) {
+            Ok(table) => found.push(table),
+            Err(e) => warnings.push(format!("{what} #{i} skipped: {}", brief(&e))),
+        }
+    }
+    found
+}
+
+/// Positions of the element offsets of the vector referenced from `field`.
+fn vector_slots(
+    v: &mut Verifier,
+    field: usize,
+) -> Result<impl Iterator<Item = usize> + use<>, InvalidFlatbuffer> {
+    let vector = field + v.get_uoffset(field)? as usize;
+    <Vector<u32>>::run_verifier(v, vector)?;
+    let len = v.get_uoffset(vector)? as usize;
+    let start = vector + SIZE_UOFFSET;
+    Ok((0..len).map(move |i| start + i * SIZE_UOFFSET))
+}
+
+/// First line of a verifier error, without the field trace.
+fn brief(e: &InvalidFlatbuffer) -> String {
+    let message = e.to_string();
+    message
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::variant;
+    use crate::types::{
+        AddressFormat, DoipConfig, MemoryAccess, MemoryConfig, MemoryRegion, TransportConfig,
+        TypeDefinition,
+    };
+
+    fn database(variants: usize) -> DiagDatabase {
+        DiagDatabase {
+            ecu_name: "ECU".into(),
+            variants: (0..variants)
+                .map(|i| variant(&format!("Variant{i}"), vec![]))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Point the offset of variant `index` past the end of the buffer.
+    fn damage_variant(fbs: &mut [u8], index: usize) {
+        let slot = {
+            let opts = VerifierOptions::default();
+            let mut v = Verifier::new(&opts, fbs);
+            let fields = root_fields(&mut v).unwrap();
+            vector_slots(&mut v, fields[3].unwrap())
+                .unwrap()
+                .nth(index)
+                .unwrap()
+        };
+        fbs[slot..slot + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    }
+
+    #[test]
+    fn test_intact_payload_converts_unchanged() {
+        let db = database(2);
+        let salvage = salvage_flatbuffers(&crate::ir_to_flatbuffers(&db));
+        assert!(salvage.warnings.is_empty());
+        assert_eq!(
+            salvage.database,
+            crate::flatbuffers_to_ir(&crate::ir_to_flatbuffers(&db)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_damaged_variant_is_skipped() {
+        let db = database(3);
+        let mut fbs = crate::ir_to_flatbuffers(&db);
+        let intact = crate::flatbuffers_to_ir(&fbs).unwrap();
+        damage_variant(&mut fbs, 1);
+
+        let salvage = salvage_flatbuffers(&fbs);
+        let names: Vec<_> = salvage
+            .database
+            .variants
+            .iter()
+            .map(|v| v.diag_layer.short_name.as_str())
+            .collect();
+        assert_eq!(names, ["Variant0", "Variant2"]);
+        assert_eq!(salvage.database.ecu_name, intact.ecu_name);
+        assert_eq!(salvage.warnings.len(), 1);
+        assert!(
+            salvage.warnings[0].starts_with("variant #1 skipped"),
+            "{:?}",
+            salvage.warnings
+        );
+    }
+
+    #[test]
+    fn test_sections_outside_the_damage_are_kept() {
+        let db = DiagDatabase {
+            memory: Some(MemoryConfig {
+                default_address_format: AddressFormat::default(),
+                regions: vec![MemoryRegion {
+                    name: "Flash".into(),
+                    description: None,
+                    start_address: 0x8000,
+                    size: 0x1000,
+                    access: MemoryAccess::ReadWrite,
+                    address_format: None,
+                    security_level: None,
+                    session: None,
+                }],
+                data_blocks: vec![],
+            }),
+            type_definitions: vec![TypeDefinition {
+                name: "Speed".into(),
+                base: "u16".into(),
+                ..Default::default()
+            }],
+            transport: Some(TransportConfig {
+                doip: Some(DoipConfig {
+                    ip: Some("192.168.0.10".into()),
+                    logical_address: Some(0x1000),
+                    ..Default::default()
+                }),
+            }),
+            ..database(2)
+        };
+        let mut fbs = crate::ir_to_flatbuffers(&db);
+        let intact = crate::flatbuffers_to_ir(&fbs).unwrap();
+        damage_variant(&mut fbs, 0);
+
+        let salvage = salvage_flatbuffers(&fbs);
+        assert_eq!(salvage.database.variants.len(), 1);
+        assert_eq!(salvage.database.memory, intact.memory);
+        assert_eq!(salvage.database.type_definitions, intact.type_definitions);
+        assert_eq!(salvage.database.transport, intact.transport);
+        assert!(salvage.database.memory.is_some());
+        assert_eq!(salvage.warnings.len(), 1, "{:?}", salvage.warnings);
+    }
+}
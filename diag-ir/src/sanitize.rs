@@ -0,0 +1,259 @@
+//! Characters XML 1.0 cannot carry.
+//!
+//! Supplier databases sometimes hold control characters in descriptions and
+//! long names, pasted from spreadsheets or terminal logs. YAML and MDD keep
+//! them, but an ODX file written with them is no longer well-formed.
+//! [`sanitize`] finds them in every string of a database and strips or
+//! escapes them, reporting each field it changed by its object path (see
+//! [`crate::ProfileMapping`]).
+
+use std::fmt::{self, Write as _};
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::profile::{item_name, join};
+use crate::types::DiagDatabase;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidCharPolicy {
+    /// Remove the characters.
+    #[default]
+    Strip,
+    /// Replace each character with its escape, e.g. `\x1B`.
+    Escape,
+    /// Fail, listing the fields that hold such characters.
+    Error,
+}
+
+impl InvalidCharPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Strip => "strip",
+            Self::Escape => "escape",
+            Self::Error => "error",
+        }
+    }
+
+    /// What the policy did to a reported field.
+    pub fn resolution(self) -> &'static str {
+        match self {
+            Self::Strip => "stripped",
+            Self::Escape => "escaped",
+            Self::Error => "rejected",
+        }
+    }
+}
+
+impl fmt::Display for InvalidCharPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for InvalidCharPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Self::Strip, Self::Escape, Self::Error]
+            .into_iter()
+            .find(|p| p.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                format!("unknown invalid character policy '{s}' (expected strip, escape or error)")
+            })
+    }
+}
+
+/// A field holding characters XML cannot represent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidChars {
+    /// Object path of the field, e.g. `variants/Base/diag_layer/description`.
+    pub path: String,
+    /// The distinct characters, in order of appearance.
+    pub chars: Vec<char>,
+}
+
+impl fmt::Display for InvalidChars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.path)?;
+        for (i, c) in self.chars.iter().enumerate() {
+            let sep = if i == 0 { " " } else { ", " };
+            write!(f, "{sep}U+{:04X}", u32::from(*c))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SanitizeError {
+    #[error("{} field(s) hold characters XML cannot represent:{}", .0.len(), list(.0))]
+    InvalidChars(Vec<InvalidChars>),
+    #[error("Cannot rewrite the database: {0}")]
+    Rewrite(#[from] serde_json::Error),
+}
+
+fn list(fields: &[InvalidChars]) -> String {
+    let mut out = String::new();
+    for field in fields {
+        out.push_str("\n  ");
+        out.push_str(&field.to_string());
+    }
+    out
+}
+
+/// Whether XML 1.0 forbids `c`: the C0 controls other than tab, line feed
+/// and carriage return, and the noncharacters U+FFFE and U+FFFF.
+pub fn is_invalid_xml_char(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0}'..='\u{8}' | '\u{B}' | '\u{C}' | '\u{E}'..='\u{1F}' | '\u{FFFE}' | '\u{FFFF}'
+    )
+}
+
+/// Apply `policy` to every string of `db` holding characters XML cannot
+/// represent, and return those fields. Under [`InvalidCharPolicy::Error`]
+/// `db` is left alone and the fields are the error.
+pub fn sanitize(
+    db: &mut DiagDatabase,
+    policy: InvalidCharPolicy,
+) -> Result<Vec<InvalidChars>, SanitizeError> {
+    // Most databases are clean; their JSON form shows it without building
+    // a value tree.
+    if !may_hold_invalid_chars(&serde_json::to_vec(&*db)?) {
+        return Ok(Vec::new());
+    }
+    let mut value = serde_json::to_value(&*db)?;
+    let mut fields = Vec::new();
+    visit(&mut value, "", policy, &mut fields);
+    if fields.is_empty() {
+        return Ok(fields);
+    }
+    if policy == InvalidCharPolicy::Error {
+        return Err(SanitizeError::InvalidChars(fields));
+    }
+    *db = serde_json::from_value(value)?;
+    Ok(fields)
+}
+
+/// serde_json writes control characters as `\b`, `\f` or `\u00XX`, and
+/// U+FFFE and U+FFFF as their UTF-8 bytes. An escaped backslash followed by
+/// `b`, `f` or `u` matches too, which only costs the full check.
+fn may_hold_invalid_chars(json: &[u8]) -> bool {
+    json.windows(2)
+        .any(|w| matches!(w, [b'\\', b'b' | b'f' | b'u']))
+        || json
+            .windows(3)
+            .any(|w| matches!(w, [0xEF, 0xBF, 0xBE | 0xBF]))
+}
+
+fn visit(value: &mut Value, path: &str, policy: InvalidCharPolicy, fields: &mut Vec<InvalidChars>) {
+    match value {
+        Value::String(s) => {
+            let mut chars: Vec<char> = Vec::new();
+            for c in s.chars().filter(|&c| is_invalid_xml_char(c)) {
+                if !chars.contains(&c) {
+                    chars.push(c);
+                }
+            }
+            if chars.is_empty() {
+                return;
+            }
+            if policy != InvalidCharPolicy::Error {
+                *s = clean(s, policy);
+            }
+            fields.push(InvalidChars {
+                path: path.to_string(),
+                chars,
+            });
+        }
+        Value::Object(object) => {
+            for (key, child) in object.iter_mut() {
+                visit(child, &join(path, key), policy, fields);
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                let segment = item_name(item).map_or_else(|| i.to_string(), str::to_string);
+                visit(item, &join(path, &segment), policy, fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn clean(s: &str, policy: InvalidCharPolicy) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if !is_invalid_xml_char(c) {
+            out.push(c);
+        } else if policy == InvalidCharPolicy::Escape {
+            let code = u32::from(c);
+            let _ = if code < 0x100 {
+                write!(out, "\\x{code:02X}")
+            } else {
+                write!(out, "\\u{code:04X}")
+            };
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, service};
+    use crate::types::LongName;
+
+    fn database() -> DiagDatabase {
+        let mut read = service("ReadVIN", "");
+        read.diag_comm.long_name = Some(LongName {
+            value: "Read\tVIN\u{FFFF}".into(),
+            ti: String::new(),
+        });
+        let mut db = test_support::database(vec![read]);
+        db.variants[0].diag_layer.description = Some("Line 1\nLine\u{1B}[0m 2\u{0}".into());
+        db
+    }
+
+    #[test]
+    fn test_sanitize_strips_or_escapes_and_reports() {
+        let mut db = database();
+        let fields = sanitize(&mut db, InvalidCharPolicy::Strip).unwrap();
+        let layer = &db.variants[0].diag_layer;
+        assert_eq!(layer.description.as_deref(), Some("Line 1\nLine[0m 2"));
+        let long_name = layer.diag_services[0].diag_comm.long_name.as_ref();
+        assert_eq!(long_name.unwrap().value, "Read\tVIN");
+        assert_eq!(
+            fields.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "variants/Base/diag_layer/description: U+001B, U+0000",
+                "variants/Base/diag_layer/diag_services/ReadVIN/diag_comm/long_name/value: U+FFFF",
+            ]
+        );
+        assert!(
+            sanitize(&mut db, InvalidCharPolicy::Strip)
+                .unwrap()
+                .is_empty()
+        );
+
+        let mut db = database();
+        sanitize(&mut db, InvalidCharPolicy::Escape).unwrap();
+        assert_eq!(
+            db.variants[0].diag_layer.description.as_deref(),
+            Some("Line 1\nLine\\x1B[0m 2\\x00")
+        );
+    }
+
+    #[test]
+    fn test_sanitize_error_leaves_the_database_alone() {
+        let mut db = database();
+        let err = sanitize(&mut db, InvalidCharPolicy::Error).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("2 field(s) hold characters XML cannot represent:\n  variants/Base"),
+            "{err}"
+        );
+        assert_eq!(db, database());
+        assert_eq!("Escape".parse(), Ok(InvalidCharPolicy::Escape));
+    }
+}
@@ -0,0 +1,289 @@
+//! Name search index: every named object of a database with its path.
+//!
+//! Looking a parameter up by name otherwise means decoding and walking the
+//! whole FlatBuffers graph. MDD output can embed the index as a chunk
+//! ([`mdd_format::writer::WriteOptions::search_index`]), so a lookup on a
+//! device only decompresses the index.
+//!
+//! Encoded as UTF-8 text, one `kind<TAB>name<TAB>path` row per line, sorted
+//! by name without regard to case. Paths are short names joined by `/`:
+//! `Layer/Service/request/Param`, `Layer/Job/input/Param`, `dtcs/DTC`. A
+//! service is listed under each of its aliases too, with its current path.
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::aliases::aliases;
+use crate::types::{DiagDatabase, DiagLayer};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SearchIndexError {
+    #[error("search index is not UTF-8")]
+    NotUtf8,
+    #[error("search index line {0} is malformed")]
+    Malformed(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SearchKind {
+    Layer,
+    Service,
+    Job,
+    Param,
+    Dtc,
+}
+
+impl SearchKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Layer => "layer",
+            Self::Service => "service",
+            Self::Job => "job",
+            Self::Param => "param",
+            Self::Dtc => "dtc",
+        }
+    }
+}
+
+impl fmt::Display for SearchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SearchKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "layer" => Ok(Self::Layer),
+            "service" => Ok(Self::Service),
+            "job" => Ok(Self::Job),
+            "param" => Ok(Self::Param),
+            "dtc" => Ok(Self::Dtc),
+            other => Err(format!(
+                "unknown kind '{other}' (expected layer, service, job, param or dtc)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SearchEntry {
+    pub kind: SearchKind,
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchIndex {
+    entries: Vec<SearchEntry>,
+}
+
+impl SearchIndex {
+    /// Index the layers, services, jobs, parameters and DTCs of `db`.
+    pub fn build(db: &DiagDatabase) -> Self {
+        let mut entries = Vec::new();
+        let mut add = |kind, name: &str, path: String| {
+            entries.push(SearchEntry {
+                kind,
+                name: clean(name),
+                path: clean(&path),
+            });
+        };
+        for layer in layers(db) {
+            let layer_name = &layer.short_name;
+            add(SearchKind::Layer, layer_name, layer_name.to_string());
+            for svc in &layer.diag_services {
+                let svc_path = format!("{layer_name}/{}", svc.diag_comm.short_name);
+                add(
+                    SearchKind::Service,
+                    &svc.diag_comm.short_name,
+                    svc_path.clone(),
+                );
+                for alias in aliases(&svc.diag_comm) {
+                    add(SearchKind::Service, alias, svc_path.clone());
+                }
+                let messages = svc
+                    .request
+                    .iter()
+                    .map(|r| ("request", &r.params))
+                    .chain(
+                        svc.pos_responses
+                            .iter()
+                            .map(|r| ("pos_response", &r.params)),
+                    )
+                    .chain(
+                        svc.neg_responses
+                            .iter()
+                            .map(|r| ("neg_response", &r.params)),
+                    );
+                for (message, params) in messages {
+                    for param in params {
+                        let path = format!("{svc_path}/{message}/{}", param.short_name);
+                        add(SearchKind::Param, &param.short_name, path);
+                    }
+                }
+            }
+            for job in &layer.single_ecu_jobs {
+                let job_path = format!("{layer_name}/{}", job.diag_comm.short_name);
+                add(SearchKind::Job, &job.diag_comm.short_name, job_path.clone());
+                let lists = [
+                    ("input", &job.input_params),
+                    ("output", &job.output_params),
+                    ("neg_output", &job.neg_output_params),
+                ];
+                for (list, params) in lists {
+                    for param in params {
+                        let path = format!("{job_path}/{list}/{}", param.short_name);
+                        add(SearchKind::Param, &param.short_name, path);
+                    }
+                }
+            }
+        }
+        for dtc in &db.dtcs {
+            add(
+                SearchKind::Dtc,
+                &dtc.short_name,
+                format!("dtcs/{}", dtc.short_name),
+            );
+        }
+        Self::from_entries(entries)
+    }
+
+    fn from_entries(mut entries: Vec<SearchEntry>) -> Self {
+        entries.sort_by_cached_key(|e| (e.name.to_lowercase(), e.kind, e.path.clone()));
+        entries.dedup();
+        Self { entries }
+    }
+
+    pub fn entries(&self) -> &[SearchEntry] {
+        &self.entries
+    }
+
+    /// Entries whose name contains `query`, ignoring case, in index order.
+    pub fn find(&self, query: &str) -> Vec<&SearchEntry> {
+        let query = query.to_lowercase();
+        self.entries
+            .iter()
+            .filter(|e| e.name.to_lowercase().contains(&query))
+            .collect()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = String::new();
+        for entry in &self.entries {
+            out.push_str(entry.kind.as_str());
+            out.push('\t');
+            out.push_str(&entry.name);
+            out.push('\t');
+            out.push_str(&entry.path);
+            out.push('\n');
+        }
+        out.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SearchIndexError> {
+        let text = std::str::from_utf8(bytes).map_err(|_| SearchIndexError::NotUtf8)?;
+        let entries = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(i, line)| {
+                let mut fields = line.split('\t');
+                let (Some(kind), Some(name), Some(path), None) =
+                    (fields.next(), fields.next(), fields.next(), fields.next())
+                else {
+                    return Err(SearchIndexError::Malformed(i + 1));
+                };
+                Ok(SearchEntry {
+                    kind: kind
+                        .parse()
+                        .map_err(|_| SearchIndexError::Malformed(i + 1))?,
+                    name: name.to_string(),
+                    path: path.to_string(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self::from_entries(entries))
+    }
+}
+
+/// Tabs and line breaks would break the row format.
+fn clean(s: &str) -> String {
+    s.replace(['\t', '\n', '\r'], " ")
+}
+
+fn layers(db: &DiagDatabase) -> impl Iterator<Item = &DiagLayer> {
+    db.variants
+        .iter()
+        .map(|v| &v.diag_layer)
+        .chain(db.functional_groups.iter().map(|fg| &fg.diag_layer))
+        .chain(db.protocols.iter().map(|p| &p.diag_layer))
+        .chain(db.ecu_shared_datas.iter().map(|e| &e.diag_layer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, service};
+    use crate::types::{DiagService, Dtc, Param, Request};
+
+    fn database() -> DiagDatabase {
+        let read = DiagService {
+            request: Some(Request {
+                params: vec![Param {
+                    short_name: "SID_RQ".into(),
+                    ..Default::default()
+                }],
+                sdgs: None,
+            }),
+            ..service("Read_VIN", "")
+        };
+        DiagDatabase {
+            dtcs: vec![Dtc {
+                short_name: "P0101\tAirflow".into(),
+                trouble_code: 0x0101,
+                display_trouble_code: "P0101".into(),
+                text: None,
+                level: None,
+                sdgs: None,
+                is_temporary: false,
+            }],
+            ..test_support::database(vec![read])
+        }
+    }
+
+    #[test]
+    fn test_index_lists_named_objects_with_paths() {
+        let index = SearchIndex::build(&database());
+        let rows: Vec<(SearchKind, &str)> = index
+            .entries()
+            .iter()
+            .map(|e| (e.kind, e.path.as_str()))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (SearchKind::Layer, "Base"),
+                (SearchKind::Dtc, "dtcs/P0101 Airflow"),
+                (SearchKind::Service, "Base/Read_VIN"),
+                (SearchKind::Param, "Base/Read_VIN/request/SID_RQ"),
+            ]
+        );
+        let found: Vec<&str> = index.find("vin").iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(found, ["Base/Read_VIN"]);
+    }
+
+    #[test]
+    fn test_index_roundtrips_through_bytes() {
+        let index = SearchIndex::build(&database());
+        assert_eq!(SearchIndex::from_bytes(&index.to_bytes()), Ok(index));
+        assert_eq!(
+            SearchIndex::from_bytes(b"param\tA\tB\nfield\tA\tB\n"),
+            Err(SearchIndexError::Malformed(2))
+        );
+    }
+}
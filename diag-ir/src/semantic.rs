@@ -0,0 +1,186 @@
+//! Normalized service semantics.
+//!
+//! ODX leaves `SEMANTIC` free-form, and tools spell the same class
+//! `DATA-READ`, `DATAREAD` or `READ-DATA`. [`Semantic`] maps the spellings
+//! to one value, and falls back to the request's service ID for services
+//! without a recognizable semantic (services generated from YAML have none).
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::types::DiagService;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Semantic {
+    Session,
+    EcuReset,
+    SecurityAccess,
+    Authentication,
+    CommunicationControl,
+    TesterPresent,
+    ControlDtcSetting,
+    DataRead,
+    DataWrite,
+    IoControl,
+    Routine,
+    ClearDtc,
+    ReadDtcInfo,
+    Download,
+    Upload,
+}
+
+/// Every semantic with its canonical name, the other spellings accepted
+/// (compared without separators, case-insensitively) and its service IDs.
+const SEMANTICS: &[(Semantic, &str, &[&str], &[u8])] = &[
+    (
+        Semantic::Session,
+        "SESSION",
+        &["DIAGNOSTICSESSIONCONTROL", "SESSIONCONTROL"],
+        &[0x10],
+    ),
+    (Semantic::EcuReset, "ECU-RESET", &["RESET"], &[0x11]),
+    (
+        Semantic::SecurityAccess,
+        "SECURITY-ACCESS",
+        &["SECURITY"],
+        &[0x27],
+    ),
+    (Semantic::Authentication, "AUTHENTICATION", &[], &[0x29]),
+    (
+        Semantic::CommunicationControl,
+        "COMMUNICATION-CONTROL",
+        &["COMMCTRL", "COMMUNICATION"],
+        &[0x28],
+    ),
+    (Semantic::TesterPresent, "TESTER-PRESENT", &[], &[0x3E]),
+    (
+        Semantic::ControlDtcSetting,
+        "CONTROL-DTC-SETTING",
+        &["DTCSETTING"],
+        &[0x85],
+    ),
+    (
+        Semantic::DataRead,
+        "DATA-READ",
+        &["READDATA", "READ", "READDATABYIDENTIFIER"],
+        &[0x22],
+    ),
+    (
+        Semantic::DataWrite,
+        "DATA-WRITE",
+        &["WRITEDATA", "WRITE", "WRITEDATABYIDENTIFIER"],
+        &[0x2E],
+    ),
+    (
+        Semantic::IoControl,
+        "IO-CONTROL",
+        &["INPUTOUTPUTCONTROL", "IOCONTROLBYIDENTIFIER"],
+        &[0x2F],
+    ),
+    (Semantic::Routine, "ROUTINE", &["ROUTINECONTROL"], &[0x31]),
+    (
+        Semantic::ClearDtc,
+        "CLEAR-DTC",
+        &["CLEARDIAGNOSTICINFORMATION", "FAULTMEMCLEAR"],
+        &[0x14],
+    ),
+    (
+        Semantic::ReadDtcInfo,
+        "READ-DTC-INFO",
+        &["READDTCINFORMATION", "READDTC", "FAULTREAD", "FAULTMEMREAD"],
+        &[0x19],
+    ),
+    (
+        Semantic::Download,
+        "DOWNLOAD",
+        &["REQUESTDOWNLOAD", "TRANSFERDATA", "FLASH"],
+        &[0x34, 0x36, 0x37],
+    ),
+    (Semantic::Upload, "UPLOAD", &["REQUESTUPLOAD"], &[0x35]),
+];
+
+impl Semantic {
+    /// Canonical ODX spelling, e.g. `DATA-READ`.
+    pub fn as_str(self) -> &'static str {
+        SEMANTICS
+            .iter()
+            .find(|(s, ..)| *s == self)
+            .map_or("", |(_, name, ..)| name)
+    }
+
+    /// Classify a free-form semantic string.
+    pub fn parse(s: &str) -> Option<Self> {
+        let key = normalized(s);
+        if key.is_empty() {
+            return None;
+        }
+        SEMANTICS
+            .iter()
+            .find(|(_, name, aliases, _)| {
+                normalized(name) == key || aliases.iter().any(|a| *a == key)
+            })
+            .map(|(s, ..)| *s)
+    }
+
+    /// Classify a UDS service ID.
+    pub fn from_sid(sid: u8) -> Option<Self> {
+        SEMANTICS
+            .iter()
+            .find(|(.., sids)| sids.contains(&sid))
+            .map(|(s, ..)| *s)
+    }
+
+    /// Semantic of a service: its `semantic` string if recognized, else its
+    /// request's service ID.
+    pub fn of(svc: &DiagService) -> Option<Self> {
+        Self::parse(&svc.diag_comm.semantic)
+            .or_else(|| crate::flash::service_sid(svc).and_then(Self::from_sid))
+    }
+}
+
+impl fmt::Display for Semantic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Semantic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s).ok_or_else(|| {
+            let known: Vec<_> = SEMANTICS.iter().map(|(_, name, ..)| *name).collect();
+            format!("unknown semantic '{s}' (expected {})", known.join(", "))
+        })
+    }
+}
+
+fn normalized(s: &str) -> String {
+    s.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_semantic_spellings() {
+        for s in ["DATA-READ", "DATAREAD", "read-data", "Read_Data"] {
+            assert_eq!(Semantic::parse(s), Some(Semantic::DataRead), "{s}");
+        }
+        assert_eq!(Semantic::parse("ROUTINE"), Some(Semantic::Routine));
+        assert_eq!(Semantic::parse("DATA"), None);
+        assert_eq!(Semantic::parse(""), None);
+        assert_eq!(Semantic::from_sid(0x36), Some(Semantic::Download));
+        assert_eq!(Semantic::DataRead.to_string(), "DATA-READ");
+        assert!(
+            "bogus"
+                .parse::<Semantic>()
+                .unwrap_err()
+                .starts_with("unknown semantic 'bogus'")
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! ODX short-name rules: only `A-Z`, `a-z`, `0-9` and `_`, at most 128
+//! characters, and unique within their scope.
+//!
+//! [`check_short_names`] reports violations. [`fix_short_names`] repairs the
+//! names of services, jobs and DTCs, the ones YAML generates from free-form
+//! names: invalid characters become `_`, long names are cut, and repeated
+//! names get a `_2`, `_3`, ... suffix in database order, so the same input
+//! always gets the same names.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::types::{DiagDatabase, DiagLayer, Name};
+
+/// Longest short name ODX allows.
+pub const MAX_SHORT_NAME_LEN: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShortNameRule {
+    Charset,
+    Length,
+    Unique,
+}
+
+/// A short name breaking a rule. `scope` is the layer short name, or
+/// `dtcs` for DTCs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShortNameIssue {
+    pub rule: ShortNameRule,
+    pub scope: String,
+    pub name: String,
+}
+
+impl fmt::Display for ShortNameIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let problem = match self.rule {
+            ShortNameRule::Charset => "has characters other than A-Z, a-z, 0-9 and _",
+            ShortNameRule::Length => "is longer than 128 characters",
+            ShortNameRule::Unique => "is not unique",
+        };
+        write!(
+            f,
+            "short name '{}' in '{}' {problem}",
+            self.name, self.scope
+        )
+    }
+}
+
+/// A short name changed by [`fix_short_names`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShortNameFix {
+    pub scope: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl fmt::Display for ShortNameFix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "renamed '{}' to '{}' in '{}'",
+            self.old, self.new, self.scope
+        )
+    }
+}
+
+/// Rules `name` breaks on its own (everything but uniqueness). Empty names
+/// are left to the checks of the objects that require one.
+pub fn short_name_rules(name: &str) -> Vec<ShortNameRule> {
+    let mut broken = Vec::new();
+    if !name.chars().all(is_valid_char) {
+        broken.push(ShortNameRule::Charset);
+    }
+    if name.chars().count() > MAX_SHORT_NAME_LEN {
+        broken.push(ShortNameRule::Length);
+    }
+    broken
+}
+
+/// Every short-name violation of layers, services, jobs, state charts and
+/// DTCs. Services and jobs share one scope per layer; layers are checked in
+/// the scope of the ECU name.
+pub fn check_short_names(db: &DiagDatabase) -> Vec<ShortNameIssue> {
+    let mut issues = Vec::new();
+    let mut check = |scope: &str, names: &mut dyn Iterator<Item = &str>| {
+        let mut seen = HashSet::new();
+        for name in names {
+            let issue = |rule| ShortNameIssue {
+                rule,
+                scope: scope.to_string(),
+                name: name.to_string(),
+            };
+            issues.extend(short_name_rules(name).into_iter().map(issue));
+            if !name.is_empty() && !seen.insert(name) {
+                issues.push(issue(ShortNameRule::Unique));
+            }
+        }
+    };
+    for layer in layers(db) {
+        check(&layer.short_name, &mut diag_comm_names(layer));
+        check(
+            &layer.short_name,
+            &mut layer.state_charts.iter().map(|sc| sc.short_name.as_str()),
+        );
+    }
+    check(
+        &db.ecu_name,
+        &mut layers(db).map(|layer| layer.short_name.as_str()),
+    );
+    check(
+        "dtcs",
+        &mut db.dtcs.iter().map(|dtc| dtc.short_name.as_str()),
+    );
+    issues
+}
+
+/// Make the short names of services, jobs and DTCs valid and unique.
+pub fn fix_short_names(db: &mut DiagDatabase) -> Vec<ShortNameFix> {
+    let mut fixes = Vec::new();
+    let layers = db
+        .variants
+        .iter_mut()
+        .map(|v| &mut v.diag_layer)
+        .chain(db.functional_groups.iter_mut().map(|fg| &mut fg.diag_layer))
+        .chain(db.protocols.iter_mut().map(|p| &mut p.diag_layer))
+        .chain(db.ecu_shared_datas.iter_mut().map(|e| &mut e.diag_layer));
+    for layer in layers {
+        let scope = layer.short_name.clone();
+        let names = layer
+            .diag_services
+            .iter_mut()
+            .map(|s| &mut s.diag_comm.short_name)
+            .chain(
+                layer
+                    .single_ecu_jobs
+                    .iter_mut()
+                    .map(|j| &mut j.diag_comm.short_name),
+            );
+        fix_scope(&scope, names.collect(), &mut fixes);
+    }
+    let names = db.dtcs.iter_mut().map(|dtc| &mut dtc.short_name).collect();
+    fix_scope("dtcs", names, &mut fixes);
+    fixes
+}
+
+/// Fix the names of one scope in place. Names that are already valid and
+/// seen first keep their spelling; the others get the first free suffix.
+fn fix_scope(scope: &str, mut names: Vec<&mut Name>, fixes: &mut Vec<ShortNameFix>) {
+    let count = names.len();
+    let valid: Vec<String> = names.iter().map(|n| sanitize(n)).collect();
+    // Names that stay as they are, so suffixes never collide with them.
+    let mut taken: HashSet<String> = HashSet::new();
+    let mut keeps = vec![false; names.len()];
+    for (i, name) in names.iter().enumerate() {
+        if *name.as_str() == valid[i] && taken.insert(valid[i].clone()) {
+            keeps[i] = true;
+        }
+    }
+    for (i, name) in names.iter_mut().enumerate() {
+        if keeps[i] {
+            continue;
+        }
+        let new = if taken.contains(&valid[i]) {
+            // `taken` holds at most one name per entry, so one of these is free.
+            (2..=count + 1)
+                .map(|n| with_suffix(&valid[i], n))
+                .find(|candidate| !taken.contains(candidate))
+                .unwrap_or_default()
+        } else {
+            valid[i].clone()
+        };
+        taken.insert(new.clone());
+        fixes.push(ShortNameFix {
+            scope: scope.to_string(),
+            old: std::mem::replace(*name, Name::from(&new)).into(),
+            new,
+        });
+    }
+}
+
+/// Replace invalid characters by `_` and cut to [`MAX_SHORT_NAME_LEN`].
+fn sanitize(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if is_valid_char(c) { c } else { '_' })
+        .take(MAX_SHORT_NAME_LEN)
+        .collect();
+    if name.is_empty() { "_".into() } else { name }
+}
+
+/// `name_<n>`, cutting `name` so the result fits [`MAX_SHORT_NAME_LEN`].
+fn with_suffix(name: &str, n: usize) -> String {
+    let suffix = format!("_{n}");
+    let keep = MAX_SHORT_NAME_LEN - suffix.len();
+    format!("{}{suffix}", &name[..name.len().min(keep)])
+}
+
+fn is_valid_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn layers(db: &DiagDatabase) -> impl Iterator<Item = &DiagLayer> {
+    db.variants
+        .iter()
+        .map(|v| &v.diag_layer)
+        .chain(db.functional_groups.iter().map(|fg| &fg.diag_layer))
+        .chain(db.protocols.iter().map(|p| &p.diag_layer))
+        .chain(db.ecu_shared_datas.iter().map(|e| &e.diag_layer))
+}
+
+fn diag_comm_names(layer: &DiagLayer) -> impl Iterator<Item = &str> {
+    layer
+        .diag_services
+        .iter()
+        .map(|s| s.diag_comm.short_name.as_str())
+        .chain(
+            layer
+                .single_ecu_jobs
+                .iter()
+                .map(|j| j.diag_comm.short_name.as_str()),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DiagComm, DiagService, Variant};
+
+    fn layer(services: &[&str]) -> DiagDatabase {
+        DiagDatabase {
+            ecu_name: "ECU".into(),
+            variants: vec![Variant {
+                diag_layer: DiagLayer {
+                    short_name: "Base".into(),
+                    diag_services: services
+                        .iter()
+                        .map(|name| DiagService {
+                            diag_comm: DiagComm {
+                                short_name: (*name).into(),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        })
+                        .collect(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn names(db: &DiagDatabase) -> Vec<&str> {
+        diag_comm_names(&db.variants[0].diag_layer).collect()
+    }
+
+    #[test]
+    fn test_check_short_names() {
+        let long = "X".repeat(129);
+        let db = layer(&["VIN_Read", "VIN_Read", "Speed.Read", &long]);
+        let issues: Vec<_> = check_short_names(&db).iter().map(|i| i.rule).collect();
+        assert_eq!(
+            issues,
+            [
+                ShortNameRule::Unique,
+                ShortNameRule::Charset,
+                ShortNameRule::Length
+            ]
+        );
+        assert_eq!(
+            check_short_names(&db)[1].to_string(),
+            "short name 'Speed.Read' in 'Base' has characters other than A-Z, a-z, 0-9 and _"
+        );
+    }
+
+    #[test]
+    fn test_fix_short_names_is_deterministic() {
+        let long = "X".repeat(130);
+        let mut db = layer(&[
+            "VIN_Read",
+            "VIN_Read",
+            "VIN Read",
+            "VIN_Read_2",
+            &long,
+            &long,
+        ]);
+        let fixes = fix_short_names(&mut db);
+        assert_eq!(
+            names(&db),
+            [
+                "VIN_Read",
+                "VIN_Read_3",
+                "VIN_Read_4",
+                "VIN_Read_2",
+                &"X".repeat(128),
+                &format!("{}_2", "X".repeat(126)),
+            ]
+        );
+        assert_eq!(fixes.len(), 4);
+        assert_eq!(
+            fixes[1].to_string(),
+            "renamed 'VIN Read' to 'VIN_Read_4' in 'Base'"
+        );
+        assert!(check_short_names(&db).is_empty());
+        assert!(fix_short_names(&mut db).is_empty());
+    }
+}
@@ -0,0 +1,505 @@
+//! Size accounting: how many bytes of the FlatBuffers encoding each part of
+//! a database accounts for, and [`DatabaseStats`], object counts for
+//! inspecting a database in depth.
+//!
+//! Sizes are measured by serializing sub-trees individually and taking the
+//! difference to the same database without them, so shared strings and
+//! tables are charged to the database rather than to one variant. The
+//! numbers are uncompressed; MDD compression shrinks all parts alike only
+//! roughly.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::flash::service_sid;
+use crate::to_fbs::ir_to_flatbuffers;
+use crate::types::{
+    DiagDatabase, DiagLayer, DiagService, DopData, MemoryRegion, Param, ParamData, Sdgs, Variant,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ContributorKind {
+    Variant,
+    Service,
+    /// A top-level list other than variants: DTCs, functional groups, ...
+    Section,
+}
+
+impl ContributorKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Variant => "variant",
+            Self::Service => "service",
+            Self::Section => "section",
+        }
+    }
+}
+
+/// Serialized size of one part of a database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeContribution {
+    pub kind: ContributorKind,
+    /// Variant short name, `variant/service` for services, or the section
+    /// name (`dtcs`, `functional_groups`, ...).
+    pub name: String,
+    pub bytes: usize,
+}
+
+impl fmt::Display for SizeContribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {} bytes",
+            self.kind.as_str(),
+            self.name,
+            self.bytes
+        )
+    }
+}
+
+/// Sizes of the variants, services and sections of a database.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeBreakdown {
+    /// Size of the whole encoding.
+    pub total: usize,
+    /// One entry per variant, in database order.
+    pub variants: Vec<SizeContribution>,
+    /// One entry per diag service of every variant, largest first.
+    pub services: Vec<SizeContribution>,
+    /// DTCs, functional groups, protocols, ECU shared data, memory
+    /// configuration and type definitions, largest first; empty sections are
+    /// left out.
+    pub sections: Vec<SizeContribution>,
+}
+
+impl SizeBreakdown {
+    /// Measure `db`. Serializes the database once per variant, service and
+    /// section.
+    pub fn of(db: &DiagDatabase) -> Self {
+        let total = ir_to_flatbuffers(db).len();
+
+        let without_variants = DiagDatabase {
+            variants: vec![],
+            ..db.clone()
+        };
+        let base = ir_to_flatbuffers(&without_variants).len();
+        let variants = db
+            .variants
+            .iter()
+            .map(|v| {
+                let only = DiagDatabase {
+                    variants: vec![v.clone()],
+                    ..without_variants.clone()
+                };
+                contribution(
+                    ContributorKind::Variant,
+                    &v.diag_layer.short_name,
+                    ir_to_flatbuffers(&only).len(),
+                    base,
+                )
+            })
+            .collect();
+
+        let mut services: Vec<SizeContribution> =
+            db.variants.iter().flat_map(service_sizes).collect();
+        services.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+
+        let mut sections: Vec<SizeContribution> = [
+            ("dtcs", !db.dtcs.is_empty()),
+            ("functional_groups", !db.functional_groups.is_empty()),
+            ("protocols", !db.protocols.is_empty()),
+            ("ecu_shared_datas", !db.ecu_shared_datas.is_empty()),
+            ("memory", db.memory.is_some()),
+            ("type_definitions", !db.type_definitions.is_empty()),
+        ]
+        .into_iter()
+        .filter(|(_, present)| *present)
+        .map(|(name, _)| {
+            let mut without = db.clone();
+            match name {
+                "dtcs" => without.dtcs.clear(),
+                "functional_groups" => without.functional_groups.clear(),
+                "protocols" => without.protocols.clear(),
+                "ecu_shared_datas" => without.ecu_shared_datas.clear(),
+                "memory" => without.memory = None,
+                _ => without.type_definitions.clear(),
+            }
+            contribution(
+                ContributorKind::Section,
+                name,
+                total,
+                ir_to_flatbuffers(&without).len(),
+            )
+        })
+        .collect();
+        sections.sort_by_key(|s| std::cmp::Reverse(s.bytes));
+
+        Self {
+            total,
+            variants,
+            services,
+            sections,
+        }
+    }
+
+    /// The `n` largest variants, services and sections together, largest
+    /// first.
+    pub fn top(&self, n: usize) -> Vec<&SizeContribution> {
+        let mut all: Vec<&SizeContribution> = self
+            .variants
+            .iter()
+            .chain(&self.services)
+            .chain(&self.sections)
+            .collect();
+        all.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.kind.cmp(&b.kind)));
+        all.truncate(n);
+        all
+    }
+
+    /// Size of the variant named `name`.
+    pub fn variant(&self, name: &str) -> Option<usize> {
+        self.variants
+            .iter()
+            .find(|v| v.name == name)
+            .map(|v| v.bytes)
+    }
+}
+
+/// Object counts and serialized size of one variant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariantStats {
+    pub name: String,
+    pub services: usize,
+    pub single_ecu_jobs: usize,
+    /// Request and response params of all services.
+    pub params: usize,
+    /// Serialized size, as in [`SizeBreakdown::variants`].
+    pub bytes: usize,
+}
+
+/// Counts of every variant of `db` next to its size in `sizes`, which must
+/// have been measured from `db`. In database order.
+pub fn variant_stats(db: &DiagDatabase, sizes: &SizeBreakdown) -> Vec<VariantStats> {
+    db.variants
+        .iter()
+        .zip(&sizes.variants)
+        .map(|(variant, size)| {
+            let layer = &variant.diag_layer;
+            VariantStats {
+                name: layer.short_name.to_string(),
+                services: layer.diag_services.len(),
+                single_ecu_jobs: layer.single_ecu_jobs.len(),
+                params: layer.diag_services.iter().map(param_count).sum(),
+                bytes: size.bytes,
+            }
+        })
+        .collect()
+}
+
+/// Number of request and response params of a service.
+pub fn param_count(svc: &DiagService) -> usize {
+    svc.request.as_ref().map_or(0, |r| r.params.len())
+        + svc
+            .pos_responses
+            .iter()
+            .chain(&svc.neg_responses)
+            .map(|r| r.params.len())
+            .sum::<usize>()
+}
+
+/// Object counts of a database beyond the summary `info` prints: services
+/// per variant, DOP kinds, SDGs and the memory map.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DatabaseStats {
+    /// One entry per variant, in database order.
+    pub variants: Vec<VariantDetail>,
+    /// Number of params referencing a DOP of each kind (see [`dop_kind`]),
+    /// over the services of all variants.
+    pub dop_kinds: BTreeMap<&'static str, usize>,
+    /// SDGs attached to layers, services, requests, responses and params.
+    pub sdgs: usize,
+    /// Memory regions ordered by start address.
+    pub memory_regions: Vec<MemoryRegion>,
+    pub dtcs: usize,
+}
+
+/// Services and counts of one variant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariantDetail {
+    pub name: String,
+    pub is_base_variant: bool,
+    /// In layer order.
+    pub services: Vec<ServiceStats>,
+    pub single_ecu_jobs: usize,
+    /// SDGs of the layer and its services.
+    pub sdgs: usize,
+}
+
+/// One diag service of a variant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceStats {
+    pub name: String,
+    pub semantic: String,
+    /// Service ID from the request's coded constant at byte 0.
+    pub sid: Option<u8>,
+    pub request_params: usize,
+    /// Params of all positive and negative responses.
+    pub response_params: usize,
+}
+
+impl DatabaseStats {
+    pub fn of(db: &DiagDatabase) -> Self {
+        let mut stats = Self {
+            dtcs: db.dtcs.len(),
+            ..Self::default()
+        };
+        for variant in &db.variants {
+            let layer = &variant.diag_layer;
+            let detail = VariantDetail {
+                name: layer.short_name.to_string(),
+                is_base_variant: variant.is_base_variant,
+                services: layer.diag_services.iter().map(service_stats).collect(),
+                single_ecu_jobs: layer.single_ecu_jobs.len(),
+                sdgs: layer_sdgs(layer),
+            };
+            stats.sdgs += detail.sdgs;
+            for param in layer.diag_services.iter().flat_map(service_params) {
+                if let Some(kind) = param_dop_kind(param) {
+                    *stats.dop_kinds.entry(kind).or_default() += 1;
+                }
+            }
+            stats.variants.push(detail);
+        }
+        if let Some(memory) = &db.memory {
+            stats.memory_regions.clone_from(&memory.regions);
+            stats.memory_regions.sort_by_key(|r| r.start_address);
+        }
+        stats
+    }
+
+    /// Total services over all variants.
+    pub fn services(&self) -> usize {
+        self.variants.iter().map(|v| v.services.len()).sum()
+    }
+}
+
+/// Short name of the kind of a DOP, e.g. `normal` or `multiplexer`.
+pub fn dop_kind(data: &DopData) -> &'static str {
+    match data {
+        DopData::NormalDop { .. } => "normal",
+        DopData::EndOfPduField { .. } => "end-of-PDU field",
+        DopData::StaticField { .. } => "static field",
+        DopData::DynamicLengthField { .. } => "dynamic-length field",
+        DopData::EnvDataDesc { .. } => "env data desc",
+        DopData::EnvData { .. } => "env data",
+        DopData::DtcDop { .. } => "DTC",
+        DopData::Structure { .. } => "structure",
+        DopData::MuxDop { .. } => "multiplexer",
+    }
+}
+
+fn service_stats(svc: &DiagService) -> ServiceStats {
+    let request_params = svc.request.as_ref().map_or(0, |r| r.params.len());
+    ServiceStats {
+        name: svc.diag_comm.short_name.to_string(),
+        semantic: svc.diag_comm.semantic.to_string(),
+        sid: service_sid(svc),
+        request_params,
+        response_params: param_count(svc) - request_params,
+    }
+}
+
+fn service_params(svc: &DiagService) -> impl Iterator<Item = &Param> {
+    svc.request.iter().flat_map(|r| &r.params).chain(
+        svc.pos_responses
+            .iter()
+            .chain(&svc.neg_responses)
+            .flat_map(|r| &r.params),
+    )
+}
+
+fn param_dop_kind(param: &Param) -> Option<&'static str> {
+    match &param.specific_data {
+        Some(
+            ParamData::Value { dop, .. }
+            | ParamData::PhysConst { dop, .. }
+            | ParamData::System { dop, .. }
+            | ParamData::LengthKeyRef { dop },
+        ) => dop.specific_data.as_ref().map(dop_kind),
+        _ => None,
+    }
+}
+
+fn layer_sdgs(layer: &DiagLayer) -> usize {
+    let count = |sdgs: Option<&Sdgs>| sdgs.map_or(0, |s| s.sdgs.len());
+    count(layer.sdgs.as_ref())
+        + layer
+            .diag_services
+            .iter()
+            .map(|svc| {
+                count(svc.diag_comm.sdgs.as_ref())
+                    + svc.request.as_ref().map_or(0, |r| count(r.sdgs.as_ref()))
+                    + svc
+                        .pos_responses
+                        .iter()
+                        .chain(&svc.neg_responses)
+                        .map(|r| count(r.sdgs.as_ref()))
+                        .sum::<usize>()
+                    + service_params(svc)
+                        .map(|p| count(p.sdgs.as_ref()))
+                        .sum::<usize>()
+            })
+            .sum::<usize>()
+}
+
+/// Services of `variant`, each measured against the variant without
+/// services in an otherwise empty database.
+fn service_sizes(variant: &Variant) -> Vec<SizeContribution> {
+    let mut shell = variant.clone();
+    shell.diag_layer.diag_services.clear();
+    let mut db = DiagDatabase {
+        variants: vec![shell],
+        ..Default::default()
+    };
+    let empty = ir_to_flatbuffers(&db).len();
+    variant
+        .diag_layer
+        .diag_services
+        .iter()
+        .map(|svc| {
+            db.variants[0].diag_layer.diag_services = vec![svc.clone()];
+            contribution(
+                ContributorKind::Service,
+                &format!(
+                    "{}/{}",
+                    variant.diag_layer.short_name, svc.diag_comm.short_name
+                ),
+                ir_to_flatbuffers(&db).len(),
+                empty,
+            )
+        })
+        .collect()
+}
+
+fn contribution(
+    kind: ContributorKind,
+    name: &str,
+    with: usize,
+    without: usize,
+) -> SizeContribution {
+    SizeContribution {
+        kind,
+        name: name.to_string(),
+        bytes: with.saturating_sub(without),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{base_variant, database, service, variant};
+    use crate::types::Request;
+
+    #[test]
+    fn test_breakdown_ranks_the_largest_service_first() {
+        let db = DiagDatabase {
+            ecu_name: "ECU".into(),
+            ..database(vec![
+                service("Small", "x"),
+                service("Large", &"y".repeat(4096)),
+            ])
+        };
+        let sizes = SizeBreakdown::of(&db);
+        assert_eq!(sizes.services[0].name, "Base/Large");
+        assert!(sizes.services[0].bytes > 4096);
+        assert!(sizes.services[1].bytes < 256);
+        let base = sizes.variant("Base").unwrap();
+        assert!(base > sizes.services[0].bytes && base <= sizes.total);
+        assert_eq!(sizes.top(1)[0].kind, ContributorKind::Variant);
+    }
+
+    #[test]
+    fn test_variant_stats_count_params() {
+        let mut read = service("Read", "");
+        read.request = Some(Request {
+            params: vec![Param::default(), Param::default()],
+            sdgs: None,
+        });
+        let db = DiagDatabase {
+            variants: vec![variant("Base", vec![read, service("Reset", "")])],
+            ..Default::default()
+        };
+        let sizes = SizeBreakdown::of(&db);
+        let stats = variant_stats(&db, &sizes);
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].services, stats[0].params), (2, 2));
+        assert_eq!(stats[0].bytes, sizes.variants[0].bytes);
+    }
+
+    #[test]
+    fn test_database_stats_counts_dop_kinds_and_sdgs() {
+        use crate::types::{AddressFormat, Dop, DopRef, DopType, MemoryAccess, MemoryConfig, Sdg};
+
+        let sdgs = Some(Sdgs {
+            sdgs: vec![Sdg {
+                caption_sn: "Info".into(),
+                sds: vec![],
+                si: String::new(),
+            }],
+        });
+        let structure = Param {
+            specific_data: Some(ParamData::Value {
+                physical_default_value: String::new(),
+                dop: DopRef::new(Dop {
+                    dop_type: DopType::Regular,
+                    short_name: "Record".into(),
+                    sdgs: None,
+                    specific_data: Some(DopData::Structure {
+                        params: vec![],
+                        byte_size: None,
+                        is_visible: true,
+                    }),
+                }),
+            }),
+            sdgs: sdgs.clone(),
+            ..Default::default()
+        };
+        let mut read = service("Read", "DATA");
+        read.request = Some(Request {
+            params: vec![structure],
+            sdgs: None,
+        });
+        let region = |name: &str, start_address| MemoryRegion {
+            name: name.into(),
+            description: None,
+            start_address,
+            size: 0x100,
+            access: MemoryAccess::Read,
+            address_format: None,
+            security_level: None,
+            session: None,
+        };
+        let mut base = base_variant("Base", vec![read, service("Reset", "")]);
+        base.diag_layer.sdgs = sdgs;
+        let db = DiagDatabase {
+            variants: vec![base],
+            memory: Some(MemoryConfig {
+                default_address_format: AddressFormat::default(),
+                regions: vec![region("High", 0x8000), region("Low", 0x1000)],
+                data_blocks: vec![],
+            }),
+            ..Default::default()
+        };
+        let stats = DatabaseStats::of(&db);
+        assert_eq!(stats.services(), 2);
+        assert_eq!(stats.variants[0].services[0].request_params, 1);
+        assert_eq!(stats.dop_kinds.get("structure"), Some(&1));
+        assert_eq!(stats.sdgs, 2);
+        let regions: Vec<&str> = stats
+            .memory_regions
+            .iter()
+            .map(|r| r.name.as_str())
+            .collect();
+        assert_eq!(regions, ["Low", "High"]);
+    }
+}
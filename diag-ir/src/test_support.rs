@@ -0,0 +1,59 @@
+//! Builders for the databases the unit tests of this crate work on. Tests
+//! fill in what they check with struct update syntax on top of these.
+
+use crate::types::{DiagComm, DiagDatabase, DiagLayer, DiagService, Dop, DopType, Variant};
+
+/// A service with nothing but a short name and semantic.
+pub(crate) fn service(name: &str, semantic: &str) -> DiagService {
+    DiagService {
+        diag_comm: DiagComm {
+            short_name: name.into(),
+            semantic: semantic.into(),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Services with nothing but the short names `names`.
+pub(crate) fn services(names: &[&str]) -> Vec<DiagService> {
+    names.iter().map(|name| service(name, "")).collect()
+}
+
+/// A regular DOP with nothing but a short name.
+pub(crate) fn dop(name: &str) -> Dop {
+    Dop {
+        dop_type: DopType::Regular,
+        short_name: name.into(),
+        sdgs: None,
+        specific_data: None,
+    }
+}
+
+/// A variant whose layer holds `services`.
+pub(crate) fn variant(name: &str, services: Vec<DiagService>) -> Variant {
+    Variant {
+        diag_layer: DiagLayer {
+            short_name: name.into(),
+            diag_services: services,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Like [`variant`], for a base variant.
+pub(crate) fn base_variant(name: &str, services: Vec<DiagService>) -> Variant {
+    Variant {
+        is_base_variant: true,
+        ..variant(name, services)
+    }
+}
+
+/// A database whose only layer is the base variant `Base` holding `services`.
+pub(crate) fn database(services: Vec<DiagService>) -> DiagDatabase {
+    DiagDatabase {
+        variants: vec![base_variant("Base", services)],
+        ..Default::default()
+    }
+}
@@ -1,10 +1,68 @@
+use crate::features::{Feature, FeatureFlags};
+use crate::loss::FormatCapabilities;
 use crate::types::*;
 use flatbuffers::FlatBufferBuilder;
 use mdd_format::dataformat;
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+
+/// What the FlatBuffers schema (and so MDD) can carry. Rich-text
+/// descriptions have no place in it.
+pub const FBS_CAPABILITIES: FormatCapabilities = FormatCapabilities {
+    format: "MDD",
+    functional_groups: true,
+    memory: true,
+    type_definitions: true,
+    descriptions: false,
+    tables: true,
+    complex_dops: true,
+    additional_audiences: true,
+    custom_state_charts: true,
+};
+
+/// Options for [`ir_to_flatbuffers_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct FbsWriteOptions {
+    /// Write structurally identical DOPs once and share the table between
+    /// all parameters that use them. Sets `Feature::DedupedDops`.
+    pub dedup_dops: bool,
+}
+
+/// DOPs already written, keyed by short name.
+type WrittenDops<'a> = HashMap<String, Vec<(Dop, flatbuffers::WIPOffset<dataformat::DOP<'a>>)>>;
+
+/// FlatBufferBuilder plus the writer state shared across all `build_*` calls.
+struct Builder<'a> {
+    fbb: FlatBufferBuilder<'a>,
+    /// `None` disables DOP sharing.
+    dops: Option<WrittenDops<'a>>,
+}
+
+impl<'a> Deref for Builder<'a> {
+    type Target = FlatBufferBuilder<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.fbb
+    }
+}
+
+impl DerefMut for Builder<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.fbb
+    }
+}
 
 /// Convert IR DiagDatabase to FlatBuffers EcuData bytes.
 pub fn ir_to_flatbuffers(db: &DiagDatabase) -> Vec<u8> {
-    let mut builder = FlatBufferBuilder::with_capacity(1024 * 256);
+    ir_to_flatbuffers_with_options(db, &FbsWriteOptions::default())
+}
+
+/// Convert IR DiagDatabase to FlatBuffers EcuData bytes with explicit options.
+pub fn ir_to_flatbuffers_with_options(db: &DiagDatabase, options: &FbsWriteOptions) -> Vec<u8> {
+    let mut builder = Builder {
+        fbb: FlatBufferBuilder::with_capacity(1024 * 256),
+        dops: options.dedup_dops.then(HashMap::new),
+    };
 
     let version = builder.create_string(&db.version);
     let ecu_name = builder.create_string(&db.ecu_name);
@@ -48,6 +106,34 @@ pub fn ir_to_flatbuffers(db: &DiagDatabase) -> Vec<u8> {
         .collect();
     let dtcs = builder.create_vector(&dtcs);
 
+    let memory = db
+        .memory
+        .as_ref()
+        .map(|m| build_memory_config(&mut builder, m));
+
+    let type_definitions: Vec<_> = db
+        .type_definitions
+        .iter()
+        .map(|td| build_type_definition(&mut builder, td))
+        .collect();
+    let type_definitions = builder.create_vector(&type_definitions);
+
+    let transport = db
+        .transport
+        .as_ref()
+        .map(|t| build_transport_config(&mut builder, t));
+
+    let identification = db
+        .identification
+        .as_ref()
+        .map(|i| build_ecu_identification(&mut builder, i));
+
+    let mut features = FeatureFlags::detect(db);
+    if options.dedup_dops {
+        features.insert(Feature::DedupedDops);
+    }
+    let feature_flags = builder.create_vector(&features.to_fbs());
+
     let ecu_data = dataformat::EcuData::create(
         &mut builder,
         &dataformat::EcuDataArgs {
@@ -55,19 +141,23 @@ pub fn ir_to_flatbuffers(db: &DiagDatabase) -> Vec<u8> {
             ecu_name: Some(ecu_name),
             revision: Some(revision),
             metadata: Some(metadata),
-            feature_flags: None,
+            feature_flags: Some(feature_flags),
             variants: Some(variants),
             functional_groups: Some(functional_groups),
             dtcs: Some(dtcs),
+            memory,
+            type_definitions: Some(type_definitions),
+            transport,
+            identification,
         },
     );
 
-    dataformat::finish_ecu_data_buffer(&mut builder, ecu_data);
+    dataformat::finish_ecu_data_buffer(&mut builder.fbb, ecu_data);
     builder.finished_data().to_vec()
 }
 
 fn build_variant<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     v: &Variant,
 ) -> flatbuffers::WIPOffset<dataformat::Variant<'a>> {
     let diag_layer = build_diag_layer(builder, &v.diag_layer);
@@ -96,7 +186,7 @@ fn build_variant<'a>(
 }
 
 fn build_functional_group<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     fg: &FunctionalGroup,
 ) -> flatbuffers::WIPOffset<dataformat::FunctionalGroup<'a>> {
     let diag_layer = build_diag_layer(builder, &fg.diag_layer);
@@ -117,7 +207,7 @@ fn build_functional_group<'a>(
 }
 
 fn build_diag_layer<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     dl: &DiagLayer,
 ) -> flatbuffers::WIPOffset<dataformat::DiagLayer<'a>> {
     let short_name = builder.create_string(&dl.short_name);
@@ -185,7 +275,7 @@ fn build_diag_layer<'a>(
 }
 
 fn build_diag_service<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     ds: &DiagService,
 ) -> flatbuffers::WIPOffset<dataformat::DiagService<'a>> {
     let diag_comm = build_diag_comm(builder, &ds.diag_comm);
@@ -226,7 +316,7 @@ fn build_diag_service<'a>(
 }
 
 fn build_diag_comm<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     dc: &DiagComm,
 ) -> flatbuffers::WIPOffset<dataformat::DiagComm<'a>> {
     let short_name = builder.create_string(&dc.short_name);
@@ -288,7 +378,7 @@ fn build_diag_comm<'a>(
 }
 
 fn build_request<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     r: &Request,
 ) -> flatbuffers::WIPOffset<dataformat::Request<'a>> {
     let params: Vec<_> = r.params.iter().map(|p| build_param(builder, p)).collect();
@@ -305,7 +395,7 @@ fn build_request<'a>(
 }
 
 fn build_response<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     r: &Response,
 ) -> flatbuffers::WIPOffset<dataformat::Response<'a>> {
     let params: Vec<_> = r.params.iter().map(|p| build_param(builder, p)).collect();
@@ -323,7 +413,7 @@ fn build_response<'a>(
 }
 
 fn build_param<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     p: &Param,
 ) -> flatbuffers::WIPOffset<dataformat::Param<'a>> {
     let short_name = builder.create_string(&p.short_name);
@@ -352,7 +442,7 @@ fn build_param<'a>(
 }
 
 fn build_param_specific_data<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     data: Option<&ParamData>,
 ) -> (
     dataformat::ParamSpecificData,
@@ -568,7 +658,29 @@ fn build_param_specific_data<'a>(
 }
 
 fn build_dop<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
+    d: &Dop,
+) -> flatbuffers::WIPOffset<dataformat::DOP<'a>> {
+    let shared = builder
+        .dops
+        .as_ref()
+        .and_then(|dops| dops.get(d.short_name.as_str()))
+        .and_then(|written| written.iter().find(|(dop, _)| dop == d))
+        .map(|(_, offset)| *offset);
+    if let Some(offset) = shared {
+        return offset;
+    }
+    let offset = write_dop(builder, d);
+    if let Some(dops) = builder.dops.as_mut() {
+        dops.entry(d.short_name.to_string())
+            .or_default()
+            .push((d.clone(), offset));
+    }
+    offset
+}
+
+fn write_dop<'a>(
+    builder: &mut Builder<'a>,
     d: &Dop,
 ) -> flatbuffers::WIPOffset<dataformat::DOP<'a>> {
     let short_name = builder.create_string(&d.short_name);
@@ -589,7 +701,7 @@ fn build_dop<'a>(
 }
 
 fn build_dop_specific_data<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     data: Option<&DopData>,
 ) -> (
     dataformat::SpecificDOPData,
@@ -869,7 +981,7 @@ fn build_dop_specific_data<'a>(
 }
 
 fn build_field<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     f: &Field,
 ) -> flatbuffers::WIPOffset<dataformat::Field<'a>> {
     let bs = f.basic_structure.as_ref().map(|d| build_dop(builder, d));
@@ -885,7 +997,7 @@ fn build_field<'a>(
 }
 
 fn build_diag_coded_type<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     dct: &DiagCodedType,
 ) -> flatbuffers::WIPOffset<dataformat::DiagCodedType<'a>> {
     let bte = builder.create_string(&dct.base_type_encoding);
@@ -981,7 +1093,7 @@ fn build_diag_coded_type<'a>(
 }
 
 fn build_compu_method<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     cm: &CompuMethod,
 ) -> flatbuffers::WIPOffset<dataformat::CompuMethod<'a>> {
     let itp = cm
@@ -1003,7 +1115,7 @@ fn build_compu_method<'a>(
 }
 
 fn build_compu_itp<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     itp: &CompuInternalToPhys,
 ) -> flatbuffers::WIPOffset<dataformat::CompuInternalToPhys<'a>> {
     let scales: Vec<_> = itp
@@ -1031,7 +1143,7 @@ fn build_compu_itp<'a>(
 }
 
 fn build_compu_pti<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     pti: &CompuPhysToInternal,
 ) -> flatbuffers::WIPOffset<dataformat::CompuPhysToInternal<'a>> {
     let pc = pti
@@ -1059,7 +1171,7 @@ fn build_compu_pti<'a>(
 }
 
 fn build_compu_scale<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     cs: &CompuScale,
 ) -> flatbuffers::WIPOffset<dataformat::CompuScale<'a>> {
     let sl = cs.short_label.as_ref().map(|t| build_text(builder, t));
@@ -1095,7 +1207,7 @@ fn build_compu_scale<'a>(
 }
 
 fn build_compu_values<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     cv: &CompuValues,
 ) -> flatbuffers::WIPOffset<dataformat::CompuValues<'a>> {
     let vt = builder.create_string(&cv.vt);
@@ -1111,7 +1223,7 @@ fn build_compu_values<'a>(
 }
 
 fn build_compu_default_value<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     cdv: &CompuDefaultValue,
 ) -> flatbuffers::WIPOffset<dataformat::CompuDefaultValue<'a>> {
     let vals = cdv.values.as_ref().map(|v| build_compu_values(builder, v));
@@ -1129,7 +1241,7 @@ fn build_compu_default_value<'a>(
 }
 
 fn build_physical_type<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     pt: &PhysicalType,
 ) -> flatbuffers::WIPOffset<dataformat::PhysicalType<'a>> {
     dataformat::PhysicalType::create(
@@ -1143,7 +1255,7 @@ fn build_physical_type<'a>(
 }
 
 fn build_internal_constr<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     ic: &InternalConstr,
 ) -> flatbuffers::WIPOffset<dataformat::InternalConstr<'a>> {
     let ll = ic.lower_limit.as_ref().map(|l| build_limit(builder, l));
@@ -1178,7 +1290,7 @@ fn build_internal_constr<'a>(
 }
 
 fn build_limit<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     l: &Limit,
 ) -> flatbuffers::WIPOffset<dataformat::Limit<'a>> {
     let v = builder.create_string(&l.value);
@@ -1192,7 +1304,7 @@ fn build_limit<'a>(
 }
 
 fn build_unit<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     u: &Unit,
 ) -> flatbuffers::WIPOffset<dataformat::Unit<'a>> {
     let sn = builder.create_string(&u.short_name);
@@ -1214,7 +1326,7 @@ fn build_unit<'a>(
 }
 
 fn build_physical_dimension<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     pd: &PhysicalDimension,
 ) -> flatbuffers::WIPOffset<dataformat::PhysicalDimension<'a>> {
     let sn = builder.create_string(&pd.short_name);
@@ -1236,7 +1348,7 @@ fn build_physical_dimension<'a>(
 }
 
 fn build_unit_spec<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     us: &UnitSpec,
 ) -> flatbuffers::WIPOffset<dataformat::UnitSpec<'a>> {
     let ugs: Vec<_> = us
@@ -1283,7 +1395,7 @@ fn build_unit_spec<'a>(
 }
 
 fn build_dtc<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     dtc: &Dtc,
 ) -> flatbuffers::WIPOffset<dataformat::DTC<'a>> {
     let sn = builder.create_string(&dtc.short_name);
@@ -1304,8 +1416,321 @@ fn build_dtc<'a>(
     )
 }
 
+fn build_address_format<'a>(
+    builder: &mut Builder<'a>,
+    af: &AddressFormat,
+) -> flatbuffers::WIPOffset<dataformat::AddressFormat<'a>> {
+    dataformat::AddressFormat::create(
+        builder,
+        &dataformat::AddressFormatArgs {
+            address_bytes: af.address_bytes,
+            length_bytes: af.length_bytes,
+        },
+    )
+}
+
+fn build_memory_config<'a>(
+    builder: &mut Builder<'a>,
+    mc: &MemoryConfig,
+) -> flatbuffers::WIPOffset<dataformat::MemoryConfig<'a>> {
+    let daf = build_address_format(builder, &mc.default_address_format);
+    let regions: Vec<_> = mc
+        .regions
+        .iter()
+        .map(|r| build_memory_region(builder, r))
+        .collect();
+    let regions = builder.create_vector(&regions);
+    let data_blocks: Vec<_> = mc
+        .data_blocks
+        .iter()
+        .map(|b| build_data_block(builder, b))
+        .collect();
+    let data_blocks = builder.create_vector(&data_blocks);
+    dataformat::MemoryConfig::create(
+        builder,
+        &dataformat::MemoryConfigArgs {
+            default_address_format: Some(daf),
+            regions: Some(regions),
+            data_blocks: Some(data_blocks),
+        },
+    )
+}
+
+fn build_memory_region<'a>(
+    builder: &mut Builder<'a>,
+    r: &MemoryRegion,
+) -> flatbuffers::WIPOffset<dataformat::MemoryRegion<'a>> {
+    let name = builder.create_string(&r.name);
+    let desc = r.description.as_ref().map(|d| builder.create_string(d));
+    let af = r
+        .address_format
+        .as_ref()
+        .map(|af| build_address_format(builder, af));
+    let sl = r.security_level.as_ref().map(|s| builder.create_string(s));
+    let sessions = r.session.as_ref().map(|sessions| {
+        let sessions: Vec<_> = sessions.iter().map(|s| builder.create_string(s)).collect();
+        builder.create_vector(&sessions)
+    });
+    let access = match r.access {
+        MemoryAccess::Read => dataformat::MemoryAccess::READ,
+        MemoryAccess::Write => dataformat::MemoryAccess::WRITE,
+        MemoryAccess::ReadWrite => dataformat::MemoryAccess::READ_WRITE,
+        MemoryAccess::Execute => dataformat::MemoryAccess::EXECUTE,
+    };
+    dataformat::MemoryRegion::create(
+        builder,
+        &dataformat::MemoryRegionArgs {
+            name: Some(name),
+            description: desc,
+            start_address: r.start_address,
+            size: r.size,
+            access,
+            address_format: af,
+            security_level: sl,
+            sessions,
+        },
+    )
+}
+
+fn build_checksum<'a>(
+    builder: &mut Builder<'a>,
+    ct: &ChecksumType,
+) -> flatbuffers::WIPOffset<dataformat::Checksum<'a>> {
+    let (algorithm, name, length, parameters) = match ct {
+        ChecksumType::Crc16Ccitt => (dataformat::ChecksumAlgorithm::CRC16_CCITT, None, 0, None),
+        ChecksumType::Crc32 => (dataformat::ChecksumAlgorithm::CRC32, None, 0, None),
+        ChecksumType::Sha256 => (dataformat::ChecksumAlgorithm::SHA256, None, 0, None),
+        ChecksumType::Oem {
+            name,
+            length,
+            parameters,
+        } => {
+            let name = builder.create_string(name);
+            let parameters: Vec<_> = parameters
+                .iter()
+                .map(|(k, v)| {
+                    let key = builder.create_string(k);
+                    let val = builder.create_string(v);
+                    dataformat::KeyValue::create(
+                        builder,
+                        &dataformat::KeyValueArgs {
+                            key: Some(key),
+                            value: Some(val),
+                        },
+                    )
+                })
+                .collect();
+            let parameters = builder.create_vector(&parameters);
+            (
+                dataformat::ChecksumAlgorithm::OEM,
+                Some(name),
+                *length,
+                Some(parameters),
+            )
+        }
+    };
+    dataformat::Checksum::create(
+        builder,
+        &dataformat::ChecksumArgs {
+            algorithm,
+            name,
+            length,
+            parameters,
+        },
+    )
+}
+
+fn build_data_block<'a>(
+    builder: &mut Builder<'a>,
+    b: &DataBlock,
+) -> flatbuffers::WIPOffset<dataformat::DataBlock<'a>> {
+    let name = builder.create_string(&b.name);
+    let desc = b.description.as_ref().map(|d| builder.create_string(d));
+    let sl = b.security_level.as_ref().map(|s| builder.create_string(s));
+    let session = b.session.as_ref().map(|s| builder.create_string(s));
+    let checksum = b
+        .checksum_type
+        .as_ref()
+        .map(|ct| build_checksum(builder, ct));
+    let block_type = match b.block_type {
+        DataBlockType::Download => dataformat::DataBlockType::DOWNLOAD,
+        DataBlockType::Upload => dataformat::DataBlockType::UPLOAD,
+    };
+    let format = match b.format {
+        DataBlockFormat::Raw => dataformat::DataBlockFormat::RAW,
+        DataBlockFormat::Encrypted => dataformat::DataBlockFormat::ENCRYPTED,
+        DataBlockFormat::Compressed => dataformat::DataBlockFormat::COMPRESSED,
+        DataBlockFormat::EncryptedCompressed => dataformat::DataBlockFormat::ENCRYPTED_COMPRESSED,
+    };
+    dataformat::DataBlock::create(
+        builder,
+        &dataformat::DataBlockArgs {
+            name: Some(name),
+            description: desc,
+            block_type,
+            memory_address: b.memory_address,
+            memory_size: b.memory_size,
+            format,
+            max_block_length: b.max_block_length,
+            security_level: sl,
+            session,
+            checksum,
+        },
+    )
+}
+
+fn build_type_definition<'a>(
+    builder: &mut Builder<'a>,
+    td: &TypeDefinition,
+) -> flatbuffers::WIPOffset<dataformat::TypeDefinition<'a>> {
+    let name = builder.create_string(&td.name);
+    let base = builder.create_string(&td.base);
+    let enum_values = td.enum_values_json.as_deref().map(|json| {
+        let entries: Vec<_> = enum_entries(json)
+            .into_iter()
+            .map(|(key, value)| {
+                let key = key.map(|k| builder.create_string(&k));
+                let (number, text) = match value {
+                    serde_json::Value::Number(n) if n.is_i64() => (n.as_i64(), None),
+                    serde_json::Value::String(s) => (None, Some(s)),
+                    other => (None, Some(other.to_string())),
+                };
+                let text = text.map(|t| builder.create_string(&t));
+                dataformat::EnumValue::create(
+                    builder,
+                    &dataformat::EnumValueArgs { key, number, text },
+                )
+            })
+            .collect();
+        builder.create_vector(&entries)
+    });
+    let desc = td.description.as_ref().map(|d| builder.create_string(d));
+    dataformat::TypeDefinition::create(
+        builder,
+        &dataformat::TypeDefinitionArgs {
+            name: Some(name),
+            base: Some(base),
+            bit_length: td.bit_length,
+            bit_mask: td.bit_mask,
+            condensed: td.condensed,
+            min_length: td.min_length,
+            max_length: td.max_length,
+            length_prefix_bits: td.length_prefix_bits,
+            enum_values,
+            description: desc,
+        },
+    )
+}
+
+/// `(key, value)` entries of a type's `enum_values_json` in written order;
+/// the items of a list have no key.
+fn enum_entries(json: &str) -> Vec<(Option<String>, serde_json::Value)> {
+    match serde_json::from_str::<EnumEntries>(json) {
+        Ok(EnumEntries(entries)) => entries,
+        Err(e) => {
+            log::warn!("Dropping enum values that are no JSON object or list: {e}");
+            Vec::new()
+        }
+    }
+}
+
+/// A JSON object or list, deserialized without sorting the object keys.
+struct EnumEntries(Vec<(Option<String>, serde_json::Value)>);
+
+impl<'de> serde::Deserialize<'de> for EnumEntries {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(EnumEntriesVisitor)
+    }
+}
+
+struct EnumEntriesVisitor;
+
+impl<'de> serde::de::Visitor<'de> for EnumEntriesVisitor {
+    type Value = EnumEntries;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("an enum mapping or list")
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<EnumEntries, A::Error> {
+        let mut entries = Vec::new();
+        while let Some((key, value)) = map.next_entry::<String, serde_json::Value>()? {
+            entries.push((Some(key), value));
+        }
+        Ok(EnumEntries(entries))
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<EnumEntries, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(value) = seq.next_element::<serde_json::Value>()? {
+            entries.push((None, value));
+        }
+        Ok(EnumEntries(entries))
+    }
+}
+
+fn build_transport_config<'a>(
+    builder: &mut Builder<'a>,
+    tc: &TransportConfig,
+) -> flatbuffers::WIPOffset<dataformat::TransportConfig<'a>> {
+    let doip = tc.doip.as_ref().map(|d| {
+        let ip = d.ip.as_ref().map(|ip| builder.create_string(ip));
+        let eid = d.eid.map(|id| builder.create_vector(&id));
+        let gid = d.gid.map(|id| builder.create_vector(&id));
+        dataformat::DoipConfig::create(
+            builder,
+            &dataformat::DoipConfigArgs {
+                ip,
+                port: d.port,
+                logical_address: d.logical_address,
+                functional_address: d.functional_address,
+                tester_address: d.tester_address,
+                eid,
+                gid,
+            },
+        )
+    });
+    dataformat::TransportConfig::create(builder, &dataformat::TransportConfigArgs { doip })
+}
+
+fn build_ecu_identification<'a>(
+    builder: &mut Builder<'a>,
+    ident: &EcuIdentification,
+) -> flatbuffers::WIPOffset<dataformat::EcuIdentification<'a>> {
+    let mut text = |s: &Option<String>| s.as_ref().map(|s| builder.create_string(s));
+    let part_number = text(&ident.part_number);
+    let supplier_code = text(&ident.supplier_code);
+    let hardware_version = text(&ident.hardware_version);
+    let software_version = text(&ident.software_version);
+    let dids: Vec<_> = ident
+        .dids
+        .iter()
+        .map(|(role, did)| {
+            let role = builder.create_string(role);
+            dataformat::IdentificationDid::create(
+                builder,
+                &dataformat::IdentificationDidArgs {
+                    role: Some(role),
+                    did: *did,
+                },
+            )
+        })
+        .collect();
+    let dids = builder.create_vector(&dids);
+    dataformat::EcuIdentification::create(
+        builder,
+        &dataformat::EcuIdentificationArgs {
+            part_number,
+            supplier_code,
+            hardware_version,
+            software_version,
+            dids: Some(dids),
+        },
+    )
+}
+
 fn build_table_row<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     tr: &TableRow,
 ) -> flatbuffers::WIPOffset<dataformat::TableRow<'a>> {
     let sn = builder.create_string(&tr.short_name);
@@ -1364,7 +1789,7 @@ fn build_table_row<'a>(
 }
 
 fn build_table_dop<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     td: &TableDop,
 ) -> flatbuffers::WIPOffset<dataformat::TableDop<'a>> {
     let sem = builder.create_string(&td.semantic);
@@ -1435,7 +1860,7 @@ fn build_table_dop<'a>(
 }
 
 fn build_variant_pattern<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     vp: &VariantPattern,
 ) -> flatbuffers::WIPOffset<dataformat::VariantPattern<'a>> {
     let mps: Vec<_> = vp
@@ -1466,7 +1891,7 @@ fn build_variant_pattern<'a>(
 }
 
 fn build_parent_ref<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     pr: &ParentRef,
 ) -> flatbuffers::WIPOffset<dataformat::ParentRef<'a>> {
     // Build the ref union target first (recursive - inner objects must be built before outer)
@@ -1559,7 +1984,7 @@ fn build_parent_ref<'a>(
 }
 
 fn build_protocol<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     p: &Protocol,
 ) -> flatbuffers::WIPOffset<dataformat::Protocol<'a>> {
     let dl = build_diag_layer(builder, &p.diag_layer);
@@ -1599,7 +2024,7 @@ fn build_protocol<'a>(
 }
 
 fn build_prot_stack<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     ps: &ProtStack,
 ) -> flatbuffers::WIPOffset<dataformat::ProtStack<'a>> {
     let sn = builder.create_string(&ps.short_name);
@@ -1662,7 +2087,7 @@ fn build_prot_stack<'a>(
 /// "pending tags not empty" panics (the VectorBuilder's `end_union_vector`
 /// does not clear `pending_tags`, breaking subsequent union vector builds).
 fn build_complex_value<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     cv: &ComplexValue,
 ) -> flatbuffers::WIPOffset<dataformat::ComplexValue<'a>> {
     // Pre-build all entry offsets, then create the union vector in one shot
@@ -1697,7 +2122,7 @@ fn build_complex_value<'a>(
 }
 
 fn build_com_param<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     cp: &ComParam,
 ) -> flatbuffers::WIPOffset<dataformat::ComParam<'a>> {
     let sn = builder.create_string(&cp.short_name);
@@ -1797,7 +2222,7 @@ fn build_com_param<'a>(
 }
 
 fn build_com_param_ref<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     cpr: &ComParamRef,
 ) -> flatbuffers::WIPOffset<dataformat::ComParamRef<'a>> {
     let sv = cpr.simple_value.as_ref().map(|sv| {
@@ -1830,7 +2255,7 @@ fn build_com_param_ref<'a>(
 }
 
 fn build_single_ecu_job<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     sej: &SingleEcuJob,
 ) -> flatbuffers::WIPOffset<dataformat::SingleEcuJob<'a>> {
     let dc = build_diag_comm(builder, &sej.diag_comm);
@@ -1871,7 +2296,7 @@ fn build_single_ecu_job<'a>(
 }
 
 fn build_prog_code<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     pc: &ProgCode,
 ) -> flatbuffers::WIPOffset<dataformat::ProgCode<'a>> {
     let cf = builder.create_string(&pc.code_file);
@@ -1920,7 +2345,7 @@ fn build_prog_code<'a>(
 }
 
 fn build_job_param<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     jp: &JobParam,
 ) -> flatbuffers::WIPOffset<dataformat::JobParam<'a>> {
     let sn = builder.create_string(&jp.short_name);
@@ -1941,7 +2366,7 @@ fn build_job_param<'a>(
 }
 
 fn build_state_chart<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     sc: &StateChart,
 ) -> flatbuffers::WIPOffset<dataformat::StateChart<'a>> {
     let sn = builder.create_string(&sc.short_name);
@@ -1997,7 +2422,7 @@ fn build_state_chart<'a>(
 }
 
 fn build_audience<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     a: &Audience,
 ) -> flatbuffers::WIPOffset<dataformat::Audience<'a>> {
     let ea: Vec<_> = a
@@ -2027,7 +2452,7 @@ fn build_audience<'a>(
 }
 
 fn build_additional_audience<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     aa: &AdditionalAudience,
 ) -> flatbuffers::WIPOffset<dataformat::AdditionalAudience<'a>> {
     let sn = builder.create_string(&aa.short_name);
@@ -2042,7 +2467,7 @@ fn build_additional_audience<'a>(
 }
 
 fn build_state_transition_ref<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     str_ref: &StateTransitionRef,
 ) -> flatbuffers::WIPOffset<dataformat::StateTransitionRef<'a>> {
     let v = builder.create_string(&str_ref.value);
@@ -2069,7 +2494,7 @@ fn build_state_transition_ref<'a>(
 }
 
 fn build_pre_condition_state_ref<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     pcsr: &PreConditionStateRef,
 ) -> flatbuffers::WIPOffset<dataformat::PreConditionStateRef<'a>> {
     let v = builder.create_string(&pcsr.value);
@@ -2100,7 +2525,7 @@ fn build_pre_condition_state_ref<'a>(
 // --- Text builders ---
 
 fn build_text<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     t: &Text,
 ) -> flatbuffers::WIPOffset<dataformat::Text<'a>> {
     let v = builder.create_string(&t.value);
@@ -2115,7 +2540,7 @@ fn build_text<'a>(
 }
 
 fn build_long_name<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     ln: &LongName,
 ) -> flatbuffers::WIPOffset<dataformat::LongName<'a>> {
     let v = builder.create_string(&ln.value);
@@ -2130,7 +2555,7 @@ fn build_long_name<'a>(
 }
 
 fn build_sdgs<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     sdgs: &Sdgs,
 ) -> flatbuffers::WIPOffset<dataformat::SDGS<'a>> {
     let s: Vec<_> = sdgs
@@ -2143,7 +2568,7 @@ fn build_sdgs<'a>(
 }
 
 fn build_sdg<'a>(
-    builder: &mut FlatBufferBuilder<'a>,
+    builder: &mut Builder<'a>,
     sdg: &Sdg,
 ) -> flatbuffers::WIPOffset<dataformat::SDG<'a>> {
     let csn = builder.create_string(&sdg.caption_sn);
@@ -0,0 +1,138 @@
+//! Requirements traceability.
+//!
+//! Services and jobs can be linked to requirement IDs (e.g. `SWR-1234`). The
+//! links are stored as a `requirements` SDG with one SD per ID, so they are
+//! preserved through ODX and MDD, and [`trace_links`] flattens them into the
+//! rows of a trace matrix.
+
+use serde::Serialize;
+
+use crate::types::{DiagComm, DiagDatabase, Sd, SdOrSdg, Sdg, Sdgs};
+
+/// SDG caption under which requirement IDs are stored.
+pub const REQUIREMENTS_CAPTION: &str = "requirements";
+
+/// Requirement IDs linked to a service or job.
+pub fn requirements(diag_comm: &DiagComm) -> Vec<&str> {
+    diag_comm
+        .sdgs
+        .iter()
+        .flat_map(|sdgs| &sdgs.sdgs)
+        .filter(|sdg| sdg.caption_sn == REQUIREMENTS_CAPTION)
+        .flat_map(|sdg| &sdg.sds)
+        .filter_map(|sd| match sd {
+            SdOrSdg::Sd(sd) => Some(sd.value.as_str()),
+            SdOrSdg::Sdg(_) => None,
+        })
+        .collect()
+}
+
+/// Link a service or job to requirement IDs (no-op when empty).
+pub fn set_requirements(diag_comm: &mut DiagComm, ids: &[String]) {
+    if ids.is_empty() {
+        return;
+    }
+    let sdg = Sdg {
+        caption_sn: REQUIREMENTS_CAPTION.into(),
+        sds: ids
+            .iter()
+            .map(|id| {
+                SdOrSdg::Sd(Sd {
+                    value: id.clone(),
+                    si: "requirement".into(),
+                    ti: String::new(),
+                })
+            })
+            .collect(),
+        si: String::new(),
+    };
+    diag_comm
+        .sdgs
+        .get_or_insert_with(|| Sdgs { sdgs: vec![] })
+        .sdgs
+        .push(sdg);
+}
+
+/// One row of the trace matrix: a requirement and an object implementing it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct TraceLink {
+    pub requirement: String,
+    /// Short name of the variant, functional group, protocol or ECU shared
+    /// data layer defining the object.
+    pub layer: String,
+    /// `service` or `job`.
+    pub kind: &'static str,
+    pub object: String,
+}
+
+/// Every requirement link in the database, sorted by requirement, layer and
+/// object.
+pub fn trace_links(db: &DiagDatabase) -> Vec<TraceLink> {
+    let layers = db
+        .variants
+        .iter()
+        .map(|v| &v.diag_layer)
+        .chain(db.functional_groups.iter().map(|fg| &fg.diag_layer))
+        .chain(db.protocols.iter().map(|p| &p.diag_layer))
+        .chain(db.ecu_shared_datas.iter().map(|e| &e.diag_layer));
+    let mut links = Vec::new();
+    for layer in layers {
+        let comms = layer
+            .diag_services
+            .iter()
+            .map(|s| ("service", &s.diag_comm))
+            .chain(layer.single_ecu_jobs.iter().map(|j| ("job", &j.diag_comm)));
+        for (kind, dc) in comms {
+            links.extend(requirements(dc).into_iter().map(|id| TraceLink {
+                requirement: id.to_string(),
+                layer: layer.short_name.to_string(),
+                kind,
+                object: dc.short_name.to_string(),
+            }));
+        }
+    }
+    links.sort();
+    links.dedup();
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{service, variant};
+    use crate::types::*;
+
+    /// A service traced to the requirements `ids`.
+    fn traced(name: &str, ids: &[&str]) -> DiagService {
+        let mut svc = service(name, "");
+        let ids: Vec<String> = ids.iter().map(|id| (*id).to_string()).collect();
+        set_requirements(&mut svc.diag_comm, &ids);
+        svc
+    }
+
+    #[test]
+    fn test_trace_links_sorted_by_requirement() {
+        let services = vec![
+            traced("VIN_Read", &["SWR-2", "SWR-1"]),
+            traced("Untraced", &[]),
+            traced("Reset", &["SWR-1"]),
+        ];
+        let db = DiagDatabase {
+            variants: vec![variant("Base", services)],
+            ..Default::default()
+        };
+
+        let rows: Vec<(String, String)> = trace_links(&db)
+            .into_iter()
+            .map(|l| (l.requirement, l.object))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("SWR-1".into(), "Reset".into()),
+                ("SWR-1".into(), "VIN_Read".into()),
+                ("SWR-2".into(), "VIN_Read".into()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,383 @@
+//! DoIP addressing of the ECU.
+//!
+//! YAML states it under `ecu.addressing.doip` and MDD in its own table; ODX
+//! has no place for it, so the ODX writer stores it as a `transport` SDG on
+//! the DIAG-LAYER-CONTAINER with a nested `doip` SDG holding one SD per set
+//! field (SI = field name). All formats may also carry the addresses as DoIP
+//! communication parameters: [`complete_transport`] fills the fields a
+//! database does not state explicitly from those, so a tester can open a
+//! connection with nothing but the database.
+
+use std::collections::BTreeMap;
+
+use crate::numbers::{parse_hex_bytes, parse_uint};
+use crate::types::*;
+
+/// SDG caption under which the ODX writer stores the transport section.
+pub const TRANSPORT_CAPTION: &str = "transport";
+
+const DOIP_CAPTION: &str = "doip";
+
+pub const CP_LOGICAL_ECU_ADDRESS: &str = "CP_DoIPLogicalEcuAddress";
+pub const CP_LOGICAL_FUNCTIONAL_ADDRESS: &str = "CP_DoIPLogicalFunctionalAddress";
+pub const CP_LOGICAL_TESTER_ADDRESS: &str = "CP_DoIPLogicalTesterAddress";
+/// Entity identification. ISO 22900-2 defines no comparam for EID and GID,
+/// so these two are diag-converter's names for them.
+pub const CP_ENTITY_ID: &str = "CP_DoIPEntityIdentification";
+pub const CP_GROUP_ID: &str = "CP_DoIPGroupIdentification";
+
+/// The DoIP addressing the comparams of `db` configure, or `None` if they
+/// configure none. The base variants are searched first, then the other
+/// variants, the protocols and the ECU shared data; the first value wins.
+pub fn doip_from_comparams(db: &DiagDatabase) -> Option<DoipConfig> {
+    let layers = db
+        .variants
+        .iter()
+        .filter(|v| v.is_base_variant)
+        .chain(db.variants.iter().filter(|v| !v.is_base_variant))
+        .map(|v| &v.diag_layer)
+        .chain(db.protocols.iter().map(|p| &p.diag_layer))
+        .chain(db.ecu_shared_datas.iter().map(|e| &e.diag_layer));
+    let mut values = BTreeMap::new();
+    for layer in layers {
+        for cp_ref in &layer.com_param_refs {
+            collect_values(cp_ref, &mut values);
+        }
+    }
+
+    let address = |name: &str| values.get(name).and_then(|v| parse_address(v));
+    let entity_id = |name: &str| values.get(name).and_then(|v| parse_entity_id(v));
+    let doip = DoipConfig {
+        logical_address: address(CP_LOGICAL_ECU_ADDRESS),
+        functional_address: address(CP_LOGICAL_FUNCTIONAL_ADDRESS),
+        tester_address: address(CP_LOGICAL_TESTER_ADDRESS),
+        eid: entity_id(CP_ENTITY_ID),
+        gid: entity_id(CP_GROUP_ID),
+        ..Default::default()
+    };
+    (!doip.is_empty()).then_some(doip)
+}
+
+/// Fill every DoIP field `db.transport` leaves unset with the value its
+/// comparams configure. Explicit values are kept.
+pub fn complete_transport(db: &mut DiagDatabase) {
+    let Some(found) = doip_from_comparams(db) else {
+        return;
+    };
+    let doip = db
+        .transport
+        .get_or_insert_with(TransportConfig::default)
+        .doip
+        .get_or_insert_with(DoipConfig::default);
+    doip.logical_address = doip.logical_address.or(found.logical_address);
+    doip.functional_address = doip.functional_address.or(found.functional_address);
+    doip.tester_address = doip.tester_address.or(found.tester_address);
+    doip.eid = doip.eid.or(found.eid);
+    doip.gid = doip.gid.or(found.gid);
+}
+
+/// The SDG holding `db`'s DoIP addressing, or `None` when it has none.
+pub fn transport_sdg(db: &DiagDatabase) -> Option<Sdg> {
+    let doip = db
+        .transport
+        .as_ref()?
+        .doip
+        .as_ref()
+        .filter(|d| !d.is_empty())?;
+    let address = |a: Option<u16>| a.map(|a| format!("0x{a:04X}"));
+    let entity_id = |id: Option<[u8; 6]>| id.map(|id| format_entity_id(&id));
+    let fields = [
+        ("ip", doip.ip.clone()),
+        ("port", doip.port.map(|p| p.to_string())),
+        ("logical_address", address(doip.logical_address)),
+        ("functional_address", address(doip.functional_address)),
+        ("tester_address", address(doip.tester_address)),
+        ("eid", entity_id(doip.eid)),
+        ("gid", entity_id(doip.gid)),
+    ];
+    let doip = Sdg {
+        caption_sn: DOIP_CAPTION.into(),
+        sds: fields
+            .into_iter()
+            .filter_map(|(si, value)| {
+                Some(SdOrSdg::Sd(Sd {
+                    value: value?,
+                    si: si.into(),
+                    ti: String::new(),
+                }))
+            })
+            .collect(),
+        si: String::new(),
+    };
+    Some(Sdg {
+        caption_sn: TRANSPORT_CAPTION.into(),
+        sds: vec![SdOrSdg::Sdg(doip)],
+        si: String::new(),
+    })
+}
+
+/// Read the transport section stored in `sdgs`. Fields that do not parse
+/// are left unset.
+pub fn transport_of(sdgs: Option<&Sdgs>) -> Option<TransportConfig> {
+    let sdg = sdgs?
+        .sdgs
+        .iter()
+        .find(|sdg| sdg.caption_sn == TRANSPORT_CAPTION)?;
+    let doip = sdg.sds.iter().find_map(|entry| match entry {
+        SdOrSdg::Sdg(doip) if doip.caption_sn == DOIP_CAPTION => Some(doip),
+        _ => None,
+    })?;
+    let mut config = DoipConfig::default();
+    for entry in &doip.sds {
+        let SdOrSdg::Sd(sd) = entry else {
+            continue;
+        };
+        let value = sd.value.as_str();
+        match sd.si.as_str() {
+            "ip" => config.ip = Some(value.to_string()),
+            "port" => config.port = parse_address(value),
+            "logical_address" => config.logical_address = parse_address(value),
+            "functional_address" => config.functional_address = parse_address(value),
+            "tester_address" => config.tester_address = parse_address(value),
+            "eid" => config.eid = parse_entity_id(value),
+            "gid" => config.gid = parse_entity_id(value),
+            _ => {}
+        }
+    }
+    Some(TransportConfig { doip: Some(config) })
+}
+
+/// Values of one ComParamRef by comparam name. Complex comparams such as
+/// `CP_UniqueRespIdTable` contribute their simple children.
+fn collect_values(cp_ref: &ComParamRef, values: &mut BTreeMap<String, String>) {
+    let Some(cp) = &cp_ref.com_param else {
+        return;
+    };
+    match &cp.specific_data {
+        Some(ComParamSpecificData::Complex {
+            com_params,
+            complex_physical_default_values,
+            ..
+        }) => {
+            let Some(complex) = cp_ref
+                .complex_value
+                .as_ref()
+                .or(complex_physical_default_values.first())
+            else {
+                return;
+            };
+            for (child, entry) in com_params.iter().zip(&complex.entries) {
+                if let SimpleOrComplexValue::Simple(value) = entry {
+                    values
+                        .entry(child.short_name.to_string())
+                        .or_insert_with(|| value.value.clone());
+                }
+            }
+        }
+        specific => {
+            let value = cp_ref
+                .simple_value
+                .as_ref()
+                .map(|v| v.value.clone())
+                .or_else(|| match specific {
+                    Some(ComParamSpecificData::Regular {
+                        physical_default_value,
+                        ..
+                    }) => Some(physical_default_value.clone()),
+                    _ => None,
+                });
+            if let Some(value) = value {
+                values.entry(cp.short_name.to_string()).or_insert(value);
+            }
+        }
+    }
+}
+
+/// A logical address in decimal or `0x` hex.
+pub fn parse_address(value: &str) -> Option<u16> {
+    parse_uint(value).and_then(|v| u16::try_from(v).ok())
+}
+
+/// A 6-byte EID or GID as 12 hex digits, optionally `0x`-prefixed or
+/// separated by `:` or `-` (`00:1A:2B:3C:4D:5E`).
+pub fn parse_entity_id(value: &str) -> Option<[u8; 6]> {
+    parse_hex_bytes(&value.replace([':', '-'], ""))?
+        .try_into()
+        .ok()
+}
+
+/// `00:1A:2B:3C:4D:5E`, the form [`parse_entity_id`] reads back.
+pub fn format_entity_id(id: &[u8; 6]) -> String {
+    id.iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn com_param(name: &str, specific_data: Option<ComParamSpecificData>) -> ComParam {
+        ComParam {
+            com_param_type: ComParamType::Regular,
+            short_name: name.into(),
+            long_name: None,
+            param_class: String::new(),
+            cp_type: ComParamStandardisationLevel::Standard,
+            display_level: None,
+            cp_usage: ComParamUsage::EcuComm,
+            specific_data,
+        }
+    }
+
+    fn simple(name: &str, value: &str) -> ComParamRef {
+        ComParamRef {
+            simple_value: Some(SimpleValue {
+                value: value.into(),
+            }),
+            complex_value: None,
+            com_param: Some(Box::new(com_param(name, None))),
+            protocol: None,
+            prot_stack: None,
+        }
+    }
+
+    fn unique_resp_id_table(ecu_address: &str) -> ComParamRef {
+        let entry = |v: &str| SimpleOrComplexValue::Simple(SimpleValue { value: v.into() });
+        ComParamRef {
+            simple_value: None,
+            complex_value: Some(ComplexValue {
+                entries: vec![entry(ecu_address), entry("0"), entry("ECU")],
+            }),
+            com_param: Some(Box::new(ComParam {
+                com_param_type: ComParamType::Complex,
+                ..com_param(
+                    "CP_UniqueRespIdTable",
+                    Some(ComParamSpecificData::Complex {
+                        com_params: vec![
+                            com_param(CP_LOGICAL_ECU_ADDRESS, None),
+                            com_param("CP_DoIPSecondaryLogicalECUResponseAddress", None),
+                            com_param("CP_ECULayerShortName", None),
+                        ],
+                        complex_physical_default_values: vec![],
+                        allow_multiple_values: false,
+                    }),
+                )
+            })),
+            protocol: None,
+            prot_stack: None,
+        }
+    }
+
+    fn database(base: Vec<ComParamRef>, protocol: Vec<ComParamRef>) -> DiagDatabase {
+        DiagDatabase {
+            variants: vec![Variant {
+                diag_layer: DiagLayer {
+                    short_name: "Base".into(),
+                    com_param_refs: base,
+                    ..Default::default()
+                },
+                is_base_variant: true,
+                ..Default::default()
+            }],
+            protocols: vec![Protocol {
+                diag_layer: DiagLayer {
+                    short_name: "UDSonDoIP".into(),
+                    com_param_refs: protocol,
+                    ..Default::default()
+                },
+                com_param_spec: None,
+                prot_stack: None,
+                parent_refs: vec![],
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reads_simple_and_complex_comparams() {
+        let db = database(
+            vec![
+                unique_resp_id_table("4096"),
+                simple(CP_ENTITY_ID, "00:1A:2B:3C:4D:5E"),
+            ],
+            vec![
+                simple(CP_LOGICAL_TESTER_ADDRESS, "0x0E80"),
+                simple(CP_LOGICAL_FUNCTIONAL_ADDRESS, "65535"),
+            ],
+        );
+        assert_eq!(
+            doip_from_comparams(&db),
+            Some(DoipConfig {
+                logical_address: Some(0x1000),
+                functional_address: Some(0xFFFF),
+                tester_address: Some(0x0E80),
+                eid: Some([0x00, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E]),
+                ..Default::default()
+            })
+        );
+        assert_eq!(doip_from_comparams(&database(vec![], vec![])), None);
+    }
+
+    #[test]
+    fn test_complete_keeps_explicit_values() {
+        let mut db = database(
+            vec![simple(CP_LOGICAL_ECU_ADDRESS, "0x1000")],
+            vec![simple(CP_LOGICAL_TESTER_ADDRESS, "0x0E80")],
+        );
+        db.transport = Some(TransportConfig {
+            doip: Some(DoipConfig {
+                ip: Some("192.168.0.1".into()),
+                logical_address: Some(0x2000),
+                ..Default::default()
+            }),
+        });
+        complete_transport(&mut db);
+        let doip = db.transport.unwrap().doip.unwrap();
+        assert_eq!(doip.ip.as_deref(), Some("192.168.0.1"));
+        assert_eq!(doip.logical_address, Some(0x2000));
+        assert_eq!(doip.tester_address, Some(0x0E80));
+    }
+
+    #[test]
+    fn test_sdg_roundtrip() {
+        assert_eq!(transport_sdg(&DiagDatabase::default()), None);
+        let transport = TransportConfig {
+            doip: Some(DoipConfig {
+                ip: Some("192.168.0.10".into()),
+                port: Some(13400),
+                logical_address: Some(0x0E00),
+                tester_address: Some(0x0E80),
+                gid: Some([0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF]),
+                ..Default::default()
+            }),
+        };
+        let db = DiagDatabase {
+            transport: Some(transport.clone()),
+            ..Default::default()
+        };
+        let sdgs = Sdgs {
+            sdgs: vec![transport_sdg(&db).unwrap()],
+        };
+        assert_eq!(transport_of(Some(&sdgs)), Some(transport));
+    }
+
+    #[test]
+    fn test_entity_id_syntax() {
+        let id = [0x00, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E];
+        assert_eq!(format_entity_id(&id), "00:1A:2B:3C:4D:5E");
+        for text in [
+            "00:1A:2B:3C:4D:5E",
+            "001a2b3c4d5e",
+            "0x001A2B3C4D5E",
+            "00-1A-2B-3C-4D-5E",
+        ] {
+            assert_eq!(parse_entity_id(text), Some(id), "{text}");
+        }
+        assert_eq!(parse_entity_id("001A2B3C4D"), None);
+        assert_eq!(parse_address("0x0E00"), Some(0x0E00));
+        assert_eq!(parse_address("4096"), Some(0x1000));
+        assert_eq!(parse_address("70000"), None);
+    }
+}
@@ -0,0 +1,138 @@
+//! Named YAML types as SDGs, for formats without a type registry.
+//!
+//! ODX has no counterpart of the YAML `types:` section, so the ODX writer
+//! stores [`DiagDatabase::type_definitions`] as a `type_definitions` SDG on
+//! the DIAG-LAYER-CONTAINER: one nested SDG per type, captioned with the type
+//! name, holding one SD per set field (SI = field name).
+
+use crate::types::{DiagDatabase, Sd, SdOrSdg, Sdg, Sdgs, TypeDefinition};
+
+/// SDG caption under which the type definitions are stored.
+pub const TYPE_DEFINITIONS_CAPTION: &str = "type_definitions";
+
+/// The SDG holding `db`'s type definitions, or `None` when it has none.
+pub fn type_definitions_sdg(db: &DiagDatabase) -> Option<Sdg> {
+    if db.type_definitions.is_empty() {
+        return None;
+    }
+    Some(Sdg {
+        caption_sn: TYPE_DEFINITIONS_CAPTION.into(),
+        sds: db
+            .type_definitions
+            .iter()
+            .map(|td| SdOrSdg::Sdg(type_sdg(td)))
+            .collect(),
+        si: String::new(),
+    })
+}
+
+fn type_sdg(td: &TypeDefinition) -> Sdg {
+    let number = |n: Option<u32>| n.map(|n| n.to_string());
+    let fields = [
+        ("base", Some(td.base.clone())),
+        ("bit_length", number(td.bit_length)),
+        ("bit_mask", td.bit_mask.map(|m| m.to_string())),
+        ("condensed", td.condensed.map(|c| c.to_string())),
+        ("min_length", number(td.min_length)),
+        ("max_length", number(td.max_length)),
+        ("length_prefix_bits", number(td.length_prefix_bits)),
+        ("enum_values", td.enum_values_json.clone()),
+        ("description", td.description.clone()),
+    ];
+    Sdg {
+        caption_sn: td.name.clone(),
+        sds: fields
+            .into_iter()
+            .filter_map(|(si, value)| {
+                Some(SdOrSdg::Sd(Sd {
+                    value: value?,
+                    si: si.into(),
+                    ti: String::new(),
+                }))
+            })
+            .collect(),
+        si: String::new(),
+    }
+}
+
+/// Read the type definitions stored in `sdgs`. Fields that do not parse are
+/// left unset.
+pub fn type_definitions_of(sdgs: Option<&Sdgs>) -> Vec<TypeDefinition> {
+    let Some(sdg) = sdgs.and_then(|s| {
+        s.sdgs
+            .iter()
+            .find(|sdg| sdg.caption_sn == TYPE_DEFINITIONS_CAPTION)
+    }) else {
+        return vec![];
+    };
+    sdg.sds
+        .iter()
+        .filter_map(|entry| match entry {
+            SdOrSdg::Sdg(sdg) => Some(type_of(sdg)),
+            SdOrSdg::Sd(_) => None,
+        })
+        .collect()
+}
+
+fn type_of(sdg: &Sdg) -> TypeDefinition {
+    let mut td = TypeDefinition {
+        name: sdg.caption_sn.clone(),
+        ..Default::default()
+    };
+    for entry in &sdg.sds {
+        let SdOrSdg::Sd(sd) = entry else { continue };
+        let value = sd.value.clone();
+        match sd.si.as_str() {
+            "base" => td.base = value,
+            "bit_length" => td.bit_length = value.parse().ok(),
+            "bit_mask" => td.bit_mask = value.parse().ok(),
+            "condensed" => td.condensed = value.parse().ok(),
+            "min_length" => td.min_length = value.parse().ok(),
+            "max_length" => td.max_length = value.parse().ok(),
+            "length_prefix_bits" => td.length_prefix_bits = value.parse().ok(),
+            "enum_values" => td.enum_values_json = Some(value),
+            "description" => td.description = Some(value),
+            _ => {}
+        }
+    }
+    td
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_type_definitions_roundtrip_through_sdgs() {
+        let db = DiagDatabase {
+            type_definitions: vec![
+                TypeDefinition {
+                    name: "VehicleSpeed".into(),
+                    base: "u16".into(),
+                    bit_length: Some(16),
+                    bit_mask: Some(0x0FFF),
+                    condensed: Some(true),
+                    description: Some("km/h".into()),
+                    ..Default::default()
+                },
+                TypeDefinition {
+                    name: "Label".into(),
+                    base: "ascii".into(),
+                    min_length: Some(1),
+                    max_length: Some(16),
+                    length_prefix_bits: Some(8),
+                    enum_values_json: Some(r#"{"OFF":0}"#.into()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(type_definitions_sdg(&DiagDatabase::default()), None);
+
+        let sdgs = Sdgs {
+            sdgs: vec![type_definitions_sdg(&db).unwrap()],
+        };
+        assert_eq!(type_definitions_of(Some(&sdgs)), db.type_definitions);
+        assert_eq!(type_definitions_of(None), vec![]);
+    }
+}
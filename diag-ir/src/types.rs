@@ -1,5 +1,10 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+pub use crate::name::Name;
 
 // --- Top-level ---
 
@@ -17,6 +22,10 @@ pub struct DiagDatabase {
     pub dtcs: Vec<Dtc>,
     pub memory: Option<MemoryConfig>,
     pub type_definitions: Vec<TypeDefinition>,
+    #[serde(default)]
+    pub transport: Option<TransportConfig>,
+    #[serde(default)]
+    pub identification: Option<EcuIdentification>,
 }
 
 // --- Variant system ---
@@ -46,7 +55,7 @@ pub struct EcuSharedData {
 /// Maps to FBS DiagLayer
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct DiagLayer {
-    pub short_name: String,
+    pub short_name: Name,
     pub long_name: Option<LongName>,
     pub funct_classes: Vec<FunctClass>,
     pub com_param_refs: Vec<ComParamRef>,
@@ -55,6 +64,8 @@ pub struct DiagLayer {
     pub state_charts: Vec<StateChart>,
     pub additional_audiences: Vec<AdditionalAudience>,
     pub sdgs: Option<Sdgs>,
+    /// Rich-text description (normalized Markdown). Not part of the FBS schema.
+    pub description: Option<String>,
 }
 
 /// Maps to FBS ParentRef
@@ -98,9 +109,9 @@ pub struct MatchingParameter {
 /// Maps to FBS DiagComm
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct DiagComm {
-    pub short_name: String,
+    pub short_name: Name,
     pub long_name: Option<LongName>,
-    pub semantic: String,
+    pub semantic: Name,
     pub funct_classes: Vec<FunctClass>,
     pub sdgs: Option<Sdgs>,
     pub diag_class_type: DiagClassType,
@@ -111,6 +122,8 @@ pub struct DiagComm {
     pub is_mandatory: bool,
     pub is_executable: bool,
     pub is_final: bool,
+    /// Rich-text description (normalized Markdown). Not part of the FBS schema.
+    pub description: Option<String>,
 }
 
 /// Maps to FBS DiagService
@@ -150,6 +163,14 @@ pub struct Response {
     pub response_type: ResponseType,
     pub params: Vec<Param>,
     pub sdgs: Option<Sdgs>,
+    /// ODX `POS-RESPONSE-SUFFIX`: suffix the supplier appends to the short
+    /// names of positive responses (e.g. `_PR`). Not stored in MDD.
+    #[serde(default)]
+    pub pos_response_suffix: Option<String>,
+    /// Audience of this response, when it differs from the service's.
+    /// Not stored in MDD.
+    #[serde(default)]
+    pub audience: Option<Audience>,
 }
 
 // --- Parameters ---
@@ -159,8 +180,8 @@ pub struct Response {
 pub struct Param {
     pub id: u32,
     pub param_type: ParamType,
-    pub short_name: String,
-    pub semantic: String,
+    pub short_name: Name,
+    pub semantic: Name,
     pub sdgs: Option<Sdgs>,
     pub physical_default_value: String,
     pub byte_position: Option<u32>,
@@ -177,7 +198,7 @@ pub enum ParamData {
     },
     Dynamic,
     LengthKeyRef {
-        dop: Box<Dop>,
+        dop: DopRef,
     },
     MatchingRequestParam {
         request_byte_pos: i32,
@@ -189,13 +210,13 @@ pub enum ParamData {
     },
     PhysConst {
         phys_constant_value: String,
-        dop: Box<Dop>,
+        dop: DopRef,
     },
     Reserved {
         bit_length: u32,
     },
     System {
-        dop: Box<Dop>,
+        dop: DopRef,
         sys_param: String,
     },
     TableEntry {
@@ -211,7 +232,7 @@ pub enum ParamData {
     },
     Value {
         physical_default_value: String,
-        dop: Box<Dop>,
+        dop: DopRef,
     },
 }
 
@@ -221,11 +242,87 @@ pub enum ParamData {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Dop {
     pub dop_type: DopType,
-    pub short_name: String,
+    pub short_name: Name,
     pub sdgs: Option<Sdgs>,
     pub specific_data: Option<DopData>,
 }
 
+/// A DOP referenced from a parameter, field, case or table.
+///
+/// Parsers resolve each DOP once and hand out clones of the same reference,
+/// so every parameter using a DOP shares one copy of it instead of owning a
+/// deep clone. Mutable access copies the DOP first if it is still shared
+/// (`Arc::make_mut`), so edits through one reference never leak into others.
+/// Serializes, compares and prints exactly like the DOP itself.
+#[derive(Clone, PartialEq)]
+pub struct DopRef(Arc<Dop>);
+
+impl DopRef {
+    pub fn new(dop: Dop) -> Self {
+        Self(Arc::new(dop))
+    }
+
+    /// Whether both references point at the same shared DOP.
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Arc::ptr_eq(&a.0, &b.0)
+    }
+
+    /// The DOP, cloned only if it is still shared.
+    pub fn into_inner(self) -> Dop {
+        Arc::unwrap_or_clone(self.0)
+    }
+}
+
+impl From<Dop> for DopRef {
+    fn from(dop: Dop) -> Self {
+        Self::new(dop)
+    }
+}
+
+impl Deref for DopRef {
+    type Target = Dop;
+
+    fn deref(&self) -> &Dop {
+        &self.0
+    }
+}
+
+impl DerefMut for DopRef {
+    fn deref_mut(&mut self) -> &mut Dop {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+impl AsRef<Dop> for DopRef {
+    fn as_ref(&self) -> &Dop {
+        &self.0
+    }
+}
+
+impl AsMut<Dop> for DopRef {
+    fn as_mut(&mut self) -> &mut Dop {
+        self
+    }
+}
+
+impl fmt::Debug for DopRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl Serialize for DopRef {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DopRef {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Dop::deserialize(deserializer).map(Self::new)
+    }
+}
+
 /// Maps to FBS SpecificDOPData union
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DopData {
@@ -285,8 +382,8 @@ pub enum DopData {
 /// Maps to FBS Field
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Field {
-    pub basic_structure: Option<Box<Dop>>,
-    pub env_data_desc: Option<Box<Dop>>,
+    pub basic_structure: Option<DopRef>,
+    pub env_data_desc: Option<DopRef>,
     pub is_visible: bool,
 }
 
@@ -295,23 +392,23 @@ pub struct Field {
 pub struct SwitchKey {
     pub byte_position: u32,
     pub bit_position: Option<u32>,
-    pub dop: Box<Dop>,
+    pub dop: DopRef,
 }
 
 /// Maps to FBS DefaultCase
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DefaultCase {
-    pub short_name: String,
+    pub short_name: Name,
     pub long_name: Option<LongName>,
-    pub structure: Option<Box<Dop>>,
+    pub structure: Option<DopRef>,
 }
 
 /// Maps to FBS Case
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Case {
-    pub short_name: String,
+    pub short_name: Name,
     pub long_name: Option<LongName>,
-    pub structure: Option<Box<Dop>>,
+    pub structure: Option<DopRef>,
     pub lower_limit: Option<Limit>,
     pub upper_limit: Option<Limit>,
 }
@@ -321,7 +418,7 @@ pub struct Case {
 pub struct DetermineNumberOfItems {
     pub byte_position: u32,
     pub bit_position: u32,
-    pub dop: Box<Dop>,
+    pub dop: DopRef,
 }
 
 // --- Type system ---
@@ -449,7 +546,7 @@ pub struct ScaleConstr {
 /// Maps to FBS Unit
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Unit {
-    pub short_name: String,
+    pub short_name: Name,
     pub display_name: String,
     pub factor_si_to_unit: Option<f64>,
     pub offset_si_to_unit: Option<f64>,
@@ -459,7 +556,7 @@ pub struct Unit {
 /// Maps to FBS PhysicalDimension
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PhysicalDimension {
-    pub short_name: String,
+    pub short_name: Name,
     pub long_name: Option<LongName>,
     pub length_exp: Option<i32>,
     pub mass_exp: Option<i32>,
@@ -475,7 +572,7 @@ pub struct PhysicalDimension {
 /// Maps to FBS DTC
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct Dtc {
-    pub short_name: String,
+    pub short_name: Name,
     pub trouble_code: u32,
     pub display_trouble_code: String,
     pub text: Option<Text>,
@@ -489,12 +586,12 @@ pub struct Dtc {
 /// Maps to FBS TableDop
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct TableDop {
-    pub semantic: String,
-    pub short_name: String,
+    pub semantic: Name,
+    pub short_name: Name,
     pub long_name: Option<LongName>,
     pub key_label: String,
     pub struct_label: String,
-    pub key_dop: Option<Box<Dop>>,
+    pub key_dop: Option<DopRef>,
     pub rows: Vec<TableRow>,
     pub diag_comm_connectors: Vec<TableDiagCommConnector>,
     pub sdgs: Option<Sdgs>,
@@ -503,18 +600,18 @@ pub struct TableDop {
 /// Maps to FBS TableRow
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct TableRow {
-    pub short_name: String,
+    pub short_name: Name,
     pub long_name: Option<LongName>,
     pub key: String,
-    pub dop: Option<Box<Dop>>,
-    pub structure: Option<Box<Dop>>,
+    pub dop: Option<DopRef>,
+    pub structure: Option<DopRef>,
     pub sdgs: Option<Sdgs>,
     pub audience: Option<Audience>,
     pub funct_class_refs: Vec<FunctClass>,
     pub state_transition_refs: Vec<StateTransitionRef>,
     pub pre_condition_state_refs: Vec<PreConditionStateRef>,
     pub is_executable: bool,
-    pub semantic: String,
+    pub semantic: Name,
     pub is_mandatory: bool,
     pub is_final: bool,
 }
@@ -523,7 +620,7 @@ pub struct TableRow {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TableDiagCommConnector {
     pub diag_comm: DiagServiceOrJob,
-    pub semantic: String,
+    pub semantic: Name,
 }
 
 /// Maps to FBS DiagServiceOrJob union
@@ -560,7 +657,7 @@ pub struct ComParamSpec {
 /// Maps to FBS ProtStack
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ProtStack {
-    pub short_name: String,
+    pub short_name: Name,
     pub long_name: Option<LongName>,
     pub pdu_protocol_type: String,
     pub physical_link_type: String,
@@ -590,7 +687,7 @@ pub struct ComParamRef {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ComParam {
     pub com_param_type: ComParamType,
-    pub short_name: String,
+    pub short_name: Name,
     pub long_name: Option<LongName>,
     pub param_class: String,
     pub cp_type: ComParamStandardisationLevel,
@@ -604,7 +701,7 @@ pub struct ComParam {
 pub enum ComParamSpecificData {
     Regular {
         physical_default_value: String,
-        dop: Option<Box<Dop>>,
+        dop: Option<DopRef>,
     },
     Complex {
         com_params: Vec<ComParam>,
@@ -618,8 +715,8 @@ pub enum ComParamSpecificData {
 /// Maps to FBS StateChart
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StateChart {
-    pub short_name: String,
-    pub semantic: String,
+    pub short_name: Name,
+    pub semantic: Name,
     pub state_transitions: Vec<StateTransition>,
     pub start_state_short_name_ref: String,
     pub states: Vec<State>,
@@ -628,14 +725,14 @@ pub struct StateChart {
 /// Maps to FBS State
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct State {
-    pub short_name: String,
+    pub short_name: Name,
     pub long_name: Option<LongName>,
 }
 
 /// Maps to FBS StateTransition
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StateTransition {
-    pub short_name: String,
+    pub short_name: Name,
     pub source_short_name_ref: String,
     pub target_short_name_ref: String,
 }
@@ -672,7 +769,7 @@ pub struct ProgCode {
 /// Maps to FBS Library
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Library {
-    pub short_name: String,
+    pub short_name: Name,
     pub long_name: Option<LongName>,
     pub code_file: String,
     pub encryption: String,
@@ -683,11 +780,11 @@ pub struct Library {
 /// Maps to FBS JobParam
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct JobParam {
-    pub short_name: String,
+    pub short_name: Name,
     pub long_name: Option<LongName>,
     pub physical_default_value: String,
-    pub dop_base: Option<Box<Dop>>,
-    pub semantic: String,
+    pub dop_base: Option<DopRef>,
+    pub semantic: Name,
 }
 
 /// Maps to FBS UnitSpec
@@ -702,7 +799,7 @@ pub struct UnitSpec {
 /// Maps to FBS UnitGroup
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UnitGroup {
-    pub short_name: String,
+    pub short_name: Name,
     pub long_name: Option<LongName>,
     pub unit_refs: Vec<Unit>,
 }
@@ -786,14 +883,14 @@ pub struct Audience {
 /// Maps to FBS AdditionalAudience
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AdditionalAudience {
-    pub short_name: String,
+    pub short_name: Name,
     pub long_name: Option<LongName>,
 }
 
 /// Maps to FBS FunctClass
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FunctClass {
-    pub short_name: String,
+    pub short_name: Name,
 }
 
 // --- Enums ---
@@ -1065,7 +1162,111 @@ pub struct DataBlock {
     pub max_block_length: Option<u64>,
     pub security_level: Option<String>,
     pub session: Option<String>,
-    pub checksum_type: Option<String>,
+    pub checksum_type: Option<ChecksumType>,
+}
+
+/// Checksum algorithm the ECU uses to verify a transferred data block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChecksumType {
+    Crc16Ccitt,
+    Crc32,
+    Sha256,
+    /// OEM-specific algorithm producing `length` bytes, with free-form
+    /// parameters (polynomial, seed, key reference, ...).
+    Oem {
+        name: String,
+        length: u32,
+        parameters: BTreeMap<String, String>,
+    },
+}
+
+impl ChecksumType {
+    /// Names accepted for the standard algorithms.
+    pub const STANDARD_NAMES: [&'static str; 3] = ["crc16_ccitt", "crc32", "sha256"];
+
+    /// Parse a standard algorithm name (case-insensitive, `-` or `_`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "crc16_ccitt" => Some(ChecksumType::Crc16Ccitt),
+            "crc32" => Some(ChecksumType::Crc32),
+            "sha256" | "sha_256" => Some(ChecksumType::Sha256),
+            _ => None,
+        }
+    }
+
+    /// Algorithm name; OEM algorithms report their own name.
+    pub fn name(&self) -> &str {
+        match self {
+            ChecksumType::Crc16Ccitt => "crc16_ccitt",
+            ChecksumType::Crc32 => "crc32",
+            ChecksumType::Sha256 => "sha256",
+            ChecksumType::Oem { name, .. } => name,
+        }
+    }
+
+    /// Size of the checksum value in bytes.
+    pub fn result_length(&self) -> u32 {
+        match self {
+            ChecksumType::Crc16Ccitt => 2,
+            ChecksumType::Crc32 => 4,
+            ChecksumType::Sha256 => 32,
+            ChecksumType::Oem { length, .. } => *length,
+        }
+    }
+}
+
+// --- Transport ---
+
+/// How a tester reaches the ECU, taken from YAML `ecu.addressing` or the
+/// DoIP communication parameters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct TransportConfig {
+    pub doip: Option<DoipConfig>,
+}
+
+/// DoIP entity and logical addresses (ISO 13400-2).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct DoipConfig {
+    /// Host name or IP address of the DoIP entity.
+    pub ip: Option<String>,
+    pub port: Option<u16>,
+    pub logical_address: Option<u16>,
+    pub functional_address: Option<u16>,
+    pub tester_address: Option<u16>,
+    /// Entity identification, usually the entity's MAC address.
+    pub eid: Option<[u8; 6]>,
+    /// Group identification shared by the entities of one vehicle.
+    pub gid: Option<[u8; 6]>,
+}
+
+impl DoipConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// ECU identification: part number, supplier, hardware and software
+/// versions, and the DIDs a tester reads them from. Taken from the YAML
+/// `identification` section.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EcuIdentification {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part_number: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supplier_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hardware_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub software_version: Option<String>,
+    /// Identification DIDs by what they report, e.g. `vin` -> 0xF190.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dids: BTreeMap<String, u16>,
+}
+
+impl EcuIdentification {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 /// A named type definition for YAML roundtrip.
@@ -1076,8 +1277,14 @@ pub struct TypeDefinition {
     pub name: String,
     pub base: String,
     pub bit_length: Option<u32>,
+    #[serde(default)]
+    pub bit_mask: Option<u64>,
+    #[serde(default)]
+    pub condensed: Option<bool>,
     pub min_length: Option<u32>,
     pub max_length: Option<u32>,
+    #[serde(default)]
+    pub length_prefix_bits: Option<u32>,
     pub enum_values_json: Option<String>,
     pub description: Option<String>,
 }
@@ -1,11 +1,11 @@
+use crate::layout::{ByteSizeMismatch, check_byte_sizes};
+use crate::short_names::{ShortNameIssue, check_short_names};
 use crate::types::DiagDatabase;
 use std::collections::HashSet;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum ValidationError {
-    #[error("duplicate service name '{0}' in variant '{1}'")]
-    DuplicateServiceName(String, String),
     #[error("duplicate DOP name '{0}' in service '{1}'")]
     DuplicateDopName(String, String),
     #[error("empty ECU name")]
@@ -18,6 +18,12 @@ pub enum ValidationError {
     EmptyStateChart(String, String),
     #[error("variant '{0}' has no services")]
     EmptyVariant(String),
+    #[error("identification DID '{0}' (0x{1:04X}) is not defined")]
+    UnknownIdentificationDid(String, u16),
+    #[error("{0}")]
+    ShortName(ShortNameIssue),
+    #[error("{0}")]
+    ByteSize(ByteSizeMismatch),
 }
 
 /// Validate a DiagDatabase for structural consistency.
@@ -32,18 +38,12 @@ pub fn validate_database(db: &DiagDatabase) -> Result<(), Vec<ValidationError>>
         let layer = &variant.diag_layer;
         let vname = &layer.short_name;
 
-        // Duplicate service names
-        let mut service_names = HashSet::new();
-        for svc in &layer.diag_services {
-            let name = &svc.diag_comm.short_name;
-            if name.is_empty() {
-                errors.push(ValidationError::EmptyServiceName(vname.clone()));
-            } else if !service_names.insert(name.as_str()) {
-                errors.push(ValidationError::DuplicateServiceName(
-                    name.clone(),
-                    vname.clone(),
-                ));
-            }
+        if layer
+            .diag_services
+            .iter()
+            .any(|svc| svc.diag_comm.short_name.is_empty())
+        {
+            errors.push(ValidationError::EmptyServiceName(vname.to_string()));
         }
 
         // Variant with no services (warn via log, not an error)
@@ -58,8 +58,8 @@ pub fn validate_database(db: &DiagDatabase) -> Result<(), Vec<ValidationError>>
         for sc in &layer.state_charts {
             if sc.states.is_empty() {
                 errors.push(ValidationError::EmptyStateChart(
-                    sc.short_name.clone(),
-                    vname.clone(),
+                    sc.short_name.to_string(),
+                    vname.to_string(),
                 ));
             }
         }
@@ -76,6 +76,33 @@ pub fn validate_database(db: &DiagDatabase) -> Result<(), Vec<ValidationError>>
         }
     }
 
+    // ODX short-name charset, length and uniqueness
+    errors.extend(
+        check_short_names(db)
+            .into_iter()
+            .map(ValidationError::ShortName),
+    );
+
+    // Declared structure and item sizes must match their layout
+    errors.extend(
+        check_byte_sizes(db)
+            .into_iter()
+            .map(ValidationError::ByteSize),
+    );
+
+    // Identification DIDs must be served by some layer
+    if let Some(ident) = &db.identification {
+        let used = crate::ids::used_ids(db);
+        for (role, did) in &ident.dids {
+            if !used.contains_key(&(crate::ids::IdKind::Did, u32::from(*did))) {
+                errors.push(ValidationError::UnknownIdentificationDid(
+                    role.clone(),
+                    *did,
+                ));
+            }
+        }
+    }
+
     if errors.is_empty() {
         Ok(())
     } else {
@@ -0,0 +1,350 @@
+//! The period and model years a database may be used for.
+//!
+//! Stored as a `validity` SDG on the base variant with one SD per bound
+//! (SI `valid_from` or `valid_until`) and per model year (SI `model_year`),
+//! so it travels through YAML, ODX and MDD. MDD output also records it in
+//! the container header, so distribution tooling can drop expired databases
+//! without decoding them.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{DiagDatabase, Sd, SdOrSdg, Sdg, Sdgs};
+
+/// SDG caption under which the validity is stored.
+pub const VALIDITY_CAPTION: &str = "validity";
+
+/// A calendar date, written `YYYY-MM-DD`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    /// Today's date in UTC.
+    pub fn today() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Self::from_days(secs / 86_400)
+    }
+
+    /// The date `days` days after 1970-01-01 (proleptic Gregorian calendar).
+    fn from_days(days: u64) -> Self {
+        let z = days + 719_468;
+        let era = z / 146_097;
+        let doe = z % 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + u64::from(month <= 2);
+        Self {
+            year: u16::try_from(year).unwrap_or(u16::MAX),
+            month: month as u8,
+            day: day as u8,
+        }
+    }
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid date '{s}' (expected YYYY-MM-DD)");
+        let mut parts = s.trim().splitn(3, '-');
+        let mut next = |len: usize| {
+            parts
+                .next()
+                .filter(|p| p.len() == len && p.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|p| p.parse::<u16>().ok())
+                .ok_or_else(invalid)
+        };
+        let (year, month, day) = (next(4)?, next(2)?, next(2)?);
+        let month = u8::try_from(month).ok().filter(|m| (1..=12).contains(m));
+        let month = month.ok_or_else(invalid)?;
+        let day = u8::try_from(day)
+            .ok()
+            .filter(|d| (1..=days_in_month(year, month)).contains(d))
+            .ok_or_else(invalid)?;
+        Ok(Self { year, month, day })
+    }
+}
+
+impl TryFrom<String> for Date {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Date> for String {
+    fn from(date: Date) -> Self {
+        date.to_string()
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Validity {
+    /// First day the database may be used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_from: Option<Date>,
+    /// Last day the database may be used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<Date>,
+    /// Vehicle model years the database applies to; empty for all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub model_years: Vec<u16>,
+}
+
+/// Where a date lies relative to a validity window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidityStatus {
+    Valid,
+    NotYetValid(Date),
+    Expired(Date),
+}
+
+impl fmt::Display for ValidityStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Valid => f.write_str("valid"),
+            Self::NotYetValid(from) => write!(f, "not valid before {from}"),
+            Self::Expired(until) => write!(f, "expired on {until}"),
+        }
+    }
+}
+
+impl Validity {
+    pub fn is_empty(&self) -> bool {
+        self.valid_from.is_none() && self.valid_until.is_none() && self.model_years.is_empty()
+    }
+
+    /// Whether `date` lies inside the window; both bounds are inclusive.
+    pub fn status_on(&self, date: Date) -> ValidityStatus {
+        match (self.valid_from, self.valid_until) {
+            (Some(from), _) if date < from => ValidityStatus::NotYetValid(from),
+            (_, Some(until)) if date > until => ValidityStatus::Expired(until),
+            _ => ValidityStatus::Valid,
+        }
+    }
+
+    pub fn is_valid_on(&self, date: Date) -> bool {
+        self.status_on(date) == ValidityStatus::Valid
+    }
+
+    pub fn applies_to_model_year(&self, year: u16) -> bool {
+        self.model_years.is_empty() || self.model_years.contains(&year)
+    }
+}
+
+impl fmt::Display for Validity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bound = |d: Option<Date>| d.map_or_else(|| "open".to_string(), |d| d.to_string());
+        write!(
+            f,
+            "{} to {}",
+            bound(self.valid_from),
+            bound(self.valid_until)
+        )?;
+        if !self.model_years.is_empty() {
+            let years: Vec<String> = self.model_years.iter().map(u16::to_string).collect();
+            write!(f, ", model years {}", years.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&mdd_format::fileformat::Validity> for Validity {
+    /// Unreadable dates in the header are treated as open bounds.
+    fn from(header: &mdd_format::fileformat::Validity) -> Self {
+        let date = |d: &Option<String>| d.as_deref().and_then(|d| d.parse().ok());
+        Self {
+            valid_from: date(&header.valid_from),
+            valid_until: date(&header.valid_until),
+            model_years: header
+                .model_years
+                .iter()
+                .filter_map(|&y| u16::try_from(y).ok())
+                .collect(),
+        }
+    }
+}
+
+impl From<&Validity> for mdd_format::fileformat::Validity {
+    fn from(validity: &Validity) -> Self {
+        Self {
+            valid_from: validity.valid_from.map(|d| d.to_string()),
+            valid_until: validity.valid_until.map(|d| d.to_string()),
+            model_years: validity.model_years.iter().map(|&y| u32::from(y)).collect(),
+        }
+    }
+}
+
+/// Read the validity stored in `sdgs`, if any. Unreadable entries are
+/// skipped.
+pub fn validity_of(sdgs: Option<&Sdgs>) -> Option<Validity> {
+    let sdg = sdgs?
+        .sdgs
+        .iter()
+        .find(|sdg| sdg.caption_sn == VALIDITY_CAPTION)?;
+    let mut validity = Validity::default();
+    for sd in &sdg.sds {
+        let SdOrSdg::Sd(sd) = sd else { continue };
+        match sd.si.as_str() {
+            "valid_from" => validity.valid_from = sd.value.parse().ok(),
+            "valid_until" => validity.valid_until = sd.value.parse().ok(),
+            "model_year" => validity
+                .model_years
+                .extend(sd.value.trim().parse::<u16>().ok()),
+            _ => {}
+        }
+    }
+    Some(validity)
+}
+
+/// The validity of `db`, stored on its base variant.
+pub fn validity(db: &DiagDatabase) -> Option<Validity> {
+    let base = db.variants.iter().find(|v| v.is_base_variant)?;
+    validity_of(base.diag_layer.sdgs.as_ref()).filter(|v| !v.is_empty())
+}
+
+/// Store `validity` in `sdgs`, replacing any previous one.
+pub fn set_validity(sdgs: &mut Option<Sdgs>, validity: &Validity) {
+    let sd = |si: &str, value: String| {
+        SdOrSdg::Sd(Sd {
+            value,
+            si: si.to_string(),
+            ti: String::new(),
+        })
+    };
+    let sds = validity
+        .valid_from
+        .map(|d| sd("valid_from", d.to_string()))
+        .into_iter()
+        .chain(
+            validity
+                .valid_until
+                .map(|d| sd("valid_until", d.to_string())),
+        )
+        .chain(
+            validity
+                .model_years
+                .iter()
+                .map(|y| sd("model_year", y.to_string())),
+        )
+        .collect();
+    let sdgs = &mut sdgs.get_or_insert_with(|| Sdgs { sdgs: vec![] }).sdgs;
+    sdgs.retain(|sdg| sdg.caption_sn != VALIDITY_CAPTION);
+    sdgs.push(Sdg {
+        caption_sn: VALIDITY_CAPTION.into(),
+        sds,
+        si: String::new(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Variant;
+
+    fn date(s: &str) -> Date {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_date_parsing() {
+        assert_eq!(
+            date("2024-02-29"),
+            Date {
+                year: 2024,
+                month: 2,
+                day: 29
+            }
+        );
+        for bad in [
+            "2023-02-29",
+            "2024-13-01",
+            "2024-1-01",
+            "24-01-01",
+            "2024-01-01x",
+        ] {
+            assert!(bad.parse::<Date>().is_err(), "{bad}");
+        }
+        assert_eq!(Date::from_days(19_782).to_string(), "2024-02-29");
+        assert!(Date::today() > date("2024-01-01"));
+    }
+
+    #[test]
+    fn test_validity_window() {
+        let validity = Validity {
+            valid_from: Some(date("2025-01-01")),
+            valid_until: Some(date("2026-06-30")),
+            model_years: vec![2025, 2026],
+        };
+        assert_eq!(
+            validity.status_on(date("2024-12-31")),
+            ValidityStatus::NotYetValid(date("2025-01-01"))
+        );
+        assert!(validity.is_valid_on(date("2026-06-30")));
+        assert_eq!(
+            validity.status_on(date("2026-07-01")).to_string(),
+            "expired on 2026-06-30"
+        );
+        assert!(validity.applies_to_model_year(2026));
+        assert!(!validity.applies_to_model_year(2027));
+        assert!(Validity::default().applies_to_model_year(2027));
+        assert_eq!(
+            validity.to_string(),
+            "2025-01-01 to 2026-06-30, model years 2025, 2026"
+        );
+    }
+
+    #[test]
+    fn test_validity_roundtrip_through_sdgs() {
+        let mut db = DiagDatabase {
+            variants: vec![Variant {
+                is_base_variant: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert_eq!(validity(&db), None);
+        let window = Validity {
+            valid_from: None,
+            valid_until: Some(date("2030-12-31")),
+            model_years: vec![2029],
+        };
+        let sdgs = &mut db.variants[0].diag_layer.sdgs;
+        set_validity(sdgs, &Validity::default());
+        set_validity(sdgs, &window);
+        assert_eq!(sdgs.as_ref().unwrap().sdgs.len(), 1);
+        assert_eq!(validity(&db), Some(window));
+    }
+}
@@ -0,0 +1,354 @@
+//! Runtime variant identification.
+//!
+//! ECU variants carry `VariantPattern`s: a variant matches when all matching
+//! parameters of at least one of its patterns match. A matching parameter
+//! names an identification service, an out-param of its positive response and
+//! the expected physical value. [`evaluate`] issues those services through a
+//! caller-supplied transport, decodes the out-params with [`crate::codec`]
+//! and returns the first matching variant, so testers do not have to
+//! reimplement the lookup against the raw IR.
+
+use std::collections::HashMap;
+
+use crate::codec::value::Value;
+use crate::codec::{RuntimeError, decode_params, encode_request};
+use crate::numbers::parse_hex_bytes;
+use crate::types::{DiagDatabase, DiagService, Name, Param, ParamType, Variant};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DetectError {
+    #[error("variant {variant}: identification service {service} not found")]
+    ServiceNotFound { variant: String, service: String },
+    #[error("service {service}: out-param {param} not found in positive response")]
+    ParamNotFound { service: String, param: String },
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+}
+
+/// Identify the ECU variant.
+///
+/// `send` is called at most once per identification service with the
+/// service to issue (see [`request_bytes`] for its coded request) and returns
+/// the raw positive response, or `None` if the ECU did not answer positively.
+/// Variants are tried in database order; base variants are never returned.
+pub fn evaluate<F>(db: &DiagDatabase, mut send: F) -> Result<Option<&Variant>, DetectError>
+where
+    F: FnMut(&DiagService) -> Option<Vec<u8>>,
+{
+    let mut responses: HashMap<Name, Option<Vec<u8>>> = HashMap::new();
+
+    for variant in db.variants.iter().filter(|v| !v.is_base_variant) {
+        for pattern in &variant.variant_patterns {
+            if pattern.matching_parameters.is_empty() {
+                continue;
+            }
+            let mut all_match = true;
+            for mp in &pattern.matching_parameters {
+                let service = resolve_service(db, variant, &mp.diag_service)?;
+                let out_param = resolve_out_param(service, &mp.out_param)?;
+                let response = responses
+                    .entry(service.diag_comm.short_name.clone())
+                    .or_insert_with(|| send(service));
+                let Some(response) = response else {
+                    all_match = false;
+                    break;
+                };
+                let value = decode_out_param(out_param, response)?;
+                if !value.is_some_and(|v| matches(&v, &mp.expected_value)) {
+                    all_match = false;
+                    break;
+                }
+            }
+            if all_match {
+                return Ok(Some(variant));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Encode the request of a service whose parameters are all coded constants
+/// or have a physical default, as identification requests usually do.
+/// Returns `None` if any parameter needs a value from the caller.
+pub fn request_bytes(service: &DiagService) -> Option<Vec<u8>> {
+    encode_request(service, &[]).ok()
+}
+
+/// Decode the out-param at its position in `response`. `Ok(None)` means the
+/// response is too short to contain it, or holds a value its DOP cannot
+/// take; neither matches any expected value.
+fn decode_out_param(param: &Param, response: &[u8]) -> Result<Option<Value>, DetectError> {
+    match decode_params(std::slice::from_ref(param), response) {
+        Ok(mut values) => Ok(values.pop().map(|v| v.value)),
+        Err(RuntimeError::Truncated { .. } | RuntimeError::InvalidValue { .. }) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Whether a decoded value equals the expected value of a matching
+/// parameter, which the IR stores as text.
+fn matches(value: &Value, expected: &str) -> bool {
+    let expected = expected.trim();
+    match (value, Value::parse(expected)) {
+        (Value::Int(v), Value::Int(e)) => *v == e,
+        (Value::Int(_) | Value::Float(_), e) => value
+            .as_f64()
+            .zip(e.as_f64())
+            .is_some_and(|(v, e)| (v - e).abs() < 1e-9),
+        (Value::Text(v), _) => v.trim() == expected,
+        (Value::Bytes(v), _) => parse_hex_bytes(expected).is_some_and(|e| &e == v),
+        _ => false,
+    }
+}
+
+/// The matching parameter may only carry the service name (ODX short-name
+/// references); look the full definition up in the variant, then in the base
+/// variants.
+fn resolve_service<'a>(
+    db: &'a DiagDatabase,
+    variant: &'a Variant,
+    service: &'a DiagService,
+) -> Result<&'a DiagService, DetectError> {
+    if service.request.is_some() {
+        return Ok(service);
+    }
+    let name = &service.diag_comm.short_name;
+    std::iter::once(variant)
+        .chain(db.variants.iter().filter(|v| v.is_base_variant))
+        .flat_map(|v| &v.diag_layer.diag_services)
+        .find(|s| &s.diag_comm.short_name == name && s.request.is_some())
+        .ok_or_else(|| DetectError::ServiceNotFound {
+            variant: variant.diag_layer.short_name.to_string(),
+            service: name.to_string(),
+        })
+}
+
+fn resolve_out_param<'a>(
+    service: &'a DiagService,
+    param: &'a Param,
+) -> Result<&'a Param, DetectError> {
+    if param.specific_data.is_some() {
+        return Ok(param);
+    }
+    let mut params = service.pos_responses.iter().flat_map(|r| &r.params);
+    let found = if param.short_name.is_empty() {
+        // Some converters drop the out-param reference; fall back to the
+        // response's only VALUE param.
+        let mut values = params.filter(|p| p.param_type == ParamType::Value);
+        match (values.next(), values.next()) {
+            (Some(p), None) => Some(p),
+            _ => None,
+        }
+    } else {
+        params.find(|p| p.short_name == param.short_name)
+    };
+    found.ok_or_else(|| DetectError::ParamNotFound {
+        service: service.diag_comm.short_name.to_string(),
+        param: param.short_name.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        DataType, DiagCodedType, DiagCodedTypeData, DiagCodedTypeName, DiagComm, DiagLayer, Dop,
+        DopData, DopRef, DopType, MatchingParameter, ParamData, Request, Response, ResponseType,
+        Termination, VariantPattern,
+    };
+
+    fn coded_type(data_type: DataType, bit_length: u32) -> DiagCodedType {
+        DiagCodedType {
+            type_name: DiagCodedTypeName::StandardLengthType,
+            base_data_type: data_type,
+            is_high_low_byte_order: true,
+            specific_data: Some(DiagCodedTypeData::StandardLength {
+                bit_length,
+                bit_mask: vec![],
+                condensed: false,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn coded_const(name: &str, pos: u32, value: u32, bits: u32) -> Param {
+        Param {
+            short_name: name.into(),
+            byte_position: Some(pos),
+            specific_data: Some(ParamData::CodedConst {
+                coded_value: value.to_string(),
+                diag_coded_type: coded_type(DataType::AUint32, bits),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn value_param(name: &str, pos: u32, dct: DiagCodedType) -> Param {
+        Param {
+            short_name: name.into(),
+            param_type: ParamType::Value,
+            byte_position: Some(pos),
+            specific_data: Some(ParamData::Value {
+                physical_default_value: String::new(),
+                dop: DopRef::new(Dop {
+                    dop_type: DopType::Regular,
+                    short_name: "DOP".into(),
+                    sdgs: None,
+                    specific_data: Some(DopData::NormalDop {
+                        compu_method: None,
+                        diag_coded_type: Some(dct),
+                        physical_type: None,
+                        internal_constr: None,
+                        unit_ref: None,
+                        phys_constr: None,
+                    }),
+                }),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn ident_service(out: Param) -> DiagService {
+        DiagService {
+            diag_comm: DiagComm {
+                short_name: "Identification_Read".into(),
+                ..Default::default()
+            },
+            request: Some(Request {
+                params: vec![
+                    coded_const("SID_RQ", 0, 0x22, 8),
+                    coded_const("DID_RQ", 1, 0xF100, 16),
+                ],
+                sdgs: None,
+            }),
+            pos_responses: vec![Response {
+                response_type: ResponseType::PosResponse,
+                params: vec![coded_const("SID_PR", 0, 0x62, 8), out],
+                sdgs: None,
+                pos_response_suffix: None,
+                audience: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// A variant whose pattern only references the service and out-param by
+    /// name, as parsed from ODX.
+    fn variant(name: &str, expected: &str) -> Variant {
+        Variant {
+            diag_layer: DiagLayer {
+                short_name: name.into(),
+                ..Default::default()
+            },
+            variant_patterns: vec![VariantPattern {
+                matching_parameters: vec![MatchingParameter {
+                    expected_value: expected.into(),
+                    diag_service: Box::new(DiagService {
+                        diag_comm: DiagComm {
+                            short_name: "Identification_Read".into(),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }),
+                    out_param: Box::new(Param {
+                        short_name: "Identification".into(),
+                        ..Default::default()
+                    }),
+                    use_physical_addressing: None,
+                }],
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn database(out: Param) -> DiagDatabase {
+        let base = Variant {
+            diag_layer: DiagLayer {
+                short_name: "Base".into(),
+                diag_services: vec![ident_service(out)],
+                ..Default::default()
+            },
+            is_base_variant: true,
+            ..Default::default()
+        };
+        DiagDatabase {
+            variants: vec![base, variant("Boot", "0xFF0000"), variant("App", "257")],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_request_bytes() {
+        let mut service = ident_service(coded_const("X", 3, 0, 8));
+        assert_eq!(request_bytes(&service), Some(vec![0x22, 0xF1, 0x00]));
+        let params = &mut service.request.as_mut().unwrap().params;
+        params.push(value_param("Counter", 3, coded_type(DataType::AUint32, 16)));
+        assert_eq!(request_bytes(&service), None);
+    }
+
+    #[test]
+    fn test_evaluate_picks_matching_variant_and_caches_requests() {
+        let db = database(value_param(
+            "Identification",
+            3,
+            coded_type(DataType::AUint32, 24),
+        ));
+        let mut calls = 0;
+        let found = evaluate(&db, |service| {
+            calls += 1;
+            assert_eq!(request_bytes(service).unwrap(), [0x22, 0xF1, 0x00]);
+            Some(vec![0x62, 0xF1, 0x00, 0x00, 0x01, 0x01])
+        })
+        .unwrap();
+        assert_eq!(found.unwrap().diag_layer.short_name, "App");
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_evaluate_no_match() {
+        let db = database(value_param(
+            "Identification",
+            3,
+            coded_type(DataType::AUint32, 24),
+        ));
+        assert!(evaluate(&db, |_| None).unwrap().is_none());
+        // Too short to contain the out-param.
+        assert!(evaluate(&db, |_| Some(vec![0x62, 0xF1])).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_evaluate_text_values() {
+        let mut dct = coded_type(DataType::AAsciiString, 0);
+        dct.type_name = DiagCodedTypeName::MinMaxLengthType;
+        dct.specific_data = Some(DiagCodedTypeData::MinMax {
+            min_length: 1,
+            max_length: Some(8),
+            termination: Termination::Zero,
+        });
+        let mut db = database(value_param("Identification", 3, dct));
+        db.variants[2].variant_patterns[0].matching_parameters[0].expected_value = "APP".into();
+        let found = evaluate(&db, |_| Some(b"\x62\xF1\x00APP\0rest".to_vec())).unwrap();
+        assert_eq!(found.unwrap().diag_layer.short_name, "App");
+    }
+
+    #[test]
+    fn test_evaluate_reports_unknown_service() {
+        let mut db = database(value_param(
+            "Identification",
+            3,
+            coded_type(DataType::AUint32, 24),
+        ));
+        db.variants[0].diag_layer.diag_services.clear();
+        let err = evaluate(&db, |_| None).unwrap_err();
+        assert!(matches!(err, DetectError::ServiceNotFound { .. }), "{err}");
+    }
+
+    #[test]
+    fn test_expected_values() {
+        assert!(matches(&Value::Int(257), "0x101"));
+        assert!(matches(&Value::Float(1.5), " 1.5 "));
+        assert!(!matches(&Value::Int(1), "2"));
+        assert!(matches(&Value::Bytes(vec![0xF1, 0x00]), "F1 00"));
+        assert!(matches(&Value::from("APP"), "APP"));
+    }
+}
@@ -0,0 +1,194 @@
+//! Borrowed, filtered views of a database.
+//!
+//! [`filter_by_audience`](crate::filter_by_audience) and friends rewrite a
+//! database in place, so answering a query for one audience means cloning
+//! the whole database first. A [`DatabaseView`] applies the same filters
+//! while iterating instead and hands out references into the database. Only
+//! [`DatabaseView::to_database`] builds a copy, and only when a filter is set.
+
+use std::borrow::Cow;
+
+use crate::filter::AudienceFilter;
+use crate::types::{DiagDatabase, DiagLayer, DiagService, FunctionalGroup, SingleEcuJob, Variant};
+
+/// A database seen through an audience and a variant selection.
+#[derive(Debug, Clone, Copy)]
+pub struct DatabaseView<'a> {
+    db: &'a DiagDatabase,
+    audience: Option<&'a AudienceFilter>,
+    variants: Option<&'a [&'a str]>,
+}
+
+impl<'a> DatabaseView<'a> {
+    /// A view of the whole database.
+    pub fn new(db: &'a DiagDatabase) -> Self {
+        Self {
+            db,
+            audience: None,
+            variants: None,
+        }
+    }
+
+    /// Hide the services and jobs not matching `audience`, with the rules
+    /// of [`filter_by_audience`](crate::filter_by_audience).
+    #[must_use]
+    pub fn with_audience(mut self, audience: &'a AudienceFilter) -> Self {
+        self.audience = Some(audience);
+        self
+    }
+
+    /// Hide all variants but the named ones.
+    #[must_use]
+    pub fn with_variants(mut self, names: &'a [&'a str]) -> Self {
+        self.variants = Some(names);
+        self
+    }
+
+    /// The unfiltered database behind the view.
+    pub fn database(&self) -> &'a DiagDatabase {
+        self.db
+    }
+
+    /// Whether the view hides anything.
+    pub fn is_filtered(&self) -> bool {
+        self.audience.is_some() || self.variants.is_some()
+    }
+
+    pub fn variants(&self) -> impl Iterator<Item = &'a Variant> + 'a {
+        let selected = self.variants;
+        self.db.variants.iter().filter(move |v| {
+            selected.is_none_or(|names| names.contains(&v.diag_layer.short_name.as_str()))
+        })
+    }
+
+    pub fn variant(&self, name: &str) -> Option<&'a Variant> {
+        self.variants().find(|v| v.diag_layer.short_name == name)
+    }
+
+    pub fn functional_groups(&self) -> impl Iterator<Item = &'a FunctionalGroup> + 'a {
+        self.db.functional_groups.iter()
+    }
+
+    /// The services of `layer` visible in the view.
+    pub fn services(&self, layer: &'a DiagLayer) -> impl Iterator<Item = &'a DiagService> + 'a {
+        let audience = self.audience;
+        layer
+            .diag_services
+            .iter()
+            .filter(move |svc| audience.is_none_or(|a| a.matches(&svc.diag_comm.audience)))
+    }
+
+    /// The single-ECU jobs of `layer` visible in the view.
+    pub fn single_ecu_jobs(
+        &self,
+        layer: &'a DiagLayer,
+    ) -> impl Iterator<Item = &'a SingleEcuJob> + 'a {
+        let audience = self.audience;
+        layer
+            .single_ecu_jobs
+            .iter()
+            .filter(move |job| audience.is_none_or(|a| a.matches(&job.diag_comm.audience)))
+    }
+
+    /// The visible service `name` (short name or alias, in any case) of the
+    /// visible variant `variant`.
+    pub fn service(&self, variant: &str, name: &str) -> Option<&'a DiagService> {
+        let layer = &self.variant(variant)?.diag_layer;
+        crate::aliases::find_by_name(self.services(layer), name, |svc| &svc.diag_comm)
+    }
+
+    /// The database as the view shows it: borrowed when nothing is hidden,
+    /// else a filtered copy.
+    pub fn to_database(&self) -> Cow<'a, DiagDatabase> {
+        if !self.is_filtered() {
+            return Cow::Borrowed(self.db);
+        }
+        let mut db = self.db.clone();
+        if let Some(names) = self.variants {
+            db.variants
+                .retain(|v| names.contains(&v.diag_layer.short_name.as_str()));
+        }
+        if let Some(audience) = self.audience {
+            crate::filter_by_audience(&mut db, audience);
+        }
+        Cow::Owned(db)
+    }
+
+    /// FlatBuffers encoding of [`to_database`](Self::to_database).
+    pub fn to_flatbuffers(&self) -> Vec<u8> {
+        crate::ir_to_flatbuffers(&self.to_database())
+    }
+}
+
+impl<'a> From<&'a DiagDatabase> for DatabaseView<'a> {
+    fn from(db: &'a DiagDatabase) -> Self {
+        Self::new(db)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{service, variant};
+    use crate::types::{AdditionalAudience, Audience};
+
+    /// A service enabled for the audiences `enabled`, for everyone if empty.
+    fn service_for(name: &str, enabled: &[&str]) -> DiagService {
+        let mut svc = service(name, "");
+        svc.diag_comm.audience = (!enabled.is_empty()).then(|| Audience {
+            enabled_audiences: enabled
+                .iter()
+                .map(|a| AdditionalAudience {
+                    short_name: (*a).into(),
+                    long_name: None,
+                })
+                .collect(),
+            ..Default::default()
+        });
+        svc
+    }
+
+    #[test]
+    fn test_view_filters_without_copying() {
+        let db = DiagDatabase {
+            variants: vec![
+                variant(
+                    "Base",
+                    vec![
+                        service_for("Read", &[]),
+                        service_for("Flash", &["Development"]),
+                    ],
+                ),
+                variant("App", vec![service_for("Read", &[])]),
+            ],
+            ..Default::default()
+        };
+        let all = DatabaseView::new(&db);
+        assert!(matches!(all.to_database(), Cow::Borrowed(_)));
+        assert_eq!(all.to_flatbuffers(), crate::ir_to_flatbuffers(&db));
+
+        let names = ["Base"];
+        let aftermarket = AudienceFilter::single("Aftermarket");
+        let view = all.with_audience(&aftermarket).with_variants(&names);
+        let base = &view.variant("Base").unwrap().diag_layer;
+        let visible: Vec<_> = view
+            .services(base)
+            .map(|s| s.diag_comm.short_name.as_str())
+            .collect();
+        assert_eq!(visible, ["Read"]);
+        assert!(view.variant("App").is_none());
+        assert!(view.service("Base", "Flash").is_none());
+        let read = view.service("Base", "Read").unwrap();
+        assert!(std::ptr::eq(
+            read,
+            &raw const db.variants[0].diag_layer.diag_services[0]
+        ));
+
+        let mut expected = DiagDatabase {
+            variants: vec![db.variants[0].clone()],
+            ..db.clone()
+        };
+        crate::filter_by_audience(&mut expected, &aftermarket);
+        assert_eq!(view.to_database().into_owned(), expected);
+    }
+}
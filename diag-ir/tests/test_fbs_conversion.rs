@@ -22,6 +22,7 @@ fn make_service(name: &str) -> DiagService {
             is_mandatory: false,
             is_executable: true,
             is_final: false,
+            description: None,
         },
         request: Some(Request {
             params: vec![Param {
@@ -63,7 +64,7 @@ fn make_service(name: &str) -> DiagService {
                 bit_position: None,
                 specific_data: Some(ParamData::Value {
                     physical_default_value: "0".into(),
-                    dop: Box::new(Dop {
+                    dop: DopRef::new(Dop {
                         dop_type: DopType::Regular,
                         short_name: "VehicleSpeedDOP".into(),
                         sdgs: None,
@@ -130,6 +131,8 @@ fn make_service(name: &str) -> DiagService {
                 }),
             }],
             sdgs: None,
+            pos_response_suffix: None,
+            audience: None,
         }],
         neg_responses: vec![],
         is_cyclic: false,
@@ -178,6 +181,7 @@ fn make_test_database() -> DiagDatabase {
                         si: "spec".into(),
                     }],
                 }),
+                description: None,
             },
             is_base_variant: true,
             variant_patterns: vec![],
@@ -200,6 +204,8 @@ fn make_test_database() -> DiagDatabase {
         }],
         memory: None,
         type_definitions: vec![],
+        transport: None,
+        identification: None,
     }
 }
 
@@ -219,6 +225,100 @@ fn roundtrip_full_database() {
     pretty_assertions::assert_eq!(db, db2);
 }
 
+#[test]
+fn selected_variants_are_converted_alone() {
+    let mut db = make_test_database();
+    let mut app = db.variants[0].clone();
+    app.diag_layer.short_name = "AppVariant".into();
+    app.is_base_variant = false;
+    db.variants.push(app);
+    let fbs_bytes = ir_to_flatbuffers(&db);
+
+    let all = flatbuffers_to_ir(&fbs_bytes).unwrap();
+    let names: Vec<_> = all
+        .variants
+        .iter()
+        .map(|v| v.diag_layer.short_name.as_str())
+        .collect();
+    assert_eq!(names, ["BaseVariant", "AppVariant"]);
+
+    let app_only = flatbuffers_to_ir_variants(&fbs_bytes, &["AppVariant"]).unwrap();
+    pretty_assertions::assert_eq!(
+        app_only,
+        DiagDatabase {
+            variants: vec![db.variants[1].clone()],
+            ..all
+        }
+    );
+    assert!(
+        flatbuffers_to_ir_variants(&fbs_bytes, &["Unknown"])
+            .unwrap()
+            .variants
+            .is_empty()
+    );
+}
+
+#[test]
+fn names_are_shared_across_variants() {
+    let mut db = make_test_database();
+    let mut app = db.variants[0].clone();
+    app.diag_layer.short_name = "AppVariant".into();
+    db.variants.push(app);
+    let db = flatbuffers_to_ir(&ir_to_flatbuffers(&db)).unwrap();
+
+    let service = |v: &Variant| v.diag_layer.diag_services[0].diag_comm.short_name.clone();
+    let (base, app) = (service(&db.variants[0]), service(&db.variants[1]));
+    assert_eq!(base, app);
+    assert!(Name::ptr_eq(&base, &app));
+}
+
+#[test]
+fn roundtrip_with_deduped_dops() {
+    let mut db = make_test_database();
+    let layer = &mut db.variants[0].diag_layer;
+    for name in ["ReadDID_F191", "ReadDID_F192", "ReadDID_F193"] {
+        layer.diag_services.push(make_service(name));
+    }
+
+    let plain = ir_to_flatbuffers(&db);
+    let deduped = ir_to_flatbuffers_with_options(&db, &FbsWriteOptions { dedup_dops: true });
+    assert!(
+        deduped.len() < plain.len(),
+        "{} >= {}",
+        deduped.len(),
+        plain.len()
+    );
+
+    let read = flatbuffers_to_ir(&deduped).expect("roundtrip failed");
+    pretty_assertions::assert_eq!(read, db);
+    // Parameters pointing at one deduplicated DOP table share one DOP again.
+    let dops: Vec<&DopRef> = read.variants[0]
+        .diag_layer
+        .diag_services
+        .iter()
+        .flat_map(|svc| &svc.pos_responses[0].params)
+        .filter_map(|p| match &p.specific_data {
+            Some(ParamData::Value { dop, .. }) => Some(dop),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(dops.len(), 4);
+    assert!(dops.iter().all(|dop| DopRef::ptr_eq(dop, dops[0])));
+    let read = flatbuffers_to_ir(&plain).expect("roundtrip failed");
+    let first = &read.variants[0].diag_layer.diag_services[0].pos_responses[0].params[0];
+    let second = &read.variants[0].diag_layer.diag_services[1].pos_responses[0].params[0];
+    match (&first.specific_data, &second.specific_data) {
+        (Some(ParamData::Value { dop: a, .. }), Some(ParamData::Value { dop: b, .. })) => {
+            assert!(!DopRef::ptr_eq(a, b));
+        }
+        other => panic!("unexpected params: {other:?}"),
+    }
+    let flags = read_feature_flags(&deduped).unwrap().unwrap();
+    assert!(flags.contains(Feature::DedupedDops));
+    let flags = read_feature_flags(&plain).unwrap();
+    assert!(flags.is_none_or(|f| !f.contains(Feature::DedupedDops)));
+}
+
 #[test]
 fn roundtrip_preserves_metadata() {
     let mut db = DiagDatabase::default();
@@ -231,6 +331,171 @@ fn roundtrip_preserves_metadata() {
     assert_eq!(db.metadata, db2.metadata);
 }
 
+#[test]
+fn roundtrip_records_feature_flags() {
+    let mut db = make_test_database();
+    db.memory = Some(MemoryConfig {
+        default_address_format: AddressFormat::default(),
+        regions: vec![],
+        data_blocks: vec![],
+    });
+    let fbs_bytes = ir_to_flatbuffers(&db);
+
+    let flags = read_feature_flags(&fbs_bytes)
+        .expect("parse failed")
+        .expect("flags not recorded");
+    assert_eq!(flags, FeatureFlags::detect(&db));
+    assert!(flags.contains(Feature::HasMemoryConfig));
+
+    // Databases without any capability still read back as "unknown".
+    let empty = ir_to_flatbuffers(&DiagDatabase::default());
+    assert_eq!(read_feature_flags(&empty).expect("parse failed"), None);
+}
+
+#[test]
+fn roundtrip_memory_config_and_type_definitions() {
+    let mut db = make_test_database();
+    db.memory = Some(MemoryConfig {
+        default_address_format: AddressFormat {
+            address_bytes: 4,
+            length_bytes: 2,
+        },
+        regions: vec![
+            MemoryRegion {
+                name: "Flash".into(),
+                description: Some("Application flash".into()),
+                start_address: 0x0800_0000,
+                size: 0x10_0000,
+                access: MemoryAccess::ReadWrite,
+                address_format: None,
+                security_level: Some("Level_01".into()),
+                session: Some(vec!["Programming".into(), "Extended".into()]),
+            },
+            MemoryRegion {
+                name: "Boot".into(),
+                description: None,
+                start_address: 0,
+                size: 0x8000,
+                access: MemoryAccess::Execute,
+                address_format: Some(AddressFormat {
+                    address_bytes: 2,
+                    length_bytes: 1,
+                }),
+                security_level: None,
+                session: Some(vec![]),
+            },
+        ],
+        data_blocks: vec![
+            DataBlock {
+                name: "App".into(),
+                description: None,
+                block_type: DataBlockType::Download,
+                memory_address: 0x0800_0000,
+                memory_size: 0x8_0000,
+                format: DataBlockFormat::EncryptedCompressed,
+                max_block_length: Some(0xFFF),
+                security_level: Some("Level_01".into()),
+                session: Some("Programming".into()),
+                checksum_type: Some(ChecksumType::Oem {
+                    name: "vendor_crc".into(),
+                    length: 4,
+                    parameters: [("polynomial".to_string(), "0x04C11DB7".to_string())].into(),
+                }),
+            },
+            DataBlock {
+                name: "Log".into(),
+                description: Some("Event log".into()),
+                block_type: DataBlockType::Upload,
+                memory_address: 0x2000_0000,
+                memory_size: 0x400,
+                format: DataBlockFormat::Raw,
+                max_block_length: None,
+                security_level: None,
+                session: None,
+                checksum_type: Some(ChecksumType::Crc16Ccitt),
+            },
+        ],
+    });
+    db.type_definitions = vec![
+        TypeDefinition {
+            name: "rpm_type".into(),
+            base: "u16".into(),
+            bit_length: Some(16),
+            bit_mask: Some(0x3FFF),
+            condensed: Some(false),
+            enum_values_json: Some(r#"{"2":"Run","10":"Off","OFF":-1}"#.into()),
+            description: Some("Engine speed".into()),
+            ..Default::default()
+        },
+        TypeDefinition {
+            name: "vin_type".into(),
+            base: "ascii".into(),
+            min_length: Some(17),
+            max_length: Some(17),
+            length_prefix_bits: Some(8),
+            ..Default::default()
+        },
+        TypeDefinition {
+            name: "mode_type".into(),
+            base: "u8".into(),
+            enum_values_json: Some(r#"["idle","drive"]"#.into()),
+            ..Default::default()
+        },
+    ];
+
+    let fbs = ir_to_flatbuffers(&db);
+    let db2 = flatbuffers_to_ir(&fbs).expect("roundtrip failed");
+    pretty_assertions::assert_eq!(db.memory, db2.memory);
+    pretty_assertions::assert_eq!(db.type_definitions, db2.type_definitions);
+    assert_eq!(find_losses(&db, &FBS_CAPABILITIES), vec![]);
+}
+
+#[test]
+fn roundtrip_transport_config() {
+    let mut db = make_test_database();
+    db.transport = Some(TransportConfig {
+        doip: Some(DoipConfig {
+            ip: Some("192.168.0.1".into()),
+            port: Some(13400),
+            logical_address: Some(0x1000),
+            functional_address: Some(0xFFFF),
+            tester_address: Some(0x0E80),
+            eid: Some([0x00, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E]),
+            gid: None,
+        }),
+    });
+
+    let fbs = ir_to_flatbuffers(&db);
+    let db2 = flatbuffers_to_ir(&fbs).expect("roundtrip failed");
+    pretty_assertions::assert_eq!(db.transport, db2.transport);
+    assert_eq!(find_losses(&db, &FBS_CAPABILITIES), vec![]);
+}
+
+#[test]
+fn roundtrip_ecu_identification() {
+    let mut db = make_test_database();
+    db.identification = Some(EcuIdentification {
+        part_number: Some("8W0907115A".into()),
+        supplier_code: None,
+        hardware_version: Some("H03".into()),
+        software_version: Some("0042".into()),
+        dids: [("vin".to_string(), 0xF190)].into(),
+    });
+
+    let fbs = ir_to_flatbuffers(&db);
+    let db2 = flatbuffers_to_ir(&fbs).expect("roundtrip failed");
+    pretty_assertions::assert_eq!(db.identification, db2.identification);
+}
+
+#[test]
+fn reference_mdd_has_no_feature_flags() {
+    let mdd_path =
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../test-fixtures/mdd/FLXC1000.mdd");
+    let (_meta, fbs_data) =
+        mdd_format::reader::read_mdd_file(&mdd_path).expect("Failed to read reference MDD");
+    assert_eq!(read_feature_flags(&fbs_data).expect("parse failed"), None);
+}
+
 #[test]
 fn roundtrip_dtc_fields() {
     let db = DiagDatabase {
@@ -271,7 +536,7 @@ fn roundtrip_diag_coded_type_variants() {
         id: 0,
         param_type: ParamType::CodedConst,
         short_name: name.into(),
-        semantic: String::new(),
+        semantic: Name::default(),
         sdgs: None,
         physical_default_value: String::new(),
         byte_position: Some(0),
@@ -325,7 +590,7 @@ fn roundtrip_diag_coded_type_variants() {
                     diag_comm: DiagComm {
                         short_name: "Svc".into(),
                         long_name: None,
-                        semantic: String::new(),
+                        semantic: Name::default(),
                         funct_classes: vec![],
                         sdgs: None,
                         diag_class_type: DiagClassType::StartComm,
@@ -336,6 +601,7 @@ fn roundtrip_diag_coded_type_variants() {
                         is_mandatory: false,
                         is_executable: true,
                         is_final: false,
+                        description: None,
                     },
                     request: Some(Request { params, sdgs: None }),
                     pos_responses: vec![],
@@ -350,6 +616,7 @@ fn roundtrip_diag_coded_type_variants() {
                 state_charts: vec![],
                 additional_audiences: vec![],
                 sdgs: None,
+                description: None,
             },
             is_base_variant: false,
             variant_patterns: vec![],
@@ -558,6 +825,105 @@ fn roundtrip_com_param_ref_with_complex_value() {
     pretty_assertions::assert_eq!(db, db2);
 }
 
+#[test]
+fn roundtrip_com_param_ref_with_definition_and_protocol() {
+    let regular = |name: &str, default: &str| ComParam {
+        com_param_type: ComParamType::Regular,
+        short_name: name.into(),
+        long_name: None,
+        param_class: "TIMING".into(),
+        cp_type: ComParamStandardisationLevel::Standard,
+        display_level: None,
+        cp_usage: ComParamUsage::EcuComm,
+        specific_data: Some(ComParamSpecificData::Regular {
+            physical_default_value: default.into(),
+            dop: Some(DopRef::new(minimal_dop())),
+        }),
+    };
+    let simple = |value: &str| {
+        SimpleOrComplexValue::Simple(SimpleValue {
+            value: value.into(),
+        })
+    };
+    let timings = ComParam {
+        com_param_type: ComParamType::Complex,
+        short_name: "CP_SessionTimings".into(),
+        long_name: Some(LongName {
+            value: "Session timings".into(),
+            ti: String::new(),
+        }),
+        param_class: "TIMING".into(),
+        cp_type: ComParamStandardisationLevel::OemSpecific,
+        display_level: Some(2),
+        cp_usage: ComParamUsage::Tester,
+        specific_data: Some(ComParamSpecificData::Complex {
+            com_params: vec![regular("CP_P2Max", "50"), regular("CP_P2Star", "5000")],
+            complex_physical_default_values: vec![ComplexValue {
+                entries: vec![simple("50"), simple("5000")],
+            }],
+            allow_multiple_values: true,
+        }),
+    };
+    let prot_stack = ProtStack {
+        short_name: "ISO_15765_4".into(),
+        long_name: None,
+        pdu_protocol_type: "ISO_15765_3_on_ISO_15765_2".into(),
+        physical_link_type: "ISO_11898_2_DWCAN".into(),
+        comparam_subset_refs: vec![ComParamSubSet {
+            com_params: vec![regular("CP_P2Max", "50")],
+            complex_com_params: vec![timings.clone()],
+            data_object_props: vec![minimal_dop()],
+            unit_spec: None,
+        }],
+    };
+    let protocol = Protocol {
+        diag_layer: DiagLayer {
+            short_name: "UDS_on_CAN".into(),
+            ..Default::default()
+        },
+        com_param_spec: Some(ComParamSpec {
+            prot_stacks: vec![prot_stack.clone()],
+        }),
+        prot_stack: Some(prot_stack.clone()),
+        parent_refs: vec![],
+    };
+    let com_param_ref = ComParamRef {
+        simple_value: None,
+        complex_value: Some(ComplexValue {
+            entries: vec![simple("25"), simple("2500")],
+        }),
+        com_param: Some(Box::new(timings)),
+        protocol: Some(Box::new(protocol.clone())),
+        prot_stack: Some(Box::new(prot_stack)),
+    };
+    // The reader rebuilds the protocol list from the ComParamRef stubs,
+    // collecting the refs that point at each protocol.
+    let mut rebuilt_protocol = protocol;
+    rebuilt_protocol
+        .diag_layer
+        .com_param_refs
+        .push(com_param_ref.clone());
+    let db = DiagDatabase {
+        variants: vec![Variant {
+            diag_layer: DiagLayer {
+                short_name: "V1".into(),
+                com_param_refs: vec![com_param_ref],
+                ..Default::default()
+            },
+            is_base_variant: false,
+            variant_patterns: vec![],
+            parent_refs: vec![],
+        }],
+        protocols: vec![rebuilt_protocol],
+        ..Default::default()
+    };
+
+    let fbs = ir_to_flatbuffers(&db);
+    let db2 = flatbuffers_to_ir(&fbs).expect("roundtrip failed");
+    pretty_assertions::assert_eq!(db, db2);
+    pretty_assertions::assert_eq!(ir_to_flatbuffers(&db2), fbs);
+}
+
 #[test]
 fn roundtrip_preserves_diag_comm_refs() {
     let db = DiagDatabase {
@@ -696,7 +1062,7 @@ fn wrap_dop_in_db(dop: Dop) -> DiagDatabase {
         short_name: "P".into(),
         specific_data: Some(ParamData::Value {
             physical_default_value: "0".into(),
-            dop: Box::new(dop),
+            dop: DopRef::new(dop),
         }),
         ..Default::default()
     };
@@ -753,7 +1119,7 @@ fn roundtrip_param_data_dop_variants() {
             param_type: ParamType::LengthKey,
             short_name: "LenKey".into(),
             specific_data: Some(ParamData::LengthKeyRef {
-                dop: Box::new(minimal_dop()),
+                dop: DopRef::new(minimal_dop()),
             }),
             ..Default::default()
         },
@@ -763,7 +1129,7 @@ fn roundtrip_param_data_dop_variants() {
             short_name: "PC".into(),
             specific_data: Some(ParamData::PhysConst {
                 phys_constant_value: "42.5".into(),
-                dop: Box::new(minimal_dop()),
+                dop: DopRef::new(minimal_dop()),
             }),
             ..Default::default()
         },
@@ -772,7 +1138,7 @@ fn roundtrip_param_data_dop_variants() {
             param_type: ParamType::System,
             short_name: "Sys".into(),
             specific_data: Some(ParamData::System {
-                dop: Box::new(minimal_dop()),
+                dop: DopRef::new(minimal_dop()),
                 sys_param: "ECU_SERIAL".into(),
             }),
             ..Default::default()
@@ -792,7 +1158,7 @@ fn roundtrip_param_data_table_variants() {
         short_name: "TDop".into(),
         key_label: "key".into(),
         struct_label: "struct".into(),
-        key_dop: Some(Box::new(minimal_dop())),
+        key_dop: Some(DopRef::new(minimal_dop())),
         rows: vec![TableRow {
             short_name: "Row1".into(),
             key: "0x01".into(),
@@ -804,7 +1170,7 @@ fn roundtrip_param_data_table_variants() {
     let table_row = TableRow {
         short_name: "TargetRow".into(),
         key: "0x02".into(),
-        dop: Some(Box::new(minimal_dop())),
+        dop: Some(DopRef::new(minimal_dop())),
         is_executable: true,
         semantic: "ROW".into(),
         ..Default::default()
@@ -833,7 +1199,7 @@ fn roundtrip_param_data_table_variants() {
                     short_name: "inner".into(),
                     specific_data: Some(ParamData::Value {
                         physical_default_value: "0".into(),
-                        dop: Box::new(minimal_dop()),
+                        dop: DopRef::new(minimal_dop()),
                     }),
                     ..Default::default()
                 }),
@@ -881,6 +1247,71 @@ fn roundtrip_param_data_table_variants() {
     pretty_assertions::assert_eq!(db, db2);
 }
 
+#[test]
+fn roundtrip_table_dop_diag_comm_connectors() {
+    let job = SingleEcuJob {
+        diag_comm: DiagComm {
+            short_name: "CalibrateTable".into(),
+            ..Default::default()
+        },
+        prog_codes: vec![],
+        input_params: vec![],
+        output_params: vec![],
+        neg_output_params: vec![],
+    };
+    let table_dop = TableDop {
+        semantic: "DATA".into(),
+        short_name: "DidTable".into(),
+        key_label: "DID".into(),
+        struct_label: "Record".into(),
+        key_dop: Some(DopRef::new(minimal_dop())),
+        rows: vec![TableRow {
+            short_name: "VIN".into(),
+            key: "0xF190".into(),
+            structure: Some(DopRef::new(minimal_dop())),
+            ..Default::default()
+        }],
+        diag_comm_connectors: vec![
+            TableDiagCommConnector {
+                diag_comm: DiagServiceOrJob::DiagService(Box::new(make_service("ReadDID_F190"))),
+                semantic: "READ".into(),
+            },
+            TableDiagCommConnector {
+                diag_comm: DiagServiceOrJob::SingleEcuJob(Box::new(job)),
+                semantic: "CALIBRATION".into(),
+            },
+        ],
+        ..Default::default()
+    };
+
+    let mut db = wrap_params_in_db(vec![Param {
+        id: 0,
+        param_type: ParamType::TableKey,
+        short_name: "TK".into(),
+        specific_data: Some(ParamData::TableKey {
+            table_key_reference: TableKeyReference::TableDop(Box::new(table_dop.clone())),
+        }),
+        ..Default::default()
+    }]);
+    db.variants[0].parent_refs.push(ParentRef {
+        ref_type: ParentRefType::TableDop(Box::new(table_dop)),
+        not_inherited_diag_comm_short_names: vec![],
+        not_inherited_variables_short_names: vec![],
+        not_inherited_dops_short_names: vec![],
+        not_inherited_tables_short_names: vec![],
+        not_inherited_global_neg_responses_short_names: vec![],
+    });
+
+    let fbs = ir_to_flatbuffers(&db);
+    let db2 = flatbuffers_to_ir(&fbs).expect("roundtrip failed");
+    pretty_assertions::assert_eq!(db, db2);
+    // A second write, as when an MDD is rewritten, keeps them as well.
+    pretty_assertions::assert_eq!(ir_to_flatbuffers(&db2), fbs);
+
+    let deduped = ir_to_flatbuffers_with_options(&db, &FbsWriteOptions { dedup_dops: true });
+    pretty_assertions::assert_eq!(flatbuffers_to_ir(&deduped).expect("roundtrip failed"), db);
+}
+
 // ---------------------------------------------------------------------------
 // Step 2: Roundtrip DopData variants
 // ---------------------------------------------------------------------------
@@ -915,7 +1346,7 @@ fn roundtrip_dop_data_field_variants() {
             max_number_of_items: Some(10),
             min_number_of_items: Some(1),
             field: Some(Field {
-                basic_structure: Some(Box::new(minimal_dop())),
+                basic_structure: Some(DopRef::new(minimal_dop())),
                 env_data_desc: None,
                 is_visible: true,
             }),
@@ -930,7 +1361,7 @@ fn roundtrip_dop_data_field_variants() {
             fixed_number_of_items: 5,
             item_byte_size: 2,
             field: Some(Field {
-                basic_structure: Some(Box::new(minimal_dop())),
+                basic_structure: Some(DopRef::new(minimal_dop())),
                 env_data_desc: None,
                 is_visible: false,
             }),
@@ -944,14 +1375,14 @@ fn roundtrip_dop_data_field_variants() {
         specific_data: Some(DopData::DynamicLengthField {
             offset: 4,
             field: Some(Field {
-                basic_structure: Some(Box::new(minimal_dop())),
+                basic_structure: Some(DopRef::new(minimal_dop())),
                 env_data_desc: None,
                 is_visible: true,
             }),
             determine_number_of_items: Some(DetermineNumberOfItems {
                 byte_position: 0,
                 bit_position: 0,
-                dop: Box::new(minimal_dop()),
+                dop: DopRef::new(minimal_dop()),
             }),
         }),
     };
@@ -982,7 +1413,7 @@ fn roundtrip_dop_data_complex_variants() {
                     bit_position: Some(0),
                     specific_data: Some(ParamData::Value {
                         physical_default_value: String::new(),
-                        dop: Box::new(Dop {
+                        dop: DopRef::new(Dop {
                             dop_type: DopType::Regular,
                             short_name: "year".into(),
                             sdgs: None,
@@ -1025,7 +1456,7 @@ fn roundtrip_dop_data_complex_variants() {
                     bit_position: Some(0),
                     specific_data: Some(ParamData::Value {
                         physical_default_value: String::new(),
-                        dop: Box::new(Dop {
+                        dop: DopRef::new(Dop {
                             dop_type: DopType::Regular,
                             short_name: "month".into(),
                             sdgs: None,
@@ -1113,7 +1544,7 @@ fn roundtrip_dop_data_complex_variants() {
                 short_name: "EP".into(),
                 specific_data: Some(ParamData::Value {
                     physical_default_value: "0".into(),
-                    dop: Box::new(minimal_dop()),
+                    dop: DopRef::new(minimal_dop()),
                 }),
                 ..Default::default()
             }],
@@ -1158,7 +1589,7 @@ fn roundtrip_dop_data_mux_dop() {
             switch_key: Some(SwitchKey {
                 byte_position: 0,
                 bit_position: Some(4),
-                dop: Box::new(minimal_dop()),
+                dop: DopRef::new(minimal_dop()),
             }),
             default_case: Some(DefaultCase {
                 short_name: "DefCase".into(),
@@ -1166,7 +1597,7 @@ fn roundtrip_dop_data_mux_dop() {
                     value: "Default Case".into(),
                     ti: "en".into(),
                 }),
-                structure: Some(Box::new(Dop {
+                structure: Some(DopRef::new(Dop {
                     dop_type: DopType::Structure,
                     short_name: "DefStruct".into(),
                     sdgs: None,
@@ -1181,7 +1612,7 @@ fn roundtrip_dop_data_mux_dop() {
                 Case {
                     short_name: "Case1".into(),
                     long_name: None,
-                    structure: Some(Box::new(Dop {
+                    structure: Some(DopRef::new(Dop {
                         dop_type: DopType::Structure,
                         short_name: "C1Struct".into(),
                         sdgs: None,
@@ -1274,7 +1705,7 @@ fn roundtrip_single_ecu_job() {
                         short_name: "InputAddr".into(),
                         long_name: None,
                         physical_default_value: "0x0000".into(),
-                        dop_base: Some(Box::new(minimal_dop())),
+                        dop_base: Some(DopRef::new(minimal_dop())),
                         semantic: "INPUT".into(),
                     }],
                     output_params: vec![JobParam {
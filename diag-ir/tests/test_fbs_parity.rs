@@ -0,0 +1,1161 @@
+//! Field-by-field parity between `ir_to_flatbuffers` and `flatbuffers_to_ir`.
+//!
+//! [`maximal_database`] sets every field of every IR type. It is written with
+//! exhaustive struct literals (no `..Default::default()`), so a new IR field
+//! does not compile until it is filled in here; the round trip then fails
+//! unless both converters carry it or it is listed in [`UNSTORED`].
+
+use diag_ir::*;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// IR fields the FlatBuffers schema deliberately does not store, as
+/// index-free paths (`[]` stands for any index; a leading `*` for any
+/// prefix). A pattern also covers every difference below its path.
+const UNSTORED: &[&str] = &[
+    // No top-level protocol table; the reader rebuilds the list from the
+    // protocol stubs on variant ComParamRefs.
+    "protocols",
+    // No top-level ECU-SHARED-DATA table; they only exist as parent refs.
+    "ecu_shared_datas",
+    // Rich-text descriptions are not part of the schema (FBS_CAPABILITIES).
+    "*diag_layer.description",
+    "*diag_comm.description",
+    // ODX-only response attributes.
+    "*responses[].pos_response_suffix",
+    "*responses[].audience",
+];
+
+#[allow(clippy::unnecessary_wraps)]
+fn long_name(name: &str) -> Option<LongName> {
+    Some(LongName {
+        value: format!("{name} long name"),
+        ti: format!("TI_{name}"),
+    })
+}
+
+fn text(value: &str) -> Text {
+    Text {
+        value: value.into(),
+        ti: format!("TI_{value}"),
+    }
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn sdgs(caption: &str) -> Option<Sdgs> {
+    Some(Sdgs {
+        sdgs: vec![Sdg {
+            caption_sn: caption.into(),
+            sds: vec![
+                SdOrSdg::Sd(Sd {
+                    value: "value".into(),
+                    si: "si".into(),
+                    ti: "ti".into(),
+                }),
+                SdOrSdg::Sdg(Sdg {
+                    caption_sn: format!("{caption}_nested"),
+                    sds: vec![],
+                    si: "nested_si".into(),
+                }),
+            ],
+            si: "si".into(),
+        }],
+    })
+}
+
+fn funct_class(name: &str) -> FunctClass {
+    FunctClass {
+        short_name: name.into(),
+    }
+}
+
+fn additional_audience(name: &str) -> AdditionalAudience {
+    AdditionalAudience {
+        short_name: name.into(),
+        long_name: long_name(name),
+    }
+}
+
+fn audience() -> Audience {
+    Audience {
+        enabled_audiences: vec![additional_audience("Workshop")],
+        disabled_audiences: vec![additional_audience("Plant")],
+        is_supplier: true,
+        is_development: true,
+        is_manufacturing: true,
+        is_after_sales: true,
+        is_after_market: true,
+    }
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn limit(value: &str, interval_type: IntervalType) -> Option<Limit> {
+    Some(Limit {
+        value: value.into(),
+        interval_type,
+    })
+}
+
+fn state(name: &str) -> State {
+    State {
+        short_name: name.into(),
+        long_name: long_name(name),
+    }
+}
+
+fn state_transition() -> StateTransition {
+    StateTransition {
+        short_name: "DefaultToExtended".into(),
+        source_short_name_ref: "Default".into(),
+        target_short_name_ref: "Extended".into(),
+    }
+}
+
+fn state_chart() -> StateChart {
+    StateChart {
+        short_name: "Session".into(),
+        semantic: "SESSION".into(),
+        state_transitions: vec![state_transition()],
+        start_state_short_name_ref: "Default".into(),
+        states: vec![state("Default"), state("Extended")],
+    }
+}
+
+fn pre_condition_state_ref() -> PreConditionStateRef {
+    PreConditionStateRef {
+        value: "Extended".into(),
+        in_param_if_short_name: "SessionType".into(),
+        in_param_path_short_name: "Session.SessionType".into(),
+        state: Some(state("Extended")),
+    }
+}
+
+fn state_transition_ref() -> StateTransitionRef {
+    StateTransitionRef {
+        value: "DefaultToExtended".into(),
+        state_transition: Some(state_transition()),
+    }
+}
+
+fn prog_code(name: &str) -> ProgCode {
+    ProgCode {
+        code_file: format!("{name}.jar"),
+        encryption: "none".into(),
+        syntax: "JAR".into(),
+        revision: "1.0".into(),
+        entrypoint: format!("com.example.{name}"),
+        libraries: vec![Library {
+            short_name: format!("{name}Lib").into(),
+            long_name: long_name(name),
+            code_file: format!("{name}Lib.jar"),
+            encryption: "aes".into(),
+            syntax: "JAR".into(),
+            entry_point: format!("com.example.{name}Lib"),
+        }],
+    }
+}
+
+// --- Types and DOPs ---
+
+fn coded_type(specific_data: DiagCodedTypeData) -> DiagCodedType {
+    let type_name = match specific_data {
+        DiagCodedTypeData::LeadingLength { .. } => DiagCodedTypeName::LeadingLengthInfoType,
+        DiagCodedTypeData::MinMax { .. } => DiagCodedTypeName::MinMaxLengthType,
+        DiagCodedTypeData::ParamLength { .. } => DiagCodedTypeName::ParamLengthInfoType,
+        DiagCodedTypeData::StandardLength { .. } => DiagCodedTypeName::StandardLengthType,
+    };
+    DiagCodedType {
+        type_name,
+        base_type_encoding: "signed".into(),
+        base_data_type: DataType::AInt32,
+        is_high_low_byte_order: true,
+        specific_data: Some(specific_data),
+    }
+}
+
+fn standard_length(bit_length: u32) -> DiagCodedType {
+    coded_type(DiagCodedTypeData::StandardLength {
+        bit_length,
+        bit_mask: vec![0x0F, 0xF0],
+        condensed: true,
+    })
+}
+
+fn compu_values(vt: &str) -> CompuValues {
+    CompuValues {
+        v: Some(1.5),
+        vt: vt.into(),
+        vt_ti: format!("TI_{vt}"),
+    }
+}
+
+fn compu_scale() -> CompuScale {
+    CompuScale {
+        short_label: Some(text("Active")),
+        lower_limit: limit("0", IntervalType::Closed),
+        upper_limit: limit("10", IntervalType::Open),
+        inverse_values: Some(compu_values("inverse")),
+        consts: Some(compu_values("Active")),
+        rational_co_effs: Some(CompuRationalCoEffs {
+            numerator: vec![0.5, 2.0],
+            denominator: vec![4.0],
+        }),
+    }
+}
+
+fn compu_default_value() -> CompuDefaultValue {
+    CompuDefaultValue {
+        values: Some(compu_values("default")),
+        inverse_values: Some(compu_values("inverse_default")),
+    }
+}
+
+fn compu_method() -> CompuMethod {
+    CompuMethod {
+        category: CompuCategory::ScaleRatFunc,
+        internal_to_phys: Some(CompuInternalToPhys {
+            compu_scales: vec![compu_scale()],
+            prog_code: Some(prog_code("ToPhys")),
+            compu_default_value: Some(compu_default_value()),
+        }),
+        phys_to_internal: Some(CompuPhysToInternal {
+            prog_code: Some(prog_code("ToInternal")),
+            compu_scales: vec![compu_scale()],
+            compu_default_value: Some(compu_default_value()),
+        }),
+    }
+}
+
+fn physical_type() -> PhysicalType {
+    PhysicalType {
+        precision: Some(2),
+        base_data_type: PhysicalTypeDataType::AFloat64,
+        display_radix: Radix::Hex,
+    }
+}
+
+fn internal_constr() -> InternalConstr {
+    InternalConstr {
+        lower_limit: limit("-40", IntervalType::Closed),
+        upper_limit: limit("215", IntervalType::Infinite),
+        scale_constrs: vec![ScaleConstr {
+            short_label: Some(text("Invalid")),
+            lower_limit: limit("254", IntervalType::Closed),
+            upper_limit: limit("255", IntervalType::Closed),
+            validity: ValidType::NotAvailable,
+        }],
+    }
+}
+
+fn physical_dimension() -> PhysicalDimension {
+    PhysicalDimension {
+        short_name: "Velocity".into(),
+        long_name: long_name("Velocity"),
+        length_exp: Some(1),
+        mass_exp: Some(2),
+        time_exp: Some(-1),
+        current_exp: Some(3),
+        temperature_exp: Some(4),
+        molar_amount_exp: Some(5),
+        luminous_intensity_exp: Some(6),
+    }
+}
+
+fn unit() -> Unit {
+    Unit {
+        short_name: "km_h".into(),
+        display_name: "km/h".into(),
+        factor_si_to_unit: Some(3.6),
+        offset_si_to_unit: Some(0.25),
+        physical_dimension: Some(physical_dimension()),
+    }
+}
+
+fn dop(dop_type: DopType, name: &str, specific_data: DopData) -> Dop {
+    Dop {
+        dop_type,
+        short_name: name.into(),
+        sdgs: sdgs(name),
+        specific_data: Some(specific_data),
+    }
+}
+
+fn normal_dop(name: &str) -> Dop {
+    dop(
+        DopType::Regular,
+        name,
+        DopData::NormalDop {
+            compu_method: Some(compu_method()),
+            diag_coded_type: Some(standard_length(16)),
+            physical_type: Some(physical_type()),
+            internal_constr: Some(internal_constr()),
+            unit_ref: Some(unit()),
+            phys_constr: Some(internal_constr()),
+        },
+    )
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn field() -> Option<Field> {
+    Some(Field {
+        basic_structure: Some(DopRef::new(structure_dop("FieldItem"))),
+        env_data_desc: Some(DopRef::new(normal_dop("FieldEnvDataDesc"))),
+        is_visible: true,
+    })
+}
+
+fn structure_dop(name: &str) -> Dop {
+    dop(
+        DopType::Structure,
+        name,
+        DopData::Structure {
+            params: vec![value_param("StructValue")],
+            byte_size: Some(2),
+            is_visible: true,
+        },
+    )
+}
+
+fn dtc(name: &str, trouble_code: u32) -> Dtc {
+    Dtc {
+        short_name: name.into(),
+        trouble_code,
+        display_trouble_code: format!("{name}-00"),
+        text: Some(text(&format!("{name} text"))),
+        level: Some(3),
+        sdgs: sdgs(name),
+        is_temporary: true,
+    }
+}
+
+/// One DOP per `DopData` arm.
+fn complex_dops() -> Vec<Dop> {
+    vec![
+        normal_dop("Normal"),
+        structure_dop("Structure"),
+        dop(
+            DopType::EndOfPduField,
+            "EndOfPdu",
+            DopData::EndOfPduField {
+                max_number_of_items: Some(8),
+                min_number_of_items: Some(1),
+                field: field(),
+            },
+        ),
+        dop(
+            DopType::StaticField,
+            "Static",
+            DopData::StaticField {
+                fixed_number_of_items: 4,
+                item_byte_size: 2,
+                field: field(),
+            },
+        ),
+        dop(
+            DopType::EnvDataDesc,
+            "EnvDataDesc",
+            DopData::EnvDataDesc {
+                param_short_name: "DtcRecord".into(),
+                param_path_short_name: "Response.DtcRecord".into(),
+                env_datas: vec![dop(
+                    DopType::EnvData,
+                    "EnvData",
+                    DopData::EnvData {
+                        dtc_values: vec![0x01_0100],
+                        params: vec![value_param("Mileage")],
+                    },
+                )],
+            },
+        ),
+        dop(
+            DopType::Dtc,
+            "DtcDop",
+            DopData::DtcDop {
+                diag_coded_type: Some(standard_length(24)),
+                physical_type: Some(physical_type()),
+                compu_method: Some(compu_method()),
+                dtcs: vec![dtc("P0100", 0x01_0000)],
+                is_visible: true,
+            },
+        ),
+        dop(
+            DopType::Mux,
+            "Mux",
+            DopData::MuxDop {
+                byte_position: 1,
+                switch_key: Some(SwitchKey {
+                    byte_position: 2,
+                    bit_position: Some(3),
+                    dop: DopRef::new(normal_dop("MuxSwitch")),
+                }),
+                default_case: Some(DefaultCase {
+                    short_name: "Default".into(),
+                    long_name: long_name("Default"),
+                    structure: Some(DopRef::new(structure_dop("MuxDefault"))),
+                }),
+                cases: vec![Case {
+                    short_name: "CaseOne".into(),
+                    long_name: long_name("CaseOne"),
+                    structure: Some(DopRef::new(structure_dop("MuxCase"))),
+                    lower_limit: limit("1", IntervalType::Closed),
+                    upper_limit: limit("1", IntervalType::Closed),
+                }],
+                is_visible: true,
+            },
+        ),
+        dop(
+            DopType::DynamicLengthField,
+            "DynamicLength",
+            DopData::DynamicLengthField {
+                offset: 1,
+                field: field(),
+                determine_number_of_items: Some(DetermineNumberOfItems {
+                    byte_position: 1,
+                    bit_position: 2,
+                    dop: DopRef::new(normal_dop("ItemCount")),
+                }),
+            },
+        ),
+    ]
+}
+
+// --- Parameters and tables ---
+
+fn param(id: u32, param_type: ParamType, name: &str, specific_data: ParamData) -> Param {
+    Param {
+        id,
+        param_type,
+        short_name: name.into(),
+        semantic: "DATA".into(),
+        sdgs: sdgs(name),
+        physical_default_value: "7".into(),
+        byte_position: Some(3),
+        bit_position: Some(4),
+        specific_data: Some(specific_data),
+    }
+}
+
+fn value_param(name: &str) -> Param {
+    param(
+        1,
+        ParamType::Value,
+        name,
+        ParamData::Value {
+            physical_default_value: "42".into(),
+            dop: DopRef::new(normal_dop(&format!("{name}Dop"))),
+        },
+    )
+}
+
+fn table_row(name: &str) -> TableRow {
+    TableRow {
+        short_name: name.into(),
+        long_name: long_name(name),
+        key: "1".into(),
+        dop: Some(DopRef::new(normal_dop("RowDop"))),
+        structure: Some(DopRef::new(structure_dop("RowStructure"))),
+        sdgs: sdgs(name),
+        audience: Some(audience()),
+        funct_class_refs: vec![funct_class("Table")],
+        state_transition_refs: vec![state_transition_ref()],
+        pre_condition_state_refs: vec![pre_condition_state_ref()],
+        is_executable: true,
+        semantic: "ROW".into(),
+        is_mandatory: true,
+        is_final: true,
+    }
+}
+
+fn table_dop() -> TableDop {
+    TableDop {
+        semantic: "TABLE".into(),
+        short_name: "Routines".into(),
+        long_name: long_name("Routines"),
+        key_label: "RoutineId".into(),
+        struct_label: "RoutineData".into(),
+        key_dop: Some(DopRef::new(normal_dop("TableKey"))),
+        rows: vec![table_row("RowOne")],
+        diag_comm_connectors: vec![
+            TableDiagCommConnector {
+                diag_comm: DiagServiceOrJob::DiagService(Box::new(plain_service("Connected"))),
+                semantic: "SERVICE".into(),
+            },
+            TableDiagCommConnector {
+                diag_comm: DiagServiceOrJob::SingleEcuJob(Box::new(single_ecu_job())),
+                semantic: "JOB".into(),
+            },
+        ],
+        sdgs: sdgs("Routines"),
+    }
+}
+
+/// One parameter per `ParamData` arm, and one per `DiagCodedTypeData` arm.
+fn all_params() -> Vec<Param> {
+    let length_key = value_param("LengthKey");
+    vec![
+        param(
+            2,
+            ParamType::CodedConst,
+            "SID",
+            ParamData::CodedConst {
+                coded_value: "0x22".into(),
+                diag_coded_type: standard_length(8),
+            },
+        ),
+        param(
+            3,
+            ParamType::CodedConst,
+            "Leading",
+            ParamData::CodedConst {
+                coded_value: "1".into(),
+                diag_coded_type: coded_type(DiagCodedTypeData::LeadingLength { bit_length: 8 }),
+            },
+        ),
+        param(
+            4,
+            ParamType::CodedConst,
+            "MinMax",
+            ParamData::CodedConst {
+                coded_value: "2".into(),
+                diag_coded_type: coded_type(DiagCodedTypeData::MinMax {
+                    min_length: 1,
+                    max_length: Some(16),
+                    termination: Termination::HexFf,
+                }),
+            },
+        ),
+        param(
+            5,
+            ParamType::CodedConst,
+            "ParamLength",
+            ParamData::CodedConst {
+                coded_value: "3".into(),
+                diag_coded_type: coded_type(DiagCodedTypeData::ParamLength {
+                    length_key: Box::new(length_key.clone()),
+                }),
+            },
+        ),
+        param(6, ParamType::Dynamic, "Dynamic", ParamData::Dynamic),
+        param(
+            7,
+            ParamType::LengthKey,
+            "LengthKeyRef",
+            ParamData::LengthKeyRef {
+                dop: DopRef::new(normal_dop("LengthKeyDop")),
+            },
+        ),
+        param(
+            8,
+            ParamType::MatchingRequestParam,
+            "Echo",
+            ParamData::MatchingRequestParam {
+                request_byte_pos: -1,
+                byte_length: 2,
+            },
+        ),
+        param(
+            9,
+            ParamType::NrcConst,
+            "Nrc",
+            ParamData::NrcConst {
+                coded_values: vec!["0x12".into(), "0x31".into()],
+                diag_coded_type: standard_length(8),
+            },
+        ),
+        param(
+            10,
+            ParamType::PhysConst,
+            "PhysConst",
+            ParamData::PhysConst {
+                phys_constant_value: "3.5".into(),
+                dop: DopRef::new(normal_dop("PhysConstDop")),
+            },
+        ),
+        param(
+            11,
+            ParamType::Reserved,
+            "Reserved",
+            ParamData::Reserved { bit_length: 4 },
+        ),
+        param(
+            12,
+            ParamType::System,
+            "System",
+            ParamData::System {
+                dop: DopRef::new(normal_dop("SystemDop")),
+                sys_param: "TIMESTAMP".into(),
+            },
+        ),
+        param(
+            13,
+            ParamType::TableEntry,
+            "TableEntry",
+            ParamData::TableEntry {
+                param: Box::new(value_param("TableEntryParam")),
+                target: TableEntryRowFragment::Struct,
+                table_row: Box::new(table_row("EntryRow")),
+            },
+        ),
+        param(
+            14,
+            ParamType::TableKey,
+            "TableKeyDop",
+            ParamData::TableKey {
+                table_key_reference: TableKeyReference::TableDop(Box::new(table_dop())),
+            },
+        ),
+        param(
+            15,
+            ParamType::TableKey,
+            "TableKeyRow",
+            ParamData::TableKey {
+                table_key_reference: TableKeyReference::TableRow(Box::new(table_row("KeyRow"))),
+            },
+        ),
+        param(
+            16,
+            ParamType::TableStruct,
+            "TableStruct",
+            ParamData::TableStruct {
+                table_key: Box::new(length_key),
+            },
+        ),
+        value_param("Value"),
+    ]
+}
+
+// --- ComParams and protocols ---
+
+fn com_param(name: &str, specific_data: ComParamSpecificData) -> ComParam {
+    let com_param_type = match specific_data {
+        ComParamSpecificData::Regular { .. } => ComParamType::Regular,
+        ComParamSpecificData::Complex { .. } => ComParamType::Complex,
+    };
+    ComParam {
+        com_param_type,
+        short_name: name.into(),
+        long_name: long_name(name),
+        param_class: "TIMING".into(),
+        cp_type: ComParamStandardisationLevel::OemOptional,
+        display_level: Some(2),
+        cp_usage: ComParamUsage::Tester,
+        specific_data: Some(specific_data),
+    }
+}
+
+fn regular_com_param(name: &str) -> ComParam {
+    com_param(
+        name,
+        ComParamSpecificData::Regular {
+            physical_default_value: "50".into(),
+            dop: Some(DopRef::new(normal_dop(&format!("{name}Dop")))),
+        },
+    )
+}
+
+fn complex_value() -> ComplexValue {
+    ComplexValue {
+        entries: vec![
+            SimpleOrComplexValue::Simple(SimpleValue { value: "1".into() }),
+            SimpleOrComplexValue::Complex(Box::new(ComplexValue {
+                entries: vec![SimpleOrComplexValue::Simple(SimpleValue {
+                    value: "2".into(),
+                })],
+            })),
+        ],
+    }
+}
+
+fn complex_com_param() -> ComParam {
+    com_param(
+        "CP_UniqueRespIdTable",
+        ComParamSpecificData::Complex {
+            com_params: vec![regular_com_param("CP_CanRespId")],
+            complex_physical_default_values: vec![complex_value()],
+            allow_multiple_values: true,
+        },
+    )
+}
+
+fn prot_stack() -> ProtStack {
+    ProtStack {
+        short_name: "ISO_15765_2".into(),
+        long_name: long_name("ISO_15765_2"),
+        pdu_protocol_type: "ISO_15765_3_on_ISO_15765_2".into(),
+        physical_link_type: "ISO_11898_2_DWCAN".into(),
+        comparam_subset_refs: vec![ComParamSubSet {
+            com_params: vec![regular_com_param("CP_P2Max")],
+            complex_com_params: vec![complex_com_param()],
+            data_object_props: vec![normal_dop("SubsetDop")],
+            unit_spec: Some(UnitSpec {
+                unit_groups: vec![UnitGroup {
+                    short_name: "Speeds".into(),
+                    long_name: long_name("Speeds"),
+                    unit_refs: vec![unit()],
+                }],
+                units: vec![unit()],
+                physical_dimensions: vec![physical_dimension()],
+                sdgs: sdgs("UnitSpec"),
+            }),
+        }],
+    }
+}
+
+/// A protocol layer with everything but nested protocols of its own.
+fn protocol() -> Protocol {
+    Protocol {
+        diag_layer: layer("UDS_CAN", vec![plain_service("ProtocolService")], vec![]),
+        com_param_spec: Some(ComParamSpec {
+            prot_stacks: vec![prot_stack()],
+        }),
+        prot_stack: Some(prot_stack()),
+        parent_refs: vec![parent_ref(ParentRefType::TableDop(Box::new(table_dop())))],
+    }
+}
+
+fn com_param_ref() -> ComParamRef {
+    ComParamRef {
+        simple_value: Some(SimpleValue { value: "25".into() }),
+        complex_value: Some(complex_value()),
+        com_param: Some(Box::new(regular_com_param("CP_P2Star"))),
+        protocol: Some(Box::new(protocol())),
+        prot_stack: Some(Box::new(prot_stack())),
+    }
+}
+
+// --- Services and layers ---
+
+fn diag_comm(name: &str, protocols: Vec<Protocol>) -> DiagComm {
+    DiagComm {
+        short_name: name.into(),
+        long_name: long_name(name),
+        semantic: "ROUTINE".into(),
+        funct_classes: vec![funct_class("Routines")],
+        sdgs: sdgs(name),
+        diag_class_type: DiagClassType::VariantIdentification,
+        pre_condition_state_refs: vec![pre_condition_state_ref()],
+        state_transition_refs: vec![state_transition_ref()],
+        protocols,
+        audience: Some(audience()),
+        is_mandatory: true,
+        is_executable: true,
+        is_final: true,
+        description: Some(format!("Runs **{name}**.")),
+    }
+}
+
+fn response(response_type: ResponseType, params: Vec<Param>) -> Response {
+    Response {
+        response_type,
+        params,
+        sdgs: sdgs("Response"),
+        pos_response_suffix: Some("_PR".into()),
+        audience: Some(audience()),
+    }
+}
+
+fn service(name: &str, params: Vec<Param>, protocols: Vec<Protocol>) -> DiagService {
+    DiagService {
+        diag_comm: diag_comm(name, protocols),
+        request: Some(Request {
+            params: params.clone(),
+            sdgs: sdgs("Request"),
+        }),
+        pos_responses: vec![response(ResponseType::PosResponse, params)],
+        neg_responses: vec![
+            response(ResponseType::NegResponse, vec![value_param("Nrc")]),
+            response(ResponseType::GlobalNegResponse, vec![]),
+        ],
+        is_cyclic: true,
+        is_multiple: true,
+        addressing: Addressing::FunctionalOrPhysical,
+        transmission_mode: TransmissionMode::SendOrReceive,
+        com_param_refs: vec![ComParamRef {
+            simple_value: Some(SimpleValue { value: "10".into() }),
+            complex_value: None,
+            com_param: Some(Box::new(regular_com_param("CP_ServiceTiming"))),
+            protocol: None,
+            prot_stack: None,
+        }],
+    }
+}
+
+/// A service without nested protocols or tables.
+fn plain_service(name: &str) -> DiagService {
+    service(name, vec![value_param("Plain")], vec![])
+}
+
+fn job_param(name: &str) -> JobParam {
+    JobParam {
+        short_name: name.into(),
+        long_name: long_name(name),
+        physical_default_value: "0".into(),
+        dop_base: Some(DopRef::new(normal_dop(&format!("{name}Dop")))),
+        semantic: "JOB-PARAM".into(),
+    }
+}
+
+fn single_ecu_job() -> SingleEcuJob {
+    SingleEcuJob {
+        diag_comm: diag_comm("FlashJob", vec![]),
+        prog_codes: vec![prog_code("FlashJob")],
+        input_params: vec![job_param("Input")],
+        output_params: vec![job_param("Output")],
+        neg_output_params: vec![job_param("Error")],
+    }
+}
+
+fn layer(
+    name: &str,
+    diag_services: Vec<DiagService>,
+    com_param_refs: Vec<ComParamRef>,
+) -> DiagLayer {
+    DiagLayer {
+        short_name: name.into(),
+        long_name: long_name(name),
+        funct_classes: vec![funct_class("Routines")],
+        com_param_refs,
+        diag_services,
+        single_ecu_jobs: vec![single_ecu_job()],
+        state_charts: vec![state_chart()],
+        additional_audiences: vec![additional_audience("Workshop")],
+        sdgs: sdgs(name),
+        description: Some(format!("The *{name}* layer.")),
+    }
+}
+
+fn parent_ref(ref_type: ParentRefType) -> ParentRef {
+    ParentRef {
+        ref_type,
+        not_inherited_diag_comm_short_names: vec!["Hidden".into()],
+        not_inherited_variables_short_names: vec!["Variable".into()],
+        not_inherited_dops_short_names: vec!["Dop".into()],
+        not_inherited_tables_short_names: vec!["Table".into()],
+        not_inherited_global_neg_responses_short_names: vec!["GlobalNeg".into()],
+    }
+}
+
+fn base_variant() -> Variant {
+    Variant {
+        diag_layer: layer(
+            "ECM_Base",
+            vec![
+                service("AllParams", all_params(), vec![protocol()]),
+                service(
+                    "AllDops",
+                    complex_dops()
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, dop)| {
+                            let name = dop.short_name.clone();
+                            param(
+                                100 + u32::try_from(i).unwrap(),
+                                ParamType::Value,
+                                &name,
+                                ParamData::Value {
+                                    physical_default_value: "0".into(),
+                                    dop: DopRef::new(dop),
+                                },
+                            )
+                        })
+                        .collect(),
+                    vec![],
+                ),
+            ],
+            vec![com_param_ref()],
+        ),
+        is_base_variant: true,
+        variant_patterns: vec![VariantPattern {
+            matching_parameters: vec![MatchingParameter {
+                expected_value: "0x0101".into(),
+                diag_service: Box::new(plain_service("IdentVariant")),
+                out_param: Box::new(value_param("VariantCode")),
+                use_physical_addressing: Some(true),
+            }],
+        }],
+        parent_refs: vec![parent_ref(ParentRefType::Protocol(Box::new(protocol())))],
+    }
+}
+
+fn functional_group() -> FunctionalGroup {
+    FunctionalGroup {
+        diag_layer: layer("AllEcus", vec![plain_service("GroupService")], vec![]),
+        parent_refs: vec![parent_ref(ParentRefType::EcuSharedData(Box::new(
+            ecu_shared_data(),
+        )))],
+    }
+}
+
+fn ecu_shared_data() -> EcuSharedData {
+    EcuSharedData {
+        diag_layer: layer("SharedData", vec![plain_service("SharedService")], vec![]),
+    }
+}
+
+/// Every field of every IR type set to a non-default value at least once.
+fn maximal_database() -> DiagDatabase {
+    let base = base_variant();
+    let app = Variant {
+        diag_layer: layer("ECM_App", vec![plain_service("AppService")], vec![]),
+        is_base_variant: false,
+        variant_patterns: vec![],
+        parent_refs: vec![
+            parent_ref(ParentRefType::Variant(Box::new(base.clone()))),
+            parent_ref(ParentRefType::FunctionalGroup(Box::new(functional_group()))),
+        ],
+    };
+    DiagDatabase {
+        version: "1.2.3".into(),
+        ecu_name: "ECM".into(),
+        revision: "r7".into(),
+        metadata: BTreeMap::from([("author".into(), "parity".into())]),
+        variants: vec![base, app],
+        functional_groups: vec![functional_group()],
+        protocols: vec![protocol()],
+        ecu_shared_datas: vec![ecu_shared_data()],
+        dtcs: vec![dtc("P0101", 0x01_0100), dtc("U0100", 0xC1_0000)],
+        memory: Some(MemoryConfig {
+            default_address_format: AddressFormat {
+                address_bytes: 3,
+                length_bytes: 2,
+            },
+            regions: vec![MemoryRegion {
+                name: "Flash".into(),
+                description: Some("Application flash".into()),
+                start_address: 0x0800_0000,
+                size: 0x10_0000,
+                access: MemoryAccess::ReadWrite,
+                address_format: Some(AddressFormat {
+                    address_bytes: 4,
+                    length_bytes: 1,
+                }),
+                security_level: Some("Level1".into()),
+                session: Some(vec!["Programming".into()]),
+            }],
+            data_blocks: vec![
+                DataBlock {
+                    name: "App".into(),
+                    description: Some("Application image".into()),
+                    block_type: DataBlockType::Upload,
+                    memory_address: 0x0800_0000,
+                    memory_size: 0x8000,
+                    format: DataBlockFormat::EncryptedCompressed,
+                    max_block_length: Some(0x0FFF),
+                    security_level: Some("Level1".into()),
+                    session: Some("Programming".into()),
+                    checksum_type: Some(ChecksumType::Oem {
+                        name: "oem_sig".into(),
+                        length: 16,
+                        parameters: BTreeMap::from([("key".into(), "K1".into())]),
+                    }),
+                },
+                DataBlock {
+                    name: "Cal".into(),
+                    description: Some("Calibration".into()),
+                    block_type: DataBlockType::Download,
+                    memory_address: 0x0810_0000,
+                    memory_size: 0x400,
+                    format: DataBlockFormat::Raw,
+                    max_block_length: Some(0x100),
+                    security_level: Some("Level2".into()),
+                    session: Some("Extended".into()),
+                    checksum_type: Some(ChecksumType::Sha256),
+                },
+            ],
+        }),
+        type_definitions: vec![TypeDefinition {
+            name: "EngineState".into(),
+            base: "u8".into(),
+            bit_length: Some(8),
+            bit_mask: Some(0x0F),
+            condensed: Some(true),
+            min_length: Some(1),
+            max_length: Some(4),
+            length_prefix_bits: Some(8),
+            enum_values_json: Some(r#"{"0":"Off","1":"On"}"#.into()),
+            description: Some("Engine state".into()),
+        }],
+        transport: Some(TransportConfig {
+            doip: Some(DoipConfig {
+                ip: Some("192.168.0.10".into()),
+                port: Some(13400),
+                logical_address: Some(0x0010),
+                functional_address: Some(0xE400),
+                tester_address: Some(0x0E80),
+                eid: Some([0x00, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E]),
+                gid: Some([0x00, 0x00, 0x00, 0x00, 0x00, 0x01]),
+            }),
+        }),
+        identification: Some(EcuIdentification {
+            part_number: Some("8W0907115A".into()),
+            supplier_code: Some("BOSCH".into()),
+            hardware_version: Some("H03".into()),
+            software_version: Some("0042".into()),
+            dids: [
+                ("part_number".to_string(), 0xF187),
+                ("vin".to_string(), 0xF190),
+            ]
+            .into(),
+        }),
+    }
+}
+
+// --- Structural diff ---
+
+/// Paths below `path` where `expected` and `actual` differ, e.g.
+/// `variants[0].diag_layer.sdgs.sdgs[0].si`.
+fn diff(path: &str, expected: &Value, actual: &Value, out: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(e), Value::Object(a)) => {
+            let keys: BTreeSet<&String> = e.keys().chain(a.keys()).collect();
+            for key in keys {
+                let child = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                diff(
+                    &child,
+                    e.get(key).unwrap_or(&Value::Null),
+                    a.get(key).unwrap_or(&Value::Null),
+                    out,
+                );
+            }
+        }
+        (Value::Array(e), Value::Array(a)) if e.len() == a.len() => {
+            for (i, (e, a)) in e.iter().zip(a).enumerate() {
+                diff(&format!("{path}[{i}]"), e, a, out);
+            }
+        }
+        _ if expected != actual => out.push(path.to_string()),
+        _ => {}
+    }
+}
+
+/// `path` with every index replaced by `[]`.
+fn without_indices(path: &str) -> String {
+    let mut out = String::new();
+    let mut in_index = false;
+    for c in path.chars() {
+        match c {
+            '[' => {
+                in_index = true;
+                out.push_str("[]");
+            }
+            ']' => in_index = false,
+            _ if !in_index => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+fn is_unstored(path: &str) -> bool {
+    let path = without_indices(path);
+    UNSTORED.iter().any(|pattern| {
+        let (anywhere, pattern) = match pattern.strip_prefix('*') {
+            Some(pattern) => (true, pattern),
+            None => (false, *pattern),
+        };
+        path.match_indices(pattern).any(|(i, _)| {
+            let tail = &path[i + pattern.len()..];
+            (anywhere || i == 0) && (tail.is_empty() || tail.starts_with(['.', '[']))
+        })
+    })
+}
+
+/// Record, per object shape (its sorted key list), which keys hold a
+/// non-default value anywhere in `value`.
+fn collect_set_fields(value: &Value, shapes: &mut BTreeMap<Vec<String>, BTreeSet<String>>) {
+    match value {
+        Value::Object(map) => {
+            let shape = map.keys().cloned().collect();
+            let set = shapes.entry(shape).or_default();
+            for (key, child) in map {
+                if !is_default(child) {
+                    set.insert(key.clone());
+                }
+            }
+            for child in map.values() {
+                collect_set_fields(child, shapes);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_set_fields(item, shapes);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_default(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Bool(b) => !b,
+        Value::Number(n) => n.as_i64() == Some(0),
+        Value::String(s) => s.is_empty(),
+        Value::Array(items) => items.is_empty(),
+        Value::Object(map) => map.is_empty(),
+    }
+}
+
+#[test]
+fn maximal_database_sets_every_field() {
+    let value = serde_json::to_value(maximal_database()).unwrap();
+    let mut shapes = BTreeMap::new();
+    collect_set_fields(&value, &mut shapes);
+
+    let unset: Vec<String> = shapes
+        .iter()
+        .flat_map(|(shape, set)| {
+            shape
+                .iter()
+                .filter(|key| !set.contains(*key))
+                .map(move |key| format!("{key} (in {{{}}})", shape.join(", ")))
+        })
+        .collect();
+    assert!(
+        unset.is_empty(),
+        "fields never set in maximal_database():\n  {}",
+        unset.join("\n  ")
+    );
+}
+
+#[test]
+fn every_ir_field_survives_flatbuffers() {
+    let db = maximal_database();
+    let fbs = ir_to_flatbuffers(&db);
+    let roundtripped = flatbuffers_to_ir(&fbs).expect("roundtrip failed");
+
+    let expected = serde_json::to_value(&db).unwrap();
+    let actual = serde_json::to_value(&roundtripped).unwrap();
+    let mut diffs = Vec::new();
+    diff("", &expected, &actual, &mut diffs);
+    diffs.retain(|path| !is_unstored(path));
+
+    assert!(
+        diffs.is_empty(),
+        "{} IR field(s) lost in the FlatBuffers round trip; handle them in \
+         to_fbs.rs and from_fbs.rs, or list them in UNSTORED:\n  {}",
+        diffs.len(),
+        diffs.join("\n  ")
+    );
+}
+
+#[test]
+fn unstored_patterns_match_whole_segments() {
+    assert!(is_unstored("protocols[0].diag_layer.short_name"));
+    assert!(is_unstored("ecu_shared_datas"));
+    assert!(is_unstored("variants[1].diag_layer.description"));
+    assert!(is_unstored(
+        "variants[0].diag_layer.diag_services[2].neg_responses[1].audience.is_supplier"
+    ));
+    assert!(!is_unstored(
+        "variants[0].diag_layer.diag_services[2].diag_comm.audience"
+    ));
+    assert!(!is_unstored(
+        "variants[0].diag_layer.diag_services[0].diag_comm.protocols"
+    ));
+    assert!(!is_unstored("memory.regions[0].description"));
+}
@@ -0,0 +1,142 @@
+//! Golden MDD files written with previous schema versions must keep loading.
+//!
+//! `test-fixtures/mdd/*.mdd` come from the upstream odx-converter,
+//! `test-fixtures/mdd/schema-compat/*.v<N>.mdd` hold descriptions at schema
+//! version N: v1 was written by this converter, v2 extends its description
+//! with the tables v2 appended to `EcuData`. Never regenerate a golden; add a
+//! new one instead.
+
+use diag_ir::*;
+use std::path::Path;
+
+struct Golden {
+    path: &'static str,
+    ecu_name: &'static str,
+    variants: usize,
+    services: usize,
+    dtcs: usize,
+    features: Option<&'static [Feature]>,
+}
+
+const GOLDENS: &[Golden] = &[
+    Golden {
+        path: "mdd/FLXC1000.mdd",
+        ecu_name: "FLXC1000",
+        variants: 3,
+        services: 93,
+        dtcs: 6,
+        features: None,
+    },
+    Golden {
+        path: "mdd/FLXCNG1000.mdd",
+        ecu_name: "FLXCNG1000",
+        variants: 2,
+        services: 58,
+        dtcs: 6,
+        features: None,
+    },
+    Golden {
+        path: "mdd/schema-compat/example-ecm.v1.mdd",
+        ecu_name: "Engine Control Module",
+        variants: 6,
+        services: 276,
+        dtcs: 10,
+        features: Some(&[Feature::HasProtocols]),
+    },
+    Golden {
+        path: "mdd/schema-compat/example-ecm.v2.mdd",
+        ecu_name: "Engine Control Module",
+        variants: 6,
+        services: 276,
+        dtcs: 10,
+        features: Some(&[Feature::HasProtocols]),
+    },
+];
+
+fn load(golden: &Golden) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../test-fixtures")
+        .join(golden.path);
+    let (_meta, fbs_data) =
+        mdd_format::reader::read_mdd_file(&path).unwrap_or_else(|e| panic!("{}: {e}", golden.path));
+    fbs_data
+}
+
+#[test]
+fn goldens_still_load() {
+    for golden in GOLDENS {
+        let fbs_data = load(golden);
+        let db = flatbuffers_to_ir(&fbs_data).unwrap_or_else(|e| panic!("{}: {e}", golden.path));
+        let services: usize = db
+            .variants
+            .iter()
+            .map(|v| v.diag_layer.diag_services.len())
+            .sum();
+
+        assert_eq!(db.ecu_name, golden.ecu_name, "{}", golden.path);
+        assert_eq!(db.variants.len(), golden.variants, "{}", golden.path);
+        assert_eq!(services, golden.services, "{}", golden.path);
+        assert_eq!(db.dtcs.len(), golden.dtcs, "{}", golden.path);
+        assert_eq!(
+            read_feature_flags(&fbs_data).unwrap(),
+            golden.features.map(|f| f.iter().copied().collect()),
+            "{}",
+            golden.path
+        );
+    }
+}
+
+#[test]
+fn goldens_survive_rewrite_with_current_schema() {
+    for golden in GOLDENS {
+        let db = flatbuffers_to_ir(&load(golden)).unwrap();
+        let rewritten = flatbuffers_to_ir(&ir_to_flatbuffers(&db)).unwrap();
+        pretty_assertions::assert_eq!(db, rewritten, "{}", golden.path);
+    }
+}
+
+#[test]
+fn v2_golden_keeps_the_appended_ecu_data_tables() {
+    let golden = GOLDENS.last().unwrap();
+    let db = flatbuffers_to_ir(&load(golden)).unwrap();
+
+    let memory = db.memory.expect("memory");
+    assert_eq!(memory.default_address_format.address_bytes, 4);
+    let names: Vec<_> = memory.regions.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names, ["application", "calibration"]);
+    assert_eq!(memory.regions[0].access, MemoryAccess::ReadWrite);
+    assert_eq!(memory.regions[0].session, Some(vec!["programming".into()]));
+    assert_eq!(memory.regions[1].address_format.unwrap().address_bytes, 3);
+    let block = &memory.data_blocks[0];
+    assert_eq!(block.format, DataBlockFormat::Compressed);
+    assert_eq!(block.max_block_length, Some(0x0FFA));
+    let Some(ChecksumType::Oem { parameters, .. }) = &block.checksum_type else {
+        panic!("{:?}", block.checksum_type);
+    };
+    assert_eq!(parameters["polynomial"], "0x04C11DB7");
+
+    let types: Vec<_> = db
+        .type_definitions
+        .iter()
+        .map(|t| t.name.as_str())
+        .collect();
+    assert_eq!(
+        types,
+        ["engine_state_type", "status_flags_type", "vin_type"]
+    );
+    assert_eq!(
+        db.type_definitions[0].enum_values_json.as_deref(),
+        Some(r#"{"0":"off","1":"running","2":"cranking"}"#)
+    );
+    assert_eq!(db.type_definitions[1].bit_mask, Some(0x3FFF));
+    assert_eq!(db.type_definitions[2].length_prefix_bits, Some(8));
+
+    let doip = db.transport.and_then(|t| t.doip).expect("DoIP");
+    assert_eq!(doip.ip.as_deref(), Some("192.168.0.10"));
+    assert_eq!(doip.logical_address, Some(0x0010));
+    assert_eq!(doip.eid, Some([0x00, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E]));
+
+    let identification = db.identification.expect("identification");
+    assert_eq!(identification.part_number.as_deref(), Some("ECM-0815-A"));
+    assert_eq!(identification.dids["vin"], 0xF190);
+}
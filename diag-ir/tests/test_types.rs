@@ -22,6 +22,7 @@ fn test_database_with_variant_validates() {
                 state_charts: vec![],
                 additional_audiences: vec![],
                 sdgs: None,
+                description: None,
             },
             is_base_variant: true,
             variant_patterns: vec![],
@@ -38,7 +39,7 @@ fn test_duplicate_service_name_detected() {
         diag_comm: DiagComm {
             short_name: "ReadDID".into(),
             long_name: None,
-            semantic: String::new(),
+            semantic: Name::default(),
             funct_classes: vec![],
             sdgs: None,
             diag_class_type: DiagClassType::StartComm,
@@ -49,6 +50,7 @@ fn test_duplicate_service_name_detected() {
             is_mandatory: false,
             is_executable: true,
             is_final: false,
+            description: None,
         },
         request: None,
         pos_responses: vec![],
@@ -73,6 +75,7 @@ fn test_duplicate_service_name_detected() {
                 state_charts: vec![],
                 additional_audiences: vec![],
                 sdgs: None,
+                description: None,
             },
             is_base_variant: false,
             variant_patterns: vec![],
@@ -202,7 +205,7 @@ fn test_empty_state_chart_detected() {
                 }],
                 state_charts: vec![StateChart {
                     short_name: "EmptyChart".into(),
-                    semantic: String::new(),
+                    semantic: Name::default(),
                     state_transitions: vec![],
                     start_state_short_name_ref: String::new(),
                     states: vec![],
@@ -254,3 +257,22 @@ fn test_duplicate_dtc_detected_without_base_variant() {
         "duplicate DTC IDs should be caught even without base variants"
     );
 }
+
+#[test]
+fn test_dop_ref_serializes_and_prints_like_its_dop() {
+    let dop = Dop {
+        dop_type: DopType::Regular,
+        short_name: "Speed".into(),
+        sdgs: None,
+        specific_data: None,
+    };
+    let shared = DopRef::new(dop.clone());
+    let json = serde_json::to_value(&dop).unwrap();
+    assert_eq!(serde_json::to_value(&shared).unwrap(), json);
+    assert_eq!(format!("{shared:?}"), format!("{dop:?}"));
+
+    let parsed: DopRef = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed, shared);
+    assert!(!DopRef::ptr_eq(&parsed, &shared));
+    assert_eq!(shared.into_inner(), dop);
+}
@@ -0,0 +1,36 @@
+use diag_ir::variant_detect::{evaluate, request_bytes};
+
+fn load(name: &str) -> diag_ir::DiagDatabase {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../test-fixtures/mdd")
+        .join(name);
+    let (_meta, fbs) = mdd_format::reader::read_mdd_file(&path).unwrap();
+    diag_ir::flatbuffers_to_ir(&fbs).unwrap()
+}
+
+fn detect(db: &diag_ir::DiagDatabase, identification: [u8; 3]) -> Option<String> {
+    evaluate(db, |service| {
+        assert_eq!(service.diag_comm.short_name, "Identification_Read");
+        let request = request_bytes(service).unwrap();
+        assert_eq!(request[0], 0x22);
+        let mut response = vec![0x62, request[1], request[2]];
+        response.extend(identification);
+        Some(response)
+    })
+    .unwrap()
+    .map(|v| v.diag_layer.short_name.to_string())
+}
+
+#[test]
+fn test_detects_reference_mdd_variants() {
+    let db = load("FLXC1000.mdd");
+    assert_eq!(
+        detect(&db, [0xFF, 0x00, 0x00]).as_deref(),
+        Some("FLXC1000_Boot_Variant")
+    );
+    assert_eq!(
+        detect(&db, [0x00, 0x01, 0x01]).as_deref(),
+        Some("FLXC1000_App_0101")
+    );
+    assert_eq!(detect(&db, [0x12, 0x34, 0x56]), None);
+}
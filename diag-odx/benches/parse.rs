@@ -0,0 +1,174 @@
+//! Parsing a large ODX document in one pass and split across threads, and
+//! interning names from every thread at once.
+//!
+//! Run with `cargo bench -p diag-odx`.
+
+use std::fmt::Write;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use diag_ir::Interner;
+use diag_odx::{ParseOptions, parse_odx_with};
+use rayon::prelude::*;
+
+/// A document of `layers` ECU variants with `services` ReadDataByIdentifier
+/// services each, laid out like supplier exports.
+fn large_odx(layers: usize, services: usize) -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<ODX VERSION="2.2.0" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <DIAG-LAYER-CONTAINER ID="DLC_Bench">
+    <SHORT-NAME>BenchECU</SHORT-NAME>
+    <ECU-VARIANTS>
+"#,
+    );
+    for l in 0..layers {
+        let _ = write!(
+            xml,
+            r#"      <ECU-VARIANT ID="EV_{l}">
+        <SHORT-NAME>Variant_{l}</SHORT-NAME>
+        <DIAG-DATA-DICTIONARY-SPEC>
+          <DATA-OBJECT-PROPS>
+            <DATA-OBJECT-PROP ID="DOP_{l}">
+              <SHORT-NAME>Value_{l}</SHORT-NAME>
+              <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32">
+                <BIT-LENGTH>16</BIT-LENGTH>
+              </DIAG-CODED-TYPE>
+              <PHYSICAL-TYPE BASE-DATA-TYPE="A_UINT32"/>
+              <COMPU-METHOD>
+                <CATEGORY>IDENTICAL</CATEGORY>
+              </COMPU-METHOD>
+            </DATA-OBJECT-PROP>
+          </DATA-OBJECT-PROPS>
+        </DIAG-DATA-DICTIONARY-SPEC>
+        <DIAG-COMMS>
+"#
+        );
+        for s in 0..services {
+            let _ = write!(
+                xml,
+                r#"          <DIAG-SERVICE ID="DS_{l}_{s}" SEMANTIC="DATA-READ">
+            <SHORT-NAME>Read_{l}_{s}</SHORT-NAME>
+            <LONG-NAME>Read data identifier {s} of variant {l}</LONG-NAME>
+            <REQUEST-REF ID-REF="RQ_{l}_{s}"/>
+            <POS-RESPONSE-REFS>
+              <POS-RESPONSE-REF ID-REF="PR_{l}_{s}"/>
+            </POS-RESPONSE-REFS>
+          </DIAG-SERVICE>
+"#
+            );
+        }
+        xml.push_str("        </DIAG-COMMS>\n        <REQUESTS>\n");
+        for s in 0..services {
+            let _ = write!(
+                xml,
+                r#"          <REQUEST ID="RQ_{l}_{s}">
+            <SHORT-NAME>RQ_{l}_{s}</SHORT-NAME>
+            <PARAMS>
+              <PARAM xsi:type="CODED-CONST" SEMANTIC="SERVICE-ID">
+                <SHORT-NAME>SID</SHORT-NAME>
+                <BYTE-POSITION>0</BYTE-POSITION>
+                <CODED-VALUE>34</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32">
+                  <BIT-LENGTH>8</BIT-LENGTH>
+                </DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="CODED-CONST" SEMANTIC="ID">
+                <SHORT-NAME>DID</SHORT-NAME>
+                <BYTE-POSITION>1</BYTE-POSITION>
+                <CODED-VALUE>{s}</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32">
+                  <BIT-LENGTH>16</BIT-LENGTH>
+                </DIAG-CODED-TYPE>
+              </PARAM>
+            </PARAMS>
+          </REQUEST>
+"#
+            );
+        }
+        xml.push_str("        </REQUESTS>\n        <POS-RESPONSES>\n");
+        for s in 0..services {
+            let _ = write!(
+                xml,
+                r#"          <POS-RESPONSE ID="PR_{l}_{s}">
+            <SHORT-NAME>PR_{l}_{s}</SHORT-NAME>
+            <PARAMS>
+              <PARAM xsi:type="CODED-CONST" SEMANTIC="SERVICE-ID">
+                <SHORT-NAME>SID</SHORT-NAME>
+                <BYTE-POSITION>0</BYTE-POSITION>
+                <CODED-VALUE>98</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32">
+                  <BIT-LENGTH>8</BIT-LENGTH>
+                </DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="VALUE" SEMANTIC="DATA">
+                <SHORT-NAME>Value</SHORT-NAME>
+                <BYTE-POSITION>3</BYTE-POSITION>
+                <DOP-REF ID-REF="DOP_{l}"/>
+              </PARAM>
+            </PARAMS>
+          </POS-RESPONSE>
+"#
+            );
+        }
+        xml.push_str("        </POS-RESPONSES>\n      </ECU-VARIANT>\n");
+    }
+    xml.push_str("    </ECU-VARIANTS>\n  </DIAG-LAYER-CONTAINER>\n</ODX>\n");
+    xml
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_odx");
+    group.sample_size(10);
+    // Many small layers, and one layer holding all services.
+    for (layers, services) in [(16, 1500), (1, 24_000)] {
+        let xml = large_odx(layers, services);
+        group.throughput(Throughput::Bytes(xml.len() as u64));
+        let size = format!("{layers}x{services}");
+        for (name, sequential) in [("sequential", true), ("parallel", false)] {
+            let options = ParseOptions {
+                sequential,
+                ..Default::default()
+            };
+            group.bench_with_input(BenchmarkId::new(name, &size), &xml, |b, xml| {
+                b.iter(|| parse_odx_with(xml, &options).unwrap());
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Every rayon worker interning into one interner, as the parallel
+/// conversions do. Behind a single lock, all threads would be no faster than
+/// one.
+fn bench_interner_contention(c: &mut Criterion) {
+    let names: Vec<String> = (0..96_000)
+        .map(|i| format!("Read_{}", i % 24_000))
+        .collect();
+    let mut group = c.benchmark_group("interner");
+    group.throughput(Throughput::Elements(names.len() as u64));
+    let one_thread = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    for (name, pool) in [("one_thread", Some(&one_thread)), ("all_threads", None)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let interner = Interner::default();
+                let intern = || {
+                    names.par_iter().for_each(|n| {
+                        interner.intern(n);
+                    });
+                };
+                match pool {
+                    Some(pool) => pool.install(intern),
+                    None => intern(),
+                }
+                interner
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse, bench_interner_contention);
+criterion_main!(benches);
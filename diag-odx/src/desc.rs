@@ -0,0 +1,193 @@
+//! ODX DESC <-> normalized Markdown.
+//!
+//! DESC elements carry an XHTML subset. The IR stores them as a lightweight
+//! Markdown string: paragraphs separated by blank lines, `- ` bullets for
+//! `<ul>` and `1. ` numbering for `<ol>`. Inline markup (`<b>`, `<i>`, ...) is
+//! flattened to its text and whitespace is collapsed, so the conversion is
+//! stable across ODX -> IR -> ODX round trips.
+
+use crate::odx_model::{OdxDesc, OdxDescNode};
+
+/// Convert a parsed DESC element into normalized Markdown.
+/// Returns `None` when the description has no text content.
+pub fn desc_to_markdown(desc: &OdxDesc) -> Option<String> {
+    let mut blocks = Vec::new();
+    collect_blocks(&desc.nodes, &mut blocks);
+    if blocks.is_empty() {
+        None
+    } else {
+        Some(blocks.join("\n\n"))
+    }
+}
+
+/// Convert normalized Markdown back into a DESC element.
+pub fn markdown_to_desc(markdown: &str) -> OdxDesc {
+    let mut nodes = Vec::new();
+    let mut block: Vec<&str> = Vec::new();
+    for line in markdown.lines().chain(std::iter::once("")) {
+        let line = line.trim();
+        if line.is_empty() {
+            if !block.is_empty() {
+                nodes.push(block_to_node(&block));
+                block.clear();
+            }
+        } else {
+            block.push(line);
+        }
+    }
+    OdxDesc { nodes }
+}
+
+fn collect_blocks(nodes: &[OdxDescNode], blocks: &mut Vec<String>) {
+    let mut inline = String::new();
+    for node in nodes {
+        match node {
+            OdxDescNode::Paragraph(p) => {
+                flush_inline(&mut inline, blocks);
+                push_nonempty(inline_text(&p.nodes), blocks);
+            }
+            OdxDescNode::UnorderedList(list) => {
+                flush_inline(&mut inline, blocks);
+                push_nonempty(list_items(&list.nodes, |_| "-".to_string()), blocks);
+            }
+            OdxDescNode::OrderedList(list) => {
+                flush_inline(&mut inline, blocks);
+                push_nonempty(list_items(&list.nodes, |n| format!("{n}.")), blocks);
+            }
+            other => append_text(&mut inline, &inline_text(std::slice::from_ref(other))),
+        }
+    }
+    flush_inline(&mut inline, blocks);
+}
+
+fn list_items(nodes: &[OdxDescNode], marker: impl Fn(usize) -> String) -> String {
+    nodes
+        .iter()
+        .filter_map(|n| match n {
+            OdxDescNode::ListItem(li) => Some(inline_text(&li.nodes)),
+            _ => None,
+        })
+        .filter(|text| !text.is_empty())
+        .enumerate()
+        .map(|(i, text)| format!("{} {text}", marker(i + 1)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Flatten nodes to a single line of text with collapsed whitespace.
+fn inline_text(nodes: &[OdxDescNode]) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        match node {
+            OdxDescNode::Text(t) => append_text(&mut out, t),
+            OdxDescNode::Paragraph(c)
+            | OdxDescNode::UnorderedList(c)
+            | OdxDescNode::OrderedList(c)
+            | OdxDescNode::ListItem(c)
+            | OdxDescNode::Inline(c) => append_text(&mut out, &inline_text(&c.nodes)),
+            OdxDescNode::Other => {}
+        }
+    }
+    out
+}
+
+fn append_text(out: &mut String, text: &str) {
+    for word in text.split_whitespace() {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(word);
+    }
+}
+
+fn flush_inline(inline: &mut String, blocks: &mut Vec<String>) {
+    push_nonempty(std::mem::take(inline), blocks);
+}
+
+fn push_nonempty(text: String, blocks: &mut Vec<String>) {
+    if !text.is_empty() {
+        blocks.push(text);
+    }
+}
+
+fn block_to_node(lines: &[&str]) -> OdxDescNode {
+    if lines.iter().all(|l| l.starts_with("- ")) {
+        OdxDescNode::UnorderedList(list_node(lines.iter().map(|l| &l[2..])))
+    } else if let Some(items) = lines
+        .iter()
+        .map(|l| strip_ordered_marker(l))
+        .collect::<Option<Vec<_>>>()
+    {
+        OdxDescNode::OrderedList(list_node(items.into_iter()))
+    } else {
+        let mut text = String::new();
+        append_text(&mut text, &lines.join(" "));
+        OdxDescNode::Paragraph(text_desc(text))
+    }
+}
+
+fn strip_ordered_marker(line: &str) -> Option<&str> {
+    let (num, rest) = line.split_once(". ")?;
+    (!num.is_empty() && num.bytes().all(|b| b.is_ascii_digit())).then_some(rest)
+}
+
+fn list_node<'a>(items: impl Iterator<Item = &'a str>) -> OdxDesc {
+    OdxDesc {
+        nodes: items
+            .map(|item| OdxDescNode::ListItem(text_desc(item.trim().to_string())))
+            .collect(),
+    }
+}
+
+fn text_desc(text: String) -> OdxDesc {
+    OdxDesc {
+        nodes: vec![OdxDescNode::Text(text)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(xml: &str) -> OdxDesc {
+        quick_xml::de::from_str(xml).unwrap()
+    }
+
+    #[test]
+    fn test_paragraphs_and_lists() {
+        let desc = parse(
+            "<DESC><p>Reads the <b>VIN</b>\n   from EEPROM.</p>\
+             <ul><li>first</li><li><p>second</p></li></ul>\
+             <ol><li>one</li><li>two</li></ol></DESC>",
+        );
+        assert_eq!(
+            desc_to_markdown(&desc).unwrap(),
+            "Reads the VIN from EEPROM.\n\n- first\n- second\n\n1. one\n2. two"
+        );
+    }
+
+    #[test]
+    fn test_plain_text_desc() {
+        let desc = parse("<DESC>  Just text  </DESC>");
+        assert_eq!(desc_to_markdown(&desc).as_deref(), Some("Just text"));
+    }
+
+    #[test]
+    fn test_empty_and_unknown_content() {
+        assert_eq!(desc_to_markdown(&parse("<DESC/>")), None);
+        let desc = parse("<DESC><table><tr><td>x</td></tr></table><p>kept</p></DESC>");
+        assert_eq!(desc_to_markdown(&desc).as_deref(), Some("kept"));
+    }
+
+    #[test]
+    fn test_markdown_roundtrip_is_stable() {
+        let md = "Reads the VIN.\n\n- first\n- second\n\n1. one\n2. two";
+        let xml = quick_xml::se::to_string_with_root("DESC", &markdown_to_desc(md)).unwrap();
+        assert_eq!(
+            xml,
+            "<DESC><p>Reads the VIN.</p><ul><li>first</li><li>second</li></ul>\
+             <ol><li>one</li><li>two</li></ol></DESC>"
+        );
+        assert_eq!(desc_to_markdown(&parse(&xml)).as_deref(), Some(md));
+    }
+}
@@ -0,0 +1,237 @@
+//! ODX schema versions ("dialects") and the spelling differences between
+//! them.
+//!
+//! `odx_model` follows ODX 2.2.0. Documents of other versions are rewritten
+//! to 2.2.0 spellings before deserialization ([`to_model`]), and written
+//! documents from 2.2.0 spellings to the chosen version ([`from_model`]).
+//! The version of a document comes from the `MODEL-VERSION` (ODX 2.0:
+//! `VERSION`) attribute of its `<ODX>` root.
+//!
+//! ODX 2.0.x differs in:
+//! - the root attribute: `VERSION` instead of `MODEL-VERSION`;
+//! - the value of a COMPARAM-REF: `VALUE` instead of `SIMPLE-VALUE`;
+//! - the length of a MATCHING-REQUEST-PARAM: `BYTE-LENGTH` instead of
+//!   `MATCH-BYTE-LENGTH`.
+//!
+//! ODX 2.3 documents use the 2.2.0 element names; only the model version
+//! differs.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use quick_xml::events::{BytesEnd, BytesStart, Event};
+use quick_xml::{Reader, Writer};
+
+/// An ODX schema version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum OdxVersion {
+    /// ODX 2.0.0 and 2.0.1.
+    V2_0,
+    /// ODX 2.2.0, the version of the model. 2.1 documents are read as 2.2.
+    #[default]
+    V2_2,
+    V2_3,
+}
+
+/// Element renames of ODX 2.0 as `(parent, ODX 2.0 name, ODX 2.2 name)`.
+const RENAMES_2_0: &[(&str, &str, &str)] = &[
+    ("COMPARAM-REF", "VALUE", "SIMPLE-VALUE"),
+    ("PARAM", "BYTE-LENGTH", "MATCH-BYTE-LENGTH"),
+];
+
+impl OdxVersion {
+    /// The model version written to the root element, e.g. `2.2.0`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::V2_0 => "2.0.1",
+            Self::V2_2 => "2.2.0",
+            Self::V2_3 => "2.3.0",
+        }
+    }
+
+    /// The version a `MODEL-VERSION` value names, e.g. `2.0.1`. `None` for
+    /// values that are not ODX 2 versions.
+    pub fn detect(model_version: &str) -> Option<Self> {
+        let mut parts = model_version.trim().split('.');
+        if parts.next()? != "2" {
+            return None;
+        }
+        match parts.next().map(str::parse::<u32>) {
+            Some(Ok(0)) => Some(Self::V2_0),
+            Some(Ok(1 | 2)) | None => Some(Self::V2_2),
+            Some(Ok(3)) => Some(Self::V2_3),
+            _ => None,
+        }
+    }
+
+    /// The version of the document `xml`, from its root element.
+    pub fn of_document(xml: &str) -> Option<Self> {
+        crate::parser::model_version(xml).and_then(|v| Self::detect(&v))
+    }
+
+    /// The name of the root attribute holding the model version.
+    fn version_attribute(self) -> &'static str {
+        match self {
+            Self::V2_0 => "VERSION",
+            Self::V2_2 | Self::V2_3 => "MODEL-VERSION",
+        }
+    }
+
+    fn renames(self) -> &'static [(&'static str, &'static str, &'static str)] {
+        match self {
+            Self::V2_0 => RENAMES_2_0,
+            Self::V2_2 | Self::V2_3 => &[],
+        }
+    }
+}
+
+impl fmt::Display for OdxVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OdxVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::detect(s)
+            .ok_or_else(|| format!("unknown ODX version '{s}' (expected 2.0.1, 2.2.0 or 2.3.0)"))
+    }
+}
+
+/// Rewrite a document of `version` to the element names of the model.
+/// Documents without differences are passed through untouched.
+pub(crate) fn to_model(xml: &str, version: OdxVersion) -> Result<Cow<'_, str>, quick_xml::Error> {
+    if version.renames().is_empty() {
+        return Ok(Cow::Borrowed(xml));
+    }
+    rewrite(xml, version.renames(), None).map(Cow::Owned)
+}
+
+/// Rewrite a document written in the element names of the model to
+/// `version`, setting the model version of its root element.
+pub(crate) fn from_model(xml: &str, version: OdxVersion) -> Result<String, quick_xml::Error> {
+    let renames: Vec<_> = version
+        .renames()
+        .iter()
+        .map(|&(parent, old, new)| (parent, new, old))
+        .collect();
+    rewrite(xml, &renames, Some(version))
+}
+
+/// Rename the elements matching `(parent, from, to)` and, with `root`, set
+/// the version attribute of the root element.
+fn rewrite(
+    xml: &str,
+    renames: &[(&str, &str, &str)],
+    root: Option<OdxVersion>,
+) -> Result<String, quick_xml::Error> {
+    let mut reader = Reader::from_str(xml);
+    let mut writer = Writer::new(Vec::with_capacity(xml.len()));
+    // Names of the open elements, as written.
+    let mut open: Vec<String> = Vec::new();
+    let start = |open: &[String], e: BytesStart<'_>| match root {
+        Some(version) if open.is_empty() => set_version(&e, version),
+        _ => {
+            let to = open.last().and_then(|parent| {
+                renames
+                    .iter()
+                    .find(|(p, from, _)| p == parent && from.as_bytes() == e.name().as_ref())
+            });
+            match to {
+                Some((_, _, to)) => renamed_start(&e, to),
+                None => Ok(e.into_owned()),
+            }
+        }
+    };
+    loop {
+        let event = match reader.read_event()? {
+            Event::Start(e) => {
+                let e = start(&open, e)?;
+                open.push(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+                Event::Start(e)
+            }
+            Event::Empty(e) => Event::Empty(start(&open, e)?),
+            Event::End(_) => Event::End(BytesEnd::new(open.pop().unwrap_or_default())),
+            Event::Eof => break,
+            other => other,
+        };
+        writer.write_event(event)?;
+    }
+    Ok(String::from_utf8_lossy(&writer.into_inner()).into_owned())
+}
+
+fn renamed_start(e: &BytesStart<'_>, name: &str) -> Result<BytesStart<'static>, quick_xml::Error> {
+    let mut out = BytesStart::new(name.to_string());
+    for attr in e.attributes() {
+        out.push_attribute(attr?);
+    }
+    Ok(out)
+}
+
+/// `e` with its model version attribute named and set for `version`.
+fn set_version(
+    e: &BytesStart<'_>,
+    version: OdxVersion,
+) -> Result<BytesStart<'static>, quick_xml::Error> {
+    let mut out = BytesStart::new(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+    out.push_attribute((version.version_attribute(), version.as_str()));
+    for attr in e.attributes() {
+        let attr = attr?;
+        if !matches!(attr.key.as_ref(), b"VERSION" | b"MODEL-VERSION") {
+            out.push_attribute(attr);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(OdxVersion::detect("2.0.1"), Some(OdxVersion::V2_0));
+        assert_eq!(OdxVersion::detect("2.0"), Some(OdxVersion::V2_0));
+        assert_eq!(OdxVersion::detect("2.1.0"), Some(OdxVersion::V2_2));
+        assert_eq!(OdxVersion::detect("2.2.0"), Some(OdxVersion::V2_2));
+        assert_eq!(OdxVersion::detect(" 2.3.0 "), Some(OdxVersion::V2_3));
+        assert_eq!(OdxVersion::detect("1.0"), None);
+        assert_eq!(OdxVersion::detect("2.x"), None);
+        assert!("3.0".parse::<OdxVersion>().is_err());
+        assert_eq!(
+            OdxVersion::of_document(r#"<ODX VERSION="2.0.1"><X/></ODX>"#),
+            Some(OdxVersion::V2_0)
+        );
+    }
+
+    #[test]
+    fn test_rewrite_2_0() {
+        let v20 = r#"<ODX VERSION="2.0.1"><COMPARAM-REF ID-REF="c"><VALUE>5</VALUE></COMPARAM-REF><PARAM><VALUE>1</VALUE><BYTE-LENGTH>2</BYTE-LENGTH></PARAM></ODX>"#;
+        let model = to_model(v20, OdxVersion::V2_0).unwrap();
+        assert_eq!(
+            model,
+            r#"<ODX VERSION="2.0.1"><COMPARAM-REF ID-REF="c"><SIMPLE-VALUE>5</SIMPLE-VALUE></COMPARAM-REF><PARAM><VALUE>1</VALUE><MATCH-BYTE-LENGTH>2</MATCH-BYTE-LENGTH></PARAM></ODX>"#
+        );
+        assert_eq!(from_model(&model, OdxVersion::V2_0).unwrap(), v20);
+        assert!(matches!(
+            to_model(v20, OdxVersion::V2_2).unwrap(),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_root_version_attribute() {
+        let xml = r#"<ODX VERSION="2.2.0" xmlns:xsi="x"><A/></ODX>"#;
+        assert_eq!(
+            from_model(xml, OdxVersion::V2_3).unwrap(),
+            r#"<ODX MODEL-VERSION="2.3.0" xmlns:xsi="x"><A/></ODX>"#
+        );
+        assert_eq!(
+            from_model("<ODX><A/></ODX>", OdxVersion::V2_0).unwrap(),
+            r#"<ODX VERSION="2.0.1"><A/></ODX>"#
+        );
+    }
+}
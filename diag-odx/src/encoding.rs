@@ -0,0 +1,136 @@
+//! Decoding of ODX file bytes into text.
+//!
+//! The encoding comes from the byte order mark, or else from the
+//! `encoding` of the XML declaration (UTF-8 when absent). UTF-8, UTF-16 and
+//! ISO-8859-1 are supported; BOMs are stripped.
+
+use std::borrow::Cow;
+
+use crate::parser::OdxParseError;
+
+/// Decode an ODX document to a string.
+pub fn decode_odx(bytes: &[u8]) -> Result<Cow<'_, str>, OdxParseError> {
+    if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        return utf8(rest);
+    }
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => return utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => return utf16(rest, u16::from_be_bytes),
+        // `<` as UTF-16 without a BOM
+        [b'<', 0, ..] => return utf16(bytes, u16::from_le_bytes),
+        [0, b'<', ..] => return utf16(bytes, u16::from_be_bytes),
+        _ => {}
+    }
+
+    let encoding = declared_encoding(bytes).unwrap_or_default();
+    match encoding.to_ascii_lowercase().as_str() {
+        "" | "utf-8" | "utf8" | "us-ascii" | "ascii" => utf8(bytes),
+        "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1" | "latin-1" | "l1" => {
+            Ok(Cow::Owned(bytes.iter().map(|&b| char::from(b)).collect()))
+        }
+        // Declared, but the bytes have no BOM and no UTF-16 layout.
+        "utf-16" | "utf-16le" | "utf-16be" => Err(OdxParseError::Encoding(format!(
+            "declared as {encoding}, but the content is not UTF-16"
+        ))),
+        _ => Err(OdxParseError::Encoding(format!(
+            "unsupported encoding '{encoding}' (supported: UTF-8, UTF-16, ISO-8859-1)"
+        ))),
+    }
+}
+
+/// Name of the encoding [`decode_odx`] would use for `bytes`, with the
+/// source it is taken from (`BOM`, `declared`, or `default`).
+pub fn detect_encoding(bytes: &[u8]) -> String {
+    match bytes {
+        [0xEF, 0xBB, 0xBF, ..] => "UTF-8 (BOM)".into(),
+        [0xFF, 0xFE, ..] => "UTF-16LE (BOM)".into(),
+        [0xFE, 0xFF, ..] => "UTF-16BE (BOM)".into(),
+        [b'<', 0, ..] => "UTF-16LE (no BOM)".into(),
+        [0, b'<', ..] => "UTF-16BE (no BOM)".into(),
+        _ => match declared_encoding(bytes) {
+            Some(encoding) => format!("{encoding} (declared)"),
+            None => "UTF-8 (default)".into(),
+        },
+    }
+}
+
+fn utf8(bytes: &[u8]) -> Result<Cow<'_, str>, OdxParseError> {
+    std::str::from_utf8(bytes)
+        .map(Cow::Borrowed)
+        .map_err(|e| OdxParseError::Encoding(format!("invalid UTF-8: {e}")))
+}
+
+fn utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> Result<Cow<'static, str>, OdxParseError> {
+    let chunks = bytes.chunks_exact(2);
+    if !chunks.remainder().is_empty() {
+        return Err(OdxParseError::Encoding(
+            "UTF-16 content has an odd number of bytes".into(),
+        ));
+    }
+    char::decode_utf16(chunks.map(|c| unit([c[0], c[1]])))
+        .collect::<Result<String, _>>()
+        .map(Cow::Owned)
+        .map_err(|e| OdxParseError::Encoding(format!("invalid UTF-16: {e}")))
+}
+
+/// The `encoding` pseudo-attribute of an ASCII-compatible XML declaration.
+fn declared_encoding(bytes: &[u8]) -> Option<String> {
+    let rest = bytes.strip_prefix(b"<?xml")?;
+    let decl = &rest[..rest.windows(2).position(|w| w == b"?>")?];
+    let decl = std::str::from_utf8(decl).ok()?;
+    let value = decl
+        .split_once("encoding")?
+        .1
+        .trim_start()
+        .strip_prefix('=')?;
+    let value = value.trim_start();
+    let quote = value.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+    let value = &value[1..];
+    Some(value[..value.find(quote)?].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latin1_is_transcoded() {
+        let bytes = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><A>Stra\xDFe</A>";
+        assert_eq!(
+            decode_odx(bytes).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><A>Stra\u{df}e</A>"
+        );
+    }
+
+    #[test]
+    fn test_boms_are_stripped() {
+        assert_eq!(decode_odx(b"\xEF\xBB\xBF<A/>").unwrap(), "<A/>");
+        let le: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("<A>\u{e4}</A>".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(decode_odx(&le).unwrap(), "<A>\u{e4}</A>");
+        let be: Vec<u8> = "<A/>".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(decode_odx(&be).unwrap(), "<A/>");
+    }
+
+    #[test]
+    fn test_unsupported_encoding_is_named() {
+        let err = decode_odx(b"<?xml version='1.0' encoding='Shift_JIS'?><A/>").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cannot decode ODX input: unsupported encoding 'Shift_JIS' (supported: UTF-8, UTF-16, ISO-8859-1)"
+        );
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        assert_eq!(detect_encoding(b"\xEF\xBB\xBF<A/>"), "UTF-8 (BOM)");
+        assert_eq!(detect_encoding(b"<\0A\0"), "UTF-16LE (no BOM)");
+        assert_eq!(
+            detect_encoding(b"<?xml version='1.0' encoding='ISO-8859-1'?><A/>"),
+            "ISO-8859-1 (declared)"
+        );
+        assert_eq!(detect_encoding(b"<A/>"), "UTF-8 (default)");
+    }
+}
@@ -0,0 +1,140 @@
+//! ODX IDs of the layers, services, jobs and DTCs of ODX documents.
+//!
+//! The IR keeps short names but not the IDs of the elements it was mapped
+//! from. Trace tooling that refers to ODX IDs needs them next to the short
+//! names; [`load_odx_ids`] reads them from the documents directly.
+
+use diag_ir::SearchKind;
+use std::path::PathBuf;
+
+use crate::encoding::decode_odx;
+use crate::odx_model::{DiagCommEntry, DiagLayerVariant, Odx};
+use crate::parser::{ParseOptions, deserialize_odx};
+use crate::project::{OdxProjectError, collect_documents};
+
+/// The ID of an ODX element and the short names identifying it in the IR.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OdxId {
+    pub kind: SearchKind,
+    /// Short name of the layer defining the element (the layer itself for
+    /// layers).
+    pub layer: String,
+    pub short_name: String,
+    pub id: String,
+}
+
+/// The IDs of the documents at `paths`, which are found like the documents
+/// of [`load_odx_project`](crate::load_odx_project). Elements without an ID
+/// are skipped.
+pub fn load_odx_ids(paths: &[PathBuf]) -> Result<Vec<OdxId>, OdxProjectError> {
+    let mut documents = Vec::new();
+    for path in paths {
+        collect_documents(path, &mut documents)?;
+    }
+    let mut ids = Vec::new();
+    let mut found = false;
+    for (name, bytes) in documents {
+        let parse_err = |source| OdxProjectError::OdxParse {
+            file: name.clone(),
+            source,
+        };
+        let xml = decode_odx(&bytes).map_err(parse_err)?;
+        let odx = deserialize_odx(&xml, &ParseOptions::default()).map_err(parse_err)?;
+        found |= odx.diag_layer_container.is_some();
+        collect_ids(&odx, &mut ids);
+    }
+    if !found {
+        return Err(OdxProjectError::NoOdxFiles);
+    }
+    Ok(ids)
+}
+
+/// Append the IDs of the layers of `odx` and of their services, jobs and
+/// DTCs to `ids`, in document order.
+fn collect_ids(odx: &Odx, ids: &mut Vec<OdxId>) {
+    let Some(dlc) = &odx.diag_layer_container else {
+        return;
+    };
+    let layers = [
+        dlc.protocols.as_ref().map(|w| &w.items),
+        dlc.functional_groups.as_ref().map(|w| &w.items),
+        dlc.base_variants.as_ref().map(|w| &w.items),
+        dlc.ecu_variants.as_ref().map(|w| &w.items),
+        dlc.ecu_shared_datas.as_ref().map(|w| &w.items),
+    ];
+    for layer in layers.into_iter().flatten().flatten() {
+        layer_ids(layer, ids);
+    }
+}
+
+fn layer_ids(layer: &DiagLayerVariant, ids: &mut Vec<OdxId>) {
+    let layer_name = layer.short_name.clone().unwrap_or_default();
+    let mut push = |kind, short_name: Option<&str>, id: Option<&str>| {
+        if let Some(id) = id {
+            ids.push(OdxId {
+                kind,
+                layer: layer_name.clone(),
+                short_name: short_name.unwrap_or_default().to_string(),
+                id: id.to_string(),
+            });
+        }
+    };
+    push(
+        SearchKind::Layer,
+        layer.short_name.as_deref(),
+        layer.id.as_deref(),
+    );
+    for entry in layer.diag_comms.iter().flat_map(|w| &w.items) {
+        match entry {
+            DiagCommEntry::DiagService(svc) => push(
+                SearchKind::Service,
+                svc.short_name.as_deref(),
+                svc.id.as_deref(),
+            ),
+            DiagCommEntry::SingleEcuJob(job) => push(
+                SearchKind::Job,
+                job.short_name.as_deref(),
+                job.id.as_deref(),
+            ),
+            DiagCommEntry::DiagCommRef(_) => {}
+        }
+    }
+    let dtcs = layer
+        .diag_data_dictionary_spec
+        .iter()
+        .flat_map(|spec| &spec.dtc_dops)
+        .flat_map(|w| &w.items)
+        .flat_map(|dop| &dop.dtcs)
+        .flat_map(|w| &w.items);
+    for dtc in dtcs {
+        push(
+            SearchKind::Dtc,
+            dtc.short_name.as_deref(),
+            dtc.id.as_deref(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_ids_of_a_project() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test-fixtures/odx/project");
+        let ids = load_odx_ids(&[dir]).unwrap();
+        let variant = ids
+            .iter()
+            .find(|id| id.kind == SearchKind::Layer && id.short_name == "SplitECU_HW1")
+            .expect("ECU variant");
+        assert_eq!(variant.layer, "SplitECU_HW1");
+        assert_eq!(variant.id, "EV_Split_HW1");
+        let service = ids
+            .iter()
+            .find(|id| id.kind == SearchKind::Service && id.short_name == "Read_Voltage")
+            .expect("Read_Voltage");
+        assert_eq!(service.layer, "SplitECU_HW1");
+        assert_eq!(service.id, "DS_ReadVoltage");
+    }
+}
@@ -1,10 +1,29 @@
+pub mod desc;
+pub mod dialect;
+pub mod encoding;
+pub mod ids;
 pub mod inheritance;
+mod location;
 pub mod odx_model;
 pub mod parser;
 pub mod pdx_reader;
+pub mod pdx_writer;
+pub mod project;
 pub mod ref_resolver;
+mod split;
+pub mod unknown_xml;
 pub mod writer;
+mod xsi_type;
 
-pub use parser::{OdxParseError, parse_odx, parse_odx_lenient};
-pub use pdx_reader::{PdxReadError, read_pdx_file};
-pub use writer::{OdxWriteError, write_odx};
+pub use dialect::OdxVersion;
+pub use encoding::{decode_odx, detect_encoding};
+pub use ids::{OdxId, load_odx_ids};
+pub use parser::{
+    OdxParseError, ParseOptions, model_version, parse_odx, parse_odx_lenient, parse_odx_with,
+};
+pub use pdx_reader::{PdxReadError, read_pdx_entries, read_pdx_file};
+pub use pdx_writer::{PdxFile, PdxWriteError, PdxWriteOptions, write_pdx_file};
+pub use project::{OdxProjectError, load_odx_project, load_odx_project_with};
+pub use writer::{
+    CAPABILITIES, OdxWriteError, OdxWriteOptions, write_odx, write_odx_pretty, write_odx_with,
+};
@@ -0,0 +1,182 @@
+//! Byte offsets of parse errors in the ODX text.
+//!
+//! The serde deserializer of quick-xml does not report positions, so when it
+//! fails the document is deserialized once more through
+//! `serde_path_to_error`, and the element path it reports (`DIAG-LAYER-
+//! CONTAINER.BASE-VARIANTS.BASE-VARIANT[1].SHORT-NAME`) is looked up in the
+//! original text. The `xsi:type` rewrite leaves the element structure alone,
+//! so the path found in the normalized text holds in the original too.
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use serde_path_to_error::Segment;
+
+use crate::xsi_type::local_name;
+
+/// Offset at which `xml` stops being well-formed, if it does.
+pub(crate) fn syntax_error_offset(xml: &str) -> Option<usize> {
+    let mut reader = Reader::from_str(xml);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Eof) => return None,
+            Ok(_) => {}
+            Err(_) => return usize::try_from(reader.error_position()).ok(),
+        }
+    }
+}
+
+/// Offset of the element (or attribute) `path` leads to, as far as the
+/// document has it. Struct fields are child elements, `@NAME` fields are
+/// attributes and `$value` sequences are children of any name.
+pub(crate) fn element_offset<'a>(
+    xml: &str,
+    path: impl IntoIterator<Item = &'a Segment>,
+) -> Option<usize> {
+    let tree = Tree::parse(xml);
+    let mut node = tree.nodes.first()?;
+    let mut segments = path.into_iter().peekable();
+    while let Some(segment) = segments.next() {
+        let Segment::Map { key } = segment else {
+            continue;
+        };
+        let index = match segments.peek() {
+            Some(Segment::Seq { index }) => {
+                segments.next();
+                *index
+            }
+            _ => 0,
+        };
+        if let Some(attribute) = key.strip_prefix('@') {
+            return Some(attribute_offset(xml, node.offset, attribute).unwrap_or(node.offset));
+        }
+        let mut children = node.children.iter().map(|&i| &tree.nodes[i]);
+        let child = match key.as_str() {
+            "$text" => None,
+            "$value" => children.nth(index),
+            name => children.filter(|c| c.name == name).nth(index),
+        };
+        match child {
+            Some(child) => node = child,
+            None => break,
+        }
+    }
+    Some(node.offset)
+}
+
+/// Offset of `name="..."` in the start tag at `tag`.
+fn attribute_offset(xml: &str, tag: usize, name: &str) -> Option<usize> {
+    let end = tag + xml.get(tag..)?.find('>')?;
+    let start_tag = &xml[tag..end];
+    let mut from = 0;
+    while let Some(found) = start_tag[from..].find(name) {
+        let at = from + found;
+        let before = start_tag[..at].chars().next_back();
+        let after = start_tag[at + name.len()..].trim_start();
+        if before.is_some_and(|c| c.is_whitespace() || c == ':') && after.starts_with('=') {
+            return Some(tag + at);
+        }
+        from = at + name.len();
+    }
+    None
+}
+
+struct Node {
+    name: String,
+    offset: usize,
+    children: Vec<usize>,
+}
+
+/// The elements of a document, root first, with their start offsets.
+struct Tree {
+    nodes: Vec<Node>,
+}
+
+impl Tree {
+    /// Read as much of `xml` as is well-formed.
+    fn parse(xml: &str) -> Self {
+        let mut reader = Reader::from_str(xml);
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut open: Vec<usize> = Vec::new();
+        loop {
+            let offset = usize::try_from(reader.buffer_position()).unwrap_or(usize::MAX);
+            let (start, empty) = match reader.read_event() {
+                Ok(Event::Start(e)) => (e, false),
+                Ok(Event::Empty(e)) => (e, true),
+                Ok(Event::End(_)) => {
+                    open.pop();
+                    continue;
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                Ok(_) => continue,
+            };
+            let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+            let index = nodes.len();
+            nodes.push(Node {
+                name: local_name(&name).to_string(),
+                offset,
+                children: Vec::new(),
+            });
+            if let Some(&parent) = open.last() {
+                nodes[parent].children.push(index);
+            }
+            if !empty {
+                open.push(index);
+            }
+        }
+        Self { nodes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<ODX MODEL-VERSION="2.2.0">
+  <DIAG-LAYER-CONTAINER ID="DLC">
+    <BASE-VARIANTS>
+      <BASE-VARIANT ID="BV1"><SHORT-NAME>A</SHORT-NAME></BASE-VARIANT>
+      <BASE-VARIANT ID="BV2" xsi:type="X"><SHORT-NAME>B</SHORT-NAME></BASE-VARIANT>
+    </BASE-VARIANTS>
+  </DIAG-LAYER-CONTAINER>
+</ODX>"#;
+
+    fn map(key: &str) -> Segment {
+        Segment::Map { key: key.into() }
+    }
+
+    #[test]
+    fn test_element_offset_follows_the_path() {
+        let path = [
+            map("DIAG-LAYER-CONTAINER"),
+            map("BASE-VARIANTS"),
+            map("BASE-VARIANT"),
+            Segment::Seq { index: 1 },
+            map("SHORT-NAME"),
+        ];
+        assert_eq!(
+            element_offset(XML, &path),
+            Some(XML.find("<SHORT-NAME>B").unwrap())
+        );
+        let attribute = [map("DIAG-LAYER-CONTAINER"), map("@ID")];
+        assert_eq!(
+            element_offset(XML, &attribute),
+            Some(XML.find("ID=\"DLC\"").unwrap())
+        );
+        // Elements the document does not have stop the walk at their parent.
+        let missing = [map("DIAG-LAYER-CONTAINER"), map("ECU-VARIANTS")];
+        assert_eq!(
+            element_offset(XML, &missing),
+            Some(XML.find("<DIAG-LAYER-CONTAINER").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_syntax_error_offset() {
+        assert_eq!(syntax_error_offset(XML), None);
+        let broken = "<ODX>\n  <A></B>\n</ODX>";
+        assert_eq!(
+            syntax_error_offset(broken),
+            Some(broken.find("</B>").unwrap())
+        );
+    }
+}
@@ -89,10 +89,19 @@ pub struct ProtocolsWrapper {
 pub struct DiagLayerVariant {
     #[serde(rename = "@ID", skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
+    /// Preserved unknown attributes; written only (see `unknown_xml`).
+    #[serde(
+        rename = "@X-PRESERVED-ATTRS",
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub preserved_attrs: Option<String>,
     #[serde(rename = "SHORT-NAME", skip_serializing_if = "Option::is_none")]
     pub short_name: Option<String>,
     #[serde(rename = "LONG-NAME", skip_serializing_if = "Option::is_none")]
     pub long_name: Option<String>,
+    #[serde(rename = "DESC", skip_serializing_if = "Option::is_none")]
+    pub desc: Option<OdxDesc>,
     #[serde(rename = "ADMIN-DATA", skip_serializing_if = "Option::is_none")]
     pub admin_data: Option<AdminData>,
     #[serde(rename = "SDGS", skip_serializing_if = "Option::is_none")]
@@ -134,6 +143,13 @@ pub struct DiagLayerVariant {
         skip_serializing_if = "Option::is_none"
     )]
     pub ecu_variant_patterns: Option<EcuVariantPatternsWrapper>,
+    /// Preserved unknown elements; written only (see `unknown_xml`).
+    #[serde(
+        rename = "X-PRESERVED-XML",
+        skip_deserializing,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub preserved_xml: Vec<String>,
 }
 
 // --- List wrappers ---
@@ -240,10 +256,19 @@ pub struct OdxDiagService {
     pub addressing: Option<String>,
     #[serde(rename = "@TRANSMISSION-MODE", skip_serializing_if = "Option::is_none")]
     pub transmission_mode: Option<String>,
+    /// Preserved unknown attributes; written only (see `unknown_xml`).
+    #[serde(
+        rename = "@X-PRESERVED-ATTRS",
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub preserved_attrs: Option<String>,
     #[serde(rename = "SHORT-NAME", skip_serializing_if = "Option::is_none")]
     pub short_name: Option<String>,
     #[serde(rename = "LONG-NAME", skip_serializing_if = "Option::is_none")]
     pub long_name: Option<String>,
+    #[serde(rename = "DESC", skip_serializing_if = "Option::is_none")]
+    pub desc: Option<OdxDesc>,
     #[serde(rename = "SDGS", skip_serializing_if = "Option::is_none")]
     pub sdgs: Option<SdgsWrapper>,
     #[serde(rename = "FUNCT-CLASS-REFS", skip_serializing_if = "Option::is_none")]
@@ -268,6 +293,13 @@ pub struct OdxDiagService {
     pub state_transition_refs: Option<StateTransitionRefsWrapper>,
     #[serde(rename = "COMPARAM-REFS", skip_serializing_if = "Option::is_none")]
     pub comparam_refs: Option<ComparamRefsWrapper>,
+    /// Preserved unknown elements; written only (see `unknown_xml`).
+    #[serde(
+        rename = "X-PRESERVED-XML",
+        skip_deserializing,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub preserved_xml: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -310,6 +342,8 @@ pub struct OdxSingleEcuJob {
     pub short_name: Option<String>,
     #[serde(rename = "LONG-NAME", skip_serializing_if = "Option::is_none")]
     pub long_name: Option<String>,
+    #[serde(rename = "DESC", skip_serializing_if = "Option::is_none")]
+    pub desc: Option<OdxDesc>,
     #[serde(rename = "SDGS", skip_serializing_if = "Option::is_none")]
     pub sdgs: Option<SdgsWrapper>,
     #[serde(rename = "PROG-CODES", skip_serializing_if = "Option::is_none")]
@@ -378,6 +412,13 @@ pub struct OdxResponse {
     pub byte_size: Option<u32>,
     #[serde(rename = "PARAMS", skip_serializing_if = "Option::is_none")]
     pub params: Option<ParamsWrapper>,
+    #[serde(
+        rename = "POS-RESPONSE-SUFFIX",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub pos_response_suffix: Option<String>,
+    #[serde(rename = "AUDIENCE", skip_serializing_if = "Option::is_none")]
+    pub audience: Option<OdxAudience>,
 }
 
 // --- Params ---
@@ -1381,6 +1422,70 @@ pub struct OdxText {
     pub value: Option<String>,
 }
 
+// --- DESC (XHTML rich text) ---
+
+/// Content of a DESC element (or of any XHTML element nested inside it).
+///
+/// Serialization is hand-written: quick-xml cannot serialize text nodes inside
+/// a `$value` sequence, so text-only content is written as a single `$text`
+/// field. The writer only produces text-only leaves (see `crate::desc`).
+#[derive(Debug, Default, Deserialize)]
+pub struct OdxDesc {
+    #[serde(rename = "$value", default)]
+    pub nodes: Vec<OdxDescNode>,
+}
+
+impl Serialize for OdxDesc {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut st = serializer.serialize_struct("OdxDesc", 1)?;
+        let texts: Option<Vec<&str>> = self
+            .nodes
+            .iter()
+            .map(|n| match n {
+                OdxDescNode::Text(t) => Some(t.as_str()),
+                _ => None,
+            })
+            .collect();
+        match texts {
+            Some(texts) => st.serialize_field("$text", &texts.join(" "))?,
+            None => st.serialize_field("$value", &self.nodes)?,
+        }
+        st.end()
+    }
+}
+
+/// XHTML subset used by ODX DESC. Inline markup is kept only for its text;
+/// anything unrecognized (tables, images, ...) is dropped.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum OdxDescNode {
+    #[serde(rename = "$text")]
+    Text(String),
+    #[serde(rename = "p")]
+    Paragraph(OdxDesc),
+    #[serde(rename = "ul")]
+    UnorderedList(OdxDesc),
+    #[serde(rename = "ol")]
+    OrderedList(OdxDesc),
+    #[serde(rename = "li")]
+    ListItem(OdxDesc),
+    #[serde(
+        rename = "span",
+        alias = "b",
+        alias = "i",
+        alias = "u",
+        alias = "em",
+        alias = "strong",
+        alias = "sub",
+        alias = "sup",
+        alias = "a"
+    )]
+    Inline(OdxDesc),
+    #[serde(other)]
+    Other,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AdminData {
     #[serde(rename = "LANGUAGE", skip_serializing_if = "Option::is_none")]
@@ -1403,6 +1508,24 @@ pub struct DocRevision {
     pub state: Option<String>,
     #[serde(rename = "DATE", skip_serializing_if = "Option::is_none")]
     pub date: Option<String>,
+    #[serde(rename = "TOOL", skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    #[serde(rename = "MODIFICATIONS", skip_serializing_if = "Option::is_none")]
+    pub modifications: Option<ModificationsWrapper>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ModificationsWrapper {
+    #[serde(rename = "MODIFICATION", default)]
+    pub items: Vec<Modification>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Modification {
+    #[serde(rename = "CHANGE", skip_serializing_if = "Option::is_none")]
+    pub change: Option<String>,
+    #[serde(rename = "REASON", skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -11,40 +11,196 @@ use std::collections::HashMap;
 use diag_ir::*;
 use thiserror::Error;
 
+use crate::desc::desc_to_markdown;
+use crate::dialect::OdxVersion;
 use crate::inheritance::MergedLayer;
 use crate::odx_model::{self, Odx};
 use crate::ref_resolver::{LayerType, OdxIndex};
+use crate::xsi_type::local_name;
 
 #[derive(Debug, Error)]
 pub enum OdxParseError {
     #[error("XML deserialization failed: {0}")]
     XmlError(#[from] quick_xml::DeError),
+    #[error("Cannot decode ODX input: {0}")]
+    Encoding(String),
+    #[error("XML read failed: {0}")]
+    XmlRead(#[from] quick_xml::Error),
     #[error("Missing required element: {0}")]
     MissingElement(String),
+    #[error("Invalid value '{value}' for {attribute} in {element}")]
+    InvalidAttribute {
+        element: String,
+        attribute: String,
+        value: String,
+    },
+    #[error(transparent)]
+    Duplicates(#[from] DuplicateError),
+    /// `error` at byte `offset` of the input, inside the element `path`
+    /// (empty when the XML itself is broken).
+    #[error("{error}{}", in_element(path))]
+    Located {
+        offset: usize,
+        path: String,
+        error: Box<OdxParseError>,
+    },
+}
+
+fn in_element(path: &str) -> String {
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!(" (in {path})")
+    }
+}
+
+impl OdxParseError {
+    /// Byte offset in the input the error was found at, when known.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Self::Located { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+}
+
+/// Settings for [`parse_odx_with`].
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Skip malformed DOPs and missing refs instead of failing.
+    pub lenient: bool,
+    /// Keep elements and attributes of diag layers and services that the
+    /// model does not know as raw XML, so the ODX writer can re-emit them.
+    pub preserve_unknown_xml: bool,
+    /// Additional `xsi:type` values as `(alias, canonical)`, e.g.
+    /// `("VALUE_PARAM", "VALUE")` for a tool with its own type names.
+    pub type_aliases: Vec<(String, String)>,
+    /// What to do with a trouble code defined by several DTC-DOPs with
+    /// different texts. Identical copies are always merged.
+    pub duplicates: DuplicatePolicy,
+    /// Deserialize large documents in a single pass on the calling thread
+    /// instead of splitting their diag layers across the rayon pool.
+    pub sequential: bool,
 }
 
 /// Parse an ODX XML string into an IR DiagDatabase.
 pub fn parse_odx(xml: &str) -> Result<DiagDatabase, OdxParseError> {
-    parse_odx_with_options(xml, false)
+    parse_odx_with(xml, &ParseOptions::default())
 }
 
 /// Parse an ODX XML string in lenient mode (skip malformed DOPs, missing refs).
 pub fn parse_odx_lenient(xml: &str) -> Result<DiagDatabase, OdxParseError> {
-    parse_odx_with_options(xml, true)
+    parse_odx_with(
+        xml,
+        &ParseOptions {
+            lenient: true,
+            ..Default::default()
+        },
+    )
+}
+
+/// The `MODEL-VERSION` (ODX 2.0: `VERSION`) of the `<ODX>` root element,
+/// read without parsing the rest of the document.
+pub fn model_version(xml: &str) -> Option<String> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_str(xml);
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(e) | Event::Empty(e) => {
+                if e.local_name().as_ref() != b"ODX" {
+                    return None;
+                }
+                let attr = e.attributes().flatten().find(|a| {
+                    matches!(a.key.local_name().as_ref(), b"MODEL-VERSION" | b"VERSION")
+                })?;
+                return attr.unescape_value().ok().map(std::borrow::Cow::into_owned);
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
 }
 
-fn parse_odx_with_options(xml: &str, lenient: bool) -> Result<DiagDatabase, OdxParseError> {
+/// Parse an ODX XML string with explicit [`ParseOptions`].
+pub fn parse_odx_with(xml: &str, options: &ParseOptions) -> Result<DiagDatabase, OdxParseError> {
     // Phase 1: XML deserialization
-    let odx: Odx = quick_xml::de::from_str(xml)?;
+    let odx = deserialize_odx(xml, options)?;
 
     // Phase 2: Build reference index
     let index = OdxIndex::build(&odx);
 
-    // Phase 3 + 4: Merge inheritance and map to IR
-    odx_to_ir(&odx, &index, lenient)
+    // Phase 3 + 4: Merge inheritance and map to IR, sharing repeated names
+    let mut db = Interner::default().enter(|| odx_to_ir(&odx, &index, options.lenient))?;
+    for duplicate in resolve_dtc_duplicates(&mut db.dtcs, options.duplicates)? {
+        log::warn!("{duplicate}; {}", options.duplicates.resolution(&duplicate));
+    }
+    if options.preserve_unknown_xml {
+        crate::unknown_xml::attach(&mut db, crate::unknown_xml::scan(xml)?);
+    }
+    Ok(db)
+}
+
+/// Phase 1 alone: the ODX tree of `xml`, with the spellings of its ODX
+/// version and `xsi:type` aliases applied.
+pub(crate) fn deserialize_odx(xml: &str, options: &ParseOptions) -> Result<Odx, OdxParseError> {
+    let version = OdxVersion::of_document(xml).unwrap_or_default();
+    if version != OdxVersion::V2_2 {
+        log::debug!("Reading an ODX {version} document");
+    }
+    let upgraded =
+        crate::dialect::to_model(xml, version).map_err(|e| locate(xml, String::new(), e.into()))?;
+    let normalized = crate::xsi_type::normalize(&upgraded, &options.type_aliases)
+        .map_err(|e| locate(xml, String::new(), e.into()))?;
+    let split = !options.sequential && normalized.len() >= crate::split::MIN_SIZE;
+    if let Some(odx) = split
+        .then(|| crate::split::deserialize(&normalized))
+        .flatten()
+    {
+        return Ok(odx);
+    }
+    quick_xml::de::from_str(&normalized).map_err(|e| locate_de(xml, &normalized, e))
+}
+
+/// Attach the offset of a syntax error to `error`, when `xml` has one.
+fn locate(xml: &str, path: String, error: OdxParseError) -> OdxParseError {
+    match crate::location::syntax_error_offset(xml) {
+        Some(offset) => OdxParseError::Located {
+            offset,
+            path,
+            error: Box::new(error),
+        },
+        None => error,
+    }
+}
+
+/// Find where deserializing `normalized` failed: broken XML at the reader's
+/// error position, anything else at the element serde was reading. Only runs
+/// on failure, so the second pass costs nothing on valid input.
+fn locate_de(xml: &str, normalized: &str, error: quick_xml::DeError) -> OdxParseError {
+    if matches!(error, quick_xml::DeError::InvalidXml(_)) {
+        return locate(xml, String::new(), error.into());
+    }
+    let mut de = quick_xml::de::Deserializer::from_str(normalized);
+    let Err(tracked) = serde_path_to_error::deserialize::<_, Odx>(&mut de) else {
+        return error.into();
+    };
+    let path = tracked.path();
+    match crate::location::element_offset(xml, path.iter()) {
+        Some(offset) => OdxParseError::Located {
+            offset,
+            path: path.to_string(),
+            error: Box::new(error.into()),
+        },
+        None => error.into(),
+    }
 }
 
-fn odx_to_ir(odx: &Odx, index: &OdxIndex, lenient: bool) -> Result<DiagDatabase, OdxParseError> {
+pub(crate) fn odx_to_ir(
+    odx: &Odx,
+    index: &OdxIndex,
+    lenient: bool,
+) -> Result<DiagDatabase, OdxParseError> {
     let dlc = odx
         .diag_layer_container
         .as_ref()
@@ -121,13 +277,11 @@ fn odx_to_ir(odx: &Odx, index: &OdxIndex, lenient: bool) -> Result<DiagDatabase,
     // DIAG-COMM-REF or inheritance.
     let service_protocols = build_service_protocol_map(&protocols);
 
-    // Dedup DTCs by trouble_code
-    dedup_dtcs(&mut all_dtcs);
-
     // Apply protocol associations to services in variants and functional groups
     apply_protocol_associations(&mut variants, &mut functional_groups, &service_protocols);
 
-    Ok(DiagDatabase {
+    let container_sdgs = map_sdgs_opt(&dlc.sdgs);
+    let mut db = DiagDatabase {
         version,
         ecu_name,
         revision,
@@ -138,8 +292,12 @@ fn odx_to_ir(odx: &Odx, index: &OdxIndex, lenient: bool) -> Result<DiagDatabase,
         ecu_shared_datas,
         dtcs: all_dtcs,
         memory: None,
-        type_definitions: vec![],
-    })
+        type_definitions: type_definitions_of(container_sdgs.as_ref()),
+        transport: transport_of(container_sdgs.as_ref()),
+        identification: identification_of(container_sdgs.as_ref()),
+    };
+    complete_transport(&mut db);
+    Ok(db)
 }
 
 fn layer_to_variant(
@@ -249,7 +407,7 @@ fn build_diag_layer(
         .diag_services
         .iter()
         .map(|ds| map_diag_service(ds, index, &req_map, &pos_resp_map, &neg_resp_map, lenient))
-        .collect();
+        .collect::<Result<_, _>>()?;
 
     // Map single ECU jobs
     let single_ecu_jobs: Vec<SingleEcuJob> = merged
@@ -277,7 +435,7 @@ fn build_diag_layer(
         w.items
             .iter()
             .map(|fc| diag_ir::FunctClass {
-                short_name: fc.short_name.clone().unwrap_or_default(),
+                short_name: fc.short_name.as_deref().unwrap_or_default().into(),
             })
             .collect()
     } else {
@@ -297,7 +455,7 @@ fn build_diag_layer(
     let sdgs = map_sdgs_opt(&layer.sdgs);
 
     let diag_layer = DiagLayer {
-        short_name: layer.short_name.clone().unwrap_or_default(),
+        short_name: layer.short_name.as_deref().unwrap_or_default().into(),
         long_name: layer.long_name.as_ref().map(|ln| LongName {
             value: ln.clone(),
             ti: String::new(),
@@ -309,6 +467,7 @@ fn build_diag_layer(
         state_charts,
         additional_audiences,
         sdgs,
+        description: layer.desc.as_ref().and_then(desc_to_markdown),
     };
 
     Ok((diag_layer, dtcs))
@@ -323,7 +482,7 @@ fn map_diag_service(
     pos_resp_map: &HashMap<&str, &odx_model::OdxResponse>,
     neg_resp_map: &HashMap<&str, &odx_model::OdxResponse>,
     lenient: bool,
-) -> DiagService {
+) -> Result<DiagService, OdxParseError> {
     let request = ds
         .request_ref
         .as_ref()
@@ -340,8 +499,9 @@ fn map_diag_service(
                 .filter_map(|r| r.id_ref.as_deref())
                 .filter_map(|id| pos_resp_map.get(id).or_else(|| index.pos_responses.get(id)))
                 .map(|r| map_response(r, index, ResponseType::PosResponse, lenient))
-                .collect()
+                .collect::<Result<_, _>>()
         })
+        .transpose()?
         .unwrap_or_default();
 
     let neg_responses = ds
@@ -353,20 +513,21 @@ fn map_diag_service(
                 .filter_map(|r| r.id_ref.as_deref())
                 .filter_map(|id| neg_resp_map.get(id).or_else(|| index.neg_responses.get(id)))
                 .map(|r| map_response(r, index, ResponseType::NegResponse, lenient))
-                .collect()
+                .collect::<Result<_, _>>()
         })
+        .transpose()?
         .unwrap_or_default();
 
     let audience = ds.audience.as_ref().map(map_audience);
 
-    DiagService {
+    Ok(DiagService {
         diag_comm: DiagComm {
-            short_name: ds.short_name.clone().unwrap_or_default(),
+            short_name: ds.short_name.as_deref().unwrap_or_default().into(),
             long_name: ds.long_name.as_ref().map(|ln| LongName {
                 value: ln.clone(),
                 ti: String::new(),
             }),
-            semantic: ds.semantic.clone().unwrap_or_default(),
+            semantic: ds.semantic.as_deref().unwrap_or_default().into(),
             funct_classes: ds
                 .funct_class_refs
                 .as_ref()
@@ -377,7 +538,7 @@ fn map_diag_service(
                             let id = r.id_ref.as_deref()?;
                             let fc = index.funct_classes.get(id)?;
                             Some(FunctClass {
-                                short_name: fc.short_name.clone().unwrap_or_default(),
+                                short_name: fc.short_name.as_deref().unwrap_or_default().into(),
                             })
                         })
                         .collect()
@@ -394,7 +555,7 @@ fn map_diag_service(
                         .filter_map(|r| {
                             let id = r.id_ref.as_deref()?;
                             let state = index.states.get(id).map(|s| State {
-                                short_name: s.short_name.clone().unwrap_or_default(),
+                                short_name: s.short_name.as_deref().unwrap_or_default().into(),
                                 long_name: None,
                             });
                             // Normalize value to canonical form so ODX roundtrip is stable
@@ -421,7 +582,7 @@ fn map_diag_service(
                             let id = r.id_ref.as_deref()?;
                             let state_transition =
                                 index.state_transitions.get(id).map(|st| StateTransition {
-                                    short_name: st.short_name.clone().unwrap_or_default(),
+                                    short_name: st.short_name.as_deref().unwrap_or_default().into(),
                                     source_short_name_ref: st
                                         .source_snref
                                         .as_ref()
@@ -451,6 +612,7 @@ fn map_diag_service(
             is_mandatory: ds.is_mandatory.as_deref() == Some("true"),
             is_executable: ds.is_executable.as_deref() != Some("false"),
             is_final: ds.is_final.as_deref() == Some("true"),
+            description: ds.desc.as_ref().and_then(desc_to_markdown),
         },
         request,
         pos_responses,
@@ -460,7 +622,7 @@ fn map_diag_service(
         addressing: parse_addressing(&ds.addressing),
         transmission_mode: parse_transmission_mode(&ds.transmission_mode),
         com_param_refs: Vec::new(),
-    }
+    })
 }
 
 fn map_single_ecu_job(
@@ -494,12 +656,12 @@ fn map_single_ecu_job(
 
     SingleEcuJob {
         diag_comm: DiagComm {
-            short_name: job.short_name.clone().unwrap_or_default(),
+            short_name: job.short_name.as_deref().unwrap_or_default().into(),
             long_name: job.long_name.as_ref().map(|ln| LongName {
                 value: ln.clone(),
                 ti: String::new(),
             }),
-            semantic: String::new(),
+            semantic: Name::default(),
             funct_classes: Vec::new(),
             sdgs: map_sdgs_opt(&job.sdgs),
             diag_class_type: DiagClassType::StartComm,
@@ -510,6 +672,7 @@ fn map_single_ecu_job(
             is_mandatory: false,
             is_executable: true,
             is_final: false,
+            description: job.desc.as_ref().and_then(desc_to_markdown),
         },
         prog_codes,
         input_params,
@@ -542,8 +705,16 @@ fn map_response(
     index: &OdxIndex,
     response_type: ResponseType,
     lenient: bool,
-) -> Response {
-    Response {
+) -> Result<Response, OdxParseError> {
+    let audience = match &resp.audience {
+        Some(aud) => {
+            let element = resp.short_name.as_deref().unwrap_or("RESPONSE");
+            check_audience_flags(aud, element, lenient)?;
+            Some(map_audience(aud))
+        }
+        None => None,
+    };
+    Ok(Response {
         response_type,
         params: resp
             .params
@@ -557,13 +728,15 @@ fn map_response(
             })
             .unwrap_or_default(),
         sdgs: map_sdgs_opt(&resp.sdgs),
-    }
+        pos_response_suffix: resp.pos_response_suffix.clone(),
+        audience,
+    })
 }
 
 // --- Parameter mapping ---
 
 fn map_param(p: &odx_model::OdxParam, id: u32, index: &OdxIndex, lenient: bool) -> Param {
-    let xsi_type = p.xsi_type.as_deref().unwrap_or("");
+    let xsi_type = local_name(p.xsi_type.as_deref().unwrap_or(""));
 
     let (param_type, specific_data) = match xsi_type {
         "CODED-CONST" => (
@@ -596,7 +769,7 @@ fn map_param(p: &odx_model::OdxParam, id: u32, index: &OdxIndex, lenient: bool)
                 ParamType::Value,
                 Some(ParamData::Value {
                     physical_default_value: p.physical_default_value.clone().unwrap_or_default(),
-                    dop: Box::new(dop),
+                    dop,
                 }),
             )
         }
@@ -606,7 +779,7 @@ fn map_param(p: &odx_model::OdxParam, id: u32, index: &OdxIndex, lenient: bool)
                 ParamType::PhysConst,
                 Some(ParamData::PhysConst {
                     phys_constant_value: p.phys_constant_value.clone().unwrap_or_default(),
-                    dop: Box::new(dop),
+                    dop,
                 }),
             )
         }
@@ -628,17 +801,14 @@ fn map_param(p: &odx_model::OdxParam, id: u32, index: &OdxIndex, lenient: bool)
             (
                 ParamType::System,
                 Some(ParamData::System {
-                    dop: Box::new(dop),
+                    dop,
                     sys_param: String::new(),
                 }),
             )
         }
         "LENGTH-KEY" => {
             let dop = resolve_dop(p, index, lenient);
-            (
-                ParamType::LengthKey,
-                Some(ParamData::LengthKeyRef { dop: Box::new(dop) }),
-            )
+            (ParamType::LengthKey, Some(ParamData::LengthKeyRef { dop }))
         }
         "DYNAMIC" => (ParamType::Dynamic, Some(ParamData::Dynamic)),
         _ => (ParamType::Value, None),
@@ -647,8 +817,8 @@ fn map_param(p: &odx_model::OdxParam, id: u32, index: &OdxIndex, lenient: bool)
     Param {
         id,
         param_type,
-        short_name: p.short_name.clone().unwrap_or_default(),
-        semantic: p.semantic.clone().unwrap_or_default(),
+        short_name: p.short_name.as_deref().unwrap_or_default().into(),
+        semantic: p.semantic.as_deref().unwrap_or_default().into(),
         sdgs: map_sdgs_opt(&p.sdgs),
         physical_default_value: p.physical_default_value.clone().unwrap_or_default(),
         byte_position: p.byte_position,
@@ -657,18 +827,12 @@ fn map_param(p: &odx_model::OdxParam, id: u32, index: &OdxIndex, lenient: bool)
     }
 }
 
-fn resolve_dop(p: &odx_model::OdxParam, index: &OdxIndex, lenient: bool) -> Dop {
+fn resolve_dop(p: &odx_model::OdxParam, index: &OdxIndex, lenient: bool) -> DopRef {
     // Try DOP-REF first
     if let Some(dop_ref) = &p.dop_ref {
         if let Some(id) = dop_ref.id_ref.as_deref() {
-            if let Some(odx_dop) = index.data_object_props.get(id) {
-                return map_data_object_prop(odx_dop, index);
-            }
-            if let Some(odx_dtc_dop) = index.dtc_dops.get(id) {
-                return map_dtc_dop_to_dop(odx_dtc_dop);
-            }
-            if let Some(odx_struct) = index.structures.get(id) {
-                return map_structure_to_dop(odx_struct);
+            if let Some(dop) = mapped_dop(id, index) {
+                return dop;
             }
             if lenient {
                 log::warn!("Unresolved DOP-REF '{}', using empty DOP", id);
@@ -679,10 +843,13 @@ fn resolve_dop(p: &odx_model::OdxParam, index: &OdxIndex, lenient: bool) -> Dop
     // Try DOP-SNREF
     if let Some(snref) = &p.dop_snref {
         if let Some(sn) = &snref.short_name {
-            for dop in index.data_object_props.values() {
-                if dop.short_name.as_deref() == Some(sn.as_str()) {
-                    return map_data_object_prop(dop, index);
-                }
+            let id = index
+                .data_object_props
+                .iter()
+                .find(|(_, dop)| dop.short_name.as_deref() == Some(sn.as_str()))
+                .map(|(id, _)| *id);
+            if let Some(dop) = id.and_then(|id| mapped_dop(id, index)) {
+                return dop;
             }
             if lenient {
                 log::warn!("Unresolved DOP-SNREF '{}', using empty DOP", sn);
@@ -691,12 +858,33 @@ fn resolve_dop(p: &odx_model::OdxParam, index: &OdxIndex, lenient: bool) -> Dop
     }
 
     // Fallback: empty DOP
-    Dop {
+    DopRef::new(Dop {
         dop_type: DopType::Regular,
-        short_name: String::new(),
+        short_name: Name::default(),
         sdgs: None,
         specific_data: None,
+    })
+}
+
+/// The DOP, DTC-DOP or structure with ID `id`, mapped on first use. Later
+/// references get the same shared DOP instead of mapping it again.
+fn mapped_dop(id: &str, index: &OdxIndex) -> Option<DopRef> {
+    if let Some(dop) = index.mapped_dops.borrow().get(id) {
+        return Some(dop.clone());
     }
+    let dop = if let Some(odx_dop) = index.data_object_props.get(id) {
+        map_data_object_prop(odx_dop, index)
+    } else if let Some(odx_dtc_dop) = index.dtc_dops.get(id) {
+        map_dtc_dop_to_dop(odx_dtc_dop)
+    } else {
+        map_structure_to_dop(index.structures.get(id)?)
+    };
+    let dop = DopRef::new(dop);
+    index
+        .mapped_dops
+        .borrow_mut()
+        .insert(id.to_string(), dop.clone());
+    Some(dop)
 }
 
 // --- DOP mapping ---
@@ -717,7 +905,7 @@ fn map_data_object_prop(dop: &odx_model::OdxDataObjectProp, index: &OdxIndex) ->
 
     Dop {
         dop_type: DopType::Regular,
-        short_name: dop.short_name.clone().unwrap_or_default(),
+        short_name: dop.short_name.as_deref().unwrap_or_default().into(),
         sdgs: map_sdgs_opt(&dop.sdgs),
         specific_data: Some(DopData::NormalDop {
             compu_method,
@@ -739,7 +927,7 @@ fn map_dtc_dop_to_dop(dop: &odx_model::OdxDtcDop) -> Dop {
 
     Dop {
         dop_type: DopType::Dtc,
-        short_name: dop.short_name.clone().unwrap_or_default(),
+        short_name: dop.short_name.as_deref().unwrap_or_default().into(),
         sdgs: map_sdgs_opt(&dop.sdgs),
         specific_data: Some(DopData::DtcDop {
             diag_coded_type: dop.diag_coded_type.as_ref().map(map_diag_coded_type),
@@ -754,7 +942,7 @@ fn map_dtc_dop_to_dop(dop: &odx_model::OdxDtcDop) -> Dop {
 fn map_structure_to_dop(s: &odx_model::OdxStructure) -> Dop {
     Dop {
         dop_type: DopType::Structure,
-        short_name: s.short_name.clone().unwrap_or_default(),
+        short_name: s.short_name.as_deref().unwrap_or_default().into(),
         sdgs: map_sdgs_opt(&s.sdgs),
         specific_data: Some(DopData::Structure {
             params: Vec::new(),
@@ -767,7 +955,7 @@ fn map_structure_to_dop(s: &odx_model::OdxStructure) -> Dop {
 // --- Type mapping ---
 
 fn map_diag_coded_type(dct: &odx_model::OdxDiagCodedType) -> DiagCodedType {
-    let xsi_type = dct.xsi_type.as_deref().unwrap_or("STANDARD-LENGTH-TYPE");
+    let xsi_type = local_name(dct.xsi_type.as_deref().unwrap_or("STANDARD-LENGTH-TYPE"));
     let base_data_type = parse_data_type(dct.base_data_type.as_deref());
     let is_high_low = dct.is_highlow_byte_order.as_deref() != Some("false");
 
@@ -797,7 +985,7 @@ fn map_diag_coded_type(dct: &odx_model::OdxDiagCodedType) -> DiagCodedType {
                 bit_mask: dct
                     .bit_mask
                     .as_ref()
-                    .map(|s| hex_to_bytes(s))
+                    .and_then(|s| diag_ir::parse_hex_bytes(s))
                     .unwrap_or_default(),
                 condensed: dct.is_condensed.as_deref() == Some("true"),
             }),
@@ -985,7 +1173,7 @@ fn map_unit(u: &odx_model::OdxUnit, index: &OdxIndex) -> Unit {
         .map(|pd| map_physical_dimension(pd));
 
     Unit {
-        short_name: u.short_name.clone().unwrap_or_default(),
+        short_name: u.short_name.as_deref().unwrap_or_default().into(),
         display_name: u.display_name.clone().unwrap_or_default(),
         factor_si_to_unit: u.factor_si_to_unit,
         offset_si_to_unit: u.offset_si_to_unit,
@@ -995,7 +1183,7 @@ fn map_unit(u: &odx_model::OdxUnit, index: &OdxIndex) -> Unit {
 
 fn map_physical_dimension(pd: &odx_model::OdxPhysicalDimension) -> PhysicalDimension {
     PhysicalDimension {
-        short_name: pd.short_name.clone().unwrap_or_default(),
+        short_name: pd.short_name.as_deref().unwrap_or_default().into(),
         long_name: None,
         length_exp: pd.length_exp,
         mass_exp: pd.mass_exp,
@@ -1011,7 +1199,7 @@ fn map_physical_dimension(pd: &odx_model::OdxPhysicalDimension) -> PhysicalDimen
 
 fn map_dtc(dtc: &odx_model::OdxDtc) -> Dtc {
     Dtc {
-        short_name: dtc.short_name.clone().unwrap_or_default(),
+        short_name: dtc.short_name.as_deref().unwrap_or_default().into(),
         trouble_code: dtc.trouble_code.unwrap_or(0),
         display_trouble_code: dtc.display_trouble_code.clone().unwrap_or_default(),
         text: dtc.text.as_ref().map(|t| Text {
@@ -1028,8 +1216,8 @@ fn map_dtc(dtc: &odx_model::OdxDtc) -> Dtc {
 
 fn map_state_chart(sc: &odx_model::OdxStateChart) -> StateChart {
     StateChart {
-        short_name: sc.short_name.clone().unwrap_or_default(),
-        semantic: sc.semantic.clone().unwrap_or_default(),
+        short_name: sc.short_name.as_deref().unwrap_or_default().into(),
+        semantic: sc.semantic.as_deref().unwrap_or_default().into(),
         start_state_short_name_ref: sc
             .start_state_snref
             .as_ref()
@@ -1042,7 +1230,7 @@ fn map_state_chart(sc: &odx_model::OdxStateChart) -> StateChart {
                 w.items
                     .iter()
                     .map(|s| State {
-                        short_name: s.short_name.clone().unwrap_or_default(),
+                        short_name: s.short_name.as_deref().unwrap_or_default().into(),
                         long_name: s.long_name.as_ref().map(|ln| LongName {
                             value: ln.clone(),
                             ti: String::new(),
@@ -1058,7 +1246,7 @@ fn map_state_chart(sc: &odx_model::OdxStateChart) -> StateChart {
                 w.items
                     .iter()
                     .map(|t| StateTransition {
-                        short_name: t.short_name.clone().unwrap_or_default(),
+                        short_name: t.short_name.as_deref().unwrap_or_default().into(),
                         source_short_name_ref: t
                             .source_snref
                             .as_ref()
@@ -1080,7 +1268,7 @@ fn map_state_chart(sc: &odx_model::OdxStateChart) -> StateChart {
 
 fn map_additional_audience(aa: &odx_model::OdxAdditionalAudience) -> AdditionalAudience {
     AdditionalAudience {
-        short_name: aa.short_name.clone().unwrap_or_default(),
+        short_name: aa.short_name.as_deref().unwrap_or_default().into(),
         long_name: aa.long_name.as_ref().map(|ln| LongName {
             value: ln.clone(),
             ti: String::new(),
@@ -1095,7 +1283,7 @@ fn map_audience(aud: &odx_model::OdxAudience) -> Audience {
                 w.items
                     .iter()
                     .map(|r| AdditionalAudience {
-                        short_name: r.id_ref.clone().unwrap_or_default(),
+                        short_name: r.id_ref.as_deref().unwrap_or_default().into(),
                         long_name: None,
                     })
                     .collect()
@@ -1105,14 +1293,51 @@ fn map_audience(aud: &odx_model::OdxAudience) -> Audience {
     Audience {
         enabled_audiences: map_refs(&aud.enabled_audience_refs),
         disabled_audiences: map_refs(&aud.disabled_audience_refs),
-        is_supplier: aud.is_supplier.as_deref() == Some("true"),
-        is_development: aud.is_development.as_deref() == Some("true"),
-        is_manufacturing: aud.is_manufacturing.as_deref() == Some("true"),
-        is_after_sales: aud.is_aftersales.as_deref() == Some("true"),
-        is_after_market: aud.is_aftermarket.as_deref() == Some("true"),
+        is_supplier: xml_bool(aud.is_supplier.as_deref()),
+        is_development: xml_bool(aud.is_development.as_deref()),
+        is_manufacturing: xml_bool(aud.is_manufacturing.as_deref()),
+        is_after_sales: xml_bool(aud.is_aftersales.as_deref()),
+        is_after_market: xml_bool(aud.is_aftermarket.as_deref()),
     }
 }
 
+/// An `xsd:boolean` attribute; absent or invalid values are false.
+fn xml_bool(value: Option<&str>) -> bool {
+    matches!(value.map(str::trim), Some("true" | "1"))
+}
+
+/// Reject audience flags that are not `xsd:boolean`. Lenient mode only warns
+/// and treats them as false.
+fn check_audience_flags(
+    aud: &odx_model::OdxAudience,
+    element: &str,
+    lenient: bool,
+) -> Result<(), OdxParseError> {
+    let flags = [
+        ("IS-SUPPLIER", &aud.is_supplier),
+        ("IS-DEVELOPMENT", &aud.is_development),
+        ("IS-MANUFACTURING", &aud.is_manufacturing),
+        ("IS-AFTERSALES", &aud.is_aftersales),
+        ("IS-AFTERMARKET", &aud.is_aftermarket),
+    ];
+    for (attribute, value) in flags {
+        let Some(value) = value else { continue };
+        if matches!(value.trim(), "true" | "false" | "1" | "0") {
+            continue;
+        }
+        if lenient {
+            log::warn!("Ignoring invalid AUDIENCE {attribute}='{value}' in {element}");
+            continue;
+        }
+        return Err(OdxParseError::InvalidAttribute {
+            element: element.to_string(),
+            attribute: format!("AUDIENCE {attribute}"),
+            value: value.clone(),
+        });
+    }
+    Ok(())
+}
+
 fn map_prog_code(pc: &odx_model::OdxProgCode) -> ProgCode {
     ProgCode {
         code_file: pc.code_file.clone().unwrap_or_default(),
@@ -1125,30 +1350,21 @@ fn map_prog_code(pc: &odx_model::OdxProgCode) -> ProgCode {
 }
 
 fn map_job_param(jp: &odx_model::OdxJobParam, index: &OdxIndex) -> JobParam {
-    let dop_base = jp.dop_base_ref.as_ref().and_then(|r| {
-        r.id_ref.as_deref().and_then(|id| {
-            index
-                .data_object_props
-                .get(id)
-                .map(|dop| Box::new(map_data_object_prop(dop, index)))
-                .or_else(|| {
-                    index
-                        .structures
-                        .get(id)
-                        .map(|s| Box::new(map_structure_to_dop(s)))
-                })
-        })
-    });
+    let dop_base = jp
+        .dop_base_ref
+        .as_ref()
+        .and_then(|r| r.id_ref.as_deref())
+        .and_then(|id| mapped_dop(id, index));
 
     JobParam {
-        short_name: jp.short_name.clone().unwrap_or_default(),
+        short_name: jp.short_name.as_deref().unwrap_or_default().into(),
         long_name: jp.long_name.as_ref().map(|ln| LongName {
             value: ln.clone(),
             ti: String::new(),
         }),
         physical_default_value: jp.physical_default_value.clone().unwrap_or_default(),
         dop_base,
-        semantic: jp.semantic.clone().unwrap_or_default(),
+        semantic: jp.semantic.as_deref().unwrap_or_default().into(),
     }
 }
 
@@ -1176,7 +1392,7 @@ fn map_comparam_ref(cr: &odx_model::OdxComparamRef) -> ComParamRef {
         snref.short_name.as_ref().map(|sn| {
             Box::new(Protocol {
                 diag_layer: DiagLayer {
-                    short_name: sn.clone(),
+                    short_name: sn.as_str().into(),
                     ..Default::default()
                 },
                 com_param_spec: None,
@@ -1188,7 +1404,7 @@ fn map_comparam_ref(cr: &odx_model::OdxComparamRef) -> ComParamRef {
     let prot_stack = cr.prot_stack_snref.as_ref().and_then(|snref| {
         snref.short_name.as_ref().map(|sn| {
             Box::new(ProtStack {
-                short_name: sn.clone(),
+                short_name: sn.as_str().into(),
                 long_name: None,
                 pdu_protocol_type: String::new(),
                 physical_link_type: String::new(),
@@ -1226,8 +1442,9 @@ fn extract_variant_patterns(layer: &odx_model::DiagLayerVariant) -> Vec<VariantP
                                             short_name: mp
                                                 .diag_comm_snref
                                                 .as_ref()
-                                                .and_then(|s| s.short_name.clone())
-                                                .unwrap_or_default(),
+                                                .and_then(|s| s.short_name.as_deref())
+                                                .unwrap_or_default()
+                                                .into(),
                                             ..Default::default()
                                         },
                                         ..Default::default()
@@ -1236,8 +1453,9 @@ fn extract_variant_patterns(layer: &odx_model::DiagLayerVariant) -> Vec<VariantP
                                         short_name: mp
                                             .out_param_snref
                                             .as_ref()
-                                            .and_then(|s| s.short_name.clone())
-                                            .unwrap_or_default(),
+                                            .and_then(|s| s.short_name.as_deref())
+                                            .unwrap_or_default()
+                                            .into(),
                                         ..Default::default()
                                     }),
                                     use_physical_addressing: None,
@@ -1317,10 +1535,10 @@ fn extract_parent_refs(layer: &odx_model::DiagLayerVariant, index: &OdxIndex) ->
                             let short_name = index
                                 .layers
                                 .get(id)
-                                .and_then(|l| l.short_name.clone())
+                                .and_then(|l| l.short_name.as_deref())
                                 .unwrap_or_default();
                             let dl = DiagLayer {
-                                short_name,
+                                short_name: short_name.into(),
                                 ..Default::default()
                             };
                             match index.layer_types.get(id) {
@@ -1416,6 +1634,11 @@ fn map_sdg(sdg: &odx_model::OdxSdg) -> Sdg {
 pub const META_ADMIN_LANGUAGE: &str = "admin_language";
 pub const META_ADMIN_DOC_STATE: &str = "admin_doc_state";
 pub const META_ADMIN_DOC_DATE: &str = "admin_doc_date";
+/// Conversion provenance, written by the ODX writer as an extra DOC-REVISION.
+pub const META_PROVENANCE_TOOL: &str = "provenance_tool";
+pub const META_PROVENANCE_DATE: &str = "provenance_date";
+pub const META_PROVENANCE_CHANGE: &str = "provenance_change";
+pub const META_PROVENANCE_REASON: &str = "provenance_reason";
 
 fn extract_admin_metadata(
     admin_data: &Option<odx_model::AdminData>,
@@ -1445,11 +1668,6 @@ fn extract_admin_metadata(
     (revision, extra)
 }
 
-fn dedup_dtcs(dtcs: &mut Vec<Dtc>) {
-    let mut seen = std::collections::HashSet::new();
-    dtcs.retain(|dtc| seen.insert(dtc.trouble_code));
-}
-
 fn parse_data_type(s: Option<&str>) -> DataType {
     match s {
         Some("A_INT32") => DataType::AInt32,
@@ -1531,14 +1749,6 @@ fn parse_transmission_mode(s: &Option<String>) -> TransmissionMode {
     }
 }
 
-fn hex_to_bytes(hex: &str) -> Vec<u8> {
-    let hex = hex.trim_start_matches("0x").trim_start_matches("0X");
-    (0..hex.len())
-        .step_by(2)
-        .filter_map(|i| u8::from_str_radix(&hex[i..i + 2.min(hex.len() - i)], 16).ok())
-        .collect()
-}
-
 // --- Protocol association helpers ---
 
 /// Build a map from service short_name to lightweight Protocol stub(s).
@@ -1559,12 +1769,12 @@ fn build_service_protocol_map(protocols: &[Protocol]) -> HashMap<String, Vec<Pro
             parent_refs: Vec::new(),
         };
         for svc in &proto.diag_layer.diag_services {
-            map.entry(svc.diag_comm.short_name.clone())
+            map.entry(svc.diag_comm.short_name.as_str().into())
                 .or_default()
                 .push(stub.clone());
         }
         for job in &proto.diag_layer.single_ecu_jobs {
-            map.entry(job.diag_comm.short_name.clone())
+            map.entry(job.diag_comm.short_name.as_str().into())
                 .or_default()
                 .push(stub.clone());
         }
@@ -1572,6 +1782,17 @@ fn build_service_protocol_map(protocols: &[Protocol]) -> HashMap<String, Vec<Pro
     map
 }
 
+/// Re-derive `DiagComm.protocols` from all protocols of `db`, e.g. after
+/// merging databases whose services and protocols came from different files.
+pub(crate) fn associate_protocols(db: &mut DiagDatabase) {
+    let service_protocols = build_service_protocol_map(&db.protocols);
+    apply_protocol_associations(
+        &mut db.variants,
+        &mut db.functional_groups,
+        &service_protocols,
+    );
+}
+
 /// For each service in variants and functional groups, if it appears in the
 /// service-protocol map, populate its `DiagComm.protocols` field.
 fn apply_protocol_associations(
@@ -1589,12 +1810,12 @@ fn apply_protocol_associations(
 
 fn apply_to_diag_layer(layer: &mut DiagLayer, service_protocols: &HashMap<String, Vec<Protocol>>) {
     for svc in &mut layer.diag_services {
-        if let Some(protos) = service_protocols.get(&svc.diag_comm.short_name) {
+        if let Some(protos) = service_protocols.get(svc.diag_comm.short_name.as_str()) {
             svc.diag_comm.protocols = protos.clone();
         }
     }
     for job in &mut layer.single_ecu_jobs {
-        if let Some(protos) = service_protocols.get(&job.diag_comm.short_name) {
+        if let Some(protos) = service_protocols.get(job.diag_comm.short_name.as_str()) {
             job.diag_comm.protocols = protos.clone();
         }
     }
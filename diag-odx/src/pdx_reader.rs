@@ -1,5 +1,6 @@
+use crate::encoding::decode_odx;
 use crate::parser::parse_odx;
-use diag_ir::types::DiagDatabase;
+use diag_ir::types::{DiagDatabase, Name};
 use std::io::Read;
 use std::path::Path;
 
@@ -44,11 +45,11 @@ pub fn read_pdx_from_reader<R: Read + std::io::Seek>(
             continue;
         }
 
-        let mut xml = String::new();
-        entry.read_to_string(&mut xml)?;
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
 
         log::info!("Parsing ODX from PDX entry: {}", name);
-        let db = match parse_odx(&xml) {
+        let db = match decode_odx(&bytes).and_then(|xml| parse_odx(&xml)) {
             Ok(db) => db,
             Err(crate::parser::OdxParseError::MissingElement(ref elem))
                 if elem == "DIAG-LAYER-CONTAINER" =>
@@ -78,12 +79,30 @@ pub fn read_pdx_from_reader<R: Read + std::io::Seek>(
     merged.ok_or(PdxReadError::NoOdxFiles)
 }
 
+/// The raw bytes of the ODX documents in a PDX file, by entry name.
+pub fn read_pdx_entries(path: &Path) -> Result<Vec<(String, Vec<u8>)>, PdxReadError> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let lower = entry.name().to_lowercase();
+        #[allow(clippy::case_sensitive_file_extension_comparisons)]
+        if !lower.ends_with(".odx") && !lower.contains(".odx-") {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+        entries.push((entry.name().to_string(), bytes));
+    }
+    Ok(entries)
+}
+
 /// Merge two DiagDatabases.
 ///
 /// Prefer metadata (ECU name, version, revision) from the database that has
 /// actual diagnostic content (variants), since protocol-layer ODX files also
 /// have DIAG-LAYER-CONTAINER but contain no variants.
-fn merge_databases(mut base: DiagDatabase, other: DiagDatabase) -> DiagDatabase {
+pub(crate) fn merge_databases(mut base: DiagDatabase, other: DiagDatabase) -> DiagDatabase {
     let base_has_variants = !base.variants.is_empty();
     let other_has_variants = !other.variants.is_empty();
 
@@ -99,7 +118,7 @@ fn merge_databases(mut base: DiagDatabase, other: DiagDatabase) -> DiagDatabase
     }
 
     // Merge variants (avoid duplicates by short_name)
-    let existing_names: std::collections::HashSet<String> = base
+    let existing_names: std::collections::HashSet<Name> = base
         .variants
         .iter()
         .map(|v| v.diag_layer.short_name.clone())
@@ -111,7 +130,7 @@ fn merge_databases(mut base: DiagDatabase, other: DiagDatabase) -> DiagDatabase
     }
 
     // Merge functional groups (avoid duplicates by short_name)
-    let existing_fg_names: std::collections::HashSet<String> = base
+    let existing_fg_names: std::collections::HashSet<Name> = base
         .functional_groups
         .iter()
         .map(|fg| fg.diag_layer.short_name.clone())
@@ -123,7 +142,7 @@ fn merge_databases(mut base: DiagDatabase, other: DiagDatabase) -> DiagDatabase
     }
 
     // Merge protocols (avoid duplicates by short_name)
-    let existing_proto_names: std::collections::HashSet<String> = base
+    let existing_proto_names: std::collections::HashSet<Name> = base
         .protocols
         .iter()
         .map(|p| p.diag_layer.short_name.clone())
@@ -135,7 +154,7 @@ fn merge_databases(mut base: DiagDatabase, other: DiagDatabase) -> DiagDatabase
     }
 
     // Merge ECU shared datas (avoid duplicates by short_name)
-    let existing_esd_names: std::collections::HashSet<String> = base
+    let existing_esd_names: std::collections::HashSet<Name> = base
         .ecu_shared_datas
         .iter()
         .map(|e| e.diag_layer.short_name.clone())
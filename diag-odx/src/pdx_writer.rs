@@ -0,0 +1,231 @@
+//! PDX writer: IR DiagDatabase -> PDX archive.
+//!
+//! A PDX is a ZIP archive holding the ODX documents of a delivery, the files
+//! they reference (e.g. the JARs of single-ECU jobs) and an `index.xml`
+//! catalog listing all of them. The writer produces one ODX-D document from
+//! the database and generates the catalog.
+
+use diag_ir::types::DiagDatabase;
+use quick_xml::escape::escape;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io::{Seek, Write};
+use std::path::Path;
+
+use crate::dialect::OdxVersion;
+use crate::writer::{OdxWriteError, OdxWriteOptions, write_odx_with};
+
+/// Name of the catalog entry every PDX carries.
+pub const PDX_INDEX: &str = "index.xml";
+
+/// Errors that can occur writing a PDX file.
+#[derive(Debug, thiserror::Error)]
+pub enum PdxWriteError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("ZIP error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("ODX write error: {0}")]
+    Odx(#[from] OdxWriteError),
+    #[error("duplicate PDX entry '{0}'")]
+    DuplicateEntry(String),
+}
+
+/// A file packed into the archive next to the ODX document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdxFile {
+    /// Entry name in the archive, e.g. `FlashJob.jar`.
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Options for [`write_pdx_file`].
+#[derive(Debug, Clone, Default)]
+pub struct PdxWriteOptions {
+    /// Indent the ODX document (see [`write_odx_pretty`](crate::write_odx_pretty)).
+    pub pretty: bool,
+    /// ODX version of the document and the catalog (see
+    /// [`OdxWriteOptions::version`]).
+    pub version: Option<OdxVersion>,
+    /// Further files to pack, e.g. the job JARs the ProgCodes reference.
+    pub extra_files: Vec<PdxFile>,
+}
+
+/// Write `db` as a PDX archive to `path`.
+pub fn write_pdx_file(
+    db: &DiagDatabase,
+    path: &Path,
+    options: &PdxWriteOptions,
+) -> Result<(), PdxWriteError> {
+    let file = std::fs::File::create(path)?;
+    write_pdx_to_writer(db, file, options)?;
+    Ok(())
+}
+
+/// Write `db` as a PDX archive to any writer (for testing with in-memory
+/// data). Returns the writer.
+pub fn write_pdx_to_writer<W: Write + Seek>(
+    db: &DiagDatabase,
+    writer: W,
+    options: &PdxWriteOptions,
+) -> Result<W, PdxWriteError> {
+    let xml = write_odx_with(
+        db,
+        &OdxWriteOptions {
+            pretty: options.pretty,
+            version: options.version,
+        },
+    )?;
+    let odx_name = format!("{}.odx-d", entry_stem(&db.ecu_name));
+
+    let mut names = BTreeSet::from([PDX_INDEX.to_string(), odx_name.clone()]);
+    for file in &options.extra_files {
+        if !names.insert(file.name.clone()) {
+            return Err(PdxWriteError::DuplicateEntry(file.name.clone()));
+        }
+    }
+    for missing in referenced_code_files(db)
+        .iter()
+        .filter(|name| !names.contains(*name))
+    {
+        log::warn!("Job file '{missing}' referenced by a ProgCode is not in the PDX");
+    }
+
+    let mut zip = zip::ZipWriter::new(writer);
+    let options_deflated = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    zip.start_file(PDX_INDEX, options_deflated)?;
+    zip.write_all(catalog(db, &odx_name, options).as_bytes())?;
+    zip.start_file(odx_name.as_str(), options_deflated)?;
+    zip.write_all(xml.as_bytes())?;
+    for file in &options.extra_files {
+        log::info!(
+            "Packing PDX entry: {} ({} bytes)",
+            file.name,
+            file.data.len()
+        );
+        zip.start_file(file.name.as_str(), options_deflated)?;
+        zip.write_all(&file.data)?;
+    }
+    Ok(zip.finish()?)
+}
+
+/// Code files (job JARs and their libraries) the single-ECU jobs of `db`
+/// reference.
+pub fn referenced_code_files(db: &DiagDatabase) -> BTreeSet<String> {
+    let layers = db
+        .variants
+        .iter()
+        .map(|v| &v.diag_layer)
+        .chain(db.functional_groups.iter().map(|fg| &fg.diag_layer))
+        .chain(db.ecu_shared_datas.iter().map(|esd| &esd.diag_layer));
+    let mut files = BTreeSet::new();
+    for job in layers.flat_map(|dl| &dl.single_ecu_jobs) {
+        for pc in &job.prog_codes {
+            files.insert(pc.code_file.clone());
+            files.extend(pc.libraries.iter().map(|lib| lib.code_file.clone()));
+        }
+    }
+    files.remove("");
+    files
+}
+
+/// The ECU name as a file name stem: characters other than ASCII letters,
+/// digits, `_` and `-` become `_`.
+fn entry_stem(ecu_name: &str) -> String {
+    let stem: String = ecu_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if stem.is_empty() { "ECU".into() } else { stem }
+}
+
+/// The `index.xml` catalog: one ABLOCK per packed file.
+fn catalog(db: &DiagDatabase, odx_name: &str, options: &PdxWriteOptions) -> String {
+    let short_name = entry_stem(&db.ecu_name);
+    let version = options.version.unwrap_or_default();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<CATALOG xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+         xsi:noNamespaceSchemaLocation=\"odx-cc.xsd\" F-DTD-VERSION=\"ODX-{version}\">"
+    );
+    let _ = writeln!(xml, "  <SHORT-NAME>{short_name}</SHORT-NAME>");
+    if !db.version.is_empty() {
+        let _ = writeln!(
+            xml,
+            "  <CATALOG-VERSION>{}</CATALOG-VERSION>",
+            escape(db.version.as_str())
+        );
+    }
+    xml.push_str("  <ABLOCKS>\n");
+    push_ablock(
+        &mut xml,
+        &short_name,
+        "ODX-DATA",
+        "application/x-asam.odx.odx-d",
+        odx_name,
+    );
+    for file in &options.extra_files {
+        let (category, mime_type) = file_kind(&file.name);
+        push_ablock(
+            &mut xml,
+            &entry_stem(file.name.rsplit('/').next().unwrap_or(&file.name)),
+            category,
+            mime_type,
+            &file.name,
+        );
+    }
+    xml.push_str("  </ABLOCKS>\n</CATALOG>\n");
+    xml
+}
+
+fn push_ablock(xml: &mut String, short_name: &str, category: &str, mime_type: &str, file: &str) {
+    let _ = write!(
+        xml,
+        "    <ABLOCK UPD=\"UNCHANGED\">\n      <SHORT-NAME>{short_name}</SHORT-NAME>\n      \
+         <CATEGORY>{category}</CATEGORY>\n      <FILES>\n        \
+         <FILE MIME-TYPE=\"{mime_type}\">{}</FILE>\n      </FILES>\n    </ABLOCK>\n",
+        escape(file)
+    );
+}
+
+/// ABLOCK category and MIME type of a packed file, by extension.
+fn file_kind(name: &str) -> (&'static str, &'static str) {
+    let extension = name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "jar" => ("JAVA-JAR", "application/java-archive"),
+        "class" => ("JAVA-CLASS", "application/java-vm"),
+        ext if ext.starts_with("odx") => ("ODX-DATA", "application/x-asam.odx"),
+        _ => ("OTHER", "application/octet-stream"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_stem() {
+        assert_eq!(entry_stem("ECM"), "ECM");
+        assert_eq!(entry_stem("Body Ctrl/2"), "Body_Ctrl_2");
+        assert_eq!(entry_stem(""), "ECU");
+    }
+
+    #[test]
+    fn test_file_kind() {
+        assert_eq!(file_kind("jobs/Flash.JAR").0, "JAVA-JAR");
+        assert_eq!(file_kind("comparams.odx-c").0, "ODX-DATA");
+        assert_eq!(file_kind("README").0, "OTHER");
+    }
+}
@@ -0,0 +1,174 @@
+//! ODX project loader: many ODX documents -> one DiagDatabase.
+//!
+//! Real deliveries split protocols, comparam subsets, base variants and ECU
+//! variants across `.odx-d`/`.odx-c` files that reference each other by ID.
+//! The loader deserializes every document first and builds one [`OdxIndex`]
+//! over all of them, so a PARENT-REF or DOP-REF resolves no matter which
+//! file defines its target. Each DIAG-LAYER-CONTAINER is then mapped to IR
+//! and the results are merged like the documents of a PDX.
+
+use diag_ir::types::DiagDatabase;
+use std::path::{Path, PathBuf};
+
+use crate::encoding::decode_odx;
+use crate::odx_model::Odx;
+use crate::parser::{OdxParseError, ParseOptions, associate_protocols, deserialize_odx, odx_to_ir};
+use crate::pdx_reader::{PdxReadError, merge_databases, read_pdx_entries};
+use crate::ref_resolver::OdxIndex;
+
+/// Errors that can occur loading an ODX project.
+#[derive(Debug, thiserror::Error)]
+pub enum OdxProjectError {
+    #[error("IO error reading '{}': {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("reading PDX '{}': {source}", path.display())]
+    Pdx { path: PathBuf, source: PdxReadError },
+    #[error("ODX parse error in '{file}': {source}")]
+    OdxParse { file: String, source: OdxParseError },
+    #[error(transparent)]
+    Duplicates(#[from] diag_ir::DuplicateError),
+    #[error("no ODX documents with a DIAG-LAYER-CONTAINER found")]
+    NoOdxFiles,
+}
+
+/// Load the ODX documents at `paths` as one project.
+///
+/// A path may be an ODX file (`.odx`, `.odx-d`, `.odx-c`, ...), a PDX
+/// archive, or a directory, which contributes every ODX and PDX file below
+/// it.
+pub fn load_odx_project(paths: &[PathBuf]) -> Result<DiagDatabase, OdxProjectError> {
+    load_odx_project_with(paths, &ParseOptions::default())
+}
+
+/// Like [`load_odx_project`], with explicit [`ParseOptions`].
+pub fn load_odx_project_with(
+    paths: &[PathBuf],
+    options: &ParseOptions,
+) -> Result<DiagDatabase, OdxProjectError> {
+    let mut documents = Vec::new();
+    for path in paths {
+        collect_documents(path, &mut documents)?;
+    }
+
+    // Phase 1 for every document before any reference is resolved.
+    let mut parsed: Vec<(String, String, Odx)> = Vec::with_capacity(documents.len());
+    for (name, bytes) in documents {
+        let xml = decode_odx(&bytes)
+            .map_err(|source| OdxProjectError::OdxParse {
+                file: name.clone(),
+                source,
+            })?
+            .into_owned();
+        let odx = deserialize_odx(&xml, options).map_err(|source| OdxProjectError::OdxParse {
+            file: name.clone(),
+            source,
+        })?;
+        parsed.push((name, xml, odx));
+    }
+
+    // Phase 2 across all documents.
+    let index = OdxIndex::build_all(parsed.iter().map(|(_, _, odx)| odx));
+
+    let mut merged: Option<DiagDatabase> = None;
+    for (name, xml, odx) in &parsed {
+        if odx.diag_layer_container.is_none() {
+            log::info!("Indexed non-DLC ODX document '{name}' (no DIAG-LAYER-CONTAINER)");
+            continue;
+        }
+        log::info!("Mapping ODX document '{name}'");
+        let parse_err = |source: OdxParseError| OdxProjectError::OdxParse {
+            file: name.clone(),
+            source,
+        };
+        let mut db = odx_to_ir(odx, &index, options.lenient).map_err(parse_err)?;
+        if options.preserve_unknown_xml {
+            let preserved = crate::unknown_xml::scan(xml).map_err(|e| parse_err(e.into()))?;
+            crate::unknown_xml::attach(&mut db, preserved);
+        }
+        merged = Some(match merged {
+            None => db,
+            Some(existing) => merge_databases(existing, db),
+        });
+    }
+
+    let mut db = merged.ok_or(OdxProjectError::NoOdxFiles)?;
+    // Services and the protocols defining them may come from different files.
+    associate_protocols(&mut db);
+    for duplicate in diag_ir::resolve_dtc_duplicates(&mut db.dtcs, options.duplicates)? {
+        log::warn!("{duplicate}; {}", options.duplicates.resolution(&duplicate));
+    }
+    Ok(db)
+}
+
+/// Append the documents at `path` to `documents` as `(name, bytes)`.
+/// Directories are walked in name order so the merge is reproducible.
+pub(crate) fn collect_documents(
+    path: &Path,
+    documents: &mut Vec<(String, Vec<u8>)>,
+) -> Result<(), OdxProjectError> {
+    let io_err = |source: std::io::Error| OdxProjectError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    if path.is_dir() {
+        let mut children = std::fs::read_dir(path)
+            .and_then(|entries| {
+                entries
+                    .map(|e| e.map(|e| e.path()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(io_err)?;
+        children.sort();
+        for child in children {
+            if child.is_dir() || is_odx_name(&child) || is_pdx_name(&child) {
+                collect_documents(&child, documents)?;
+            }
+        }
+    } else if is_pdx_name(path) {
+        let entries = read_pdx_entries(path).map_err(|source| OdxProjectError::Pdx {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        documents.extend(
+            entries
+                .into_iter()
+                .map(|(entry, bytes)| (format!("{}!{entry}", path.display()), bytes)),
+        );
+    } else {
+        let bytes = std::fs::read(path).map_err(io_err)?;
+        documents.push((path.display().to_string(), bytes));
+    }
+    Ok(())
+}
+
+/// `.odx` or any `.odx-*` extension, case-insensitively.
+fn is_odx_name(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase)
+        .is_some_and(|ext| ext == "odx" || ext.starts_with("odx-"))
+}
+
+fn is_pdx_name(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pdx"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_odx_name() {
+        assert!(is_odx_name(Path::new("a/ECU.odx")));
+        assert!(is_odx_name(Path::new("ECU.ODX-D")));
+        assert!(is_odx_name(Path::new("comparams.odx-c")));
+        assert!(!is_odx_name(Path::new("index.xml")));
+        assert!(!is_odx_name(Path::new("odx")));
+        assert!(is_pdx_name(Path::new("delivery.PDX")));
+    }
+}
@@ -3,8 +3,11 @@
 //! Builds ID-indexed lookup tables from the parsed ODX tree so that ID-REF
 //! and SNREF attributes can be resolved to actual objects.
 
+use std::cell::RefCell;
 use std::collections::HashMap;
 
+use diag_ir::DopRef;
+
 use crate::odx_model::*;
 
 /// Category of a DiagLayerVariant within the DIAG-LAYER-CONTAINER.
@@ -39,11 +42,21 @@ pub struct OdxIndex<'a> {
     pub additional_audiences: HashMap<&'a str, &'a OdxAdditionalAudience>,
     pub state_transitions: HashMap<&'a str, &'a OdxStateTransition>,
     pub funct_classes: HashMap<&'a str, &'a FunctClass>,
+    /// DOPs already mapped to IR, by ID, shared by every parameter that
+    /// references them.
+    pub(crate) mapped_dops: RefCell<HashMap<String, DopRef>>,
 }
 
 impl<'a> OdxIndex<'a> {
     /// Build an index from the parsed ODX root.
     pub fn build(odx: &'a Odx) -> Self {
+        Self::build_all([odx])
+    }
+
+    /// Build one index over several parsed ODX documents, so that ID-REFs
+    /// resolve across files. When two documents define the same ID, the
+    /// later one wins.
+    pub fn build_all(odxs: impl IntoIterator<Item = &'a Odx>) -> Self {
         let mut idx = OdxIndex {
             requests: HashMap::new(),
             pos_responses: HashMap::new(),
@@ -64,25 +77,31 @@ impl<'a> OdxIndex<'a> {
             additional_audiences: HashMap::new(),
             state_transitions: HashMap::new(),
             funct_classes: HashMap::new(),
+            mapped_dops: RefCell::default(),
         };
 
+        for odx in odxs {
+            idx.index_odx(odx);
+        }
+        idx
+    }
+
+    fn index_odx(&mut self, odx: &'a Odx) {
         if let Some(dlc) = &odx.diag_layer_container {
-            idx.index_layer_list(&dlc.base_variants, |w| &w.items, LayerType::BaseVariant);
-            idx.index_layer_list(&dlc.ecu_variants, |w| &w.items, LayerType::EcuVariant);
-            idx.index_layer_list(
+            self.index_layer_list(&dlc.base_variants, |w| &w.items, LayerType::BaseVariant);
+            self.index_layer_list(&dlc.ecu_variants, |w| &w.items, LayerType::EcuVariant);
+            self.index_layer_list(
                 &dlc.ecu_shared_datas,
                 |w| &w.items,
                 LayerType::EcuSharedData,
             );
-            idx.index_layer_list(
+            self.index_layer_list(
                 &dlc.functional_groups,
                 |w| &w.items,
                 LayerType::FunctionalGroup,
             );
-            idx.index_layer_list(&dlc.protocols, |w| &w.items, LayerType::Protocol);
+            self.index_layer_list(&dlc.protocols, |w| &w.items, LayerType::Protocol);
         }
-
-        idx
     }
 
     fn index_layer_list<W, F>(
@@ -0,0 +1,395 @@
+//! Parallel deserialization of large ODX documents.
+//!
+//! Deserializing is the slowest phase of parsing, and a single serde pass
+//! cannot be spread over threads. A structural scan first finds the byte
+//! ranges of the diag layers and of the entries of their DIAG-COMMS,
+//! REQUESTS, POS-RESPONSES and NEG-RESPONSES. The rest of the document, each
+//! layer without those entries and chunks of the entries are then
+//! deserialized on the rayon pool and put back together in document order.
+//!
+//! The split only moves text between serde calls, so the result is the one
+//! a single pass gives. When any piece fails the caller deserializes the
+//! whole document again, which reports the error at its original position.
+
+use std::ops::Range;
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use rayon::prelude::*;
+
+use crate::odx_model::{
+    DiagCommEntry, DiagCommsWrapper, DiagLayerContainer, DiagLayerVariant, NegResponsesWrapper,
+    Odx, OdxRequest, OdxResponse, PosResponsesWrapper, RequestsWrapper,
+};
+
+/// Documents smaller than this are deserialized in one pass.
+pub(crate) const MIN_SIZE: usize = 1 << 20;
+
+/// Bytes of section entries deserialized as one piece.
+const CHUNK_SIZE: usize = 256 << 10;
+
+/// The layer lists of DIAG-LAYER-CONTAINER and their item elements.
+const LISTS: [(&[u8], &[u8], LayerList); 5] = [
+    (b"BASE-VARIANTS", b"BASE-VARIANT", LayerList::BaseVariants),
+    (b"ECU-VARIANTS", b"ECU-VARIANT", LayerList::EcuVariants),
+    (
+        b"ECU-SHARED-DATAS",
+        b"ECU-SHARED-DATA",
+        LayerList::EcuSharedDatas,
+    ),
+    (
+        b"FUNCTIONAL-GROUPS",
+        b"FUNCTIONAL-GROUP",
+        LayerList::FunctionalGroups,
+    ),
+    (b"PROTOCOLS", b"PROTOCOL", LayerList::Protocols),
+];
+
+/// The sections of a layer whose entries are split into chunks.
+const SECTIONS: [(&str, Section); 4] = [
+    ("DIAG-COMMS", Section::DiagComms),
+    ("REQUESTS", Section::Requests),
+    ("POS-RESPONSES", Section::PosResponses),
+    ("NEG-RESPONSES", Section::NegResponses),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LayerList {
+    BaseVariants,
+    EcuVariants,
+    EcuSharedDatas,
+    FunctionalGroups,
+    Protocols,
+}
+
+impl LayerList {
+    fn items(self, container: &mut DiagLayerContainer) -> Option<&mut Vec<DiagLayerVariant>> {
+        match self {
+            Self::BaseVariants => container.base_variants.as_mut().map(|w| &mut w.items),
+            Self::EcuVariants => container.ecu_variants.as_mut().map(|w| &mut w.items),
+            Self::EcuSharedDatas => container.ecu_shared_datas.as_mut().map(|w| &mut w.items),
+            Self::FunctionalGroups => container.functional_groups.as_mut().map(|w| &mut w.items),
+            Self::Protocols => container.protocols.as_mut().map(|w| &mut w.items),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    DiagComms,
+    Requests,
+    PosResponses,
+    NegResponses,
+}
+
+impl Section {
+    fn tag(self) -> &'static str {
+        SECTIONS
+            .iter()
+            .find(|(_, s)| *s == self)
+            .map_or("", |(tag, _)| *tag)
+    }
+
+    /// Deserialize consecutive entries of this section.
+    fn deserialize(self, entries: &str) -> Option<Entries> {
+        let tag = self.tag();
+        let xml = format!("<{tag}>{entries}</{tag}>");
+        let entries = match self {
+            Self::DiagComms => Entries::DiagComms(
+                quick_xml::de::from_str::<DiagCommsWrapper>(&xml)
+                    .ok()?
+                    .items,
+            ),
+            Self::Requests => {
+                Entries::Requests(quick_xml::de::from_str::<RequestsWrapper>(&xml).ok()?.items)
+            }
+            Self::PosResponses => Entries::PosResponses(
+                quick_xml::de::from_str::<PosResponsesWrapper>(&xml)
+                    .ok()?
+                    .items,
+            ),
+            Self::NegResponses => Entries::NegResponses(
+                quick_xml::de::from_str::<NegResponsesWrapper>(&xml)
+                    .ok()?
+                    .items,
+            ),
+        };
+        Some(entries)
+    }
+}
+
+/// A chunk of deserialized section entries.
+enum Entries {
+    DiagComms(Vec<DiagCommEntry>),
+    Requests(Vec<OdxRequest>),
+    PosResponses(Vec<OdxResponse>),
+    NegResponses(Vec<OdxResponse>),
+}
+
+impl Entries {
+    /// Append to the section of `layer`, which its skeleton left empty.
+    fn append_to(self, layer: &mut DiagLayerVariant) -> Option<()> {
+        match self {
+            Self::DiagComms(items) => layer.diag_comms.as_mut()?.items.extend(items),
+            Self::Requests(items) => layer.requests.as_mut()?.items.extend(items),
+            Self::PosResponses(items) => layer.pos_responses.as_mut()?.items.extend(items),
+            Self::NegResponses(items) => layer.neg_responses.as_mut()?.items.extend(items),
+        }
+        Some(())
+    }
+}
+
+/// Where a diag layer and the entries of its sections are in the document.
+#[derive(Debug)]
+struct LayerSpan {
+    list: LayerList,
+    range: Range<usize>,
+    sections: Vec<(Section, Vec<Range<usize>>)>,
+}
+
+/// Deserialize `xml` in pieces on the rayon pool; `None` when the document
+/// has no layers to split off or a piece fails.
+pub(crate) fn deserialize(xml: &str) -> Option<Odx> {
+    deserialize_in_chunks(xml, CHUNK_SIZE)
+}
+
+fn deserialize_in_chunks(xml: &str, chunk_size: usize) -> Option<Odx> {
+    let layers = scan(xml).ok()?;
+    if layers.is_empty() {
+        return None;
+    }
+    let rest = without(xml, 0..xml.len(), layers.iter().map(|l| l.range.clone()));
+
+    let (odx, parsed) = rayon::join(
+        || quick_xml::de::from_str::<Odx>(&rest).ok(),
+        || {
+            layers
+                .par_iter()
+                .map(|layer| deserialize_layer(xml, layer, chunk_size))
+                .collect::<Option<Vec<_>>>()
+        },
+    );
+    let mut odx = odx?;
+    let container = odx.diag_layer_container.as_mut()?;
+    for (span, layer) in layers.iter().zip(parsed?) {
+        span.list.items(container)?.push(layer);
+    }
+    Some(odx)
+}
+
+/// A layer, with the entries of its sections deserialized in chunks of
+/// about `chunk_size` bytes when there are enough of them.
+fn deserialize_layer(xml: &str, span: &LayerSpan, chunk_size: usize) -> Option<DiagLayerVariant> {
+    let chunks: Vec<(Section, Range<usize>)> = span
+        .sections
+        .iter()
+        .flat_map(|(section, entries)| {
+            chunks(entries, chunk_size)
+                .into_iter()
+                .map(move |chunk| (*section, chunk))
+        })
+        .collect();
+    if chunks.len() < 2 {
+        return quick_xml::de::from_str(&xml[span.range.clone()]).ok();
+    }
+    let skeleton = without(
+        xml,
+        span.range.clone(),
+        span.sections
+            .iter()
+            .filter_map(|(_, entries)| Some(entries.first()?.start..entries.last()?.end)),
+    );
+
+    let (layer, entries) = rayon::join(
+        || quick_xml::de::from_str::<DiagLayerVariant>(&skeleton).ok(),
+        || {
+            chunks
+                .par_iter()
+                .map(|(section, chunk)| section.deserialize(&xml[chunk.clone()]))
+                .collect::<Option<Vec<_>>>()
+        },
+    );
+    let mut layer = layer?;
+    for entries in entries? {
+        entries.append_to(&mut layer)?;
+    }
+    Some(layer)
+}
+
+/// The text of `range` with the ordered, disjoint `holes` inside it cut out.
+fn without(xml: &str, range: Range<usize>, holes: impl Iterator<Item = Range<usize>>) -> String {
+    let mut text = String::with_capacity(range.len());
+    let mut from = range.start;
+    for hole in holes {
+        text.push_str(&xml[from..hole.start]);
+        from = hole.end;
+    }
+    text.push_str(&xml[from..range.end]);
+    text
+}
+
+/// Consecutive entries grouped into ranges of at least `size` bytes.
+fn chunks(entries: &[Range<usize>], size: usize) -> Vec<Range<usize>> {
+    let mut chunks: Vec<Range<usize>> = Vec::new();
+    for entry in entries {
+        match chunks.last_mut() {
+            Some(chunk) if chunk.len() < size => chunk.end = entry.end,
+            _ => chunks.push(entry.clone()),
+        }
+    }
+    chunks
+}
+
+/// Find the diag layers of `xml` and the entries of their sections.
+/// Elements off that path are skipped without looking inside.
+fn scan(xml: &str) -> Result<Vec<LayerSpan>, quick_xml::Error> {
+    let mut reader = Reader::from_str(xml);
+    // Open elements: ODX, DIAG-LAYER-CONTAINER, a layer list, a layer and
+    // one of its sections.
+    let mut path: Vec<Vec<u8>> = Vec::new();
+    let mut list = None;
+    let mut layers: Vec<LayerSpan> = Vec::new();
+    loop {
+        let start = position(&reader);
+        let (e, empty) = match reader.read_event()? {
+            Event::Start(e) => (e, false),
+            Event::Empty(e) => (e, true),
+            Event::End(_) => {
+                path.pop();
+                if let Some(layer) = layers.last_mut().filter(|_| path.len() == 3) {
+                    layer.range.end = position(&reader);
+                }
+                continue;
+            }
+            Event::Eof => return Ok(layers),
+            _ => continue,
+        };
+        let name = e.local_name();
+        let name = name.as_ref();
+        let wanted = match path.len() {
+            0 => name == b"ODX",
+            1 => name == b"DIAG-LAYER-CONTAINER",
+            2 => {
+                list = LISTS.iter().find(|(tag, ..)| *tag == name);
+                list.is_some()
+            }
+            3 => match list {
+                Some((_, item, kind)) if *item == name => {
+                    layers.push(LayerSpan {
+                        list: *kind,
+                        range: start..position(&reader),
+                        sections: Vec::new(),
+                    });
+                    true
+                }
+                _ => false,
+            },
+            4 => match SECTIONS.iter().find(|(tag, _)| tag.as_bytes() == name) {
+                Some((_, section)) if !empty => {
+                    if let Some(layer) = layers.last_mut() {
+                        layer.sections.push((*section, Vec::new()));
+                    }
+                    true
+                }
+                _ => false,
+            },
+            _ => {
+                // An entry of a section.
+                if !empty {
+                    reader.read_to_end(e.to_end().name())?;
+                }
+                if let Some((_, entries)) = layers.last_mut().and_then(|l| l.sections.last_mut()) {
+                    entries.push(start..position(&reader));
+                }
+                continue;
+            }
+        };
+        if !empty {
+            if wanted {
+                path.push(name.to_vec());
+            } else {
+                reader.read_to_end(e.to_end().name())?;
+            }
+        }
+    }
+}
+
+fn position(reader: &Reader<&[u8]>) -> usize {
+    usize::try_from(reader.buffer_position()).unwrap_or(usize::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequential(xml: &str) -> String {
+        format!("{:?}", quick_xml::de::from_str::<Odx>(xml).unwrap())
+    }
+
+    #[test]
+    fn test_split_deserialization_matches_one_pass() {
+        for xml in [
+            include_str!("../../test-fixtures/odx/minimal.odx"),
+            include_str!("../../test-fixtures/odx/dtc_interleaved.odx"),
+        ] {
+            // Chunks of one entry each, so every layer with services splits.
+            let split = deserialize_in_chunks(xml, 1).unwrap();
+            assert_eq!(format!("{split:?}"), sequential(xml));
+            let unsplit = deserialize_in_chunks(xml, usize::MAX).unwrap();
+            assert_eq!(format!("{unsplit:?}"), sequential(xml));
+        }
+    }
+
+    #[test]
+    fn test_scan_finds_layers_and_entries() {
+        let xml = r#"<ODX><DIAG-LAYER-CONTAINER>
+  <ADMIN-DATA><BASE-VARIANT/></ADMIN-DATA>
+  <BASE-VARIANTS><BASE-VARIANT ID="A">
+    <DIAG-COMMS><DIAG-SERVICE/><DIAG-COMM-REF ID-REF="x"></DIAG-COMM-REF></DIAG-COMMS>
+    <REQUESTS><REQUEST ID="R"><PARAMS/></REQUEST></REQUESTS>
+    <X-UNKNOWN><REQUESTS/></X-UNKNOWN>
+  </BASE-VARIANT></BASE-VARIANTS>
+  <PROTOCOLS><PROTOCOL ID="P"/><X-UNKNOWN/></PROTOCOLS>
+</DIAG-LAYER-CONTAINER></ODX>"#;
+        let layers = scan(xml).unwrap();
+        let text = |r: &Range<usize>| &xml[r.clone()];
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].list, LayerList::BaseVariants);
+        assert!(text(&layers[0].range).starts_with(r#"<BASE-VARIANT ID="A">"#));
+        assert!(text(&layers[0].range).ends_with("</BASE-VARIANT>"));
+        let sections: Vec<(Section, Vec<&str>)> = layers[0]
+            .sections
+            .iter()
+            .map(|(section, entries)| (*section, entries.iter().map(text).collect()))
+            .collect();
+        assert_eq!(
+            sections,
+            [
+                (
+                    Section::DiagComms,
+                    vec![
+                        "<DIAG-SERVICE/>",
+                        r#"<DIAG-COMM-REF ID-REF="x"></DIAG-COMM-REF>"#
+                    ]
+                ),
+                (
+                    Section::Requests,
+                    vec![r#"<REQUEST ID="R"><PARAMS/></REQUEST>"#]
+                ),
+            ]
+        );
+        assert_eq!(layers[1].list, LayerList::Protocols);
+        assert_eq!(text(&layers[1].range), r#"<PROTOCOL ID="P"/>"#);
+        assert!(layers[1].sections.is_empty());
+    }
+
+    #[test]
+    fn test_broken_pieces_fall_back() {
+        let xml = include_str!("../../test-fixtures/odx/minimal.odx").replace(
+            "<BYTE-POSITION>0</BYTE-POSITION>",
+            "<BYTE-POSITION>x</BYTE-POSITION>",
+        );
+        assert!(deserialize_in_chunks(&xml, 1).is_none());
+        assert!(deserialize_in_chunks("<ODX/>", 1).is_none());
+    }
+}
@@ -0,0 +1,353 @@
+//! Preservation of ODX content the model does not know.
+//!
+//! With [`ParseOptions::preserve_unknown_xml`](crate::ParseOptions), the
+//! parser scans diag layers and diag services for child elements and
+//! attributes `odx_model` does not map, and keeps them verbatim in an SDG
+//! captioned [`UNKNOWN_XML_CAPTION`] on the layer or service. The ODX writer
+//! re-emits them in place of that SDG: attributes on the start tag, elements
+//! at the end of the container. The SDG survives MDD and YAML like any other.
+
+use std::collections::HashMap;
+
+use diag_ir::{DiagDatabase, DiagLayer, Sd, SdOrSdg, Sdg, Sdgs};
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+
+/// Caption of the SDG holding preserved XML. Each SD's SI is the element
+/// name, or `@NAME` for an attribute; its value is the raw XML.
+pub const UNKNOWN_XML_CAPTION: &str = "odx-unknown-xml";
+
+const LAYER_TAGS: &[&str] = &[
+    "BASE-VARIANT",
+    "ECU-VARIANT",
+    "ECU-SHARED-DATA",
+    "FUNCTIONAL-GROUP",
+    "PROTOCOL",
+];
+
+/// Children and attributes of a diag layer that `DiagLayerVariant` maps.
+const KNOWN_LAYER_ITEMS: &[&str] = &[
+    "@ID",
+    "SHORT-NAME",
+    "LONG-NAME",
+    "DESC",
+    "ADMIN-DATA",
+    "SDGS",
+    "FUNCT-CLASSS",
+    "DIAG-DATA-DICTIONARY-SPEC",
+    "DIAG-COMMS",
+    "REQUESTS",
+    "POS-RESPONSES",
+    "NEG-RESPONSES",
+    "GLOBAL-NEG-RESPONSES",
+    "STATE-CHARTS",
+    "ADDITIONAL-AUDIENCES",
+    "PARENT-REFS",
+    "COMPARAM-REFS",
+    "ECU-VARIANT-PATTERNS",
+];
+
+/// Children and attributes of a DIAG-SERVICE that `OdxDiagService` maps.
+const KNOWN_SERVICE_ITEMS: &[&str] = &[
+    "@ID",
+    "@SEMANTIC",
+    "@DIAGNOSTIC-CLASS",
+    "@IS-MANDATORY",
+    "@IS-EXECUTABLE",
+    "@IS-FINAL",
+    "@IS-CYCLIC",
+    "@IS-MULTIPLE",
+    "@ADDRESSING",
+    "@TRANSMISSION-MODE",
+    "SHORT-NAME",
+    "LONG-NAME",
+    "DESC",
+    "SDGS",
+    "FUNCT-CLASS-REFS",
+    "AUDIENCE",
+    "REQUEST-REF",
+    "POS-RESPONSE-REFS",
+    "NEG-RESPONSE-REFS",
+    "PRE-CONDITION-STATE-REFS",
+    "STATE-TRANSITION-REFS",
+    "COMPARAM-REFS",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Container {
+    Layer,
+    Service,
+}
+
+impl Container {
+    fn of(tag: &str) -> Option<Self> {
+        if LAYER_TAGS.contains(&tag) {
+            Some(Self::Layer)
+        } else if tag == "DIAG-SERVICE" {
+            Some(Self::Service)
+        } else {
+            None
+        }
+    }
+
+    fn knows(self, item: &str) -> bool {
+        let known = match self {
+            Self::Layer => KNOWN_LAYER_ITEMS,
+            Self::Service => KNOWN_SERVICE_ITEMS,
+        };
+        // Namespace declarations and xsi:type are handled by the XML layer.
+        known.contains(&item) || item.starts_with("@xmlns") || item.starts_with("@xsi:")
+    }
+}
+
+struct Frame {
+    tag: String,
+    container: Option<Container>,
+    short_name: Option<String>,
+    preserved: Vec<Sd>,
+}
+
+/// Preserved items keyed by layer short name and, for services, the
+/// service short name.
+pub(crate) type Preserved = HashMap<(String, Option<String>), Vec<Sd>>;
+
+/// Collect the unknown attributes and child elements of all diag layers and
+/// diag services in `xml`.
+pub(crate) fn scan(xml: &str) -> Result<Preserved, quick_xml::Error> {
+    let mut reader = Reader::from_str(xml);
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut preserved = Preserved::new();
+    let mut in_short_name = false;
+
+    loop {
+        let start = position(&reader);
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let tag = name(&e);
+                if let Some(container) = unknown_child_of(&mut stack, &tag) {
+                    let end = e.to_end().into_owned();
+                    reader.read_to_end(end.name())?;
+                    container
+                        .preserved
+                        .push(sd(tag, &xml[start..position(&reader)]));
+                    continue;
+                }
+                in_short_name =
+                    tag == "SHORT-NAME" && stack.last().is_some_and(|f| f.container.is_some());
+                stack.push(frame(&e, tag));
+            }
+            Event::Empty(e) => {
+                let tag = name(&e);
+                if let Some(container) = unknown_child_of(&mut stack, &tag) {
+                    container
+                        .preserved
+                        .push(sd(tag, &xml[start..position(&reader)]));
+                    continue;
+                }
+                let frame = frame(&e, tag);
+                if frame.container.is_some() {
+                    finish(frame, &stack, &mut preserved);
+                }
+            }
+            Event::Text(t) if in_short_name => {
+                let text = t.unescape()?.trim().to_string();
+                let len = stack.len();
+                if let Some(container) = len.checked_sub(2).and_then(|i| stack.get_mut(i)) {
+                    container.short_name = Some(text);
+                }
+            }
+            Event::End(_) => {
+                in_short_name = false;
+                if let Some(frame) = stack.pop() {
+                    if frame.container.is_some() {
+                        finish(frame, &stack, &mut preserved);
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(preserved)
+}
+
+/// Attach scanned items to the layers and services they were found in.
+pub(crate) fn attach(db: &mut DiagDatabase, mut preserved: Preserved) {
+    let layers = db
+        .variants
+        .iter_mut()
+        .map(|v| &mut v.diag_layer)
+        .chain(db.functional_groups.iter_mut().map(|f| &mut f.diag_layer))
+        .chain(db.protocols.iter_mut().map(|p| &mut p.diag_layer))
+        .chain(db.ecu_shared_datas.iter_mut().map(|e| &mut e.diag_layer));
+    for layer in layers {
+        attach_to_layer(layer, &mut preserved);
+    }
+}
+
+fn attach_to_layer(layer: &mut DiagLayer, preserved: &mut Preserved) {
+    if let Some(sds) = preserved.remove(&(layer.short_name.to_string(), None)) {
+        push_sdg(&mut layer.sdgs, sds);
+    }
+    for svc in &mut layer.diag_services {
+        let key = (
+            layer.short_name.to_string(),
+            Some(svc.diag_comm.short_name.to_string()),
+        );
+        if let Some(sds) = preserved.remove(&key) {
+            push_sdg(&mut svc.diag_comm.sdgs, sds);
+        }
+    }
+}
+
+fn push_sdg(sdgs: &mut Option<Sdgs>, sds: Vec<Sd>) {
+    sdgs.get_or_insert_with(|| Sdgs { sdgs: vec![] })
+        .sdgs
+        .push(Sdg {
+            caption_sn: UNKNOWN_XML_CAPTION.into(),
+            sds: sds.into_iter().map(SdOrSdg::Sd).collect(),
+            si: String::new(),
+        });
+}
+
+/// Split preserved XML off `sdgs`: the remaining SDGs, the raw attributes
+/// (`NAME="value"`) and the raw elements.
+pub(crate) fn take_preserved(sdgs: &Option<Sdgs>) -> (Option<Sdgs>, Option<String>, Vec<String>) {
+    let Some(sdgs) = sdgs else {
+        return (None, None, vec![]);
+    };
+    let (unknown, rest): (Vec<&Sdg>, Vec<&Sdg>) = sdgs
+        .sdgs
+        .iter()
+        .partition(|sdg| sdg.caption_sn == UNKNOWN_XML_CAPTION);
+    let mut attributes = Vec::new();
+    let mut elements = Vec::new();
+    for sd in unknown.iter().flat_map(|sdg| &sdg.sds) {
+        if let SdOrSdg::Sd(sd) = sd {
+            if sd.si.starts_with('@') {
+                attributes.push(sd.value.clone());
+            } else {
+                elements.push(sd.value.clone());
+            }
+        }
+    }
+    let rest = (!rest.is_empty()).then(|| Sdgs {
+        sdgs: rest.into_iter().cloned().collect(),
+    });
+    let attributes = (!attributes.is_empty()).then(|| attributes.join(" "));
+    (rest, attributes, elements)
+}
+
+fn position(reader: &Reader<&[u8]>) -> usize {
+    usize::try_from(reader.buffer_position()).unwrap_or(usize::MAX)
+}
+
+fn name(e: &BytesStart<'_>) -> String {
+    String::from_utf8_lossy(e.name().as_ref()).into_owned()
+}
+
+fn sd(si: String, raw: &str) -> Sd {
+    Sd {
+        value: raw.to_string(),
+        si,
+        ti: String::new(),
+    }
+}
+
+/// The innermost frame, if it is a container that does not know `tag`.
+fn unknown_child_of<'a>(stack: &'a mut [Frame], tag: &str) -> Option<&'a mut Frame> {
+    stack
+        .last_mut()
+        .filter(|f| f.container.is_some_and(|c| !c.knows(tag)))
+}
+
+fn frame(e: &BytesStart<'_>, tag: String) -> Frame {
+    let container = Container::of(&tag);
+    let mut preserved = Vec::new();
+    if let Some(container) = container {
+        for attr in e.attributes().flatten() {
+            let key = format!("@{}", String::from_utf8_lossy(attr.key.as_ref()));
+            if !container.knows(&key) {
+                let raw = format!("{}=\"{}\"", &key[1..], String::from_utf8_lossy(&attr.value));
+                preserved.push(sd(key, &raw));
+            }
+        }
+    }
+    Frame {
+        tag,
+        container,
+        short_name: None,
+        preserved,
+    }
+}
+
+fn finish(frame: Frame, stack: &[Frame], preserved: &mut Preserved) {
+    if frame.preserved.is_empty() {
+        return;
+    }
+    let key = match frame.container {
+        Some(Container::Service) => {
+            let Some(layer) = stack
+                .iter()
+                .rev()
+                .find(|f| f.container == Some(Container::Layer))
+            else {
+                return;
+            };
+            (
+                layer.short_name.clone().unwrap_or_default(),
+                frame.short_name,
+            )
+        }
+        _ => (frame.short_name.unwrap_or_default(), None),
+    };
+    log::debug!(
+        "Preserving {} unknown item(s) of {} {:?}",
+        frame.preserved.len(),
+        frame.tag,
+        key
+    );
+    preserved.entry(key).or_default().extend(frame.preserved);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_collects_unknown_items() {
+        let xml = r#"<ODX><DIAG-LAYER-CONTAINER><BASE-VARIANTS>
+<BASE-VARIANT ID="BV" VENDOR-FLAG="x&amp;y">
+  <SHORT-NAME>Base</SHORT-NAME>
+  <VENDOR-INFO><NOTE>kept</NOTE></VENDOR-INFO>
+  <DIAG-COMMS>
+    <DIAG-SERVICE ID="DS" SEMANTIC="DATA">
+      <SHORT-NAME>Read</SHORT-NAME>
+      <VENDOR-MARK/>
+    </DIAG-SERVICE>
+  </DIAG-COMMS>
+</BASE-VARIANT>
+</BASE-VARIANTS></DIAG-LAYER-CONTAINER></ODX>"#;
+        let preserved = scan(xml).unwrap();
+        let items = |key: (&str, Option<&str>)| -> Vec<(String, String)> {
+            preserved[&(key.0.to_string(), key.1.map(String::from))]
+                .iter()
+                .map(|sd| (sd.si.clone(), sd.value.clone()))
+                .collect()
+        };
+        assert_eq!(
+            items(("Base", None)),
+            [
+                ("@VENDOR-FLAG".into(), "VENDOR-FLAG=\"x&amp;y\"".into()),
+                (
+                    "VENDOR-INFO".into(),
+                    "<VENDOR-INFO><NOTE>kept</NOTE></VENDOR-INFO>".into()
+                ),
+            ]
+        );
+        assert_eq!(
+            items(("Base", Some("Read"))),
+            [("VENDOR-MARK".into(), "<VENDOR-MARK/>".into())]
+        );
+    }
+}
@@ -4,9 +4,17 @@
 //! to XML via quick-xml.
 
 use diag_ir::*;
+use serde::Serialize;
+use std::fmt::Write as _;
 use thiserror::Error;
 
+use crate::desc::markdown_to_desc;
+use crate::dialect::{self, OdxVersion};
 use crate::odx_model::*;
+use crate::parser::{
+    META_PROVENANCE_CHANGE, META_PROVENANCE_DATE, META_PROVENANCE_REASON, META_PROVENANCE_TOOL,
+};
+use crate::unknown_xml::take_preserved;
 
 #[derive(Debug, Error)]
 pub enum OdxWriteError {
@@ -16,16 +24,98 @@ pub enum OdxWriteError {
     SerError(String),
 }
 
+/// What ODX can carry. Memory configuration has no ODX counterpart; YAML
+/// type definitions are kept in an SDG of the DIAG-LAYER-CONTAINER.
+pub const CAPABILITIES: FormatCapabilities = FormatCapabilities {
+    format: "ODX",
+    functional_groups: true,
+    memory: false,
+    type_definitions: true,
+    descriptions: true,
+    tables: true,
+    complex_dops: true,
+    additional_audiences: true,
+    custom_state_charts: true,
+};
+
+/// Options for [`write_odx_with`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OdxWriteOptions {
+    /// Indent nested elements by two spaces.
+    pub pretty: bool,
+    /// Write the element names and model version of this ODX version. By
+    /// default the document uses the names of ODX 2.2.0 and carries the
+    /// model version of the database.
+    pub version: Option<OdxVersion>,
+}
+
 /// Write an IR DiagDatabase to an ODX XML string.
 pub fn write_odx(db: &DiagDatabase) -> Result<String, OdxWriteError> {
+    write_odx_with(db, &OdxWriteOptions::default())
+}
+
+/// Like [`write_odx`], but indents nested elements by two spaces.
+pub fn write_odx_pretty(db: &DiagDatabase) -> Result<String, OdxWriteError> {
+    write_odx_with(
+        db,
+        &OdxWriteOptions {
+            pretty: true,
+            ..Default::default()
+        },
+    )
+}
+
+/// Write an IR DiagDatabase to an ODX XML string with explicit
+/// [`OdxWriteOptions`].
+pub fn write_odx_with(
+    db: &DiagDatabase,
+    options: &OdxWriteOptions,
+) -> Result<String, OdxWriteError> {
     let odx = ir_to_odx(db);
-    let xml = quick_xml::se::to_string(&odx).map_err(|e| OdxWriteError::SerError(e.to_string()))?;
+    let mut xml = String::new();
+    let mut ser = quick_xml::se::Serializer::new(&mut xml);
+    if options.pretty {
+        ser.indent(' ', 2);
+    }
+    odx.serialize(ser)
+        .map_err(|e| OdxWriteError::SerError(e.to_string()))?;
 
+    let mut xml = restore_preserved_xml(&xml);
+    if let Some(version) = options.version {
+        xml = dialect::from_model(&xml, version)
+            .map_err(|e| OdxWriteError::SerError(e.to_string()))?;
+    }
     // Add XML declaration and format
-    Ok(format!(
-        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}",
-        xml
-    ))
+    Ok(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{xml}"))
+}
+
+/// Replace the placeholders of preserved unknown XML with the raw XML they
+/// carry (escaped by the serializer).
+fn restore_preserved_xml(xml: &str) -> String {
+    const PLACEHOLDERS: [(&str, &str, &str); 2] = [
+        ("<X-PRESERVED-XML>", "</X-PRESERVED-XML>", ""),
+        (" X-PRESERVED-ATTRS=\"", "\"", " "),
+    ];
+    let mut out = xml.to_string();
+    for (open, close, prefix) in PLACEHOLDERS {
+        let mut restored = String::with_capacity(out.len());
+        let mut rest = out.as_str();
+        while let Some(start) = rest.find(open) {
+            let body = &rest[start + open.len()..];
+            let Some(end) = body.find(close) else {
+                break;
+            };
+            let raw = quick_xml::escape::unescape(&body[..end])
+                .map_or_else(|_| body[..end].to_string(), std::borrow::Cow::into_owned);
+            restored.push_str(&rest[..start]);
+            restored.push_str(prefix);
+            restored.push_str(&raw);
+            rest = &body[end + close.len()..];
+        }
+        restored.push_str(rest);
+        out = restored;
+    }
+    out
 }
 
 fn ir_to_odx(db: &DiagDatabase) -> Odx {
@@ -54,28 +144,8 @@ fn ir_to_odx(db: &DiagDatabase) -> Odx {
             id: None,
             short_name: Some(db.ecu_name.clone()),
             long_name: None,
-            admin_data: if db.revision.is_empty()
-                && !db.metadata.contains_key("admin_language")
-                && !db.metadata.contains_key("admin_doc_state")
-            {
-                None
-            } else {
-                Some(AdminData {
-                    language: db.metadata.get("admin_language").cloned(),
-                    doc_revisions: Some(DocRevisionsWrapper {
-                        items: vec![DocRevision {
-                            revision_label: if db.revision.is_empty() {
-                                None
-                            } else {
-                                Some(db.revision.clone())
-                            },
-                            state: db.metadata.get("admin_doc_state").cloned(),
-                            date: db.metadata.get("admin_doc_date").cloned(),
-                        }],
-                    }),
-                })
-            },
-            sdgs: None,
+            admin_data: ir_admin_data(db),
+            sdgs: container_sdgs(db),
             base_variants: if base_variants.is_empty() {
                 None
             } else {
@@ -120,6 +190,65 @@ fn ir_to_odx(db: &DiagDatabase) -> Odx {
     }
 }
 
+/// SDGs of the DIAG-LAYER-CONTAINER: the type definitions, the transport
+/// section and the ECU identification, which ODX has no elements for.
+fn container_sdgs(db: &DiagDatabase) -> Option<SdgsWrapper> {
+    let items: Vec<_> = [
+        type_definitions_sdg(db),
+        transport_sdg(db),
+        identification_sdg(db),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|sdg| ir_sdg_to_odx(&sdg))
+    .collect();
+    (!items.is_empty()).then_some(SdgsWrapper { items })
+}
+
+fn ir_admin_data(db: &DiagDatabase) -> Option<AdminData> {
+    let mut revisions = Vec::new();
+    if !db.revision.is_empty()
+        || db.metadata.contains_key("admin_language")
+        || db.metadata.contains_key("admin_doc_state")
+    {
+        revisions.push(DocRevision {
+            revision_label: if db.revision.is_empty() {
+                None
+            } else {
+                Some(db.revision.clone())
+            },
+            state: db.metadata.get("admin_doc_state").cloned(),
+            date: db.metadata.get("admin_doc_date").cloned(),
+            tool: None,
+            modifications: None,
+        });
+    }
+    if let Some(tool) = db.metadata.get(META_PROVENANCE_TOOL) {
+        let change = db.metadata.get(META_PROVENANCE_CHANGE).cloned();
+        let reason = db.metadata.get(META_PROVENANCE_REASON).cloned();
+        revisions.push(DocRevision {
+            revision_label: None,
+            state: None,
+            date: db.metadata.get(META_PROVENANCE_DATE).cloned(),
+            tool: Some(tool.clone()),
+            modifications: if change.is_none() && reason.is_none() {
+                None
+            } else {
+                Some(ModificationsWrapper {
+                    items: vec![Modification { change, reason }],
+                })
+            },
+        });
+    }
+    if revisions.is_empty() {
+        return None;
+    }
+    Some(AdminData {
+        language: db.metadata.get("admin_language").cloned(),
+        doc_revisions: Some(DocRevisionsWrapper { items: revisions }),
+    })
+}
+
 fn ir_variant_to_layer(variant: &Variant, db: &DiagDatabase) -> DiagLayerVariant {
     let mut layer = ir_diag_layer_to_odx(&variant.diag_layer, db);
 
@@ -137,10 +266,12 @@ fn ir_variant_to_layer(variant: &Variant, db: &DiagDatabase) -> DiagLayerVariant
                             .map(|mp| OdxMatchingParameter {
                                 expected_value: Some(mp.expected_value.clone()),
                                 diag_comm_snref: Some(OdxSnRef {
-                                    short_name: Some(mp.diag_service.diag_comm.short_name.clone()),
+                                    short_name: Some(
+                                        mp.diag_service.diag_comm.short_name.as_str().into(),
+                                    ),
                                 }),
                                 out_param_snref: Some(OdxSnRef {
-                                    short_name: Some(mp.out_param.short_name.clone()),
+                                    short_name: Some(mp.out_param.short_name.as_str().into()),
                                 }),
                             })
                             .collect(),
@@ -341,12 +472,15 @@ fn ir_diag_layer_to_odx_no_dtcs(diag_layer: &DiagLayer) -> DiagLayerVariant {
         })
     };
 
+    let (sdgs, preserved_attrs, preserved_xml) = take_preserved(&diag_layer.sdgs);
     DiagLayerVariant {
         id: None,
-        short_name: Some(diag_layer.short_name.clone()),
+        preserved_attrs,
+        short_name: Some(diag_layer.short_name.as_str().into()),
         long_name: diag_layer.long_name.as_ref().map(|ln| ln.value.clone()),
+        desc: diag_layer.description.as_deref().map(markdown_to_desc),
         admin_data: None,
-        sdgs: ir_sdgs_to_odx(&diag_layer.sdgs),
+        sdgs: ir_sdgs_to_odx(&sdgs),
         funct_classs: if diag_layer.funct_classes.is_empty() {
             None
         } else {
@@ -356,7 +490,7 @@ fn ir_diag_layer_to_odx_no_dtcs(diag_layer: &DiagLayer) -> DiagLayerVariant {
                     .iter()
                     .map(|fc| crate::odx_model::FunctClass {
                         id: Some(format!("FC_{}", fc.short_name)),
-                        short_name: Some(fc.short_name.clone()),
+                        short_name: Some(fc.short_name.as_str().into()),
                         long_name: None,
                     })
                     .collect(),
@@ -408,7 +542,7 @@ fn ir_diag_layer_to_odx_no_dtcs(diag_layer: &DiagLayer) -> DiagLayerVariant {
                     .iter()
                     .map(|aa| OdxAdditionalAudience {
                         id: None,
-                        short_name: Some(aa.short_name.clone()),
+                        short_name: Some(aa.short_name.as_str().into()),
                         long_name: aa.long_name.as_ref().map(|ln| ln.value.clone()),
                     })
                     .collect(),
@@ -427,6 +561,7 @@ fn ir_diag_layer_to_odx_no_dtcs(diag_layer: &DiagLayer) -> DiagLayerVariant {
             })
         },
         ecu_variant_patterns: None,
+        preserved_xml,
     }
 }
 
@@ -445,10 +580,10 @@ fn ir_comparam_ref_to_odx(cr: &ComParamRef) -> OdxComparamRef {
                 .collect(),
         }),
         protocol_snref: cr.protocol.as_ref().map(|p| OdxSnRef {
-            short_name: Some(p.diag_layer.short_name.clone()),
+            short_name: Some(p.diag_layer.short_name.as_str().into()),
         }),
         prot_stack_snref: cr.prot_stack.as_ref().map(|ps| OdxSnRef {
-            short_name: Some(ps.short_name.clone()),
+            short_name: Some(ps.short_name.as_str().into()),
         }),
     }
 }
@@ -490,12 +625,14 @@ fn ir_diag_service_to_odx(svc: &DiagService, svc_id: &str, idx: usize) -> OdxDia
         })
     };
 
+    let (sdgs, preserved_attrs, preserved_xml) = take_preserved(&svc.diag_comm.sdgs);
     OdxDiagService {
         id: Some(svc_id.to_string()),
+        preserved_attrs,
         semantic: if svc.diag_comm.semantic.is_empty() {
             None
         } else {
-            Some(svc.diag_comm.semantic.clone())
+            Some(svc.diag_comm.semantic.as_str().into())
         },
         diagnostic_class: None,
         is_mandatory: if svc.diag_comm.is_mandatory {
@@ -525,9 +662,10 @@ fn ir_diag_service_to_odx(svc: &DiagService, svc_id: &str, idx: usize) -> OdxDia
         },
         addressing: None,
         transmission_mode: None,
-        short_name: Some(svc.diag_comm.short_name.clone()),
+        short_name: Some(svc.diag_comm.short_name.as_str().into()),
         long_name: svc.diag_comm.long_name.as_ref().map(|ln| ln.value.clone()),
-        sdgs: ir_sdgs_to_odx(&svc.diag_comm.sdgs),
+        desc: svc.diag_comm.description.as_deref().map(markdown_to_desc),
+        sdgs: ir_sdgs_to_odx(&sdgs),
         funct_class_refs: if svc.diag_comm.funct_classes.is_empty() {
             None
         } else {
@@ -589,6 +727,7 @@ fn ir_diag_service_to_odx(svc: &DiagService, svc_id: &str, idx: usize) -> OdxDia
             })
         },
         comparam_refs: None,
+        preserved_xml,
     }
 }
 
@@ -631,6 +770,8 @@ fn ir_response_to_odx(resp: &Response, resp_id: &str, dops: &[OdxDataObjectProp]
                     .collect(),
             })
         },
+        pos_response_suffix: resp.pos_response_suffix.clone(),
+        audience: resp.audience.as_ref().map(ir_audience_to_odx),
     }
 }
 
@@ -658,9 +799,9 @@ fn ir_param_to_odx(p: &Param, dops: &[OdxDataObjectProp]) -> OdxParam {
         semantic: if p.semantic.is_empty() {
             None
         } else {
-            Some(p.semantic.clone())
+            Some(p.semantic.as_str().into())
         },
-        short_name: Some(p.short_name.clone()),
+        short_name: Some(p.short_name.as_str().into()),
         long_name: None,
         byte_position: p.byte_position,
         bit_position: p.bit_position,
@@ -882,7 +1023,7 @@ fn collect_dops_from_params(params: &[Param], col: &mut DopCollection) {
                     {
                         col.end_of_pdu_fields.push(OdxEndOfPduField {
                             id: Some(format!("EOPF_{name}")),
-                            short_name: Some(name.clone()),
+                            short_name: Some(name.as_str().into()),
                             max_number_of_items: *max_number_of_items,
                             min_number_of_items: *min_number_of_items,
                         });
@@ -900,7 +1041,7 @@ fn collect_dops_from_params(params: &[Param], col: &mut DopCollection) {
                     {
                         col.static_fields.push(OdxStaticField {
                             id: Some(format!("SF_{name}")),
-                            short_name: Some(name.clone()),
+                            short_name: Some(name.as_str().into()),
                             fixed_number_of_items: Some(*fixed_number_of_items),
                             item_byte_size: Some(*item_byte_size),
                         });
@@ -914,7 +1055,7 @@ fn collect_dops_from_params(params: &[Param], col: &mut DopCollection) {
                     {
                         col.dynamic_length_fields.push(OdxDynamicLengthField {
                             id: Some(format!("DLF_{name}")),
-                            short_name: Some(name.clone()),
+                            short_name: Some(name.as_str().into()),
                             offset: Some(*offset),
                         });
                     }
@@ -927,7 +1068,7 @@ fn collect_dops_from_params(params: &[Param], col: &mut DopCollection) {
                     {
                         col.muxs.push(OdxMux {
                             id: Some(format!("MUX_{name}")),
-                            short_name: Some(name.clone()),
+                            short_name: Some(name.as_str().into()),
                         });
                     }
                 }
@@ -939,7 +1080,7 @@ fn collect_dops_from_params(params: &[Param], col: &mut DopCollection) {
                     {
                         col.env_datas.push(OdxEnvData {
                             id: Some(format!("ED_{name}")),
-                            short_name: Some(name.clone()),
+                            short_name: Some(name.as_str().into()),
                         });
                     }
                 }
@@ -951,7 +1092,7 @@ fn collect_dops_from_params(params: &[Param], col: &mut DopCollection) {
                     {
                         col.env_data_descs.push(OdxEnvDataDesc {
                             id: Some(format!("EDD_{name}")),
-                            short_name: Some(name.clone()),
+                            short_name: Some(name.as_str().into()),
                         });
                     }
                 }
@@ -1029,7 +1170,7 @@ fn ir_dop_to_odx(dop: &Dop) -> OdxDataObjectProp {
 
     OdxDataObjectProp {
         id: Some(format!("DOP_{}", dop.short_name)),
-        short_name: Some(dop.short_name.clone()),
+        short_name: Some(dop.short_name.as_str().into()),
         long_name: None,
         sdgs: ir_sdgs_to_odx(&dop.sdgs),
         diag_coded_type: dct,
@@ -1106,9 +1247,22 @@ fn ir_dct_to_odx(dct: &DiagCodedType) -> OdxDiagCodedType {
         } else {
             Some(dct.base_type_encoding.clone())
         },
-        is_condensed: None,
+        is_condensed: match &dct.specific_data {
+            Some(DiagCodedTypeData::StandardLength {
+                condensed: true, ..
+            }) => Some("true".into()),
+            _ => None,
+        },
         bit_length,
-        bit_mask: None,
+        bit_mask: match &dct.specific_data {
+            Some(DiagCodedTypeData::StandardLength { bit_mask, .. }) if !bit_mask.is_empty() => {
+                Some(bit_mask.iter().fold(String::new(), |mut hex, b| {
+                    let _ = write!(hex, "{b:02X}");
+                    hex
+                }))
+            }
+            _ => None,
+        },
         min_length,
         max_length,
         termination,
@@ -1249,7 +1403,7 @@ fn ir_dtc_to_odx(dtc: &Dtc) -> OdxDtc {
         } else {
             None
         },
-        short_name: Some(dtc.short_name.clone()),
+        short_name: Some(dtc.short_name.as_str().into()),
         long_name: None,
         trouble_code: Some(dtc.trouble_code),
         display_trouble_code: Some(dtc.display_trouble_code.clone()),
@@ -1275,7 +1429,7 @@ fn ir_dtc_to_odx(dtc: &Dtc) -> OdxDtc {
 fn ir_unit_to_odx(unit: &Unit) -> OdxUnit {
     OdxUnit {
         id: Some(format!("UNIT_{}", unit.short_name)),
-        short_name: Some(unit.short_name.clone()),
+        short_name: Some(unit.short_name.as_str().into()),
         display_name: if unit.display_name.is_empty() {
             None
         } else {
@@ -1294,7 +1448,7 @@ fn ir_unit_to_odx(unit: &Unit) -> OdxUnit {
 fn ir_physical_dimension_to_odx(pd: &PhysicalDimension) -> OdxPhysicalDimension {
     OdxPhysicalDimension {
         id: Some(format!("PD_{}", pd.short_name)),
-        short_name: Some(pd.short_name.clone()),
+        short_name: Some(pd.short_name.as_str().into()),
         length_exp: pd.length_exp,
         mass_exp: pd.mass_exp,
         time_exp: pd.time_exp,
@@ -1308,11 +1462,11 @@ fn ir_physical_dimension_to_odx(pd: &PhysicalDimension) -> OdxPhysicalDimension
 fn ir_state_chart_to_odx(sc: &StateChart) -> OdxStateChart {
     OdxStateChart {
         id: None,
-        short_name: Some(sc.short_name.clone()),
+        short_name: Some(sc.short_name.as_str().into()),
         semantic: if sc.semantic.is_empty() {
             None
         } else {
-            Some(sc.semantic.clone())
+            Some(sc.semantic.as_str().into())
         },
         start_state_snref: Some(OdxSnRef {
             short_name: Some(sc.start_state_short_name_ref.clone()),
@@ -1326,7 +1480,7 @@ fn ir_state_chart_to_odx(sc: &StateChart) -> OdxStateChart {
                     .iter()
                     .map(|s| OdxState {
                         id: Some(format!("S_{}", s.short_name)),
-                        short_name: Some(s.short_name.clone()),
+                        short_name: Some(s.short_name.as_str().into()),
                         long_name: s.long_name.as_ref().map(|ln| ln.value.clone()),
                     })
                     .collect(),
@@ -1341,7 +1495,7 @@ fn ir_state_chart_to_odx(sc: &StateChart) -> OdxStateChart {
                     .iter()
                     .map(|t| OdxStateTransition {
                         id: Some(format!("ST_{}", t.short_name)),
-                        short_name: Some(t.short_name.clone()),
+                        short_name: Some(t.short_name.as_str().into()),
                         source_snref: Some(OdxSnRef {
                             short_name: Some(t.source_short_name_ref.clone()),
                         }),
@@ -1365,7 +1519,7 @@ fn audience_refs_to_odx(audiences: &[AdditionalAudience]) -> Option<AudienceRefs
             items: audiences
                 .iter()
                 .map(|a| OdxRef {
-                    id_ref: Some(a.short_name.clone()),
+                    id_ref: Some(a.short_name.as_str().into()),
                     docref: None,
                     doctype: None,
                 })
@@ -1411,8 +1565,9 @@ fn ir_audience_to_odx(aud: &Audience) -> OdxAudience {
 fn ir_ecu_job_to_odx(job: &SingleEcuJob, idx: usize) -> OdxSingleEcuJob {
     OdxSingleEcuJob {
         id: Some(format!("SEJ_{}", idx)),
-        short_name: Some(job.diag_comm.short_name.clone()),
+        short_name: Some(job.diag_comm.short_name.as_str().into()),
         long_name: job.diag_comm.long_name.as_ref().map(|ln| ln.value.clone()),
+        desc: job.diag_comm.description.as_deref().map(markdown_to_desc),
         sdgs: ir_sdgs_to_odx(&job.diag_comm.sdgs),
         prog_codes: if job.prog_codes.is_empty() {
             None
@@ -1477,7 +1632,7 @@ fn ir_ecu_job_to_odx(job: &SingleEcuJob, idx: usize) -> OdxSingleEcuJob {
 
 fn ir_job_param_to_odx(jp: &JobParam) -> OdxJobParam {
     OdxJobParam {
-        short_name: Some(jp.short_name.clone()),
+        short_name: Some(jp.short_name.as_str().into()),
         long_name: jp.long_name.as_ref().map(|ln| ln.value.clone()),
         physical_default_value: if jp.physical_default_value.is_empty() {
             None
@@ -1488,7 +1643,7 @@ fn ir_job_param_to_odx(jp: &JobParam) -> OdxJobParam {
         semantic: if jp.semantic.is_empty() {
             None
         } else {
-            Some(jp.semantic.clone())
+            Some(jp.semantic.as_str().into())
         },
     }
 }
@@ -1497,11 +1652,11 @@ fn ir_job_param_to_odx(jp: &JobParam) -> OdxJobParam {
 
 fn ir_parent_ref_to_odx(pref: &ParentRef) -> OdxParentRef {
     let id_ref = match &pref.ref_type {
-        ParentRefType::Variant(v) => Some(v.diag_layer.short_name.clone()),
-        ParentRefType::Protocol(p) => Some(p.diag_layer.short_name.clone()),
-        ParentRefType::FunctionalGroup(fg) => Some(fg.diag_layer.short_name.clone()),
-        ParentRefType::EcuSharedData(esd) => Some(esd.diag_layer.short_name.clone()),
-        ParentRefType::TableDop(td) => Some(td.short_name.clone()),
+        ParentRefType::Variant(v) => Some(v.diag_layer.short_name.to_string()),
+        ParentRefType::Protocol(p) => Some(p.diag_layer.short_name.to_string()),
+        ParentRefType::FunctionalGroup(fg) => Some(fg.diag_layer.short_name.to_string()),
+        ParentRefType::EcuSharedData(esd) => Some(esd.diag_layer.short_name.to_string()),
+        ParentRefType::TableDop(td) => Some(td.short_name.to_string()),
     };
     OdxParentRef {
         id_ref,
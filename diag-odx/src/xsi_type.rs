@@ -0,0 +1,134 @@
+//! Normalization of `xsi:type` attributes before deserialization.
+//!
+//! `odx_model` dispatches params and diag coded types on `@xsi:type`, but
+//! tools bind the XML Schema instance namespace to other prefixes
+//! (`ns2:type`, `xs:type`). This pass renames every prefixed `type`
+//! attribute to `xsi:type` and replaces values registered as aliases in
+//! [`ParseOptions::type_aliases`](crate::ParseOptions). Documents that need
+//! neither are passed through untouched.
+
+use std::borrow::Cow;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, Writer};
+
+/// Rewrite the type attributes of `xml` as described in the module docs.
+pub(crate) fn normalize<'a>(
+    xml: &'a str,
+    aliases: &[(String, String)],
+) -> Result<Cow<'a, str>, quick_xml::Error> {
+    if !needs_rewrite(xml, aliases)? {
+        return Ok(Cow::Borrowed(xml));
+    }
+    let mut reader = Reader::from_str(xml);
+    let mut writer = Writer::new(Vec::with_capacity(xml.len()));
+    loop {
+        let event = match reader.read_event()? {
+            Event::Start(e) => Event::Start(rewrite(&e, aliases)?),
+            Event::Empty(e) => Event::Empty(rewrite(&e, aliases)?),
+            Event::Eof => break,
+            other => other,
+        };
+        writer.write_event(event)?;
+    }
+    let out = String::from_utf8_lossy(&writer.into_inner()).into_owned();
+    Ok(Cow::Owned(out))
+}
+
+/// The local name of a type value: `odx:VALUE` -> `VALUE`.
+pub(crate) fn local_name(value: &str) -> &str {
+    value.rsplit_once(':').map_or(value, |(_, local)| local)
+}
+
+fn needs_rewrite(xml: &str, aliases: &[(String, String)]) -> Result<bool, quick_xml::Error> {
+    let mut reader = Reader::from_str(xml);
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) => {
+                for attr in e.attributes().flatten() {
+                    let key = attr.key.as_ref();
+                    if !is_type_attribute(key) {
+                        continue;
+                    }
+                    if key != b"xsi:type" || alias_of(&attr.value, aliases).is_some() {
+                        return Ok(true);
+                    }
+                }
+            }
+            Event::Eof => return Ok(false),
+            _ => {}
+        }
+    }
+}
+
+fn rewrite(
+    e: &BytesStart<'_>,
+    aliases: &[(String, String)],
+) -> Result<BytesStart<'static>, quick_xml::Error> {
+    let mut out = BytesStart::new(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+    for attr in e.attributes() {
+        let attr = attr?;
+        if is_type_attribute(attr.key.as_ref()) {
+            let value = match alias_of(&attr.value, aliases) {
+                Some(canonical) => canonical.as_bytes().to_vec(),
+                None => attr.value.into_owned(),
+            };
+            out.push_attribute((b"xsi:type".as_slice(), value.as_slice()));
+        } else {
+            out.push_attribute(attr);
+        }
+    }
+    Ok(out)
+}
+
+/// `type` or `<prefix>:type`; `xmlns:type` is a namespace declaration.
+fn is_type_attribute(key: &[u8]) -> bool {
+    key == b"type" || (key.ends_with(b":type") && !key.starts_with(b"xmlns:"))
+}
+
+fn alias_of<'a>(value: &[u8], aliases: &'a [(String, String)]) -> Option<&'a str> {
+    let value = std::str::from_utf8(value).ok()?;
+    aliases
+        .iter()
+        .find(|(alias, _)| alias == value || alias == local_name(value))
+        .map(|(_, canonical)| canonical.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefixes_are_normalized() {
+        let xml = r#"<PARAMS><PARAM ns2:type="VALUE" SEMANTIC="DATA"/><PARAM xsi:type="CODED-CONST"></PARAM></PARAMS>"#;
+        assert_eq!(
+            normalize(xml, &[]).unwrap(),
+            r#"<PARAMS><PARAM xsi:type="VALUE" SEMANTIC="DATA"/><PARAM xsi:type="CODED-CONST"></PARAM></PARAMS>"#
+        );
+    }
+
+    #[test]
+    fn test_canonical_documents_are_borrowed() {
+        let xml = r#"<PARAM xmlns:type="urn:x" xsi:type="VALUE"/>"#;
+        assert!(matches!(normalize(xml, &[]).unwrap(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_aliases_replace_values() {
+        let aliases = [("VALUE_PARAM".to_string(), "VALUE".to_string())];
+        let xml = r#"<PARAM xsi:type="odx:VALUE_PARAM"/>"#;
+        assert_eq!(
+            normalize(xml, &aliases).unwrap(),
+            r#"<PARAM xsi:type="VALUE"/>"#
+        );
+    }
+
+    #[test]
+    fn test_local_name() {
+        assert_eq!(
+            local_name("odx:STANDARD-LENGTH-TYPE"),
+            "STANDARD-LENGTH-TYPE"
+        );
+        assert_eq!(local_name("VALUE"), "VALUE");
+    }
+}
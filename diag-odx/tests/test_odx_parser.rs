@@ -91,6 +91,24 @@ fn test_parse_odx_request_params() {
     }
 }
 
+#[test]
+fn test_parse_odx_shares_repeated_names() {
+    let db = parse_minimal();
+    let sids: Vec<&Name> = db
+        .variants
+        .iter()
+        .flat_map(|v| &v.diag_layer.diag_services)
+        .flat_map(|s| {
+            let requests = s.request.iter().flat_map(|r| &r.params);
+            requests.chain(s.pos_responses.iter().flat_map(|r| &r.params))
+        })
+        .map(|p| &p.short_name)
+        .filter(|name| *name == "SID")
+        .collect();
+    assert!(sids.len() > 1);
+    assert!(sids.iter().all(|sid| Name::ptr_eq(sid, sids[0])));
+}
+
 #[test]
 fn test_parse_odx_response_value_param() {
     let db = parse_minimal();
@@ -166,6 +184,32 @@ fn test_parse_odx_nrc_const() {
     }
 }
 
+#[test]
+fn test_parse_odx_shares_referenced_dops() {
+    let db = parse_minimal();
+    // The ECU variant inherits Read_VehicleSpeed, so both layers map a
+    // parameter referencing DOP_VehicleSpeed.
+    let speed_dops: Vec<&DopRef> = db
+        .variants
+        .iter()
+        .flat_map(|v| &v.diag_layer.diag_services)
+        .filter(|s| s.diag_comm.short_name == "Read_VehicleSpeed")
+        .flat_map(|s| &s.pos_responses[0].params)
+        .filter_map(|p| match &p.specific_data {
+            Some(ParamData::Value { dop, .. }) => Some(dop),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(speed_dops.len(), 2);
+    assert!(DopRef::ptr_eq(speed_dops[0], speed_dops[1]));
+
+    // Editing one parameter's DOP leaves the other one untouched.
+    let mut edited = speed_dops[0].clone();
+    edited.short_name = "Edited".into();
+    assert!(!DopRef::ptr_eq(&edited, speed_dops[1]));
+    assert_eq!(speed_dops[1].short_name, "VehicleSpeed");
+}
+
 #[test]
 fn test_parse_odx_single_ecu_job() {
     let db = parse_minimal();
@@ -533,3 +577,195 @@ fn test_parse_odx_with_interleaved_dtcs() {
         "should parse both DTCs despite interleaving"
     );
 }
+
+#[test]
+fn test_parse_odx_duplicate_trouble_codes_follow_the_policy() {
+    let xml = include_str!("../../test-fixtures/odx/dtc_interleaved.odx").replace(
+        "<TROUBLE-CODE>512</TROUBLE-CODE>",
+        "<TROUBLE-CODE>256</TROUBLE-CODE>",
+    );
+    let parse = |duplicates| {
+        diag_odx::parse_odx_with(
+            &xml,
+            &diag_odx::ParseOptions {
+                duplicates,
+                ..Default::default()
+            },
+        )
+    };
+    let names = |db: &DiagDatabase| -> Vec<String> {
+        db.dtcs.iter().map(|d| d.short_name.to_string()).collect()
+    };
+
+    assert_eq!(names(&parse(DuplicatePolicy::First).unwrap()), ["P0100"]);
+    assert_eq!(names(&parse(DuplicatePolicy::Last).unwrap()), ["P0200"]);
+    let merged = parse(DuplicatePolicy::MergeTexts).unwrap();
+    assert_eq!(
+        merged.dtcs[0].text.as_ref().unwrap().ti,
+        "Mass Air Flow Circuit Malfunction / Injector Circuit Malfunction"
+    );
+    let err = parse(DuplicatePolicy::Error).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("DTC 0x000100 is defined 2 times (P0100, P0200)"),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_parse_odx_desc_rich_text() {
+    let xml = include_str!("../../test-fixtures/odx/minimal.odx")
+        .replace(
+            "<LONG-NAME>Read Vehicle Speed</LONG-NAME>",
+            "<LONG-NAME>Read Vehicle Speed</LONG-NAME>\
+             <DESC><p>Reads the <b>current</b> speed.</p>\
+             <ul><li>unit: km/h</li><li>resolution: 0.01</li></ul></DESC>",
+        )
+        .replace(
+            "<LONG-NAME>Test ECU Base Variant</LONG-NAME>",
+            "<LONG-NAME>Test ECU Base Variant</LONG-NAME><DESC><p>Base layer</p></DESC>",
+        );
+    let db = parse_odx(&xml).unwrap();
+    let base = db.variants.iter().find(|v| v.is_base_variant).unwrap();
+    assert_eq!(base.diag_layer.description.as_deref(), Some("Base layer"));
+    let svc = base
+        .diag_layer
+        .diag_services
+        .iter()
+        .find(|s| s.diag_comm.short_name == "Read_VehicleSpeed")
+        .unwrap();
+    assert_eq!(
+        svc.diag_comm.description.as_deref(),
+        Some("Reads the current speed.\n\n- unit: km/h\n- resolution: 0.01")
+    );
+}
+
+fn minimal_with_response_extras(audience: &str) -> String {
+    include_str!("../../test-fixtures/odx/minimal.odx").replacen(
+        "</PARAMS>\n          </POS-RESPONSE>",
+        &format!(
+            "</PARAMS>\n            <POS-RESPONSE-SUFFIX>_PR</POS-RESPONSE-SUFFIX>\
+             <AUDIENCE {audience}/>\n          </POS-RESPONSE>"
+        ),
+        1,
+    )
+}
+
+fn read_speed_response(db: &DiagDatabase) -> &Response {
+    let base = db.variants.iter().find(|v| v.is_base_variant).unwrap();
+    let svc = base
+        .diag_layer
+        .diag_services
+        .iter()
+        .find(|s| s.diag_comm.short_name == "Read_VehicleSpeed")
+        .unwrap();
+    &svc.pos_responses[0]
+}
+
+#[test]
+fn test_parse_odx_response_suffix_and_audience() {
+    let xml = minimal_with_response_extras(r#"IS-SUPPLIER="1" IS-AFTERSALES="false""#);
+    let db = parse_odx(&xml).unwrap();
+    let resp = read_speed_response(&db);
+    assert_eq!(resp.pos_response_suffix.as_deref(), Some("_PR"));
+    let audience = resp.audience.as_ref().unwrap();
+    assert!(audience.is_supplier);
+    assert!(!audience.is_after_sales);
+}
+
+#[test]
+fn test_invalid_response_audience_is_lenient_only() {
+    let xml = minimal_with_response_extras(r#"IS-SUPPLIER="yes" IS-DEVELOPMENT="true""#);
+    let err = parse_odx(&xml).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid value 'yes' for AUDIENCE IS-SUPPLIER in PR_ReadSpeed"
+    );
+
+    let db = diag_odx::parse_odx_lenient(&xml).unwrap();
+    let audience = read_speed_response(&db).audience.as_ref().unwrap();
+    assert!(!audience.is_supplier);
+    assert!(audience.is_development);
+}
+
+#[test]
+fn test_parse_odx_type_attribute_prefixes() {
+    let original = include_str!("../../test-fixtures/odx/minimal.odx");
+    let expected = parse_odx(original).unwrap();
+    let variants = [
+        original
+            .replace("xmlns:xsi=", "xmlns:ns2=")
+            .replace("xsi:type=", "ns2:type="),
+        original.replace("xsi:type=", "type="),
+        original.replace("xsi:type=\"", "xsi:type=\"odx:"),
+    ];
+    for xml in &variants {
+        let db = parse_odx(xml).unwrap();
+        assert_eq!(db.variants, expected.variants);
+    }
+}
+
+#[test]
+fn test_parse_odx_registered_type_aliases() {
+    let xml = include_str!("../../test-fixtures/odx/minimal.odx")
+        .replace("xsi:type=\"VALUE\"", "xsi:type=\"VALUE-PARAM\"");
+    let options = diag_odx::ParseOptions {
+        type_aliases: vec![("VALUE-PARAM".into(), "VALUE".into())],
+        ..Default::default()
+    };
+    let with_alias = diag_odx::parse_odx_with(&xml, &options).unwrap();
+    assert_eq!(
+        with_alias.variants,
+        parse_odx(include_str!("../../test-fixtures/odx/minimal.odx"))
+            .unwrap()
+            .variants
+    );
+    let without = parse_odx(&xml).unwrap();
+    assert_ne!(without.variants, with_alias.variants);
+}
+
+#[test]
+fn test_parse_odx_latin1_and_utf16_inputs() {
+    let utf8 = include_str!("../../test-fixtures/odx/minimal.odx").replace(
+        "<LONG-NAME>Read Vehicle Speed</LONG-NAME>",
+        "<LONG-NAME>Geschwindigkeit lesen (km/h \u{b1}1)</LONG-NAME>",
+    );
+    let expected = parse_odx(&utf8).unwrap();
+
+    let latin1: Vec<u8> = utf8
+        .replace("encoding=\"UTF-8\"", "encoding=\"ISO-8859-1\"")
+        .chars()
+        .map(|c| u8::try_from(u32::from(c)).unwrap())
+        .collect();
+    let utf16: Vec<u8> = [0xFF, 0xFE]
+        .into_iter()
+        .chain(
+            utf8.replace("encoding=\"UTF-8\"", "encoding=\"UTF-16\"")
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        )
+        .collect();
+    for bytes in [latin1, utf16] {
+        let xml = diag_odx::decode_odx(&bytes).unwrap();
+        assert_eq!(parse_odx(&xml).unwrap().variants, expected.variants);
+    }
+}
+
+#[test]
+fn test_parse_odx_errors_locate_the_element() {
+    let xml = include_str!("../../test-fixtures/odx/minimal.odx").replacen(
+        "<BYTE-POSITION>0</BYTE-POSITION>",
+        "<BYTE-POSITION>x</BYTE-POSITION>",
+        1,
+    );
+    let err = parse_odx(&xml).unwrap_err();
+    assert_eq!(err.offset(), xml.find("<BYTE-POSITION>x"));
+    assert!(
+        err.to_string().contains(".PARAM[0].BYTE-POSITION)"),
+        "{err}"
+    );
+
+    let broken = xml.replacen("</SHORT-NAME>", "</SHORT>", 1);
+    let err = parse_odx(&broken).unwrap_err();
+    assert_eq!(err.offset(), broken.find("</SHORT>"));
+}
@@ -0,0 +1,113 @@
+use diag_ir::types::*;
+use diag_odx::{OdxProjectError, load_odx_project, parse_odx};
+use std::path::{Path, PathBuf};
+
+fn project_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../test-fixtures/odx/project")
+}
+
+fn voltage_dop(db: &DiagDatabase) -> &Dop {
+    let variant = db
+        .variants
+        .iter()
+        .find(|v| v.diag_layer.short_name == "SplitECU_HW1")
+        .expect("ECU variant");
+    let service = variant
+        .diag_layer
+        .diag_services
+        .iter()
+        .find(|s| s.diag_comm.short_name == "Read_Voltage")
+        .expect("Read_Voltage");
+    let param = service.pos_responses[0]
+        .params
+        .iter()
+        .find(|p| p.short_name == "Voltage")
+        .expect("Voltage param");
+    match &param.specific_data {
+        Some(ParamData::Value { dop, .. }) => dop,
+        other => panic!("expected a VALUE param, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_single_file_leaves_cross_file_refs_unresolved() {
+    let xml = std::fs::read_to_string(project_dir().join("variant.odx-d")).unwrap();
+    let db = parse_odx(&xml).unwrap();
+    assert_eq!(voltage_dop(&db).short_name, "");
+}
+
+#[test]
+fn test_project_resolves_refs_across_files() {
+    let db = load_odx_project(&[project_dir()]).unwrap();
+    assert_eq!(voltage_dop(&db).short_name, "Voltage");
+
+    // The ECU name comes from the first document with variants.
+    assert_eq!(db.ecu_name, "SplitECU");
+    let names: Vec<&str> = db
+        .variants
+        .iter()
+        .map(|v| v.diag_layer.short_name.as_str())
+        .collect();
+    assert_eq!(names, ["SplitECU_Base", "SplitECU_HW1"]);
+    assert_eq!(db.protocols.len(), 1);
+}
+
+#[test]
+fn test_project_inherits_from_layers_in_other_files() {
+    let db = load_odx_project(&[project_dir()]).unwrap();
+    let variant = db
+        .variants
+        .iter()
+        .find(|v| v.diag_layer.short_name == "SplitECU_HW1")
+        .unwrap();
+    assert_eq!(variant.parent_refs.len(), 1);
+    let tester_present = variant
+        .diag_layer
+        .diag_services
+        .iter()
+        .find(|s| s.diag_comm.short_name == "TesterPresent")
+        .expect("TesterPresent inherited via the base variant from the protocol");
+    let protocols: Vec<&str> = tester_present
+        .diag_comm
+        .protocols
+        .iter()
+        .map(|p| p.diag_layer.short_name.as_str())
+        .collect();
+    assert_eq!(protocols, ["UDS_on_CAN"]);
+}
+
+#[test]
+fn test_project_file_order_does_not_matter() {
+    let dir = project_dir();
+    let forward = load_odx_project(&[dir.join("base.odx-d"), dir.join("variant.odx-d")]).unwrap();
+    let backward = load_odx_project(&[dir.join("variant.odx-d"), dir.join("base.odx-d")]).unwrap();
+    assert_eq!(voltage_dop(&backward), voltage_dop(&forward));
+}
+
+#[test]
+fn test_project_without_odx_files() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("notes.txt"), "not ODX").unwrap();
+    let err = load_odx_project(&[dir.path().to_path_buf()]).unwrap_err();
+    assert!(matches!(err, OdxProjectError::NoOdxFiles));
+}
+
+#[test]
+fn test_project_reports_the_broken_file() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::copy(
+        project_dir().join("base.odx-d"),
+        dir.path().join("base.odx-d"),
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("broken.odx-d"),
+        "<ODX><DIAG-LAYER-CONTAINER>",
+    )
+    .unwrap();
+    let err = load_odx_project(&[dir.path().to_path_buf()]).unwrap_err();
+    match err {
+        OdxProjectError::OdxParse { file, .. } => assert!(file.ends_with("broken.odx-d")),
+        other => panic!("expected a parse error, got {other}"),
+    }
+}
@@ -1,4 +1,6 @@
-use diag_odx::{parse_odx, write_odx};
+use diag_odx::{
+    OdxVersion, OdxWriteOptions, parse_odx, write_odx, write_odx_pretty, write_odx_with,
+};
 
 #[test]
 fn test_odx_roundtrip_preserves_ecu_name() {
@@ -183,6 +185,43 @@ fn test_odx_roundtrip_preserves_comparam_refs() {
     );
 }
 
+#[test]
+fn test_odx_2_0_dialect_roundtrip() {
+    let xml = include_str!("../../test-fixtures/odx/minimal.odx");
+    let original = parse_odx(xml).unwrap();
+    let options = OdxWriteOptions {
+        pretty: true,
+        version: Some(OdxVersion::V2_0),
+    };
+    let odx_2_0 = write_odx_with(&original, &options).unwrap();
+    assert!(odx_2_0.contains(r#"<ODX VERSION="2.0.1""#), "{odx_2_0}");
+    assert!(odx_2_0.contains("<VALUE>500000</VALUE>"));
+    assert!(!odx_2_0.contains("SIMPLE-VALUE"));
+    assert_eq!(OdxVersion::of_document(&odx_2_0), Some(OdxVersion::V2_0));
+
+    let reparsed = parse_odx(&odx_2_0).unwrap();
+    assert_eq!(reparsed.version, "2.0.1");
+    let values = |db: &diag_ir::DiagDatabase| -> Vec<Option<String>> {
+        db.variants
+            .iter()
+            .flat_map(|v| &v.diag_layer.com_param_refs)
+            .map(|r| r.simple_value.as_ref().map(|sv| sv.value.clone()))
+            .collect()
+    };
+    assert_eq!(values(&reparsed), values(&original));
+
+    let options = OdxWriteOptions {
+        version: Some(OdxVersion::V2_3),
+        ..Default::default()
+    };
+    let odx_2_3 = write_odx_with(&original, &options).unwrap();
+    assert!(
+        odx_2_3.contains(r#"<ODX MODEL-VERSION="2.3.0""#),
+        "{odx_2_3}"
+    );
+    assert_eq!(values(&parse_odx(&odx_2_3).unwrap()), values(&original));
+}
+
 #[test]
 fn test_odx_roundtrip_preserves_audience_refs() {
     let xml = include_str!("../../test-fixtures/odx/minimal.odx");
@@ -395,3 +434,202 @@ fn test_odx_roundtrip_preserves_ecu_shared_data() {
         );
     }
 }
+
+#[test]
+fn test_odx_roundtrip_preserves_descriptions() {
+    let xml = include_str!("../../test-fixtures/odx/minimal.odx");
+    let mut original = parse_odx(xml).unwrap();
+    let base = original
+        .variants
+        .iter_mut()
+        .find(|v| v.is_base_variant)
+        .unwrap();
+    base.diag_layer.description = Some("Base layer".into());
+    base.diag_layer.diag_services[0].diag_comm.description =
+        Some("Reads the speed.\n\n1. request\n2. response".into());
+
+    let odx_output = write_odx(&original).unwrap();
+    assert!(odx_output.contains("<DESC><p>Base layer</p></DESC>"));
+    let reparsed = parse_odx(&odx_output).unwrap();
+    let base = reparsed
+        .variants
+        .iter()
+        .find(|v| v.is_base_variant)
+        .unwrap();
+    assert_eq!(base.diag_layer.description.as_deref(), Some("Base layer"));
+    assert_eq!(
+        base.diag_layer.diag_services[0]
+            .diag_comm
+            .description
+            .as_deref(),
+        Some("Reads the speed.\n\n1. request\n2. response")
+    );
+}
+
+#[test]
+fn test_pretty_odx_roundtrips_like_compact() {
+    let xml = include_str!("../../test-fixtures/odx/minimal.odx");
+    let mut db = parse_odx(xml).unwrap();
+    db.variants[0].diag_layer.description = Some("First.\n\n- a\n- b".into());
+
+    let pretty = write_odx_pretty(&db).unwrap();
+    assert!(pretty.contains("\n  <"), "expected indented output");
+    assert_eq!(
+        parse_odx(&pretty).unwrap(),
+        parse_odx(&write_odx(&db).unwrap()).unwrap()
+    );
+}
+
+#[test]
+fn test_odx_roundtrip_preserves_response_suffix_and_audience() {
+    let xml = include_str!("../../test-fixtures/odx/minimal.odx");
+    let mut original = parse_odx(xml).unwrap();
+    let base = original
+        .variants
+        .iter_mut()
+        .find(|v| v.is_base_variant)
+        .unwrap();
+    let resp = &mut base.diag_layer.diag_services[0].pos_responses[0];
+    resp.pos_response_suffix = Some("_PR".into());
+    resp.audience = Some(diag_ir::Audience {
+        enabled_audiences: vec![],
+        disabled_audiences: vec![],
+        is_supplier: true,
+        is_development: false,
+        is_manufacturing: false,
+        is_after_sales: true,
+        is_after_market: false,
+    });
+
+    let odx_output = write_odx(&original).unwrap();
+    let reparsed = parse_odx(&odx_output).unwrap();
+    let base = reparsed
+        .variants
+        .iter()
+        .find(|v| v.is_base_variant)
+        .unwrap();
+    let resp = &base.diag_layer.diag_services[0].pos_responses[0];
+    assert_eq!(resp.pos_response_suffix.as_deref(), Some("_PR"));
+    let audience = resp.audience.as_ref().unwrap();
+    assert!(audience.is_supplier && audience.is_after_sales);
+    assert!(!audience.is_development);
+}
+
+#[test]
+fn test_odx_roundtrip_preserves_unknown_xml() {
+    let xml = include_str!("../../test-fixtures/odx/minimal.odx")
+        .replace(
+            "<SHORT-NAME>TestECU_Base</SHORT-NAME>",
+            "<SHORT-NAME>TestECU_Base</SHORT-NAME><VENDOR-INFO><TOOL>X &amp; Y</TOOL></VENDOR-INFO>",
+        )
+        .replace(
+            r#"<DIAG-SERVICE ID="DS_ReadSpeed" SEMANTIC="DATA-READ">"#,
+            r#"<DIAG-SERVICE ID="DS_ReadSpeed" SEMANTIC="DATA-READ" VENDOR-PRIO="2">"#,
+        );
+    let options = diag_odx::ParseOptions {
+        preserve_unknown_xml: true,
+        ..Default::default()
+    };
+    let db = diag_odx::parse_odx_with(&xml, &options).unwrap();
+
+    let odx_output = write_odx(&db).unwrap();
+    assert!(
+        odx_output.contains("<VENDOR-INFO><TOOL>X &amp; Y</TOOL></VENDOR-INFO></BASE-VARIANT>")
+    );
+    assert!(odx_output.contains(r#"SEMANTIC="DATA-READ" VENDOR-PRIO="2">"#));
+    assert!(!odx_output.contains(diag_odx::unknown_xml::UNKNOWN_XML_CAPTION));
+
+    let reparsed = diag_odx::parse_odx_with(&odx_output, &options).unwrap();
+    let base = |db: &diag_ir::DiagDatabase| {
+        let layer = &db
+            .variants
+            .iter()
+            .find(|v| v.is_base_variant)
+            .unwrap()
+            .diag_layer;
+        (
+            layer.sdgs.clone(),
+            layer.diag_services[0].diag_comm.sdgs.clone(),
+        )
+    };
+    assert_eq!(base(&reparsed), base(&db));
+
+    // Without the option, vendor content is dropped as before.
+    let plain = parse_odx(&xml).unwrap();
+    assert!(!write_odx(&plain).unwrap().contains("VENDOR"));
+}
+
+#[test]
+fn test_odx_roundtrip_preserves_type_definitions() {
+    let xml = include_str!("../../test-fixtures/odx/minimal.odx");
+    let mut original = parse_odx(xml).unwrap();
+    original.type_definitions = vec![
+        diag_ir::TypeDefinition {
+            name: "VehicleSpeed".into(),
+            base: "u16".into(),
+            bit_length: Some(16),
+            ..Default::default()
+        },
+        diag_ir::TypeDefinition {
+            name: "EngineState".into(),
+            base: "u8".into(),
+            enum_values_json: Some(r#"{"OFF":0,"RUNNING":1}"#.into()),
+            ..Default::default()
+        },
+    ];
+    let odx_output = write_odx(&original).unwrap();
+    let reparsed = parse_odx(&odx_output).unwrap();
+
+    assert_eq!(reparsed.type_definitions, original.type_definitions);
+}
+
+#[test]
+fn test_odx_roundtrip_preserves_transport() {
+    let xml = include_str!("../../test-fixtures/odx/minimal.odx");
+    let mut original = parse_odx(xml).unwrap();
+    original.transport = Some(diag_ir::TransportConfig {
+        doip: Some(diag_ir::DoipConfig {
+            ip: Some("192.168.0.10".into()),
+            port: Some(13400),
+            logical_address: Some(0x1000),
+            functional_address: Some(0xFFFF),
+            eid: Some([0x00, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E]),
+            ..Default::default()
+        }),
+    });
+    let odx_output = write_odx(&original).unwrap();
+    assert!(odx_output.contains(r#"GID="transport""#), "{odx_output}");
+    let reparsed = parse_odx(&odx_output).unwrap();
+
+    assert_eq!(reparsed.transport, original.transport);
+}
+
+#[test]
+fn test_odx_roundtrip_preserves_identification() {
+    let xml = include_str!("../../test-fixtures/odx/minimal.odx");
+    let mut original = parse_odx(xml).unwrap();
+    original.identification = Some(diag_ir::EcuIdentification {
+        part_number: Some("8W0907115A".into()),
+        supplier_code: Some("BOSCH".into()),
+        software_version: Some("0042".into()),
+        dids: [("vin".to_string(), 0xF190)].into(),
+        ..Default::default()
+    });
+    let odx_output = write_odx(&original).unwrap();
+    assert!(
+        odx_output.contains(r#"GID="ecu_identification""#),
+        "{odx_output}"
+    );
+    let reparsed = parse_odx(&odx_output).unwrap();
+
+    assert_eq!(reparsed.identification, original.identification);
+    // Stored on the container, not as a hidden SDG of a layer.
+    let captions: Vec<_> = reparsed
+        .variants
+        .iter()
+        .filter_map(|v| v.diag_layer.sdgs.as_ref())
+        .flat_map(|s| &s.sdgs)
+        .map(|sdg| sdg.caption_sn.as_str())
+        .collect();
+    assert!(!captions.contains(&"ecu_identification"), "{captions:?}");
+}
@@ -0,0 +1,81 @@
+use diag_odx::pdx_reader::read_pdx_from_reader;
+use diag_odx::pdx_writer::{PDX_INDEX, write_pdx_to_writer};
+use diag_odx::{PdxFile, PdxWriteError, PdxWriteOptions, parse_odx, write_pdx_file};
+use std::io::{Cursor, Read};
+
+fn minimal_db() -> diag_ir::DiagDatabase {
+    parse_odx(include_str!("../../test-fixtures/odx/minimal.odx")).unwrap()
+}
+
+fn entry(bytes: &[u8], name: &str) -> String {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let mut content = String::new();
+    archive
+        .by_name(name)
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    content
+}
+
+#[test]
+fn test_pdx_roundtrip() {
+    let db = minimal_db();
+    let bytes = write_pdx_to_writer(&db, Cursor::new(Vec::new()), &PdxWriteOptions::default())
+        .unwrap()
+        .into_inner();
+
+    let reread = read_pdx_from_reader(Cursor::new(bytes.as_slice())).unwrap();
+    assert_eq!(reread.ecu_name, db.ecu_name);
+    assert_eq!(reread.variants.len(), db.variants.len());
+
+    let index = entry(&bytes, PDX_INDEX);
+    assert!(index.contains("<CATALOG "));
+    assert!(index.contains(&format!(
+        "<FILE MIME-TYPE=\"application/x-asam.odx.odx-d\">{}.odx-d</FILE>",
+        db.ecu_name
+    )));
+}
+
+#[test]
+fn test_pdx_packs_extra_files() {
+    let options = PdxWriteOptions {
+        pretty: true,
+        version: None,
+        extra_files: vec![PdxFile {
+            name: "jobs/FlashJob.jar".into(),
+            data: b"PK\x03\x04jar".to_vec(),
+        }],
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.pdx");
+    write_pdx_file(&minimal_db(), &path, &options).unwrap();
+
+    let bytes = std::fs::read(&path).unwrap();
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes.as_slice())).unwrap();
+    let mut jar = Vec::new();
+    archive
+        .by_name("jobs/FlashJob.jar")
+        .unwrap()
+        .read_to_end(&mut jar)
+        .unwrap();
+    assert_eq!(jar, b"PK\x03\x04jar");
+
+    let index = entry(&bytes, PDX_INDEX);
+    assert!(index.contains("<CATEGORY>JAVA-JAR</CATEGORY>"));
+    assert!(index.contains(">jobs/FlashJob.jar</FILE>"));
+}
+
+#[test]
+fn test_pdx_rejects_duplicate_entries() {
+    let options = PdxWriteOptions {
+        pretty: false,
+        version: None,
+        extra_files: vec![PdxFile {
+            name: PDX_INDEX.into(),
+            data: vec![],
+        }],
+    };
+    let err = write_pdx_to_writer(&minimal_db(), Cursor::new(Vec::new()), &options).unwrap_err();
+    assert!(matches!(err, PdxWriteError::DuplicateEntry(ref name) if name == PDX_INDEX));
+}
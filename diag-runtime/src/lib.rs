@@ -0,0 +1,15 @@
+//! Runtime codec for UDS messages described by a diagnostic database.
+//!
+//! [`decode_request`], [`decode_response`] and [`decode_message`] turn a raw
+//! payload into named parameters with physical values, applying the DOPs and
+//! compu methods of the IR; [`encode_request`] and [`encode_params`] go the
+//! other way. Structures, static, dynamic-length and END-OF-PDU fields and
+//! multiplexers nest: their values are [`Value::Struct`], [`Value::Array`]
+//! and [`Value::Mux`]. Table params and environment data are not supported.
+//!
+//! [`variant_detect`] identifies the ECU variant with the same codec. Both
+//! are implemented in diag-ir ([`diag_ir::codec`]), whose variant detection
+//! needs the codec; this crate is the tester-facing entry point to them.
+
+pub use diag_ir::codec::*;
+pub use diag_ir::variant_detect;
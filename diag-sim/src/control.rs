@@ -0,0 +1,152 @@
+//! Line-based control protocol for injecting faults at runtime.
+//!
+//! Each line is one command; each reply is one line (`list` replies with one
+//! line per active DTC followed by `ok`):
+//!
+//! | Command                  | Effect                                         |
+//! |--------------------------|------------------------------------------------|
+//! | `inject <dtc> [status]`  | set status bits (default `0x09`)               |
+//! | `clear [<dtc>]`          | reset one DTC, or all                          |
+//! | `list`                   | `<code> <status>` for every active DTC         |
+//!
+//! `<dtc>` is a DTC short name or its code (`0x012300`).
+
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+
+use crate::{DEFAULT_FAULT_STATUS, FaultMemory, SimError};
+
+/// Run one control command against the fault memory and return the reply.
+pub fn execute_command(store: &Mutex<FaultMemory>, line: &str) -> String {
+    let mut store = store.lock().unwrap_or_else(PoisonError::into_inner);
+    match run(&mut store, line) {
+        Ok(reply) => reply,
+        Err(e) => format!("error: {e}"),
+    }
+}
+
+fn run(store: &mut FaultMemory, line: &str) -> Result<String, SimError> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        ["inject", dtc, rest @ ..] if rest.len() <= 1 => {
+            let code = store.resolve(dtc)?;
+            let status = rest
+                .first()
+                .map_or(Ok(DEFAULT_FAULT_STATUS), |s| parse_status(s))?;
+            store.inject(code, status)?;
+            Ok("ok".into())
+        }
+        ["clear"] => {
+            store.clear(None)?;
+            Ok("ok".into())
+        }
+        ["clear", dtc] => {
+            let code = store.resolve(dtc)?;
+            store.clear(Some(code))?;
+            Ok("ok".into())
+        }
+        ["list"] => {
+            let mut reply = String::new();
+            for (code, status) in store.active() {
+                let _ = writeln!(reply, "0x{code:06X} 0x{status:02X}");
+            }
+            reply.push_str("ok");
+            Ok(reply)
+        }
+        _ => Err(SimError::InvalidValue(format!("unknown command '{line}'"))),
+    }
+}
+
+fn parse_status(s: &str) -> Result<u8, SimError> {
+    diag_ir::parse_uint(s)
+        .and_then(|v| u8::try_from(v).ok())
+        .ok_or_else(|| SimError::InvalidValue(format!("status byte '{s}'")))
+}
+
+/// Serve the control protocol on `listener`, one thread per connection.
+pub fn spawn_control_server(
+    listener: TcpListener,
+    store: Arc<Mutex<FaultMemory>>,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let store = Arc::clone(&store);
+                    std::thread::spawn(move || {
+                        if let Err(e) = serve_connection(stream, &store) {
+                            log::warn!("control connection closed: {e}");
+                        }
+                    });
+                }
+                Err(e) => log::warn!("control connection failed: {e}"),
+            }
+        }
+    })
+}
+
+fn serve_connection(stream: TcpStream, store: &Mutex<FaultMemory>) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        writeln!(writer, "{}", execute_command(store, &line))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diag_ir::{DiagDatabase, Dtc};
+
+    fn store() -> Arc<Mutex<FaultMemory>> {
+        Arc::new(Mutex::new(FaultMemory::from_database(&DiagDatabase {
+            dtcs: vec![Dtc {
+                short_name: "P0123".into(),
+                trouble_code: 0x012300,
+                ..Default::default()
+            }],
+            ..Default::default()
+        })))
+    }
+
+    #[test]
+    fn test_commands() {
+        let store = store();
+        assert_eq!(execute_command(&store, "inject P0123"), "ok");
+        assert_eq!(execute_command(&store, "list"), "0x012300 0x09\nok");
+        assert_eq!(execute_command(&store, "clear 0x012300"), "ok");
+        assert_eq!(execute_command(&store, "list"), "ok");
+        assert_eq!(
+            execute_command(&store, "inject P9999"),
+            "error: unknown DTC: P9999"
+        );
+        assert!(execute_command(&store, "inject P0123 0x1FF").starts_with("error"));
+        assert!(execute_command(&store, "reboot").starts_with("error"));
+    }
+
+    #[test]
+    fn test_control_server_injects_into_shared_store() {
+        let store = store();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        spawn_control_server(listener, Arc::clone(&store));
+
+        let mut conn = TcpStream::connect(addr).unwrap();
+        writeln!(conn, "inject P0123 0x01").unwrap();
+        let mut reply = String::new();
+        BufReader::new(conn.try_clone().unwrap())
+            .read_line(&mut reply)
+            .unwrap();
+        assert_eq!(reply, "ok\n");
+
+        let response = store.lock().unwrap().handle(&[0x19, 0x02, 0x01]).unwrap();
+        assert_eq!(response, [0x59, 0x02, 0xFF, 0x01, 0x23, 0x00, 0x01]);
+    }
+}
@@ -0,0 +1,235 @@
+//! Mutable DTC store answering ClearDiagnosticInformation (0x14) and
+//! ReadDTCInformation (0x19).
+
+use std::collections::BTreeMap;
+
+use diag_ir::DiagDatabase;
+
+use crate::{
+    NRC_INCORRECT_LENGTH, NRC_REQUEST_OUT_OF_RANGE, NRC_SUBFUNCTION_NOT_SUPPORTED, SimError,
+    negative_response,
+};
+
+/// Status of an injected fault: testFailed | confirmedDTC.
+pub const DEFAULT_FAULT_STATUS: u8 = 0x09;
+
+/// `groupOfDTC` addressing all DTCs in a 0x14 request.
+const ALL_DTCS: u32 = 0xFF_FFFF;
+
+/// The ECU's fault memory: every DTC the database defines, with its current
+/// status byte. DTCs start with status 0 (no fault).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaultMemory {
+    status: BTreeMap<u32, u8>,
+    names: BTreeMap<String, u32>,
+    availability_mask: u8,
+}
+
+impl FaultMemory {
+    /// Seed the store with the DTCs of `db`.
+    pub fn from_database(db: &DiagDatabase) -> Self {
+        FaultMemory {
+            status: db.dtcs.iter().map(|dtc| (dtc.trouble_code, 0)).collect(),
+            names: db
+                .dtcs
+                .iter()
+                .map(|dtc| (dtc.short_name.to_string(), dtc.trouble_code))
+                .collect(),
+            availability_mask: 0xFF,
+        }
+    }
+
+    /// Status bits the ECU supports; reported status bytes are masked by it.
+    pub fn with_availability_mask(mut self, mask: u8) -> Self {
+        self.availability_mask = mask;
+        self
+    }
+
+    /// Resolve a DTC given by short name, or as a decimal or `0x` hex code.
+    pub fn resolve(&self, dtc: &str) -> Result<u32, SimError> {
+        let code = self
+            .names
+            .get(dtc)
+            .copied()
+            .or_else(|| diag_ir::parse_uint(dtc).and_then(|v| u32::try_from(v).ok()));
+        code.filter(|code| self.status.contains_key(code))
+            .ok_or_else(|| SimError::UnknownDtc(dtc.to_string()))
+    }
+
+    /// Current status byte of a DTC.
+    pub fn status(&self, code: u32) -> Option<u8> {
+        self.status.get(&code).map(|s| s & self.availability_mask)
+    }
+
+    /// Set status bits of a DTC, as if a monitor had reported a fault.
+    pub fn inject(&mut self, code: u32, status: u8) -> Result<(), SimError> {
+        let entry = self
+            .status
+            .get_mut(&code)
+            .ok_or_else(|| SimError::UnknownDtc(format!("0x{code:06X}")))?;
+        *entry |= status;
+        Ok(())
+    }
+
+    /// Reset the status of one DTC, or of all DTCs when `code` is `None`.
+    pub fn clear(&mut self, code: Option<u32>) -> Result<(), SimError> {
+        match code {
+            Some(code) => {
+                let entry = self
+                    .status
+                    .get_mut(&code)
+                    .ok_or_else(|| SimError::UnknownDtc(format!("0x{code:06X}")))?;
+                *entry = 0;
+            }
+            None => self.status.values_mut().for_each(|s| *s = 0),
+        }
+        Ok(())
+    }
+
+    /// DTCs with a non-zero status, in code order.
+    pub fn active(&self) -> Vec<(u32, u8)> {
+        self.matching(0xFF)
+    }
+
+    fn matching(&self, mask: u8) -> Vec<(u32, u8)> {
+        self.status
+            .keys()
+            .filter_map(|&code| {
+                let status = self.status(code)?;
+                (status & mask != 0).then_some((code, status))
+            })
+            .collect()
+    }
+
+    /// Answer a 0x14 or 0x19 request. Returns `None` for other services.
+    pub fn handle(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        match request.first()? {
+            0x14 => Some(self.clear_diagnostic_information(request)),
+            0x19 => Some(self.read_dtc_information(request)),
+            _ => None,
+        }
+    }
+
+    fn clear_diagnostic_information(&mut self, request: &[u8]) -> Vec<u8> {
+        let [_, a, b, c] = request else {
+            return negative_response(0x14, NRC_INCORRECT_LENGTH);
+        };
+        let group = u32::from_be_bytes([0, *a, *b, *c]);
+        let code = (group != ALL_DTCS).then_some(group);
+        match self.clear(code) {
+            Ok(()) => vec![0x54],
+            Err(_) => negative_response(0x14, NRC_REQUEST_OUT_OF_RANGE),
+        }
+    }
+
+    fn read_dtc_information(&self, request: &[u8]) -> Vec<u8> {
+        let Some(&subfunction) = request.get(1) else {
+            return negative_response(0x19, NRC_INCORRECT_LENGTH);
+        };
+        let mut response = vec![0x59, subfunction, self.availability_mask];
+        match (subfunction, &request[2..]) {
+            // reportNumberOfDTCByStatusMask, format ISO 14229-1
+            (0x01, [mask]) => {
+                let count = self.matching(*mask).len() as u16;
+                response.push(0x01);
+                response.extend(count.to_be_bytes());
+            }
+            // reportDTCByStatusMask
+            (0x02, [mask]) => push_records(&mut response, &self.matching(*mask)),
+            // reportSupportedDTC
+            (0x0A, []) => {
+                let all: Vec<(u32, u8)> = self
+                    .status
+                    .keys()
+                    .map(|&code| (code, self.status(code).unwrap_or_default()))
+                    .collect();
+                push_records(&mut response, &all);
+            }
+            (0x01 | 0x02 | 0x0A, _) => return negative_response(0x19, NRC_INCORRECT_LENGTH),
+            _ => return negative_response(0x19, NRC_SUBFUNCTION_NOT_SUPPORTED),
+        }
+        response
+    }
+}
+
+fn push_records(response: &mut Vec<u8>, records: &[(u32, u8)]) {
+    for (code, status) in records {
+        response.extend(&code.to_be_bytes()[1..]);
+        response.push(*status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diag_ir::Dtc;
+
+    fn store() -> FaultMemory {
+        let dtc = |name: &str, code: u32| Dtc {
+            short_name: name.into(),
+            trouble_code: code,
+            ..Default::default()
+        };
+        FaultMemory::from_database(&DiagDatabase {
+            dtcs: vec![dtc("P0123", 0x012300), dtc("U0100", 0xC10000)],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_read_dtc_by_status_mask_reflects_injected_faults() {
+        let mut fm = store();
+        assert_eq!(fm.handle(&[0x19, 0x02, 0xFF]), Some(vec![0x59, 0x02, 0xFF]));
+
+        let code = fm.resolve("P0123").unwrap();
+        fm.inject(code, DEFAULT_FAULT_STATUS).unwrap();
+        assert_eq!(
+            fm.handle(&[0x19, 0x02, 0x08]),
+            Some(vec![0x59, 0x02, 0xFF, 0x01, 0x23, 0x00, 0x09])
+        );
+        assert_eq!(
+            fm.handle(&[0x19, 0x01, 0x01]),
+            Some(vec![0x59, 0x01, 0xFF, 0x01, 0x00, 0x01])
+        );
+        assert_eq!(
+            fm.handle(&[0x19, 0x0A]).unwrap().len(),
+            3 + 2 * 4,
+            "supported DTCs lists every DTC"
+        );
+    }
+
+    #[test]
+    fn test_clear_diagnostic_information() {
+        let mut fm = store();
+        fm.inject(0x012300, 0x09).unwrap();
+        fm.inject(0xC10000, 0x01).unwrap();
+
+        assert_eq!(fm.handle(&[0x14, 0x01, 0x23, 0x00]), Some(vec![0x54]));
+        assert_eq!(fm.active(), [(0xC10000, 0x01)]);
+        assert_eq!(fm.handle(&[0x14, 0xFF, 0xFF, 0xFF]), Some(vec![0x54]));
+        assert!(fm.active().is_empty());
+
+        assert_eq!(
+            fm.handle(&[0x14, 0x00, 0x00, 0x01]),
+            Some(vec![0x7F, 0x14, NRC_REQUEST_OUT_OF_RANGE])
+        );
+        assert_eq!(
+            fm.handle(&[0x14, 0xFF]),
+            Some(vec![0x7F, 0x14, NRC_INCORRECT_LENGTH])
+        );
+    }
+
+    #[test]
+    fn test_unsupported_requests() {
+        let mut fm = store().with_availability_mask(0x09);
+        fm.inject(0x012300, 0xFF).unwrap();
+        assert_eq!(fm.status(0x012300), Some(0x09));
+        assert_eq!(
+            fm.handle(&[0x19, 0x06, 0x01, 0x23, 0x00, 0xFF]),
+            Some(vec![0x7F, 0x19, NRC_SUBFUNCTION_NOT_SUPPORTED])
+        );
+        assert_eq!(fm.handle(&[0x22, 0xF1, 0x90]), None);
+        assert!(fm.inject(0x999999, 0x01).is_err());
+        assert!(fm.resolve("P9999").is_err());
+    }
+}
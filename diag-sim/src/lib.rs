@@ -0,0 +1,39 @@
+//! Building blocks for a mock ECU that answers UDS requests from a diagnostic
+//! database, so tester software can be integration-tested without a bench.
+
+pub mod control;
+pub mod fault_memory;
+pub mod matcher;
+pub mod profile;
+pub mod server;
+
+pub use control::{execute_command, spawn_control_server};
+pub use fault_memory::{DEFAULT_FAULT_STATUS, FaultMemory};
+pub use matcher::match_service;
+pub use profile::{Frame, Latency, NrcInjection, ServiceProfile, SimProfile, SimRng};
+pub use server::serve;
+
+/// Errors raised by the simulator.
+#[derive(Debug, thiserror::Error)]
+pub enum SimError {
+    #[error("unknown DTC: {0}")]
+    UnknownDtc(String),
+    #[error("invalid value: {0}")]
+    InvalidValue(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("transport error: {0}")]
+    Transport(#[from] diag_transport::TransportError),
+}
+
+/// Build a negative response (`7F <sid> <nrc>`).
+pub fn negative_response(sid: u8, nrc: u8) -> Vec<u8> {
+    vec![0x7F, sid, nrc]
+}
+
+/// NRC 0x12 subFunctionNotSupported.
+pub const NRC_SUBFUNCTION_NOT_SUPPORTED: u8 = 0x12;
+/// NRC 0x13 incorrectMessageLengthOrInvalidFormat.
+pub const NRC_INCORRECT_LENGTH: u8 = 0x13;
+/// NRC 0x31 requestOutOfRange.
+pub const NRC_REQUEST_OUT_OF_RANGE: u8 = 0x31;
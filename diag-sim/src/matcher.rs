@@ -0,0 +1,104 @@
+//! Map a raw request to the database service it addresses.
+
+use diag_ir::{DiagCodedTypeData, DiagService, ParamData, parse_uint};
+
+/// The service whose byte-aligned CODED-CONST request params all match
+/// `request`; when several match, the one matching the most bytes wins
+/// (e.g. a DID read over the bare 0x22 service).
+pub fn match_service<'a>(services: &'a [DiagService], request: &[u8]) -> Option<&'a DiagService> {
+    services
+        .iter()
+        .filter_map(|svc| {
+            let consts = coded_consts(svc)?;
+            let matches = consts.iter().all(|(pos, bytes)| {
+                request
+                    .get(*pos..*pos + bytes.len())
+                    .is_some_and(|actual| actual == bytes.as_slice())
+            });
+            let matched: usize = consts.iter().map(|(_, bytes)| bytes.len()).sum();
+            (matches && matched > 0).then_some((matched, svc))
+        })
+        .max_by_key(|(matched, _)| *matched)
+        .map(|(_, svc)| svc)
+}
+
+/// `(byte position, big-endian bytes)` of every byte-aligned CODED-CONST
+/// request param, or `None` if the service has no request.
+fn coded_consts(svc: &DiagService) -> Option<Vec<(usize, Vec<u8>)>> {
+    let params = &svc.request.as_ref()?.params;
+    Some(
+        params
+            .iter()
+            .filter(|p| p.bit_position.unwrap_or(0) == 0)
+            .filter_map(|p| {
+                let Some(ParamData::CodedConst {
+                    coded_value,
+                    diag_coded_type,
+                }) = &p.specific_data
+                else {
+                    return None;
+                };
+                let Some(DiagCodedTypeData::StandardLength { bit_length, .. }) =
+                    &diag_coded_type.specific_data
+                else {
+                    return None;
+                };
+                let len = (*bit_length as usize / 8).clamp(1, 8);
+                let value = parse_uint(coded_value)?;
+                let bytes = value.to_be_bytes()[8 - len..].to_vec();
+                Some((p.byte_position? as usize, bytes))
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diag_ir::{DiagCodedType, DiagComm, Param, Request};
+
+    fn coded(byte: u32, bits: u32, value: &str) -> Param {
+        Param {
+            byte_position: Some(byte),
+            specific_data: Some(ParamData::CodedConst {
+                coded_value: value.into(),
+                diag_coded_type: DiagCodedType {
+                    specific_data: Some(DiagCodedTypeData::StandardLength {
+                        bit_length: bits,
+                        bit_mask: vec![],
+                        condensed: false,
+                    }),
+                    ..Default::default()
+                },
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn service(name: &str, params: Vec<Param>) -> DiagService {
+        DiagService {
+            diag_comm: DiagComm {
+                short_name: name.into(),
+                ..Default::default()
+            },
+            request: Some(Request { params, sdgs: None }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_most_specific_service_wins() {
+        let services = vec![
+            service("ReadDataByIdentifier", vec![coded(0, 8, "34")]),
+            service("VIN_Read", vec![coded(0, 8, "34"), coded(1, 16, "0xF190")]),
+            service("HardReset", vec![coded(0, 8, "17"), coded(1, 8, "1")]),
+        ];
+        let name =
+            |req: &[u8]| match_service(&services, req).map(|s| s.diag_comm.short_name.as_str());
+        assert_eq!(name(&[0x22, 0xF1, 0x90]), Some("VIN_Read"));
+        assert_eq!(name(&[0x22, 0xF1, 0x8C]), Some("ReadDataByIdentifier"));
+        assert_eq!(name(&[0x11, 0x01]), Some("HardReset"));
+        assert_eq!(name(&[0x11, 0x03]), None);
+        assert_eq!(name(&[0x22]), Some("ReadDataByIdentifier"));
+    }
+}
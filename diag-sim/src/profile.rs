@@ -0,0 +1,310 @@
+//! Simulator profiles: per-service response latency, busy-repeat-request
+//! (NRC 0x78) sequences and probabilistic negative responses, for robustness
+//! testing of tester implementations.
+//!
+//! ```yaml
+//! seed: 42                      # optional, makes runs reproducible
+//! default:
+//!   latency: { distribution: fixed, ms: 5 }
+//! services:
+//!   RequestDownload:
+//!     latency: { distribution: uniform, min_ms: 20, max_ms: 80 }
+//!     response_pending: 2       # two 7F 34 78 frames before the response
+//!     pending_interval_ms: 100
+//!     negative_responses:
+//!       - { nrc: 0x22, probability: 0.05 }
+//! ```
+//!
+//! A service entry replaces the `default` entry as a whole. Entries may name
+//! a service by a former name (`aliases` in YAML) and in any case.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use diag_ir::{DiagComm, name_match};
+use serde::Deserialize;
+
+use crate::{SimError, negative_response};
+
+pub use diag_transport::NRC_RESPONSE_PENDING;
+
+const DEFAULT_PENDING_INTERVAL_MS: u64 = 50;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimProfile {
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub default: ServiceProfile,
+    /// Profiles by service short name.
+    #[serde(default)]
+    pub services: BTreeMap<String, ServiceProfile>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceProfile {
+    #[serde(default)]
+    pub latency: Option<Latency>,
+    /// Number of NRC 0x78 frames sent before the final response.
+    #[serde(default)]
+    pub response_pending: u32,
+    /// Delay before each NRC 0x78 frame (default 50 ms).
+    #[serde(default)]
+    pub pending_interval_ms: Option<u64>,
+    /// Checked in order; the first hit replaces the final response.
+    #[serde(default)]
+    pub negative_responses: Vec<NrcInjection>,
+}
+
+/// Delay before the final response.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "distribution", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Latency {
+    Fixed { ms: u64 },
+    Uniform { min_ms: u64, max_ms: u64 },
+    Normal { mean_ms: f64, std_dev_ms: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NrcInjection {
+    pub nrc: u8,
+    pub probability: f64,
+}
+
+/// One frame the simulator sends, after waiting `delay`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub delay: Duration,
+    pub data: Vec<u8>,
+}
+
+impl SimProfile {
+    /// Parse and validate a profile file.
+    pub fn from_yaml(text: &str) -> Result<Self, SimError> {
+        let profile: SimProfile = serde_yaml::from_str(text)
+            .map_err(|e| SimError::InvalidValue(format!("simulator profile: {e}")))?;
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    fn validate(&self) -> Result<(), SimError> {
+        let entries = std::iter::once(("default", &self.default))
+            .chain(self.services.iter().map(|(name, p)| (name.as_str(), p)));
+        for (name, p) in entries {
+            let invalid = |msg: String| Err(SimError::InvalidValue(format!("{name}: {msg}")));
+            match p.latency {
+                Some(Latency::Uniform { min_ms, max_ms }) if min_ms > max_ms => {
+                    return invalid(format!("latency min_ms {min_ms} > max_ms {max_ms}"));
+                }
+                Some(Latency::Normal { std_dev_ms, .. }) if std_dev_ms < 0.0 => {
+                    return invalid("latency std_dev_ms must not be negative".into());
+                }
+                _ => {}
+            }
+            for inj in &p.negative_responses {
+                if !(0.0..=1.0).contains(&inj.probability) {
+                    return invalid(format!("probability {} not in 0..1", inj.probability));
+                }
+                if inj.nrc == NRC_RESPONSE_PENDING {
+                    return invalid("use response_pending for NRC 0x78".into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The `services` key naming `diag_comm`: its short name, else the same
+    /// name in another case, else one of its aliases.
+    pub fn profile_name(&self, diag_comm: &DiagComm) -> Option<&str> {
+        self.services
+            .keys()
+            .filter_map(|key| Some((name_match(diag_comm, key)?, key.as_str())))
+            .min_by_key(|(found, _)| *found)
+            .map(|(_, key)| key)
+    }
+
+    /// Profile applying to a service.
+    pub fn service(&self, name: Option<&str>) -> &ServiceProfile {
+        name.and_then(|n| self.services.get(n))
+            .unwrap_or(&self.default)
+    }
+
+    /// A random generator seeded from the profile, or from the clock.
+    pub fn rng(&self) -> SimRng {
+        SimRng::new(self.seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64)
+        }))
+    }
+
+    /// Frames to send for `response`, the simulator's answer to a request of
+    /// service `name` with service ID `sid`.
+    pub fn frames(
+        &self,
+        name: Option<&str>,
+        sid: u8,
+        response: Vec<u8>,
+        rng: &mut SimRng,
+    ) -> Vec<Frame> {
+        let p = self.service(name);
+        let interval =
+            Duration::from_millis(p.pending_interval_ms.unwrap_or(DEFAULT_PENDING_INTERVAL_MS));
+        let mut frames: Vec<Frame> = (0..p.response_pending)
+            .map(|_| Frame {
+                delay: interval,
+                data: negative_response(sid, NRC_RESPONSE_PENDING),
+            })
+            .collect();
+        let data = p
+            .negative_responses
+            .iter()
+            .find(|inj| rng.next_f64() < inj.probability)
+            .map_or(response, |inj| negative_response(sid, inj.nrc));
+        let delay = p.latency.as_ref().map_or(Duration::ZERO, |l| l.sample(rng));
+        frames.push(Frame { delay, data });
+        frames
+    }
+}
+
+impl Latency {
+    pub fn sample(&self, rng: &mut SimRng) -> Duration {
+        let ms = match *self {
+            Latency::Fixed { ms } => ms as f64,
+            Latency::Uniform { min_ms, max_ms } => {
+                min_ms as f64 + rng.next_f64() * (max_ms - min_ms) as f64
+            }
+            Latency::Normal {
+                mean_ms,
+                std_dev_ms,
+            } => mean_ms + std_dev_ms * rng.next_gaussian(),
+        };
+        Duration::from_secs_f64(ms.max(0.0) / 1000.0)
+    }
+}
+
+/// Small deterministic generator (xorshift64*); good enough for test traffic.
+#[derive(Debug, Clone)]
+pub struct SimRng(u64);
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift must not start at zero
+        SimRng(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal (Box-Muller).
+    pub fn next_gaussian(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILE: &str = r#"
+seed: 7
+default:
+  latency: { distribution: fixed, ms: 5 }
+services:
+  RequestDownload:
+    latency: { distribution: uniform, min_ms: 20, max_ms: 80 }
+    response_pending: 2
+    pending_interval_ms: 100
+  Flaky:
+    negative_responses:
+      - { nrc: 0x22, probability: 0.5 }
+"#;
+
+    #[test]
+    fn test_pending_frames_precede_response() {
+        let profile = SimProfile::from_yaml(PROFILE).unwrap();
+        let mut rng = profile.rng();
+        let frames = profile.frames(Some("RequestDownload"), 0x34, vec![0x74, 0x20], &mut rng);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].data, [0x7F, 0x34, 0x78]);
+        assert_eq!(frames[1].delay, Duration::from_millis(100));
+        assert_eq!(frames[2].data, [0x74, 0x20]);
+        assert!((20..=80).contains(&frames[2].delay.as_millis()));
+
+        let other = profile.frames(Some("TesterPresent"), 0x3E, vec![0x7E, 0x00], &mut rng);
+        assert_eq!(
+            other,
+            [Frame {
+                delay: Duration::from_millis(5),
+                data: vec![0x7E, 0x00],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_profile_name_accepts_case_and_aliases() {
+        let profile = SimProfile::from_yaml(PROFILE).unwrap();
+        let mut comm = DiagComm {
+            short_name: "Download_Request".into(),
+            ..Default::default()
+        };
+        assert_eq!(profile.profile_name(&comm), None);
+        diag_ir::set_aliases(&mut comm, &["requestdownload".into()]);
+        assert_eq!(profile.profile_name(&comm), Some("RequestDownload"));
+        comm.short_name = "FLAKY".into();
+        assert_eq!(profile.profile_name(&comm), Some("Flaky"));
+    }
+
+    #[test]
+    fn test_negative_responses_follow_probability() {
+        let profile = SimProfile::from_yaml(PROFILE).unwrap();
+        let mut rng = profile.rng();
+        let nrcs = (0..1000)
+            .filter(|_| {
+                let frames = profile.frames(Some("Flaky"), 0x22, vec![0x62], &mut rng);
+                frames[0].data == [0x7F, 0x22, 0x22]
+            })
+            .count();
+        assert!((400..600).contains(&nrcs), "{nrcs}");
+    }
+
+    #[test]
+    fn test_normal_latency_is_never_negative() {
+        let latency = Latency::Normal {
+            mean_ms: 1.0,
+            std_dev_ms: 10.0,
+        };
+        let mut rng = SimRng::new(1);
+        let samples: Vec<Duration> = (0..200).map(|_| latency.sample(&mut rng)).collect();
+        assert!(samples.contains(&Duration::ZERO));
+        assert!(samples.iter().any(|d| *d > Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn test_invalid_profiles_are_rejected() {
+        for bad in [
+            "default: { latency: { distribution: uniform, min_ms: 9, max_ms: 1 } }",
+            "services: { X: { negative_responses: [{ nrc: 0x22, probability: 1.5 }] } }",
+            "services: { X: { negative_responses: [{ nrc: 0x78, probability: 0.1 }] } }",
+            "default: { latency: { distribution: poisson } }",
+        ] {
+            assert!(SimProfile::from_yaml(bad).is_err(), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Serve simulated responses over a transport.
+
+use diag_ir::DiagService;
+use diag_transport::{Transport, TransportError};
+
+use crate::{SimError, SimProfile, SimRng, match_service, negative_response};
+
+/// NRC 0x11 serviceNotSupported.
+const NRC_SERVICE_NOT_SUPPORTED: u8 = 0x11;
+
+/// Answer requests arriving on `transport` until the peer disconnects.
+///
+/// `respond` produces the response to a request, or `None` for
+/// serviceNotSupported; `profile` then shapes timing and injected NRCs per
+/// service, matched against `services`.
+pub fn serve(
+    transport: &mut dyn Transport,
+    services: &[DiagService],
+    profile: &SimProfile,
+    rng: &mut SimRng,
+    mut respond: impl FnMut(&[u8]) -> Option<Vec<u8>>,
+) -> Result<(), SimError> {
+    loop {
+        let request = match transport.receive() {
+            Ok(request) => request,
+            Err(TransportError::Closed) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let Some(&sid) = request.first() else {
+            continue;
+        };
+        let name =
+            match_service(services, &request).and_then(|svc| profile.profile_name(&svc.diag_comm));
+        let response =
+            respond(&request).unwrap_or_else(|| negative_response(sid, NRC_SERVICE_NOT_SUPPORTED));
+        for frame in profile.frames(name, sid, response, rng) {
+            std::thread::sleep(frame.delay);
+            transport.send(&frame.data)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FaultMemory;
+    use diag_ir::{DiagDatabase, Dtc};
+    use diag_transport::{DoipClient, DoipServer, TransportOptions};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_serves_fault_memory_over_doip() {
+        let mut store = FaultMemory::from_database(&DiagDatabase {
+            dtcs: vec![Dtc {
+                short_name: "P0123".into(),
+                trouble_code: 0x012300,
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        store.inject(0x012300, 0x09).unwrap();
+        let profile =
+            SimProfile::from_yaml("default: { response_pending: 1, pending_interval_ms: 1 }")
+                .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let ecu = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut server = DoipServer::new(stream, 0x1000);
+            let mut rng = profile.rng();
+            serve(&mut server, &[], &profile, &mut rng, |req| {
+                store.handle(req)
+            })
+        });
+
+        let mut client = DoipClient::connect(&addr, &TransportOptions::default()).unwrap();
+        assert_eq!(
+            client.request(&[0x19, 0x02, 0x01]).unwrap(),
+            [0x59, 0x02, 0xFF, 0x01, 0x23, 0x00, 0x09]
+        );
+        assert_eq!(
+            client.request(&[0x22, 0xF1, 0x90]).unwrap(),
+            [0x7F, 0x22, 0x11]
+        );
+        drop(client);
+        ecu.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,307 @@
+//! DoIP (ISO 13400-2) over TCP: message framing, and both ends of a
+//! diagnostic connection with routing activation.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+use crate::{Transport, TransportError, TransportOptions};
+
+pub const DEFAULT_PORT: u16 = 13400;
+
+const PROTOCOL_VERSION: u8 = 0x02;
+pub const ROUTING_ACTIVATION_REQUEST: u16 = 0x0005;
+pub const ROUTING_ACTIVATION_RESPONSE: u16 = 0x0006;
+pub const ALIVE_CHECK_REQUEST: u16 = 0x0007;
+pub const ALIVE_CHECK_RESPONSE: u16 = 0x0008;
+pub const DIAGNOSTIC_MESSAGE: u16 = 0x8001;
+pub const DIAGNOSTIC_MESSAGE_ACK: u16 = 0x8002;
+pub const DIAGNOSTIC_MESSAGE_NACK: u16 = 0x8003;
+
+const ROUTING_SUCCESSFUL: u8 = 0x10;
+
+/// One DoIP message: payload type and payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub payload_type: u16,
+    pub payload: Vec<u8>,
+}
+
+impl Message {
+    pub fn new(payload_type: u16, payload: Vec<u8>) -> Self {
+        Message {
+            payload_type,
+            payload,
+        }
+    }
+
+    /// A diagnostic message from `source` to `target` carrying UDS `data`.
+    pub fn diagnostic(source: u16, target: u16, data: &[u8]) -> Self {
+        let mut payload = Vec::with_capacity(4 + data.len());
+        payload.extend(source.to_be_bytes());
+        payload.extend(target.to_be_bytes());
+        payload.extend(data);
+        Message::new(DIAGNOSTIC_MESSAGE, payload)
+    }
+
+    /// `(source, target, data)` of a diagnostic message.
+    pub fn as_diagnostic(&self) -> Option<(u16, u16, &[u8])> {
+        if self.payload_type != DIAGNOSTIC_MESSAGE || self.payload.len() < 4 {
+            return None;
+        }
+        let p = &self.payload;
+        Some((
+            u16::from_be_bytes([p[0], p[1]]),
+            u16::from_be_bytes([p[2], p[3]]),
+            &p[4..],
+        ))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + self.payload.len());
+        bytes.extend([PROTOCOL_VERSION, !PROTOCOL_VERSION]);
+        bytes.extend(self.payload_type.to_be_bytes());
+        bytes.extend((self.payload.len() as u32).to_be_bytes());
+        bytes.extend(&self.payload);
+        bytes
+    }
+}
+
+/// Read one message; `None` on a clean end of stream.
+pub fn read_message(stream: &mut impl Read) -> Result<Option<Message>, TransportError> {
+    let mut header = [0u8; 8];
+    match stream.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(io_error(e)),
+    }
+    if header[0] != !header[1] {
+        return Err(TransportError::Protocol(format!(
+            "invalid DoIP header {:02X?}",
+            &header[..2]
+        )));
+    }
+    let payload_type = u16::from_be_bytes([header[2], header[3]]);
+    let len = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload).map_err(io_error)?;
+    Ok(Some(Message::new(payload_type, payload)))
+}
+
+pub fn write_message(stream: &mut impl Write, message: &Message) -> Result<(), TransportError> {
+    Ok(stream.write_all(&message.to_bytes())?)
+}
+
+/// Read timeouts surface as `WouldBlock` or `TimedOut` depending on the OS.
+fn io_error(e: std::io::Error) -> TransportError {
+    match e.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => TransportError::Timeout,
+        _ => TransportError::Io(e),
+    }
+}
+
+/// Whether `address` is a functional (group) logical address, which ISO
+/// 13400-2 puts at 0xE000 to 0xEFFF: legislated ones below 0xE400,
+/// manufacturer-specific ones from there on.
+pub fn is_functional_address(address: u16) -> bool {
+    (0xE000..=0xEFFF).contains(&address)
+}
+
+/// A tester connection to a DoIP entity with routing activated.
+pub struct DoipClient {
+    stream: TcpStream,
+    tester_address: u16,
+    ecu_address: u16,
+}
+
+impl DoipClient {
+    pub fn connect(addr: &str, options: &TransportOptions) -> Result<Self, TransportError> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_read_timeout(Some(options.timeout))?;
+        let mut request = options.tester_address.to_be_bytes().to_vec();
+        request.extend([0x00, 0, 0, 0, 0]);
+        write_message(
+            &mut stream,
+            &Message::new(ROUTING_ACTIVATION_REQUEST, request),
+        )?;
+        let mut client = DoipClient {
+            stream,
+            tester_address: options.tester_address,
+            ecu_address: 0,
+        };
+        let response = client.next(ROUTING_ACTIVATION_RESPONSE)?;
+        match response.payload.as_slice() {
+            [_, _, hi, lo, ROUTING_SUCCESSFUL, ..] => {
+                client.ecu_address = options
+                    .ecu_address
+                    .unwrap_or(u16::from_be_bytes([*hi, *lo]));
+                Ok(client)
+            }
+            p => Err(TransportError::Protocol(format!(
+                "routing activation rejected by {addr} (code {:02X?})",
+                p.get(4)
+            ))),
+        }
+    }
+
+    /// Logical address requests are sent to.
+    pub fn ecu_address(&self) -> u16 {
+        self.ecu_address
+    }
+
+    /// Next message of `payload_type`, answering alive checks and skipping
+    /// diagnostic acknowledgements on the way.
+    fn next(&mut self, payload_type: u16) -> Result<Message, TransportError> {
+        loop {
+            let message = read_message(&mut self.stream)?.ok_or(TransportError::Closed)?;
+            match message.payload_type {
+                t if t == payload_type => return Ok(message),
+                ALIVE_CHECK_REQUEST => write_message(
+                    &mut self.stream,
+                    &Message::new(
+                        ALIVE_CHECK_RESPONSE,
+                        self.tester_address.to_be_bytes().to_vec(),
+                    ),
+                )?,
+                DIAGNOSTIC_MESSAGE_ACK => {}
+                DIAGNOSTIC_MESSAGE_NACK => {
+                    return Err(TransportError::Protocol(format!(
+                        "diagnostic message rejected (NACK code {:02X?})",
+                        message.payload.get(4)
+                    )));
+                }
+                other => log::debug!("ignoring DoIP payload type 0x{other:04X}"),
+            }
+        }
+    }
+}
+
+impl Transport for DoipClient {
+    fn send(&mut self, data: &[u8]) -> Result<(), TransportError> {
+        write_message(
+            &mut self.stream,
+            &Message::diagnostic(self.tester_address, self.ecu_address, data),
+        )
+    }
+
+    fn receive(&mut self) -> Result<Vec<u8>, TransportError> {
+        let message = self.next(DIAGNOSTIC_MESSAGE)?;
+        let (_, _, data) = message
+            .as_diagnostic()
+            .ok_or_else(|| TransportError::Protocol("truncated diagnostic message".into()))?;
+        Ok(data.to_vec())
+    }
+}
+
+/// The DoIP entity side of one accepted tester connection: activates routing
+/// on request and acknowledges each diagnostic message it receives.
+pub struct DoipServer {
+    stream: TcpStream,
+    ecu_address: u16,
+    tester_address: Option<u16>,
+}
+
+impl DoipServer {
+    pub fn new(stream: TcpStream, ecu_address: u16) -> Self {
+        DoipServer {
+            stream,
+            ecu_address,
+            tester_address: None,
+        }
+    }
+
+    fn reply(&mut self, payload_type: u16, payload: Vec<u8>) -> Result<(), TransportError> {
+        write_message(&mut self.stream, &Message::new(payload_type, payload))
+    }
+}
+
+impl Transport for DoipServer {
+    fn send(&mut self, data: &[u8]) -> Result<(), TransportError> {
+        let tester = self
+            .tester_address
+            .ok_or_else(|| TransportError::Protocol("no tester has activated routing".into()))?;
+        write_message(
+            &mut self.stream,
+            &Message::diagnostic(self.ecu_address, tester, data),
+        )
+    }
+
+    fn receive(&mut self) -> Result<Vec<u8>, TransportError> {
+        loop {
+            let message = read_message(&mut self.stream)?.ok_or(TransportError::Closed)?;
+            match message.payload_type {
+                ROUTING_ACTIVATION_REQUEST if message.payload.len() >= 2 => {
+                    let mut response = message.payload[..2].to_vec();
+                    response.extend(self.ecu_address.to_be_bytes());
+                    response.extend([ROUTING_SUCCESSFUL, 0, 0, 0, 0]);
+                    self.tester_address =
+                        Some(u16::from_be_bytes([message.payload[0], message.payload[1]]));
+                    self.reply(ROUTING_ACTIVATION_RESPONSE, response)?;
+                }
+                DIAGNOSTIC_MESSAGE => {
+                    let Some((_, _, data)) = message.as_diagnostic() else {
+                        return Err(TransportError::Protocol(
+                            "truncated diagnostic message".into(),
+                        ));
+                    };
+                    let data = data.to_vec();
+                    let mut ack = message.payload[..4].to_vec();
+                    ack.push(0x00);
+                    self.reply(DIAGNOSTIC_MESSAGE_ACK, ack)?;
+                    return Ok(data);
+                }
+                other => log::debug!("ignoring DoIP payload type 0x{other:04X}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_client_and_server_exchange_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut server = DoipServer::new(stream, 0x1000);
+            while let Ok(request) = server.receive() {
+                server.send(&[0x7F, request[0], 0x78]).unwrap();
+                let mut response = vec![request[0] + 0x40];
+                response.extend(&request[1..]);
+                server.send(&response).unwrap();
+            }
+        });
+
+        let mut client = DoipClient::connect(&addr, &TransportOptions::default()).unwrap();
+        assert_eq!(client.ecu_address(), 0x1000);
+        assert_eq!(
+            client.request(&[0x22, 0xF1, 0x90]).unwrap(),
+            [0x62, 0xF1, 0x90]
+        );
+    }
+
+    #[test]
+    fn test_message_framing() {
+        let message = Message::diagnostic(0x0E00, 0x1000, &[0x3E, 0x00]);
+        let bytes = message.to_bytes();
+        assert_eq!(
+            bytes,
+            [
+                0x02, 0xFD, 0x80, 0x01, 0, 0, 0, 6, 0x0E, 0x00, 0x10, 0x00, 0x3E, 0x00
+            ]
+        );
+        let parsed = read_message(&mut bytes.as_slice()).unwrap().unwrap();
+        assert_eq!(
+            parsed.as_diagnostic(),
+            Some((0x0E00, 0x1000, &[0x3E, 0x00][..]))
+        );
+        assert!(
+            read_message(&mut [0x02, 0x02].as_slice())
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! ISO-TP (ISO 15765-2) over the Linux kernel's socketcan `CAN_ISOTP`
+//! sockets; segmentation and flow control are handled by the kernel.
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::Duration;
+
+use crate::{Transport, TransportError};
+
+/// Largest ISO-TP payload (FF_DL with 32-bit length is not used by UDS).
+const MAX_MESSAGE_LEN: usize = 4095;
+
+/// A bound ISO-TP socket sending on `tx_id` and receiving on `rx_id`.
+pub struct IsoTpSocket {
+    file: File,
+}
+
+impl IsoTpSocket {
+    /// Open an ISO-TP channel on `interface` (e.g. `can0`). IDs above 0x7FF
+    /// are sent as 29-bit extended frames.
+    // Opening and binding the socket needs raw libc calls; everything after
+    // that goes through `File`.
+    #[allow(unsafe_code)]
+    pub fn open(
+        interface: &str,
+        tx_id: u32,
+        rx_id: u32,
+        timeout: Duration,
+    ) -> Result<Self, TransportError> {
+        let name = CString::new(interface)
+            .map_err(|_| TransportError::InvalidSpec(interface.to_string()))?;
+        // SAFETY: `name` is a valid NUL-terminated string.
+        let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if ifindex == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        // SAFETY: plain socket(2) call; the result is checked before use.
+        let fd = unsafe { libc::socket(libc::PF_CAN, libc::SOCK_DGRAM, libc::CAN_ISOTP) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // SAFETY: `fd` is a freshly opened descriptor owned by nobody else.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let file = File::from(fd);
+
+        let tv = libc::timeval {
+            tv_sec: libc::time_t::try_from(timeout.as_secs()).unwrap_or(libc::time_t::MAX),
+            tv_usec: libc::suseconds_t::from(timeout.subsec_micros()),
+        };
+        // SAFETY: `tv` outlives the call and its size is passed alongside.
+        let rc = unsafe {
+            libc::setsockopt(
+                file.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                std::ptr::from_ref(&tv).cast(),
+                size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        };
+        if rc < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        // SAFETY: an all-zero sockaddr_can is a valid value.
+        let mut addr: libc::sockaddr_can = unsafe { std::mem::zeroed() };
+        addr.can_family = libc::AF_CAN as libc::sa_family_t;
+        addr.can_ifindex = libc::c_int::try_from(ifindex)
+            .map_err(|_| TransportError::InvalidSpec(interface.to_string()))?;
+        addr.can_addr.tp.tx_id = can_id(tx_id);
+        addr.can_addr.tp.rx_id = can_id(rx_id);
+        // SAFETY: `addr` is a fully initialised sockaddr_can of the given size.
+        let rc = unsafe {
+            libc::bind(
+                file.as_raw_fd(),
+                std::ptr::from_ref(&addr).cast(),
+                size_of::<libc::sockaddr_can>() as libc::socklen_t,
+            )
+        };
+        if rc < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(IsoTpSocket { file })
+    }
+}
+
+fn can_id(id: u32) -> libc::canid_t {
+    if id > libc::CAN_SFF_MASK {
+        id | libc::CAN_EFF_FLAG
+    } else {
+        id
+    }
+}
+
+impl Transport for IsoTpSocket {
+    fn send(&mut self, data: &[u8]) -> Result<(), TransportError> {
+        if data.len() > MAX_MESSAGE_LEN {
+            return Err(TransportError::Protocol(format!(
+                "{} bytes exceed the ISO-TP limit of {MAX_MESSAGE_LEN}",
+                data.len()
+            )));
+        }
+        self.file.write_all(data)?;
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Vec<u8>, TransportError> {
+        let mut buf = vec![0; MAX_MESSAGE_LEN];
+        match self.file.read(&mut buf) {
+            Ok(n) => {
+                buf.truncate(n);
+                Ok(buf)
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                Err(TransportError::Timeout)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extended_ids_get_eff_flag() {
+        assert_eq!(can_id(0x7E0), 0x7E0);
+        assert_eq!(can_id(0x18DA_10F1), 0x18DA_10F1 | libc::CAN_EFF_FLAG);
+    }
+
+    #[test]
+    fn test_unknown_interface_is_an_error() {
+        assert!(IsoTpSocket::open("nosuchcan0", 0x7E0, 0x7E8, Duration::from_secs(1)).is_err());
+    }
+}
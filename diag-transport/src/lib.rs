@@ -0,0 +1,314 @@
+//! Transports carrying UDS messages between a tester and an ECU: DoIP over
+//! TCP, and ISO-TP over socketcan on Linux (feature `isotp`).
+
+pub mod doip;
+#[cfg(all(target_os = "linux", feature = "isotp"))]
+pub mod isotp;
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
+
+pub use doip::{DoipClient, DoipServer};
+
+/// NRC 0x78 requestCorrectlyReceived-ResponsePending.
+pub const NRC_RESPONSE_PENDING: u8 = 0x78;
+
+/// Errors raised by a transport.
+#[derive(Debug, thiserror::Error)]
+pub enum TransportError {
+    #[error("invalid transport '{0}'")]
+    InvalidSpec(String),
+    #[error("unsupported transport: {0}")]
+    Unsupported(String),
+    #[error("protocol error: {0}")]
+    Protocol(String),
+    #[error("timed out waiting for a response")]
+    Timeout,
+    #[error("connection closed by peer")]
+    Closed,
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// A channel exchanging whole UDS messages with one peer.
+///
+/// Testers `send` requests and `receive` responses; a simulated ECU does the
+/// opposite on the server side of the same transport.
+pub trait Transport: Send {
+    fn send(&mut self, data: &[u8]) -> Result<(), TransportError>;
+
+    /// Next message from the peer; `Closed` once the peer disconnects.
+    fn receive(&mut self) -> Result<Vec<u8>, TransportError>;
+
+    /// Send a request and return the final response, skipping NRC 0x78
+    /// response-pending frames.
+    fn request(&mut self, data: &[u8]) -> Result<Vec<u8>, TransportError> {
+        self.send(data)?;
+        loop {
+            let response = self.receive()?;
+            if !is_response_pending(&response) {
+                return Ok(response);
+            }
+        }
+    }
+}
+
+/// `7F <sid> 78`.
+pub fn is_response_pending(data: &[u8]) -> bool {
+    matches!(data, [0x7F, _, NRC_RESPONSE_PENDING])
+}
+
+/// Where to reach an ECU, parsed from `doip:HOST[:PORT]` (IPv6 hosts as
+/// `[ADDR]:PORT`) or `isotp:IFACE:TX_ID:RX_ID` (CAN IDs in hex, e.g.
+/// `isotp:can0:0x7E0:0x7E8`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportSpec {
+    Doip {
+        addr: String,
+    },
+    IsoTp {
+        interface: String,
+        tx_id: u32,
+        rx_id: u32,
+    },
+}
+
+impl FromStr for TransportSpec {
+    type Err = TransportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || TransportError::InvalidSpec(s.to_string());
+        match s.split_once(':') {
+            Some(("doip", addr)) => Ok(TransportSpec::Doip {
+                addr: doip_addr(addr).ok_or_else(invalid)?,
+            }),
+            Some(("isotp", rest)) => {
+                let [interface, tx, rx] = rest.split(':').collect::<Vec<_>>()[..] else {
+                    return Err(invalid());
+                };
+                let id = |s: &str| diag_ir::parse_uint(s).and_then(|v| u32::try_from(v).ok());
+                Ok(TransportSpec::IsoTp {
+                    interface: interface.to_string(),
+                    tx_id: id(tx).ok_or_else(invalid)?,
+                    rx_id: id(rx).ok_or_else(invalid)?,
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// `HOST:PORT` of a `doip:` spec, with [`doip::DEFAULT_PORT`] if it names
+/// none. IPv6 addresses are bracketed when followed by a port
+/// (`[fe80::1]:13400`) and may be given bare without one (`fe80::1`).
+fn doip_addr(addr: &str) -> Option<String> {
+    if let Ok(socket) = addr.parse::<SocketAddr>() {
+        return Some(socket.to_string());
+    }
+    let host = addr
+        .strip_prefix('[')
+        .and_then(|a| a.strip_suffix(']'))
+        .unwrap_or(addr);
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Some(SocketAddr::new(ip, doip::DEFAULT_PORT).to_string());
+    }
+    match addr.split_once(':') {
+        None if !addr.is_empty() => Some(format!("{addr}:{}", doip::DEFAULT_PORT)),
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+            Some(addr.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// The `doip:` / `isotp:` form [`TransportSpec`] is parsed from.
+impl fmt::Display for TransportSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportSpec::Doip { addr } => write!(f, "doip:{addr}"),
+            TransportSpec::IsoTp {
+                interface,
+                tx_id,
+                rx_id,
+            } => write!(f, "isotp:{interface}:0x{tx_id:X}:0x{rx_id:X}"),
+        }
+    }
+}
+
+impl TransportSpec {
+    /// The DoIP entity named by a database's transport section, or `None`
+    /// if it has no IP address.
+    pub fn from_config(config: &diag_ir::TransportConfig) -> Option<Self> {
+        let entity = config.doip.as_ref()?;
+        let ip = entity.ip.as_deref()?;
+        let port = entity.port.unwrap_or(doip::DEFAULT_PORT);
+        let addr = match ip.parse::<IpAddr>() {
+            Ok(ip) => SocketAddr::new(ip, port).to_string(),
+            Err(_) => format!("{ip}:{port}"),
+        };
+        Some(TransportSpec::Doip { addr })
+    }
+}
+
+/// Addressing and timing for [`open`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransportOptions {
+    /// DoIP logical address of the tester.
+    pub tester_address: u16,
+    /// DoIP logical address of the ECU; defaults to the address the DoIP
+    /// entity reports during routing activation.
+    pub ecu_address: Option<u16>,
+    /// How long to wait for each response frame.
+    pub timeout: Duration,
+}
+
+impl Default for TransportOptions {
+    fn default() -> Self {
+        TransportOptions {
+            tester_address: 0x0E00,
+            ecu_address: None,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl TransportOptions {
+    /// Default options with the tester and ECU logical addresses of a
+    /// database's transport section.
+    pub fn from_config(config: &diag_ir::TransportConfig) -> Self {
+        let mut options = TransportOptions::default();
+        if let Some(doip) = &config.doip {
+            if let Some(tester) = doip.tester_address {
+                options.tester_address = tester;
+            }
+            options.ecu_address = doip.logical_address;
+        }
+        options
+    }
+}
+
+/// Connect to the ECU described by `spec` as a tester.
+pub fn open(
+    spec: &TransportSpec,
+    options: &TransportOptions,
+) -> Result<Box<dyn Transport>, TransportError> {
+    match spec {
+        TransportSpec::Doip { addr } => Ok(Box::new(DoipClient::connect(addr, options)?)),
+        #[cfg(all(target_os = "linux", feature = "isotp"))]
+        TransportSpec::IsoTp {
+            interface,
+            tx_id,
+            rx_id,
+        } => Ok(Box::new(isotp::IsoTpSocket::open(
+            interface,
+            *tx_id,
+            *rx_id,
+            options.timeout,
+        )?)),
+        #[cfg(not(all(target_os = "linux", feature = "isotp")))]
+        TransportSpec::IsoTp { .. } => Err(TransportError::Unsupported(
+            "ISO-TP requires Linux and the `isotp` feature".into(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spec() {
+        assert_eq!(
+            "doip:192.168.1.10".parse::<TransportSpec>().unwrap(),
+            TransportSpec::Doip {
+                addr: "192.168.1.10:13400".into()
+            }
+        );
+        assert_eq!(
+            "doip:ecu:13401".parse::<TransportSpec>().unwrap(),
+            TransportSpec::Doip {
+                addr: "ecu:13401".into()
+            }
+        );
+        assert_eq!(
+            "isotp:can0:0x7E0:0x7E8".parse::<TransportSpec>().unwrap(),
+            TransportSpec::IsoTp {
+                interface: "can0".into(),
+                tx_id: 0x7E0,
+                rx_id: 0x7E8,
+            }
+        );
+        assert_eq!(
+            "isotp:can0:0x7E0:0x7E8"
+                .parse::<TransportSpec>()
+                .unwrap()
+                .to_string(),
+            "isotp:can0:0x7E0:0x7E8"
+        );
+        for bad in [
+            "doip:",
+            "doip:ecu:port",
+            "doip:fe80::1:13400:x",
+            "can:vcan0",
+            "isotp:can0:0x7E0",
+            "isotp:can0:7E0:7E8",
+        ] {
+            assert!(bad.parse::<TransportSpec>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_parse_ipv6_spec() {
+        for (spec, addr) in [
+            ("doip:[fe80::1]:13401", "[fe80::1]:13401"),
+            ("doip:[fe80::1]", "[fe80::1]:13400"),
+            ("doip:fe80::1", "[fe80::1]:13400"),
+            ("doip:::1", "[::1]:13400"),
+        ] {
+            assert_eq!(
+                spec.parse::<TransportSpec>().unwrap(),
+                TransportSpec::Doip { addr: addr.into() },
+                "{spec}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_config() {
+        let mut config = diag_ir::TransportConfig {
+            doip: Some(diag_ir::DoipConfig {
+                ip: Some("192.168.0.10".into()),
+                logical_address: Some(0x1000),
+                tester_address: Some(0x0E80),
+                ..Default::default()
+            }),
+        };
+        assert_eq!(
+            TransportSpec::from_config(&config),
+            Some(TransportSpec::Doip {
+                addr: "192.168.0.10:13400".into()
+            })
+        );
+        let options = TransportOptions::from_config(&config);
+        assert_eq!(options.tester_address, 0x0E80);
+        assert_eq!(options.ecu_address, Some(0x1000));
+
+        let doip = config.doip.as_mut().unwrap();
+        doip.ip = Some("fe80::1".into());
+        doip.port = Some(13401);
+        assert_eq!(
+            TransportSpec::from_config(&config),
+            Some(TransportSpec::Doip {
+                addr: "[fe80::1]:13401".into()
+            })
+        );
+        config.doip = None;
+        assert_eq!(TransportSpec::from_config(&config), None);
+        assert_eq!(
+            TransportOptions::from_config(&config),
+            TransportOptions::default()
+        );
+    }
+}
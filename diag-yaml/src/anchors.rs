@@ -0,0 +1,369 @@
+//! Explicit handling of YAML anchors, aliases and merge keys.
+//!
+//! serde_yaml expands aliases silently: an alias inside its own anchored node
+//! ends in a recursion limit error, a redefined anchor shadows the earlier
+//! one without notice, and `<<` merge keys are kept as an ordinary key. This
+//! module scans the document text first. It rejects recursive aliases,
+//! reports redefined anchors and records where every alias is used, so a
+//! diagnostic for `dids/0xF190/type` can name the anchor the value came
+//! from. [`apply_merges`] then resolves merge keys, warning when two merged
+//! mappings supply different values for the same key.
+
+use serde_yaml::Value;
+
+use crate::comments::{PathTracker, trailing_comment};
+use crate::parser::YamlParseError;
+
+/// Where an anchor is defined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Anchor {
+    pub name: String,
+    /// 1-based line of the definition.
+    pub line: usize,
+    /// Key path of the line the anchor is on, lowercased.
+    pub path: String,
+}
+
+/// A use of an alias and the anchor it resolves to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AliasUse {
+    pub anchor: Anchor,
+    /// 1-based line of the alias.
+    pub line: usize,
+    /// Key path of the node the alias provides, lowercased
+    /// (`dids/0xf190/type`). For a merge key (`<<: *base`) it is the path of
+    /// the mapping that receives the merged keys.
+    pub path: String,
+    pub merge: bool,
+}
+
+/// Result of [`analyze_anchors`].
+#[derive(Debug, Default)]
+pub struct AnchorReport {
+    pub anchors: Vec<Anchor>,
+    pub aliases: Vec<AliasUse>,
+    /// Redefined anchors.
+    pub warnings: Vec<String>,
+    /// Whether the document uses `<<` merge keys.
+    pub has_merge_keys: bool,
+}
+
+impl AnchorReport {
+    /// The alias that supplied the node at `path`, if any. The path may point
+    /// below the aliased node; the innermost alias wins.
+    pub fn origin(&self, path: &str) -> Option<&AliasUse> {
+        let path = path.to_ascii_lowercase();
+        self.aliases
+            .iter()
+            .filter(|a| {
+                !a.path.is_empty()
+                    && path
+                        .strip_prefix(&a.path)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|a| a.path.len())
+    }
+}
+
+impl std::fmt::Display for AliasUse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "from anchor &{} (line {}) via {} on line {}",
+            self.anchor.name,
+            self.anchor.line,
+            if self.merge { "merge key" } else { "alias" },
+            self.line
+        )
+    }
+}
+
+/// An anchored node whose lines are still being read.
+struct OpenAnchor {
+    name: String,
+    line: usize,
+    /// Lines indented deeper than this belong to the node.
+    indent: usize,
+}
+
+/// Scan `yaml` for anchors and aliases. Fails on an alias used inside the
+/// node of its own anchor.
+pub fn analyze_anchors(yaml: &str) -> Result<AnchorReport, YamlParseError> {
+    let mut report = AnchorReport::default();
+    let mut tracker = PathTracker::default();
+    let mut open: Vec<OpenAnchor> = Vec::new();
+
+    for (idx, line) in yaml.lines().enumerate() {
+        let lineno = idx + 1;
+        if tracker.in_block_scalar(line) {
+            continue;
+        }
+        let content = line.trim_start();
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - content.len();
+        open.retain(|a| indent > a.indent);
+
+        let key = tracker.line(line);
+        let code = &line[..trailing_comment(line).unwrap_or(line.len())];
+        let is_merge = code[indent..]
+            .trim_start_matches(['-', ' '])
+            .starts_with("<<:");
+        report.has_merge_keys |= is_merge;
+
+        for token in tokens(code) {
+            match token {
+                Token::Anchor(at, name) => {
+                    if let Some(prev) = report.anchors.iter().find(|a| a.name == name) {
+                        report.warnings.push(format!(
+                            "line {lineno}: anchor &{name} redefines the anchor of line {}; \
+                             later aliases refer to the new definition",
+                            prev.line
+                        ));
+                    }
+                    let anchor = Anchor {
+                        name: name.to_string(),
+                        line: lineno,
+                        path: key.as_ref().map_or("", |k| k.path.as_str()).to_string(),
+                    };
+                    report.anchors.push(anchor);
+                    open.push(OpenAnchor {
+                        name: name.to_string(),
+                        line: lineno,
+                        indent: owner_indent(code, indent, at),
+                    });
+                }
+                Token::Alias(name) => {
+                    if let Some(cycle) = open.iter().find(|a| a.name == name) {
+                        return Err(YamlParseError::RecursiveAlias(format!(
+                            "alias *{name} on line {lineno} is part of the node anchored \
+                             as &{name} on line {}",
+                            cycle.line
+                        )));
+                    }
+                    // Unknown aliases are reported by the YAML parser.
+                    let Some(anchor) = report.anchors.iter().rev().find(|a| a.name == name) else {
+                        continue;
+                    };
+                    let path = key.as_ref().map_or("", |k| k.path.as_str());
+                    let path = if is_merge {
+                        path.strip_suffix("<<")
+                            .unwrap_or(path)
+                            .trim_end_matches('/')
+                    } else {
+                        path
+                    };
+                    report.aliases.push(AliasUse {
+                        anchor: anchor.clone(),
+                        line: lineno,
+                        path: path.to_string(),
+                        merge: is_merge,
+                    });
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+enum Token<'a> {
+    /// Byte offset and name of `&name`.
+    Anchor(usize, &'a str),
+    Alias(&'a str),
+}
+
+/// Anchors and aliases of a line without its comment, outside quotes.
+fn tokens(code: &str) -> Vec<Token<'_>> {
+    let bytes = code.as_bytes();
+    let mut out = Vec::new();
+    let mut quote = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        let at_token_start = i == 0 || matches!(bytes[i - 1], b' ' | b'[' | b'{' | b',');
+        match (quote, b) {
+            (None, b'"' | b'\'') if at_token_start => quote = Some(b),
+            (Some(q), _) if b == q => quote = None,
+            (None, b'&' | b'*') if at_token_start => {
+                let len = code[i + 1..]
+                    .find(|c: char| c.is_whitespace() || matches!(c, ',' | '[' | ']' | '{' | '}'))
+                    .unwrap_or(code.len() - i - 1);
+                let name = &code[i + 1..i + 1 + len];
+                if !name.is_empty() {
+                    out.push(if b == b'&' {
+                        Token::Anchor(i, name)
+                    } else {
+                        Token::Alias(name)
+                    });
+                }
+                i += len;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Indent of the node an anchor at byte `at` belongs to: the sequence item
+/// for `- &a`, else the key the anchor follows.
+fn owner_indent(code: &str, indent: usize, at: usize) -> usize {
+    let mut pos = indent;
+    loop {
+        let rest = &code[pos..];
+        match rest.strip_prefix('-') {
+            Some(after) if after.is_empty() || after.starts_with(' ') => {
+                let next = pos + 1 + (after.len() - after.trim_start().len());
+                if next > at {
+                    return pos;
+                }
+                pos = next;
+            }
+            _ => return pos,
+        }
+    }
+}
+
+/// Resolve `<<` merge keys in `value`. Explicit keys win over merged ones and
+/// earlier merged mappings over later ones, as in YAML 1.1; a key merged from
+/// several mappings with different values is reported in the returned
+/// warnings.
+pub fn apply_merges(value: &mut Value) -> Result<Vec<String>, YamlParseError> {
+    let mut warnings = Vec::new();
+    collect_conflicts(value, "", &mut warnings);
+    value.apply_merge()?;
+    Ok(warnings)
+}
+
+fn collect_conflicts(value: &Value, path: &str, warnings: &mut Vec<String>) {
+    match value {
+        Value::Mapping(mapping) => {
+            if let Some(Value::Sequence(sources)) = mapping.get("<<") {
+                let mut seen: Vec<(&Value, &Value)> = Vec::new();
+                for source in sources.iter().filter_map(Value::as_mapping) {
+                    for (k, v) in source {
+                        if mapping.contains_key(k) {
+                            continue;
+                        }
+                        match seen.iter().find(|(sk, _)| *sk == k) {
+                            Some((_, first)) if *first != v => warnings.push(format!(
+                                "{}: merge key conflict on '{}': merged mappings disagree, \
+                                 the first one wins",
+                                if path.is_empty() { "/" } else { path },
+                                key_str(k)
+                            )),
+                            Some(_) => {}
+                            None => seen.push((k, v)),
+                        }
+                    }
+                }
+            }
+            for (k, v) in mapping {
+                let child = if path.is_empty() {
+                    key_str(k)
+                } else {
+                    format!("{path}/{}", key_str(k))
+                };
+                collect_conflicts(v, &child, warnings);
+            }
+        }
+        Value::Sequence(items) => {
+            for (i, item) in items.iter().enumerate() {
+                collect_conflicts(item, &format!("{path}/{i}"), warnings);
+            }
+        }
+        Value::Tagged(tagged) => collect_conflicts(&tagged.value, path, warnings),
+        _ => {}
+    }
+}
+
+fn key_str(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        other => serde_yaml::to_string(other)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recursive_alias_is_rejected() {
+        let yaml = "\
+types:
+  node: &node
+    name: n
+    children:
+      - *node
+";
+        let err = analyze_anchors(yaml).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Recursive alias: alias *node on line 5 is part of the node anchored as &node on line 2"
+        );
+    }
+
+    #[test]
+    fn test_alias_provenance_and_redefinition() {
+        let yaml = "\
+types:
+  u16: &u16 {base: u16}
+dids:
+  0xF190:
+    type: *u16
+  0xF191:
+    type: &u16 {base: u8}
+  0xF192:
+    - *u16
+";
+        let report = analyze_anchors(yaml).unwrap();
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].starts_with("line 7: anchor &u16 redefines"));
+        let origin = report.origin("dids/0xF190/type/base").unwrap();
+        assert_eq!(origin.anchor.line, 2);
+        assert_eq!(
+            origin.to_string(),
+            "from anchor &u16 (line 2) via alias on line 5"
+        );
+        assert_eq!(report.aliases[1].anchor.line, 7);
+        assert!(report.origin("dids/0xF191/type").is_none());
+    }
+
+    #[test]
+    fn test_sibling_alias_after_anchor_is_not_a_cycle() {
+        let yaml = "a: &x\n  - 1\nb: *x\nc: 'not *x an alias'  # *x\nd: it's *x\n";
+        let report = analyze_anchors(yaml).unwrap();
+        let paths: Vec<&str> = report.aliases.iter().map(|a| a.path.as_str()).collect();
+        assert_eq!(paths, ["b", "d"]);
+    }
+
+    #[test]
+    fn test_merge_conflicts_are_reported() {
+        let yaml = "\
+base: &base {timeout: 10, retries: 1}
+fast: &fast {timeout: 1}
+svc:
+  <<: [*fast, *base]
+  retries: 3
+";
+        let report = analyze_anchors(yaml).unwrap();
+        assert!(report.has_merge_keys);
+        assert!(report.origin("svc/timeout").unwrap().merge);
+
+        let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+        let warnings = apply_merges(&mut value).unwrap();
+        assert_eq!(
+            warnings,
+            ["svc: merge key conflict on 'timeout': merged mappings disagree, the first one wins"]
+        );
+        assert_eq!(value["svc"]["timeout"], 1);
+        assert_eq!(value["svc"]["retries"], 3);
+    }
+}
@@ -0,0 +1,651 @@
+//! Carry comments from a YAML document over to its rewritten form.
+//!
+//! Going through the IR drops comments. This module re-inserts them by
+//! token patching: every comment of the original is tied to the key path of
+//! the mapping key it precedes or trails (`dids/0xF190/type`), and emitted
+//! again next to the key with the same path in the rewritten text. Sequence
+//! items are identified by their first `key: value` pair (`- name: VIN`)
+//! rather than by position, so comments follow items that were reordered;
+//! items of one sequence sharing that pair are told apart by occurrence
+//! (`[name=vin]#2`). Lines continuing a flow collection over several lines
+//! are skipped like block scalars.
+//!
+//! Anchors and aliases, which the round trip expands, are put back the same
+//! way before the comments: an anchor lands on the first of its nodes in the
+//! rewritten text, and later nodes still identical to it become aliases
+//! again. This covers anchors and aliases written as the value of a key
+//! (`type: &u16`, `type: *u16`); anchored sequence items and `<<` merge keys
+//! stay expanded.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::anchors::analyze_anchors;
+
+#[derive(Debug, Default)]
+struct KeyComments {
+    leading: Vec<String>,
+    trailing: Option<String>,
+}
+
+/// A key or sequence item line and the path it opens.
+pub(crate) struct KeyLine {
+    pub(crate) path: String,
+    pub(crate) indent: usize,
+    /// Column of the key, past any `- ` indicators.
+    column: usize,
+    /// Byte offset of the `:` ending the key, if the line has one.
+    colon: Option<usize>,
+    /// Byte offset of a trailing ` # comment`.
+    comment_at: Option<usize>,
+}
+
+/// Tracks the key path through a block-style YAML document line by line.
+#[derive(Default)]
+pub(crate) struct PathTracker {
+    /// `(indent, component, is_sequence_item)`
+    stack: Vec<(usize, String, bool)>,
+    /// Indent of a block scalar (`key: |`) whose lines are being skipped.
+    block_scalar: Option<usize>,
+    /// Bracket depth of a flow collection continued on the next line.
+    flow: usize,
+    /// Sequence item paths seen so far, with their number of occurrences.
+    items: HashMap<String, usize>,
+}
+
+impl PathTracker {
+    /// Whether `line` is part of the block scalar being skipped.
+    pub(crate) fn in_block_scalar(&mut self, line: &str) -> bool {
+        let content = line.trim_start();
+        if let Some(block) = self.block_scalar {
+            if content.is_empty() || line.len() - content.len() > block {
+                return true;
+            }
+            self.block_scalar = None;
+        }
+        false
+    }
+
+    pub(crate) fn line(&mut self, line: &str) -> Option<KeyLine> {
+        if self.in_block_scalar(line) {
+            return None;
+        }
+        if self.flow > 0 {
+            self.flow = scan(line, self.flow).1;
+            return None;
+        }
+        let content = line.trim_start();
+        let indent = line.len() - content.len();
+        if content.is_empty() || content.starts_with('#') || content.starts_with("---") {
+            return None;
+        }
+        let (comment_at, flow) = scan(line, 0);
+        self.flow = flow;
+        let code = line[indent..comment_at.unwrap_or(line.len())].trim_end();
+
+        let (item, key_indent, rest) = match code.strip_prefix('-') {
+            Some(rest) if rest.is_empty() || rest.starts_with(' ') => {
+                let body = rest.trim_start();
+                (true, indent + code.len() - body.len(), body)
+            }
+            _ => (false, indent, code),
+        };
+        let key = split_key(rest).map(|(k, at, value)| (k, key_indent + at, value));
+
+        if item {
+            // Sequences may sit at the indent of their parent key.
+            while self
+                .stack
+                .last()
+                .is_some_and(|(i, _, is_item)| *i > indent || (*i == indent && *is_item))
+            {
+                self.stack.pop();
+            }
+            let mut id = match &key {
+                Some((k, _, v)) if !v.is_empty() => format!("[{k}={}]", normalize(v)),
+                Some((k, _, _)) => format!("[{k}]"),
+                None => format!("[{}]", normalize(rest)),
+            };
+            let item_path = format!("{}/{id}", self.path());
+            let seen = self.items.entry(item_path).or_default();
+            *seen += 1;
+            if *seen > 1 {
+                id = format!("{id}#{seen}");
+            }
+            self.stack.push((indent, id, true));
+        } else {
+            while self.stack.last().is_some_and(|(i, _, _)| *i >= indent) {
+                self.stack.pop();
+            }
+        }
+        let colon = key.as_ref().map(|&(_, at, _)| at);
+        if let Some((k, _, value)) = key {
+            self.stack.push((key_indent, k, false));
+            let value = strip_anchor(value);
+            if value.starts_with('|') || value.starts_with('>') {
+                self.block_scalar = Some(key_indent);
+            }
+        } else if !item {
+            return None;
+        }
+
+        Some(KeyLine {
+            path: self.path(),
+            indent,
+            column: key_indent,
+            colon,
+            comment_at,
+        })
+    }
+
+    fn path(&self) -> String {
+        self.stack
+            .iter()
+            .map(|(_, c, _)| c.as_str())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+/// Re-insert the comments of `original` into `rewritten`, a rewrite of the
+/// same document. Comments whose key no longer exists are dropped with a
+/// warning.
+pub fn carry_comments(original: &str, rewritten: &str) -> String {
+    let rewritten = &restore_anchors(original, rewritten);
+    let mut comments: HashMap<String, KeyComments> = HashMap::new();
+    let mut pending: Vec<String> = Vec::new();
+    let mut tracker = PathTracker::default();
+    for line in original.lines() {
+        if tracker.in_block_scalar(line) {
+            continue;
+        }
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('#') {
+            pending.push(trimmed.to_string());
+            continue;
+        }
+        let Some(key) = tracker.line(line) else {
+            if trimmed.is_empty() && !pending.is_empty() {
+                pending.push(String::new());
+            }
+            continue;
+        };
+        let entry = comments.entry(key.path).or_default();
+        entry.leading.append(&mut pending);
+        entry.trailing = key.comment_at.map(|at| line[at..].trim().to_string());
+    }
+    let epilogue = pending;
+
+    let mut out = String::with_capacity(rewritten.len() + original.len() / 4);
+    let mut tracker = PathTracker::default();
+    for line in rewritten.lines() {
+        let Some(key) = tracker.line(line) else {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        };
+        let Some(found) = comments.remove(&key.path) else {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        };
+        for comment in trim_blank(&found.leading) {
+            if !comment.is_empty() {
+                out.push_str(&" ".repeat(key.indent));
+                out.push_str(comment);
+            }
+            out.push('\n');
+        }
+        out.push_str(line);
+        if let Some(trailing) = found.trailing {
+            out.push_str("  ");
+            out.push_str(&trailing);
+        }
+        out.push('\n');
+    }
+    for comment in trim_blank(&epilogue) {
+        out.push_str(comment);
+        out.push('\n');
+    }
+
+    let lost: usize = comments
+        .values()
+        .map(|c| {
+            c.leading.iter().filter(|l| !l.is_empty()).count() + usize::from(c.trailing.is_some())
+        })
+        .sum();
+    if lost > 0 {
+        log::warn!("{lost} comment line(s) belong to keys that no longer exist and were dropped");
+    }
+    out
+}
+
+/// Put the anchors and aliases of `original` back into `rewritten`, where
+/// the round trip expanded them. Copies that the rewrite changed stay
+/// expanded, with a warning.
+fn restore_anchors(original: &str, rewritten: &str) -> String {
+    let Ok(report) = analyze_anchors(original) else {
+        return rewritten.to_string();
+    };
+    let original: Vec<&str> = original.lines().collect();
+    // Whether the key on 1-based line `lineno` has `token` as its value.
+    let is_value = |lineno: usize, token: &str| {
+        let line = original[lineno - 1];
+        let code = &line[..trailing_comment(line).unwrap_or(line.len())];
+        split_key(code.trim_start().trim_start_matches(['-', ' ']))
+            .is_some_and(|(_, _, value)| value.split_whitespace().next() == Some(token))
+    };
+    // Paths of the nodes sharing each anchor, by the anchor's line.
+    let mut groups: BTreeMap<usize, (&str, Vec<&str>)> = BTreeMap::new();
+    for alias in &report.aliases {
+        let anchor = &alias.anchor;
+        if alias.merge
+            || !is_value(alias.line, &format!("*{}", anchor.name))
+            || !is_value(anchor.line, &format!("&{}", anchor.name))
+        {
+            continue;
+        }
+        groups
+            .entry(anchor.line)
+            .or_insert_with(|| (anchor.name.as_str(), vec![anchor.path.as_str()]))
+            .1
+            .push(alias.path.as_str());
+    }
+    if groups.is_empty() {
+        return rewritten.to_string();
+    }
+
+    let lines: Vec<&str> = rewritten.lines().collect();
+    let mut keys: HashMap<String, (usize, KeyLine)> = HashMap::new();
+    let mut tracker = PathTracker::default();
+    for (idx, line) in lines.iter().enumerate() {
+        if let Some(key) = tracker.line(line).filter(|k| k.colon.is_some()) {
+            keys.entry(key.path.clone()).or_insert((idx, key));
+        }
+    }
+
+    // Line index to `(colon, anchor)`, and for aliases also the index of the
+    // line after the node they replace.
+    let mut anchored: HashMap<usize, (usize, &str)> = HashMap::new();
+    let mut aliased: HashMap<usize, (usize, &str, usize)> = HashMap::new();
+    for (name, paths) in groups.into_values() {
+        let hidden = |idx: usize| {
+            aliased
+                .iter()
+                .any(|(&start, &(_, _, end))| start < idx && idx < end)
+        };
+        let mut nodes: Vec<(usize, &KeyLine)> = paths
+            .iter()
+            .filter_map(|p| keys.get(*p))
+            .map(|(idx, key)| (*idx, key))
+            .filter(|&(idx, _)| !hidden(idx))
+            .collect();
+        nodes.sort_by_key(|&(idx, _)| idx);
+        nodes.dedup_by_key(|&mut (idx, _)| idx);
+        let Some((&(first, first_key), rest)) = nodes.split_first() else {
+            continue;
+        };
+        let (reference, _) = node(&lines, first, first_key);
+        for &(idx, key) in rest {
+            let (text, end) = node(&lines, idx, key);
+            if text != reference {
+                log::warn!(
+                    "{}: no longer matches anchor &{name} and is written out in full",
+                    key.path
+                );
+            } else if !anchored.keys().any(|&a| idx < a && a < end) {
+                anchored.insert(first, (first_key.colon.unwrap_or_default(), name));
+                aliased.insert(idx, (key.colon.unwrap_or_default(), name, end));
+            }
+        }
+    }
+
+    let mut out = String::with_capacity(rewritten.len());
+    let mut idx = 0;
+    while let Some(&line) = lines.get(idx) {
+        let mut next = idx + 1;
+        if let Some(&(colon, name, end)) = aliased.get(&idx) {
+            out.push_str(&line[..=colon]);
+            out.push_str(" *");
+            out.push_str(name);
+            next = end;
+        } else if let Some(&(colon, name)) = anchored.get(&idx) {
+            out.push_str(&line[..=colon]);
+            out.push_str(" &");
+            out.push_str(name);
+            out.push_str(&line[colon + 1..]);
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+        idx = next;
+    }
+    out
+}
+
+/// The text of the node whose key is on line `idx`, relative to the key's
+/// column, and the index of the line after the node.
+fn node(lines: &[&str], idx: usize, key: &KeyLine) -> (String, usize) {
+    let colon = key.colon.unwrap_or_default();
+    let mut text = lines[idx][colon + 1..].trim().to_string();
+    let mut end = idx + 1;
+    while let Some(line) = lines.get(end) {
+        let content = line.trim_start();
+        let indent = line.len() - content.len();
+        let inside = content.is_empty()
+            || indent > key.column
+            || (indent == key.column && content.starts_with('-'));
+        if !inside {
+            break;
+        }
+        text.push('\n');
+        text.push_str(line.get(key.column..).unwrap_or_default());
+        end += 1;
+    }
+    (text, end)
+}
+
+/// Comment block without blank lines at either end.
+fn trim_blank(lines: &[String]) -> &[String] {
+    let start = lines
+        .iter()
+        .position(|l| !l.is_empty())
+        .unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|l| !l.is_empty())
+        .map_or(start, |e| e + 1);
+    &lines[start..end]
+}
+
+/// Split `key: value` into the normalized key, the offset of the `:` and the
+/// raw value.
+fn split_key(code: &str) -> Option<(String, usize, &str)> {
+    let mut quote = None;
+    let bytes = code.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        match (quote, b) {
+            (None, b'"' | b'\'') if i == 0 => quote = Some(b),
+            (Some(q), _) if b == q => quote = None,
+            (None, b':') if bytes.get(i + 1).is_none_or(|&n| n == b' ') => {
+                if code.starts_with('{') || code.starts_with('[') {
+                    return None;
+                }
+                return Some((normalize(&code[..i]), i, code[i + 1..].trim()));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `value` without a leading `&anchor`.
+fn strip_anchor(value: &str) -> &str {
+    match value.strip_prefix('&') {
+        Some(rest) => rest.split_once(' ').map_or("", |(_, v)| v.trim_start()),
+        None => value,
+    }
+}
+
+/// Keys and ids compare without quotes and case: `'0xf190'` == `0xF190`.
+fn normalize(s: &str) -> String {
+    s.trim()
+        .trim_matches(|c| c == '"' || c == '\'')
+        .to_ascii_lowercase()
+}
+
+/// Offset of a ` #` comment outside quoted scalars. Quotes inside plain
+/// scalars (`driver's seat`) do not start one.
+pub(crate) fn trailing_comment(line: &str) -> Option<usize> {
+    scan(line, 0).0
+}
+
+/// [`trailing_comment`] of a line starting at flow collection depth `flow`,
+/// and the depth at the end of the line.
+fn scan(line: &str, mut flow: usize) -> (Option<usize>, usize) {
+    let mut quote = None;
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        match quote {
+            Some(b'"') if b == b'\\' => i += 1,
+            Some(b'\'') if b == b'\'' && bytes.get(i + 1) == Some(&b'\'') => i += 1,
+            Some(q) if b == q => quote = None,
+            Some(_) => {}
+            None => match b {
+                b'"' | b'\'' if scalar_start(&bytes[..i], flow) => quote = Some(b),
+                b'[' | b'{' if flow > 0 || scalar_start(&bytes[..i], flow) => flow += 1,
+                b']' | b'}' => flow = flow.saturating_sub(1),
+                b'#' if i > 0 && bytes[i - 1].is_ascii_whitespace() => return (Some(i), flow),
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+    (None, flow)
+}
+
+/// Whether a scalar may start after `before`: at the start of the content,
+/// after a `: `, `- ` or `? ` indicator, or after `[`, `{` or `,` in a flow
+/// collection.
+fn scalar_start(before: &[u8], flow: usize) -> bool {
+    let trimmed = before.trim_ascii_end();
+    let spaced = trimmed.len() < before.len();
+    match trimmed.split_last() {
+        None => true,
+        Some((b'[' | b'{' | b',', _)) => flow > 0,
+        Some((b':', _)) => spaced,
+        Some((b'-' | b'?', head)) => spaced && head.last().is_none_or(u8::is_ascii_whitespace),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comments_follow_their_keys() {
+        let original = "\
+# ECU description
+ecu:
+  # supplier name
+  name: TEST  # keep short
+dids:
+  0xf190:
+    # VIN
+    name: VIN
+    access:
+      - public
+";
+        let rewritten = "\
+ecu:
+  name: TEST
+  id: '1'
+dids:
+  0xF190:
+    access:
+    - public
+    name: VIN
+";
+        assert_eq!(
+            carry_comments(original, rewritten),
+            "\
+# ECU description
+ecu:
+  # supplier name
+  name: TEST  # keep short
+  id: '1'
+dids:
+  0xF190:
+    access:
+    - public
+    # VIN
+    name: VIN
+"
+        );
+    }
+
+    #[test]
+    fn test_sequence_items_match_by_first_pair() {
+        let original = "\
+variants:
+  - name: B  # second
+    id: 2
+  - name: A
+    # first id
+    id: 1
+";
+        let rewritten = "\
+variants:
+- name: A
+  id: 1
+- name: B
+  id: 2
+";
+        assert_eq!(
+            carry_comments(original, rewritten),
+            "\
+variants:
+- name: A
+  # first id
+  id: 1
+- name: B  # second
+  id: 2
+"
+        );
+    }
+
+    #[test]
+    fn test_block_scalars_and_quoted_hashes() {
+        let original = "\
+description: |
+  # not a comment
+  text
+url: 'a#b'  # real
+";
+        let rewritten = "description: |\n  # not a comment\n  text\nurl: a#b\n";
+        assert_eq!(
+            carry_comments(original, rewritten),
+            "description: |\n  # not a comment\n  text\nurl: a#b  # real\n"
+        );
+    }
+
+    #[test]
+    fn test_apostrophes_in_plain_scalars() {
+        assert_eq!(
+            trailing_comment("description: driver's seat  # note"),
+            Some(28)
+        );
+        assert_eq!(trailing_comment("name: 'it''s # here'  # note"), Some(22));
+        assert_eq!(trailing_comment("ids: [\"a#\", 'b']  # note"), Some(18));
+        assert_eq!(trailing_comment("url: 'a#b'"), None);
+    }
+
+    #[test]
+    fn test_duplicate_sequence_items_keep_their_comments() {
+        let original = "\
+params:
+  - name: Reserved  # first
+    bits: 4
+  - name: Reserved  # second
+    bits: 2
+";
+        let rewritten = "\
+params:
+- name: Reserved
+  bits: 4
+- name: Reserved
+  bits: 2
+";
+        assert_eq!(
+            carry_comments(original, rewritten),
+            "\
+params:
+- name: Reserved  # first
+  bits: 4
+- name: Reserved  # second
+  bits: 2
+"
+        );
+    }
+
+    #[test]
+    fn test_anchors_and_aliases_are_restored() {
+        let original = "\
+types:
+  u16: &u16
+    base: u16
+    bits: 16
+dids:
+  0xF190:
+    type: *u16  # shared
+  0xF191:
+    type: *u16
+";
+        // The writer put `dids` first and a transform changed one copy.
+        let rewritten = "\
+dids:
+  0xF190:
+    type:
+      base: u16
+      bits: 16
+  0xF191:
+    type:
+      base: u16
+      bits: 8
+types:
+  u16:
+    base: u16
+    bits: 16
+";
+        let restored = carry_comments(original, rewritten);
+        assert_eq!(
+            restored,
+            "\
+dids:
+  0xF190:
+    type: &u16  # shared
+      base: u16
+      bits: 16
+  0xF191:
+    type:
+      base: u16
+      bits: 8
+types:
+  u16: *u16
+"
+        );
+        let value = |s: &str| serde_yaml::from_str::<serde_yaml::Value>(s).unwrap();
+        assert_eq!(value(&restored), value(rewritten));
+    }
+
+    #[test]
+    fn test_multi_line_flow_collections_are_skipped() {
+        let original = "\
+ecu:
+  ids: [
+0x10, 0x20]
+  # the name
+  name: TEST
+";
+        let rewritten = "\
+ecu:
+  ids:
+  - 16
+  - 32
+  name: TEST
+";
+        assert_eq!(
+            carry_comments(original, rewritten),
+            "\
+ecu:
+  ids:
+  - 16
+  - 32
+  # the name
+  name: TEST
+"
+        );
+    }
+}
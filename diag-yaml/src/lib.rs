@@ -1,12 +1,23 @@
+pub mod anchors;
+pub mod comments;
+pub mod numerals;
 pub mod parser;
 pub mod semantic_validator;
 pub mod service_extractor;
 pub mod service_generator;
+pub mod strict;
 pub mod validator;
 pub mod writer;
 pub mod yaml_model;
 
-pub use parser::{YamlParseError, parse_yaml};
+pub use numerals::NumeralPolicy;
+pub use parser::{
+    InvalidKey, YamlParseError, invalid_keys, parse_yaml, parse_yaml_document, parse_yaml_strict,
+};
 pub use semantic_validator::{SemanticIssue, Severity, validate_semantics};
+pub use strict::{UnknownKey, unknown_keys};
 pub use validator::{SchemaError, validate_yaml_schema};
-pub use writer::{YamlWriteError, write_yaml};
+pub use writer::{
+    CAPABILITIES, SECTIONS, YamlWriteError, YamlWriteOptions, check_sections, write_yaml,
+    write_yaml_with,
+};
@@ -0,0 +1,151 @@
+//! How the YAML writer spells DID, RID and DTC numbers.
+//!
+//! The writer builds `dids`, `routines` and `dtcs` as mappings keyed by
+//! integers, which serde_yaml emits in decimal (`61840:`). The policy is
+//! applied to the serialized text afterwards: keys of those mappings are
+//! rewritten as YAML hex integers (`0xF190:`), which read back as the same
+//! numbers.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::comments::PathTracker;
+
+/// Spelling of identifier keys in YAML output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumeralPolicy {
+    /// `61840:`
+    Decimal,
+    /// `0xF190:`, `0x1234:`; no leading zeros.
+    Hex,
+    /// Hex, zero-padded to the identifier width: 4 digits for DIDs and RIDs,
+    /// 6 for DTCs (`0x00F1:`, `0x012345:`).
+    #[default]
+    PaddedHex,
+}
+
+impl NumeralPolicy {
+    pub const ALL: [Self; 3] = [Self::Decimal, Self::Hex, Self::PaddedHex];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Decimal => "decimal",
+            Self::Hex => "hex",
+            Self::PaddedHex => "padded-hex",
+        }
+    }
+
+    fn format(self, n: u64, width: usize) -> String {
+        match self {
+            Self::Decimal => n.to_string(),
+            Self::Hex => format!("0x{n:X}"),
+            Self::PaddedHex => format!("0x{n:0width$X}"),
+        }
+    }
+}
+
+impl fmt::Display for NumeralPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for NumeralPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|p| p.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                format!("unknown numeral policy '{s}' (expected decimal, hex or padded-hex)")
+            })
+    }
+}
+
+/// Hex digits of an identifier in the mapping named `section`.
+fn width_of(section: &str) -> Option<usize> {
+    match section {
+        "dids" | "routines" => Some(4),
+        "dtcs" => Some(6),
+        _ => None,
+    }
+}
+
+/// Rewrite the integer keys of `dids`, `routines` and `dtcs` mappings in
+/// writer output according to `policy`.
+pub(crate) fn apply(yaml: &str, policy: NumeralPolicy) -> String {
+    if policy == NumeralPolicy::Decimal {
+        return yaml.to_string();
+    }
+    let mut out = String::with_capacity(yaml.len());
+    let mut tracker = PathTracker::default();
+    for line in yaml.lines() {
+        let rewritten = tracker.line(line).and_then(|key| {
+            let (parent, _) = key.path.rsplit_once('/')?;
+            let section = parent.rsplit('/').next()?;
+            let width = width_of(section)?;
+            let content = &line[key.indent..];
+            let (number, rest) = content.split_once(':')?;
+            let n = number.parse::<u64>().ok()?;
+            Some(format!(
+                "{}{}:{rest}",
+                &line[..key.indent],
+                policy.format(n, width)
+            ))
+        });
+        out.push_str(rewritten.as_deref().unwrap_or(line));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = "\
+dids:
+  241:
+    name: A
+  61840:
+    name: VIN
+routines:
+  65281:
+    name: Erase
+dtcs:
+  74565:
+    name: P0123
+sessions:
+  default:
+    id: 1
+";
+
+    #[test]
+    fn test_padded_hex_keys() {
+        let out = apply(YAML, NumeralPolicy::PaddedHex);
+        assert!(out.contains("dids:\n  0x00F1:\n    name: A\n  0xF190:\n"));
+        assert!(out.contains("routines:\n  0xFF01:\n"));
+        assert!(out.contains("dtcs:\n  0x012345:\n"));
+        assert!(out.contains("    id: 1\n"), "other numbers are untouched");
+
+        let value: serde_yaml::Value = serde_yaml::from_str(&out).unwrap();
+        assert_eq!(value["dids"][61840]["name"], "VIN");
+    }
+
+    #[test]
+    fn test_hex_and_decimal() {
+        let out = apply(YAML, NumeralPolicy::Hex);
+        assert!(out.contains("  0xF1:\n") && out.contains("  0x12345:\n"));
+        assert_eq!(apply(YAML, NumeralPolicy::Decimal), YAML);
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(
+            "Padded-Hex".parse::<NumeralPolicy>(),
+            Ok(NumeralPolicy::PaddedHex)
+        );
+        assert!("octal".parse::<NumeralPolicy>().is_err());
+    }
+}
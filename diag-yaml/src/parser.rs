@@ -5,7 +5,7 @@
 
 use crate::yaml_model::*;
 use diag_ir::*;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 #[derive(Debug, thiserror::Error)]
 pub enum YamlParseError {
@@ -15,6 +15,43 @@ pub enum YamlParseError {
     MissingField(String),
     #[error("Invalid value: {0}")]
     InvalidValue(String),
+    #[error("Recursive alias: {0}")]
+    RecursiveAlias(String),
+    #[error("{} unknown key(s):{}", .0.len(), crate::strict::list(.0))]
+    UnknownKeys(Vec<crate::strict::UnknownKey>),
+    #[error("{} invalid key(s):{}", .0.len(), crate::strict::list(.0))]
+    InvalidKeys(Vec<InvalidKey>),
+}
+
+/// A key of a `dids`, `routines` or `dtcs` map that is not a number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidKey {
+    /// Path of the map holding the key, e.g. `dids` or
+    /// `protocols.UDS.routines`.
+    pub section: String,
+    /// The key as written.
+    pub key: String,
+}
+
+impl std::fmt::Display for InvalidKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}: invalid key (expected a decimal or 0x hex number up to 0xFFFFFFFF)",
+            self.section, self.key
+        )
+    }
+}
+
+impl YamlParseError {
+    /// Byte offset in the input the error was found at, when known. Errors
+    /// in documents with merge keys come from the merged value and have none.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Self::Yaml(e) => e.location().map(|l| l.index()),
+            _ => None,
+        }
+    }
 }
 
 /// Serialize a serde_yaml::Value to a canonical JSON string with sorted keys.
@@ -30,7 +67,7 @@ fn yaml_service_audience_to_ir(a: &YamlServiceAudience) -> Audience {
     let mut enabled = Vec::new();
     for g in &a.groups {
         enabled.push(AdditionalAudience {
-            short_name: g.clone(),
+            short_name: g.as_str().into(),
             long_name: None,
         });
     }
@@ -45,10 +82,91 @@ fn yaml_service_audience_to_ir(a: &YamlServiceAudience) -> Audience {
     }
 }
 
-/// Parse a YAML string into a DiagDatabase IR.
+/// Parse a YAML string into a DiagDatabase IR. DID, routine and DTC entries
+/// whose key is not a number are skipped with a warning.
 pub fn parse_yaml(yaml: &str) -> Result<DiagDatabase, YamlParseError> {
-    let doc: YamlDocument = serde_yaml::from_str(yaml)?;
-    yaml_to_ir(&doc)
+    let doc = parse_yaml_document(yaml)?;
+    for key in invalid_keys(&doc) {
+        log::warn!("{key}; entry skipped");
+    }
+    Interner::default().enter(|| yaml_to_ir(&doc))
+}
+
+/// Like [`parse_yaml`], but reject keys the format does not know instead of
+/// ignoring them (see [`crate::strict`]), and DID, routine and DTC keys that
+/// are not numbers instead of skipping their entries.
+pub fn parse_yaml_strict(yaml: &str) -> Result<DiagDatabase, YamlParseError> {
+    let unknown = crate::strict::unknown_keys(yaml)?;
+    if !unknown.is_empty() {
+        return Err(YamlParseError::UnknownKeys(unknown));
+    }
+    let doc = parse_yaml_document(yaml)?;
+    let invalid = invalid_keys(&doc);
+    if !invalid.is_empty() {
+        return Err(YamlParseError::InvalidKeys(invalid));
+    }
+    Interner::default().enter(|| yaml_to_ir(&doc))
+}
+
+/// The keys of the `dids`, `routines` and `dtcs` maps of `doc`, and of its
+/// protocol and ECU shared data layers, that are not numbers.
+pub fn invalid_keys(doc: &YamlDocument) -> Vec<InvalidKey> {
+    let mut maps: Vec<(String, Option<&serde_yaml::Value>)> = vec![
+        ("dids".into(), doc.dids.as_ref()),
+        ("routines".into(), doc.routines.as_ref()),
+        ("dtcs".into(), doc.dtcs.as_ref()),
+    ];
+    let layers = doc
+        .protocols
+        .iter()
+        .flatten()
+        .map(|(name, p)| (format!("protocols.{name}"), &p.layer))
+        .chain(
+            doc.ecu_shared_data
+                .iter()
+                .flatten()
+                .map(|(name, e)| (format!("ecu_shared_data.{name}"), &e.layer)),
+        );
+    for (path, layer) in layers {
+        maps.push((format!("{path}.dids"), layer.dids.as_ref()));
+        maps.push((format!("{path}.routines"), layer.routines.as_ref()));
+    }
+
+    let mut invalid = Vec::new();
+    for (section, map) in maps {
+        let Some(serde_yaml::Value::Mapping(map)) = map else {
+            continue;
+        };
+        for key in map.keys().filter(|k| parse_hex_key(k).is_none()) {
+            invalid.push(InvalidKey {
+                section: section.clone(),
+                key: match key {
+                    serde_yaml::Value::String(s) => s.clone(),
+                    other => serde_yaml::to_string(other)
+                        .map_or_else(|_| format!("{other:?}"), |s| s.trim().to_string()),
+                },
+            });
+        }
+    }
+    invalid
+}
+
+/// Deserialize a YAML string into the YAML model. Recursive aliases are
+/// rejected, merge keys resolved, and redefined anchors and merge conflicts
+/// logged as warnings (see [`crate::anchors`]).
+pub fn parse_yaml_document(yaml: &str) -> Result<YamlDocument, YamlParseError> {
+    let report = crate::anchors::analyze_anchors(yaml)?;
+    for warning in &report.warnings {
+        log::warn!("{warning}");
+    }
+    if !report.has_merge_keys {
+        return Ok(serde_yaml::from_str(yaml)?);
+    }
+    let mut value: serde_yaml::Value = serde_yaml::from_str(yaml)?;
+    for warning in crate::anchors::apply_merges(&mut value)? {
+        log::warn!("{warning}");
+    }
+    Ok(serde_yaml::from_value(value)?)
 }
 
 /// Transform a parsed YAML document into the canonical IR.
@@ -92,7 +210,7 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
     metadata.insert("schema".into(), doc.schema.clone());
 
     // Build named type registry for resolving type references in DIDs
-    let type_registry = build_type_registry(doc.types.as_ref());
+    let type_registry = build_type_registry(doc.types.as_ref(), doc.defaults.as_ref());
 
     // Store type definitions in IR for roundtrip
     let type_definitions: Vec<TypeDefinition> = doc
@@ -105,8 +223,15 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
                     name: name.clone(),
                     base: yt.base.clone(),
                     bit_length: yt.bit_length,
+                    bit_mask: yt
+                        .bit_mask
+                        .as_ref()
+                        .or(yt.bitmask.as_ref())
+                        .map(yaml_value_to_u64),
+                    condensed: yt.condensed,
                     min_length: yt.min_length,
                     max_length: yt.max_length,
+                    length_prefix_bits: yt.length_prefix_bits,
                     enum_values_json: yt
                         .enum_values
                         .as_ref()
@@ -131,16 +256,33 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
     // Generate ReadDataByIdentifier services from DIDs
     if let Some(serde_yaml::Value::Mapping(dids)) = &doc.dids {
         for (key, val) in dids {
-            let did_id = parse_hex_key(key);
+            let Some(did_id) = parse_hex_key(key) else {
+                continue;
+            };
             if let Ok(did) = serde_yaml::from_value::<Did>(val.clone()) {
+                let dop = did_dop(&did, &type_registry);
                 if did.readable.unwrap_or(true) {
-                    let mut svc = did_to_read_service(did_id, &did, &type_registry);
+                    let mut svc = did_to_read_service(did_id, &did, dop.clone(), &type_registry);
                     apply_access_pattern(&mut svc.diag_comm, &did.access, &access_patterns);
+                    apply_item_tags(
+                        &mut svc.diag_comm,
+                        did.features.as_deref(),
+                        did.lifecycle.as_ref(),
+                        did.requirements.as_deref(),
+                    );
+                    apply_aliases(&mut svc.diag_comm, did.aliases.as_deref(), "_Read");
                     diag_services.push(svc);
                 }
                 if did.writable.unwrap_or(false) {
-                    let mut svc = did_to_write_service(did_id, &did, &type_registry);
+                    let mut svc = did_to_write_service(did_id, &did, dop);
                     apply_access_pattern(&mut svc.diag_comm, &did.access, &access_patterns);
+                    apply_item_tags(
+                        &mut svc.diag_comm,
+                        did.features.as_deref(),
+                        did.lifecycle.as_ref(),
+                        did.requirements.as_deref(),
+                    );
+                    apply_aliases(&mut svc.diag_comm, did.aliases.as_deref(), "_Write");
                     diag_services.push(svc);
                 }
             }
@@ -150,10 +292,19 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
     // Generate RoutineControl services from routines
     if let Some(serde_yaml::Value::Mapping(routines)) = &doc.routines {
         for (key, val) in routines {
-            let rid = parse_hex_key(key);
+            let Some(rid) = parse_hex_key(key) else {
+                continue;
+            };
             if let Ok(routine) = serde_yaml::from_value::<Routine>(val.clone()) {
                 let mut svc = routine_to_service(rid, &routine, &type_registry);
                 apply_access_pattern(&mut svc.diag_comm, &routine.access, &access_patterns);
+                apply_item_tags(
+                    &mut svc.diag_comm,
+                    routine.features.as_deref(),
+                    routine.lifecycle.as_ref(),
+                    routine.requirements.as_deref(),
+                );
+                apply_aliases(&mut svc.diag_comm, routine.aliases.as_deref(), "");
                 diag_services.push(svc);
             }
         }
@@ -163,10 +314,26 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
     if let Some(yaml_services) = &doc.services {
         let svc_gen = crate::service_generator::ServiceGenerator::new(yaml_services)
             .with_sessions(doc.sessions.as_ref())
-            .with_security(doc.security.as_ref());
+            .with_security(doc.security.as_ref())
+            .with_access_patterns(Some(&access_patterns));
         diag_services.extend(svc_gen.generate_all());
     }
 
+    // Generate CheckMemory routines for data blocks with a checksum algorithm
+    let memory = doc.memory.as_ref().map(parse_memory_config).transpose()?;
+    if let Some(mc) = &memory {
+        diag_services.extend(crate::service_generator::generate_check_memory(mc));
+    }
+
+    // Negative responses for services whose `services:` entry lists NRCs,
+    // and the default NRC set for the others
+    let default_nrcs = doc
+        .defaults
+        .as_ref()
+        .and_then(|d| d.nrcs.as_deref())
+        .unwrap_or_default();
+    crate::service_generator::apply_nrcs(&mut diag_services, doc.services.as_deref(), default_nrcs);
+
     // Build ECU jobs from ecu_jobs section
     let mut single_ecu_jobs = Vec::new();
     if let Some(jobs) = &doc.ecu_jobs {
@@ -182,17 +349,35 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
         layer_sdg_vec.extend(converted.sdgs);
     }
     if let Some(ident) = &doc.identification {
-        if let Ok(ident_yaml) = serde_yaml::to_string(ident) {
-            layer_sdg_vec.push(Sdg {
-                caption_sn: "identification".into(),
-                sds: vec![SdOrSdg::Sd(Sd {
-                    value: ident_yaml,
+        // Expected idents are kept as YAML text and the part numbers get an
+        // SDG of their own; the typed fields go to `DiagDatabase::identification`.
+        if ident.expected_idents.is_some() {
+            let expected = Identification {
+                expected_idents: ident.expected_idents.clone(),
+                ..Default::default()
+            };
+            if let Ok(ident_yaml) = serde_yaml::to_string(&expected) {
+                layer_sdg_vec.push(Sdg {
+                    caption_sn: "identification".into(),
+                    sds: vec![SdOrSdg::Sd(Sd {
+                        value: ident_yaml,
+                        si: String::new(),
+                        ti: String::new(),
+                    })],
                     si: String::new(),
-                    ti: String::new(),
-                })],
-                si: String::new(),
-            });
+                });
+            }
+        }
+        let mut sdgs = None;
+        if let Some(compatibility) = &ident.compatibility {
+            set_compatibility(&mut sdgs, compatibility);
         }
+        layer_sdg_vec.extend(sdgs.into_iter().flat_map(|s| s.sdgs));
+    }
+    if let Some(validity) = doc.meta.as_ref().and_then(|m| m.validity.as_ref()) {
+        let mut sdgs = None;
+        set_validity(&mut sdgs, validity);
+        layer_sdg_vec.extend(sdgs.into_iter().flat_map(|s| s.sdgs));
     }
     if let Some(comparams) = &doc.comparams {
         if let Ok(cp_yaml) = serde_yaml::to_string(comparams) {
@@ -220,6 +405,9 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
             });
         }
     }
+    if let Some(steps) = &doc.flash_sequence {
+        layer_sdg_vec.push(flash_sequence_sdg(steps));
+    }
     if let Some(annotations) = &doc.annotations {
         let ann_json = canonical_json(annotations);
         layer_sdg_vec.push(Sdg {
@@ -257,7 +445,7 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
         dtc_map
             .iter()
             .filter_map(|(key, val)| {
-                let code = parse_hex_key(key);
+                let code = parse_hex_key(key)?;
                 serde_yaml::from_value::<YamlDtc>(val.clone())
                     .ok()
                     .map(|dtc| convert_dtc(code, &dtc))
@@ -292,7 +480,7 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
             classes
                 .iter()
                 .map(|name| FunctClass {
-                    short_name: name.clone(),
+                    short_name: name.as_str().into(),
                 })
                 .collect()
         })
@@ -310,7 +498,7 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
         for (proto_name, yaml_proto) in yaml_protocols {
             let protocol_stub = Protocol {
                 diag_layer: DiagLayer {
-                    short_name: proto_name.clone(),
+                    short_name: proto_name.as_str().into(),
                     ..Default::default()
                 },
                 com_param_spec: None,
@@ -331,7 +519,7 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
                                 complex_value: None,
                                 com_param: Some(Box::new(ComParam {
                                     com_param_type: ComParamType::Regular,
-                                    short_name: param_name.clone(),
+                                    short_name: param_name.as_str().into(),
                                     long_name: None,
                                     param_class: String::new(),
                                     cp_type: ComParamStandardisationLevel::Standard,
@@ -339,7 +527,7 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
                                     cp_usage: ComParamUsage::EcuComm,
                                     specific_data: Some(ComParamSpecificData::Regular {
                                         physical_default_value: value_str,
-                                        dop: Some(Box::new(default_comparam_dop())),
+                                        dop: Some(DopRef::new(default_comparam_dop())),
                                     }),
                                 })),
                                 protocol: Some(Box::new(protocol_stub.clone())),
@@ -386,7 +574,7 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
                                     complex_value,
                                     com_param: Some(Box::new(ComParam {
                                         com_param_type: ComParamType::Complex,
-                                        short_name: param_name.clone(),
+                                        short_name: param_name.as_str().into(),
                                         long_name: None,
                                         param_class: full.param_class.clone().unwrap_or_default(),
                                         cp_type: ComParamStandardisationLevel::Standard,
@@ -418,7 +606,7 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
                                     complex_value: None,
                                     com_param: Some(Box::new(ComParam {
                                         com_param_type: ComParamType::Regular,
-                                        short_name: param_name.clone(),
+                                        short_name: param_name.as_str().into(),
                                         long_name: None,
                                         param_class: full.param_class.clone().unwrap_or_default(),
                                         cp_type: ComParamStandardisationLevel::Standard,
@@ -427,8 +615,10 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
                                         specific_data: Some(ComParamSpecificData::Regular {
                                             physical_default_value: default_val,
                                             dop: make_comparam_dop(full.dop.as_ref())
-                                                .map(Box::new)
-                                                .or_else(|| Some(Box::new(default_comparam_dop()))),
+                                                .map(DopRef::new)
+                                                .or_else(|| {
+                                                    Some(DopRef::new(default_comparam_dop()))
+                                                }),
                                         }),
                                     })),
                                     protocol: Some(Box::new(protocol_stub.clone())),
@@ -455,8 +645,6 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
         }
     }
 
-    let memory = doc.memory.as_ref().map(parse_memory_config);
-
     // Build additional variants from variants.definitions FIRST
     // (so we can reference diag_services before moving it into the main variant)
     let mut additional_variants = Vec::new();
@@ -470,6 +658,7 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
                     doc.sessions.as_ref(),
                     doc.security.as_ref(),
                     &diag_services,
+                    default_nrcs,
                 );
                 additional_variants.push(ecu_variant);
             }
@@ -479,7 +668,7 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
     // Build the main variant containing all services
     let variant = Variant {
         diag_layer: DiagLayer {
-            short_name: ecu_name.clone(),
+            short_name: ecu_name.as_str().into(),
             long_name: None,
             funct_classes,
             com_param_refs,
@@ -488,6 +677,7 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
             state_charts,
             additional_audiences: vec![],
             sdgs,
+            description: None,
         },
         is_base_variant: true,
         variant_patterns: vec![],
@@ -498,7 +688,7 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
     let mut variants = vec![variant];
     variants.extend(additional_variants);
 
-    Ok(DiagDatabase {
+    let mut db = DiagDatabase {
         version,
         ecu_name,
         revision,
@@ -510,17 +700,32 @@ fn yaml_to_ir(doc: &YamlDocument) -> Result<DiagDatabase, YamlParseError> {
         dtcs,
         memory,
         type_definitions,
-    })
+        transport: parse_transport(ecu),
+        identification: doc
+            .identification
+            .as_ref()
+            .map(ecu_identification_from_yaml)
+            .transpose()?
+            .filter(|i| !i.is_empty()),
+    };
+    complete_transport(&mut db);
+    Ok(db)
 }
 
-/// Registry of named types for resolving type references in DIDs.
+/// Registry of named types for resolving type references in DIDs, with the
+/// defaults the types are converted under.
 struct TypeRegistry {
     types: BTreeMap<String, YamlType>,
+    defaults: YamlDefaults,
 }
 
-fn build_type_registry(types: Option<&BTreeMap<String, YamlType>>) -> TypeRegistry {
+fn build_type_registry(
+    types: Option<&BTreeMap<String, YamlType>>,
+    defaults: Option<&YamlDefaults>,
+) -> TypeRegistry {
     TypeRegistry {
         types: types.cloned().unwrap_or_default(),
+        defaults: defaults.cloned().unwrap_or_default(),
     }
 }
 
@@ -581,7 +786,7 @@ fn yaml_struct_to_dop(name: &str, yaml_type: &YamlType, registry: &TypeRegistry)
             let field_dop = field_yaml_type.as_ref().map_or_else(
                 || Dop {
                     dop_type: DopType::Regular,
-                    short_name: field_name.clone(),
+                    short_name: field_name.as_str().into(),
                     sdgs: None,
                     specific_data: None,
                 },
@@ -598,7 +803,7 @@ fn yaml_struct_to_dop(name: &str, yaml_type: &YamlType, registry: &TypeRegistry)
             params.push(Param {
                 id: idx as u32,
                 param_type: ParamType::Value,
-                short_name: field_name,
+                short_name: field_name.into(),
                 semantic: "DATA".into(),
                 sdgs: None,
                 physical_default_value: String::new(),
@@ -606,7 +811,7 @@ fn yaml_struct_to_dop(name: &str, yaml_type: &YamlType, registry: &TypeRegistry)
                 bit_position: Some(0),
                 specific_data: Some(ParamData::Value {
                     physical_default_value: String::new(),
-                    dop: Box::new(field_dop),
+                    dop: DopRef::new(field_dop),
                 }),
             });
 
@@ -644,7 +849,18 @@ fn yaml_type_to_dop(name: &str, yaml_type: &YamlType, registry: &TypeRegistry) -
 
     let (base_data_type, phys_data_type) = base_type_to_data_type(&yaml_type.base);
 
-    let is_high_low = yaml_type.endian.as_deref().is_none_or(|e| e == "big");
+    let defaults = &registry.defaults;
+    let is_high_low = yaml_type
+        .endian
+        .as_deref()
+        .or(defaults.endian.as_deref())
+        .is_none_or(|e| e == "big");
+    let display_radix = yaml_type
+        .display_radix
+        .as_deref()
+        .or(defaults.display_radix.as_deref())
+        .and_then(parse_radix)
+        .unwrap_or(Radix::Dec);
 
     let bit_length = yaml_type
         .bit_length
@@ -655,19 +871,30 @@ fn yaml_type_to_dop(name: &str, yaml_type: &YamlType, registry: &TypeRegistry) -
     let compu_method = Some(build_compu_method(yaml_type));
 
     let diag_coded_type = DiagCodedType {
-        type_name: if yaml_type.min_length.is_some() || yaml_type.max_length.is_some() {
+        type_name: if yaml_type.length_prefix_bits.is_some() {
+            DiagCodedTypeName::LeadingLengthInfoType
+        } else if yaml_type.min_length.is_some() || yaml_type.max_length.is_some() {
             DiagCodedTypeName::MinMaxLengthType
         } else {
             DiagCodedTypeName::StandardLengthType
         },
         base_type_encoding: if yaml_type.base.starts_with('s') || yaml_type.base.starts_with('i') {
-            "signed".into()
+            yaml_type
+                .signed_encoding
+                .as_deref()
+                .or(defaults.signed_encoding.as_deref())
+                .unwrap_or("signed")
+                .into()
         } else {
             "unsigned".into()
         },
         base_data_type,
         is_high_low_byte_order: is_high_low,
-        specific_data: if yaml_type.min_length.is_some() || yaml_type.max_length.is_some() {
+        specific_data: if let Some(prefix_bits) = yaml_type.length_prefix_bits {
+            Some(DiagCodedTypeData::LeadingLength {
+                bit_length: prefix_bits,
+            })
+        } else if yaml_type.min_length.is_some() || yaml_type.max_length.is_some() {
             let termination = match yaml_type.termination.as_deref() {
                 Some("zero") => Termination::Zero,
                 Some("hex_ff") | Some("hexff") => Termination::HexFf,
@@ -681,15 +908,20 @@ fn yaml_type_to_dop(name: &str, yaml_type: &YamlType, registry: &TypeRegistry) -
         } else {
             bit_length.map(|bl| DiagCodedTypeData::StandardLength {
                 bit_length: bl,
-                bit_mask: vec![],
-                condensed: false,
+                bit_mask: yaml_type
+                    .bit_mask
+                    .as_ref()
+                    .or(yaml_type.bitmask.as_ref())
+                    .map(|mask| mask_bytes(yaml_value_to_u64(mask), bl))
+                    .unwrap_or_default(),
+                condensed: yaml_type.condensed.unwrap_or(false),
             })
         },
     };
 
     // Build unit if present
     let unit_ref = yaml_type.unit.as_ref().map(|u| Unit {
-        short_name: u.clone(),
+        short_name: u.as_str().into(),
         display_name: u.clone(),
         factor_si_to_unit: None,
         offset_si_to_unit: None,
@@ -729,7 +961,7 @@ fn yaml_type_to_dop(name: &str, yaml_type: &YamlType, registry: &TypeRegistry) -
             physical_type: Some(PhysicalType {
                 precision: None,
                 base_data_type: phys_data_type,
-                display_radix: Radix::Dec,
+                display_radix,
             }),
             internal_constr,
             unit_ref,
@@ -812,22 +1044,33 @@ fn build_compu_method(yaml_type: &YamlType) -> CompuMethod {
     }
 }
 
-/// Create a ReadDataByIdentifier (0x22) service from a DID definition.
-fn did_to_read_service(did_id: u32, did: &Did, registry: &TypeRegistry) -> DiagService {
+/// The DOP of a DID's data, shared by its read and write services.
+fn did_dop(did: &Did, registry: &TypeRegistry) -> DopRef {
     let (yaml_type, _type_key) = resolve_did_type(&did.did_type, registry);
     let dop_name = yaml_type
         .as_ref()
         .map_or_else(|| did.name.clone(), |t| cda_dop_name_for_type(t, &did.name));
-    let data_param_name = did.param_name.as_deref().unwrap_or(&did.name);
-    let dop = yaml_type.as_ref().map_or_else(
+    DopRef::new(yaml_type.as_ref().map_or_else(
         || Dop {
             dop_type: DopType::Regular,
-            short_name: dop_name.clone(),
+            short_name: dop_name.as_str().into(),
             sdgs: None,
             specific_data: None,
         },
         |t| yaml_type_to_dop(&dop_name, t, registry),
-    );
+    ))
+}
+
+/// Create a ReadDataByIdentifier (0x22) service from a DID definition, with
+/// `dop` (see [`did_dop`]) for its data.
+fn did_to_read_service(
+    did_id: u32,
+    did: &Did,
+    dop: DopRef,
+    registry: &TypeRegistry,
+) -> DiagService {
+    let (_yaml_type, type_key) = resolve_did_type(&did.did_type, registry);
+    let data_param_name = did.param_name.as_deref().unwrap_or(&did.name);
 
     // Preserve DID-specific YAML fields in an SDG for roundtrip
     let mut did_extra = serde_json::Map::new();
@@ -838,6 +1081,11 @@ fn did_to_read_service(did_id: u32, did: &Did, registry: &TypeRegistry) -> DiagS
         let json_val = serde_json::to_value(ioc).unwrap_or_default();
         did_extra.insert("io_control".into(), json_val);
     }
+    // Named types are otherwise inlined into the DOP; keep the reference so
+    // the writer can rebuild `type: <name>` instead of a per-DID type.
+    if let Some(name) = type_key.filter(|name| registry.types.contains_key(name)) {
+        did_extra.insert("type".into(), serde_json::Value::String(name));
+    }
     let did_sdgs = if did_extra.is_empty() {
         None
     } else {
@@ -857,9 +1105,9 @@ fn did_to_read_service(did_id: u32, did: &Did, registry: &TypeRegistry) -> DiagS
 
     DiagService {
         diag_comm: DiagComm {
-            short_name: format!("{}_Read", did.name),
+            short_name: format!("{}_Read", did.name).into(),
             long_name: None,
-            semantic: String::new(),
+            semantic: Name::default(),
             funct_classes: vec![FunctClass {
                 short_name: "Ident".into(),
             }],
@@ -872,6 +1120,7 @@ fn did_to_read_service(did_id: u32, did: &Did, registry: &TypeRegistry) -> DiagS
             is_mandatory: false,
             is_executable: true,
             is_final: false,
+            description: did.description.clone(),
         },
         request: Some(Request {
             params: vec![
@@ -941,7 +1190,7 @@ fn did_to_read_service(did_id: u32, did: &Did, registry: &TypeRegistry) -> DiagS
                 Param {
                     id: 2,
                     param_type: ParamType::Value,
-                    short_name: data_param_name.to_string(),
+                    short_name: data_param_name.into(),
                     semantic: "DATA".into(),
                     sdgs: None,
                     physical_default_value: String::new(),
@@ -949,11 +1198,13 @@ fn did_to_read_service(did_id: u32, did: &Did, registry: &TypeRegistry) -> DiagS
                     bit_position: None,
                     specific_data: Some(ParamData::Value {
                         physical_default_value: String::new(),
-                        dop: Box::new(dop),
+                        dop,
                     }),
                 },
             ],
             sdgs: None,
+            pos_response_suffix: None,
+            audience: None,
         }],
         neg_responses: vec![],
         is_cyclic: false,
@@ -964,28 +1215,16 @@ fn did_to_read_service(did_id: u32, did: &Did, registry: &TypeRegistry) -> DiagS
     }
 }
 
-/// Create a WriteDataByIdentifier (0x2E) service from a DID definition.
-fn did_to_write_service(did_id: u32, did: &Did, registry: &TypeRegistry) -> DiagService {
-    let (yaml_type, _type_key) = resolve_did_type(&did.did_type, registry);
-    let dop_name = yaml_type
-        .as_ref()
-        .map_or_else(|| did.name.clone(), |t| cda_dop_name_for_type(t, &did.name));
+/// Create a WriteDataByIdentifier (0x2E) service from a DID definition, with
+/// `dop` (see [`did_dop`]) for its data.
+fn did_to_write_service(did_id: u32, did: &Did, dop: DopRef) -> DiagService {
     let data_param_name = did.param_name.as_deref().unwrap_or(&did.name);
-    let dop = yaml_type.as_ref().map_or_else(
-        || Dop {
-            dop_type: DopType::Regular,
-            short_name: dop_name.clone(),
-            sdgs: None,
-            specific_data: None,
-        },
-        |t| yaml_type_to_dop(&dop_name, t, registry),
-    );
 
     DiagService {
         diag_comm: DiagComm {
-            short_name: format!("{}_Write", did.name),
+            short_name: format!("{}_Write", did.name).into(),
             long_name: None,
-            semantic: String::new(),
+            semantic: Name::default(),
             funct_classes: vec![FunctClass {
                 short_name: "Ident".into(),
             }],
@@ -998,6 +1237,7 @@ fn did_to_write_service(did_id: u32, did: &Did, registry: &TypeRegistry) -> Diag
             is_mandatory: false,
             is_executable: true,
             is_final: false,
+            description: did.description.clone(),
         },
         request: Some(Request {
             params: vec![
@@ -1032,7 +1272,7 @@ fn did_to_write_service(did_id: u32, did: &Did, registry: &TypeRegistry) -> Diag
                 Param {
                     id: 2,
                     param_type: ParamType::Value,
-                    short_name: data_param_name.to_string(),
+                    short_name: data_param_name.into(),
                     semantic: "DATA".into(),
                     sdgs: None,
                     physical_default_value: String::new(),
@@ -1040,7 +1280,7 @@ fn did_to_write_service(did_id: u32, did: &Did, registry: &TypeRegistry) -> Diag
                     bit_position: None,
                     specific_data: Some(ParamData::Value {
                         physical_default_value: String::new(),
-                        dop: Box::new(dop),
+                        dop,
                     }),
                 },
             ],
@@ -1079,6 +1319,8 @@ fn did_to_write_service(did_id: u32, did: &Did, registry: &TypeRegistry) -> Diag
                 },
             ],
             sdgs: None,
+            pos_response_suffix: None,
+            audience: None,
         }],
         neg_responses: vec![],
         is_cyclic: false,
@@ -1123,93 +1365,43 @@ fn routine_to_service(rid: u32, routine: &Routine, registry: &TypeRegistry) -> D
     ];
 
     // Add start input params if present
-    if let Some(params) = &routine.parameters {
-        if let Some(start) = params.get("start") {
-            if let Some(inputs) = &start.input {
-                for (id, input) in (2u32..).zip(inputs) {
-                    let yaml_type: Option<YamlType> =
-                        serde_yaml::from_value(input.param_type.clone()).ok();
-                    let dop = yaml_type.as_ref().map_or_else(
-                        || Dop {
-                            dop_type: DopType::Regular,
-                            short_name: input.name.clone(),
-                            sdgs: None,
-                            specific_data: None,
-                        },
-                        |t| yaml_type_to_dop(&input.name, t, registry),
-                    );
-                    request_params.push(Param {
-                        id,
-                        param_type: ParamType::Value,
-                        short_name: input.name.clone(),
-                        semantic: input.semantic.clone().unwrap_or_else(|| "DATA".into()),
-                        sdgs: None,
-                        physical_default_value: String::new(),
-                        byte_position: None,
-                        bit_position: None,
-                        specific_data: Some(ParamData::Value {
-                            physical_default_value: String::new(),
-                            dop: Box::new(dop),
-                        }),
-                    });
-                }
-            }
-        }
+    if let Some(inputs) = routine
+        .parameters
+        .as_ref()
+        .and_then(|p| p.get("start"))
+        .and_then(|start| start.input.as_ref())
+    {
+        request_params.extend(routine_params_to_ir(inputs, 2, true, registry));
     }
 
     // Build positive response from result output params
     let mut pos_responses = Vec::new();
-    if let Some(params) = &routine.parameters {
-        if let Some(result) = params.get("result") {
-            if let Some(outputs) = &result.output {
-                let mut resp_params = Vec::new();
-                for (id, output) in outputs.iter().enumerate() {
-                    let yaml_type: Option<YamlType> =
-                        serde_yaml::from_value(output.param_type.clone()).ok();
-                    let dop = yaml_type.as_ref().map_or_else(
-                        || Dop {
-                            dop_type: DopType::Regular,
-                            short_name: output.name.clone(),
-                            sdgs: None,
-                            specific_data: None,
-                        },
-                        |t| yaml_type_to_dop(&output.name, t, registry),
-                    );
-                    let id = id as u32;
-                    resp_params.push(Param {
-                        id,
-                        param_type: ParamType::Value,
-                        short_name: output.name.clone(),
-                        semantic: "DATA".into(),
-                        sdgs: None,
-                        physical_default_value: String::new(),
-                        byte_position: None,
-                        bit_position: None,
-                        specific_data: Some(ParamData::Value {
-                            physical_default_value: String::new(),
-                            dop: Box::new(dop),
-                        }),
-                    });
-                }
-                if !resp_params.is_empty() {
-                    pos_responses.push(Response {
-                        response_type: ResponseType::PosResponse,
-                        params: resp_params,
-                        sdgs: None,
-                    });
-                }
-            }
+    if let Some(outputs) = routine
+        .parameters
+        .as_ref()
+        .and_then(|p| p.get("result"))
+        .and_then(|result| result.output.as_ref())
+    {
+        let resp_params = routine_params_to_ir(outputs, 0, false, registry);
+        if !resp_params.is_empty() {
+            pos_responses.push(Response {
+                response_type: ResponseType::PosResponse,
+                params: resp_params,
+                sdgs: None,
+                pos_response_suffix: None,
+                audience: None,
+            });
         }
     }
 
     DiagService {
         diag_comm: DiagComm {
-            short_name: routine.name.clone(),
+            short_name: routine.name.as_str().into(),
             long_name: routine.description.as_ref().map(|d| LongName {
                 value: d.clone(),
                 ti: String::new(),
             }),
-            semantic: String::new(),
+            semantic: Name::default(),
             funct_classes: vec![],
             sdgs: None,
             diag_class_type: DiagClassType::StartComm,
@@ -1220,6 +1412,7 @@ fn routine_to_service(rid: u32, routine: &Routine, registry: &TypeRegistry) -> D
             is_mandatory: false,
             is_executable: true,
             is_final: false,
+            description: routine.description.clone(),
         },
         request: Some(Request {
             params: request_params,
@@ -1235,6 +1428,101 @@ fn routine_to_service(rid: u32, routine: &Routine, registry: &TypeRegistry) -> D
     }
 }
 
+/// Convert routine params to IR params numbered from `first_id`. Inputs keep
+/// their `semantic`; outputs are always `DATA`.
+///
+/// Besides plain values a param can be a `reserved` filler, a `sys_param`
+/// SYSTEM param, or carry a `length_key` naming the param (of the same list)
+/// that holds its length, which then becomes a LENGTH-KEY.
+fn routine_params_to_ir(
+    params: &[RoutineParam],
+    first_id: u32,
+    keep_semantic: bool,
+    registry: &TypeRegistry,
+) -> Vec<Param> {
+    let length_keys: HashSet<&str> = params
+        .iter()
+        .filter_map(|p| p.length_key.as_deref())
+        .collect();
+    let mut ir_params: Vec<Param> = Vec::with_capacity(params.len());
+    for (id, param) in (first_id..).zip(params) {
+        let semantic = match &param.semantic {
+            Some(s) if keep_semantic => s.clone(),
+            _ => "DATA".into(),
+        };
+        let mut ir_param = Param {
+            id,
+            param_type: ParamType::Value,
+            short_name: param.name.as_str().into(),
+            semantic: semantic.into(),
+            sdgs: None,
+            physical_default_value: String::new(),
+            byte_position: None,
+            bit_position: None,
+            specific_data: None,
+        };
+        if let Some(bit_length) = param.reserved {
+            ir_param.param_type = ParamType::Reserved;
+            ir_param.specific_data = Some(ParamData::Reserved { bit_length });
+            ir_params.push(ir_param);
+            continue;
+        }
+
+        let yaml_type: Option<YamlType> = serde_yaml::from_value(param.param_type.clone()).ok();
+        let mut dop = yaml_type.as_ref().map_or_else(
+            || Dop {
+                dop_type: DopType::Regular,
+                short_name: param.name.as_str().into(),
+                sdgs: None,
+                specific_data: None,
+            },
+            |t| yaml_type_to_dop(&param.name, t, registry),
+        );
+        // Keys precede the params they measure, so they are already converted.
+        if let Some(key) = param
+            .length_key
+            .as_deref()
+            .and_then(|key| ir_params.iter().find(|p| p.short_name == key))
+        {
+            if let Some(DopData::NormalDop {
+                diag_coded_type: Some(dct),
+                ..
+            }) = &mut dop.specific_data
+            {
+                dct.type_name = DiagCodedTypeName::ParamLengthInfoType;
+                dct.specific_data = Some(DiagCodedTypeData::ParamLength {
+                    length_key: Box::new(key.clone()),
+                });
+            }
+        }
+
+        let dop = DopRef::new(dop);
+        let (param_type, specific_data) = if let Some(sys_param) = &param.sys_param {
+            (
+                ParamType::System,
+                ParamData::System {
+                    dop,
+                    sys_param: sys_param.clone(),
+                },
+            )
+        } else if length_keys.contains(param.name.as_str()) {
+            (ParamType::LengthKey, ParamData::LengthKeyRef { dop })
+        } else {
+            (
+                ParamType::Value,
+                ParamData::Value {
+                    physical_default_value: String::new(),
+                    dop,
+                },
+            )
+        };
+        ir_param.param_type = param_type;
+        ir_param.specific_data = Some(specific_data);
+        ir_params.push(ir_param);
+    }
+    ir_params
+}
+
 /// Convert an ECU job definition to IR SingleEcuJob.
 fn ecu_job_to_ir(job: &EcuJob, registry: &TypeRegistry) -> SingleEcuJob {
     let convert_job_params = |params: &Option<Vec<JobParamDef>>| -> Vec<JobParam> {
@@ -1247,9 +1535,9 @@ fn ecu_job_to_ir(job: &EcuJob, registry: &TypeRegistry) -> SingleEcuJob {
                             serde_yaml::from_value(p.param_type.clone()).ok();
                         let dop_base = yaml_type
                             .as_ref()
-                            .map(|t| Box::new(yaml_type_to_dop(&p.name, t, registry)));
+                            .map(|t| DopRef::new(yaml_type_to_dop(&p.name, t, registry)));
                         JobParam {
-                            short_name: p.name.clone(),
+                            short_name: p.name.as_str().into(),
                             long_name: p.description.as_ref().map(|d| LongName {
                                 value: d.clone(),
                                 ti: String::new(),
@@ -1257,10 +1545,10 @@ fn ecu_job_to_ir(job: &EcuJob, registry: &TypeRegistry) -> SingleEcuJob {
                             physical_default_value: p
                                 .default_value
                                 .as_ref()
-                                .map(yaml_value_to_string)
+                                .map(yaml_default_to_string)
                                 .unwrap_or_default(),
                             dop_base,
-                            semantic: p.semantic.clone().unwrap_or_default(),
+                            semantic: p.semantic.as_deref().unwrap_or_default().into(),
                         }
                     })
                     .collect()
@@ -1270,7 +1558,7 @@ fn ecu_job_to_ir(job: &EcuJob, registry: &TypeRegistry) -> SingleEcuJob {
 
     SingleEcuJob {
         diag_comm: DiagComm {
-            short_name: job.name.clone(),
+            short_name: job.name.as_str().into(),
             long_name: job.description.as_ref().map(|d| LongName {
                 value: d.clone(),
                 ti: String::new(),
@@ -1286,6 +1574,7 @@ fn ecu_job_to_ir(job: &EcuJob, registry: &TypeRegistry) -> SingleEcuJob {
             is_mandatory: false,
             is_executable: true,
             is_final: false,
+            description: job.description.clone(),
         },
         prog_codes: job
             .prog_code
@@ -1385,37 +1674,50 @@ fn convert_dtc(trouble_code: u32, yaml_dtc: &YamlDtc) -> Dtc {
         }
     }
 
+    let mut sdgs = if sdg_entries.is_empty() {
+        None
+    } else {
+        Some(Sdgs { sdgs: sdg_entries })
+    };
+    if let Some(lifecycle) = &yaml_dtc.lifecycle {
+        set_lifecycle(&mut sdgs, lifecycle);
+    }
+    for (lang, text) in &yaml_dtc.translations {
+        set_dtc_translation(&mut sdgs, lang, text);
+    }
+
     Dtc {
-        short_name: yaml_dtc.name.clone(),
+        short_name: yaml_dtc.name.as_str().into(),
         trouble_code,
         display_trouble_code: yaml_dtc.sae.clone(),
         text: yaml_dtc.description.as_ref().map(|d| Text {
             value: d.clone(),
-            ti: String::new(),
+            ti: yaml_dtc.text_id.clone().unwrap_or_default(),
         }),
         level: yaml_dtc.severity,
-        sdgs: if sdg_entries.is_empty() {
-            None
-        } else {
-            Some(Sdgs { sdgs: sdg_entries })
-        },
+        sdgs,
         is_temporary: false,
     }
 }
 
 // --- Helpers ---
 
-fn parse_hex_key(key: &serde_yaml::Value) -> u32 {
+/// The number a DID, routine or DTC key names: decimal, or hex with `0x`.
+/// `None` for anything else, including numbers above 32 bits.
+fn parse_hex_key(key: &serde_yaml::Value) -> Option<u32> {
     match key {
-        serde_yaml::Value::Number(n) => n.as_u64().unwrap_or(0) as u32,
-        serde_yaml::Value::String(s) => {
-            if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
-                u32::from_str_radix(hex, 16).unwrap_or(0)
-            } else {
-                s.parse::<u32>().unwrap_or(0)
-            }
-        }
-        _ => 0,
+        serde_yaml::Value::Number(n) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+        serde_yaml::Value::String(s) => diag_ir::parse_uint(s).and_then(|n| u32::try_from(n).ok()),
+        _ => None,
+    }
+}
+
+/// Like [`yaml_value_to_string`], but `{ comparam: NAME }` becomes a comparam
+/// reference resolved at convert time.
+fn yaml_default_to_string(v: &serde_yaml::Value) -> String {
+    match v.get("comparam").and_then(serde_yaml::Value::as_str) {
+        Some(name) => diag_ir::comparam_defaults::comparam_reference(name),
+        None => yaml_value_to_string(v),
     }
 }
 
@@ -1447,6 +1749,17 @@ fn base_type_to_data_type(base: &str) -> (DataType, PhysicalTypeDataType) {
     }
 }
 
+/// A `display_radix` value: `hex`, `dec`, `bin` or `oct`.
+fn parse_radix(radix: &str) -> Option<Radix> {
+    match radix.to_ascii_lowercase().as_str() {
+        "hex" => Some(Radix::Hex),
+        "dec" => Some(Radix::Dec),
+        "bin" => Some(Radix::Bin),
+        "oct" => Some(Radix::Oct),
+        _ => None,
+    }
+}
+
 fn default_bit_length(base: &str) -> Option<u32> {
     match base {
         "u8" | "s8" => Some(8),
@@ -1485,9 +1798,35 @@ fn uint16_coded_type() -> DiagCodedType {
     }
 }
 
+// --- Transport ---
+
+/// DoIP addressing from `ecu.addressing.doip`. Addresses are numbers or `0x`
+/// strings; values that do not parse are left unset, so the DoIP comparams
+/// can still supply them.
+fn parse_transport(ecu: Option<&Ecu>) -> Option<TransportConfig> {
+    let doip = ecu?.addressing.as_ref()?.get("doip")?;
+    let text = |key: &str| match doip.get(key)? {
+        serde_yaml::Value::String(s) => Some(s.clone()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    };
+    let address = |key: &str| text(key).and_then(|v| transport::parse_address(&v));
+    let entity_id = |key: &str| text(key).and_then(|v| transport::parse_entity_id(&v));
+    let config = DoipConfig {
+        ip: text("ip"),
+        port: address("port"),
+        logical_address: address("logical_address"),
+        functional_address: address("functional_address"),
+        tester_address: address("tester_address"),
+        eid: entity_id("eid"),
+        gid: entity_id("gid"),
+    };
+    (!config.is_empty()).then_some(TransportConfig { doip: Some(config) })
+}
+
 // --- Memory config ---
 
-fn parse_memory_config(mc: &YamlMemoryConfig) -> MemoryConfig {
+fn parse_memory_config(mc: &YamlMemoryConfig) -> Result<MemoryConfig, YamlParseError> {
     let default_address_format = mc
         .default_address_format
         .as_ref()
@@ -1541,49 +1880,112 @@ fn parse_memory_config(mc: &YamlMemoryConfig) -> MemoryConfig {
         .map(|blocks| {
             blocks
                 .values()
-                .map(|b| DataBlock {
-                    name: b.name.clone(),
-                    description: b.description.clone(),
-                    block_type: match b.block_type.as_str() {
-                        "upload" => DataBlockType::Upload,
-                        _ => DataBlockType::Download,
-                    },
-                    memory_address: b.memory_address,
-                    memory_size: b.memory_size,
-                    format: match b.format.as_str() {
-                        "encrypted" => DataBlockFormat::Encrypted,
-                        "compressed" => DataBlockFormat::Compressed,
-                        "encrypted_compressed" => DataBlockFormat::EncryptedCompressed,
-                        _ => DataBlockFormat::Raw,
-                    },
-                    max_block_length: b.max_block_length,
-                    security_level: b.security_level.clone(),
-                    session: b.session.clone(),
-                    checksum_type: b.checksum_type.clone(),
+                .map(|b| {
+                    Ok(DataBlock {
+                        name: b.name.clone(),
+                        description: b.description.clone(),
+                        block_type: match b.block_type.as_str() {
+                            "upload" => DataBlockType::Upload,
+                            _ => DataBlockType::Download,
+                        },
+                        memory_address: b.memory_address,
+                        memory_size: b.memory_size,
+                        format: match b.format.as_str() {
+                            "encrypted" => DataBlockFormat::Encrypted,
+                            "compressed" => DataBlockFormat::Compressed,
+                            "encrypted_compressed" => DataBlockFormat::EncryptedCompressed,
+                            _ => DataBlockFormat::Raw,
+                        },
+                        max_block_length: b.max_block_length,
+                        security_level: b.security_level.clone(),
+                        session: b.session.clone(),
+                        checksum_type: b
+                            .checksum_type
+                            .as_ref()
+                            .map(|c| parse_checksum_type(&b.name, c))
+                            .transpose()?,
+                    })
                 })
-                .collect()
+                .collect::<Result<Vec<_>, YamlParseError>>()
         })
+        .transpose()?
         .unwrap_or_default();
 
-    MemoryConfig {
+    Ok(MemoryConfig {
         default_address_format,
         regions,
         data_blocks,
+    })
+}
+
+fn parse_checksum_type(
+    block: &str,
+    checksum: &YamlChecksumType,
+) -> Result<ChecksumType, YamlParseError> {
+    match checksum {
+        YamlChecksumType::Named(name) => ChecksumType::from_name(name).ok_or_else(|| {
+            YamlParseError::InvalidValue(format!(
+                "data block '{block}': unknown checksum_type '{name}' (expected {} \
+                 or an `oem:` mapping)",
+                ChecksumType::STANDARD_NAMES.join(", ")
+            ))
+        }),
+        YamlChecksumType::Oem { length: 0, .. } => Err(YamlParseError::InvalidValue(format!(
+            "data block '{block}': OEM checksum length must be at least 1 byte"
+        ))),
+        YamlChecksumType::Oem {
+            oem,
+            length,
+            parameters,
+        } => Ok(ChecksumType::Oem {
+            name: oem.clone(),
+            length: *length,
+            parameters: parameters.clone(),
+        }),
     }
 }
 
 // --- Sessions and security -> state chart ---
 
+/// Big-endian bytes of `mask`, as wide as a `bit_length`-bit coded value.
+fn mask_bytes(mask: u64, bit_length: u32) -> Vec<u8> {
+    let width = bit_length.div_ceil(8).clamp(1, 8) as usize;
+    mask.to_be_bytes()[8 - width..].to_vec()
+}
+
+/// The typed fields of the YAML `identification:` section. A DID that is not
+/// a 16-bit number is an error.
+fn ecu_identification_from_yaml(
+    ident: &Identification,
+) -> Result<EcuIdentification, YamlParseError> {
+    Ok(EcuIdentification {
+        part_number: ident.part_number.clone(),
+        supplier_code: ident.supplier_code.clone(),
+        hardware_version: ident.hardware_version.clone(),
+        software_version: ident.software_version.clone(),
+        dids: ident
+            .dids
+            .iter()
+            .flatten()
+            .map(|(role, did)| {
+                parse_hex_key(did)
+                    .and_then(|did| u16::try_from(did).ok())
+                    .map(|did| (role.clone(), did))
+                    .ok_or_else(|| {
+                        YamlParseError::InvalidValue(format!(
+                            "identification DID '{role}': '{}' is not a 16-bit DID",
+                            yaml_value_to_string(did)
+                        ))
+                    })
+            })
+            .collect::<Result<_, _>>()?,
+    })
+}
+
 fn yaml_value_to_u64(v: &serde_yaml::Value) -> u64 {
     match v {
         serde_yaml::Value::Number(n) => n.as_u64().unwrap_or(0),
-        serde_yaml::Value::String(s) => {
-            if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
-                u64::from_str_radix(hex, 16).unwrap_or(0)
-            } else {
-                s.parse().unwrap_or(0)
-            }
-        }
+        serde_yaml::Value::String(s) => diag_ir::parse_uint(s).unwrap_or(0),
         _ => 0,
     }
 }
@@ -1613,7 +2015,7 @@ fn parse_sessions_to_state_chart(
                 .cloned()
                 .unwrap_or_else(|| key.clone());
             State {
-                short_name: cda_name,
+                short_name: cda_name.into(),
                 long_name: Some(LongName {
                     value: id.to_string(),
                     ti: key.clone(), // Store YAML key for roundtrip
@@ -1647,7 +2049,7 @@ fn parse_sessions_to_state_chart(
                         .cloned()
                         .unwrap_or_else(|| to.clone());
                     result.push(StateTransition {
-                        short_name: format!("{cda_from}_to_{cda_to}"),
+                        short_name: format!("{cda_from}_to_{cda_to}").into(),
                         source_short_name_ref: cda_from.clone(),
                         target_short_name_ref: cda_to.clone(),
                     });
@@ -1659,7 +2061,7 @@ fn parse_sessions_to_state_chart(
 
     StateChart {
         short_name: "Session".into(),
-        semantic: String::new(),
+        semantic: Name::default(),
         state_transitions,
         start_state_short_name_ref: start_state,
         states,
@@ -1696,7 +2098,7 @@ fn parse_security_to_state_chart(security: &BTreeMap<String, SecurityLevel>) ->
             .cloned()
             .unwrap_or_else(|| key.clone());
         states.push(State {
-            short_name: cda_name,
+            short_name: cda_name.into(),
             long_name: Some(LongName {
                 value: level.level.to_string(),
                 ti: key.clone(), // Store YAML key for roundtrip
@@ -1716,7 +2118,7 @@ fn parse_security_to_state_chart(security: &BTreeMap<String, SecurityLevel>) ->
             .cloned()
             .unwrap_or_else(|| key.clone());
         transitions.push(StateTransition {
-            short_name: format!("Locked_to_{cda_name}"),
+            short_name: format!("Locked_to_{cda_name}").into(),
             source_short_name_ref: "Locked".into(),
             target_short_name_ref: cda_name.clone(),
         });
@@ -1727,7 +2129,7 @@ fn parse_security_to_state_chart(security: &BTreeMap<String, SecurityLevel>) ->
             .cloned()
             .unwrap_or_else(|| key.clone());
         transitions.push(StateTransition {
-            short_name: format!("{cda_name}_to_Locked"),
+            short_name: format!("{cda_name}_to_Locked").into(),
             source_short_name_ref: cda_name,
             target_short_name_ref: "Locked".into(),
         });
@@ -1735,7 +2137,7 @@ fn parse_security_to_state_chart(security: &BTreeMap<String, SecurityLevel>) ->
 
     StateChart {
         short_name: "SecurityAccess".into(),
-        semantic: String::new(),
+        semantic: Name::default(),
         state_transitions: transitions,
         start_state_short_name_ref: "Locked".into(),
         states,
@@ -1752,7 +2154,7 @@ fn parse_authentication_to_state_chart(auth: &Authentication) -> Option<StateCha
         .map(|(key, role_val)| {
             let id = role_val.get("id").map_or(0, yaml_value_to_u64);
             State {
-                short_name: key.clone(),
+                short_name: key.as_str().into(),
                 long_name: Some(LongName {
                     value: id.to_string(),
                     ti: String::new(),
@@ -1763,7 +2165,7 @@ fn parse_authentication_to_state_chart(auth: &Authentication) -> Option<StateCha
 
     Some(StateChart {
         short_name: "Authentication".into(),
-        semantic: String::new(),
+        semantic: Name::default(),
         state_transitions: vec![],
         start_state_short_name_ref: String::new(),
         states,
@@ -1777,6 +2179,7 @@ fn parse_variant_definition(
     sessions: Option<&BTreeMap<String, Session>>,
     security: Option<&BTreeMap<String, SecurityLevel>>,
     base_services: &[DiagService],
+    default_nrcs: &[serde_yaml::Value],
 ) -> Variant {
     // Build matching parameters from detect section
     let variant_patterns = if let Some(detect) = &vdef.detect {
@@ -1805,11 +2208,16 @@ fn parse_variant_definition(
             diag_services.retain(|s| s.diag_comm.short_name != override_svc.diag_comm.short_name);
             diag_services.push(override_svc);
         }
+        crate::service_generator::apply_nrcs(
+            &mut diag_services,
+            Some(&yaml_services),
+            default_nrcs,
+        );
     }
 
     Variant {
         diag_layer: DiagLayer {
-            short_name: format!("{base_variant_name}_{name}"),
+            short_name: format!("{base_variant_name}_{name}").into(),
             long_name: None,
             funct_classes: vec![],
             com_param_refs: vec![],
@@ -1818,13 +2226,14 @@ fn parse_variant_definition(
             state_charts: vec![],
             additional_audiences: vec![],
             sdgs: None,
+            description: vdef.description.clone(),
         },
         is_base_variant: false,
         variant_patterns,
         parent_refs: vec![ParentRef {
             ref_type: ParentRefType::Variant(Box::new(Variant {
                 diag_layer: DiagLayer {
-                    short_name: base_variant_name.to_string(),
+                    short_name: base_variant_name.into(),
                     ..Default::default()
                 },
                 is_base_variant: true,
@@ -1864,7 +2273,7 @@ fn build_access_pattern_lookup(
             (
                 name.as_str(),
                 State {
-                    short_name: cda_name,
+                    short_name: cda_name.into(),
                     long_name: Some(LongName {
                         value: id.to_string(),
                         ti: name.clone(),
@@ -1882,7 +2291,7 @@ fn build_access_pattern_lookup(
             (
                 name.as_str(),
                 State {
-                    short_name: cda_name,
+                    short_name: cda_name.into(),
                     long_name: Some(LongName {
                         value: level.level.to_string(),
                         ti: name.clone(),
@@ -1901,7 +2310,7 @@ fn build_access_pattern_lookup(
             (
                 name.as_str(),
                 State {
-                    short_name: name.clone(),
+                    short_name: name.as_str().into(),
                     long_name: Some(LongName {
                         value: id.to_string(),
                         ti: String::new(),
@@ -1926,7 +2335,7 @@ fn build_access_pattern_lookup(
                                 refs.push(PreConditionStateRef {
                                     value: "Session".into(),
                                     in_param_if_short_name: String::new(),
-                                    in_param_path_short_name: state.short_name.clone(),
+                                    in_param_path_short_name: state.short_name.to_string(),
                                     state: Some(state.clone()),
                                 });
                             }
@@ -1946,7 +2355,7 @@ fn build_access_pattern_lookup(
                                 refs.push(PreConditionStateRef {
                                     value: "SecurityAccess".into(),
                                     in_param_if_short_name: String::new(),
-                                    in_param_path_short_name: state.short_name.clone(),
+                                    in_param_path_short_name: state.short_name.to_string(),
                                     state: Some(state.clone()),
                                 });
                             }
@@ -1982,7 +2391,7 @@ fn build_access_pattern_lookup(
 }
 
 /// Look up access pattern for a service and attach pre-condition state refs + SDG metadata.
-fn apply_access_pattern(
+pub(crate) fn apply_access_pattern(
     diag_comm: &mut DiagComm,
     pattern_name: &str,
     patterns: &HashMap<String, Vec<PreConditionStateRef>>,
@@ -2009,19 +2418,43 @@ fn apply_access_pattern(
     }
 }
 
+/// Attach the `features`, `lifecycle` and `requirements` of a DID/routine/service
+/// entry to its service.
+pub(crate) fn apply_item_tags(
+    diag_comm: &mut DiagComm,
+    features: Option<&[String]>,
+    lifecycle: Option<&Lifecycle>,
+    requirements: Option<&[String]>,
+) {
+    set_feature_tags(diag_comm, features.unwrap_or_default());
+    if let Some(lifecycle) = lifecycle {
+        set_lifecycle(&mut diag_comm.sdgs, lifecycle);
+    }
+    set_requirements(diag_comm, requirements.unwrap_or_default());
+}
+
+/// Store the former names of a DID or routine on one of its services, with
+/// the `suffix` its service names add (`_Read`, `_Write`).
+fn apply_aliases(diag_comm: &mut DiagComm, aliases: Option<&[String]>, suffix: &str) {
+    let names: Vec<String> = aliases
+        .unwrap_or_default()
+        .iter()
+        .map(|alias| format!("{alias}{suffix}"))
+        .collect();
+    set_aliases(diag_comm, &names);
+}
+
 fn parse_detect_to_matching_parameter(
     detect: &serde_yaml::Value,
     base_services: &[DiagService],
 ) -> Option<MatchingParameter> {
+    if let Some(did) = detect.get("did") {
+        return did_matching_parameter(did, detect.get("equals")?, base_services);
+    }
     let rpm = detect.get("response_param_match")?;
     let service_name = rpm.get("service")?.as_str()?;
     let param_path = rpm.get("param_path")?.as_str()?;
-    let expected = rpm.get("expected_value")?;
-    let expected_str = match expected {
-        serde_yaml::Value::Number(n) => format!("0x{:X}", n.as_u64().unwrap_or(0)),
-        serde_yaml::Value::String(s) => s.clone(),
-        _ => format!("{expected:?}"),
-    };
+    let expected_str = expected_value_string(rpm.get("expected_value")?);
 
     // Look up the actual service from the base services list
     let diag_service = base_services
@@ -2032,7 +2465,7 @@ fn parse_detect_to_matching_parameter(
             // Fallback to stub if service not found
             DiagService {
                 diag_comm: DiagComm {
-                    short_name: service_name.to_string(),
+                    short_name: service_name.into(),
                     ..Default::default()
                 },
                 ..Default::default()
@@ -2050,7 +2483,7 @@ fn parse_detect_to_matching_parameter(
                 .cloned()
         })
         .unwrap_or_else(|| Param {
-            short_name: param_path.to_string(),
+            short_name: param_path.into(),
             ..Default::default()
         });
 
@@ -2062,6 +2495,62 @@ fn parse_detect_to_matching_parameter(
     })
 }
 
+/// `detect: { did: 0xF187, equals: ... }`: match the data param of the
+/// ReadDataByIdentifier service generated for the DID.
+fn did_matching_parameter(
+    did: &serde_yaml::Value,
+    expected: &serde_yaml::Value,
+    base_services: &[DiagService],
+) -> Option<MatchingParameter> {
+    let Some(did) = parse_hex_key(did) else {
+        log::warn!("variant detect: invalid DID {did:?}; detection skipped");
+        return None;
+    };
+    let coded_at = |svc: &DiagService, byte: u32| -> Option<u32> {
+        svc.request
+            .as_ref()?
+            .params
+            .iter()
+            .find_map(|p| match &p.specific_data {
+                Some(ParamData::CodedConst { coded_value, .. })
+                    if p.byte_position == Some(byte) =>
+                {
+                    coded_value.parse::<u32>().ok()
+                }
+                _ => None,
+            })
+    };
+    let Some(diag_service) = base_services
+        .iter()
+        .find(|svc| coded_at(svc, 0) == Some(0x22) && coded_at(svc, 1) == Some(did))
+    else {
+        log::warn!("variant detect: DID 0x{did:04X} has no read service; detection skipped");
+        return None;
+    };
+    let out_param = diag_service
+        .pos_responses
+        .first()?
+        .params
+        .iter()
+        .find(|p| p.semantic == "DATA")?;
+
+    Some(MatchingParameter {
+        expected_value: expected_value_string(expected),
+        diag_service: Box::new(diag_service.clone()),
+        out_param: Box::new(out_param.clone()),
+        use_physical_addressing: None,
+    })
+}
+
+/// The expected value of a variant match; numbers are written in hex.
+fn expected_value_string(expected: &serde_yaml::Value) -> String {
+    match expected {
+        serde_yaml::Value::Number(n) => format!("0x{:X}", n.as_u64().unwrap_or(0)),
+        serde_yaml::Value::String(s) => s.clone(),
+        _ => format!("{expected:?}"),
+    }
+}
+
 /// Create a DOP for a comparam from an explicit YAML definition.
 /// Create a default DOP for comparams that don't have an explicit DOP definition.
 /// CDA requires every ComParam to have a DOP with a diag_coded_type for value resolution.
@@ -2069,7 +2558,7 @@ fn parse_detect_to_matching_parameter(
 fn default_comparam_dop() -> Dop {
     Dop {
         dop_type: DopType::Regular,
-        short_name: "IDENTICAL_A_UINT32".to_string(),
+        short_name: "IDENTICAL_A_UINT32".into(),
         sdgs: None,
         specific_data: Some(DopData::NormalDop {
             compu_method: Some(CompuMethod {
@@ -2151,7 +2640,7 @@ fn make_comparam_dop(dop_def: Option<&ComParamDopDef>) -> Option<Dop> {
 
     Some(Dop {
         dop_type: DopType::Regular,
-        short_name: name,
+        short_name: name.into(),
         sdgs: None,
         specific_data: Some(DopData::NormalDop {
             compu_method: Some(CompuMethod {
@@ -2204,7 +2693,7 @@ fn build_complex_comparam_children(
             let value = values.and_then(|v| v.get(idx)).cloned().unwrap_or_default();
             ComParam {
                 com_param_type: ComParamType::Regular,
-                short_name: child.name.clone(),
+                short_name: child.name.as_str().into(),
                 long_name: None,
                 param_class: child.param_class.clone().unwrap_or_default(),
                 cp_type: ComParamStandardisationLevel::Standard,
@@ -2212,7 +2701,7 @@ fn build_complex_comparam_children(
                 cp_usage: ComParamUsage::EcuComm,
                 specific_data: Some(ComParamSpecificData::Regular {
                     physical_default_value: value,
-                    dop: make_comparam_dop(child.dop.as_ref()).map(Box::new),
+                    dop: make_comparam_dop(child.dop.as_ref()).map(DopRef::new),
                 }),
             }
         })
@@ -2232,7 +2721,7 @@ fn parse_yaml_comparam_subset(def: &YamlComParamSubSetDef) -> ComParamSubSet {
             };
             com_params.push(ComParam {
                 com_param_type: ComParamType::Regular,
-                short_name: name.clone(),
+                short_name: name.as_str().into(),
                 long_name: None,
                 param_class: p.param_class.clone().unwrap_or_default(),
                 cp_type,
@@ -2240,7 +2729,7 @@ fn parse_yaml_comparam_subset(def: &YamlComParamSubSetDef) -> ComParamSubSet {
                 cp_usage: parse_comparam_usage(p.usage.as_deref()),
                 specific_data: Some(ComParamSpecificData::Regular {
                     physical_default_value: p.default.clone().unwrap_or_default(),
-                    dop: make_comparam_dop(p.dop.as_ref()).map(Box::new),
+                    dop: make_comparam_dop(p.dop.as_ref()).map(DopRef::new),
                 }),
             });
         }
@@ -2256,7 +2745,7 @@ fn parse_yaml_comparam_subset(def: &YamlComParamSubSetDef) -> ComParamSubSet {
                     kids.iter()
                         .map(|child| ComParam {
                             com_param_type: ComParamType::Regular,
-                            short_name: child.name.clone(),
+                            short_name: child.name.as_str().into(),
                             long_name: None,
                             param_class: child.param_class.clone().unwrap_or_default(),
                             cp_type: ComParamStandardisationLevel::Standard,
@@ -2264,7 +2753,7 @@ fn parse_yaml_comparam_subset(def: &YamlComParamSubSetDef) -> ComParamSubSet {
                             cp_usage: ComParamUsage::EcuComm,
                             specific_data: Some(ComParamSpecificData::Regular {
                                 physical_default_value: child.default.clone().unwrap_or_default(),
-                                dop: make_comparam_dop(child.dop.as_ref()).map(Box::new),
+                                dop: make_comparam_dop(child.dop.as_ref()).map(DopRef::new),
                             }),
                         })
                         .collect()
@@ -2273,7 +2762,7 @@ fn parse_yaml_comparam_subset(def: &YamlComParamSubSetDef) -> ComParamSubSet {
 
             complex_com_params.push(ComParam {
                 com_param_type: ComParamType::Complex,
-                short_name: name.clone(),
+                short_name: name.as_str().into(),
                 long_name: None,
                 param_class: cp.param_class.clone().unwrap_or_default(),
                 cp_type: match cp.cp_type.as_deref() {
@@ -2331,7 +2820,7 @@ fn parse_yaml_parent_refs(refs: Option<&Vec<YamlParentRef>>) -> Vec<ParentRef> {
             let ref_type = match pr.ref_type.as_str() {
                 "protocol" => ParentRefType::Protocol(Box::new(Protocol {
                     diag_layer: DiagLayer {
-                        short_name: pr.target.clone(),
+                        short_name: pr.target.as_str().into(),
                         ..Default::default()
                     },
                     com_param_spec: None,
@@ -2340,13 +2829,13 @@ fn parse_yaml_parent_refs(refs: Option<&Vec<YamlParentRef>>) -> Vec<ParentRef> {
                 })),
                 "ecu_shared_data" => ParentRefType::EcuSharedData(Box::new(EcuSharedData {
                     diag_layer: DiagLayer {
-                        short_name: pr.target.clone(),
+                        short_name: pr.target.as_str().into(),
                         ..Default::default()
                     },
                 })),
                 "variant" => ParentRefType::Variant(Box::new(Variant {
                     diag_layer: DiagLayer {
-                        short_name: pr.target.clone(),
+                        short_name: pr.target.as_str().into(),
                         ..Default::default()
                     },
                     is_base_variant: false,
@@ -2356,7 +2845,7 @@ fn parse_yaml_parent_refs(refs: Option<&Vec<YamlParentRef>>) -> Vec<ParentRef> {
                 // functional_group or any unrecognized type
                 _ => ParentRefType::FunctionalGroup(Box::new(FunctionalGroup {
                     diag_layer: DiagLayer {
-                        short_name: pr.target.clone(),
+                        short_name: pr.target.as_str().into(),
                         ..Default::default()
                     },
                     parent_refs: vec![],
@@ -2386,7 +2875,7 @@ fn parse_yaml_parent_refs(refs: Option<&Vec<YamlParentRef>>) -> Vec<ParentRef> {
 /// Parse a YAML diagnostic layer block into an IR DiagLayer.
 /// Used by both protocol and ecu_shared_data layers.
 fn parse_yaml_diag_layer_block(short_name: &str, block: &YamlDiagLayerBlock) -> DiagLayer {
-    let type_registry = build_type_registry(block.types.as_ref());
+    let type_registry = build_type_registry(block.types.as_ref(), None);
 
     // Build com_param_refs from block comparams
     let mut com_param_refs = Vec::new();
@@ -2402,7 +2891,7 @@ fn parse_yaml_diag_layer_block(short_name: &str, block: &YamlDiagLayerBlock) ->
                         complex_value: None,
                         com_param: Some(Box::new(ComParam {
                             com_param_type: ComParamType::Regular,
-                            short_name: param_name.clone(),
+                            short_name: param_name.as_str().into(),
                             long_name: None,
                             param_class: String::new(),
                             cp_type: ComParamStandardisationLevel::Standard,
@@ -2424,7 +2913,7 @@ fn parse_yaml_diag_layer_block(short_name: &str, block: &YamlDiagLayerBlock) ->
                             let value_str = yaml_value_to_string(val);
                             let protocol = Protocol {
                                 diag_layer: DiagLayer {
-                                    short_name: proto_name.clone(),
+                                    short_name: proto_name.as_str().into(),
                                     ..Default::default()
                                 },
                                 com_param_spec: None,
@@ -2438,7 +2927,7 @@ fn parse_yaml_diag_layer_block(short_name: &str, block: &YamlDiagLayerBlock) ->
                                 complex_value: None,
                                 com_param: Some(Box::new(ComParam {
                                     com_param_type: ComParamType::Regular,
-                                    short_name: param_name.clone(),
+                                    short_name: param_name.as_str().into(),
                                     long_name: None,
                                     param_class: full.param_class.clone().unwrap_or_default(),
                                     cp_type: ComParamStandardisationLevel::Standard,
@@ -2446,7 +2935,7 @@ fn parse_yaml_diag_layer_block(short_name: &str, block: &YamlDiagLayerBlock) ->
                                     cp_usage: parse_comparam_usage(full.usage.as_deref()),
                                     specific_data: Some(ComParamSpecificData::Regular {
                                         physical_default_value: value_str,
-                                        dop: make_comparam_dop(full.dop.as_ref()).map(Box::new),
+                                        dop: make_comparam_dop(full.dop.as_ref()).map(DopRef::new),
                                     }),
                                 })),
                                 protocol: Some(Box::new(protocol)),
@@ -2463,7 +2952,7 @@ fn parse_yaml_diag_layer_block(short_name: &str, block: &YamlDiagLayerBlock) ->
                             complex_value: None,
                             com_param: Some(Box::new(ComParam {
                                 com_param_type: ComParamType::Regular,
-                                short_name: param_name.clone(),
+                                short_name: param_name.as_str().into(),
                                 long_name: None,
                                 param_class: full.param_class.clone().unwrap_or_default(),
                                 cp_type: ComParamStandardisationLevel::Standard,
@@ -2471,7 +2960,7 @@ fn parse_yaml_diag_layer_block(short_name: &str, block: &YamlDiagLayerBlock) ->
                                 cp_usage: parse_comparam_usage(full.usage.as_deref()),
                                 specific_data: Some(ComParamSpecificData::Regular {
                                     physical_default_value: default_val,
-                                    dop: make_comparam_dop(full.dop.as_ref()).map(Box::new),
+                                    dop: make_comparam_dop(full.dop.as_ref()).map(DopRef::new),
                                 }),
                             })),
                             protocol: None,
@@ -2496,13 +2985,21 @@ fn parse_yaml_diag_layer_block(short_name: &str, block: &YamlDiagLayerBlock) ->
     // Build services from DID definitions
     if let Some(serde_yaml::Value::Mapping(dids)) = &block.dids {
         for (key, val) in dids {
-            let did_id = parse_hex_key(key);
+            let Some(did_id) = parse_hex_key(key) else {
+                continue;
+            };
             if let Ok(did) = serde_yaml::from_value::<Did>(val.clone()) {
+                let dop = did_dop(&did, &type_registry);
                 if did.readable.unwrap_or(true) {
-                    diag_services.push(did_to_read_service(did_id, &did, &type_registry));
+                    diag_services.push(did_to_read_service(
+                        did_id,
+                        &did,
+                        dop.clone(),
+                        &type_registry,
+                    ));
                 }
                 if did.writable.unwrap_or(false) {
-                    diag_services.push(did_to_write_service(did_id, &did, &type_registry));
+                    diag_services.push(did_to_write_service(did_id, &did, dop));
                 }
             }
         }
@@ -2511,12 +3008,15 @@ fn parse_yaml_diag_layer_block(short_name: &str, block: &YamlDiagLayerBlock) ->
     // Build services from routine definitions
     if let Some(serde_yaml::Value::Mapping(routines)) = &block.routines {
         for (key, val) in routines {
-            let rid = parse_hex_key(key);
+            let Some(rid) = parse_hex_key(key) else {
+                continue;
+            };
             if let Ok(routine) = serde_yaml::from_value::<Routine>(val.clone()) {
                 diag_services.push(routine_to_service(rid, &routine, &type_registry));
             }
         }
     }
+    crate::service_generator::apply_nrcs(&mut diag_services, block.services.as_deref(), &[]);
 
     // Build ECU jobs
     let mut single_ecu_jobs = Vec::new();
@@ -2541,6 +3041,7 @@ fn parse_yaml_diag_layer_block(short_name: &str, block: &YamlDiagLayerBlock) ->
         state_charts: vec![],
         additional_audiences: vec![],
         sdgs,
+        description: None,
     }
 }
 
@@ -2572,7 +3073,7 @@ fn parse_yaml_protocols(protocols: Option<&BTreeMap<String, YamlProtocolLayer>>)
                             .unwrap_or_default();
 
                         ProtStack {
-                            short_name: nps.short_name.clone(),
+                            short_name: nps.short_name.as_str().into(),
                             long_name: nps.long_name.as_ref().map(|ln| LongName {
                                 value: ln.clone(),
                                 ti: String::new(),
@@ -2639,7 +3140,7 @@ fn parse_comparams(doc: &YamlDocument) -> Vec<ComParamRef> {
                     complex_value: None,
                     com_param: Some(Box::new(ComParam {
                         com_param_type: ComParamType::Regular,
-                        short_name: param_name.clone(),
+                        short_name: param_name.as_str().into(),
                         long_name: None,
                         param_class: String::new(),
                         cp_type: ComParamStandardisationLevel::Standard,
@@ -2666,7 +3167,7 @@ fn parse_comparams(doc: &YamlDocument) -> Vec<ComParamRef> {
 
                         let protocol = Protocol {
                             diag_layer: DiagLayer {
-                                short_name: proto_name.clone(),
+                                short_name: proto_name.as_str().into(),
                                 ..Default::default()
                             },
                             com_param_spec: None,
@@ -2710,7 +3211,7 @@ fn parse_comparams(doc: &YamlDocument) -> Vec<ComParamRef> {
                                 ComParamType::Regular,
                                 Some(ComParamSpecificData::Regular {
                                     physical_default_value,
-                                    dop: make_comparam_dop(full.dop.as_ref()).map(Box::new),
+                                    dop: make_comparam_dop(full.dop.as_ref()).map(DopRef::new),
                                 }),
                             )
                         };
@@ -2720,7 +3221,7 @@ fn parse_comparams(doc: &YamlDocument) -> Vec<ComParamRef> {
                             complex_value,
                             com_param: Some(Box::new(ComParam {
                                 com_param_type,
-                                short_name: param_name.clone(),
+                                short_name: param_name.as_str().into(),
                                 long_name: None,
                                 param_class: full.param_class.clone().unwrap_or_default(),
                                 cp_type: ComParamStandardisationLevel::Standard,
@@ -2752,7 +3253,7 @@ fn parse_comparams(doc: &YamlDocument) -> Vec<ComParamRef> {
                             ComParamType::Regular,
                             Some(ComParamSpecificData::Regular {
                                 physical_default_value: value_str.clone(),
-                                dop: make_comparam_dop(full.dop.as_ref()).map(Box::new),
+                                dop: make_comparam_dop(full.dop.as_ref()).map(DopRef::new),
                             }),
                         )
                     };
@@ -2762,7 +3263,7 @@ fn parse_comparams(doc: &YamlDocument) -> Vec<ComParamRef> {
                         complex_value: None,
                         com_param: Some(Box::new(ComParam {
                             com_param_type,
-                            short_name: param_name.clone(),
+                            short_name: param_name.as_str().into(),
                             long_name: None,
                             param_class: full.param_class.clone().unwrap_or_default(),
                             cp_type: ComParamStandardisationLevel::Standard,
@@ -1,4 +1,5 @@
-use crate::yaml_model::YamlDocument;
+use crate::service_generator::{entries_by_sid, profile_services, resolve_nrc};
+use crate::yaml_model::{Routine, YamlDocument};
 use std::collections::{BTreeMap, HashSet};
 
 /// Severity of a semantic validation finding.
@@ -41,6 +42,10 @@ pub fn validate_semantics(doc: &YamlDocument) -> Vec<SemanticIssue> {
     validate_access_pattern_session_refs(doc, &mut issues);
     validate_access_pattern_security_refs(doc, &mut issues);
     validate_state_model_session_refs(doc, &mut issues);
+    validate_routine_length_keys(doc, &mut issues);
+    validate_service_nrcs(doc, &mut issues);
+    validate_service_profile(doc, &mut issues);
+    validate_service_access_refs(doc, &mut issues);
 
     issues
 }
@@ -186,3 +191,155 @@ fn validate_state_model_session_refs(doc: &YamlDocument, issues: &mut Vec<Semant
         }
     }
 }
+
+/// Check that each routine param's `length_key` names an earlier param of the
+/// same list, and that a param is not both a filler and something else.
+fn validate_routine_length_keys(doc: &YamlDocument, issues: &mut Vec<SemanticIssue>) {
+    let Some(serde_yaml::Value::Mapping(routines)) = &doc.routines else {
+        return;
+    };
+    for (key, value) in routines {
+        let Ok(routine) = serde_yaml::from_value::<Routine>(value.clone()) else {
+            continue;
+        };
+        let rid = match key {
+            serde_yaml::Value::Number(n) => n
+                .as_u64()
+                .map_or_else(|| n.to_string(), |n| format!("0x{n:04X}")),
+            serde_yaml::Value::String(s) => s.clone(),
+            _ => String::new(),
+        };
+        for (phase_name, phase) in routine.parameters.iter().flatten() {
+            let lists = [("input", &phase.input), ("output", &phase.output)];
+            for (direction, params) in lists {
+                let Some(params) = params else { continue };
+                let path = format!("routines/{rid}/parameters/{phase_name}/{direction}");
+                for (i, param) in params.iter().enumerate() {
+                    if param.reserved.is_some()
+                        && (param.sys_param.is_some() || param.length_key.is_some())
+                    {
+                        issues.push(SemanticIssue {
+                            severity: Severity::Error,
+                            path: path.clone(),
+                            message: format!(
+                                "reserved param '{}' cannot have sys_param or length_key",
+                                param.name
+                            ),
+                        });
+                    }
+                    let Some(length_key) = &param.length_key else {
+                        continue;
+                    };
+                    if !params[..i].iter().any(|p| &p.name == length_key) {
+                        issues.push(SemanticIssue {
+                            severity: Severity::Error,
+                            path: path.clone(),
+                            message: format!(
+                                "length_key '{length_key}' of '{}' is not an earlier param",
+                                param.name
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Check that `nrcs:` entries, of services and of `defaults`, are byte codes
+/// or names from the ISO NRC table.
+fn validate_service_nrcs(doc: &YamlDocument, issues: &mut Vec<SemanticIssue>) {
+    let entries = doc.services.as_deref().map(entries_by_sid);
+    let lists = entries
+        .iter()
+        .flatten()
+        .filter_map(|(name, _, entry)| {
+            let nrcs = entry.as_ref()?.nrcs.as_ref()?;
+            Some((format!("services/{name}/nrcs"), nrcs))
+        })
+        .chain(
+            doc.defaults
+                .as_ref()
+                .and_then(|d| d.nrcs.as_ref())
+                .map(|nrcs| ("defaults/nrcs".to_string(), nrcs)),
+        );
+    for (path, nrcs) in lists {
+        for nrc in nrcs.iter().filter(|nrc| resolve_nrc(nrc).is_none()) {
+            let shown = match nrc {
+                serde_yaml::Value::String(s) => s.clone(),
+                other => serde_yaml::to_string(other)
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+            };
+            issues.push(SemanticIssue {
+                severity: Severity::Error,
+                path: path.clone(),
+                message: format!("'{shown}' is neither an NRC byte nor an ISO 14229-1 NRC name"),
+            });
+        }
+    }
+}
+
+/// Check that `services.profile` names a built-in service profile.
+fn validate_service_profile(doc: &YamlDocument, issues: &mut Vec<SemanticIssue>) {
+    let Some(profile) = doc.services.as_ref().and_then(|s| s.profile.as_deref()) else {
+        return;
+    };
+    if profile_services(profile).is_none() {
+        issues.push(SemanticIssue {
+            severity: Severity::Error,
+            path: "services/profile".into(),
+            message: format!("unknown service profile '{profile}'"),
+        });
+    }
+}
+
+/// Check that the `access` and `subfunction_access` of service entries
+/// reference defined access patterns.
+fn validate_service_access_refs(doc: &YamlDocument, issues: &mut Vec<SemanticIssue>) {
+    let Some(services) = &doc.services else {
+        return;
+    };
+    let pattern_names: HashSet<&str> = doc
+        .access_patterns
+        .as_ref()
+        .map(|p| p.keys().map(String::as_str).collect())
+        .unwrap_or_default();
+
+    for (name, _, entry) in entries_by_sid(services) {
+        let Some(entry) = entry else {
+            continue;
+        };
+        let refs = entry
+            .access
+            .iter()
+            .map(|pattern| (format!("services/{name}/access"), pattern.as_str()))
+            .chain(
+                entry
+                    .subfunction_access
+                    .iter()
+                    .flatten()
+                    .map(|(sf, pattern)| {
+                        let sf = match sf {
+                            serde_yaml::Value::String(s) => s.clone(),
+                            other => serde_yaml::to_string(other)
+                                .unwrap_or_default()
+                                .trim()
+                                .to_string(),
+                        };
+                        let pattern = pattern.as_str().unwrap_or_default();
+                        (format!("services/{name}/subfunction_access/{sf}"), pattern)
+                    }),
+            );
+        for (path, pattern) in refs {
+            if !pattern_names.contains(pattern) {
+                issues.push(SemanticIssue {
+                    severity: Severity::Error,
+                    path,
+                    message: format!("references undefined access pattern '{pattern}'"),
+                });
+            }
+        }
+    }
+}
@@ -17,6 +17,13 @@
 //!   from service names since these have no standalone writer sections.
 //! - **ControlDTCSetting, ReadDTCInformation**: subfunctions are reconstructed
 //!   from service names. If they match the defaults, `subfunctions: None` is emitted.
+//! - **InputOutputControlByIdentifier**: `control_types` are reconstructed from the
+//!   names of the generated `IOControl_*` services.
+//!
+//! - **Feature tags, lifecycle, requirements and NRCs**: each is taken from the
+//!   first of the entry's services that carries it.
+//! - **Access patterns**: one pattern shared by all of an entry's services becomes
+//!   its `access`, differing ones a `subfunction_access` map keyed by `0x` byte.
 //!
 //! ## Known limitations
 //!
@@ -26,8 +33,11 @@
 //! This does not affect IR -> YAML -> IR roundtrip because these fields are
 //! only consumed during initial YAML parsing.
 
+use diag_ir::lifecycle::lifecycle;
 use diag_ir::types::{DiagService, ParamData, ParamType};
 
+use crate::service_generator::IO_CONTROL_TYPES;
+use crate::writer::{extract_access_pattern_name, extract_feature_tags, extract_requirements};
 use crate::yaml_model::{ServiceEntry, YamlServices};
 
 /// Extract the UDS SID byte from a service's first request parameter.
@@ -63,11 +73,7 @@ pub fn extract_subfunction(svc: &DiagService) -> Option<u8> {
 }
 
 fn parse_hex_or_decimal(s: &str) -> Option<u8> {
-    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
-        u8::from_str_radix(hex, 16).ok()
-    } else {
-        s.parse().ok()
-    }
+    diag_ir::parse_uint(s).and_then(|v| u8::try_from(v).ok())
 }
 
 /// Reconstruct YamlServices from a list of IR DiagService entries.
@@ -91,6 +97,7 @@ pub fn extract_services(services: &[DiagService]) -> YamlServices {
     let mut dtc_setting_svcs = Vec::new();
     let mut has_clear_dtc = false;
     let mut read_dtc_svcs = Vec::new();
+    let mut io_control_svcs = Vec::new();
 
     for svc in services {
         if let Some(sid) = extract_sid(svc) {
@@ -107,6 +114,9 @@ pub fn extract_services(services: &[DiagService]) -> YamlServices {
                 0x85 => dtc_setting_svcs.push(svc),
                 0x14 => has_clear_dtc = true,
                 0x19 => read_dtc_svcs.push(svc),
+                0x2F if svc.diag_comm.short_name.starts_with("IOControl_") => {
+                    io_control_svcs.push(svc);
+                }
                 0x22 | 0x2E | 0x2F | 0x31 => {} // DID/IO/routine
                 _ => {}
             }
@@ -174,9 +184,96 @@ pub fn extract_services(services: &[DiagService]) -> YamlServices {
         yaml.read_dtc_information = Some(extract_read_dtc_entry(&read_dtc_svcs));
     }
 
+    if !io_control_svcs.is_empty() {
+        yaml.input_output_control = Some(extract_io_control_entry(&io_control_svcs));
+    }
+
+    let entries = [
+        (&mut yaml.diagnostic_session_control, &[0x10][..]),
+        (&mut yaml.ecu_reset, &[0x11]),
+        (&mut yaml.security_access, &[0x27]),
+        (&mut yaml.communication_control, &[0x28]),
+        (&mut yaml.authentication, &[0x29]),
+        (&mut yaml.request_download, &[0x34, 0x36, 0x37]),
+        (&mut yaml.request_upload, &[0x35]),
+        (&mut yaml.tester_present, &[0x3E]),
+        (&mut yaml.control_dtc_setting, &[0x85]),
+        (&mut yaml.clear_diagnostic_information, &[0x14]),
+        (&mut yaml.read_dtc_information, &[0x19]),
+        (&mut yaml.input_output_control, &[0x2F]),
+    ];
+    for (entry, sids) in entries {
+        if let Some(entry) = entry {
+            let matching = services
+                .iter()
+                .filter(|svc| extract_sid(svc).is_some_and(|sid| sids.contains(&sid)));
+            entry.features = matching
+                .clone()
+                .find_map(|svc| extract_feature_tags(&svc.diag_comm));
+            entry.lifecycle = matching
+                .clone()
+                .find_map(|svc| lifecycle(svc.diag_comm.sdgs.as_ref()));
+            entry.requirements = matching
+                .clone()
+                .find_map(|svc| extract_requirements(&svc.diag_comm));
+            entry.nrcs = matching.clone().find_map(extract_nrcs);
+            (entry.access, entry.subfunction_access) = extract_access(matching);
+        }
+    }
+
     yaml
 }
 
+/// The `access` and `subfunction_access` of an entry, from the access
+/// pattern names the parser stored on its services.
+fn extract_access<'a>(
+    services: impl Iterator<Item = &'a DiagService>,
+) -> (Option<String>, Option<serde_yaml::Mapping>) {
+    let named: Vec<(Option<u8>, String)> = services
+        .map(|svc| {
+            (
+                extract_subfunction(svc),
+                extract_access_pattern_name(&svc.diag_comm),
+            )
+        })
+        .collect();
+    let Some((_, first)) = named.first() else {
+        return (None, None);
+    };
+    if named.iter().all(|(_, name)| name == first) {
+        let access = (!first.is_empty()).then(|| first.clone());
+        return (access, None);
+    }
+    let by_subfunction: serde_yaml::Mapping = named
+        .iter()
+        .filter(|(_, name)| !name.is_empty())
+        .filter_map(|(sf, name)| {
+            Some((
+                serde_yaml::Value::String(format!("0x{:02X}", (*sf)?)),
+                serde_yaml::Value::String(name.clone()),
+            ))
+        })
+        .collect();
+    (None, Some(by_subfunction).filter(|m| !m.is_empty()))
+}
+
+/// The codes of a service's NRC-CONST negative response, as `0x` hex.
+fn extract_nrcs(svc: &DiagService) -> Option<Vec<serde_yaml::Value>> {
+    svc.neg_responses
+        .iter()
+        .flat_map(|r| &r.params)
+        .find_map(|p| match &p.specific_data {
+            Some(ParamData::NrcConst { coded_values, .. }) => Some(
+                coded_values
+                    .iter()
+                    .filter_map(|v| parse_hex_or_decimal(v))
+                    .map(|code| serde_yaml::Value::String(format!("0x{code:02X}")))
+                    .collect(),
+            ),
+            _ => None,
+        })
+}
+
 /// Returns true if the YamlServices has at least one service type set.
 pub fn has_any_service(svcs: &YamlServices) -> bool {
     svcs.diagnostic_session_control.is_some()
@@ -190,6 +287,7 @@ pub fn has_any_service(svcs: &YamlServices) -> bool {
         || svcs.control_dtc_setting.is_some()
         || svcs.clear_diagnostic_information.is_some()
         || svcs.read_dtc_information.is_some()
+        || svcs.input_output_control.is_some()
 }
 
 /// The 4 default CommunicationControl subtypes from service_generator.rs.
@@ -284,6 +382,26 @@ fn extract_read_dtc_entry(services: &[&DiagService]) -> ServiceEntry {
     }
 }
 
+/// Build an InputOutputControlByIdentifier ServiceEntry with the control
+/// types of the `IOControl_*` services, in service order.
+fn extract_io_control_entry(services: &[&DiagService]) -> ServiceEntry {
+    let control_types = services
+        .iter()
+        .filter_map(|svc| {
+            let control = svc.diag_comm.short_name.strip_prefix("IOControl_")?;
+            IO_CONTROL_TYPES
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(control))
+                .map(|(name, _)| (*name).to_string())
+        })
+        .collect();
+    ServiceEntry {
+        enabled: true,
+        control_types: Some(control_types),
+        ..Default::default()
+    }
+}
+
 /// Build a ServiceEntry with subfunctions extracted from service names.
 ///
 /// Supports both prefix stripping (e.g., `Authentication_` -> `Deauthenticate`)
@@ -330,13 +448,13 @@ mod tests {
     fn make_service(name: &str, semantic: &str, sid: &str) -> DiagService {
         DiagService {
             diag_comm: DiagComm {
-                short_name: name.to_string(),
-                semantic: semantic.to_string(),
+                short_name: name.into(),
+                semantic: semantic.into(),
                 ..Default::default()
             },
             request: Some(Request {
                 params: vec![Param {
-                    short_name: "SID_RQ".to_string(),
+                    short_name: "SID_RQ".into(),
                     param_type: ParamType::CodedConst,
                     byte_position: Some(0),
                     bit_position: Some(0),
@@ -363,14 +481,14 @@ mod tests {
     ) -> DiagService {
         DiagService {
             diag_comm: DiagComm {
-                short_name: name.to_string(),
-                semantic: semantic.to_string(),
+                short_name: name.into(),
+                semantic: semantic.into(),
                 ..Default::default()
             },
             request: Some(Request {
                 params: vec![
                     Param {
-                        short_name: "SID_RQ".to_string(),
+                        short_name: "SID_RQ".into(),
                         param_type: ParamType::CodedConst,
                         byte_position: Some(0),
                         bit_position: Some(0),
@@ -384,7 +502,7 @@ mod tests {
                         ..Default::default()
                     },
                     Param {
-                        short_name: "SubFunction".to_string(),
+                        short_name: "SubFunction".into(),
                         param_type: ParamType::CodedConst,
                         byte_position: Some(1),
                         bit_position: Some(0),
@@ -622,14 +740,14 @@ mod tests {
         // ODX service with "ResetType" instead of "SubFunction"
         let svc = DiagService {
             diag_comm: DiagComm {
-                short_name: "HardReset".to_string(),
-                semantic: String::new(),
+                short_name: "HardReset".into(),
+                semantic: Name::default(),
                 ..Default::default()
             },
             request: Some(Request {
                 params: vec![
                     Param {
-                        short_name: "SID_RQ".to_string(),
+                        short_name: "SID_RQ".into(),
                         param_type: ParamType::CodedConst,
                         byte_position: Some(0),
                         bit_position: Some(0),
@@ -643,7 +761,7 @@ mod tests {
                         ..Default::default()
                     },
                     Param {
-                        short_name: "ResetType".to_string(),
+                        short_name: "ResetType".into(),
                         param_type: ParamType::CodedConst,
                         byte_position: Some(1),
                         bit_position: Some(0),
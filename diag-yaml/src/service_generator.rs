@@ -2,23 +2,38 @@
 //!
 //! Each public method generates `Vec<DiagService>` for one UDS service type.
 
-use crate::yaml_model::{SecurityLevel, Session, YamlServices};
+use crate::yaml_model::{SecurityLevel, ServiceEntry, Session, SessionTiming, YamlServices};
 use diag_ir::*;
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 
 /// Generates DiagService instances from the YAML `services` configuration.
 pub struct ServiceGenerator<'a> {
-    services: &'a YamlServices,
+    services: Cow<'a, YamlServices>,
     sessions: Option<&'a BTreeMap<String, Session>>,
     security: Option<&'a BTreeMap<String, SecurityLevel>>,
+    access_patterns: Option<&'a HashMap<String, Vec<PreConditionStateRef>>>,
 }
 
 impl<'a> ServiceGenerator<'a> {
+    /// A generator for `services`, with the entries of its `profile` filled in.
     pub fn new(services: &'a YamlServices) -> Self {
+        let services = match services.profile.as_deref() {
+            Some(name) => {
+                if let Some(profile) = profile_services(name) {
+                    Cow::Owned(apply_profile(services, profile))
+                } else {
+                    log::warn!("unknown service profile '{name}', ignoring it");
+                    Cow::Borrowed(services)
+                }
+            }
+            None => Cow::Borrowed(services),
+        };
         Self {
             services,
             sessions: None,
             security: None,
+            access_patterns: None,
         }
     }
 
@@ -32,23 +47,112 @@ impl<'a> ServiceGenerator<'a> {
         self
     }
 
+    /// Resolve the `access` and `subfunction_access` of the service entries
+    /// against these access patterns.
+    pub fn with_access_patterns(
+        mut self,
+        access_patterns: Option<&'a HashMap<String, Vec<PreConditionStateRef>>>,
+    ) -> Self {
+        self.access_patterns = access_patterns;
+        self
+    }
+
     /// Generate all enabled services.
     pub fn generate_all(&self) -> Vec<DiagService> {
+        let s = &*self.services;
         let mut result = Vec::new();
-        result.extend(self.generate_diagnostic_session_control());
-        result.extend(self.generate_security_access());
-        result.extend(self.generate_ecu_reset());
-        result.extend(self.generate_authentication());
-        result.extend(self.generate_communication_control());
-        result.extend(self.generate_request_download());
-        result.extend(self.generate_request_upload());
-        result.extend(self.generate_tester_present());
-        result.extend(self.generate_control_dtc_setting());
-        result.extend(self.generate_clear_diagnostic_information());
-        result.extend(self.generate_read_dtc_information());
+        let mut add = |entry: &Option<ServiceEntry>, services: Vec<DiagService>| {
+            result.extend(services.into_iter().map(|mut svc| {
+                if let Some(e) = entry {
+                    crate::parser::apply_item_tags(
+                        &mut svc.diag_comm,
+                        e.features.as_deref(),
+                        e.lifecycle.as_ref(),
+                        e.requirements.as_deref(),
+                    );
+                    if let Some(patterns) = self.access_patterns {
+                        let subfunc = crate::service_extractor::extract_subfunction(&svc);
+                        let access = self.entry_access(e, subfunc);
+                        crate::parser::apply_access_pattern(&mut svc.diag_comm, access, patterns);
+                    }
+                }
+                svc
+            }));
+        };
+        add(
+            &s.diagnostic_session_control,
+            self.generate_diagnostic_session_control(),
+        );
+        add(&s.security_access, self.generate_security_access());
+        add(&s.ecu_reset, self.generate_ecu_reset());
+        add(&s.authentication, self.generate_authentication());
+        add(
+            &s.communication_control,
+            self.generate_communication_control(),
+        );
+        add(&s.request_download, self.generate_request_download());
+        add(&s.request_upload, self.generate_request_upload());
+        add(&s.tester_present, self.generate_tester_present());
+        add(&s.control_dtc_setting, self.generate_control_dtc_setting());
+        add(
+            &s.clear_diagnostic_information,
+            self.generate_clear_diagnostic_information(),
+        );
+        add(
+            &s.read_dtc_information,
+            self.generate_read_dtc_information(),
+        );
+        add(
+            &s.input_output_control,
+            self.generate_input_output_control(),
+        );
         result
     }
 
+    /// The access pattern of the service of `entry` with subfunction byte
+    /// `subfunc`: its `subfunction_access` pattern, else the entry's `access`.
+    fn entry_access<'e>(&self, entry: &'e ServiceEntry, subfunc: Option<u8>) -> &'e str {
+        let by_subfunction = subfunc.and_then(|sf| {
+            entry
+                .subfunction_access
+                .as_ref()?
+                .iter()
+                .find(|(key, _)| self.subfunction_bytes(entry, key).contains(&sf))
+                .and_then(|(_, pattern)| pattern.as_str())
+        });
+        by_subfunction
+            .or(entry.access.as_deref())
+            .unwrap_or_default()
+    }
+
+    /// The subfunction bytes a `subfunction_access` key stands for: a `0x`
+    /// byte, a name of the entry's `subfunctions` map, or a security level
+    /// name (its RequestSeed and SendKey subfunctions).
+    fn subfunction_bytes(&self, entry: &ServiceEntry, key: &serde_yaml::Value) -> Vec<u8> {
+        let key = match key {
+            serde_yaml::Value::Number(_) => return vec![yaml_value_to_u8(key)],
+            serde_yaml::Value::String(s) if s.starts_with("0x") || s.starts_with("0X") => {
+                return vec![yaml_value_to_u8(key)];
+            }
+            serde_yaml::Value::String(s) => s.as_str(),
+            _ => return vec![],
+        };
+        if let Some(serde_yaml::Value::Mapping(map)) = &entry.subfunctions {
+            if let Some(v) = map.get(key) {
+                return vec![yaml_value_to_u8(v)];
+            }
+        }
+        self.security
+            .and_then(|s| s.get(key))
+            .map(|level| {
+                vec![
+                    yaml_value_to_u8(&level.seed_request),
+                    yaml_value_to_u8(&level.key_send),
+                ]
+            })
+            .unwrap_or_default()
+    }
+
     // --- Session, Security, Reset (Task 12b) ---
 
     /// DiagnosticSessionControl (0x10): one service per session.
@@ -78,6 +182,11 @@ impl<'a> ServiceGenerator<'a> {
                 let id = yaml_value_to_u8(&session.id);
                 let label = session.alias.as_deref().unwrap_or(name);
                 let sf = subfunction_param_name("SESSION");
+                let mut response = vec![
+                    coded_const_param("SID_PR", 0, 8, "80"),
+                    matching_request_param(sf, 1, 1),
+                ];
+                response.extend(session_timing_params(session.timing.as_ref()));
                 build_service(
                     &format!("{label}_Start"),
                     "SESSION",
@@ -85,10 +194,7 @@ impl<'a> ServiceGenerator<'a> {
                         coded_const_param("SID_RQ", 0, 8, "16"),
                         coded_const_param(sf, 1, 8, &id.to_string()),
                     ],
-                    vec![
-                        coded_const_param("SID_PR", 0, 8, "80"),
-                        matching_request_param(sf, 1, 1),
-                    ],
+                    response,
                 )
             })
             .collect()
@@ -113,6 +219,15 @@ impl<'a> ServiceGenerator<'a> {
                     let name = k.as_str()?;
                     let id = yaml_value_to_u8(v);
                     let label = display_name(name);
+                    let timing = self
+                        .sessions
+                        .and_then(|s| s.get(name))
+                        .and_then(|s| s.timing.as_ref());
+                    let mut response = vec![
+                        coded_const_param("SID_PR", 0, 8, "80"),
+                        matching_request_param(sf, 1, 1),
+                    ];
+                    response.extend(session_timing_params(timing));
                     Some(build_service(
                         &format!("{label}_Start"),
                         "SESSION",
@@ -120,10 +235,7 @@ impl<'a> ServiceGenerator<'a> {
                             coded_const_param("SID_RQ", 0, 8, "16"),
                             coded_const_param(sf, 1, 8, &id.to_string()),
                         ],
-                        vec![
-                            coded_const_param("SID_PR", 0, 8, "80"),
-                            matching_request_param(sf, 1, 1),
-                        ],
+                        response,
                     ))
                 })
                 .collect(),
@@ -384,12 +496,12 @@ impl<'a> ServiceGenerator<'a> {
                 value_param("MaxNumberOfBlockLength", 2, 32, "IDENTICAL_UINT_32"),
             ],
         );
-        req_download.diag_comm.semantic = "DATA".to_string();
+        req_download.diag_comm.semantic = "DATA".into();
         vec![
             req_download,
             {
                 let mut bsc_resp = matching_request_param("BlockSequenceCounter", 1, 1);
-                bsc_resp.semantic = "DATA".to_string();
+                bsc_resp.semantic = "DATA".into();
                 build_service(
                     "TransferData",
                     "DOWNLOAD",
@@ -579,6 +691,173 @@ impl<'a> ServiceGenerator<'a> {
             vec![build_by_subfunc("ReportDTCByStatusMask", 0x02)]
         }
     }
+
+    // --- Input/output control ---
+
+    /// InputOutputControlByIdentifier (0x2F): one service per configured control type.
+    ///
+    /// Service naming: `IOControl_{PascalName}` (e.g., `IOControl_ShortTermAdjustment`).
+    /// The DataIdentifier is a request value, so the services cover every DID.
+    /// Control types come from `control_types`; defaults to all four ISO 14229-1 types.
+    pub fn generate_input_output_control(&self) -> Vec<DiagService> {
+        let entry = match &self.services.input_output_control {
+            Some(e) if e.enabled => e,
+            _ => return vec![],
+        };
+
+        let names: Vec<&str> = match &entry.control_types {
+            Some(types) => types.iter().map(String::as_str).collect(),
+            None => IO_CONTROL_TYPES.iter().map(|(name, _)| *name).collect(),
+        };
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let Some((_, control)) = IO_CONTROL_TYPES.iter().find(|(n, _)| *n == name) else {
+                    log::warn!("unknown inputOutputControlByIdentifier control type '{name}'");
+                    return None;
+                };
+                Some(io_control_service(&to_pascal_case(name), *control))
+            })
+            .collect()
+    }
+}
+
+/// Service profiles selectable with `services.profile`, as `services:` YAML.
+const SERVICE_PROFILES: &[(&str, &str)] = &[("uds", include_str!("../profiles/uds.yml"))];
+
+/// The `services:` entries of the built-in profile `name`, `None` for an
+/// unknown profile.
+pub fn profile_services(name: &str) -> Option<YamlServices> {
+    let (_, source) = SERVICE_PROFILES.iter().find(|(n, _)| *n == name)?;
+    Some(serde_yaml::from_str(source).expect("built-in service profile is valid"))
+}
+
+/// `services` with the entries of `profile` filled in. A service `services`
+/// configures itself keeps its entry, taking only the `subfunctions` and
+/// `control_types` it does not list from the profile.
+fn apply_profile(services: &YamlServices, profile: YamlServices) -> YamlServices {
+    fn merge(own: Option<&ServiceEntry>, profile: Option<ServiceEntry>) -> Option<ServiceEntry> {
+        match (own.cloned(), profile) {
+            (Some(mut own), Some(profile)) => {
+                if own.subfunctions.is_none() {
+                    own.subfunctions = profile.subfunctions;
+                }
+                if own.control_types.is_none() {
+                    own.control_types = profile.control_types;
+                }
+                Some(own)
+            }
+            (own, profile) => own.or(profile),
+        }
+    }
+    let s = services;
+    let p = profile;
+    YamlServices {
+        profile: s.profile.clone(),
+        diagnostic_session_control: merge(
+            s.diagnostic_session_control.as_ref(),
+            p.diagnostic_session_control,
+        ),
+        ecu_reset: merge(s.ecu_reset.as_ref(), p.ecu_reset),
+        security_access: merge(s.security_access.as_ref(), p.security_access),
+        authentication: merge(s.authentication.as_ref(), p.authentication),
+        tester_present: merge(s.tester_present.as_ref(), p.tester_present),
+        control_dtc_setting: merge(s.control_dtc_setting.as_ref(), p.control_dtc_setting),
+        read_data_by_identifier: merge(
+            s.read_data_by_identifier.as_ref(),
+            p.read_data_by_identifier,
+        ),
+        write_data_by_identifier: merge(
+            s.write_data_by_identifier.as_ref(),
+            p.write_data_by_identifier,
+        ),
+        read_dtc_information: merge(s.read_dtc_information.as_ref(), p.read_dtc_information),
+        clear_diagnostic_information: merge(
+            s.clear_diagnostic_information.as_ref(),
+            p.clear_diagnostic_information,
+        ),
+        input_output_control: merge(s.input_output_control.as_ref(), p.input_output_control),
+        routine_control: merge(s.routine_control.as_ref(), p.routine_control),
+        read_memory_by_address: merge(s.read_memory_by_address.as_ref(), p.read_memory_by_address),
+        write_memory_by_address: merge(
+            s.write_memory_by_address.as_ref(),
+            p.write_memory_by_address,
+        ),
+        read_scaling_data: merge(s.read_scaling_data.as_ref(), p.read_scaling_data),
+        read_data_periodic: merge(s.read_data_periodic.as_ref(), p.read_data_periodic),
+        dynamically_define_did: merge(s.dynamically_define_did.as_ref(), p.dynamically_define_did),
+        request_download: merge(s.request_download.as_ref(), p.request_download),
+        request_upload: merge(s.request_upload.as_ref(), p.request_upload),
+        transfer_data: merge(s.transfer_data.as_ref(), p.transfer_data),
+        request_transfer_exit: merge(s.request_transfer_exit.as_ref(), p.request_transfer_exit),
+        request_file_transfer: merge(s.request_file_transfer.as_ref(), p.request_file_transfer),
+        secured_data_transmission: merge(
+            s.secured_data_transmission.as_ref(),
+            p.secured_data_transmission,
+        ),
+        communication_control: merge(s.communication_control.as_ref(), p.communication_control),
+        response_on_event: merge(s.response_on_event.as_ref(), p.response_on_event),
+        link_control: merge(s.link_control.as_ref(), p.link_control),
+        custom: s.custom.clone().or(p.custom),
+    }
+}
+
+/// Routine identifier of the generated CheckMemory routines.
+pub const CHECK_MEMORY_RID: u16 = 0x0202;
+
+/// RoutineControl startRoutine checkMemory (0x31 0x01 0x0202) for every data
+/// block with a `checksum_type`. The request carries the block's address and
+/// size in the default address format followed by the checksum, sized by the
+/// algorithm; the response reports a one-byte routine status.
+pub fn generate_check_memory(mc: &MemoryConfig) -> Vec<DiagService> {
+    let address_bits = u32::from(mc.default_address_format.address_bytes) * 8;
+    let length_bits = u32::from(mc.default_address_format.length_bytes) * 8;
+    mc.data_blocks
+        .iter()
+        .filter_map(|block| {
+            let checksum = block.checksum_type.as_ref()?;
+            let size_pos = 4 + address_bits / 8;
+            let checksum_pos = size_pos + length_bits / 8;
+            let mut checksum_param = value_param(
+                "Checksum",
+                checksum_pos,
+                checksum.result_length() * 8,
+                &checksum.name().to_ascii_uppercase(),
+            );
+            if let Some(ParamData::Value { dop, .. }) = &mut checksum_param.specific_data {
+                if let Some(DopData::NormalDop {
+                    diag_coded_type: Some(dct),
+                    ..
+                }) = &mut dop.specific_data
+                {
+                    dct.base_data_type = DataType::ABytefield;
+                }
+            }
+            let mut svc = build_service(
+                &check_memory_service_name(&block.name),
+                "ROUTINE",
+                vec![
+                    coded_const_param("SID_RQ", 0, 8, "49"),
+                    coded_const_param("SubFunction", 1, 8, "1"),
+                    coded_const_param("RID_RQ", 2, 16, &CHECK_MEMORY_RID.to_string()),
+                    value_param("MemoryAddress", 4, address_bits, "MemoryAddressArray"),
+                    value_param("MemorySize", size_pos, length_bits, "MemorySizeArray"),
+                    checksum_param,
+                ],
+                vec![
+                    coded_const_param("SID_PR", 0, 8, "113"),
+                    coded_const_param("SubFunction", 1, 8, "1"),
+                    matching_request_param("RID_PR", 2, 2),
+                    value_param("RoutineStatus", 4, 8, "IDENTICAL_UINT_8"),
+                ],
+            );
+            svc.diag_comm.long_name = Some(LongName {
+                value: format!("Check Memory {} ({})", block.name, checksum.name()),
+                ti: String::new(),
+            });
+            Some(svc)
+        })
+        .collect()
 }
 
 // --- Helper functions ---
@@ -601,27 +880,12 @@ fn yaml_value_to_u8(v: &serde_yaml::Value) -> u8 {
             });
             val as u8
         }
-        serde_yaml::Value::String(s) => {
-            if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
-                u8::from_str_radix(hex, 16).unwrap_or_else(|e| {
-                    log::warn!(
-                        "yaml_value_to_u8: invalid hex '{}': {}, defaulting to 0",
-                        s,
-                        e
-                    );
-                    0
-                })
-            } else {
-                s.parse().unwrap_or_else(|e| {
-                    log::warn!(
-                        "yaml_value_to_u8: invalid decimal '{}': {}, defaulting to 0",
-                        s,
-                        e
-                    );
-                    0
-                })
-            }
-        }
+        serde_yaml::Value::String(s) => diag_ir::parse_uint(s)
+            .and_then(|v| u8::try_from(v).ok())
+            .unwrap_or_else(|| {
+                log::warn!("yaml_value_to_u8: invalid byte '{s}', defaulting to 0");
+                0
+            }),
         other => {
             log::warn!(
                 "yaml_value_to_u8: unexpected YAML type {:?}, defaulting to 0",
@@ -664,6 +928,10 @@ fn service_long_name(short_name: &str, semantic: &str) -> Option<String> {
             Some(format!("DTC Setting {}", camel_to_words(mode)))
         }
         "CLEAR-DTC" => Some("Clear DTCs".to_string()),
+        "IO-CONTROL" => {
+            let control = short_name.strip_prefix("IOControl_").unwrap_or(short_name);
+            Some(format!("IO Control {}", camel_to_words(control)))
+        }
         "READ-DTC-INFO" => {
             let name = short_name.strip_prefix("FaultMem_").unwrap_or(short_name);
             let words = camel_to_words(name)
@@ -741,7 +1009,7 @@ fn camel_to_words(s: &str) -> String {
 /// Map service semantic to CDA-compatible functional classes.
 fn semantic_to_funct_classes(semantic: &str) -> Vec<FunctClass> {
     let fc = |name: &str| FunctClass {
-        short_name: name.to_string(),
+        short_name: name.into(),
     };
     match semantic {
         "SESSION" => vec![fc("Session")],
@@ -753,6 +1021,7 @@ fn semantic_to_funct_classes(semantic: &str) -> Vec<FunctClass> {
         "DOWNLOAD" => vec![fc("StandardDataTransfer")],
         "CONTROL-DTC-SETTING" => vec![fc("DtcSetting")],
         "CLEAR-DTC" | "READ-DTC-INFO" => vec![fc("FaultMem")],
+        "IO-CONTROL" => vec![fc("IOCtrl")],
         _ => vec![],
     }
 }
@@ -766,12 +1035,12 @@ fn build_service(
     let ir_semantic = String::new();
     DiagService {
         diag_comm: DiagComm {
-            short_name: short_name.to_string(),
+            short_name: short_name.into(),
             long_name: service_long_name(short_name, semantic).map(|v| LongName {
                 value: v,
                 ti: String::new(),
             }),
-            semantic: ir_semantic,
+            semantic: ir_semantic.into(),
             funct_classes: semantic_to_funct_classes(semantic),
             is_executable: true,
             ..Default::default()
@@ -784,6 +1053,8 @@ fn build_service(
             response_type: ResponseType::PosResponse,
             params: response_params,
             sdgs: None,
+            pos_response_suffix: None,
+            audience: None,
         }],
         neg_responses: vec![],
         addressing: Addressing::Physical,
@@ -810,9 +1081,9 @@ fn coded_const_param(name: &str, byte_pos: u32, bit_size: u32, value: &str) -> P
         "DATA".to_string()
     };
     Param {
-        short_name: name.to_string(),
+        short_name: name.into(),
         param_type: ParamType::CodedConst,
-        semantic,
+        semantic: semantic.into(),
         byte_position: Some(byte_pos),
         bit_position: Some(0),
         specific_data: Some(ParamData::CodedConst {
@@ -834,15 +1105,15 @@ fn coded_const_param(name: &str, byte_pos: u32, bit_size: u32, value: &str) -> P
 
 fn value_param(name: &str, byte_pos: u32, bit_size: u32, dop_name: &str) -> Param {
     Param {
-        short_name: name.to_string(),
+        short_name: name.into(),
         param_type: ParamType::Value,
-        semantic: "DATA".to_string(),
+        semantic: "DATA".into(),
         byte_position: Some(byte_pos),
         bit_position: Some(0),
         specific_data: Some(ParamData::Value {
-            dop: Box::new(Dop {
+            dop: DopRef::new(Dop {
                 dop_type: DopType::Regular,
-                short_name: dop_name.to_string(),
+                short_name: dop_name.into(),
                 specific_data: Some(DopData::NormalDop {
                     diag_coded_type: Some(DiagCodedType {
                         base_data_type: DataType::AUint32,
@@ -868,12 +1139,80 @@ fn value_param(name: &str, byte_pos: u32, bit_size: u32, dop_name: &str) -> Para
     }
 }
 
+/// Response param names of the DiagnosticSessionControl sessionParameterRecord.
+pub const P2_SERVER_MAX_PARAM: &str = "P2_Server_Max";
+pub const P2_STAR_SERVER_MAX_PARAM: &str = "P2_Star_Server_Max";
+
+/// ISO 14229-1 defaults used when only one of the two timings is configured.
+const DEFAULT_P2_SERVER_MAX_MS: u32 = 50;
+const DEFAULT_P2_STAR_SERVER_MAX_MS: u32 = 5000;
+
+/// The sessionParameterRecord of a DiagnosticSessionControl positive response:
+/// P2Server_max (uint16, 1 ms) followed by P2*Server_max (uint16, 10 ms).
+/// Empty unless the session configures at least one of them.
+fn session_timing_params(timing: Option<&SessionTiming>) -> Vec<Param> {
+    let Some(timing) = timing else {
+        return vec![];
+    };
+    if timing.p2_server_max.is_none() && timing.p2_star_server_max.is_none() {
+        return vec![];
+    }
+    let p2 = timing
+        .p2_server_max
+        .map_or(DEFAULT_P2_SERVER_MAX_MS, u32::from);
+    let p2_star = timing
+        .p2_star_server_max
+        .unwrap_or(DEFAULT_P2_STAR_SERVER_MAX_MS);
+
+    let mut p2_param = value_param(P2_SERVER_MAX_PARAM, 2, 16, "P2_SERVER_MAX");
+    let mut p2_star_param = value_param(P2_STAR_SERVER_MAX_PARAM, 4, 16, "P2_STAR_SERVER_MAX");
+    if let Some(ParamData::Value { dop, .. }) = &mut p2_star_param.specific_data {
+        if let Some(DopData::NormalDop { compu_method, .. }) = &mut dop.specific_data {
+            *compu_method = Some(linear_compu(10.0));
+        }
+    }
+    for (param, ms) in [(&mut p2_param, p2), (&mut p2_star_param, p2_star)] {
+        param.physical_default_value = ms.to_string();
+        if let Some(ParamData::Value {
+            physical_default_value,
+            ..
+        }) = &mut param.specific_data
+        {
+            *physical_default_value = ms.to_string();
+        }
+    }
+    vec![p2_param, p2_star_param]
+}
+
+/// `phys = internal * factor`
+fn linear_compu(factor: f64) -> CompuMethod {
+    CompuMethod {
+        category: CompuCategory::Linear,
+        internal_to_phys: Some(CompuInternalToPhys {
+            compu_scales: vec![CompuScale {
+                short_label: None,
+                lower_limit: None,
+                upper_limit: None,
+                inverse_values: None,
+                consts: None,
+                rational_co_effs: Some(CompuRationalCoEffs {
+                    numerator: vec![0.0, factor],
+                    denominator: vec![1.0],
+                }),
+            }],
+            prog_code: None,
+            compu_default_value: None,
+        }),
+        phys_to_internal: None,
+    }
+}
+
 #[allow(clippy::cast_possible_wrap)]
 fn matching_request_param(name: &str, byte_pos: u32, byte_length: u32) -> Param {
     Param {
-        short_name: name.to_string(),
+        short_name: name.into(),
         param_type: ParamType::MatchingRequestParam,
-        semantic: "SEMANTIC".to_string(),
+        semantic: "SEMANTIC".into(),
         byte_position: Some(byte_pos),
         bit_position: Some(0),
         specific_data: Some(ParamData::MatchingRequestParam {
@@ -887,6 +1226,7 @@ fn matching_request_param(name: &str, byte_pos: u32, byte_length: u32) -> Param
 /// Build subfunction-specific request and response parameters for ReadDTCInformation.
 ///
 /// The parameter structure depends on the UDS subfunction:
+/// - 0x01 (ReportNumberOfDTCByStatusMask): SID + SubFunction + status mask, DTC count response
 /// - 0x02 (ReportDTCByStatusMask): SID + SubFunction + 8 status mask bit params
 /// - 0x04 (ReportDTCSnapshotRecordByDtcNumber): SID + SubFunction + DtcCode + RecordNr
 /// - 0x06 (ReportDTCExtDataRecordByDtcNumber): SID + SubFunction + DtcCode + RecordNr
@@ -899,6 +1239,22 @@ fn read_dtc_params(subfunc: u8) -> (Vec<Param>, Vec<Param>) {
     let subfunc_echo = matching_request_param(sf, 1, 1);
 
     match subfunc {
+        0x01 => {
+            // ReportNumberOfDTCByStatusMask: availability mask, format and count
+            let req = vec![
+                sid_rq,
+                subfunc_rq,
+                value_param("DTCStatusMask", 2, 8, "IDENTICAL_UINT_8"),
+            ];
+            let resp = vec![
+                sid_pr,
+                subfunc_echo,
+                value_param("DTCStatusAvailabilityMask", 2, 8, "IDENTICAL_UINT_8"),
+                value_param("DTCFormatIdentifier", 3, 8, "IDENTICAL_UINT_8"),
+                value_param("DTCCount", 4, 16, "IDENTICAL_UINT_16"),
+            ];
+            (req, resp)
+        }
         0x02 => {
             // ReportDTCByStatusMask: status mask bits as individual parameters
             let status_bit_names = [
@@ -960,7 +1316,7 @@ fn read_dtc_params(subfunc: u8) -> (Vec<Param>, Vec<Param>) {
 /// - `NRC` (byte 2): Value param with DOP name `NRC_{short_name}` (CDA template)
 fn standard_neg_response() -> Response {
     let mut sidrq = matching_request_param("SIDRQ_NR", 1, 1);
-    sidrq.semantic = "SERVICEIDRQ".to_string();
+    sidrq.semantic = "SERVICEIDRQ".into();
     Response {
         response_type: ResponseType::NegResponse,
         params: vec![
@@ -969,6 +1325,195 @@ fn standard_neg_response() -> Response {
             value_param("NRC", 2, 8, "NRC_{short_name}"),
         ],
         sdgs: None,
+        pos_response_suffix: None,
+        audience: None,
+    }
+}
+
+/// ISO 14229-1 negative response codes, the table `nrcs:` names refer to.
+pub const NRC_TABLE: &[(u8, &str)] = &[
+    (0x10, "generalReject"),
+    (0x11, "serviceNotSupported"),
+    (0x12, "subFunctionNotSupported"),
+    (0x13, "incorrectMessageLengthOrInvalidFormat"),
+    (0x14, "responseTooLong"),
+    (0x21, "busyRepeatRequest"),
+    (0x22, "conditionsNotCorrect"),
+    (0x24, "requestSequenceError"),
+    (0x25, "noResponseFromSubnetComponent"),
+    (0x26, "failurePreventsExecutionOfRequestedAction"),
+    (0x31, "requestOutOfRange"),
+    (0x33, "securityAccessDenied"),
+    (0x34, "authenticationRequired"),
+    (0x35, "invalidKey"),
+    (0x36, "exceedNumberOfAttempts"),
+    (0x37, "requiredTimeDelayNotExpired"),
+    (0x70, "uploadDownloadNotAccepted"),
+    (0x71, "transferDataSuspended"),
+    (0x72, "generalProgrammingFailure"),
+    (0x73, "wrongBlockSequenceCounter"),
+    (0x78, "requestCorrectlyReceivedResponsePending"),
+    (0x7E, "subFunctionNotSupportedInActiveSession"),
+    (0x7F, "serviceNotSupportedInActiveSession"),
+    (0x81, "rpmTooHigh"),
+    (0x82, "rpmTooLow"),
+    (0x83, "engineIsRunning"),
+    (0x84, "engineIsNotRunning"),
+    (0x85, "engineRunTimeTooLow"),
+    (0x86, "temperatureTooHigh"),
+    (0x87, "temperatureTooLow"),
+    (0x88, "vehicleSpeedTooHigh"),
+    (0x89, "vehicleSpeedTooLow"),
+    (0x92, "voltageTooHigh"),
+    (0x93, "voltageTooLow"),
+];
+
+/// Resolve one `nrcs:` entry, given as a code or a [`NRC_TABLE`] name.
+pub fn resolve_nrc(v: &serde_yaml::Value) -> Option<u8> {
+    match v {
+        serde_yaml::Value::Number(n) => n.as_u64().and_then(|n| u8::try_from(n).ok()),
+        serde_yaml::Value::String(s) => NRC_TABLE
+            .iter()
+            .find(|(_, name)| name == s)
+            .map(|(code, _)| *code)
+            .or_else(|| diag_ir::parse_uint(s).and_then(|n| u8::try_from(n).ok())),
+        _ => None,
+    }
+}
+
+/// The `services:` entries with the request SIDs of the services they cover.
+pub(crate) fn entries_by_sid(
+    s: &YamlServices,
+) -> [(&'static str, &[u8], &Option<ServiceEntry>); 26] {
+    [
+        (
+            "diagnosticSessionControl",
+            &[0x10],
+            &s.diagnostic_session_control,
+        ),
+        ("ecuReset", &[0x11], &s.ecu_reset),
+        ("securityAccess", &[0x27], &s.security_access),
+        ("authentication", &[0x29], &s.authentication),
+        ("testerPresent", &[0x3E], &s.tester_present),
+        ("controlDTCSetting", &[0x85], &s.control_dtc_setting),
+        ("readDataByIdentifier", &[0x22], &s.read_data_by_identifier),
+        (
+            "writeDataByIdentifier",
+            &[0x2E],
+            &s.write_data_by_identifier,
+        ),
+        ("readDTCInformation", &[0x19], &s.read_dtc_information),
+        (
+            "clearDiagnosticInformation",
+            &[0x14],
+            &s.clear_diagnostic_information,
+        ),
+        (
+            "inputOutputControlByIdentifier",
+            &[0x2F],
+            &s.input_output_control,
+        ),
+        ("routineControl", &[0x31], &s.routine_control),
+        ("readMemoryByAddress", &[0x23], &s.read_memory_by_address),
+        ("writeMemoryByAddress", &[0x3D], &s.write_memory_by_address),
+        ("readScalingDataByIdentifier", &[0x24], &s.read_scaling_data),
+        (
+            "readDataByPeriodicIdentifier",
+            &[0x2A],
+            &s.read_data_periodic,
+        ),
+        (
+            "dynamicallyDefineDataIdentifier",
+            &[0x2C],
+            &s.dynamically_define_did,
+        ),
+        ("requestDownload", &[0x34], &s.request_download),
+        ("requestUpload", &[0x35], &s.request_upload),
+        ("transferData", &[0x36], &s.transfer_data),
+        ("requestTransferExit", &[0x37], &s.request_transfer_exit),
+        ("requestFileTransfer", &[0x38], &s.request_file_transfer),
+        (
+            "securedDataTransmission",
+            &[0x84],
+            &s.secured_data_transmission,
+        ),
+        ("communicationControl", &[0x28], &s.communication_control),
+        ("responseOnEvent", &[0x86], &s.response_on_event),
+        ("linkControl", &[0x87], &s.link_control),
+    ]
+}
+
+/// Give each service whose `services:` entry lists `nrcs` a negative
+/// response with those codes, replacing any generated default. Covers the
+/// generated services as well as DID and routine services. Services without
+/// listed codes and without an NRC-CONST response get `default_nrcs`
+/// (`defaults.nrcs`) when that is not empty.
+pub fn apply_nrcs(
+    diag_services: &mut [DiagService],
+    yaml_services: Option<&YamlServices>,
+    default_nrcs: &[serde_yaml::Value],
+) {
+    let entries = yaml_services.map(entries_by_sid);
+    let defaults: Vec<u8> = default_nrcs.iter().filter_map(resolve_nrc).collect();
+    for svc in diag_services {
+        let Some(sid) = crate::service_extractor::extract_sid(svc) else {
+            continue;
+        };
+        let nrcs = entries
+            .iter()
+            .flatten()
+            .find(|(_, sids, _)| sids.contains(&sid))
+            .and_then(|(_, _, entry)| entry.as_ref()?.nrcs.as_ref());
+        let codes: Vec<u8> = match nrcs {
+            Some(nrcs) => nrcs.iter().filter_map(resolve_nrc).collect(),
+            None if !has_nrc_const(svc) => defaults.clone(),
+            None => continue,
+        };
+        if !codes.is_empty() {
+            svc.neg_responses = vec![nrc_neg_response(&codes)];
+        }
+    }
+}
+
+fn has_nrc_const(svc: &DiagService) -> bool {
+    svc.neg_responses
+        .iter()
+        .flat_map(|r| &r.params)
+        .any(|p| matches!(p.specific_data, Some(ParamData::NrcConst { .. })))
+}
+
+/// ISO 14229-1 negative response: `7F <SID> <NRC>`, the NRC restricted to
+/// `codes` by an NRC-CONST param.
+fn nrc_neg_response(codes: &[u8]) -> Response {
+    let mut sidrq = matching_request_param("SIDRQ_NR", 1, 1);
+    sidrq.semantic = "SERVICEIDRQ".into();
+    let nrc = Param {
+        short_name: "NRC".into(),
+        param_type: ParamType::NrcConst,
+        semantic: "DATA".into(),
+        byte_position: Some(2),
+        bit_position: Some(0),
+        specific_data: Some(ParamData::NrcConst {
+            coded_values: codes.iter().map(ToString::to_string).collect(),
+            diag_coded_type: DiagCodedType {
+                base_data_type: DataType::AUint32,
+                is_high_low_byte_order: true,
+                specific_data: Some(DiagCodedTypeData::StandardLength {
+                    bit_length: 8,
+                    bit_mask: vec![],
+                    condensed: false,
+                }),
+                ..Default::default()
+            },
+        }),
+        ..Default::default()
+    };
+    Response {
+        response_type: ResponseType::NegResponse,
+        params: vec![coded_const_param("SID_NR", 0, 8, "127"), sidrq, nrc],
+        sdgs: None,
+        pos_response_suffix: None,
+        audience: None,
     }
 }
 
@@ -1009,6 +1554,38 @@ fn comm_control_service(name: &str, subfunc: u8) -> DiagService {
     )
 }
 
+/// ISO 14229-1 inputOutputControlParameter values, by `control_types` name.
+pub const IO_CONTROL_TYPES: &[(&str, u8)] = &[
+    ("returnControlToECU", 0x00),
+    ("resetToDefault", 0x01),
+    ("freezeCurrentState", 0x02),
+    ("shortTermAdjustment", 0x03),
+];
+
+/// InputOutputControlByIdentifier service for one control parameter. Only
+/// shortTermAdjustment carries a controlState in the request.
+fn io_control_service(name: &str, control: u8) -> DiagService {
+    let mut request = vec![
+        coded_const_param("SID_RQ", 0, 8, "47"),
+        value_param("DataIdentifier", 1, 16, "IDENTICAL_UINT_16"),
+        coded_const_param("InputOutputControlParameter", 3, 8, &control.to_string()),
+    ];
+    if control == 0x03 {
+        request.push(value_param("ControlState", 4, 0, "ControlStateRecord"));
+    }
+    build_service(
+        &format!("IOControl_{name}"),
+        "IO-CONTROL",
+        request,
+        vec![
+            coded_const_param("SID_PR", 0, 8, "111"),
+            matching_request_param("DataIdentifier", 1, 2),
+            matching_request_param("InputOutputControlParameter", 3, 1),
+            value_param("ControlStatusRecord", 4, 0, "ControlStateRecord"),
+        ],
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1154,6 +1731,49 @@ mod tests {
         assert_eq!(resp.params.len(), 2); // SID, subfunc echo
     }
 
+    #[test]
+    fn test_session_control_with_server_timing() {
+        let svc = services_with(|s| {
+            s.diagnostic_session_control = Some(enabled_entry());
+        });
+        let mut sessions = BTreeMap::new();
+        sessions.insert(
+            "extended".into(),
+            Session {
+                id: serde_yaml::Value::Number(3.into()),
+                alias: None,
+                requires_unlock: None,
+                timing: Some(SessionTiming {
+                    p2_ms: None,
+                    p2_star_ms: None,
+                    p2_server_max: Some(25),
+                    p2_star_server_max: Some(2000),
+                }),
+            },
+        );
+        let generator = ServiceGenerator::new(&svc).with_sessions(Some(&sessions));
+        let services = generator.generate_diagnostic_session_control();
+        let params = &services[0].pos_responses[0].params;
+        assert_eq!(params.len(), 4);
+        assert_eq!(params[2].short_name, P2_SERVER_MAX_PARAM);
+        assert_eq!(params[2].byte_position, Some(2));
+        assert_eq!(params[2].physical_default_value, "25");
+        assert_eq!(params[3].short_name, P2_STAR_SERVER_MAX_PARAM);
+        assert_eq!(params[3].byte_position, Some(4));
+        assert_eq!(params[3].physical_default_value, "2000");
+        let Some(ParamData::Value { dop, .. }) = &params[3].specific_data else {
+            panic!("P2* should be a VALUE param");
+        };
+        let Some(DopData::NormalDop {
+            compu_method: Some(cm),
+            ..
+        }) = &dop.specific_data
+        else {
+            panic!("P2* DOP should carry a compu method");
+        };
+        assert_eq!(cm.category, CompuCategory::Linear);
+    }
+
     #[test]
     fn test_security_access_generation() {
         let svc = services_with(|s| s.security_access = Some(enabled_entry()));
@@ -1376,4 +1996,208 @@ mod tests {
         assert!(generator.generate_request_download().is_empty());
         assert!(generator.generate_request_upload().is_empty());
     }
+
+    #[test]
+    fn test_check_memory_layout_follows_checksum_and_address_format() {
+        let block = |name: &str, checksum: Option<ChecksumType>| DataBlock {
+            name: name.into(),
+            description: None,
+            block_type: DataBlockType::Download,
+            memory_address: 0,
+            memory_size: 0,
+            format: DataBlockFormat::Raw,
+            max_block_length: None,
+            security_level: None,
+            session: None,
+            checksum_type: checksum,
+        };
+        let mc = MemoryConfig {
+            default_address_format: AddressFormat {
+                address_bytes: 4,
+                length_bytes: 2,
+            },
+            regions: vec![],
+            data_blocks: vec![
+                block("App Image", Some(ChecksumType::Sha256)),
+                block("NoCheck", None),
+                block("Cal", Some(ChecksumType::Crc16Ccitt)),
+            ],
+        };
+
+        let services = generate_check_memory(&mc);
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].diag_comm.short_name, "CheckMemory_App_Image");
+
+        let layout = |svc: &DiagService| -> Vec<(String, u32, u32)> {
+            svc.request.as_ref().unwrap().params[3..]
+                .iter()
+                .map(|p| {
+                    let Some(ParamData::Value { dop, .. }) = &p.specific_data else {
+                        panic!("{} is not a value param", p.short_name);
+                    };
+                    let Some(DopData::NormalDop {
+                        diag_coded_type: Some(dct),
+                        ..
+                    }) = &dop.specific_data
+                    else {
+                        panic!("{} has no coded type", p.short_name);
+                    };
+                    let Some(DiagCodedTypeData::StandardLength { bit_length, .. }) =
+                        &dct.specific_data
+                    else {
+                        panic!("{} is not standard length", p.short_name);
+                    };
+                    (
+                        p.short_name.to_string(),
+                        p.byte_position.unwrap(),
+                        *bit_length,
+                    )
+                })
+                .collect()
+        };
+        assert_eq!(
+            layout(&services[0]),
+            [
+                ("MemoryAddress".into(), 4, 32),
+                ("MemorySize".into(), 8, 16),
+                ("Checksum".into(), 10, 256),
+            ]
+        );
+        assert_eq!(layout(&services[1])[2], ("Checksum".into(), 10, 16));
+
+        let resp = &services[0].pos_responses[0].params;
+        assert_eq!(resp.last().unwrap().short_name, "RoutineStatus");
+    }
+
+    #[test]
+    fn test_input_output_control_from_control_types() {
+        let svc = services_with(|s| {
+            let mut entry = enabled_entry();
+            entry.control_types = Some(vec![
+                "returnControlToECU".into(),
+                "shortTermAdjustment".into(),
+                "notAControlType".into(),
+            ]);
+            s.input_output_control = Some(entry);
+        });
+        let services = ServiceGenerator::new(&svc).generate_input_output_control();
+        let names: Vec<&str> = services
+            .iter()
+            .map(|s| s.diag_comm.short_name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "IOControl_ReturnControlToECU",
+                "IOControl_ShortTermAdjustment"
+            ]
+        );
+        // Only shortTermAdjustment carries a controlState
+        assert_eq!(services[0].request.as_ref().unwrap().params.len(), 3);
+        let req = &services[1].request.as_ref().unwrap().params;
+        assert_eq!(req[3].short_name, "ControlState");
+        if let Some(ParamData::CodedConst { coded_value, .. }) = &req[2].specific_data {
+            assert_eq!(coded_value, "3");
+        } else {
+            panic!("expected CodedConst for InputOutputControlParameter");
+        }
+    }
+
+    #[test]
+    fn test_uds_profile_generates_standard_services() {
+        let svc = services_with(|s| s.profile = Some("uds".into()));
+        let services = ServiceGenerator::new(&svc).generate_all();
+        let count = |sid: u8| {
+            services
+                .iter()
+                .filter(|s| crate::service_extractor::extract_sid(s) == Some(sid))
+                .count()
+        };
+        assert_eq!(count(0x11), 5);
+        assert_eq!(count(0x14), 1);
+        assert_eq!(count(0x19), 16);
+        assert_eq!(count(0x28), 6);
+        assert_eq!(count(0x29), 9);
+        assert_eq!(count(0x2F), 4);
+        assert_eq!(count(0x34), 1);
+        assert_eq!(count(0x35), 1);
+        assert_eq!(count(0x36), 2);
+        assert_eq!(count(0x37), 2);
+        assert_eq!(count(0x3E), 1);
+        assert_eq!(count(0x85), 2);
+        // Sessions and security levels come from their own sections
+        assert_eq!(count(0x10), 0);
+        assert_eq!(count(0x27), 0);
+    }
+
+    #[test]
+    fn test_profile_entries_yield_to_own_entries() {
+        let svc = services_with(|s| {
+            s.profile = Some("uds".into());
+            s.ecu_reset = Some(ServiceEntry {
+                enabled: true,
+                features: Some(vec!["debug".into()]),
+                ..Default::default()
+            });
+            s.read_dtc_information = Some(ServiceEntry::default());
+        });
+        let generator = ServiceGenerator::new(&svc);
+        // An own entry without subfunctions takes the profile's
+        let resets = generator.generate_ecu_reset();
+        assert_eq!(resets.len(), 5);
+        assert!(
+            resets
+                .iter()
+                .any(|s| s.diag_comm.short_name == "EnableRapidPowerShutDown")
+        );
+        // A disabled own entry removes the service
+        assert!(generator.generate_read_dtc_information().is_empty());
+        assert!(profile_services("kwp").is_none());
+    }
+
+    #[test]
+    fn test_subfunction_access_overrides_entry_access() {
+        let state_ref = |session: &str| PreConditionStateRef {
+            value: "Session".into(),
+            in_param_if_short_name: String::new(),
+            in_param_path_short_name: session.into(),
+            state: None,
+        };
+        let patterns = HashMap::from([
+            ("extended".to_string(), vec![state_ref("Extended")]),
+            ("programming".to_string(), vec![state_ref("Programming")]),
+        ]);
+        let svc = services_with(|s| {
+            s.profile = Some("uds".into());
+            s.ecu_reset = Some(ServiceEntry {
+                enabled: true,
+                access: Some("extended".into()),
+                subfunction_access: Some(
+                    serde_yaml::from_str("{hardReset: programming, 0x03: programming}").unwrap(),
+                ),
+                ..Default::default()
+            });
+        });
+        let services = ServiceGenerator::new(&svc)
+            .with_access_patterns(Some(&patterns))
+            .generate_all();
+        let session_of = |name: &str| {
+            let svc = services
+                .iter()
+                .find(|s| s.diag_comm.short_name == name)
+                .unwrap();
+            svc.diag_comm.pre_condition_state_refs[0]
+                .in_param_path_short_name
+                .clone()
+        };
+        assert_eq!(session_of("HardReset"), "Programming");
+        assert_eq!(session_of("SoftReset"), "Programming");
+        assert_eq!(session_of("KeyOffOnReset"), "Extended");
+        // Services of other entries stay unrestricted
+        let tester_present = services
+            .iter()
+            .find(|s| s.diag_comm.short_name == "TesterPresent")
+            .unwrap();
+        assert!(tester_present.diag_comm.pre_condition_state_refs.is_empty());
+    }
 }
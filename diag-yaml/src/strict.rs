@@ -0,0 +1,288 @@
+//! Strict mode: keys the parser does not know.
+//!
+//! serde skips keys the YAML model has no field for, so a misspelled
+//! `writeable:` silently does nothing. The strict pass deserializes the
+//! document once more, recording every key serde skips, including those of
+//! the `dids`, `routines` and `dtcs` entries the parser reads from raw
+//! values. Suggestions come from the properties the embedded JSON Schema
+//! lists at the same place.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::parser::YamlParseError;
+use crate::yaml_model::{Did, Routine, YamlDocument, YamlDtc};
+
+/// A key the parser does not know.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownKey {
+    /// Keys and indices leading to the mapping that holds the key.
+    pub path: Vec<String>,
+    pub key: String,
+    /// The known key closest to `key`, if one is close enough.
+    pub suggestion: Option<String>,
+}
+
+impl UnknownKey {
+    /// JSON pointer of the mapping that holds the key, as in schema errors.
+    pub fn pointer(&self) -> String {
+        let mut pointer = String::new();
+        for segment in &self.path {
+            pointer.push('/');
+            pointer.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+        }
+        pointer
+    }
+}
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for segment in &self.path {
+            write!(f, "{segment}.")?;
+        }
+        write!(f, "{}: unknown key", self.key)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean '{suggestion}'?)")?;
+        }
+        Ok(())
+    }
+}
+
+/// One indented line per key, for error messages.
+pub(crate) fn list(keys: &[impl fmt::Display]) -> String {
+    let mut out = String::new();
+    for key in keys {
+        out.push_str("\n  ");
+        out.push_str(&key.to_string());
+    }
+    out
+}
+
+/// The sections the parser deserializes entry by entry.
+#[derive(Deserialize)]
+struct RawSections {
+    #[serde(default)]
+    dids: Option<BTreeMap<String, serde_yaml::Value>>,
+    #[serde(default)]
+    routines: Option<BTreeMap<String, serde_yaml::Value>>,
+    #[serde(default)]
+    dtcs: Option<BTreeMap<String, serde_yaml::Value>>,
+}
+
+/// List the keys of `yaml` that the parser does not know, sorted by path.
+/// Merge keys are resolved first, so `<<` itself is never reported.
+pub fn unknown_keys(yaml: &str) -> Result<Vec<UnknownKey>, YamlParseError> {
+    let report = crate::anchors::analyze_anchors(yaml)?;
+    let mut paths: Vec<Vec<String>> = Vec::new();
+    // Without merge keys, deserialize the text itself so keys keep their
+    // spelling (`0xF190`, not `61840`).
+    let sections: Option<RawSections> = if report.has_merge_keys {
+        let mut value: serde_yaml::Value = serde_yaml::from_str(yaml)?;
+        crate::anchors::apply_merges(&mut value)?;
+        ignored::<YamlDocument, _>(value.clone(), &[], &mut paths)?;
+        serde_yaml::from_value(value).ok()
+    } else {
+        ignored::<YamlDocument, _>(serde_yaml::Deserializer::from_str(yaml), &[], &mut paths)?;
+        serde_yaml::from_str(yaml).ok()
+    };
+
+    if let Some(sections) = sections {
+        let entries = |section: &'static str, map: Option<BTreeMap<String, serde_yaml::Value>>| {
+            map.into_iter()
+                .flatten()
+                .map(move |(key, value)| (vec![section.to_string(), key], value))
+        };
+        for (prefix, value) in entries("dids", sections.dids) {
+            // Entries that do not deserialize at all are skipped by the
+            // parser as well; only their unknown keys matter here.
+            let _ = ignored::<Did, _>(value, &prefix, &mut paths);
+        }
+        for (prefix, value) in entries("routines", sections.routines) {
+            let _ = ignored::<Routine, _>(value, &prefix, &mut paths);
+        }
+        for (prefix, value) in entries("dtcs", sections.dtcs) {
+            let _ = ignored::<YamlDtc, _>(value, &prefix, &mut paths);
+        }
+    }
+
+    paths.sort();
+    paths.dedup();
+    let schema = crate::validator::embedded_schema();
+    Ok(paths
+        .into_iter()
+        .filter_map(|mut path| {
+            let key = path.pop()?;
+            let suggestion = nearest(&key, &schema_properties(&schema, &path));
+            Some(UnknownKey {
+                path,
+                key,
+                suggestion,
+            })
+        })
+        .collect())
+}
+
+/// Deserialize a `T` from `de`, appending the path of every skipped key
+/// (after `prefix`) to `paths`.
+fn ignored<'de, T, D>(
+    de: D,
+    prefix: &[String],
+    paths: &mut Vec<Vec<String>>,
+) -> Result<(), YamlParseError>
+where
+    T: DeserializeOwned,
+    D: serde::Deserializer<'de, Error = serde_yaml::Error>,
+{
+    serde_ignored::deserialize::<_, _, T>(de, |path| {
+        let mut segments = Vec::new();
+        collect_segments(&path, &mut segments);
+        paths.push(prefix.iter().cloned().chain(segments).collect());
+    })?;
+    Ok(())
+}
+
+fn collect_segments(path: &serde_ignored::Path, out: &mut Vec<String>) {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => {}
+        Path::Seq { parent, index } => {
+            collect_segments(parent, out);
+            out.push(index.to_string());
+        }
+        Path::Map { parent, key } => {
+            collect_segments(parent, out);
+            out.push(key.clone());
+        }
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => {
+            collect_segments(parent, out);
+        }
+    }
+}
+
+/// The properties the schema allows in the mapping at `path`.
+fn schema_properties<'a>(root: &'a Value, path: &[String]) -> Vec<&'a String> {
+    let mut nodes = vec![root];
+    for segment in path {
+        let mut next = Vec::new();
+        for node in nodes {
+            for branch in branches(node, root) {
+                let child = branch
+                    .get("properties")
+                    .and_then(|p| p.get(segment))
+                    .or_else(|| branch.get("items"))
+                    .or_else(|| branch.get("additionalProperties").filter(|a| a.is_object()));
+                next.extend(child);
+            }
+        }
+        nodes = next;
+    }
+    nodes
+        .into_iter()
+        .flat_map(|node| branches(node, root))
+        .filter_map(|b| b.get("properties").and_then(Value::as_object))
+        .flat_map(Map::keys)
+        .collect()
+}
+
+/// `node` and the schemas its `$ref`, `oneOf`, `anyOf` and `allOf` lead to.
+fn branches<'a>(node: &'a Value, root: &'a Value) -> Vec<&'a Value> {
+    let mut out = Vec::new();
+    let mut pending = vec![node];
+    while let Some(node) = pending.pop() {
+        // Bounded, so a `$ref` cycle cannot hang the walk.
+        if out.len() > 64 {
+            break;
+        }
+        if let Some(target) = node
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|r| r.strip_prefix('#'))
+            .and_then(|pointer| root.pointer(pointer))
+        {
+            pending.push(target);
+        }
+        for keyword in ["oneOf", "anyOf", "allOf"] {
+            pending.extend(
+                node.get(keyword)
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten(),
+            );
+        }
+        out.push(node);
+    }
+    out
+}
+
+/// The candidate closest to `key`, if within a third of its length in edits
+/// (at least one).
+fn nearest(key: &str, candidates: &[&String]) -> Option<String> {
+    let lower = key.to_lowercase();
+    let limit = (key.chars().count() / 3).max(1);
+    candidates
+        .iter()
+        .filter(|candidate| candidate.as_str() != key)
+        .map(|candidate| {
+            (
+                strsim::levenshtein(&lower, &candidate.to_lowercase()),
+                *candidate,
+            )
+        })
+        .filter(|(distance, _)| *distance <= limit)
+        .min()
+        .map(|(_, candidate)| candidate.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_keys_are_found_with_suggestions() {
+        let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+meta:
+  autor: "Test"
+ecu:
+  name: "ECU"
+dids:
+  0xF190:
+    name: VIN
+    writeable: true
+    frobnicate: 1
+x-oem:
+  anything: goes
+"#;
+        let keys = unknown_keys(yaml).unwrap();
+        let text: Vec<String> = keys.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            text,
+            [
+                "dids.0xF190.frobnicate: unknown key",
+                "dids.0xF190.writeable: unknown key (did you mean 'writable'?)",
+                "meta.autor: unknown key (did you mean 'author'?)",
+            ]
+        );
+        assert_eq!(keys[0].pointer(), "/dids/0xF190");
+    }
+
+    #[test]
+    fn test_merge_keys_are_not_reported() {
+        let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+sessions:
+  default: &session
+    id: 0x01
+  extended:
+    <<: *session
+    id: 0x03
+"#;
+        assert_eq!(unknown_keys(yaml).unwrap(), []);
+    }
+}
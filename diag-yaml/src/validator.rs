@@ -19,6 +19,11 @@ impl std::fmt::Display for SchemaError {
     }
 }
 
+/// The JSON Schema of the YAML format.
+pub(crate) fn embedded_schema() -> Value {
+    serde_json::from_str(SCHEMA_JSON).expect("embedded schema is valid JSON")
+}
+
 /// Validate a YAML string against the embedded JSON Schema.
 ///
 /// Returns `Ok(())` if valid, or a list of schema validation errors.
@@ -30,7 +35,7 @@ pub fn validate_yaml_schema(yaml_text: &str) -> Result<(), Vec<SchemaError>> {
         }]
     })?;
 
-    let schema: Value = serde_json::from_str(SCHEMA_JSON).expect("embedded schema is valid JSON");
+    let schema = embedded_schema();
 
     let validator =
         jsonschema::draft202012::new(&schema).expect("embedded schema is a valid JSON Schema");
@@ -3,6 +3,7 @@
 //! Converts the canonical DiagDatabase IR back to a YAML string using the
 //! OpenSOVD CDA diagnostic YAML schema format.
 
+use crate::numerals::NumeralPolicy;
 use crate::service_extractor;
 use crate::yaml_model::*;
 use diag_ir::*;
@@ -13,13 +14,121 @@ use std::collections::{BTreeMap, HashMap};
 pub enum YamlWriteError {
     #[error("YAML serialization error: {0}")]
     Yaml(#[from] serde_yaml::Error),
+    #[error("unknown YAML section '{0}' (expected one of: {list})", list = SECTIONS.join(", "))]
+    UnknownSection(String),
+}
+
+/// Top-level keys of a YAML document, in the order they are written.
+pub const SECTIONS: &[&str] = &[
+    "schema",
+    "meta",
+    "ecu",
+    "audience",
+    "sdgs",
+    "comparams",
+    "sessions",
+    "state_model",
+    "security",
+    "authentication",
+    "identification",
+    "variants",
+    "services",
+    "access_patterns",
+    "types",
+    "dids",
+    "routines",
+    "dtc_config",
+    "dtcs",
+    "annotations",
+    "x-oem",
+    "ecu_jobs",
+    "memory",
+    "flash_sequence",
+    "functional_classes",
+    "protocols",
+    "ecu_shared_data",
+];
+
+/// Sections written with every partial document, so it is still recognised
+/// as YAML input and merges into the document of the same ECU.
+const HEADER_SECTIONS: &[&str] = &["schema", "ecu"];
+
+/// What the YAML format can carry. Services are regenerated from templates,
+/// so tables, fields/multiplexers and functional groups have no YAML form, and
+/// only the session, security and authentication state charts are kept.
+/// Descriptions are only written for DIDs, routines, jobs and variants, not
+/// for the other services or the base layer, so they count as lost.
+pub const CAPABILITIES: FormatCapabilities = FormatCapabilities {
+    format: "YAML",
+    functional_groups: false,
+    memory: true,
+    type_definitions: true,
+    descriptions: false,
+    tables: false,
+    complex_dops: false,
+    additional_audiences: false,
+    custom_state_charts: false,
+};
+
+/// Options for [`write_yaml_with`].
+#[derive(Debug, Clone, Default)]
+pub struct YamlWriteOptions {
+    /// Spelling of DID, RID and DTC keys.
+    pub numerals: NumeralPolicy,
+    /// Write only these top-level sections (plus `schema` and `ecu`);
+    /// empty writes the whole document.
+    pub sections: Vec<String>,
+}
+
+/// Check that every name in `sections` is one of [`SECTIONS`].
+pub fn check_sections(sections: &[String]) -> Result<(), YamlWriteError> {
+    match sections
+        .iter()
+        .find(|name| !SECTIONS.contains(&name.as_str()))
+    {
+        Some(name) => Err(YamlWriteError::UnknownSection(name.clone())),
+        None => Ok(()),
+    }
 }
 
 /// Write a DiagDatabase IR to a YAML string.
 pub fn write_yaml(db: &DiagDatabase) -> Result<String, YamlWriteError> {
+    write_yaml_with(db, &YamlWriteOptions::default())
+}
+
+/// Write a DiagDatabase IR to a YAML string with explicit options.
+pub fn write_yaml_with(
+    db: &DiagDatabase,
+    opts: &YamlWriteOptions,
+) -> Result<String, YamlWriteError> {
     let doc = ir_to_yaml(db);
-    let yaml = serde_yaml::to_string(&doc)?;
-    Ok(yaml)
+    let yaml = if opts.sections.is_empty() {
+        serde_yaml::to_string(&doc)?
+    } else {
+        check_sections(&opts.sections)?;
+        serde_yaml::to_string(&select_sections(
+            serde_yaml::to_value(&doc)?,
+            &opts.sections,
+        ))?
+    };
+    Ok(crate::numerals::apply(&yaml, opts.numerals))
+}
+
+/// Keep the header and the requested top-level sections of a serialized
+/// document, dropping the ones with no content.
+fn select_sections(doc: serde_yaml::Value, sections: &[String]) -> serde_yaml::Value {
+    let serde_yaml::Value::Mapping(map) = doc else {
+        return doc;
+    };
+    let kept = map
+        .into_iter()
+        .filter(|(key, value)| {
+            key.as_str().is_some_and(|key| {
+                HEADER_SECTIONS.contains(&key) || sections.iter().any(|s| s == key)
+            }) && !value.is_null()
+        })
+        .collect();
+    serde_yaml::Value::Mapping(kept)
 }
 
 /// Convert an IR Audience to the per-service YAML audience struct.
@@ -43,7 +152,7 @@ fn ir_audience_to_yaml(a: &Audience) -> Option<YamlServiceAudience> {
         groups: a
             .enabled_audiences
             .iter()
-            .map(|aa| aa.short_name.clone())
+            .map(|aa| aa.short_name.to_string())
             .collect(),
     })
 }
@@ -67,6 +176,7 @@ fn ir_to_yaml(db: &DiagDatabase) -> YamlDocument {
         description: db.metadata.get("description").cloned().unwrap_or_default(),
         tags: vec![],
         revisions: vec![],
+        validity: validity(db),
     });
 
     let ecu = Some(Ecu {
@@ -74,7 +184,7 @@ fn ir_to_yaml(db: &DiagDatabase) -> YamlDocument {
         name: db.ecu_name.clone(),
         protocols: None,
         default_addressing_mode: None,
-        addressing: None,
+        addressing: ir_transport_to_yaml(db.transport.as_ref()),
         annotations: None,
     });
 
@@ -93,8 +203,11 @@ fn ir_to_yaml(db: &DiagDatabase) -> YamlDocument {
                 YamlType {
                     base: td.base.clone(),
                     bit_length: td.bit_length,
+                    bit_mask: td.bit_mask.map(hex_mask),
+                    condensed: td.condensed,
                     min_length: td.min_length,
                     max_length: td.max_length,
+                    length_prefix_bits: td.length_prefix_bits,
                     enum_values: td
                         .enum_values_json
                         .as_ref()
@@ -105,8 +218,20 @@ fn ir_to_yaml(db: &DiagDatabase) -> YamlDocument {
         })
         .collect();
 
+    // CheckMemory routines are regenerated from the memory config on parse.
+    let check_memory: Vec<String> = db
+        .memory
+        .iter()
+        .flat_map(|mc| &mc.data_blocks)
+        .filter(|b| b.checksum_type.is_some())
+        .map(|b| check_memory_service_name(&b.name))
+        .collect();
+
     if let Some(layer) = layer {
         for svc in &layer.diag_services {
+            if check_memory.iter().any(|n| *n == svc.diag_comm.short_name) {
+                continue;
+            }
             if svc.diag_comm.short_name.starts_with("Routine_")
                 || extract_sid_value(svc) == Some(0x31)
             {
@@ -123,15 +248,26 @@ fn ir_to_yaml(db: &DiagDatabase) -> YamlDocument {
                     .unwrap_or(&svc.diag_comm.short_name);
 
                 // Extract type info from DOP if available
-                let (did_type_val, type_name) = extract_did_type(svc, did_name);
-
-                // Register named type if we extracted one
-                if let Some((name, yaml_type)) = type_name {
-                    types_map.entry(name).or_insert(yaml_type);
+                let (mut did_type_val, type_name) = extract_did_type(svc, did_name);
+                let (snap, ioc, type_ref) = extract_did_extra(svc);
+
+                match (type_name, type_ref) {
+                    // A named type: refer to it and fill in what the type
+                    // definition does not record from the DOP.
+                    (Some((_, yaml_type)), Some(name)) if types_map.contains_key(&name) => {
+                        if let Some(named) = types_map.get_mut(&name) {
+                            fill_named_type(named, yaml_type);
+                        }
+                        did_type_val = serde_yaml::Value::String(name);
+                    }
+                    // Register named type if we extracted one
+                    (Some((name, yaml_type)), _) => {
+                        types_map.entry(name).or_insert(yaml_type);
+                    }
+                    (None, _) => {}
                 }
 
                 let access_name = extract_access_pattern_name(&svc.diag_comm);
-                let (snap, ioc) = extract_did_extra(svc);
                 let data_param_name = svc.pos_responses.first().and_then(|resp| {
                     resp.params
                         .iter()
@@ -144,7 +280,7 @@ fn ir_to_yaml(db: &DiagDatabase) -> YamlDocument {
                 let did = Did {
                     name: did_name.to_string(),
                     param_name,
-                    description: svc.diag_comm.long_name.as_ref().map(|ln| ln.value.clone()),
+                    description: comm_description(&svc.diag_comm),
                     did_type: did_type_val,
                     access: if access_name.is_empty() {
                         "public".into()
@@ -161,6 +297,10 @@ fn ir_to_yaml(db: &DiagDatabase) -> YamlDocument {
                         .audience
                         .as_ref()
                         .and_then(ir_audience_to_yaml),
+                    features: extract_feature_tags(&svc.diag_comm),
+                    lifecycle: lifecycle(svc.diag_comm.sdgs.as_ref()),
+                    requirements: extract_requirements(&svc.diag_comm),
+                    aliases: extract_aliases(&svc.diag_comm, "_Read"),
                 };
 
                 let key = serde_yaml::Value::Number(serde_yaml::Number::from(did_id as u64));
@@ -193,12 +333,19 @@ fn ir_to_yaml(db: &DiagDatabase) -> YamlDocument {
             let key = serde_yaml::Value::Number(serde_yaml::Number::from(dtc.trouble_code as u64));
             let (snapshots, extended_data) = extract_dtc_records(dtc);
             let yaml_dtc = YamlDtc {
-                name: dtc.short_name.clone(),
+                name: dtc.short_name.to_string(),
                 sae: dtc.display_trouble_code.clone(),
                 description: dtc.text.as_ref().map(|t| t.value.clone()),
                 severity: dtc.level,
                 snapshots,
                 extended_data,
+                lifecycle: lifecycle(dtc.sdgs.as_ref()),
+                text_id: dtc
+                    .text
+                    .as_ref()
+                    .map(|t| t.ti.clone())
+                    .filter(|ti| !ti.is_empty()),
+                translations: dtc_translations(dtc.sdgs.as_ref()),
                 x_oem: None,
             };
             dtc_map.insert(key, serde_yaml::to_value(&yaml_dtc).unwrap_or_default());
@@ -231,7 +378,7 @@ fn ir_to_yaml(db: &DiagDatabase) -> YamlDocument {
         audience: None,
         sdgs,
         comparams: base_variant.and_then(|v| extract_comparams(&v.diag_layer)),
-        sessions: layer.and_then(|l| extract_sessions_from_state_charts(&l.state_charts)),
+        sessions: layer.and_then(extract_sessions_from_state_charts),
         state_model: layer.and_then(|l| extract_state_model_from_state_charts(&l.state_charts)),
         security: layer.and_then(|l| {
             let mut levels = extract_security_from_state_charts(&l.state_charts)?;
@@ -240,12 +387,14 @@ fn ir_to_yaml(db: &DiagDatabase) -> YamlDocument {
         }),
         authentication: layer
             .and_then(|l| extract_authentication_from_state_charts(&l.state_charts)),
-        identification: base_variant.and_then(|v| extract_identification(&v.diag_layer)),
+        identification: extract_identification(db, base_variant.map(|v| &v.diag_layer)),
         variants: extract_variants(db),
         services: layer
             .map(|l| service_extractor::extract_services(&l.diag_services))
-            .filter(service_extractor::has_any_service),
+            .filter(service_extractor::has_any_service)
+            .map(Box::new),
         access_patterns: base_variant.and_then(extract_access_patterns),
+        defaults: None,
         types: if types_map.is_empty() {
             None
         } else {
@@ -267,12 +416,15 @@ fn ir_to_yaml(db: &DiagDatabase) -> YamlDocument {
         x_oem: base_variant.and_then(|v| extract_sdg_json(&v.diag_layer, "yaml_x_oem")),
         ecu_jobs,
         memory: db.memory.as_ref().map(ir_memory_to_yaml),
+        flash_sequence: base_variant
+            .map(|v| flash_sequence(v.diag_layer.sdgs.as_ref()))
+            .filter(|steps| !steps.is_empty()),
         functional_classes: base_variant.and_then(|v| {
             let classes: Vec<String> = v
                 .diag_layer
                 .funct_classes
                 .iter()
-                .map(|fc| fc.short_name.clone())
+                .map(|fc| fc.short_name.to_string())
                 .collect();
             if classes.is_empty() {
                 None
@@ -324,11 +476,9 @@ fn extract_routine_id(svc: &DiagService) -> u32 {
 }
 
 fn parse_coded_value(s: &str) -> u32 {
-    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
-        u32::from_str_radix(hex, 16).unwrap_or(0)
-    } else {
-        s.parse().unwrap_or(0)
-    }
+    diag_ir::parse_uint(s)
+        .and_then(|v| u32::try_from(v).ok())
+        .unwrap_or(0)
 }
 
 /// Extract DID type info from the service's response DOP.
@@ -349,6 +499,7 @@ fn extract_did_type(
                     compu_method,
                     unit_ref,
                     internal_constr,
+                    physical_type,
                     ..
                 }) = &dop.specific_data
                 {
@@ -356,10 +507,17 @@ fn extract_did_type(
                         base: String::new(),
                         dop_name: None,
                         endian: None,
+                        display_radix: physical_type
+                            .as_ref()
+                            .and_then(|pt| radix_name(pt.display_radix)),
+                        signed_encoding: None,
                         bit_length: None,
+                        bit_mask: None,
+                        condensed: None,
                         length: None,
                         min_length: None,
                         max_length: None,
+                        length_prefix_bits: None,
                         encoding: None,
                         termination: None,
                         scale: None,
@@ -379,6 +537,7 @@ fn extract_did_type(
 
                     if let Some(dct) = diag_coded_type {
                         yaml_type.base = data_type_to_base(&dct.base_data_type);
+                        yaml_type.signed_encoding = signed_encoding(&dct.base_type_encoding);
                         if !dct.is_high_low_byte_order {
                             yaml_type.endian = Some("little".into());
                         } else if matches!(
@@ -389,9 +548,23 @@ fn extract_did_type(
                         }
 
                         match &dct.specific_data {
-                            Some(DiagCodedTypeData::StandardLength { bit_length, .. }) => {
+                            Some(DiagCodedTypeData::StandardLength {
+                                bit_length,
+                                bit_mask,
+                                condensed,
+                            }) => {
                                 yaml_type.bit_length = Some(*bit_length);
                                 yaml_type.base = bit_length_to_base(*bit_length, &yaml_type.base);
+                                if !bit_mask.is_empty() {
+                                    let mask = bit_mask
+                                        .iter()
+                                        .take(8)
+                                        .fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+                                    yaml_type.bit_mask = Some(hex_mask(mask));
+                                }
+                                if *condensed {
+                                    yaml_type.condensed = Some(true);
+                                }
                             }
                             Some(DiagCodedTypeData::MinMax {
                                 min_length,
@@ -406,6 +579,9 @@ fn extract_did_type(
                                     Termination::EndOfPdu => "end_of_pdu".into(),
                                 });
                             }
+                            Some(DiagCodedTypeData::LeadingLength { bit_length }) => {
+                                yaml_type.length_prefix_bits = Some(*bit_length);
+                            }
                             _ => {}
                         }
                     }
@@ -482,7 +658,7 @@ fn extract_did_type(
                                 let mut field_map = serde_yaml::Mapping::new();
                                 field_map.insert(
                                     serde_yaml::Value::String("name".into()),
-                                    serde_yaml::Value::String(p.short_name.clone()),
+                                    serde_yaml::Value::String(p.short_name.to_string()),
                                 );
                                 if let Some(DopData::NormalDop {
                                     diag_coded_type, ..
@@ -502,7 +678,7 @@ fn extract_did_type(
                                             serde_yaml::Value::String("base".into()),
                                             serde_yaml::Value::String(base),
                                         );
-                                        if dct.base_type_encoding == "signed" {
+                                        if is_signed(&dct.base_type_encoding) {
                                             // The IR maps all integer types to AUint32 and
                                             // tracks signedness separately in base_type_encoding.
                                             // Restore the signed YAML base type so the
@@ -523,6 +699,14 @@ fn extract_did_type(
                                                 );
                                             }
                                         }
+                                        if let Some(encoding) =
+                                            signed_encoding(&dct.base_type_encoding)
+                                        {
+                                            type_map.insert(
+                                                serde_yaml::Value::String("signed_encoding".into()),
+                                                serde_yaml::Value::String(encoding),
+                                            );
+                                        }
                                         if dct.is_high_low_byte_order
                                             && matches!(
                                                 dct.base_data_type,
@@ -572,6 +756,63 @@ fn extract_did_type(
     (serde_yaml::Value::Mapping(serde_yaml::Mapping::new()), None)
 }
 
+/// `ecu.addressing` holding the DoIP addressing, or `None` without any.
+fn ir_transport_to_yaml(transport: Option<&TransportConfig>) -> Option<serde_yaml::Value> {
+    let doip = transport?.doip.as_ref().filter(|d| !d.is_empty())?;
+    let mut map = serde_yaml::Mapping::new();
+    if let Some(ip) = &doip.ip {
+        map.insert("ip".into(), ip.as_str().into());
+    }
+    if let Some(port) = doip.port {
+        map.insert("port".into(), port.into());
+    }
+    let addresses = [
+        ("logical_address", doip.logical_address),
+        ("functional_address", doip.functional_address),
+        ("tester_address", doip.tester_address),
+    ];
+    for (key, address) in addresses {
+        if let Some(address) = address {
+            map.insert(key.into(), format!("0x{address:04X}").into());
+        }
+    }
+    for (key, id) in [("eid", doip.eid), ("gid", doip.gid)] {
+        if let Some(id) = id {
+            map.insert(key.into(), transport::format_entity_id(&id).into());
+        }
+    }
+    let mut addressing = serde_yaml::Mapping::new();
+    addressing.insert("doip".into(), serde_yaml::Value::Mapping(map));
+    Some(serde_yaml::Value::Mapping(addressing))
+}
+
+fn hex_mask(mask: u64) -> serde_yaml::Value {
+    serde_yaml::Value::String(format!("0x{mask:X}"))
+}
+
+/// The `display_radix` of a type displayed in `radix`; `None` for decimal,
+/// the built-in default.
+fn radix_name(radix: Radix) -> Option<String> {
+    match radix {
+        Radix::Dec => None,
+        Radix::Hex => Some("hex".into()),
+        Radix::Bin => Some("bin".into()),
+        Radix::Oct => Some("oct".into()),
+    }
+}
+
+/// Whether a base type encoding is one of a signed type: `signed` or an
+/// ODX signed encoding.
+fn is_signed(encoding: &str) -> bool {
+    matches!(encoding, "signed" | "2C" | "1C" | "SM")
+}
+
+/// The `signed_encoding` of a signed type whose encoding is not the built-in
+/// `signed`.
+fn signed_encoding(encoding: &str) -> Option<String> {
+    (is_signed(encoding) && encoding != "signed").then(|| encoding.to_string())
+}
+
 fn data_type_to_base(dt: &DataType) -> String {
     match dt {
         DataType::AUint32 => "u32".into(),
@@ -601,7 +842,7 @@ fn bit_length_to_base(bit_length: u32, current: &str) -> String {
 
 /// Convert a DiagService back to a Routine YAML model.
 /// Extract the access pattern name stored in SDG metadata by the parser.
-fn extract_access_pattern_name(diag_comm: &DiagComm) -> String {
+pub(crate) fn extract_access_pattern_name(diag_comm: &DiagComm) -> String {
     if let Some(sdgs) = &diag_comm.sdgs {
         for sdg in &sdgs.sdgs {
             if sdg.caption_sn == "access_pattern" {
@@ -614,26 +855,53 @@ fn extract_access_pattern_name(diag_comm: &DiagComm) -> String {
     String::new()
 }
 
-/// Extract DID snapshot and io_control from SDG "did_extra" on a service.
-fn extract_did_extra(svc: &DiagService) -> (Option<bool>, Option<serde_yaml::Value>) {
+/// Requirement IDs stored by the parser, `None` when the item has none.
+pub(crate) fn extract_requirements(diag_comm: &DiagComm) -> Option<Vec<String>> {
+    let ids = requirements(diag_comm);
+    (!ids.is_empty()).then(|| ids.into_iter().map(str::to_string).collect())
+}
+
+/// Aliases stored by the parser without the `suffix` the service name adds
+/// to the item name, `None` when the item has none.
+pub(crate) fn extract_aliases(diag_comm: &DiagComm, suffix: &str) -> Option<Vec<String>> {
+    let names = aliases(diag_comm);
+    (!names.is_empty()).then(|| {
+        names
+            .into_iter()
+            .map(|name| name.strip_suffix(suffix).unwrap_or(name).to_string())
+            .collect()
+    })
+}
+
+/// Feature tags stored by the parser, `None` when the item is untagged.
+pub(crate) fn extract_feature_tags(diag_comm: &DiagComm) -> Option<Vec<String>> {
+    let tags = feature_tags(diag_comm);
+    (!tags.is_empty()).then(|| tags.into_iter().map(str::to_string).collect())
+}
+
+/// Extract DID snapshot, io_control and the named type reference from SDG
+/// "did_extra" on a service.
+fn extract_did_extra(
+    svc: &DiagService,
+) -> (Option<bool>, Option<serde_yaml::Value>, Option<String>) {
     let sdgs = match &svc.diag_comm.sdgs {
         Some(s) => s,
-        None => return (None, None),
+        None => return (None, None, None),
     };
     let entry = match sdgs.sdgs.iter().find(|e| e.caption_sn == "did_extra") {
         Some(e) => e,
-        None => return (None, None),
+        None => return (None, None, None),
     };
     let sd = match entry.sds.iter().find_map(|c| match c {
         SdOrSdg::Sd(sd) => Some(&sd.value),
         SdOrSdg::Sdg(_) => None,
     }) {
         Some(s) => s,
-        None => return (None, None),
+        None => return (None, None, None),
     };
     let json_val: serde_json::Value = match serde_json::from_str(sd) {
         Ok(v) => v,
-        Err(_) => return (None, None),
+        Err(_) => return (None, None, None),
     };
     let snapshot = json_val
         .get("snapshot")
@@ -641,7 +909,29 @@ fn extract_did_extra(svc: &DiagService) -> (Option<bool>, Option<serde_yaml::Val
     let io_control = json_val
         .get("io_control")
         .and_then(|v| serde_json::from_value::<serde_yaml::Value>(v.clone()).ok());
-    (snapshot, io_control)
+    let type_ref = json_val
+        .get("type")
+        .and_then(serde_json::Value::as_str)
+        .map(str::to_string);
+    (snapshot, io_control, type_ref)
+}
+
+/// Complete a type rebuilt from a [`TypeDefinition`] with the DOP details the
+/// definition does not record (endianness, length, scaling, unit, ...). The
+/// fields the definition does record are left alone so it reads back equal.
+fn fill_named_type(named: &mut YamlType, from_dop: YamlType) {
+    named.endian = named.endian.take().or(from_dop.endian);
+    named.display_radix = named.display_radix.take().or(from_dop.display_radix);
+    named.signed_encoding = named.signed_encoding.take().or(from_dop.signed_encoding);
+    named.length = named.length.take().or(from_dop.length);
+    named.encoding = named.encoding.take().or(from_dop.encoding);
+    named.termination = named.termination.take().or(from_dop.termination);
+    named.scale = named.scale.take().or(from_dop.scale);
+    named.offset = named.offset.take().or(from_dop.offset);
+    named.unit = named.unit.take().or(from_dop.unit);
+    named.constraints = named.constraints.take().or(from_dop.constraints);
+    named.fields = named.fields.take().or(from_dop.fields);
+    named.size = named.size.take().or(from_dop.size);
 }
 
 /// Extract a JSON-serialized SDG entry from a DiagLayer by caption, returning it as serde_yaml::Value.
@@ -657,19 +947,38 @@ fn extract_sdg_json(layer: &DiagLayer, caption: &str) -> Option<serde_yaml::Valu
 }
 
 /// Reconstruct access_patterns from PreConditionStateRef data on services.
-/// Extract identification section from DiagLayer SDG metadata.
-fn extract_identification(layer: &DiagLayer) -> Option<Identification> {
-    let sdgs = layer.sdgs.as_ref()?;
-    for sdg in &sdgs.sdgs {
-        if sdg.caption_sn == "identification" {
-            if let Some(SdOrSdg::Sd(sd)) = sdg.sds.first() {
-                if let Ok(ident) = serde_yaml::from_str::<Identification>(&sd.value) {
-                    return Some(ident);
-                }
-            }
-        }
+/// Extract the identification section from `db.identification` and the
+/// SDG metadata of the base layer.
+fn extract_identification(db: &DiagDatabase, layer: Option<&DiagLayer>) -> Option<Identification> {
+    let sdgs = layer.and_then(|l| l.sdgs.as_ref());
+    let mut identification = sdgs
+        .iter()
+        .flat_map(|s| &s.sdgs)
+        .filter(|sdg| sdg.caption_sn == "identification")
+        .find_map(|sdg| match sdg.sds.first() {
+            Some(SdOrSdg::Sd(sd)) => serde_yaml::from_str::<Identification>(&sd.value).ok(),
+            _ => None,
+        });
+    if let Some(typed) = db.identification.clone().filter(|i| !i.is_empty()) {
+        let ident = identification.get_or_insert_with(Identification::default);
+        ident.part_number = typed.part_number;
+        ident.supplier_code = typed.supplier_code;
+        ident.hardware_version = typed.hardware_version;
+        ident.software_version = typed.software_version;
+        ident.dids = Some(typed.dids)
+            .filter(|dids| !dids.is_empty())
+            .map(|dids| {
+                dids.into_iter()
+                    .map(|(role, did)| (role, serde_yaml::Value::String(format!("0x{did:04X}"))))
+                    .collect()
+            });
     }
-    None
+    if let Some(compatibility) = compatibility_of(sdgs).filter(|c| !c.is_empty()) {
+        identification
+            .get_or_insert_with(Identification::default)
+            .compatibility = Some(compatibility);
+    }
+    identification
 }
 
 /// Extract snapshot and extended_data references from DTC SDGs.
@@ -992,6 +1301,15 @@ fn extract_access_patterns(variant: &Variant) -> Option<BTreeMap<String, AccessP
     }
 }
 
+/// The `description:` of a DID, routine or job: the rich-text description of
+/// its service, else the service's long name.
+fn comm_description(diag_comm: &DiagComm) -> Option<String> {
+    diag_comm
+        .description
+        .clone()
+        .or_else(|| diag_comm.long_name.as_ref().map(|ln| ln.value.clone()))
+}
+
 fn service_to_routine(svc: &DiagService) -> Routine {
     let mut operations = vec![];
     if svc.request.is_some() {
@@ -1003,8 +1321,8 @@ fn service_to_routine(svc: &DiagService) -> Routine {
 
     let access_name = extract_access_pattern_name(&svc.diag_comm);
     Routine {
-        name: svc.diag_comm.short_name.clone(),
-        description: svc.diag_comm.long_name.as_ref().map(|ln| ln.value.clone()),
+        name: svc.diag_comm.short_name.to_string(),
+        description: comm_description(&svc.diag_comm),
         access: if access_name.is_empty() {
             "public".into()
         } else {
@@ -1017,6 +1335,10 @@ fn service_to_routine(svc: &DiagService) -> Routine {
             .audience
             .as_ref()
             .and_then(ir_audience_to_yaml),
+        features: extract_feature_tags(&svc.diag_comm),
+        lifecycle: lifecycle(svc.diag_comm.sdgs.as_ref()),
+        requirements: extract_requirements(&svc.diag_comm),
+        aliases: extract_aliases(&svc.diag_comm, ""),
         annotations: None,
     }
 }
@@ -1025,8 +1347,11 @@ fn service_to_routine(svc: &DiagService) -> Routine {
 /// appear in the generic `sdgs:` output to avoid duplication on roundtrip.
 const DEDICATED_SDG_CAPTIONS: &[&str] = &[
     "identification",
+    COMPATIBILITY_CAPTION,
+    VALIDITY_CAPTION,
     "comparams",
     "dtc_config",
+    FLASH_SEQUENCE_CAPTION,
     "yaml_annotations",
     "yaml_x_oem",
 ];
@@ -1094,13 +1419,13 @@ fn ir_job_to_yaml(job: &SingleEcuJob) -> EcuJob {
             params
                 .iter()
                 .map(|p| JobParamDef {
-                    name: p.short_name.clone(),
+                    name: p.short_name.to_string(),
                     description: p.long_name.as_ref().map(|ln| ln.value.clone()),
                     param_type: serde_yaml::Value::Null,
                     semantic: if p.semantic.is_empty() {
                         None
                     } else {
-                        Some(p.semantic.clone())
+                        Some(p.semantic.to_string())
                     },
                     default_value: if p.physical_default_value.is_empty() {
                         None
@@ -1113,8 +1438,8 @@ fn ir_job_to_yaml(job: &SingleEcuJob) -> EcuJob {
     };
 
     EcuJob {
-        name: job.diag_comm.short_name.clone(),
-        description: job.diag_comm.long_name.as_ref().map(|ln| ln.value.clone()),
+        name: job.diag_comm.short_name.to_string(),
+        description: comm_description(&job.diag_comm),
         prog_code: job.prog_codes.first().map(|pc| pc.code_file.clone()),
         input_params: convert_params(&job.input_params),
         output_params: convert_params(&job.output_params),
@@ -1131,15 +1456,22 @@ fn ir_job_to_yaml(job: &SingleEcuJob) -> EcuJob {
 
 /// Extract sessions from a "Session" state chart.
 /// State short_name is the CDA name (CamelCase), long_name.ti is the YAML key (lowercase).
-fn extract_sessions_from_state_charts(
-    state_charts: &[StateChart],
-) -> Option<BTreeMap<String, Session>> {
-    let sc = state_charts.iter().find(|sc| sc.short_name == "Session")?;
+/// Server timing is recovered from the matching DiagnosticSessionControl response.
+fn extract_sessions_from_state_charts(layer: &DiagLayer) -> Option<BTreeMap<String, Session>> {
+    let sc = layer
+        .state_charts
+        .iter()
+        .find(|sc| sc.short_name == "Session")?;
     if sc.states.is_empty() {
         return None;
     }
     let mut sessions = BTreeMap::new();
     for state in &sc.states {
+        let timing = state
+            .long_name
+            .as_ref()
+            .and_then(|ln| ln.value.parse().ok())
+            .and_then(|id| extract_session_timing(&layer.diag_services, id));
         let (id_val, yaml_key, alias) = if let Some(ln) = &state.long_name {
             let id: u64 = ln.value.parse().unwrap_or(0);
             // long_name.ti stores the YAML key; short_name is the CDA alias
@@ -1150,7 +1482,7 @@ fn extract_sessions_from_state_charts(
             };
             // Only output alias if it differs from trivial capitalization of the key
             let alias = if state.short_name != crate::parser::capitalize_first(&yaml_key) {
-                Some(state.short_name.clone())
+                Some(state.short_name.to_string())
             } else {
                 None
             };
@@ -1172,13 +1504,55 @@ fn extract_sessions_from_state_charts(
                 id: id_val,
                 alias,
                 requires_unlock: None,
-                timing: None,
+                timing,
             },
         );
     }
     Some(sessions)
 }
 
+/// P2/P2* server timing from the positive response of the
+/// DiagnosticSessionControl service for session `id`, if it carries them.
+fn extract_session_timing(services: &[DiagService], id: u64) -> Option<SessionTiming> {
+    let coded = |params: &[Param], byte: u32| {
+        params.iter().find_map(|p| match &p.specific_data {
+            Some(ParamData::CodedConst { coded_value, .. }) if p.byte_position == Some(byte) => {
+                coded_value.parse::<u64>().ok()
+            }
+            _ => None,
+        })
+    };
+    let svc = services.iter().find(|s| {
+        s.request
+            .as_ref()
+            .is_some_and(|r| coded(&r.params, 0) == Some(0x10) && coded(&r.params, 1) == Some(id))
+    })?;
+    let params = &svc.pos_responses.first()?.params;
+    let default_of = |name: &str| {
+        let param = params.iter().find(|p| p.short_name == name)?;
+        let value = match &param.specific_data {
+            Some(ParamData::Value {
+                physical_default_value,
+                ..
+            }) if !physical_default_value.is_empty() => physical_default_value,
+            _ => &param.physical_default_value,
+        };
+        value.parse::<u32>().ok()
+    };
+    let p2_server_max = default_of(crate::service_generator::P2_SERVER_MAX_PARAM)
+        .and_then(|v| u16::try_from(v).ok());
+    let p2_star_server_max = default_of(crate::service_generator::P2_STAR_SERVER_MAX_PARAM);
+    if p2_server_max.is_none() && p2_star_server_max.is_none() {
+        return None;
+    }
+    Some(SessionTiming {
+        p2_ms: None,
+        p2_star_ms: None,
+        p2_server_max,
+        p2_star_server_max,
+    })
+}
+
 /// Extract state_model from a "Session" state chart (transitions + start state).
 /// CDA names are mapped back to YAML keys via long_name.ti.
 fn extract_state_model_from_state_charts(state_charts: &[StateChart]) -> Option<StateModel> {
@@ -1273,7 +1647,7 @@ fn extract_security_from_state_charts(
             .long_name
             .as_ref()
             .filter(|ln| !ln.ti.is_empty())
-            .map_or_else(|| state.short_name.clone(), |ln| ln.ti.clone());
+            .map_or_else(|| state.short_name.to_string(), |ln| ln.ti.as_str().into());
         levels.insert(
             yaml_key,
             SecurityLevel {
@@ -1386,7 +1760,7 @@ fn extract_authentication_from_state_charts(state_charts: &[StateChart]) -> Opti
             serde_yaml::Value::Number(serde_yaml::Number::from(id)),
         );
         roles.insert(
-            state.short_name.clone(),
+            state.short_name.to_string(),
             serde_yaml::Value::Mapping(role_map),
         );
     }
@@ -1430,11 +1804,11 @@ fn extract_variants(db: &DiagDatabase) -> Option<Variants> {
                 let mut rpm = serde_yaml::Mapping::new();
                 rpm.insert(
                     serde_yaml::Value::String("service".into()),
-                    serde_yaml::Value::String(mp.diag_service.diag_comm.short_name.clone()),
+                    serde_yaml::Value::String(mp.diag_service.diag_comm.short_name.to_string()),
                 );
                 rpm.insert(
                     serde_yaml::Value::String("param_path".into()),
-                    serde_yaml::Value::String(mp.out_param.short_name.clone()),
+                    serde_yaml::Value::String(mp.out_param.short_name.to_string()),
                 );
                 rpm.insert(
                     serde_yaml::Value::String("expected_value".into()),
@@ -1467,11 +1841,13 @@ fn extract_variants(db: &DiagDatabase) -> Option<Variants> {
         definitions.insert(
             name,
             VariantDef {
-                description: variant
-                    .diag_layer
-                    .long_name
-                    .as_ref()
-                    .map(|ln| ln.value.clone()),
+                description: variant.diag_layer.description.clone().or_else(|| {
+                    variant
+                        .diag_layer
+                        .long_name
+                        .as_ref()
+                        .map(|ln| ln.value.clone())
+                }),
                 detect,
                 inheritance: None,
                 overrides: variant_overrides,
@@ -1482,7 +1858,7 @@ fn extract_variants(db: &DiagDatabase) -> Option<Variants> {
 
     Some(Variants {
         detection_order,
-        fallback: non_base.last().map(|v| v.diag_layer.short_name.clone()),
+        fallback: non_base.last().map(|v| v.diag_layer.short_name.to_string()),
         definitions: if definitions.is_empty() {
             None
         } else {
@@ -1525,7 +1901,7 @@ fn ir_dop_to_comparam_dop(dop: &Dop) -> ComParamDopDef {
         _ => (None, None),
     };
     ComParamDopDef {
-        name: Some(dop.short_name.clone()),
+        name: Some(dop.short_name.to_string()),
         base_type,
         bit_length,
         min: None,
@@ -1554,11 +1930,11 @@ fn com_param_refs_to_yaml_comparams(refs: &[ComParamRef]) -> Option<YamlComParam
         let proto_name = cpr
             .protocol
             .as_ref()
-            .map(|p| p.diag_layer.short_name.clone());
+            .map(|p| p.diag_layer.short_name.to_string());
 
         if let Some(proto) = proto_name {
             // Per-protocol value
-            let entry = map.entry(cp.short_name.clone()).or_insert_with(|| {
+            let entry = map.entry(cp.short_name.to_string()).or_insert_with(|| {
                 ComParamEntry::Full(ComParamFull {
                     cptype: None,
                     unit: None,
@@ -1580,7 +1956,7 @@ fn com_param_refs_to_yaml_comparams(refs: &[ComParamRef]) -> Option<YamlComParam
                     .insert(proto, smart_yaml_value(&value_str));
             }
         } else {
-            map.entry(cp.short_name.clone())
+            map.entry(cp.short_name.to_string())
                 .or_insert_with(|| ComParamEntry::Simple(smart_yaml_value(&value_str)));
         }
     }
@@ -1617,7 +1993,7 @@ fn ir_comparam_subset_to_yaml(subset: &ComParamSubSet) -> YamlComParamSubSetDef
                         _ => (None, None),
                     };
                     (
-                        cp.short_name.clone(),
+                        cp.short_name.to_string(),
                         YamlSubSetComParam {
                             param_class: Some(cp.param_class.clone()).filter(|s| !s.is_empty()),
                             cp_type: Some(format_cp_type(&cp.cp_type)),
@@ -1659,7 +2035,7 @@ fn ir_comparam_subset_to_yaml(subset: &ComParamSubSet) -> YamlComParamSubSetDef
                                         _ => (None, None),
                                     };
                                     YamlSubSetComParamChild {
-                                        name: child.short_name.clone(),
+                                        name: child.short_name.to_string(),
                                         param_class: Some(child.param_class.clone())
                                             .filter(|s| !s.is_empty()),
                                         default: default_val,
@@ -1679,7 +2055,7 @@ fn ir_comparam_subset_to_yaml(subset: &ComParamSubSet) -> YamlComParamSubSetDef
                         _ => (None, false),
                     };
                     (
-                        cp.short_name.clone(),
+                        cp.short_name.to_string(),
                         YamlSubSetComplexComParam {
                             param_class: Some(cp.param_class.clone()).filter(|s| !s.is_empty()),
                             cp_type: Some(format_cp_type(&cp.cp_type)),
@@ -1735,11 +2111,11 @@ fn ir_diag_layer_to_yaml_block(layer: &DiagLayer) -> YamlDiagLayerBlock {
                 .unwrap_or(&svc.diag_comm.short_name);
             let (did_type_val, _) = extract_did_type(svc, did_name);
             let access_name = extract_access_pattern_name(&svc.diag_comm);
-            let (snap, ioc) = extract_did_extra(svc);
+            let (snap, ioc, _) = extract_did_extra(svc);
             let did = Did {
                 name: did_name.to_string(),
                 param_name: None,
-                description: svc.diag_comm.long_name.as_ref().map(|ln| ln.value.clone()),
+                description: comm_description(&svc.diag_comm),
                 did_type: did_type_val,
                 access: if access_name.is_empty() {
                     "public".into()
@@ -1756,6 +2132,10 @@ fn ir_diag_layer_to_yaml_block(layer: &DiagLayer) -> YamlDiagLayerBlock {
                     .audience
                     .as_ref()
                     .and_then(ir_audience_to_yaml),
+                features: extract_feature_tags(&svc.diag_comm),
+                lifecycle: lifecycle(svc.diag_comm.sdgs.as_ref()),
+                requirements: extract_requirements(&svc.diag_comm),
+                aliases: extract_aliases(&svc.diag_comm, "_Read"),
             };
             let key = serde_yaml::Value::Number(serde_yaml::Number::from(did_id as u64));
             dids_map.insert(key, serde_yaml::to_value(&did).unwrap_or_default());
@@ -1786,7 +2166,7 @@ fn ir_diag_layer_to_yaml_block(layer: &DiagLayer) -> YamlDiagLayerBlock {
 
     YamlDiagLayerBlock {
         long_name: layer.long_name.as_ref().map(|ln| ln.value.clone()),
-        services,
+        services: services.map(Box::new),
         comparams,
         types: None,
         dids: if dids_map.is_empty() {
@@ -1813,15 +2193,15 @@ fn ir_parent_refs_to_yaml(refs: &[ParentRef]) -> Option<Vec<YamlParentRef>> {
         refs.iter()
             .map(|pr| {
                 let (target, ref_type) = match &pr.ref_type {
-                    ParentRefType::Variant(v) => (v.diag_layer.short_name.clone(), "variant"),
-                    ParentRefType::Protocol(p) => (p.diag_layer.short_name.clone(), "protocol"),
+                    ParentRefType::Variant(v) => (v.diag_layer.short_name.to_string(), "variant"),
+                    ParentRefType::Protocol(p) => (p.diag_layer.short_name.to_string(), "protocol"),
                     ParentRefType::FunctionalGroup(fg) => {
-                        (fg.diag_layer.short_name.clone(), "functional_group")
+                        (fg.diag_layer.short_name.to_string(), "functional_group")
                     }
                     ParentRefType::EcuSharedData(esd) => {
-                        (esd.diag_layer.short_name.clone(), "ecu_shared_data")
+                        (esd.diag_layer.short_name.to_string(), "ecu_shared_data")
                     }
-                    ParentRefType::TableDop(td) => (td.short_name.clone(), "table_dop"),
+                    ParentRefType::TableDop(td) => (td.short_name.to_string(), "table_dop"),
                 };
                 let not_inherited = {
                     let ni = YamlNotInherited {
@@ -1905,7 +2285,7 @@ fn ir_protocols_to_yaml(protocols: &[Protocol]) -> Option<BTreeMap<String, YamlP
                             .prot_stacks
                             .iter()
                             .map(|ps| YamlNamedProtStackDef {
-                                short_name: ps.short_name.clone(),
+                                short_name: ps.short_name.to_string(),
                                 long_name: ps.long_name.as_ref().map(|ln| ln.value.clone()),
                                 pdu_protocol_type: ps.pdu_protocol_type.clone(),
                                 physical_link_type: ps.physical_link_type.clone(),
@@ -1923,7 +2303,7 @@ fn ir_protocols_to_yaml(protocols: &[Protocol]) -> Option<BTreeMap<String, YamlP
                             .collect(),
                     });
                 (
-                    proto.diag_layer.short_name.clone(),
+                    proto.diag_layer.short_name.to_string(),
                     YamlProtocolLayer {
                         layer: ir_diag_layer_to_yaml_block(&proto.diag_layer),
                         prot_stack,
@@ -1946,7 +2326,7 @@ fn ir_ecu_shared_datas_to_yaml(
         esds.iter()
             .map(|esd| {
                 (
-                    esd.diag_layer.short_name.clone(),
+                    esd.diag_layer.short_name.to_string(),
                     YamlEcuSharedDataLayer {
                         layer: ir_diag_layer_to_yaml_block(&esd.diag_layer),
                     },
@@ -1957,15 +2337,10 @@ fn ir_ecu_shared_datas_to_yaml(
 }
 
 fn parse_expected_value(s: &str) -> serde_yaml::Value {
-    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
-        if let Ok(n) = u64::from_str_radix(hex, 16) {
-            return serde_yaml::Value::Number(serde_yaml::Number::from(n));
-        }
+    match diag_ir::parse_uint(s) {
+        Some(n) => serde_yaml::Value::Number(serde_yaml::Number::from(n)),
+        None => serde_yaml::Value::String(s.to_string()),
     }
-    if let Ok(n) = s.parse::<u64>() {
-        return serde_yaml::Value::Number(serde_yaml::Number::from(n));
-    }
-    serde_yaml::Value::String(s.to_string())
 }
 
 fn ir_memory_to_yaml(mc: &MemoryConfig) -> YamlMemoryConfig {
@@ -2038,7 +2413,18 @@ fn ir_memory_to_yaml(mc: &MemoryConfig) -> YamlMemoryConfig {
                     max_block_length: b.max_block_length,
                     security_level: b.security_level.clone(),
                     session: b.session.clone(),
-                    checksum_type: b.checksum_type.clone(),
+                    checksum_type: b.checksum_type.as_ref().map(|c| match c {
+                        ChecksumType::Oem {
+                            name,
+                            length,
+                            parameters,
+                        } => YamlChecksumType::Oem {
+                            oem: name.clone(),
+                            length: *length,
+                            parameters: parameters.clone(),
+                        },
+                        _ => YamlChecksumType::Named(c.name().to_string()),
+                    }),
                 },
             )
         })
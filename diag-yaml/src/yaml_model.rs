@@ -34,9 +34,11 @@ pub struct YamlDocument {
     #[serde(default)]
     pub variants: Option<Variants>,
     #[serde(default)]
-    pub services: Option<YamlServices>,
+    pub services: Option<Box<YamlServices>>,
     #[serde(default)]
     pub access_patterns: Option<BTreeMap<String, AccessPattern>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub defaults: Option<YamlDefaults>,
     #[serde(default)]
     pub types: Option<BTreeMap<String, YamlType>>,
     #[serde(default)]
@@ -55,6 +57,13 @@ pub struct YamlDocument {
     pub ecu_jobs: Option<BTreeMap<String, EcuJob>>,
     #[serde(default)]
     pub memory: Option<YamlMemoryConfig>,
+    /// Steps are single-key maps (`- session: programming`), not YAML tags.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "serde_yaml::with::singleton_map_recursive"
+    )]
+    pub flash_sequence: Option<Vec<diag_ir::FlashStep>>,
     #[serde(default)]
     pub functional_classes: Option<Vec<String>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -83,6 +92,9 @@ pub struct Meta {
     pub tags: Vec<String>,
     #[serde(default)]
     pub revisions: Vec<Revision>,
+    /// Period and model years the database may be used for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validity: Option<diag_ir::Validity>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -156,9 +168,15 @@ pub struct YamlServiceAudience {
     pub development: Option<bool>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub manufacturing: Option<bool>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Also accepted as `aftersales`, the spelling of the schema.
+    #[serde(default, alias = "aftersales", skip_serializing_if = "Option::is_none")]
     pub after_sales: Option<bool>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Also accepted as `aftermarket`, the spelling of the schema.
+    #[serde(
+        default,
+        alias = "aftermarket",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub after_market: Option<bool>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub groups: Vec<String>,
@@ -287,7 +305,7 @@ pub struct YamlDiagLayerBlock {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub long_name: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub services: Option<YamlServices>,
+    pub services: Option<Box<YamlServices>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub comparams: Option<YamlComParams>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -447,6 +465,12 @@ pub struct SessionTiming {
     pub p2_ms: Option<u32>,
     #[serde(default)]
     pub p2_star_ms: Option<u32>,
+    /// P2Server_max in ms, reported in the DiagnosticSessionControl response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p2_server_max: Option<u16>,
+    /// P2*Server_max in ms (sent with 10 ms resolution).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p2_star_server_max: Option<u32>,
 }
 
 // --- State Model ---
@@ -511,10 +535,24 @@ pub struct Authentication {
 
 // --- Identification ---
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Identification {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part_number: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supplier_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hardware_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub software_version: Option<String>,
+    /// Identification DIDs by what they report, e.g. `vin: 0xF190`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dids: Option<BTreeMap<String, serde_yaml::Value>>,
     #[serde(default)]
     pub expected_idents: Option<BTreeMap<String, serde_yaml::Value>>,
+    /// ECU hardware/software part numbers the database applies to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compatibility: Option<diag_ir::Compatibility>,
 }
 
 // --- Variants ---
@@ -558,6 +596,10 @@ impl VariantDef {
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct YamlServices {
+    /// Service profile (`uds`) whose entries fill in the services this
+    /// section does not configure itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
     #[serde(default, rename = "diagnosticSessionControl")]
     pub diagnostic_session_control: Option<ServiceEntry>,
     #[serde(default, rename = "ecuReset")]
@@ -626,12 +668,23 @@ pub struct ServiceEntry {
     pub state_effects: Option<serde_yaml::Value>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub audience: Option<YamlServiceAudience>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub features: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<diag_ir::Lifecycle>,
+    /// Requirement IDs (e.g. `SWR-1234`) this item implements.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requirements: Option<Vec<String>>,
     #[serde(default)]
     pub response_outputs: Option<serde_yaml::Value>,
     #[serde(default)]
     pub request_layout: Option<serde_yaml::Value>,
     #[serde(default)]
     pub control_types: Option<Vec<String>>,
+    /// Negative response codes the services may answer with: codes (`0x22`) or
+    /// ISO 14229-1 names (`conditionsNotCorrect`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nrcs: Option<Vec<serde_yaml::Value>>,
     // Memory services
     #[serde(default)]
     pub alfid: Option<serde_yaml::Value>,
@@ -664,6 +717,14 @@ pub struct ServiceEntry {
     pub nrc_on_fail: Option<serde_yaml::Value>,
     #[serde(default)]
     pub max_active_events: Option<u32>,
+    /// Access pattern of the generated services.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access: Option<String>,
+    /// Access patterns of single subfunctions, keyed by subfunction name,
+    /// `0x` byte or (for `securityAccess`) security level name. Overrides
+    /// `access` for those subfunctions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subfunction_access: Option<serde_yaml::Mapping>,
     // CommunicationControl extras
     #[serde(default)]
     pub temporal_sync: Option<bool>,
@@ -703,6 +764,29 @@ pub struct AccessPattern {
     pub nrc_on_fail: Option<serde_yaml::Value>,
 }
 
+// --- Defaults ---
+
+/// Document-wide defaults of the type and service conversion. A type's own
+/// `endian`, `display_radix` and `signed_encoding` override them, and a
+/// `services:` entry's own `nrcs` the default NRC set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct YamlDefaults {
+    /// Byte order of types without `endian`: `big` (built in) or `little`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endian: Option<String>,
+    /// Radix physical values are displayed in: `dec` (built in), `hex`,
+    /// `bin` or `oct`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_radix: Option<String>,
+    /// Base type encoding of the signed bases `s8` to `s64`, e.g. `2C`
+    /// (built in: `signed`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signed_encoding: Option<String>,
+    /// Negative response codes of services whose `services:` entry lists none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nrcs: Option<Vec<serde_yaml::Value>>,
+}
+
 // --- Types ---
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -714,14 +798,30 @@ pub struct YamlType {
     pub dop_name: Option<String>,
     #[serde(default)]
     pub endian: Option<String>,
+    /// Overrides `defaults.display_radix`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_radix: Option<String>,
+    /// Overrides `defaults.signed_encoding`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signed_encoding: Option<String>,
     #[serde(default)]
     pub bit_length: Option<u32>,
+    /// Mask selecting the bits of the coded value that carry data (ODX BIT-MASK).
+    #[serde(default)]
+    pub bit_mask: Option<serde_yaml::Value>,
+    /// Pack the masked bits into a contiguous value (ODX IS-CONDENSED).
+    #[serde(default)]
+    pub condensed: Option<bool>,
     #[serde(default)]
     pub length: Option<u32>,
     #[serde(default)]
     pub min_length: Option<u32>,
     #[serde(default)]
     pub max_length: Option<u32>,
+    /// Width of the length prefix (8 or 16 bits) of a leading-length string
+    /// or bytefield (ODX LEADING-LENGTH-INFO-TYPE).
+    #[serde(default)]
+    pub length_prefix_bits: Option<u32>,
     #[serde(default)]
     pub encoding: Option<String>,
     #[serde(default)]
@@ -789,6 +889,16 @@ pub struct Did {
     pub annotations: Option<serde_yaml::Value>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub audience: Option<YamlServiceAudience>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub features: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<diag_ir::Lifecycle>,
+    /// Requirement IDs (e.g. `SWR-1234`) this item implements.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requirements: Option<Vec<String>>,
+    /// Former names, accepted by service lookups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<Vec<String>>,
 }
 
 // --- Routines ---
@@ -807,6 +917,16 @@ pub struct Routine {
     pub parameters: Option<BTreeMap<String, RoutinePhase>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub audience: Option<YamlServiceAudience>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub features: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<diag_ir::Lifecycle>,
+    /// Requirement IDs (e.g. `SWR-1234`) this item implements.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requirements: Option<Vec<String>>,
+    /// Former names, accepted by service lookups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<Vec<String>>,
     #[serde(default)]
     pub annotations: Option<serde_yaml::Value>,
 }
@@ -829,6 +949,16 @@ pub struct RoutineParam {
     pub param_type: serde_yaml::Value,
     #[serde(default)]
     pub semantic: Option<String>,
+    /// Value filled in by the ECU/tester runtime (ODX SYSTEM param, e.g. `TIMESTAMP`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sys_param: Option<String>,
+    /// Name of the param in the same list holding this param's byte length
+    /// (ODX PARAM-LENGTH-INFO-TYPE); that param becomes a LENGTH-KEY.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length_key: Option<String>,
+    /// Filler of this many bits instead of a value (ODX RESERVED param).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reserved: Option<u32>,
 }
 
 // --- DTC Config ---
@@ -859,6 +989,14 @@ pub struct YamlDtc {
     pub snapshots: Option<Vec<String>>,
     #[serde(default)]
     pub extended_data: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<diag_ir::Lifecycle>,
+    /// Text ID of `description`, the key translations are exchanged by.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_id: Option<String>,
+    /// `description` in other languages, by language code.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub translations: BTreeMap<String, String>,
     #[serde(default, rename = "x-oem")]
     pub x_oem: Option<serde_yaml::Value>,
 }
@@ -959,7 +1097,22 @@ pub struct YamlDataBlock {
     #[serde(default)]
     pub session: Option<String>,
     #[serde(default)]
-    pub checksum_type: Option<String>,
+    pub checksum_type: Option<YamlChecksumType>,
+}
+
+/// `checksum_type:` of a data block: a standard algorithm name or an
+/// OEM-specific algorithm.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum YamlChecksumType {
+    Named(String),
+    Oem {
+        oem: String,
+        /// Checksum size in bytes.
+        length: u32,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        parameters: BTreeMap<String, String>,
+    },
 }
 
 fn default_download() -> String {
@@ -1,3 +1,4 @@
+use diag_ir::{DopRef, ParamData};
 use diag_yaml::parse_yaml;
 
 #[test]
@@ -64,6 +65,32 @@ fn test_parse_example_ecm() {
     assert!(flash_job.is_some(), "should have FlashECU job");
 }
 
+#[test]
+fn test_read_and_write_services_share_the_did_dop() {
+    let content = include_str!("../../test-fixtures/yaml/example-ecm.yml");
+    let db = parse_yaml(content).unwrap();
+    let services = &db.variants[0].diag_layer.diag_services;
+    let data_dop = |name: &str| {
+        let svc = services
+            .iter()
+            .find(|s| s.diag_comm.short_name == name)
+            .unwrap_or_else(|| panic!("no {name}"));
+        svc.request
+            .iter()
+            .flat_map(|r| &r.params)
+            .chain(svc.pos_responses.iter().flat_map(|r| &r.params))
+            .find_map(|p| match &p.specific_data {
+                Some(ParamData::Value { dop, .. }) => Some(dop.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("{name} has no data param"))
+    };
+    assert!(DopRef::ptr_eq(
+        &data_dop("IdleSpeedTarget_Read"),
+        &data_dop("IdleSpeedTarget_Write")
+    ));
+}
+
 #[test]
 fn test_parse_preserves_metadata() {
     let content = include_str!("../../test-fixtures/yaml/example-ecm.yml");
@@ -268,6 +295,141 @@ comparams:
     assert_eq!(unique[0].complex_value.as_ref().unwrap().entries.len(), 3);
 }
 
+#[test]
+fn test_parse_doip_addressing() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+  addressing:
+    doip:
+      ip: "10.0.0.5"
+      logical_address: 0x1000
+      eid: "00:1A:2B:3C:4D:5E"
+comparams:
+  CP_DoIPLogicalEcuAddress: 0x2000
+  CP_DoIPLogicalTesterAddress: 0x0E80
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let doip = db.transport.unwrap().doip.unwrap();
+    assert_eq!(doip.ip.as_deref(), Some("10.0.0.5"));
+    assert_eq!(doip.port, None);
+    // Explicit addressing wins over the comparams, which fill the rest.
+    assert_eq!(doip.logical_address, Some(0x1000));
+    assert_eq!(doip.tester_address, Some(0x0E80));
+    assert_eq!(doip.eid, Some([0x00, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E]));
+
+    let db = parse_yaml(include_str!("../../test-fixtures/yaml/FLXC1000.yml")).unwrap();
+    let doip = db.transport.unwrap().doip.unwrap();
+    assert_eq!(doip.logical_address, Some(0x1000));
+    assert_eq!(doip.functional_address, Some(0xFFFF));
+}
+
+#[test]
+fn test_parse_uds_service_profile_with_access() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+sessions:
+  default:
+    id: 0x01
+  extended:
+    id: 0x03
+security:
+  level_01:
+    level: 1
+    seed_request: 0x01
+    key_send: 0x02
+    seed_size: 4
+    key_size: 4
+access_patterns:
+  extended_only:
+    sessions: [extended]
+    security: none
+  unlocked:
+    sessions: [extended]
+    security: [level_01]
+services:
+  profile: uds
+  ecuReset:
+    enabled: true
+    access: extended_only
+    subfunction_access:
+      hardReset: unlocked
+  securityAccess:
+    enabled: true
+    subfunction_access:
+      level_01: extended_only
+  requestUpload:
+    enabled: false
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let services = &db.variants[0].diag_layer.diag_services;
+    let find = |name: &str| {
+        services
+            .iter()
+            .find(|s| s.diag_comm.short_name == name)
+            .unwrap_or_else(|| panic!("{name} not generated"))
+    };
+    // Profile services next to the document's own entries
+    find("default_Start");
+    find("RequestSeed_Level_1");
+    find("FaultMem_ReportDTCWithPermanentStatus");
+    find("IOControl_FreezeCurrentState");
+    find("Authentication_AuthenticationConfiguration");
+    assert!(
+        services
+            .iter()
+            .all(|s| s.diag_comm.short_name != "RequestUpload")
+    );
+
+    let refs = |name: &str| -> Vec<(String, String)> {
+        find(name)
+            .diag_comm
+            .pre_condition_state_refs
+            .iter()
+            .map(|r| (r.value.clone(), r.in_param_path_short_name.clone()))
+            .collect()
+    };
+    let extended = ("Session".to_string(), "Extended".to_string());
+    let level_1 = ("SecurityAccess".to_string(), "Level_1".to_string());
+    assert_eq!(refs("SoftReset"), std::slice::from_ref(&extended));
+    assert_eq!(refs("HardReset"), [extended.clone(), level_1]);
+    assert_eq!(refs("SendKey_Level_1"), [extended]);
+    assert!(refs("TesterPresent").is_empty());
+}
+
+#[test]
+fn test_parse_job_default_from_comparam() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+comparams:
+  CP_TesterAddress: 0x0E80
+ecu_jobs:
+  flash:
+    name: "Flash"
+    input_params:
+      - name: "tester"
+        type:
+          base: u16
+        default_value:
+          comparam: CP_TesterAddress
+"#;
+    let mut db = parse_yaml(yaml).unwrap();
+    let job = &db.variants[0].diag_layer.single_ecu_jobs[0];
+    assert_eq!(
+        job.input_params[0].physical_default_value,
+        "${comparam:CP_TesterAddress}"
+    );
+
+    assert!(diag_ir::resolve_comparam_defaults(&mut db).is_empty());
+    let job = &db.variants[0].diag_layer.single_ecu_jobs[0];
+    assert_eq!(job.input_params[0].physical_default_value, "3712");
+}
+
 #[test]
 fn test_parse_did_audience() {
     let yaml = r#"
@@ -713,3 +875,350 @@ access_patterns:
         }
     }
 }
+
+#[test]
+fn test_routine_system_length_key_and_reserved_params() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+routines:
+  0xFF01:
+    name: WriteBlock
+    access: public
+    operations: [start]
+    parameters:
+      start:
+        input:
+          - name: Timestamp
+            type: { base: u32 }
+            sys_param: TIMESTAMP
+          - name: Filler
+            reserved: 8
+          - name: BlockLength
+            type: { base: u16 }
+          - name: Block
+            type: { base: bytes }
+            length_key: BlockLength
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let svc = db.variants[0]
+        .diag_layer
+        .diag_services
+        .iter()
+        .find(|s| s.diag_comm.short_name == "WriteBlock")
+        .expect("routine service not found");
+    let params = &svc.request.as_ref().unwrap().params;
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|p| p.short_name == name)
+            .unwrap_or_else(|| panic!("param {name} not found"))
+    };
+
+    let timestamp = param("Timestamp");
+    assert_eq!(timestamp.param_type, diag_ir::ParamType::System);
+    assert!(matches!(
+        &timestamp.specific_data,
+        Some(diag_ir::ParamData::System { sys_param, .. }) if sys_param == "TIMESTAMP"
+    ));
+
+    let filler = param("Filler");
+    assert_eq!(filler.param_type, diag_ir::ParamType::Reserved);
+    assert_eq!(
+        filler.specific_data,
+        Some(diag_ir::ParamData::Reserved { bit_length: 8 })
+    );
+
+    let key = param("BlockLength");
+    assert_eq!(key.param_type, diag_ir::ParamType::LengthKey);
+    assert!(matches!(
+        &key.specific_data,
+        Some(diag_ir::ParamData::LengthKeyRef { .. })
+    ));
+
+    let Some(diag_ir::ParamData::Value { dop, .. }) = &param("Block").specific_data else {
+        panic!("Block should be a value param");
+    };
+    let Some(diag_ir::DopData::NormalDop {
+        diag_coded_type: Some(dct),
+        ..
+    }) = &dop.specific_data
+    else {
+        panic!("Block should have a coded type");
+    };
+    assert_eq!(
+        dct.type_name,
+        diag_ir::DiagCodedTypeName::ParamLengthInfoType
+    );
+    match &dct.specific_data {
+        Some(diag_ir::DiagCodedTypeData::ParamLength { length_key }) => {
+            assert_eq!(length_key.short_name, "BlockLength");
+        }
+        other => panic!("expected ParamLength, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_parse_service_nrcs() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+services:
+  readDataByIdentifier:
+    enabled: true
+    nrcs: [0x13, conditionsNotCorrect, 0x31]
+  testerPresent:
+    enabled: true
+    nrcs: [0x12, incorrectMessageLengthOrInvalidFormat]
+dids:
+  0xF190:
+    name: VIN
+    type:
+      base: ascii
+      length: 17
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let svc = db.variants[0]
+        .diag_layer
+        .diag_services
+        .iter()
+        .find(|s| s.diag_comm.short_name == "VIN_Read")
+        .unwrap();
+    assert_eq!(svc.neg_responses.len(), 1);
+    let params = &svc.neg_responses[0].params;
+    let names: Vec<_> = params.iter().map(|p| p.short_name.as_str()).collect();
+    assert_eq!(names, ["SID_NR", "SIDRQ_NR", "NRC"]);
+    assert_eq!(
+        params[1].param_type,
+        diag_ir::ParamType::MatchingRequestParam
+    );
+    assert_eq!(params[2].param_type, diag_ir::ParamType::NrcConst);
+    match &params[2].specific_data {
+        Some(diag_ir::ParamData::NrcConst { coded_values, .. }) => {
+            assert_eq!(coded_values, &["19", "34", "49"]);
+        }
+        other => panic!("expected NrcConst, got {other:?}"),
+    }
+
+    let out = diag_yaml::write_yaml(&db).unwrap();
+    let doc: diag_yaml::yaml_model::YamlDocument = serde_yaml::from_str(&out).unwrap();
+    let tester_present = doc.services.unwrap().tester_present.unwrap();
+    let nrcs: Vec<_> = tester_present
+        .nrcs
+        .unwrap()
+        .iter()
+        .filter_map(diag_yaml::service_generator::resolve_nrc)
+        .collect();
+    assert_eq!(nrcs, [0x12, 0x13]);
+}
+
+#[test]
+fn test_parse_defaults() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+defaults:
+  endian: little
+  display_radix: hex
+  signed_encoding: 2C
+  nrcs: [serviceNotSupported]
+services:
+  readDataByIdentifier:
+    enabled: true
+  testerPresent:
+    enabled: true
+    nrcs: [0x12]
+types:
+  offset_type:
+    base: s16
+  word_type:
+    base: u16
+    endian: big
+    display_radix: dec
+dids:
+  0x0101:
+    name: Offset
+    type: offset_type
+  0x0102:
+    name: Word
+    type: word_type
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let services = &db.variants[0].diag_layer.diag_services;
+    let coding = |name: &str| {
+        let svc = services
+            .iter()
+            .find(|s| s.diag_comm.short_name == name)
+            .unwrap();
+        let dop = svc.pos_responses[0]
+            .params
+            .iter()
+            .find_map(|p| match &p.specific_data {
+                Some(diag_ir::ParamData::Value { dop, .. }) => Some(dop),
+                _ => None,
+            })
+            .unwrap();
+        match &dop.specific_data {
+            Some(diag_ir::DopData::NormalDop {
+                diag_coded_type: Some(dct),
+                physical_type: Some(pt),
+                ..
+            }) => (
+                dct.is_high_low_byte_order,
+                dct.base_type_encoding.clone(),
+                pt.display_radix,
+            ),
+            other => panic!("expected a NormalDop, got {other:?}"),
+        }
+    };
+    assert_eq!(
+        coding("Offset_Read"),
+        (false, "2C".to_string(), diag_ir::Radix::Hex)
+    );
+    assert_eq!(
+        coding("Word_Read"),
+        (true, "unsigned".to_string(), diag_ir::Radix::Dec)
+    );
+
+    let nrc_codes = |name: &str| {
+        let svc = services
+            .iter()
+            .find(|s| s.diag_comm.short_name == name)
+            .unwrap();
+        svc.neg_responses
+            .iter()
+            .flat_map(|r| &r.params)
+            .find_map(|p| match &p.specific_data {
+                Some(diag_ir::ParamData::NrcConst { coded_values, .. }) => {
+                    Some(coded_values.clone())
+                }
+                _ => None,
+            })
+    };
+    assert_eq!(nrc_codes("Offset_Read"), Some(vec!["17".to_string()]));
+    assert_eq!(nrc_codes("TesterPresent"), Some(vec!["18".to_string()]));
+
+    // Written types carry the resolved values, so the output does not
+    // depend on the defaults section.
+    let out = diag_yaml::write_yaml(&db).unwrap();
+    let doc: diag_yaml::yaml_model::YamlDocument = serde_yaml::from_str(&out).unwrap();
+    let types = doc.types.unwrap();
+    assert_eq!(types["offset_type"].endian.as_deref(), Some("little"));
+    assert_eq!(types["offset_type"].display_radix.as_deref(), Some("hex"));
+    assert_eq!(types["offset_type"].signed_encoding.as_deref(), Some("2C"));
+}
+
+#[test]
+fn test_parse_merge_keys_and_recursive_aliases() {
+    let yaml = r#"
+schema: "1.0"
+x-defaults: &ecu_defaults
+  name: "TEST_ECU"
+  id: "ECU000"
+ecu:
+  <<: *ecu_defaults
+  id: "ECU001"
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    assert_eq!(db.ecu_name, "TEST_ECU");
+    assert_eq!(db.metadata["ecu_id"], "ECU001");
+
+    let recursive = "ecu: &ecu\n  name: x\n  id: y\n  parent: *ecu\n";
+    let err = parse_yaml(recursive).unwrap_err();
+    assert!(
+        matches!(err, diag_yaml::YamlParseError::RecursiveAlias(_)),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_identification_is_typed_and_validated() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+identification:
+  part_number: "8W0907115A"
+  supplier_code: "BOSCH"
+  software_version: "0042"
+  dids:
+    vin: 0xF190
+    part_number: "0xF187"
+dids:
+  0xF190:
+    name: VIN
+    type: ascii
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let ident = db.identification.clone().unwrap();
+    assert_eq!(ident.part_number.as_deref(), Some("8W0907115A"));
+    assert_eq!(ident.hardware_version, None);
+    assert_eq!(ident.dids["vin"], 0xF190);
+
+    let errors = diag_ir::validate_database(&db).unwrap_err();
+    let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        errors,
+        ["identification DID 'part_number' (0xF187) is not defined"]
+    );
+
+    let out = diag_yaml::write_yaml(&db).unwrap();
+    assert!(!out.contains("ecu_identification"), "{out}");
+    let back = parse_yaml(&out).unwrap();
+    assert_eq!(back.identification, Some(ident));
+
+    for (did, shown) in [
+        ("0xF19G", "'0xF19G'"),
+        ("\"0x1F190\"", "'0x1F190'"),
+        ("70000", "'70000'"),
+    ] {
+        let bad = yaml.replace("vin: 0xF190", &format!("vin: {did}"));
+        let err = parse_yaml(&bad).unwrap_err().to_string();
+        assert!(err.contains("identification DID 'vin'"), "{err}");
+        assert!(err.contains(shown), "{err}");
+    }
+}
+
+#[test]
+fn test_variant_detect_by_did() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0xF187:
+    name: SparePartNumber
+    type: ascii
+variants:
+  detection_order: [app, other]
+  definitions:
+    app:
+      detect:
+        did: 0xF187
+        equals: "8W0907115A"
+    other:
+      detect:
+        did: 0xF1AA
+        equals: "x"
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let variant = |name: &str| {
+        db.variants
+            .iter()
+            .find(|v| v.diag_layer.short_name.ends_with(name))
+            .unwrap()
+    };
+
+    let patterns = &variant("_app").variant_patterns;
+    assert_eq!(patterns.len(), 1);
+    let mp = &patterns[0].matching_parameters[0];
+    assert_eq!(mp.diag_service.diag_comm.short_name, "SparePartNumber_Read");
+    assert_eq!(mp.out_param.short_name, "SparePartNumber");
+    assert_eq!(mp.expected_value, "8W0907115A");
+
+    // A DID without a read service gives no pattern.
+    assert!(variant("_other").variant_patterns.is_empty());
+}
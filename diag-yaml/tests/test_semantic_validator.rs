@@ -184,3 +184,90 @@ access_patterns:
         session_errors
     );
 }
+
+#[test]
+fn test_routine_length_key_must_name_earlier_param() {
+    let doc = parse_doc(
+        r#"
+routines:
+  0xFF01:
+    name: Flash
+    access: public
+    operations: [start]
+    parameters:
+      start:
+        input:
+          - name: Data
+            type: { base: bytes }
+            length_key: DataLength
+          - name: DataLength
+            type: { base: u16 }
+          - name: Pad
+            reserved: 8
+            sys_param: TIMESTAMP
+"#,
+    );
+    let issues = validate_semantics(&doc);
+    let messages: Vec<String> = issues.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        messages,
+        [
+            "error: routines/0xFF01/parameters/start/input: length_key 'DataLength' of 'Data' is not an earlier param",
+            "error: routines/0xFF01/parameters/start/input: reserved param 'Pad' cannot have sys_param or length_key",
+        ]
+    );
+    assert!(issues.iter().all(|i| i.severity == Severity::Error));
+}
+
+#[test]
+fn test_unknown_nrc_name() {
+    let doc = parse_doc(
+        r#"
+services:
+  readDataByIdentifier:
+    enabled: true
+    nrcs: [0x31, conditionsNotCorrect, notAnNrc, 0x1FF]
+"#,
+    );
+    let messages: Vec<String> = validate_semantics(&doc)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "error: services/readDataByIdentifier/nrcs: 'notAnNrc' is neither an NRC byte nor an ISO 14229-1 NRC name",
+            "error: services/readDataByIdentifier/nrcs: '511' is neither an NRC byte nor an ISO 14229-1 NRC name",
+        ]
+    );
+}
+
+#[test]
+fn test_unknown_service_profile_and_access_pattern() {
+    let doc = parse_doc(
+        r#"
+access_patterns:
+  extended_only:
+    sessions: any
+    security: none
+services:
+  profile: kwp2000
+  ecuReset:
+    enabled: true
+    access: extended_only
+    subfunction_access:
+      hardReset: programming_only
+"#,
+    );
+    let messages: Vec<String> = validate_semantics(&doc)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "error: services/profile: unknown service profile 'kwp2000'",
+            "error: services/ecuReset/subfunction_access/hardReset: references undefined access pattern 'programming_only'",
+        ]
+    );
+}
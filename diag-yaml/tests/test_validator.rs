@@ -178,3 +178,79 @@ access_patterns:
         errors
     );
 }
+
+#[test]
+fn test_fixtures_have_no_unknown_keys() {
+    for (name, content) in [
+        (
+            "example-ecm.yml",
+            include_str!("../../test-fixtures/yaml/example-ecm.yml"),
+        ),
+        (
+            "FLXC1000.yml",
+            include_str!("../../test-fixtures/yaml/FLXC1000.yml"),
+        ),
+        (
+            "minimal-ecu.yml",
+            include_str!("../../test-fixtures/yaml/minimal-ecu.yml"),
+        ),
+        (
+            "protocol-esd-fixture.yml",
+            include_str!("../../test-fixtures/yaml/protocol-esd-fixture.yml"),
+        ),
+    ] {
+        let unknown = diag_yaml::unknown_keys(content).unwrap();
+        assert!(unknown.is_empty(), "{name}: {unknown:?}");
+    }
+}
+
+#[test]
+fn test_strict_parse_rejects_misspelled_keys() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "ECU"
+dids:
+  0xF190:
+    name: VIN
+    writeable: true
+"#;
+    assert!(diag_yaml::parse_yaml(yaml).is_ok());
+    let err = diag_yaml::parse_yaml_strict(yaml).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "1 unknown key(s):\n  dids.0xF190.writeable: unknown key (did you mean 'writable'?)"
+    );
+}
+
+#[test]
+fn test_invalid_did_keys_are_reported() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "ECU"
+dids:
+  0xF190:
+    name: VIN
+  0xF19O:
+    name: Typo
+routines:
+  0x1234FFFFF:
+    name: TooWide
+"#;
+    let doc = diag_yaml::parse_yaml_document(yaml).unwrap();
+    let invalid = diag_yaml::invalid_keys(&doc);
+    assert_eq!(invalid.len(), 2);
+    assert_eq!(invalid[0].section, "dids");
+    assert_eq!(invalid[0].key, "0xF19O");
+    assert_eq!(invalid[1].section, "routines");
+
+    assert!(diag_yaml::parse_yaml(yaml).is_ok());
+    let err = diag_yaml::parse_yaml_strict(yaml).unwrap_err();
+    assert!(
+        err.to_string().starts_with(
+            "2 invalid key(s):\n  dids.0xF19O: invalid key (expected a decimal or 0x hex number"
+        ),
+        "{err}"
+    );
+}
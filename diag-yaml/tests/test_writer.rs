@@ -1,4 +1,4 @@
-use diag_yaml::{parse_yaml, write_yaml};
+use diag_yaml::{YamlWriteError, YamlWriteOptions, parse_yaml, write_yaml, write_yaml_with};
 
 #[test]
 fn test_yaml_roundtrip_preserves_did_snapshot() {
@@ -18,7 +18,8 @@ dids:
     let db = parse_yaml(yaml).unwrap();
     let yaml_out = write_yaml(&db).unwrap();
     let doc: serde_yaml::Value = serde_yaml::from_str(&yaml_out).unwrap();
-    // Writer outputs DID keys as decimal numbers (0xF190 = 61840)
+    // DID keys are written as YAML hex integers and read back as 61840
+    assert!(yaml_out.contains("\n  0xF190:\n"), "{yaml_out}");
     let did = &doc["dids"][61840];
     assert_eq!(
         did["snapshot"].as_bool(),
@@ -94,6 +95,55 @@ dids:
     );
 }
 
+#[test]
+fn test_yaml_roundtrip_keeps_named_did_types() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+types:
+  VehicleSpeed:
+    base: u16
+    endian: little
+    scale: 0.01
+    unit: km/h
+dids:
+  0x0100:
+    name: Speed
+    type: VehicleSpeed
+  0x0101:
+    name: TargetSpeed
+    type: VehicleSpeed
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let yaml_out = write_yaml(&db).unwrap();
+    let doc: serde_yaml::Value = serde_yaml::from_str(&yaml_out).unwrap();
+    let types = doc["types"].as_mapping().unwrap();
+    assert_eq!(types.len(), 1, "no per-DID types expected:\n{yaml_out}");
+    let speed = &types[&serde_yaml::Value::String("VehicleSpeed".into())];
+    assert_eq!(speed["endian"].as_str(), Some("little"));
+    assert_eq!(speed["unit"].as_str(), Some("km/h"));
+    assert_eq!(doc["dids"][256]["type"].as_str(), Some("VehicleSpeed"));
+    assert_eq!(doc["dids"][257]["type"].as_str(), Some("VehicleSpeed"));
+
+    let reparsed = parse_yaml(&yaml_out).unwrap();
+    assert_eq!(reparsed.type_definitions, db.type_definitions);
+    let response = |db: &diag_ir::DiagDatabase| {
+        db.variants
+            .iter()
+            .find(|v| v.is_base_variant)
+            .unwrap()
+            .diag_layer
+            .diag_services
+            .iter()
+            .find(|s| s.diag_comm.short_name == "Speed_Read")
+            .unwrap()
+            .pos_responses
+            .clone()
+    };
+    assert_eq!(response(&reparsed), response(&db));
+}
+
 #[test]
 fn test_yaml_roundtrip_minimal() {
     let content = include_str!("../../test-fixtures/yaml/minimal-ecu.yml");
@@ -166,6 +216,42 @@ fn test_write_yaml_contains_schema() {
     );
 }
 
+#[test]
+fn test_write_yaml_sections() {
+    let content = include_str!("../../test-fixtures/yaml/example-ecm.yml");
+    let db = parse_yaml(content).unwrap();
+    let opts = YamlWriteOptions {
+        sections: vec!["dids".into(), "dtcs".into()],
+        ..Default::default()
+    };
+    let yaml_output = write_yaml_with(&db, &opts).unwrap();
+
+    let doc: serde_yaml::Value = serde_yaml::from_str(&yaml_output).unwrap();
+    let keys: Vec<&str> = doc
+        .as_mapping()
+        .unwrap()
+        .keys()
+        .filter_map(|k| k.as_str())
+        .collect();
+    assert_eq!(keys, ["schema", "ecu", "dids", "dtcs"]);
+
+    // The fragment is still a YAML document of the same ECU.
+    let reparsed = parse_yaml(&yaml_output).unwrap();
+    assert_eq!(reparsed.ecu_name, db.ecu_name);
+    assert_eq!(reparsed.dtcs.len(), db.dtcs.len());
+}
+
+#[test]
+fn test_write_yaml_unknown_section() {
+    let db = parse_yaml(include_str!("../../test-fixtures/yaml/minimal-ecu.yml")).unwrap();
+    let opts = YamlWriteOptions {
+        sections: vec!["dtc".into()],
+        ..Default::default()
+    };
+    let err = write_yaml_with(&db, &opts).unwrap_err();
+    assert!(matches!(err, YamlWriteError::UnknownSection(ref name) if name == "dtc"));
+}
+
 /// Regression test: writable DID flag must survive IR -> YAML roundtrip.
 /// Previously, `.cloned()` on a mutable borrow caused the writable flag to be
 /// lost because the clone was modified instead of the original map entry.
@@ -296,6 +382,185 @@ memory:
     assert_eq!(flash.access, flash2.access);
 }
 
+#[test]
+fn test_doip_addressing_roundtrip() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+  addressing:
+    doip:
+      ip: "10.0.0.5"
+      port: 13401
+      logical_address: 0x1000
+      functional_address: 0xE400
+      tester_address: 0x0E80
+      gid: "AABBCCDDEEFF"
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let yaml_out = write_yaml(&db).unwrap();
+    let db2 = parse_yaml(&yaml_out).unwrap();
+    assert_eq!(db2.transport, db.transport);
+    assert_eq!(
+        db2.transport.unwrap().doip.unwrap().gid,
+        Some([0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF])
+    );
+}
+
+#[test]
+fn test_service_profile_and_access_roundtrip() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+sessions:
+  default:
+    id: 0x01
+  extended:
+    id: 0x03
+access_patterns:
+  extended_only:
+    sessions: [extended]
+    security: none
+  public:
+    sessions: any
+    security: none
+services:
+  profile: uds
+  ecuReset:
+    enabled: true
+    access: public
+    subfunction_access:
+      hardReset: extended_only
+  inputOutputControlByIdentifier:
+    enabled: true
+    access: extended_only
+    control_types: [returnControlToECU, shortTermAdjustment]
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let yaml_out = write_yaml(&db).unwrap();
+    let db2 = parse_yaml(&yaml_out).unwrap();
+
+    let summary = |db: &diag_ir::DiagDatabase| -> Vec<(String, usize)> {
+        let mut services: Vec<(String, usize)> = db.variants[0]
+            .diag_layer
+            .diag_services
+            .iter()
+            .map(|s| {
+                (
+                    s.diag_comm.short_name.to_string(),
+                    s.diag_comm.pre_condition_state_refs.len(),
+                )
+            })
+            .collect();
+        services.sort();
+        services
+    };
+    let services = summary(&db);
+    assert!(services.contains(&("HardReset".into(), 1)));
+    assert!(services.contains(&("IOControl_ShortTermAdjustment".into(), 1)));
+    assert!(
+        services
+            .iter()
+            .all(|(name, _)| name != "IOControl_ResetToDefault")
+    );
+    assert_eq!(summary(&db2), services);
+}
+
+#[test]
+fn test_checksum_type_roundtrip_and_check_memory_routine() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  id: "MEM_ECU"
+  name: "MemoryTestECU"
+memory:
+  data_blocks:
+    app:
+      name: Application
+      memory_address: 0x08000000
+      memory_size: 0x100000
+      checksum_type: CRC32
+    cal:
+      name: Calibration
+      memory_address: 0x08100000
+      memory_size: 0x10000
+      checksum_type:
+        oem: XorSum
+        length: 1
+        parameters:
+          seed: "0x5A"
+"#;
+
+    let db = parse_yaml(yaml).unwrap();
+    let checksums = |db: &diag_ir::DiagDatabase| -> Vec<Option<diag_ir::ChecksumType>> {
+        db.memory
+            .as_ref()
+            .unwrap()
+            .data_blocks
+            .iter()
+            .map(|b| b.checksum_type.clone())
+            .collect()
+    };
+    let check_memory = |db: &diag_ir::DiagDatabase| -> Vec<String> {
+        db.variants[0]
+            .diag_layer
+            .diag_services
+            .iter()
+            .map(|s| s.diag_comm.short_name.to_string())
+            .filter(|n| n.starts_with("CheckMemory_"))
+            .collect()
+    };
+    assert_eq!(
+        checksums(&db)[0],
+        Some(diag_ir::ChecksumType::Crc32),
+        "names are case-insensitive"
+    );
+    assert_eq!(
+        check_memory(&db),
+        ["CheckMemory_Application", "CheckMemory_Calibration"]
+    );
+
+    // The routines are regenerated, not written back as `routines:` entries.
+    let yaml_out = write_yaml(&db).unwrap();
+    assert!(!yaml_out.contains("CheckMemory_"), "{yaml_out}");
+    let db2 = parse_yaml(&yaml_out).unwrap();
+    assert_eq!(checksums(&db2), checksums(&db));
+    assert_eq!(check_memory(&db2), check_memory(&db));
+
+    let invalid = yaml.replace("CRC32", "md5");
+    let err = parse_yaml(&invalid).unwrap_err().to_string();
+    assert!(err.contains("unknown checksum_type 'md5'"), "{err}");
+}
+
+#[test]
+fn test_flash_sequence_roundtrip() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "FLASH_ECU"
+flash_sequence:
+  - session: programming
+  - security: 1
+  - routine: EraseMemory
+  - transfer: Application
+  - reset: hardReset
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let steps = diag_ir::flash_sequence(db.variants[0].diag_layer.sdgs.as_ref());
+    assert_eq!(steps.len(), 5);
+    assert_eq!(steps[1], diag_ir::FlashStep::Security(1));
+
+    let yaml_out = write_yaml(&db).unwrap();
+    assert!(yaml_out.contains("- session: programming"), "{yaml_out}");
+    assert_eq!(yaml_out.matches("flash_sequence").count(), 1, "{yaml_out}");
+    let db2 = parse_yaml(&yaml_out).unwrap();
+    assert_eq!(
+        diag_ir::flash_sequence(db2.variants[0].diag_layer.sdgs.as_ref()),
+        steps
+    );
+}
+
 #[test]
 fn test_sessions_state_model_security_roundtrip() {
     let yaml = r#"
@@ -1109,3 +1374,324 @@ ecu:
         "empty ecu_shared_data should be omitted"
     );
 }
+
+#[test]
+fn test_session_server_timing_roundtrip() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  id: "TIMING_ECU"
+  name: "TimingECU"
+sessions:
+  default:
+    id: 1
+    timing:
+      p2_server_max: 50
+      p2_star_server_max: 5000
+  extended:
+    id: 3
+services:
+  diagnosticSessionControl:
+    enabled: true
+"#;
+
+    let db = parse_yaml(yaml).unwrap();
+    let services = &db.variants[0].diag_layer.diag_services;
+    let response_len = |name: &str| {
+        services
+            .iter()
+            .find(|s| s.diag_comm.short_name == name)
+            .map(|s| s.pos_responses[0].params.len())
+    };
+    assert_eq!(response_len("default_Start"), Some(4));
+    assert_eq!(response_len("extended_Start"), Some(2));
+
+    let yaml_out = write_yaml(&db).unwrap();
+    assert!(yaml_out.contains("p2_server_max: 50"), "{yaml_out}");
+    assert!(yaml_out.contains("p2_star_server_max: 5000"), "{yaml_out}");
+    let db2 = parse_yaml(&yaml_out).unwrap();
+    assert_eq!(
+        db2.variants[0].diag_layer.diag_services,
+        db.variants[0].diag_layer.diag_services
+    );
+}
+
+#[test]
+fn test_feature_tags_roundtrip() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0xFD00:
+    name: DebugCounters
+    type: u32
+    access: public
+    features: [debug]
+services:
+  testerPresent:
+    enabled: true
+    features: [base, debug]
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let yaml_out = write_yaml(&db).unwrap();
+    let doc: serde_yaml::Value = serde_yaml::from_str(&yaml_out).unwrap();
+    assert_eq!(doc["dids"][0xFD00]["features"][0].as_str(), Some("debug"));
+    let tp_features = &doc["services"]["testerPresent"]["features"];
+    assert_eq!(tp_features[0].as_str(), Some("base"));
+    assert_eq!(tp_features[1].as_str(), Some("debug"));
+    let tags = |db: &diag_ir::DiagDatabase| -> Vec<(String, Vec<String>)> {
+        db.variants[0]
+            .diag_layer
+            .diag_services
+            .iter()
+            .map(|s| {
+                let tags = diag_ir::feature_tags(&s.diag_comm);
+                (
+                    s.diag_comm.short_name.to_string(),
+                    tags.into_iter().map(String::from).collect(),
+                )
+            })
+            .collect()
+    };
+    assert_eq!(tags(&parse_yaml(&yaml_out).unwrap()), tags(&db));
+}
+
+#[test]
+fn test_descriptions_roundtrip() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0xF190:
+    name: VIN
+    description: Vehicle identification number
+    type: ascii
+    access: public
+routines:
+  0x0203:
+    name: EraseMemory
+    description: Erase the application
+    access: public
+    operations: [start]
+"#;
+    let mut db = parse_yaml(yaml).unwrap();
+    let description = |db: &diag_ir::DiagDatabase, name: &str| -> Option<String> {
+        db.variants[0]
+            .diag_layer
+            .diag_services
+            .iter()
+            .find(|s| s.diag_comm.short_name == name)
+            .and_then(|s| s.diag_comm.description.clone())
+    };
+    assert_eq!(
+        description(&db, "VIN_Read").as_deref(),
+        Some("Vehicle identification number")
+    );
+    assert_eq!(
+        description(&db, "EraseMemory").as_deref(),
+        Some("Erase the application")
+    );
+
+    // A Markdown description, e.g. from an ODX DESC, is written instead of
+    // the long name.
+    let markdown = "Erases **all** blocks:\n\n- application\n- calibration";
+    let erase = db.variants[0]
+        .diag_layer
+        .diag_services
+        .iter_mut()
+        .find(|s| s.diag_comm.short_name == "EraseMemory")
+        .unwrap();
+    erase.diag_comm.description = Some(markdown.into());
+    let db2 = parse_yaml(&write_yaml(&db).unwrap()).unwrap();
+    assert_eq!(description(&db2, "EraseMemory").as_deref(), Some(markdown));
+    assert_eq!(description(&db2, "VIN_Read"), description(&db, "VIN_Read"));
+}
+
+#[test]
+fn test_lifecycle_roundtrip() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0xF1A0:
+    name: LegacyId
+    type: ascii
+    access: public
+    lifecycle:
+      status: deprecated
+      since: "2.1"
+dtcs:
+  0x012300:
+    name: OldFault
+    sae: P0123
+    lifecycle:
+      status: obsolete
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let yaml_out = write_yaml(&db).unwrap();
+    let doc: serde_yaml::Value = serde_yaml::from_str(&yaml_out).unwrap();
+    let did_lc = &doc["dids"][0xF1A0]["lifecycle"];
+    assert_eq!(did_lc["status"].as_str(), Some("deprecated"));
+    assert_eq!(did_lc["since"].as_str(), Some("2.1"));
+    assert_eq!(
+        doc["dtcs"][0x012300]["lifecycle"]["status"].as_str(),
+        Some("obsolete")
+    );
+}
+
+#[test]
+fn test_aliases_roundtrip() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0xF190:
+    name: VIN
+    type: ascii
+    access: public
+    writable: true
+    aliases: [VehicleIdent, VIN_Number]
+routines:
+  0x0203:
+    name: EraseMemory
+    access: public
+    operations: [start]
+    aliases: [Erase]
+"#;
+    let db = parse_yaml(yaml).unwrap();
+    let services = &db.variants[0].diag_layer.diag_services;
+    let find = |name| diag_ir::find_by_name(services, name, |s| &s.diag_comm);
+    let short_name = |name| find(name).map(|s| s.diag_comm.short_name.as_str());
+    assert_eq!(short_name("vehicleident_read"), Some("VIN_Read"));
+    assert_eq!(short_name("VIN_Number_Write"), Some("VIN_Write"));
+    assert_eq!(short_name("ERASE"), Some("EraseMemory"));
+
+    let yaml_out = write_yaml(&db).unwrap();
+    let doc: serde_yaml::Value = serde_yaml::from_str(&yaml_out).unwrap();
+    let aliases = |v: &serde_yaml::Value| -> Vec<String> {
+        serde_yaml::from_value(v["aliases"].clone()).unwrap()
+    };
+    assert_eq!(
+        aliases(&doc["dids"][0xF190]),
+        ["VehicleIdent", "VIN_Number"]
+    );
+    assert_eq!(aliases(&doc["routines"][0x0203]), ["Erase"]);
+}
+
+#[test]
+fn test_leading_length_type_roundtrip() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0xF18C:
+    name: SerialNumber
+    type:
+      base: ascii
+      length_prefix_bits: 8
+    access: public
+"#;
+    let coded_type = |db: &diag_ir::DiagDatabase| {
+        let svc = db.variants[0]
+            .diag_layer
+            .diag_services
+            .iter()
+            .find(|s| s.diag_comm.short_name == "SerialNumber_Read")
+            .expect("service not found");
+        let param = svc.pos_responses[0]
+            .params
+            .iter()
+            .find(|p| p.param_type == diag_ir::ParamType::Value)
+            .expect("data param not found");
+        match &param.specific_data {
+            Some(diag_ir::ParamData::Value { dop, .. }) => match &dop.specific_data {
+                Some(diag_ir::DopData::NormalDop {
+                    diag_coded_type: Some(dct),
+                    ..
+                }) => dct.clone(),
+                other => panic!("Expected NormalDop, got {other:?}"),
+            },
+            other => panic!("Expected Value param, got {other:?}"),
+        }
+    };
+
+    let db = parse_yaml(yaml).unwrap();
+    let dct = coded_type(&db);
+    assert_eq!(
+        dct.type_name,
+        diag_ir::DiagCodedTypeName::LeadingLengthInfoType
+    );
+    assert_eq!(
+        dct.specific_data,
+        Some(diag_ir::DiagCodedTypeData::LeadingLength { bit_length: 8 })
+    );
+
+    let yaml_out = write_yaml(&db).unwrap();
+    let doc: serde_yaml::Value = serde_yaml::from_str(&yaml_out).unwrap();
+    let type_ref = doc["dids"][61836]["type"].as_str().expect("type reference");
+    assert_eq!(
+        doc["types"][type_ref]["length_prefix_bits"].as_u64(),
+        Some(8)
+    );
+    assert_eq!(coded_type(&parse_yaml(&yaml_out).unwrap()), dct);
+}
+
+#[test]
+fn test_bit_mask_type_roundtrip() {
+    let yaml = r#"
+schema: "opensovd.cda.diagdesc/v1"
+ecu:
+  name: "TEST"
+dids:
+  0x0101:
+    name: Status
+    type:
+      base: u16
+      bit_mask: 0x0F30
+      condensed: true
+    access: public
+"#;
+    let coded_type = |db: &diag_ir::DiagDatabase| {
+        let svc = db.variants[0]
+            .diag_layer
+            .diag_services
+            .iter()
+            .find(|s| s.diag_comm.short_name == "Status_Read")
+            .expect("service not found");
+        let param = svc.pos_responses[0]
+            .params
+            .iter()
+            .find(|p| p.param_type == diag_ir::ParamType::Value)
+            .expect("data param not found");
+        match &param.specific_data {
+            Some(diag_ir::ParamData::Value { dop, .. }) => match &dop.specific_data {
+                Some(diag_ir::DopData::NormalDop {
+                    diag_coded_type: Some(dct),
+                    ..
+                }) => dct.specific_data.clone(),
+                other => panic!("Expected NormalDop, got {other:?}"),
+            },
+            other => panic!("Expected Value param, got {other:?}"),
+        }
+    };
+
+    let db = parse_yaml(yaml).unwrap();
+    let expected = Some(diag_ir::DiagCodedTypeData::StandardLength {
+        bit_length: 16,
+        bit_mask: vec![0x0F, 0x30],
+        condensed: true,
+    });
+    assert_eq!(coded_type(&db), expected);
+
+    let yaml_out = write_yaml(&db).unwrap();
+    let doc: serde_yaml::Value = serde_yaml::from_str(&yaml_out).unwrap();
+    let type_ref = doc["dids"][257]["type"].as_str().expect("type reference");
+    assert_eq!(doc["types"][type_ref]["bit_mask"].as_str(), Some("0xF30"));
+    assert_eq!(doc["types"][type_ref]["condensed"].as_bool(), Some(true));
+    assert_eq!(coded_type(&parse_yaml(&yaml_out).unwrap()), expected);
+}
@@ -19,6 +19,15 @@ impl Compression {
         }
     }
 
+    /// Valid `level` range for [`compress_with_level`], `None` for no compression.
+    pub fn level_range(&self) -> Option<std::ops::RangeInclusive<i32>> {
+        match self {
+            Compression::None => None,
+            Compression::Lzma | Compression::Gzip => Some(0..=9),
+            Compression::Zstd => Some(1..=22),
+        }
+    }
+
     pub fn from_name(name: &str) -> Result<Self, CompressionError> {
         match name {
             "lzma" => Ok(Compression::Lzma),
@@ -37,15 +46,35 @@ pub enum CompressionError {
     DecompressFailed(String),
     #[error("unknown compression algorithm: {0}")]
     UnknownAlgorithm(String),
+    #[error("compression level {level} out of range for {algorithm}")]
+    InvalidLevel { algorithm: String, level: i32 },
 }
 
 pub fn compress(data: &[u8], algo: &Compression) -> Result<Vec<u8>, CompressionError> {
+    compress_with_level(data, algo, None)
+}
+
+/// Compress with an explicit level (see [`Compression::level_range`]).
+/// `None` uses the codec default: LZMA preset 6, gzip 6, zstd 3.
+pub fn compress_with_level(
+    data: &[u8],
+    algo: &Compression,
+    level: Option<i32>,
+) -> Result<Vec<u8>, CompressionError> {
+    if let (Some(level), Some(range)) = (level, algo.level_range()) {
+        if !range.contains(&level) {
+            return Err(CompressionError::InvalidLevel {
+                algorithm: algo.algorithm_name().unwrap_or("none").into(),
+                level,
+            });
+        }
+    }
     match algo {
         Compression::None => Ok(data.to_vec()),
         Compression::Lzma => {
             // MUST use new_lzma_encoder (LZMA_ALONE format), NOT MtStreamBuilder/XzEncoder (XZ format).
             // CDA reads with: xz2::stream::Stream::new_lzma_decoder(u64::MAX)
-            let opts = xz2::stream::LzmaOptions::new_preset(6)
+            let opts = xz2::stream::LzmaOptions::new_preset(level.map_or(6, i32::unsigned_abs))
                 .map_err(|e| CompressionError::CompressFailed(e.to_string()))?;
             let stream = xz2::stream::Stream::new_lzma_encoder(&opts)
                 .map_err(|e| CompressionError::CompressFailed(e.to_string()))?;
@@ -59,7 +88,10 @@ pub fn compress(data: &[u8], algo: &Compression) -> Result<Vec<u8>, CompressionE
         }
         Compression::Gzip => {
             use flate2::write::GzEncoder;
-            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            let gz_level = level.map_or_else(flate2::Compression::default, |l| {
+                flate2::Compression::new(l.unsigned_abs())
+            });
+            let mut encoder = GzEncoder::new(Vec::new(), gz_level);
             encoder
                 .write_all(data)
                 .map_err(|e| CompressionError::CompressFailed(e.to_string()))?;
@@ -67,7 +99,7 @@ pub fn compress(data: &[u8], algo: &Compression) -> Result<Vec<u8>, CompressionE
                 .finish()
                 .map_err(|e| CompressionError::CompressFailed(e.to_string()))
         }
-        Compression::Zstd => zstd::encode_all(std::io::Cursor::new(data), 3)
+        Compression::Zstd => zstd::encode_all(std::io::Cursor::new(data), level.unwrap_or(3))
             .map_err(|e| CompressionError::CompressFailed(e.to_string())),
     }
 }
@@ -142,6 +174,52 @@ pub fn decompress_bounded(
     }
 }
 
+/// A streaming decoder for `data`, so callers can process a payload piece
+/// by piece instead of decompressing it into one buffer. The output is not
+/// bounded; wrap it in [`Read::take`] for untrusted input.
+pub fn decoder<'a>(
+    data: &'a [u8],
+    algorithm: &str,
+) -> Result<Box<dyn Read + 'a>, CompressionError> {
+    match algorithm {
+        "lzma" => {
+            const LZMA_MEMLIMIT: u64 = 256 * 1024 * 1024;
+            let stream = xz2::stream::Stream::new_lzma_decoder(LZMA_MEMLIMIT)
+                .map_err(|e| CompressionError::DecompressFailed(e.to_string()))?;
+            Ok(Box::new(xz2::bufread::XzDecoder::new_stream(
+                std::io::BufReader::new(data),
+                stream,
+            )))
+        }
+        "gzip" => Ok(Box::new(flate2::read::GzDecoder::new(data))),
+        "zstd" => zstd::Decoder::new(std::io::Cursor::new(data))
+            .map(|decoder| Box::new(decoder) as Box<dyn Read + 'a>)
+            .map_err(|e| CompressionError::DecompressFailed(e.to_string())),
+        other => Err(CompressionError::UnknownAlgorithm(other.into())),
+    }
+}
+
+/// Like [`decompress_bounded`], but for damaged input: returns the bytes
+/// decoded before the stream broke off, with the error that stopped it.
+pub fn decompress_partial(
+    data: &[u8],
+    algorithm: &str,
+    max_size: u64,
+) -> (Vec<u8>, Option<CompressionError>) {
+    let decoder = match decoder(data, algorithm) {
+        Ok(decoder) => decoder,
+        Err(e) => return (vec![], Some(e)),
+    };
+    let mut out = Vec::new();
+    // On error, `read_to_end` leaves everything decoded so far in `out`.
+    let error = decoder
+        .take(max_size)
+        .read_to_end(&mut out)
+        .err()
+        .map(|e| CompressionError::DecompressFailed(e.to_string()));
+    (out, error)
+}
+
 #[cfg(any(test, feature = "test-utils"))]
 pub fn decompress(data: &[u8], algorithm: &str) -> Result<Vec<u8>, CompressionError> {
     match algorithm {
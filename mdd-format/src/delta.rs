@@ -0,0 +1,641 @@
+//! Binary deltas between two MDD files, for over-the-air updates.
+//!
+//! A patch rebuilds the new file from the old one byte for byte. The new
+//! file is cut into segments: the data of each chunk and the container bytes
+//! between them. Container bytes are copied from anywhere in the old file or
+//! inserted. Compressed chunk data that recompresses to the same bytes is
+//! instead rebuilt from its uncompressed payload, diffed against the payload
+//! of the old chunk with the same type and name: a small change to the
+//! description changes most of its compressed bytes but few of its
+//! uncompressed ones. Other chunk data is diffed like container bytes.
+//!
+//! Rebuilding chunk data relies on the compressor applying the patch giving
+//! the same bytes as the one that made it, which another compressor version
+//! or default level need not. Each such segment records a fingerprint of its
+//! compressor: the digest of a fixed sample compressed by it. Applying the
+//! patch with a compressor of another fingerprint fails before anything is
+//! rebuilt.
+//!
+//! A patch records the SHA-512 of the old and of the new file. Applying it
+//! to any other file, or getting a result that does not hash to the new
+//! file, is an error. Neither the new file nor a rebuilt payload may grow
+//! past [`MAX_DECOMPRESSED_SIZE`](compression::MAX_DECOMPRESSED_SIZE).
+//!
+//! Layout: [`PATCH_MAGIC`], the varint size of the body, then the
+//! LZMA-compressed body: both digests and the segments, with varint counts
+//! and lengths (see `Patch::encode`). The body is never decompressed past
+//! its declared size, which is itself capped at
+//! [`MAX_DECOMPRESSED_SIZE`](compression::MAX_DECOMPRESSED_SIZE).
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::ops::Range;
+
+use prost::encoding::{
+    DecodeContext, WireType, decode_key, decode_varint, encode_varint, skip_field,
+};
+use sha2::{Digest, Sha512};
+use thiserror::Error;
+
+use crate::compression::{self, Compression, CompressionError};
+use crate::fileformat;
+use crate::reader::{CHUNK_DATA, FILE_MAGIC, MDD_FILE_CHUNKS, MddReadError, read_mdd_container};
+
+/// Magic header bytes of a patch: "MDD patch 1        \0" (20 bytes).
+pub const PATCH_MAGIC: &[u8; 20] = b"MDD patch 1        \0";
+
+/// Length of the blocks of the old data that matches start from.
+const BLOCK: usize = 16;
+
+#[derive(Debug, Error)]
+pub enum DeltaError {
+    #[error("invalid MDD patch magic header")]
+    InvalidMagic,
+    #[error("malformed patch: {0}")]
+    Malformed(String),
+    #[error("the patch was made for another file (SHA-512 of the old file differs)")]
+    SourceMismatch,
+    #[error("the patched file does not match the SHA-512 recorded in the patch")]
+    TargetMismatch,
+    #[error(
+        "the patch was made with a {0} compressor that compresses differently from this one; \
+         make the patch with this version of the converter"
+    )]
+    CompressorMismatch(String),
+    #[error("{0}")]
+    Read(#[from] MddReadError),
+    #[error("{0}")]
+    Compression(#[from] CompressionError),
+}
+
+/// A patch turning `old` MDD bytes into `new` ones.
+pub fn make_delta(old: &[u8], new: &[u8]) -> Result<Vec<u8>, DeltaError> {
+    let old_file = read_mdd_container(old)?;
+    let new_file = read_mdd_container(new)?;
+    let old_index = BlockIndex::new(old);
+    let old_payloads: Vec<Option<Vec<u8>>> = old_file
+        .chunks
+        .iter()
+        .map(|c| payload(c, c.data.as_deref().unwrap_or_default()))
+        .collect();
+
+    let mut segments = Vec::new();
+    let mut at = 0;
+    for (chunk, range) in new_file.chunks.iter().zip(chunk_data_ranges(new)?) {
+        let Some(range) = range else { continue };
+        let Some(segment) = payload_segment(chunk, &new[range.clone()], &old_file, &old_payloads)
+        else {
+            continue;
+        };
+        segments.push(Segment::Bytes(old_index.diff(&new[at..range.start])));
+        segments.push(segment);
+        at = range.end;
+    }
+    segments.push(Segment::Bytes(old_index.diff(&new[at..])));
+
+    let patch = Patch {
+        source_sha512: Sha512::digest(old).to_vec(),
+        target_sha512: Sha512::digest(new).to_vec(),
+        segments,
+    };
+    wrap(&patch.encode())
+}
+
+/// A patch file holding `body`.
+fn wrap(body: &[u8]) -> Result<Vec<u8>, DeltaError> {
+    let mut out = PATCH_MAGIC.to_vec();
+    put_usize(&mut out, body.len());
+    out.extend(compression::compress(body, &Compression::Lzma)?);
+    Ok(out)
+}
+
+/// Apply a patch from [`make_delta`] to `old`, returning the new MDD bytes.
+pub fn apply_delta(old: &[u8], patch: &[u8]) -> Result<Vec<u8>, DeltaError> {
+    let mut input = Input(
+        patch
+            .strip_prefix(PATCH_MAGIC.as_slice())
+            .ok_or(DeltaError::InvalidMagic)?,
+    );
+    let body_size = input.usize()? as u64;
+    if body_size > compression::MAX_DECOMPRESSED_SIZE {
+        return Err(DeltaError::Malformed(format!(
+            "body of {body_size} bytes exceeds limit {}",
+            compression::MAX_DECOMPRESSED_SIZE
+        )));
+    }
+    let body = compression::decompress_bounded(input.0, "lzma", body_size)?;
+    if body.len() as u64 != body_size {
+        return Err(DeltaError::Malformed(format!(
+            "body of {} bytes, {body_size} declared",
+            body.len()
+        )));
+    }
+    let patch = Patch::decode(&body)?;
+    if Sha512::digest(old).as_slice() != patch.source_sha512 {
+        return Err(DeltaError::SourceMismatch);
+    }
+    let mut fingerprints = HashMap::new();
+    for segment in &patch.segments {
+        if let Segment::Payload {
+            algorithm,
+            compressor,
+            ..
+        } = segment
+        {
+            let local = match fingerprints.entry(algorithm) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    entry.insert(fingerprint(&Compression::from_name(algorithm)?)?)
+                }
+            };
+            if *local != *compressor {
+                return Err(DeltaError::CompressorMismatch(algorithm.clone()));
+            }
+        }
+    }
+    let old_file = read_mdd_container(old)?;
+
+    let mut new = Vec::new();
+    for segment in &patch.segments {
+        match segment {
+            Segment::Bytes(ops) => rebuild(old, ops, &mut new, MAX_REBUILT_SIZE)?,
+            Segment::Payload {
+                source,
+                algorithm,
+                ops,
+                ..
+            } => {
+                let base = match source {
+                    Some(i) => old_file
+                        .chunks
+                        .get(*i)
+                        .and_then(|c| payload(c, c.data.as_deref().unwrap_or_default()))
+                        .ok_or_else(|| {
+                            DeltaError::Malformed(format!(
+                                "chunk {i} of the old file has no compressed payload"
+                            ))
+                        })?,
+                    None => Vec::new(),
+                };
+                let mut payload = Vec::new();
+                rebuild(&base, ops, &mut payload, MAX_REBUILT_SIZE)?;
+                let compression = Compression::from_name(algorithm)?;
+                let data = compression::compress(&payload, &compression)?;
+                if new.len() + data.len() > MAX_REBUILT_SIZE {
+                    return Err(too_large());
+                }
+                new.extend(data);
+            }
+        }
+    }
+    if Sha512::digest(&new).as_slice() != patch.target_sha512 {
+        return Err(DeltaError::TargetMismatch);
+    }
+    Ok(new)
+}
+
+/// Most bytes [`rebuild`] may produce for the new file or a payload.
+const MAX_REBUILT_SIZE: usize = compression::MAX_DECOMPRESSED_SIZE as usize;
+
+fn too_large() -> DeltaError {
+    DeltaError::Malformed(format!(
+        "rebuilt data exceeds limit {}",
+        compression::MAX_DECOMPRESSED_SIZE
+    ))
+}
+
+/// Input compressed to tell compressors apart: text repeating at several
+/// distances, like a diagnostic description, so that match finding and
+/// entropy coding both shape the output.
+fn probe() -> Vec<u8> {
+    (0..4096u32)
+        .flat_map(|i| format!("{}:{:X};", i % 61, i.wrapping_mul(2_654_435_761) >> 20).into_bytes())
+        .collect()
+}
+
+/// The fingerprint of the compressor of `compression`: the first 16 bytes
+/// of the SHA-512 of [`probe`] compressed at the default level.
+fn fingerprint(compression: &Compression) -> Result<Vec<u8>, DeltaError> {
+    let compressed = compression::compress(&probe(), compression)?;
+    Ok(Sha512::digest(compressed)[..16].to_vec())
+}
+
+/// The uncompressed payload of a compressed, unencrypted chunk.
+fn payload(chunk: &fileformat::Chunk, data: &[u8]) -> Option<Vec<u8>> {
+    if chunk.encryption.is_some() {
+        return None;
+    }
+    let algorithm = chunk
+        .compression_algorithm
+        .as_deref()
+        .filter(|a| !a.is_empty())?;
+    let max_size = chunk
+        .uncompressed_size
+        .unwrap_or(compression::MAX_DECOMPRESSED_SIZE);
+    compression::decompress_bounded(data, algorithm, max_size).ok()
+}
+
+/// The segment rebuilding the compressed `data` of `chunk` from its
+/// payload, if compressing the payload again gives the same bytes.
+fn payload_segment(
+    chunk: &fileformat::Chunk,
+    data: &[u8],
+    old_file: &fileformat::MddFile,
+    old_payloads: &[Option<Vec<u8>>],
+) -> Option<Segment> {
+    let new_payload = payload(chunk, data)?;
+    let algorithm = chunk.compression_algorithm.clone()?;
+    let compression = Compression::from_name(&algorithm).ok()?;
+    if compression::compress(&new_payload, &compression).ok()? != data {
+        return None;
+    }
+    let source = old_file.chunks.iter().enumerate().find_map(|(i, c)| {
+        let same = c.r#type == chunk.r#type && c.name == chunk.name;
+        old_payloads[i].as_deref().filter(|_| same).map(|p| (i, p))
+    });
+    let ops = match source {
+        Some((_, old_payload)) => BlockIndex::new(old_payload).diff(&new_payload),
+        None => vec![Op::Insert(new_payload)],
+    };
+    Some(Segment::Payload {
+        source: source.map(|(i, _)| i),
+        compressor: fingerprint(&compression).ok()?,
+        algorithm,
+        ops,
+    })
+}
+
+/// File offsets of the data of every chunk of MDD bytes, in container order.
+fn chunk_data_ranges(data: &[u8]) -> Result<Vec<Option<Range<usize>>>, DeltaError> {
+    let decode_error = |e: prost::DecodeError| DeltaError::Read(e.into());
+    let mut ranges = Vec::new();
+    let mut buf = &data[FILE_MAGIC.len()..];
+    while !buf.is_empty() {
+        let (tag, wire_type) = decode_key(&mut buf).map_err(decode_error)?;
+        if tag != MDD_FILE_CHUNKS || wire_type != WireType::LengthDelimited {
+            skip_field(wire_type, tag, &mut buf, DecodeContext::default()).map_err(decode_error)?;
+            continue;
+        }
+        let len = length(&mut buf).map_err(decode_error)?;
+        let chunk_end = data.len() - buf.len() + len;
+        let mut chunk = &buf[..len];
+        buf = &buf[len..];
+        let mut range = None;
+        while !chunk.is_empty() {
+            let (tag, wire_type) = decode_key(&mut chunk).map_err(decode_error)?;
+            if tag == CHUNK_DATA && wire_type == WireType::LengthDelimited {
+                let len = length(&mut chunk).map_err(decode_error)?;
+                let start = chunk_end - chunk.len();
+                range = Some(start..start + len);
+                chunk = &chunk[len..];
+            } else {
+                skip_field(wire_type, tag, &mut chunk, DecodeContext::default())
+                    .map_err(decode_error)?;
+            }
+        }
+        ranges.push(range);
+    }
+    Ok(ranges)
+}
+
+/// A varint length that fits in `buf`.
+fn length(buf: &mut &[u8]) -> Result<usize, prost::DecodeError> {
+    usize::try_from(decode_varint(buf)?)
+        .ok()
+        .filter(|&len| len <= buf.len())
+        .ok_or_else(|| prost::DecodeError::new("buffer underflow"))
+}
+
+// --- Copy/insert deltas ---
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Op {
+    /// Bytes `offset..offset + len` of the old data.
+    Copy {
+        offset: usize,
+        len: usize,
+    },
+    Insert(Vec<u8>),
+}
+
+/// The aligned blocks of old data, for finding where new data matches it.
+struct BlockIndex<'a> {
+    old: &'a [u8],
+    blocks: HashMap<&'a [u8], usize>,
+}
+
+impl<'a> BlockIndex<'a> {
+    fn new(old: &'a [u8]) -> Self {
+        let mut blocks = HashMap::new();
+        for (i, block) in old.chunks_exact(BLOCK).enumerate() {
+            blocks.entry(block).or_insert(i * BLOCK);
+        }
+        Self { old, blocks }
+    }
+
+    /// Copies of the old data and inserts that rebuild `new`. Every block
+    /// of `new` is looked up, and a hit is extended in both directions.
+    fn diff(&self, new: &[u8]) -> Vec<Op> {
+        let mut ops = Vec::new();
+        let mut pending = 0;
+        let mut i = 0;
+        while i + BLOCK <= new.len() {
+            let Some(&pos) = self.blocks.get(&new[i..i + BLOCK]) else {
+                i += 1;
+                continue;
+            };
+            let back = new[pending..i]
+                .iter()
+                .rev()
+                .zip(self.old[..pos].iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+            let forward = new[i..]
+                .iter()
+                .zip(&self.old[pos..])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if i - back > pending {
+                ops.push(Op::Insert(new[pending..i - back].to_vec()));
+            }
+            ops.push(Op::Copy {
+                offset: pos - back,
+                len: back + forward,
+            });
+            i += forward;
+            pending = i;
+        }
+        if pending < new.len() {
+            ops.push(Op::Insert(new[pending..].to_vec()));
+        }
+        ops
+    }
+}
+
+/// Append the data `ops` rebuild from `old` to `out`, which may not grow
+/// past `limit` bytes.
+fn rebuild(old: &[u8], ops: &[Op], out: &mut Vec<u8>, limit: usize) -> Result<(), DeltaError> {
+    for op in ops {
+        let len = match op {
+            Op::Copy { len, .. } => *len,
+            Op::Insert(bytes) => bytes.len(),
+        };
+        if out.len().saturating_add(len) > limit {
+            return Err(too_large());
+        }
+        match op {
+            Op::Copy { offset, len } => {
+                let bytes = offset
+                    .checked_add(*len)
+                    .and_then(|end| old.get(*offset..end))
+                    .ok_or_else(|| {
+                        DeltaError::Malformed(format!(
+                            "copy of {len} bytes at {offset} beyond the old data ({} bytes)",
+                            old.len()
+                        ))
+                    })?;
+                out.extend_from_slice(bytes);
+            }
+            Op::Insert(bytes) => out.extend_from_slice(bytes),
+        }
+    }
+    Ok(())
+}
+
+// --- Patch encoding ---
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// Bytes rebuilt from the old file.
+    Bytes(Vec<Op>),
+    /// Chunk data: a payload rebuilt from the payload of the old chunk
+    /// `source` (or from nothing), compressed with `algorithm` by a
+    /// compressor of [`fingerprint`] `compressor`.
+    Payload {
+        source: Option<usize>,
+        algorithm: String,
+        compressor: Vec<u8>,
+        ops: Vec<Op>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Patch {
+    source_sha512: Vec<u8>,
+    target_sha512: Vec<u8>,
+    segments: Vec<Segment>,
+}
+
+const SEGMENT_BYTES: u8 = 0;
+const SEGMENT_PAYLOAD: u8 = 1;
+const OP_COPY: u8 = 0;
+const OP_INSERT: u8 = 1;
+
+impl Patch {
+    /// `source_sha512`, `target_sha512`, then the segments: a tag byte, for
+    /// payloads the old chunk index + 1 (0 for none), the algorithm and the
+    /// compressor fingerprint, and the ops. Ops are a tag byte followed by offset and length of a copy
+    /// or the bytes of an insert. Byte strings are length-prefixed.
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        put_bytes(&mut out, &self.source_sha512);
+        put_bytes(&mut out, &self.target_sha512);
+        put_usize(&mut out, self.segments.len());
+        for segment in &self.segments {
+            match segment {
+                Segment::Bytes(ops) => {
+                    out.push(SEGMENT_BYTES);
+                    put_ops(&mut out, ops);
+                }
+                Segment::Payload {
+                    source,
+                    algorithm,
+                    compressor,
+                    ops,
+                } => {
+                    out.push(SEGMENT_PAYLOAD);
+                    put_usize(&mut out, source.map_or(0, |i| i + 1));
+                    put_bytes(&mut out, algorithm.as_bytes());
+                    put_bytes(&mut out, compressor);
+                    put_ops(&mut out, ops);
+                }
+            }
+        }
+        out
+    }
+
+    fn decode(body: &[u8]) -> Result<Self, DeltaError> {
+        let mut input = Input(body);
+        let source_sha512 = input.bytes()?.to_vec();
+        let target_sha512 = input.bytes()?.to_vec();
+        let count = input.usize()?;
+        let mut segments = Vec::new();
+        for _ in 0..count {
+            segments.push(match input.byte()? {
+                SEGMENT_BYTES => Segment::Bytes(input.ops()?),
+                SEGMENT_PAYLOAD => Segment::Payload {
+                    source: input.usize()?.checked_sub(1),
+                    algorithm: String::from_utf8(input.bytes()?.to_vec())
+                        .map_err(|_| DeltaError::Malformed("algorithm is not UTF-8".into()))?,
+                    compressor: input.bytes()?.to_vec(),
+                    ops: input.ops()?,
+                },
+                tag => return Err(DeltaError::Malformed(format!("unknown segment {tag}"))),
+            });
+        }
+        if !input.0.is_empty() {
+            return Err(DeltaError::Malformed("trailing bytes".into()));
+        }
+        Ok(Self {
+            source_sha512,
+            target_sha512,
+            segments,
+        })
+    }
+}
+
+fn put_usize(out: &mut Vec<u8>, value: usize) {
+    encode_varint(value as u64, out);
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_usize(out, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+fn put_ops(out: &mut Vec<u8>, ops: &[Op]) {
+    put_usize(out, ops.len());
+    for op in ops {
+        match op {
+            Op::Copy { offset, len } => {
+                out.push(OP_COPY);
+                put_usize(out, *offset);
+                put_usize(out, *len);
+            }
+            Op::Insert(bytes) => {
+                out.push(OP_INSERT);
+                put_bytes(out, bytes);
+            }
+        }
+    }
+}
+
+/// The rest of a patch body being decoded.
+struct Input<'a>(&'a [u8]);
+
+impl<'a> Input<'a> {
+    fn byte(&mut self) -> Result<u8, DeltaError> {
+        let (&byte, rest) = self
+            .0
+            .split_first()
+            .ok_or_else(|| DeltaError::Malformed("unexpected end".into()))?;
+        self.0 = rest;
+        Ok(byte)
+    }
+
+    fn usize(&mut self) -> Result<usize, DeltaError> {
+        let value = decode_varint(&mut self.0).map_err(|e| DeltaError::Malformed(e.to_string()))?;
+        usize::try_from(value).map_err(|_| DeltaError::Malformed(format!("{value} is too large")))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], DeltaError> {
+        let len = self.usize()?;
+        if len > self.0.len() {
+            return Err(DeltaError::Malformed("unexpected end".into()));
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn ops(&mut self) -> Result<Vec<Op>, DeltaError> {
+        let count = self.usize()?;
+        let mut ops = Vec::new();
+        for _ in 0..count {
+            ops.push(match self.byte()? {
+                OP_COPY => Op::Copy {
+                    offset: self.usize()?,
+                    len: self.usize()?,
+                },
+                OP_INSERT => Op::Insert(self.bytes()?.to_vec()),
+                tag => return Err(DeltaError::Malformed(format!("unknown op {tag}"))),
+            });
+        }
+        Ok(ops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_copies_shared_runs() {
+        let old: Vec<u8> = (0..=255).collect();
+        let mut new = old[100..200].to_vec();
+        new.extend_from_slice(b"inserted");
+        new.extend_from_slice(&old[..40]);
+        let ops = BlockIndex::new(&old).diff(&new);
+        assert_eq!(
+            ops,
+            vec![
+                Op::Copy {
+                    offset: 100,
+                    len: 100
+                },
+                Op::Insert(b"inserted".to_vec()),
+                Op::Copy { offset: 0, len: 40 },
+            ]
+        );
+        let mut rebuilt = Vec::new();
+        rebuild(&old, &ops, &mut rebuilt, new.len()).unwrap();
+        assert_eq!(rebuilt, new);
+        // Copies are small to encode; what they rebuild is capped.
+        let mut capped = Vec::new();
+        assert!(matches!(
+            rebuild(&old, &ops, &mut capped, new.len() - 1),
+            Err(DeltaError::Malformed(_))
+        ));
+
+        let patch = Patch {
+            source_sha512: vec![1; 64],
+            target_sha512: vec![2; 64],
+            segments: vec![
+                Segment::Bytes(ops.clone()),
+                Segment::Payload {
+                    source: Some(0),
+                    algorithm: "lzma".into(),
+                    compressor: vec![3; 16],
+                    ops,
+                },
+            ],
+        };
+        assert_eq!(Patch::decode(&patch.encode()).unwrap(), patch);
+    }
+
+    #[test]
+    fn test_patch_from_another_compressor_is_rejected() {
+        let options = crate::writer::WriteOptions {
+            compression: Compression::Lzma,
+            ..Default::default()
+        };
+        let old = crate::writer::write_mdd_bytes(b"old description", &options).unwrap();
+        let new = crate::writer::write_mdd_bytes(b"new description", &options).unwrap();
+        let patch = make_delta(&old, &new).unwrap();
+        assert_eq!(apply_delta(&old, &patch).unwrap(), new);
+
+        let mut input = Input(&patch[PATCH_MAGIC.len()..]);
+        let body_size = input.usize().unwrap() as u64;
+        let body = compression::decompress_bounded(input.0, "lzma", body_size).unwrap();
+        let mut decoded = Patch::decode(&body).unwrap();
+        for segment in &mut decoded.segments {
+            if let Segment::Payload { compressor, .. } = segment {
+                compressor[0] ^= 1;
+            }
+        }
+        assert!(matches!(
+            apply_delta(&old, &wrap(&decoded.encode()).unwrap()),
+            Err(DeltaError::CompressorMismatch(algorithm)) if algorithm == "lzma"
+        ));
+    }
+}
@@ -0,0 +1,191 @@
+//! Chunk payload encryption.
+//!
+//! An encrypted chunk records the algorithm and the identifier of its key in
+//! the chunk's `Encryption` message (see `proto/file_format.proto`). Its data
+//! is the 12-byte nonce followed by the AES-256-GCM ciphertext and tag of the
+//! compressed payload, authenticated together with the chunk name. Encrypted
+//! chunks carry no SHA-512 signature, which would give away the plaintext;
+//! the GCM tag authenticates them instead. Neither does a search index next to
+//! an encrypted description record the description's digest.
+
+use std::fmt;
+use std::sync::Arc;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use thiserror::Error;
+
+use crate::fileformat;
+
+/// `encryption_algorithm` of chunks this crate encrypts.
+pub const AES_256_GCM: &str = "aes-256-gcm";
+
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Error)]
+pub enum EncryptionError {
+    #[error("unsupported encryption algorithm '{0}'")]
+    UnsupportedAlgorithm(String),
+    #[error("invalid key: {0}")]
+    InvalidKey(String),
+    #[error("no key for key identifier {0}")]
+    NoKey(String),
+    #[error("encryption failed")]
+    Encrypt,
+    #[error("decryption failed: wrong key, or the data was tampered with")]
+    Decrypt,
+}
+
+/// A 256-bit AES key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Key([u8; 32]);
+
+impl Key {
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// The key of a key file: 32 raw bytes, or 64 hex digits optionally
+    /// surrounded by whitespace.
+    pub fn from_file_contents(contents: &[u8]) -> Result<Self, EncryptionError> {
+        crate::key_file_bytes(contents)
+            .map(Self)
+            .map_err(EncryptionError::InvalidKey)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(&self.0.into())
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+/// A callback returning the key for a key identifier.
+pub type KeyLookup = dyn Fn(Option<&[u8]>) -> Option<Key> + Send + Sync;
+
+/// Where the key of a chunk comes from.
+#[derive(Clone)]
+pub enum KeySource {
+    /// One key for every chunk.
+    Key(Key),
+    /// A callback returning the key for a key identifier (`None` for chunks
+    /// written without one), e.g. looked up in a key store.
+    Provider(Arc<KeyLookup>),
+}
+
+impl KeySource {
+    pub fn provider(lookup: impl Fn(Option<&[u8]>) -> Option<Key> + Send + Sync + 'static) -> Self {
+        Self::Provider(Arc::new(lookup))
+    }
+
+    fn key_for(&self, key_identifier: Option<&[u8]>) -> Result<Key, EncryptionError> {
+        match self {
+            Self::Key(key) => Ok(key.clone()),
+            Self::Provider(lookup) => lookup(key_identifier).ok_or_else(|| {
+                EncryptionError::NoKey(key_identifier.map_or_else(
+                    || "(none)".to_string(),
+                    |id| String::from_utf8_lossy(id).into_owned(),
+                ))
+            }),
+        }
+    }
+}
+
+impl fmt::Debug for KeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(key) => f.debug_tuple("Key").field(key).finish(),
+            Self::Provider(_) => f.write_str("Provider(..)"),
+        }
+    }
+}
+
+impl From<Key> for KeySource {
+    fn from(key: Key) -> Self {
+        Self::Key(key)
+    }
+}
+
+/// Encryption of the payload chunks a file is written with.
+#[derive(Debug, Clone)]
+pub struct ChunkEncryption {
+    pub keys: KeySource,
+    /// Recorded in every encrypted chunk so readers can pick the key.
+    pub key_identifier: Option<Vec<u8>>,
+}
+
+impl ChunkEncryption {
+    /// Encrypt the (compressed) payload of the chunk `name`. Returns the
+    /// chunk data and its `Encryption` message.
+    pub(crate) fn encrypt(
+        &self,
+        name: &str,
+        payload: &[u8],
+    ) -> Result<(Vec<u8>, fileformat::Encryption), EncryptionError> {
+        let cipher = self.keys.key_for(self.key_identifier.as_deref())?.cipher();
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let sealed = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: payload,
+                    aad: name.as_bytes(),
+                },
+            )
+            .map_err(|_| EncryptionError::Encrypt)?;
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&sealed);
+        let encryption = fileformat::Encryption {
+            encryption_algorithm: AES_256_GCM.into(),
+            key_identifier: self.key_identifier.clone(),
+        };
+        Ok((data, encryption))
+    }
+}
+
+/// Decrypt the data of an encrypted chunk back to its (compressed) payload.
+pub(crate) fn decrypt(
+    chunk: &fileformat::Chunk,
+    encryption: &fileformat::Encryption,
+    data: &[u8],
+    keys: &KeySource,
+) -> Result<Vec<u8>, EncryptionError> {
+    if encryption.encryption_algorithm != AES_256_GCM {
+        return Err(EncryptionError::UnsupportedAlgorithm(
+            encryption.encryption_algorithm.clone(),
+        ));
+    }
+    if data.len() < NONCE_LEN {
+        return Err(EncryptionError::Decrypt);
+    }
+    let cipher = keys.key_for(encryption.key_identifier.as_deref())?.cipher();
+    let (nonce, sealed) = data.split_at(NONCE_LEN);
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: sealed,
+                aad: chunk.name.as_deref().unwrap_or_default().as_bytes(),
+            },
+        )
+        .map_err(|_| EncryptionError::Decrypt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_file_contents() {
+        let raw = Key::from_file_contents(&[7u8; 32]).unwrap();
+        assert_eq!(raw, Key::new([7; 32]));
+        let hex = format!("{}\n", "07".repeat(32));
+        assert_eq!(Key::from_file_contents(hex.as_bytes()).unwrap(), raw);
+        assert!(Key::from_file_contents(b"too short").is_err());
+        assert!(Key::from_file_contents("zz".repeat(32).as_bytes()).is_err());
+    }
+}
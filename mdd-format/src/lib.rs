@@ -14,9 +14,40 @@ pub mod fbs_generated {
 }
 
 pub mod compression;
+pub mod delta;
+pub mod encryption;
 pub mod reader;
+pub mod signing;
 pub mod writer;
 
 // Re-export generated types for consumers
 pub use fbs_generated::dataformat;
 pub use proto_generated as fileformat;
+
+/// The 32 bytes of a key file: 32 raw bytes, or 64 hex digits optionally
+/// surrounded by whitespace.
+pub(crate) fn key_file_bytes(contents: &[u8]) -> Result<[u8; 32], String> {
+    if let Ok(bytes) = <[u8; 32]>::try_from(contents) {
+        return Ok(bytes);
+    }
+    let expected = || {
+        format!(
+            "expected 32 raw bytes or 64 hex digits, got {} bytes",
+            contents.len()
+        )
+    };
+    let hex = std::str::from_utf8(contents)
+        .map(str::trim)
+        .map_err(|_| expected())?;
+    if hex.len() != 64 {
+        return Err(expected());
+    }
+    let mut bytes = [0u8; 32];
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = std::str::from_utf8(pair)
+            .ok()
+            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            .ok_or_else(|| "not a hex key".to_string())?;
+    }
+    Ok(bytes)
+}
@@ -1,13 +1,29 @@
 use crate::compression;
+use crate::encryption::{self, EncryptionError, KeySource};
 use crate::fileformat;
+use crate::signing::{self, SigningError, VerifyingKey};
 use prost::Message;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt::{self, Write as _};
 use std::path::Path;
 use thiserror::Error;
 
+mod stream;
+
+pub use stream::{ChunkView, MddSource, MddStream};
+
 /// Magic header bytes: "MDD version 0      \0" (20 bytes)
 pub const FILE_MAGIC: &[u8; 20] = b"MDD version 0      \0";
 
+/// Name of the VENDOR_SPECIFIC chunk holding a search index.
+pub const SEARCH_INDEX_CHUNK: &str = "search_index";
+
+/// Search index chunk metadata: hex SHA-512 of the uncompressed description
+/// the index was built from. An index whose description has since been
+/// replaced is ignored.
+pub const INDEXED_DESCRIPTION_KEY: &str = "description_sha512";
+
 #[derive(Debug, Error)]
 pub enum MddReadError {
     #[error("invalid MDD magic header")]
@@ -24,6 +40,12 @@ pub enum MddReadError {
     SignatureMismatch,
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("chunk is encrypted; a key is needed to read it")]
+    Encrypted,
+    #[error("{0}")]
+    Decryption(#[from] EncryptionError),
+    #[error("{0}")]
+    ChunksSignature(#[from] SigningError),
 }
 
 /// Metadata extracted from the MDD Protobuf container.
@@ -33,6 +55,23 @@ pub struct MddMetadata {
     pub ecu_name: String,
     pub revision: String,
     pub metadata: HashMap<String, String>,
+    /// Compatible ECU hardware/software part numbers, if recorded.
+    pub compatibility: Option<fileformat::Compatibility>,
+    /// Validity window and model years, if recorded.
+    pub validity: Option<fileformat::Validity>,
+}
+
+impl MddMetadata {
+    pub(crate) fn from_container(mdd_file: &fileformat::MddFile) -> Self {
+        Self {
+            version: mdd_file.version.clone(),
+            ecu_name: mdd_file.ecu_name.clone(),
+            revision: mdd_file.revision.clone(),
+            metadata: mdd_file.metadata.clone(),
+            compatibility: mdd_file.compatibility.clone(),
+            validity: mdd_file.validity.clone(),
+        }
+    }
 }
 
 /// Read an MDD file and return metadata + raw FlatBuffers bytes.
@@ -41,30 +80,178 @@ pub fn read_mdd_file(path: &Path) -> Result<(MddMetadata, Vec<u8>), MddReadError
     read_mdd_bytes(&data)
 }
 
-/// Read MDD from bytes and return metadata + raw FlatBuffers bytes.
-pub fn read_mdd_bytes(data: &[u8]) -> Result<(MddMetadata, Vec<u8>), MddReadError> {
+/// Like [`read_mdd_file`], decrypting an encrypted description with a key
+/// from `keys`.
+pub fn read_mdd_file_with_key(
+    path: &Path,
+    keys: &KeySource,
+) -> Result<(MddMetadata, Vec<u8>), MddReadError> {
+    let data = std::fs::read(path)?;
+    read_mdd_bytes_with_key(&data, keys)
+}
+
+/// Read the header of an MDD file without decompressing any chunk.
+pub fn read_metadata_file(path: &Path) -> Result<MddMetadata, MddReadError> {
+    read_metadata(&std::fs::read(path)?)
+}
+
+/// Read the header of MDD bytes without decompressing any chunk.
+pub fn read_metadata(data: &[u8]) -> Result<MddMetadata, MddReadError> {
+    Ok(MddMetadata::from_container(&read_mdd_container(data)?))
+}
+
+/// Decode the Protobuf container of an MDD file without touching any chunk data.
+pub fn read_mdd_container(data: &[u8]) -> Result<fileformat::MddFile, MddReadError> {
     if data.len() < FILE_MAGIC.len() || &data[..FILE_MAGIC.len()] != FILE_MAGIC {
         return Err(MddReadError::InvalidMagic);
     }
+    Ok(fileformat::MddFile::decode(&data[FILE_MAGIC.len()..])?)
+}
+
+/// Read MDD from bytes and return metadata + raw FlatBuffers bytes.
+pub fn read_mdd_bytes(data: &[u8]) -> Result<(MddMetadata, Vec<u8>), MddReadError> {
+    let mdd_file = read_mdd_container(data)?;
+    let metadata = MddMetadata::from_container(&mdd_file);
+    let fbs_bytes = decode_chunk(&mdd_file.chunks[description_chunk_index(&mdd_file)?])?;
+    Ok((metadata, fbs_bytes.into_owned()))
+}
 
-    let mdd_file = fileformat::MddFile::decode(&data[FILE_MAGIC.len()..])?;
+/// Like [`read_mdd_bytes`], decrypting an encrypted description with a key
+/// from `keys`. Unencrypted descriptions are read as they are.
+pub fn read_mdd_bytes_with_key(
+    data: &[u8],
+    keys: &KeySource,
+) -> Result<(MddMetadata, Vec<u8>), MddReadError> {
+    let mut mdd_file = read_mdd_container(data)?;
+    let metadata = MddMetadata::from_container(&mdd_file);
+    let index = description_chunk_index(&mdd_file)?;
+    let chunk = &mut mdd_file.chunks[index];
+    decrypt_chunk(chunk, keys)?;
+    Ok((metadata, decode_chunk(chunk)?.into_owned()))
+}
 
-    let metadata = MddMetadata {
-        version: mdd_file.version.clone(),
-        ecu_name: mdd_file.ecu_name.clone(),
-        revision: mdd_file.revision.clone(),
-        metadata: mdd_file.metadata,
+/// Replace the data of an encrypted chunk with its decrypted (still
+/// compressed) payload. Unencrypted chunks are left alone.
+pub(crate) fn decrypt_chunk(
+    chunk: &mut fileformat::Chunk,
+    keys: &KeySource,
+) -> Result<(), MddReadError> {
+    let Some(encryption) = chunk.encryption.take() else {
+        return Ok(());
     };
+    let raw_data = chunk
+        .data
+        .as_deref()
+        .ok_or(MddReadError::MissingChunkData)?;
+    chunk.data = Some(encryption::decrypt(chunk, &encryption, raw_data, keys)?);
+    Ok(())
+}
+
+/// Check the Ed25519 `chunksSignature` of MDD bytes against `key` without
+/// decompressing any chunk. Unsigned files are an error.
+pub fn verify_mdd_signature(data: &[u8], key: &VerifyingKey) -> Result<(), MddReadError> {
+    Ok(signing::verify(&read_mdd_container(data)?, key)?)
+}
+
+/// Read the search index of MDD bytes without decompressing the
+/// description. `None` when there is none, or when it was built for another
+/// description than the one in the file.
+pub fn read_search_index(data: &[u8]) -> Result<Option<Vec<u8>>, MddReadError> {
+    let mdd_file = read_mdd_container(data)?;
+    match search_index_chunk_index(&mdd_file) {
+        Some(i) => Ok(Some(decode_chunk(&mdd_file.chunks[i])?.into_owned())),
+        None => Ok(None),
+    }
+}
+
+/// Index of the search index chunk of a container, if it matches the
+/// description chunk.
+fn search_index_chunk_index(mdd_file: &fileformat::MddFile) -> Option<usize> {
+    let index = mdd_file.chunks.iter().position(is_search_index)?;
+    let description = &mdd_file.chunks[description_chunk_index(mdd_file).ok()?];
+    indexes_description(&mdd_file.chunks[index], description).then_some(index)
+}
+
+/// Whether the search index chunk `index` was built from `description`.
+pub(crate) fn indexes_description(
+    index: &fileformat::Chunk,
+    description: &fileformat::Chunk,
+) -> bool {
+    let indexed = index.metadata.get(INDEXED_DESCRIPTION_KEY);
+    if indexed != description_digest(description).as_ref() {
+        log::warn!("ignoring search index: it was built for a different diagnostic description");
+        return false;
+    }
+    true
+}
+
+pub(crate) fn is_search_index(chunk: &fileformat::Chunk) -> bool {
+    chunk.r#type == fileformat::chunk::DataType::VendorSpecific as i32
+        && chunk.name.as_deref() == Some(SEARCH_INDEX_CHUNK)
+}
 
-    // Find DIAGNOSTIC_DESCRIPTION chunk (type = 0)
-    let chunk = mdd_file
+/// Hex SHA-512 signature of a description chunk, if it is signed.
+pub(crate) fn description_digest(chunk: &fileformat::Chunk) -> Option<String> {
+    let signature = chunk
+        .signatures
+        .iter()
+        .find(|s| s.algorithm == "sha512_uncompressed")?;
+    let mut hex = String::with_capacity(signature.signature.len() * 2);
+    for byte in &signature.signature {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    Some(hex)
+}
+
+/// Index of the DIAGNOSTIC_DESCRIPTION chunk (type = 0) of a container.
+pub(crate) fn description_chunk_index(
+    mdd_file: &fileformat::MddFile,
+) -> Result<usize, MddReadError> {
+    mdd_file
         .chunks
         .iter()
-        .find(|c| c.r#type == fileformat::chunk::DataType::DiagnosticDescription as i32)
-        .ok_or(MddReadError::NoDescriptionChunk)?;
+        .position(|c| c.r#type == fileformat::chunk::DataType::DiagnosticDescription as i32)
+        .ok_or(MddReadError::NoDescriptionChunk)
+}
 
-    let raw_data = chunk.data.as_ref().ok_or(MddReadError::MissingChunkData)?;
+/// Decompress and verify the payload of a chunk written by this crate: the
+/// FlatBuffers description or a search index. Uncompressed payloads are
+/// borrowed from the chunk.
+pub(crate) fn decode_chunk(chunk: &fileformat::Chunk) -> Result<Cow<'_, [u8]>, MddReadError> {
+    let raw_data = chunk
+        .data
+        .as_deref()
+        .ok_or(MddReadError::MissingChunkData)?;
+    decode_payload(chunk, raw_data)
+}
 
+/// Like [`decode_chunk`], for chunk data stored apart from its header.
+/// Encrypted chunks are an error.
+pub(crate) fn decode_payload<'a>(
+    chunk: &fileformat::Chunk,
+    raw_data: &'a [u8],
+) -> Result<Cow<'a, [u8]>, MddReadError> {
+    if chunk.encryption.is_some() {
+        return Err(MddReadError::Encrypted);
+    }
+    let fbs_bytes = decompress_payload(chunk, raw_data)?;
+    verify_description(chunk, &fbs_bytes)?;
+    Ok(fbs_bytes)
+}
+
+/// Decompress the payload of a description chunk without verifying it.
+fn decompress_description(chunk: &fileformat::Chunk) -> Result<Cow<'_, [u8]>, MddReadError> {
+    let raw_data = chunk
+        .data
+        .as_deref()
+        .ok_or(MddReadError::MissingChunkData)?;
+    decompress_payload(chunk, raw_data)
+}
+
+fn decompress_payload<'a>(
+    chunk: &fileformat::Chunk,
+    raw_data: &'a [u8],
+) -> Result<Cow<'a, [u8]>, MddReadError> {
     // CDA hardcodes LZMA decompression regardless of the compression_algorithm field.
     // We try LZMA first (matching CDA behavior). If LZMA fails, we allow raw data
     // only when it's at least 4 bytes (minimum FlatBuffers size - the root u32 offset).
@@ -74,24 +261,27 @@ pub fn read_mdd_bytes(data: &[u8]) -> Result<(MddMetadata, Vec<u8>), MddReadErro
         .uncompressed_size
         .unwrap_or(compression::MAX_DECOMPRESSED_SIZE);
 
-    let fbs_bytes = match &chunk.compression_algorithm {
+    Ok(match &chunk.compression_algorithm {
         Some(algo) if !algo.is_empty() => {
-            compression::decompress_bounded(raw_data, algo, max_size)?
+            Cow::Owned(compression::decompress_bounded(raw_data, algo, max_size)?)
         }
         _ => match compression::decompress_bounded(raw_data, "lzma", max_size) {
-            Ok(decompressed) => decompressed,
+            Ok(decompressed) => Cow::Owned(decompressed),
             Err(_) if raw_data.len() >= 4 => {
                 log::warn!(
                     "no compression_algorithm specified and LZMA failed; \
                      treating {} bytes as uncompressed",
                     raw_data.len()
                 );
-                raw_data.clone()
+                Cow::Borrowed(raw_data)
             }
             Err(e) => return Err(MddReadError::DecompressionFailed(e)),
         },
-    };
+    })
+}
 
+/// Check decompressed payload against the chunk's SHA-512 signature.
+fn verify_description(chunk: &fileformat::Chunk, fbs_bytes: &[u8]) -> Result<(), MddReadError> {
     // Verify SHA-512 signature if present.
     // Absent signatures are OK (backward compat with older MDD files / CDA output).
     if let Some(sig) = chunk
@@ -100,11 +290,224 @@ pub fn read_mdd_bytes(data: &[u8]) -> Result<(MddMetadata, Vec<u8>), MddReadErro
         .find(|s| s.algorithm == "sha512_uncompressed")
     {
         use sha2::{Digest, Sha512};
-        let actual_hash = Sha512::digest(&fbs_bytes);
+        let actual_hash = Sha512::digest(fbs_bytes);
         if actual_hash.as_slice() != sig.signature.as_slice() {
             return Err(MddReadError::SignatureMismatch);
         }
     }
+    Ok(())
+}
+
+/// Field numbers of the container messages, for reading damaged files field
+/// by field (see `proto/file_format.proto`).
+pub(crate) const MDD_FILE_CHUNKS: u32 = 6;
+pub(crate) const CHUNK_DATA: u32 = 8;
+
+/// A chunk of a possibly damaged MDD file and what went wrong reading it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkReport {
+    pub index: usize,
+    pub r#type: i32,
+    pub name: Option<String>,
+    /// Bytes of chunk data present in the file.
+    pub stored_bytes: usize,
+    /// Why the chunk is unusable or incomplete; `None` when it reads fine.
+    pub error: Option<String>,
+}
+
+impl fmt::Display for ChunkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use fileformat::chunk::DataType;
+        let kind = match DataType::try_from(self.r#type) {
+            Ok(DataType::DiagnosticDescription) => "diagnostic description",
+            Ok(DataType::JarFile) => "JAR file",
+            Ok(DataType::JarFilePartial) => "partial JAR file",
+            Ok(DataType::EmbeddedFile) => "embedded file",
+            Ok(DataType::VendorSpecific) | Err(_) => "vendor-specific",
+        };
+        write!(f, "chunk {} ({kind}", self.index)?;
+        if let Some(name) = &self.name {
+            write!(f, " '{name}'")?;
+        }
+        write!(f, ", {} bytes): ", self.stored_bytes)?;
+        f.write_str(self.error.as_deref().unwrap_or("ok"))
+    }
+}
 
-    Ok((metadata, fbs_bytes))
+/// Everything [`recover_mdd_bytes`] could read from an MDD file.
+#[derive(Debug)]
+pub struct MddRecovery {
+    /// Header fields read before any damage.
+    pub metadata: MddMetadata,
+    /// Damage to the container itself, e.g. where the file is cut off.
+    pub container_error: Option<String>,
+    pub chunks: Vec<ChunkReport>,
+    /// The FlatBuffers payload as far as it could be decompressed; `None`
+    /// when no description chunk survived.
+    pub description: Option<Vec<u8>>,
+    /// Whether `description` is complete and matches its signature.
+    pub description_complete: bool,
+}
+
+impl MddRecovery {
+    /// Whether anything is damaged.
+    pub fn is_damaged(&self) -> bool {
+        self.container_error.is_some() || self.chunks.iter().any(|c| c.error.is_some())
+    }
+}
+
+/// Read as much as possible of a truncated or corrupted MDD file: the
+/// container is decoded field by field up to the first damage, every chunk
+/// is checked on its own, and the description payload is decompressed as
+/// far as its data goes. Only a missing magic header is an error.
+pub fn recover_mdd_bytes(data: &[u8]) -> Result<MddRecovery, MddReadError> {
+    if data.len() < FILE_MAGIC.len() || &data[..FILE_MAGIC.len()] != FILE_MAGIC {
+        return Err(MddReadError::InvalidMagic);
+    }
+    let (container, chunk_errors, container_error) = salvage_container(&data[FILE_MAGIC.len()..]);
+    let mut recovery = MddRecovery {
+        metadata: MddMetadata::from_container(&container),
+        container_error,
+        chunks: Vec::new(),
+        description: None,
+        description_complete: false,
+    };
+    for (index, (chunk, error)) in container.chunks.iter().zip(chunk_errors).enumerate() {
+        let is_description =
+            chunk.r#type == fileformat::chunk::DataType::DiagnosticDescription as i32;
+        let mut error = error;
+        if chunk.data.is_none() {
+            error = Some(MddReadError::MissingChunkData.to_string());
+        } else if chunk.encryption.is_some() {
+            error = error.or(Some(MddReadError::Encrypted.to_string()));
+        } else if is_description && recovery.description.is_none() {
+            let (payload, payload_error) = salvage_description(chunk);
+            recovery.description_complete = error.is_none() && payload_error.is_none();
+            recovery.description = Some(payload);
+            error = error.or(payload_error);
+        } else if let Some(algo) = chunk
+            .compression_algorithm
+            .as_deref()
+            .filter(|a| !a.is_empty())
+        {
+            let max_size = chunk
+                .uncompressed_size
+                .unwrap_or(compression::MAX_DECOMPRESSED_SIZE);
+            let raw = chunk.data.as_deref().unwrap_or_default();
+            if let Err(e) = compression::decompress_bounded(raw, algo, max_size) {
+                error = error.or(Some(e.to_string()));
+            }
+        }
+        recovery.chunks.push(ChunkReport {
+            index,
+            r#type: chunk.r#type,
+            name: chunk.name.clone(),
+            stored_bytes: chunk.data.as_ref().map_or(0, Vec::len),
+            error,
+        });
+    }
+    Ok(recovery)
+}
+
+/// Decode a container up to its first damage. Returns the container, an
+/// error per chunk and the damage that stopped decoding.
+fn salvage_container(body: &[u8]) -> (fileformat::MddFile, Vec<Option<String>>, Option<String>) {
+    use prost::encoding::{DecodeContext, WireType, decode_key, decode_varint};
+
+    let mut file = fileformat::MddFile::default();
+    let mut chunk_errors = Vec::new();
+    let mut buf = body;
+    while !buf.is_empty() {
+        let offset = FILE_MAGIC.len() + body.len() - buf.len();
+        let damaged = |e: &dyn fmt::Display| Some(format!("damaged at byte {offset}: {e}"));
+        let (tag, wire_type) = match decode_key(&mut buf) {
+            Ok(key) => key,
+            Err(e) => return (file, chunk_errors, damaged(&e)),
+        };
+        if tag == MDD_FILE_CHUNKS && wire_type == WireType::LengthDelimited {
+            let len = match decode_varint(&mut buf) {
+                Ok(len) => usize::try_from(len).unwrap_or(usize::MAX),
+                Err(e) => return (file, chunk_errors, damaged(&e)),
+            };
+            let present = len.min(buf.len());
+            let (chunk, error) = salvage_chunk(&buf[..present]);
+            let index = file.chunks.len();
+            file.chunks.push(chunk);
+            chunk_errors.push(error);
+            if present < len {
+                let error =
+                    format!("file ends in chunk {index} ({present} of {len} bytes present)");
+                return (file, chunk_errors, Some(error));
+            }
+            buf = &buf[len..];
+        } else if let Err(e) = file.merge_field(tag, wire_type, &mut buf, DecodeContext::default())
+        {
+            return (file, chunk_errors, damaged(&e));
+        }
+    }
+    (file, chunk_errors, None)
+}
+
+/// Decode a chunk up to its first damage. Data cut off keeps the bytes
+/// present.
+fn salvage_chunk(body: &[u8]) -> (fileformat::Chunk, Option<String>) {
+    use prost::encoding::{DecodeContext, WireType, decode_key, decode_varint};
+
+    let mut chunk = fileformat::Chunk::default();
+    let mut buf = body;
+    while !buf.is_empty() {
+        let (tag, wire_type) = match decode_key(&mut buf) {
+            Ok(key) => key,
+            Err(e) => return (chunk, Some(e.to_string())),
+        };
+        if tag == CHUNK_DATA && wire_type == WireType::LengthDelimited {
+            let len = match decode_varint(&mut buf) {
+                Ok(len) => usize::try_from(len).unwrap_or(usize::MAX),
+                Err(e) => return (chunk, Some(e.to_string())),
+            };
+            if len > buf.len() {
+                let error = format!("data cut off ({} of {len} bytes present)", buf.len());
+                chunk.data = Some(buf.to_vec());
+                return (chunk, Some(error));
+            }
+            chunk.data = Some(buf[..len].to_vec());
+            buf = &buf[len..];
+        } else if let Err(e) = chunk.merge_field(tag, wire_type, &mut buf, DecodeContext::default())
+        {
+            return (chunk, Some(e.to_string()));
+        }
+    }
+    (chunk, None)
+}
+
+/// Decompress a description payload as far as possible. Returns the bytes
+/// and, when they are incomplete or unverified, why.
+fn salvage_description(chunk: &fileformat::Chunk) -> (Vec<u8>, Option<String>) {
+    match decompress_description(chunk) {
+        Ok(payload) => {
+            let error = verify_description(chunk, &payload)
+                .err()
+                .map(|e| e.to_string());
+            (payload.into_owned(), error)
+        }
+        Err(e) => {
+            let algorithm = chunk
+                .compression_algorithm
+                .as_deref()
+                .filter(|a| !a.is_empty())
+                .unwrap_or("lzma");
+            let max_size = chunk
+                .uncompressed_size
+                .unwrap_or(compression::MAX_DECOMPRESSED_SIZE);
+            let raw = chunk.data.as_deref().unwrap_or_default();
+            let (payload, partial_error) =
+                compression::decompress_partial(raw, algorithm, max_size);
+            let cause = partial_error.map_or_else(|| e.to_string(), |e| e.to_string());
+            let error = format!(
+                "payload broken off after {} decompressed bytes: {cause}",
+                payload.len()
+            );
+            (payload, Some(error))
+        }
+    }
 }
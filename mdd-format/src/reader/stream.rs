@@ -0,0 +1,316 @@
+//! Lazy MDD reading for large databases.
+//!
+//! [`read_mdd_file`](super::read_mdd_file) decodes the whole container,
+//! copying every chunk, and decompresses the description into a new buffer.
+//! [`MddStream`] instead borrows the file bytes: it decodes the header
+//! fields up front, skips over the chunks, and decodes a chunk's header only
+//! when the chunk is visited. Chunk data stays a slice of the file until a
+//! caller asks for the payload, which is then decrypted and decompressed on
+//! its own or read piece by piece through [`ChunkView::reader`].
+//!
+//! [`MddSource`] holds the bytes the stream borrows. With the opt-in `mmap`
+//! feature it maps the file, so an uncompressed description is never
+//! copied: the slice [`MddStream::flatbuffers`] returns points into the map
+//! and `dataformat::root_as_ecu_data` reads the FlatBuffers root in place.
+//! Without it the file is read into memory, and only the payload is
+//! decompressed on demand.
+//!
+//! Decompression runs on one thread. Each chunk is a single LZMA, gzip or
+//! zstd stream, which decodes front to back, and a conversion reads one
+//! chunk, the description; there are no independent pieces to hand to other
+//! threads. The work that does run in parallel is the conversion of the
+//! decompressed variants to the IR.
+
+use std::borrow::Cow;
+use std::io::Read;
+use std::path::Path;
+
+use prost::Message;
+use prost::encoding::{DecodeContext, WireType, decode_key, decode_varint, skip_field};
+
+use super::{
+    CHUNK_DATA, FILE_MAGIC, MDD_FILE_CHUNKS, MddMetadata, MddReadError, decode_payload,
+    indexes_description, is_search_index,
+};
+use crate::encryption::{self, KeySource};
+use crate::{compression, fileformat};
+
+/// The bytes of an MDD file, mapped or read into memory.
+pub struct MddSource {
+    bytes: SourceBytes,
+}
+
+enum SourceBytes {
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl MddSource {
+    /// Open `path`: mapped with the `mmap` feature, read otherwise.
+    ///
+    /// A mapped file must not be changed while the source is alive; the
+    /// stream would see the new bytes, or fault if the file shrinks.
+    pub fn open(path: &Path) -> Result<Self, MddReadError> {
+        #[cfg(feature = "mmap")]
+        let bytes = SourceBytes::Mapped(map(&std::fs::File::open(path)?)?);
+        #[cfg(not(feature = "mmap"))]
+        let bytes = SourceBytes::Owned(std::fs::read(path)?);
+        Ok(Self { bytes })
+    }
+
+    pub fn from_vec(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes: SourceBytes::Owned(bytes),
+        }
+    }
+
+    /// Whether the bytes are a memory map of the file.
+    pub fn is_mapped(&self) -> bool {
+        match self.bytes {
+            SourceBytes::Owned(_) => false,
+            #[cfg(feature = "mmap")]
+            SourceBytes::Mapped(_) => true,
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        match &self.bytes {
+            SourceBytes::Owned(bytes) => bytes,
+            #[cfg(feature = "mmap")]
+            SourceBytes::Mapped(map) => map,
+        }
+    }
+
+    /// A stream over the file; see [`MddStream::new`].
+    pub fn stream(&self) -> Result<MddStream<'_>, MddReadError> {
+        MddStream::new(self.bytes())
+    }
+}
+
+impl std::fmt::Debug for MddSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MddSource")
+            .field("len", &self.bytes().len())
+            .field("mapped", &self.is_mapped())
+            .finish()
+    }
+}
+
+#[cfg(feature = "mmap")]
+#[allow(unsafe_code)]
+fn map(file: &std::fs::File) -> std::io::Result<memmap2::Mmap> {
+    // SAFETY: the map is read-only. Changes to the file by other processes
+    // are outside our control; `MddSource::open` documents that the file
+    // must stay unchanged while it is mapped.
+    unsafe { memmap2::Mmap::map(file) }
+}
+
+/// The container of MDD bytes with its chunks left undecoded.
+#[derive(Debug, Clone)]
+pub struct MddStream<'a> {
+    metadata: MddMetadata,
+    /// Encoded chunk messages, in file order.
+    chunks: Vec<&'a [u8]>,
+}
+
+impl<'a> MddStream<'a> {
+    /// Check the magic header and decode the container fields. Chunks are
+    /// only delimited, not decoded.
+    pub fn new(data: &'a [u8]) -> Result<Self, MddReadError> {
+        if data.len() < FILE_MAGIC.len() || &data[..FILE_MAGIC.len()] != FILE_MAGIC {
+            return Err(MddReadError::InvalidMagic);
+        }
+        let mut header = fileformat::MddFile::default();
+        let mut chunks = Vec::new();
+        let mut buf = &data[FILE_MAGIC.len()..];
+        while !buf.is_empty() {
+            let (tag, wire_type) = decode_key(&mut buf)?;
+            if tag == MDD_FILE_CHUNKS && wire_type == WireType::LengthDelimited {
+                chunks.push(length_delimited(tag, &mut buf)?);
+            } else {
+                header.merge_field(tag, wire_type, &mut buf, DecodeContext::default())?;
+            }
+        }
+        Ok(Self {
+            metadata: MddMetadata::from_container(&header),
+            chunks,
+        })
+    }
+
+    pub fn metadata(&self) -> &MddMetadata {
+        &self.metadata
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// The chunks in file order, each decoded when the iterator reaches it.
+    pub fn chunks(&self) -> impl Iterator<Item = Result<ChunkView<'a>, MddReadError>> {
+        self.chunks.iter().map(|&chunk| ChunkView::decode(chunk))
+    }
+
+    /// The DIAGNOSTIC_DESCRIPTION chunk.
+    pub fn description(&self) -> Result<ChunkView<'a>, MddReadError> {
+        for chunk in self.chunks() {
+            let chunk = chunk?;
+            if chunk.header.r#type == fileformat::chunk::DataType::DiagnosticDescription as i32 {
+                return Ok(chunk);
+            }
+        }
+        Err(MddReadError::NoDescriptionChunk)
+    }
+
+    /// The FlatBuffers payload, verified against its signature. Borrowed
+    /// from the file when the description is stored uncompressed.
+    pub fn flatbuffers(&self) -> Result<Cow<'a, [u8]>, MddReadError> {
+        self.description()?.payload()
+    }
+
+    /// Like [`flatbuffers`](Self::flatbuffers), decrypting an encrypted
+    /// description with a key from `keys`.
+    pub fn flatbuffers_with_key(&self, keys: &KeySource) -> Result<Cow<'a, [u8]>, MddReadError> {
+        self.description()?.payload_with_key(keys)
+    }
+
+    /// The embedded search index, without decompressing the description.
+    /// `None` when the file has none that matches its description.
+    pub fn search_index(&self) -> Result<Option<Vec<u8>>, MddReadError> {
+        self.search_index_chunk()?
+            .map(|chunk| chunk.payload().map(Cow::into_owned))
+            .transpose()
+    }
+
+    /// Like [`search_index`](Self::search_index), decrypting an encrypted
+    /// index with a key from `keys`.
+    pub fn search_index_with_key(&self, keys: &KeySource) -> Result<Option<Vec<u8>>, MddReadError> {
+        self.search_index_chunk()?
+            .map(|chunk| chunk.payload_with_key(keys).map(Cow::into_owned))
+            .transpose()
+    }
+
+    fn search_index_chunk(&self) -> Result<Option<ChunkView<'a>>, MddReadError> {
+        let mut index = None;
+        for chunk in self.chunks() {
+            let chunk = chunk?;
+            if is_search_index(&chunk.header) {
+                index = Some(chunk);
+                break;
+            }
+        }
+        let Some(index) = index else {
+            return Ok(None);
+        };
+        let description = match self.description() {
+            Ok(description) => description,
+            Err(MddReadError::NoDescriptionChunk) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Ok(indexes_description(&index.header, &description.header).then_some(index))
+    }
+}
+
+/// A chunk of an [`MddStream`]: its decoded header and its data as stored.
+#[derive(Debug, Clone)]
+pub struct ChunkView<'a> {
+    header: fileformat::Chunk,
+    data: Option<&'a [u8]>,
+}
+
+impl<'a> ChunkView<'a> {
+    fn decode(mut buf: &'a [u8]) -> Result<Self, MddReadError> {
+        let mut header = fileformat::Chunk::default();
+        let mut data = None;
+        while !buf.is_empty() {
+            let (tag, wire_type) = decode_key(&mut buf)?;
+            if tag == CHUNK_DATA && wire_type == WireType::LengthDelimited {
+                data = Some(length_delimited(tag, &mut buf)?);
+            } else {
+                header.merge_field(tag, wire_type, &mut buf, DecodeContext::default())?;
+            }
+        }
+        Ok(Self { header, data })
+    }
+
+    /// Type, name, metadata, signatures and compression of the chunk; its
+    /// `data` is always `None`.
+    pub fn header(&self) -> &fileformat::Chunk {
+        &self.header
+    }
+
+    /// The data as stored in the file, still compressed.
+    pub fn data(&self) -> Option<&'a [u8]> {
+        self.data
+    }
+
+    /// The compression algorithm, `None` for data stored as is.
+    pub fn compression(&self) -> Option<&str> {
+        self.header
+            .compression_algorithm
+            .as_deref()
+            .filter(|a| !a.is_empty())
+    }
+
+    /// Decompress and verify the payload, like the data of
+    /// [`read_mdd_bytes`](super::read_mdd_bytes). Uncompressed payloads are
+    /// borrowed from the file.
+    pub fn payload(&self) -> Result<Cow<'a, [u8]>, MddReadError> {
+        decode_payload(
+            &self.header,
+            self.data.ok_or(MddReadError::MissingChunkData)?,
+        )
+    }
+
+    /// Like [`payload`](Self::payload), decrypting an encrypted chunk with a
+    /// key from `keys` first. Unencrypted chunks are read as they are.
+    pub fn payload_with_key(&self, keys: &KeySource) -> Result<Cow<'a, [u8]>, MddReadError> {
+        let Some(encryption) = &self.header.encryption else {
+            return self.payload();
+        };
+        let data = self.data.ok_or(MddReadError::MissingChunkData)?;
+        let decrypted = encryption::decrypt(&self.header, encryption, data, keys)?;
+        let header = fileformat::Chunk {
+            encryption: None,
+            ..self.header.clone()
+        };
+        Ok(Cow::Owned(
+            decode_payload(&header, &decrypted)?.into_owned(),
+        ))
+    }
+
+    /// Read the payload piece by piece, decompressing as it goes, without
+    /// holding all of it in memory. The output stops after the recorded
+    /// uncompressed size, or [`MAX_DECOMPRESSED_SIZE`](compression::MAX_DECOMPRESSED_SIZE).
+    ///
+    /// Unlike [`payload`](Self::payload) this does not check the signature,
+    /// and data without a compression algorithm is read as stored.
+    pub fn reader(&self) -> Result<impl Read + use<'a>, MddReadError> {
+        let data = self.data.ok_or(MddReadError::MissingChunkData)?;
+        let max_size = self
+            .header
+            .uncompressed_size
+            .unwrap_or(compression::MAX_DECOMPRESSED_SIZE);
+        let decoder: Box<dyn Read + 'a> = match self.compression() {
+            Some(algorithm) => compression::decoder(data, algorithm)?,
+            None => Box::new(data),
+        };
+        Ok(decoder.take(max_size))
+    }
+}
+
+/// The contents of the length-delimited field `tag` at the front of `buf`,
+/// borrowed.
+fn length_delimited<'a>(tag: u32, buf: &mut &'a [u8]) -> Result<&'a [u8], MddReadError> {
+    let start = *buf;
+    // Validates the length against the bytes present.
+    skip_field(
+        WireType::LengthDelimited,
+        tag,
+        buf,
+        DecodeContext::default(),
+    )?;
+    let mut field = &start[..start.len() - buf.len()];
+    decode_varint(&mut field)?;
+    Ok(field)
+}
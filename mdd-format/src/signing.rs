@@ -0,0 +1,187 @@
+//! Ed25519 signatures over a whole container.
+//!
+//! The signature goes into the container's `chunksSignature` (see
+//! `proto/file_format.proto`). It covers the header (version, ECU name,
+//! revision, metadata, compatibility, validity) and every chunk as stored:
+//! its metadata, per-chunk signatures, compression and encryption fields and
+//! compressed (or encrypted) data. Protobuf does not fix the order of map
+//! entries, so the signed message is a SHA-512 over a canonical encoding of
+//! these fields rather than over the encoded container.
+
+use std::collections::{BTreeMap, HashMap};
+
+use ed25519_dalek::{Signature, Signer};
+use prost::Message;
+use sha2::{Digest, Sha512};
+use thiserror::Error;
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+use crate::fileformat;
+
+/// `algorithm` of the `chunksSignature` this crate writes.
+pub const ED25519: &str = "ed25519";
+
+/// Prefix of the signed message, so the digest cannot be mistaken for the
+/// hash of anything else.
+const DOMAIN: &[u8] = b"MDD chunksSignature v1";
+
+#[derive(Debug, Error)]
+pub enum SigningError {
+    #[error("invalid key: {0}")]
+    InvalidKey(String),
+    #[error("the file is not signed")]
+    Unsigned,
+    #[error("unsupported signature algorithm '{0}'")]
+    UnsupportedAlgorithm(String),
+    #[error("malformed Ed25519 signature")]
+    Malformed,
+    #[error(
+        "Ed25519 signature verification failed: the file was changed or signed with another key"
+    )]
+    Mismatch,
+}
+
+/// The signing key of a key file: the 32-byte secret seed, raw or as 64 hex
+/// digits.
+pub fn signing_key_from_file_contents(contents: &[u8]) -> Result<SigningKey, SigningError> {
+    let seed = crate::key_file_bytes(contents).map_err(SigningError::InvalidKey)?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+/// The public key of a key file: 32 bytes, raw or as 64 hex digits.
+pub fn verifying_key_from_file_contents(contents: &[u8]) -> Result<VerifyingKey, SigningError> {
+    let bytes = crate::key_file_bytes(contents).map_err(SigningError::InvalidKey)?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| SigningError::InvalidKey(e.to_string()))
+}
+
+/// Signing of the containers a file is written as.
+#[derive(Debug, Clone)]
+pub struct ContainerSigning {
+    pub key: SigningKey,
+    /// Recorded in the signature so verifiers can pick the public key.
+    pub key_identifier: Option<Vec<u8>>,
+}
+
+impl ContainerSigning {
+    /// The `chunksSignature` of `mdd_file`; its current one is ignored.
+    pub(crate) fn sign(&self, mdd_file: &fileformat::MddFile) -> fileformat::Signature {
+        let signature = self.key.sign(&signed_message(mdd_file));
+        fileformat::Signature {
+            algorithm: ED25519.into(),
+            key_identifier: self.key_identifier.clone(),
+            metadata: HashMap::new(),
+            signature: signature.to_bytes().to_vec(),
+        }
+    }
+}
+
+/// Check the `chunksSignature` of `mdd_file` against `key`.
+pub fn verify(mdd_file: &fileformat::MddFile, key: &VerifyingKey) -> Result<(), SigningError> {
+    let signature = mdd_file
+        .chunks_signature
+        .as_ref()
+        .ok_or(SigningError::Unsigned)?;
+    if signature.algorithm != ED25519 {
+        return Err(SigningError::UnsupportedAlgorithm(
+            signature.algorithm.clone(),
+        ));
+    }
+    let signature =
+        Signature::from_slice(&signature.signature).map_err(|_| SigningError::Malformed)?;
+    key.verify_strict(&signed_message(mdd_file), &signature)
+        .map_err(|_| SigningError::Mismatch)
+}
+
+/// SHA-512 of the canonical encoding of everything but `chunksSignature`.
+fn signed_message(mdd_file: &fileformat::MddFile) -> Vec<u8> {
+    let mut feed = Feed(Sha512::new());
+    feed.bytes(DOMAIN);
+    feed.bytes(mdd_file.version.as_bytes());
+    feed.bytes(mdd_file.ecu_name.as_bytes());
+    feed.bytes(mdd_file.revision.as_bytes());
+    feed.map(&mdd_file.metadata);
+    feed.count(mdd_file.feature_flags.len());
+    for flag in &mdd_file.feature_flags {
+        feed.0.update(flag.to_le_bytes());
+    }
+    feed.opt(
+        mdd_file
+            .compatibility
+            .as_ref()
+            .map(Message::encode_to_vec)
+            .as_deref(),
+    );
+    feed.opt(
+        mdd_file
+            .validity
+            .as_ref()
+            .map(Message::encode_to_vec)
+            .as_deref(),
+    );
+    feed.count(mdd_file.chunks.len());
+    for chunk in &mdd_file.chunks {
+        feed.0.update(chunk.r#type.to_le_bytes());
+        feed.opt(chunk.name.as_deref().map(str::as_bytes));
+        feed.map(&chunk.metadata);
+        feed.count(chunk.signatures.len());
+        for signature in &chunk.signatures {
+            feed.bytes(signature.algorithm.as_bytes());
+            feed.opt(signature.key_identifier.as_deref());
+            feed.map(&signature.metadata);
+            feed.bytes(&signature.signature);
+        }
+        feed.opt(chunk.compression_algorithm.as_deref().map(str::as_bytes));
+        feed.opt(
+            chunk
+                .uncompressed_size
+                .map(u64::to_le_bytes)
+                .as_ref()
+                .map(<[u8; 8]>::as_slice),
+        );
+        feed.opt(
+            chunk
+                .encryption
+                .as_ref()
+                .map(Message::encode_to_vec)
+                .as_deref(),
+        );
+        feed.opt(chunk.mime_type.as_deref().map(str::as_bytes));
+        feed.opt(chunk.data.as_deref());
+    }
+    feed.0.finalize().to_vec()
+}
+
+/// Length-prefixed fields fed to a hash, so no two field sequences encode
+/// the same.
+struct Feed(Sha512);
+
+impl Feed {
+    fn count(&mut self, len: usize) {
+        self.0.update((len as u64).to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.count(bytes.len());
+        self.0.update(bytes);
+    }
+
+    fn opt(&mut self, bytes: Option<&[u8]>) {
+        match bytes {
+            Some(bytes) => {
+                self.0.update([1]);
+                self.bytes(bytes);
+            }
+            None => self.0.update([0]),
+        }
+    }
+
+    fn map(&mut self, map: &HashMap<String, String>) {
+        let sorted: BTreeMap<&String, &String> = map.iter().collect();
+        self.count(sorted.len());
+        for (key, value) in sorted {
+            self.bytes(key.as_bytes());
+            self.bytes(value.as_bytes());
+        }
+    }
+}
@@ -1,6 +1,11 @@
 use crate::compression::{self, Compression};
+use crate::encryption::{ChunkEncryption, EncryptionError};
 use crate::fileformat;
-use crate::reader::FILE_MAGIC;
+use crate::reader::{
+    FILE_MAGIC, INDEXED_DESCRIPTION_KEY, SEARCH_INDEX_CHUNK, description_chunk_index,
+    description_digest, is_search_index, read_mdd_container,
+};
+use crate::signing::ContainerSigning;
 use prost::Message;
 use sha2::{Digest, Sha512};
 use std::collections::HashMap;
@@ -15,6 +20,12 @@ pub enum MddWriteError {
     CompressionFailed(#[from] crate::compression::CompressionError),
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("reading original MDD failed: {0}")]
+    Read(#[from] crate::reader::MddReadError),
+    #[error("encryption failed: {0}")]
+    Encryption(#[from] EncryptionError),
+    #[error("the original MDD is encrypted; write it anew with its key instead")]
+    EncryptedOriginal,
 }
 
 #[derive(Debug, Clone)]
@@ -23,9 +34,23 @@ pub struct WriteOptions {
     pub ecu_name: String,
     pub revision: String,
     pub compression: Compression,
+    /// Codec-specific level, `None` for the codec default.
+    pub compression_level: Option<i32>,
     pub metadata: HashMap<String, String>,
     /// Additional chunks (e.g. JAR_FILE, JAR_FILE_PARTIAL) to include.
     pub extra_chunks: Vec<ExtraChunk>,
+    /// Compatible ECU hardware/software part numbers for the header.
+    pub compatibility: Option<fileformat::Compatibility>,
+    /// Validity window and model years for the header.
+    pub validity: Option<fileformat::Validity>,
+    /// Encoded search index (`diag_ir::SearchIndex`) to embed as a
+    /// [`SEARCH_INDEX_CHUNK`] chunk, compressed like the description.
+    pub search_index: Option<Vec<u8>>,
+    /// Encrypt the description and search index chunks (see
+    /// [`crate::encryption`]).
+    pub encryption: Option<ChunkEncryption>,
+    /// Sign the container with Ed25519 (see [`crate::signing`]).
+    pub signing: Option<ContainerSigning>,
 }
 
 /// An additional chunk to embed in the MDD file.
@@ -49,8 +74,14 @@ impl Default for WriteOptions {
             ecu_name: String::new(),
             revision: String::new(),
             compression: Compression::Lzma,
+            compression_level: None,
             metadata: HashMap::new(),
             extra_chunks: Vec::new(),
+            compatibility: None,
+            validity: None,
+            search_index: None,
+            encryption: None,
+            signing: None,
         }
     }
 }
@@ -68,35 +99,10 @@ pub fn write_mdd_file(
 
 /// Write raw FlatBuffers data as MDD bytes.
 pub fn write_mdd_bytes(fbs_data: &[u8], options: &WriteOptions) -> Result<Vec<u8>, MddWriteError> {
-    let uncompressed_size = fbs_data.len() as u64;
-
-    // Compute SHA-512 of uncompressed data before compression
-    let hash = Sha512::digest(fbs_data);
-    let signature = fileformat::Signature {
-        algorithm: "sha512_uncompressed".into(),
-        key_identifier: None,
-        metadata: HashMap::new(),
-        signature: hash.to_vec(),
-    };
-
-    let chunk_data = compression::compress(fbs_data, &options.compression)?;
-
-    let chunk = fileformat::Chunk {
-        r#type: fileformat::chunk::DataType::DiagnosticDescription as i32,
-        name: Some("diagnostic_description".into()),
-        metadata: HashMap::new(),
-        signatures: vec![signature],
-        compression_algorithm: options.compression.algorithm_name().map(String::from),
-        uncompressed_size: if options.compression != Compression::None {
-            Some(uncompressed_size)
-        } else {
-            None
-        },
-        encryption: None,
-        mime_type: Some("application/x-flatbuffers".into()),
-        data: Some(chunk_data),
-    };
-
+    let mut chunk = description_chunk(fbs_data, options.compression, options.compression_level)?;
+    if let Some(encryption) = &options.encryption {
+        encrypt_chunk(&mut chunk, encryption)?;
+    }
     let mut chunks = vec![chunk];
 
     for extra in &options.extra_chunks {
@@ -116,8 +122,20 @@ pub fn write_mdd_bytes(fbs_data: &[u8], options: &WriteOptions) -> Result<Vec<u8
             data: Some(extra.data.clone()),
         });
     }
+    if let Some(index) = &options.search_index {
+        let mut chunk = search_index_chunk(
+            index,
+            &chunks[0],
+            options.compression,
+            options.compression_level,
+        )?;
+        if let Some(encryption) = &options.encryption {
+            encrypt_chunk(&mut chunk, encryption)?;
+        }
+        chunks.push(chunk);
+    }
 
-    let mdd_file = fileformat::MddFile {
+    let mut mdd_file = fileformat::MddFile {
         version: options.version.clone(),
         ecu_name: options.ecu_name.clone(),
         revision: options.revision.clone(),
@@ -125,8 +143,177 @@ pub fn write_mdd_bytes(fbs_data: &[u8], options: &WriteOptions) -> Result<Vec<u8
         chunks,
         feature_flags: vec![],
         chunks_signature: None,
+        compatibility: options.compatibility.clone(),
+        validity: options.validity.clone(),
+    };
+    if let Some(signing) = &options.signing {
+        mdd_file.chunks_signature = Some(signing.sign(&mdd_file));
+    }
+
+    encode_container(&mdd_file)
+}
+
+/// Replace the diagnostic description of an existing MDD file.
+///
+/// The description chunk is recompressed and re-signed; container metadata,
+/// feature flags and all other chunks are kept byte-for-byte. A container-wide
+/// `chunksSignature` no longer matches and is dropped. Encrypted originals
+/// are refused rather than repacked in the clear.
+pub fn repack_mdd_bytes(
+    original: &[u8],
+    fbs_data: &[u8],
+    compression: Compression,
+    compression_level: Option<i32>,
+) -> Result<Vec<u8>, MddWriteError> {
+    let mut mdd_file = read_mdd_container(original)?;
+    refuse_encrypted(&mdd_file)?;
+    let description = description_chunk(fbs_data, compression, compression_level)?;
+    let is_description = |c: &fileformat::Chunk| {
+        c.r#type == fileformat::chunk::DataType::DiagnosticDescription as i32
+    };
+    match mdd_file.chunks.iter().position(is_description) {
+        Some(i) => mdd_file.chunks[i] = description,
+        None => mdd_file.chunks.insert(0, description),
+    }
+    if mdd_file.chunks_signature.take().is_some() {
+        log::warn!("dropping chunksSignature: it does not cover the repacked chunks");
+    }
+    encode_container(&mdd_file)
+}
+
+/// Embed `index` in existing MDD bytes as a [`SEARCH_INDEX_CHUNK`] chunk,
+/// replacing any previous index. Everything else is kept byte-for-byte but
+/// a `chunksSignature`, which no longer matches. Encrypted originals are
+/// refused, as the index would be in the clear.
+pub fn add_search_index(
+    original: &[u8],
+    index: &[u8],
+    compression: Compression,
+    compression_level: Option<i32>,
+) -> Result<Vec<u8>, MddWriteError> {
+    let mut mdd_file = read_mdd_container(original)?;
+    refuse_encrypted(&mdd_file)?;
+    mdd_file.chunks.retain(|c| !is_search_index(c));
+    let description = &mdd_file.chunks[description_chunk_index(&mdd_file)?];
+    let chunk = search_index_chunk(index, description, compression, compression_level)?;
+    mdd_file.chunks.push(chunk);
+    if mdd_file.chunks_signature.take().is_some() {
+        log::warn!("dropping chunksSignature: it does not cover the new search index");
+    }
+    encode_container(&mdd_file)
+}
+
+/// Sign existing MDD bytes, replacing any previous `chunksSignature`.
+pub fn sign_mdd_bytes(
+    original: &[u8],
+    signing: &ContainerSigning,
+) -> Result<Vec<u8>, MddWriteError> {
+    let mut mdd_file = read_mdd_container(original)?;
+    mdd_file.chunks_signature = Some(signing.sign(&mdd_file));
+    encode_container(&mdd_file)
+}
+
+/// A search index chunk stamped with the digest of `description`.
+fn search_index_chunk(
+    index: &[u8],
+    description: &fileformat::Chunk,
+    compression: Compression,
+    compression_level: Option<i32>,
+) -> Result<fileformat::Chunk, MddWriteError> {
+    let mut chunk = signed_chunk(
+        fileformat::chunk::DataType::VendorSpecific,
+        SEARCH_INDEX_CHUNK,
+        "text/tab-separated-values",
+        index,
+        compression,
+        compression_level,
+    )?;
+    if let Some(digest) = description_digest(description) {
+        chunk
+            .metadata
+            .insert(INDEXED_DESCRIPTION_KEY.to_string(), digest);
+    }
+    Ok(chunk)
+}
+
+fn description_chunk(
+    fbs_data: &[u8],
+    compression: Compression,
+    compression_level: Option<i32>,
+) -> Result<fileformat::Chunk, MddWriteError> {
+    signed_chunk(
+        fileformat::chunk::DataType::DiagnosticDescription,
+        "diagnostic_description",
+        "application/x-flatbuffers",
+        fbs_data,
+        compression,
+        compression_level,
+    )
+}
+
+/// A compressed chunk with the SHA-512 of its uncompressed data.
+fn signed_chunk(
+    data_type: fileformat::chunk::DataType,
+    name: &str,
+    mime_type: &str,
+    data: &[u8],
+    compression: Compression,
+    compression_level: Option<i32>,
+) -> Result<fileformat::Chunk, MddWriteError> {
+    let uncompressed_size = data.len() as u64;
+
+    // Compute SHA-512 of uncompressed data before compression
+    let hash = Sha512::digest(data);
+    let signature = fileformat::Signature {
+        algorithm: "sha512_uncompressed".into(),
+        key_identifier: None,
+        metadata: HashMap::new(),
+        signature: hash.to_vec(),
     };
 
+    let chunk_data = compression::compress_with_level(data, &compression, compression_level)?;
+
+    Ok(fileformat::Chunk {
+        r#type: data_type as i32,
+        name: Some(name.into()),
+        metadata: HashMap::new(),
+        signatures: vec![signature],
+        compression_algorithm: compression.algorithm_name().map(String::from),
+        uncompressed_size: if compression != Compression::None {
+            Some(uncompressed_size)
+        } else {
+            None
+        },
+        encryption: None,
+        mime_type: Some(mime_type.into()),
+        data: Some(chunk_data),
+    })
+}
+
+/// Encrypt the payload of a chunk in place. The SHA-512 of the plaintext is
+/// dropped: it would identify the content, and the GCM tag already
+/// authenticates the chunk.
+fn encrypt_chunk(
+    chunk: &mut fileformat::Chunk,
+    encryption: &ChunkEncryption,
+) -> Result<(), MddWriteError> {
+    let name = chunk.name.as_deref().unwrap_or_default();
+    let payload = chunk.data.as_deref().unwrap_or_default();
+    let (data, message) = encryption.encrypt(name, payload)?;
+    chunk.data = Some(data);
+    chunk.encryption = Some(message);
+    chunk.signatures.clear();
+    Ok(())
+}
+
+fn refuse_encrypted(mdd_file: &fileformat::MddFile) -> Result<(), MddWriteError> {
+    if mdd_file.chunks.iter().any(|c| c.encryption.is_some()) {
+        return Err(MddWriteError::EncryptedOriginal);
+    }
+    Ok(())
+}
+
+fn encode_container(mdd_file: &fileformat::MddFile) -> Result<Vec<u8>, MddWriteError> {
     let mut output = Vec::from(FILE_MAGIC.as_slice());
     mdd_file.encode(&mut output)?;
     Ok(output)
@@ -1,4 +1,6 @@
-use mdd_format::compression::{Compression, compress, decompress, decompress_bounded};
+use mdd_format::compression::{
+    Compression, compress, compress_with_level, decompress, decompress_bounded,
+};
 
 #[test]
 fn test_lzma_roundtrip() {
@@ -70,3 +72,25 @@ fn test_decompress_bounded_exact_limit() {
         );
     }
 }
+
+#[test]
+fn test_compression_levels_roundtrip() {
+    let original = b"level test data level test data level test data".repeat(20);
+    for (algo, name, level) in [
+        (Compression::Lzma, "lzma", 9),
+        (Compression::Gzip, "gzip", 1),
+        (Compression::Zstd, "zstd", 19),
+    ] {
+        let compressed = compress_with_level(&original, &algo, Some(level)).unwrap();
+        assert_eq!(decompress(&compressed, name).unwrap(), original);
+    }
+}
+
+#[test]
+fn test_compression_level_out_of_range() {
+    let err = compress_with_level(b"data", &Compression::Gzip, Some(10)).unwrap_err();
+    assert!(err.to_string().contains("out of range for gzip"), "{err}");
+    assert!(compress_with_level(b"data", &Compression::Zstd, Some(0)).is_err());
+    // Levels are meaningless without compression and ignored.
+    assert!(compress_with_level(b"data", &Compression::None, Some(99)).is_ok());
+}
@@ -0,0 +1,116 @@
+use mdd_format::compression::{Compression, compress};
+use mdd_format::delta::{DeltaError, PATCH_MAGIC, apply_delta, make_delta};
+use mdd_format::writer::{ExtraChunk, ExtraChunkType, WriteOptions, write_mdd_bytes};
+
+/// Incompressible bytes, so a delta only stays small by diffing payloads.
+fn noise(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            state.to_be_bytes()[0]
+        })
+        .collect()
+}
+
+fn mdd(description: &[u8], compression: Compression, level: Option<i32>) -> Vec<u8> {
+    let options = WriteOptions {
+        ecu_name: "TEST_ECU".into(),
+        compression,
+        compression_level: level,
+        metadata: [("tool".to_string(), "test".to_string())].into(),
+        extra_chunks: vec![ExtraChunk {
+            chunk_type: ExtraChunkType::JarFile,
+            name: "job.jar".into(),
+            data: noise(4096, 7),
+        }],
+        search_index: Some(b"service\tRead_VIN\tBase/Read_VIN\n".to_vec()),
+        ..Default::default()
+    };
+    write_mdd_bytes(description, &options).unwrap()
+}
+
+#[test]
+fn test_delta_rebuilds_the_new_file() {
+    let old_description = noise(64 * 1024, 1);
+    let mut new_description = old_description.clone();
+    new_description[30_000..30_010].copy_from_slice(b"0123456789");
+    new_description.extend_from_slice(b"appended service");
+
+    for compression in [Compression::Lzma, Compression::Zstd, Compression::None] {
+        let old = mdd(&old_description, compression, None);
+        let new = mdd(&new_description, compression, None);
+        let patch = make_delta(&old, &new).unwrap();
+        assert!(patch.starts_with(PATCH_MAGIC));
+        assert!(
+            patch.len() < new.len() / 10,
+            "{compression:?}: {} byte patch for {} bytes",
+            patch.len(),
+            new.len()
+        );
+        assert_eq!(apply_delta(&old, &patch).unwrap(), new, "{compression:?}");
+    }
+}
+
+#[test]
+fn test_delta_of_data_compressed_at_another_level() {
+    // Data that does not recompress to the same bytes is diffed as stored.
+    let text = |n: usize| -> Vec<u8> {
+        (0..n)
+            .flat_map(|i| format!("service {} reads DID {:#06X}\n", i % 97, i * 31).into_bytes())
+            .collect()
+    };
+    let old = mdd(&text(2000), Compression::Zstd, Some(19));
+    let new = mdd(&text(2100), Compression::Zstd, Some(19));
+    let patch = make_delta(&old, &new).unwrap();
+    assert_eq!(apply_delta(&old, &patch).unwrap(), new);
+}
+
+#[test]
+fn test_apply_delta_verifies_old_and_new_file() {
+    let old = mdd(b"old description", Compression::Lzma, None);
+    let new = mdd(b"new description", Compression::Lzma, None);
+    let patch = make_delta(&old, &new).unwrap();
+
+    let other = mdd(b"other description", Compression::Lzma, None);
+    assert!(matches!(
+        apply_delta(&other, &patch),
+        Err(DeltaError::SourceMismatch)
+    ));
+    assert!(matches!(
+        apply_delta(&old, &new),
+        Err(DeltaError::InvalidMagic)
+    ));
+    let mut truncated = patch.clone();
+    truncated.truncate(patch.len() - 8);
+    assert!(apply_delta(&old, &truncated).is_err());
+    assert!(matches!(
+        make_delta(b"not an mdd", &new),
+        Err(DeltaError::Read(_))
+    ));
+}
+
+#[test]
+fn test_apply_delta_caps_the_body_at_its_declared_size() {
+    let old = mdd(b"old description", Compression::Lzma, None);
+
+    // A body declared as 16 bytes that decompresses to 1 MiB.
+    let mut bomb = PATCH_MAGIC.to_vec();
+    bomb.push(16);
+    bomb.extend(compress(&vec![0; 1 << 20], &Compression::Lzma).unwrap());
+    match apply_delta(&old, &bomb) {
+        Err(DeltaError::Compression(e)) => assert!(e.to_string().contains("exceeds limit 16")),
+        other => panic!("expected the body to be capped, got {other:?}"),
+    }
+
+    // Declaring more than the body holds is rejected too.
+    let mut short = PATCH_MAGIC.to_vec();
+    short.push(100);
+    short.extend(compress(&[0; 50], &Compression::Lzma).unwrap());
+    assert!(matches!(
+        apply_delta(&old, &short),
+        Err(DeltaError::Malformed(_))
+    ));
+}
@@ -65,3 +65,165 @@ fn test_no_algorithm_with_garbage_data_returns_error() {
         "tiny garbage data with no algorithm should error, not silently fallback"
     );
 }
+
+#[test]
+fn test_stream_decompresses_on_demand() {
+    use mdd_format::compression::Compression;
+    use mdd_format::reader::MddSource;
+    use mdd_format::writer::{WriteOptions, write_mdd_bytes};
+
+    let fbs = b"flatbuffers payload decompressed on demand".repeat(8);
+    let dir = tempfile::tempdir().unwrap();
+    for compression in [Compression::Lzma, Compression::Zstd, Compression::None] {
+        let options = WriteOptions {
+            compression,
+            ecu_name: "LAZY_ECU".into(),
+            ..Default::default()
+        };
+        let path = dir.path().join("lazy.mdd");
+        std::fs::write(&path, write_mdd_bytes(&fbs, &options).unwrap()).unwrap();
+
+        let source = MddSource::open(&path).unwrap();
+        let stream = source.stream().unwrap();
+        assert_eq!(stream.metadata().ecu_name, "LAZY_ECU");
+        assert_eq!(stream.flatbuffers().unwrap().as_ref(), fbs.as_slice());
+        assert_eq!(stream.flatbuffers().unwrap().as_ref(), fbs.as_slice());
+    }
+}
+
+#[test]
+fn test_recover_truncated_mdd() {
+    use mdd_format::compression::Compression;
+    use mdd_format::reader::recover_mdd_bytes;
+    use mdd_format::writer::{WriteOptions, write_mdd_bytes};
+
+    let fbs: Vec<u8> = (0..200_000u32).flat_map(u32::to_le_bytes).collect();
+    let options = WriteOptions {
+        compression: Compression::Lzma,
+        ecu_name: "CUT_ECU".into(),
+        ..Default::default()
+    };
+    let mdd = write_mdd_bytes(&fbs, &options).unwrap();
+    let intact = recover_mdd_bytes(&mdd).unwrap();
+    assert!(!intact.is_damaged());
+    assert!(intact.description_complete);
+    assert_eq!(intact.description.as_deref(), Some(fbs.as_slice()));
+
+    let cut = &mdd[..mdd.len() * 3 / 4];
+    assert!(read_mdd_bytes(cut).is_err());
+    let recovery = recover_mdd_bytes(cut).unwrap();
+    assert_eq!(recovery.metadata.ecu_name, "CUT_ECU");
+    assert!(
+        recovery
+            .container_error
+            .as_deref()
+            .unwrap()
+            .starts_with("file ends in chunk 0"),
+        "{:?}",
+        recovery.container_error
+    );
+    let report = recovery.chunks[0].to_string();
+    assert!(
+        report.starts_with("chunk 0 (diagnostic description") && report.contains("cut off"),
+        "{report}"
+    );
+    assert!(!recovery.description_complete);
+    let partial = recovery.description.unwrap();
+    assert!(!partial.is_empty() && partial.len() < fbs.len());
+    assert_eq!(partial, fbs[..partial.len()]);
+
+    assert!(matches!(
+        recover_mdd_bytes(b"garbage"),
+        Err(MddReadError::InvalidMagic)
+    ));
+}
+
+#[test]
+fn test_stream_visits_chunks_without_decoding_the_container() {
+    use mdd_format::compression::Compression;
+    use mdd_format::fileformat::chunk::DataType;
+    use mdd_format::reader::MddSource;
+    use mdd_format::writer::{ExtraChunk, ExtraChunkType, WriteOptions, write_mdd_bytes};
+    use std::io::Read;
+
+    let fbs: Vec<u8> = (0..50_000u32).flat_map(u32::to_le_bytes).collect();
+    let dir = tempfile::tempdir().unwrap();
+    for compression in [Compression::Lzma, Compression::Gzip, Compression::Zstd] {
+        let options = WriteOptions {
+            compression,
+            ecu_name: "STREAM_ECU".into(),
+            extra_chunks: vec![ExtraChunk {
+                chunk_type: ExtraChunkType::JarFile,
+                name: "Flash.jar".into(),
+                data: b"PK jar bytes".to_vec(),
+            }],
+            ..Default::default()
+        };
+        let path = dir.path().join("stream.mdd");
+        std::fs::write(&path, write_mdd_bytes(&fbs, &options).unwrap()).unwrap();
+
+        let source = MddSource::open(&path).unwrap();
+        assert_eq!(source.is_mapped(), cfg!(feature = "mmap"));
+        let stream = source.stream().unwrap();
+        assert_eq!(stream.metadata().ecu_name, "STREAM_ECU");
+        assert_eq!(stream.chunk_count(), 2);
+
+        let chunks: Vec<_> = stream.chunks().map(Result::unwrap).collect();
+        assert_eq!(
+            chunks[0].header().r#type,
+            DataType::DiagnosticDescription as i32
+        );
+        assert!(chunks[0].header().data.is_none());
+        assert!(chunks[0].data().unwrap().len() < fbs.len());
+        assert_eq!(chunks[1].header().name.as_deref(), Some("Flash.jar"));
+        assert_eq!(chunks[1].data(), Some(b"PK jar bytes".as_slice()));
+
+        let mut streamed = Vec::new();
+        chunks[0]
+            .reader()
+            .unwrap()
+            .read_to_end(&mut streamed)
+            .unwrap();
+        assert_eq!(streamed, fbs);
+        assert_eq!(stream.flatbuffers().unwrap().as_ref(), fbs.as_slice());
+    }
+}
+
+#[test]
+fn test_stream_borrows_uncompressed_payload() {
+    use mdd_format::compression::Compression;
+    use mdd_format::reader::MddSource;
+    use mdd_format::writer::{WriteOptions, write_mdd_bytes};
+    use std::borrow::Cow;
+
+    let fbs = b"uncompressed flatbuffers read in place".repeat(4);
+    let options = WriteOptions {
+        compression: Compression::None,
+        ..Default::default()
+    };
+    let source = MddSource::from_vec(write_mdd_bytes(&fbs, &options).unwrap());
+    assert!(!source.is_mapped());
+    let stream = source.stream().unwrap();
+    let payload = stream.flatbuffers().unwrap();
+    assert_eq!(payload.as_ref(), fbs.as_slice());
+    let Cow::Borrowed(payload) = payload else {
+        panic!("uncompressed payload was copied");
+    };
+    assert!(source.bytes().as_ptr_range().contains(&payload.as_ptr()));
+}
+
+#[test]
+fn test_stream_rejects_truncated_container() {
+    use mdd_format::reader::MddStream;
+    use mdd_format::writer::{WriteOptions, write_mdd_bytes};
+
+    let mdd = write_mdd_bytes(b"some flatbuffers bytes", &WriteOptions::default()).unwrap();
+    assert!(matches!(
+        MddStream::new(&mdd[..mdd.len() - 4]),
+        Err(MddReadError::ProtobufDecode(_))
+    ));
+    assert!(matches!(
+        MddStream::new(b"MDD"),
+        Err(MddReadError::InvalidMagic)
+    ));
+}
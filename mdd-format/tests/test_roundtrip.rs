@@ -1,6 +1,14 @@
 use mdd_format::compression::Compression;
-use mdd_format::reader::{FILE_MAGIC, read_mdd_bytes};
-use mdd_format::writer::{ExtraChunk, ExtraChunkType, WriteOptions, write_mdd_bytes};
+use mdd_format::encryption::{AES_256_GCM, ChunkEncryption, Key, KeySource};
+use mdd_format::reader::{
+    FILE_MAGIC, INDEXED_DESCRIPTION_KEY, MddReadError, read_mdd_bytes, read_mdd_bytes_with_key,
+    read_mdd_container, read_metadata, read_search_index, verify_mdd_signature,
+};
+use mdd_format::signing::{ContainerSigning, ED25519, SigningError, SigningKey};
+use mdd_format::writer::{
+    ExtraChunk, ExtraChunkType, MddWriteError, WriteOptions, add_search_index, repack_mdd_bytes,
+    sign_mdd_bytes, write_mdd_bytes,
+};
 use prost::Message;
 use sha2::{Digest, Sha512};
 
@@ -119,6 +127,47 @@ fn test_extra_chunks_included_in_output() {
     );
 }
 
+#[test]
+fn test_compatibility_is_readable_from_the_header() {
+    let compatibility = mdd_format::fileformat::Compatibility {
+        hardware_part_numbers: vec!["HW-0815".into(), "HW-0816".into()],
+        software_part_numbers: vec!["SW-4711".into()],
+    };
+    let options = WriteOptions {
+        compression: Compression::None,
+        ecu_name: "COMPAT".into(),
+        compatibility: Some(compatibility.clone()),
+        ..Default::default()
+    };
+    let mdd_bytes = write_mdd_bytes(b"fake fbs data", &options).unwrap();
+
+    let meta = read_metadata(&mdd_bytes).unwrap();
+    assert_eq!(meta.ecu_name, "COMPAT");
+    assert_eq!(meta.compatibility, Some(compatibility));
+
+    let plain = write_mdd_bytes(b"fake fbs data", &WriteOptions::default()).unwrap();
+    assert_eq!(read_metadata(&plain).unwrap().compatibility, None);
+}
+
+#[test]
+fn test_validity_is_readable_from_the_header() {
+    let validity = mdd_format::fileformat::Validity {
+        valid_from: Some("2025-01-01".into()),
+        valid_until: Some("2027-12-31".into()),
+        model_years: vec![2025, 2026],
+    };
+    let options = WriteOptions {
+        compression: Compression::None,
+        validity: Some(validity.clone()),
+        ..Default::default()
+    };
+    let mdd_bytes = write_mdd_bytes(b"fake fbs data", &options).unwrap();
+    assert_eq!(read_metadata(&mdd_bytes).unwrap().validity, Some(validity));
+
+    let plain = write_mdd_bytes(b"fake fbs data", &WriteOptions::default()).unwrap();
+    assert_eq!(read_metadata(&plain).unwrap().validity, None);
+}
+
 #[test]
 fn test_no_extra_chunks_by_default() {
     let fake_fbs_data = b"fake fbs";
@@ -229,3 +278,182 @@ fn test_signature_verification_fails_on_tampered_data() {
         "tampered MDD data should fail signature verification"
     );
 }
+
+#[test]
+fn test_repack_replaces_description_and_keeps_other_chunks() {
+    let options = WriteOptions {
+        ecu_name: "REPACK".into(),
+        compression: Compression::Lzma,
+        metadata: [("origin".to_string(), "test".to_string())].into(),
+        extra_chunks: vec![ExtraChunk {
+            chunk_type: ExtraChunkType::JarFile,
+            name: "job.jar".into(),
+            data: b"PK\x03\x04jar".to_vec(),
+        }],
+        ..Default::default()
+    };
+    let original = write_mdd_bytes(b"old description data", &options).unwrap();
+
+    let repacked = repack_mdd_bytes(
+        &original,
+        b"new description data",
+        Compression::Zstd,
+        Some(19),
+    )
+    .unwrap();
+
+    let (meta, fbs) = read_mdd_bytes(&repacked).unwrap();
+    assert_eq!(fbs, b"new description data");
+    assert_eq!(meta.ecu_name, "REPACK");
+    assert_eq!(meta.metadata["origin"], "test");
+
+    let old_file = read_mdd_container(&original).unwrap();
+    let new_file = read_mdd_container(&repacked).unwrap();
+    assert_eq!(new_file.chunks.len(), 2);
+    assert_eq!(
+        new_file.chunks[0].compression_algorithm.as_deref(),
+        Some("zstd")
+    );
+    assert_eq!(new_file.chunks[1], old_file.chunks[1]);
+}
+
+#[test]
+fn test_repack_rejects_invalid_input() {
+    let result = repack_mdd_bytes(b"not an mdd", b"fbs", Compression::None, None);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_search_index_is_dropped_from_reads_once_the_description_changes() {
+    let options = WriteOptions {
+        compression: Compression::Zstd,
+        search_index: Some(b"service\tRead_VIN\tBase/Read_VIN\n".to_vec()),
+        ..Default::default()
+    };
+    let mdd = write_mdd_bytes(b"description data", &options).unwrap();
+    assert_eq!(
+        read_search_index(&mdd).unwrap().as_deref(),
+        Some(b"service\tRead_VIN\tBase/Read_VIN\n".as_slice())
+    );
+    let stream = mdd_format::reader::MddStream::new(&mdd).unwrap();
+    assert!(stream.search_index().unwrap().is_some());
+
+    let repacked = repack_mdd_bytes(&mdd, b"other data", Compression::Zstd, None).unwrap();
+    assert_eq!(read_search_index(&repacked).unwrap(), None);
+
+    let reindexed = add_search_index(&repacked, b"", Compression::Gzip, None).unwrap();
+    assert_eq!(
+        read_search_index(&reindexed).unwrap().as_deref(),
+        Some(&b""[..])
+    );
+    assert_eq!(read_mdd_container(&reindexed).unwrap().chunks.len(), 2);
+}
+
+#[test]
+fn test_encrypted_description_needs_its_key() {
+    let key = Key::new([0x42; 32]);
+    let options = WriteOptions {
+        compression: Compression::Zstd,
+        search_index: Some(b"service\tRead_VIN\tBase/Read_VIN\n".to_vec()),
+        encryption: Some(ChunkEncryption {
+            keys: KeySource::Key(key.clone()),
+            key_identifier: Some(b"workshop-2026".to_vec()),
+        }),
+        ..Default::default()
+    };
+    let mdd = write_mdd_bytes(b"secret description", &options).unwrap();
+
+    let container = read_mdd_container(&mdd).unwrap();
+    for chunk in &container.chunks {
+        let encryption = chunk.encryption.as_ref().unwrap();
+        assert_eq!(encryption.encryption_algorithm, AES_256_GCM);
+        assert_eq!(
+            encryption.key_identifier.as_deref(),
+            Some(&b"workshop-2026"[..])
+        );
+        assert!(chunk.signatures.is_empty());
+        assert!(!chunk.metadata.contains_key(INDEXED_DESCRIPTION_KEY));
+    }
+    assert!(!mdd.windows(6).any(|w| w == b"secret"));
+
+    assert!(matches!(read_mdd_bytes(&mdd), Err(MddReadError::Encrypted)));
+    assert!(matches!(
+        read_search_index(&mdd),
+        Err(MddReadError::Encrypted)
+    ));
+    let (_, fbs) = read_mdd_bytes_with_key(&mdd, &KeySource::Key(key.clone())).unwrap();
+    assert_eq!(fbs, b"secret description");
+
+    let stream = mdd_format::reader::MddStream::new(&mdd).unwrap();
+    assert!(matches!(stream.flatbuffers(), Err(MddReadError::Encrypted)));
+    let keys = KeySource::Key(key.clone());
+    assert_eq!(
+        stream.flatbuffers_with_key(&keys).unwrap().as_ref(),
+        fbs.as_slice()
+    );
+    assert_eq!(
+        stream.search_index_with_key(&keys).unwrap().as_deref(),
+        Some(&b"service\tRead_VIN\tBase/Read_VIN\n"[..])
+    );
+
+    let provider = KeySource::provider(move |id| {
+        (id == Some(b"workshop-2026".as_slice())).then(|| key.clone())
+    });
+    assert_eq!(read_mdd_bytes_with_key(&mdd, &provider).unwrap().1, fbs);
+    assert!(matches!(
+        read_mdd_bytes_with_key(&mdd, &KeySource::Key(Key::new([0; 32]))),
+        Err(MddReadError::Decryption(_))
+    ));
+
+    assert!(matches!(
+        repack_mdd_bytes(&mdd, b"other", Compression::None, None),
+        Err(MddWriteError::EncryptedOriginal)
+    ));
+}
+
+#[test]
+fn test_signed_container_verifies_with_its_public_key() {
+    let signing = ContainerSigning {
+        key: SigningKey::from_bytes(&[7; 32]),
+        key_identifier: Some(b"release".to_vec()),
+    };
+    let public = signing.key.verifying_key();
+    let options = WriteOptions {
+        ecu_name: "TEST_ECU".into(),
+        compression: Compression::Lzma,
+        metadata: [("tool".to_string(), "test".to_string())].into(),
+        search_index: Some(b"service\tRead_VIN\tBase/Read_VIN\n".to_vec()),
+        signing: Some(signing.clone()),
+        ..Default::default()
+    };
+    let mdd = write_mdd_bytes(b"signed description", &options).unwrap();
+
+    let signature = read_mdd_container(&mdd).unwrap().chunks_signature.unwrap();
+    assert_eq!(signature.algorithm, ED25519);
+    assert_eq!(signature.key_identifier.as_deref(), Some(&b"release"[..]));
+    verify_mdd_signature(&mdd, &public).unwrap();
+    assert_eq!(read_mdd_bytes(&mdd).unwrap().1, b"signed description");
+
+    let other = SigningKey::from_bytes(&[8; 32]).verifying_key();
+    assert!(matches!(
+        verify_mdd_signature(&mdd, &other),
+        Err(MddReadError::ChunksSignature(SigningError::Mismatch))
+    ));
+
+    let mut tampered = read_mdd_container(&mdd).unwrap();
+    tampered.ecu_name = "OTHER_ECU".into();
+    let mut tampered_bytes = FILE_MAGIC.to_vec();
+    tampered.encode(&mut tampered_bytes).unwrap();
+    assert!(matches!(
+        verify_mdd_signature(&tampered_bytes, &public),
+        Err(MddReadError::ChunksSignature(SigningError::Mismatch))
+    ));
+
+    let repacked = repack_mdd_bytes(&mdd, b"other", Compression::None, None).unwrap();
+    assert!(matches!(
+        verify_mdd_signature(&repacked, &public),
+        Err(MddReadError::ChunksSignature(SigningError::Unsigned))
+    ));
+    let resigned = sign_mdd_bytes(&repacked, &signing).unwrap();
+    verify_mdd_signature(&resigned, &public).unwrap();
+}
@@ -0,0 +1,277 @@
+//! FlatBuffers schema evolution guard.
+//!
+//! Every released schema is frozen under `schemas/history/`. The current
+//! `diagnostic_description.fbs` must be an additive evolution of each of them:
+//! FlatBuffers addresses table fields by declaration order and enums/unions by
+//! value, so removing, reordering, retyping or renumbering anything breaks MDD
+//! files that are already deployed.
+//!
+//! When a schema change is released, copy the new schema to
+//! `schemas/history/diagnostic_description.v<N>.fbs` and register it below.
+
+use std::collections::BTreeMap;
+
+const CURRENT: &str = include_str!("../schemas/diagnostic_description.fbs");
+
+const HISTORY: &[(&str, &str)] = &[
+    (
+        "v1",
+        include_str!("../schemas/history/diagnostic_description.v1.fbs"),
+    ),
+    (
+        "v2",
+        include_str!("../schemas/history/diagnostic_description.v2.fbs"),
+    ),
+];
+
+#[derive(Debug, Default)]
+struct Schema {
+    tables: BTreeMap<String, Vec<(String, String)>>,
+    enums: BTreeMap<String, (String, Vec<(String, i64)>)>,
+    unions: BTreeMap<String, Vec<String>>,
+    root_type: Option<String>,
+}
+
+fn strip_comments(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut rest = src;
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix("//") {
+            rest = r.find('\n').map_or("", |i| &r[i..]);
+        } else if let Some(r) = rest.strip_prefix("/*") {
+            rest = r.find("*/").map_or("", |i| &r[i + 2..]);
+        } else {
+            let c = rest.chars().next().unwrap();
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+fn parse_schema(src: &str) -> Schema {
+    let src = strip_comments(src);
+    let mut schema = Schema::default();
+    let mut rest = src.as_str();
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic()) {
+        rest = &rest[start..];
+        let keyword_end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let keyword = &rest[..keyword_end];
+        if matches!(keyword, "table" | "enum" | "union") {
+            let open = rest.find('{').expect("declaration without body");
+            let close = open + rest[open..].find('}').expect("unterminated body");
+            let header = rest[keyword_end..open].trim();
+            let body = &rest[open + 1..close];
+            let (name, base) = match header.split_once(':') {
+                Some((n, b)) => (n.trim().to_string(), b.trim().to_string()),
+                None => (header.to_string(), String::new()),
+            };
+            match keyword {
+                "table" => {
+                    schema.tables.insert(name, parse_fields(body));
+                }
+                "enum" => {
+                    schema.enums.insert(name, (base, parse_enum_values(body)));
+                }
+                _ => {
+                    schema.unions.insert(name, parse_list(body));
+                }
+            }
+            rest = &rest[close + 1..];
+        } else {
+            let end = rest.find(';').map_or(rest.len(), |i| i + 1);
+            if keyword == "root_type" {
+                schema.root_type = Some(rest[keyword_end..end - 1].trim().to_string());
+            }
+            rest = &rest[end..];
+        }
+    }
+    schema
+}
+
+/// Table fields in declaration order, as `(name, "type = default (attrs)")`.
+fn parse_fields(body: &str) -> Vec<(String, String)> {
+    body.split(';')
+        .filter_map(|f| f.split_once(':'))
+        .map(|(name, ty)| {
+            let ty = ty.split_whitespace().collect::<Vec<_>>().join(" ");
+            (name.trim().to_string(), ty)
+        })
+        .collect()
+}
+
+fn parse_list(body: &str) -> Vec<String> {
+    body.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn parse_enum_values(body: &str) -> Vec<(String, i64)> {
+    let mut next = 0;
+    parse_list(body)
+        .into_iter()
+        .map(|item| {
+            let (name, value) = match item.split_once('=') {
+                Some((n, v)) => (n.trim().to_string(), v.trim().parse().unwrap()),
+                None => (item, next),
+            };
+            next = value + 1;
+            (name, value)
+        })
+        .collect()
+}
+
+/// List every change from `old` to `new` that breaks binary compatibility.
+fn breaking_changes(old: &Schema, new: &Schema) -> Vec<String> {
+    let mut errors = Vec::new();
+    if old.root_type != new.root_type {
+        errors.push(format!(
+            "root_type changed from {:?} to {:?}",
+            old.root_type, new.root_type
+        ));
+    }
+    for (name, old_fields) in &old.tables {
+        let Some(new_fields) = new.tables.get(name) else {
+            errors.push(format!("table {name} removed"));
+            continue;
+        };
+        for (i, (field, ty)) in old_fields.iter().enumerate() {
+            match new_fields.get(i) {
+                None => errors.push(format!("table {name}: field {field} (id {i}) removed")),
+                Some((new_field, _)) if new_field != field => errors.push(format!(
+                    "table {name}: field id {i} renamed or reordered from {field} to {new_field}"
+                )),
+                Some((_, new_ty)) if new_ty != ty && !is_deprecation(ty, new_ty) => {
+                    errors.push(format!(
+                        "table {name}: field {field} changed from `{ty}` to `{new_ty}`"
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    for (name, (old_base, old_values)) in &old.enums {
+        let Some((new_base, new_values)) = new.enums.get(name) else {
+            errors.push(format!("enum {name} removed"));
+            continue;
+        };
+        if old_base != new_base {
+            errors.push(format!(
+                "enum {name}: underlying type changed from {old_base} to {new_base}"
+            ));
+        }
+        for (value_name, value) in old_values {
+            match new_values.iter().find(|(n, _)| n == value_name) {
+                None => errors.push(format!("enum {name}: value {value_name} removed")),
+                Some((_, v)) if v != value => errors.push(format!(
+                    "enum {name}: {value_name} renumbered from {value} to {v}"
+                )),
+                Some(_) => {}
+            }
+        }
+    }
+    for (name, old_members) in &old.unions {
+        let Some(new_members) = new.unions.get(name) else {
+            errors.push(format!("union {name} removed"));
+            continue;
+        };
+        if !new_members.starts_with(old_members) {
+            errors.push(format!(
+                "union {name}: members must only be appended (was {old_members:?}, now {new_members:?})"
+            ));
+        }
+    }
+    errors
+}
+
+/// Marking a field `(deprecated)` keeps its slot and is allowed.
+fn is_deprecation(old_ty: &str, new_ty: &str) -> bool {
+    new_ty.contains("deprecated") && new_ty.starts_with(old_ty.split(" (").next().unwrap_or(""))
+}
+
+#[test]
+fn test_current_schema_is_additive_over_history() {
+    let current = parse_schema(CURRENT);
+    for (version, src) in HISTORY {
+        let errors = breaking_changes(&parse_schema(src), &current);
+        assert!(
+            errors.is_empty(),
+            "diagnostic_description.fbs is not backwards compatible with {version}:\n  {}",
+            errors.join("\n  ")
+        );
+    }
+}
+
+#[test]
+fn test_parser_reads_current_schema() {
+    let schema = parse_schema(CURRENT);
+    assert_eq!(schema.root_type.as_deref(), Some("EcuData"));
+    let ecu_data = &schema.tables["EcuData"];
+    assert_eq!(ecu_data[0], ("version".to_string(), "string".to_string()));
+    assert_eq!(ecu_data[4].0, "feature_flags");
+    assert_eq!(
+        schema.enums["FeatureFlag"],
+        ("byte".to_string(), vec![("RESERVED".to_string(), 0)])
+    );
+    assert_eq!(schema.unions["SDxorSDG"], vec!["SD", "SDG"]);
+}
+
+const BASE: &str = "
+table T { a: string; b: uint32 = 1; }
+enum E: byte { X = 0, Y }
+union U { T }
+root_type T;
+";
+
+#[test]
+fn test_additive_changes_are_accepted() {
+    let new = "
+table T { a: string; b: uint32 = 1 (deprecated); c: bool; }
+table T2 { x: int; }
+enum E: byte { X = 0, Y, Z }
+union U { T, T2 }
+root_type T;
+";
+    assert_eq!(
+        breaking_changes(&parse_schema(BASE), &parse_schema(new)),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn test_field_renumbering_is_rejected() {
+    let reordered = "
+table T { b: uint32 = 1; a: string; }
+enum E: byte { Y = 1, X = 0 }
+union U { T }
+root_type T;
+";
+    let errors = breaking_changes(&parse_schema(BASE), &parse_schema(reordered));
+    assert_eq!(errors.len(), 2, "{errors:?}");
+    assert!(errors[0].contains("field id 0 renamed or reordered"));
+}
+
+#[test]
+fn test_breaking_changes_are_rejected() {
+    let broken = "
+table T { a: string; b: uint64 = 1; }
+enum E: short { X = 0, Y = 5 }
+union U { T2, T }
+root_type T2;
+table T2 { }
+";
+    let errors = breaking_changes(&parse_schema(BASE), &parse_schema(broken));
+    assert!(errors.iter().any(|e| e.contains("root_type changed")));
+    assert!(errors.iter().any(|e| e.contains("field b changed")));
+    assert!(errors.iter().any(|e| e.contains("underlying type changed")));
+    assert!(
+        errors
+            .iter()
+            .any(|e| e.contains("Y renumbered from 1 to 5"))
+    );
+    assert!(errors.iter().any(|e| e.contains("union U")));
+}
@@ -0,0 +1,55 @@
+//! Development tasks, run as `cargo xtask <task>`.
+
+use std::process::{Command, ExitCode};
+
+const USAGE: &str = "\
+Usage: cargo xtask <task>
+
+Tasks:
+  goldens [--bless]  Compare converter output for the sample databases with the
+                     golden files in test-fixtures/goldens; --bless rewrites them";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["goldens"] => goldens(false),
+        ["goldens", "--bless"] => goldens(true),
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run the golden-file suite of `diag-converter`, rewriting the goldens
+/// when `bless` is set.
+fn goldens(bless: bool) -> ExitCode {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+    let mut cmd = Command::new(cargo);
+    cmd.args([
+        "test",
+        "--package",
+        "diag-converter",
+        "--test",
+        "test_goldens",
+    ]);
+    if bless {
+        cmd.env("UPDATE_GOLDENS", "1");
+    }
+    match cmd.status() {
+        Ok(status) if status.success() => {
+            if bless {
+                eprintln!(
+                    "Goldens updated; review the changes with `git diff test-fixtures/goldens`"
+                );
+            }
+            ExitCode::SUCCESS
+        }
+        Ok(_) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("Failed to run cargo: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}